@@ -0,0 +1,116 @@
+//! Pacing the commands sent by the operator UI for rapid input, e.g. dragging an override slider or holding a jog key.
+//!
+//! The UI produces an event every frame while the input changes, sending a command for each event floods the link to
+//! the server.  Instead the events are coalesced, either to a bounded rate, see [`RateLimiter`], or until the input
+//! has settled, see [`Debouncer`].  Either way the last value is always sent, so the machine ends up at the value the
+//! operator chose.
+//!
+//! Neither has a timer of its own, the UI calls `poll` each frame and requests a repaint after `delay`, so that a
+//! pending value is sent even when there is no further input.
+
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests;
+
+/// Sends at most one value per interval, the first value is sent immediately, the values in between are coalesced,
+/// the latest one is sent once the interval has passed.
+pub struct RateLimiter<T> {
+    interval: Duration,
+    sent_at: Option<Instant>,
+    pending: Option<T>,
+}
+
+impl<T> RateLimiter<T> {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            sent_at: None,
+            pending: None,
+        }
+    }
+
+    /// Returns the value if it is to be sent now, otherwise it replaces the pending value.
+    pub fn push(&mut self, value: T, now: Instant) -> Option<T> {
+        self.pending = Some(value);
+        self.poll(now)
+    }
+
+    /// Returns the pending value once the interval since the last value that was sent has passed.
+    pub fn poll(&mut self, now: Instant) -> Option<T> {
+        if self.pending.is_none() || !self.is_due(now) {
+            return None;
+        }
+        self.sent_at = Some(now);
+        self.pending.take()
+    }
+
+    /// The time until the pending value is due, `None` when there is no pending value.
+    pub fn delay(&self, now: Instant) -> Option<Duration> {
+        self.pending.as_ref()?;
+        let delay = match self.sent_at {
+            Some(sent_at) => (sent_at + self.interval).saturating_duration_since(now),
+            None => Duration::ZERO,
+        };
+        Some(delay)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.sent_at
+            .is_none_or(|sent_at| now.saturating_duration_since(sent_at) >= self.interval)
+    }
+}
+
+/// Sends a value once no other value has been pushed for the delay, e.g. the value of a slider once the operator has
+/// stopped dragging it.
+pub struct Debouncer<T> {
+    delay: Duration,
+    /// with the time it was pushed
+    pending: Option<(T, Instant)>,
+}
+
+impl<T> Debouncer<T> {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: None,
+        }
+    }
+
+    /// Replaces the pending value, and restarts the delay.
+    pub fn push(&mut self, value: T, now: Instant) {
+        self.pending = Some((value, now));
+    }
+
+    /// Returns the pending value once the delay since it was pushed has passed.
+    pub fn poll(&mut self, now: Instant) -> Option<T> {
+        let (_, pushed_at) = self.pending.as_ref()?;
+        if now.saturating_duration_since(*pushed_at) < self.delay {
+            return None;
+        }
+        self.pending
+            .take()
+            .map(|(value, _)| value)
+    }
+
+    /// Returns the pending value without waiting, e.g. when the operator releases the slider.
+    pub fn flush(&mut self) -> Option<T> {
+        self.pending
+            .take()
+            .map(|(value, _)| value)
+    }
+
+    /// The time until the pending value is due, `None` when there is no pending value.
+    pub fn delay(&self, now: Instant) -> Option<Duration> {
+        let (_, pushed_at) = self.pending.as_ref()?;
+        Some((*pushed_at + self.delay).saturating_duration_since(now))
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+use super::{Debouncer, RateLimiter};
+
+const INTERVAL: Duration = Duration::from_millis(50);
+
+#[test]
+pub fn rate_limiter_sends_the_first_value_immediately() {
+    // given
+    let now = Instant::now();
+    let mut limiter = RateLimiter::new(INTERVAL);
+
+    // expect
+    assert_eq!(limiter.push(1, now), Some(1));
+    assert!(!limiter.is_pending());
+    assert_eq!(limiter.delay(now), None);
+}
+
+#[test]
+pub fn rate_limiter_coalesces_values_within_the_interval() {
+    // given
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(INTERVAL);
+    limiter.push(1, start);
+
+    // when
+    let sent = (1..=10)
+        .filter_map(|index| limiter.push(index * 10, start + Duration::from_millis(index as u64 * 4)))
+        .collect::<Vec<_>>();
+
+    // then
+    assert!(sent.is_empty());
+    assert_eq!(
+        limiter.delay(start + Duration::from_millis(40)),
+        Some(Duration::from_millis(10))
+    );
+    assert_eq!(limiter.poll(start + Duration::from_millis(49)), None);
+    assert_eq!(limiter.poll(start + INTERVAL), Some(100));
+    assert_eq!(limiter.poll(start + INTERVAL * 3), None);
+}
+
+#[test]
+pub fn rate_limiter_bounds_the_rate_of_a_continuous_stream() {
+    // given
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(INTERVAL);
+
+    // when
+    // a value every frame, at 100fps, for one second
+    let mut sent = (0..100)
+        .filter_map(|frame| limiter.push(frame, start + Duration::from_millis(frame as u64 * 10)))
+        .collect::<Vec<_>>();
+    sent.extend(limiter.poll(start + Duration::from_secs(2)));
+
+    // then
+    assert_eq!(sent.len(), 21);
+    assert_eq!(sent.last(), Some(&99));
+}
+
+#[test]
+pub fn debouncer_sends_the_value_once_settled() {
+    // given
+    let start = Instant::now();
+    let mut debouncer = Debouncer::new(INTERVAL);
+
+    // when
+    for index in 0..10 {
+        debouncer.push(index, start + Duration::from_millis(index as u64 * 10));
+    }
+
+    // then
+    let last_pushed_at = start + Duration::from_millis(90);
+    assert_eq!(debouncer.poll(last_pushed_at + Duration::from_millis(49)), None);
+    assert_eq!(debouncer.delay(last_pushed_at), Some(INTERVAL));
+    assert_eq!(debouncer.poll(last_pushed_at + INTERVAL), Some(9));
+    assert!(!debouncer.is_pending());
+    assert_eq!(debouncer.poll(last_pushed_at + INTERVAL * 2), None);
+}
+
+#[test]
+pub fn debouncer_flushed() {
+    // given
+    let now = Instant::now();
+    let mut debouncer = Debouncer::new(INTERVAL);
+    debouncer.push(1, now);
+    debouncer.push(2, now);
+
+    // expect
+    assert_eq!(debouncer.flush(), Some(2));
+    assert_eq!(debouncer.flush(), None);
+    assert_eq!(debouncer.delay(now), None);
+}
@@ -35,6 +35,25 @@ where
             .map_err(|_e| ClientError::Timeout(self.timeout))
             .map(|r| r.map_err(|e| ClientError::RequestError(e)))?
     }
+
+    /// Re-sends the request after a timeout, up to `attempts` requests in total.
+    ///
+    /// The request is re-sent unchanged, a timeout does not mean the request was not executed, so the request must
+    /// be idempotent, e.g. by including an idempotency key.
+    pub async fn request_with_retry(&self, req: &E::Request, attempts: u32) -> Result<E::Response, ClientError>
+    where
+        E: Endpoint,
+        E::Request: Serialize + Clone + DeserializeOwned + 'static,
+        E::Response: Serialize + Clone + DeserializeOwned + 'static,
+    {
+        let mut attempt = 1;
+        loop {
+            match self.request(req).await {
+                Err(ClientError::Timeout(_)) if attempt < attempts => attempt += 1,
+                result => return result,
+            }
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -0,0 +1,17 @@
+//! Decodes a sequence of camera frame chunks, and reassembles them into frames, like the operator UI does with the
+//! chunks received from the server.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use operator_shared::camera::CameraFrameChunk;
+use operator_shared::frame_assembly::FrameAssembler;
+
+fuzz_target!(|data: &[u8]| {
+    let mut assembler = FrameAssembler::new(4, 64 * 1024);
+    let mut remaining = data;
+    while let Ok((chunk, rest)) = postcard::take_from_bytes::<CameraFrameChunk>(remaining) {
+        remaining = rest;
+        let _ = assembler.insert(chunk);
+    }
+});
@@ -0,0 +1,78 @@
+//! Decodes a frame as each type sent between the server and the io boards.
+
+#![no_main]
+
+use ioboard_shared::analog::{AnalogReading, AnalogRequest, AnalogResponse};
+use ioboard_shared::batch::CommandBatch;
+use ioboard_shared::commands::{IoBoardCommand, Sequenced};
+use ioboard_shared::dispenser::{DispenserRequest, DispenserResponse};
+use ioboard_shared::driver::{DriverRequest, DriverResponse};
+use ioboard_shared::estop::EStop;
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::expansion::{ExpansionRequest, ExpansionResponse};
+use ioboard_shared::force::ForceTrace;
+use ioboard_shared::homing::{HomingRequest, HomingResponse};
+use ioboard_shared::load::AxisLoad;
+use ioboard_shared::load_cell::LoadCellSample;
+use ioboard_shared::motion::{
+    FlushQueueRequest, FlushQueueResponse, MotionCommandRequest, MotionCommandResponse, MotionSetpoint, PositionReport,
+};
+use ioboard_shared::power::{PowerRequest, PowerResponse};
+use ioboard_shared::probe::{ProbeRequest, ProbeResponse};
+use ioboard_shared::safe_z::{SafeZRequest, SafeZResponse};
+use ioboard_shared::safety::SafetyStatus;
+use ioboard_shared::self_test::SelfTestStatus;
+use ioboard_shared::thermal::ThermalReading;
+use ioboard_shared::vacuum::{VacuumRequest, VacuumResponse};
+use ioboard_shared::vibration::VibrationReport;
+use libfuzzer_sys::fuzz_target;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Anything that decodes must encode again, and decode to the same encoding, floats may be NaN.
+fn decode<T: Serialize + DeserializeOwned>(data: &[u8]) {
+    if let Ok(value) = postcard::from_bytes::<T>(data) {
+        let encoded = postcard::to_allocvec(&value).unwrap();
+        let decoded = postcard::from_bytes::<T>(&encoded).unwrap();
+        assert_eq!(postcard::to_allocvec(&decoded).unwrap(), encoded);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    decode::<IoBoardCommand>(data);
+    decode::<CommandBatch>(data);
+    decode::<IoBoardEvent>(data);
+    decode::<AxisLoad>(data);
+    decode::<ForceTrace>(data);
+    decode::<MotionSetpoint>(data);
+    decode::<PositionReport>(data);
+    decode::<SafetyStatus>(data);
+    decode::<SelfTestStatus>(data);
+    decode::<ThermalReading>(data);
+    decode::<VibrationReport>(data);
+    decode::<LoadCellSample>(data);
+    decode::<EStop>(data);
+    decode::<AnalogReading>(data);
+    decode::<Sequenced<PowerRequest>>(data);
+    decode::<PowerResponse>(data);
+    decode::<Sequenced<VacuumRequest>>(data);
+    decode::<VacuumResponse>(data);
+    decode::<Sequenced<DispenserRequest>>(data);
+    decode::<DispenserResponse>(data);
+    decode::<Sequenced<HomingRequest>>(data);
+    decode::<HomingResponse>(data);
+    decode::<Sequenced<DriverRequest>>(data);
+    decode::<DriverResponse>(data);
+    decode::<Sequenced<SafeZRequest>>(data);
+    decode::<SafeZResponse>(data);
+    decode::<Sequenced<ProbeRequest>>(data);
+    decode::<ProbeResponse>(data);
+    decode::<Sequenced<ExpansionRequest>>(data);
+    decode::<ExpansionResponse>(data);
+    decode::<Sequenced<AnalogRequest>>(data);
+    decode::<AnalogResponse>(data);
+    decode::<Sequenced<FlushQueueRequest>>(data);
+    decode::<FlushQueueResponse>(data);
+    decode::<Sequenced<MotionCommandRequest>>(data);
+    decode::<MotionCommandResponse>(data);
+});
@@ -0,0 +1,51 @@
+//! Decodes a frame as each type sent between the server and the operator UI.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use operator_shared::camera::CameraFrameChunk;
+use operator_shared::commands::{OperatorCommandRequest, OperatorCommandResponse};
+use operator_shared::diagnostics::{CameraMemoryReport, CommandLatencyReport};
+use operator_shared::feeders::{FeederEvent, FeedersStatus};
+use operator_shared::homing::HomingStatus;
+use operator_shared::job::JobEvent;
+use operator_shared::maintenance::MaintenanceEvent;
+use operator_shared::readiness::ReadinessStatus;
+use operator_shared::test_area::TestShotEvent;
+use operator_shared::vision::VisionStatus;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Anything that decodes must encode again, and decode to the same encoding.
+fn decode<T: Serialize + DeserializeOwned>(data: &[u8]) {
+    if let Ok(value) = postcard::from_bytes::<T>(data) {
+        let encoded = postcard::to_allocvec(&value).unwrap();
+        let decoded = postcard::from_bytes::<T>(&encoded).unwrap();
+        assert_eq!(postcard::to_allocvec(&decoded).unwrap(), encoded);
+    }
+}
+
+/// The same as [`decode`], for the chunks, which borrow the bytes they are decoded from.
+fn decode_chunk(data: &[u8]) {
+    if let Ok(chunk) = postcard::from_bytes::<CameraFrameChunk>(data) {
+        let encoded = postcard::to_allocvec(&chunk).unwrap();
+        let decoded = postcard::from_bytes::<CameraFrameChunk>(&encoded).unwrap();
+        assert_eq!(postcard::to_allocvec(&decoded).unwrap(), encoded);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    decode_chunk(data);
+    decode::<CommandLatencyReport>(data);
+    decode::<CameraMemoryReport>(data);
+    decode::<FeederEvent>(data);
+    decode::<FeedersStatus>(data);
+    decode::<HomingStatus>(data);
+    decode::<JobEvent>(data);
+    decode::<MaintenanceEvent>(data);
+    decode::<ReadinessStatus>(data);
+    decode::<TestShotEvent>(data);
+    decode::<VisionStatus>(data);
+    decode::<OperatorCommandRequest>(data);
+    decode::<OperatorCommandResponse>(data);
+});
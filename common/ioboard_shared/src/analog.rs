@@ -0,0 +1,123 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// The most analog channels of an io board.
+pub const ANALOG_CHANNELS_MAX: usize = 8;
+
+/// The most bytes of the name or units of a channel.
+pub const ANALOG_LABEL_MAX: usize = 16;
+
+/// The shortest sample interval of a channel, the inputs are sampled by a low priority task.
+pub const ANALOG_SAMPLE_INTERVAL_MIN_MS: u32 = 10;
+
+/// The name or units of a channel, utf-8, so that the readings can be shown without knowing the configuration.
+#[derive(Schema, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AnalogLabel {
+    /// the number of valid entries in `bytes`
+    pub length: u8,
+    pub bytes: [u8; ANALOG_LABEL_MAX],
+}
+
+impl AnalogLabel {
+    /// `None` if the label is longer than [`ANALOG_LABEL_MAX`] bytes.
+    pub fn new(label: &str) -> Option<Self> {
+        if label.len() > ANALOG_LABEL_MAX {
+            return None;
+        }
+        let mut result = Self {
+            length: label.len() as u8,
+            ..Self::default()
+        };
+        result.bytes[..label.len()].copy_from_slice(label.as_bytes());
+        Some(result)
+    }
+
+    /// Empty if the bytes are not utf-8.
+    pub fn as_str(&self) -> &str {
+        let bytes = &self.bytes[..(self.length as usize).min(ANALOG_LABEL_MAX)];
+        core::str::from_utf8(bytes).unwrap_or_default()
+    }
+}
+
+/// An ADC input of the io board, for ad-hoc sensors, e.g. flow, pressure or a potentiometer.
+///
+/// The value of a reading is `fraction * scale + offset`, where the fraction is the raw value relative to the full
+/// scale of the ADC, 0.0-1.0, so that the scaling doesn't depend on the resolution of the ADC of the io board.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AnalogChannelConfig {
+    /// the index of the input, the inputs are specific to the io board
+    pub input: u8,
+    pub name: AnalogLabel,
+    pub units: AnalogLabel,
+    pub scale: f32,
+    pub offset: f32,
+    /// at least [`ANALOG_SAMPLE_INTERVAL_MIN_MS`]
+    pub sample_interval_ms: u32,
+}
+
+impl AnalogChannelConfig {
+    /// `fraction` is 0.0-1.0 of the full scale of the ADC.
+    pub fn value(&self, fraction: f32) -> f32 {
+        fraction * self.scale + self.offset
+    }
+}
+
+/// Configures the analog channels of an io board, the configuration isn't persisted by the io board, it's sent again
+/// after the io board restarts.  `channel` is the index of the channel, 0 to [`ANALOG_CHANNELS_MAX`] - 1.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AnalogRequest {
+    Channels,
+    /// Replaces the configuration of the channel, if any.
+    Configure {
+        channel: u8,
+        config: AnalogChannelConfig,
+    },
+    /// Stops sampling the channel.
+    Remove {
+        channel: u8,
+    },
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AnalogReply {
+    Channels {
+        /// by channel, `None` if the channel is not configured
+        channels: [Option<AnalogChannelConfig>; ANALOG_CHANNELS_MAX],
+        /// the number of inputs of the io board
+        inputs: u8,
+    },
+    Configured,
+    Removed,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AnalogError {
+    InvalidChannel(u8),
+    /// the io board doesn't have the input
+    InvalidInput(u8),
+    /// shorter than [`ANALOG_SAMPLE_INTERVAL_MIN_MS`]
+    InvalidSampleInterval,
+    /// the scale or offset is not finite
+    InvalidScaling,
+}
+
+pub type AnalogResponse = Result<AnalogReply, AnalogError>;
+
+/// A sample of an analog channel, published at the sample interval of the channel, samples that could not be read are
+/// not published.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AnalogReading {
+    pub channel: u8,
+    pub name: AnalogLabel,
+    pub units: AnalogLabel,
+    /// scaled, in the units of the channel
+    pub value: f32,
+    /// the raw value of the ADC, for calibrating the scaling
+    pub raw: u16,
+}
@@ -0,0 +1,31 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::IoBoardCommand;
+use crate::motion::MotionSetpoint;
+
+/// The most commands in a batch, a full batch of setpoints still fits in a single frame.
+pub const COMMAND_BATCH_MAX: usize = 8;
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BatchedCommand {
+    Setpoint(MotionSetpoint),
+    Command(IoBoardCommand),
+}
+
+/// Consecutive commands sent in a single frame, to reduce the per-frame overhead when many small commands are sent
+/// close together, e.g. the setpoints of several axes.  The commands are handled in order, the same as if they had
+/// been sent separately.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CommandBatch {
+    /// the commands are the `Some` entries, unused entries are at the end
+    pub commands: [Option<BatchedCommand>; COMMAND_BATCH_MAX],
+}
+
+impl CommandBatch {
+    pub fn iter(&self) -> impl Iterator<Item = &BatchedCommand> {
+        self.commands.iter().flatten()
+    }
+}
@@ -5,6 +5,27 @@ use serde::{Deserialize, Serialize};
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum IoBoardCommand {
     Test(u64),
-    BeginYeetTest,
-    EndYeetTest,
+    /// Publish the samples of the load cell, see [`LoadCellSample`](crate::load_cell::LoadCellSample).
+    BeginLoadCellStream,
+    EndLoadCellStream,
+}
+
+/// Identifies a request, retried requests use the same key so the io board can detect duplicates.
+///
+/// The session is chosen by the sender when it starts, so that sequence numbers from a restarted sender are not
+/// mistaken for duplicates.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IdempotencyKey {
+    pub session: u32,
+    pub sequence: u32,
+}
+
+/// An endpoint request with an idempotency key, a request that was already executed is not executed again, instead
+/// the original response is returned.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sequenced<T> {
+    pub key: IdempotencyKey,
+    pub request: T,
 }
@@ -0,0 +1,35 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// A paste or glue dispenser, either pneumatic or auger driven.  The server times the dispense cycle, the io board
+/// switches the outputs off by itself if they are left on for too long, e.g. if the server stops responding.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DispenserRequest {
+    /// Pneumatic: open the pressure valve of the syringe.  Auger: run the auger forward.
+    StartPressure,
+    StopPressure,
+    /// Pneumatic: apply suck-back vacuum.  Auger: run the auger in reverse.  The pressure is stopped.
+    StartRetract,
+    StopRetract,
+    Status,
+}
+
+#[derive(Schema, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DispenserStatus {
+    pub pressure: bool,
+    pub retract: bool,
+    /// the outputs were last switched off by the io board because they were on for too long, cleared when an output
+    /// is started again
+    pub timed_out: bool,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DispenserError {
+    /// the io board has no dispenser outputs
+    NoDispenser,
+}
+
+pub type DispenserResponse = Result<DispenserStatus, DispenserError>;
@@ -0,0 +1,39 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Controls the stepper driver of an axis while the axis is at rest, a request is answered once the axis is at rest.
+///
+/// A disabled axis is not powered, so it can be moved by hand, and its position is no longer known, it's enabled again
+/// by the next move or homing, the axis should be homed again first.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DriverRequest {
+    Enable {
+        axis: u8,
+    },
+    Disable {
+        axis: u8,
+    },
+    /// Reduces the current while the axis is at standstill, e.g. to keep idle axes cool, the holding torque is reduced
+    /// accordingly.
+    SetHoldCurrentReduction {
+        axis: u8,
+        /// the hold current, in percent of the run current, 100 is no reduction
+        hold_percent: u8,
+    },
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AxisDriverError {
+    /// the axis is not on the io board
+    InvalidAxis,
+    /// more than 100 percent
+    InvalidHoldCurrent,
+    /// the driver can't reduce the hold current, e.g. a step/dir driver without a configuration interface
+    Unsupported,
+    /// the driver didn't respond, or reported an error
+    Driver,
+}
+
+pub type DriverResponse = Result<(), AxisDriverError>;
@@ -0,0 +1,25 @@
+use ergot::topic;
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+// Broadcast, not sent to each io board, so that every io board stops without waiting for a round trip.
+topic!(EStopTopic, EStop, "topic/estop");
+
+/// Who published the e-stop.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EStopSource {
+    Server,
+    OperatorUi,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EStop {
+    /// Stop step generation immediately and disable the drivers, the io boards latch the e-stop until it is reset,
+    /// see [`SafetyStatus::estop`](crate::safety::SafetyStatus::estop)
+    Stop(EStopSource),
+    /// Clear the latched e-stop, ignored by an io board while a safety input with an e-stop policy is tripped, or while
+    /// its supply is faulted.  A stop received since the last check takes precedence.
+    Reset(EStopSource),
+}
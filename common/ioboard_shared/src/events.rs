@@ -0,0 +1,102 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::estop::EStopSource;
+use crate::motion::MotionAnomaly;
+use crate::power::{Interlock, PowerRail};
+use crate::safety::{SafetyInput, SafetyPolicy};
+use crate::thermal::{TemperatureSensor, ThermalLevel};
+
+/// Asynchronous events published by the io board, e.g. for display/logging on the server.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IoBoardEvent {
+    /// The motion limits of an axis were changed by the driver thermal model.
+    AxisDerating {
+        axis: u8,
+        /// scale applied to the max velocity and acceleration of the axis, 0.0-1.0, 1.0 = no derating
+        factor: f32,
+        /// estimated driver temperature, in degrees celsius
+        temperature: f32,
+    },
+    /// The thermal level of a temperature sensor changed, `Critical` is a machine fault.
+    ThermalLevelChanged {
+        sensor: TemperatureSensor,
+        level: ThermalLevel,
+        /// in degrees celsius
+        temperature: f32,
+    },
+    /// The supply voltage dropped below the undervoltage threshold and motion was stopped, sent once the supply
+    /// is stable again.
+    SupplyUndervoltage {
+        /// lowest voltage measured during the undervoltage, in volts
+        min_voltage: f32,
+        /// time below the recovery voltage
+        duration_ms: u32,
+    },
+    /// A power rail was disabled because an interlock is no longer satisfied.
+    PowerRailInterlocked {
+        rail: PowerRail,
+        interlock: Interlock,
+    },
+    /// A safety input was tripped or cleared, the policy applies while it is tripped.
+    SafetyInputChanged {
+        input: SafetyInput,
+        tripped: bool,
+        policy: SafetyPolicy,
+    },
+    /// A sudden rise in the load of a moving axis was detected, e.g. the nozzle hit something, motion was stopped.
+    AxisCrash {
+        axis: u8,
+        /// 0.0-1.0, see [`AxisLoad`](crate::load::AxisLoad)
+        load: f32,
+        /// the load before the crash
+        baseline: f32,
+    },
+    /// An X or Y setpoint was refused because the Z axis was below the safe height, see
+    /// [`SafeZConfig`](crate::safe_z::SafeZConfig), the axis holds its position.  Sent once per refused move.
+    MoveRefusedBelowSafeZ {
+        axis: u8,
+        /// in steps, `None` if the position of the Z axis has not been reported
+        z_position: Option<i64>,
+    },
+    /// The motion of an axis did not follow the commanded steps, e.g. the nozzle hit an obstacle on the way down,
+    /// motion was stopped.  Detected without a load cell or driver feedback.
+    AxisMotionAnomaly {
+        axis: u8,
+        anomaly: MotionAnomaly,
+    },
+    /// The stepper driver of an axis could not be enabled, motion was stopped.
+    AxisDriverFault {
+        axis: u8,
+    },
+    /// A smart feeder was inserted into a slot of the feeder bank, and its identity was read from its ID chip.
+    FeederInserted {
+        slot: u8,
+        /// the serial number of the ID chip, e.g. the EUI-48 of an ID EEPROM or the ROM ID of a one-wire chip
+        identity: u64,
+    },
+    FeederRemoved {
+        slot: u8,
+    },
+    /// An e-stop was latched or reset, see [`EStop`](crate::estop::EStop).
+    EStopChanged {
+        source: EStopSource,
+        latched: bool,
+    },
+    /// The supply voltage dropped below the undervoltage threshold, motion was stopped and the power rails disabled,
+    /// sent at the time of the fault, the network may not deliver it, see
+    /// [`SupplyUndervoltage`](IoBoardEvent::SupplyUndervoltage) for the report once the supply is stable.
+    SupplyFault {
+        /// in volts
+        voltage: f32,
+    },
+    /// A temperature sensor reads at a rail of its ADC, it's disconnected or shorted, treated as a critical
+    /// temperature, a machine fault.  Sent once, the next reading of the sensor is a
+    /// [`ThermalLevelChanged`](IoBoardEvent::ThermalLevelChanged).
+    TemperatureSensorFault {
+        sensor: TemperatureSensor,
+        /// the raw ADC value
+        raw: u16,
+    },
+}
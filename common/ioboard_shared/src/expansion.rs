@@ -0,0 +1,122 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// The most devices in the device table of an io board.
+pub const EXPANSION_DEVICES_MAX: usize = 8;
+
+/// The most bytes read or written by a single request, larger transfers are split by the client.
+pub const EXPANSION_DATA_MAX: usize = 16;
+
+/// Where a device is connected to the expansion header of the io board.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ExpansionBus {
+    /// 7-bit address
+    I2c { address: u8 },
+    /// the index of the chip select output
+    Spi { chip_select: u8 },
+}
+
+/// Selects the driver of a device, i.e. how registers are addressed.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ExpansionDeviceKind {
+    /// 8-bit registers, e.g. MCP23017
+    PortExpander,
+    /// 8-bit registers, e.g. ADS1115
+    Adc,
+    /// 16-bit memory addresses, writes are split at the page boundaries, e.g. 24LC256
+    Eeprom { page_size: u16 },
+    /// 8-bit registers, for prototyping devices that have no dedicated kind
+    Generic,
+}
+
+/// An entry of the device table of an io board.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExpansionDevice {
+    pub bus: ExpansionBus,
+    pub kind: ExpansionDeviceKind,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExpansionDeviceInfo {
+    pub device: ExpansionDevice,
+    /// the device responded when it was registered, SPI devices can't be detected and are always present
+    pub present: bool,
+}
+
+/// The bytes of a register read or write.
+#[derive(Schema, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExpansionData {
+    /// the number of valid entries in `bytes`
+    pub length: u8,
+    pub bytes: [u8; EXPANSION_DATA_MAX],
+}
+
+impl ExpansionData {
+    /// `None` if there are more than [`EXPANSION_DATA_MAX`] bytes.
+    pub fn new(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > EXPANSION_DATA_MAX {
+            return None;
+        }
+        let mut data = Self {
+            length: bytes.len() as u8,
+            ..Self::default()
+        };
+        data.bytes[..bytes.len()].copy_from_slice(bytes);
+        Some(data)
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..(self.length as usize).min(EXPANSION_DATA_MAX)]
+    }
+}
+
+/// Generic access to the registers of the devices on the expansion header, for prototyping new sensors before they
+/// have a dedicated driver.  `device` is the index in the device table.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ExpansionRequest {
+    Devices,
+    Read {
+        device: u8,
+        register: u16,
+        length: u8,
+    },
+    Write {
+        device: u8,
+        register: u16,
+        data: ExpansionData,
+    },
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ExpansionReply {
+    Devices {
+        /// the `Some` entries, in the order of the device table
+        devices: [Option<ExpansionDeviceInfo>; EXPANSION_DEVICES_MAX],
+    },
+    Data(ExpansionData),
+    Written,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ExpansionError {
+    /// there is no device at the index of the device table
+    UnknownDevice,
+    /// the device did not respond when it was registered
+    NotPresent,
+    /// more than [`EXPANSION_DATA_MAX`] bytes were requested
+    TooLong,
+    /// the register is outside the addressing of the device, e.g. above 255 for 8-bit registers
+    InvalidRegister,
+    /// the transfer failed, e.g. the device did not acknowledge
+    Bus,
+}
+
+pub type ExpansionResponse = Result<ExpansionReply, ExpansionError>;
@@ -0,0 +1,35 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// At the 320Hz of the load cell a trace covers 100ms around the contact.
+pub const FORCE_TRACE_SAMPLES: usize = 32;
+
+/// A touchdown of a nozzle, told apart by whether a part was held by the nozzle before the contact.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Touchdown {
+    Pick,
+    Place,
+}
+
+/// The force of the nozzle load cell in a short window around a touchdown, published once the window has ended.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ForceTrace {
+    pub nozzle: u8,
+    pub touchdown: Touchdown,
+    /// load cell sample rate, in Hz
+    pub sample_rate: f32,
+    /// the index of the sample at which the contact was detected
+    pub contact: u8,
+    /// the number of valid entries in `samples`
+    pub sample_count: u8,
+    /// in N, positive when the nozzle is pressed against the part
+    pub samples: [f32; FORCE_TRACE_SAMPLES],
+}
+
+impl ForceTrace {
+    pub fn samples(&self) -> &[f32] {
+        &self.samples[..(self.sample_count as usize).min(FORCE_TRACE_SAMPLES)]
+    }
+}
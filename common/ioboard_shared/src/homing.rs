@@ -0,0 +1,27 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Home a single axis, the io board moves the axis towards its endstop until it triggers, backs off, and approaches
+/// the endstop again slowly, the position of the axis is zeroed at the endstop.
+///
+/// The order the axes are homed in is decided by the server, the io board homes whichever axis it is asked to.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HomingRequest {
+    pub axis: u8,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AxisHomingError {
+    /// the axis has no endstop
+    NoEndstop,
+    /// the endstop did not trigger within the travel of the axis
+    EndstopNotFound,
+    /// the endstop was still triggered after backing off, e.g. a shorted switch
+    EndstopStuck,
+    /// the safety state does not allow motion
+    MotionNotAllowed,
+}
+
+pub type HomingResponse = Result<(), AxisHomingError>;
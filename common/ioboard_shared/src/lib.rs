@@ -1,5 +1,30 @@
 #![no_std]
 
-pub mod yeet;
+#[cfg(test)]
+extern crate std;
 
+pub mod analog;
+pub mod batch;
 pub mod commands;
+pub mod dispenser;
+pub mod driver;
+pub mod estop;
+pub mod events;
+pub mod expansion;
+pub mod force;
+pub mod homing;
+pub mod load;
+pub mod load_cell;
+pub mod motion;
+pub mod power;
+pub mod probe;
+pub mod resonance;
+pub mod safe_z;
+pub mod safety;
+pub mod self_test;
+pub mod thermal;
+pub mod vacuum;
+pub mod vibration;
+
+#[cfg(test)]
+mod tests;
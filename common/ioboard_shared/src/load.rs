@@ -0,0 +1,17 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Per-axis load, from the current feedback of the stepper driver, e.g. the StallGuard result and actual current
+/// scale of a TMC driver.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AxisLoad {
+    pub axis: u8,
+    /// 0.0-1.0, 1.0 = stalled, only meaningful while the axis is moving
+    pub load: f32,
+    /// actual motor current, as a fraction of the run current, 0.0-1.0
+    pub current: f32,
+    /// the moving average of the load, crashes are detected as a sudden rise above this
+    pub baseline: f32,
+    pub moving: bool,
+}
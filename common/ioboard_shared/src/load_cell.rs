@@ -0,0 +1,15 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// A sample of the nozzle load cell, the HX717 samples at 320Hz, a sample is published for each conversion while the
+/// stream is begun, see [`IoBoardCommand::BeginLoadCellStream`](crate::commands::IoBoardCommand::BeginLoadCellStream).
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LoadCellSample {
+    /// the 24 bit conversion of the ADC, sign extended, for calibrating the scale
+    pub raw: i32,
+    /// tared and scaled, positive when the nozzle is pressed against the part
+    pub grams: f32,
+    /// when the conversion was read, in microseconds since the io board started
+    pub timestamp: u64,
+}
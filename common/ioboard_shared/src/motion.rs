@@ -0,0 +1,240 @@
+use ergot::traits::Schema;
+use machine_ids::MoveId;
+use serde::{Deserialize, Serialize};
+
+/// A position setpoint for server-planned motion.
+///
+/// Setpoints are sent at a fixed, low rate, the io board interpolates between consecutive setpoints and steps the
+/// axis, it does no trajectory planning itself.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MotionSetpoint {
+    pub axis: u8,
+    /// the same for every setpoint of a move
+    pub move_id: MoveId,
+    /// incremented for each setpoint of a move, starts at 0 for the first setpoint of a move, with the `move_id` it
+    /// identifies the setpoint, so that a re-delivered setpoint is not followed twice
+    pub sequence: u32,
+    /// absolute position, in steps
+    pub position: f64,
+    /// time to reach `position` from the previous setpoint, in microseconds
+    pub interval_us: u32,
+}
+
+/// Position telemetry, published periodically while an axis is moving.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PositionReport {
+    pub axis: u8,
+    /// absolute position, in steps
+    pub position: i64,
+}
+
+/// How the motion of an axis differed from the commanded motion, see `IoBoardEvent::AxisMotionAnomaly`.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MotionAnomaly {
+    /// The step pulses of a cycle took longer than the cycle, for several consecutive cycles
+    PulsesDelayed {
+        /// of the last cycle, in microseconds
+        commanded_us: u32,
+        elapsed_us: u32,
+    },
+    /// The position measured by the encoder fell behind the commanded position, since the start of the move
+    FollowingError {
+        /// in steps
+        commanded_steps: i64,
+        measured_steps: i64,
+    },
+}
+
+/// Aborts the setpoint stream of an axis, e.g. when a job is paused or aborted.
+///
+/// The io board discards the queued setpoints, brakes the axis from its current velocity, and reports where it
+/// stopped, see [`QueueFlushed`].  The setpoints of the interrupted move that are still in flight are discarded as they
+/// arrive, the next move starts at sequence 0, planned from the stop position.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FlushQueueRequest {
+    pub axis: u8,
+    /// in steps/s², an axis that can't brake at the deceleration limit of the planner stops within a cycle
+    pub max_deceleration: f64,
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct QueueFlushed {
+    pub axis: u8,
+    /// the number of queued setpoints that were discarded
+    pub discarded: u32,
+    /// `None` if the axis was not following a move
+    pub interrupted_move: Option<MoveId>,
+    /// the sequence of the last setpoint of the interrupted move that was followed
+    pub last_sequence: Option<u32>,
+    /// absolute position, in steps, where the axis stopped
+    pub position: i64,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlushQueueError {
+    /// the io board doesn't follow setpoints for the axis
+    UnknownAxis,
+    /// the stepper failed, or was cancelled, e.g. by an emergency stop, before the axis was braked to a stop
+    Stopped,
+}
+
+pub type FlushQueueResponse = Result<QueueFlushed, FlushQueueError>;
+
+/// A move queued on an io board that plans its own trajectories, see [`MotionCommand::Move`].
+///
+/// Each move starts and ends at rest, all values are in steps.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct QueuedMove {
+    pub move_id: MoveId,
+    /// absolute position
+    pub target: f64,
+    /// in steps/s
+    pub max_velocity: f64,
+    /// in steps/s²
+    pub max_acceleration: f64,
+    /// in steps/s³
+    pub max_jerk: f64,
+}
+
+impl QueuedMove {
+    /// The target must be finite and the limits positive, the trajectory generator can't plan anything else.
+    pub fn is_valid(&self) -> bool {
+        let positive = |limit: f64| limit.is_finite() && limit > 0.0;
+        self.target.is_finite()
+            && positive(self.max_velocity)
+            && positive(self.max_acceleration)
+            && positive(self.max_jerk)
+    }
+}
+
+/// Where the trajectories of an axis are planned, see [`MotionCommand::SetPlanning`].
+///
+/// The io board starts with the planning of its firmware, until the server sends the planning of the io board
+/// definition.
+#[derive(Schema, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MotionPlanning {
+    /// The io board runs the trajectory generator, for the moves queued with [`MotionCommand::Move`].
+    #[default]
+    OnBoard,
+    /// The server runs the trajectory generator and streams setpoints, see [`MotionSetpoint`], the io board only
+    /// interpolates and steps.
+    Server,
+}
+
+/// Commands the motion queue of an axis, for io boards that plan their own trajectories.
+///
+/// The trajectory loop pulls the next move from the queue when the move in progress is finished, and holds the
+/// position while the queue is empty.  Every command is answered with the state of the queue, see
+/// [`MotionQueueStatus`].
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MotionCommand {
+    /// Queue a move, after the moves already queued
+    Move(QueuedMove),
+    /// Only report the state of the queue
+    QueueDepth,
+    /// Discard the queued moves, the move in progress is finished
+    Flush,
+    /// Discard the queued moves, and brake the move in progress to a stop at its acceleration limit
+    Abort,
+    /// Switch where the trajectories of the axis are planned, only while the axis is at rest, also accepted by io
+    /// boards that follow setpoints
+    SetPlanning(MotionPlanning),
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MotionCommandRequest {
+    pub axis: u8,
+    pub command: MotionCommand,
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MotionQueueStatus {
+    pub axis: u8,
+    /// the number of queued moves, not including the move in progress
+    pub depth: u32,
+    pub capacity: u32,
+    /// `None` when the axis is at rest
+    pub current_move: Option<MoveId>,
+    /// the number of queued moves discarded by a flush or an abort, otherwise 0
+    pub discarded: u32,
+    /// absolute position, in steps, for an abort where the axis stopped
+    pub position: i64,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MotionCommandError {
+    /// the io board doesn't plan the trajectories of the axis
+    UnknownAxis,
+    /// the io board follows setpoints for the axis instead, see [`MotionSetpoint`]
+    ServerPlanned,
+    QueueFull,
+    /// see [`QueuedMove::is_valid`]
+    InvalidMove,
+    /// the target is outside the travel of the axis, in steps, both inclusive
+    OutsideSoftLimits {
+        min: i64,
+        max: i64,
+    },
+    /// the stepper failed, or was cancelled, e.g. by an emergency stop, before the axis was braked to a stop
+    Stopped,
+    /// the planning can only be switched while the axis is at rest, see [`MotionCommand::SetPlanning`]
+    Moving,
+    /// the axis has an endstop and must be homed before it's moved, see
+    /// [`Interlock::HomingConfigured`](crate::power::Interlock::HomingConfigured)
+    NotHomed,
+}
+
+pub type MotionCommandResponse = Result<MotionQueueStatus, MotionCommandError>;
+
+/// The positions of an axis braking at a constant deceleration, one per cycle, the last is where the axis stops.
+///
+/// The velocity is in steps per cycle, the deceleration in steps per cycle², an axis that brakes without a positive
+/// deceleration stops within a cycle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StopRamp {
+    position: f64,
+    velocity: f64,
+    deceleration: f64,
+}
+
+impl StopRamp {
+    pub fn new(position: f64, velocity: f64, deceleration: f64) -> Self {
+        Self {
+            position,
+            velocity,
+            deceleration,
+        }
+    }
+}
+
+impl Iterator for StopRamp {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<f64> {
+        if self.velocity == 0.0 || !self.velocity.is_finite() {
+            return None;
+        }
+
+        let velocity = match self.deceleration > 0.0 {
+            false => 0.0,
+            true if self.velocity > 0.0 => (self.velocity - self.deceleration).max(0.0),
+            true => (self.velocity + self.deceleration).min(0.0),
+        };
+        // the average velocity over the cycle
+        self.position += (self.velocity + velocity) / 2.0;
+        self.velocity = velocity;
+        Some(self.position)
+    }
+}
@@ -0,0 +1,52 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerRail {
+    MotorPower,
+    VacuumPump,
+    Lighting,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Interlock {
+    /// The supply voltage is above the undervoltage threshold
+    SupplyOk,
+    /// The axis has been homed, or has no endstop, required before the axis is moved, not by the motor power, homing
+    /// needs it
+    HomingConfigured,
+    /// No safety input with an e-stop policy is tripped
+    SafetyInputsClear,
+    /// No temperature sensor is at the critical level
+    ThermalOk,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerRequest {
+    /// Enable all rails, in sequence
+    PowerUp,
+    /// Disable all rails, in reverse sequence
+    PowerDown,
+    Enable(PowerRail),
+    Disable(PowerRail),
+    Status,
+}
+
+#[derive(Schema, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PowerStatus {
+    pub motor_power: bool,
+    pub vacuum_pump: bool,
+    pub lighting: bool,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerError {
+    InterlockNotSatisfied(PowerRail, Interlock),
+}
+
+pub type PowerResponse = Result<PowerStatus, PowerError>;
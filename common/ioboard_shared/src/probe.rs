@@ -0,0 +1,103 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// What triggers the probe, selected for each probing move.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProbeSource {
+    /// the nozzle load cell, triggers on the contact force
+    LoadCell,
+    /// a dedicated probe input, e.g. a touch probe, or a conductive nozzle touching a grounded plate
+    Electrical,
+}
+
+#[derive(Schema, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProbeSources {
+    pub load_cell: bool,
+    pub electrical: bool,
+}
+
+impl ProbeSources {
+    pub const NONE: ProbeSources = ProbeSources {
+        load_cell: false,
+        electrical: false,
+    };
+
+    pub fn contains(&self, source: ProbeSource) -> bool {
+        match source {
+            ProbeSource::LoadCell => self.load_cell,
+            ProbeSource::Electrical => self.electrical,
+        }
+    }
+
+    pub fn set(&mut self, source: ProbeSource, value: bool) {
+        match source {
+            ProbeSource::LoadCell => self.load_cell = value,
+            ProbeSource::Electrical => self.electrical = value,
+        }
+    }
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProbeArming {
+    pub axis: u8,
+    pub source: ProbeSource,
+}
+
+/// Probing is done by arming the probe and then moving the axis towards the surface, when the probe triggers the axis
+/// stops and the position of the axis at the trigger is latched.  The rest of the move is not run, the axis only
+/// moves again once the probe is disarmed.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProbeRequest {
+    /// Arms the probe, a trigger latched earlier is cleared.
+    Arm(ProbeArming),
+    /// The latched trigger is kept, so that it can still be read after the axis is allowed to move again.
+    Disarm,
+    Status,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProbeTrigger {
+    pub axis: u8,
+    pub source: ProbeSource,
+    /// absolute position of the axis when the probe triggered, in steps
+    pub position: i64,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProbeStatus {
+    /// the sources this io board has
+    pub available: ProbeSources,
+    /// the sources that are currently triggered, armed or not, e.g. a nozzle still touching the plate
+    pub active: ProbeSources,
+    /// `None` when disarmed
+    pub armed: Option<ProbeArming>,
+    /// the latest trigger since the probe was armed
+    pub triggered: Option<ProbeTrigger>,
+}
+
+impl ProbeStatus {
+    pub const IDLE: ProbeStatus = ProbeStatus {
+        available: ProbeSources::NONE,
+        active: ProbeSources::NONE,
+        armed: None,
+        triggered: None,
+    };
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProbeError {
+    /// the io board doesn't have the source
+    SourceUnavailable(ProbeSource),
+    /// the source is already triggered, the probe would trigger immediately, e.g. a stuck touch probe
+    AlreadyTriggered(ProbeSource),
+}
+
+/// The status after the request has been applied.
+pub type ProbeResponse = Result<ProbeStatus, ProbeError>;
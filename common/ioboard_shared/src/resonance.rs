@@ -0,0 +1,68 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_SWEEP_FREQUENCIES: usize = 16;
+
+/// Shakes an axis at rest sinusoidally at each frequency of the sweep while the accelerometer records the response,
+/// the frequency with the largest response is the resonance frequency to configure the input shaper of the axis with.
+///
+/// The axis needs `amplitude_steps` of free travel either side of its position, it's back at the position afterwards.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ResonanceSweepRequest {
+    pub axis: u8,
+    /// frequency of the first step of the sweep, in Hz
+    pub start_frequency: f32,
+    /// frequency step between the steps of the sweep, in Hz
+    pub frequency_step: f32,
+    /// number of steps of the sweep, 1 to [`MAX_SWEEP_FREQUENCIES`]
+    pub frequencies: u8,
+    pub amplitude_steps: f32,
+    /// how long the axis is shaken at each frequency, in milliseconds
+    pub duration_ms: u32,
+}
+
+impl ResonanceSweepRequest {
+    /// The frequency of each step of the sweep, in Hz.
+    pub fn frequency(&self, index: usize) -> f32 {
+        self.start_frequency + self.frequency_step * index as f32
+    }
+
+    /// At least one frequency and at most [`MAX_SWEEP_FREQUENCIES`], the frequencies, the amplitude and the duration
+    /// must be positive and finite.
+    pub fn is_valid(&self) -> bool {
+        // also refuses NaN
+        let positive = |value: f32| value > 0.0 && value.is_finite();
+        let frequencies = self.frequencies as usize;
+        (1..=MAX_SWEEP_FREQUENCIES).contains(&frequencies)
+            && positive(self.start_frequency)
+            && positive(self.frequency(frequencies - 1))
+            && positive(self.amplitude_steps)
+            && self.duration_ms > 0
+    }
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ResonanceSweep {
+    /// the frequency with the largest response, in Hz
+    pub peak_frequency: f32,
+    /// RMS acceleration at each frequency of the request, in g, only the first `frequencies` are used, all 0 when the io
+    /// board has no accelerometer
+    pub responses: [f32; MAX_SWEEP_FREQUENCIES],
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResonanceSweepError {
+    /// the axis is not on the io board
+    InvalidAxis,
+    /// no frequencies, too many, or a frequency or amplitude that isn't positive and finite
+    InvalidSweep,
+    /// the axis follows the setpoints of the server, it is not swept by the io board
+    Unsupported,
+    /// the safety state does not allow motion, or the sweep was cancelled, e.g. by an e-stop
+    MotionNotAllowed,
+}
+
+pub type ResonanceSweepResponse = Result<ResonanceSweep, ResonanceSweepError>;
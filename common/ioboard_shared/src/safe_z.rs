@@ -0,0 +1,55 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// The io board refuses X and Y moves while the Z axis is below the safe height, so that a lowered nozzle is never
+/// dragged across the board, unless the guard is overridden.
+///
+/// The Z axis may be on another io board, its position is then taken from the position reports of that board.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SafeZConfig {
+    pub z_axis: u8,
+    /// absolute position, in steps, Z positions at or above this are safe
+    pub safe_height: i64,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SafeZRequest {
+    Configure(SafeZConfig),
+    /// `true` allows X and Y moves below the safe height, e.g. for moving a nozzle over a part during calibration,
+    /// until the override is cleared again
+    Override(bool),
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SafeZStatus {
+    /// `None` until the guard has been configured
+    pub config: Option<SafeZConfig>,
+    /// `None` until the position of the Z axis has been reported, X and Y moves are refused until then
+    pub z_position: Option<i64>,
+    pub overridden: bool,
+}
+
+impl SafeZStatus {
+    pub const UNCONFIGURED: SafeZStatus = SafeZStatus {
+        config: None,
+        z_position: None,
+        overridden: false,
+    };
+
+    /// An unconfigured guard allows every move.
+    pub fn allows_xy_moves(&self) -> bool {
+        let Some(config) = self.config else {
+            return true;
+        };
+        self.overridden
+            || self
+                .z_position
+                .is_some_and(|position| position >= config.safe_height)
+    }
+}
+
+/// The status after the request has been applied.
+pub type SafeZResponse = SafeZStatus;
@@ -0,0 +1,84 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::estop::EStopSource;
+
+/// Machine guarding inputs, tripped when the machine is not safe to operate at full speed.
+#[derive(Schema, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SafetyInput {
+    /// Tripped when the door is open
+    Door,
+    /// Tripped when the light curtain is broken
+    LightCurtain,
+}
+
+/// What happens to motion while a safety input is tripped.
+///
+/// Ordered by severity, when several inputs are tripped the most severe policy applies.
+#[derive(Schema, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SafetyPolicy {
+    /// Motion continues at a reduced speed
+    ReduceSpeed,
+    /// Moves in progress are completed, new moves are not started
+    PauseMotion,
+    /// Motion is stopped immediately and motor power is disabled, motion only resumes once the fault has been cleared
+    EStop,
+}
+
+/// The restriction on motion that results from the tripped safety inputs.
+#[derive(
+    Schema,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MotionRestriction {
+    #[default]
+    None,
+    ReducedSpeed,
+    Paused,
+    EStopped,
+}
+
+impl From<SafetyPolicy> for MotionRestriction {
+    fn from(policy: SafetyPolicy) -> Self {
+        match policy {
+            SafetyPolicy::ReduceSpeed => MotionRestriction::ReducedSpeed,
+            SafetyPolicy::PauseMotion => MotionRestriction::Paused,
+            SafetyPolicy::EStop => MotionRestriction::EStopped,
+        }
+    }
+}
+
+#[derive(Schema, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SafetyInputState {
+    /// The input is not configured on this io board
+    #[default]
+    NotConfigured,
+    Clear,
+    Tripped,
+}
+
+/// Published periodically, and whenever it changes.
+#[derive(Schema, Debug, Default, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SafetyStatus {
+    pub door: SafetyInputState,
+    pub light_curtain: SafetyInputState,
+    pub restriction: MotionRestriction,
+    /// scale applied to the max velocity and acceleration while the speed is reduced, 0.0-1.0
+    pub reduced_speed_factor: f32,
+    /// the source of the latched e-stop, `None` once it has been reset, see [`EStop`](crate::estop::EStop)
+    pub estop: Option<EStopSource>,
+}
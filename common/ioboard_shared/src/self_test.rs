@@ -0,0 +1,61 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::power::PowerRail;
+
+/// The most faults reported by the self test, further faults are counted but not reported.
+pub const SELF_TEST_FAULTS_MAX: usize = 8;
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SelfTestSensor {
+    SupplyVoltage,
+    Vacuum,
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SelfTestFault {
+    /// The output of a power rail did not read back the state it was set to, e.g. a welded relay
+    OutputStuck {
+        rail: PowerRail,
+        enabled: bool,
+    },
+    SensorUnreadable(SelfTestSensor),
+    /// The reading of an idle machine was outside the plausible range, e.g. a disconnected or shorted sensor
+    SensorOutOfRange {
+        sensor: SelfTestSensor,
+        value: f32,
+    },
+    /// The driver of the axis did not respond
+    DriverNotResponding {
+        axis: u8,
+    },
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BoardState {
+    Ready,
+    /// The self test failed, the board remains degraded until it is restarted
+    Degraded,
+}
+
+/// The result of the power-on self test, published periodically once the test has completed.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SelfTestStatus {
+    pub state: BoardState,
+    /// the checks that were run, checks of hardware the board doesn't have are skipped
+    pub checks: u8,
+    /// all the faults, including those that were not reported
+    pub fault_count: u8,
+    /// the first faults, the `Some` entries, unused entries are at the end
+    pub faults: [Option<SelfTestFault>; SELF_TEST_FAULTS_MAX],
+}
+
+impl SelfTestStatus {
+    pub fn faults(&self) -> impl Iterator<Item = &SelfTestFault> {
+        self.faults.iter().flatten()
+    }
+}
@@ -0,0 +1,706 @@
+//! Property tests of the encoding of the io board protocol, frames received from the network are decoded by the
+//! server and the firmware, malformed frames must be rejected, never panic.
+
+use std::vec::Vec;
+
+use machine_ids::MoveId;
+use proptest::prelude::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::analog::{
+    ANALOG_CHANNELS_MAX, ANALOG_LABEL_MAX, AnalogChannelConfig, AnalogError, AnalogLabel, AnalogReading, AnalogReply,
+    AnalogRequest, AnalogResponse,
+};
+use crate::batch::{BatchedCommand, COMMAND_BATCH_MAX, CommandBatch};
+use crate::commands::{IdempotencyKey, IoBoardCommand, Sequenced};
+use crate::dispenser::{DispenserRequest, DispenserResponse};
+use crate::driver::{DriverRequest, DriverResponse};
+use crate::estop::{EStop, EStopSource};
+use crate::events::IoBoardEvent;
+use crate::expansion::{
+    EXPANSION_DATA_MAX, EXPANSION_DEVICES_MAX, ExpansionBus, ExpansionData, ExpansionDevice, ExpansionDeviceInfo,
+    ExpansionDeviceKind, ExpansionError, ExpansionReply, ExpansionRequest, ExpansionResponse,
+};
+use crate::force::{FORCE_TRACE_SAMPLES, ForceTrace, Touchdown};
+use crate::homing::{HomingRequest, HomingResponse};
+use crate::load::AxisLoad;
+use crate::load_cell::LoadCellSample;
+use crate::motion::{
+    FlushQueueError, FlushQueueRequest, FlushQueueResponse, MotionCommand, MotionCommandError, MotionCommandRequest,
+    MotionCommandResponse, MotionPlanning, MotionQueueStatus, MotionSetpoint, PositionReport, QueueFlushed, QueuedMove,
+    StopRamp,
+};
+use crate::power::{PowerRail, PowerRequest, PowerResponse};
+use crate::probe::{ProbeRequest, ProbeResponse};
+use crate::safe_z::{SafeZRequest, SafeZResponse};
+use crate::safety::{MotionRestriction, SafetyInputState, SafetyStatus};
+use crate::self_test::{BoardState, SELF_TEST_FAULTS_MAX, SelfTestFault, SelfTestSensor, SelfTestStatus};
+use crate::thermal::ThermalReading;
+use crate::vacuum::{NozzleRequest, VacuumRequest, VacuumResponse};
+use crate::vibration::VibrationReport;
+
+/// Anything that decodes must encode again, and decode to the same value.
+fn decode<T: Serialize + DeserializeOwned>(bytes: &[u8]) {
+    if let Ok(value) = postcard::from_bytes::<T>(bytes) {
+        assert_round_trip(&value);
+    }
+}
+
+/// Compares the encodings, not the values, floats may be NaN.
+fn assert_round_trip<T: Serialize + DeserializeOwned>(value: &T) {
+    let encoded = postcard::to_allocvec(value).unwrap();
+    let decoded = postcard::from_bytes::<T>(&encoded).unwrap();
+    assert_eq!(postcard::to_allocvec(&decoded).unwrap(), encoded);
+
+    // a truncated frame is an error, not a panic
+    for length in 0..encoded.len() {
+        let _ = postcard::from_bytes::<T>(&encoded[..length]);
+    }
+}
+
+fn decode_all(bytes: &[u8]) {
+    decode::<IoBoardCommand>(bytes);
+    decode::<CommandBatch>(bytes);
+    decode::<IoBoardEvent>(bytes);
+    decode::<AxisLoad>(bytes);
+    decode::<ForceTrace>(bytes);
+    decode::<MotionSetpoint>(bytes);
+    decode::<PositionReport>(bytes);
+    decode::<SafetyStatus>(bytes);
+    decode::<SelfTestStatus>(bytes);
+    decode::<ThermalReading>(bytes);
+    decode::<VibrationReport>(bytes);
+    decode::<LoadCellSample>(bytes);
+    decode::<EStop>(bytes);
+    decode::<AnalogReading>(bytes);
+    decode::<Sequenced<PowerRequest>>(bytes);
+    decode::<PowerResponse>(bytes);
+    decode::<Sequenced<VacuumRequest>>(bytes);
+    decode::<VacuumResponse>(bytes);
+    decode::<Sequenced<DispenserRequest>>(bytes);
+    decode::<DispenserResponse>(bytes);
+    decode::<Sequenced<HomingRequest>>(bytes);
+    decode::<HomingResponse>(bytes);
+    decode::<Sequenced<DriverRequest>>(bytes);
+    decode::<DriverResponse>(bytes);
+    decode::<Sequenced<SafeZRequest>>(bytes);
+    decode::<SafeZResponse>(bytes);
+    decode::<Sequenced<ProbeRequest>>(bytes);
+    decode::<ProbeResponse>(bytes);
+    decode::<Sequenced<ExpansionRequest>>(bytes);
+    decode::<ExpansionResponse>(bytes);
+    decode::<Sequenced<AnalogRequest>>(bytes);
+    decode::<AnalogResponse>(bytes);
+    decode::<Sequenced<FlushQueueRequest>>(bytes);
+    decode::<FlushQueueResponse>(bytes);
+    decode::<Sequenced<MotionCommandRequest>>(bytes);
+    decode::<MotionCommandResponse>(bytes);
+}
+
+fn idempotency_key() -> impl Strategy<Value = IdempotencyKey> {
+    (any::<u32>(), any::<u32>()).prop_map(|(session, sequence)| IdempotencyKey {
+        session,
+        sequence,
+    })
+}
+
+fn motion_setpoint() -> impl Strategy<Value = MotionSetpoint> {
+    (any::<u8>(), any::<u32>(), any::<u32>(), any::<f64>(), any::<u32>()).prop_map(
+        |(axis, move_id, sequence, position, interval_us)| MotionSetpoint {
+            axis,
+            move_id: MoveId::new(move_id),
+            sequence,
+            position,
+            interval_us,
+        },
+    )
+}
+
+fn flush_queue_response() -> impl Strategy<Value = FlushQueueResponse> {
+    let flushed = (
+        any::<u8>(),
+        any::<u32>(),
+        any::<Option<u32>>(),
+        any::<Option<u32>>(),
+        any::<i64>(),
+    )
+        .prop_map(
+            |(axis, discarded, interrupted_move, last_sequence, position)| QueueFlushed {
+                axis,
+                discarded,
+                interrupted_move: interrupted_move.map(MoveId::new),
+                last_sequence,
+                position,
+            },
+        );
+    prop_oneof![
+        flushed.prop_map(Ok),
+        Just(Err(FlushQueueError::UnknownAxis)),
+        Just(Err(FlushQueueError::Stopped)),
+    ]
+}
+
+fn motion_command_request() -> impl Strategy<Value = MotionCommandRequest> {
+    let queued_move = (any::<u32>(), any::<f64>(), any::<f64>(), any::<f64>(), any::<f64>()).prop_map(
+        |(move_id, target, max_velocity, max_acceleration, max_jerk)| QueuedMove {
+            move_id: MoveId::new(move_id),
+            target,
+            max_velocity,
+            max_acceleration,
+            max_jerk,
+        },
+    );
+    let command = prop_oneof![
+        queued_move.prop_map(MotionCommand::Move),
+        Just(MotionCommand::QueueDepth),
+        Just(MotionCommand::Flush),
+        Just(MotionCommand::Abort),
+        Just(MotionCommand::SetPlanning(MotionPlanning::OnBoard)),
+        Just(MotionCommand::SetPlanning(MotionPlanning::Server)),
+    ];
+    (any::<u8>(), command).prop_map(|(axis, command)| MotionCommandRequest {
+        axis,
+        command,
+    })
+}
+
+fn motion_command_response() -> impl Strategy<Value = MotionCommandResponse> {
+    let status = (
+        any::<u8>(),
+        any::<u32>(),
+        any::<u32>(),
+        any::<Option<u32>>(),
+        any::<u32>(),
+        any::<i64>(),
+    )
+        .prop_map(
+            |(axis, depth, capacity, current_move, discarded, position)| MotionQueueStatus {
+                axis,
+                depth,
+                capacity,
+                current_move: current_move.map(MoveId::new),
+                discarded,
+                position,
+            },
+        );
+    let error = prop_oneof![
+        Just(MotionCommandError::UnknownAxis),
+        Just(MotionCommandError::ServerPlanned),
+        Just(MotionCommandError::QueueFull),
+        Just(MotionCommandError::InvalidMove),
+        (any::<i64>(), any::<i64>()).prop_map(|(min, max)| MotionCommandError::OutsideSoftLimits {
+            min,
+            max,
+        }),
+        Just(MotionCommandError::Stopped),
+        Just(MotionCommandError::Moving),
+        Just(MotionCommandError::NotHomed),
+    ];
+    prop_oneof![status.prop_map(Ok), error.prop_map(Err),]
+}
+
+fn command_batch() -> impl Strategy<Value = CommandBatch> {
+    let command = prop_oneof![
+        motion_setpoint().prop_map(BatchedCommand::Setpoint),
+        any::<u64>().prop_map(|counter| BatchedCommand::Command(IoBoardCommand::Test(counter))),
+        Just(BatchedCommand::Command(IoBoardCommand::BeginLoadCellStream)),
+        Just(BatchedCommand::Command(IoBoardCommand::EndLoadCellStream)),
+    ];
+    proptest::collection::vec(command, 0..=COMMAND_BATCH_MAX).prop_map(|commands| {
+        let mut batch = CommandBatch {
+            commands: [None; COMMAND_BATCH_MAX],
+        };
+        for (entry, command) in batch.commands.iter_mut().zip(commands) {
+            *entry = Some(command);
+        }
+        batch
+    })
+}
+
+fn power_rail() -> impl Strategy<Value = PowerRail> {
+    prop_oneof![
+        Just(PowerRail::MotorPower),
+        Just(PowerRail::VacuumPump),
+        Just(PowerRail::Lighting),
+    ]
+}
+
+fn power_request() -> impl Strategy<Value = PowerRequest> {
+    prop_oneof![
+        Just(PowerRequest::PowerUp),
+        Just(PowerRequest::PowerDown),
+        power_rail().prop_map(PowerRequest::Enable),
+        power_rail().prop_map(PowerRequest::Disable),
+        Just(PowerRequest::Status),
+    ]
+}
+
+fn vacuum_request() -> impl Strategy<Value = VacuumRequest> {
+    let nozzle_request = prop_oneof![
+        Just(NozzleRequest::OpenValve),
+        Just(NozzleRequest::CloseValve),
+        Just(NozzleRequest::StartBlowOff),
+        Just(NozzleRequest::StopBlowOff),
+        any::<f32>().prop_map(NozzleRequest::SetPartPresentThreshold),
+    ];
+    prop_oneof![
+        any::<f32>().prop_map(VacuumRequest::SetSetpoint),
+        Just(VacuumRequest::Off),
+        (any::<u8>(), nozzle_request).prop_map(|(nozzle, request)| VacuumRequest::Nozzle(nozzle, request)),
+        Just(VacuumRequest::Status),
+    ]
+}
+
+fn safety_status() -> impl Strategy<Value = SafetyStatus> {
+    let input_state = || {
+        prop_oneof![
+            Just(SafetyInputState::NotConfigured),
+            Just(SafetyInputState::Clear),
+            Just(SafetyInputState::Tripped),
+        ]
+    };
+    let restriction = prop_oneof![
+        Just(MotionRestriction::None),
+        Just(MotionRestriction::ReducedSpeed),
+        Just(MotionRestriction::Paused),
+        Just(MotionRestriction::EStopped),
+    ];
+    (
+        input_state(),
+        input_state(),
+        restriction,
+        any::<f32>(),
+        proptest::option::of(estop_source()),
+    )
+        .prop_map(
+            |(door, light_curtain, restriction, reduced_speed_factor, estop)| SafetyStatus {
+                door,
+                light_curtain,
+                restriction,
+                reduced_speed_factor,
+                estop,
+            },
+        )
+}
+
+fn estop_source() -> impl Strategy<Value = EStopSource> {
+    prop_oneof![Just(EStopSource::Server), Just(EStopSource::OperatorUi),]
+}
+
+fn estop() -> impl Strategy<Value = EStop> {
+    prop_oneof![
+        estop_source().prop_map(EStop::Stop),
+        estop_source().prop_map(EStop::Reset),
+    ]
+}
+
+fn self_test_status() -> impl Strategy<Value = SelfTestStatus> {
+    let state = prop_oneof![Just(BoardState::Ready), Just(BoardState::Degraded),];
+    let sensor = || prop_oneof![Just(SelfTestSensor::SupplyVoltage), Just(SelfTestSensor::Vacuum),];
+    let fault = prop_oneof![
+        (power_rail(), any::<bool>()).prop_map(|(rail, enabled)| SelfTestFault::OutputStuck {
+            rail,
+            enabled,
+        }),
+        sensor().prop_map(SelfTestFault::SensorUnreadable),
+        (sensor(), any::<f32>()).prop_map(|(sensor, value)| SelfTestFault::SensorOutOfRange {
+            sensor,
+            value,
+        }),
+        any::<u8>().prop_map(|axis| SelfTestFault::DriverNotResponding {
+            axis,
+        }),
+    ];
+    (
+        state,
+        any::<u8>(),
+        any::<u8>(),
+        proptest::collection::vec(fault, 0..=SELF_TEST_FAULTS_MAX),
+    )
+        .prop_map(|(state, checks, fault_count, faults)| {
+            let mut status = SelfTestStatus {
+                state,
+                checks,
+                fault_count,
+                faults: [None; SELF_TEST_FAULTS_MAX],
+            };
+            for (entry, fault) in status.faults.iter_mut().zip(faults) {
+                *entry = Some(fault);
+            }
+            status
+        })
+}
+
+fn force_trace() -> impl Strategy<Value = ForceTrace> {
+    let touchdown = prop_oneof![Just(Touchdown::Pick), Just(Touchdown::Place),];
+    (
+        any::<u8>(),
+        touchdown,
+        any::<f32>(),
+        any::<u8>(),
+        proptest::collection::vec(any::<f32>(), 0..=FORCE_TRACE_SAMPLES),
+    )
+        .prop_map(|(nozzle, touchdown, sample_rate, contact, values)| {
+            let mut trace = ForceTrace {
+                nozzle,
+                touchdown,
+                sample_rate,
+                contact,
+                sample_count: values.len() as u8,
+                samples: [0.0; FORCE_TRACE_SAMPLES],
+            };
+            trace.samples[..values.len()].copy_from_slice(&values);
+            trace
+        })
+}
+
+fn expansion_data() -> impl Strategy<Value = ExpansionData> {
+    proptest::collection::vec(any::<u8>(), 0..=EXPANSION_DATA_MAX).prop_map(|bytes| ExpansionData::new(&bytes).unwrap())
+}
+
+fn expansion_request() -> impl Strategy<Value = ExpansionRequest> {
+    prop_oneof![
+        Just(ExpansionRequest::Devices),
+        (any::<u8>(), any::<u16>(), any::<u8>()).prop_map(|(device, register, length)| ExpansionRequest::Read {
+            device,
+            register,
+            length,
+        }),
+        (any::<u8>(), any::<u16>(), expansion_data()).prop_map(|(device, register, data)| ExpansionRequest::Write {
+            device,
+            register,
+            data,
+        }),
+    ]
+}
+
+fn expansion_response() -> impl Strategy<Value = ExpansionResponse> {
+    let bus = prop_oneof![
+        any::<u8>().prop_map(|address| ExpansionBus::I2c {
+            address,
+        }),
+        any::<u8>().prop_map(|chip_select| ExpansionBus::Spi {
+            chip_select,
+        }),
+    ];
+    let kind = prop_oneof![
+        Just(ExpansionDeviceKind::PortExpander),
+        Just(ExpansionDeviceKind::Adc),
+        any::<u16>().prop_map(|page_size| ExpansionDeviceKind::Eeprom {
+            page_size,
+        }),
+        Just(ExpansionDeviceKind::Generic),
+    ];
+    let info = (bus, kind, any::<bool>()).prop_map(|(bus, kind, present)| ExpansionDeviceInfo {
+        device: ExpansionDevice {
+            bus,
+            kind,
+        },
+        present,
+    });
+    let devices = proptest::collection::vec(info, 0..=EXPANSION_DEVICES_MAX).prop_map(|infos| {
+        let mut devices = [None; EXPANSION_DEVICES_MAX];
+        for (entry, info) in devices.iter_mut().zip(infos) {
+            *entry = Some(info);
+        }
+        ExpansionReply::Devices {
+            devices,
+        }
+    });
+    let error = prop_oneof![
+        Just(ExpansionError::UnknownDevice),
+        Just(ExpansionError::NotPresent),
+        Just(ExpansionError::TooLong),
+        Just(ExpansionError::InvalidRegister),
+        Just(ExpansionError::Bus),
+    ];
+    prop_oneof![
+        devices.prop_map(Ok),
+        expansion_data().prop_map(|data| Ok(ExpansionReply::Data(data))),
+        Just(Ok(ExpansionReply::Written)),
+        error.prop_map(Err),
+    ]
+}
+
+/// At most 4 characters, i.e. at most 16 bytes.
+fn analog_label() -> impl Strategy<Value = AnalogLabel> {
+    "(?s).{0,4}".prop_map(|label| AnalogLabel::new(&label).unwrap())
+}
+
+fn analog_channel_config() -> impl Strategy<Value = AnalogChannelConfig> {
+    (
+        any::<u8>(),
+        analog_label(),
+        analog_label(),
+        any::<f32>(),
+        any::<f32>(),
+        any::<u32>(),
+    )
+        .prop_map(
+            |(input, name, units, scale, offset, sample_interval_ms)| AnalogChannelConfig {
+                input,
+                name,
+                units,
+                scale,
+                offset,
+                sample_interval_ms,
+            },
+        )
+}
+
+fn analog_request() -> impl Strategy<Value = AnalogRequest> {
+    prop_oneof![
+        Just(AnalogRequest::Channels),
+        (any::<u8>(), analog_channel_config()).prop_map(|(channel, config)| AnalogRequest::Configure {
+            channel,
+            config,
+        }),
+        any::<u8>().prop_map(|channel| AnalogRequest::Remove {
+            channel,
+        }),
+    ]
+}
+
+fn analog_response() -> impl Strategy<Value = AnalogResponse> {
+    let channels = (
+        proptest::collection::vec(proptest::option::of(analog_channel_config()), ANALOG_CHANNELS_MAX),
+        any::<u8>(),
+    )
+        .prop_map(|(configs, inputs)| {
+            let mut channels = [None; ANALOG_CHANNELS_MAX];
+            for (entry, config) in channels.iter_mut().zip(configs) {
+                *entry = config;
+            }
+            AnalogReply::Channels {
+                channels,
+                inputs,
+            }
+        });
+    let error = prop_oneof![
+        any::<u8>().prop_map(AnalogError::InvalidChannel),
+        any::<u8>().prop_map(AnalogError::InvalidInput),
+        Just(AnalogError::InvalidSampleInterval),
+        Just(AnalogError::InvalidScaling),
+    ];
+    prop_oneof![
+        channels.prop_map(Ok),
+        Just(Ok(AnalogReply::Configured)),
+        Just(Ok(AnalogReply::Removed)),
+        error.prop_map(Err),
+    ]
+}
+
+fn analog_reading() -> impl Strategy<Value = AnalogReading> {
+    (any::<u8>(), analog_label(), analog_label(), any::<f32>(), any::<u16>()).prop_map(
+        |(channel, name, units, value, raw)| AnalogReading {
+            channel,
+            name,
+            units,
+            value,
+            raw,
+        },
+    )
+}
+
+fn load_cell_sample() -> impl Strategy<Value = LoadCellSample> {
+    (any::<i32>(), any::<f32>(), any::<u64>()).prop_map(|(raw, grams, timestamp)| LoadCellSample {
+        raw,
+        grams,
+        timestamp,
+    })
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_frames_never_panic(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+        decode_all(&bytes);
+    }
+
+    #[test]
+    fn motion_setpoints_round_trip(setpoint in motion_setpoint()) {
+        assert_round_trip(&setpoint);
+    }
+
+    #[test]
+    fn command_batches_round_trip(batch in command_batch()) {
+        assert_round_trip(&batch);
+    }
+
+    #[test]
+    fn sequenced_power_requests_round_trip(key in idempotency_key(), request in power_request()) {
+        assert_round_trip(&Sequenced {
+            key,
+            request,
+        });
+    }
+
+    #[test]
+    fn sequenced_vacuum_requests_round_trip(key in idempotency_key(), request in vacuum_request()) {
+        assert_round_trip(&Sequenced {
+            key,
+            request,
+        });
+    }
+
+    #[test]
+    fn safety_status_round_trips(status in safety_status()) {
+        assert_round_trip(&status);
+    }
+
+    #[test]
+    fn estops_round_trip(estop in estop()) {
+        assert_round_trip(&estop);
+    }
+
+    #[test]
+    fn self_test_status_round_trips(status in self_test_status()) {
+        assert_round_trip(&status);
+    }
+
+    #[test]
+    fn force_traces_round_trip(trace in force_trace()) {
+        assert_round_trip(&trace);
+        assert_eq!(trace.samples().len(), trace.sample_count as usize);
+    }
+
+    #[test]
+    fn sequenced_expansion_requests_round_trip(key in idempotency_key(), request in expansion_request()) {
+        assert_round_trip(&Sequenced {
+            key,
+            request,
+        });
+    }
+
+    #[test]
+    fn expansion_responses_round_trip(response in expansion_response()) {
+        assert_round_trip(&response);
+    }
+
+    #[test]
+    fn sequenced_analog_requests_round_trip(key in idempotency_key(), request in analog_request()) {
+        assert_round_trip(&Sequenced {
+            key,
+            request,
+        });
+    }
+
+    #[test]
+    fn analog_responses_round_trip(response in analog_response()) {
+        assert_round_trip(&response);
+    }
+
+    #[test]
+    fn analog_readings_round_trip(reading in analog_reading()) {
+        assert_round_trip(&reading);
+    }
+
+    #[test]
+    fn load_cell_samples_round_trip(sample in load_cell_sample()) {
+        assert_round_trip(&sample);
+    }
+
+    #[test]
+    fn analog_labels_keep_their_text(label in "(?s).{0,32}") {
+        match AnalogLabel::new(&label) {
+            Some(analog_label) => prop_assert_eq!(analog_label.as_str(), label.as_str()),
+            None => prop_assert!(label.len() > ANALOG_LABEL_MAX),
+        }
+    }
+
+    #[test]
+    fn sequenced_flush_queue_requests_round_trip(
+        key in idempotency_key(),
+        axis in any::<u8>(),
+        max_deceleration in any::<f64>(),
+    ) {
+        assert_round_trip(&Sequenced {
+            key,
+            request: FlushQueueRequest {
+                axis,
+                max_deceleration,
+            },
+        });
+    }
+
+    #[test]
+    fn flush_queue_responses_round_trip(response in flush_queue_response()) {
+        assert_round_trip(&response);
+    }
+
+    #[test]
+    fn sequenced_motion_command_requests_round_trip(key in idempotency_key(), request in motion_command_request()) {
+        assert_round_trip(&Sequenced {
+            key,
+            request,
+        });
+    }
+
+    #[test]
+    fn motion_command_responses_round_trip(response in motion_command_response()) {
+        assert_round_trip(&response);
+    }
+
+    #[test]
+    fn queued_moves_with_a_non_positive_limit_are_invalid(
+        target in -1.0e6..1.0e6_f64,
+        limit in -1.0e6..=0.0_f64,
+        index in 0..3_usize,
+    ) {
+        let mut limits = [1000.0; 3];
+        limits[index] = limit;
+        let queued_move = QueuedMove {
+            move_id: MoveId::new(1),
+            target,
+            max_velocity: limits[0],
+            max_acceleration: limits[1],
+            max_jerk: limits[2],
+        };
+
+        prop_assert!(!queued_move.is_valid());
+        prop_assert!(QueuedMove {
+            max_velocity: 1000.0,
+            max_acceleration: 1000.0,
+            max_jerk: 1000.0,
+            ..queued_move
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn stop_ramps_stop_within_the_braking_distance(
+        position in -1.0e6..1.0e6_f64,
+        velocity in -100.0..100.0_f64,
+        deceleration in 0.01..10.0_f64,
+    ) {
+        let positions: Vec<f64> = StopRamp::new(position, velocity, deceleration)
+            .take(100_000)
+            .collect();
+
+        // one cycle more than the continuous ramp, for the partial last cycle
+        prop_assert!(positions.len() as f64 <= velocity.abs() / deceleration + 1.0);
+        let stop = positions.last().copied().unwrap_or(position);
+        let distance = stop - position;
+        prop_assert!(distance * velocity >= 0.0);
+        prop_assert!(distance.abs() <= velocity * velocity / (2.0 * deceleration) + velocity.abs());
+        // the axis never reverses while braking
+        let mut previous = position;
+        for next in positions {
+            prop_assert!((next - previous) * velocity >= 0.0);
+            previous = next;
+        }
+    }
+
+    #[test]
+    fn corrupted_motion_setpoints_never_panic(
+        setpoint in motion_setpoint(),
+        index in any::<prop::sample::Index>(),
+        value in any::<u8>(),
+    ) {
+        let mut encoded: Vec<u8> = postcard::to_allocvec(&setpoint).unwrap();
+        let index = index.index(encoded.len());
+        encoded[index] = value;
+
+        decode_all(&encoded);
+    }
+}
@@ -0,0 +1,40 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Schema, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TemperatureSensor {
+    /// Stepper driver, by axis index
+    Driver(u8),
+    Ambient,
+}
+
+#[derive(
+    Schema,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ThermalLevel {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ThermalReading {
+    pub sensor: TemperatureSensor,
+    /// in degrees celsius
+    pub temperature: f32,
+    pub level: ThermalLevel,
+}
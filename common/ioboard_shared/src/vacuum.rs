@@ -0,0 +1,63 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of nozzles on a vacuum manifold.
+pub const MAX_NOZZLES: usize = 8;
+
+/// Vacuum levels are in kPa below ambient pressure, i.e. positive values.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VacuumRequest {
+    /// Regulate the vacuum to the setpoint, in kPa
+    SetSetpoint(f32),
+    /// Stop the pump
+    Off,
+    /// Control a nozzle on the manifold, by nozzle index
+    Nozzle(u8, NozzleRequest),
+    Status,
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NozzleRequest {
+    OpenValve,
+    CloseValve,
+    /// Blow air out of the nozzle, e.g. to clear a clog, the vacuum valve is closed
+    StartBlowOff,
+    StopBlowOff,
+    /// A part is considered present when the valve is open and the nozzle vacuum is above the threshold, in kPa
+    SetPartPresentThreshold(f32),
+}
+
+#[derive(Schema, Debug, Default, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NozzleStatus {
+    pub valve_open: bool,
+    pub blow_off: bool,
+    /// latest measured nozzle vacuum, in kPa, `None` if the sensor could not be read
+    pub vacuum: Option<f32>,
+    /// `None` if the valve is closed or the vacuum is unknown
+    pub part_present: Option<bool>,
+}
+
+#[derive(Schema, Debug, Default, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VacuumStatus {
+    /// in kPa, `None` when the pump is off
+    pub setpoint: Option<f32>,
+    /// latest measured vacuum, in kPa, `None` if the sensor could not be read
+    pub vacuum: Option<f32>,
+    /// pump PWM duty, 0.0-1.0
+    pub duty: f32,
+    /// the number of valid entries in `nozzles`, 0 when there is no manifold
+    pub nozzle_count: u8,
+    pub nozzles: [NozzleStatus; MAX_NOZZLES],
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VacuumError {
+    InvalidNozzle(u8),
+}
+
+pub type VacuumResponse = Result<VacuumStatus, VacuumError>;
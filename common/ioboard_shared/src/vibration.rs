@@ -0,0 +1,20 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+pub const VIBRATION_SPECTRUM_BINS: usize = 16;
+
+/// Summary of the accelerometer samples collected since the previous report.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VibrationReport {
+    /// accelerometer sample rate, in Hz
+    pub sample_rate: f32,
+    /// per-axis (x, y, z) RMS acceleration with the static (gravity) component removed, in g
+    pub rms: [f32; 3],
+    /// frequency of the first spectrum bin, in Hz
+    pub spectrum_start: f32,
+    /// frequency step between spectrum bins, in Hz
+    pub spectrum_step: f32,
+    /// amplitude of the acceleration magnitude at each frequency bin, in g
+    pub spectrum: [f32; VIBRATION_SPECTRUM_BINS],
+}
@@ -1 +0,0 @@
-pub type Yeet = u64;
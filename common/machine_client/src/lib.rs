@@ -0,0 +1,495 @@
+//! A typed async client for the server, for integrations and custom automation, e.g. starting a job from a line
+//! controller, without the operator UI.
+//!
+//! The client is an ergot edge node, connected to the server over UDP the same way as the operator UI.  The operator
+//! command endpoint of the server is discovered when connecting, see [`MachineClient::connect`], each command is then a
+//! method returning the result of the command, and the state of the machine is received as [`MachineEvent`]s, see
+//! [`MachineClient::subscribe_state`].
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), machine_client::MachineClientError> {
+//! use machine_client::{ClientConfig, MachineClient, MachineEvent};
+//!
+//! let client = MachineClient::connect(&ClientConfig::default()).await?;
+//! let mut state = client.subscribe_state();
+//!
+//! if let Err(e) = client.home_all().await? {
+//!     println!("homing refused: {:?}", e);
+//! }
+//! while let Some(event) = state.recv().await {
+//!     if let MachineEvent::Homing(status) = event
+//!         && !status.running
+//!     {
+//!         break;
+//!     }
+//! }
+//! if let Err(e) = client.run_job().await? {
+//!     println!("job refused: {:?}", e);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With the `machine-vision` feature, which must match the server, snapshots of the cameras can be captured, see
+//! [`MachineClient::capture_snapshot`], the capture endpoint of the server is then also discovered when connecting.
+//! With the `python` feature the client is also a python module, see `pyproject.toml`.
+//!
+//! FUTURE jogging, once the server has a jog command
+
+use std::net::SocketAddr;
+use std::pin::pin;
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::{EdgeStack, new_std_queue, new_target_stack, register_edge_target_interface};
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, endpoint, topic};
+use ergot_util::{ClientError, ClientWrapper};
+use log::{debug, info};
+#[cfg(feature = "machine-vision")]
+use machine_ids::CameraId;
+#[cfg(feature = "machine-vision")]
+use operator_shared::camera::{CameraCommandError, CaptureRequest, CaptureResponse};
+#[cfg(feature = "machine-vision")]
+use operator_shared::captures::{CaptureError, CaptureKey};
+use operator_shared::commands::{OperatorCommandRequest, OperatorCommandResponse};
+use operator_shared::estop::EStopError;
+use operator_shared::geometry::MachineGeometry;
+use operator_shared::homing::{HomingError, HomingStatus};
+use operator_shared::job::{
+    InterventionError, InterventionResolution, JobCheckpoint, JobEvent, ResumeChoice, ResumeError,
+};
+use operator_shared::limits::LimitOverrideStatus;
+use operator_shared::readiness::{ReadinessStatus, StartJobError};
+use thiserror::Error;
+use tokio::net::UdpSocket;
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "python")]
+mod python;
+
+#[cfg(test)]
+mod tests;
+
+endpoint!(
+    OperatorCommandEndpoint,
+    OperatorCommandRequest,
+    OperatorCommandResponse,
+    "topic/operator/command"
+);
+
+#[cfg(feature = "machine-vision")]
+endpoint!(
+    OperatorCaptureEndpoint,
+    CaptureRequest,
+    CaptureResponse,
+    "topic/operator/capture"
+);
+
+topic!(ReadinessTopic, ReadinessStatus, "topic/operator/readiness");
+topic!(HomingStatusTopic, HomingStatus, "topic/operator/homing");
+topic!(JobEventTopic, JobEvent, "topic/operator/job");
+topic!(
+    LimitOverrideTopic,
+    LimitOverrideStatus,
+    "topic/operator/limit-overrides"
+);
+
+/// Events are dropped when the subscriber falls behind by more than this.
+const EVENT_QUEUE_SIZE: usize = 64;
+
+/// Longer than the server's timeout for a vision frame, the request may also be queued behind other vision requests.
+#[cfg(feature = "machine-vision")]
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// the address of the UDP socket of the client, the server only talks to the address its operator interface is
+    /// connected to, currently the same address as the operator UI, so the client is used instead of the operator UI
+    pub local_addr: SocketAddr,
+    /// the address of the UDP interface of the server
+    pub server_addr: SocketAddr,
+    /// of each discovery attempt, see `discovery_attempts`
+    pub discovery_timeout: Duration,
+    /// the server may not have started yet
+    pub discovery_attempts: u32,
+    /// of each command, the server answers most commands immediately, e.g. a job is started, not run to completion
+    pub request_timeout: Duration,
+}
+
+impl Default for ClientConfig {
+    /// The server on the same host, the same addresses as the operator UI.
+    fn default() -> Self {
+        Self {
+            local_addr: SocketAddr::from(([0, 0, 0, 0], 8002)),
+            server_addr: SocketAddr::from(([127, 0, 0, 1], 8001)),
+            discovery_timeout: Duration::from_secs(1),
+            discovery_attempts: 5,
+            request_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MachineClientError {
+    #[error("Socket error: {0}")]
+    Socket(#[from] std::io::Error),
+    #[error("Interface error: {0}")]
+    Interface(String),
+    #[error("Operator endpoint not found")]
+    NotFound,
+    #[error("Request failed: {0}")]
+    Request(#[from] ClientError),
+    /// the server and the client were built from different versions of the operator commands
+    #[error("Unexpected response: {0:?}")]
+    UnexpectedResponse(Box<OperatorCommandResponse>),
+}
+
+#[cfg(feature = "machine-vision")]
+#[derive(Debug, Clone)]
+pub enum SnapshotError {
+    /// e.g. the camera is not streaming
+    Capture(CameraCommandError),
+    /// e.g. the snapshot could not be saved by the server, the details are only logged by the server
+    Fetch(CaptureError),
+    /// the snapshot was replaced while fetching it, e.g. due to retention
+    Changed,
+}
+
+/// The state of the machine, as published by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MachineEvent {
+    Readiness(ReadinessStatus),
+    Homing(HomingStatus),
+    Job(JobEvent),
+    LimitOverrides(LimitOverrideStatus),
+}
+
+pub struct MachineClient {
+    stack: EdgeStack,
+    /// of the operator command endpoint of the server
+    address: Address,
+    /// of the capture endpoint of the server, frames are captured without holding up the operator commands
+    #[cfg(feature = "machine-vision")]
+    capture_address: Address,
+    request_timeout: Duration,
+}
+
+impl MachineClient {
+    /// Binds the socket of the client and discovers the operator command endpoint of the server.
+    ///
+    /// Must be called from within the tokio runtime.
+    pub async fn connect(config: &ClientConfig) -> Result<Self, MachineClientError> {
+        let socket = UdpSocket::bind(config.local_addr).await?;
+        socket
+            .connect(config.server_addr)
+            .await?;
+        info!(
+            "Connecting to server. local: {}, server: {}",
+            socket.local_addr()?,
+            config.server_addr
+        );
+
+        Self::from_socket(socket, config).await
+    }
+
+    /// Uses a socket that is already connected to the server, `local_addr` and `server_addr` of the config are
+    /// ignored.
+    pub async fn from_socket(socket: UdpSocket, config: &ClientConfig) -> Result<Self, MachineClientError> {
+        let queue = new_std_queue(4096);
+        let stack: EdgeStack = new_target_stack(&queue, 1024);
+        register_edge_target_interface(&stack, socket, &queue, None, None)
+            .await
+            .map_err(|e| MachineClientError::Interface(format!("{:?}", e)))?;
+
+        let address = discover(&stack, config, OperatorCommandEndpoint::REQ_KEY.to_bytes()).await?;
+        info!("Operator command endpoint found. address: {:?}", address);
+        #[cfg(feature = "machine-vision")]
+        let capture_address = discover(&stack, config, OperatorCaptureEndpoint::REQ_KEY.to_bytes()).await?;
+
+        Ok(Self {
+            stack,
+            address,
+            #[cfg(feature = "machine-vision")]
+            capture_address,
+            request_timeout: config.request_timeout,
+        })
+    }
+
+    /// The stack of the client, for the topics and endpoints without a method of their own.
+    pub fn stack(&self) -> &EdgeStack {
+        &self.stack
+    }
+
+    /// Sends any operator command, the typed methods are preferred.
+    pub async fn request(
+        &self,
+        request: &OperatorCommandRequest,
+    ) -> Result<OperatorCommandResponse, MachineClientError> {
+        let client = self
+            .stack
+            .endpoints()
+            .client::<OperatorCommandEndpoint>(self.address, None);
+        let client = ClientWrapper::new(self.request_timeout, client);
+        Ok(client.request(request).await?)
+    }
+
+    pub async fn fetch_machine_geometry(&self) -> Result<MachineGeometry, MachineClientError> {
+        match self
+            .request(&OperatorCommandRequest::FetchMachineGeometry)
+            .await?
+        {
+            OperatorCommandResponse::MachineGeometry(geometry) => Ok(geometry),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// The outer error is a communication error, the inner error is the reason the server refused to home.
+    ///
+    /// The progress is published as [`MachineEvent::Homing`].
+    pub async fn home_all(&self) -> Result<Result<(), HomingError>, MachineClientError> {
+        match self
+            .request(&OperatorCommandRequest::HomeAll)
+            .await?
+        {
+            OperatorCommandResponse::HomeAll(result) => Ok(result),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Starts the job selected on the server, the same as the start button of the operator UI.
+    ///
+    /// The outer error is a communication error, the inner error is the reason the server refused to start the job.
+    /// The progress is published as [`MachineEvent::Job`].
+    pub async fn run_job(&self) -> Result<Result<(), StartJobError>, MachineClientError> {
+        match self
+            .request(&OperatorCommandRequest::StartJob)
+            .await?
+        {
+            OperatorCommandResponse::StartJob(result) => Ok(result),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Asks the server to broadcast an e-stop to the io boards, they latch it until it is reset, see
+    /// [`MachineClient::reset_estop`].
+    ///
+    /// The outer error is a communication error, the inner error is returned if the server couldn't broadcast it.
+    pub async fn estop(&self) -> Result<Result<(), EStopError>, MachineClientError> {
+        match self
+            .request(&OperatorCommandRequest::EStop)
+            .await?
+        {
+            OperatorCommandResponse::EStop(result) => Ok(result),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// An io board ignores the reset while a safety input with an e-stop policy is tripped, the latched e-stop is
+    /// published in its safety status.
+    pub async fn reset_estop(&self) -> Result<Result<(), EStopError>, MachineClientError> {
+        match self
+            .request(&OperatorCommandRequest::ResetEStop)
+            .await?
+        {
+            OperatorCommandResponse::EStop(result) => Ok(result),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// The outer error is a communication error, the inner error is the reason the server refused the resolution.
+    pub async fn resolve_intervention(
+        &self,
+        id: u32,
+        resolution: InterventionResolution,
+    ) -> Result<Result<(), InterventionError>, MachineClientError> {
+        let request = OperatorCommandRequest::ResolveIntervention {
+            id,
+            resolution,
+        };
+        match self.request(&request).await? {
+            OperatorCommandResponse::InterventionResolved(result) => Ok(result),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns `None` if there is no interrupted job.
+    pub async fn fetch_job_checkpoint(&self) -> Result<Option<JobCheckpoint>, MachineClientError> {
+        match self
+            .request(&OperatorCommandRequest::FetchJobCheckpoint)
+            .await?
+        {
+            OperatorCommandResponse::JobCheckpoint(checkpoint) => Ok(checkpoint),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// The outer error is a communication error, the inner error is the reason the server refused the choice.
+    pub async fn confirm_resume(&self, choice: ResumeChoice) -> Result<Result<(), ResumeError>, MachineClientError> {
+        match self
+            .request(&OperatorCommandRequest::ConfirmResume(choice))
+            .await?
+        {
+            OperatorCommandResponse::ResumeConfirmed(result) => Ok(result),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Captures a frame with the camera and saves it as a snapshot on the server, returns the jpeg bytes.
+    ///
+    /// The preview stream of the operator UI is not paused.  The outer error is a communication error, the inner error
+    /// is the reason there is no snapshot.
+    #[cfg(feature = "machine-vision")]
+    pub async fn capture_snapshot(
+        &self,
+        camera: CameraId,
+    ) -> Result<Result<Vec<u8>, SnapshotError>, MachineClientError> {
+        let client = self
+            .stack
+            .endpoints()
+            .client::<OperatorCaptureEndpoint>(self.capture_address, None);
+        let client = ClientWrapper::new(CAPTURE_TIMEOUT, client);
+
+        let request = CaptureRequest {
+            camera,
+            pause_preview: false,
+            save: true,
+        };
+        let frame_timestamp = match client.request(&request).await? {
+            Ok(frame) => frame.frame_timestamp,
+            Err(e) => return Ok(Err(SnapshotError::Capture(e))),
+        };
+
+        // the server saves the snapshot by the timestamp of the frame
+        let key = CaptureKey::snapshot(camera, &frame_timestamp);
+        self.fetch_capture(&key).await
+    }
+
+    /// Fetches a saved capture, chunk by chunk, returns the jpeg bytes.
+    #[cfg(feature = "machine-vision")]
+    pub async fn fetch_capture(&self, key: &CaptureKey) -> Result<Result<Vec<u8>, SnapshotError>, MachineClientError> {
+        let mut bytes = Vec::new();
+        loop {
+            let request = OperatorCommandRequest::FetchCapture {
+                key: key.clone(),
+                offset: bytes.len() as u32,
+            };
+            let chunk = match self.request(&request).await? {
+                OperatorCommandResponse::CaptureChunk(Ok(chunk)) => chunk,
+                OperatorCommandResponse::CaptureChunk(Err(e)) => return Ok(Err(SnapshotError::Fetch(e))),
+                response => return Err(unexpected(response)),
+            };
+
+            if chunk.bytes.is_empty() {
+                return Ok(Err(SnapshotError::Changed));
+            }
+            bytes.extend_from_slice(&chunk.bytes);
+            if bytes.len() >= chunk.total_bytes as usize {
+                break;
+            }
+        }
+
+        Ok(Ok(bytes))
+    }
+
+    /// Receives the state of the machine until the subscription is dropped.
+    ///
+    /// Only the state published after subscribing is received, e.g. the readiness is published when it changes.
+    pub fn subscribe_state(&self) -> StateSubscription {
+        let (tx, rx) = mpsc::channel(EVENT_QUEUE_SIZE);
+        let listener = tokio::spawn(state_listener(self.stack.clone(), tx));
+
+        StateSubscription {
+            rx,
+            listener,
+        }
+    }
+}
+
+pub struct StateSubscription {
+    rx: mpsc::Receiver<MachineEvent>,
+    listener: JoinHandle<()>,
+}
+
+impl StateSubscription {
+    /// Returns `None` once the client has stopped listening.
+    pub async fn recv(&mut self) -> Option<MachineEvent> {
+        self.rx.recv().await
+    }
+}
+
+impl Drop for StateSubscription {
+    fn drop(&mut self) {
+        self.listener.abort();
+    }
+}
+
+/// Discovers the endpoint with the request `key` on the server.
+async fn discover(stack: &EdgeStack, config: &ClientConfig, key: [u8; 8]) -> Result<Address, MachineClientError> {
+    let query = SocketQuery {
+        key,
+        nash_req: NameRequirement::Any,
+        frame_kind: FrameKind::ENDPOINT_REQ,
+        broadcast: false,
+    };
+
+    for attempt in 1..=config.discovery_attempts {
+        // TODO just using the first one for now, the same as the operator UI
+        if let Some(result) = stack
+            .discovery()
+            .discover_sockets(4, config.discovery_timeout, &query)
+            .await
+            .into_iter()
+            .next()
+        {
+            return Ok(result.address);
+        }
+        debug!("Endpoint not found. attempt: {}", attempt);
+    }
+    Err(MachineClientError::NotFound)
+}
+
+async fn state_listener(stack: EdgeStack, tx: mpsc::Sender<MachineEvent>) {
+    let readiness_subber = stack
+        .topics()
+        .heap_bounded_receiver::<ReadinessTopic>(4, None);
+    let readiness_subber = pin!(readiness_subber);
+    let mut readiness_hdl = readiness_subber.subscribe();
+
+    let homing_subber = stack
+        .topics()
+        .heap_bounded_receiver::<HomingStatusTopic>(16, None);
+    let homing_subber = pin!(homing_subber);
+    let mut homing_hdl = homing_subber.subscribe();
+
+    let job_subber = stack
+        .topics()
+        .heap_bounded_receiver::<JobEventTopic>(16, None);
+    let job_subber = pin!(job_subber);
+    let mut job_hdl = job_subber.subscribe();
+
+    let limit_override_subber = stack
+        .topics()
+        .heap_bounded_receiver::<LimitOverrideTopic>(4, None);
+    let limit_override_subber = pin!(limit_override_subber);
+    let mut limit_override_hdl = limit_override_subber.subscribe();
+
+    loop {
+        let event = select! {
+            msg = readiness_hdl.recv() => MachineEvent::Readiness(msg.t),
+            msg = homing_hdl.recv() => MachineEvent::Homing(msg.t),
+            msg = job_hdl.recv() => MachineEvent::Job(msg.t),
+            msg = limit_override_hdl.recv() => MachineEvent::LimitOverrides(msg.t),
+            _ = tx.closed() => break,
+        };
+        if let Err(e) = tx.try_send(event) {
+            debug!("State event dropped. error: {:?}", e);
+        }
+    }
+}
+
+fn unexpected(response: OperatorCommandResponse) -> MachineClientError {
+    MachineClientError::UnexpectedResponse(Box::new(response))
+}
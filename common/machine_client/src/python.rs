@@ -0,0 +1,212 @@
+//! The python module, a blocking API over [`MachineClient`], each client has its own tokio runtime.
+//!
+//! ```python
+//! import machine_client
+//!
+//! client = machine_client.MachineClient()
+//! state = client.subscribe_state()
+//! client.home_all()
+//! while True:
+//!     event = state.recv(timeout=1.0)
+//!     if event and event["kind"] == "homing" and not event["value"]["running"]:
+//!         break
+//! try:
+//!     client.run_job()
+//! except machine_client.CommandRefused as e:
+//!     print("job refused:", e)
+//! ```
+//!
+//! The state events are dicts, `kind` is one of `readiness`, `homing`, `job` and `limit_overrides`, `value` is the
+//! status or event published by the server.
+
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+#[cfg(feature = "machine-vision")]
+use pyo3::types::PyBytes;
+use pyo3::types::PyDict;
+use pythonize::pythonize;
+use tokio::runtime::Runtime;
+use tokio::time;
+
+use crate::{ClientConfig, MachineClient, MachineEvent, StateSubscription};
+
+create_exception!(
+    machine_client,
+    MachineClientError,
+    PyException,
+    "Communication with the server failed."
+);
+create_exception!(
+    machine_client,
+    CommandRefused,
+    PyException,
+    "The server refused the command, the message is the reason."
+);
+
+impl From<crate::MachineClientError> for PyErr {
+    fn from(error: crate::MachineClientError) -> Self {
+        MachineClientError::new_err(error.to_string())
+    }
+}
+
+/// The outer error is raised as a `MachineClientError`, the inner error as a `CommandRefused`.
+fn refusal<T, E: Debug>(result: Result<Result<T, E>, crate::MachineClientError>) -> PyResult<T> {
+    result?.map_err(|e| CommandRefused::new_err(format!("{:?}", e)))
+}
+
+fn socket_addr(addr: &str) -> PyResult<SocketAddr> {
+    addr.parse()
+        .map_err(|e| PyValueError::new_err(format!("Invalid address. address: {}, error: {}", addr, e)))
+}
+
+#[pyclass(name = "MachineClient", module = "machine_client")]
+struct PyMachineClient {
+    runtime: Arc<Runtime>,
+    client: MachineClient,
+}
+
+#[pymethods]
+impl PyMachineClient {
+    /// Connects to the server, the addresses are `"ip:port"` strings, the defaults are the same as the operator UI.
+    #[new]
+    #[pyo3(signature = (server_addr = None, local_addr = None))]
+    fn new(py: Python<'_>, server_addr: Option<&str>, local_addr: Option<&str>) -> PyResult<Self> {
+        let mut config = ClientConfig::default();
+        if let Some(server_addr) = server_addr {
+            config.server_addr = socket_addr(server_addr)?;
+        }
+        if let Some(local_addr) = local_addr {
+            config.local_addr = socket_addr(local_addr)?;
+        }
+
+        let runtime = Arc::new(
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?,
+        );
+        let client = py.detach(|| runtime.block_on(MachineClient::connect(&config)))?;
+
+        Ok(Self {
+            runtime,
+            client,
+        })
+    }
+
+    /// Starts homing every axis, the progress is received as `homing` events.
+    fn home_all(&self, py: Python<'_>) -> PyResult<()> {
+        refusal(py.detach(|| {
+            self.runtime
+                .block_on(self.client.home_all())
+        }))
+    }
+
+    /// Asks the server to broadcast an e-stop to the io boards, they latch it until it is reset.
+    fn estop(&self, py: Python<'_>) -> PyResult<()> {
+        refusal(py.detach(|| {
+            self.runtime
+                .block_on(self.client.estop())
+        }))
+    }
+
+    fn reset_estop(&self, py: Python<'_>) -> PyResult<()> {
+        refusal(py.detach(|| {
+            self.runtime
+                .block_on(self.client.reset_estop())
+        }))
+    }
+
+    /// Starts the job selected on the server, the progress is received as `job` events.
+    fn run_job(&self, py: Python<'_>) -> PyResult<()> {
+        refusal(py.detach(|| {
+            self.runtime
+                .block_on(self.client.run_job())
+        }))
+    }
+
+    /// Captures a frame with the camera, by index, and saves it as a snapshot on the server, returns the jpeg bytes.
+    #[cfg(feature = "machine-vision")]
+    fn capture_snapshot<'py>(&self, py: Python<'py>, camera: u8) -> PyResult<Bound<'py, PyBytes>> {
+        let camera = machine_ids::CameraId::new(camera);
+        let bytes = refusal(py.detach(|| {
+            self.runtime
+                .block_on(self.client.capture_snapshot(camera))
+        }))?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    /// Only the state published after subscribing is received.
+    fn subscribe_state(&self) -> PyStateSubscription {
+        // the listener is spawned on the runtime of the client
+        let _guard = self.runtime.enter();
+        PyStateSubscription {
+            runtime: self.runtime.clone(),
+            subscription: self.client.subscribe_state(),
+        }
+    }
+}
+
+#[pyclass(name = "StateSubscription", module = "machine_client")]
+struct PyStateSubscription {
+    runtime: Arc<Runtime>,
+    subscription: StateSubscription,
+}
+
+#[pymethods]
+impl PyStateSubscription {
+    /// Returns the next event, or `None` once the timeout, in seconds, has passed.
+    ///
+    /// Without a timeout ctrl-c is only handled once an event is received.
+    #[pyo3(signature = (timeout = None))]
+    fn recv<'py>(&mut self, py: Python<'py>, timeout: Option<f64>) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let timeout = timeout
+            .map(Duration::try_from_secs_f64)
+            .transpose()
+            .map_err(|e| PyValueError::new_err(format!("Invalid timeout. error: {}", e)))?;
+
+        let runtime = &self.runtime;
+        let subscription = &mut self.subscription;
+        let event = py.detach(|| {
+            runtime.block_on(async {
+                match timeout {
+                    Some(timeout) => time::timeout(timeout, subscription.recv())
+                        .await
+                        .unwrap_or(None),
+                    None => subscription.recv().await,
+                }
+            })
+        });
+
+        event
+            .map(|event| event_to_py(py, &event))
+            .transpose()
+    }
+}
+
+fn event_to_py<'py>(py: Python<'py>, event: &MachineEvent) -> PyResult<Bound<'py, PyAny>> {
+    let (kind, value) = match event {
+        MachineEvent::Readiness(status) => ("readiness", pythonize(py, status)?),
+        MachineEvent::Homing(status) => ("homing", pythonize(py, status)?),
+        MachineEvent::Job(event) => ("job", pythonize(py, event)?),
+        MachineEvent::LimitOverrides(status) => ("limit_overrides", pythonize(py, status)?),
+    };
+
+    let dict = PyDict::new(py);
+    dict.set_item("kind", kind)?;
+    dict.set_item("value", value)?;
+    Ok(dict.into_any())
+}
+
+#[pymodule]
+fn machine_client(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMachineClient>()?;
+    m.add_class::<PyStateSubscription>()?;
+    m.add("MachineClientError", m.py().get_type::<MachineClientError>())?;
+    m.add("CommandRefused", m.py().get_type::<CommandRefused>())?;
+    Ok(())
+}
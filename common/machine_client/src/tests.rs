@@ -0,0 +1,185 @@
+use std::pin::pin;
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::{RouterStack, register_router_interface};
+use operator_shared::commands::{OperatorCommandRequest, OperatorCommandResponse};
+use operator_shared::homing::{AxisHomingState, AxisHomingStatus, HomingStatus};
+use operator_shared::readiness::StartJobError;
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+use super::{
+    ClientConfig, HomingStatusTopic, MachineClient, MachineClientError, MachineEvent, OperatorCommandEndpoint,
+};
+
+const PAYLOAD_SIZE_MAX: usize = 1024;
+const TX_BUFFER_SIZE: usize = 4096;
+
+fn config() -> ClientConfig {
+    ClientConfig {
+        discovery_timeout: Duration::from_millis(200),
+        request_timeout: Duration::from_millis(200),
+        ..ClientConfig::default()
+    }
+}
+
+/// The router is the server, it answers `HomeAll`, refuses `StartJob`, and acknowledges every other command.
+async fn server() -> (RouterStack, UdpSocket, Vec<JoinHandle<()>>) {
+    let server_socket = UdpSocket::bind("127.0.0.1:0")
+        .await
+        .unwrap();
+    let client_socket = UdpSocket::bind("127.0.0.1:0")
+        .await
+        .unwrap();
+    server_socket
+        .connect(client_socket.local_addr().unwrap())
+        .await
+        .unwrap();
+    client_socket
+        .connect(server_socket.local_addr().unwrap())
+        .await
+        .unwrap();
+
+    let router: RouterStack = RouterStack::new();
+    register_router_interface(&router, server_socket, PAYLOAD_SIZE_MAX as _, TX_BUFFER_SIZE)
+        .await
+        .unwrap();
+
+    let query_handler = tokio::spawn({
+        let router = router.clone();
+        async move {
+            router
+                .services()
+                .socket_query_handler::<4>()
+                .await
+        }
+    });
+
+    let command_server = tokio::spawn({
+        let router = router.clone();
+        async move {
+            let server = router
+                .endpoints()
+                .bounded_server::<OperatorCommandEndpoint, 2>(None);
+            let server = pin!(server);
+            let mut hdl = server.attach();
+            loop {
+                let _ = hdl
+                    .serve_full(async |msg| match msg.t {
+                        OperatorCommandRequest::HomeAll => OperatorCommandResponse::HomeAll(Ok(())),
+                        OperatorCommandRequest::StartJob => {
+                            OperatorCommandResponse::StartJob(Err(StartJobError::NoJob))
+                        }
+                        _ => OperatorCommandResponse::Acknowledged,
+                    })
+                    .await;
+            }
+        }
+    });
+
+    (router, client_socket, vec![query_handler, command_server])
+}
+
+#[tokio::test]
+pub async fn commands_return_the_result_of_the_server() {
+    // given
+    let (_router, socket, tasks) = server().await;
+    let client = MachineClient::from_socket(socket, &config())
+        .await
+        .unwrap();
+
+    // when
+    let homing = client.home_all().await;
+    let job = client.run_job().await;
+
+    // then
+    assert!(matches!(homing, Ok(Ok(()))));
+    assert!(matches!(job, Ok(Err(StartJobError::NoJob))));
+
+    tasks.iter().for_each(JoinHandle::abort);
+}
+
+#[tokio::test]
+pub async fn unexpected_response_is_an_error() {
+    // given
+    let (_router, socket, tasks) = server().await;
+    let client = MachineClient::from_socket(socket, &config())
+        .await
+        .unwrap();
+
+    // when
+    let result = client.fetch_machine_geometry().await;
+
+    // then
+    assert!(matches!(
+        result,
+        Err(MachineClientError::UnexpectedResponse(response)) if matches!(*response, OperatorCommandResponse::Acknowledged)
+    ));
+
+    tasks.iter().for_each(JoinHandle::abort);
+}
+
+#[tokio::test]
+pub async fn state_is_received_once_subscribed() {
+    // given
+    let (router, socket, tasks) = server().await;
+    let client = MachineClient::from_socket(socket, &config())
+        .await
+        .unwrap();
+    let mut state = client.subscribe_state();
+    let status = HomingStatus {
+        axes: vec![AxisHomingStatus {
+            axis: "Z".to_string(),
+            state: AxisHomingState::Homed,
+        }],
+        running: false,
+    };
+
+    // when, published repeatedly, the subscription may not have started yet
+    let publisher = tokio::spawn({
+        let status = status.clone();
+        async move {
+            loop {
+                let _ = router
+                    .topics()
+                    .broadcast::<HomingStatusTopic>(&status, None);
+                time::sleep(Duration::from_millis(20)).await;
+            }
+        }
+    });
+    let event = time::timeout(Duration::from_secs(2), state.recv())
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(event, Some(MachineEvent::Homing(status)));
+
+    publisher.abort();
+    tasks.iter().for_each(JoinHandle::abort);
+}
+
+#[tokio::test]
+pub async fn connect_fails_without_a_server() {
+    // given
+    let socket = UdpSocket::bind("127.0.0.1:0")
+        .await
+        .unwrap();
+    let unused = UdpSocket::bind("127.0.0.1:0")
+        .await
+        .unwrap();
+    socket
+        .connect(unused.local_addr().unwrap())
+        .await
+        .unwrap();
+    let config = ClientConfig {
+        discovery_attempts: 2,
+        ..config()
+    };
+
+    // when
+    let result = MachineClient::from_socket(socket, &config).await;
+
+    // then
+    assert!(matches!(result, Err(MachineClientError::NotFound)));
+}
@@ -0,0 +1,248 @@
+//! 2D geometry of the machine, points, rectangles and transforms, shared by the server, vision and the operator UI, so
+//! that positions are not passed around as tuples and converted differently by each crate.
+//!
+//! Machine positions are in millimeters, with X to the right and Y away from the operator, seen from above.  Image
+//! positions are in pixels, with X to the right and Y downwards, see [`PixelPoint`].
+
+use core::ops::{Add, Sub};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+mod tests;
+
+/// In millimeters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn distance(&self, other: &Point) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+/// An axis-aligned rectangle, in millimeters, `min` is the corner with the lowest X and Y.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rect {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Rect {
+    /// From any two opposite corners.
+    pub fn from_corners(a: Point, b: Point) -> Self {
+        Self {
+            min: Point {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+            },
+            max: Point {
+                x: a.x.max(b.x),
+                y: a.y.max(b.y),
+            },
+        }
+    }
+
+    /// `origin` is the corner with the lowest X and Y.
+    pub fn from_origin_and_size(origin: Point, width: f64, height: f64) -> Self {
+        Self::from_corners(origin, Point {
+            x: origin.x + width,
+            y: origin.y + height,
+        })
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max.x - self.min.x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max.y - self.min.y
+    }
+
+    pub fn center(&self) -> Point {
+        Point {
+            x: (self.min.x + self.max.x) / 2.0,
+            y: (self.min.y + self.max.y) / 2.0,
+        }
+    }
+
+    /// The edges are inside.
+    pub fn contains(&self, point: Point) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x && point.y >= self.min.y && point.y <= self.max.y
+    }
+}
+
+/// `[xx xy; yx yy] * point + [tx ty]`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AffineTransform {
+    pub xx: f64,
+    pub xy: f64,
+    pub yx: f64,
+    pub yy: f64,
+    pub tx: f64,
+    pub ty: f64,
+}
+
+impl AffineTransform {
+    pub const IDENTITY: AffineTransform = AffineTransform {
+        xx: 1.0,
+        xy: 0.0,
+        yx: 0.0,
+        yy: 1.0,
+        tx: 0.0,
+        ty: 0.0,
+    };
+
+    /// Rotates by `rotation_degrees` about the origin, counter-clockwise, then translates by `translation`.
+    pub fn rigid(rotation_degrees: f64, translation: Point) -> Self {
+        let (sin, cos) = rotation_degrees.to_radians().sin_cos();
+        Self {
+            xx: cos,
+            xy: -sin,
+            yx: sin,
+            yy: cos,
+            tx: translation.x,
+            ty: translation.y,
+        }
+    }
+
+    /// Scales each axis, then translates, e.g. the scale and offset errors of the axes of a machine.
+    pub fn scaled(scale_x: f64, scale_y: f64, translation: Point) -> Self {
+        Self {
+            xx: scale_x,
+            yy: scale_y,
+            tx: translation.x,
+            ty: translation.y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// The geometry of a machine whose Y axis is `skew_degrees` from perpendicular to the X axis, positive when the
+    /// Y axis leans towards +X.  Moving the Y axis by `d` moves the head by `d` along the leaning Y axis.
+    pub fn skewed(skew_degrees: f64) -> Self {
+        let skew = skew_degrees.to_radians();
+        Self {
+            xy: skew.sin(),
+            yy: skew.cos(),
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Cancels the geometry of [`AffineTransform::skewed`].
+    pub fn deskewed(skew_degrees: f64) -> Self {
+        let skew = skew_degrees.to_radians();
+        Self {
+            xy: -skew.tan(),
+            yy: 1.0 / skew.cos(),
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        Point {
+            x: self.xx * point.x + self.xy * point.y + self.tx,
+            y: self.yx * point.x + self.yy * point.y + self.ty,
+        }
+    }
+
+    /// Applies `first`, then this transform.
+    pub fn after(&self, first: &AffineTransform) -> AffineTransform {
+        AffineTransform {
+            xx: self.xx * first.xx + self.xy * first.yx,
+            xy: self.xx * first.xy + self.xy * first.yy,
+            yx: self.yx * first.xx + self.yy * first.yx,
+            yy: self.yx * first.xy + self.yy * first.yy,
+            tx: self.xx * first.tx + self.xy * first.ty + self.tx,
+            ty: self.yx * first.tx + self.yy * first.ty + self.ty,
+        }
+    }
+
+    /// The angle the X axis is rotated by, counter-clockwise, in degrees.
+    pub fn rotation_degrees(&self) -> f64 {
+        self.yx.atan2(self.xx).to_degrees()
+    }
+
+    /// The angle between the transformed Y axis and the perpendicular of the transformed X axis, in degrees, positive
+    /// when the Y axis leans towards +X, see [`AffineTransform::skewed`].
+    pub fn skew_degrees(&self) -> f64 {
+        let x_axis = self.yx.atan2(self.xx);
+        let y_axis = self.yy.atan2(self.xy);
+        90.0 - (y_axis - x_axis).to_degrees()
+    }
+}
+
+/// In image pixels, relative to the center of the image, X to the right and Y downwards.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PixelPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl PixelPoint {
+    pub fn distance_from_center(&self) -> f64 {
+        self.x.hypot(self.y)
+    }
+}
+
+/// Which way a camera looks at the machine, this decides the direction of the image axes in machine coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageView {
+    /// e.g. a down camera on the head, the top of the image is away from the operator, image Y is machine -Y
+    FromAbove,
+    /// e.g. an up camera looking at the nozzle, the image is mirrored, image Y is machine Y
+    FromBelow,
+}
+
+/// Millimeters per image pixel, from the calibration of a camera.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PixelScale {
+    pub mm_per_pixel_x: f64,
+    pub mm_per_pixel_y: f64,
+}
+
+impl PixelScale {
+    /// A length along the image axes, in millimeters, the directions are unchanged.
+    pub fn to_mm(&self, pixels: PixelPoint) -> Point {
+        Point {
+            x: pixels.x * self.mm_per_pixel_x,
+            y: pixels.y * self.mm_per_pixel_y,
+        }
+    }
+
+    /// The offset in machine coordinates of a position in the image from the center of the image.
+    pub fn to_machine(&self, pixels: PixelPoint, view: ImageView) -> Point {
+        let mm = self.to_mm(pixels);
+        match view {
+            ImageView::FromAbove => Point {
+                x: mm.x,
+                y: -mm.y,
+            },
+            ImageView::FromBelow => mm,
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use super::{AffineTransform, ImageView, PixelPoint, PixelScale, Point, Rect};
+
+fn assert_near(actual: Point, expected: Point) {
+    assert!(
+        actual.distance(&expected) < 1e-9,
+        "actual: {:?}, expected: {:?}",
+        actual,
+        expected
+    );
+}
+
+fn point(x: f64, y: f64) -> Point {
+    Point {
+        x,
+        y,
+    }
+}
+
+#[test]
+pub fn point_arithmetic() {
+    // expect
+    assert_eq!(point(1.0, 2.0) + point(0.5, -3.0), point(1.5, -1.0));
+    assert_eq!(point(1.0, 2.0) - point(0.5, -3.0), point(0.5, 5.0));
+    assert_eq!(point(0.0, 0.0).distance(&point(3.0, 4.0)), 5.0);
+}
+
+#[test]
+pub fn rect_from_any_corners() {
+    // given
+    let rect = Rect::from_corners(point(10.0, 5.0), point(-2.0, 20.0));
+
+    // expect
+    assert_eq!(rect.min, point(-2.0, 5.0));
+    assert_eq!(rect.max, point(10.0, 20.0));
+    assert_eq!(rect.width(), 12.0);
+    assert_eq!(rect.height(), 15.0);
+    assert_eq!(rect.center(), point(4.0, 12.5));
+    assert_eq!(Rect::from_origin_and_size(point(-2.0, 5.0), 12.0, 15.0), rect);
+}
+
+#[test]
+pub fn rect_contains_its_edges() {
+    // given
+    let rect = Rect::from_origin_and_size(point(0.0, 0.0), 10.0, 5.0);
+
+    // expect
+    assert!(rect.contains(point(0.0, 0.0)));
+    assert!(rect.contains(point(10.0, 5.0)));
+    assert!(rect.contains(point(3.0, 2.0)));
+    assert!(!rect.contains(point(10.1, 2.0)));
+    assert!(!rect.contains(point(3.0, -0.1)));
+}
+
+#[test]
+pub fn rigid_transform_rotates_then_translates() {
+    // given
+    let transform = AffineTransform::rigid(90.0, point(10.0, 0.0));
+
+    // when
+    let moved = transform.apply(point(1.0, 0.0));
+
+    // then
+    assert_near(moved, point(10.0, 1.0));
+    assert!((transform.rotation_degrees() - 90.0).abs() < 1e-9);
+}
+
+#[test]
+pub fn after_applies_first_transform_first() {
+    // given
+    let rotate = AffineTransform::rigid(90.0, Point::default());
+    let scale = AffineTransform::scaled(2.0, 1.0, point(0.0, 1.0));
+
+    // when
+    let combined = scale.after(&rotate);
+
+    // then
+    assert_near(
+        combined.apply(point(1.0, 0.0)),
+        scale.apply(rotate.apply(point(1.0, 0.0))),
+    );
+    assert_near(combined.apply(point(1.0, 0.0)), point(0.0, 2.0));
+}
+
+#[test]
+pub fn skew_angle_of_skewed_geometry() {
+    // expect
+    for skew_degrees in [-0.5, 0.0, 0.1, 2.0] {
+        let skew = AffineTransform::skewed(skew_degrees).skew_degrees();
+        assert!(
+            (skew - skew_degrees).abs() < 1e-9,
+            "skew: {}, expected: {}",
+            skew,
+            skew_degrees
+        );
+    }
+}
+
+#[test]
+pub fn deskew_cancels_skew() {
+    // given
+    let geometry = AffineTransform::skewed(0.3);
+    let target = point(100.0, 250.0);
+
+    // when
+    let machine = AffineTransform::deskewed(0.3).apply(target);
+
+    // then
+    assert_near(geometry.apply(machine), target);
+}
+
+#[test]
+pub fn pixels_to_machine_coordinates() {
+    // given
+    let scale = PixelScale {
+        mm_per_pixel_x: 0.02,
+        mm_per_pixel_y: 0.025,
+    };
+    let pixels = PixelPoint {
+        x: 100.0,
+        y: 40.0,
+    };
+
+    // expect
+    assert_near(scale.to_mm(pixels), point(2.0, 1.0));
+    assert_near(scale.to_machine(pixels, ImageView::FromAbove), point(2.0, -1.0));
+    assert_near(scale.to_machine(pixels, ImageView::FromBelow), point(2.0, 1.0));
+}
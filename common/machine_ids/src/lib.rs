@@ -0,0 +1,178 @@
+//! Typed identifiers, shared by the protocols between the server, the operator UI and the io boards, and used as the
+//! keys of the maps of the server, so that e.g. a feeder cannot be given where a camera is expected.
+//!
+//! Identifiers that are read from the configuration and job files are serialized as the value they wrap, so the files
+//! are unchanged.
+
+#![no_std]
+extern crate alloc;
+#[cfg(test)]
+extern crate std;
+
+use alloc::string::String;
+use core::borrow::Borrow;
+use core::fmt::{Display, Formatter};
+
+use postcard_schema::Schema;
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+mod tests;
+
+/// A board of a panel, boards are numbered row by row, starting at 1.
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Schema,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(transparent)]
+pub struct BoardId(u32);
+
+impl BoardId {
+    pub const fn new(number: u32) -> Self {
+        Self(number)
+    }
+
+    pub fn number(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Display for BoardId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A camera, by the index of its definition in the configuration of the server.
+///
+/// Not transparent, the layout of the operator UI, which is persisted, has camera panes by identifier.
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Schema,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CameraId(u8);
+
+impl CameraId {
+    pub const fn new(index: u8) -> Self {
+        Self(index)
+    }
+
+    pub fn index(&self) -> u8 {
+        self.0
+    }
+}
+
+impl Display for CameraId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "C{:03}", self.0)
+    }
+}
+
+impl From<CameraId> for u8 {
+    fn from(value: CameraId) -> Self {
+        value.0
+    }
+}
+
+/// A feeder, by the name in its definition, e.g. "F1".
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct FeederId(String);
+
+/// A job, by its name, e.g. the name of the job file.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct JobId(String);
+
+macro_rules! named_id {
+    ($id:ident) => {
+        impl $id {
+            pub fn new(name: impl Into<String>) -> Self {
+                Self(name.into())
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Display for $id {
+            fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl From<&str> for $id {
+            fn from(value: &str) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl From<String> for $id {
+            fn from(value: String) -> Self {
+                Self(value)
+            }
+        }
+
+        /// Allows maps keyed by the identifier to be looked up by name.
+        impl Borrow<str> for $id {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+    };
+}
+
+named_id!(FeederId);
+named_id!(JobId);
+
+/// A move of an axis, all the setpoints of a move have the same identifier, so that the io board can tell the first
+/// setpoint of a move from a setpoint of the previous move.
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Schema,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[serde(transparent)]
+pub struct MoveId(u32);
+
+impl MoveId {
+    pub const fn new(id: u32) -> Self {
+        Self(id)
+    }
+}
+
+impl Display for MoveId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "M{}", self.0)
+    }
+}
@@ -0,0 +1,40 @@
+use alloc::collections::BTreeMap;
+use alloc::string::ToString;
+
+use super::{BoardId, CameraId, FeederId, MoveId};
+
+#[test]
+pub fn serialized_as_the_wrapped_value() {
+    // expect
+    assert_eq!(
+        postcard::to_allocvec(&CameraId::new(3)).unwrap(),
+        postcard::to_allocvec(&3_u8).unwrap()
+    );
+    assert_eq!(
+        postcard::to_allocvec(&BoardId::new(300)).unwrap(),
+        postcard::to_allocvec(&300_u32).unwrap()
+    );
+    assert_eq!(
+        postcard::to_allocvec(&FeederId::new("F1")).unwrap(),
+        postcard::to_allocvec(&"F1").unwrap()
+    );
+}
+
+#[test]
+pub fn map_looked_up_by_name() {
+    // given
+    let counts = BTreeMap::from([(FeederId::new("F1"), 10), (FeederId::new("F2"), 20)]);
+
+    // expect
+    assert_eq!(counts.get("F2"), Some(&20));
+    assert_eq!(counts.get("F3"), None);
+}
+
+#[test]
+pub fn displayed_for_logs() {
+    // expect
+    assert_eq!(CameraId::new(1).to_string(), "C001");
+    assert_eq!(FeederId::new("F1").to_string(), "F1");
+    assert_eq!(BoardId::new(2).to_string(), "2");
+    assert_eq!(MoveId::new(7).to_string(), "M7");
+}
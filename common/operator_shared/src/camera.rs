@@ -1,23 +1,47 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
 use alloc::vec::Vec;
-use core::fmt::Display;
-use core::ops::Deref;
 
+use machine_ids::CameraId;
 use postcard_schema::Schema;
 use serde::{Deserialize, Serialize};
 
 use crate::commands::CommandArg;
 use crate::common::TimeStampUTC;
 
+/// A chunk of a camera frame, see [`frame_assembly`](crate::frame_assembly).
+///
+/// The bytes of an image chunk are borrowed, from the jpeg of the frame when sending, and from the received message
+/// when decoding, so that a chunk is only copied once, into the frame being reassembled.
 #[derive(Serialize, Deserialize, Schema, Clone, Debug)]
-pub struct CameraFrameChunk {
+pub struct CameraFrameChunk<'a> {
     pub frame_number: u64,
-    pub kind: CameraFrameChunkKind,
+    #[serde(borrow)]
+    pub kind: CameraFrameChunkKind<'a>,
+}
+
+impl CameraFrameChunk<'_> {
+    /// Copies the bytes of an image chunk, if borrowed, e.g. to keep a chunk after the received message is released.
+    pub fn into_owned(self) -> CameraFrameChunk<'static> {
+        CameraFrameChunk {
+            frame_number: self.frame_number,
+            kind: match self.kind {
+                CameraFrameChunkKind::Meta(meta) => CameraFrameChunkKind::Meta(meta),
+                CameraFrameChunkKind::ImageChunk(image_chunk) => {
+                    CameraFrameChunkKind::ImageChunk(CameraFrameImageChunk {
+                        chunk_index: image_chunk.chunk_index,
+                        bytes: Cow::Owned(image_chunk.bytes.into_owned()),
+                    })
+                }
+            },
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Schema, Clone, Debug)]
-pub enum CameraFrameChunkKind {
+pub enum CameraFrameChunkKind<'a> {
     Meta(CameraFrameMeta),
-    ImageChunk(CameraFrameImageChunk),
+    ImageChunk(#[serde(borrow)] CameraFrameImageChunk<'a>),
 }
 
 #[derive(Serialize, Deserialize, Schema, Clone, Debug)]
@@ -25,12 +49,23 @@ pub struct CameraFrameMeta {
     pub total_chunks: u32,
     pub frame_timestamp: TimeStampUTC,
     pub total_bytes: u32,
+    /// `None` when the server has no position telemetry for the time of the frame
+    pub head_position: Option<HeadPosition>,
+}
+
+/// The X and Y position of the head when a frame was captured, in machine coordinates, in millimeters.
+#[derive(Serialize, Deserialize, Schema, Clone, Copy, Debug, PartialEq)]
+pub struct HeadPosition {
+    pub x: f64,
+    pub y: f64,
 }
 
 #[derive(Serialize, Deserialize, Schema, Clone, Debug)]
-pub struct CameraFrameImageChunk {
+pub struct CameraFrameImageChunk<'a> {
     pub chunk_index: u32,
-    pub bytes: Vec<u8>,
+    /// `Cow::Borrowed` when decoded
+    #[serde(borrow)]
+    pub bytes: Cow<'a, [u8]>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq)]
@@ -42,44 +77,63 @@ pub enum CameraCommand {
     // SetCameraProperties { properties: CameraProperties },
 }
 
-#[derive(
-    Debug,
-    Serialize,
-    Deserialize,
-    Schema,
-    Clone,
-    Copy,
-    PartialEq,
-    Hash,
-    Eq,
-    PartialOrd,
-    Ord
-)]
-pub struct CameraIdentifier(u8);
-
-impl CameraIdentifier {
-    pub fn new(id: u8) -> Self {
-        Self(id)
-    }
+/// Capture a single frame via the vision request queue, optionally pausing the preview stream meanwhile.
+///
+/// Served by its own endpoint, so that the operator commands are not held up while the frame is captured.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq)]
+pub struct CaptureRequest {
+    pub camera: CameraId,
+    pub pause_preview: bool,
+    /// When set the frame is saved as a snapshot, see [`crate::captures::CaptureKey`].
+    pub save: bool,
 }
 
-impl Display for CameraIdentifier {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "C{:03}", self.0)
-    }
+#[derive(Debug, Serialize, Deserialize, Schema, Clone)]
+pub struct CapturedFrame {
+    pub frame_number: u64,
+    pub frame_timestamp: TimeStampUTC,
 }
-impl From<CameraIdentifier> for u8 {
-    fn from(value: CameraIdentifier) -> Self {
-        value.0
-    }
+
+pub type CaptureResponse = Result<CapturedFrame, CameraCommandError>;
+
+/// Camera metadata, so that the operator UI doesn't need to be configured with the cameras of the machine.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq)]
+pub struct CameraInfo {
+    pub identifier: CameraId,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub fps: f32,
+    pub mounting: CameraMounting,
+    pub layout: CameraLayoutHint,
+    pub calibration: Option<CameraCalibration>,
 }
 
-impl Deref for CameraIdentifier {
-    type Target = u8;
+/// The scale of the image at the focal plane, used for measurements in the operator UI.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, Copy, PartialEq)]
+pub struct CameraCalibration {
+    pub mm_per_pixel_x: f32,
+    pub mm_per_pixel_y: f32,
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMounting {
+    /// Looking up, e.g. for inspecting picked parts.
+    Up,
+    /// Looking down, e.g. a head camera for fiducials and feeders.
+    Down,
+    Other,
+}
+
+/// How the operator UI should initially arrange the camera panel, the operator can re-arrange it afterwards.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, Copy, PartialEq, Eq)]
+pub enum CameraLayoutHint {
+    /// Shown in the main area.
+    Primary,
+    /// Shown in a window.
+    Secondary,
+    /// Available, but not shown.
+    Hidden,
 }
 
 #[derive(Debug, Serialize, Deserialize, Schema, Clone)]
@@ -101,6 +155,7 @@ pub enum CameraCommandErrorCode {
     InvalidIdentifier = 0,
     Busy = 1,
     NotStreaming = 2,
+    CaptureFailed = 3,
 }
 
 impl CameraCommandError {
@@ -0,0 +1,90 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use machine_ids::CameraId;
+use postcard_schema::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::common::TimeStampUTC;
+
+/// Identifies a saved capture, captures are stored as `{job}/{placement}/{stage}.jpg`.
+///
+/// Each component may only contain ascii alphanumerics, `-`, `_` and `.`, and may not start with a `.`.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CaptureKey {
+    pub job: String,
+    pub placement: String,
+    pub stage: String,
+}
+
+impl CaptureKey {
+    /// Snapshots are not part of a job, they are grouped by camera instead of placement, see
+    /// [`crate::camera::CaptureRequest`].
+    pub fn snapshot(camera: CameraId, frame_timestamp: &chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            job: "snapshots".to_string(),
+            placement: camera.to_string(),
+            stage: frame_timestamp
+                .format("%Y%m%dT%H%M%S%.3fZ")
+                .to_string(),
+        }
+    }
+}
+
+impl Display for CaptureKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}/{}/{}", self.job, self.placement, self.stage)
+    }
+}
+
+/// Limits the size of the annotations of a capture, so that they fit in a single operator response.
+pub const MAX_CAPTURE_ANNOTATIONS: usize = 32;
+
+/// A vision annotation recorded with a capture, e.g. a detected feature or a measurement, in image pixels.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq)]
+pub struct CaptureAnnotation {
+    pub shape: AnnotationShape,
+    pub label: Option<String>,
+    /// `false` for annotations of a failed check, e.g. a feature that was out of tolerance
+    pub passed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, Copy, PartialEq)]
+pub enum AnnotationShape {
+    Point { x: f32, y: f32 },
+    Line { x1: f32, y1: f32, x2: f32, y2: f32 },
+    Rectangle { x: f32, y: f32, width: f32, height: f32 },
+    Circle { x: f32, y: f32, radius: f32 },
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema, Clone)]
+pub struct CaptureEntry {
+    pub key: CaptureKey,
+    pub total_bytes: u32,
+    pub saved_at: TimeStampUTC,
+}
+
+/// The capture list is paged, since it doesn't fit in a single response, newest first.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone)]
+pub struct CaptureListPage {
+    /// the number of captures in the list, not in the page
+    pub total: u32,
+    pub entries: Vec<CaptureEntry>,
+}
+
+/// Captures are fetched in chunks, since they don't fit in a single response.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq)]
+pub struct CaptureChunk {
+    pub total_bytes: u32,
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureError {
+    InvalidKey,
+    NotFound,
+    /// Details are only logged by the server.
+    Storage,
+}
@@ -1,23 +1,200 @@
 use alloc::string::String;
+use alloc::vec::Vec;
 
 use ergot::traits::Schema;
+use machine_ids::{CameraId, FeederId};
 use serde::{Deserialize, Serialize};
 
-use crate::camera::{CameraCommand, CameraCommandError, CameraIdentifier, CameraStreamerCommandResult};
+use crate::camera::{CameraCommand, CameraCommandError, CameraInfo, CameraStreamerCommandResult};
+use crate::captures::{CaptureAnnotation, CaptureChunk, CaptureError, CaptureKey, CaptureListPage};
+use crate::config::{ConfigChange, ConfigError};
+use crate::diagnostics::{LogLevel, LogLevelError, LogLevels, TopicTapError};
+use crate::estop::EStopError;
+use crate::feeders::{FeederError, TapeOrientation};
+use crate::geometry::MachineGeometry;
+use crate::homing::HomingError;
+use crate::job::{
+    EstimateError, InterventionError, InterventionResolution, JobCheckpoint, JobEstimate, ResumeChoice, ResumeError,
+};
+use crate::limits::{AxisLimit, LimitOverrideError, LimitOverrideRequest};
+use crate::readiness::{ReadinessCheck, ReadinessError, StartJobError};
+use crate::resonance::{ResonanceSweepError, ResonanceSweepSettings};
+#[cfg(feature = "machine-vision")]
+use crate::templates::{TemplateCapture, TemplateError, TemplateInfo, TemplateKind, TemplateListPage};
+use crate::test_area::{TestPattern, TestShotError, TestShotKind};
+#[cfg(feature = "machine-vision")]
+use crate::vision::{OrientationError, ScanError, ScanTarget};
 
 // TODO determine which is better: a) a single enum for all commands, or b) maintain many specific-endpoints?
 #[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub enum OperatorCommandRequest {
     Heartbeat(u64),
+    FetchMachineGeometry,
+    /// Home every axis, in the order configured on the server, the progress is published as a `HomingStatus`
+    HomeAll,
+    /// Mark a check as satisfied, the reason is logged by the server
+    OverrideReadinessCheck {
+        check: ReadinessCheck,
+        reason: String,
+    },
+    StartJob,
+    ResolveIntervention {
+        id: u32,
+        resolution: InterventionResolution,
+    },
+    FetchJobCheckpoint,
+    ConfirmResume(ResumeChoice),
+    /// Simulate the selected job, to estimate its run time before it is started, the placements are returned in pages
+    EstimateJob {
+        offset: u32,
+    },
+    /// e.g. after loading a reel, or after counting the parts
+    SetFeederCount {
+        feeder: FeederId,
+        count: u32,
+    },
+    /// Take a grid of test shots in the test area, e.g. to test dispensing or to verify the pickup
+    RunTestPattern {
+        pattern: TestPattern,
+        kind: TestShotKind,
+    },
+    /// Called after the operator has cleared the test area, the next pattern starts from the first row again
+    ClearTestArea,
+    /// Start or stop dumping the messages of the topics configured on the server, the tap stops by itself after the
+    /// configured duration
+    SetTopicTap(bool),
+    FetchLogLevels,
+    /// Change the level of the log of the server until it restarts, the default level if `module` is `None`
+    SetLogLevel {
+        module: Option<String>,
+        level: LogLevel,
+    },
+    /// Remove the level of the module, the level of its parent module applies again
+    ClearLogLevel {
+        module: String,
+    },
+    /// Apply the changes of the operator to the settings, all of them or none of them
+    ApplyConfig(Vec<ConfigChange>),
+    /// Disable a limit of an axis for maintenance, the limit is re-enabled once the duration has passed, the progress
+    /// is published as a `LimitOverrideStatus`
+    OverrideAxisLimit(LimitOverrideRequest),
+    /// Re-enable the limit before the duration has passed
+    ClearAxisLimitOverride {
+        axis: String,
+        limit: AxisLimit,
+    },
+    /// Shake an axis to find its resonance frequency, the result is published as a `ResonanceSweepStatus`
+    RunResonanceSweep(ResonanceSweepSettings),
+    /// Broadcast an e-stop to the io boards, the operator UI broadcasts it directly, without the round trip to the
+    /// server
+    EStop,
+    /// Broadcast the reset of the latched e-stop to the io boards
+    ResetEStop,
     #[cfg(feature = "machine-vision")]
-    CameraCommand(CameraIdentifier, CameraCommand),
+    CameraCommand(CameraId, CameraCommand),
+    #[cfg(feature = "machine-vision")]
+    ListCameras,
+    #[cfg(feature = "machine-vision")]
+    ListCaptures {
+        offset: u32,
+    },
+    #[cfg(feature = "machine-vision")]
+    FetchCapture {
+        key: CaptureKey,
+        offset: u32,
+    },
+    /// Fetch a chunk of the thumbnail of a capture, the response is a [`OperatorCommandResponse::CaptureChunk`]
+    #[cfg(feature = "machine-vision")]
+    FetchCaptureThumbnail {
+        key: CaptureKey,
+        offset: u32,
+    },
+    #[cfg(feature = "machine-vision")]
+    FetchCaptureAnnotations {
+        key: CaptureKey,
+    },
+    /// Scan a barcode or QR code using the down camera
+    #[cfg(feature = "machine-vision")]
+    ScanCode(ScanTarget),
+    /// Capture the first pocket of the feeder using the down camera, and verify the orientation of the part, e.g. after
+    /// loading a reel
+    #[cfg(feature = "machine-vision")]
+    VerifyFeederOrientation {
+        feeder: FeederId,
+    },
+    #[cfg(feature = "machine-vision")]
+    ListTemplates {
+        offset: u32,
+    },
+    /// Capture the image of a new template
+    #[cfg(feature = "machine-vision")]
+    CreateTemplate {
+        name: String,
+        kind: TemplateKind,
+        capture: TemplateCapture,
+    },
+    /// Rename a template or change its kind, the image is re-captured if `capture` is given
+    #[cfg(feature = "machine-vision")]
+    UpdateTemplate {
+        name: String,
+        new_name: String,
+        kind: TemplateKind,
+        capture: Option<TemplateCapture>,
+    },
+    #[cfg(feature = "machine-vision")]
+    DeleteTemplate {
+        name: String,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Schema, Clone)]
 pub enum OperatorCommandResponse {
     Acknowledged,
+    MachineGeometry(MachineGeometry),
+    /// Homing was started
+    HomeAll(Result<(), HomingError>),
+    ReadinessOverride(Result<(), ReadinessError>),
+    StartJob(Result<(), StartJobError>),
+    InterventionResolved(Result<(), InterventionError>),
+    JobCheckpoint(Option<JobCheckpoint>),
+    ResumeConfirmed(Result<(), ResumeError>),
+    JobEstimate(Result<JobEstimate, EstimateError>),
+    FeederCount(Result<(), FeederError>),
+    /// The number of shots of the started pattern
+    TestPatternStarted(Result<u32, TestShotError>),
+    TestAreaCleared(Result<(), TestShotError>),
+    TopicTap(Result<(), TopicTapError>),
+    /// The levels after the change, if any
+    LogLevels(Result<LogLevels, LogLevelError>),
+    ConfigApplied(Result<(), ConfigError>),
+    AxisLimitOverridden(Result<(), LimitOverrideError>),
+    AxisLimitOverrideCleared(Result<(), LimitOverrideError>),
+    /// The sweep was started
+    ResonanceSweepStarted(Result<(), ResonanceSweepError>),
+    /// The e-stop, or its reset, was broadcast
+    EStop(Result<(), EStopError>),
     #[cfg(feature = "machine-vision")]
     CameraCommandResult(Result<CameraStreamerCommandResult, CameraCommandError>),
+    #[cfg(feature = "machine-vision")]
+    Cameras(Vec<CameraInfo>),
+    #[cfg(feature = "machine-vision")]
+    Captures(Result<CaptureListPage, CaptureError>),
+    #[cfg(feature = "machine-vision")]
+    CaptureChunk(Result<CaptureChunk, CaptureError>),
+    #[cfg(feature = "machine-vision")]
+    CaptureAnnotations(Result<Vec<CaptureAnnotation>, CaptureError>),
+    /// The scanned code
+    #[cfg(feature = "machine-vision")]
+    CodeScanned(Result<String, ScanError>),
+    #[cfg(feature = "machine-vision")]
+    FeederOrientation(Result<TapeOrientation, OrientationError>),
+    #[cfg(feature = "machine-vision")]
+    Templates(Result<TemplateListPage, TemplateError>),
+    /// The created or updated template
+    #[cfg(feature = "machine-vision")]
+    TemplateSaved(Result<TemplateInfo, TemplateError>),
+    #[cfg(feature = "machine-vision")]
+    TemplateDeleted(Result<(), TemplateError>),
 }
 
 #[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq, Eq)]
@@ -0,0 +1,49 @@
+use alloc::vec::Vec;
+
+use ergot::traits::Schema;
+use machine_ids::FeederId;
+use serde::{Deserialize, Serialize};
+
+/// A setting that can be changed by the operator while the server is running.
+#[derive(Schema, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone)]
+pub enum ConfigField {
+    FeederCount(FeederId),
+    FeederLowStockThreshold(FeederId),
+}
+
+/// A change is only applied if the current value is still the value the operator changed, otherwise the operator
+/// would overwrite a change they have not seen, e.g. the count of a feeder that was picked from.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct ConfigChange {
+    pub field: ConfigField,
+    pub old: u32,
+    pub new: u32,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum ConfigFieldError {
+    UnknownFeeder,
+    /// the field was changed more than once in the transaction
+    Duplicate,
+    OutOfRange {
+        max: u32,
+    },
+    /// the value was changed since the operator started editing it
+    Changed {
+        current: u32,
+    },
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct FieldError {
+    pub field: ConfigField,
+    pub error: ConfigFieldError,
+}
+
+/// A transaction is applied in full or not at all.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum ConfigError {
+    NoChanges,
+    /// the errors of every change that failed validation, none of the changes were applied
+    Invalid(Vec<FieldError>),
+}
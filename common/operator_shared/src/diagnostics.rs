@@ -0,0 +1,127 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use ergot::traits::Schema;
+use machine_ids::CameraId;
+use serde::{Deserialize, Serialize};
+
+/// Upper bounds of the latency histogram buckets, in microseconds, the last bucket has no upper bound.
+pub const LATENCY_BUCKET_LIMITS_US: [u32; 7] = [500, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000];
+
+pub const LATENCY_BUCKET_COUNT: usize = LATENCY_BUCKET_LIMITS_US.len() + 1;
+
+/// Round-trip latency of the server to io board command path, over a rolling window of probes.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct CommandLatencyReport {
+    /// number of probes in the window that were acknowledged
+    pub samples: u32,
+    /// number of probes in the window that timed out
+    pub lost: u32,
+    /// all latencies are in microseconds, 0 when there are no samples
+    pub p50_us: u32,
+    pub p99_us: u32,
+    pub max_us: u32,
+    /// see [`LATENCY_BUCKET_LIMITS_US`]
+    pub buckets: [u32; LATENCY_BUCKET_COUNT],
+    /// the alarm is raised when the p99 latency exceeds this limit
+    pub p99_limit_us: u32,
+    pub alarm: bool,
+}
+
+/// Memory used by the camera frames in flight on the server, e.g. frames being sent to a lagging operator UI.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CameraMemoryReport {
+    pub cameras: Vec<CameraMemoryUsage>,
+    /// all sizes are in bytes
+    pub total_bytes: u64,
+    pub total_limit_bytes: u64,
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct CameraMemoryUsage {
+    pub camera: CameraId,
+    pub bytes: u64,
+    pub limit_bytes: u64,
+    /// preview frames that were not sent since the limits were reached, since the server started
+    pub dropped_frames: u64,
+    /// preview frames that were aborted while being sent, to make room for vision frames, since the server started
+    pub evicted_frames: u64,
+}
+
+/// The nodes reachable through the router of the server, by interface, for troubleshooting a node that doesn't
+/// respond, e.g. an io board.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct RouterReport {
+    pub interfaces: Vec<RouterInterfaceReport>,
+}
+
+/// An interface of the router, e.g. the UDP link to the io boards, each interface is a network of its own.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct RouterInterfaceReport {
+    pub network_id: u16,
+    pub nodes: Vec<RouterNodeReport>,
+    /// the pings answered by the nodes of the interface over the last interval, and those that were lost
+    pub answered: u32,
+    pub lost: u32,
+}
+
+/// All counters are since the node was first discovered.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct RouterNodeReport {
+    pub node_id: u8,
+    /// of the last ping, in microseconds, `None` if it was lost
+    pub rtt_us: Option<u32>,
+    /// the highest of the answered pings, in microseconds
+    pub max_rtt_us: u32,
+    pub pings: u64,
+    pub lost: u64,
+    /// the pings lost since the last answered ping, a node that stopped responding has a growing count
+    pub lost_in_a_row: u32,
+}
+
+/// Why the topic tap could not be started, see `OperatorCommandRequest::SetTopicTap`.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum TopicTapError {
+    /// No topic patterns are configured on the server
+    NotConfigured,
+    InvalidPattern(String),
+    /// None of the topics of the server match the configured patterns
+    NoMatchingTopics,
+    /// A tap is already running, it has to be stopped first
+    Running,
+}
+
+/// The levels of the log of the server, from the least to the most verbose.
+#[derive(Schema, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Copy)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// The level of a module of the server and its sub-modules, e.g. `server_cli::motion`.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct ModuleLogLevel {
+    pub module: String,
+    pub level: LogLevel,
+}
+
+/// The levels of the log of the server, the level of the longest matching module applies, otherwise the default.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct LogLevels {
+    pub default: LogLevel,
+    /// ordered by module
+    pub modules: Vec<ModuleLogLevel>,
+}
+
+/// Why a log level could not be changed, see `OperatorCommandRequest::SetLogLevel`.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum LogLevelError {
+    /// The module is not a module path, e.g. `server_cli::motion`
+    InvalidModule(String),
+    /// The module has no level of its own
+    UnknownModule(String),
+}
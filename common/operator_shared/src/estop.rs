@@ -0,0 +1,12 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Why the e-stop, or its reset, was not broadcast to the io boards, see `OperatorCommandRequest::EStop`.
+///
+/// The io boards report the latched e-stop in their safety status, a reset that was broadcast can still be refused by
+/// an io board, e.g. while a safety input with an e-stop policy is tripped.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum EStopError {
+    /// The broadcast could not be queued, e.g. the interface to the io boards is full, it should be retried
+    NotSent,
+}
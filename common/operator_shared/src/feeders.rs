@@ -0,0 +1,82 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use ergot::traits::Schema;
+use machine_ids::FeederId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum Stock {
+    Ok,
+    /// at or below the low-stock threshold of the feeder
+    Low,
+    Out,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct FeederStatus {
+    pub name: FeederId,
+    /// the remaining parts
+    pub count: u32,
+    pub low_stock_threshold: u32,
+    pub stock: Stock,
+    /// the ID of the loaded reel, if it has been scanned
+    pub reel: Option<String>,
+    /// `None` if the part of the feeder has no polarity mark, there is nothing to verify
+    pub orientation: Option<TapeOrientation>,
+    /// the slot of the feeder bank a smart feeder is inserted into, `None` for other feeders
+    pub slot: Option<u8>,
+}
+
+/// The orientation of the parts in the tape, verified by the polarity mark of the part in the first pocket.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum TapeOrientation {
+    /// not verified since the reel was loaded
+    Unverified,
+    Correct,
+    /// the polarity mark is not where the part definition has it, e.g. the tape was loaded reversed, parts are not
+    /// picked from the feeder until it is verified again
+    Reversed,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct FeedersStatus {
+    pub feeders: Vec<FeederStatus>,
+}
+
+/// Raised when the stock of a feeder changes to low or out, when reversed tape is found, or when a smart feeder is
+/// inserted or removed.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum FeederEvent {
+    LowStock {
+        feeder: FeederId,
+        count: u32,
+    },
+    OutOfStock {
+        feeder: FeederId,
+    },
+    ReversedTape {
+        feeder: FeederId,
+    },
+    Inserted {
+        feeder: FeederId,
+        slot: u8,
+    },
+    Removed {
+        feeder: FeederId,
+        slot: u8,
+    },
+    /// a smart feeder whose identity is not in the configuration of any feeder
+    UnknownInserted {
+        slot: u8,
+        identity: u64,
+    },
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum FeederError {
+    UnknownFeeder,
+    OutOfStock,
+    /// see [`TapeOrientation::Reversed`]
+    ReversedTape,
+}
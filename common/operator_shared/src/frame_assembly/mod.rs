@@ -0,0 +1,255 @@
+//! Splitting camera frames into chunks that fit in a network frame, and reassembling them, see [`frame_chunks`] and
+//! [`FrameAssembler`].
+//!
+//! The chunks are sent without retries, the chunks of a frame may arrive in any order, more than once, or not at all,
+//! a frame that is missing a chunk is discarded, the next frame replaces it.
+//!
+//! The image chunks borrow the bytes of the frame, and the received chunks borrow the bytes of the received message,
+//! the bytes of a received chunk are copied once, into the buffer of its frame, when it's inserted into the
+//! [`FrameAssembler`]. The buffer is the assembled frame, unless the chunks arrived out of order, they are then copied
+//! again to put them in order.
+
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::camera::{CameraFrameChunk, CameraFrameChunkKind, CameraFrameImageChunk, CameraFrameMeta, HeadPosition};
+use crate::common::TimeStampUTC;
+
+#[cfg(test)]
+mod tests;
+
+/// Splits the jpeg bytes of a frame into the meta chunk, which is sent first, and the image chunks, which borrow the
+/// jpeg bytes.
+///
+/// Panics if `chunk_size` is 0.
+pub fn frame_chunks<'a>(
+    frame_number: u64,
+    frame_timestamp: TimeStampUTC,
+    head_position: Option<HeadPosition>,
+    jpeg_bytes: &'a [u8],
+    chunk_size: usize,
+) -> (CameraFrameChunk<'static>, Vec<CameraFrameChunk<'a>>) {
+    let image_chunks: Vec<CameraFrameChunk<'a>> = jpeg_bytes
+        .chunks(chunk_size)
+        .enumerate()
+        .map(|(chunk_index, bytes)| CameraFrameChunk {
+            frame_number,
+            kind: CameraFrameChunkKind::ImageChunk(CameraFrameImageChunk {
+                chunk_index: chunk_index as u32,
+                bytes: Cow::Borrowed(bytes),
+            }),
+        })
+        .collect();
+
+    let meta = CameraFrameChunk {
+        frame_number,
+        kind: CameraFrameChunkKind::Meta(CameraFrameMeta {
+            total_chunks: image_chunks.len() as u32,
+            frame_timestamp,
+            total_bytes: jpeg_bytes.len() as u32,
+            head_position,
+        }),
+    };
+
+    (meta, image_chunks)
+}
+
+/// A frame reassembled by a [`FrameAssembler`].
+#[derive(Clone, Debug)]
+pub struct AssembledFrame {
+    pub frame_number: u64,
+    pub frame_timestamp: TimeStampUTC,
+    pub head_position: Option<HeadPosition>,
+    pub jpeg_bytes: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AssemblyStats {
+    pub assembled: u64,
+    /// frames that were discarded before all their chunks were received
+    pub incomplete: u64,
+    /// frames with chunks that don't match their meta chunk, or that are too large
+    pub invalid: u64,
+    /// chunks of frames that were already assembled or discarded
+    pub late_chunks: u64,
+}
+
+#[derive(Default)]
+struct PendingFrame {
+    meta: Option<CameraFrameMeta>,
+    /// the bytes of the received chunks, in the order they were received
+    bytes: Vec<u8>,
+    /// the range of each received chunk in `bytes`, by chunk index
+    chunks: BTreeMap<u32, Range<usize>>,
+}
+
+impl PendingFrame {
+    /// The chunks in `bytes` are in the order of their indexes.
+    fn in_order(&self) -> bool {
+        self.chunks
+            .values()
+            .try_fold(0, |end, range| (range.start == end).then_some(range.end))
+            .is_some()
+    }
+}
+
+/// Reassembles frames from their chunks.
+///
+/// Frames are numbered in the order they are captured, a frame is not returned once a newer frame has been returned,
+/// and the incomplete frames older than a returned frame are discarded.  Since there is always a newer frame, there
+/// are no timeouts, at most `max_pending` incomplete frames are kept.
+///
+/// Chunks come from the network, a chunk that doesn't match the other chunks of its frame invalidates the frame,
+/// and frames larger than `max_frame_bytes` are rejected, so that bad chunks can't use up memory.
+pub struct FrameAssembler {
+    pending: BTreeMap<u64, PendingFrame>,
+    max_pending: usize,
+    max_frame_bytes: u32,
+    last_assembled: Option<u64>,
+    stats: AssemblyStats,
+}
+
+impl FrameAssembler {
+    pub fn new(max_pending: usize, max_frame_bytes: u32) -> Self {
+        Self {
+            pending: BTreeMap::new(),
+            max_pending,
+            max_frame_bytes,
+            last_assembled: None,
+            stats: AssemblyStats::default(),
+        }
+    }
+
+    /// Returns the frame if the chunk completed it, the bytes of an image chunk are copied into the buffer of its frame.
+    pub fn insert(&mut self, chunk: CameraFrameChunk<'_>) -> Option<AssembledFrame> {
+        let frame_number = chunk.frame_number;
+        if self
+            .last_assembled
+            .is_some_and(|last| frame_number <= last)
+        {
+            self.stats.late_chunks += 1;
+            return None;
+        }
+
+        let max_frame_bytes = self.max_frame_bytes as usize;
+        let frame = self
+            .pending
+            .entry(frame_number)
+            .or_default();
+        let valid = match chunk.kind {
+            CameraFrameChunkKind::Meta(meta) => {
+                let consistent = match &frame.meta {
+                    // a duplicate
+                    Some(existing) => {
+                        existing.total_chunks == meta.total_chunks && existing.total_bytes == meta.total_bytes
+                    }
+                    None => {
+                        // every chunk has at least one byte
+                        meta.total_chunks <= meta.total_bytes
+                            && (meta.total_bytes as usize) <= max_frame_bytes
+                            && frame.bytes.len() <= meta.total_bytes as usize
+                            && frame
+                                .chunks
+                                .keys()
+                                .all(|chunk_index| *chunk_index < meta.total_chunks)
+                    }
+                };
+                if consistent && frame.meta.is_none() {
+                    // the buffer isn't reallocated, which would copy the chunks again
+                    frame
+                        .bytes
+                        .reserve_exact(meta.total_bytes as usize - frame.bytes.len());
+                }
+                frame.meta.get_or_insert(meta);
+                consistent
+            }
+            CameraFrameChunkKind::ImageChunk(image_chunk) => {
+                let limit = frame
+                    .meta
+                    .as_ref()
+                    .map_or(max_frame_bytes, |meta| meta.total_bytes as usize);
+                let in_range = frame
+                    .meta
+                    .as_ref()
+                    .is_none_or(|meta| image_chunk.chunk_index < meta.total_chunks);
+                let duplicate = frame
+                    .chunks
+                    .contains_key(&image_chunk.chunk_index);
+                let empty = image_chunk.bytes.is_empty();
+                let fits = duplicate || frame.bytes.len() + image_chunk.bytes.len() <= limit;
+                if in_range && !duplicate && fits {
+                    let start = frame.bytes.len();
+                    frame
+                        .bytes
+                        .extend_from_slice(&image_chunk.bytes);
+                    frame
+                        .chunks
+                        .insert(image_chunk.chunk_index, start..frame.bytes.len());
+                }
+                in_range && !empty && fits
+            }
+        };
+        if !valid {
+            self.pending.remove(&frame_number);
+            self.stats.invalid += 1;
+            return None;
+        }
+
+        let complete = frame
+            .meta
+            .as_ref()
+            .is_some_and(|meta| frame.chunks.len() == meta.total_chunks as usize);
+        if !complete {
+            while self.pending.len() > self.max_pending {
+                self.pending.pop_first();
+                self.stats.incomplete += 1;
+            }
+            return None;
+        }
+
+        let frame = self.pending.remove(&frame_number)?;
+        // the newer frames are still pending
+        let newer = self.pending.split_off(&frame_number);
+        self.stats.incomplete += self.pending.len() as u64;
+        self.pending = newer;
+        self.last_assembled = Some(frame_number);
+
+        let meta = frame.meta?;
+        if frame.bytes.len() != meta.total_bytes as usize {
+            self.stats.invalid += 1;
+            return None;
+        }
+        let jpeg_bytes = match frame.in_order() {
+            true => frame.bytes,
+            false => {
+                let mut jpeg_bytes: Vec<u8> = Vec::with_capacity(frame.bytes.len());
+                for range in frame.chunks.values() {
+                    jpeg_bytes.extend_from_slice(&frame.bytes[range.clone()]);
+                }
+                jpeg_bytes
+            }
+        };
+        self.stats.assembled += 1;
+
+        Some(AssembledFrame {
+            frame_number,
+            frame_timestamp: meta.frame_timestamp,
+            head_position: meta.head_position,
+            jpeg_bytes,
+        })
+    }
+
+    /// Discards the pending frames and forgets the last assembled frame, e.g. when a stream is restarted, the frame
+    /// numbers of a restarted camera start again from 0.
+    pub fn reset(&mut self) {
+        self.stats.incomplete += self.pending.len() as u64;
+        self.pending.clear();
+        self.last_assembled = None;
+    }
+
+    pub fn stats(&self) -> AssemblyStats {
+        self.stats
+    }
+}
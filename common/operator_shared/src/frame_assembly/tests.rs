@@ -0,0 +1,314 @@
+use std::borrow::Cow;
+use std::vec;
+use std::vec::Vec;
+
+use super::{AssembledFrame, FrameAssembler, frame_chunks};
+use crate::camera::{CameraFrameChunk, CameraFrameChunkKind, CameraFrameImageChunk, CameraFrameMeta, HeadPosition};
+use crate::common::TimeStampUTC;
+
+const MAX_FRAME_BYTES: u32 = 1024;
+
+fn timestamp() -> TimeStampUTC {
+    chrono::DateTime::from_timestamp_millis(1_700_000_000_000)
+        .unwrap()
+        .into()
+}
+
+fn jpeg_bytes(frame_number: u64) -> Vec<u8> {
+    (0..250_u64)
+        .map(|index| (index + frame_number) as u8)
+        .collect()
+}
+
+/// The meta chunk, and the image chunks of 100, 100 and 50 bytes.
+fn chunks(frame_number: u64) -> (CameraFrameChunk<'static>, Vec<CameraFrameChunk<'static>>) {
+    let jpeg_bytes = jpeg_bytes(frame_number);
+    let (meta, image_chunks) = frame_chunks(frame_number, timestamp(), None, &jpeg_bytes, 100);
+    let image_chunks = image_chunks
+        .into_iter()
+        .map(CameraFrameChunk::into_owned)
+        .collect();
+    (meta, image_chunks)
+}
+
+fn insert_all(assembler: &mut FrameAssembler, chunks: Vec<CameraFrameChunk>) -> Vec<AssembledFrame> {
+    chunks
+        .into_iter()
+        .filter_map(|chunk| assembler.insert(chunk))
+        .collect()
+}
+
+fn image_chunk(frame_number: u64, chunk_index: u32, bytes: Vec<u8>) -> CameraFrameChunk<'static> {
+    CameraFrameChunk {
+        frame_number,
+        kind: CameraFrameChunkKind::ImageChunk(CameraFrameImageChunk {
+            chunk_index,
+            bytes: Cow::Owned(bytes),
+        }),
+    }
+}
+
+fn meta_chunk(frame_number: u64, total_chunks: u32, total_bytes: u32) -> CameraFrameChunk<'static> {
+    CameraFrameChunk {
+        frame_number,
+        kind: CameraFrameChunkKind::Meta(CameraFrameMeta {
+            total_chunks,
+            frame_timestamp: timestamp(),
+            total_bytes,
+            head_position: None,
+        }),
+    }
+}
+
+#[test]
+pub fn chunks_in_order_assemble_the_frame() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let (meta, image_chunks) = chunks(1);
+
+    // when
+    let frames = insert_all(&mut assembler, [vec![meta], image_chunks].concat());
+
+    // then
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].frame_number, 1);
+    assert_eq!(frames[0].jpeg_bytes, jpeg_bytes(1));
+    assert_eq!(assembler.stats().assembled, 1);
+}
+
+#[test]
+pub fn head_position_is_carried_to_the_frame() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let head_position = HeadPosition {
+        x: 12.5,
+        y: -3.25,
+    };
+    let jpeg_bytes = jpeg_bytes(1);
+    let (meta, image_chunks) = frame_chunks(1, timestamp(), Some(head_position), &jpeg_bytes, 100);
+
+    // when
+    let frames = insert_all(&mut assembler, [vec![meta], image_chunks].concat());
+
+    // then
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].head_position, Some(head_position));
+}
+
+#[test]
+pub fn chunks_out_of_order_assemble_the_frame() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let (meta, mut image_chunks) = chunks(1);
+    image_chunks.reverse();
+
+    // when
+    // the meta chunk is received last
+    let frames = insert_all(&mut assembler, [image_chunks, vec![meta]].concat());
+
+    // then
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].jpeg_bytes, jpeg_bytes(1));
+}
+
+#[test]
+pub fn duplicate_chunks_are_ignored() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let (meta, image_chunks) = chunks(1);
+    let duplicated = vec![
+        meta.clone(),
+        image_chunks[0].clone(),
+        image_chunks[0].clone(),
+        meta,
+        image_chunks[1].clone(),
+        image_chunks[2].clone(),
+        image_chunks[2].clone(),
+    ];
+
+    // when
+    let frames = insert_all(&mut assembler, duplicated);
+
+    // then
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].jpeg_bytes, jpeg_bytes(1));
+    assert_eq!(assembler.stats().late_chunks, 1);
+    assert_eq!(assembler.stats().invalid, 0);
+}
+
+#[test]
+pub fn frame_with_missing_chunk_is_discarded_when_a_newer_frame_completes() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let (meta_1, mut image_chunks_1) = chunks(1);
+    let missing = image_chunks_1.remove(1);
+    let (meta_2, image_chunks_2) = chunks(2);
+
+    // when
+    let frames = insert_all(
+        &mut assembler,
+        [vec![meta_1], image_chunks_1, vec![meta_2], image_chunks_2].concat(),
+    );
+    // too late, frame 2 was already assembled
+    let late = assembler.insert(missing);
+
+    // then
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].frame_number, 2);
+    assert!(late.is_none());
+    assert_eq!(assembler.stats().incomplete, 1);
+    assert_eq!(assembler.stats().late_chunks, 1);
+}
+
+#[test]
+pub fn incomplete_frames_are_limited() {
+    // given
+    let mut assembler = FrameAssembler::new(2, MAX_FRAME_BYTES);
+
+    // when
+    for frame_number in 1..=5 {
+        let (meta, _image_chunks) = chunks(frame_number);
+        assert!(assembler.insert(meta).is_none());
+    }
+
+    // then
+    assert_eq!(assembler.stats().incomplete, 3);
+}
+
+#[test]
+pub fn empty_frame_is_assembled_from_its_meta_chunk() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let (meta, image_chunks) = frame_chunks(1, timestamp(), None, &[], 100);
+
+    // when
+    let frame = assembler.insert(meta);
+
+    // then
+    assert!(image_chunks.is_empty());
+    assert!(frame.unwrap().jpeg_bytes.is_empty());
+}
+
+#[test]
+pub fn frame_larger_than_the_limit_is_rejected() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+
+    // when
+    let frame = assembler.insert(meta_chunk(1, 2, MAX_FRAME_BYTES + 1));
+
+    // then
+    assert!(frame.is_none());
+    assert_eq!(assembler.stats().invalid, 1);
+}
+
+#[test]
+pub fn chunks_larger_than_the_limit_are_rejected_before_the_meta_chunk() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let half = vec![0; MAX_FRAME_BYTES as usize / 2 + 1];
+
+    // when
+    let frames = insert_all(&mut assembler, vec![
+        image_chunk(1, 0, half.clone()),
+        image_chunk(1, 1, half),
+    ]);
+
+    // then
+    assert!(frames.is_empty());
+    assert_eq!(assembler.stats().invalid, 1);
+}
+
+#[test]
+pub fn chunk_index_beyond_the_meta_chunk_invalidates_the_frame() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let (meta, image_chunks) = chunks(1);
+
+    // when
+    let frames = insert_all(
+        &mut assembler,
+        [vec![image_chunk(1, 3, vec![0; 10]), meta], image_chunks].concat(),
+    );
+
+    // then
+    assert!(frames.is_empty());
+    assert_eq!(assembler.stats().invalid, 1);
+}
+
+#[test]
+pub fn conflicting_meta_chunks_invalidate_the_frame() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let (meta, image_chunks) = chunks(1);
+
+    // when
+    let frames = insert_all(
+        &mut assembler,
+        [vec![meta, meta_chunk(1, 3, 300)], image_chunks].concat(),
+    );
+
+    // then
+    assert!(frames.is_empty());
+    assert_eq!(assembler.stats().invalid, 1);
+}
+
+#[test]
+pub fn frame_with_wrong_size_is_rejected() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+
+    // when
+    let frames = insert_all(&mut assembler, vec![
+        meta_chunk(1, 2, 200),
+        image_chunk(1, 0, vec![0; 100]),
+        image_chunk(1, 1, vec![0; 50]),
+    ]);
+
+    // then
+    assert!(frames.is_empty());
+    assert_eq!(assembler.stats().invalid, 1);
+}
+
+#[test]
+pub fn reset_accepts_frame_numbers_of_a_restarted_stream() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let (meta, image_chunks) = chunks(10);
+    insert_all(&mut assembler, [vec![meta], image_chunks].concat());
+
+    // when
+    assembler.reset();
+    let (meta, image_chunks) = chunks(0);
+    let frames = insert_all(&mut assembler, [vec![meta], image_chunks].concat());
+
+    // then
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].frame_number, 0);
+}
+
+#[test]
+pub fn received_chunks_borrow_the_bytes_of_the_message() {
+    // given
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let (meta, image_chunks) = chunks(1);
+    let messages: Vec<Vec<u8>> = [vec![meta], image_chunks]
+        .concat()
+        .iter()
+        .map(|chunk| postcard::to_allocvec(chunk).unwrap())
+        .collect();
+
+    // when
+    let mut frames = vec![];
+    for message in &messages {
+        let chunk = postcard::from_bytes::<CameraFrameChunk>(message).unwrap();
+        if let CameraFrameChunkKind::ImageChunk(image_chunk) = &chunk.kind {
+            assert!(matches!(image_chunk.bytes, Cow::Borrowed(_)));
+        }
+        frames.extend(assembler.insert(chunk));
+    }
+
+    // then
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].jpeg_bytes, jpeg_bytes(1));
+}
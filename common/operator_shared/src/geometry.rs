@@ -0,0 +1,12 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// The measured geometry of the machine, applied between job coordinates and machine axes.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct MachineGeometry {
+    /// the angle of the Y axis from perpendicular to the X axis, positive when the Y axis leans towards +X, in degrees
+    pub skew_degrees: f32,
+    /// the scale corrections of the axes, 1.0 when uncorrected
+    pub scale_x: f32,
+    pub scale_y: f32,
+}
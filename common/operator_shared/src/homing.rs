@@ -0,0 +1,46 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum AxisHomingState {
+    NotHomed,
+    Homing,
+    Homed,
+    Failed,
+    /// not homed because an axis it depends on was not homed, e.g. X and Y are not moved if Z failed
+    Skipped,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct AxisHomingStatus {
+    /// the name of the axis in the homing configuration of the server
+    pub axis: String,
+    pub state: AxisHomingState,
+}
+
+/// Published whenever the state of an axis changes while homing, in the configured order of the axes.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct HomingStatus {
+    pub axes: Vec<AxisHomingStatus>,
+    /// `false` once every axis has been homed, has failed, or has been skipped
+    pub running: bool,
+}
+
+impl HomingStatus {
+    pub fn all_homed(&self) -> bool {
+        self.axes
+            .iter()
+            .all(|status| status.state == AxisHomingState::Homed)
+    }
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum HomingError {
+    /// there are no axes in the homing configuration of the server
+    NotConfigured,
+    /// a job, test shots, or homing are running
+    Running,
+}
@@ -0,0 +1,131 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use ergot::traits::Schema;
+use machine_ids::JobId;
+use serde::{Deserialize, Serialize};
+
+/// How the operator chose to continue after a placement failed.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum InterventionResolution {
+    /// attempt the same placement again
+    Retry,
+    /// leave the placement unplaced and continue with the next one
+    Skip,
+    /// stop the job
+    Abort,
+}
+
+/// The job is paused until the operator resolves the intervention.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct Intervention {
+    /// used to reject resolutions of an earlier intervention
+    pub id: u32,
+    pub job: JobId,
+    pub placement: String,
+    pub error: String,
+    /// the resolutions the operator can choose from
+    pub options: Vec<InterventionResolution>,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum JobEvent {
+    Started {
+        job: JobId,
+        placements: u32,
+        /// non-zero when the job was resumed from a checkpoint
+        placed: u32,
+        skipped: u32,
+        /// nothing is moved, the placements are only logged, see the job mode of the server
+        dry_run: bool,
+    },
+    Placed {
+        job: JobId,
+        placement: String,
+    },
+    /// published repeatedly until the intervention is resolved
+    InterventionRequired(Intervention),
+    InterventionResolved {
+        id: u32,
+        resolution: InterventionResolution,
+    },
+    Finished {
+        job: JobId,
+        placed: u32,
+        skipped: u32,
+    },
+    Aborted {
+        job: JobId,
+        placed: u32,
+        skipped: u32,
+    },
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum InterventionError {
+    /// the job is not waiting for an intervention
+    NoIntervention,
+    /// the intervention has already been resolved, the id of the current intervention is given
+    Stale(u32),
+}
+
+/// A run of the job that was interrupted, e.g. by a crash or power loss.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct JobCheckpoint {
+    pub job: JobId,
+    pub placements: u32,
+    pub placed: u32,
+    pub skipped: u32,
+    /// `None` until the operator has confirmed how to continue
+    pub confirmed: Option<ResumeChoice>,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum ResumeChoice {
+    /// continue with the placement after the last completed placement, the machine must be homed again first
+    Resume,
+    /// discard the checkpoint and start the job from the first placement
+    Restart,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum ResumeError {
+    NoCheckpoint,
+    /// the choice cannot be changed while the job is running
+    Running,
+}
+
+/// Small enough that a page of the placements of an estimate fits in a single operator response.
+pub const ESTIMATE_PAGE_SIZE: usize = 32;
+
+/// The run time of the selected job, estimated by simulating the job without the machine, before it is started.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct JobEstimate {
+    pub job: JobId,
+    /// in milliseconds
+    pub duration_ms: u64,
+    /// the placements of the job
+    pub total: u32,
+    /// in the order they are placed, a page of at most [`ESTIMATE_PAGE_SIZE`] from the requested offset
+    pub placements: Vec<PlacementEstimate>,
+}
+
+/// When a placement is placed, relative to the start of the job, all in milliseconds.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct PlacementEstimate {
+    pub placement: String,
+    pub start_ms: u64,
+    /// the move from the previous placement
+    pub move_ms: u64,
+    /// placing the part, or dispensing
+    pub operation_ms: u64,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum EstimateError {
+    NoJob,
+    /// not the name of a dispenser head, the placement is given
+    UnknownHead(String),
+    /// the move to the placement could not be planned, e.g. the motion limits are invalid, the placement is given
+    Planning(String),
+}
@@ -1,8 +1,47 @@
 #![no_std]
 extern crate alloc;
+#[cfg(test)]
+extern crate std;
 
 pub mod commands;
 
 pub mod camera;
 
+pub mod captures;
+
 pub mod common;
+
+pub mod config;
+
+pub mod diagnostics;
+
+pub mod estop;
+
+pub mod feeders;
+
+pub mod frame_assembly;
+
+pub mod geometry;
+
+pub mod homing;
+
+pub mod job;
+
+pub mod limits;
+
+pub mod maintenance;
+
+pub mod power;
+
+pub mod readiness;
+
+pub mod resonance;
+
+pub mod templates;
+
+pub mod test_area;
+
+pub mod vision;
+
+#[cfg(test)]
+mod tests;
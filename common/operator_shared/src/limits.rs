@@ -0,0 +1,62 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// A limit of an axis that an authorized operator can disable for a while, for maintenance.
+#[derive(Schema, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone, Copy)]
+pub enum AxisLimit {
+    /// X and Y moves are refused while Z is below the safe height, e.g. disabled to move a nozzle over a part during
+    /// calibration
+    SafeZGuard,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct LimitOverrideRequest {
+    /// the name of the axis in the homing configuration of the server
+    pub axis: String,
+    pub limit: AxisLimit,
+    /// one of the operators authorized in the maintenance configuration of the server
+    pub operator: String,
+    pub access_code: String,
+    /// logged by the server
+    pub reason: String,
+    /// the limit is re-enabled automatically after this
+    pub duration_s: u32,
+}
+
+/// A limit that is disabled, until `remaining_s` has passed or the override is cleared.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct LimitOverride {
+    pub axis: String,
+    pub limit: AxisLimit,
+    pub operator: String,
+    pub reason: String,
+    pub remaining_s: u32,
+}
+
+/// Published by the server when an override starts or ends, and every second while any override is active.
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct LimitOverrideStatus {
+    pub overrides: Vec<LimitOverride>,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum LimitOverrideError {
+    /// there are no authorized operators in the maintenance configuration of the server
+    NotConfigured,
+    /// unknown operator or wrong access code
+    Unauthorized,
+    /// an override must be given a reason, so it can be logged
+    MissingReason,
+    UnknownAxis,
+    /// the duration must be between 1s and the configured maximum
+    InvalidDuration {
+        max_s: u32,
+    },
+    AlreadyOverridden,
+    NotOverridden,
+    /// the io board of the axis did not accept the override, the limit is still enabled
+    NotApplied,
+}
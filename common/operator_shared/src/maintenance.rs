@@ -0,0 +1,27 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// An abnormal vacuum response of a nozzle, vacuum levels are in kPa below ambient pressure.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub enum ClogSymptom {
+    /// a part is present but the vacuum stayed below the expected pickup vacuum
+    PoorPickupVacuum { vacuum: f32 },
+    /// the vacuum was still above the expected release vacuum when the valve had been closed for the decay time
+    SlowDecay { vacuum: f32 },
+}
+
+/// Published by the server when the machine needs maintenance.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub enum MaintenanceEvent {
+    /// raised after several consecutive abnormal vacuum responses, the latest symptom is given
+    NozzleClogSuspected {
+        nozzle: u8,
+        symptom: ClogSymptom,
+    },
+    NozzleCleaned {
+        nozzle: u8,
+    },
+    NozzleCleaningFailed {
+        nozzle: u8,
+    },
+}
@@ -0,0 +1,12 @@
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// A reading of the power meter of the machine, published by the server at the configured interval.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct PowerReading {
+    pub volts: f32,
+    pub amps: f32,
+    pub watts: f32,
+    /// the energy used since the server started
+    pub energy_wh: f64,
+}
@@ -0,0 +1,76 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// A pre-run step that must be completed before a job is started.
+#[derive(Schema, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone, Copy)]
+pub enum ReadinessCheck {
+    Homed,
+    VacuumOk,
+    CamerasCalibrated,
+    FeedersVerified,
+    /// The io board passed its power-on self test
+    IoBoardReady,
+    /// No task of the server has failed repeatedly, see the server log for the task
+    ServerTasksRunning,
+    /// No temperature sensor of the io board is at the critical level
+    TemperaturesNormal,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum CheckState {
+    Passed,
+    Failed,
+    /// the server has no way to determine the state, the check must be overridden
+    Unknown,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct ReadinessCheckStatus {
+    pub check: ReadinessCheck,
+    pub state: CheckState,
+    /// the reason given by the operator, `None` if the check has not been overridden
+    pub override_reason: Option<String>,
+}
+
+impl ReadinessCheckStatus {
+    pub fn satisfied(&self) -> bool {
+        self.state == CheckState::Passed || self.override_reason.is_some()
+    }
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct ReadinessStatus {
+    pub checks: Vec<ReadinessCheckStatus>,
+}
+
+impl ReadinessStatus {
+    /// A job can be started when every check has passed or has been overridden.
+    pub fn ready(&self) -> bool {
+        self.checks
+            .iter()
+            .all(ReadinessCheckStatus::satisfied)
+    }
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum ReadinessError {
+    /// an override must be given a reason, so it can be logged
+    MissingReason,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum StartJobError {
+    /// the checks that have not passed and have not been overridden
+    NotReady(Vec<ReadinessCheck>),
+    /// no job was given to the server
+    NoJob,
+    /// a job is already running
+    Running,
+    /// the job was interrupted, the operator must confirm whether to resume or restart it
+    ResumeUnconfirmed,
+    /// the parts are placed by the head, but no io board follows the moves planned by the server
+    NoMotion,
+}
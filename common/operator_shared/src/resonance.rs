@@ -0,0 +1,61 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use ergot::traits::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Shakes an axis at each frequency of the sweep while the accelerometer of its io board records the response, to find
+/// the resonance frequency of the axis for its input shaper.
+///
+/// The axis is shaken around its position, it needs `amplitude` of free travel either side of it.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ResonanceSweepSettings {
+    /// the name of the axis in the homing configuration of the server
+    pub axis: String,
+    /// in Hz
+    pub start_frequency: f32,
+    /// in Hz
+    pub frequency_step: f32,
+    pub frequencies: u8,
+    /// in mm
+    pub amplitude: f32,
+    /// how long the axis is shaken at each frequency
+    pub duration_ms: u32,
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct FrequencyResponse {
+    /// in Hz
+    pub frequency: f32,
+    /// RMS acceleration, in g
+    pub response: f32,
+}
+
+/// Published by the server when a sweep has finished, or failed.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ResonanceSweepStatus {
+    pub axis: String,
+    pub result: Result<ResonanceSweepResult, ResonanceSweepError>,
+}
+
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ResonanceSweepResult {
+    /// the frequency with the largest response, in Hz
+    pub peak_frequency: f32,
+    pub responses: Vec<FrequencyResponse>,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum ResonanceSweepError {
+    UnknownAxis,
+    /// a job, test shots, homing or another sweep are running
+    Running,
+    /// no frequencies, too many, or a frequency or amplitude that isn't positive
+    InvalidSweep,
+    /// the io board of the axis follows the setpoints of the server, it can't sweep the axis
+    Unsupported,
+    /// the safety state does not allow motion, or the sweep was cancelled, e.g. by an e-stop
+    MotionNotAllowed,
+    /// the io board of the axis was not found, or didn't answer
+    NotAnswered,
+}
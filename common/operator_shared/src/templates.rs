@@ -0,0 +1,76 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use machine_ids::CameraId;
+use postcard_schema::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::common::TimeStampUTC;
+
+/// Small enough that a page of templates fits in a single operator response.
+pub const TEMPLATE_LIST_PAGE_SIZE: usize = 16;
+
+/// What a vision template is matched against.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TemplateKind {
+    /// a fiducial mark of a board
+    #[default]
+    Fiducial,
+    /// the appearance of a part, e.g. as seen by the up camera
+    Part,
+}
+
+/// The region of a camera frame that becomes the template, in image pixels.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, Copy, PartialEq, Eq)]
+pub struct TemplateCrop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The image of a template is cropped from a frame captured by the camera when the request is handled, not from the
+/// frame the operator was looking at, so the machine should be stationary.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, Copy, PartialEq, Eq)]
+pub struct TemplateCapture {
+    pub camera: CameraId,
+    pub crop: TemplateCrop,
+}
+
+/// A template in the parts library.
+///
+/// Names may only contain ascii alphanumerics, `-`, `_` and `.`, and may not start with a `.`.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone)]
+pub struct TemplateInfo {
+    pub name: String,
+    pub kind: TemplateKind,
+    /// the camera the image was captured with
+    pub camera: CameraId,
+    /// in pixels
+    pub width: u32,
+    /// in pixels
+    pub height: u32,
+    pub updated_at: TimeStampUTC,
+}
+
+/// The template list is paged, since it doesn't fit in a single response, ordered by name.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone)]
+pub struct TemplateListPage {
+    /// the number of templates in the list, not in the page
+    pub total: u32,
+    pub templates: Vec<TemplateInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateError {
+    InvalidName,
+    /// a template with the name already exists
+    AlreadyExists,
+    NotFound,
+    UnknownCamera,
+    CaptureFailed,
+    /// the crop is empty or extends beyond the captured frame
+    InvalidCrop,
+    /// Details are only logged by the server.
+    Storage,
+}
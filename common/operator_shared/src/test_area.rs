@@ -0,0 +1,60 @@
+use alloc::string::String;
+
+use ergot::traits::Schema;
+use machine_ids::FeederId;
+use serde::{Deserialize, Serialize};
+
+/// Bounds the number of shots a single pattern can take, e.g. a tiny pitch in a large test area.
+pub const TEST_PATTERN_SHOTS_MAX: u32 = 1000;
+
+/// A grid of test shots, laid out row by row from the first free row of the test area.
+#[derive(Schema, Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct TestPattern {
+    /// along X
+    pub columns: u32,
+    /// along Y
+    pub rows: u32,
+    /// between adjacent shots, in millimeters
+    pub pitch: f32,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum TestShotKind {
+    /// a dot of glue or paste from the dispenser head, no part is picked
+    Dispense { head: String },
+    /// a part is picked from the feeder and placed, to verify the pickup
+    Place { feeder: FeederId },
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum TestShotError {
+    /// there is no test area in the configuration of the machine
+    NotConfigured,
+    /// no rows or columns, a pitch that is not positive, or too many shots
+    InvalidPattern,
+    /// the pattern does not fit in the free part of the test area, the operator has to clear the area
+    AreaFull,
+    /// a job or other test shots are running
+    Running,
+    UnknownFeeder,
+    /// not the name of a dispenser head
+    UnknownHead,
+    /// the shots are placed by the head, but no io board follows the moves planned by the server
+    NoMotion,
+}
+
+#[derive(Schema, Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub enum TestShotEvent {
+    Started {
+        shots: u32,
+    },
+    /// `index` is in the order the shots are taken, row by row
+    Shot {
+        index: u32,
+        ok: bool,
+    },
+    Finished {
+        placed: u32,
+        failed: u32,
+    },
+}
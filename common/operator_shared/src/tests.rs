@@ -0,0 +1,211 @@
+//! Property tests of the encoding of the operator protocol, and of the reassembly of camera frames, frames received
+//! from the network are decoded by the server and the operator UI, malformed frames must be rejected, never panic.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::vec::Vec;
+
+use machine_ids::CameraId;
+use proptest::prelude::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::camera::{CameraFrameChunk, CameraFrameChunkKind, CameraFrameImageChunk, CameraFrameMeta, HeadPosition};
+use crate::captures::CaptureKey;
+use crate::commands::{OperatorCommandRequest, OperatorCommandResponse};
+use crate::common::TimeStampUTC;
+use crate::diagnostics::{CameraMemoryReport, CommandLatencyReport};
+use crate::feeders::{FeederEvent, FeedersStatus};
+use crate::frame_assembly::{FrameAssembler, frame_chunks};
+use crate::homing::HomingStatus;
+use crate::job::JobEvent;
+use crate::limits::LimitOverrideStatus;
+use crate::maintenance::MaintenanceEvent;
+use crate::readiness::ReadinessStatus;
+use crate::test_area::TestShotEvent;
+use crate::vision::VisionStatus;
+
+const MAX_FRAME_BYTES: u32 = 4096;
+
+/// Anything that decodes must encode again, and decode to the same value.
+fn decode<T: Serialize + DeserializeOwned>(bytes: &[u8]) {
+    if let Ok(value) = postcard::from_bytes::<T>(bytes) {
+        assert_round_trip(&value);
+    }
+}
+
+/// Compares the encodings, not the values, not every protocol type is `PartialEq`.
+fn assert_round_trip<T: Serialize + DeserializeOwned>(value: &T) {
+    let encoded = postcard::to_allocvec(value).unwrap();
+    let decoded = postcard::from_bytes::<T>(&encoded).unwrap();
+    assert_eq!(postcard::to_allocvec(&decoded).unwrap(), encoded);
+
+    // a truncated frame is an error, not a panic
+    for length in 0..encoded.len() {
+        let _ = postcard::from_bytes::<T>(&encoded[..length]);
+    }
+}
+
+/// The same as [`assert_round_trip`], for the chunks, which borrow the bytes they are decoded from.
+fn assert_chunk_round_trip(chunk: &CameraFrameChunk) {
+    let encoded = postcard::to_allocvec(chunk).unwrap();
+    let decoded = postcard::from_bytes::<CameraFrameChunk>(&encoded).unwrap();
+    assert_eq!(postcard::to_allocvec(&decoded).unwrap(), encoded);
+
+    for length in 0..encoded.len() {
+        let _ = postcard::from_bytes::<CameraFrameChunk>(&encoded[..length]);
+    }
+}
+
+fn decode_all(bytes: &[u8]) {
+    if let Ok(chunk) = postcard::from_bytes::<CameraFrameChunk>(bytes) {
+        assert_chunk_round_trip(&chunk);
+    }
+    decode::<CommandLatencyReport>(bytes);
+    decode::<CameraMemoryReport>(bytes);
+    decode::<FeederEvent>(bytes);
+    decode::<FeedersStatus>(bytes);
+    decode::<HomingStatus>(bytes);
+    decode::<JobEvent>(bytes);
+    decode::<LimitOverrideStatus>(bytes);
+    decode::<MaintenanceEvent>(bytes);
+    decode::<ReadinessStatus>(bytes);
+    decode::<TestShotEvent>(bytes);
+    decode::<VisionStatus>(bytes);
+    decode::<OperatorCommandRequest>(bytes);
+    decode::<OperatorCommandResponse>(bytes);
+}
+
+fn timestamp() -> impl Strategy<Value = TimeStampUTC> {
+    // up to the year 2100
+    (0_i64..4_102_444_800_000).prop_map(|millis| {
+        chrono::DateTime::from_timestamp_millis(millis)
+            .unwrap()
+            .into()
+    })
+}
+
+/// The frame number, and the chunks of the frame in the order they were sent, the meta chunk first.
+fn frame() -> impl Strategy<Value = (u64, Vec<u8>, Vec<CameraFrameChunk<'static>>)> {
+    (
+        any::<u64>(),
+        timestamp(),
+        head_position(),
+        proptest::collection::vec(any::<u8>(), 0..4096),
+        1_usize..1024,
+    )
+        .prop_map(
+            |(frame_number, frame_timestamp, head_position, jpeg_bytes, chunk_size)| {
+                let (meta, image_chunks) =
+                    frame_chunks(frame_number, frame_timestamp, head_position, &jpeg_bytes, chunk_size);
+                let chunks = core::iter::once(meta)
+                    .chain(image_chunks)
+                    .map(CameraFrameChunk::into_owned)
+                    .collect();
+                (frame_number, jpeg_bytes, chunks)
+            },
+        )
+}
+
+/// The chunks of a frame, some sent more than once, in any order.
+fn shuffled_frame() -> impl Strategy<Value = (u64, Vec<u8>, Vec<CameraFrameChunk<'static>>)> {
+    frame().prop_flat_map(|(frame_number, jpeg_bytes, chunks)| {
+        let duplicates = proptest::collection::vec(any::<prop::sample::Index>(), 0..4);
+        let chunks = (Just(chunks), duplicates)
+            .prop_map(|(mut chunks, duplicates)| {
+                for index in duplicates {
+                    let duplicate = chunks[index.index(chunks.len())].clone();
+                    chunks.push(duplicate);
+                }
+                chunks
+            })
+            .prop_shuffle();
+        (Just(frame_number), Just(jpeg_bytes), chunks)
+    })
+}
+
+fn chunk_kind() -> impl Strategy<Value = CameraFrameChunkKind<'static>> {
+    let meta = (any::<u32>(), timestamp(), any::<u32>(), head_position()).prop_map(
+        |(total_chunks, frame_timestamp, total_bytes, head_position)| {
+            CameraFrameChunkKind::Meta(CameraFrameMeta {
+                total_chunks,
+                frame_timestamp,
+                total_bytes,
+                head_position,
+            })
+        },
+    );
+    let image_chunk = (0_u32..8, proptest::collection::vec(any::<u8>(), 0..64)).prop_map(|(chunk_index, bytes)| {
+        CameraFrameChunkKind::ImageChunk(CameraFrameImageChunk {
+            chunk_index,
+            bytes: Cow::Owned(bytes),
+        })
+    });
+    prop_oneof![meta, image_chunk]
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_frames_never_panic(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+        decode_all(&bytes);
+    }
+
+    #[test]
+    fn camera_frame_chunks_round_trip((_frame_number, _jpeg_bytes, chunks) in frame()) {
+        for chunk in &chunks {
+            assert_chunk_round_trip(chunk);
+        }
+    }
+
+    #[test]
+    fn camera_frames_assemble_from_shuffled_and_duplicated_chunks(
+        (frame_number, jpeg_bytes, chunks) in shuffled_frame(),
+    ) {
+        // given
+        let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+
+        // when
+        let frames: Vec<_> = chunks
+            .into_iter()
+            .filter_map(|chunk| assembler.insert(chunk))
+            .collect();
+
+        // then
+        prop_assert_eq!(frames.len(), 1);
+        prop_assert_eq!(frames[0].frame_number, frame_number);
+        prop_assert_eq!(&frames[0].jpeg_bytes, &jpeg_bytes);
+    }
+
+    #[test]
+    fn arbitrary_chunks_never_panic_the_assembler(
+        chunks in proptest::collection::vec((0_u64..4, chunk_kind()), 0..64),
+    ) {
+        let mut assembler = FrameAssembler::new(2, MAX_FRAME_BYTES);
+        let mut total_bytes = BTreeMap::new();
+        for (frame_number, kind) in chunks {
+            if let CameraFrameChunkKind::Meta(meta) = &kind {
+                total_bytes.insert(frame_number, meta.total_bytes);
+            }
+            if let Some(frame) = assembler.insert(CameraFrameChunk {
+                frame_number,
+                kind,
+            }) {
+                // a frame is only returned with the size given by its meta chunk
+                prop_assert_eq!(Some(&(frame.jpeg_bytes.len() as u32)), total_bytes.get(&frame_number));
+            }
+        }
+    }
+
+    #[test]
+    fn snapshot_key_is_the_same_after_sending_the_timestamp(timestamp in timestamp(), camera in any::<u8>()) {
+        // given, the client finds the snapshot saved by the server from the timestamp in the capture response
+        let camera = CameraId::new(camera);
+        let encoded = postcard::to_allocvec(&timestamp).unwrap();
+
+        // when
+        let decoded = postcard::from_bytes::<TimeStampUTC>(&encoded).unwrap();
+
+        // then
+        prop_assert_eq!(CaptureKey::snapshot(camera, &decoded), CaptureKey::snapshot(camera, &timestamp));
+    }
+}
@@ -0,0 +1,53 @@
+use alloc::string::String;
+
+use machine_ids::{CameraId, FeederId};
+use postcard_schema::Schema;
+use serde::{Deserialize, Serialize};
+
+/// Published by the server when a vision routine starts or finishes using a camera.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, Copy, PartialEq, Eq)]
+pub struct VisionStatus {
+    pub camera: CameraId,
+    /// a vision routine is using the camera
+    pub busy: bool,
+    /// the preview stream of the camera is paused until the vision routine has finished
+    pub preview_paused: bool,
+    /// vision requests waiting for a camera, for all cameras
+    pub queued: u32,
+}
+
+/// What a scanned barcode or QR code is used for.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq, Eq)]
+pub enum ScanTarget {
+    /// the code is the ID of the board, the job for the board is selected
+    Board,
+    /// the code is the ID of a reel that has been loaded into the feeder
+    Reel { feeder: FeederId },
+}
+
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq, Eq)]
+pub enum ScanError {
+    /// there is no camera to scan with
+    NoCamera,
+    CaptureFailed,
+    /// no barcode or QR code was found in the image
+    NoCode,
+    /// there is no job for the scanned board ID
+    UnknownJob(String),
+    /// the job can't be changed while it is running
+    JobRunning,
+    UnknownFeeder,
+}
+
+/// Why the orientation of the tape in a feeder could not be verified.
+#[derive(Debug, Serialize, Deserialize, Schema, Clone, PartialEq, Eq)]
+pub enum OrientationError {
+    UnknownFeeder,
+    /// the part of the feeder has no polarity mark, there is nothing to verify
+    NoPolarityMark,
+    /// there is no camera to capture the pocket with
+    NoCamera,
+    CaptureFailed,
+    /// no polarity mark was found in the pocket, e.g. the pocket is empty, or the mark is too faint
+    MarkNotFound,
+}
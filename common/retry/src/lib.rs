@@ -0,0 +1,188 @@
+//! Retrying an operation with an exponential backoff, e.g. discovering an endpoint, reconnecting, or waiting for an
+//! address to be allocated.
+//!
+//! The delays are randomized, see [`BackoffConfig::jitter`], so that clients that failed at the same time, e.g. when
+//! the server restarted, don't retry at the same time again.  The core, [`Backoff`] and [`retry_with`], is `no_std` and
+//! independent of the runtime, the io board passes the timer of its executor, [`retry`] blocks the thread, and
+//! [`retry_async`] uses tokio, see the `std` and `tokio` features.
+
+#![no_std]
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+use core::time::Duration;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackoffConfig {
+    /// the delay after the first failed attempt
+    pub initial: Duration,
+    /// the delays are not increased beyond this
+    pub max: Duration,
+    /// the delay is multiplied by this after each attempt, 1 for a constant delay
+    pub multiplier: u32,
+    /// 0.0-1.0, the fraction of each delay that is random, e.g. 0.5 gives a delay between half the delay and the delay
+    pub jitter: f32,
+    /// no attempt is made after this time since the first attempt, `None` to retry until the operation succeeds
+    pub max_elapsed: Option<Duration>,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(10),
+            multiplier: 2,
+            jitter: 0.5,
+            max_elapsed: None,
+        }
+    }
+}
+
+/// The delays between the attempts of an operation.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    config: BackoffConfig,
+    /// the delay before jitter
+    delay: Duration,
+    attempts: u32,
+    /// xorshift state, never 0
+    random: u32,
+}
+
+impl Backoff {
+    /// `seed` makes the jitter of each client different, e.g. a random number or the current time.
+    pub fn new(config: BackoffConfig, seed: u32) -> Self {
+        Self {
+            config,
+            delay: config.initial,
+            attempts: 0,
+            random: seed.max(1),
+        }
+    }
+
+    /// Seeded from the current time.
+    #[cfg(feature = "std")]
+    pub fn from_time(config: BackoffConfig) -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.subsec_nanos())
+            .unwrap_or_default();
+        Self::new(config, seed)
+    }
+
+    pub fn config(&self) -> &BackoffConfig {
+        &self.config
+    }
+
+    /// The attempts that failed since the backoff was created or reset.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Called once the operation has succeeded, so that the next failure starts from the initial delay again.
+    pub fn reset(&mut self) {
+        self.delay = self.config.initial;
+        self.attempts = 0;
+    }
+
+    /// The delay before the next attempt, regardless of [`BackoffConfig::max_elapsed`], for operations that are
+    /// retried until they are cancelled.
+    pub fn next_unbounded(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = self
+            .delay
+            .saturating_mul(self.config.multiplier)
+            .min(self.config.max);
+        self.attempts = self.attempts.saturating_add(1);
+
+        let jitter = self.config.jitter.clamp(0.0, 1.0) * self.random_fraction();
+        delay.saturating_sub(delay.mul_f32(jitter))
+    }
+
+    /// The delay before the next attempt, `elapsed` is the time since the first attempt.  `None` once the next attempt
+    /// would be after [`BackoffConfig::max_elapsed`].
+    pub fn next_delay(&mut self, elapsed: Duration) -> Option<Duration> {
+        let Some(max_elapsed) = self.config.max_elapsed else {
+            return Some(self.next_unbounded());
+        };
+        if elapsed >= max_elapsed {
+            return None;
+        }
+        // the last attempt is made at the max elapsed time, rather than not at all
+        Some(
+            self.next_unbounded()
+                .min(max_elapsed - elapsed),
+        )
+    }
+
+    /// 0.0-1.0, xorshift32
+    fn random_fraction(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        (self.random >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Calls `operation` until it succeeds, waiting between the attempts using `sleep`, the error of the last attempt is
+/// returned once the backoff gives up.  `elapsed` is the time since the first attempt.
+pub async fn retry_with<T, E, Op, OpFuture, Sleep, SleepFuture, Elapsed>(
+    backoff: &mut Backoff,
+    mut operation: Op,
+    mut sleep: Sleep,
+    elapsed: Elapsed,
+) -> Result<T, E>
+where
+    Op: FnMut() -> OpFuture,
+    OpFuture: Future<Output = Result<T, E>>,
+    Sleep: FnMut(Duration) -> SleepFuture,
+    SleepFuture: Future<Output = ()>,
+    Elapsed: Fn() -> Duration,
+{
+    loop {
+        let error = match operation().await {
+            Ok(value) => {
+                backoff.reset();
+                return Ok(value);
+            }
+            Err(error) => error,
+        };
+        let Some(delay) = backoff.next_delay(elapsed()) else {
+            return Err(error);
+        };
+        sleep(delay).await;
+    }
+}
+
+/// Calls `operation` until it succeeds, blocking the thread between the attempts, see [`retry_with`].
+#[cfg(feature = "std")]
+pub fn retry<T, E>(backoff: &mut Backoff, mut operation: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    let started_at = std::time::Instant::now();
+    loop {
+        let error = match operation() {
+            Ok(value) => {
+                backoff.reset();
+                return Ok(value);
+            }
+            Err(error) => error,
+        };
+        let Some(delay) = backoff.next_delay(started_at.elapsed()) else {
+            return Err(error);
+        };
+        std::thread::sleep(delay);
+    }
+}
+
+/// Calls `operation` until it succeeds, using the tokio timer between the attempts, see [`retry_with`].
+#[cfg(feature = "tokio")]
+pub async fn retry_async<T, E, Op, OpFuture>(backoff: &mut Backoff, operation: Op) -> Result<T, E>
+where
+    Op: FnMut() -> OpFuture,
+    OpFuture: Future<Output = Result<T, E>>,
+{
+    let started_at = tokio::time::Instant::now();
+    retry_with(backoff, operation, tokio::time::sleep, || started_at.elapsed()).await
+}
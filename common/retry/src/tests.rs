@@ -0,0 +1,162 @@
+use core::cell::RefCell;
+use core::time::Duration;
+use std::vec;
+use std::vec::Vec;
+
+use super::{Backoff, BackoffConfig, retry, retry_with};
+
+fn config(jitter: f32, max_elapsed: Option<Duration>) -> BackoffConfig {
+    BackoffConfig {
+        initial: Duration::from_millis(100),
+        max: Duration::from_millis(500),
+        multiplier: 2,
+        jitter,
+        max_elapsed,
+    }
+}
+
+#[test]
+pub fn delays_are_multiplied_up_to_the_max() {
+    // given
+    let mut backoff = Backoff::new(config(0.0, None), 1);
+
+    // when
+    let delays = (0..5)
+        .map(|_| backoff.next_unbounded())
+        .collect::<Vec<_>>();
+
+    // then
+    assert_eq!(delays, vec![
+        Duration::from_millis(100),
+        Duration::from_millis(200),
+        Duration::from_millis(400),
+        Duration::from_millis(500),
+        Duration::from_millis(500),
+    ]);
+    assert_eq!(backoff.attempts(), 5);
+}
+
+#[test]
+pub fn jitter_shortens_the_delays_by_at_most_the_fraction() {
+    // given
+    let mut backoff = Backoff::new(
+        BackoffConfig {
+            multiplier: 1,
+            ..config(0.5, None)
+        },
+        12345,
+    );
+
+    // when
+    let delays = (0..100)
+        .map(|_| backoff.next_unbounded())
+        .collect::<Vec<_>>();
+
+    // then
+    assert!(
+        delays
+            .iter()
+            .all(|delay| *delay >= Duration::from_millis(50) && *delay <= Duration::from_millis(100))
+    );
+    // not all the same
+    assert!(
+        delays
+            .iter()
+            .any(|delay| *delay != delays[0])
+    );
+}
+
+#[test]
+pub fn clients_with_different_seeds_retry_at_different_times() {
+    // given
+    let mut first = Backoff::new(config(0.5, None), 1);
+    let mut second = Backoff::new(config(0.5, None), 2);
+
+    // expect
+    assert_ne!(first.next_unbounded(), second.next_unbounded());
+}
+
+#[test]
+pub fn reset_starts_from_the_initial_delay() {
+    // given
+    let mut backoff = Backoff::new(config(0.0, None), 1);
+    backoff.next_unbounded();
+    backoff.next_unbounded();
+
+    // when
+    backoff.reset();
+
+    // then
+    assert_eq!(backoff.attempts(), 0);
+    assert_eq!(backoff.next_unbounded(), Duration::from_millis(100));
+}
+
+#[test]
+pub fn no_delay_after_the_max_elapsed_time() {
+    // given
+    let mut backoff = Backoff::new(config(0.0, Some(Duration::from_millis(250))), 1);
+
+    // expect
+    assert_eq!(backoff.next_delay(Duration::ZERO), Some(Duration::from_millis(100)));
+    // the last attempt is made at the max elapsed time
+    assert_eq!(
+        backoff.next_delay(Duration::from_millis(100)),
+        Some(Duration::from_millis(150))
+    );
+    assert_eq!(backoff.next_delay(Duration::from_millis(250)), None);
+}
+
+#[test]
+pub fn retry_until_the_operation_succeeds() {
+    // given
+    let mut backoff = Backoff::new(
+        BackoffConfig {
+            initial: Duration::from_millis(1),
+            ..config(0.0, None)
+        },
+        1,
+    );
+    let mut attempts = 0;
+
+    // when
+    let result = retry(&mut backoff, || {
+        attempts += 1;
+        match attempts {
+            3 => Ok(attempts),
+            _ => Err(()),
+        }
+    });
+
+    // then
+    assert_eq!(result, Ok(3));
+    // reset once the operation succeeded
+    assert_eq!(backoff.attempts(), 0);
+}
+
+#[tokio::test]
+pub async fn retry_with_returns_the_last_error_once_the_time_has_elapsed() {
+    // given
+    let mut backoff = Backoff::new(config(0.0, Some(Duration::from_millis(500))), 1);
+    let elapsed = RefCell::new(Duration::ZERO);
+    let mut attempts = 0;
+
+    // when
+    let result: Result<(), u32> = retry_with(
+        &mut backoff,
+        || {
+            attempts += 1;
+            core::future::ready(Err(attempts))
+        },
+        |delay| {
+            *elapsed.borrow_mut() += delay;
+            core::future::ready(())
+        },
+        || *elapsed.borrow(),
+    )
+    .await;
+
+    // then
+    // attempts at 0ms, 100ms, 300ms and 500ms
+    assert_eq!(result, Err(4));
+    assert_eq!(*elapsed.borrow(), Duration::from_millis(500));
+}
@@ -0,0 +1,155 @@
+//! Pacing the presentation of the frames of a camera stream.
+//!
+//! Frames arrive in bursts, e.g. the chunks of a frame are delayed by the network, presenting each frame as soon as
+//! it arrives makes the stream stutter.  Instead the frame rate of the stream is estimated from the capture
+//! timestamps of the frames, see [`FpsEstimator`], and frames are presented at that rate, see [`FramePacer`].
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PacingConfig {
+    /// the weight of each measurement in the exponential moving average of the frame rate, 0.0-1.0, higher values
+    /// follow changes in the frame rate sooner, lower values are steadier
+    pub smoothing: f32,
+    /// the frame interval is clamped to these frame rates, in frames per second
+    pub min_fps: f32,
+    pub max_fps: f32,
+    /// the frame rate is measured over the frames captured in this window
+    pub window: Duration,
+}
+
+impl Default for PacingConfig {
+    fn default() -> Self {
+        Self {
+            smoothing: 0.1,
+            min_fps: 5.0,
+            max_fps: 60.0,
+            window: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Estimates the frame rate of a stream from the capture timestamps of its frames.
+pub struct FpsEstimator {
+    config: PacingConfig,
+    timestamps: VecDeque<DateTime<Utc>>,
+    effective_fps: f32,
+}
+
+impl FpsEstimator {
+    /// The estimate starts at the `target_fps`, the frame rate requested for the stream.
+    pub fn new(target_fps: f32, config: PacingConfig) -> Self {
+        Self {
+            config,
+            timestamps: VecDeque::with_capacity(64),
+            effective_fps: target_fps,
+        }
+    }
+
+    /// Returns the frame rate measured over the window, `None` until there are enough frames.
+    ///
+    /// Timestamps that are not newer than the newest timestamp are ignored, e.g. the frames of a lossy link can
+    /// arrive out of order.
+    pub fn update(&mut self, frame_timestamp: DateTime<Utc>) -> Option<f32> {
+        if self
+            .timestamps
+            .back()
+            .is_some_and(|newest| frame_timestamp <= *newest)
+        {
+            return None;
+        }
+
+        self.timestamps
+            .push_back(frame_timestamp);
+        let window = chrono::TimeDelta::from_std(self.config.window).unwrap_or(chrono::TimeDelta::MAX);
+        while self
+            .timestamps
+            .front()
+            .is_some_and(|oldest| frame_timestamp - *oldest > window)
+        {
+            self.timestamps.pop_front();
+        }
+
+        let (Some(oldest), Some(newest)) = (self.timestamps.front(), self.timestamps.back()) else {
+            return None;
+        };
+        let total_span = (*newest - *oldest).as_seconds_f64();
+        if self.timestamps.len() < 2 || total_span <= 0.0 {
+            return None;
+        }
+
+        let frame_count = self.timestamps.len() - 1;
+        let measured_fps = (frame_count as f64 / total_span) as f32;
+
+        let smoothing = self.config.smoothing.clamp(0.0, 1.0);
+        self.effective_fps = (1.0 - smoothing) * self.effective_fps + smoothing * measured_fps;
+
+        Some(measured_fps)
+    }
+
+    /// The smoothed frame rate, not clamped.
+    pub fn effective_fps(&self) -> f32 {
+        self.effective_fps
+    }
+
+    /// The interval to present frames at, from the smoothed frame rate clamped to the configured frame rates.
+    pub fn frame_interval(&self) -> Duration {
+        // `max` then `min`, instead of `clamp`, which panics when the configured range is empty
+        let fps = self
+            .effective_fps
+            .max(self.config.min_fps)
+            .min(self.config.max_fps)
+            .max(f32::EPSILON);
+
+        Duration::from_secs_f64(1.0 / fps as f64)
+    }
+}
+
+/// Schedules the presentation of frames, one frame per frame interval.
+pub struct FramePacer {
+    next_frame_at: Instant,
+    /// the number of times presentation fell behind and was rescheduled
+    lagged: u64,
+}
+
+impl FramePacer {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            next_frame_at: now,
+            lagged: 0,
+        }
+    }
+
+    /// Whether a new frame should be presented, when pacing is skipped frames are presented as soon as they arrive.
+    pub fn is_due(&self, now: Instant, skip_pacing: bool) -> bool {
+        skip_pacing || now > self.next_frame_at
+    }
+
+    /// Schedules the next frame, after a frame was presented.
+    pub fn presented(&mut self, now: Instant, frame_interval: Duration, skip_pacing: bool) {
+        self.next_frame_at += frame_interval;
+        if skip_pacing {
+            self.next_frame_at = now + frame_interval;
+        } else if now > self.next_frame_at {
+            // catch up if we fall behind
+            self.next_frame_at = now + frame_interval;
+            self.lagged = self.lagged.wrapping_add(1);
+        }
+    }
+
+    /// The time until the next frame is due.
+    pub fn delay(&self, now: Instant) -> Duration {
+        self.next_frame_at
+            .saturating_duration_since(now)
+    }
+
+    pub fn lagged(&self) -> u64 {
+        self.lagged
+    }
+}
@@ -0,0 +1,193 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::{FpsEstimator, FramePacer, PacingConfig};
+
+fn timestamps(fps: u32, count: u32) -> Vec<DateTime<Utc>> {
+    let start = DateTime::from_timestamp_millis(1_700_000_000_000).unwrap();
+    (0..count)
+        .map(|index| start + TimeDelta::microseconds(1_000_000 / fps as i64 * index as i64))
+        .collect()
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() < 0.1,
+        "actual: {}, expected: {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+pub fn estimate_starts_at_the_target() {
+    // given
+    let estimator = FpsEstimator::new(30.0, PacingConfig::default());
+
+    // expect
+    assert_eq!(estimator.effective_fps(), 30.0);
+    assert_eq!(estimator.frame_interval(), Duration::from_secs_f64(1.0 / 30.0));
+}
+
+#[test]
+pub fn estimate_converges_to_the_measured_rate() {
+    // given
+    let mut estimator = FpsEstimator::new(30.0, PacingConfig::default());
+
+    // when
+    let mut measured = None;
+    for timestamp in timestamps(20, 200) {
+        measured = estimator.update(timestamp).or(measured);
+    }
+
+    // then
+    assert_close(measured.unwrap(), 20.0);
+    assert_close(estimator.effective_fps(), 20.0);
+}
+
+#[test]
+pub fn smoothing_limits_the_change_per_frame() {
+    // given
+    let config = PacingConfig {
+        smoothing: 0.5,
+        ..PacingConfig::default()
+    };
+    let mut estimator = FpsEstimator::new(30.0, config);
+    let timestamps = timestamps(10, 2);
+
+    // when
+    assert_eq!(estimator.update(timestamps[0]), None);
+    let measured = estimator.update(timestamps[1]);
+
+    // then
+    assert_close(measured.unwrap(), 10.0);
+    assert_close(estimator.effective_fps(), 20.0);
+}
+
+#[test]
+pub fn out_of_order_and_duplicate_timestamps_are_ignored() {
+    // given
+    let mut estimator = FpsEstimator::new(30.0, PacingConfig::default());
+    let timestamps = timestamps(10, 3);
+    estimator.update(timestamps[0]);
+    estimator.update(timestamps[2]);
+    let effective_fps = estimator.effective_fps();
+
+    // when
+    let duplicate = estimator.update(timestamps[2]);
+    let out_of_order = estimator.update(timestamps[1]);
+
+    // then
+    assert_eq!(duplicate, None);
+    assert_eq!(out_of_order, None);
+    assert_eq!(estimator.effective_fps(), effective_fps);
+}
+
+#[test]
+pub fn frame_interval_is_clamped() {
+    // given
+    let config = PacingConfig {
+        smoothing: 1.0,
+        min_fps: 5.0,
+        max_fps: 25.0,
+        ..PacingConfig::default()
+    };
+    let mut fast = FpsEstimator::new(30.0, config);
+    let mut slow = FpsEstimator::new(30.0, config);
+
+    // when
+    for timestamp in timestamps(100, 10) {
+        fast.update(timestamp);
+    }
+    for timestamp in timestamps(1, 3) {
+        slow.update(timestamp);
+    }
+
+    // then
+    assert_eq!(fast.frame_interval(), Duration::from_millis(40));
+    assert_eq!(slow.frame_interval(), Duration::from_millis(200));
+}
+
+#[test]
+pub fn only_frames_in_the_window_are_measured() {
+    // given
+    let config = PacingConfig {
+        smoothing: 1.0,
+        window: Duration::from_secs(1),
+        ..PacingConfig::default()
+    };
+    let mut estimator = FpsEstimator::new(30.0, config);
+    let slow = timestamps(5, 10);
+    let start = *slow.last().unwrap();
+
+    // when
+    for timestamp in slow {
+        estimator.update(timestamp);
+    }
+    let mut measured = None;
+    for index in 1..=20 {
+        measured = estimator.update(start + TimeDelta::milliseconds(50 * index));
+    }
+
+    // then
+    assert_close(measured.unwrap(), 20.0);
+}
+
+#[test]
+pub fn pacer_presents_one_frame_per_interval() {
+    // given
+    let start = Instant::now();
+    let interval = Duration::from_millis(50);
+    let mut pacer = FramePacer::new(start);
+
+    // when
+    let first = pacer.is_due(start + Duration::from_millis(1), false);
+    pacer.presented(start + Duration::from_millis(1), interval, false);
+    let too_soon = pacer.is_due(start + Duration::from_millis(30), false);
+    let next = pacer.is_due(start + Duration::from_millis(51), false);
+
+    // then
+    assert!(first);
+    assert!(!too_soon);
+    assert!(next);
+    assert_eq!(
+        pacer.delay(start + Duration::from_millis(30)),
+        Duration::from_millis(20)
+    );
+    assert_eq!(pacer.lagged(), 0);
+}
+
+#[test]
+pub fn pacer_catches_up_when_behind() {
+    // given
+    let start = Instant::now();
+    let interval = Duration::from_millis(50);
+    let mut pacer = FramePacer::new(start);
+    let late = start + Duration::from_millis(500);
+
+    // when
+    pacer.presented(late, interval, false);
+
+    // then
+    assert_eq!(pacer.lagged(), 1);
+    assert_eq!(pacer.delay(late), interval);
+}
+
+#[test]
+pub fn skipped_pacing_presents_frames_as_they_arrive() {
+    // given
+    let start = Instant::now();
+    let interval = Duration::from_millis(50);
+    let mut pacer = FramePacer::new(start);
+    pacer.presented(start, interval, false);
+
+    // when
+    let due = pacer.is_due(start + Duration::from_millis(10), true);
+    pacer.presented(start + Duration::from_millis(10), interval, true);
+
+    // then
+    assert!(due);
+    assert_eq!(pacer.delay(start + Duration::from_millis(10)), interval);
+    assert_eq!(pacer.lagged(), 0);
+}
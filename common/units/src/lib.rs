@@ -0,0 +1,159 @@
+//! Formatting of machine quantities, e.g. lengths, angles, durations and forces, according to the unit system and
+//! locale, so that the operator UI and reports don't each hardcode units and number formats.
+//!
+//! Values are always given in the units of the machine, millimeters, degrees, newtons, degrees celsius and
+//! kilopascals, and are converted to the unit system when they are formatted.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+mod tests;
+
+const MM_PER_INCH: f64 = 25.4;
+const LBF_PER_NEWTON: f64 = 0.224_808_943;
+const PSI_PER_KPA: f64 = 0.145_037_738;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UnitSystem {
+    /// millimeters and newtons
+    #[default]
+    Metric,
+    /// inches and pounds-force
+    Imperial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthUnit {
+    Millimeter,
+    Inch,
+}
+
+impl LengthUnit {
+    pub fn mm_per_unit(&self) -> f64 {
+        match self {
+            LengthUnit::Millimeter => 1.0,
+            LengthUnit::Inch => MM_PER_INCH,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            LengthUnit::Millimeter => "mm",
+            LengthUnit::Inch => "in",
+        }
+    }
+}
+
+/// The locale specific parts of number formatting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    decimal_separator: char,
+}
+
+impl Locale {
+    /// From a language identifier, e.g. `en-US` or `es-ES`, the same identifiers as the translations.
+    ///
+    /// Languages that are not known use `.` as the decimal separator.
+    pub fn from_language_identifier(identifier: &str) -> Self {
+        let language = identifier
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default();
+
+        let decimal_separator = match language {
+            "de" | "es" | "fr" | "it" | "nl" | "pl" | "pt" => ',',
+            _ => '.',
+        };
+
+        Self {
+            decimal_separator,
+        }
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            decimal_separator: '.',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Formatter {
+    pub unit_system: UnitSystem,
+    pub locale: Locale,
+}
+
+impl Formatter {
+    pub fn new(unit_system: UnitSystem, locale: Locale) -> Self {
+        Self {
+            unit_system,
+            locale,
+        }
+    }
+
+    /// A number without a unit, e.g. a scale factor.
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let formatted = format!("{:.*}", decimals, value);
+        match self.locale.decimal_separator {
+            '.' => formatted,
+            separator => formatted.replace('.', &separator.to_string()),
+        }
+    }
+
+    pub fn length_unit(&self) -> LengthUnit {
+        match self.unit_system {
+            UnitSystem::Metric => LengthUnit::Millimeter,
+            UnitSystem::Imperial => LengthUnit::Inch,
+        }
+    }
+
+    /// `decimals` is for millimeters, inches are formatted with one more decimal, since an inch is 25.4mm.
+    pub fn length(&self, mm: f64, decimals: usize) -> String {
+        let unit = self.length_unit();
+        let decimals = match unit {
+            LengthUnit::Millimeter => decimals,
+            LengthUnit::Inch => decimals + 1,
+        };
+        format!("{} {}", self.number(mm / unit.mm_per_unit(), decimals), unit.symbol())
+    }
+
+    /// The same in both unit systems.
+    pub fn angle(&self, degrees: f64, decimals: usize) -> String {
+        format!("{}°", self.number(degrees, decimals))
+    }
+
+    /// Milliseconds below a second, seconds below a minute, otherwise minutes and seconds.
+    pub fn duration(&self, duration: Duration) -> String {
+        let seconds = duration.as_secs_f64();
+        match duration.as_secs() {
+            0 => format!("{} ms", self.number(seconds * 1000.0, 1)),
+            1..60 => format!("{} s", self.number(seconds, 2)),
+            whole_seconds => format!("{} min {} s", whole_seconds / 60, whole_seconds % 60),
+        }
+    }
+
+    pub fn force(&self, newtons: f64, decimals: usize) -> String {
+        match self.unit_system {
+            UnitSystem::Metric => format!("{} N", self.number(newtons, decimals)),
+            UnitSystem::Imperial => format!("{} lbf", self.number(newtons * LBF_PER_NEWTON, decimals)),
+        }
+    }
+
+    pub fn temperature(&self, celsius: f64, decimals: usize) -> String {
+        match self.unit_system {
+            UnitSystem::Metric => format!("{}°C", self.number(celsius, decimals)),
+            UnitSystem::Imperial => format!("{}°F", self.number(celsius * 9.0 / 5.0 + 32.0, decimals)),
+        }
+    }
+
+    pub fn pressure(&self, kpa: f64, decimals: usize) -> String {
+        match self.unit_system {
+            UnitSystem::Metric => format!("{} kPa", self.number(kpa, decimals)),
+            UnitSystem::Imperial => format!("{} psi", self.number(kpa * PSI_PER_KPA, decimals)),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use super::{Formatter, Locale, UnitSystem};
+
+fn formatter(unit_system: UnitSystem, language_identifier: &str) -> Formatter {
+    Formatter::new(unit_system, Locale::from_language_identifier(language_identifier))
+}
+
+#[test]
+pub fn metric_lengths() {
+    // given
+    let formatter = formatter(UnitSystem::Metric, "en-US");
+
+    // expect
+    assert_eq!(formatter.length(12.3456, 3), "12.346 mm");
+    assert_eq!(formatter.length(-0.5, 1), "-0.5 mm");
+}
+
+#[test]
+pub fn imperial_lengths_have_an_extra_decimal() {
+    // given
+    let formatter = formatter(UnitSystem::Imperial, "en-US");
+
+    // expect
+    assert_eq!(formatter.length(25.4, 3), "1.0000 in");
+    assert_eq!(formatter.force(10.0, 2), "2.25 lbf");
+    assert_eq!(formatter.temperature(100.0, 0), "212°F");
+    assert_eq!(formatter.pressure(100.0, 1), "14.5 psi");
+}
+
+#[test]
+pub fn decimal_separator_from_language() {
+    // given
+    let formatter = formatter(UnitSystem::Metric, "es-ES");
+
+    // expect
+    assert_eq!(formatter.length(1.25, 2), "1,25 mm");
+    assert_eq!(formatter.angle(90.06, 1), "90,1°");
+    assert_eq!(formatter.force(1.5, 1), "1,5 N");
+}
+
+#[test]
+pub fn unknown_language_uses_a_decimal_point() {
+    // given
+    let formatter = formatter(UnitSystem::Metric, "xx");
+
+    // expect
+    assert_eq!(formatter.number(0.125, 3), "0.125");
+}
+
+#[test]
+pub fn durations_scaled_to_the_duration() {
+    // given
+    let formatter = formatter(UnitSystem::Metric, "en-US");
+
+    // expect
+    assert_eq!(formatter.duration(Duration::from_micros(1500)), "1.5 ms");
+    assert_eq!(formatter.duration(Duration::from_millis(2500)), "2.50 s");
+    assert_eq!(formatter.duration(Duration::from_secs(125)), "2 min 5 s");
+}
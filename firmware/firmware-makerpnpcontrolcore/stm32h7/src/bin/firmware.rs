@@ -32,7 +32,17 @@ use embassy_stm32::spi::Spi;
 use embassy_stm32::time::mhz;
 use embassy_time::{Delay, Duration, Ticker, Timer};
 use embedded_alloc::LlffHeap as Heap;
-use ioboard_main::stepper::Stepper;
+use ioboard_main::analog::{AnalogChannels, NoAnalogInputs};
+use ioboard_main::homing::NoLimitSwitch;
+use ioboard_main::load::LoadConfig;
+use ioboard_main::safety::{NoSafetyInputs, SafetyConfig};
+use ioboard_main::self_test::{SelfTest, SelfTestConfig};
+use ioboard_main::stepper::{SoftwarePulseGenerator, Stepper, StepperCancellation};
+use ioboard_main::temperature::{NtcConfig, ThermalProtection, ThermalProtectionConfig, ThermalThresholds};
+use ioboard_main::thermal::ThermalConfig;
+use ioboard_main::{AxisConfig, MotionPlanning};
+use ioboard_shared::self_test::SelfTestStatus;
+use ioboard_shared::thermal::TemperatureSensor;
 #[cfg(feature = "tracepin")]
 use ioboard_trace::tracepin;
 #[cfg(feature = "tracepin")]
@@ -460,8 +470,18 @@ async fn init_task(lp_spawner: Spawner, hp_spawner: SendSpawner, p: Peripherals)
         embassy_stm32::adc::SampleTime::Cycles325,
     );
 
+    // EXT_SENSE_1 is the driver thermistor, EXT_SENSE_2 the ambient thermistor
+    let thermal_config = ThermalProtectionConfig {
+        ntc: NtcConfig::default(),
+        sensors: [
+            (TemperatureSensor::Driver(0), ThermalThresholds::DRIVER),
+            (TemperatureSensor::Ambient, ThermalThresholds::AMBIENT),
+        ],
+    };
+
     lp_spawner.spawn(unwrap!(adc_task(
         adc_mux,
+        ThermalProtection::new(thermal_config),
 
         // other adc inputs
         adc3,
@@ -557,8 +577,19 @@ async fn init_task(lp_spawner: Spawner, hp_spawner: SendSpawner, p: Peripherals)
     );
     stepper.initialize_io().unwrap();
 
+    // FUTURE check the supply voltage and outputs, once they are supported on this board
+    let mut self_test = SelfTest::new(SelfTestConfig::default());
+    self_test.check_driver(0, &mut stepper);
+    lp_spawner.spawn(unwrap!(self_test_task(self_test.status())));
+
     info!("Initialisation complete");
 
+    // FUTURE the safety inputs, once they are supported on this board, until then only the e-stop broadcast is handled
+    hp_spawner.spawn(unwrap!(safety_monitor_task(NoSafetyInputs, SafetyConfig::default())));
+
+    // FUTURE the spare inputs of the adc mux, they are read by the adc task, until then every analog channel is refused
+    lp_spawner.spawn(unwrap!(analog_task(AnalogChannels::new(NoAnalogInputs))));
+
     hp_spawner.spawn(unwrap!(stepper_task(StepperRunner::new(stepper))));
 
     info!("running");
@@ -580,6 +611,7 @@ type AdcMuxInstance = adc::Mux<
 #[embassy_executor::task]
 async fn adc_task(
     mut adc_mux: AdcMuxInstance,
+    mut thermal_protection: ThermalProtection<2>,
     mut adc: Adc<'static, ADC3>,
     mut ext1_in: Peri<'static, PC2_C>,
     mut ext2_in: Peri<'static, PC3_C>,
@@ -604,6 +636,8 @@ async fn adc_task(
         );
         defmt::info!("ADC ext inputs. values: {:?})", ext);
 
+        thermal_protection.update([ext.0, ext.1], &STEPPER_CANCELLATION);
+
         let vac = (
             adc.blocking_read(&mut vac1_in, SampleTime::Cycles325),
             adc.blocking_read(&mut vac2_in, SampleTime::Cycles325),
@@ -751,12 +785,30 @@ async fn embassy_net_task(mut runner: embassy_net::Runner<'static, Device>) -> !
     runner.run().await
 }
 
+#[embassy_executor::task]
+async fn self_test_task(status: SelfTestStatus) {
+    ioboard_main::self_test::publish_self_test(status).await
+}
+
 type StepperInstance = Tmc5160Stepper<Spi<'static, Blocking, Master>, Output<'static>, Output<'static>, Delay, Output<'static>, Output<'static>>;
 #[embassy_executor::task]
 async fn stepper_task(runner: StepperRunner<StepperInstance>) {
     runner.run().await
 }
 
+#[embassy_executor::task]
+async fn safety_monitor_task(inputs: NoSafetyInputs, config: SafetyConfig) {
+    ioboard_main::safety::monitor_safety(inputs, config, &STEPPER_CANCELLATION).await
+}
+
+#[embassy_executor::task]
+async fn analog_task(analog_channels: AnalogChannels<NoAnalogInputs>) {
+    analog_channels.run().await
+}
+
+/// Cancelling this interrupts any in-progress step burst, e.g. on e-stop.
+static STEPPER_CANCELLATION: StepperCancellation = StepperCancellation::new();
+
 struct StepperRunner<STEPPER: Stepper> {
     stepper: STEPPER,
 }
@@ -773,7 +825,21 @@ impl<STEPPER: Stepper> StepperRunner<STEPPER> {
             stepper,
         } = self;
 
-        ioboard_main::run(stepper).await;
+        let axis_config = AxisConfig {
+            thermal: Some(ThermalConfig::default()),
+            // FUTURE enable once the resonance frequency of the axis has been measured, see `ResonanceSweepRequest`
+            input_shaper: None,
+            load: Some(LoadConfig::default()),
+            // FUTURE enable for the Z axis, once the encoders of the FPGA are read by the stepper
+            motion_anomaly: None,
+            planning: MotionPlanning::OnBoard,
+            // FUTURE the endstops are read by the FPGA, not yet exposed to the stepper
+            homing: None,
+            // FUTURE once the axis is homed, the travel of an axis positioned from where it is at power up is unknown
+            soft_limits: None,
+        };
+
+        ioboard_main::run(stepper, SoftwarePulseGenerator, NoLimitSwitch, &STEPPER_CANCELLATION, axis_config).await;
     }
 }
 
@@ -101,22 +101,6 @@ where
         .map_err(|_e| StepperError::IoError)
     }
 
-    #[inline(always)]
-    async fn step_and_wait(&mut self) -> Result<(), StepperError> {
-        let now = Instant::now();
-        self.step_pin
-            .set_high()
-            .map_err(|_e| StepperError::IoError)?;
-        let mut deadline = now + Duration::from_micros(self.pulse_width as u64);
-        Timer::at(deadline).await;
-        self.step_pin
-            .set_low()
-            .map_err(|_e| StepperError::IoError)?;
-        deadline += Duration::from_micros(self.pulse_width as u64);
-        Timer::at(deadline).await;
-        Ok(())
-    }
-
     #[inline(always)]
     async fn step(&mut self) -> Result<u32, StepperError> {
         let now = Instant::now();
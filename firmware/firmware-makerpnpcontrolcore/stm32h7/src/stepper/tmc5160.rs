@@ -1,6 +1,7 @@
 use embedded_hal::delay::DelayNs;
 use embedded_hal::digital::OutputPin;
 use embedded_hal::spi::SpiBus;
+use ioboard_main::load::DriverFeedback;
 use ioboard_main::stepper::{Stepper, StepperDirection, StepperError};
 
 use modular_bitfield_to_value::ToValue;
@@ -228,6 +229,19 @@ where
         Ok(())
     }
 
+    fn set_hold_current_reduction(&mut self, hold_percent: u8) -> Result<(), StepperError> {
+        // IHOLD is relative to the current scale, the same as IRUN, 0-31
+        let i_run = self.driver.ihold_irun.i_run() as u32;
+        let i_hold = i_run * hold_percent.min(100) as u32 / 100;
+        self.driver.ihold_irun.set_i_hold(i_hold as u8);
+        self.driver.update_ihold_irun()
+            .map_err(|_error|StepperError::DriverError)?;
+
+        info!("Configuring ihold_irun: {:08x}(LE)", self.driver.ihold_irun.to_u32_le());
+
+        Ok(())
+    }
+
     fn direction(&mut self, direction: StepperDirection) -> Result<(), StepperError> {
         match direction {
             StepperDirection::Normal => self.direction_pin.set_low(),
@@ -236,20 +250,20 @@ where
             .map_err(|_e| StepperError::IoError)
     }
 
-    #[inline(always)]
-    async fn step_and_wait(&mut self) -> Result<(), StepperError> {
-        let now = Instant::now();
-        self.step_pin
-            .set_high()
-            .map_err(|_e| StepperError::IoError)?;
-        let mut deadline = now + Duration::from_micros(self.pulse_width as u64);
-        Timer::at(deadline).await;
-        self.step_pin
-            .set_low()
-            .map_err(|_e| StepperError::IoError)?;
-        deadline += Duration::from_micros(self.pulse_width as u64);
-        Timer::at(deadline).await;
-        Ok(())
+    // FUTURE SG_RESULT is only valid in SpreadCycle above TCOOLTHRS, configure SGT and TCOOLTHRS for the motor once
+    //        the StallGuard tuning has been done
+    fn read_feedback(&mut self) -> Result<Option<DriverFeedback>, StepperError> {
+        let drv_status = self.driver.read_drv_status()
+            .map_err(|_error|StepperError::DriverError)?;
+
+        Ok(Some(DriverFeedback {
+            // SG_RESULT is 10 bits, CS_ACTUAL is 5 bits
+            stall_guard: drv_status.sg_result(),
+            stall_guard_max: 1023,
+            current_scale: drv_status.cs_actual(),
+            current_scale_max: 31,
+            standstill: drv_status.stst(),
+        }))
     }
 
     #[inline(always)]
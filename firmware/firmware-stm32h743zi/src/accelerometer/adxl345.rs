@@ -0,0 +1,94 @@
+use embedded_hal::i2c::I2c;
+
+/// I2C address when the ALT ADDRESS pin is low, `0x1D` when high.
+pub const DEFAULT_ADDRESS: u8 = 0x53;
+
+const REG_DEVID: u8 = 0x00;
+const REG_BW_RATE: u8 = 0x2C;
+const REG_POWER_CTL: u8 = 0x2D;
+const REG_DATA_FORMAT: u8 = 0x31;
+const REG_DATAX0: u8 = 0x32;
+
+const DEVICE_ID: u8 = 0xE5;
+
+const POWER_CTL_MEASURE: u8 = 0x08;
+/// full resolution, +/-16g range, right justified
+const DATA_FORMAT_FULL_RES_16G: u8 = 0x0B;
+/// In full resolution mode the scale is fixed, 3.9mg/LSB
+const FULL_RES_SCALE: f32 = 0.0039;
+
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum DataRate {
+    Hz400 = 0x0C,
+    Hz800 = 0x0D,
+    Hz1600 = 0x0E,
+    Hz3200 = 0x0F,
+}
+
+impl DataRate {
+    pub fn hz(&self) -> f32 {
+        match self {
+            DataRate::Hz400 => 400.0,
+            DataRate::Hz800 => 800.0,
+            DataRate::Hz1600 => 1600.0,
+            DataRate::Hz3200 => 3200.0,
+        }
+    }
+}
+
+#[derive(Debug, defmt::Format)]
+pub enum Adxl345Error {
+    IoError,
+    UnexpectedDeviceId(u8),
+}
+
+pub struct Adxl345<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C: I2c> Adxl345<I2C> {
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self {
+            i2c,
+            address,
+        }
+    }
+
+    pub fn initialize(&mut self, data_rate: DataRate) -> Result<(), Adxl345Error> {
+        let device_id = self.read_register(REG_DEVID)?;
+        if device_id != DEVICE_ID {
+            return Err(Adxl345Error::UnexpectedDeviceId(device_id));
+        }
+
+        self.write_register(REG_DATA_FORMAT, DATA_FORMAT_FULL_RES_16G)?;
+        self.write_register(REG_BW_RATE, data_rate as u8)?;
+        self.write_register(REG_POWER_CTL, POWER_CTL_MEASURE)
+    }
+
+    /// Read the (x, y, z) acceleration, in g.
+    pub fn read(&mut self) -> Result<[f32; 3], Adxl345Error> {
+        let mut buffer = [0u8; 6];
+        self.i2c
+            .write_read(self.address, &[REG_DATAX0], &mut buffer)
+            .map_err(|_e| Adxl345Error::IoError)?;
+
+        let axis = |index: usize| i16::from_le_bytes([buffer[index], buffer[index + 1]]) as f32 * FULL_RES_SCALE;
+
+        Ok([axis(0), axis(2), axis(4)])
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, Adxl345Error> {
+        let mut buffer = [0u8; 1];
+        self.i2c
+            .write_read(self.address, &[register], &mut buffer)
+            .map_err(|_e| Adxl345Error::IoError)?;
+        Ok(buffer[0])
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), Adxl345Error> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .map_err(|_e| Adxl345Error::IoError)
+    }
+}
@@ -0,0 +1 @@
+pub mod adxl345;
@@ -0,0 +1,49 @@
+use embassy_stm32::adc;
+use embassy_stm32::adc::{Adc, AnyAdcChannel, BasicAdcRegs, BasicInstance};
+use ioboard_main::analog::AnalogInputs;
+
+/// Spare ADC inputs, for the analog channels, the inputs are indexed in the order of `inputs`.
+pub struct AdcAnalogInputs<'a, ADC, const N: usize>
+where
+    ADC: adc::Instance,
+    <ADC as BasicInstance>::Regs: BasicAdcRegs,
+{
+    adc: Adc<'a, ADC>,
+    inputs: [AnyAdcChannel<ADC>; N],
+    sample_time: <<ADC as BasicInstance>::Regs as BasicAdcRegs>::SampleTime,
+    full_scale: u16,
+}
+
+impl<'a, ADC: adc::Instance + adc::BasicInstance, const N: usize> AdcAnalogInputs<'a, ADC, N> {
+    pub fn new(
+        adc: Adc<'a, ADC>,
+        inputs: [AnyAdcChannel<ADC>; N],
+        sample_time: <<ADC as BasicInstance>::Regs as BasicAdcRegs>::SampleTime,
+        full_scale: u16,
+    ) -> Self {
+        Self {
+            adc,
+            inputs,
+            sample_time,
+            full_scale,
+        }
+    }
+}
+
+impl<'a, ADC: adc::Instance + adc::BasicInstance, const N: usize> AnalogInputs for AdcAnalogInputs<'a, ADC, N> {
+    fn input_count(&self) -> u8 {
+        N as u8
+    }
+
+    fn full_scale(&self) -> u16 {
+        self.full_scale
+    }
+
+    fn read(&mut self, input: u8) -> Option<u16> {
+        let input = self.inputs.get_mut(input as usize)?;
+        Some(
+            self.adc
+                .blocking_read(input, self.sample_time),
+        )
+    }
+}
@@ -16,21 +16,46 @@ use embassy_executor::{Executor, InterruptExecutor, Spawner};
 use embassy_stm32::Peripherals;
 use embassy_stm32::eth::{PacketQueue, Sma, StationManagement};
 use embassy_stm32::eth::{Ethernet, GenericPhy};
-use embassy_stm32::gpio::{Level, Output, Speed};
+use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::gpio::{Input, Level, Output, Pull, Speed};
 use embassy_stm32::interrupt::{InterruptExt, Priority};
 use embassy_stm32::pac::rcc::vals::{Pllm, Plln, Pllsrc};
-use embassy_stm32::peripherals::{ETH, ETH_SMA};
+use embassy_stm32::gpio::OutputType;
+use embassy_stm32::peripherals::{ADC1, ADC2, ADC3, ETH, ETH_SMA, PA3, PC0, TIM3};
+use embassy_stm32::timer::low_level::CountingMode;
+use embassy_stm32::timer::simple_pwm::{PwmPin, SimplePwm, SimplePwmChannel};
 use embassy_stm32::rcc::mux::{
     Fdcansel, Fmcsel, I2c4sel, I2c1235sel, Saisel, Sdmmcsel, Spi6sel, Spi45sel, Usart16910sel, Usart234578sel, Usbsel,
 };
 use embassy_stm32::rcc::{AHBPrescaler, APBPrescaler, LsConfig, PllDiv, Sysclk};
 use embassy_stm32::rng::Rng;
-use embassy_stm32::{Config, bind_interrupts, eth, interrupt, peripherals, rcc, rng};
+use embassy_stm32::adc::{Adc, AdcChannel, SampleTime};
+use embassy_stm32::i2c::{self, I2c};
+use embassy_stm32::spi::{self, Spi};
+use embassy_stm32::mode::Blocking;
+use embassy_stm32::time::{khz, mhz};
+use embassy_stm32::{Config, Peri, bind_interrupts, eth, interrupt, peripherals, rcc, rng};
 use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
 use embassy_sync::mutex::Mutex;
 use embassy_time::{Duration, Ticker, Timer};
 use embedded_alloc::LlffHeap as Heap;
-use ioboard_main::stepper::Stepper;
+use ioboard_main::AxisConfig;
+use ioboard_main::analog::AnalogChannels;
+use ioboard_main::dispenser::{DispenserConfig, DispenserController};
+use ioboard_main::expansion::ExpansionRegistry;
+use ioboard_main::feeder_slots::{FeederSlotConfig, FeederSlotMonitor};
+use ioboard_main::homing::{HomingConfig, LimitSwitch};
+use ioboard_main::power::{PowerSequenceConfig, PowerSequencer, SupplyThresholds};
+use ioboard_main::probe::{ElectricalProbe, ElectricalProbeConfig, PROBE};
+use ioboard_main::safe_z::SAFE_Z_GUARD;
+use ioboard_main::safety::SafetyConfig;
+use ioboard_main::self_test::{SelfTest, SelfTestConfig};
+use ioboard_main::stepper::{SoftwarePulseGenerator, Stepper, StepperCancellation};
+use ioboard_main::vacuum::{NoManifold, PiConfig, VacuumController};
+use ioboard_main::vibration::VIBRATION_MONITOR;
+use ioboard_shared::expansion::{ExpansionBus, ExpansionDevice, ExpansionDeviceKind};
+use ioboard_shared::safety::SafetyPolicy;
+use ioboard_shared::self_test::SelfTestStatus;
 #[cfg(feature = "tracepin")]
 use ioboard_trace::tracepin;
 #[cfg(feature = "tracepin")]
@@ -38,7 +63,18 @@ use ioboard_trace::tracepin::TracePins;
 use static_cell::StaticCell;
 use {defmt_rtt as _, panic_probe as _};
 
+use firmware_stm32h743zi::accelerometer::adxl345::{self, Adxl345, DataRate};
+use firmware_stm32h743zi::analog::AdcAnalogInputs;
+use firmware_stm32h743zi::dispenser::GpioDispenserOutputs;
+use firmware_stm32h743zi::expansion::HalExpansionBuses;
+use firmware_stm32h743zi::feeder_id::EepromFeederIds;
+use firmware_stm32h743zi::limit_switch::GpioLimitSwitch;
+use firmware_stm32h743zi::power::GpioPowerRails;
+use firmware_stm32h743zi::probe::GpioProbeInput;
+use firmware_stm32h743zi::safety::GpioSafetyInputs;
 use firmware_stm32h743zi::stepper::bitbash::{GpioBitbashStepper, StepperEnableMode};
+use firmware_stm32h743zi::supply::AdcSupplySensor;
+use firmware_stm32h743zi::vacuum::{AdcVacuumSensor, PwmPumpOutput};
 #[cfg(feature = "tracepin")]
 use firmware_stm32h743zi::trace::TracePinsService;
 
@@ -259,9 +295,153 @@ async fn init_task(lp_spawner: Spawner, hp_spawner: SendSpawner, p: Peripherals)
     );
     stepper.initialize_io().unwrap();
 
+    // each part is checked before it is handed to its task, the result is published once all parts are initialized
+    let mut self_test = SelfTest::new(SelfTestConfig::default());
+    self_test.check_driver(0, &mut stepper);
+
+    info!("Initializing Supply monitor");
+    // A0 on the arduino header, via a 100K/10K divider from the motor supply
+    let mut supply_sensor = AdcSupplySensor::new(Adc::new(p.ADC1), p.PA3, SampleTime::Cycles325, 3.3, 65535.0, 11.0);
+    self_test.check_supply(&mut supply_sensor);
+
+    // started before the stepper so that motion is never started on an undervoltage supply
+    hp_spawner.spawn(unwrap!(supply_monitor_task(supply_sensor)));
+
+    info!("Initializing Safety inputs");
+    // CN9 header, door switch and light curtain, both normally-closed to ground
+    let safety_inputs = GpioSafetyInputs::new(Input::new(p.PE6, Pull::Up), Input::new(p.PE3, Pull::Up));
+    let safety_config = SafetyConfig {
+        door: Some(SafetyPolicy::PauseMotion),
+        light_curtain: Some(SafetyPolicy::EStop),
+        ..SafetyConfig::default()
+    };
+
+    // started before the stepper so that motion is never started with a tripped safety input
+    hp_spawner.spawn(unwrap!(safety_monitor_task(safety_inputs, safety_config)));
+
+    info!("Initializing Power rails");
+    // CN9 header, to MOSFET/relay drivers
+    let mut power_rails = GpioPowerRails::new(
+        // motor power
+        Output::new(p.PE2, Level::Low, Speed::Low),
+        // vacuum pump
+        Output::new(p.PE4, Level::Low, Speed::Low),
+        // lighting
+        Output::new(p.PE5, Level::Low, Speed::Low),
+    );
+    self_test
+        .check_outputs(&mut power_rails)
+        .await;
+    let power_sequencer = PowerSequencer::new(power_rails, PowerSequenceConfig::default());
+    lp_spawner.spawn(unwrap!(power_task(power_sequencer)));
+
+    info!("Initializing Vacuum control");
+    // A1 on the arduino header, 0.5V = ambient, -100kPa = 4.5V, via a 2:3 divider
+    let mut vacuum_sensor =
+        AdcVacuumSensor::new(Adc::new(p.ADC2), p.PC0, SampleTime::Cycles325, 3.3, 65535.0, 0.333, 37.5);
+    self_test.check_vacuum(&mut vacuum_sensor);
+    // D12 on the arduino header, 25kHz is above the audible range
+    let pump_pwm = SimplePwm::new(
+        p.TIM3,
+        Some(PwmPin::new(p.PA6, OutputType::PushPull)),
+        None,
+        None,
+        None,
+        khz(25),
+        CountingMode::EdgeAlignedUp,
+    );
+    let mut pump_channel = pump_pwm.split().ch1;
+    pump_channel.enable();
+    // single vacuum circuit, no per-nozzle valves
+    let vacuum_controller = VacuumController::new(
+        vacuum_sensor,
+        PwmPumpOutput::new(pump_channel),
+        NoManifold,
+        PiConfig::default(),
+    );
+    lp_spawner.spawn(unwrap!(vacuum_task(vacuum_controller)));
+
+    info!("Initializing Dispenser");
+    // CN10 header, to the valve or auger motor drivers
+    let dispenser_outputs = GpioDispenserOutputs::new(
+        // pressure
+        Output::new(p.PG0, Level::Low, Speed::Low),
+        // retract
+        Output::new(p.PG1, Level::Low, Speed::Low),
+    );
+    let dispenser_controller = DispenserController::new(dispenser_outputs, DispenserConfig::default());
+    lp_spawner.spawn(unwrap!(dispenser_task(dispenser_controller)));
+
+    lp_spawner.spawn(unwrap!(safe_z_task()));
+
+    info!("Initializing Probe input");
+    // CN10 header, touch probe or nozzle contact plate, to ground
+    let probe_input = GpioProbeInput::new(ExtiInput::new(p.PG2, p.EXTI2, Pull::Up));
+    let electrical_probe = ElectricalProbe::new(probe_input, ElectricalProbeConfig::default());
+    // the position is latched by the probe task, on the same executor as the stepper so it runs promptly after the edge
+    hp_spawner.spawn(unwrap!(electrical_probe_task(electrical_probe)));
+    lp_spawner.spawn(unwrap!(probe_task()));
+
+    info!("Initializing Accelerometer");
+    let mut i2c_config = i2c::Config::default();
+    i2c_config.frequency = khz(400);
+    // I2C1 on the arduino header, SCL = D15, SDA = D14
+    let i2c = I2c::new_blocking(p.I2C1, p.PB8, p.PB9, i2c_config);
+    let mut accelerometer = Adxl345::new(i2c, adxl345::DEFAULT_ADDRESS);
+    let accelerometer_data_rate = DataRate::Hz800;
+    match accelerometer.initialize(accelerometer_data_rate) {
+        Ok(()) => lp_spawner.spawn(unwrap!(accelerometer_task(accelerometer, accelerometer_data_rate))),
+        // the accelerometer is optional
+        Err(e) => warn!("Accelerometer unavailable, error: {}", e),
+    }
+
+    info!("Initializing Expansion devices");
+    let mut expansion_i2c_config = i2c::Config::default();
+    expansion_i2c_config.frequency = khz(400);
+    // I2C2 on the CN9 header, SCL = PF1, SDA = PF0
+    let expansion_i2c = I2c::new_blocking(p.I2C2, p.PF1, p.PF0, expansion_i2c_config);
+    let mut expansion_spi_config = spi::Config::default();
+    expansion_spi_config.frequency = mhz(1);
+    // SPI1, SCK = PB3, MOSI = PB5 (D11 on the arduino header), MISO = PB4
+    let expansion_spi = Spi::new_blocking(p.SPI1, p.PB3, p.PB5, p.PB4, expansion_spi_config);
+    let expansion_buses = HalExpansionBuses::new(
+        expansion_i2c,
+        expansion_spi,
+        // chip select 0, D10
+        [Output::new(p.PD14, Level::High, Speed::Low)],
+    );
+    let expansion_registry = ExpansionRegistry::new(expansion_buses, EXPANSION_DEVICES);
+    lp_spawner.spawn(unwrap!(expansion_task(expansion_registry)));
+
+    info!("Initializing Analog inputs");
+    // A3, A4 and A5 on the arduino header, 0-3.3V, for ad-hoc sensors, the channels are configured by the server
+    let analog_inputs = AdcAnalogInputs::new(
+        Adc::new(p.ADC3),
+        [p.PF3.degrade_adc(), p.PF5.degrade_adc(), p.PF10.degrade_adc()],
+        SampleTime::Cycles325,
+        65535,
+    );
+    lp_spawner.spawn(unwrap!(analog_task(AnalogChannels::new(analog_inputs))));
+
+    info!("Initializing Feeder slots");
+    let mut feeder_i2c_config = i2c::Config::default();
+    feeder_i2c_config.frequency = khz(100);
+    // I2C4 to the feeder bank, SCL = PF14, SDA = PF15, 100kHz for the long cable to the slots
+    let feeder_i2c = I2c::new_blocking(p.I2C4, p.PF14, p.PF15, feeder_i2c_config);
+    let feeder_slot_monitor = FeederSlotMonitor::new(
+        EepromFeederIds::new(feeder_i2c, FEEDER_SLOTS),
+        FeederSlotConfig::default(),
+    );
+    lp_spawner.spawn(unwrap!(feeder_slot_task(feeder_slot_monitor)));
+
+    lp_spawner.spawn(unwrap!(self_test_task(self_test.status())));
+
     info!("Initialisation complete");
 
-    hp_spawner.spawn(unwrap!(stepper_task(StepperRunner::new(stepper))));
+    // CN10 header, endstop at the min end of the axis, normally-open to ground
+    let limit_switch = GpioLimitSwitch::new(Input::new(p.PG3, Pull::Up));
+
+    hp_spawner.spawn(unwrap!(stepper_task(StepperRunner::new(stepper, limit_switch))));
 
     info!("running");
 
@@ -279,6 +459,149 @@ async fn embassy_net_task(mut runner: embassy_net::Runner<'static, Device>) -> !
     runner.run().await
 }
 
+type SupplySensorInstance = AdcSupplySensor<'static, ADC1, Peri<'static, PA3>>;
+
+#[embassy_executor::task]
+async fn supply_monitor_task(sensor: SupplySensorInstance) {
+    ioboard_main::power::monitor_supply(sensor, SupplyThresholds::SUPPLY_24V, &STEPPER_CANCELLATION).await
+}
+
+type SafetyInputsInstance = GpioSafetyInputs<Input<'static>, Input<'static>>;
+
+#[embassy_executor::task]
+async fn safety_monitor_task(inputs: SafetyInputsInstance, config: SafetyConfig) {
+    ioboard_main::safety::monitor_safety(inputs, config, &STEPPER_CANCELLATION).await
+}
+
+type PowerSequencerInstance = PowerSequencer<GpioPowerRails<Output<'static>, Output<'static>, Output<'static>>>;
+
+#[embassy_executor::task]
+async fn power_task(power_sequencer: PowerSequencerInstance) {
+    power_sequencer.run().await
+}
+
+type VacuumControllerInstance = VacuumController<
+    AdcVacuumSensor<'static, ADC2, Peri<'static, PC0>>,
+    PwmPumpOutput<SimplePwmChannel<'static, TIM3>>,
+    NoManifold,
+>;
+
+#[embassy_executor::task]
+async fn vacuum_task(vacuum_controller: VacuumControllerInstance) {
+    vacuum_controller.run().await
+}
+
+type DispenserControllerInstance = DispenserController<GpioDispenserOutputs<Output<'static>, Output<'static>>>;
+
+#[embassy_executor::task]
+async fn dispenser_task(dispenser_controller: DispenserControllerInstance) {
+    dispenser_controller.run().await
+}
+
+#[embassy_executor::task]
+async fn safe_z_task() {
+    SAFE_Z_GUARD.run().await
+}
+
+type ElectricalProbeInstance = ElectricalProbe<GpioProbeInput<ExtiInput<'static>>>;
+
+#[embassy_executor::task]
+async fn electrical_probe_task(electrical_probe: ElectricalProbeInstance) {
+    electrical_probe.run().await
+}
+
+#[embassy_executor::task]
+async fn probe_task() {
+    PROBE.run().await
+}
+
+type AccelerometerInstance = Adxl345<I2c<'static, Blocking, i2c::Master>>;
+
+/// Interval between vibration reports, each report summarizes the samples since the previous report.
+const VIBRATION_REPORT_INTERVAL_MS: u32 = 500;
+
+#[embassy_executor::task]
+async fn accelerometer_task(mut accelerometer: AccelerometerInstance, data_rate: DataRate) {
+    let sample_rate = data_rate.hz();
+    let samples_per_report = (sample_rate as u32 * VIBRATION_REPORT_INTERVAL_MS) / 1000;
+
+    let mut sample_ticker = Ticker::every(Duration::from_micros((1_000_000.0 / sample_rate) as u64));
+    let mut samples = 0;
+    loop {
+        match accelerometer.read() {
+            Ok(sample) => VIBRATION_MONITOR.record(sample),
+            Err(e) => warn!("Accelerometer read error: {}", e),
+        }
+
+        samples += 1;
+        if samples >= samples_per_report {
+            samples = 0;
+            let report = VIBRATION_MONITOR.report(sample_rate);
+            ioboard_net::publish_vibration(&report);
+        }
+
+        sample_ticker.next().await;
+    }
+}
+
+/// The devices on the expansion header, edit to match the devices that are connected, see [`ExpansionDevice`].
+const EXPANSION_DEVICES: &[ExpansionDevice] = &[
+    // MCP23017 port expander, A0-A2 low
+    ExpansionDevice {
+        bus: ExpansionBus::I2c {
+            address: 0x20,
+        },
+        kind: ExpansionDeviceKind::PortExpander,
+    },
+    // ADS1115 ADC, ADDR to ground
+    ExpansionDevice {
+        bus: ExpansionBus::I2c {
+            address: 0x48,
+        },
+        kind: ExpansionDeviceKind::Adc,
+    },
+    // 24LC256 EEPROM, A0-A2 low
+    ExpansionDevice {
+        bus: ExpansionBus::I2c {
+            address: 0x50,
+        },
+        kind: ExpansionDeviceKind::Eeprom {
+            page_size: 64,
+        },
+    },
+];
+
+type ExpansionRegistryInstance = ExpansionRegistry<
+    HalExpansionBuses<I2c<'static, Blocking, i2c::Master>, Spi<'static, Blocking, spi::Master>, Output<'static>, 1>,
+>;
+
+#[embassy_executor::task]
+async fn expansion_task(expansion_registry: ExpansionRegistryInstance) {
+    expansion_registry.run().await
+}
+
+type AnalogChannelsInstance = AnalogChannels<AdcAnalogInputs<'static, ADC3, 3>>;
+
+#[embassy_executor::task]
+async fn analog_task(analog_channels: AnalogChannelsInstance) {
+    analog_channels.run().await
+}
+
+/// The slots of the feeder bank, each with a connector to the ID EEPROM of the feeder.
+const FEEDER_SLOTS: u8 = 8;
+
+type FeederSlotMonitorInstance = FeederSlotMonitor<EepromFeederIds<I2c<'static, Blocking, i2c::Master>>>;
+
+#[embassy_executor::task]
+async fn feeder_slot_task(feeder_slot_monitor: FeederSlotMonitorInstance) {
+    feeder_slot_monitor.run().await
+}
+
+#[embassy_executor::task]
+async fn self_test_task(status: SelfTestStatus) {
+    ioboard_main::self_test::publish_self_test(status).await
+}
+
 type LedType = Mutex<ThreadModeRawMutex, Option<Output<'static>>>;
 static LED: LedType = Mutex::new(None);
 
@@ -298,28 +621,41 @@ async fn activity_indicator_task(led: &'static LedType, delay: Duration) {
 }
 
 type StepperInstance = GpioBitbashStepper<Output<'static>, Output<'static>, Output<'static>>;
+type LimitSwitchInstance = GpioLimitSwitch<Input<'static>>;
 #[embassy_executor::task]
-async fn stepper_task(runner: StepperRunner<StepperInstance>) {
+async fn stepper_task(runner: StepperRunner<StepperInstance, LimitSwitchInstance>) {
     runner.run().await
 }
 
-struct StepperRunner<STEPPER: Stepper> {
+/// Cancelling this interrupts any in-progress step burst, e.g. on e-stop.
+static STEPPER_CANCELLATION: StepperCancellation = StepperCancellation::new();
+
+struct StepperRunner<STEPPER: Stepper, SWITCH: LimitSwitch> {
     stepper: STEPPER,
+    limit_switch: SWITCH,
 }
 
-impl<STEPPER: Stepper> StepperRunner<STEPPER> {
-    pub fn new(stepper: STEPPER) -> Self {
+impl<STEPPER: Stepper, SWITCH: LimitSwitch> StepperRunner<STEPPER, SWITCH> {
+    pub fn new(stepper: STEPPER, limit_switch: SWITCH) -> Self {
         Self {
             stepper,
+            limit_switch,
         }
     }
 
     pub async fn run(self) {
         let Self {
             stepper,
+            limit_switch,
         } = self;
 
-        ioboard_main::run(stepper).await;
+        let axis_config = AxisConfig {
+            homing: Some(HomingConfig::default()),
+            // no driver thermal model for the bit-bashed driver, its current/duty characteristics are unknown
+            ..AxisConfig::default()
+        };
+        // FUTURE generate the step pulses with a timer and DMA, the bit-bashed step pin is timed by the 32kHz time driver
+        ioboard_main::run(stepper, SoftwarePulseGenerator, limit_switch, &STEPPER_CANCELLATION, axis_config).await;
     }
 }
 
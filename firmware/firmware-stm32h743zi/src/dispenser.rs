@@ -0,0 +1,29 @@
+use embedded_hal::digital::OutputPin;
+use ioboard_main::dispenser::DispenserOutputs;
+
+/// Dispenser outputs switched via active-high GPIO outputs, e.g. to the solenoid valves of a pneumatic dispenser or
+/// the forward and reverse inputs of an auger motor driver.
+pub struct GpioDispenserOutputs<PIN1, PIN2> {
+    pressure: PIN1,
+    retract: PIN2,
+}
+
+impl<PIN1, PIN2> GpioDispenserOutputs<PIN1, PIN2> {
+    pub fn new(pressure: PIN1, retract: PIN2) -> Self {
+        Self {
+            pressure,
+            retract,
+        }
+    }
+}
+
+impl<PIN1: OutputPin, PIN2: OutputPin> DispenserOutputs for GpioDispenserOutputs<PIN1, PIN2> {
+    fn set_pressure(&mut self, on: bool) {
+        // GPIO outputs on this platform are infallible
+        let _ = self.pressure.set_state(on.into());
+    }
+
+    fn set_retract(&mut self, on: bool) {
+        let _ = self.retract.set_state(on.into());
+    }
+}
@@ -0,0 +1,60 @@
+use embedded_hal::digital::OutputPin;
+use embedded_hal::i2c::I2c;
+use embedded_hal::spi::SpiBus;
+use ioboard_main::expansion::ExpansionBuses;
+use ioboard_shared::expansion::ExpansionError;
+
+/// An I2C bus and an SPI bus, with a GPIO output for the active-low chip select of each SPI device.
+pub struct HalExpansionBuses<I2C, SPI, CS, const CHIP_SELECTS: usize> {
+    i2c: I2C,
+    spi: SPI,
+    chip_selects: [CS; CHIP_SELECTS],
+}
+
+impl<I2C, SPI, CS: OutputPin, const CHIP_SELECTS: usize> HalExpansionBuses<I2C, SPI, CS, CHIP_SELECTS> {
+    /// The chip selects are released on creation.
+    pub fn new(i2c: I2C, spi: SPI, mut chip_selects: [CS; CHIP_SELECTS]) -> Self {
+        for chip_select in chip_selects.iter_mut() {
+            // GPIO outputs on this platform are infallible
+            let _ = chip_select.set_high();
+        }
+        Self {
+            i2c,
+            spi,
+            chip_selects,
+        }
+    }
+}
+
+impl<I2C: I2c, SPI: SpiBus, CS: OutputPin, const CHIP_SELECTS: usize> ExpansionBuses
+    for HalExpansionBuses<I2C, SPI, CS, CHIP_SELECTS>
+{
+    fn i2c_write(&mut self, address: u8, write: &[u8]) -> Result<(), ExpansionError> {
+        self.i2c
+            .write(address, write)
+            .map_err(|_| ExpansionError::Bus)
+    }
+
+    fn i2c_write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), ExpansionError> {
+        self.i2c
+            .write_read(address, write, read)
+            .map_err(|_| ExpansionError::Bus)
+    }
+
+    fn spi_transaction(&mut self, chip_select: u8, write: &[u8], read: &mut [u8]) -> Result<(), ExpansionError> {
+        let chip_select = self
+            .chip_selects
+            .get_mut(chip_select as usize)
+            .ok_or(ExpansionError::Bus)?;
+
+        let _ = chip_select.set_low();
+        let result = self
+            .spi
+            .write(write)
+            .and_then(|_| self.spi.read(read))
+            .and_then(|_| self.spi.flush());
+        let _ = chip_select.set_high();
+
+        result.map_err(|_| ExpansionError::Bus)
+    }
+}
@@ -0,0 +1,48 @@
+use embedded_hal::i2c::{Error, ErrorKind, I2c};
+use ioboard_main::feeder_slots::{FeederIdError, FeederIdReader};
+
+/// The address of the ID EEPROM of the feeder in the first slot, the address pins of the EEPROM are strapped by the
+/// slot, so the feeder in slot `n` is at `BASE_ADDRESS + n`.
+pub const BASE_ADDRESS: u8 = 0x50;
+
+/// The EUI-48 of a 24AA025E48 is stored in the write protected upper quarter of the array.
+const REG_EUI48: u8 = 0xFA;
+
+/// Feeders with a 24AA025E48 ID EEPROM, the identity of a feeder is its EUI-48, which is unique and can't be erased.
+pub struct EepromFeederIds<I2C> {
+    i2c: I2C,
+    slots: u8,
+}
+
+impl<I2C: I2c> EepromFeederIds<I2C> {
+    /// At most 8 slots, the EEPROM has 3 address pins.
+    pub fn new(i2c: I2C, slots: u8) -> Self {
+        Self {
+            i2c,
+            slots: slots.min(8),
+        }
+    }
+}
+
+impl<I2C: I2c> FeederIdReader for EepromFeederIds<I2C> {
+    fn slots(&self) -> u8 {
+        self.slots
+    }
+
+    fn read_identity(&mut self, slot: u8) -> Result<Option<u64>, FeederIdError> {
+        let mut eui48 = [0; 6];
+        match self
+            .i2c
+            .write_read(BASE_ADDRESS + slot, &[REG_EUI48], &mut eui48)
+        {
+            Ok(()) => {}
+            // nothing at the address, the slot is empty
+            Err(e) if matches!(e.kind(), ErrorKind::NoAcknowledge(_)) => return Ok(None),
+            Err(_) => return Err(FeederIdError::Bus),
+        }
+
+        let mut identity = [0; 8];
+        identity[2..].copy_from_slice(&eui48);
+        Ok(Some(u64::from_be_bytes(identity)))
+    }
+}
@@ -1,7 +1,18 @@
 #![no_std]
 #![no_main]
 
+pub mod accelerometer;
+pub mod analog;
+pub mod dispenser;
+pub mod expansion;
+pub mod feeder_id;
+pub mod limit_switch;
+pub mod power;
+pub mod probe;
+pub mod safety;
 pub mod stepper;
+pub mod supply;
+pub mod vacuum;
 #[cfg(feature = "tracepin")]
 pub mod trace;
 
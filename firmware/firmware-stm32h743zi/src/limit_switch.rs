@@ -0,0 +1,24 @@
+use embedded_hal::digital::InputPin;
+use ioboard_main::homing::LimitSwitch;
+
+/// An endstop switch that is low when triggered, a normally-open switch to ground with a pull-up.
+///
+/// The switch is polled before each homing step, so any input will do.
+pub struct GpioLimitSwitch<PIN> {
+    pin: PIN,
+}
+
+impl<PIN> GpioLimitSwitch<PIN> {
+    pub fn new(pin: PIN) -> Self {
+        Self {
+            pin,
+        }
+    }
+}
+
+impl<PIN: InputPin> LimitSwitch for GpioLimitSwitch<PIN> {
+    fn is_triggered(&mut self) -> bool {
+        // GPIO inputs on this platform are infallible
+        self.pin.is_low().unwrap_or(false)
+    }
+}
@@ -0,0 +1,31 @@
+use embedded_hal::digital::OutputPin;
+use ioboard_main::power::PowerRails;
+use ioboard_shared::power::PowerRail;
+
+/// Power rails switched via active-high GPIO outputs, e.g. to MOSFET or relay drivers.
+pub struct GpioPowerRails<PIN1, PIN2, PIN3> {
+    motor_power: PIN1,
+    vacuum_pump: PIN2,
+    lighting: PIN3,
+}
+
+impl<PIN1, PIN2, PIN3> GpioPowerRails<PIN1, PIN2, PIN3> {
+    pub fn new(motor_power: PIN1, vacuum_pump: PIN2, lighting: PIN3) -> Self {
+        Self {
+            motor_power,
+            vacuum_pump,
+            lighting,
+        }
+    }
+}
+
+impl<PIN1: OutputPin, PIN2: OutputPin, PIN3: OutputPin> PowerRails for GpioPowerRails<PIN1, PIN2, PIN3> {
+    fn set_rail(&mut self, rail: PowerRail, enabled: bool) {
+        // GPIO outputs on this platform are infallible
+        let _ = match rail {
+            PowerRail::MotorPower => self.motor_power.set_state(enabled.into()),
+            PowerRail::VacuumPump => self.vacuum_pump.set_state(enabled.into()),
+            PowerRail::Lighting => self.lighting.set_state(enabled.into()),
+        };
+    }
+}
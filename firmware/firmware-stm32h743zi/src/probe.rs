@@ -0,0 +1,35 @@
+use embedded_hal::digital::InputPin;
+use embedded_hal_async::digital::Wait;
+use ioboard_main::probe::ProbeInput;
+
+/// A probe input that is low when triggered, e.g. a touch probe, or a conductive nozzle touching a plate, to ground with
+/// a pull-up.
+///
+/// The pin must wake on its edges, e.g. an EXTI input, so that the position is latched at the edge, not on the next
+/// poll.
+pub struct GpioProbeInput<PIN> {
+    pin: PIN,
+}
+
+impl<PIN> GpioProbeInput<PIN> {
+    pub fn new(pin: PIN) -> Self {
+        Self {
+            pin,
+        }
+    }
+}
+
+impl<PIN: InputPin + Wait> ProbeInput for GpioProbeInput<PIN> {
+    async fn wait_for_trigger(&mut self) {
+        // GPIO inputs on this platform are infallible
+        let _ = self.pin.wait_for_low().await;
+    }
+
+    async fn wait_for_release(&mut self) {
+        let _ = self.pin.wait_for_high().await;
+    }
+
+    fn is_triggered(&mut self) -> bool {
+        self.pin.is_low().unwrap_or(false)
+    }
+}
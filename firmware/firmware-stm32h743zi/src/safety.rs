@@ -0,0 +1,28 @@
+use embedded_hal::digital::InputPin;
+use ioboard_main::safety::SafetyInputs;
+use ioboard_shared::safety::SafetyInput;
+
+/// Safety inputs via GPIO inputs that are high when tripped, e.g. a normally-closed door switch to ground with a
+/// pull-up, so that a disconnected switch is also tripped.
+pub struct GpioSafetyInputs<PIN1, PIN2> {
+    door: PIN1,
+    light_curtain: PIN2,
+}
+
+impl<PIN1, PIN2> GpioSafetyInputs<PIN1, PIN2> {
+    pub fn new(door: PIN1, light_curtain: PIN2) -> Self {
+        Self {
+            door,
+            light_curtain,
+        }
+    }
+}
+
+impl<PIN1: InputPin, PIN2: InputPin> SafetyInputs for GpioSafetyInputs<PIN1, PIN2> {
+    fn is_tripped(&mut self, input: SafetyInput) -> Option<bool> {
+        match input {
+            SafetyInput::Door => self.door.is_high().ok(),
+            SafetyInput::LightCurtain => self.light_curtain.is_high().ok(),
+        }
+    }
+}
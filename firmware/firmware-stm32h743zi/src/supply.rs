@@ -0,0 +1,45 @@
+use embassy_stm32::adc;
+use embassy_stm32::adc::{Adc, AdcChannel, BasicAdcRegs, BasicInstance};
+use ioboard_main::power::SupplySensor;
+
+/// Measures the supply voltage via a resistor divider connected to an ADC input.
+pub struct AdcSupplySensor<'a, ADC, IN>
+where
+    ADC: adc::Instance,
+    <ADC as BasicInstance>::Regs: BasicAdcRegs,
+    IN: AdcChannel<ADC>,
+{
+    adc: Adc<'a, ADC>,
+    input: IN,
+    sample_time: <<ADC as BasicInstance>::Regs as BasicAdcRegs>::SampleTime,
+    /// volts per ADC count, including the divider ratio
+    scale: f32,
+}
+
+impl<'a, ADC: adc::Instance + adc::BasicInstance, IN: AdcChannel<ADC>> AdcSupplySensor<'a, ADC, IN> {
+    /// `divider_ratio` is the supply voltage divided by the voltage at the ADC input.
+    pub fn new(
+        adc: Adc<'a, ADC>,
+        input: IN,
+        sample_time: <<ADC as BasicInstance>::Regs as BasicAdcRegs>::SampleTime,
+        reference_voltage: f32,
+        adc_max: f32,
+        divider_ratio: f32,
+    ) -> Self {
+        Self {
+            adc,
+            input,
+            sample_time,
+            scale: reference_voltage / adc_max * divider_ratio,
+        }
+    }
+}
+
+impl<'a, ADC: adc::Instance + adc::BasicInstance, IN: AdcChannel<ADC>> SupplySensor for AdcSupplySensor<'a, ADC, IN> {
+    fn read_voltage(&mut self) -> Option<f32> {
+        let raw = self
+            .adc
+            .blocking_read(&mut self.input, self.sample_time);
+        Some(raw as f32 * self.scale)
+    }
+}
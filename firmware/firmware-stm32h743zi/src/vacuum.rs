@@ -0,0 +1,75 @@
+use embassy_stm32::adc;
+use embassy_stm32::adc::{Adc, AdcChannel, BasicAdcRegs, BasicInstance};
+use embedded_hal::pwm::SetDutyCycle;
+use ioboard_main::vacuum::{PumpOutput, VacuumSensor};
+
+/// Analog vacuum sensor with a linear output, connected to an ADC input.
+pub struct AdcVacuumSensor<'a, ADC, IN>
+where
+    ADC: adc::Instance,
+    <ADC as BasicInstance>::Regs: BasicAdcRegs,
+    IN: AdcChannel<ADC>,
+{
+    adc: Adc<'a, ADC>,
+    input: IN,
+    sample_time: <<ADC as BasicInstance>::Regs as BasicAdcRegs>::SampleTime,
+    /// volts per ADC count
+    scale: f32,
+    /// sensor output voltage at ambient pressure
+    zero_voltage: f32,
+    kpa_per_volt: f32,
+}
+
+impl<'a, ADC: adc::Instance + adc::BasicInstance, IN: AdcChannel<ADC>> AdcVacuumSensor<'a, ADC, IN> {
+    pub fn new(
+        adc: Adc<'a, ADC>,
+        input: IN,
+        sample_time: <<ADC as BasicInstance>::Regs as BasicAdcRegs>::SampleTime,
+        reference_voltage: f32,
+        adc_max: f32,
+        zero_voltage: f32,
+        kpa_per_volt: f32,
+    ) -> Self {
+        Self {
+            adc,
+            input,
+            sample_time,
+            scale: reference_voltage / adc_max,
+            zero_voltage,
+            kpa_per_volt,
+        }
+    }
+}
+
+impl<'a, ADC: adc::Instance + adc::BasicInstance, IN: AdcChannel<ADC>> VacuumSensor for AdcVacuumSensor<'a, ADC, IN> {
+    fn read_vacuum(&mut self) -> Option<f32> {
+        let raw = self
+            .adc
+            .blocking_read(&mut self.input, self.sample_time);
+        let voltage = raw as f32 * self.scale;
+        Some((voltage - self.zero_voltage) * self.kpa_per_volt)
+    }
+}
+
+/// Pump driven by a PWM output, e.g. via a MOSFET.
+pub struct PwmPumpOutput<PWM> {
+    pwm: PWM,
+}
+
+impl<PWM: SetDutyCycle> PwmPumpOutput<PWM> {
+    pub fn new(pwm: PWM) -> Self {
+        Self {
+            pwm,
+        }
+    }
+}
+
+impl<PWM: SetDutyCycle> PumpOutput for PwmPumpOutput<PWM> {
+    fn set_duty(&mut self, duty: f32) {
+        let permille = (duty.clamp(0.0, 1.0) * 1000.0) as u16;
+        // PWM outputs on this platform are infallible
+        let _ = self
+            .pwm
+            .set_duty_cycle_fraction(permille, 1000);
+    }
+}
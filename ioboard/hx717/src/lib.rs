@@ -0,0 +1,181 @@
+//! A driver for the HX717, a 24 bit ADC for load cells, read via its serial interface, PD_SCK and DOUT, see
+//! [`Hx717`], and the calibration of a load cell, see [`Calibration`].
+//!
+//! The HX717 converts continuously, DOUT goes low once a conversion is ready, it's then read by clocking 24 bits out of
+//! DOUT, MSB first.  The pulses after the 24th select the channel and the rate of the next conversion, see [`Rate`].
+//!
+//! Keeping PD_SCK high for more than 60us powers the HX717 down, so the pulses are clocked in a critical section.
+
+#![no_std]
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+
+#[cfg(test)]
+mod tests;
+
+/// The bits of a conversion.
+const CONVERSION_BITS: u8 = 24;
+
+/// Keeping PD_SCK high for longer powers the HX717 down.
+const POWER_DOWN_US: u32 = 60;
+
+/// The rate of the conversions, of channel A, with a gain of 128.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rate {
+    Hz10,
+    Hz40,
+    #[default]
+    Hz320,
+}
+
+impl Rate {
+    /// The pulses of a read, the pulses after the 24th select the rate of the next conversion, 26 pulses would select
+    /// channel B, the supply voltage, which is not used.
+    const fn pulses(&self) -> u8 {
+        match self {
+            Rate::Hz10 => 25,
+            Rate::Hz40 => 27,
+            Rate::Hz320 => 28,
+        }
+    }
+
+    pub const fn hz(&self) -> u16 {
+        match self {
+            Rate::Hz10 => 10,
+            Rate::Hz40 => 40,
+            Rate::Hz320 => 320,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// PD_SCK or DOUT could not be set or read
+    Pin,
+    /// no conversion is ready, DOUT is high
+    NotReady,
+}
+
+pub struct Hx717<SCK, DOUT, DELAY> {
+    sck: SCK,
+    dout: DOUT,
+    delay: DELAY,
+    rate: Rate,
+}
+
+impl<SCK, DOUT, DELAY> Hx717<SCK, DOUT, DELAY>
+where
+    SCK: OutputPin,
+    DOUT: InputPin,
+    DELAY: DelayNs,
+{
+    /// The HX717 is powered up by setting PD_SCK low, the rate is set by the first read, the first conversion is at
+    /// the rate the HX717 was left at, 10Hz after a power-on reset.
+    pub fn new(mut sck: SCK, dout: DOUT, delay: DELAY, rate: Rate) -> Result<Self, Error> {
+        sck.set_low().map_err(|_e| Error::Pin)?;
+        Ok(Self {
+            sck,
+            dout,
+            delay,
+            rate,
+        })
+    }
+
+    pub fn rate(&self) -> Rate {
+        self.rate
+    }
+
+    pub fn is_ready(&mut self) -> Result<bool, Error> {
+        self.dout
+            .is_low()
+            .map_err(|_e| Error::Pin)
+    }
+
+    /// Reads the conversion that is ready, sign extended, the next conversion is made at the [`Rate`].
+    pub fn read(&mut self) -> Result<i32, Error> {
+        if !self.is_ready()? {
+            return Err(Error::NotReady);
+        }
+
+        let mut value = 0_u32;
+        for pulse in 0..self.rate.pulses() {
+            // a pulse of PD_SCK is about 2us, interrupting it would power the HX717 down
+            let bit = critical_section::with(|_cs| {
+                self.sck
+                    .set_high()
+                    .map_err(|_e| Error::Pin)?;
+                self.delay.delay_us(1);
+                let bit = self
+                    .dout
+                    .is_high()
+                    .map_err(|_e| Error::Pin);
+                self.sck
+                    .set_low()
+                    .map_err(|_e| Error::Pin)?;
+                bit
+            })?;
+            self.delay.delay_us(1);
+
+            if pulse < CONVERSION_BITS {
+                value = (value << 1) | bit as u32;
+            }
+        }
+        Ok(sign_extend(value))
+    }
+
+    /// The HX717 stops converting until [`Hx717::power_up`].
+    pub fn power_down(&mut self) -> Result<(), Error> {
+        self.sck
+            .set_high()
+            .map_err(|_e| Error::Pin)?;
+        self.delay.delay_us(POWER_DOWN_US + 1);
+        Ok(())
+    }
+
+    /// The HX717 is reset, the first conversion is at 10Hz, the rate is set again by the first read.
+    pub fn power_up(&mut self) -> Result<(), Error> {
+        self.sck
+            .set_low()
+            .map_err(|_e| Error::Pin)
+    }
+}
+
+/// The conversions are 24 bits, two's complement.
+pub const fn sign_extend(value: u32) -> i32 {
+    ((value << 8) as i32) >> 8
+}
+
+/// The calibration of a load cell, the offset is the conversion at no load, the scale is the grams per count of the
+/// conversion, from a reference weight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub offset: i32,
+    pub grams_per_count: f32,
+}
+
+impl Calibration {
+    /// Not tared.
+    pub const fn new(grams_per_count: f32) -> Self {
+        Self {
+            offset: 0,
+            grams_per_count,
+        }
+    }
+
+    /// Sets the offset to the mean of the conversions at no load, the offset is unchanged without conversions.
+    pub fn tare(&mut self, conversions: &[i32]) {
+        if conversions.is_empty() {
+            return;
+        }
+        let sum: i64 = conversions
+            .iter()
+            .map(|conversion| *conversion as i64)
+            .sum();
+        self.offset = (sum / conversions.len() as i64) as i32;
+    }
+
+    pub fn grams(&self, conversion: i32) -> f32 {
+        (conversion as i64 - self.offset as i64) as f32 * self.grams_per_count
+    }
+}
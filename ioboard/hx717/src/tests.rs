@@ -0,0 +1,37 @@
+use crate::{Calibration, sign_extend};
+
+#[test]
+pub fn conversions_are_sign_extended() {
+    assert_eq!(sign_extend(0x00_0000), 0);
+    assert_eq!(sign_extend(0x7F_FFFF), 8_388_607);
+    assert_eq!(sign_extend(0x80_0000), -8_388_608);
+    assert_eq!(sign_extend(0xFF_FFFF), -1);
+}
+
+#[test]
+pub fn the_tare_is_the_mean_of_the_conversions() {
+    // given
+    let mut calibration = Calibration::new(0.5);
+
+    // when
+    calibration.tare(&[-100, 1_000, 2_100]);
+
+    // then
+    assert_eq!(calibration.offset, 1_000);
+    assert_eq!(calibration.grams(1_000), 0.0);
+    assert_eq!(calibration.grams(1_200), 100.0);
+    assert_eq!(calibration.grams(800), -100.0);
+}
+
+#[test]
+pub fn the_offset_is_kept_without_conversions() {
+    // given
+    let mut calibration = Calibration::new(1.0);
+    calibration.tare(&[42]);
+
+    // when
+    calibration.tare(&[]);
+
+    // then
+    assert_eq!(calibration.offset, 42);
+}
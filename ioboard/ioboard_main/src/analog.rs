@@ -0,0 +1,185 @@
+//! Analog input channels, for ad-hoc sensors, e.g. flow, pressure or a potentiometer, wired to the ADC inputs of the
+//! io board without firmware changes.
+//!
+//! The channels are configured via [`AnalogRequest`]s, each channel has the input, the scaling and the units of the
+//! sensor.  A configured channel is sampled at its own interval and each sample is published as an [`AnalogReading`].
+//! The configuration is not persisted, the channels are configured again after the io board restarts.
+
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_time::{Duration, Instant, Ticker};
+use ioboard_net::ANALOG_REQUESTS;
+use ioboard_shared::analog::{
+    ANALOG_CHANNELS_MAX, ANALOG_SAMPLE_INTERVAL_MIN_MS, AnalogChannelConfig, AnalogError, AnalogReading, AnalogReply,
+    AnalogRequest, AnalogResponse,
+};
+
+/// The channels that are due are sampled at this interval, the shortest sample interval of a channel.
+const TICK_INTERVAL: Duration = Duration::from_millis(ANALOG_SAMPLE_INTERVAL_MIN_MS as u64);
+
+/// The ADC inputs that are available for analog channels, inputs are addressed by index, `0..input_count()`.
+pub trait AnalogInputs {
+    fn input_count(&self) -> u8;
+    /// The raw value at the full scale of the ADC, e.g. 65535 for a 16 bit ADC.
+    fn full_scale(&self) -> u16;
+    /// Returns the raw value, or `None` if it could not be read.
+    fn read(&mut self, input: u8) -> Option<u16>;
+}
+
+/// For io boards without spare ADC inputs, every channel is refused.
+pub struct NoAnalogInputs;
+
+impl AnalogInputs for NoAnalogInputs {
+    fn input_count(&self) -> u8 {
+        0
+    }
+
+    fn full_scale(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn read(&mut self, _input: u8) -> Option<u16> {
+        None
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Channel {
+    config: AnalogChannelConfig,
+    next_sample_at: Instant,
+}
+
+pub struct AnalogChannels<INPUTS: AnalogInputs> {
+    inputs: INPUTS,
+    channels: [Option<Channel>; ANALOG_CHANNELS_MAX],
+}
+
+impl<INPUTS: AnalogInputs> AnalogChannels<INPUTS> {
+    /// No channels are configured on creation.
+    pub fn new(inputs: INPUTS) -> Self {
+        Self {
+            inputs,
+            channels: [None; ANALOG_CHANNELS_MAX],
+        }
+    }
+
+    fn validate(&self, config: &AnalogChannelConfig) -> Result<(), AnalogError> {
+        if config.input >= self.inputs.input_count() {
+            return Err(AnalogError::InvalidInput(config.input));
+        }
+        if config.sample_interval_ms < ANALOG_SAMPLE_INTERVAL_MIN_MS {
+            return Err(AnalogError::InvalidSampleInterval);
+        }
+        if !config.scale.is_finite() || !config.offset.is_finite() {
+            return Err(AnalogError::InvalidScaling);
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: AnalogRequest, now: Instant) -> AnalogResponse {
+        match request {
+            AnalogRequest::Channels => {
+                let mut channels = [None; ANALOG_CHANNELS_MAX];
+                for (entry, channel) in channels
+                    .iter_mut()
+                    .zip(self.channels.iter())
+                {
+                    *entry = channel.map(|channel| channel.config);
+                }
+                Ok(AnalogReply::Channels {
+                    channels,
+                    inputs: self.inputs.input_count(),
+                })
+            }
+            AnalogRequest::Configure {
+                channel,
+                config,
+            } => {
+                if channel as usize >= ANALOG_CHANNELS_MAX {
+                    return Err(AnalogError::InvalidChannel(channel));
+                }
+                self.validate(&config)?;
+                info!(
+                    "Analog channel configured. channel: {}, name: {}, input: {}, units: {}, interval: {}ms",
+                    channel,
+                    config.name.as_str(),
+                    config.input,
+                    config.units.as_str(),
+                    config.sample_interval_ms
+                );
+                self.channels[channel as usize] = Some(Channel {
+                    config,
+                    next_sample_at: now,
+                });
+                Ok(AnalogReply::Configured)
+            }
+            AnalogRequest::Remove {
+                channel,
+            } => {
+                let entry = self
+                    .channels
+                    .get_mut(channel as usize)
+                    .ok_or(AnalogError::InvalidChannel(channel))?;
+                if entry.take().is_some() {
+                    info!("Analog channel removed. channel: {}", channel);
+                }
+                Ok(AnalogReply::Removed)
+            }
+        }
+    }
+
+    /// Samples and publishes the channels that are due.
+    fn sample(&mut self, now: Instant) {
+        let full_scale = self.inputs.full_scale().max(1) as f32;
+        for (index, entry) in self.channels.iter_mut().enumerate() {
+            let Some(channel) = entry else {
+                continue;
+            };
+            if channel.next_sample_at > now {
+                continue;
+            }
+
+            let interval = Duration::from_millis(channel.config.sample_interval_ms as u64);
+            channel.next_sample_at += interval;
+            // a late sample isn't made up for, e.g. while a request was handled
+            if channel.next_sample_at <= now {
+                channel.next_sample_at = now + interval;
+            }
+
+            let Some(raw) = self.inputs.read(channel.config.input) else {
+                warn!(
+                    "Unable to read analog input. channel: {}, input: {}",
+                    index, channel.config.input
+                );
+                continue;
+            };
+            let reading = AnalogReading {
+                channel: index as u8,
+                name: channel.config.name,
+                units: channel.config.units,
+                value: channel
+                    .config
+                    .value(raw as f32 / full_scale),
+                raw,
+            };
+            ioboard_net::publish_analog(&reading);
+        }
+    }
+
+    /// Handle requests from the analog endpoint, and sample the configured channels.
+    pub async fn run(mut self) -> ! {
+        let mut ticker = Ticker::every(TICK_INTERVAL);
+        loop {
+            match select(ANALOG_REQUESTS.receive(), ticker.next()).await {
+                Either::First(request) => {
+                    let response = self.handle_request(request, Instant::now());
+                    if let Err(error) = &response {
+                        warn!("Analog request failed. request: {}, error: {}", request, error);
+                    }
+                    ANALOG_REQUESTS.respond(response).await;
+                }
+                Either::Second(_) => self.sample(Instant::now()),
+            }
+        }
+    }
+}
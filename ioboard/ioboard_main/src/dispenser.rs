@@ -0,0 +1,161 @@
+//! Paste and glue dispenser outputs.
+//!
+//! The server times the dispense cycle, pre-pressure, dispense and retract, using [`DispenserRequest`]s.  An output
+//! that stays on for longer than the configured maximum is switched off, so that a lost request or a server that
+//! stopped responding can't empty the syringe onto the board.
+
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_time::{Duration, Instant, Ticker};
+use ioboard_net::DISPENSER_REQUESTS;
+use ioboard_shared::dispenser::{DispenserError, DispenserRequest, DispenserResponse, DispenserStatus};
+
+/// Pneumatic dispensers have a pressure valve and a suck-back vacuum valve, auger dispensers have a motor that runs
+/// forward to dispense and in reverse to retract.
+pub trait DispenserOutputs {
+    /// Returns `false` if the io board has no dispenser, the requests are then rejected.
+    fn is_present(&self) -> bool {
+        true
+    }
+    fn set_pressure(&mut self, on: bool);
+    /// never called with `on` while the pressure is on
+    fn set_retract(&mut self, on: bool);
+}
+
+/// For machines without a dispenser.
+pub struct NoDispenser;
+
+impl DispenserOutputs for NoDispenser {
+    fn is_present(&self) -> bool {
+        false
+    }
+
+    fn set_pressure(&mut self, _on: bool) {}
+
+    fn set_retract(&mut self, _on: bool) {}
+}
+
+/// How often the time the outputs have been on is checked.
+const TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy)]
+pub struct DispenserConfig {
+    /// the longest an output may stay on, longer than the longest dispense cycle of the machine
+    pub max_on: Duration,
+}
+
+impl Default for DispenserConfig {
+    fn default() -> Self {
+        Self {
+            max_on: Duration::from_secs(10),
+        }
+    }
+}
+
+pub struct DispenserController<OUTPUTS: DispenserOutputs> {
+    outputs: OUTPUTS,
+    config: DispenserConfig,
+    /// when the output that is on was started
+    on_since: Option<Instant>,
+    status: DispenserStatus,
+}
+
+impl<OUTPUTS: DispenserOutputs> DispenserController<OUTPUTS> {
+    /// All outputs are switched off on creation.
+    pub fn new(mut outputs: OUTPUTS, config: DispenserConfig) -> Self {
+        outputs.set_pressure(false);
+        outputs.set_retract(false);
+
+        Self {
+            outputs,
+            config,
+            on_since: None,
+            status: DispenserStatus::default(),
+        }
+    }
+
+    fn handle_request(&mut self, request: DispenserRequest) -> DispenserResponse {
+        if !self.outputs.is_present() {
+            return Err(DispenserError::NoDispenser);
+        }
+
+        match request {
+            DispenserRequest::StartPressure => {
+                info!("Dispenser pressure on");
+                self.set_retract(false);
+                self.set_pressure(true);
+                self.start_timeout();
+            }
+            DispenserRequest::StopPressure => {
+                info!("Dispenser pressure off");
+                self.set_pressure(false);
+            }
+            DispenserRequest::StartRetract => {
+                info!("Dispenser retract on");
+                self.set_pressure(false);
+                self.set_retract(true);
+                self.start_timeout();
+            }
+            DispenserRequest::StopRetract => {
+                info!("Dispenser retract off");
+                self.set_retract(false);
+            }
+            DispenserRequest::Status => {}
+        }
+        Ok(self.status)
+    }
+
+    fn set_pressure(&mut self, on: bool) {
+        self.outputs.set_pressure(on);
+        self.status.pressure = on;
+    }
+
+    fn set_retract(&mut self, on: bool) {
+        self.outputs.set_retract(on);
+        self.status.retract = on;
+    }
+
+    fn start_timeout(&mut self) {
+        self.on_since = Some(Instant::now());
+        self.status.timed_out = false;
+    }
+
+    fn check_timeout(&mut self) {
+        if !self.status.pressure && !self.status.retract {
+            self.on_since = None;
+            return;
+        }
+        let Some(on_since) = self.on_since else {
+            return;
+        };
+        if on_since.elapsed() > self.config.max_on {
+            warn!(
+                "Dispenser output on for too long, switching off. pressure: {}, retract: {}",
+                self.status.pressure, self.status.retract
+            );
+            self.set_pressure(false);
+            self.set_retract(false);
+            self.on_since = None;
+            self.status.timed_out = true;
+        }
+    }
+
+    /// Handle requests from the dispenser endpoint, and switch off outputs that are on for too long.
+    pub async fn run(mut self) -> ! {
+        let mut ticker = Ticker::every(TIMEOUT_CHECK_INTERVAL);
+        loop {
+            match select(DISPENSER_REQUESTS.receive(), ticker.next()).await {
+                Either::First(request) => {
+                    let response = self.handle_request(request);
+                    if let Err(error) = &response {
+                        warn!("Dispenser request failed. request: {}, error: {}", request, error);
+                    }
+                    DISPENSER_REQUESTS
+                        .respond(response)
+                        .await;
+                }
+                Either::Second(_) => self.check_timeout(),
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Enabling and disabling the stepper driver of an axis, and reducing its hold current, see [`DriverRequest`], so that
+//! an idle axis can be released or kept cool without power-cycling the io board.
+//!
+//! The requests are handled by the motion task of the axis while the axis is at rest.  A disabled axis is enabled
+//! again before it's moved or homed, its position is not tracked while it's disabled.
+
+use defmt::{info, warn};
+use ioboard_net::DRIVER_REQUESTS;
+use ioboard_shared::driver::{AxisDriverError, DriverRequest, DriverResponse};
+
+use crate::stepper::{Stepper, StepperError};
+
+pub struct AxisDriver {
+    axis: u8,
+    /// disabled by a request, the driver is enabled by the motion task before the [`AxisDriver`] is created
+    disabled: bool,
+    /// as a fraction of the run current, 0.0-1.0
+    hold_current: f32,
+}
+
+impl AxisDriver {
+    pub fn new(axis: u8) -> Self {
+        Self {
+            axis,
+            disabled: false,
+            hold_current: 1.0,
+        }
+    }
+
+    /// Executes the request and responds to it.
+    pub async fn handle(&mut self, stepper: &mut impl Stepper, request: DriverRequest) {
+        let response = self.execute(stepper, request);
+        if let Err(e) = &response {
+            warn!("Driver request failed, request: {}, error: {}", request, e);
+        }
+        DRIVER_REQUESTS.respond(response).await;
+    }
+
+    fn execute(&mut self, stepper: &mut impl Stepper, request: DriverRequest) -> DriverResponse {
+        let axis = match request {
+            DriverRequest::Enable {
+                axis,
+            }
+            | DriverRequest::Disable {
+                axis,
+            }
+            | DriverRequest::SetHoldCurrentReduction {
+                axis, ..
+            } => axis,
+        };
+        if axis != self.axis {
+            return Err(AxisDriverError::InvalidAxis);
+        }
+
+        match request {
+            DriverRequest::Enable {
+                ..
+            } => {
+                stepper.enable().map_err(driver_error)?;
+                self.disabled = false;
+                info!("Axis driver enabled, axis: {}", self.axis);
+            }
+            DriverRequest::Disable {
+                ..
+            } => {
+                stepper
+                    .disable()
+                    .map_err(driver_error)?;
+                self.disabled = true;
+                info!("Axis driver disabled, axis: {}", self.axis);
+            }
+            DriverRequest::SetHoldCurrentReduction {
+                hold_percent, ..
+            } => {
+                if hold_percent > 100 {
+                    return Err(AxisDriverError::InvalidHoldCurrent);
+                }
+                stepper
+                    .set_hold_current_reduction(hold_percent)
+                    .map_err(driver_error)?;
+                self.hold_current = hold_percent as f32 / 100.0;
+                info!("Axis hold current set, axis: {}, hold: {}%", self.axis, hold_percent);
+            }
+        }
+        Ok(())
+    }
+
+    /// Enables the driver if it was disabled by a request, called before the axis is moved or homed.
+    pub fn engage(&mut self, stepper: &mut impl Stepper) -> Result<(), StepperError> {
+        if self.disabled {
+            stepper.enable()?;
+            self.disabled = false;
+            info!("Axis driver enabled to move, axis: {}", self.axis);
+        }
+        Ok(())
+    }
+
+    /// The current of the driver, as a fraction of the run current, see [`ThermalModel`](crate::thermal::ThermalModel).
+    pub fn current(&self, moving: bool) -> f32 {
+        match (self.disabled, moving) {
+            (true, _) => 0.0,
+            (false, true) => 1.0,
+            (false, false) => self.hold_current,
+        }
+    }
+}
+
+fn driver_error(error: StepperError) -> AxisDriverError {
+    match error {
+        StepperError::Unsupported => AxisDriverError::Unsupported,
+        _ => AxisDriverError::Driver,
+    }
+}
@@ -0,0 +1,336 @@
+//! Devices on the I2C and SPI buses of the expansion header, e.g. port expanders, ADCs and EEPROMs, so that new
+//! sensors can be prototyped before they have a dedicated driver.
+//!
+//! The devices are listed in the device table of the io board, a driver is registered for each device at boot, and
+//! their registers are read and written via [`ExpansionRequest`]s.  Devices that did not respond at boot are listed,
+//! but not accessed.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use defmt::{info, warn};
+use ioboard_net::EXPANSION_REQUESTS;
+use ioboard_shared::expansion::{
+    EXPANSION_DATA_MAX, EXPANSION_DEVICES_MAX, ExpansionBus, ExpansionData, ExpansionDevice, ExpansionDeviceInfo,
+    ExpansionDeviceKind, ExpansionError, ExpansionReply, ExpansionRequest, ExpansionResponse,
+};
+
+/// SPI registers are read with the top bit of the register address set, the convention of most SPI sensors.
+const SPI_READ_FLAG: u8 = 0x80;
+
+/// An EEPROM does not acknowledge its address until the write cycle has finished, at most 5ms, ~100us per poll at
+/// 400kHz.
+const EEPROM_WRITE_POLLS: usize = 100;
+
+/// The buses of the expansion header.
+pub trait ExpansionBuses {
+    fn i2c_write(&mut self, address: u8, write: &[u8]) -> Result<(), ExpansionError>;
+    fn i2c_write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), ExpansionError>;
+    /// The chip select is held for the whole transaction, `read` is filled after `write` has been sent.
+    fn spi_transaction(&mut self, chip_select: u8, write: &[u8], read: &mut [u8]) -> Result<(), ExpansionError>;
+}
+
+/// For io boards without an expansion header.
+pub struct NoExpansionBuses;
+
+impl ExpansionBuses for NoExpansionBuses {
+    fn i2c_write(&mut self, _address: u8, _write: &[u8]) -> Result<(), ExpansionError> {
+        Err(ExpansionError::Bus)
+    }
+
+    fn i2c_write_read(&mut self, _address: u8, _write: &[u8], _read: &mut [u8]) -> Result<(), ExpansionError> {
+        Err(ExpansionError::Bus)
+    }
+
+    fn spi_transaction(&mut self, _chip_select: u8, _write: &[u8], _read: &mut [u8]) -> Result<(), ExpansionError> {
+        Err(ExpansionError::Bus)
+    }
+}
+
+/// How the registers of a kind of device are addressed.
+pub trait ExpansionDriver {
+    /// Returns `false` if the device did not respond.
+    fn probe(&mut self, buses: &mut dyn ExpansionBuses) -> bool;
+    fn read(&mut self, buses: &mut dyn ExpansionBuses, register: u16, read: &mut [u8]) -> Result<(), ExpansionError>;
+    fn write(&mut self, buses: &mut dyn ExpansionBuses, register: u16, data: &[u8]) -> Result<(), ExpansionError>;
+}
+
+/// Devices with 8-bit register addresses, consecutive registers are read or written by a single transfer.
+pub struct RegisterDriver {
+    bus: ExpansionBus,
+}
+
+impl RegisterDriver {
+    pub fn new(bus: ExpansionBus) -> Self {
+        Self {
+            bus,
+        }
+    }
+}
+
+impl ExpansionDriver for RegisterDriver {
+    fn probe(&mut self, buses: &mut dyn ExpansionBuses) -> bool {
+        match self.bus {
+            ExpansionBus::I2c {
+                address,
+            } => buses
+                .i2c_write_read(address, &[0], &mut [0])
+                .is_ok(),
+            // an SPI device can't be detected without knowing its identification register
+            ExpansionBus::Spi {
+                ..
+            } => true,
+        }
+    }
+
+    fn read(&mut self, buses: &mut dyn ExpansionBuses, register: u16, read: &mut [u8]) -> Result<(), ExpansionError> {
+        let register = u8::try_from(register).map_err(|_| ExpansionError::InvalidRegister)?;
+        match self.bus {
+            ExpansionBus::I2c {
+                address,
+            } => buses.i2c_write_read(address, &[register], read),
+            ExpansionBus::Spi {
+                chip_select,
+            } => buses.spi_transaction(chip_select, &[register | SPI_READ_FLAG], read),
+        }
+    }
+
+    fn write(&mut self, buses: &mut dyn ExpansionBuses, register: u16, data: &[u8]) -> Result<(), ExpansionError> {
+        let register = u8::try_from(register).map_err(|_| ExpansionError::InvalidRegister)?;
+        let mut frame = [0; EXPANSION_DATA_MAX + 1];
+        let frame = frame
+            .get_mut(..data.len() + 1)
+            .ok_or(ExpansionError::TooLong)?;
+        frame[0] = register;
+        frame[1..].copy_from_slice(data);
+        match self.bus {
+            ExpansionBus::I2c {
+                address,
+            } => buses.i2c_write(address, frame),
+            ExpansionBus::Spi {
+                chip_select,
+            } => buses.spi_transaction(chip_select, frame, &mut []),
+        }
+    }
+}
+
+/// I2C EEPROMs with 16-bit memory addresses, e.g. the 24LC series.
+///
+/// A write that crosses a page boundary would wrap around to the start of the page, so writes are split at the page
+/// boundaries, and each page is waited for before the next is written.
+pub struct EepromDriver {
+    address: u8,
+    page_size: u16,
+}
+
+impl EepromDriver {
+    pub fn new(address: u8, page_size: u16) -> Self {
+        Self {
+            address,
+            // a page size of 0 would never make progress
+            page_size: page_size.max(1),
+        }
+    }
+
+    fn wait_for_write_cycle(&mut self, buses: &mut dyn ExpansionBuses) -> Result<(), ExpansionError> {
+        for _ in 0..EEPROM_WRITE_POLLS {
+            if buses
+                .i2c_write(self.address, &[])
+                .is_ok()
+            {
+                return Ok(());
+            }
+        }
+        Err(ExpansionError::Bus)
+    }
+}
+
+impl ExpansionDriver for EepromDriver {
+    fn probe(&mut self, buses: &mut dyn ExpansionBuses) -> bool {
+        buses
+            .i2c_write_read(self.address, &[0, 0], &mut [0])
+            .is_ok()
+    }
+
+    fn read(&mut self, buses: &mut dyn ExpansionBuses, register: u16, read: &mut [u8]) -> Result<(), ExpansionError> {
+        buses.i2c_write_read(self.address, &register.to_be_bytes(), read)
+    }
+
+    fn write(&mut self, buses: &mut dyn ExpansionBuses, register: u16, data: &[u8]) -> Result<(), ExpansionError> {
+        if data.len() > EXPANSION_DATA_MAX {
+            return Err(ExpansionError::TooLong);
+        }
+        if register as usize + data.len() > u16::MAX as usize + 1 {
+            return Err(ExpansionError::InvalidRegister);
+        }
+
+        let mut written = 0;
+        while written < data.len() {
+            let address = register + written as u16;
+            let page_remaining = (self.page_size - address % self.page_size) as usize;
+            let length = page_remaining.min(data.len() - written);
+
+            let mut frame = [0; EXPANSION_DATA_MAX + 2];
+            frame[..2].copy_from_slice(&address.to_be_bytes());
+            frame[2..2 + length].copy_from_slice(&data[written..written + length]);
+            buses.i2c_write(self.address, &frame[..2 + length])?;
+            self.wait_for_write_cycle(buses)?;
+
+            written += length;
+        }
+        Ok(())
+    }
+}
+
+/// The driver for a device of the device table, `None` if the kind of device is not supported on its bus.
+pub fn driver_for(device: &ExpansionDevice) -> Option<Box<dyn ExpansionDriver>> {
+    match (device.kind, device.bus) {
+        (ExpansionDeviceKind::PortExpander | ExpansionDeviceKind::Adc | ExpansionDeviceKind::Generic, bus) => {
+            Some(Box::new(RegisterDriver::new(bus)))
+        }
+        (
+            ExpansionDeviceKind::Eeprom {
+                page_size,
+            },
+            ExpansionBus::I2c {
+                address,
+            },
+        ) => Some(Box::new(EepromDriver::new(address, page_size))),
+        // FUTURE SPI EEPROMs, e.g. the 25LC series, use instructions instead of registers
+        (
+            ExpansionDeviceKind::Eeprom {
+                ..
+            },
+            ExpansionBus::Spi {
+                ..
+            },
+        ) => None,
+    }
+}
+
+struct RegisteredDevice {
+    device: ExpansionDevice,
+    /// `None` if the device is not supported, or did not respond
+    driver: Option<Box<dyn ExpansionDriver>>,
+}
+
+fn registered_driver(
+    devices: &mut [RegisteredDevice],
+    device: u8,
+) -> Result<&mut Box<dyn ExpansionDriver>, ExpansionError> {
+    devices
+        .get_mut(device as usize)
+        .ok_or(ExpansionError::UnknownDevice)?
+        .driver
+        .as_mut()
+        .ok_or(ExpansionError::NotPresent)
+}
+
+pub struct ExpansionRegistry<BUSES: ExpansionBuses> {
+    buses: BUSES,
+    devices: Vec<RegisteredDevice>,
+}
+
+impl<BUSES: ExpansionBuses> ExpansionRegistry<BUSES> {
+    /// Registers a driver for each device of the `table`, devices beyond [`EXPANSION_DEVICES_MAX`] are ignored.
+    pub fn new(mut buses: BUSES, table: &[ExpansionDevice]) -> Self {
+        if table.len() > EXPANSION_DEVICES_MAX {
+            warn!(
+                "Expansion device table too long, ignoring devices. devices: {}, max: {}",
+                table.len(),
+                EXPANSION_DEVICES_MAX
+            );
+        }
+
+        let devices = table
+            .iter()
+            .take(EXPANSION_DEVICES_MAX)
+            .enumerate()
+            .map(|(index, device)| {
+                let Some(mut driver) = driver_for(device) else {
+                    warn!("Expansion device not supported. device: {}, {}", index, device);
+                    return RegisteredDevice {
+                        device: *device,
+                        driver: None,
+                    };
+                };
+                let present = driver.probe(&mut buses);
+                match present {
+                    true => info!("Expansion device registered. device: {}, {}", index, device),
+                    false => warn!("Expansion device not responding. device: {}, {}", index, device),
+                }
+                RegisteredDevice {
+                    device: *device,
+                    driver: present.then_some(driver),
+                }
+            })
+            .collect();
+
+        Self {
+            buses,
+            devices,
+        }
+    }
+
+    fn handle_request(&mut self, request: ExpansionRequest) -> ExpansionResponse {
+        match request {
+            ExpansionRequest::Devices => {
+                let mut devices = [None; EXPANSION_DEVICES_MAX];
+                for (entry, registered) in devices
+                    .iter_mut()
+                    .zip(self.devices.iter())
+                {
+                    *entry = Some(ExpansionDeviceInfo {
+                        device: registered.device,
+                        present: registered.driver.is_some(),
+                    });
+                }
+                Ok(ExpansionReply::Devices {
+                    devices,
+                })
+            }
+            ExpansionRequest::Read {
+                device,
+                register,
+                length,
+            } => {
+                if length as usize > EXPANSION_DATA_MAX {
+                    return Err(ExpansionError::TooLong);
+                }
+                let driver = registered_driver(&mut self.devices, device)?;
+                let mut data = ExpansionData {
+                    length,
+                    ..ExpansionData::default()
+                };
+                driver.read(&mut self.buses, register, &mut data.bytes[..length as usize])?;
+                Ok(ExpansionReply::Data(data))
+            }
+            ExpansionRequest::Write {
+                device,
+                register,
+                data,
+            } => {
+                if data.length as usize > EXPANSION_DATA_MAX {
+                    return Err(ExpansionError::TooLong);
+                }
+                let driver = registered_driver(&mut self.devices, device)?;
+                driver.write(&mut self.buses, register, data.bytes())?;
+                Ok(ExpansionReply::Written)
+            }
+        }
+    }
+
+    /// Handle requests from the expansion endpoint.
+    pub async fn run(mut self) -> ! {
+        loop {
+            let request = EXPANSION_REQUESTS.receive().await;
+            let response = self.handle_request(request);
+            if let Err(error) = &response {
+                warn!("Expansion request failed. request: {}, error: {}", request, error);
+            }
+            EXPANSION_REQUESTS
+                .respond(response)
+                .await;
+        }
+    }
+}
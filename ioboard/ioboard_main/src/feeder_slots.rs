@@ -0,0 +1,119 @@
+//! Smart feeders, the slots of the feeder bank are polled for the ID chip of a feeder, e.g. an ID EEPROM or a
+//! one-wire chip, and an [`IoBoardEvent::FeederInserted`] event is published with the identity of each inserted
+//! feeder, so that the server can load the part assignment and calibration it has stored for the feeder.
+//!
+//! The contacts of a feeder that is being inserted bounce, an identity is only reported once it has been read the same
+//! on consecutive polls.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use defmt::{info, warn};
+use embassy_time::{Duration, Ticker};
+use ioboard_shared::events::IoBoardEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub enum FeederIdError {
+    /// the ID chip responded, but the transfer failed, e.g. a feeder that is being inserted
+    Bus,
+}
+
+pub trait FeederIdReader {
+    fn slots(&self) -> u8;
+    /// Returns `Ok(None)` if there is no feeder in the slot.
+    fn read_identity(&mut self, slot: u8) -> Result<Option<u64>, FeederIdError>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeederSlotConfig {
+    pub poll_interval: Duration,
+}
+
+impl Default for FeederSlotConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(250),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct SlotState {
+    /// the identity last published
+    reported: Option<u64>,
+    /// the identity of the previous poll
+    previous: Option<u64>,
+}
+
+pub struct FeederSlotMonitor<READER: FeederIdReader> {
+    reader: READER,
+    config: FeederSlotConfig,
+    slots: Vec<SlotState>,
+}
+
+impl<READER: FeederIdReader> FeederSlotMonitor<READER> {
+    pub fn new(reader: READER, config: FeederSlotConfig) -> Self {
+        let slots = vec![SlotState::default(); reader.slots() as usize];
+        Self {
+            reader,
+            config,
+            slots,
+        }
+    }
+
+    /// Reads the identity of each slot, the events of the slots whose feeder changed are passed to `publish`, which
+    /// returns `false` if the event was not published, it is then published again on the next poll.
+    pub fn poll(&mut self, mut publish: impl FnMut(IoBoardEvent) -> bool) {
+        for (slot, state) in self.slots.iter_mut().enumerate() {
+            let slot = slot as u8;
+            let identity = match self.reader.read_identity(slot) {
+                Ok(identity) => identity,
+                Err(e) => {
+                    warn!("Feeder identity unreadable. slot: {}, error: {}", slot, e);
+                    continue;
+                }
+            };
+
+            let stable = identity == state.previous;
+            state.previous = identity;
+            if !stable || identity == state.reported {
+                continue;
+            }
+
+            // a feeder swapped between polls is removed before the new one is inserted
+            if state.reported.is_some() {
+                if !publish(IoBoardEvent::FeederRemoved {
+                    slot,
+                }) {
+                    continue;
+                }
+                info!("Feeder removed. slot: {}", slot);
+                state.reported = None;
+            }
+            if let Some(identity) = identity {
+                if !publish(IoBoardEvent::FeederInserted {
+                    slot,
+                    identity,
+                }) {
+                    continue;
+                }
+                info!("Feeder inserted. slot: {}, identity: {:x}", slot, identity);
+                state.reported = Some(identity);
+            }
+        }
+    }
+
+    pub async fn run(mut self) -> ! {
+        let mut ticker = Ticker::every(self.config.poll_interval);
+        loop {
+            self.poll(|event| match ioboard_net::publish_event(event) {
+                Ok(()) => true,
+                Err(_) => {
+                    warn!("Event queue full, feeder event delayed");
+                    false
+                }
+            });
+            ticker.next().await;
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Records the force of the nozzle load cell around each touchdown, and publishes it as a [`ForceTrace`], so that the
+//! server can attach the forces to the placement and flag touchdowns that were too hard or too soft.
+//!
+//! A touchdown is a rise of the force above the contact threshold, the trace starts a few samples before the contact
+//! and ends once it is full.  The next touchdown is only detected once the force has dropped below half of the
+//! threshold, i.e. the nozzle has been lifted.
+//!
+//! FUTURE feed the samples of the load cell, see [`load_cell`](crate::load_cell), in N.
+
+use alloc::collections::VecDeque;
+
+use defmt::debug;
+use ioboard_shared::force::{FORCE_TRACE_SAMPLES, ForceTrace, Touchdown};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ForceConfig {
+    /// load cell sample rate, in Hz
+    pub sample_rate: f32,
+    /// a force above this is a contact, in N
+    pub contact_threshold: f32,
+    /// samples before the contact included in the trace
+    pub pre_contact_samples: usize,
+}
+
+impl Default for ForceConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 320.0,
+            contact_threshold: 0.2,
+            pre_contact_samples: 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TouchdownRecorder {
+    nozzle: u8,
+    config: ForceConfig,
+    /// the samples before a contact, most recent last
+    history: VecDeque<f32>,
+    /// the trace of the current touchdown, until it is full
+    trace: Option<ForceTrace>,
+    /// cleared by a contact, set again once the nozzle has been lifted
+    armed: bool,
+}
+
+impl TouchdownRecorder {
+    pub fn new(nozzle: u8, config: ForceConfig) -> Self {
+        let pre_contact_samples = config
+            .pre_contact_samples
+            .min(FORCE_TRACE_SAMPLES - 1);
+        Self {
+            nozzle,
+            config: ForceConfig {
+                pre_contact_samples,
+                ..config
+            },
+            history: VecDeque::with_capacity(pre_contact_samples),
+            trace: None,
+            armed: true,
+        }
+    }
+
+    /// Record a force sample, in N, `part_held` is whether the nozzle holds a part, e.g. the part present state of
+    /// the nozzle vacuum.
+    ///
+    /// Returns the trace once it is full.
+    pub fn record(&mut self, force: f32, part_held: bool) -> Option<ForceTrace> {
+        if let Some(trace) = &mut self.trace {
+            trace.samples[trace.sample_count as usize] = force;
+            trace.sample_count += 1;
+            if trace.sample_count as usize == FORCE_TRACE_SAMPLES {
+                return self.trace.take();
+            }
+            return None;
+        }
+
+        if !self.armed {
+            if force < self.config.contact_threshold / 2.0 {
+                self.armed = true;
+            }
+        } else if force >= self.config.contact_threshold {
+            self.armed = false;
+
+            let mut trace = ForceTrace {
+                nozzle: self.nozzle,
+                // a part held before the contact is being placed
+                touchdown: match part_held {
+                    true => Touchdown::Place,
+                    false => Touchdown::Pick,
+                },
+                sample_rate: self.config.sample_rate,
+                contact: self.history.len() as u8,
+                sample_count: 0,
+                samples: [0.0; FORCE_TRACE_SAMPLES],
+            };
+            for (entry, sample) in trace
+                .samples
+                .iter_mut()
+                .zip(self.history.drain(..))
+            {
+                *entry = sample;
+            }
+            trace.samples[trace.contact as usize] = force;
+            trace.sample_count = trace.contact + 1;
+            if trace.sample_count as usize == FORCE_TRACE_SAMPLES {
+                return Some(trace);
+            }
+            self.trace = Some(trace);
+            return None;
+        }
+
+        if self.config.pre_contact_samples > 0 {
+            if self.history.len() == self.config.pre_contact_samples {
+                self.history.pop_front();
+            }
+            self.history.push_back(force);
+        }
+        None
+    }
+
+    /// Record a sample, and publish the trace once it is full.
+    pub fn sample(&mut self, force: f32, part_held: bool) {
+        if let Some(trace) = self.record(force, part_held) {
+            debug!(
+                "Touchdown recorded, nozzle: {}, touchdown: {}, contact: {}",
+                trace.nozzle, trace.touchdown, trace.contact
+            );
+            ioboard_net::publish_force_trace(&trace);
+        }
+    }
+}
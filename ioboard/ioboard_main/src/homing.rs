@@ -0,0 +1,337 @@
+//! Homing, see [`HomingRequest`], the axis is moved towards its endstop until the [`LimitSwitch`] triggers, backs off,
+//! and approaches the endstop again slowly, the position of the axis is zeroed where the switch triggers on the slow
+//! approach.
+//!
+//! When the endstop is outside the [`SoftLimits`] of the axis, the axis is then moved away from the endstop to the
+//! nearest soft limit, so that the position after homing is always within the travel of the axis.
+//!
+//! The steps are generated one at a time by the [`Homing`] state machine, the switch is polled before each step, so a
+//! switch doesn't need an interrupt capable input.
+//!
+//! Homing requests are handled by the motion task of the axis while the axis is at rest, see [`AxisHoming`].
+//!
+//! The moves of an axis with an endstop are refused until it's homed, see [`AxisHoming::is_homed`], the motor power is
+//! enabled before homing.
+
+use defmt::{info, warn};
+use embassy_time::{Duration, Timer};
+use ioboard_net::HOMING_REQUESTS;
+use ioboard_shared::homing::{AxisHomingError, HomingRequest, HomingResponse};
+use ioboard_shared::power::Interlock;
+use ioboard_shared::safety::MotionRestriction;
+
+use crate::power::POWER_INTERLOCKS;
+use crate::safety::MOTION_RESTRICTIONS;
+use crate::soft_limits::SoftLimits;
+use crate::stepper::{Stepper, StepperCancellation, StepperDirection, StepperError};
+
+pub trait LimitSwitch {
+    /// Returns `true` while the axis is at the endstop.
+    fn is_triggered(&mut self) -> bool;
+}
+
+/// For axes without an endstop, the axis can't be homed.
+pub struct NoLimitSwitch;
+
+impl LimitSwitch for NoLimitSwitch {
+    fn is_triggered(&mut self) -> bool {
+        false
+    }
+}
+
+/// The end of the travel the endstop is at.
+#[derive(Debug, Default, Clone, Copy, PartialEq, defmt::Format)]
+pub enum EndstopSide {
+    /// the axis moves towards it with [`StepperDirection::Reversed`]
+    #[default]
+    Min,
+    /// the axis moves towards it with [`StepperDirection::Normal`]
+    Max,
+}
+
+impl EndstopSide {
+    fn towards(self) -> StepperDirection {
+        match self {
+            EndstopSide::Min => StepperDirection::Reversed,
+            EndstopSide::Max => StepperDirection::Normal,
+        }
+    }
+
+    fn away(self) -> StepperDirection {
+        match self {
+            EndstopSide::Min => StepperDirection::Normal,
+            EndstopSide::Max => StepperDirection::Reversed,
+        }
+    }
+}
+
+/// Speeds are in steps/s, distances in steps.
+#[derive(Debug, Clone, Copy)]
+pub struct HomingConfig {
+    pub endstop: EndstopSide,
+    /// of the first approach, and of the back-off
+    pub seek_speed: u32,
+    /// of the second approach, the position is zeroed where the switch triggers on this approach
+    pub slow_speed: u32,
+    /// far enough to release the switch, the second approach fails if the switch doesn't trigger within twice this
+    pub back_off_steps: u32,
+    /// the first approach fails if the switch doesn't trigger within this, more than the travel of the axis
+    pub max_travel_steps: u32,
+}
+
+impl Default for HomingConfig {
+    fn default() -> Self {
+        Self {
+            endstop: EndstopSide::Min,
+            seek_speed: 4000,
+            slow_speed: 400,
+            back_off_steps: 400,
+            max_travel_steps: 400_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Seek {
+        travelled: u32,
+    },
+    BackOff {
+        remaining: u32,
+    },
+    ReSeek {
+        travelled: u32,
+    },
+    /// away from the endstop, into the soft limits
+    Recover {
+        remaining: u32,
+    },
+}
+
+/// What to do next, see [`Homing::next`].
+#[derive(Debug, PartialEq)]
+pub enum HomingStep {
+    /// Step once, `speed` in steps/s
+    Step {
+        direction: StepperDirection,
+        speed: u32,
+    },
+    /// The axis is at the endstop, or at the nearest soft limit
+    Homed,
+    Failed(AxisHomingError),
+}
+
+pub struct Homing {
+    config: HomingConfig,
+    /// from the endstop, after the slow approach
+    recovery_steps: u32,
+    phase: Phase,
+}
+
+impl Homing {
+    pub fn new(config: HomingConfig, recovery_steps: u32) -> Self {
+        Self {
+            config,
+            recovery_steps,
+            phase: Phase::Seek {
+                travelled: 0,
+            },
+        }
+    }
+
+    /// `triggered` is the state of the switch before the step, an axis that starts at the endstop only backs off.
+    pub fn next(&mut self, triggered: bool) -> HomingStep {
+        let config = self.config;
+        match self.phase {
+            Phase::Seek {
+                travelled,
+            } => {
+                if triggered {
+                    self.phase = Phase::BackOff {
+                        remaining: config.back_off_steps,
+                    };
+                    return self.next(triggered);
+                }
+                if travelled >= config.max_travel_steps {
+                    return HomingStep::Failed(AxisHomingError::EndstopNotFound);
+                }
+                self.phase = Phase::Seek {
+                    travelled: travelled + 1,
+                };
+                step(config.endstop.towards(), config.seek_speed)
+            }
+            Phase::BackOff {
+                remaining: 0,
+            } => {
+                if triggered {
+                    return HomingStep::Failed(AxisHomingError::EndstopStuck);
+                }
+                self.phase = Phase::ReSeek {
+                    travelled: 0,
+                };
+                self.next(triggered)
+            }
+            Phase::BackOff {
+                remaining,
+            } => {
+                self.phase = Phase::BackOff {
+                    remaining: remaining - 1,
+                };
+                step(config.endstop.away(), config.seek_speed)
+            }
+            Phase::ReSeek {
+                travelled,
+            } => {
+                if triggered {
+                    // the position is zeroed here
+                    self.phase = Phase::Recover {
+                        remaining: self.recovery_steps,
+                    };
+                    return self.next(triggered);
+                }
+                if travelled >= config.back_off_steps.saturating_mul(2) {
+                    return HomingStep::Failed(AxisHomingError::EndstopNotFound);
+                }
+                self.phase = Phase::ReSeek {
+                    travelled: travelled + 1,
+                };
+                step(config.endstop.towards(), config.slow_speed)
+            }
+            Phase::Recover {
+                remaining: 0,
+            } => HomingStep::Homed,
+            Phase::Recover {
+                remaining,
+            } => {
+                self.phase = Phase::Recover {
+                    remaining: remaining - 1,
+                };
+                step(config.endstop.away(), config.seek_speed)
+            }
+        }
+    }
+}
+
+fn step(direction: StepperDirection, speed: u32) -> HomingStep {
+    HomingStep::Step {
+        direction,
+        speed,
+    }
+}
+
+/// The limit switch and homing configuration of an axis, `config` is `None` for an axis that can't be homed.
+pub struct AxisHoming<SWITCH: LimitSwitch> {
+    axis: u8,
+    switch: SWITCH,
+    config: Option<HomingConfig>,
+    soft_limits: Option<SoftLimits>,
+    homed: bool,
+}
+
+impl<SWITCH: LimitSwitch> AxisHoming<SWITCH> {
+    pub fn new(axis: u8, switch: SWITCH, config: Option<HomingConfig>, soft_limits: Option<SoftLimits>) -> Self {
+        Self {
+            axis,
+            switch,
+            config,
+            soft_limits,
+            homed: false,
+        }
+    }
+
+    /// The axis has been homed, or has no endstop, its position is known and it can be moved.
+    pub fn is_homed(&self) -> bool {
+        self.config.is_none() || self.homed
+    }
+
+    /// The position after homing, the endstop is zero, clamped to the soft limits, only away from the endstop.
+    fn homed_position(&self, endstop: EndstopSide) -> i64 {
+        let position = self
+            .soft_limits
+            .map_or(0, |soft_limits| soft_limits.clamp(0));
+        match endstop {
+            EndstopSide::Min => position.max(0),
+            EndstopSide::Max => position.min(0),
+        }
+    }
+
+    /// Homes the axis and responds to the request, returns the position of the axis when it was homed, see
+    /// [`Self::homed_position`].
+    ///
+    /// The request is answered before a stepper error is returned, e.g. when cancelled by an e-stop.
+    ///
+    /// The axis is homed, see [`Self::is_homed`], and the [`Interlock::HomingConfigured`] interlock is satisfied, once
+    /// homing succeeds, and no longer when homing it again fails, its position is then unknown.
+    pub async fn handle(
+        &mut self,
+        stepper: &mut impl Stepper,
+        request: HomingRequest,
+        cancellation: &StepperCancellation,
+    ) -> Result<Option<i64>, StepperError> {
+        let position = self
+            .config
+            .map_or(0, |config| self.homed_position(config.endstop));
+        let result = match (request.axis == self.axis, self.config) {
+            (true, Some(config)) => {
+                self.home(stepper, config, position.unsigned_abs() as u32, cancellation)
+                    .await
+            }
+            _ => Ok(Err(AxisHomingError::NoEndstop)),
+        };
+
+        let response = match &result {
+            Ok(response) => *response,
+            Err(_) => Err(AxisHomingError::MotionNotAllowed),
+        };
+        match response {
+            Ok(()) => info!("Axis homed, axis: {}, position: {}", self.axis, position),
+            Err(e) => warn!("Homing failed, axis: {}, error: {}", request.axis, e),
+        }
+        if request.axis == self.axis && self.config.is_some() {
+            self.homed = response.is_ok();
+            POWER_INTERLOCKS.set(Interlock::HomingConfigured, self.homed);
+        }
+        HOMING_REQUESTS.respond(response).await;
+
+        result.map(|response| response.ok().map(|_| position))
+    }
+
+    async fn home(
+        &mut self,
+        stepper: &mut impl Stepper,
+        config: HomingConfig,
+        recovery_steps: u32,
+        cancellation: &StepperCancellation,
+    ) -> Result<HomingResponse, StepperError> {
+        info!("Homing, axis: {}, endstop: {}", self.axis, config.endstop);
+        let mut homing = Homing::new(config, recovery_steps);
+        let mut direction: Option<StepperDirection> = None;
+        loop {
+            cancellation.check()?;
+            if matches!(
+                MOTION_RESTRICTIONS.restriction(),
+                MotionRestriction::Paused | MotionRestriction::EStopped
+            ) {
+                return Ok(Err(AxisHomingError::MotionNotAllowed));
+            }
+
+            match homing.next(self.switch.is_triggered()) {
+                HomingStep::Step {
+                    direction: required_direction,
+                    speed,
+                } => {
+                    if direction.as_ref() != Some(&required_direction) {
+                        stepper.direction(required_direction.clone())?;
+                        direction = Some(required_direction);
+                    }
+                    stepper.step().await?;
+                    // the interval is far longer than the pulse delay at homing speeds
+                    let speed = (speed as f32 * MOTION_RESTRICTIONS.speed_factor()).max(1.0);
+                    Timer::after(Duration::from_micros((1_000_000.0 / speed) as u64)).await;
+                }
+                HomingStep::Homed => return Ok(Ok(())),
+                HomingStep::Failed(e) => return Ok(Err(e)),
+            }
+        }
+    }
+}
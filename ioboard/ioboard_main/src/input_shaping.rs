@@ -0,0 +1,300 @@
+//! Input shaping, convolves the planned position with a small set of impulses so that the
+//! resonance of the axis (e.g. gantry ringing) is not excited by the motion.
+//!
+//! The shaped motion lags the planned motion by the duration of the shaper, see [`InputShaper::delay_cycles`].
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use ioboard_net::RESONANCE_SWEEP_REQUESTS;
+use ioboard_shared::resonance::{
+    MAX_SWEEP_FREQUENCIES, ResonanceSweep, ResonanceSweepError, ResonanceSweepRequest, ResonanceSweepResponse,
+};
+use ioboard_shared::safety::MotionRestriction;
+use libm::{exp, round, sin, sqrt};
+
+use crate::safety::MOTION_RESTRICTIONS;
+use crate::stepper::{Stepper, StepperCancellation, StepperDirection, StepperError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShaperKind {
+    /// Zero vibration, 2 impulses, shortest delay (half the damped period) but sensitive to frequency errors.
+    Zv,
+    /// Zero vibration and derivative, 3 impulses, twice the delay of ZV but more robust to frequency errors.
+    Zvd,
+}
+
+/// The lowest frequency of a shaper, the history of a ZVD shaper is a damped period long, over 1000 cycles at 1 Hz.
+pub const MIN_SHAPER_FREQUENCY: f64 = 1.0;
+/// The highest damping ratio of a shaper, an axis damped more than that does not ring.
+pub const MAX_SHAPER_DAMPING: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShaperConfig {
+    pub kind: ShaperKind,
+    /// resonance frequency of the axis, in Hz, at least [`MIN_SHAPER_FREQUENCY`]
+    pub frequency: f64,
+    /// damping ratio of the resonance, 0.0 to [`MAX_SHAPER_DAMPING`], typically 0.05-0.1 for a gantry
+    pub damping: f64,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, defmt::Format)]
+pub enum ShaperConfigError {
+    /// not finite, or below [`MIN_SHAPER_FREQUENCY`]
+    InvalidFrequency,
+    /// not from 0.0 to [`MAX_SHAPER_DAMPING`]
+    InvalidDamping,
+}
+
+impl ShaperConfig {
+    pub fn validate(&self) -> Result<(), ShaperConfigError> {
+        // `is_finite` also refuses NaN
+        if !(self.frequency.is_finite() && self.frequency >= MIN_SHAPER_FREQUENCY) {
+            return Err(ShaperConfigError::InvalidFrequency);
+        }
+        if !(0.0..=MAX_SHAPER_DAMPING).contains(&self.damping) {
+            return Err(ShaperConfigError::InvalidDamping);
+        }
+        Ok(())
+    }
+}
+
+pub struct InputShaper {
+    /// (amplitude, delay in cycles), amplitudes sum to 1.0
+    impulses: Vec<(f64, usize)>,
+    /// ring buffer of previous unshaped positions, long enough for the largest delay
+    history: Vec<f64>,
+    head: usize,
+}
+
+impl InputShaper {
+    /// `dt` is the control cycle interval, in seconds.
+    pub fn new(config: &ShaperConfig, dt: f64) -> Result<Self, ShaperConfigError> {
+        config.validate()?;
+
+        let damping = config.damping;
+        let damped_root = sqrt(1.0 - damping * damping);
+        let damped_period = 1.0 / (config.frequency * damped_root);
+        let k = exp(-damping * core::f64::consts::PI / damped_root);
+
+        let to_cycles = |time: f64| round(time / dt) as usize;
+
+        let impulses = match config.kind {
+            ShaperKind::Zv => vec![(1.0 / (1.0 + k), 0), (k / (1.0 + k), to_cycles(damped_period / 2.0))],
+            ShaperKind::Zvd => {
+                let scale = (1.0 + k) * (1.0 + k);
+                vec![
+                    (1.0 / scale, 0),
+                    (2.0 * k / scale, to_cycles(damped_period / 2.0)),
+                    (k * k / scale, to_cycles(damped_period)),
+                ]
+            }
+        };
+
+        let delay_cycles = impulses
+            .iter()
+            .map(|(_, delay)| *delay)
+            .max()
+            .unwrap_or(0);
+
+        info!(
+            "Input shaper, frequency: {} Hz, damping: {}, delay: {} cycles",
+            config.frequency, damping, delay_cycles
+        );
+
+        Ok(Self {
+            impulses,
+            history: vec![0.0; delay_cycles + 1],
+            head: 0,
+        })
+    }
+
+    /// Number of cycles the shaped position lags the unshaped position by.
+    pub fn delay_cycles(&self) -> usize {
+        self.history.len() - 1
+    }
+
+    /// Reset the shaper so that it is at rest at `position`.
+    pub fn reset(&mut self, position: f64) {
+        self.history.fill(position);
+        self.head = 0;
+    }
+
+    /// Push the next unshaped position, returns the shaped position.
+    pub fn shape(&mut self, position: f64) -> f64 {
+        let len = self.history.len();
+        self.head = (self.head + 1) % len;
+        self.history[self.head] = position;
+
+        self.impulses
+            .iter()
+            .map(|(amplitude, delay)| amplitude * self.history[(self.head + len - delay) % len])
+            .sum()
+    }
+}
+
+/// Measures the response of the machine while the axis is being excited, e.g. an accelerometer or load cell.
+pub trait ResponseSensor {
+    /// Start accumulating the response.
+    fn begin(&mut self);
+    /// Stop accumulating the response and return the RMS of the response since [`ResponseSensor::begin`].
+    fn end(&mut self) -> f32;
+}
+
+/// Shake the axis sinusoidally at each of the `frequencies`, with an amplitude of `amplitude_steps`,
+/// for `duration` per frequency, recording the response of the `sensor`.
+///
+/// Returns the frequency with the largest response, which can be used as the [`ShaperConfig::frequency`],
+/// along with the response at each frequency.
+///
+/// The axis must be enabled and have enough free travel either side of the current position.
+pub async fn resonance_sweep(
+    stepper: &mut impl Stepper,
+    sensor: &mut impl ResponseSensor,
+    frequencies: &[f64],
+    amplitude_steps: f64,
+    duration: Duration,
+    cancellation: &StepperCancellation,
+) -> Result<(f64, Vec<f32>), StepperError> {
+    let cycle_interval_micros = 1000;
+    let dt = 1.0_f64 / cycle_interval_micros as f64;
+    let settle_delay = Duration::from_millis(250);
+
+    let mut responses = Vec::with_capacity(frequencies.len());
+
+    for &frequency in frequencies {
+        let cycles = duration.as_micros() / cycle_interval_micros;
+
+        let mut direction = None;
+        let mut last_position_steps = 0i64;
+        let mut cycle_ticker = Ticker::every(Duration::from_micros(cycle_interval_micros));
+
+        sensor.begin();
+        for cycle in 0..=cycles {
+            // the final cycle returns the axis to the starting position
+            let position = match cycle == cycles {
+                true => 0.0,
+                false => amplitude_steps * sin(2.0 * core::f64::consts::PI * frequency * cycle as f64 * dt),
+            };
+            let position_steps = round(position) as i64;
+            let delta = position_steps - last_position_steps;
+
+            let required_direction = match delta {
+                0 => direction.clone(),
+                d if d > 0 => Some(StepperDirection::Normal),
+                _ => Some(StepperDirection::Reversed),
+            };
+            if required_direction != direction {
+                if let Some(required_direction) = required_direction.clone() {
+                    stepper.direction(required_direction)?;
+                }
+                direction = required_direction;
+            }
+
+            stepper
+                .step_burst(
+                    delta.unsigned_abs() as u32,
+                    cycle_interval_micros,
+                    Instant::now(),
+                    cancellation,
+                )
+                .await?;
+            last_position_steps = position_steps;
+
+            cycle_ticker.next().await;
+        }
+        let response = sensor.end();
+        info!("Resonance sweep, frequency: {} Hz, response: {}", frequency, response);
+        responses.push(response);
+
+        // let the axis come to rest before the next frequency
+        Timer::after(settle_delay).await;
+    }
+
+    let peak_frequency = frequencies
+        .iter()
+        .zip(responses.iter())
+        .fold((0.0, f32::MIN), |peak, (frequency, response)| {
+            match *response > peak.1 {
+                true => (*frequency, *response),
+                false => peak,
+            }
+        })
+        .0;
+
+    Ok((peak_frequency, responses))
+}
+
+/// Runs the [`resonance_sweep`] of the request and responds to it, for the axis `axis`.
+///
+/// The request is answered before a stepper error is returned, e.g. when cancelled by an e-stop.
+pub async fn handle_resonance_sweep(
+    axis: u8,
+    stepper: &mut impl Stepper,
+    sensor: &mut impl ResponseSensor,
+    request: ResonanceSweepRequest,
+    cancellation: &StepperCancellation,
+) -> Result<(), StepperError> {
+    let result = match validate_sweep(axis, &request) {
+        Err(e) => Ok(Err(e)),
+        Ok(())
+            if matches!(
+                MOTION_RESTRICTIONS.restriction(),
+                MotionRestriction::Paused | MotionRestriction::EStopped
+            ) =>
+        {
+            Ok(Err(ResonanceSweepError::MotionNotAllowed))
+        }
+        Ok(()) => {
+            let frequencies = (0..request.frequencies as usize)
+                .map(|index| request.frequency(index) as f64)
+                .collect::<Vec<_>>();
+            resonance_sweep(
+                stepper,
+                sensor,
+                &frequencies,
+                request.amplitude_steps as f64,
+                Duration::from_millis(request.duration_ms as u64),
+                cancellation,
+            )
+            .await
+            .map(|(peak_frequency, sweep_responses)| {
+                let mut responses = [0.0; MAX_SWEEP_FREQUENCIES];
+                responses[..sweep_responses.len()].copy_from_slice(&sweep_responses);
+                Ok(ResonanceSweep {
+                    peak_frequency: peak_frequency as f32,
+                    responses,
+                })
+            })
+        }
+    };
+
+    let response: ResonanceSweepResponse = match &result {
+        Ok(response) => *response,
+        Err(_) => Err(ResonanceSweepError::MotionNotAllowed),
+    };
+    match response {
+        Ok(sweep) => info!(
+            "Resonance sweep done, axis: {}, peak: {} Hz",
+            axis, sweep.peak_frequency
+        ),
+        Err(e) => warn!("Resonance sweep failed, axis: {}, error: {}", request.axis, e),
+    }
+    RESONANCE_SWEEP_REQUESTS
+        .respond(response)
+        .await;
+
+    result.map(|_| ())
+}
+
+fn validate_sweep(axis: u8, request: &ResonanceSweepRequest) -> Result<(), ResonanceSweepError> {
+    if request.axis != axis {
+        return Err(ResonanceSweepError::InvalidAxis);
+    }
+    if !request.is_valid() {
+        return Err(ResonanceSweepError::InvalidSweep);
+    }
+    Ok(())
+}
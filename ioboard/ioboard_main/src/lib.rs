@@ -1,20 +1,115 @@
 #![no_std]
 
 extern crate alloc;
-
+#[cfg(test)]
+extern crate std;
+
+pub mod analog;
+pub mod dispenser;
+pub mod driver;
+pub mod expansion;
+pub mod feeder_slots;
+pub mod force;
+pub mod homing;
+pub mod input_shaping;
+pub mod load;
+pub mod load_cell;
+pub mod motion_anomaly;
+pub mod motion_queue;
+pub mod power;
+pub mod probe;
+pub mod safe_z;
+pub mod safety;
+pub mod self_test;
+pub mod setpoint;
+pub mod soft_limits;
 pub mod stepper;
-
-use alloc::vec::Vec;
-
-use defmt::info;
-use embassy_time::{Duration, Instant, Ticker, Timer};
+pub mod temperature;
+pub mod thermal;
+pub mod tmc;
+pub mod trajectory;
+pub mod vacuum;
+pub mod vibration;
+
+#[cfg(test)]
+mod tests;
+
+use defmt::{error, info, warn};
+use embassy_futures::select::{Either4, select4};
+use embassy_time::{Duration, Instant, Ticker, Timer, with_timeout};
+use ioboard_net::{DRIVER_REQUESTS, HOMING_REQUESTS, MOTION_COMMANDS, MOTION_SETPOINTS, RESONANCE_SWEEP_REQUESTS};
+use ioboard_shared::events::IoBoardEvent;
+pub use ioboard_shared::motion::MotionPlanning;
+use ioboard_shared::motion::{MotionCommandError, PositionReport, QueuedMove, StopRamp};
+use ioboard_shared::power::Interlock;
 use ioboard_trace::tracepin;
 use libm::round;
 use rsruckig::prelude::*;
 
-use crate::stepper::{Stepper, StepperDirection, StepperError};
+use crate::driver::AxisDriver;
+use crate::homing::{AxisHoming, HomingConfig, LimitSwitch};
+use crate::input_shaping::{InputShaper, ShaperConfig, handle_resonance_sweep};
+use crate::load::{LoadConfig, LoadMonitor};
+use crate::motion_anomaly::{MotionAnomalyConfig, MotionAnomalyMonitor, StepCycle};
+use crate::motion_queue::{CommandAction, MotionQueue};
+use crate::safety::MOTION_RESTRICTIONS;
+use crate::setpoint::SetpointFollower;
+use crate::soft_limits::SoftLimits;
+use crate::stepper::{
+    PulseIntervals, StepPulseGenerator, Stepper, StepperCancellation, StepperDirection, StepperError,
+};
+use crate::thermal::{ThermalConfig, ThermalModel};
+use crate::trajectory::{AxisRuckig, advance_segment, convert_to_steps, prepare_segment};
+use crate::vibration::VIBRATION_MONITOR;
+
+/// The axis index used in events, there is currently only a single axis.
+const AXIS: u8 = 0;
+
+/// 100Hz at the 1ms trajectory cycle, frequent enough for the server to interpolate the position at a frame's capture
+/// time.
+const POSITION_REPORT_INTERVAL_CYCLES: u32 = 10;
+
+/// 100Hz at the 1ms trajectory cycle, reading the driver feedback takes an SPI transaction so it is not done every
+/// cycle.
+const LOAD_SAMPLE_INTERVAL_CYCLES: u32 = 10;
+
+/// While the axis is at rest the loop waits for a command instead of the next cycle, the cancellation is checked this
+/// often.
+const REST_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Optional per-axis motion features
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AxisConfig {
+    /// when `None` the motion limits are never derated
+    pub thermal: Option<ThermalConfig>,
+    /// when `None` the planned trajectory is stepped as-is
+    pub input_shaper: Option<ShaperConfig>,
+    /// when `None` the load is not monitored, also ignored for drivers without current feedback
+    pub load: Option<LoadConfig>,
+    /// when `None` the motion is not checked for crashes, intended for the Z axis, see [`motion_anomaly`]
+    pub motion_anomaly: Option<MotionAnomalyConfig>,
+    /// until the server sends the planning of the io board definition, see
+    /// [`MotionCommand::SetPlanning`](ioboard_shared::motion::MotionCommand::SetPlanning), while the server plans the
+    /// trajectories the thermal model and input shaper are not used, the server is responsible for the motion limits
+    pub planning: MotionPlanning,
+    /// when `None` the axis has no endstop and can't be homed, it is positioned from where it is at power up, see
+    /// [`homing`]
+    pub homing: Option<HomingConfig>,
+    /// when `None` the travel of the axis is not limited, see [`soft_limits`]
+    pub soft_limits: Option<SoftLimits>,
+}
 
-pub async fn run<STEPPER: Stepper>(mut stepper: STEPPER) {
+/// `limit_switch` is the endstop of the axis, see [`NoLimitSwitch`](homing::NoLimitSwitch) for axes without one.
+///
+/// `pulse_generator` outputs the step pulses of the trajectory loop, see
+/// [`SoftwarePulseGenerator`](stepper::SoftwarePulseGenerator) for steppers without a hardware timer for the step pin.
+pub async fn run<STEPPER: Stepper, PULSES: StepPulseGenerator<STEPPER>, SWITCH: LimitSwitch>(
+    mut stepper: STEPPER,
+    mut pulse_generator: PULSES,
+    limit_switch: SWITCH,
+    cancellation: &'static StepperCancellation,
+    axis_config: AxisConfig,
+) {
     let step_frequency_khz = 20_000;
     let step_period_us = 1_000_000 / step_frequency_khz;
     let step_pulse_width_us = 4;
@@ -26,6 +121,10 @@ pub async fn run<STEPPER: Stepper>(mut stepper: STEPPER) {
     stepper.set_pulse_width_us(step_pulse_width_us);
     stepper.set_pulse_delay_us(step_pulse_delay_us);
 
+    // an axis with an endstop must be homed before it's moved, see `AxisHoming::is_homed`
+    power::POWER_INTERLOCKS.set(Interlock::HomingConfigured, axis_config.homing.is_none());
+    let mut homing = AxisHoming::new(AXIS, limit_switch, axis_config.homing, axis_config.soft_limits);
+
     // NEMA 17 = 200 full steps/revolution.
     let default_motor_steps = 200;
     let micro_stepping_multiplier = 8;
@@ -39,64 +138,157 @@ pub async fn run<STEPPER: Stepper>(mut stepper: STEPPER) {
 
     let move_steps = motor_steps;
 
-    let trajectory_units: &[(f64, f64, f64, f64)] = &[
-        // (degrees, max_jerk, max_acc, max_vel)
-
-        // 0-1 encoder resets
-        // (90.0, 5000.0, 10000.0, 10000.0),
-        // (0.0, 5000.0, 10000.0, 10000.0),
-
-        // 1-2 encoder resets
-        (540.0, 5000.0, 10000.0, 10000.0),
-        (0.0, 5000.0, 10000.0, 10000.0),
-
-        // various different settings
-        // (1440.0, 5000.0, 10000.0, 10000.0),
-        // (0.0, 5000.0, 10000.0, 10000.0),
-        // (1440.0, 5000.0, 15000.0, 10000.0),
-        // (0.0, 5000.0, 15000.0, 10000.0),
-        // (1440.0, 5000.0, 10000.0, 15000.0),
-        // (0.0, 5000.0, 10000.0, 15000.0),
-    ];
+    let mut follower = SetpointFollower::new(
+        AXIS,
+        axis_config
+            .load
+            .map(|config| LoadMonitor::new(AXIS, config)),
+        axis_config
+            .motion_anomaly
+            .map(|config| MotionAnomalyMonitor::new(AXIS, config)),
+    );
 
-    let steps_per_unit = motor_steps as f64 / 360.0;
+    let mut thermal_model = axis_config
+        .thermal
+        .map(ThermalModel::new);
+    let mut load_monitor = axis_config
+        .load
+        .map(|config| LoadMonitor::new(AXIS, config));
+    let mut motion_anomaly_monitor = axis_config
+        .motion_anomaly
+        .map(|config| MotionAnomalyMonitor::new(AXIS, config));
+
+    if false {
+        for i in 0..2 {
+            info!("Run simple loop {}", i);
+            if let Err(e) = enable_stepper(&mut stepper, cancellation) {
+                handle_loop_error(&mut stepper, e, cancellation).await;
+                break;
+            }
+            Timer::after(Duration::from_millis(100)).await;
+            if let Err(e) = run_simple_loop(&mut stepper, move_steps, cancellation).await {
+                handle_loop_error(&mut stepper, e, cancellation).await;
+                break;
+            }
+            stepper.disable().unwrap();
+            info!("Stopped loop {}", i);
+            Timer::after(Duration::from_millis(1000)).await;
+        }
+    }
 
+    let mut queue = MotionQueue::new(AXIS, axis_config.soft_limits);
+    let mut planning = axis_config.planning;
+    // in steps, kept when the planning is switched
+    let mut position = 0;
     loop {
-        if false {
-            for i in 0..2 {
-                info!("Run simple loop {}", i);
-                stepper.enable().unwrap();
+        info!("Motion planning, axis: {}, planning: {}", AXIS, planning);
+        position = match planning {
+            MotionPlanning::Server => {
+                run_setpoint_follower(&mut stepper, &mut homing, &mut follower, position, cancellation).await
+            }
+            MotionPlanning::OnBoard => loop {
+                info!("Run trajectory loop");
+                if let Err(e) = enable_stepper(&mut stepper, cancellation) {
+                    handle_loop_error(&mut stepper, e, cancellation).await;
+                    continue;
+                }
                 Timer::after(Duration::from_millis(100)).await;
-                if run_simple_loop(&mut stepper, move_steps)
-                    .await
-                    .is_err()
+                match run_trajectory_loop(
+                    &mut stepper,
+                    &mut pulse_generator,
+                    &mut queue,
+                    &mut homing,
+                    thermal_model.as_mut(),
+                    load_monitor.as_mut(),
+                    motion_anomaly_monitor.as_mut(),
+                    axis_config.input_shaper.as_ref(),
+                    position,
+                    cancellation,
+                )
+                .await
                 {
-                    break;
+                    Ok(position) => break position,
+                    Err(e) => {
+                        if let Some(response) = queue.stopped() {
+                            MOTION_COMMANDS.respond(response).await;
+                        }
+                        handle_loop_error(&mut stepper, e, cancellation).await;
+                    }
                 }
-                stepper.disable().unwrap();
-                info!("Stopped loop {}", i);
-                Timer::after(Duration::from_millis(1000)).await;
-            }
-        }
+            },
+        };
+        planning = match planning {
+            MotionPlanning::Server => MotionPlanning::OnBoard,
+            MotionPlanning::OnBoard => MotionPlanning::Server,
+        };
+    }
+}
 
-        for i in 0..1 {
-            info!("Run trajectory {}", i);
-            stepper.enable().unwrap();
-            Timer::after(Duration::from_millis(100)).await;
-            if run_trajectory_loop(&mut stepper, trajectory_units, steps_per_unit)
-                .await
-                .is_err()
-            {
-                break;
-            }
-            stepper.disable().unwrap();
-            info!("Stopped trajectory {}", i);
-            Timer::after(Duration::from_millis(5000)).await;
+/// Follows setpoints from `position`, in steps, until the server switches the axis to on-board planning, returns the
+/// position of the axis.
+async fn run_setpoint_follower(
+    stepper: &mut impl Stepper,
+    homing: &mut AxisHoming<impl LimitSwitch>,
+    follower: &mut SetpointFollower,
+    position: i64,
+    cancellation: &StepperCancellation,
+) -> i64 {
+    // setpoints received while the io board planned the trajectories itself are stale
+    while MOTION_SETPOINTS.try_receive().is_ok() {}
+    follower.reset_position(position);
+    loop {
+        if let Err(e) = enable_stepper(stepper, cancellation) {
+            handle_loop_error(stepper, e, cancellation).await;
+            continue;
+        }
+        match follower
+            .run(stepper, homing, cancellation)
+            .await
+        {
+            Ok(()) => return follower.position(),
+            Err(e) => handle_loop_error(stepper, e, cancellation).await,
         }
     }
 }
 
-async fn run_simple_loop(stepper: &mut impl Stepper, move_steps: i32) -> Result<(), StepperError> {
+/// A driver that can't be enabled is a fault, motion is stopped until the fault is cleared.
+fn enable_stepper(stepper: &mut impl Stepper, cancellation: &StepperCancellation) -> Result<(), StepperError> {
+    if stepper.enable().is_ok() {
+        return Ok(());
+    }
+
+    cancellation.cancel();
+    error!("Unable to enable stepper, motion stopped. axis: {}", AXIS);
+    if ioboard_net::publish_event(IoBoardEvent::AxisDriverFault {
+        axis: AXIS,
+    })
+    .is_err()
+    {
+        warn!("Event queue full, dropped driver fault event");
+    }
+    Err(StepperError::Cancelled)
+}
+
+/// On cancellation the stepper is disabled and this waits until the cancellation is reset.
+async fn handle_loop_error(stepper: &mut impl Stepper, error: StepperError, cancellation: &StepperCancellation) {
+    if !matches!(error, StepperError::Cancelled) {
+        return;
+    }
+
+    info!("Stepper cancelled, disabling");
+    let _ = stepper.disable();
+
+    while cancellation.is_cancelled() {
+        Timer::after(Duration::from_millis(10)).await;
+    }
+    info!("Stepper cancellation reset");
+}
+
+async fn run_simple_loop(
+    stepper: &mut impl Stepper,
+    move_steps: i32,
+    cancellation: &StepperCancellation,
+) -> Result<(), StepperError> {
     let cycle_interval_micros = 175;
     let direction_change_delay_ms = 250;
 
@@ -108,7 +300,9 @@ async fn run_simple_loop(stepper: &mut impl Stepper, move_steps: i32) -> Result<
     let mut step_ticker = Ticker::every(Duration::from_micros(cycle_interval_micros));
 
     for _ in 0..move_steps {
-        stepper.step_and_wait().await?;
+        stepper
+            .step_and_wait(cancellation)
+            .await?;
         step_ticker.next().await;
     }
 
@@ -119,148 +313,397 @@ async fn run_simple_loop(stepper: &mut impl Stepper, move_steps: i32) -> Result<
 
     step_ticker.reset();
     for _ in 0..move_steps {
-        stepper.step_and_wait().await?;
+        stepper
+            .step_and_wait(cancellation)
+            .await?;
         step_ticker.next().await;
     }
     Ok::<(), StepperError>(())
 }
 
-async fn run_trajectory_loop(
-    stepper: &mut impl Stepper,
-    trajectory_units: &[(f64, f64, f64, f64)],
-    steps_per_unit: f64,
-) -> Result<(), StepperError> {
+/// Advances the thermal model to now, with the driver at `current` since the last update, see
+/// [`AxisDriver::current`], and publishes changes of the derating.
+fn update_thermal_model(model: Option<&mut ThermalModel>, current: f32, updated_at: &mut Instant) {
+    let Some(model) = model else {
+        return;
+    };
+    let now = Instant::now();
+    let dt = (now - *updated_at).as_micros() as f32 / 1_000_000.0;
+    *updated_at = now;
+
+    if let Some(factor) = model.update(current, dt) {
+        let temperature = model.temperature();
+        info!(
+            "Axis derating changed, factor: {}, temperature: {}",
+            factor, temperature
+        );
+        let event = IoBoardEvent::AxisDerating {
+            axis: AXIS,
+            factor,
+            temperature,
+        };
+        if ioboard_net::publish_event(event).is_err() {
+            warn!("Event queue full, dropped derating event");
+        }
+    }
+}
+
+/// Steps the moves pulled from the queue from `start_position`, in steps, holding the position while the queue is
+/// empty, returns the position once the server has switched the axis to server planning, see
+/// [`CommandAction::SwitchPlanning`].
+///
+/// The axis is only homed while at rest, a homing request waits for the queued moves to finish, the same for driver
+/// requests, see [`driver`], and resonance sweeps, see [`input_shaping`].
+async fn run_trajectory_loop<STEPPER: Stepper>(
+    stepper: &mut STEPPER,
+    pulse_generator: &mut impl StepPulseGenerator<STEPPER>,
+    queue: &mut MotionQueue,
+    homing: &mut AxisHoming<impl LimitSwitch>,
+    mut thermal_model: Option<&mut ThermalModel>,
+    mut load_monitor: Option<&mut LoadMonitor>,
+    mut motion_anomaly_monitor: Option<&mut MotionAnomalyMonitor>,
+    shaper_config: Option<&ShaperConfig>,
+    start_position: i64,
+    cancellation: &StepperCancellation,
+) -> Result<i64, StepperError> {
     // -------- Configuration ---------
     let cycle_interval_micros = 1000; // 1 ms cycle (1000 Hz)
     let dt = 1.0_f64 / cycle_interval_micros as f64;
 
     info!("cycle_interval_micros: {}, dt: {}", cycle_interval_micros, dt);
 
-    info!("Trajectory (units):");
-    for (position, jerk, acc, velocity) in trajectory_units {
-        info!(
-            "position: {}, jerk: {}, acc: {}, velocity: {}",
-            position, jerk, acc, velocity
-        );
-    }
+    let mut ruckig = AxisRuckig::new(None, dt);
 
-    let trajectory_steps = trajectory_units
-        .iter()
-        .map(|(position, jerk, acc, velocity)| {
-            (
-                (position * steps_per_unit) as i64,
-                jerk * steps_per_unit,
-                acc * steps_per_unit,
-                velocity * steps_per_unit,
-            )
-        })
-        .collect::<Vec<(i64, f64, f64, f64)>>();
-
-    info!("Trajectory (steps):");
-    for (position, jerk, acc, velocity) in &trajectory_steps {
-        info!(
-            "position: {}, jerk: {}, acc: {}, velocity: {}",
-            position, jerk, acc, velocity
-        );
+    let mut input = InputParameter::<1>::new(None);
+    let mut output = OutputParameter::<1>::new(None);
+    let mut last_position_steps = start_position;
+
+    // the planned position of the cycle, in steps, before shaping
+    let mut planned_position = start_position as f64;
+    input.current_position = daov_stack![planned_position];
+
+    // an invalid shaper config moves the axis unshaped, rather than not at all
+    let mut shaper = shaper_config.and_then(|config| {
+        InputShaper::new(config, dt)
+            .inspect_err(|e| warn!("Input shaper disabled, invalid config. error: {}", e))
+            .ok()
+    });
+    if let Some(shaper) = &mut shaper {
+        shaper.reset(planned_position);
     }
 
-    let mut ruckig = Ruckig::<1, ThrowErrorHandler>::new(None, dt);
+    // the direction is set per-cycle, since with input shaping the tail of one move overlaps the next
+    let mut direction: Option<StepperDirection> = None;
 
-    let mut input = InputParameter::<1>::new(None);
-    let mut output = OutputParameter::<1>::new(None);
-    let mut last_position_steps = 0i64;
+    // `None` while the axis is at rest
+    let mut current_move: Option<QueuedMove> = None;
 
-    let mut segment_index = 0;
+    // after the last queued move is finished, the remaining cycles to step while the shaped position settles
+    let mut settle_cycles: Option<usize> = None;
 
-    let mut prepare_next_segment = true;
+    // the rest of the stop of an aborted move
+    let mut braking: Option<StopRamp> = None;
 
-    let mut cycle_ticker = Ticker::every(Duration::from_micros(cycle_interval_micros));
+    let mut position_report_cycle = 0_u32;
+    let mut load_sample_cycle = 0_u32;
 
-    loop {
-        if prepare_next_segment {
-            info!("Preparing segment, index: {}", segment_index);
+    // filled each cycle, and output by the pulse generator
+    let mut pulses = PulseIntervals::new();
 
-            let (target_steps, max_jerk, max_acc, max_vel) = trajectory_steps[segment_index];
+    let mut driver = AxisDriver::new(AXIS);
 
-            if target_steps as f64 > output.new_position[0] {
-                info!("Direction: Normal");
-                stepper.direction(StepperDirection::Normal)?;
-            } else {
-                info!("Direction: Reversed");
-                stepper.direction(StepperDirection::Reversed)?;
-            }
+    let mut thermal_updated_at = Instant::now();
 
-            input.target_position = daov_stack![target_steps as f64];
-            input.target_velocity = daov_stack![0.0];
-            input.target_acceleration = daov_stack![0.0];
+    let mut cycle_ticker = Ticker::every(Duration::from_micros(cycle_interval_micros));
 
-            input.max_jerk = daov_stack![max_jerk];
-            input.max_acceleration = daov_stack![max_acc];
-            input.max_velocity = daov_stack![max_vel];
+    loop {
+        // the abort is answered once the axis has stopped, no other command is sent until then
+        if braking.is_none() {
+            let at_rest = current_move.is_none() && settle_cycles.is_none() && queue.is_empty();
+            let request = match at_rest {
+                // there is nothing to step until a move is queued, only the cancellation is checked
+                true => match with_timeout(
+                    REST_POLL_INTERVAL,
+                    select4(
+                        MOTION_COMMANDS.receive(),
+                        HOMING_REQUESTS.receive(),
+                        DRIVER_REQUESTS.receive(),
+                        RESONANCE_SWEEP_REQUESTS.receive(),
+                    ),
+                )
+                .await
+                {
+                    Ok(Either4::First(request)) => {
+                        cycle_ticker.reset();
+                        Some(request)
+                    }
+                    Ok(Either4::Second(request)) => {
+                        // the homing moves step the stepper directly
+                        pulse_generator.wait_idle().await?;
+                        update_thermal_model(
+                            thermal_model.as_deref_mut(),
+                            driver.current(false),
+                            &mut thermal_updated_at,
+                        );
+                        driver.engage(stepper)?;
+                        let homed = homing
+                            .handle(stepper, request, cancellation)
+                            .await;
+                        update_thermal_model(
+                            thermal_model.as_deref_mut(),
+                            driver.current(true),
+                            &mut thermal_updated_at,
+                        );
+                        if let Some(position) = homed? {
+                            // the next move is planned from the endstop, or the nearest soft limit
+                            last_position_steps = position;
+                            planned_position = position as f64;
+                            input.current_position = daov_stack![planned_position];
+                            input.current_velocity = daov_stack![0.0];
+                            input.current_acceleration = daov_stack![0.0];
+                            if let Some(shaper) = &mut shaper {
+                                shaper.reset(planned_position);
+                            }
+                            ioboard_net::publish_position(&PositionReport {
+                                axis: AXIS,
+                                position: last_position_steps,
+                            });
+                        }
+                        // the homing moves changed the direction
+                        direction = None;
+                        cycle_ticker.reset();
+                        continue;
+                    }
+                    Ok(Either4::Third(request)) => {
+                        pulse_generator.wait_idle().await?;
+                        driver.handle(stepper, request).await;
+                        cycle_ticker.reset();
+                        continue;
+                    }
+                    Ok(Either4::Fourth(request)) => {
+                        // the sweep steps the stepper directly, and returns the axis to its position
+                        pulse_generator.wait_idle().await?;
+                        update_thermal_model(
+                            thermal_model.as_deref_mut(),
+                            driver.current(false),
+                            &mut thermal_updated_at,
+                        );
+                        driver.engage(stepper)?;
+                        let swept =
+                            handle_resonance_sweep(AXIS, stepper, &mut &VIBRATION_MONITOR, request, cancellation).await;
+                        update_thermal_model(
+                            thermal_model.as_deref_mut(),
+                            driver.current(true),
+                            &mut thermal_updated_at,
+                        );
+                        swept?;
+                        direction = None;
+                        cycle_ticker.reset();
+                        continue;
+                    }
+                    Err(_) => {
+                        cancellation.check()?;
+                        // the driver cools while the axis is at rest
+                        update_thermal_model(
+                            thermal_model.as_deref_mut(),
+                            driver.current(false),
+                            &mut thermal_updated_at,
+                        );
+                        continue;
+                    }
+                },
+                false => MOTION_COMMANDS.try_receive(),
+            };
+
+            if let Some(request) = request {
+                let move_id = current_move.map(|current_move| current_move.move_id);
+                match queue.handle(&request, move_id, last_position_steps, homing.is_homed()) {
+                    CommandAction::Respond(response) => MOTION_COMMANDS.respond(response).await,
+                    // the shaped position is still settling
+                    CommandAction::SwitchPlanning(_) if settle_cycles.is_some() => {
+                        warn!("Planning not switched, axis moving, axis: {}", AXIS);
+                        MOTION_COMMANDS
+                            .respond(Err(MotionCommandError::Moving))
+                            .await
+                    }
+                    CommandAction::SwitchPlanning(response) => {
+                        info!(
+                            "Switching to server planning, axis: {}, position: {}",
+                            AXIS, last_position_steps
+                        );
+                        MOTION_COMMANDS.respond(response).await;
+                        return Ok(last_position_steps);
+                    }
+                    CommandAction::Abort => {
+                        // in steps per cycle, and steps per cycle², the same as the limits of the move
+                        let velocity = match current_move {
+                            Some(_) => output.new_velocity[0] * dt,
+                            None => 0.0,
+                        };
+                        let deceleration = input.max_acceleration[0] * dt * dt;
+                        info!("Aborting move, id: {}, velocity: {}", move_id, velocity);
+                        braking = Some(StopRamp::new(planned_position, velocity, deceleration));
+                        current_move = None;
+                    }
+                }
+            }
+        }
 
-            output.time = 0.0;
-            output.new_section = segment_index;
+        let mut move_started = false;
+        if braking.is_none() && current_move.is_none() && !queue.is_empty() {
+            // a move in progress is completed, stopping mid-move could lose steps
+            MOTION_RESTRICTIONS
+                .wait_while_paused()
+                .await;
+
+            // only this loop takes commands, the queue didn't change while paused
+            let next_move = queue.pop().unwrap();
+            info!("Starting move, id: {}, target: {}", next_move.move_id, next_move.target);
+            driver.engage(stepper)?;
+
+            // derating and speed reduction are only applied at move boundaries, re-planning mid-move is too
+            // expensive
+            let derating_factor = thermal_model
+                .as_ref()
+                .map_or(1.0, |model| model.factor() as f64)
+                * MOTION_RESTRICTIONS.speed_factor() as f64;
+
+            prepare_segment(&mut ruckig, &mut input, &mut output, &next_move, derating_factor);
+
+            if let Some(monitor) = motion_anomaly_monitor.as_deref_mut() {
+                monitor.reset();
+            }
 
-            ruckig.reset();
+            current_move = Some(next_move);
+            settle_cycles = None;
+            move_started = true;
         }
 
-        tracepin::on(0);
+        if let Some(ramp) = &mut braking {
+            match ramp.next() {
+                Some(position) => planned_position = position,
+                None => {
+                    // the next move is planned from where the axis stopped
+                    input.current_position = daov_stack![planned_position];
+                    input.current_velocity = daov_stack![0.0];
+                    input.current_acceleration = daov_stack![0.0];
+                    braking = None;
+                    settle_cycles = Some(
+                        shaper
+                            .as_ref()
+                            .map_or(0, InputShaper::delay_cycles),
+                    );
+                    MOTION_COMMANDS
+                        .respond(queue.finish_abort(round(planned_position) as i64))
+                        .await;
+                }
+            }
+        } else if let Some(active_move) = current_move {
+            tracepin::on(0);
 
-        // On an STM32H743ZI @ 400Mhz this takes ~758us when the segment is changed, and ~25us otherwise (including tracepin overheads)
-        let result = ruckig
-            .update(&input, &mut output)
-            .unwrap();
-        output.pass_to_input(&mut input);
+            // see `advance_segment` for the timings, `ioboard/motion_bench` for the host benchmarks
+            let (position, finished) = advance_segment(&mut ruckig, &mut input, &mut output);
 
-        tracepin::off(0);
+            tracepin::off(0);
 
-        if prepare_next_segment {
-            prepare_next_segment = false;
+            if move_started {
+                // When changing the move, after the initial calculation is done, which takes longer then normal,
+                // a the cycle deadline is reset to avoid first-step jitter on the rare case where there is actually
+                // a step on the first cycle.
+                cycle_ticker.reset();
+            }
 
-            // When changing the segment, after the initial calculation is done, which takes longer then normal,
-            // a the cycle deadline is reset to avoid first-step jitter on the rare case where there is actually
-            // a step on the first cycle.
-            cycle_ticker.reset();
+            planned_position = position;
+            if finished {
+                info!("Move finished, id: {}", active_move.move_id);
+                current_move = None;
+                if queue.is_empty() {
+                    settle_cycles = Some(
+                        shaper
+                            .as_ref()
+                            .map_or(0, InputShaper::delay_cycles),
+                    );
+                }
+            }
         }
 
-        if matches!(result, RuckigResult::Finished) {
-            // prepare for new segment
-            segment_index += 1;
-            if segment_index >= trajectory_steps.len() {
-                break;
-            } else {
-                prepare_next_segment = true;
+        let position = match &mut shaper {
+            Some(shaper) => shaper.shape(planned_position),
+            None => planned_position,
+        };
+
+        let cycle_steps = convert_to_steps(position, last_position_steps);
+        let new_position_steps = cycle_steps.position_steps;
+        let delta_steps = cycle_steps.delta_steps;
+        let steps_this_cycle = cycle_steps.steps();
+
+        let required_direction = match delta_steps {
+            0 => direction.clone(),
+            delta if delta > 0 => Some(StepperDirection::Normal),
+            _ => Some(StepperDirection::Reversed),
+        };
+        if required_direction != direction {
+            if let Some(required_direction) = required_direction.clone() {
+                info!("Direction: {}", required_direction);
+                // the pulses of the previous cycle may still be being output
+                pulse_generator.wait_idle().await?;
+                stepper.direction(required_direction)?;
             }
+            direction = required_direction;
         }
 
-        // Convert to steps with rounding - deterministic and safe because ruckig final position always includes target position.
-        let new_position_steps = round(output.new_position[0]) as i64;
-        let steps_this_cycle = (new_position_steps - last_position_steps).abs() as u32;
-
-        // FUTURE improve step spacing (e.g. by using a hardware timer to control the step pulse width and frequency
-        //        or by using a hardware driven DMA stream
+        // the driver is at the run current for the whole move, also in the cycles without a step
+        let moving = current_move.is_some() || braking.is_some() || settle_cycles.is_some();
+        update_thermal_model(
+            thermal_model.as_deref_mut(),
+            driver.current(moving),
+            &mut thermal_updated_at,
+        );
 
-        if steps_this_cycle > 0 {
-            let cycle_start_us = Instant::now().as_micros();
-            let pulse_interval_us: u64 = cycle_interval_micros / steps_this_cycle as u64;
+        pulses.fill(steps_this_cycle, cycle_interval_micros as u32)?;
+        let burst_started_at = Instant::now();
+        pulse_generator
+            .generate(stepper, &pulses, burst_started_at, cancellation)
+            .await?;
+
+        if let Some(monitor) = motion_anomaly_monitor.as_deref_mut() {
+            let cycle = StepCycle {
+                steps: delta_steps,
+                commanded: Duration::from_micros(cycle_interval_micros),
+                elapsed: burst_started_at.elapsed(),
+            };
+            monitor.check(stepper, &cycle, cancellation)?;
+        }
 
-            let mut step_deadline = cycle_start_us;
+        // Prepare input for next cycle
+        last_position_steps = new_position_steps;
 
-            for _ in 0..steps_this_cycle {
-                let pulse_delay = stepper.step().await?;
+        position_report_cycle += 1;
+        if position_report_cycle >= POSITION_REPORT_INTERVAL_CYCLES {
+            position_report_cycle = 0;
+            ioboard_net::publish_position(&PositionReport {
+                axis: AXIS,
+                position: last_position_steps,
+            });
+        }
 
-                // wait until next step pulse or the pulse delay has elapsed
-                step_deadline = step_deadline.wrapping_add(pulse_interval_us.max(pulse_delay as u64));
-                Timer::at(Instant::from_micros(step_deadline)).await
+        if let Some(monitor) = load_monitor.as_deref_mut() {
+            load_sample_cycle += 1;
+            if load_sample_cycle >= LOAD_SAMPLE_INTERVAL_CYCLES {
+                load_sample_cycle = 0;
+                monitor.sample(stepper, cancellation)?;
             }
         }
 
-        // Prepare input for next cycle
-        last_position_steps = new_position_steps;
+        // once settled the axis is at rest, and the loop waits for the next command
+        if let Some(remaining) = &mut settle_cycles {
+            match *remaining {
+                0 => settle_cycles = None,
+                _ => *remaining -= 1,
+            }
+        }
 
         // Sleep until next RT cycle
         cycle_ticker.next().await;
     }
-
-    Ok::<(), StepperError>(())
 }
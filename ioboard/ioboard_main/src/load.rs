@@ -0,0 +1,169 @@
+//! Per-axis load monitoring using the current feedback of the stepper driver, see [`Stepper::read_feedback`].
+//!
+//! The load is published periodically, and a sudden rise of the load of a moving axis above its moving average is
+//! treated as a crash, e.g. the nozzle hitting a component or the fixture, which cancels stepper operations the same
+//! as an e-stop.  This complements the load cell, which only sees the nozzle force along the z axis.
+//!
+//! StallGuard is not valid at low speeds or at standstill, so the baseline is only tracked, and crashes are only
+//! detected, while the driver reports that the axis is moving.
+
+use defmt::{error, warn};
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::load::AxisLoad;
+
+use crate::stepper::{Stepper, StepperCancellation, StepperError};
+
+/// Raw feedback from a driver with current feedback.
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub struct DriverFeedback {
+    /// StallGuard result, 0 = stalled, `stall_guard_max` = no load
+    pub stall_guard: u16,
+    pub stall_guard_max: u16,
+    /// actual current scale, 0 to `current_scale_max`
+    pub current_scale: u8,
+    pub current_scale_max: u8,
+    pub standstill: bool,
+}
+
+impl DriverFeedback {
+    /// 0.0-1.0, 1.0 = stalled
+    pub fn load(&self) -> f32 {
+        let max = self.stall_guard_max.max(1) as f32;
+        1.0 - (self
+            .stall_guard
+            .min(self.stall_guard_max) as f32
+            / max)
+    }
+
+    /// 0.0-1.0
+    pub fn current(&self) -> f32 {
+        let max = self.current_scale_max.max(1) as f32;
+        self.current_scale
+            .min(self.current_scale_max) as f32
+            / max
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoadConfig {
+    /// a rise of the load above the baseline of at least this much is a crash, 0.0-1.0
+    pub crash_threshold: f32,
+    /// consecutive samples above the threshold required for a crash, filters out single noisy readings
+    pub crash_samples: u8,
+    /// smoothing factor of the baseline, 0.0-1.0
+    pub baseline_alpha: f32,
+    /// moving samples used to establish the baseline before crashes are detected, e.g. during acceleration
+    pub warmup_samples: u16,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            crash_threshold: 0.35,
+            crash_samples: 3,
+            baseline_alpha: 0.05,
+            warmup_samples: 20,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LoadMonitor {
+    axis: u8,
+    config: LoadConfig,
+    baseline: Option<f32>,
+    moving_samples: u16,
+    samples_above_threshold: u8,
+}
+
+impl LoadMonitor {
+    pub fn new(axis: u8, config: LoadConfig) -> Self {
+        Self {
+            axis,
+            config,
+            baseline: None,
+            moving_samples: 0,
+            samples_above_threshold: 0,
+        }
+    }
+
+    /// Update the baseline using the latest feedback.
+    ///
+    /// Returns the load, and an event if a crash was detected.
+    pub fn update(&mut self, feedback: &DriverFeedback) -> (AxisLoad, Option<IoBoardEvent>) {
+        let load = feedback.load();
+        let moving = !feedback.standstill;
+
+        let mut event = None;
+        if !moving {
+            // the baseline is re-established for each move, the load depends on the velocity
+            self.baseline = None;
+            self.moving_samples = 0;
+            self.samples_above_threshold = 0;
+        } else {
+            let baseline = *self.baseline.get_or_insert(load);
+            self.moving_samples = self.moving_samples.saturating_add(1);
+
+            let warmed_up = self.moving_samples > self.config.warmup_samples;
+            if warmed_up && load - baseline >= self.config.crash_threshold {
+                self.samples_above_threshold = self
+                    .samples_above_threshold
+                    .saturating_add(1);
+                if self.samples_above_threshold == self.config.crash_samples.max(1) {
+                    event = Some(IoBoardEvent::AxisCrash {
+                        axis: self.axis,
+                        load,
+                        baseline,
+                    });
+                }
+            } else {
+                self.samples_above_threshold = 0;
+                // spikes are not added to the baseline, otherwise a slow crash would raise it
+                self.baseline = Some(baseline + (load - baseline) * self.config.baseline_alpha);
+            }
+        }
+
+        let axis_load = AxisLoad {
+            axis: self.axis,
+            load,
+            current: feedback.current(),
+            baseline: self.baseline.unwrap_or(load),
+            moving,
+        };
+
+        (axis_load, event)
+    }
+
+    /// Read the driver feedback, publish the load, and cancel stepper operations if a crash was detected.
+    ///
+    /// Does nothing for drivers without feedback.  A crash returns [`StepperError::Cancelled`], feedback read
+    /// errors are only logged, the driver error will also be seen by the next stepper operation.
+    pub fn sample(
+        &mut self,
+        stepper: &mut impl Stepper,
+        cancellation: &StepperCancellation,
+    ) -> Result<(), StepperError> {
+        let feedback = match stepper.read_feedback() {
+            Ok(Some(feedback)) => feedback,
+            Ok(None) => return Ok(()),
+            Err(_e) => {
+                warn!("Unable to read driver feedback, axis: {}", self.axis);
+                return Ok(());
+            }
+        };
+
+        let (axis_load, event) = self.update(&feedback);
+        ioboard_net::publish_load(&axis_load);
+
+        let Some(event) = event else {
+            return Ok(());
+        };
+
+        cancellation.cancel();
+        error!("Axis crash detected, motion stopped. {}", event);
+        if ioboard_net::publish_event(event).is_err() {
+            warn!("Event queue full, dropped crash event");
+        }
+        Err(StepperError::Cancelled)
+    }
+}
@@ -0,0 +1,108 @@
+//! The nozzle load cell, read via an HX717 ADC at 320Hz, see [`LoadCell`], each conversion is tared and scaled to
+//! grams, and published as a [`LoadCellSample`] while the server has begun the stream.
+//!
+//! The load cell is tared when the task starts, so nothing must press against the nozzle then.  The scale is the
+//! calibration of the load cell, it doesn't change when the io board restarts.
+
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use hx717::{Calibration, Hx717};
+use ioboard_shared::load_cell::LoadCellSample;
+
+/// The load cell is tared to the mean of this many conversions, 100ms at 320Hz.
+const TARE_CONVERSIONS: usize = 32;
+
+/// DOUT is polled at this interval until a conversion is ready, at 320Hz a conversion is ready every 3.125ms.
+const POLL_INTERVAL: Duration = Duration::from_micros(250);
+
+/// A conversion that isn't ready after this is reported, e.g. the HX717 is not connected.
+const CONVERSION_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub struct LoadCell<SCK, DOUT, DELAY> {
+    adc: Hx717<SCK, DOUT, DELAY>,
+    calibration: Calibration,
+}
+
+impl<SCK, DOUT, DELAY> LoadCell<SCK, DOUT, DELAY>
+where
+    SCK: OutputPin,
+    DOUT: InputPin,
+    DELAY: DelayNs,
+{
+    /// `grams_per_count` is the scale of the load cell, the grams per count of the conversions of the HX717.
+    pub fn new(adc: Hx717<SCK, DOUT, DELAY>, grams_per_count: f32) -> Self {
+        Self {
+            adc,
+            calibration: Calibration::new(grams_per_count),
+        }
+    }
+
+    /// Waits for the next conversion, `None` if it isn't ready within the [`CONVERSION_TIMEOUT`] or can't be read.
+    async fn next_conversion(&mut self) -> Option<i32> {
+        let deadline = Instant::now() + CONVERSION_TIMEOUT;
+        loop {
+            match self.adc.read() {
+                Ok(conversion) => return Some(conversion),
+                Err(hx717::Error::NotReady) if Instant::now() < deadline => Timer::after(POLL_INTERVAL).await,
+                Err(_) => {
+                    // a failed read is retried at the deadline, so a broken connection doesn't spin
+                    Timer::at(deadline).await;
+                    return None;
+                }
+            }
+        }
+    }
+
+    async fn tare(&mut self) {
+        let mut conversions = [0_i32; TARE_CONVERSIONS];
+        let mut count = 0;
+        let mut failing = false;
+        while count < TARE_CONVERSIONS {
+            match self.next_conversion().await {
+                Some(conversion) => {
+                    conversions[count] = conversion;
+                    count += 1;
+                }
+                None if !failing => {
+                    warn!("Unable to tare load cell, conversion failed");
+                    failing = true;
+                }
+                None => {}
+            }
+        }
+        self.calibration.tare(&conversions);
+        info!(
+            "Load cell tared, offset: {}, grams per count: {}",
+            self.calibration.offset, self.calibration.grams_per_count
+        );
+    }
+
+    /// Tare the load cell, then publish each conversion.
+    pub async fn run(mut self) -> ! {
+        self.tare().await;
+
+        let mut failing = false;
+        loop {
+            let Some(raw) = self.next_conversion().await else {
+                if !failing {
+                    warn!("Unable to read load cell, rate: {}Hz", self.adc.rate().hz());
+                }
+                failing = true;
+                continue;
+            };
+            if failing {
+                info!("Load cell recovered");
+                failing = false;
+            }
+
+            let sample = LoadCellSample {
+                raw,
+                grams: self.calibration.grams(raw),
+                timestamp: Instant::now().as_micros(),
+            };
+            ioboard_net::publish_load_cell(&sample);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Crash detection from the motion of an axis, for axes without a load cell or driver feedback, see
+//! [`LoadMonitor`](crate::load::LoadMonitor) for drivers with feedback.
+//!
+//! Intended for the Z axis, where the nozzle can hit an unplanned obstacle on the way down, e.g. a tall component.
+//! Each cycle the step pulses are compared with the commanded motion:
+//! * the time the pulses took, pulses held off by the driver take longer than the cycle.
+//! * the position measured by the encoder, when the axis has one, a blocked axis falls behind the commanded position.
+//!
+//! An anomaly cancels stepper operations the same as an e-stop.
+
+use defmt::{error, warn};
+use embassy_time::Duration;
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::motion::MotionAnomaly;
+use libm::round;
+
+use crate::stepper::{Stepper, StepperCancellation, StepperError};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MotionAnomalyConfig {
+    /// the pulses of a cycle that took longer than the cycle by more than this are delayed, in microseconds
+    pub max_pulse_overrun_us: u32,
+    /// consecutive delayed cycles required for an anomaly, filters out a single late cycle, e.g. an interrupt
+    pub delayed_cycles: u8,
+    /// steps per count of the encoder, ignored for axes without an encoder
+    pub steps_per_count: f64,
+    /// a difference of the measured position from the commanded position of more than this is an anomaly, in steps
+    pub max_following_error_steps: u32,
+}
+
+impl Default for MotionAnomalyConfig {
+    fn default() -> Self {
+        Self {
+            max_pulse_overrun_us: 200,
+            delayed_cycles: 3,
+            steps_per_count: 1.0,
+            max_following_error_steps: 16,
+        }
+    }
+}
+
+/// The step pulses of a cycle.
+#[derive(Debug, Clone, Copy)]
+pub struct StepCycle {
+    /// the steps of the cycle, negative when reversed
+    pub steps: i64,
+    pub commanded: Duration,
+    /// the time the pulses took
+    pub elapsed: Duration,
+}
+
+#[derive(Debug)]
+pub struct MotionAnomalyMonitor {
+    axis: u8,
+    config: MotionAnomalyConfig,
+    delayed_cycles: u8,
+    /// since the start of the move
+    commanded_steps: i64,
+    measured_counts: i64,
+    last_count: Option<u16>,
+}
+
+impl MotionAnomalyMonitor {
+    pub fn new(axis: u8, config: MotionAnomalyConfig) -> Self {
+        Self {
+            axis,
+            config,
+            delayed_cycles: 0,
+            commanded_steps: 0,
+            measured_counts: 0,
+            last_count: None,
+        }
+    }
+
+    /// Called at the start of each move, the following error is measured from the start of the move.
+    pub fn reset(&mut self) {
+        self.delayed_cycles = 0;
+        self.commanded_steps = 0;
+        self.measured_counts = 0;
+        self.last_count = None;
+    }
+
+    /// Update using the step pulses of the cycle, and the encoder count after the cycle, `None` for axes without an
+    /// encoder.
+    ///
+    /// Returns an event if an anomaly was detected.
+    pub fn update(&mut self, cycle: &StepCycle, encoder_count: Option<u16>) -> Option<IoBoardEvent> {
+        self.update_pulses(cycle)
+            .or_else(|| self.update_position(cycle, encoder_count))
+            .map(|anomaly| IoBoardEvent::AxisMotionAnomaly {
+                axis: self.axis,
+                anomaly,
+            })
+    }
+
+    fn update_pulses(&mut self, cycle: &StepCycle) -> Option<MotionAnomaly> {
+        let overrun_us = cycle
+            .elapsed
+            .as_micros()
+            .saturating_sub(cycle.commanded.as_micros());
+        // an idle cycle only waits, the time it takes says nothing about the motion
+        if cycle.steps == 0 || overrun_us <= self.config.max_pulse_overrun_us as u64 {
+            self.delayed_cycles = 0;
+            return None;
+        }
+
+        self.delayed_cycles = self.delayed_cycles.saturating_add(1);
+        if self.delayed_cycles != self.config.delayed_cycles.max(1) {
+            return None;
+        }
+        Some(MotionAnomaly::PulsesDelayed {
+            commanded_us: cycle.commanded.as_micros() as u32,
+            elapsed_us: cycle.elapsed.as_micros() as u32,
+        })
+    }
+
+    fn update_position(&mut self, cycle: &StepCycle, encoder_count: Option<u16>) -> Option<MotionAnomaly> {
+        self.commanded_steps += cycle.steps;
+
+        let count = encoder_count?;
+        // the first count of a move is the origin, the steps of that cycle were taken before it was read
+        let Some(last_count) = self.last_count.replace(count) else {
+            self.commanded_steps = 0;
+            return None;
+        };
+        // the counter wraps around, the axis moves far less than half the range of the counter per cycle
+        self.measured_counts += count.wrapping_sub(last_count) as i16 as i64;
+
+        let measured_steps = round(self.measured_counts as f64 * self.config.steps_per_count) as i64;
+        if self
+            .commanded_steps
+            .abs_diff(measured_steps)
+            <= self.config.max_following_error_steps as u64
+        {
+            return None;
+        }
+        Some(MotionAnomaly::FollowingError {
+            commanded_steps: self.commanded_steps,
+            measured_steps,
+        })
+    }
+
+    /// Check the cycle, and cancel stepper operations if an anomaly was detected.
+    ///
+    /// A detected anomaly returns [`StepperError::Cancelled`], encoder read errors are only logged and the cycle is
+    /// checked without the encoder.
+    pub fn check(
+        &mut self,
+        stepper: &mut impl Stepper,
+        cycle: &StepCycle,
+        cancellation: &StepperCancellation,
+    ) -> Result<(), StepperError> {
+        let encoder_count = match stepper.read_encoder() {
+            Ok(count) => count,
+            Err(_e) => {
+                warn!("Unable to read encoder, axis: {}", self.axis);
+                None
+            }
+        };
+
+        let Some(event) = self.update(cycle, encoder_count) else {
+            return Ok(());
+        };
+
+        cancellation.cancel();
+        error!("Axis motion anomaly detected, motion stopped. {}", event);
+        if ioboard_net::publish_event(event).is_err() {
+            warn!("Event queue full, dropped motion anomaly event");
+        }
+        Err(StepperError::Cancelled)
+    }
+}
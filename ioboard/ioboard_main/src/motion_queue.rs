@@ -0,0 +1,161 @@
+//! Commanded motion, for boards that plan their own trajectories.
+//!
+//! The server queues moves via the [`MotionCommandEndpoint`](ioboard_net::MotionCommandEndpoint), the trajectory loop
+//! pulls the next move from the queue when the move in progress is finished, and holds the position while the queue is
+//! empty, see [`MotionCommand`].
+//!
+//! A move is checked when it's queued, an invalid move, a move outside the [`SoftLimits`] of the axis, or a move of an
+//! axis that isn't homed, see [`AxisHoming::is_homed`](crate::homing::AxisHoming::is_homed), is refused.
+
+use alloc::collections::VecDeque;
+
+use defmt::{info, warn};
+use ioboard_shared::motion::{
+    MotionCommand, MotionCommandError, MotionCommandRequest, MotionCommandResponse, MotionPlanning, MotionQueueStatus,
+    QueuedMove,
+};
+use machine_ids::MoveId;
+
+use crate::soft_limits::SoftLimits;
+
+/// A few seconds of short moves, the server tops the queue up as moves finish.
+pub const MOTION_QUEUE_SIZE: usize = 32;
+
+/// What the trajectory loop has to do to complete a command.
+pub enum CommandAction {
+    Respond(MotionCommandResponse),
+    /// Brake the move in progress, then respond with [`MotionQueue::finish_abort`], the queued moves have already
+    /// been discarded
+    Abort,
+    /// Respond, then hand the axis over to the setpoint follower, see [`MotionCommand::SetPlanning`], the queue is
+    /// empty and no move is in progress
+    SwitchPlanning(MotionCommandResponse),
+}
+
+pub struct MotionQueue {
+    axis: u8,
+    /// `None` if the travel of the axis is not limited
+    soft_limits: Option<SoftLimits>,
+    moves: VecDeque<QueuedMove>,
+    /// the number of moves discarded by an abort that is braking the axis
+    aborting: Option<u32>,
+}
+
+impl MotionQueue {
+    pub fn new(axis: u8, soft_limits: Option<SoftLimits>) -> Self {
+        Self {
+            axis,
+            soft_limits,
+            moves: VecDeque::with_capacity(MOTION_QUEUE_SIZE),
+            aborting: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    pub fn pop(&mut self) -> Option<QueuedMove> {
+        self.moves.pop_front()
+    }
+
+    /// `current_move` and `position`, in steps, are of the trajectory loop, they are reported in the status, moves are
+    /// refused unless the axis is `homed`.
+    pub fn handle(
+        &mut self,
+        request: &MotionCommandRequest,
+        current_move: Option<MoveId>,
+        position: i64,
+        homed: bool,
+    ) -> CommandAction {
+        if request.axis != self.axis {
+            warn!("Motion command refused, unknown axis: {}", request.axis);
+            return CommandAction::Respond(Err(MotionCommandError::UnknownAxis));
+        }
+
+        let discarded = match request.command {
+            MotionCommand::Move(queued_move) => {
+                if !homed {
+                    warn!("Move refused, axis not homed, move: {}", queued_move.move_id);
+                    return CommandAction::Respond(Err(MotionCommandError::NotHomed));
+                }
+                if !queued_move.is_valid() {
+                    warn!("Move refused, invalid move: {}", queued_move);
+                    return CommandAction::Respond(Err(MotionCommandError::InvalidMove));
+                }
+                if let Some(soft_limits) = &self.soft_limits
+                    && let Err(e) = soft_limits.check(queued_move.target)
+                {
+                    warn!(
+                        "Move refused, outside soft limits, move: {}, target: {}, limits: {}",
+                        queued_move.move_id, queued_move.target, soft_limits
+                    );
+                    return CommandAction::Respond(Err(e));
+                }
+                if self.moves.len() >= MOTION_QUEUE_SIZE {
+                    warn!("Move refused, queue full, move: {}", queued_move.move_id);
+                    return CommandAction::Respond(Err(MotionCommandError::QueueFull));
+                }
+                self.moves.push_back(queued_move);
+                0
+            }
+            MotionCommand::QueueDepth => 0,
+            MotionCommand::Flush => self.discard(),
+            MotionCommand::Abort => {
+                self.aborting = Some(self.discard());
+                return CommandAction::Abort;
+            }
+            MotionCommand::SetPlanning(MotionPlanning::OnBoard) => 0,
+            MotionCommand::SetPlanning(MotionPlanning::Server) => {
+                if current_move.is_some() || !self.moves.is_empty() {
+                    warn!("Planning not switched, axis moving, axis: {}", self.axis);
+                    return CommandAction::Respond(Err(MotionCommandError::Moving));
+                }
+                return CommandAction::SwitchPlanning(Ok(self.status(None, 0, position)));
+            }
+        };
+
+        CommandAction::Respond(Ok(self.status(current_move, discarded, position)))
+    }
+
+    /// The axis has stopped, `position` is in steps.
+    pub fn finish_abort(&mut self, position: i64) -> MotionCommandResponse {
+        let discarded = self.aborting.take().unwrap_or(0);
+        info!(
+            "Motion aborted, axis: {}, discarded: {}, position: {}",
+            self.axis, discarded, position
+        );
+        Ok(self.status(None, discarded, position))
+    }
+
+    /// The stepper failed or was cancelled, the queued moves are discarded so that the axis doesn't resume moving
+    /// when the cancellation is reset.
+    ///
+    /// Returns the response to an abort that was braking the axis.
+    pub fn stopped(&mut self) -> Option<MotionCommandResponse> {
+        let discarded = self.discard();
+        if discarded > 0 {
+            warn!("Queued moves discarded, axis: {}, discarded: {}", self.axis, discarded);
+        }
+        self.aborting
+            .take()
+            .map(|_| Err(MotionCommandError::Stopped))
+    }
+
+    fn discard(&mut self) -> u32 {
+        let discarded = self.moves.len() as u32;
+        self.moves.clear();
+        discarded
+    }
+
+    fn status(&self, current_move: Option<MoveId>, discarded: u32, position: i64) -> MotionQueueStatus {
+        MotionQueueStatus {
+            axis: self.axis,
+            depth: self.moves.len() as u32,
+            capacity: MOTION_QUEUE_SIZE as u32,
+            current_move,
+            discarded,
+            position,
+        }
+    }
+}
@@ -0,0 +1,469 @@
+//! Power management, supply voltage monitoring and power rail sequencing.
+//!
+//! Motion is stopped via the [`StepperCancellation`] as soon as the supply drops below a threshold, it is checked
+//! before every step pulse, and the motion task then disables the stepper outputs. The cancellation is not reset on
+//! recovery, motion only resumes once the fault has been cleared.
+//!
+//! The fault also clears the [`Interlock::SupplyOk`] interlock, which wakes the power sequencer, so every power rail,
+//! including the motor power of the stepper drivers, is switched off without waiting for its next interlock check, and
+//! a [`IoBoardEvent::SupplyFault`] is published so that the server can park the head once the supply is back.
+//!
+//! Power rails (motor power, vacuum pump, lighting) are enabled in sequence, with a settling delay after each, and
+//! are only enabled when their [`Interlock`]s are satisfied.
+//!
+//! The motor power doesn't require [`Interlock::HomingConfigured`], homing steps the motor, the moves of an axis that
+//! isn't homed are refused instead, see [`AxisHoming::is_homed`](crate::homing::AxisHoming::is_homed).
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::{error, info, warn};
+use embassy_futures::select::{Either, Either3, select, select3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use ioboard_net::POWER_REQUESTS;
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::power::{Interlock, PowerError, PowerRail, PowerRequest, PowerResponse, PowerStatus};
+
+use crate::stepper::StepperCancellation;
+
+/// Supply voltage is sampled at the same rate as the motion control cycle.
+const SAMPLE_INTERVAL: Duration = Duration::from_micros(1000);
+
+pub trait SupplySensor {
+    /// Returns the supply voltage, in volts, or `None` if it could not be read.
+    fn read_voltage(&mut self) -> Option<f32>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SupplyThresholds {
+    /// motion is stopped immediately below this voltage
+    pub undervoltage: f32,
+    /// the supply is considered recovered above this voltage
+    pub recovery: f32,
+    /// how long the supply must stay above the `recovery` voltage before it is reported as stable
+    pub stable_for: Duration,
+}
+
+impl SupplyThresholds {
+    /// Thresholds for a nominal 24V motor supply
+    pub const SUPPLY_24V: Self = Self {
+        undervoltage: 20.0,
+        recovery: 22.0,
+        stable_for: Duration::from_millis(500),
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SupplyState {
+    Normal,
+    Undervoltage {
+        since: Instant,
+        min_voltage: f32,
+    },
+    Recovering {
+        undervoltage_since: Instant,
+        recovered_at: Instant,
+        min_voltage: f32,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SupplyAction {
+    None,
+    /// The supply dropped below the undervoltage threshold, motion must be stopped.
+    Fault,
+    /// The supply has been stable since a fault, the event describes the fault.
+    Stable(IoBoardEvent),
+}
+
+pub struct SupplyMonitor {
+    thresholds: SupplyThresholds,
+    state: SupplyState,
+}
+
+impl SupplyMonitor {
+    pub fn new(thresholds: SupplyThresholds) -> Self {
+        Self {
+            thresholds,
+            state: SupplyState::Normal,
+        }
+    }
+
+    pub fn update(&mut self, voltage: f32, now: Instant) -> SupplyAction {
+        let thresholds = &self.thresholds;
+
+        let (state, action) = match self.state {
+            SupplyState::Normal if voltage < thresholds.undervoltage => (
+                SupplyState::Undervoltage {
+                    since: now,
+                    min_voltage: voltage,
+                },
+                SupplyAction::Fault,
+            ),
+            SupplyState::Normal => (SupplyState::Normal, SupplyAction::None),
+
+            SupplyState::Undervoltage {
+                since,
+                min_voltage,
+            } if voltage >= thresholds.recovery => (
+                SupplyState::Recovering {
+                    undervoltage_since: since,
+                    recovered_at: now,
+                    min_voltage,
+                },
+                SupplyAction::None,
+            ),
+            SupplyState::Undervoltage {
+                since,
+                min_voltage,
+            } => (
+                SupplyState::Undervoltage {
+                    since,
+                    min_voltage: min_voltage.min(voltage),
+                },
+                SupplyAction::None,
+            ),
+
+            SupplyState::Recovering {
+                undervoltage_since,
+                min_voltage,
+                ..
+            } if voltage < thresholds.recovery => (
+                SupplyState::Undervoltage {
+                    since: undervoltage_since,
+                    min_voltage: min_voltage.min(voltage),
+                },
+                SupplyAction::None,
+            ),
+            SupplyState::Recovering {
+                undervoltage_since,
+                recovered_at,
+                min_voltage,
+            } if now - recovered_at >= thresholds.stable_for => {
+                let duration = recovered_at - undervoltage_since;
+                (
+                    SupplyState::Normal,
+                    SupplyAction::Stable(IoBoardEvent::SupplyUndervoltage {
+                        min_voltage,
+                        duration_ms: duration.as_millis() as u32,
+                    }),
+                )
+            }
+            recovering @ SupplyState::Recovering {
+                ..
+            } => (recovering, SupplyAction::None),
+        };
+
+        self.state = state;
+        action
+    }
+}
+
+/// Monitor the supply voltage, cancelling stepper operations and disabling the power rails on undervoltage.
+///
+/// Should be run on the same (high-priority) executor as the motion task so that it is not delayed by other tasks.
+pub async fn monitor_supply(
+    mut sensor: impl SupplySensor,
+    thresholds: SupplyThresholds,
+    cancellation: &StepperCancellation,
+) -> ! {
+    let mut monitor = SupplyMonitor::new(thresholds);
+    let mut ticker = Ticker::every(SAMPLE_INTERVAL);
+
+    info!(
+        "Supply monitor started, undervoltage: {}V, recovery: {}V",
+        thresholds.undervoltage, thresholds.recovery
+    );
+
+    loop {
+        if let Some(voltage) = sensor.read_voltage() {
+            match monitor.update(voltage, Instant::now()) {
+                SupplyAction::None => {}
+                SupplyAction::Fault => {
+                    cancellation.cancel();
+                    // wakes the power sequencer, which switches the rails off
+                    POWER_INTERLOCKS.set(Interlock::SupplyOk, false);
+                    error!("Supply undervoltage, motion stopped. voltage: {}V", voltage);
+                    if ioboard_net::publish_event(IoBoardEvent::SupplyFault {
+                        voltage,
+                    })
+                    .is_err()
+                    {
+                        warn!("Event queue full, dropped supply fault event");
+                    }
+                }
+                SupplyAction::Stable(event) => {
+                    POWER_INTERLOCKS.set(Interlock::SupplyOk, true);
+                    // reported once stable, the network is unlikely to have been usable during the fault
+                    warn!("Supply stable after undervoltage: {}", event);
+                    if ioboard_net::publish_event(event).is_err() {
+                        warn!("Event queue full, dropped supply event");
+                    }
+                }
+            }
+        }
+
+        ticker.next().await;
+    }
+}
+
+/// Interlock states, set by the parts of the system that own them, checked by the power sequencer.
+pub static POWER_INTERLOCKS: PowerInterlocks = PowerInterlocks::new();
+
+pub struct PowerInterlocks {
+    supply_ok: AtomicBool,
+    homing_configured: AtomicBool,
+    safety_inputs_clear: AtomicBool,
+    thermal_ok: AtomicBool,
+    /// signalled when an interlock is no longer satisfied, so the rails are disabled without waiting for the next
+    /// check
+    broken: Signal<CriticalSectionRawMutex, ()>,
+}
+
+impl PowerInterlocks {
+    pub const fn new() -> Self {
+        Self {
+            // assumed ok until the supply monitor reports otherwise
+            supply_ok: AtomicBool::new(true),
+            homing_configured: AtomicBool::new(false),
+            // assumed clear until the safety monitor reports otherwise, same as the supply
+            safety_inputs_clear: AtomicBool::new(true),
+            // assumed ok until a temperature sensor reports otherwise, boards without sensors never do
+            thermal_ok: AtomicBool::new(true),
+            broken: Signal::new(),
+        }
+    }
+
+    fn flag(&self, interlock: Interlock) -> &AtomicBool {
+        match interlock {
+            Interlock::SupplyOk => &self.supply_ok,
+            Interlock::HomingConfigured => &self.homing_configured,
+            Interlock::SafetyInputsClear => &self.safety_inputs_clear,
+            Interlock::ThermalOk => &self.thermal_ok,
+        }
+    }
+
+    pub fn set(&self, interlock: Interlock, satisfied: bool) {
+        let was_satisfied = self
+            .flag(interlock)
+            .swap(satisfied, Ordering::AcqRel);
+        if was_satisfied && !satisfied {
+            self.broken.signal(());
+        }
+    }
+
+    /// Completes once an interlock that was satisfied no longer is.
+    async fn wait_broken(&self) {
+        self.broken.wait().await
+    }
+
+    pub fn is_satisfied(&self, interlock: Interlock) -> bool {
+        self.flag(interlock)
+            .load(Ordering::Acquire)
+    }
+
+    /// Returns the first interlock required by the `rail` that is not satisfied.
+    pub fn check(&self, rail: PowerRail) -> Result<(), Interlock> {
+        let required: &[Interlock] = match rail {
+            PowerRail::MotorPower => &[Interlock::SupplyOk, Interlock::SafetyInputsClear, Interlock::ThermalOk],
+            PowerRail::VacuumPump => &[Interlock::SupplyOk, Interlock::ThermalOk],
+            PowerRail::Lighting => &[Interlock::SupplyOk],
+        };
+
+        match required
+            .iter()
+            .find(|interlock| !self.is_satisfied(**interlock))
+        {
+            Some(interlock) => Err(*interlock),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for PowerInterlocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hardware outputs that switch the power rails.
+pub trait PowerRails {
+    fn set_rail(&mut self, rail: PowerRail, enabled: bool);
+
+    /// Returns the state of the rail read back from the hardware, `None` for rails without feedback.
+    fn read_rail(&mut self, _rail: PowerRail) -> Option<bool> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PowerSequenceConfig {
+    /// the order the rails are enabled by [`PowerRequest::PowerUp`], disabled in reverse order
+    pub sequence: [PowerRail; 3],
+    /// delay after enabling motor power, allows the driver supply capacitors to charge
+    pub motor_power_settle: Duration,
+    /// delay after enabling the vacuum pump, limits the combined inrush current
+    pub vacuum_pump_settle: Duration,
+    pub lighting_settle: Duration,
+}
+
+impl Default for PowerSequenceConfig {
+    fn default() -> Self {
+        Self {
+            sequence: [PowerRail::Lighting, PowerRail::MotorPower, PowerRail::VacuumPump],
+            motor_power_settle: Duration::from_millis(500),
+            vacuum_pump_settle: Duration::from_millis(250),
+            lighting_settle: Duration::from_millis(50),
+        }
+    }
+}
+
+impl PowerSequenceConfig {
+    fn settle_delay(&self, rail: PowerRail) -> Duration {
+        match rail {
+            PowerRail::MotorPower => self.motor_power_settle,
+            PowerRail::VacuumPump => self.vacuum_pump_settle,
+            PowerRail::Lighting => self.lighting_settle,
+        }
+    }
+}
+
+/// How often the interlocks of enabled rails are re-checked.
+const INTERLOCK_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct PowerSequencer<RAILS: PowerRails> {
+    rails: RAILS,
+    config: PowerSequenceConfig,
+    status: PowerStatus,
+}
+
+impl<RAILS: PowerRails> PowerSequencer<RAILS> {
+    /// All rails are disabled on creation.
+    pub fn new(mut rails: RAILS, config: PowerSequenceConfig) -> Self {
+        for rail in config.sequence {
+            rails.set_rail(rail, false);
+        }
+
+        Self {
+            rails,
+            config,
+            status: PowerStatus::default(),
+        }
+    }
+
+    fn is_enabled(&self, rail: PowerRail) -> bool {
+        match rail {
+            PowerRail::MotorPower => self.status.motor_power,
+            PowerRail::VacuumPump => self.status.vacuum_pump,
+            PowerRail::Lighting => self.status.lighting,
+        }
+    }
+
+    fn set(&mut self, rail: PowerRail, enabled: bool) {
+        self.rails.set_rail(rail, enabled);
+        match rail {
+            PowerRail::MotorPower => self.status.motor_power = enabled,
+            PowerRail::VacuumPump => self.status.vacuum_pump = enabled,
+            PowerRail::Lighting => self.status.lighting = enabled,
+        }
+    }
+
+    async fn enable(&mut self, rail: PowerRail) -> Result<(), PowerError> {
+        if self.is_enabled(rail) {
+            return Ok(());
+        }
+        POWER_INTERLOCKS
+            .check(rail)
+            .map_err(|interlock| PowerError::InterlockNotSatisfied(rail, interlock))?;
+
+        info!("Enabling power rail: {}", rail);
+        self.set(rail, true);
+
+        // an interlock broken while the rail settles, e.g. by a supply fault in the middle of a power up, switches the
+        // rails off without waiting for the rest of the sequence
+        let settled_at = Instant::now() + self.config.settle_delay(rail);
+        while let Either::Second(_) = select(Timer::at(settled_at), POWER_INTERLOCKS.wait_broken()).await {
+            self.check_interlocks();
+            POWER_INTERLOCKS
+                .check(rail)
+                .map_err(|interlock| PowerError::InterlockNotSatisfied(rail, interlock))?;
+        }
+        Ok(())
+    }
+
+    fn disable(&mut self, rail: PowerRail) {
+        if self.is_enabled(rail) {
+            info!("Disabling power rail: {}", rail);
+            self.set(rail, false);
+        }
+    }
+
+    async fn handle_request(&mut self, request: PowerRequest) -> PowerResponse {
+        match request {
+            PowerRequest::PowerUp => {
+                for rail in self.config.sequence {
+                    self.enable(rail).await?;
+                }
+            }
+            PowerRequest::PowerDown => {
+                for rail in self.config.sequence.into_iter().rev() {
+                    self.disable(rail);
+                }
+            }
+            PowerRequest::Enable(rail) => self.enable(rail).await?,
+            PowerRequest::Disable(rail) => self.disable(rail),
+            PowerRequest::Status => {}
+        }
+        Ok(self.status)
+    }
+
+    /// Disable any enabled rail whose interlocks are no longer satisfied.
+    fn check_interlocks(&mut self) {
+        for rail in self.config.sequence {
+            if !self.is_enabled(rail) {
+                continue;
+            }
+            if let Err(interlock) = POWER_INTERLOCKS.check(rail) {
+                warn!(
+                    "Power rail interlocked, disabling. rail: {}, interlock: {}",
+                    rail, interlock
+                );
+                self.set(rail, false);
+
+                if ioboard_net::publish_event(IoBoardEvent::PowerRailInterlocked {
+                    rail,
+                    interlock,
+                })
+                .is_err()
+                {
+                    warn!("Event queue full, dropped power event");
+                }
+            }
+        }
+    }
+
+    /// Handle requests from the power endpoint, and enforce the interlocks, the rails are checked as soon as an
+    /// interlock is broken, e.g. on a supply fault, and periodically, also during the settling delays of a request.
+    pub async fn run(mut self) -> ! {
+        let mut interlock_ticker = Ticker::every(INTERLOCK_CHECK_INTERVAL);
+        loop {
+            match select3(
+                POWER_REQUESTS.receive(),
+                interlock_ticker.next(),
+                POWER_INTERLOCKS.wait_broken(),
+            )
+            .await
+            {
+                Either3::First(request) => {
+                    let response = self.handle_request(request).await;
+                    if let Err(error) = &response {
+                        warn!("Power request failed. request: {}, error: {}", request, error);
+                    }
+                    POWER_REQUESTS.respond(response).await;
+                }
+                Either3::Second(_) | Either3::Third(_) => self.check_interlocks(),
+            }
+        }
+    }
+}
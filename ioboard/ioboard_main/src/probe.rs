@@ -0,0 +1,243 @@
+//! Probing, the position of an axis is latched when the probe triggers and the axis stops, see
+//! [`ProbeRequest`](ioboard_shared::probe::ProbeRequest).
+//!
+//! The probe is triggered by a [`ProbeSource`], the source is chosen by the server each time the probe is armed, a
+//! board can have both.  Boards with a dedicated probe input, e.g. a touch probe, or a conductive nozzle and a
+//! grounded plate, run an [`ElectricalProbe`].  A load cell triggers the probe the same way, via [`Probe::latch`],
+//! [`Probe::confirm`] and [`Probe::release`], none of the current boards have one.
+//!
+//! The position is latched by [`Probe::latch`], which doesn't block, so it can be called from an interrupt handler,
+//! at the edge of the input, the trigger is only reported once the input has stayed triggered for the debounce time,
+//! a glitch discards the latched position.  The position is the position of the axis at the start of the cycle the
+//! edge is in, see [`SetpointFollower`](crate::setpoint::SetpointFollower), at most a cycle of steps early.
+//!
+//! Only server-planned moves are stopped, the on-board planner doesn't run moves requested by the server yet.
+
+use core::cell::Cell;
+
+use defmt::{info, warn};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::{Duration, Timer};
+use ioboard_net::PROBE_REQUESTS;
+use ioboard_shared::probe::{ProbeError, ProbeRequest, ProbeResponse, ProbeSource, ProbeStatus, ProbeTrigger};
+
+pub static PROBE: Probe = Probe::new();
+
+#[derive(Debug, Clone, Copy)]
+struct ProbeState {
+    status: ProbeStatus,
+    /// the latest position recorded by the motion executor, `(axis, position)`
+    position: Option<(u8, i64)>,
+    /// latched at the edge, until the debounce confirms or discards it
+    pending: Option<ProbeTrigger>,
+}
+
+pub struct Probe {
+    state: Mutex<CriticalSectionRawMutex, Cell<ProbeState>>,
+}
+
+impl Probe {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(Cell::new(ProbeState {
+                status: ProbeStatus::IDLE,
+                position: None,
+                pending: None,
+            })),
+        }
+    }
+
+    pub fn status(&self) -> ProbeStatus {
+        self.state.lock(Cell::get).status
+    }
+
+    fn update<R>(&self, f: impl FnOnce(&mut ProbeState) -> R) -> R {
+        self.state.lock(|state| {
+            let mut value = state.get();
+            let result = f(&mut value);
+            state.set(value);
+            result
+        })
+    }
+
+    /// Called by the task that runs the source, once it has been initialized.
+    pub fn set_available(&self, source: ProbeSource) {
+        self.update(|state| state.status.available.set(source, true));
+    }
+
+    pub fn handle_request(&self, request: ProbeRequest) -> ProbeResponse {
+        self.update(|state| {
+            let status = &mut state.status;
+            match request {
+                ProbeRequest::Arm(arming) => {
+                    if !status.available.contains(arming.source) {
+                        return Err(ProbeError::SourceUnavailable(arming.source));
+                    }
+                    if status.active.contains(arming.source) {
+                        return Err(ProbeError::AlreadyTriggered(arming.source));
+                    }
+                    status.armed = Some(arming);
+                    status.triggered = None;
+                    state.pending = None;
+                }
+                ProbeRequest::Disarm => {
+                    status.armed = None;
+                    state.pending = None;
+                }
+                ProbeRequest::Status => {}
+            }
+            Ok(state.status)
+        })
+    }
+
+    /// Called by the motion executor every cycle, before the steps of the cycle.
+    pub fn record_position(&self, axis: u8, position: i64) {
+        self.update(|state| state.position = Some((axis, position)));
+    }
+
+    /// Latches the position of the armed axis, at the edge of the source, doesn't block.
+    ///
+    /// Ignored when the probe isn't armed with the source, or a trigger is already latched.
+    pub fn latch(&self, source: ProbeSource) {
+        self.update(|state| {
+            state.status.active.set(source, true);
+
+            let Some(arming) = state.status.armed else {
+                return;
+            };
+            if arming.source != source || state.pending.is_some() || state.status.triggered.is_some() {
+                return;
+            }
+            // the axis hasn't moved since startup
+            let position = match state.position {
+                Some((axis, position)) if axis == arming.axis => position,
+                _ => 0,
+            };
+            state.pending = Some(ProbeTrigger {
+                axis: arming.axis,
+                source,
+                position,
+            });
+        });
+    }
+
+    /// Called once the source has stayed triggered for the debounce time, the latched trigger is reported and the
+    /// axis stops.
+    pub fn confirm(&self, source: ProbeSource) -> Option<ProbeTrigger> {
+        self.update(|state| {
+            let trigger = state
+                .pending
+                .take()
+                .filter(|trigger| trigger.source == source)?;
+            state.status.triggered = Some(trigger);
+            Some(trigger)
+        })
+    }
+
+    /// Called when the source is released, a trigger latched by a glitch is discarded.
+    pub fn release(&self, source: ProbeSource) {
+        self.update(|state| {
+            state.status.active.set(source, false);
+            if state
+                .pending
+                .is_some_and(|trigger| trigger.source == source)
+            {
+                state.pending = None;
+            }
+        });
+    }
+
+    /// `true` while the probe armed on the axis has triggered, the axis must not move until the probe is disarmed.
+    pub fn stops_axis(&self, axis: u8) -> bool {
+        let status = self.status();
+        status
+            .armed
+            .zip(status.triggered)
+            .is_some_and(|(arming, trigger)| arming.axis == axis && trigger.axis == axis)
+    }
+
+    /// Handle requests from the probe endpoint.
+    pub async fn run(&self) -> ! {
+        info!("Probe started");
+        loop {
+            let request = PROBE_REQUESTS.receive().await;
+            let response = self.handle_request(request);
+            match &response {
+                Ok(status) => info!("Probe updated: {}", status),
+                Err(error) => warn!("Probe request failed. request: {}, error: {}", request, error),
+            }
+            PROBE_REQUESTS.respond(response).await;
+        }
+    }
+}
+
+impl Default for Probe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A dedicated probe input.
+#[allow(async_fn_in_trait)]
+pub trait ProbeInput {
+    /// Completes at the edge of the input, immediately if it is already triggered.
+    async fn wait_for_trigger(&mut self);
+    /// Completes once the input is no longer triggered, immediately if it isn't triggered.
+    async fn wait_for_release(&mut self);
+    fn is_triggered(&mut self) -> bool;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ElectricalProbeConfig {
+    /// how long the input must stay triggered for the trigger to be reported
+    pub debounce: Duration,
+}
+
+impl Default for ElectricalProbeConfig {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_micros(500),
+        }
+    }
+}
+
+/// Triggers the [`PROBE`] from a [`ProbeInput`].
+pub struct ElectricalProbe<INPUT: ProbeInput> {
+    input: INPUT,
+    config: ElectricalProbeConfig,
+}
+
+impl<INPUT: ProbeInput> ElectricalProbe<INPUT> {
+    pub fn new(input: INPUT, config: ElectricalProbeConfig) -> Self {
+        Self {
+            input,
+            config,
+        }
+    }
+
+    /// Run on the high-priority executor, so that the position is latched promptly after the edge.
+    pub async fn run(mut self) -> ! {
+        PROBE.set_available(ProbeSource::Electrical);
+        info!(
+            "Electrical probe started, debounce: {}us",
+            self.config.debounce.as_micros()
+        );
+        loop {
+            self.input.wait_for_trigger().await;
+            PROBE.latch(ProbeSource::Electrical);
+
+            Timer::after(self.config.debounce).await;
+            match self.input.is_triggered() {
+                true => {
+                    if let Some(trigger) = PROBE.confirm(ProbeSource::Electrical) {
+                        info!("Probe triggered: {}", trigger);
+                    }
+                    self.input.wait_for_release().await;
+                }
+                false => warn!("Probe input glitch, trigger discarded"),
+            }
+            PROBE.release(ProbeSource::Electrical);
+        }
+    }
+}
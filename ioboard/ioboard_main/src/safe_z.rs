@@ -0,0 +1,109 @@
+//! Refuses X and Y moves while the Z axis is below the safe height, see
+//! [`SafeZConfig`](ioboard_shared::safe_z::SafeZConfig).
+//!
+//! The server raises Z before moving X and Y, this catches a server bug or a lost Z setpoint before a lowered nozzle
+//! is dragged across the board.  The guard is configured by the server, until then every move is allowed, once
+//! configured moves are refused until the position of the Z axis is known.
+//!
+//! Only server-planned moves are guarded, see [`SetpointFollower`](crate::setpoint::SetpointFollower), the on-board
+//! planner doesn't run moves requested by the server yet.
+
+use core::cell::Cell;
+
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use ioboard_net::{POSITION_REPORTS, SAFE_Z_REQUESTS};
+use ioboard_shared::motion::PositionReport;
+use ioboard_shared::safe_z::{SafeZRequest, SafeZResponse, SafeZStatus};
+
+pub static SAFE_Z_GUARD: SafeZGuard = SafeZGuard::new();
+
+pub struct SafeZGuard {
+    status: Mutex<CriticalSectionRawMutex, Cell<SafeZStatus>>,
+}
+
+impl SafeZGuard {
+    pub const fn new() -> Self {
+        Self {
+            status: Mutex::new(Cell::new(SafeZStatus::UNCONFIGURED)),
+        }
+    }
+
+    pub fn status(&self) -> SafeZStatus {
+        self.status.lock(Cell::get)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut SafeZStatus)) -> SafeZStatus {
+        self.status.lock(|status| {
+            let mut value = status.get();
+            f(&mut value);
+            status.set(value);
+            value
+        })
+    }
+
+    pub fn handle_request(&self, request: SafeZRequest) -> SafeZResponse {
+        self.update(|status| match request {
+            SafeZRequest::Configure(config) => {
+                // the position is kept if the Z axis didn't change, e.g. when the server re-sends the configuration
+                if status
+                    .config
+                    .is_none_or(|current| current.z_axis != config.z_axis)
+                {
+                    status.z_position = None;
+                }
+                status.config = Some(config);
+            }
+            SafeZRequest::Override(overridden) => status.overridden = overridden,
+        })
+    }
+
+    /// Called with the position reports of every axis, reports of other axes than the Z axis are ignored.
+    pub fn record_position(&self, report: &PositionReport) {
+        self.status.lock(|status| {
+            let mut value = status.get();
+            if value
+                .config
+                .is_some_and(|config| config.z_axis == report.axis)
+            {
+                value.z_position = Some(report.position);
+                status.set(value);
+            }
+        })
+    }
+
+    /// Moves of the Z axis itself are always allowed, so that a lowered nozzle can be raised.
+    pub fn allows_move(&self, axis: u8) -> bool {
+        let status = self.status();
+        status
+            .config
+            .is_none_or(|config| config.z_axis == axis)
+            || status.allows_xy_moves()
+    }
+
+    /// Handle requests from the safe-Z endpoint, and track the position of the Z axis.
+    pub async fn run(&self) -> ! {
+        info!("Safe-Z guard started");
+        loop {
+            match select(SAFE_Z_REQUESTS.receive(), POSITION_REPORTS.receive()).await {
+                Either::First(request) => {
+                    let response = self.handle_request(request);
+                    match (request, response.overridden) {
+                        (SafeZRequest::Override(_), true) => warn!("Safe-Z guard overridden"),
+                        _ => info!("Safe-Z guard updated: {}", response),
+                    }
+                    SAFE_Z_REQUESTS.respond(response).await;
+                }
+                Either::Second(report) => self.record_position(&report),
+            }
+        }
+    }
+}
+
+impl Default for SafeZGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,390 @@
+//! Machine guarding, e.g. a door switch or a light curtain.
+//!
+//! Each configured [`SafetyInput`] has a [`SafetyPolicy`] that applies while it is tripped, the most severe policy of
+//! the tripped inputs is the current [`MotionRestriction`]:
+//! * `ReduceSpeed` and `PauseMotion` are applied by the on-board trajectory planner at segment boundaries, via
+//!   [`MOTION_RESTRICTIONS`].  When the server plans the motion it applies them, from the published [`SafetyStatus`].
+//! * `EStop` cancels stepper operations immediately and disables motor power via the
+//!   [`Interlock::SafetyInputsClear`] interlock.  The cancellation is not reset when the input is cleared, the same
+//!   as a supply fault.
+//!
+//! Inputs trip immediately, but are only cleared once they have stayed clear for [`SafetyConfig::clear_after`], so
+//! that a bouncing switch does not resume motion.
+//!
+//! An [`EStop`] broadcast by the server or the operator UI is handled on the next sample, i.e. within a control cycle,
+//! the same as an input with an e-stop policy, and is latched until an [`EStop::Reset`].  The motion task disables the
+//! drivers via [`Stepper::disable`](crate::stepper::Stepper::disable) once it sees the cancellation.  The reset also
+//! resets the cancellation, of any fault, e.g. a crash or a supply fault, it is refused while an input with an e-stop
+//! policy is tripped, while the supply is faulted or while a temperature is critical.
+
+use core::sync::atomic::{AtomicU8, AtomicU32, Ordering};
+
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use ioboard_shared::estop::{EStop, EStopSource};
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::power::Interlock;
+use ioboard_shared::safety::{MotionRestriction, SafetyInput, SafetyInputState, SafetyPolicy, SafetyStatus};
+
+use crate::power::POWER_INTERLOCKS;
+use crate::stepper::StepperCancellation;
+
+/// Inputs are sampled at the same rate as the motion control cycle.
+const SAMPLE_INTERVAL: Duration = Duration::from_micros(1000);
+
+/// The status is also published whenever it changes.
+const STATUS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often a paused trajectory checks if it can resume.
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+pub trait SafetyInputs {
+    /// Returns `true` if the input is tripped, or `None` if it could not be read.
+    fn is_tripped(&mut self, input: SafetyInput) -> Option<bool>;
+}
+
+/// For boards without machine guarding inputs, the safety monitor then only handles the e-stop broadcast.
+pub struct NoSafetyInputs;
+
+impl SafetyInputs for NoSafetyInputs {
+    fn is_tripped(&mut self, _input: SafetyInput) -> Option<bool> {
+        Some(false)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SafetyConfig {
+    /// `None` if the machine has no door switch
+    pub door: Option<SafetyPolicy>,
+    /// `None` if the machine has no light curtain
+    pub light_curtain: Option<SafetyPolicy>,
+    /// scale applied to the max velocity and acceleration by the `ReduceSpeed` policy, 0.0-1.0
+    pub reduced_speed_factor: f32,
+    /// how long a tripped input must be clear before its policy no longer applies
+    pub clear_after: Duration,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            door: None,
+            light_curtain: None,
+            reduced_speed_factor: 0.25,
+            clear_after: Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct InputMonitor {
+    policy: SafetyPolicy,
+    tripped: bool,
+    clear_since: Option<Instant>,
+}
+
+impl InputMonitor {
+    fn new(policy: SafetyPolicy) -> Self {
+        Self {
+            policy,
+            tripped: false,
+            clear_since: None,
+        }
+    }
+
+    /// Returns the new tripped state if it changed.
+    fn update(&mut self, tripped: bool, now: Instant, clear_after: Duration) -> Option<bool> {
+        match (self.tripped, tripped) {
+            (false, true) => {
+                self.tripped = true;
+                Some(true)
+            }
+            (true, true) => {
+                self.clear_since = None;
+                None
+            }
+            (true, false) => {
+                let clear_since = *self.clear_since.get_or_insert(now);
+                if now - clear_since < clear_after {
+                    return None;
+                }
+                self.tripped = false;
+                self.clear_since = None;
+                Some(false)
+            }
+            (false, false) => None,
+        }
+    }
+
+    fn state(&self) -> SafetyInputState {
+        match self.tripped {
+            true => SafetyInputState::Tripped,
+            false => SafetyInputState::Clear,
+        }
+    }
+}
+
+pub struct SafetyMonitor {
+    config: SafetyConfig,
+    door: Option<InputMonitor>,
+    light_curtain: Option<InputMonitor>,
+    /// the source of the latched e-stop
+    estop: Option<EStopSource>,
+}
+
+impl SafetyMonitor {
+    pub fn new(config: SafetyConfig) -> Self {
+        Self {
+            door: config.door.map(InputMonitor::new),
+            light_curtain: config
+                .light_curtain
+                .map(InputMonitor::new),
+            config,
+            estop: None,
+        }
+    }
+
+    fn monitor(&self, input: SafetyInput) -> Option<&InputMonitor> {
+        match input {
+            SafetyInput::Door => self.door.as_ref(),
+            SafetyInput::LightCurtain => self.light_curtain.as_ref(),
+        }
+    }
+
+    fn monitor_mut(&mut self, input: SafetyInput) -> Option<&mut InputMonitor> {
+        match input {
+            SafetyInput::Door => self.door.as_mut(),
+            SafetyInput::LightCurtain => self.light_curtain.as_mut(),
+        }
+    }
+
+    pub fn is_configured(&self, input: SafetyInput) -> bool {
+        self.monitor(input).is_some()
+    }
+
+    /// Returns an event if the tripped state of the input changed, unconfigured inputs are ignored.
+    pub fn update(&mut self, input: SafetyInput, tripped: bool, now: Instant) -> Option<IoBoardEvent> {
+        let clear_after = self.config.clear_after;
+        let monitor = self.monitor_mut(input)?;
+
+        monitor
+            .update(tripped, now, clear_after)
+            .map(|tripped| IoBoardEvent::SafetyInputChanged {
+                input,
+                tripped,
+                policy: monitor.policy,
+            })
+    }
+
+    /// The most severe policy of the tripped inputs.
+    pub fn input_restriction(&self) -> MotionRestriction {
+        [&self.door, &self.light_curtain]
+            .into_iter()
+            .flatten()
+            .filter(|monitor| monitor.tripped)
+            .map(|monitor| MotionRestriction::from(monitor.policy))
+            .max()
+            .unwrap_or(MotionRestriction::None)
+    }
+
+    /// The restriction of the tripped inputs, or `EStopped` while an e-stop is latched.
+    pub fn restriction(&self) -> MotionRestriction {
+        match self.estop {
+            Some(_) => MotionRestriction::EStopped,
+            None => self.input_restriction(),
+        }
+    }
+
+    /// Returns an event if the e-stop was not already latched.
+    pub fn latch_estop(&mut self, source: EStopSource) -> Option<IoBoardEvent> {
+        if self.estop.is_some() {
+            return None;
+        }
+        self.estop = Some(source);
+        Some(IoBoardEvent::EStopChanged {
+            source,
+            latched: true,
+        })
+    }
+
+    pub fn reset_estop(&mut self, source: EStopSource) -> IoBoardEvent {
+        self.estop = None;
+        IoBoardEvent::EStopChanged {
+            source,
+            latched: false,
+        }
+    }
+
+    pub fn status(&self) -> SafetyStatus {
+        let state = |monitor: &Option<InputMonitor>| {
+            monitor
+                .as_ref()
+                .map_or(SafetyInputState::NotConfigured, InputMonitor::state)
+        };
+
+        SafetyStatus {
+            door: state(&self.door),
+            light_curtain: state(&self.light_curtain),
+            restriction: self.restriction(),
+            reduced_speed_factor: self.config.reduced_speed_factor,
+            estop: self.estop,
+        }
+    }
+}
+
+/// Monitor the safety inputs, cancelling stepper operations when an input with an e-stop policy is tripped, or when an
+/// e-stop is received.
+///
+/// Should be run on the same (high-priority) executor as the motion task so that it is not delayed by other tasks.
+pub async fn monitor_safety(
+    mut inputs: impl SafetyInputs,
+    config: SafetyConfig,
+    cancellation: &StepperCancellation,
+) -> ! {
+    let mut monitor = SafetyMonitor::new(config);
+    let mut ticker = Ticker::every(SAMPLE_INTERVAL);
+    let mut status_published_at: Option<Instant> = None;
+
+    info!(
+        "Safety monitor started, door: {}, light curtain: {}",
+        config.door, config.light_curtain
+    );
+
+    loop {
+        let now = Instant::now();
+
+        let mut changed = false;
+        match ioboard_net::ESTOP_REQUESTS.take() {
+            Some(EStop::Stop(source)) => {
+                // before anything else, the event and the status can wait
+                cancellation.cancel();
+                if let Some(event) = monitor.latch_estop(source) {
+                    changed = true;
+                    warn!("E-stop latched, source: {}", source);
+                    publish_event(event);
+                }
+            }
+            Some(EStop::Reset(source)) => {
+                if monitor.input_restriction() == MotionRestriction::EStopped {
+                    warn!("E-stop reset refused, a safety input is tripped, source: {}", source);
+                } else if !POWER_INTERLOCKS.is_satisfied(Interlock::SupplyOk) {
+                    warn!("E-stop reset refused, the supply is faulted, source: {}", source);
+                } else if !POWER_INTERLOCKS.is_satisfied(Interlock::ThermalOk) {
+                    warn!("E-stop reset refused, a temperature is critical, source: {}", source);
+                } else if monitor.restriction() == MotionRestriction::EStopped || cancellation.is_cancelled() {
+                    changed = true;
+                    info!("E-stop reset, source: {}", source);
+                    publish_event(monitor.reset_estop(source));
+                    cancellation.reset();
+                }
+            }
+            None => {}
+        }
+
+        for input in [SafetyInput::Door, SafetyInput::LightCurtain] {
+            if !monitor.is_configured(input) {
+                continue;
+            }
+            // fail safe, an input that can't be read is treated as tripped
+            let tripped = inputs.is_tripped(input).unwrap_or(true);
+
+            if let Some(event) = monitor.update(input, tripped, now) {
+                changed = true;
+                warn!("Safety input changed: {}", event);
+                publish_event(event);
+            }
+        }
+
+        if changed {
+            let restriction = monitor.restriction();
+            if restriction == MotionRestriction::EStopped {
+                cancellation.cancel();
+            }
+            POWER_INTERLOCKS.set(Interlock::SafetyInputsClear, restriction != MotionRestriction::EStopped);
+            MOTION_RESTRICTIONS.set(restriction, config.reduced_speed_factor);
+        }
+
+        let publish_status =
+            changed || status_published_at.is_none_or(|published_at| now - published_at >= STATUS_INTERVAL);
+        if publish_status {
+            status_published_at = Some(now);
+            ioboard_net::publish_safety(&monitor.status());
+        }
+
+        ticker.next().await;
+    }
+}
+
+fn publish_event(event: IoBoardEvent) {
+    if ioboard_net::publish_event(event).is_err() {
+        warn!("Event queue full, dropped safety event");
+    }
+}
+
+/// The current restriction, set by the safety monitor, applied by the trajectory planner.
+pub static MOTION_RESTRICTIONS: MotionRestrictions = MotionRestrictions::new();
+
+pub struct MotionRestrictions {
+    restriction: AtomicU8,
+    /// f32 bits
+    reduced_speed_factor: AtomicU32,
+}
+
+impl MotionRestrictions {
+    pub const fn new() -> Self {
+        Self {
+            restriction: AtomicU8::new(0),
+            reduced_speed_factor: AtomicU32::new(0),
+        }
+    }
+
+    fn set(&self, restriction: MotionRestriction, reduced_speed_factor: f32) {
+        self.reduced_speed_factor
+            .store(reduced_speed_factor.to_bits(), Ordering::Release);
+        let value = match restriction {
+            MotionRestriction::None => 0,
+            MotionRestriction::ReducedSpeed => 1,
+            MotionRestriction::Paused => 2,
+            MotionRestriction::EStopped => 3,
+        };
+        self.restriction
+            .store(value, Ordering::Release);
+    }
+
+    pub fn restriction(&self) -> MotionRestriction {
+        match self.restriction.load(Ordering::Acquire) {
+            0 => MotionRestriction::None,
+            1 => MotionRestriction::ReducedSpeed,
+            2 => MotionRestriction::Paused,
+            _ => MotionRestriction::EStopped,
+        }
+    }
+
+    /// Scale to apply to the max velocity and acceleration, 1.0 unless the speed is reduced.
+    pub fn speed_factor(&self) -> f32 {
+        match self.restriction() {
+            MotionRestriction::ReducedSpeed => f32::from_bits(
+                self.reduced_speed_factor
+                    .load(Ordering::Acquire),
+            ),
+            _ => 1.0,
+        }
+    }
+
+    /// Returns once motion is no longer paused.
+    pub async fn wait_while_paused(&self) {
+        if self.restriction() != MotionRestriction::Paused {
+            return;
+        }
+        info!("Motion paused by safety input");
+        while self.restriction() == MotionRestriction::Paused {
+            Timer::after(PAUSE_CHECK_INTERVAL).await;
+        }
+        info!("Motion resumed");
+    }
+}
+
+impl Default for MotionRestrictions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,202 @@
+//! Power-on self test, run once on boot before the board is marked ready.
+//!
+//! Each part of the board is checked as it is initialized, before it is handed to the task that runs it:
+//! * the power rails are toggled on and off, and read back, for rails with feedback, see [`PowerRails::read_rail`].
+//! * the sensors of the idle machine must read a plausible value.
+//! * the stepper drivers with feedback must respond.
+//!
+//! A failed check does not stop the board, it remains [`BoardState::Degraded`] until it is restarted, the server
+//! then refuses to start a job unless the operator overrides it.
+
+use defmt::{error, info, warn};
+use embassy_time::{Duration, Ticker, Timer};
+use ioboard_shared::power::PowerRail;
+use ioboard_shared::self_test::{BoardState, SELF_TEST_FAULTS_MAX, SelfTestFault, SelfTestSensor, SelfTestStatus};
+
+use crate::power::{PowerRails, SupplySensor};
+use crate::stepper::Stepper;
+use crate::vacuum::VacuumSensor;
+
+/// The status is published periodically, the server may start after the board.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The plausible readings of a sensor, inclusive.
+#[derive(Debug, Clone, Copy)]
+pub struct SensorRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl SensorRange {
+    pub fn contains(&self, value: f32) -> bool {
+        value >= self.min && value <= self.max
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SelfTestConfig {
+    /// the rails that are toggled, motor power is not toggled by default, it is interlocked until motion is configured
+    pub toggle_rails: &'static [PowerRail],
+    /// the delay after switching a rail before it is read back
+    pub toggle_settle: Duration,
+    /// in volts, `None` to skip the check
+    pub supply_voltage: Option<SensorRange>,
+    /// in kPa below ambient, the pump is off so it should read ambient, `None` to skip the check
+    pub vacuum: Option<SensorRange>,
+}
+
+impl Default for SelfTestConfig {
+    fn default() -> Self {
+        Self {
+            toggle_rails: &[PowerRail::Lighting, PowerRail::VacuumPump],
+            toggle_settle: Duration::from_millis(20),
+            // a nominal 24V motor supply, the same as `SupplyThresholds::SUPPLY_24V`
+            supply_voltage: Some(SensorRange {
+                min: 20.0,
+                max: 28.0,
+            }),
+            vacuum: Some(SensorRange {
+                min: -5.0,
+                max: 5.0,
+            }),
+        }
+    }
+}
+
+pub struct SelfTest {
+    config: SelfTestConfig,
+    checks: u8,
+    fault_count: u8,
+    faults: [Option<SelfTestFault>; SELF_TEST_FAULTS_MAX],
+}
+
+impl SelfTest {
+    pub fn new(config: SelfTestConfig) -> Self {
+        Self {
+            config,
+            checks: 0,
+            fault_count: 0,
+            faults: [None; SELF_TEST_FAULTS_MAX],
+        }
+    }
+
+    fn record(&mut self, fault: Option<SelfTestFault>) {
+        self.checks = self.checks.saturating_add(1);
+
+        let Some(fault) = fault else {
+            return;
+        };
+        warn!("Self test fault: {}", fault);
+        if let Some(entry) = self
+            .faults
+            .get_mut(self.fault_count as usize)
+        {
+            *entry = Some(fault);
+        }
+        self.fault_count = self.fault_count.saturating_add(1);
+    }
+
+    /// Toggle the configured rails on and off, the rails are left disabled.
+    ///
+    /// Must be called before the rails are handed to the power sequencer, the interlocks are not checked.
+    pub async fn check_outputs(&mut self, rails: &mut impl PowerRails) {
+        for &rail in self.config.toggle_rails {
+            let on = self.switch(rails, rail, true).await;
+            let off = self.switch(rails, rail, false).await;
+
+            // rails without feedback are toggled but can't be checked
+            let (Some(on), Some(off)) = (on, off) else {
+                continue;
+            };
+            let fault = match (on, off) {
+                (false, _) => Some(SelfTestFault::OutputStuck {
+                    rail,
+                    enabled: true,
+                }),
+                (_, true) => Some(SelfTestFault::OutputStuck {
+                    rail,
+                    enabled: false,
+                }),
+                (true, false) => None,
+            };
+            self.record(fault);
+        }
+    }
+
+    /// Returns the state read back once the rail has settled.
+    async fn switch(&self, rails: &mut impl PowerRails, rail: PowerRail, enabled: bool) -> Option<bool> {
+        rails.set_rail(rail, enabled);
+        Timer::after(self.config.toggle_settle).await;
+        rails.read_rail(rail)
+    }
+
+    pub fn check_supply(&mut self, sensor: &mut impl SupplySensor) {
+        let Some(range) = self.config.supply_voltage else {
+            return;
+        };
+        self.record(sensor_fault(
+            SelfTestSensor::SupplyVoltage,
+            range,
+            sensor.read_voltage(),
+        ));
+    }
+
+    pub fn check_vacuum(&mut self, sensor: &mut impl VacuumSensor) {
+        let Some(range) = self.config.vacuum else {
+            return;
+        };
+        self.record(sensor_fault(SelfTestSensor::Vacuum, range, sensor.read_vacuum()));
+    }
+
+    /// Skipped for drivers without feedback, there is no way to tell whether they respond.
+    pub fn check_driver(&mut self, axis: u8, stepper: &mut impl Stepper) {
+        match stepper.read_feedback() {
+            Ok(None) => {}
+            Ok(Some(_)) => self.record(None),
+            Err(_e) => self.record(Some(SelfTestFault::DriverNotResponding {
+                axis,
+            })),
+        }
+    }
+
+    pub fn status(&self) -> SelfTestStatus {
+        SelfTestStatus {
+            state: match self.fault_count {
+                0 => BoardState::Ready,
+                _ => BoardState::Degraded,
+            },
+            checks: self.checks,
+            fault_count: self.fault_count,
+            faults: self.faults,
+        }
+    }
+}
+
+/// Returns `None` if the reading is in range.
+fn sensor_fault(sensor: SelfTestSensor, range: SensorRange, reading: Option<f32>) -> Option<SelfTestFault> {
+    match reading {
+        None => Some(SelfTestFault::SensorUnreadable(sensor)),
+        Some(value) if !range.contains(value) => Some(SelfTestFault::SensorOutOfRange {
+            sensor,
+            value,
+        }),
+        Some(_) => None,
+    }
+}
+
+/// Publish the result of the self test, the result does not change until the board is restarted.
+pub async fn publish_self_test(status: SelfTestStatus) -> ! {
+    match status.state {
+        BoardState::Ready => info!("Self test passed, board ready. checks: {}", status.checks),
+        BoardState::Degraded => error!(
+            "Self test failed, board degraded. checks: {}, faults: {}",
+            status.checks, status.fault_count
+        ),
+    }
+
+    let mut ticker = Ticker::every(STATUS_INTERVAL);
+    loop {
+        ioboard_net::publish_self_test(&status);
+        ticker.next().await;
+    }
+}
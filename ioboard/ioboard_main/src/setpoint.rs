@@ -0,0 +1,516 @@
+//! Setpoint following, for boards that are too small to run trajectory generation themselves.
+//!
+//! The server plans the motion and streams low-rate position setpoints, see [`MotionSetpoint`], this linearly
+//! interpolates between consecutive setpoints at the step cycle rate.  The setpoint queue acts as a jitter buffer,
+//! when it runs dry the axis holds the position of the last setpoint.
+//!
+//! The move and the sequence of a setpoint identify it, a setpoint that is received again, e.g. re-delivered on a lossy
+//! link, or that arrives after a later setpoint, is discarded instead of moving the axis back.
+//!
+//! A flush, see [`FlushQueueRequest`], aborts the move being followed, the queued setpoints are discarded and the axis
+//! brakes to a stop from the velocity of the last cycle, see [`StopRamp`].
+//!
+//! Motion commands and resonance sweeps are refused, they are for boards that plan their own trajectories, see
+//! [`motion_queue`](crate::motion_queue), except for switching the axis to on-board planning between moves, see
+//! [`MotionCommand::SetPlanning`].
+//!
+//! The axis is homed between setpoints, see [`homing`](crate::homing), the server must not stream setpoints for the
+//! axis while it is homed, the setpoints of an axis that isn't homed yet are discarded.  Driver requests are handled
+//! between setpoints too, see [`driver`](crate::driver).
+
+use defmt::{info, warn};
+use embassy_futures::select::{Either3, Either4, select3, select4};
+use embassy_time::{Duration, Instant, Ticker, with_timeout};
+use ioboard_net::{
+    DRIVER_REQUESTS, FLUSH_QUEUE_REQUESTS, HOMING_REQUESTS, MOTION_COMMANDS, MOTION_SETPOINTS, RESONANCE_SWEEP_REQUESTS,
+};
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::motion::{
+    FlushQueueError, FlushQueueRequest, MotionCommand, MotionCommandError, MotionCommandRequest, MotionPlanning,
+    MotionQueueStatus, MotionSetpoint, PositionReport, QueueFlushed, StopRamp,
+};
+use ioboard_shared::resonance::ResonanceSweepError;
+use libm::round;
+use machine_ids::MoveId;
+
+use crate::driver::AxisDriver;
+use crate::homing::{AxisHoming, LimitSwitch};
+use crate::load::LoadMonitor;
+use crate::motion_anomaly::{MotionAnomalyMonitor, StepCycle};
+use crate::probe::PROBE;
+use crate::safe_z::SAFE_Z_GUARD;
+use crate::stepper::{Stepper, StepperCancellation, StepperDirection, StepperError};
+
+/// Interpolation cycle, same as the on-board trajectory cycle.
+const CYCLE_INTERVAL_US: u64 = 1000;
+
+/// When no setpoint is received within this time the move is considered finished, or starved.
+const SETPOINT_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub struct SetpointFollower {
+    axis: u8,
+    /// commanded position of the last cycle, in steps, the position of the last setpoint once it has been reached
+    position: f64,
+    position_steps: i64,
+    direction: Option<StepperDirection>,
+    /// of the last cycle, in steps per cycle, zero while the axis holds its position
+    velocity: f64,
+    last_sequence: Option<u32>,
+    /// the move of the last setpoint, a move starts when the identifier changes, even if its first setpoint was lost
+    current_move: Option<MoveId>,
+    /// the setpoints of the move before the current one that are still in flight are discarded
+    previous_move: Option<MoveId>,
+    /// the move and the sequence of the last setpoint, kept when the setpoints stop, unlike the `last_sequence`
+    last_setpoint: Option<(MoveId, u32)>,
+    /// the setpoints of a flushed move that are still in flight are discarded
+    flushed_move: Option<MoveId>,
+    /// the rest of a refused move is refused too, until the next move starts
+    move_refused: bool,
+    /// the rest of a move stopped by the probe is skipped, until the next move starts
+    move_stopped: bool,
+    load_monitor: Option<LoadMonitor>,
+    motion_anomaly_monitor: Option<MotionAnomalyMonitor>,
+}
+
+impl SetpointFollower {
+    pub fn new(
+        axis: u8,
+        load_monitor: Option<LoadMonitor>,
+        motion_anomaly_monitor: Option<MotionAnomalyMonitor>,
+    ) -> Self {
+        Self {
+            axis,
+            position: 0.0,
+            position_steps: 0,
+            direction: None,
+            velocity: 0.0,
+            last_sequence: None,
+            current_move: None,
+            previous_move: None,
+            last_setpoint: None,
+            flushed_move: None,
+            move_refused: false,
+            move_stopped: false,
+            load_monitor,
+            motion_anomaly_monitor,
+        }
+    }
+
+    /// Follow setpoints until cancelled, or until the axis is switched to on-board planning, setpoints for other axes
+    /// are ignored.
+    ///
+    /// Flush requests are handled between setpoints and during the cycles of a setpoint, homing and driver requests only
+    /// between setpoints.
+    pub async fn run(
+        &mut self,
+        stepper: &mut impl Stepper,
+        homing: &mut AxisHoming<impl LimitSwitch>,
+        cancellation: &StepperCancellation,
+    ) -> Result<(), StepperError> {
+        info!("Following setpoints, axis: {}", self.axis);
+        let mut driver = AxisDriver::new(self.axis);
+        loop {
+            cancellation.check()?;
+
+            let received = with_timeout(
+                SETPOINT_TIMEOUT,
+                select4(
+                    MOTION_SETPOINTS.receive(),
+                    FLUSH_QUEUE_REQUESTS.receive(),
+                    MOTION_COMMANDS.receive(),
+                    select3(
+                        HOMING_REQUESTS.receive(),
+                        DRIVER_REQUESTS.receive(),
+                        RESONANCE_SWEEP_REQUESTS.receive(),
+                    ),
+                ),
+            )
+            .await;
+            let setpoint = match received {
+                Ok(Either4::First(setpoint)) => setpoint,
+                Ok(Either4::Second(request)) => {
+                    self.flush(stepper, request, cancellation)
+                        .await?;
+                    continue;
+                }
+                Ok(Either4::Third(request)) => {
+                    if self.handle_command(&request).await {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                Ok(Either4::Fourth(Either3::First(request))) => {
+                    driver.engage(stepper)?;
+                    let homed = homing
+                        .handle(stepper, request, cancellation)
+                        .await;
+                    // the homing moves changed the direction
+                    self.direction = None;
+                    self.velocity = 0.0;
+                    if let Some(position) = homed? {
+                        self.position = position as f64;
+                        self.position_steps = position;
+                        let report = PositionReport {
+                            axis: self.axis,
+                            position: self.position_steps,
+                        };
+                        SAFE_Z_GUARD.record_position(&report);
+                        ioboard_net::publish_position(&report);
+                    }
+                    continue;
+                }
+                Ok(Either4::Fourth(Either3::Second(request))) => {
+                    driver.handle(stepper, request).await;
+                    continue;
+                }
+                Ok(Either4::Fourth(Either3::Third(request))) => {
+                    warn!("Resonance sweep refused, following setpoints, axis: {}", request.axis);
+                    RESONANCE_SWEEP_REQUESTS
+                        .respond(Err(ResonanceSweepError::Unsupported))
+                        .await;
+                    continue;
+                }
+                Err(_) => {
+                    self.velocity = 0.0;
+                    if let Some(sequence) = self.last_sequence.take() {
+                        info!(
+                            "Setpoints stopped, last sequence: {}, position: {}",
+                            sequence, self.position_steps
+                        );
+                    }
+                    continue;
+                }
+            };
+
+            if setpoint.axis != self.axis || self.flushed_move == Some(setpoint.move_id) {
+                continue;
+            }
+            if self.is_duplicate(&setpoint) {
+                warn!(
+                    "Duplicate setpoint discarded, move: {}, sequence: {}",
+                    setpoint.move_id, setpoint.sequence
+                );
+                continue;
+            }
+
+            let new_move = self.current_move != Some(setpoint.move_id);
+            self.check_sequence(&setpoint, new_move);
+
+            if new_move {
+                self.previous_move = self.current_move;
+                self.current_move = Some(setpoint.move_id);
+                self.move_refused = false;
+                self.move_stopped = false;
+                if let Some(monitor) = &mut self.motion_anomaly_monitor {
+                    monitor.reset();
+                }
+            }
+            // the position of the axis is unknown
+            if !homing.is_homed() {
+                if new_move {
+                    warn!(
+                        "Move refused, axis not homed, axis: {}, move: {}",
+                        self.axis, setpoint.move_id
+                    );
+                }
+                continue;
+            }
+            if self.move_refused || !SAFE_Z_GUARD.allows_move(self.axis) {
+                self.refuse_move();
+                continue;
+            }
+            // moves are skipped until the probe is disarmed
+            if self.move_stopped || PROBE.stops_axis(self.axis) {
+                self.move_stopped = true;
+                continue;
+            }
+
+            driver.engage(stepper)?;
+            self.interpolate(stepper, &setpoint, cancellation)
+                .await?;
+
+            // one report per setpoint, the setpoint rate is already low
+            let report = PositionReport {
+                axis: self.axis,
+                position: self.position_steps,
+            };
+            // the guard doesn't receive the reports of this board from the network
+            SAFE_Z_GUARD.record_position(&report);
+            ioboard_net::publish_position(&report);
+
+            if let Some(monitor) = &mut self.load_monitor {
+                monitor.sample(stepper, cancellation)?;
+            }
+        }
+    }
+
+    /// The position of the axis, in steps.
+    pub fn position(&self) -> i64 {
+        self.position_steps
+    }
+
+    /// The axis was moved by the on-board planner, the next setpoint is interpolated from `position`, in steps.
+    pub fn reset_position(&mut self, position: i64) {
+        self.position = position as f64;
+        self.position_steps = position;
+        self.velocity = 0.0;
+    }
+
+    /// Only the planning can be set, returns `true` if the axis is to be switched to on-board planning.
+    async fn handle_command(&mut self, request: &MotionCommandRequest) -> bool {
+        let planning = match request.command {
+            MotionCommand::SetPlanning(planning) if request.axis == self.axis => planning,
+            _ => {
+                warn!("Motion command refused, following setpoints, axis: {}", request.axis);
+                MOTION_COMMANDS
+                    .respond(Err(MotionCommandError::ServerPlanned))
+                    .await;
+                return false;
+            }
+        };
+
+        // the setpoints of a move are still arriving
+        let moving = self.last_sequence.is_some() || !MOTION_SETPOINTS.is_empty();
+        let switch = planning == MotionPlanning::OnBoard;
+        let response = match switch && moving {
+            true => {
+                warn!("Planning not switched, axis moving, axis: {}", self.axis);
+                Err(MotionCommandError::Moving)
+            }
+            false => Ok(MotionQueueStatus {
+                axis: self.axis,
+                depth: 0,
+                capacity: 0,
+                current_move: None,
+                discarded: 0,
+                position: self.position_steps,
+            }),
+        };
+        let switched = switch && response.is_ok();
+        if switched {
+            info!(
+                "Switching to on-board planning, axis: {}, position: {}",
+                self.axis, self.position_steps
+            );
+        }
+        MOTION_COMMANDS.respond(response).await;
+        switched
+    }
+
+    /// The axis holds its position, the event is only published for the first refused setpoint of a move.
+    fn refuse_move(&mut self) {
+        if self.move_refused {
+            return;
+        }
+        self.move_refused = true;
+
+        let z_position = SAFE_Z_GUARD.status().z_position;
+        warn!(
+            "Move refused, Z below the safe height, axis: {}, z position: {}",
+            self.axis, z_position
+        );
+        let event = IoBoardEvent::MoveRefusedBelowSafeZ {
+            axis: self.axis,
+            z_position,
+        };
+        if ioboard_net::publish_event(event).is_err() {
+            warn!("Event queue full, dropped safe-Z event");
+        }
+    }
+
+    /// Discards the queued setpoints and brakes the axis to a stop, the response has the stop position.
+    ///
+    /// The sequence is resynchronized, the next move is expected to start at sequence 0.
+    async fn flush(
+        &mut self,
+        stepper: &mut impl Stepper,
+        request: FlushQueueRequest,
+        cancellation: &StepperCancellation,
+    ) -> Result<(), StepperError> {
+        if request.axis != self.axis {
+            warn!("Flush refused, unknown axis: {}", request.axis);
+            FLUSH_QUEUE_REQUESTS
+                .respond(Err(FlushQueueError::UnknownAxis))
+                .await;
+            return Ok(());
+        }
+
+        let mut discarded = 0;
+        while let Ok(setpoint) = MOTION_SETPOINTS.try_receive() {
+            if setpoint.axis == self.axis {
+                discarded += 1;
+            }
+        }
+
+        let braked = self
+            .brake(stepper, request.max_deceleration, cancellation)
+            .await;
+
+        let interrupted_move = self.current_move.take();
+        let last_sequence = self.last_sequence.take();
+        self.flushed_move = interrupted_move;
+
+        let response = match braked {
+            Ok(()) => {
+                info!(
+                    "Setpoints flushed, axis: {}, discarded: {}, last sequence: {}, position: {}",
+                    self.axis, discarded, last_sequence, self.position_steps
+                );
+                Ok(QueueFlushed {
+                    axis: self.axis,
+                    discarded,
+                    interrupted_move,
+                    last_sequence,
+                    position: self.position_steps,
+                })
+            }
+            Err(_) => Err(FlushQueueError::Stopped),
+        };
+        FLUSH_QUEUE_REQUESTS
+            .respond(response)
+            .await;
+        braked
+    }
+
+    /// `max_deceleration` is in steps/s².
+    async fn brake(
+        &mut self,
+        stepper: &mut impl Stepper,
+        max_deceleration: f64,
+        cancellation: &StepperCancellation,
+    ) -> Result<(), StepperError> {
+        let cycle_s = CYCLE_INTERVAL_US as f64 / 1_000_000.0;
+        let ramp = StopRamp::new(self.position, self.velocity, max_deceleration * cycle_s * cycle_s);
+        self.velocity = 0.0;
+
+        let mut cycle_ticker = Ticker::every(Duration::from_micros(CYCLE_INTERVAL_US));
+        for position in ramp {
+            self.step_to(stepper, position, cancellation)
+                .await?;
+            cycle_ticker.next().await;
+        }
+        self.position = self.position_steps as f64;
+        Ok(())
+    }
+
+    /// A setpoint of the previous move, or of the current move that is not after the last setpoint, was already
+    /// followed.
+    fn is_duplicate(&self, setpoint: &MotionSetpoint) -> bool {
+        if self.previous_move == Some(setpoint.move_id) {
+            return true;
+        }
+        match self.last_setpoint {
+            // wrapping, so that the sequence of a long move may overflow
+            Some((move_id, last)) if move_id == setpoint.move_id => (setpoint.sequence.wrapping_sub(last) as i32) <= 0,
+            _ => false,
+        }
+    }
+
+    fn check_sequence(&mut self, setpoint: &MotionSetpoint, new_move: bool) {
+        match (new_move, self.last_sequence) {
+            (true, _) if setpoint.sequence != 0 => {
+                warn!(
+                    "Setpoint stream joined mid-move, move: {}, sequence: {}",
+                    setpoint.move_id, setpoint.sequence
+                );
+            }
+            (false, Some(last)) if setpoint.sequence != last.wrapping_add(1) => {
+                warn!(
+                    "Setpoint sequence gap, expected: {}, received: {}",
+                    last.wrapping_add(1),
+                    setpoint.sequence
+                );
+            }
+            _ => {}
+        }
+        self.last_sequence = Some(setpoint.sequence);
+        self.last_setpoint = Some((setpoint.move_id, setpoint.sequence));
+    }
+
+    async fn interpolate(
+        &mut self,
+        stepper: &mut impl Stepper,
+        setpoint: &MotionSetpoint,
+        cancellation: &StepperCancellation,
+    ) -> Result<(), StepperError> {
+        let cycles = (setpoint.interval_us as u64 / CYCLE_INTERVAL_US).max(1);
+        let start = self.position;
+        let delta = setpoint.position - start;
+
+        let mut cycle_ticker = Ticker::every(Duration::from_micros(CYCLE_INTERVAL_US));
+        for cycle in 1..=cycles {
+            PROBE.record_position(self.axis, self.position_steps);
+            if PROBE.stops_axis(self.axis) {
+                info!(
+                    "Stopped by the probe, axis: {}, position: {}",
+                    self.axis, self.position_steps
+                );
+                self.position = self.position_steps as f64;
+                self.velocity = 0.0;
+                self.move_stopped = true;
+                return Ok(());
+            }
+            // the rest of the setpoint is discarded with the queue
+            if let Some(request) = FLUSH_QUEUE_REQUESTS.try_receive() {
+                return self
+                    .flush(stepper, request, cancellation)
+                    .await;
+            }
+
+            let position = start + delta * (cycle as f64 / cycles as f64);
+            self.velocity = delta / cycles as f64;
+            self.step_to(stepper, position, cancellation)
+                .await?;
+
+            cycle_ticker.next().await;
+        }
+
+        self.position = setpoint.position;
+        Ok(())
+    }
+
+    /// Steps the axis to the position, rounded to whole steps, within a cycle.
+    async fn step_to(
+        &mut self,
+        stepper: &mut impl Stepper,
+        position: f64,
+        cancellation: &StepperCancellation,
+    ) -> Result<(), StepperError> {
+        let new_position_steps = round(position) as i64;
+        let delta_steps = new_position_steps - self.position_steps;
+
+        let required_direction = match delta_steps {
+            0 => self.direction.clone(),
+            delta if delta > 0 => Some(StepperDirection::Normal),
+            _ => Some(StepperDirection::Reversed),
+        };
+        if required_direction != self.direction {
+            if let Some(required_direction) = required_direction.clone() {
+                stepper.direction(required_direction)?;
+            }
+            self.direction = required_direction;
+        }
+
+        let burst_started_at = Instant::now();
+        stepper
+            .step_burst(
+                delta_steps.unsigned_abs() as u32,
+                CYCLE_INTERVAL_US,
+                burst_started_at,
+                cancellation,
+            )
+            .await?;
+        self.position = position;
+        self.position_steps = new_position_steps;
+
+        if let Some(monitor) = &mut self.motion_anomaly_monitor {
+            let cycle = StepCycle {
+                steps: delta_steps,
+                commanded: Duration::from_micros(CYCLE_INTERVAL_US),
+                elapsed: burst_started_at.elapsed(),
+            };
+            monitor.check(stepper, &cycle, cancellation)?;
+        }
+        Ok(())
+    }
+}
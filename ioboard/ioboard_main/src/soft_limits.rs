@@ -0,0 +1,72 @@
+//! Soft limits, the travel of an axis, a move to a target outside the soft limits is refused when it's queued, see
+//! [`MotionCommandError::OutsideSoftLimits`], before the trajectory is planned.
+//!
+//! The limits are relative to the homed position.  The endstop may be outside the soft limits, e.g. when they keep the
+//! axis clear of the switch, the axis is then moved to the nearest soft limit after homing, see
+//! [`AxisHoming`](crate::homing::AxisHoming).
+//!
+//! Only the moves planned by the io board are limited, the server is responsible for the travel of the setpoints it
+//! streams, see [`MotionPlanning::Server`](crate::MotionPlanning::Server).
+//!
+//! FUTURE jog requests, once the io board has them, are limited the same way.
+
+use ioboard_shared::motion::MotionCommandError;
+use libm::{ceil, floor, round};
+
+/// In steps, relative to the homed position, both inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub struct SoftLimits {
+    min: i64,
+    max: i64,
+}
+
+impl SoftLimits {
+    /// `None` if `min` is greater than `max`.
+    pub const fn new(min: i64, max: i64) -> Option<Self> {
+        match min <= max {
+            true => Some(Self {
+                min,
+                max,
+            }),
+            false => None,
+        }
+    }
+
+    /// From the limits in the units of the axis, e.g. mm or degrees, rounded inwards to whole steps, so that the travel
+    /// is never extended.
+    ///
+    /// `steps_per_unit` is negative for an axis that is reversed, `None` if the limits are not finite.
+    pub fn from_units(min: f64, max: f64, steps_per_unit: f64) -> Option<Self> {
+        let (first, second) = (min * steps_per_unit, max * steps_per_unit);
+        let (min, max) = (ceil(first.min(second)), floor(first.max(second)));
+        if !min.is_finite() || !max.is_finite() {
+            return None;
+        }
+        Self::new(min as i64, max as i64)
+    }
+
+    pub fn min(&self) -> i64 {
+        self.min
+    }
+
+    pub fn max(&self) -> i64 {
+        self.max
+    }
+
+    /// The trajectory ends on the step of the target, rounded, see
+    /// [`convert_to_steps`](crate::trajectory::convert_to_steps).
+    pub fn check(&self, target: f64) -> Result<(), MotionCommandError> {
+        let target_steps = round(target);
+        match target_steps >= self.min as f64 && target_steps <= self.max as f64 {
+            true => Ok(()),
+            false => Err(MotionCommandError::OutsideSoftLimits {
+                min: self.min,
+                max: self.max,
+            }),
+        }
+    }
+
+    pub fn clamp(&self, position: i64) -> i64 {
+        position.clamp(self.min, self.max)
+    }
+}
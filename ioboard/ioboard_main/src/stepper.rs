@@ -1,11 +1,21 @@
-#[derive(Debug, Default, PartialEq, Clone)]
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::load::DriverFeedback;
+
+#[derive(Debug, Default, PartialEq, Clone, defmt::Format)]
 pub enum StepperDirection {
     #[default]
     Normal,
     Reversed,
 }
 
-/// A simple synchronous stepper trait.
+/// A single stepper trait used by all runtime front-ends.
+///
+/// Implementations only need to provide the single-pulse primitive, [`Stepper::step`], the
+/// waiting and burst behavior is provided and honors a [`StepperCancellation`] so that an e-stop
+/// can interrupt a long burst of steps mid-cycle.
 #[allow(async_fn_in_trait)]
 pub trait Stepper {
     // configuration
@@ -18,16 +28,73 @@ pub trait Stepper {
 
     // operation
 
+    /// Sets the enable pin of the driver, the motor holds its position while enabled.
     fn enable(&mut self) -> Result<(), StepperError>;
+    /// Clears the enable pin of the driver, the motor is not powered, e.g. after an e-stop, see
+    /// [`safety`](crate::safety).
     fn disable(&mut self) -> Result<(), StepperError>;
+    /// Reduces the current of the driver while the motor is at standstill, `hold_percent` is the hold current in
+    /// percent of the run current, 0-100.  Returns [`StepperError::Unsupported`] for drivers without current control.
+    fn set_hold_current_reduction(&mut self, hold_percent: u8) -> Result<(), StepperError> {
+        let _ = hold_percent;
+        Err(StepperError::Unsupported)
+    }
     fn direction(&mut self, direction: StepperDirection) -> Result<(), StepperError>;
 
-    /// Perform a single step pulse and waits for the pulse delay to expire
-    async fn step_and_wait(&mut self) -> Result<(), StepperError>;
+    /// Returns `None` for drivers without current feedback.
+    fn read_feedback(&mut self) -> Result<Option<DriverFeedback>, StepperError> {
+        Ok(None)
+    }
+
+    /// Returns the count of the encoder of the axis, the count wraps around, `None` for axes without an encoder.
+    fn read_encoder(&mut self) -> Result<Option<u16>, StepperError> {
+        Ok(None)
+    }
 
     /// Perform a single step pulse and return the pulse delay so the caller can schedule the next
     /// step without an additional await.
     async fn step(&mut self) -> Result<u32, StepperError>;
+
+    /// Perform a single step pulse and wait for the pulse delay to expire.
+    async fn step_and_wait(&mut self, cancellation: &StepperCancellation) -> Result<(), StepperError> {
+        cancellation.check()?;
+        let pulse_delay = self.step().await?;
+        Timer::after(Duration::from_micros(pulse_delay as u64)).await;
+        Ok(())
+    }
+
+    /// Perform `steps` step pulses, evenly spaced over `period_us`, starting at `start`.
+    ///
+    /// The spacing is never less than the pulse delay returned by the stepper.
+    ///
+    /// Cancellation is checked before every pulse, returns [`StepperError::Cancelled`] if the burst
+    /// was interrupted, the remaining steps are not issued.
+    async fn step_burst(
+        &mut self,
+        steps: u32,
+        period_us: u64,
+        start: Instant,
+        cancellation: &StepperCancellation,
+    ) -> Result<(), StepperError> {
+        if steps == 0 {
+            return Ok(());
+        }
+
+        let pulse_interval_us: u64 = period_us / steps as u64;
+        let mut step_deadline = start.as_micros();
+
+        for _ in 0..steps {
+            cancellation.check()?;
+
+            let pulse_delay = self.step().await?;
+
+            // wait until next step pulse or the pulse delay has elapsed
+            step_deadline = step_deadline.wrapping_add(pulse_interval_us.max(pulse_delay as u64));
+            Timer::at(Instant::from_micros(step_deadline)).await
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -35,4 +102,179 @@ pub enum StepperError {
     IoError,
     // FUTURE add a generic error type so the driver errors can be retained/handled/printed
     DriverError,
+    /// The operation was interrupted via a [`StepperCancellation`].
+    Cancelled,
+    /// More steps in a cycle than fit in the [`PulseIntervals`].
+    TooManySteps,
+    /// The driver doesn't support the operation.
+    Unsupported,
+}
+
+/// The most step pulses in a trajectory cycle, at the 1ms cycle this is a step rate of 64kHz, above the step rate of
+/// the drivers.
+pub const PULSES_PER_CYCLE_MAX: usize = 64;
+
+/// The step pulses of a trajectory cycle, as the interval after each pulse, in microseconds.
+///
+/// The intervals add up to the period of the cycle, so that the pulses of consecutive cycles are evenly spaced too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PulseIntervals {
+    intervals: [u32; PULSES_PER_CYCLE_MAX],
+    len: usize,
+}
+
+impl PulseIntervals {
+    pub const fn new() -> Self {
+        Self {
+            intervals: [0; PULSES_PER_CYCLE_MAX],
+            len: 0,
+        }
+    }
+
+    /// Spreads `steps` pulses evenly over `period_us`, the remainder of the division is spread over the intervals
+    /// instead of being added to the last one.
+    ///
+    /// The buffer is left empty if there are more than [`PULSES_PER_CYCLE_MAX`] steps.
+    pub fn fill(&mut self, steps: u32, period_us: u32) -> Result<(), StepperError> {
+        self.len = 0;
+        if steps as usize > PULSES_PER_CYCLE_MAX {
+            return Err(StepperError::TooManySteps);
+        }
+
+        let mut elapsed_us = 0_u64;
+        for (index, interval) in self.intervals[..steps as usize]
+            .iter_mut()
+            .enumerate()
+        {
+            let next_us = (index as u64 + 1) * period_us as u64 / steps as u64;
+            *interval = (next_us - elapsed_us) as u32;
+            elapsed_us = next_us;
+        }
+        self.len = steps as usize;
+        Ok(())
+    }
+
+    pub fn as_slice(&self) -> &[u32] {
+        &self.intervals[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Default for PulseIntervals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generates the step pulses of a trajectory cycle from its [`PulseIntervals`], the trajectory loop fills the
+/// intervals of each cycle and leaves the timing of the pulses to the generator.
+///
+/// An implementation backed by a hardware timer loads the intervals, e.g. into the auto-reload register of a timer
+/// with a DMA burst per pulse, and returns once they are loaded, the pulses are then output by the timer while the
+/// next cycle is planned.  The step pin is then driven by the timer, not by [`Stepper::step`].
+///
+/// Implementations never output a pulse sooner than the pulse width plus the pulse delay of the stepper after the
+/// previous one, an interval shorter than that delays the remaining pulses of the cycle.
+#[allow(async_fn_in_trait)]
+pub trait StepPulseGenerator<STEPPER: Stepper> {
+    /// Outputs the pulses, the first one at `start`, after the pulses of the previous cycle.
+    ///
+    /// Cancellation is checked before the pulses are output, returns [`StepperError::Cancelled`] if interrupted, the
+    /// remaining pulses are not output.
+    async fn generate(
+        &mut self,
+        stepper: &mut STEPPER,
+        pulses: &PulseIntervals,
+        start: Instant,
+        cancellation: &StepperCancellation,
+    ) -> Result<(), StepperError>;
+
+    /// Waits until the pulses already generated have been output, e.g. before the direction is changed.
+    async fn wait_idle(&mut self) -> Result<(), StepperError> {
+        Ok(())
+    }
+}
+
+/// Steps the stepper from software, each pulse is timed with `Timer::at`, so the spacing of the pulses is subject to
+/// the resolution of the time driver and the latency of the executor, the same as [`Stepper::step_burst`].
+///
+/// The pulses have been output once [`StepPulseGenerator::generate`] returns.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SoftwarePulseGenerator;
+
+impl<STEPPER: Stepper> StepPulseGenerator<STEPPER> for SoftwarePulseGenerator {
+    async fn generate(
+        &mut self,
+        stepper: &mut STEPPER,
+        pulses: &PulseIntervals,
+        start: Instant,
+        cancellation: &StepperCancellation,
+    ) -> Result<(), StepperError> {
+        let mut step_deadline = start.as_micros();
+
+        for interval in pulses.as_slice() {
+            cancellation.check()?;
+
+            let pulse_delay = stepper.step().await?;
+
+            // wait until next step pulse or the pulse delay has elapsed
+            step_deadline = step_deadline.wrapping_add((*interval).max(pulse_delay) as u64);
+            Timer::at(Instant::from_micros(step_deadline)).await
+        }
+
+        Ok(())
+    }
+}
+
+/// Cancellation flag for stepper operations, usually held in a `static` so it can be shared
+/// between the motion task and an e-stop handler running on a different executor.
+///
+/// Once cancelled, all stepper operations that take the cancellation fail with
+/// [`StepperError::Cancelled`] until [`StepperCancellation::reset`] is called.
+pub struct StepperCancellation {
+    cancelled: AtomicBool,
+}
+
+impl StepperCancellation {
+    pub const fn new() -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled
+            .store(true, Ordering::Release);
+    }
+
+    pub fn reset(&self) {
+        self.cancelled
+            .store(false, Ordering::Release);
+    }
+
+    #[inline(always)]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    #[inline(always)]
+    pub fn check(&self) -> Result<(), StepperError> {
+        match self.is_cancelled() {
+            true => Err(StepperError::Cancelled),
+            false => Ok(()),
+        }
+    }
+}
+
+impl Default for StepperCancellation {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -0,0 +1,241 @@
+//! Temperature sensor conversion and warning/critical threshold monitoring.
+//!
+//! A sensor at the [`ThermalLevel::Critical`] level faults the board, see [`ThermalProtection`], so does a disconnected
+//! or shorted sensor, it can't tell a hot driver from a cool one.
+
+use defmt::{error, warn};
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::power::Interlock;
+use ioboard_shared::thermal::{TemperatureSensor, ThermalLevel, ThermalReading};
+use libm::logf;
+
+use crate::power::POWER_INTERLOCKS;
+use crate::stepper::StepperCancellation;
+
+const KELVIN_OFFSET: f32 = 273.15;
+
+/// NTC thermistor, connected between the ADC input and ground, with a series resistor to the ADC reference voltage.
+#[derive(Debug, Clone, Copy)]
+pub struct NtcConfig {
+    /// resistance at 25C, in ohms
+    pub r25: f32,
+    pub beta: f32,
+    /// in ohms
+    pub series_resistor: f32,
+    /// maximum raw ADC value, e.g. 65535 for a 16 bit ADC
+    pub adc_max: f32,
+}
+
+impl Default for NtcConfig {
+    /// 10K B3950 NTC with a 10K series resistor and a 16 bit ADC
+    fn default() -> Self {
+        Self {
+            r25: 10_000.0,
+            beta: 3950.0,
+            series_resistor: 10_000.0,
+            adc_max: 65535.0,
+        }
+    }
+}
+
+impl NtcConfig {
+    /// Returns `None` if the reading indicates a disconnected or shorted sensor.
+    pub fn temperature(&self, raw: u16) -> Option<f32> {
+        let ratio = raw as f32 / self.adc_max;
+        if ratio <= 0.0 || ratio >= 1.0 {
+            return None;
+        }
+        let resistance = self.series_resistor * ratio / (1.0 - ratio);
+
+        // beta equation, simplified Steinhart-Hart
+        let inverse_kelvin = 1.0 / (25.0 + KELVIN_OFFSET) + logf(resistance / self.r25) / self.beta;
+        Some(1.0 / inverse_kelvin - KELVIN_OFFSET)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThermalThresholds {
+    /// in degrees celsius
+    pub warning: f32,
+    /// in degrees celsius
+    pub critical: f32,
+    /// the temperature must drop this far below a threshold before the level is lowered, in degrees celsius
+    pub hysteresis: f32,
+}
+
+impl ThermalThresholds {
+    pub const DRIVER: Self = Self {
+        warning: 80.0,
+        critical: 100.0,
+        hysteresis: 5.0,
+    };
+
+    pub const AMBIENT: Self = Self {
+        warning: 45.0,
+        critical: 60.0,
+        hysteresis: 2.0,
+    };
+}
+
+/// Tracks the thermal level of a single sensor.
+pub struct TemperatureMonitor {
+    sensor: TemperatureSensor,
+    thresholds: ThermalThresholds,
+    level: ThermalLevel,
+    /// the sensor is disconnected or shorted, until its next reading
+    faulty: bool,
+}
+
+impl TemperatureMonitor {
+    pub fn new(sensor: TemperatureSensor, thresholds: ThermalThresholds) -> Self {
+        Self {
+            sensor,
+            thresholds,
+            level: ThermalLevel::Normal,
+            faulty: false,
+        }
+    }
+
+    /// The sensor is disconnected or shorted, `raw` is the ADC value, the level is critical until the next reading.
+    ///
+    /// Returns an event if the sensor wasn't faulty already.
+    pub fn fault(&mut self, raw: u16) -> Option<IoBoardEvent> {
+        self.level = ThermalLevel::Critical;
+        if self.faulty {
+            return None;
+        }
+        self.faulty = true;
+        Some(IoBoardEvent::TemperatureSensorFault {
+            sensor: self.sensor,
+            raw,
+        })
+    }
+
+    /// Update the level using the latest temperature.
+    ///
+    /// Returns the reading, and an event if the level changed.
+    pub fn update(&mut self, temperature: f32) -> (ThermalReading, Option<IoBoardEvent>) {
+        self.faulty = false;
+        let thresholds = &self.thresholds;
+
+        let level = if temperature >= thresholds.critical {
+            ThermalLevel::Critical
+        } else if temperature >= thresholds.warning {
+            // only lower from critical once sufficiently cool
+            match self.level {
+                ThermalLevel::Critical if temperature > thresholds.critical - thresholds.hysteresis => {
+                    ThermalLevel::Critical
+                }
+                _ => ThermalLevel::Warning,
+            }
+        } else {
+            match self.level {
+                ThermalLevel::Critical | ThermalLevel::Warning
+                    if temperature > thresholds.warning - thresholds.hysteresis =>
+                {
+                    ThermalLevel::Warning
+                }
+                _ => ThermalLevel::Normal,
+            }
+        };
+
+        let event = match level != self.level {
+            true => Some(IoBoardEvent::ThermalLevelChanged {
+                sensor: self.sensor,
+                level,
+                temperature,
+            }),
+            false => None,
+        };
+        self.level = level;
+
+        let reading = ThermalReading {
+            sensor: self.sensor,
+            temperature,
+            level,
+        };
+
+        (reading, event)
+    }
+
+    pub fn level(&self) -> ThermalLevel {
+        self.level
+    }
+}
+
+/// The temperature sensors of a board, and their thresholds.
+#[derive(Debug, Clone, Copy)]
+pub struct ThermalProtectionConfig<const N: usize> {
+    pub ntc: NtcConfig,
+    pub sensors: [(TemperatureSensor, ThermalThresholds); N],
+}
+
+/// Monitors the temperature sensors of a board.
+///
+/// When a sensor reaches the critical level, or is disconnected or shorted, motion is stopped via the
+/// [`StepperCancellation`], and the motor power and vacuum pump rails are disabled by the [`Interlock::ThermalOk`]
+/// interlock until every sensor has cooled below the critical level.  The cancellation is not reset once cooled, motion
+/// only resumes once the fault has been cleared.
+pub struct ThermalProtection<const N: usize> {
+    ntc: NtcConfig,
+    monitors: [TemperatureMonitor; N],
+}
+
+impl<const N: usize> ThermalProtection<N> {
+    pub fn new(config: ThermalProtectionConfig<N>) -> Self {
+        Self {
+            ntc: config.ntc,
+            monitors: config
+                .sensors
+                .map(|(sensor, thresholds)| TemperatureMonitor::new(sensor, thresholds)),
+        }
+    }
+
+    /// Update the sensors using the latest raw ADC values, in the order of the sensors of the config.
+    ///
+    /// The readings and any level changes are published.
+    pub fn update(&mut self, raw: [u16; N], cancellation: &StepperCancellation) {
+        for (monitor, raw) in self.monitors.iter_mut().zip(raw) {
+            let Some(temperature) = self.ntc.temperature(raw) else {
+                // sensor disconnected or shorted
+                if let Some(event) = monitor.fault(raw) {
+                    cancellation.cancel();
+                    error!(
+                        "Temperature sensor fault, motion stopped. sensor: {}, raw: {}",
+                        monitor.sensor, raw
+                    );
+                    if ioboard_net::publish_event(event).is_err() {
+                        warn!("Event queue full, dropped thermal event");
+                    }
+                }
+                continue;
+            };
+
+            let (reading, event) = monitor.update(temperature);
+            ioboard_net::publish_thermal(&reading);
+
+            let Some(event) = event else {
+                continue;
+            };
+            match reading.level {
+                ThermalLevel::Critical => {
+                    cancellation.cancel();
+                    error!(
+                        "Thermal fault, motion stopped. sensor: {}, temperature: {}C",
+                        reading.sensor, temperature
+                    );
+                }
+                _ => warn!("Thermal level changed: {}", event),
+            }
+            if ioboard_net::publish_event(event).is_err() {
+                warn!("Event queue full, dropped thermal event");
+            }
+        }
+
+        let critical = self
+            .monitors
+            .iter()
+            .any(|monitor| monitor.level() == ThermalLevel::Critical);
+        POWER_INTERLOCKS.set(Interlock::ThermalOk, !critical);
+    }
+}
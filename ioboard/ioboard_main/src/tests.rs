@@ -0,0 +1,1081 @@
+//! Tests of the monitors and state machines of the io board, run on the host.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::vec::Vec;
+
+use embassy_futures::block_on;
+use embassy_time::{Duration, Instant};
+use ioboard_shared::estop::EStopSource;
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::homing::{AxisHomingError, HomingRequest};
+use ioboard_shared::motion::{
+    MotionCommand, MotionCommandError, MotionCommandRequest, MotionPlanning, PositionReport, QueuedMove,
+};
+use ioboard_shared::power::{Interlock, PowerRail};
+use ioboard_shared::safe_z::{SafeZConfig, SafeZRequest};
+use ioboard_shared::safety::{MotionRestriction, SafetyInput, SafetyInputState, SafetyPolicy};
+use ioboard_shared::thermal::{TemperatureSensor, ThermalLevel};
+use machine_ids::MoveId;
+
+use crate::homing::{AxisHoming, EndstopSide, Homing, HomingConfig, HomingStep, LimitSwitch};
+use crate::input_shaping::{InputShaper, ShaperConfig, ShaperConfigError, ShaperKind};
+use crate::load::{DriverFeedback, LoadConfig, LoadMonitor};
+use crate::motion_queue::{CommandAction, MotionQueue};
+use crate::power::{POWER_INTERLOCKS, PowerInterlocks};
+use crate::safe_z::SafeZGuard;
+use crate::safety::{SafetyConfig, SafetyMonitor};
+use crate::soft_limits::SoftLimits;
+use crate::stepper::{Stepper, StepperCancellation, StepperDirection, StepperError};
+use crate::temperature::{
+    NtcConfig, TemperatureMonitor, ThermalProtection, ThermalProtectionConfig, ThermalThresholds,
+};
+use crate::thermal::{ThermalConfig, ThermalModel};
+
+/// The logs are not needed by the tests.
+#[defmt::global_logger]
+struct NoLogger;
+
+unsafe impl defmt::Logger for NoLogger {
+    fn acquire() {}
+    unsafe fn flush() {}
+    unsafe fn release() {}
+    unsafe fn write(_bytes: &[u8]) {}
+}
+
+#[defmt::panic_handler]
+fn defmt_panic() -> ! {
+    panic!("defmt panic")
+}
+
+//
+// homing
+//
+
+fn homing_config() -> HomingConfig {
+    HomingConfig {
+        endstop: EndstopSide::Min,
+        seek_speed: 4000,
+        slow_speed: 400,
+        back_off_steps: 3,
+        max_travel_steps: 10,
+    }
+}
+
+fn steps(count: usize, direction: StepperDirection, speed: u32) -> Vec<HomingStep> {
+    (0..count)
+        .map(|_| HomingStep::Step {
+            direction: direction.clone(),
+            speed,
+        })
+        .collect()
+}
+
+/// Runs the homing until it completes, `triggered` is the state of the switch at a position, returns the steps, the
+/// result and the final position.
+fn simulate(
+    mut homing: Homing,
+    mut position: i64,
+    triggered: impl Fn(i64) -> bool,
+) -> (Vec<HomingStep>, HomingStep, i64) {
+    let mut taken = Vec::new();
+    for _ in 0..1000 {
+        match homing.next(triggered(position)) {
+            HomingStep::Step {
+                direction,
+                speed,
+            } => {
+                position += match direction {
+                    StepperDirection::Normal => 1,
+                    StepperDirection::Reversed => -1,
+                };
+                taken.push(HomingStep::Step {
+                    direction,
+                    speed,
+                });
+            }
+            result => return (taken, result, position),
+        }
+    }
+    panic!("Homing did not complete");
+}
+
+#[test]
+pub fn homing_backs_off_and_re_approaches_slowly() {
+    // given
+    let homing = Homing::new(homing_config(), 0);
+
+    // when
+    let (taken, result, position) = simulate(homing, 5, |position| position <= 0);
+
+    // then
+    let mut expected = steps(5, StepperDirection::Reversed, 4000);
+    expected.extend(steps(3, StepperDirection::Normal, 4000));
+    expected.extend(steps(3, StepperDirection::Reversed, 400));
+    assert_eq!(taken, expected);
+    assert_eq!(result, HomingStep::Homed);
+    assert_eq!(position, 0);
+}
+
+#[test]
+pub fn homing_recovers_away_from_the_endstop() {
+    // given
+    let homing = Homing::new(homing_config(), 2);
+
+    // when
+    let (taken, result, position) = simulate(homing, 5, |position| position <= 0);
+
+    // then
+    assert_eq!(taken[taken.len() - 2..], steps(2, StepperDirection::Normal, 4000));
+    assert_eq!(result, HomingStep::Homed);
+    assert_eq!(position, 2);
+}
+
+#[test]
+pub fn homing_towards_a_max_endstop_steps_normal() {
+    // given
+    let config = HomingConfig {
+        endstop: EndstopSide::Max,
+        ..homing_config()
+    };
+    let homing = Homing::new(config, 0);
+
+    // when
+    let (taken, result, position) = simulate(homing, -2, |position| position >= 0);
+
+    // then
+    let mut expected = steps(2, StepperDirection::Normal, 4000);
+    expected.extend(steps(3, StepperDirection::Reversed, 4000));
+    expected.extend(steps(3, StepperDirection::Normal, 400));
+    assert_eq!(taken, expected);
+    assert_eq!(result, HomingStep::Homed);
+    assert_eq!(position, 0);
+}
+
+#[test]
+pub fn homing_at_the_endstop_only_backs_off() {
+    // given
+    let homing = Homing::new(homing_config(), 0);
+
+    // when
+    let (taken, result, position) = simulate(homing, 0, |position| position <= 0);
+
+    // then
+    let mut expected = steps(3, StepperDirection::Normal, 4000);
+    expected.extend(steps(3, StepperDirection::Reversed, 400));
+    assert_eq!(taken, expected);
+    assert_eq!(result, HomingStep::Homed);
+    assert_eq!(position, 0);
+}
+
+#[test]
+pub fn homing_fails_when_the_endstop_is_not_found_within_the_travel() {
+    // given
+    let homing = Homing::new(homing_config(), 0);
+
+    // when
+    let (taken, result, _) = simulate(homing, 100, |_| false);
+
+    // then
+    assert_eq!(taken, steps(10, StepperDirection::Reversed, 4000));
+    assert_eq!(result, HomingStep::Failed(AxisHomingError::EndstopNotFound));
+}
+
+#[test]
+pub fn homing_fails_when_the_endstop_is_not_found_again() {
+    // given, the switch only triggers once, e.g. a loose wire
+    let homing = Homing::new(homing_config(), 0);
+    let first = Cell::new(true);
+
+    // when
+    let (taken, result, _) = simulate(homing, 2, |position| position <= 0 && first.replace(false));
+
+    // then, the slow approach gives up after twice the back-off
+    let mut expected = steps(2, StepperDirection::Reversed, 4000);
+    expected.extend(steps(3, StepperDirection::Normal, 4000));
+    expected.extend(steps(6, StepperDirection::Reversed, 400));
+    assert_eq!(taken, expected);
+    assert_eq!(result, HomingStep::Failed(AxisHomingError::EndstopNotFound));
+}
+
+#[test]
+pub fn homing_fails_when_the_endstop_stays_triggered() {
+    // given, e.g. a shorted switch
+    let homing = Homing::new(homing_config(), 0);
+
+    // when
+    let (taken, result, _) = simulate(homing, 5, |_| true);
+
+    // then
+    assert_eq!(taken, steps(3, StepperDirection::Normal, 4000));
+    assert_eq!(result, HomingStep::Failed(AxisHomingError::EndstopStuck));
+}
+
+/// An axis with its endstop at position 0.
+#[derive(Clone)]
+struct SimulatedAxis {
+    position: Rc<Cell<i64>>,
+    direction: StepperDirection,
+}
+
+impl LimitSwitch for SimulatedAxis {
+    fn is_triggered(&mut self) -> bool {
+        self.position.get() <= 0
+    }
+}
+
+impl Stepper for SimulatedAxis {
+    fn set_pulse_width_us(&mut self, _pulse_width: u32) {}
+
+    fn set_pulse_delay_us(&mut self, _pulse_delay: u32) {}
+
+    fn enable(&mut self) -> Result<(), StepperError> {
+        Ok(())
+    }
+
+    fn disable(&mut self) -> Result<(), StepperError> {
+        Ok(())
+    }
+
+    fn direction(&mut self, direction: StepperDirection) -> Result<(), StepperError> {
+        self.direction = direction;
+        Ok(())
+    }
+
+    async fn step(&mut self) -> Result<u32, StepperError> {
+        let delta = match self.direction {
+            StepperDirection::Normal => 1,
+            StepperDirection::Reversed => -1,
+        };
+        self.position
+            .set(self.position.get() + delta);
+        Ok(0)
+    }
+}
+
+#[test]
+pub fn a_homed_axis_is_moved_into_the_soft_limits() {
+    // given
+    let mut axis = SimulatedAxis {
+        position: Rc::new(Cell::new(20)),
+        direction: StepperDirection::Normal,
+    };
+    let config = HomingConfig {
+        seek_speed: 100_000,
+        slow_speed: 100_000,
+        max_travel_steps: 100,
+        ..homing_config()
+    };
+    let mut homing = AxisHoming::new(0, axis.clone(), Some(config), SoftLimits::new(5, 1000));
+    let cancellation = StepperCancellation::new();
+
+    // when
+    let result = block_on(homing.handle(
+        &mut axis,
+        HomingRequest {
+            axis: 0,
+        },
+        &cancellation,
+    ));
+
+    // then
+    assert_eq!(result, Ok(Some(5)));
+    assert_eq!(axis.position.get(), 5);
+}
+
+#[test]
+pub fn an_axis_is_homed_from_the_power_off_state() {
+    // given, the interlocks at power up
+    let interlocks = PowerInterlocks::new();
+    let mut axis = SimulatedAxis {
+        position: Rc::new(Cell::new(20)),
+        direction: StepperDirection::Normal,
+    };
+    let config = HomingConfig {
+        seek_speed: 100_000,
+        slow_speed: 100_000,
+        max_travel_steps: 100,
+        ..homing_config()
+    };
+    let mut homing = AxisHoming::new(0, axis.clone(), Some(config), None);
+    let cancellation = StepperCancellation::new();
+    let mut queue = MotionQueue::new(0, None);
+    let queued_move = command(MotionCommand::Move(QueuedMove {
+        move_id: MoveId::new(1),
+        target: 100.0,
+        max_velocity: 1000.0,
+        max_acceleration: 1000.0,
+        max_jerk: 1000.0,
+    }));
+
+    // expect, the motor power can be enabled, but the axis can't be moved
+    assert!(!interlocks.is_satisfied(Interlock::HomingConfigured));
+    assert_eq!(interlocks.check(PowerRail::MotorPower), Ok(()));
+    assert!(!homing.is_homed());
+    assert!(matches!(
+        queue.handle(&queued_move, None, 20, homing.is_homed()),
+        CommandAction::Respond(Err(MotionCommandError::NotHomed))
+    ));
+
+    // when
+    let result = block_on(homing.handle(
+        &mut axis,
+        HomingRequest {
+            axis: 0,
+        },
+        &cancellation,
+    ));
+
+    // then
+    assert_eq!(result, Ok(Some(0)));
+    assert!(homing.is_homed());
+    assert!(matches!(
+        queue.handle(&queued_move, None, 0, homing.is_homed()),
+        CommandAction::Respond(Ok(_))
+    ));
+}
+
+//
+// safety
+//
+
+fn at(ms: u64) -> Instant {
+    Instant::from_millis(ms)
+}
+
+fn safety_config(door: Option<SafetyPolicy>, light_curtain: Option<SafetyPolicy>) -> SafetyConfig {
+    SafetyConfig {
+        door,
+        light_curtain,
+        clear_after: Duration::from_millis(500),
+        ..SafetyConfig::default()
+    }
+}
+
+#[test]
+pub fn a_safety_input_trips_immediately_and_clears_once_it_stayed_clear() {
+    // given
+    let mut monitor = SafetyMonitor::new(safety_config(Some(SafetyPolicy::PauseMotion), None));
+
+    // expect
+    assert_eq!(
+        monitor.update(SafetyInput::Door, true, at(1000)),
+        Some(IoBoardEvent::SafetyInputChanged {
+            input: SafetyInput::Door,
+            tripped: true,
+            policy: SafetyPolicy::PauseMotion,
+        })
+    );
+    assert_eq!(monitor.restriction(), MotionRestriction::Paused);
+
+    // and
+    assert_eq!(monitor.update(SafetyInput::Door, false, at(1100)), None);
+    assert_eq!(monitor.update(SafetyInput::Door, false, at(1599)), None);
+    assert_eq!(monitor.restriction(), MotionRestriction::Paused);
+
+    // and
+    assert_eq!(
+        monitor.update(SafetyInput::Door, false, at(1600)),
+        Some(IoBoardEvent::SafetyInputChanged {
+            input: SafetyInput::Door,
+            tripped: false,
+            policy: SafetyPolicy::PauseMotion,
+        })
+    );
+    assert_eq!(monitor.restriction(), MotionRestriction::None);
+}
+
+#[test]
+pub fn a_bouncing_safety_input_restarts_the_clear_time() {
+    // given
+    let mut monitor = SafetyMonitor::new(safety_config(Some(SafetyPolicy::PauseMotion), None));
+    monitor.update(SafetyInput::Door, true, at(1000));
+
+    // when
+    assert_eq!(monitor.update(SafetyInput::Door, false, at(1100)), None);
+    assert_eq!(monitor.update(SafetyInput::Door, true, at(1200)), None);
+    assert_eq!(monitor.update(SafetyInput::Door, false, at(1300)), None);
+
+    // then
+    assert_eq!(monitor.update(SafetyInput::Door, false, at(1700)), None);
+    assert_eq!(monitor.status().door, SafetyInputState::Tripped);
+
+    // and
+    assert!(
+        monitor
+            .update(SafetyInput::Door, false, at(1800))
+            .is_some()
+    );
+    assert_eq!(monitor.status().door, SafetyInputState::Clear);
+}
+
+#[test]
+pub fn the_most_severe_policy_of_the_tripped_inputs_applies() {
+    // given
+    let mut monitor = SafetyMonitor::new(safety_config(
+        Some(SafetyPolicy::ReduceSpeed),
+        Some(SafetyPolicy::EStop),
+    ));
+
+    // when
+    monitor.update(SafetyInput::Door, true, at(1000));
+
+    // then
+    assert_eq!(monitor.restriction(), MotionRestriction::ReducedSpeed);
+
+    // when
+    monitor.update(SafetyInput::LightCurtain, true, at(1000));
+
+    // then
+    assert_eq!(monitor.restriction(), MotionRestriction::EStopped);
+
+    // when
+    monitor.update(SafetyInput::LightCurtain, false, at(1000));
+    monitor.update(SafetyInput::LightCurtain, false, at(1500));
+
+    // then
+    assert_eq!(monitor.restriction(), MotionRestriction::ReducedSpeed);
+}
+
+#[test]
+pub fn unconfigured_safety_inputs_are_ignored() {
+    // given
+    let mut monitor = SafetyMonitor::new(SafetyConfig::default());
+
+    // expect
+    assert!(!monitor.is_configured(SafetyInput::Door));
+    assert_eq!(monitor.update(SafetyInput::Door, true, at(1000)), None);
+    assert_eq!(monitor.restriction(), MotionRestriction::None);
+    assert_eq!(monitor.status().door, SafetyInputState::NotConfigured);
+}
+
+#[test]
+pub fn an_estop_is_latched_until_it_is_reset() {
+    // given
+    let mut monitor = SafetyMonitor::new(SafetyConfig::default());
+
+    // expect
+    assert_eq!(
+        monitor.latch_estop(EStopSource::OperatorUi),
+        Some(IoBoardEvent::EStopChanged {
+            source: EStopSource::OperatorUi,
+            latched: true,
+        })
+    );
+    assert_eq!(monitor.latch_estop(EStopSource::Server), None);
+    assert_eq!(monitor.restriction(), MotionRestriction::EStopped);
+    assert_eq!(monitor.status().estop, Some(EStopSource::OperatorUi));
+
+    // and
+    assert_eq!(monitor.reset_estop(EStopSource::Server), IoBoardEvent::EStopChanged {
+        source: EStopSource::Server,
+        latched: false,
+    });
+    assert_eq!(monitor.restriction(), MotionRestriction::None);
+    assert_eq!(monitor.status().estop, None);
+}
+
+//
+// soft limits
+//
+
+#[test]
+pub fn soft_limits_must_not_be_reversed() {
+    // expect
+    assert!(SoftLimits::new(10, 0).is_none());
+    assert!(SoftLimits::new(0, 0).is_some());
+}
+
+#[test]
+pub fn soft_limits_in_units_are_rounded_inwards() {
+    // expect
+    assert_eq!(SoftLimits::from_units(-0.25, 10.25, 10.0), SoftLimits::new(-2, 102));
+
+    // and, a reversed axis
+    assert_eq!(SoftLimits::from_units(-0.25, 10.25, -10.0), SoftLimits::new(-102, 2));
+
+    // and
+    assert_eq!(SoftLimits::from_units(0.0, f64::INFINITY, 10.0), None);
+}
+
+#[test]
+pub fn targets_outside_the_soft_limits_are_refused() {
+    // given
+    let soft_limits = SoftLimits::new(0, 100).unwrap();
+
+    // expect, the target is rounded to the step the trajectory ends on
+    assert_eq!(soft_limits.check(100.4), Ok(()));
+    assert_eq!(soft_limits.check(-0.4), Ok(()));
+    assert_eq!(
+        soft_limits.check(100.6),
+        Err(MotionCommandError::OutsideSoftLimits {
+            min: 0,
+            max: 100,
+        })
+    );
+    assert!(soft_limits.check(-1.0).is_err());
+
+    // and
+    assert_eq!(soft_limits.clamp(-5), 0);
+    assert_eq!(soft_limits.clamp(50), 50);
+    assert_eq!(soft_limits.clamp(105), 100);
+}
+
+//
+// motion queue
+//
+
+fn command(command: MotionCommand) -> MotionCommandRequest {
+    MotionCommandRequest {
+        axis: 0,
+        command,
+    }
+}
+
+#[test]
+pub fn the_planning_is_only_switched_to_the_server_at_rest() {
+    // given
+    let mut queue = MotionQueue::new(0, None);
+    let to_server = command(MotionCommand::SetPlanning(MotionPlanning::Server));
+
+    // expect, a move in progress
+    assert!(matches!(
+        queue.handle(&to_server, Some(MoveId::new(1)), 10, true),
+        CommandAction::Respond(Err(MotionCommandError::Moving))
+    ));
+
+    // and, a queued move
+    let queued_move = QueuedMove {
+        move_id: MoveId::new(2),
+        target: 100.0,
+        max_velocity: 1000.0,
+        max_acceleration: 1000.0,
+        max_jerk: 1000.0,
+    };
+    assert!(matches!(
+        queue.handle(&command(MotionCommand::Move(queued_move)), None, 10, true),
+        CommandAction::Respond(Ok(_))
+    ));
+    assert!(matches!(
+        queue.handle(&to_server, None, 10, true),
+        CommandAction::Respond(Err(MotionCommandError::Moving))
+    ));
+
+    // when
+    queue.pop();
+
+    // then
+    assert!(matches!(
+        queue.handle(&to_server, None, 10, true),
+        CommandAction::SwitchPlanning(Ok(status)) if status.position == 10
+    ));
+
+    // and, the planning is already on-board
+    assert!(matches!(
+        queue.handle(
+            &command(MotionCommand::SetPlanning(MotionPlanning::OnBoard)),
+            Some(MoveId::new(3)),
+            10,
+            true
+        ),
+        CommandAction::Respond(Ok(_))
+    ));
+}
+
+//
+// input shaping
+//
+
+/// 1 ms control cycle
+const SHAPER_DT: f64 = 0.001;
+
+fn shaper_config(kind: ShaperKind, damping: f64) -> ShaperConfig {
+    // a damped period of 20 ms when undamped, 20 cycles
+    ShaperConfig {
+        kind,
+        frequency: 50.0,
+        damping,
+    }
+}
+
+/// The shaped positions of a step from 0.0 to 1.0, for each cycle from the step.
+fn step_response(shaper: &mut InputShaper, cycles: usize) -> Vec<f64> {
+    shaper.reset(0.0);
+    (0..cycles)
+        .map(|_| shaper.shape(1.0))
+        .collect()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() < 1e-4,
+        "actual: {}, expected: {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+pub fn a_shaper_frequency_that_is_too_low_or_not_finite_is_refused() {
+    for frequency in [0.0, -50.0, 0.5, f64::NAN, f64::INFINITY] {
+        // given
+        let config = ShaperConfig {
+            frequency,
+            ..shaper_config(ShaperKind::Zv, 0.0)
+        };
+
+        // expect
+        assert_eq!(config.validate(), Err(ShaperConfigError::InvalidFrequency));
+        assert!(matches!(
+            InputShaper::new(&config, SHAPER_DT),
+            Err(ShaperConfigError::InvalidFrequency)
+        ));
+    }
+}
+
+#[test]
+pub fn a_shaper_damping_out_of_range_is_refused() {
+    for damping in [-0.1, 0.6, 1.0, f64::NAN] {
+        // given
+        let config = shaper_config(ShaperKind::Zvd, damping);
+
+        // expect
+        assert_eq!(config.validate(), Err(ShaperConfigError::InvalidDamping));
+        assert!(matches!(
+            InputShaper::new(&config, SHAPER_DT),
+            Err(ShaperConfigError::InvalidDamping)
+        ));
+    }
+}
+
+#[test]
+pub fn an_undamped_zv_shaper_splits_a_step_into_two_halves_half_a_period_apart() {
+    // given
+    let mut shaper = InputShaper::new(&shaper_config(ShaperKind::Zv, 0.0), SHAPER_DT).unwrap();
+
+    // when
+    let response = step_response(&mut shaper, 15);
+
+    // then
+    assert_eq!(shaper.delay_cycles(), 10);
+    for (cycle, position) in response.iter().enumerate() {
+        let expected = match cycle < 10 {
+            true => 0.5,
+            false => 1.0,
+        };
+        assert_close(*position, expected);
+    }
+}
+
+#[test]
+pub fn a_damped_zv_shaper_weights_the_first_impulse_more() {
+    // given
+    // k = exp(-0.1 * pi / sqrt(1 - 0.1^2)) = 0.7292, a damped period of 20.1 ms
+    let mut shaper = InputShaper::new(&shaper_config(ShaperKind::Zv, 0.1), SHAPER_DT).unwrap();
+
+    // when
+    let response = step_response(&mut shaper, 15);
+
+    // then
+    assert_eq!(shaper.delay_cycles(), 10);
+    // 1 / (1 + k), then the second impulse of k / (1 + k) completes the step
+    assert_close(response[0], 0.5783);
+    assert_close(response[9], 0.5783);
+    assert_close(response[10], 1.0);
+    assert_close(response[14], 1.0);
+}
+
+#[test]
+pub fn an_undamped_zvd_shaper_splits_a_step_into_three_impulses_half_a_period_apart() {
+    // given
+    let mut shaper = InputShaper::new(&shaper_config(ShaperKind::Zvd, 0.0), SHAPER_DT).unwrap();
+
+    // when
+    let response = step_response(&mut shaper, 25);
+
+    // then
+    assert_eq!(shaper.delay_cycles(), 20);
+    for (cycle, position) in response.iter().enumerate() {
+        // impulses of 0.25, 0.5 and 0.25
+        let expected = match cycle {
+            0..10 => 0.25,
+            10..20 => 0.75,
+            _ => 1.0,
+        };
+        assert_close(*position, expected);
+    }
+}
+
+#[test]
+pub fn a_damped_zvd_shaper_weights_the_impulses_by_the_damping() {
+    // given
+    let mut shaper = InputShaper::new(&shaper_config(ShaperKind::Zvd, 0.1), SHAPER_DT).unwrap();
+
+    // when
+    let response = step_response(&mut shaper, 25);
+
+    // then
+    assert_eq!(shaper.delay_cycles(), 20);
+    // 1 / (1 + k)^2, 2k / (1 + k)^2 and k^2 / (1 + k)^2
+    assert_close(response[0], 0.3344);
+    assert_close(response[10], 0.3344 + 0.4877);
+    assert_close(response[20], 1.0);
+}
+
+#[test]
+pub fn a_reset_shaper_is_at_rest_at_the_position() {
+    // given
+    let mut shaper = InputShaper::new(&shaper_config(ShaperKind::Zvd, 0.1), SHAPER_DT).unwrap();
+    step_response(&mut shaper, 5);
+
+    // when
+    shaper.reset(200.0);
+
+    // then
+    for _ in 0..25 {
+        assert_close(shaper.shape(200.0), 200.0);
+    }
+}
+
+//
+// thermal
+//
+
+fn thermal_config() -> ThermalConfig {
+    ThermalConfig {
+        run_current: 1.0,
+        ambient: 25.0,
+        full_duty_rise: 90.0,
+        time_constant: 60.0,
+        derate_start: 70.0,
+        derate_end: 100.0,
+        min_factor: 0.4,
+        factor_step: 0.05,
+    }
+}
+
+/// The derating factors published while the model is updated every second for `seconds`.
+fn thermal_factors(model: &mut ThermalModel, current: f32, seconds: u32) -> Vec<f32> {
+    (0..seconds)
+        .filter_map(|_| model.update(current, 1.0))
+        .collect()
+}
+
+#[test]
+pub fn a_driver_below_the_derating_temperature_is_not_derated() {
+    // given, 25 + 90 * 0.6^2 = 57.4 degrees at the run current
+    let mut model = ThermalModel::new(ThermalConfig {
+        run_current: 0.6,
+        ..thermal_config()
+    });
+
+    // when
+    let factors = thermal_factors(&mut model, 1.0, 3600);
+
+    // then
+    assert!(factors.is_empty());
+    assert_eq!(model.factor(), 1.0);
+    assert!((model.temperature() - 57.4).abs() < 0.1);
+}
+
+#[test]
+pub fn a_driver_at_full_duty_is_derated_with_the_default_config() {
+    // given, 25 + 90 * 0.7^2 = 69.1 degrees at the default run current
+    let mut model = ThermalModel::new(ThermalConfig::default());
+
+    // when, full duty for several time constants
+    let factors = thermal_factors(&mut model, 1.0, 600);
+
+    // then, (69.1 - 60) / (90 - 60) of the way to the min factor, rounded down to a step
+    assert!(!factors.is_empty());
+    assert!((model.temperature() - 69.1).abs() < 0.1);
+    assert!((model.factor() - 0.8).abs() < 1e-4);
+}
+
+#[test]
+pub fn a_driver_at_the_run_current_is_derated_in_steps_down_to_the_min_factor() {
+    // given
+    let mut model = ThermalModel::new(thermal_config());
+
+    // when
+    let factors = thermal_factors(&mut model, 1.0, 3600);
+
+    // then
+    assert!(factors.len() > 1);
+    assert!(factors.len() <= 12);
+    assert!(
+        factors
+            .windows(2)
+            .all(|pair| pair[1] < pair[0] && pair[0] - pair[1] > 0.05 - 1e-4)
+    );
+    assert!((model.factor() - 0.4).abs() < 1e-6);
+    assert!(model.temperature() > 100.0);
+}
+
+#[test]
+pub fn the_derating_is_lifted_once_the_driver_cooled_down() {
+    // given
+    let mut model = ThermalModel::new(thermal_config());
+    thermal_factors(&mut model, 1.0, 3600);
+
+    // when, e.g. a disabled driver
+    let factors = thermal_factors(&mut model, 0.0, 3600);
+
+    // then
+    assert!(
+        factors
+            .windows(2)
+            .all(|pair| pair[1] > pair[0])
+    );
+    assert_eq!(factors.last(), Some(&1.0));
+    assert!((model.temperature() - 25.0).abs() < 0.1);
+}
+
+#[test]
+pub fn a_reduced_hold_current_keeps_the_driver_cool() {
+    // given, 25 + 90 * 0.5^2 = 47.5 degrees at the hold current
+    let mut model = ThermalModel::new(thermal_config());
+
+    // when
+    let factors = thermal_factors(&mut model, 0.5, 3600);
+
+    // then
+    assert!(factors.is_empty());
+    assert!((model.temperature() - 47.5).abs() < 0.1);
+}
+
+#[test]
+pub fn the_heating_follows_the_time_constant() {
+    // given
+    let mut model = ThermalModel::new(thermal_config());
+
+    // when, a single update of one time constant
+    model.update(1.0, 60.0);
+
+    // then, 63% of the way to 115 degrees
+    assert!((model.temperature() - (25.0 + 90.0 * 0.632)).abs() < 0.1);
+}
+
+//
+// temperature
+//
+
+#[test]
+pub fn a_temperature_sensor_at_a_rail_of_its_adc_faults_the_board() {
+    // shorted, and disconnected
+    for raw in [0, u16::MAX] {
+        // given
+        let mut protection = ThermalProtection::new(ThermalProtectionConfig {
+            ntc: NtcConfig::default(),
+            sensors: [(TemperatureSensor::Driver(0), ThermalThresholds::DRIVER)],
+        });
+        let mut monitor = TemperatureMonitor::new(TemperatureSensor::Driver(0), ThermalThresholds::DRIVER);
+        let cancellation = StepperCancellation::new();
+
+        // when
+        protection.update([raw], &cancellation);
+
+        // then
+        assert!(cancellation.is_cancelled());
+        assert!(!POWER_INTERLOCKS.is_satisfied(Interlock::ThermalOk));
+
+        // and, the fault is reported once
+        assert_eq!(
+            monitor.fault(raw),
+            Some(IoBoardEvent::TemperatureSensorFault {
+                sensor: TemperatureSensor::Driver(0),
+                raw,
+            })
+        );
+        assert_eq!(monitor.fault(raw), None);
+        assert_eq!(monitor.level(), ThermalLevel::Critical);
+
+        // and, lifted once the sensor reads 25 degrees again
+        protection.update([u16::MAX / 2], &cancellation);
+        assert!(POWER_INTERLOCKS.is_satisfied(Interlock::ThermalOk));
+    }
+}
+
+//
+// load
+//
+
+fn feedback(stall_guard: u16, standstill: bool) -> DriverFeedback {
+    DriverFeedback {
+        stall_guard,
+        stall_guard_max: 1000,
+        current_scale: 16,
+        current_scale_max: 31,
+        standstill,
+    }
+}
+
+/// Past the warm-up, with a baseline of the `normal` load.
+fn warmed_up_load_monitor(normal: &DriverFeedback) -> LoadMonitor {
+    let config = LoadConfig::default();
+    let mut monitor = LoadMonitor::new(0, config);
+    for _ in 0..=config.warmup_samples {
+        assert_eq!(monitor.update(normal).1, None);
+    }
+    monitor
+}
+
+#[test]
+pub fn a_sudden_rise_of_the_load_is_a_crash() {
+    // given
+    let (normal, crashed) = (feedback(800, false), feedback(300, false));
+    let mut monitor = warmed_up_load_monitor(&normal);
+
+    // expect, a crash requires consecutive samples
+    assert_eq!(monitor.update(&crashed).1, None);
+    assert_eq!(monitor.update(&crashed).1, None);
+    assert_eq!(
+        monitor.update(&crashed).1,
+        Some(IoBoardEvent::AxisCrash {
+            axis: 0,
+            load: crashed.load(),
+            baseline: normal.load(),
+        })
+    );
+
+    // and, only reported once
+    assert_eq!(monitor.update(&crashed).1, None);
+}
+
+#[test]
+pub fn a_single_spike_of_the_load_is_not_a_crash() {
+    // given
+    let (normal, crashed) = (feedback(800, false), feedback(300, false));
+    let mut monitor = warmed_up_load_monitor(&normal);
+
+    // expect
+    for sample in [crashed, crashed, normal, crashed, crashed] {
+        assert_eq!(monitor.update(&sample).1, None);
+    }
+}
+
+#[test]
+pub fn crashes_are_not_detected_during_the_warm_up() {
+    // given
+    let config = LoadConfig::default();
+    let mut monitor = LoadMonitor::new(0, config);
+    monitor.update(&feedback(800, false));
+
+    // expect, e.g. while accelerating
+    for _ in 1..config.warmup_samples {
+        assert_eq!(monitor.update(&feedback(300, false)).1, None);
+    }
+}
+
+#[test]
+pub fn the_load_baseline_is_reset_at_standstill() {
+    // given
+    let mut monitor = warmed_up_load_monitor(&feedback(800, false));
+
+    // when
+    let (load, event) = monitor.update(&feedback(300, true));
+
+    // then
+    assert_eq!(event, None);
+    assert!(!load.moving);
+    assert_eq!(load.baseline, load.load);
+
+    // and, the next move starts a new warm-up
+    assert_eq!(monitor.update(&feedback(300, false)).1, None);
+    assert_eq!(monitor.update(&feedback(300, false)).1, None);
+    assert_eq!(monitor.update(&feedback(300, false)).1, None);
+}
+
+//
+// safe-z
+//
+
+const Z_AXIS: u8 = 2;
+
+fn configure_safe_z(guard: &SafeZGuard, z_axis: u8) {
+    guard.handle_request(SafeZRequest::Configure(SafeZConfig {
+        z_axis,
+        safe_height: 1000,
+    }));
+}
+
+fn report(axis: u8, position: i64) -> PositionReport {
+    PositionReport {
+        axis,
+        position,
+    }
+}
+
+#[test]
+pub fn an_unconfigured_safe_z_guard_allows_every_move() {
+    // given
+    let guard = SafeZGuard::new();
+
+    // expect
+    assert!(guard.allows_move(0));
+    assert!(guard.allows_move(Z_AXIS));
+}
+
+#[test]
+pub fn xy_moves_are_refused_until_z_is_at_the_safe_height() {
+    // given
+    let guard = SafeZGuard::new();
+    configure_safe_z(&guard, Z_AXIS);
+
+    // expect, the position of z is not known yet
+    assert!(!guard.allows_move(0));
+    assert!(guard.allows_move(Z_AXIS));
+
+    // and, reports of other axes are ignored
+    guard.record_position(&report(0, 5000));
+    assert!(!guard.allows_move(0));
+
+    // and
+    guard.record_position(&report(Z_AXIS, 999));
+    assert!(!guard.allows_move(0));
+    assert!(guard.allows_move(Z_AXIS));
+
+    // and
+    guard.record_position(&report(Z_AXIS, 1000));
+    assert!(guard.allows_move(0));
+}
+
+#[test]
+pub fn the_safe_z_guard_can_be_overridden() {
+    // given
+    let guard = SafeZGuard::new();
+    configure_safe_z(&guard, Z_AXIS);
+    guard.record_position(&report(Z_AXIS, 0));
+
+    // when
+    let status = guard.handle_request(SafeZRequest::Override(true));
+
+    // then
+    assert!(status.overridden);
+    assert!(guard.allows_move(0));
+
+    // and
+    guard.handle_request(SafeZRequest::Override(false));
+    assert!(!guard.allows_move(0));
+}
+
+#[test]
+pub fn the_z_position_is_kept_when_the_z_axis_is_unchanged() {
+    // given
+    let guard = SafeZGuard::new();
+    configure_safe_z(&guard, Z_AXIS);
+    guard.record_position(&report(Z_AXIS, 2000));
+
+    // when
+    configure_safe_z(&guard, Z_AXIS);
+
+    // then
+    assert_eq!(guard.status().z_position, Some(2000));
+
+    // when
+    configure_safe_z(&guard, Z_AXIS + 1);
+
+    // then
+    assert_eq!(guard.status().z_position, None);
+    assert!(!guard.allows_move(0));
+}
@@ -0,0 +1,111 @@
+//! First-order thermal model of a stepper driver, used to derate the motion limits of an axis
+//! when sustained duty is high, so that a driver slows down instead of overheating and stalling.
+//!
+//! The driver heats with the square of its current, the run current while the axis moves, even in the cycles of a slow
+//! move without a step, and the hold current while it's at rest, see [`AxisDriver::current`].  The model also cools
+//! while the motion task waits for a command at rest.
+//!
+//! [`AxisDriver::current`]: crate::driver::AxisDriver::current
+
+use libm::{expf, floorf};
+
+/// Driver thermal parameters for a single axis.
+#[derive(Debug, Clone, Copy)]
+pub struct ThermalConfig {
+    /// run current, as a fraction of the rated driver current, 0.0-1.0
+    pub run_current: f32,
+    /// estimated ambient temperature, in degrees celsius
+    pub ambient: f32,
+    /// temperature rise above ambient when continuously at the rated current, in degrees celsius
+    pub full_duty_rise: f32,
+    /// thermal time constant, in seconds
+    pub time_constant: f32,
+    /// temperature at which derating begins, in degrees celsius
+    pub derate_start: f32,
+    /// temperature at which the `min_factor` is reached, in degrees celsius
+    pub derate_end: f32,
+    /// lowest scale applied to the max velocity and acceleration, 0.0-1.0
+    pub min_factor: f32,
+    /// derating is applied in steps of this size, avoids re-planning/publishing on tiny changes
+    pub factor_step: f32,
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        Self {
+            run_current: 0.7,
+            ambient: 25.0,
+            full_duty_rise: 90.0,
+            time_constant: 60.0,
+            // below the 25 + 90 * 0.7^2 = 69.1 degrees of sustained full duty at the run current, so that it's derated
+            derate_start: 60.0,
+            derate_end: 90.0,
+            min_factor: 0.4,
+            factor_step: 0.05,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ThermalModel {
+    config: ThermalConfig,
+    temperature: f32,
+    factor: f32,
+}
+
+impl ThermalModel {
+    pub fn new(config: ThermalConfig) -> Self {
+        Self {
+            temperature: config.ambient,
+            factor: 1.0,
+            config,
+        }
+    }
+
+    /// Advance the model by `dt` seconds with the driver at `current` during that time, as a fraction of the run
+    /// current, 0.0-1.0, 0.0 while the driver is disabled.
+    ///
+    /// Returns the new derating factor if it changed.
+    pub fn update(&mut self, current: f32, dt: f32) -> Option<f32> {
+        let config = &self.config;
+
+        // heating is proportional to I^2
+        let current = current.clamp(0.0, 1.0) * config.run_current;
+        let steady_state = config.ambient + config.full_duty_rise * current * current;
+        let alpha = 1.0 - expf(-dt / config.time_constant);
+        self.temperature += (steady_state - self.temperature) * alpha;
+
+        let factor = self.factor_for(self.temperature);
+        if factor != self.factor {
+            self.factor = factor;
+            Some(factor)
+        } else {
+            None
+        }
+    }
+
+    fn factor_for(&self, temperature: f32) -> f32 {
+        let config = &self.config;
+        if temperature <= config.derate_start {
+            return 1.0;
+        }
+        let range = (config.derate_end - config.derate_start).max(f32::EPSILON);
+        let progress = ((temperature - config.derate_start) / range).min(1.0);
+        let factor = 1.0 - progress * (1.0 - config.min_factor);
+
+        // quantize downwards so that the factor only changes in `factor_step` increments
+        let step = config.factor_step.max(f32::EPSILON);
+        let quantized = floorf(factor / step) * step;
+        quantized.max(config.min_factor)
+    }
+
+    /// Scale applied to the max velocity and acceleration, 1.0 = no derating
+    pub fn factor(&self) -> f32 {
+        self.factor
+    }
+
+    /// Estimated driver temperature, in degrees celsius
+    pub fn temperature(&self) -> f32 {
+        self.temperature
+    }
+}
@@ -0,0 +1,426 @@
+//! Trinamic TMC2209 and TMC5160 stepper drivers, configured via their registers, see [`TmcConfig`], and stepped via
+//! their step and direction inputs, see [`TmcStepper`].
+//!
+//! The registers of a TMC2209 are accessed via its single-wire UART, see [`TmcUart`], the registers of a TMC5160 via
+//! SPI, see [`TmcSpi`].  The configuration is written when the driver is configured, the drivers don't persist it, so
+//! it's written again after the driver was powered off.
+//!
+//! StallGuard, the load measurement of the drivers, is output on the DIAG pin of the driver when the motor stalls, see
+//! [`StallGuardSwitch`], so that an axis can be homed without an endstop, sensorless homing.
+
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::digital::{InputPin, OutputPin};
+
+use crate::homing::LimitSwitch;
+use crate::load::DriverFeedback;
+use crate::stepper::{Stepper, StepperDirection, StepperError};
+
+pub mod registers;
+mod spi;
+mod uart;
+
+use registers::*;
+pub use spi::TmcSpi;
+pub use uart::TmcUart;
+
+/// The register interface of a driver, see [`TmcUart`] and [`TmcSpi`].
+pub trait TmcBus {
+    fn read_register(&mut self, register: u8) -> Result<u32, TmcError>;
+    fn write_register(&mut self, register: u8, value: u32) -> Result<(), TmcError>;
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, defmt::Format)]
+pub enum TmcError {
+    /// the UART or SPI failed
+    Bus,
+    /// the driver didn't reply, e.g. it's not powered or the node address is wrong
+    Timeout,
+    /// the CRC of a reply is wrong
+    Crc,
+    UnexpectedReply,
+    /// the write counter of the driver didn't increment after a write
+    WriteNotAcknowledged,
+    /// the version of the driver is not the version of the chip of the configuration
+    UnexpectedVersion(u8),
+    InvalidConfig(TmcConfigError),
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, defmt::Format)]
+pub enum TmcConfigError {
+    /// not a power of two from 1 to 256
+    InvalidMicrosteps,
+    /// more than [`CURRENT_SCALE_MAX`]
+    InvalidCurrent,
+    /// more than [`HOLD_DELAY_MAX`]
+    InvalidHoldDelay,
+    /// out of the range of the threshold of the chip, see [`StallGuardConfig::threshold`]
+    InvalidStallGuardThreshold,
+    /// StallGuard of the TMC2209 requires stealthChop, StallGuard of the TMC5160 requires spreadCycle
+    StallGuardRequiresChopperMode,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, defmt::Format)]
+pub enum TmcChip {
+    /// UART, StallGuard4, in stealthChop
+    Tmc2209,
+    /// SPI, StallGuard2, in spreadCycle
+    Tmc5160,
+}
+
+impl TmcChip {
+    const fn version(&self) -> (u8, u8) {
+        match self {
+            TmcChip::Tmc2209 => (IOIN_2209, VERSION_2209),
+            TmcChip::Tmc5160 => (IOIN_5160, VERSION_5160),
+        }
+    }
+
+    /// The maximum of the StallGuard result, SG_RESULT is 10 bits, the TMC2209 uses the upper 9 bits.
+    const fn stall_guard_max(&self) -> u16 {
+        match self {
+            TmcChip::Tmc2209 => 510,
+            TmcChip::Tmc5160 => 1023,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, defmt::Format)]
+pub enum ChopperMode {
+    /// Quiet, the voltage is regulated, at every velocity.
+    StealthChop,
+    /// The current is regulated, more torque at high velocities, at every velocity.
+    SpreadCycle,
+    /// stealthChop up to the velocity of `tpwmthrs`, spreadCycle above it, `tpwmthrs` is the time between microsteps,
+    /// TSTEP, in clock cycles of the driver, so a higher value is a lower velocity.
+    Hybrid { tpwmthrs: u32 },
+}
+
+/// StallGuard, the stall is output on the DIAG pin, DIAG0 for the TMC5160.
+///
+/// StallGuard is only active above the velocity of `tcoolthrs`, the time between microsteps, TSTEP, in clock cycles of
+/// the driver, so a higher value is a lower velocity.
+#[derive(Debug, PartialEq, Copy, Clone, defmt::Format)]
+pub struct StallGuardConfig {
+    /// TMC2209, SGTHRS, 0-255, higher is more sensitive, a stall is output when the StallGuard result is below twice
+    /// the threshold.
+    /// TMC5160, SGT, -64 to 63, lower is more sensitive.
+    pub threshold: i16,
+    pub tcoolthrs: u32,
+}
+
+/// The most current scale, 31 is the full scale current of the driver, set by its sense resistors.
+pub const CURRENT_SCALE_MAX: u8 = 31;
+pub const HOLD_DELAY_MAX: u8 = 15;
+
+#[derive(Debug, PartialEq, Copy, Clone, defmt::Format)]
+pub struct TmcConfig {
+    pub chip: TmcChip,
+    /// per full step, a power of two from 1 to 256, the driver interpolates to 256 microsteps
+    pub microsteps: u16,
+    /// current scale, 0 to [`CURRENT_SCALE_MAX`]
+    pub run_current: u8,
+    /// current scale at standstill, 0 to [`CURRENT_SCALE_MAX`]
+    pub hold_current: u8,
+    /// the delay of the reduction to the hold current after standstill, 0 to [`HOLD_DELAY_MAX`], in multiples of 2^18
+    /// clock cycles of the driver
+    pub hold_delay: u8,
+    pub chopper: ChopperMode,
+    pub stall_guard: Option<StallGuardConfig>,
+}
+
+impl TmcConfig {
+    pub fn validate(&self) -> Result<(), TmcConfigError> {
+        if mres(self.microsteps).is_none() {
+            return Err(TmcConfigError::InvalidMicrosteps);
+        }
+        if self.run_current > CURRENT_SCALE_MAX || self.hold_current > CURRENT_SCALE_MAX {
+            return Err(TmcConfigError::InvalidCurrent);
+        }
+        if self.hold_delay > HOLD_DELAY_MAX {
+            return Err(TmcConfigError::InvalidHoldDelay);
+        }
+        if let Some(stall_guard) = &self.stall_guard {
+            let (range, chopper_supported) = match self.chip {
+                TmcChip::Tmc2209 => (0..=255, self.chopper == ChopperMode::StealthChop),
+                TmcChip::Tmc5160 => (-64..=63, self.chopper == ChopperMode::SpreadCycle),
+            };
+            if !range.contains(&stall_guard.threshold) {
+                return Err(TmcConfigError::InvalidStallGuardThreshold);
+            }
+            if !chopper_supported {
+                return Err(TmcConfigError::StallGuardRequiresChopperMode);
+            }
+        }
+        Ok(())
+    }
+
+    /// The registers to write, in order, the configuration must be valid.
+    fn registers(&self) -> [(u8, u32); 6] {
+        let stealth_chop = self.chopper != ChopperMode::SpreadCycle;
+        let tpwmthrs = match self.chopper {
+            ChopperMode::Hybrid {
+                tpwmthrs,
+            } => tpwmthrs,
+            // 0 disables the switch to spreadCycle
+            _ => 0,
+        };
+
+        // for the TMC2209 I_scale_analog is left clear, so that the current is set by IHOLD_IRUN only, not scaled by
+        // the VREF input
+        let gconf = match self.chip {
+            TmcChip::Tmc2209 => {
+                let mut gconf = GCONF_2209_PDN_DISABLE | GCONF_2209_MSTEP_REG_SELECT | GCONF_2209_MULTISTEP_FILT;
+                if !stealth_chop {
+                    gconf |= GCONF_2209_EN_SPREADCYCLE;
+                }
+                gconf
+            }
+            TmcChip::Tmc5160 => {
+                let mut gconf = GCONF_5160_MULTISTEP_FILT;
+                if stealth_chop {
+                    gconf |= GCONF_5160_EN_PWM_MODE;
+                }
+                if self.stall_guard.is_some() {
+                    gconf |= GCONF_5160_DIAG0_STALL | GCONF_5160_DIAG0_PUSHPULL;
+                }
+                gconf
+            }
+        };
+        let stall_guard = self
+            .stall_guard
+            .unwrap_or(StallGuardConfig {
+                threshold: 0,
+                tcoolthrs: 0,
+            });
+        let stall_guard_threshold = match self.chip {
+            TmcChip::Tmc2209 => (SGTHRS, stall_guard.threshold as u32 & 0xFF),
+            TmcChip::Tmc5160 => (COOLCONF, coolconf_5160(stall_guard.threshold as i8)),
+        };
+
+        [
+            (GCONF, gconf),
+            (CHOPCONF, chopconf(mres(self.microsteps).unwrap_or_default())),
+            (
+                IHOLD_IRUN,
+                ihold_irun(self.hold_current, self.run_current, self.hold_delay),
+            ),
+            (TPWMTHRS, tpwmthrs),
+            (TCOOLTHRS, stall_guard.tcoolthrs),
+            stall_guard_threshold,
+        ]
+    }
+}
+
+/// A TMC2209 or TMC5160, the registers are accessed via the `BUS`, the driver is enabled via its ENN pin, active low,
+/// and stepped via its STEP and DIR pins.
+pub struct TmcStepper<BUS, EN, STEP, DIR> {
+    bus: BUS,
+    config: TmcConfig,
+    enable_pin: EN,
+    step_pin: STEP,
+    direction_pin: DIR,
+    /// pulse width (us)
+    pulse_width: u32,
+    /// minimum delay between pulses (us)
+    pulse_delay: u32,
+}
+
+impl<BUS, EN, STEP, DIR> TmcStepper<BUS, EN, STEP, DIR>
+where
+    BUS: TmcBus,
+    EN: OutputPin,
+    STEP: OutputPin,
+    DIR: OutputPin,
+{
+    /// The driver is not configured until [`TmcStepper::configure`] is called.
+    pub fn new(
+        bus: BUS,
+        config: TmcConfig,
+        enable_pin: EN,
+        step_pin: STEP,
+        direction_pin: DIR,
+        pulse_width: u32,
+        pulse_delay: u32,
+    ) -> Self {
+        Self {
+            bus,
+            config,
+            enable_pin,
+            step_pin,
+            direction_pin,
+            pulse_width,
+            pulse_delay,
+        }
+    }
+
+    /// Sets the step and direction pins low, and disables the driver.
+    pub fn initialize_io(&mut self) -> Result<(), StepperError> {
+        self.step_pin
+            .set_low()
+            .map_err(|_e| StepperError::IoError)?;
+        self.direction_pin
+            .set_low()
+            .map_err(|_e| StepperError::IoError)?;
+        self.disable()
+    }
+
+    /// Checks the version of the driver and writes the configuration, the driver stays disabled, a configuration that
+    /// could not be written completely leaves the driver partially configured.
+    pub fn configure(&mut self) -> Result<(), TmcError> {
+        self.config
+            .validate()
+            .map_err(TmcError::InvalidConfig)?;
+
+        let (ioin, expected_version) = self.config.chip.version();
+        let version = ioin_version(self.bus.read_register(ioin)?);
+        if version != expected_version {
+            warn!(
+                "Unexpected TMC driver version, chip: {}, version: {:#04x}",
+                self.config.chip, version
+            );
+            return Err(TmcError::UnexpectedVersion(version));
+        }
+
+        for (register, value) in self.config.registers() {
+            self.bus
+                .write_register(register, value)
+                .inspect_err(|e| {
+                    warn!(
+                        "Unable to write TMC driver register, register: {:#04x}, error: {}",
+                        register, e
+                    )
+                })?;
+        }
+
+        info!(
+            "TMC driver configured, chip: {}, microsteps: {}, run current: {}, hold current: {}, chopper: {}, stall guard: {}",
+            self.config.chip,
+            self.config.microsteps,
+            self.config.run_current,
+            self.config.hold_current,
+            self.config.chopper,
+            self.config.stall_guard
+        );
+        Ok(())
+    }
+
+    /// The StallGuard result, 0 is stalled, see [`DriverFeedback`].
+    pub fn read_stall_guard(&mut self) -> Result<u16, TmcError> {
+        match self.config.chip {
+            TmcChip::Tmc2209 => Ok(self.bus.read_register(SG_RESULT)? as u16 & 0x3FF),
+            TmcChip::Tmc5160 => Ok(drv_status_sg_result(self.bus.read_register(DRV_STATUS)?)),
+        }
+    }
+}
+
+impl<BUS, EN, STEP, DIR> Stepper for TmcStepper<BUS, EN, STEP, DIR>
+where
+    BUS: TmcBus,
+    EN: OutputPin,
+    STEP: OutputPin,
+    DIR: OutputPin,
+{
+    fn set_pulse_width_us(&mut self, pulse_width: u32) {
+        self.pulse_width = pulse_width;
+    }
+
+    fn set_pulse_delay_us(&mut self, pulse_delay: u32) {
+        self.pulse_delay = pulse_delay;
+    }
+
+    #[inline(always)]
+    fn enable(&mut self) -> Result<(), StepperError> {
+        self.enable_pin
+            .set_low()
+            .map_err(|_e| StepperError::IoError)
+    }
+
+    #[inline(always)]
+    fn disable(&mut self) -> Result<(), StepperError> {
+        self.enable_pin
+            .set_high()
+            .map_err(|_e| StepperError::IoError)
+    }
+
+    fn set_hold_current_reduction(&mut self, hold_percent: u8) -> Result<(), StepperError> {
+        // IHOLD is relative to the current scale, the same as IRUN, 0-31
+        let hold_current = (self.config.run_current as u32 * hold_percent.min(100) as u32 / 100) as u8;
+        // IHOLD_IRUN is write-only, so it's written from the configuration
+        self.bus
+            .write_register(
+                IHOLD_IRUN,
+                ihold_irun(hold_current, self.config.run_current, self.config.hold_delay),
+            )
+            .map_err(|_e| StepperError::DriverError)?;
+        self.config.hold_current = hold_current;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn direction(&mut self, direction: StepperDirection) -> Result<(), StepperError> {
+        match direction {
+            StepperDirection::Normal => self.direction_pin.set_low(),
+            StepperDirection::Reversed => self.direction_pin.set_high(),
+        }
+        .map_err(|_e| StepperError::IoError)
+    }
+
+    // the StallGuard result is only valid above the velocity of TCOOLTHRS, in the chopper mode of the StallGuard of the
+    // chip, see [`StallGuardConfig`]
+    fn read_feedback(&mut self) -> Result<Option<DriverFeedback>, StepperError> {
+        let drv_status = self
+            .bus
+            .read_register(DRV_STATUS)
+            .map_err(|_e| StepperError::DriverError)?;
+        let stall_guard = self
+            .read_stall_guard()
+            .map_err(|_e| StepperError::DriverError)?;
+
+        Ok(Some(DriverFeedback {
+            stall_guard,
+            stall_guard_max: self.config.chip.stall_guard_max(),
+            current_scale: drv_status_cs_actual(drv_status),
+            current_scale_max: CURRENT_SCALE_MAX,
+            standstill: drv_status_standstill(drv_status),
+        }))
+    }
+
+    #[inline(always)]
+    async fn step(&mut self) -> Result<u32, StepperError> {
+        let now = Instant::now();
+        self.step_pin
+            .set_high()
+            .map_err(|_e| StepperError::IoError)?;
+        let deadline = now + Duration::from_micros(self.pulse_width as u64);
+        Timer::at(deadline).await;
+        self.step_pin
+            .set_low()
+            .map_err(|_e| StepperError::IoError)?;
+        Ok(self.pulse_delay)
+    }
+}
+
+/// The DIAG pin of a driver with StallGuard, see [`StallGuardConfig`], as the endstop of an axis, for sensorless
+/// homing, the pin is high while the motor is stalled.
+///
+/// StallGuard is only active above the velocity of TCOOLTHRS, so the slow speed of the homing of the axis must be above
+/// it, otherwise the stall at the endstop is not detected.  A pin that can't be read is treated as triggered, so that
+/// the axis is not driven into the endstop.
+pub struct StallGuardSwitch<PIN> {
+    diag_pin: PIN,
+}
+
+impl<PIN: InputPin> StallGuardSwitch<PIN> {
+    pub fn new(diag_pin: PIN) -> Self {
+        Self {
+            diag_pin,
+        }
+    }
+}
+
+impl<PIN: InputPin> LimitSwitch for StallGuardSwitch<PIN> {
+    fn is_triggered(&mut self) -> bool {
+        self.diag_pin.is_high().unwrap_or(true)
+    }
+}
@@ -0,0 +1,97 @@
+//! The registers of the TMC2209 and the TMC5160 that are used, and the fields of the registers that are written, the
+//! addresses and fields are the same for both drivers unless noted.
+
+/// Global configuration
+pub const GCONF: u8 = 0x00;
+/// Counts the UART writes, wraps around, used to check that a write was received
+pub const IFCNT: u8 = 0x02;
+/// TMC5160, the inputs and the version, see [`ioin_version`]
+pub const IOIN_5160: u8 = 0x04;
+/// TMC2209, the inputs and the version, see [`ioin_version`]
+pub const IOIN_2209: u8 = 0x06;
+/// Write-only
+pub const IHOLD_IRUN: u8 = 0x10;
+/// stealthChop is used while TSTEP is above this, i.e. below the velocity, write-only
+pub const TPWMTHRS: u8 = 0x13;
+/// StallGuard is active while TSTEP is below this, i.e. above the velocity, write-only
+pub const TCOOLTHRS: u8 = 0x14;
+/// TMC2209 only, the StallGuard threshold, write-only
+pub const SGTHRS: u8 = 0x40;
+/// TMC2209 only, the StallGuard result
+pub const SG_RESULT: u8 = 0x41;
+pub const CHOPCONF: u8 = 0x6C;
+/// TMC5160 only, contains the StallGuard threshold, write-only
+pub const COOLCONF: u8 = 0x6D;
+pub const DRV_STATUS: u8 = 0x6F;
+
+// GCONF, TMC2209
+
+/// spreadCycle instead of stealthChop
+pub const GCONF_2209_EN_SPREADCYCLE: u32 = 1 << 2;
+/// required for UART control, the PDN_UART pin is the UART
+pub const GCONF_2209_PDN_DISABLE: u32 = 1 << 6;
+/// the microstep resolution is set by CHOPCONF, not by the MS1 and MS2 pins
+pub const GCONF_2209_MSTEP_REG_SELECT: u32 = 1 << 7;
+pub const GCONF_2209_MULTISTEP_FILT: u32 = 1 << 8;
+
+// GCONF, TMC5160
+
+/// stealthChop, below TPWMTHRS
+pub const GCONF_5160_EN_PWM_MODE: u32 = 1 << 2;
+pub const GCONF_5160_MULTISTEP_FILT: u32 = 1 << 3;
+/// a stall is output on DIAG0
+pub const GCONF_5160_DIAG0_STALL: u32 = 1 << 7;
+/// DIAG0 is a push-pull output, active high, the same as the DIAG output of the TMC2209
+pub const GCONF_5160_DIAG0_PUSHPULL: u32 = 1 << 12;
+
+pub const VERSION_2209: u8 = 0x21;
+pub const VERSION_5160: u8 = 0x30;
+
+pub const fn ioin_version(ioin: u32) -> u8 {
+    (ioin >> 24) as u8
+}
+
+/// IHOLD, IRUN and IHOLDDELAY, the current scales are 0-31.
+pub const fn ihold_irun(hold_current: u8, run_current: u8, hold_delay: u8) -> u32 {
+    (hold_current as u32 & 0x1F) | ((run_current as u32 & 0x1F) << 8) | ((hold_delay as u32 & 0x0F) << 16)
+}
+
+/// The chopper settings are the same as the ones of the TMC5160 of the MakerPnP control core, TOFF=3, HSTRT=4,
+/// HEND=1, TBL=2, with interpolation to 256 microsteps, `mres` is the microstep resolution, see [`mres`].
+pub const fn chopconf(mres: u8) -> u32 {
+    let toff = 3;
+    let hstrt = 4;
+    let hend = 1;
+    let tbl = 2;
+    let intpol = 1;
+    toff | (hstrt << 4) | (hend << 7) | (tbl << 15) | ((mres as u32 & 0x0F) << 24) | (intpol << 28)
+}
+
+/// The MRES field for the number of microsteps per full step, `None` unless a power of two from 1 to 256.
+pub const fn mres(microsteps: u16) -> Option<u8> {
+    if microsteps == 0 || microsteps > 256 || !microsteps.is_power_of_two() {
+        return None;
+    }
+    Some(8 - microsteps.trailing_zeros() as u8)
+}
+
+/// COOLCONF of the TMC5160 with the StallGuard threshold, SGT, a 7 bit signed value, -64 to 63, the other fields
+/// are left at 0, which disables coolStep.
+pub const fn coolconf_5160(sgt: i8) -> u32 {
+    ((sgt as u8 as u32) & 0x7F) << 16
+}
+
+// DRV_STATUS
+
+/// TMC5160 only, for the TMC2209 see [`SG_RESULT`]
+pub const fn drv_status_sg_result(drv_status: u32) -> u16 {
+    (drv_status & 0x3FF) as u16
+}
+
+pub const fn drv_status_cs_actual(drv_status: u32) -> u8 {
+    ((drv_status >> 16) & 0x1F) as u8
+}
+
+pub const fn drv_status_standstill(drv_status: u32) -> bool {
+    drv_status & (1 << 31) != 0
+}
@@ -0,0 +1,43 @@
+use embedded_hal::spi::SpiDevice;
+
+use super::{TmcBus, TmcError};
+
+const WRITE: u8 = 0x80;
+
+/// The SPI interface of the TMC5160, SPI mode 3, the datagrams are 40 bits, the address and 32 bits of data, MSB
+/// first.
+///
+/// The reads are pipelined, the reply to a datagram is returned with the next datagram, so a read is two transfers,
+/// the first byte of the reply is the status of the driver.
+pub struct TmcSpi<SPI> {
+    spi: SPI,
+}
+
+impl<SPI: SpiDevice> TmcSpi<SPI> {
+    pub fn new(spi: SPI) -> Self {
+        Self {
+            spi,
+        }
+    }
+
+    fn transfer(&mut self, address: u8, value: u32) -> Result<u32, TmcError> {
+        let value = value.to_be_bytes();
+        let mut datagram = [address, value[0], value[1], value[2], value[3]];
+        self.spi
+            .transfer_in_place(&mut datagram)
+            .map_err(|_e| TmcError::Bus)?;
+        Ok(u32::from_be_bytes([datagram[1], datagram[2], datagram[3], datagram[4]]))
+    }
+}
+
+impl<SPI: SpiDevice> TmcBus for TmcSpi<SPI> {
+    fn read_register(&mut self, register: u8) -> Result<u32, TmcError> {
+        self.transfer(register, 0)?;
+        self.transfer(register, 0)
+    }
+
+    fn write_register(&mut self, register: u8, value: u32) -> Result<(), TmcError> {
+        self.transfer(register | WRITE, value)
+            .map(|_| ())
+    }
+}
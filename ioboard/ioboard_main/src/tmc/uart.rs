@@ -0,0 +1,152 @@
+use embassy_time::{Duration, Instant};
+use embedded_io::{Read, ReadReady, Write};
+
+use super::registers::IFCNT;
+use super::{TmcBus, TmcError};
+
+const SYNC: u8 = 0x05;
+/// the node address of the replies of the driver
+const REPLY_ADDRESS: u8 = 0xFF;
+const WRITE: u8 = 0x80;
+
+/// The driver replies after 8 bit times, plus the send delay, a few bytes at the slowest baud rate of the driver.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// The single-wire UART of the TMC2209, the register datagrams are 8 bytes, with a CRC, read requests are 4 bytes.
+///
+/// Up to 4 drivers share a UART, each driver has its node address, 0-3, set by its MS1 and MS2 pins.  When RX and TX
+/// are joined, e.g. with a resistor, each byte that is sent is received too, set `echo` so the echo is discarded.
+///
+/// The reads wait for the reply by polling the UART, so the registers are only accessed outside the step timing, e.g.
+/// when the driver is configured or the feedback is read.
+pub struct TmcUart<SERIAL> {
+    serial: SERIAL,
+    node_address: u8,
+    echo: bool,
+}
+
+impl<SERIAL: Read + Write + ReadReady> TmcUart<SERIAL> {
+    pub fn new(serial: SERIAL, node_address: u8, echo: bool) -> Self {
+        Self {
+            serial,
+            node_address,
+            echo,
+        }
+    }
+
+    fn send(&mut self, datagram: &[u8]) -> Result<(), TmcError> {
+        // discard anything left over from a previous request, e.g. a late reply
+        let mut discard = [0_u8; 8];
+        while self
+            .serial
+            .read_ready()
+            .map_err(|_e| TmcError::Bus)?
+        {
+            self.serial
+                .read(&mut discard)
+                .map_err(|_e| TmcError::Bus)?;
+        }
+
+        self.serial
+            .write_all(datagram)
+            .map_err(|_e| TmcError::Bus)?;
+        self.serial
+            .flush()
+            .map_err(|_e| TmcError::Bus)?;
+
+        if self.echo {
+            let mut echo = [0_u8; 8];
+            let echo = &mut echo[..datagram.len()];
+            self.receive(echo)?;
+            if echo != datagram {
+                return Err(TmcError::UnexpectedReply);
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<(), TmcError> {
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        let mut filled = 0;
+        while filled < buffer.len() {
+            let ready = self
+                .serial
+                .read_ready()
+                .map_err(|_e| TmcError::Bus)?;
+            if !ready {
+                if Instant::now() >= deadline {
+                    return Err(TmcError::Timeout);
+                }
+                continue;
+            }
+            filled += self
+                .serial
+                .read(&mut buffer[filled..])
+                .map_err(|_e| TmcError::Bus)?;
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, register: u8) -> Result<u32, TmcError> {
+        let mut request = [SYNC, self.node_address, register, 0];
+        request[3] = crc(&request[..3]);
+        self.send(&request)?;
+
+        let mut reply = [0_u8; 8];
+        self.receive(&mut reply)?;
+        if reply[7] != crc(&reply[..7]) {
+            return Err(TmcError::Crc);
+        }
+        if reply[0] != SYNC || reply[1] != REPLY_ADDRESS || reply[2] != register {
+            return Err(TmcError::UnexpectedReply);
+        }
+        Ok(u32::from_be_bytes([reply[3], reply[4], reply[5], reply[6]]))
+    }
+}
+
+impl<SERIAL: Read + Write + ReadReady> TmcBus for TmcUart<SERIAL> {
+    fn read_register(&mut self, register: u8) -> Result<u32, TmcError> {
+        self.read(register)
+    }
+
+    /// The driver doesn't reply to writes, the write counter, IFCNT, is read before and after the write, a write that
+    /// was not received, e.g. because of a CRC error, doesn't increment it.
+    fn write_register(&mut self, register: u8, value: u32) -> Result<(), TmcError> {
+        let count = self.read(IFCNT)? as u8;
+
+        let value = value.to_be_bytes();
+        let mut datagram = [
+            SYNC,
+            self.node_address,
+            register | WRITE,
+            value[0],
+            value[1],
+            value[2],
+            value[3],
+            0,
+        ];
+        datagram[7] = crc(&datagram[..7]);
+        self.send(&datagram)?;
+
+        match self.read(IFCNT)? as u8 == count.wrapping_add(1) {
+            true => Ok(()),
+            false => Err(TmcError::WriteNotAcknowledged),
+        }
+    }
+}
+
+/// The CRC8 of the datagrams, polynomial x^8 + x^2 + x + 1, the bits of each byte are processed LSB first.
+fn crc(bytes: &[u8]) -> u8 {
+    let mut crc = 0_u8;
+    for byte in bytes {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            crc = match (crc >> 7) ^ (byte & 0x01) {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x07,
+            };
+            byte >>= 1;
+        }
+    }
+    crc
+}
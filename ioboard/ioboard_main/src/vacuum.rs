@@ -0,0 +1,251 @@
+//! Closed-loop vacuum control, the pump PWM duty is regulated against the vacuum sensor using a PI loop.
+//!
+//! When the setpoint is reached with less than the minimum duty the pump is stopped, so an idle, sealed system
+//! is quiet and draws no power, it is restarted when the vacuum decays.
+//!
+//! Machines with a valve per nozzle provide a [`NozzleManifold`], each nozzle has its own vacuum sensor which is
+//! used for part-present detection.
+
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_time::{Duration, Ticker};
+use ioboard_net::VACUUM_REQUESTS;
+use ioboard_shared::vacuum::{MAX_NOZZLES, NozzleRequest, VacuumError, VacuumRequest, VacuumResponse, VacuumStatus};
+
+pub trait VacuumSensor {
+    /// Returns the vacuum, in kPa below ambient, or `None` if it could not be read.
+    fn read_vacuum(&mut self) -> Option<f32>;
+}
+
+pub trait PumpOutput {
+    /// `duty` is 0.0-1.0
+    fn set_duty(&mut self, duty: f32);
+}
+
+/// Per-nozzle vacuum valves and sensors, nozzles are addressed by index, `0..nozzle_count()`.
+pub trait NozzleManifold {
+    /// At most [`MAX_NOZZLES`]
+    fn nozzle_count(&self) -> usize;
+    fn set_valve(&mut self, nozzle: usize, open: bool);
+    /// Manifolds without blow-off valves ignore this.
+    fn set_blow_off(&mut self, _nozzle: usize, _on: bool) {}
+    /// Returns the nozzle vacuum, in kPa below ambient, or `None` if it could not be read.
+    fn read_vacuum(&mut self, nozzle: usize) -> Option<f32>;
+}
+
+/// For machines with a single vacuum circuit and no per-nozzle valves.
+pub struct NoManifold;
+
+impl NozzleManifold for NoManifold {
+    fn nozzle_count(&self) -> usize {
+        0
+    }
+
+    fn set_valve(&mut self, _nozzle: usize, _open: bool) {}
+
+    fn read_vacuum(&mut self, _nozzle: usize) -> Option<f32> {
+        None
+    }
+}
+
+/// Default part-present threshold, in kPa.
+const DEFAULT_PART_PRESENT_THRESHOLD: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PiConfig {
+    /// duty per kPa of error
+    pub kp: f32,
+    /// duty per kPa of error, per second
+    pub ki: f32,
+    /// below this duty the pump stalls, so it is stopped instead
+    pub min_duty: f32,
+    pub max_duty: f32,
+    pub interval: Duration,
+}
+
+impl Default for PiConfig {
+    fn default() -> Self {
+        Self {
+            kp: 0.02,
+            ki: 0.05,
+            min_duty: 0.15,
+            max_duty: 1.0,
+            interval: Duration::from_millis(10),
+        }
+    }
+}
+
+pub struct PiController {
+    config: PiConfig,
+    integral: f32,
+}
+
+impl PiController {
+    pub fn new(config: PiConfig) -> Self {
+        Self {
+            config,
+            integral: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+    }
+
+    /// Returns the new duty, 0.0 or `min_duty..=max_duty`.
+    pub fn update(&mut self, setpoint: f32, measured: f32, dt: f32) -> f32 {
+        let config = &self.config;
+        let error = setpoint - measured;
+
+        // clamping the integral prevents wind-up while the pump is saturated or stopped
+        self.integral = (self.integral + config.ki * error * dt).clamp(0.0, config.max_duty);
+
+        let duty = (config.kp * error + self.integral).clamp(0.0, config.max_duty);
+        match duty < config.min_duty {
+            true => 0.0,
+            false => duty,
+        }
+    }
+}
+
+pub struct VacuumController<SENSOR: VacuumSensor, PUMP: PumpOutput, MANIFOLD: NozzleManifold> {
+    sensor: SENSOR,
+    pump: PUMP,
+    manifold: MANIFOLD,
+    pi: PiController,
+    interval: Duration,
+    part_present_thresholds: [f32; MAX_NOZZLES],
+    status: VacuumStatus,
+}
+
+impl<SENSOR: VacuumSensor, PUMP: PumpOutput, MANIFOLD: NozzleManifold> VacuumController<SENSOR, PUMP, MANIFOLD> {
+    /// The pump is stopped and all valves are closed on creation.
+    pub fn new(sensor: SENSOR, mut pump: PUMP, mut manifold: MANIFOLD, config: PiConfig) -> Self {
+        pump.set_duty(0.0);
+
+        let nozzle_count = manifold.nozzle_count().min(MAX_NOZZLES);
+        for nozzle in 0..nozzle_count {
+            manifold.set_valve(nozzle, false);
+            manifold.set_blow_off(nozzle, false);
+        }
+
+        Self {
+            sensor,
+            pump,
+            manifold,
+            interval: config.interval,
+            pi: PiController::new(config),
+            part_present_thresholds: [DEFAULT_PART_PRESENT_THRESHOLD; MAX_NOZZLES],
+            status: VacuumStatus {
+                nozzle_count: nozzle_count as u8,
+                ..VacuumStatus::default()
+            },
+        }
+    }
+
+    fn handle_request(&mut self, request: VacuumRequest) -> VacuumResponse {
+        match request {
+            VacuumRequest::SetSetpoint(setpoint) => {
+                info!("Vacuum setpoint: {} kPa", setpoint);
+                self.status.setpoint = Some(setpoint);
+            }
+            VacuumRequest::Off => {
+                info!("Vacuum off");
+                self.status.setpoint = None;
+                self.pi.reset();
+                self.set_duty(0.0);
+            }
+            VacuumRequest::Nozzle(nozzle, request) => {
+                if nozzle >= self.status.nozzle_count {
+                    return Err(VacuumError::InvalidNozzle(nozzle));
+                }
+                let index = nozzle as usize;
+                match request {
+                    NozzleRequest::OpenValve | NozzleRequest::CloseValve => {
+                        let open = matches!(request, NozzleRequest::OpenValve);
+                        info!("Nozzle {} valve open: {}", nozzle, open);
+                        self.manifold.set_valve(index, open);
+                        self.status.nozzles[index].valve_open = open;
+                    }
+                    NozzleRequest::StartBlowOff | NozzleRequest::StopBlowOff => {
+                        let on = matches!(request, NozzleRequest::StartBlowOff);
+                        info!("Nozzle {} blow off: {}", nozzle, on);
+                        if on {
+                            // blowing against the vacuum would just drain the reservoir
+                            self.manifold.set_valve(index, false);
+                            self.status.nozzles[index].valve_open = false;
+                        }
+                        self.manifold.set_blow_off(index, on);
+                        self.status.nozzles[index].blow_off = on;
+                    }
+                    NozzleRequest::SetPartPresentThreshold(threshold) => {
+                        info!("Nozzle {} part present threshold: {} kPa", nozzle, threshold);
+                        self.part_present_thresholds[index] = threshold;
+                    }
+                }
+                self.update_nozzle(index);
+            }
+            VacuumRequest::Status => {}
+        }
+        Ok(self.status)
+    }
+
+    fn update_nozzle(&mut self, index: usize) {
+        let vacuum = self.manifold.read_vacuum(index);
+        let threshold = self.part_present_thresholds[index];
+
+        let nozzle = &mut self.status.nozzles[index];
+        nozzle.vacuum = vacuum;
+        nozzle.part_present = match (nozzle.valve_open, vacuum) {
+            (true, Some(vacuum)) => Some(vacuum >= threshold),
+            _ => None,
+        };
+    }
+
+    fn set_duty(&mut self, duty: f32) {
+        self.pump.set_duty(duty);
+        self.status.duty = duty;
+    }
+
+    fn regulate(&mut self) {
+        self.status.vacuum = self.sensor.read_vacuum();
+
+        for index in 0..self.status.nozzle_count as usize {
+            self.update_nozzle(index);
+        }
+
+        let Some(setpoint) = self.status.setpoint else {
+            return;
+        };
+
+        let duty = match self.status.vacuum {
+            Some(vacuum) => self
+                .pi
+                .update(setpoint, vacuum, self.interval.as_micros() as f32 / 1_000_000.0),
+            None => {
+                // fail safe, without feedback the pump could run indefinitely
+                warn!("Vacuum sensor unavailable, stopping pump");
+                self.pi.reset();
+                0.0
+            }
+        };
+        self.set_duty(duty);
+    }
+
+    /// Handle requests from the vacuum endpoint, and regulate the vacuum.
+    pub async fn run(mut self) -> ! {
+        let mut ticker = Ticker::every(self.interval);
+        loop {
+            match select(VACUUM_REQUESTS.receive(), ticker.next()).await {
+                Either::First(request) => {
+                    let response = self.handle_request(request);
+                    if let Err(error) = &response {
+                        warn!("Vacuum request failed. request: {}, error: {}", request, error);
+                    }
+                    VACUUM_REQUESTS.respond(response).await;
+                }
+                Either::Second(_) => self.regulate(),
+            }
+        }
+    }
+}
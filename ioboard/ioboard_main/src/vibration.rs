@@ -0,0 +1,179 @@
+//! Accumulates accelerometer samples, producing periodic [`VibrationReport`]s and acting as the
+//! [`ResponseSensor`] for the resonance sweep.
+
+use alloc::collections::VecDeque;
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use ioboard_shared::vibration::{VIBRATION_SPECTRUM_BINS, VibrationReport};
+use libm::{cosf, sinf, sqrtf};
+
+use crate::input_shaping::ResponseSensor;
+
+/// Maximum number of samples used for the spectrum, older samples are discarded.
+const SPECTRUM_WINDOW: usize = 256;
+
+/// Smoothing factor used to track the static (gravity) component of the acceleration magnitude.
+const BASELINE_ALPHA: f32 = 0.01;
+
+/// The spectrum covers `SPECTRUM_START` to `SPECTRUM_START + SPECTRUM_STEP * (VIBRATION_SPECTRUM_BINS - 1)` Hz,
+/// which covers typical gantry resonances.
+const SPECTRUM_START: f32 = 10.0;
+const SPECTRUM_STEP: f32 = 10.0;
+
+pub static VIBRATION_MONITOR: VibrationMonitor = VibrationMonitor::new();
+
+pub struct VibrationMonitor {
+    state: Mutex<CriticalSectionRawMutex, RefCell<State>>,
+}
+
+struct State {
+    sum: [f32; 3],
+    sum_of_squares: [f32; 3],
+    count: u32,
+
+    /// acceleration magnitude samples, most recent last
+    window: VecDeque<f32>,
+    baseline: Option<f32>,
+
+    response: Option<(f32, u32)>,
+}
+
+impl VibrationMonitor {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(State {
+                sum: [0.0; 3],
+                sum_of_squares: [0.0; 3],
+                count: 0,
+                window: VecDeque::new(),
+                baseline: None,
+                response: None,
+            })),
+        }
+    }
+
+    /// Record an (x, y, z) acceleration sample, in g.
+    pub fn record(&self, sample: [f32; 3]) {
+        self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+
+            for (axis, value) in sample.iter().enumerate() {
+                state.sum[axis] += value;
+                state.sum_of_squares[axis] += value * value;
+            }
+            state.count += 1;
+
+            let magnitude = sqrtf(
+                sample
+                    .iter()
+                    .map(|value| value * value)
+                    .sum(),
+            );
+
+            if state.window.len() >= SPECTRUM_WINDOW {
+                state.window.pop_front();
+            }
+            state.window.push_back(magnitude);
+
+            let baseline = match state.baseline {
+                Some(baseline) => baseline + (magnitude - baseline) * BASELINE_ALPHA,
+                None => magnitude,
+            };
+            state.baseline = Some(baseline);
+
+            if let Some((sum_of_squares, count)) = &mut state.response {
+                let deviation = magnitude - baseline;
+                *sum_of_squares += deviation * deviation;
+                *count += 1;
+            }
+        });
+    }
+
+    /// Summarize the samples recorded since the previous report, `sample_rate` is in Hz.
+    pub fn report(&self, sample_rate: f32) -> VibrationReport {
+        // the spectrum is calculated outside the critical section, only the samples are copied
+        let (rms, window) = self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+
+            let mut rms = [0.0; 3];
+            if state.count > 0 {
+                let count = state.count as f32;
+                for (axis, rms) in rms.iter_mut().enumerate() {
+                    let mean = state.sum[axis] / count;
+                    // variance = E[x^2] - E[x]^2, i.e. the static component is removed
+                    let variance = state.sum_of_squares[axis] / count - mean * mean;
+                    *rms = sqrtf(variance.max(0.0));
+                }
+            }
+
+            state.sum = [0.0; 3];
+            state.sum_of_squares = [0.0; 3];
+            state.count = 0;
+
+            (rms, state.window.clone())
+        });
+
+        let mut spectrum = [0.0; VIBRATION_SPECTRUM_BINS];
+        for (bin, amplitude) in spectrum.iter_mut().enumerate() {
+            let frequency = SPECTRUM_START + SPECTRUM_STEP * bin as f32;
+            *amplitude = goertzel_amplitude(&window, frequency, sample_rate);
+        }
+
+        VibrationReport {
+            sample_rate,
+            rms,
+            spectrum_start: SPECTRUM_START,
+            spectrum_step: SPECTRUM_STEP,
+            spectrum,
+        }
+    }
+}
+
+impl Default for VibrationMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResponseSensor for &VibrationMonitor {
+    fn begin(&mut self) {
+        self.state.lock(|state| {
+            state.borrow_mut().response = Some((0.0, 0));
+        });
+    }
+
+    fn end(&mut self) -> f32 {
+        self.state
+            .lock(|state| match state.borrow_mut().response.take() {
+                Some((sum_of_squares, count)) if count > 0 => sqrtf(sum_of_squares / count as f32),
+                _ => 0.0,
+            })
+    }
+}
+
+/// Amplitude of a single frequency component of `samples`, with the mean removed.
+///
+/// Uses the Goertzel algorithm, which is cheaper than an FFT when only a few frequencies are required.
+fn goertzel_amplitude(samples: &VecDeque<f32>, frequency: f32, sample_rate: f32) -> f32 {
+    if samples.is_empty() || frequency >= sample_rate / 2.0 {
+        return 0.0;
+    }
+
+    let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+    let omega = 2.0 * core::f32::consts::PI * frequency / sample_rate;
+    let coefficient = 2.0 * cosf(omega);
+
+    let (mut s1, mut s2) = (0.0_f32, 0.0_f32);
+    for sample in samples {
+        let s0 = (sample - mean) + coefficient * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+
+    let real = s1 - s2 * cosf(omega);
+    let imaginary = s2 * sinf(omega);
+
+    2.0 * sqrtf(real * real + imaginary * imaginary) / samples.len() as f32
+}
@@ -4,15 +4,16 @@ extern crate alloc;
 use alloc::boxed::Box;
 use core::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 use core::pin::pin;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
 use embassy_executor::Spawner;
 use embassy_net::driver::Driver;
 use embassy_net::tcp::client::{TcpClient, TcpClientState};
 use embassy_net::udp::{PacketMetadata, UdpSocket};
 use embassy_net::{IpEndpoint, Ipv4Address, Runner, StackResources};
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
-use embassy_sync::channel::{Channel, Receiver, Sender};
-use embassy_time::{Duration, Ticker, Timer, WithTimeout};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex as EmbassyCriticalSectionRawMutex;
+use embassy_sync::channel::{Channel, Receiver};
+use embassy_time::{Duration, Instant, Ticker, Timer, WithTimeout};
 use embedded_io_async::Write;
 use embedded_nal_async::TcpConnect;
 use ergot::exports::bbqueue::traits::coordination::cas::AtomicCoord;
@@ -22,14 +23,37 @@ use ergot::interface_manager::transports::embassy_net_udp::{
 use ergot::logging::log_v0_4::LogSink;
 use ergot::toolkits::embassy_net_v0_7 as kit;
 use ergot::well_known::{DeviceInfo, ErgotPingEndpoint};
-use ergot::{Address, topic};
+use ergot::{Address, endpoint, topic};
 use ergot::interface_manager::InterfaceState;
 use ergot::prelude::{EdgeFrameProcessor, EDGE_NODE_ID};
-use ioboard_shared::commands::IoBoardCommand;
-use ioboard_shared::yeet::Yeet;
+use ioboard_shared::analog::{AnalogReading, AnalogRequest, AnalogResponse};
+use ioboard_shared::batch::{BatchedCommand, CommandBatch};
+use ioboard_shared::commands::{IdempotencyKey, IoBoardCommand, Sequenced};
+use ioboard_shared::dispenser::{DispenserRequest, DispenserResponse};
+use ioboard_shared::driver::{DriverRequest, DriverResponse};
+use ioboard_shared::estop::{EStop, EStopSource, EStopTopic};
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::expansion::{ExpansionRequest, ExpansionResponse};
+use ioboard_shared::force::ForceTrace;
+use ioboard_shared::homing::{HomingRequest, HomingResponse};
+use ioboard_shared::load::AxisLoad;
+use ioboard_shared::load_cell::LoadCellSample;
+use ioboard_shared::motion::{
+    FlushQueueRequest, FlushQueueResponse, MotionCommandRequest, MotionCommandResponse, MotionSetpoint, PositionReport,
+};
+use ioboard_shared::power::{PowerRequest, PowerResponse};
+use ioboard_shared::probe::{ProbeRequest, ProbeResponse};
+use ioboard_shared::resonance::{ResonanceSweepRequest, ResonanceSweepResponse};
+use ioboard_shared::safe_z::{SafeZRequest, SafeZResponse};
+use ioboard_shared::safety::SafetyStatus;
+use ioboard_shared::self_test::SelfTestStatus;
+use ioboard_shared::thermal::ThermalReading;
+use ioboard_shared::vacuum::{VacuumRequest, VacuumResponse};
+use ioboard_shared::vibration::VibrationReport;
 use ioboard_trace::tracepin;
 use log::{error, info};
 use mutex::raw_impls::cs::CriticalSectionRawMutex;
+use retry::{Backoff, BackoffConfig, retry_with};
 use static_cell::{ConstStaticCell, StaticCell};
 use defmt::unwrap;
 
@@ -104,6 +128,15 @@ impl<CLIENT: TcpConnect> IoConnection<CLIENT> {
     }
 }
 
+/// The DHCP server is polled quickly at first, the address is usually allocated within a second of the link coming up.
+const DHCP_BACKOFF: BackoffConfig = BackoffConfig {
+    initial: core::time::Duration::from_millis(100),
+    max: core::time::Duration::from_secs(2),
+    multiplier: 2,
+    jitter: 0.5,
+    max_elapsed: None,
+};
+
 pub fn init<'d, D: Driver>(driver: D, random_seed: u64, spawner: Spawner) -> Runner<'d, D> {
     let config = embassy_net::Config::dhcpv4(Default::default());
     //let config = embassy_net::Config::ipv4_static(embassy_net::StaticConfigV4 {
@@ -118,30 +151,44 @@ pub fn init<'d, D: Driver>(driver: D, random_seed: u64, spawner: Spawner) -> Run
 
     defmt::info!("Hardware address: {}", stack.hardware_address());
 
-    spawner
-        .spawn(unwrap!(networking_task(stack, spawner.clone(), SCRATCH_BUF.take())));
+    spawner.spawn(unwrap!(networking_task(
+        stack,
+        spawner.clone(),
+        SCRATCH_BUF.take(),
+        random_seed as u32
+    )));
 
     runner
 }
 
 #[embassy_executor::task]
-async fn networking_task(stack: embassy_net::Stack<'static>, spawner: Spawner, scratch_buf: &'static mut [u8]) -> ! {
+async fn networking_task(
+    stack: embassy_net::Stack<'static>,
+    spawner: Spawner,
+    scratch_buf: &'static mut [u8],
+    random_seed: u32,
+) -> ! {
     defmt::info!("Network task initialized");
 
     // Ensure DHCP configuration is up before trying connect
+    let mut backoff = Backoff::new(DHCP_BACKOFF, random_seed);
     let mut attempts: u32 = 0;
-    let config = loop {
-        if let Some(config) = stack.config_v4() {
-            break config;
-        }
-
-        if attempts % 10 == 0 {
-            defmt::info!("Waiting for DHCP address allocation");
-        }
-
-        attempts = attempts.wrapping_add(1);
-        Timer::after(Duration::from_millis(100)).await;
-    };
+    let started_at = Instant::now();
+    let config = retry_with(
+        &mut backoff,
+        || {
+            if attempts % 10 == 0 {
+                defmt::info!("Waiting for DHCP address allocation");
+            }
+            attempts = attempts.wrapping_add(1);
+            core::future::ready(stack.config_v4().ok_or(()))
+        },
+        |delay| Timer::after(Duration::from_micros(delay.as_micros() as u64)),
+        || core::time::Duration::from_micros(started_at.elapsed().as_micros()),
+    )
+    .await
+    // the backoff has no max elapsed time
+    .unwrap();
 
     defmt::info!(
         "IP address: {}, gateway: {}, dns: {}",
@@ -192,11 +239,25 @@ async fn networking_task(stack: embassy_net::Stack<'static>, spawner: Spawner, s
     spawner.spawn(unwrap!(pinger()));
     spawner.spawn(unwrap!(discovery_responder()));
 
-    let yeet_command_sender = YEET_COMMAND_CHANNEL.sender();
-    let yeet_command_receiver = YEET_COMMAND_CHANNEL.receiver();
-
-    spawner.spawn(unwrap!(yeeter(yeet_command_receiver)));
-    spawner.spawn(unwrap!(command_listener(yeet_command_sender)));
+    spawner.spawn(unwrap!(command_listener()));
+    spawner.spawn(unwrap!(batch_listener()));
+    spawner.spawn(unwrap!(event_publisher(EVENT_CHANNEL.receiver())));
+    spawner.spawn(unwrap!(power_server()));
+    spawner.spawn(unwrap!(vacuum_server()));
+    spawner.spawn(unwrap!(dispenser_server()));
+    spawner.spawn(unwrap!(safe_z_server()));
+    spawner.spawn(unwrap!(probe_server()));
+    spawner.spawn(unwrap!(expansion_server()));
+    spawner.spawn(unwrap!(analog_server()));
+    spawner.spawn(unwrap!(setpoint_listener()));
+    spawner.spawn(unwrap!(flush_queue_server()));
+    spawner.spawn(unwrap!(motion_command_server()));
+    spawner.spawn(unwrap!(homing_server()));
+    spawner.spawn(unwrap!(driver_server()));
+    spawner.spawn(unwrap!(resonance_sweep_server()));
+    spawner.spawn(unwrap!(position_listener()));
+    spawner.spawn(unwrap!(latency_probe_server()));
+    spawner.spawn(unwrap!(estop_listener()));
 
     LOGSINK.register_static(log::LevelFilter::Info);
 
@@ -287,124 +348,914 @@ async fn discovery_responder() {
         .await;
 }
 
-// TODO replace with the the load-cell data type and topic
-topic!(YeetTopic, Yeet, "topic/yeet");
+topic!(CommandTopic, IoBoardCommand, "topic/ioboard/command");
 
-#[derive(Debug, Clone, Copy)]
-enum YeetCommand {
-    Begin,
-    End,
+#[embassy_executor::task]
+async fn command_listener() {
+    let subber = STACK
+        .topics()
+        .bounded_receiver::<CommandTopic, 32>(None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    defmt::info!("Command listener started");
+    loop {
+        tracepin::on(3);
+        let msg = hdl.recv().await;
+        tracepin::off(3);
+        handle_command(msg.t);
+    }
 }
 
-static YEET_COMMAND_CHANNEL: Channel<ThreadModeRawMutex, YeetCommand, 1> = Channel::new();
+fn handle_command(command: IoBoardCommand) {
+    match command {
+        IoBoardCommand::Test(counter) => {
+            defmt::info!("Test command received: {}", counter);
+        }
+        IoBoardCommand::BeginLoadCellStream => {
+            defmt::info!("Load cell stream begun");
+            LOAD_CELL_STREAM.store(true, Ordering::Relaxed);
+        }
+        IoBoardCommand::EndLoadCellStream => {
+            defmt::info!("Load cell stream ended");
+            LOAD_CELL_STREAM.store(false, Ordering::Relaxed);
+        }
+    }
+}
 
-type YeetCommandSender = Sender<'static, ThreadModeRawMutex, YeetCommand, 1>;
-type YeetCommandReceiver = Receiver<'static, ThreadModeRawMutex, YeetCommand, 1>;
+topic!(BatchTopic, CommandBatch, "topic/ioboard/batch");
 
+/// Commands batched by the server, handled in order, the same as commands and setpoints sent separately.
 #[embassy_executor::task]
-async fn yeeter(receiver: YeetCommandReceiver) {
-    let mut counter = 0;
-    let mut error_counter = 0;
+async fn batch_listener() {
+    let subber = STACK
+        .topics()
+        .bounded_receiver::<BatchTopic, 16>(None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    defmt::info!("Batch listener started");
+    loop {
+        let msg = hdl.recv().await;
+        for command in msg.t.iter() {
+            match command {
+                BatchedCommand::Setpoint(setpoint) => queue_setpoint(*setpoint),
+                BatchedCommand::Command(command) => handle_command(*command),
+            }
+        }
+    }
+}
+
+topic!(EventTopic, IoBoardEvent, "topic/ioboard/event");
+
+const EVENT_QUEUE_SIZE: usize = 8;
 
-    defmt::info!("Yeeter started");
+/// Events can be queued from any executor, including the high-priority motion executor.
+static EVENT_CHANNEL: Channel<EmbassyCriticalSectionRawMutex, IoBoardEvent, EVENT_QUEUE_SIZE> = Channel::new();
 
-    // FIXME remove this arbitrary startup delay
-    Timer::after(Duration::from_secs(8)).await;
+type EventReceiver = Receiver<'static, EmbassyCriticalSectionRawMutex, IoBoardEvent, EVENT_QUEUE_SIZE>;
 
-    // Using a target frequency of 320Hz, the same as the HX717 load-cell ADC sensor
-    const TARGET_HZ: u16 = 320;
-    let mut cycle_ticker: Option<Ticker> = None;
-    let mut report_ticker = Ticker::every(Duration::from_secs(10));
+/// Queue an event for publishing, does not block.
+///
+/// Returns the event if the queue is full.
+pub fn publish_event(event: IoBoardEvent) -> Result<(), IoBoardEvent> {
+    EVENT_CHANNEL
+        .try_send(event)
+        .map_err(|embassy_sync::channel::TrySendError::Full(event)| event)
+}
 
+#[embassy_executor::task]
+async fn event_publisher(receiver: EventReceiver) {
+    defmt::info!("Event publisher started");
     loop {
-        let cycle_ticker_fut = async {
-            if let Some(ticker) = &mut cycle_ticker {
-                ticker.next().await
-            } else {
-                core::future::pending().await
-            }
-        };
+        let event = receiver.receive().await;
+        if STACK
+            .topics()
+            .broadcast::<EventTopic>(&event, None)
+            .is_err()
+        {
+            defmt::warn!("Unable to publish event: {}", event);
+        }
+    }
+}
 
-        match embassy_futures::select::select3(report_ticker.next(), receiver.receive(), cycle_ticker_fut).await {
-            embassy_futures::select::Either3::First(_) => {
-                info!("Yeet report, counter: {}, errors: {}", counter, error_counter);
-            }
-            embassy_futures::select::Either3::Second(cmd) => match cmd {
-                YeetCommand::Begin => {
-                    cycle_ticker = Some(Ticker::every(Duration::from_micros(1_000_000_u64 / TARGET_HZ as u64)));
+/// The number of recent requests remembered by each endpoint, retries of older requests are executed again.
+const DUPLICATE_WINDOW_SIZE: usize = 8;
+
+/// Remembers the responses to the most recent requests so that retried requests are not executed twice.
+pub struct DuplicateFilter<RESP, const N: usize> {
+    entries: [Option<(IdempotencyKey, RESP)>; N],
+    next: usize,
+}
+
+impl<RESP: Copy, const N: usize> DuplicateFilter<RESP, N> {
+    pub const fn new() -> Self {
+        Self {
+            entries: [None; N],
+            next: 0,
+        }
+    }
+
+    /// Returns the original response if the request was already executed.
+    pub fn duplicate(&self, key: &IdempotencyKey) -> Option<RESP> {
+        self.entries
+            .iter()
+            .flatten()
+            .find(|(entry_key, _)| entry_key == key)
+            .map(|(_, response)| *response)
+    }
+
+    pub fn record(&mut self, key: IdempotencyKey, response: RESP) {
+        self.entries[self.next] = Some((key, response));
+        self.next = (self.next + 1) % N;
+    }
+}
+
+/// Forwards endpoint requests to the task that handles them and the responses back, one request at a time.
+pub struct RequestChannel<REQ, RESP> {
+    requests: Channel<EmbassyCriticalSectionRawMutex, REQ, 1>,
+    responses: Channel<EmbassyCriticalSectionRawMutex, RESP, 1>,
+}
+
+impl<REQ, RESP> RequestChannel<REQ, RESP> {
+    pub const fn new() -> Self {
+        Self {
+            requests: Channel::new(),
+            responses: Channel::new(),
+        }
+    }
+
+    async fn request(&self, request: REQ) -> RESP {
+        self.requests.send(request).await;
+        self.responses.receive().await
+    }
+
+    /// Wait for the next request, [`RequestChannel::respond`] must be called for each request.
+    pub async fn receive(&self) -> REQ {
+        self.requests.receive().await
+    }
+
+    /// The pending request, if any, for tasks that can't wait for requests, see [`RequestChannel::receive`].
+    pub fn try_receive(&self) -> Option<REQ> {
+        self.requests.try_receive().ok()
+    }
+
+    pub async fn respond(&self, response: RESP) {
+        self.responses.send(response).await
+    }
+}
+
+endpoint!(
+    PowerEndpoint,
+    Sequenced<PowerRequest>,
+    PowerResponse,
+    "topic/ioboard/power"
+);
+
+/// Power requests received via the [`PowerEndpoint`], handled by the power sequencer.
+pub static POWER_REQUESTS: RequestChannel<PowerRequest, PowerResponse> = RequestChannel::new();
+
+#[embassy_executor::task]
+async fn power_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<PowerEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<PowerResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Power server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<PowerRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate power request, not executed: {}", request);
+                    return response;
                 }
-                YeetCommand::End => {
-                    cycle_ticker = None;
+                defmt::info!("Power request: {}", request);
+                let response = POWER_REQUESTS
+                    .request(request.request)
+                    .await;
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+endpoint!(
+    VacuumEndpoint,
+    Sequenced<VacuumRequest>,
+    VacuumResponse,
+    "topic/ioboard/vacuum"
+);
+
+/// Vacuum requests received via the [`VacuumEndpoint`], handled by the vacuum controller.
+pub static VACUUM_REQUESTS: RequestChannel<VacuumRequest, VacuumResponse> = RequestChannel::new();
+
+#[embassy_executor::task]
+async fn vacuum_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<VacuumEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<VacuumResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Vacuum server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<VacuumRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate vacuum request, not executed: {}", request);
+                    return response;
                 }
-            },
-            embassy_futures::select::Either3::Third(_) => {
-                let Some(ref mut cycle_ticker) = cycle_ticker else {
-                    continue;
-                };
-
-                enum Action {
-                    Retry,
-                    Wait,
+                defmt::info!("Vacuum request: {}", request);
+                let response = VACUUM_REQUESTS
+                    .request(request.request)
+                    .await;
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+endpoint!(
+    DispenserEndpoint,
+    Sequenced<DispenserRequest>,
+    DispenserResponse,
+    "topic/ioboard/dispenser"
+);
+
+/// Dispenser requests received via the [`DispenserEndpoint`], handled by the dispenser controller.
+pub static DISPENSER_REQUESTS: RequestChannel<DispenserRequest, DispenserResponse> = RequestChannel::new();
+
+#[embassy_executor::task]
+async fn dispenser_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<DispenserEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<DispenserResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Dispenser server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<DispenserRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate dispenser request, not executed: {}", request);
+                    return response;
                 }
+                defmt::info!("Dispenser request: {}", request);
+                let response = DISPENSER_REQUESTS
+                    .request(request.request)
+                    .await;
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+endpoint!(
+    SafeZEndpoint,
+    Sequenced<SafeZRequest>,
+    SafeZResponse,
+    "topic/ioboard/safe-z"
+);
+
+/// Safe-Z requests received via the [`SafeZEndpoint`], handled by the safe-Z guard.
+pub static SAFE_Z_REQUESTS: RequestChannel<SafeZRequest, SafeZResponse> = RequestChannel::new();
+
+#[embassy_executor::task]
+async fn safe_z_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<SafeZEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<SafeZResponse, DUPLICATE_WINDOW_SIZE>::new();
 
-                tracepin::on(1);
-                let action = match STACK
-                    .topics()
-                    .broadcast::<YeetTopic>(&counter, None)
-                {
-                    Ok(_) => {
-                        counter += 1;
-                        Action::Wait
-                    }
-                    Err(_e) => {
-                        error_counter += 1;
-                        // TODO look at the error and act appropriately instead of just retrying
-                        Action::Retry
-                    }
-                };
-                tracepin::off(1);
-
-                if matches!(action, Action::Retry) {
-                    Timer::after(Duration::from_millis(100)).await;
-                    cycle_ticker.reset();
-                    continue;
+    defmt::info!("Safe-Z server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<SafeZRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate safe-Z request, not executed: {}", request);
+                    return response;
                 }
-            }
-        }
+                defmt::info!("Safe-Z request: {}", request);
+                let response = SAFE_Z_REQUESTS
+                    .request(request.request)
+                    .await;
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
     }
 }
 
-topic!(CommandTopic, IoBoardCommand, "topic/ioboard/command");
+endpoint!(
+    ProbeEndpoint,
+    Sequenced<ProbeRequest>,
+    ProbeResponse,
+    "topic/ioboard/probe"
+);
+
+/// Probe requests received via the [`ProbeEndpoint`], handled by the probe.
+pub static PROBE_REQUESTS: RequestChannel<ProbeRequest, ProbeResponse> = RequestChannel::new();
 
 #[embassy_executor::task]
-async fn command_listener(yeet_command_sender: YeetCommandSender) {
+async fn probe_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<ProbeEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<ProbeResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Probe server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<ProbeRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate probe request, not executed: {}", request);
+                    return response;
+                }
+                defmt::info!("Probe request: {}", request);
+                let response = PROBE_REQUESTS
+                    .request(request.request)
+                    .await;
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+endpoint!(
+    ExpansionEndpoint,
+    Sequenced<ExpansionRequest>,
+    ExpansionResponse,
+    "topic/ioboard/expansion"
+);
+
+/// Expansion requests received via the [`ExpansionEndpoint`], handled by the expansion device registry.
+pub static EXPANSION_REQUESTS: RequestChannel<ExpansionRequest, ExpansionResponse> = RequestChannel::new();
+
+#[embassy_executor::task]
+async fn expansion_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<ExpansionEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<ExpansionResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Expansion server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<ExpansionRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate expansion request, not executed: {}", request);
+                    return response;
+                }
+                defmt::info!("Expansion request: {}", request);
+                let response = EXPANSION_REQUESTS
+                    .request(request.request)
+                    .await;
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+endpoint!(
+    AnalogEndpoint,
+    Sequenced<AnalogRequest>,
+    AnalogResponse,
+    "topic/ioboard/analog/config"
+);
+
+/// Analog requests received via the [`AnalogEndpoint`], handled by the analog channels, see `ioboard_main::analog`.
+pub static ANALOG_REQUESTS: RequestChannel<AnalogRequest, AnalogResponse> = RequestChannel::new();
+
+#[embassy_executor::task]
+async fn analog_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<AnalogEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<AnalogResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Analog server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<AnalogRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate analog request, not executed: {}", request);
+                    return response;
+                }
+                defmt::info!("Analog request: {}", request);
+                let response = ANALOG_REQUESTS
+                    .request(request.request)
+                    .await;
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+topic!(SetpointTopic, MotionSetpoint, "topic/ioboard/motion/setpoint");
+
+const SETPOINT_QUEUE_SIZE: usize = 16;
+
+/// Setpoints for server-planned motion, consumed by the motion executor.
+pub static MOTION_SETPOINTS: Channel<EmbassyCriticalSectionRawMutex, MotionSetpoint, SETPOINT_QUEUE_SIZE> =
+    Channel::new();
+
+#[embassy_executor::task]
+async fn setpoint_listener() {
     let subber = STACK
         .topics()
-        .bounded_receiver::<CommandTopic, 32>(None);
+        .bounded_receiver::<SetpointTopic, 16>(None);
     let subber = pin!(subber);
     let mut hdl = subber.subscribe();
 
-    defmt::info!("Command listener started");
+    defmt::info!("Setpoint listener started");
     loop {
-        tracepin::on(3);
         let msg = hdl.recv().await;
-        tracepin::off(3);
-        match msg.t {
-            IoBoardCommand::Test(counter) => {
-                defmt::info!("Test command received: {}", counter);
-            }
-            IoBoardCommand::BeginYeetTest => {
-                yeet_command_sender
-                    .send(YeetCommand::Begin)
+        queue_setpoint(msg.t);
+    }
+}
+
+fn queue_setpoint(setpoint: MotionSetpoint) {
+    // boards that plan their own motion never consume setpoints, so never block the listener
+    if MOTION_SETPOINTS
+        .try_send(setpoint)
+        .is_err()
+    {
+        defmt::warn!("Setpoint queue full, dropped setpoint: {}", setpoint.sequence);
+    }
+}
+
+endpoint!(
+    FlushQueueEndpoint,
+    Sequenced<FlushQueueRequest>,
+    FlushQueueResponse,
+    "topic/ioboard/motion/flush"
+);
+
+/// Flush requests received via the [`FlushQueueEndpoint`], handled by the motion executor, which discards the
+/// setpoints queued in [`MOTION_SETPOINTS`].
+pub static FLUSH_QUEUE_REQUESTS: RequestChannel<FlushQueueRequest, FlushQueueResponse> = RequestChannel::new();
+
+#[embassy_executor::task]
+async fn flush_queue_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<FlushQueueEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<FlushQueueResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Flush queue server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<FlushQueueRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate flush queue request, not executed: {}", request);
+                    return response;
+                }
+                defmt::info!("Flush queue request: {}", request);
+                let response = FLUSH_QUEUE_REQUESTS
+                    .request(request.request)
                     .await;
-            }
-            IoBoardCommand::EndYeetTest => {
-                yeet_command_sender
-                    .send(YeetCommand::End)
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+endpoint!(
+    MotionCommandEndpoint,
+    Sequenced<MotionCommandRequest>,
+    MotionCommandResponse,
+    "topic/ioboard/motion/command"
+);
+
+/// Motion commands received via the [`MotionCommandEndpoint`], handled by the trajectory loop of a board that plans
+/// its own trajectories, a duplicate move is not queued twice.
+pub static MOTION_COMMANDS: RequestChannel<MotionCommandRequest, MotionCommandResponse> = RequestChannel::new();
+
+#[embassy_executor::task]
+async fn motion_command_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<MotionCommandEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<MotionCommandResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Motion command server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<MotionCommandRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate motion command, not executed: {}", request);
+                    return response;
+                }
+                defmt::info!("Motion command: {}", request);
+                let response = MOTION_COMMANDS
+                    .request(request.request)
                     .await;
-            }
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+endpoint!(
+    HomingEndpoint,
+    Sequenced<HomingRequest>,
+    HomingResponse,
+    "topic/ioboard/homing"
+);
+
+/// Homing requests received via the [`HomingEndpoint`], handled by the motion task of the axis, the request is answered
+/// once the axis is homed, a duplicate request doesn't home the axis twice.
+pub static HOMING_REQUESTS: RequestChannel<HomingRequest, HomingResponse> = RequestChannel::new();
+
+#[embassy_executor::task]
+async fn homing_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<HomingEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<HomingResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Homing server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<HomingRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate homing request, not executed: {}", request);
+                    return response;
+                }
+                defmt::info!("Homing request: {}", request);
+                let response = HOMING_REQUESTS
+                    .request(request.request)
+                    .await;
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+endpoint!(
+    DriverEndpoint,
+    Sequenced<DriverRequest>,
+    DriverResponse,
+    "topic/ioboard/driver"
+);
+
+/// Driver requests received via the [`DriverEndpoint`], handled by the motion task of the axis while the axis is at
+/// rest.
+pub static DRIVER_REQUESTS: RequestChannel<DriverRequest, DriverResponse> = RequestChannel::new();
+
+#[embassy_executor::task]
+async fn driver_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<DriverEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<DriverResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Driver server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<DriverRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate driver request, not executed: {}", request);
+                    return response;
+                }
+                defmt::info!("Driver request: {}", request);
+                let response = DRIVER_REQUESTS
+                    .request(request.request)
+                    .await;
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+endpoint!(
+    ResonanceSweepEndpoint,
+    Sequenced<ResonanceSweepRequest>,
+    ResonanceSweepResponse,
+    "topic/ioboard/resonance-sweep"
+);
+
+/// Resonance sweeps received via the [`ResonanceSweepEndpoint`], handled by the motion task of the axis while the axis
+/// is at rest.
+pub static RESONANCE_SWEEP_REQUESTS: RequestChannel<ResonanceSweepRequest, ResonanceSweepResponse> =
+    RequestChannel::new();
+
+#[embassy_executor::task]
+async fn resonance_sweep_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<ResonanceSweepEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    let mut duplicates = DuplicateFilter::<ResonanceSweepResponse, DUPLICATE_WINDOW_SIZE>::new();
+
+    defmt::info!("Resonance sweep server started");
+    loop {
+        let _ = hdl
+            .serve(async |request: &Sequenced<ResonanceSweepRequest>| {
+                if let Some(response) = duplicates.duplicate(&request.key) {
+                    defmt::warn!("Duplicate resonance sweep request, not executed: {}", request);
+                    return response;
+                }
+                defmt::info!("Resonance sweep request: {}", request);
+                let response = RESONANCE_SWEEP_REQUESTS
+                    .request(request.request)
+                    .await;
+                duplicates.record(request.key, response);
+                response
+            })
+            .await;
+    }
+}
+
+const POSITION_QUEUE_SIZE: usize = 8;
+
+/// Position reports of every io board, consumed by the safe-Z guard, which needs the position of the Z axis even
+/// when it is on another io board.
+pub static POSITION_REPORTS: Channel<EmbassyCriticalSectionRawMutex, PositionReport, POSITION_QUEUE_SIZE> =
+    Channel::new();
+
+#[embassy_executor::task]
+async fn position_listener() {
+    let subber = STACK
+        .topics()
+        .bounded_receiver::<PositionTopic, 8>(None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    defmt::info!("Position listener started");
+    loop {
+        let msg = hdl.recv().await;
+        // only the latest position matters, a dropped report is superseded by the next one
+        let _ = POSITION_REPORTS.try_send(msg.t);
+    }
+}
+
+// Same executor as the command listener, so that the probe round-trip is representative of the command path.
+endpoint!(LatencyProbeEndpoint, u32, u32, "topic/ioboard/latency-probe");
+
+/// Echoes latency probes from the server.
+#[embassy_executor::task]
+async fn latency_probe_server() {
+    let server = STACK
+        .endpoints()
+        .bounded_server::<LatencyProbeEndpoint, 2>(None);
+    let server = pin!(server);
+    let mut hdl = server.attach();
+
+    defmt::info!("Latency probe server started");
+    loop {
+        let _ = hdl
+            .serve(async |probe: &u32| *probe)
+            .await;
+    }
+}
+
+topic!(PositionTopic, PositionReport, "topic/ioboard/motion/position");
+
+/// Publish the position of an axis, reports are periodic so failures are only logged.
+pub fn publish_position(report: &PositionReport) {
+    if STACK
+        .topics()
+        .broadcast::<PositionTopic>(report, None)
+        .is_err()
+    {
+        defmt::warn!("Unable to publish position report");
+    }
+}
+
+topic!(VibrationTopic, VibrationReport, "topic/ioboard/vibration");
+
+/// Publish a vibration report, reports are periodic so failures are only logged.
+pub fn publish_vibration(report: &VibrationReport) {
+    if STACK
+        .topics()
+        .broadcast::<VibrationTopic>(report, None)
+        .is_err()
+    {
+        defmt::warn!("Unable to publish vibration report");
+    }
+}
+
+topic!(ThermalTopic, ThermalReading, "topic/ioboard/thermal");
+
+/// Publish a temperature reading, readings are periodic so failures are only logged.
+pub fn publish_thermal(reading: &ThermalReading) {
+    if STACK
+        .topics()
+        .broadcast::<ThermalTopic>(reading, None)
+        .is_err()
+    {
+        defmt::warn!("Unable to publish thermal reading");
+    }
+}
+
+topic!(LoadTopic, AxisLoad, "topic/ioboard/load");
+
+/// Publish the load of an axis, the load is periodic so failures are only logged.
+pub fn publish_load(load: &AxisLoad) {
+    if STACK
+        .topics()
+        .broadcast::<LoadTopic>(load, None)
+        .is_err()
+    {
+        defmt::warn!("Unable to publish axis load");
+    }
+}
+
+topic!(AnalogTopic, AnalogReading, "topic/ioboard/analog");
+
+/// Publish a sample of an analog channel, samples are periodic so failures are only logged.
+pub fn publish_analog(reading: &AnalogReading) {
+    if STACK
+        .topics()
+        .broadcast::<AnalogTopic>(reading, None)
+        .is_err()
+    {
+        defmt::warn!("Unable to publish analog reading");
+    }
+}
+
+topic!(ForceTopic, ForceTrace, "topic/ioboard/force");
+
+/// Publish the force trace of a touchdown, the server records the forces of the placements, a lost trace is only
+/// missing from the record, so failures are only logged.
+pub fn publish_force_trace(trace: &ForceTrace) {
+    if STACK
+        .topics()
+        .broadcast::<ForceTopic>(trace, None)
+        .is_err()
+    {
+        defmt::warn!("Unable to publish force trace");
+    }
+}
+
+topic!(LoadCellTopic, LoadCellSample, "topic/ioboard/load_cell");
+
+/// Set by [`IoBoardCommand::BeginLoadCellStream`], the samples are only published while the stream is begun, since a
+/// sample is published at the sample rate of the load cell.
+static LOAD_CELL_STREAM: AtomicBool = AtomicBool::new(false);
+
+/// Publish a sample of the load cell while the stream is begun, samples are periodic so failures are only logged.
+pub fn publish_load_cell(sample: &LoadCellSample) {
+    if !LOAD_CELL_STREAM.load(Ordering::Relaxed) {
+        return;
+    }
+    if STACK
+        .topics()
+        .broadcast::<LoadCellTopic>(sample, None)
+        .is_err()
+    {
+        defmt::warn!("Unable to publish load cell sample");
+    }
+}
+
+topic!(SafetyTopic, SafetyStatus, "topic/ioboard/safety");
+
+/// Publish the safety status, the status is periodic so failures are only logged.
+pub fn publish_safety(status: &SafetyStatus) {
+    if STACK
+        .topics()
+        .broadcast::<SafetyTopic>(status, None)
+        .is_err()
+    {
+        defmt::warn!("Unable to publish safety status");
+    }
+}
+
+/// The e-stops received since the safety monitor last checked, see `ioboard_main::safety`.
+pub static ESTOP_REQUESTS: EStopRequests = EStopRequests::new();
+
+/// Unlike a channel a stop is never lost, however many messages are received between checks, and it takes precedence
+/// over a reset.
+pub struct EStopRequests {
+    /// 0 when there is no request, otherwise the source, see [`encode_source`]
+    stop: AtomicU8,
+    reset: AtomicU8,
+}
+
+impl EStopRequests {
+    pub const fn new() -> Self {
+        Self {
+            stop: AtomicU8::new(0),
+            reset: AtomicU8::new(0),
         }
     }
+
+    pub fn request(&self, estop: EStop) {
+        match estop {
+            EStop::Stop(source) => self
+                .stop
+                .store(encode_source(source), Ordering::Release),
+            EStop::Reset(source) => self
+                .reset
+                .store(encode_source(source), Ordering::Release),
+        }
+    }
+
+    /// Returns the pending request, a reset received in the same period as a stop is discarded.
+    pub fn take(&self) -> Option<EStop> {
+        let stop = self.stop.swap(0, Ordering::AcqRel);
+        let reset = self.reset.swap(0, Ordering::AcqRel);
+        match (decode_source(stop), decode_source(reset)) {
+            (Some(source), _) => Some(EStop::Stop(source)),
+            (None, Some(source)) => Some(EStop::Reset(source)),
+            (None, None) => None,
+        }
+    }
+}
+
+impl Default for EStopRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn encode_source(source: EStopSource) -> u8 {
+    match source {
+        EStopSource::Server => 1,
+        EStopSource::OperatorUi => 2,
+    }
+}
+
+fn decode_source(value: u8) -> Option<EStopSource> {
+    match value {
+        1 => Some(EStopSource::Server),
+        2 => Some(EStopSource::OperatorUi),
+        _ => None,
+    }
+}
+
+#[embassy_executor::task]
+async fn estop_listener() {
+    let subber = STACK
+        .topics()
+        .bounded_receiver::<EStopTopic, 4>(None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    defmt::info!("E-stop listener started");
+    loop {
+        let msg = hdl.recv().await;
+        ESTOP_REQUESTS.request(msg.t);
+    }
+}
+
+topic!(SelfTestTopic, SelfTestStatus, "topic/ioboard/self-test");
+
+/// Publish the result of the power-on self test, the status is periodic so failures are only logged.
+pub fn publish_self_test(status: &SelfTestStatus) {
+    if STACK
+        .topics()
+        .broadcast::<SelfTestTopic>(status, None)
+        .is_err()
+    {
+        defmt::warn!("Unable to publish self test status");
+    }
 }
 
 #[embassy_executor::task]
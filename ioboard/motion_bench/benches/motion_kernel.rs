@@ -0,0 +1,44 @@
+//! The cycles of the trajectory loop of the io board, on the host, see `motion_bench::Kernel`.
+//!
+//! `cargo bench -p motion_bench`, criterion compares each run with the previous one.
+
+use std::time::Duration;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use motion_bench::Kernel;
+
+fn segment_change(c: &mut Criterion) {
+    let mut kernel = Kernel::new();
+    c.bench_function("segment_change", |b| {
+        b.iter_custom(|iterations| {
+            (0..iterations)
+                .map(|_| kernel.segment_change())
+                .sum::<Duration>()
+        })
+    });
+}
+
+fn steady_state(c: &mut Criterion) {
+    let mut kernel = Kernel::new();
+    c.bench_function("steady_state", |b| {
+        b.iter_custom(|iterations| {
+            (0..iterations)
+                .map(|_| kernel.steady_state())
+                .sum::<Duration>()
+        })
+    });
+}
+
+fn step_conversion(c: &mut Criterion) {
+    let mut kernel = Kernel::new();
+    c.bench_function("step_conversion", |b| {
+        b.iter_custom(|iterations| {
+            (0..iterations)
+                .map(|_| kernel.step_conversion())
+                .sum::<Duration>()
+        })
+    });
+}
+
+criterion_group!(benches, segment_change, steady_state, step_conversion);
+criterion_main!(benches);
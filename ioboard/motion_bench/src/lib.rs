@@ -0,0 +1,231 @@
+//! The cases of the motion kernel benchmarks, the computations of a cycle of the trajectory loop of the io board, see
+//! [`ioboard_main::trajectory`], run on the host.
+//!
+//! The criterion benchmarks, see `benches/motion_kernel.rs`, are for comparing changes, the `motion-profile` binary
+//! fails when the steady-state cycle regresses, see [`regressions`].
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ioboard_main::stepper::PulseIntervals;
+use ioboard_main::trajectory::{AxisRuckig, advance_segment, convert_to_steps, prepare_segment};
+use ioboard_shared::motion::QueuedMove;
+use machine_ids::MoveId;
+use rsruckig::prelude::*;
+
+#[cfg(test)]
+mod tests;
+
+/// The same as the trajectory loop of the io board.
+pub const CYCLE_INTERVAL_US: u32 = 1000;
+
+/// Back and forth between these positions, in steps, each move takes several thousand cycles, so the steady-state case
+/// rarely changes the move.
+const POSITIONS: [f64; 2] = [0.0, 200_000.0];
+
+/// The limits of the moves, in steps/s, steps/s² and steps/s³, below the step rate of
+/// [`PULSES_PER_CYCLE_MAX`](ioboard_main::stepper::PULSES_PER_CYCLE_MAX).
+const MAX_VELOCITY: f64 = 40_000.0;
+const MAX_ACCELERATION: f64 = 400_000.0;
+const MAX_JERK: f64 = 4_000_000.0;
+
+/// The state of the trajectory loop of an axis, without the io.
+pub struct Kernel {
+    ruckig: AxisRuckig,
+    input: InputParameter<1>,
+    output: OutputParameter<1>,
+    last_position_steps: i64,
+    pulses: PulseIntervals,
+    /// the index of the target of the next move, in [`POSITIONS`]
+    next_target: usize,
+    finished: bool,
+    /// of the step conversion case, see [`triangle_wave`]
+    conversion_cycle: u32,
+}
+
+impl Kernel {
+    pub fn new() -> Self {
+        let dt = 1.0_f64 / CYCLE_INTERVAL_US as f64;
+        Self {
+            ruckig: AxisRuckig::new(None, dt),
+            input: InputParameter::<1>::new(None),
+            output: OutputParameter::<1>::new(None),
+            last_position_steps: 0,
+            pulses: PulseIntervals::new(),
+            next_target: 1,
+            finished: true,
+            conversion_cycle: 0,
+        }
+    }
+
+    fn next_move(&mut self) -> QueuedMove {
+        let target = POSITIONS[self.next_target];
+        self.next_target = (self.next_target + 1) % POSITIONS.len();
+        QueuedMove {
+            move_id: MoveId::new(self.next_target as u32),
+            target,
+            max_velocity: MAX_VELOCITY,
+            max_acceleration: MAX_ACCELERATION,
+            max_jerk: MAX_JERK,
+        }
+    }
+
+    /// Advances the segment, converts the position to steps, and fills the pulse intervals, the same as a cycle of the
+    /// trajectory loop without input shaping.
+    fn cycle(&mut self) {
+        let (position, finished) = advance_segment(&mut self.ruckig, &mut self.input, &mut self.output);
+        self.step(position);
+        self.finished = finished;
+    }
+
+    fn step(&mut self, position: f64) {
+        let cycle_steps = convert_to_steps(black_box(position), self.last_position_steps);
+        self.last_position_steps = cycle_steps.position_steps;
+        self.pulses
+            .fill(cycle_steps.steps(), CYCLE_INTERVAL_US)
+            .unwrap();
+        black_box(&self.pulses);
+    }
+
+    /// The first cycle of a move, from rest at the target of the previous move, returns the duration of the cycle.
+    pub fn segment_change(&mut self) -> Duration {
+        let at = POSITIONS[(self.next_target + POSITIONS.len() - 1) % POSITIONS.len()];
+        self.input.current_position = daov_stack![at];
+        self.input.current_velocity = daov_stack![0.0];
+        self.input.current_acceleration = daov_stack![0.0];
+        self.last_position_steps = at as i64;
+        let next_move = self.next_move();
+
+        let started_at = Instant::now();
+        prepare_segment(&mut self.ruckig, &mut self.input, &mut self.output, &next_move, 1.0);
+        self.cycle();
+        started_at.elapsed()
+    }
+
+    /// A cycle of a move in progress, returns the duration of the cycle.
+    ///
+    /// Once the move is finished the next move is started first, the first cycle of the next move isn't included.
+    pub fn steady_state(&mut self) -> Duration {
+        if self.finished {
+            let next_move = self.next_move();
+            prepare_segment(&mut self.ruckig, &mut self.input, &mut self.output, &next_move, 1.0);
+            self.cycle();
+        }
+
+        let started_at = Instant::now();
+        self.cycle();
+        started_at.elapsed()
+    }
+
+    /// Converts the position to steps and fills the pulse intervals, returns the duration of the conversion.
+    ///
+    /// The position is a triangle wave, at a step rate of 50kHz, in both directions.
+    pub fn step_conversion(&mut self) -> Duration {
+        let position = triangle_wave(self.conversion_cycle) * 50.0;
+        self.conversion_cycle = (self.conversion_cycle + 1) % TRIANGLE_WAVE_CYCLES;
+
+        let started_at = Instant::now();
+        self.step(position);
+        started_at.elapsed()
+    }
+}
+
+impl Default for Kernel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The median durations of the cases.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Profile {
+    pub segment_change: Duration,
+    pub steady_state: Duration,
+    pub step_conversion: Duration,
+}
+
+/// Runs each case for `cycles` cycles, the median is less affected by the scheduler of the host than the mean.
+pub fn profile(cycles: u32) -> Profile {
+    let mut kernel = Kernel::new();
+    let mut segment_change: Vec<_> = (0..cycles)
+        .map(|_| kernel.segment_change())
+        .collect();
+
+    let mut kernel = Kernel::new();
+    let mut steady_state: Vec<_> = (0..cycles)
+        .map(|_| kernel.steady_state())
+        .collect();
+
+    let mut kernel = Kernel::new();
+    let mut step_conversion: Vec<_> = (0..cycles)
+        .map(|_| kernel.step_conversion())
+        .collect();
+
+    Profile {
+        segment_change: median(&mut segment_change),
+        steady_state: median(&mut steady_state),
+        step_conversion: median(&mut step_conversion),
+    }
+}
+
+const TRIANGLE_WAVE_CYCLES: u32 = 2000;
+
+/// From 0 to 1000 and back to 0 over [`TRIANGLE_WAVE_CYCLES`].
+fn triangle_wave(cycle: u32) -> f64 {
+    let phase = cycle % TRIANGLE_WAVE_CYCLES;
+    match phase {
+        phase if phase < TRIANGLE_WAVE_CYCLES / 2 => phase as f64,
+        phase => (TRIANGLE_WAVE_CYCLES - phase) as f64,
+    }
+}
+
+pub(crate) fn median(durations: &mut [Duration]) -> Duration {
+    durations.sort();
+    durations
+        .get(durations.len() / 2)
+        .copied()
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Regression {
+    /// the steady-state cycle is too close to the cost of a segment change, e.g. the trajectory is re-calculated every
+    /// cycle
+    SteadyStateRatio {
+        ratio: f64,
+        max_ratio: f64,
+    },
+    SteadyStateSlower {
+        steady_state: Duration,
+        max: Duration,
+    },
+}
+
+/// The ratio is independent of the speed of the host, so that it can be checked on a shared CI runner, on the io board
+/// it's about 0.03.  `max_steady_state` is for a host with a known speed, e.g. a dedicated runner.
+pub fn regressions(profile: &Profile, max_ratio: f64, max_steady_state: Option<Duration>) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+
+    let ratio = profile.steady_state.as_secs_f64()
+        / profile
+            .segment_change
+            .as_secs_f64()
+            .max(f64::MIN_POSITIVE);
+    if ratio > max_ratio {
+        regressions.push(Regression::SteadyStateRatio {
+            ratio,
+            max_ratio,
+        });
+    }
+
+    if let Some(max) = max_steady_state
+        && profile.steady_state > max
+    {
+        regressions.push(Regression::SteadyStateSlower {
+            steady_state: profile.steady_state,
+            max,
+        });
+    }
+
+    regressions
+}
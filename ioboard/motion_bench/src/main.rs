@@ -0,0 +1,79 @@
+//! Profiles the motion kernel of the io board on the host, and fails when the steady-state cycle regresses, see
+//! [`motion_bench::regressions`].
+
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::Parser;
+use motion_bench::{Profile, Regression};
+
+#[derive(Parser, Debug)]
+#[command(name = "motion-profile", version, about = "MakerPnP - Motion kernel profile")]
+struct Args {
+    /// How many cycles of each case are run, the median duration is reported
+    #[arg(long = "cycles", default_value_t = 20_000)]
+    cycles: u32,
+
+    /// The steady-state cycle is a regression when it takes longer than this fraction of a segment change
+    #[arg(long = "max-steady-state-ratio", default_value_t = 0.1)]
+    max_steady_state_ratio: f64,
+
+    /// The steady-state cycle is a regression when it takes longer than this, in nanoseconds, only for a host with a
+    /// known speed
+    #[arg(long = "max-steady-state-ns", value_name = "NANOSECONDS")]
+    max_steady_state_ns: Option<u64>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let profile = motion_bench::profile(args.cycles);
+    println!("{}", format_profile(&profile));
+
+    let regressions = motion_bench::regressions(
+        &profile,
+        args.max_steady_state_ratio,
+        args.max_steady_state_ns
+            .map(Duration::from_nanos),
+    );
+
+    println!();
+    if regressions.is_empty() {
+        println!("No regressions. cycles: {}", args.cycles);
+        return ExitCode::SUCCESS;
+    }
+
+    for regression in &regressions {
+        println!("{}", format_regression(regression));
+    }
+    println!("Regressions: {}", regressions.len());
+    ExitCode::FAILURE
+}
+
+fn format_profile(profile: &Profile) -> String {
+    [
+        ("segment change", profile.segment_change),
+        ("steady state", profile.steady_state),
+        ("step conversion", profile.step_conversion),
+    ]
+    .iter()
+    .map(|(case, duration)| format!("{:<16} {:>10.3} us", case, duration.as_secs_f64() * 1_000_000.0))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn format_regression(regression: &Regression) -> String {
+    match regression {
+        Regression::SteadyStateRatio {
+            ratio,
+            max_ratio,
+        } => format!(
+            "Steady state too close to a segment change. ratio: {:.3}, max: {:.3}",
+            ratio, max_ratio
+        ),
+        Regression::SteadyStateSlower {
+            steady_state,
+            max,
+        } => format!("Steady state slower. duration: {:?}, max: {:?}", steady_state, max),
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use crate::{Kernel, Profile, Regression, TRIANGLE_WAVE_CYCLES, median, regressions};
+
+fn profile(segment_change_us: u64, steady_state_us: u64) -> Profile {
+    Profile {
+        segment_change: Duration::from_micros(segment_change_us),
+        steady_state: Duration::from_micros(steady_state_us),
+        step_conversion: Duration::from_micros(1),
+    }
+}
+
+#[test]
+pub fn median_duration_is_reported() {
+    // given
+    let mut durations = [3, 1, 100, 2, 4].map(Duration::from_micros);
+
+    // expect
+    assert_eq!(median(&mut durations), Duration::from_micros(3));
+    assert_eq!(median(&mut []), Duration::ZERO);
+}
+
+#[test]
+pub fn steady_state_close_to_a_segment_change_is_a_regression() {
+    // given, the trajectory is re-calculated every cycle
+    let profile = profile(758, 700);
+
+    // when
+    let regressions = regressions(&profile, 0.1, None);
+
+    // then
+    assert_eq!(regressions.len(), 1);
+    assert!(matches!(
+        regressions[0],
+        Regression::SteadyStateRatio { max_ratio, .. } if max_ratio == 0.1
+    ));
+}
+
+#[test]
+pub fn steady_state_slower_than_the_maximum_is_a_regression() {
+    // given
+    let profile = profile(758, 25);
+
+    // when
+    let regressions = regressions(&profile, 0.1, Some(Duration::from_micros(20)));
+
+    // then
+    assert_eq!(regressions, vec![Regression::SteadyStateSlower {
+        steady_state: Duration::from_micros(25),
+        max: Duration::from_micros(20),
+    }]);
+}
+
+#[test]
+pub fn profile_of_the_io_board_has_no_regressions() {
+    // given, the timings noted in the trajectory loop
+    let profile = profile(758, 25);
+
+    // expect
+    assert!(regressions(&profile, 0.1, Some(Duration::from_micros(25))).is_empty());
+}
+
+#[test]
+pub fn steps_of_the_step_conversion_case_fit_in_a_cycle() {
+    // given
+    let mut kernel = Kernel::new();
+
+    // expect no `TooManySteps`, in both directions, and when the wave starts again
+    for _ in 0..TRIANGLE_WAVE_CYCLES * 2 {
+        kernel.step_conversion();
+    }
+}
+
+#[test]
+pub fn steady_state_case_continues_with_the_next_move() {
+    // given
+    let mut kernel = Kernel::new();
+
+    // when, the first move finishes after about 5200 cycles
+    for _ in 0..7000 {
+        kernel.steady_state();
+    }
+
+    // then, the axis is moving back
+    assert!(kernel.last_position_steps > 0);
+    assert!(kernel.last_position_steps < 200_000);
+}
@@ -6,32 +6,69 @@ theme-button-system = 💻 System
 language-es-ES = Español (España)
 language-en-US = English (United States)
 
+unit-system-metric = Metric (mm)
+unit-system-imperial = Imperial (in)
+
+status-palette-standard = Status colors: Standard
+status-palette-color-blind-safe = Status colors: Color-blind safe
+
 menu-top-level-file = File
 menu-item-quit = Quit
 
+layout-selected = Layout: {$name}
+layout-unnamed = Layout {$index}
+layout-new-name-hint = New layout name
+layout-save-as = Save as
+
 main-window-title = MakerPnP - OperatorUI
 viewport-title = MakerPnP - OperatorUI ({$id})
 
 panel-camera-name = Camera
+panel-captures-name = Captures
 panel-controls-name = Controls
 panel-diagnostics-name = Diagnostics
+panel-feeders-name = Feeders
+panel-firmware-logs-name = Firmware logs
+panel-job-name = Job
+job-mode = Mode
+job-mode-dry-run = Dry run, nothing is moved
+panel-limits-name = Limits
 panel-plot-name = Plot
+panel-readiness-name = Readiness
 panel-settings-name = Settings
 panel-status-name = Status
+panel-templates-name = Templates
+panel-test-shots-name = Test shots
 
 panel-camera-icon = 📷
+panel-captures-icon = 🖼
 panel-controls-icon = ⛶
 panel-diagnostics-icon = 🛠
+panel-feeders-icon = 🎞
+panel-firmware-logs-icon = 📜
+panel-job-icon = ▶
+panel-limits-icon = ⚠
 panel-plot-icon = 📈
+panel-readiness-icon = ✅
 panel-settings-icon = ⛭
 panel-status-icon = 🚦
+panel-templates-icon = 🎯
+panel-test-shots-icon = ⊞
 
 panel-camera-window-title = Camera
+panel-captures-window-title = Captures
 panel-controls-window-title = Controls
 panel-diagnostics-window-title = Diagnostics
+panel-feeders-window-title = Feeders
+panel-firmware-logs-window-title = Firmware logs
+panel-job-window-title = Job
+panel-limits-window-title = Axis limits
 panel-plot-window-title = Plot
+panel-readiness-window-title = Readiness
 panel-settings-window-title = Settings
 panel-status-window-title = Status
+panel-templates-window-title = Templates
+panel-test-shots-window-title = Test shots
 
 jog-y-minus = Y-
 jog-y-plus = Y+
@@ -43,4 +80,340 @@ jog-z-plus = Z{$index}+
 jog-z-park = Z{$index} P
 
 camera-toolwindow-fps-stats-title = Stats
-camera-message-waiting = Waiting...
\ No newline at end of file
+camera-toolwindow-low-latency = Low latency
+camera-toolwindow-low-latency-hover = Show frames as soon as they arrive when the latency is above {$threshold}
+camera-message-waiting = Waiting...
+camera-button-pause = ⏸ Pause
+camera-button-resume = ▶ Resume
+camera-button-step = ⏭ Step
+camera-overlay-paused = Paused
+
+measurement-tool-none = None
+measurement-tool-ruler = 📏 Ruler
+measurement-tool-angle = 📐 Angle
+measurement-distance-px = {$distance} px
+camera-overlay-latency = Latency: {$latency}
+camera-overlay-vision-busy = Vision busy
+camera-overlay-vision-busy-preview-paused = Vision busy, preview paused
+camera-overlay-coordinates = X: {$x}, Y: {$y}
+camera-menu-copy-position = Copy as position
+camera-menu-copy-position-hover = In millimeters, e.g. for a park position in the server configuration
+camera-menu-copy-coordinates = Copy X, Y
+
+captures-button-refresh = Refresh
+captures-button-back = ⬅ Back
+captures-button-previous = ⏴ Newer
+captures-button-next = Older ⏵
+captures-label-page = {$first}-{$last} of {$total}
+captures-message-no-thumbnail = No thumbnail
+captures-message-waiting = Waiting for server...
+captures-message-empty = No captures
+
+plot-vibration-waiting = Waiting for vibration data...
+plot-vibration-rms-title = Vibration RMS (g)
+plot-vibration-spectrum-title = Vibration spectrum (g)
+
+status-safety-heading = Safety
+status-safety-waiting = Waiting for safety status...
+status-safety-input-door = Door
+status-safety-input-light-curtain = Light curtain
+status-safety-input-not-configured = Not configured
+status-safety-input-clear = Clear
+status-safety-input-tripped = Tripped
+status-safety-restriction = Motion
+status-safety-restriction-none = Normal
+status-safety-restriction-reduced-speed = Reduced speed ({$percent}%)
+status-safety-restriction-paused = Paused
+status-safety-restriction-estopped = Emergency stopped
+status-safety-estop = E-stop
+status-safety-estop-latched-server = Latched by the server
+status-safety-estop-latched-operator-ui = Latched by the operator UI
+status-safety-estop-not-latched = Not latched
+status-estop-button = EMERGENCY STOP
+status-estop-reset-button = Reset e-stop
+status-estop-not-sent = The e-stop could not be sent, try again
+
+status-self-test-heading = Io board
+status-self-test-waiting = Waiting for self test status...
+status-self-test-state = State
+status-self-test-state-ready = Ready
+status-self-test-state-degraded = Degraded
+status-self-test-checks = Checks
+status-self-test-faults-unreported = {$count} further faults
+status-self-test-fault-output-stuck-on = {$rail} output stuck on
+status-self-test-fault-output-stuck-off = {$rail} output stuck off
+status-self-test-fault-sensor-unreadable = {$sensor} sensor unreadable
+status-self-test-fault-sensor-out-of-range = {$sensor} sensor out of range ({$value})
+status-self-test-fault-driver-not-responding = Axis {$axis} driver not responding
+status-self-test-rail-motor-power = Motor power
+status-self-test-rail-vacuum-pump = Vacuum pump
+status-self-test-rail-lighting = Lighting
+status-self-test-sensor-supply-voltage = Supply voltage
+status-self-test-sensor-vacuum = Vacuum
+
+status-loads-heading = Axis load
+status-loads-waiting = Waiting for axis load data...
+status-load-axis = Axis {$axis}
+status-load-load = Load {$percent}%
+status-load-standstill = Standstill
+status-load-current = Current {$percent}%
+status-power-heading = Power
+status-power-waiting = Waiting for power meter data...
+status-power-voltage = Voltage
+status-power-current = Current
+status-power-power = Power
+status-power-energy = Energy since server start
+
+status-temperatures-heading = Temperatures
+status-temperatures-waiting = Waiting for temperature data...
+status-temperature-sensor-driver = Driver {$axis}
+status-temperature-sensor-ambient = Ambient
+
+feeders-column-feeder = Feeder
+feeders-column-count = Count
+feeders-column-low-stock-threshold = Low-stock threshold
+feeders-column-stock = Stock
+feeders-column-reel = Reel
+feeders-column-slot = Slot
+feeders-column-orientation = Orientation
+feeders-orientation-unverified = Unverified
+feeders-orientation-correct = Correct
+feeders-orientation-reversed = Reversed
+feeders-stock-ok = Ok
+feeders-stock-low = Low
+feeders-stock-out = Out
+feeders-button-apply = Apply changes
+feeders-button-discard = Discard changes
+feeders-button-scan-reel = Scan reel
+feeders-button-verify = Verify orientation
+feeders-event-low-stock = Feeder {$feeder} is low on stock, {$count} parts remaining.
+feeders-event-out-of-stock = Feeder {$feeder} is out of stock.
+feeders-event-reversed-tape = The tape of feeder {$feeder} is reversed, parts are not picked from it until it is verified again.
+feeders-event-inserted = Feeder {$feeder} was inserted into slot {$slot}.
+feeders-event-removed = Feeder {$feeder} was removed from slot {$slot}.
+feeders-event-unknown-inserted = An unknown feeder was inserted into slot {$slot}, add its identity {$identity} to the feeder configuration.
+feeders-message-waiting = Waiting for feeder status...
+feeders-message-none = There are no feeders configured.
+feeders-message-unknown-feeder = Unknown feeder {$feeder}.
+feeders-message-error = Error: {$error}
+feeders-message-scan-failed = Scan failed: {$error}
+feeders-message-tape-reversed = The tape of feeder {$feeder} is reversed.
+feeders-message-verify-failed = Verification failed: {$error}
+feeders-message-changes-rejected = The changes were rejected, none of them were applied.
+feeders-pending-heading = Pending changes
+feeders-pending-column-field = Setting
+feeders-pending-column-old = Current
+feeders-pending-column-new = New
+feeders-field-count = {$feeder} count
+feeders-field-low-stock-threshold = {$feeder} low-stock threshold
+feeders-field-was = was {$value}
+feeders-field-error-unknown-feeder = Unknown feeder
+feeders-field-error-duplicate = Changed more than once
+feeders-field-error-out-of-range = At most {$max}
+feeders-field-error-changed = Changed on the machine to {$current}
+
+job-name = Job
+job-mode = Mode
+job-mode-dry-run = Dry run, nothing is moved
+job-placed = Placed
+job-skipped = Skipped
+job-state = State
+job-state-running = Running
+job-state-paused = Paused
+job-state-finished = Finished
+job-state-aborted = Aborted
+job-intervention-heading = Placement {$placement} failed, choose how to continue
+job-intervention-error = Error: {$error}
+job-button-retry = Retry
+job-button-skip = Skip
+job-button-abort = Abort job
+job-message-waiting = No job has been started.
+job-message-stale = The intervention has already been resolved.
+job-message-no-intervention = The job is not waiting for an intervention.
+job-message-error = Error: {$error}
+job-button-estimate = Estimate
+job-button-estimate-hover = Simulate the selected job, to estimate how long it will take to run.
+job-estimate-duration = Estimated run time of {$job}: {$duration}
+job-estimate-timeline = Timeline
+job-estimate-placement = Placement
+job-estimate-start = Start
+job-estimate-move = Move
+job-estimate-operation = Operation
+job-estimate-no-job = No job is selected.
+job-estimate-unknown-head = The head of placement {$placement} is not configured.
+job-estimate-planning = Unable to plan the move to placement {$placement}.
+
+readiness-check-homed = Homed
+readiness-check-vacuum-ok = Vacuum ok
+readiness-check-cameras-calibrated = Cameras calibrated
+readiness-check-feeders-verified = Feeders verified
+readiness-check-io-board-ready = Io board self test passed
+readiness-check-server-tasks-running = Server tasks running
+readiness-check-temperatures-normal = Io board temperatures normal
+readiness-state-passed = Passed
+readiness-state-failed = Failed
+readiness-state-unknown = Unknown
+readiness-overridden = Overridden: {$reason}
+readiness-override-hint = Reason
+readiness-button-override = Override
+readiness-button-start-job = Start job
+readiness-button-scan-board = Scan board
+readiness-button-home-all = Home all
+readiness-homing-heading = Homing:
+readiness-homing-not-homed = not homed
+readiness-homing-homing = homing
+readiness-homing-homed = homed
+readiness-homing-failed = failed
+readiness-homing-skipped = skipped
+readiness-checkpoint-heading = Job {$job} was interrupted after {$completed} of {$placements} placements.
+readiness-checkpoint-homing = Home the machine before resuming, the job continues with the next placement.
+readiness-checkpoint-resume-confirmed = The job will be resumed.
+readiness-checkpoint-restart-confirmed = The job will be restarted from the first placement.
+readiness-button-resume = Resume
+readiness-button-restart = Restart
+readiness-message-resume-unconfirmed = Choose whether to resume or restart the interrupted job.
+readiness-message-waiting = Waiting for readiness status...
+readiness-message-overridden = Checks have been overridden, the reasons are logged by the server.
+readiness-message-job-started = Job started.
+readiness-message-not-ready = The machine is not ready.
+readiness-message-no-job = There is no job to run.
+readiness-message-running = A job is already running.
+readiness-message-no-motion = No io board follows the moves of the server, the parts can not be placed.
+readiness-message-homing-not-configured = There are no axes in the homing configuration of the server.
+readiness-message-board-scanned = Job selected for board {$board}.
+readiness-message-unknown-board = There is no job for board {$board}.
+readiness-message-scan-failed = Scan failed: {$error}
+readiness-message-error = Error: {$error}
+readiness-maintenance-heading = Maintenance
+readiness-maintenance-poor-pickup-vacuum = Nozzle {$nozzle} may be clogged, the pickup vacuum was only {$vacuum}.
+readiness-maintenance-slow-decay = Nozzle {$nozzle} may be clogged, the vacuum was still {$vacuum} after release.
+readiness-maintenance-nozzle-cleaned = Nozzle {$nozzle} has been cleaned.
+readiness-maintenance-nozzle-cleaning-failed = Cleaning nozzle {$nozzle} failed, clean it manually.
+
+status-geometry-heading = Machine geometry
+status-geometry-waiting = Waiting for machine geometry...
+status-geometry-skew = XY skew
+status-geometry-scale-x = X scale
+status-geometry-scale-y = Y scale
+
+diagnostics-command-latency-heading = Command latency
+diagnostics-command-latency-waiting = Waiting for command latency data...
+diagnostics-command-latency-alarm = ⚠ p99 latency above {$limit}
+diagnostics-command-latency-samples = Samples
+diagnostics-command-latency-lost = Lost
+diagnostics-command-latency-p50 = p50
+diagnostics-command-latency-p99 = p99
+diagnostics-command-latency-max = Max
+diagnostics-camera-memory-heading = Camera memory
+diagnostics-camera-memory-waiting = Waiting for camera memory data...
+diagnostics-camera-memory-camera = Camera
+diagnostics-camera-memory-used = Used / limit
+diagnostics-camera-memory-dropped = Dropped frames
+diagnostics-camera-memory-evicted = Evicted frames
+diagnostics-camera-memory-total = Total
+diagnostics-router-heading = Router
+diagnostics-router-waiting = Waiting for router data...
+diagnostics-router-no-nodes = No nodes discovered
+diagnostics-router-interface = Network { $network }, answered: { $answered }, lost: { $lost }
+diagnostics-router-node = Node
+diagnostics-router-rtt = RTT
+diagnostics-router-max-rtt = Max RTT
+diagnostics-router-pings = Pings
+diagnostics-router-lost = Lost
+diagnostics-router-lost-in-a-row = Lost in a row
+diagnostics-router-unreachable = Unreachable
+
+firmware-logs-button-pause = ⏸ Pause
+firmware-logs-button-resume = ▶ Resume ({$count} new)
+firmware-logs-button-clear = Clear
+firmware-logs-label-level = Level
+firmware-logs-label-board = Board
+firmware-logs-board-all = All
+firmware-logs-hint-filter = Filter
+firmware-logs-level-error = ERROR
+firmware-logs-level-warn = WARN
+firmware-logs-level-info = INFO
+firmware-logs-level-debug = DEBUG
+firmware-logs-level-trace = TRACE
+firmware-logs-message-waiting = Waiting for io board logs...
+
+templates-button-refresh = Refresh
+templates-button-create = Create
+templates-button-save = Save
+templates-button-cancel = Cancel
+templates-button-edit = Edit
+templates-button-delete = Delete
+templates-label-camera = Camera
+templates-label-name = Name
+templates-label-editing = Editing {$name}
+templates-checkbox-recapture = Re-capture image
+templates-hint-crop = Enter a name and drag on the camera image to select the region of the template.
+templates-kind-fiducial = Fiducial
+templates-kind-part = Part
+templates-column-name = Name
+templates-column-kind = Kind
+templates-column-size = Size
+templates-column-camera = Camera
+templates-column-updated = Updated
+templates-message-waiting = Waiting for server...
+templates-message-no-camera = Start a camera to capture a template.
+templates-message-empty = No templates
+templates-message-saved = Template {$name} saved.
+templates-message-error = Error: {$error}
+templates-error-invalid-name = Invalid name, only letters, digits, '-', '_' and '.' are allowed.
+templates-error-already-exists = A template with the name already exists.
+templates-error-not-found = The template no longer exists.
+templates-error-unknown-camera = Unknown camera.
+templates-error-capture-failed = Unable to capture the image.
+templates-error-invalid-crop = The selected region is outside of the camera image.
+templates-error-storage = Unable to store the template, see the server log.
+
+test-shots-label-columns = Columns
+test-shots-label-rows = Rows
+test-shots-label-pitch = Pitch
+test-shots-label-kind = Kind
+test-shots-label-feeder = Feeder
+test-shots-label-head = Head
+test-shots-kind-dispense = Dispense
+test-shots-kind-place = Pick and place
+test-shots-button-run = Run {$shots} shots
+test-shots-button-clear = Area cleared
+test-shots-hint-clear = Click after removing the test shots from the test area, the next pattern starts from the first row again.
+test-shots-state-running = Running
+test-shots-state-finished = Finished
+test-shots-progress = Placed: {$placed}, failed: {$failed}, shots: {$shots}
+test-shots-message-waiting = Waiting for server...
+test-shots-message-cleared = The test area is clear.
+test-shots-message-error = Error: {$error}
+test-shots-error-not-configured = The machine has no test area.
+test-shots-error-invalid-pattern = Invalid pattern.
+test-shots-error-area-full = The pattern does not fit in the free part of the test area, clear the area first.
+test-shots-error-running = A job or test shots are running.
+test-shots-error-unknown-feeder = Unknown feeder.
+test-shots-error-unknown-head = Unknown dispenser head.
+test-shots-error-no-motion = No io board follows the moves of the server.
+
+limits-warning = Overriding a limit disables a protection of the machine, the limit is re-enabled automatically once the duration has passed.
+limits-label-axis = Axis
+limits-label-limit = Limit
+limits-label-operator = Operator
+limits-label-access-code = Access code
+limits-label-reason = Reason
+limits-label-duration = Duration
+limits-limit-safe-z-guard = Safe-Z guard
+limits-button-override = Override
+limits-button-clear = Re-enable now
+limits-none = No limits are overridden.
+limits-overridden-by = By {$operator}: {$reason}
+limits-remaining = {$remaining} s remaining
+limits-banner = ⚠ {$limit} of axis {$axis} overridden by {$operator}, {$remaining} s remaining
+limits-message-waiting = Waiting for server...
+limits-message-error = Error: {$error}
+limits-error-not-configured = No operators are authorized to override limits.
+limits-error-unauthorized = Unknown operator or wrong access code.
+limits-error-missing-reason = A reason is required.
+limits-error-unknown-axis = Unknown axis.
+limits-error-invalid-duration = The duration must be between 1 and {$max} s.
+limits-error-already-overridden = The limit is already overridden.
+limits-error-not-overridden = The limit is not overridden.
+limits-error-not-applied = The io board did not accept the override, the limit is still enabled.
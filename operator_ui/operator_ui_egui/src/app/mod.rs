@@ -9,26 +9,52 @@ use egui_i18n::tr;
 use egui_mobius::types::{Enqueue, ValueGuard};
 use egui_mobius::{Slot, Value};
 use ergot::Address;
+use ergot::fmtlog::Level;
 use ergot::toolkits::tokio_udp::EdgeStack;
-use operator_shared::camera::CameraIdentifier;
+use ioboard_shared::load::AxisLoad;
+use ioboard_shared::safety::SafetyStatus;
+use ioboard_shared::self_test::SelfTestStatus;
+use ioboard_shared::thermal::ThermalReading;
+use ioboard_shared::vibration::VibrationReport;
+use machine_ids::CameraId;
+use operator_shared::camera::{CameraCalibration, CameraMounting};
+use operator_shared::diagnostics::{CameraMemoryReport, CommandLatencyReport, RouterReport};
+use operator_shared::feeders::{FeederEvent, FeedersStatus};
+use operator_shared::geometry::MachineGeometry;
+use operator_shared::homing::HomingStatus;
+use operator_shared::job::{JobCheckpoint, JobEvent};
+use operator_shared::limits::LimitOverrideStatus;
+use operator_shared::maintenance::MaintenanceEvent;
+use operator_shared::power::PowerReading;
+use operator_shared::readiness::ReadinessStatus;
+use operator_shared::test_area::TestShotEvent;
+use operator_shared::vision::VisionStatus;
 use tokio::runtime::Handle;
-use tokio::sync::{broadcast, watch};
+use tokio::sync::{broadcast, mpsc, watch};
 use tracing::{info, trace, warn};
 use ui::camera::CameraUi;
+use ui::captures::CapturesUi;
 use ui::controls::ControlsUi;
 use ui::diagnostics::DiagnosticsUi;
+use ui::feeders::FeedersUi;
+use ui::firmware_logs::FirmwareLogsUi;
+use ui::job::JobUi;
+use ui::limits::LimitsUi;
 use ui::plot::PlotUi;
+use ui::readiness::ReadinessUi;
 use ui::settings::SettingsUi;
 use ui::status::StatusUi;
+use ui::templates::TemplatesUi;
+use ui::test_shots::TestShotsUi;
 
 use crate::config::Config;
 use crate::events::AppEvent;
-use crate::net::camera::{CameraFrame, camera_frame_listener};
+use crate::net::camera::{CameraFrame, CameraStreamControl, camera_frame_listener};
 use crate::net::ergot_task;
 use crate::runtime::tokio_runtime::TokioRuntime;
 use crate::ui_commands::{UiCommand, handle_command};
 use crate::workspace::{ViewportState, Workspaces};
-use crate::task;
+use crate::{REMOTE_ADDR, task, ui_common};
 
 mod ui;
 
@@ -41,24 +67,40 @@ pub struct AppState {
 }
 
 pub struct UiState {
-    pub(crate) camera_uis: BTreeMap<CameraIdentifier, CameraUi>,
+    pub(crate) camera_uis: BTreeMap<CameraId, CameraUi>,
 
+    pub(crate) captures_ui: CapturesUi,
     pub(crate) controls_ui: ControlsUi,
     pub(crate) diagnostics_ui: DiagnosticsUi,
+    pub(crate) feeders_ui: FeedersUi,
+    pub(crate) firmware_logs_ui: FirmwareLogsUi,
+    pub(crate) job_ui: JobUi,
+    pub(crate) limits_ui: LimitsUi,
     pub(crate) plot_ui: PlotUi,
+    pub(crate) readiness_ui: ReadinessUi,
     pub(crate) settings_ui: SettingsUi,
     pub(crate) status_ui: StatusUi,
+    pub(crate) templates_ui: TemplatesUi,
+    pub(crate) test_shots_ui: TestShotsUi,
 }
 
 impl AppState {
     pub fn init(sender: Enqueue<UiCommand>, context: Context) -> Self {
         let ui_state = UiState {
             camera_uis: BTreeMap::new(),
+            captures_ui: CapturesUi::default(),
             controls_ui: ControlsUi::default(),
             diagnostics_ui: DiagnosticsUi::default(),
+            feeders_ui: FeedersUi::default(),
+            firmware_logs_ui: FirmwareLogsUi::default(),
+            job_ui: JobUi::default(),
+            limits_ui: LimitsUi::default(),
             plot_ui: PlotUi::default(),
+            readiness_ui: ReadinessUi::default(),
             settings_ui: SettingsUi::default(),
             status_ui: StatusUi::default(),
+            templates_ui: TemplatesUi::default(),
+            test_shots_ui: TestShotsUi::default(),
         };
 
         let ui_state = Value::new(ui_state);
@@ -77,13 +119,16 @@ impl AppState {
 
     pub fn add_camera(
         &self,
-        camera_identifier: CameraIdentifier,
+        camera_identifier: CameraId,
         stack: EdgeStack,
         command_endpoint_remote_address: Address,
         target_fps: f32,
+        mounting: CameraMounting,
+        calibration: Option<CameraCalibration>,
     ) {
         let shutdown_token = tokio_util::sync::CancellationToken::new();
         let (camera_tx, camera_rx) = watch::channel::<CameraFrame>(CameraFrame::default());
+        let (control_tx, control_rx) = mpsc::unbounded_channel::<CameraStreamControl>();
 
         let camera_frame_listener_handle = {
             let context = self.context.clone();
@@ -95,12 +140,20 @@ impl AppState {
                 shutdown_token.clone(),
                 camera_identifier.clone(),
                 target_fps,
+                control_rx,
             ))
         };
 
         info!("Started camera frame listener.  id: {}", camera_identifier);
 
-        let camera_ui = CameraUi::new(camera_rx, camera_frame_listener_handle, shutdown_token);
+        let camera_ui = CameraUi::new(
+            camera_rx,
+            control_tx,
+            mounting,
+            calibration,
+            camera_frame_listener_handle,
+            shutdown_token,
+        );
 
         let mut ui_state = self.ui_state.lock().unwrap();
         let result = ui_state
@@ -109,13 +162,238 @@ impl AppState {
         assert!(result.is_none(), "Camera id already exists");
     }
 
-    pub(crate) fn prepare_stop_all_cameras(&self) -> BTreeMap<CameraIdentifier, CameraUi> {
+    /// Must be called from within the tokio runtime.
+    pub fn connect_captures(&self, stack: EdgeStack, command_endpoint_remote_address: Address) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .captures_ui
+            .connect(stack, command_endpoint_remote_address);
+        self.context.request_repaint();
+    }
+
+    /// Must be called from within the tokio runtime.
+    pub fn connect_templates(&self, stack: EdgeStack, command_endpoint_remote_address: Address) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .templates_ui
+            .connect(stack, command_endpoint_remote_address);
+        self.context.request_repaint();
+    }
+
+    /// Must be called from within the tokio runtime.
+    pub fn connect_test_shots(&self, stack: EdgeStack, command_endpoint_remote_address: Address) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .test_shots_ui
+            .connect(stack, command_endpoint_remote_address);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_test_shots(&self, event: TestShotEvent) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state.test_shots_ui.update(event);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn add_vibration_report(&self, report: VibrationReport) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .plot_ui
+            .add_vibration_report(report);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_temperature(&self, reading: ThermalReading) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .status_ui
+            .update_temperature(reading);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_safety_status(&self, status: SafetyStatus) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state.status_ui.update_safety(status);
+        self.context.request_repaint();
+    }
+
+    pub fn connect_estop(&self, stack: EdgeStack) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state.status_ui.connect(stack);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_self_test_status(&self, status: SelfTestStatus) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .status_ui
+            .update_self_test(status);
+        self.context.request_repaint();
+    }
+
+    pub fn connect_feeders(&self, stack: EdgeStack, command_endpoint_remote_address: Address) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .feeders_ui
+            .connect(stack, command_endpoint_remote_address);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_feeders(&self, status: FeedersStatus) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .feeders_ui
+            .update_status(status);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn add_feeder_event(&self, event: FeederEvent) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state.feeders_ui.add_event(event);
+        self.context.request_repaint();
+    }
+
+    pub fn connect_job(&self, stack: EdgeStack, command_endpoint_remote_address: Address) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .job_ui
+            .connect(stack, command_endpoint_remote_address);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_job(&self, event: JobEvent) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state.job_ui.update(event);
+        self.context.request_repaint();
+    }
+
+    pub fn connect_readiness(&self, stack: EdgeStack, command_endpoint_remote_address: Address) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .readiness_ui
+            .connect(stack, command_endpoint_remote_address);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_job_checkpoint(&self, checkpoint: Option<JobCheckpoint>) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .readiness_ui
+            .update_checkpoint(checkpoint);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_readiness(&self, status: ReadinessStatus) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .readiness_ui
+            .update_status(status);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_homing(&self, status: HomingStatus) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .readiness_ui
+            .update_homing(status);
+        self.context.request_repaint();
+    }
+
+    /// Must be called from within the tokio runtime.
+    pub fn connect_limits(&self, stack: EdgeStack, command_endpoint_remote_address: Address) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .limits_ui
+            .connect(stack, command_endpoint_remote_address);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_limit_overrides(&self, status: LimitOverrideStatus) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state.limits_ui.update_status(status);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn add_maintenance_event(&self, event: MaintenanceEvent) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .readiness_ui
+            .add_maintenance_event(event);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_machine_geometry(&self, geometry: MachineGeometry) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .status_ui
+            .update_machine_geometry(geometry);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_axis_load(&self, load: AxisLoad) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .status_ui
+            .update_axis_load(load);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_power(&self, reading: PowerReading) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state.status_ui.update_power(reading);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_command_latency(&self, report: CommandLatencyReport) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .diagnostics_ui
+            .update_command_latency(report);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_camera_memory(&self, report: CameraMemoryReport) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .diagnostics_ui
+            .update_camera_memory(report);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_router_report(&self, report: RouterReport) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .diagnostics_ui
+            .update_router_report(report);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn add_firmware_log(&self, board: Address, level: &Level, message: String) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        ui_state
+            .firmware_logs_ui
+            .add_line(board, level, message);
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn update_vision_status(&self, status: VisionStatus) {
+        let mut ui_state = self.ui_state.lock().unwrap();
+        if let Some(camera_ui) = ui_state
+            .camera_uis
+            .get_mut(&status.camera)
+        {
+            camera_ui.update_vision_status(status);
+        }
+        self.context.request_repaint();
+    }
+
+    pub(crate) fn prepare_stop_all_cameras(&self) -> BTreeMap<CameraId, CameraUi> {
         let mut ui_state = self.ui_state.lock().unwrap();
         let camera_uis = std::mem::take(&mut ui_state.camera_uis);
         camera_uis
     }
 
-    pub(crate) async fn stop_all_cameras(camera_uis: BTreeMap<CameraIdentifier, CameraUi>) {
+    pub(crate) async fn stop_all_cameras(camera_uis: BTreeMap<CameraId, CameraUi>) {
         for (camera_identifier, camera_ui) in camera_uis.into_iter() {
             info!("Stopping camera UI.  id: {}", camera_identifier);
             camera_ui.shutdown().await;
@@ -192,6 +470,8 @@ impl OperatorUiApp {
         {
             let config = instance.config.lock().unwrap();
             egui_i18n::set_language(&config.language_identifier);
+            ui_common::units::set_unit_system(config.unit_system);
+            ui_common::style::set_status_palette(config.status_palette);
 
             // Safety: now safe to use i18n translation system (e.g. [`egui_i18n::tr!`])
         }
@@ -218,6 +498,13 @@ impl OperatorUiApp {
 
         let app_state = AppState::init(app_message_sender.clone(), cc.egui_ctx.clone());
 
+        // the layouts are per machine, there's one machine for now
+        instance
+            .workspaces
+            .lock()
+            .unwrap()
+            .select_profile(REMOTE_ADDR);
+
         {
             let mut viewports = instance.viewports.lock().unwrap();
             if viewports.is_empty() {
@@ -486,12 +773,20 @@ impl eframe::App for OperatorUiApp {
 
 #[derive(serde::Deserialize, serde::Serialize, PartialEq, Eq, Debug, Clone, Copy, Hash)]
 pub enum PaneKind {
-    Camera { id: CameraIdentifier },
+    Camera { id: CameraId },
+    Captures,
     Controls,
     Diagnostics,
+    Feeders,
+    FirmwareLogs,
+    Job,
+    Limits,
     Plot,
+    Readiness,
     Settings,
     Status,
+    Templates,
+    TestShots,
 }
 
 pub(crate) fn show_panel_content(kind: &PaneKind, ui: &mut Ui, ui_state: &mut UiState) {
@@ -505,10 +800,20 @@ pub(crate) fn show_panel_content(kind: &PaneKind, ui: &mut Ui, ui_state: &mut Ui
                 ui.spinner();
             }
         }
+        PaneKind::Captures => ui_state.captures_ui.ui(ui),
         PaneKind::Controls => ui_state.controls_ui.ui(ui),
         PaneKind::Diagnostics => ui_state.diagnostics_ui.ui(ui),
+        PaneKind::Feeders => ui_state.feeders_ui.ui(ui),
+        PaneKind::FirmwareLogs => ui_state.firmware_logs_ui.ui(ui),
+        PaneKind::Job => ui_state.job_ui.ui(ui),
+        PaneKind::Limits => ui_state.limits_ui.ui(ui),
         PaneKind::Plot => ui_state.plot_ui.ui(ui),
+        PaneKind::Readiness => ui_state.readiness_ui.ui(ui),
         PaneKind::Settings => ui_state.settings_ui.ui(ui),
         PaneKind::Status => ui_state.status_ui.ui(ui),
+        PaneKind::Templates => ui_state
+            .templates_ui
+            .ui(ui, &ui_state.camera_uis),
+        PaneKind::TestShots => ui_state.test_shots_ui.ui(ui),
     }
 }
@@ -1,11 +1,16 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use eframe::epaint::Color32;
 use eframe::epaint::textures::TextureOptions;
-use egui::{Frame, RichText, Ui, UiBuilder, Widget};
+use egui::{Frame, Pos2, Response, RichText, Sense, Ui, UiBuilder, Vec2, Widget};
 use egui_i18n::tr;
 use egui_mobius::Value;
 use egui_tool_windows::ToolWindows;
+use machine_geometry::{ImageView, PixelScale, Point};
+use operator_shared::camera::{CameraCalibration, CameraMounting, HeadPosition};
+use operator_shared::vision::VisionStatus;
+use stream_pacing::FramePacer;
+use tokio::sync::mpsc;
 use tokio::sync::watch::Receiver;
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
@@ -13,12 +18,24 @@ use tracing::{error, trace};
 
 use crate::fps_stats::egui::show_frame_durations;
 use crate::fps_stats::{FpsSnapshot, FpsStats};
-use crate::net::camera::CameraFrame;
+use crate::net::camera::{CameraFrame, CameraStreamControl};
+use crate::ui_common::measurement::{MeasurementOverlay, machine_coordinates};
+use crate::ui_common::style::{Status, status_color};
+use crate::ui_common::units::formatter;
+
+/// When low-latency mode is enabled and the latency exceeds this, frames are presented as soon as they arrive instead
+/// of being paced at the frame interval.
+const LOW_LATENCY_THRESHOLD: Duration = Duration::from_millis(150);
+
+/// Smoothing factor for the exponential moving average of the latency.
+const LATENCY_ALPHA: f32 = 0.1;
 
 pub(crate) struct CameraUi {
     rx: Receiver<CameraFrame>,
+    control_tx: mpsc::UnboundedSender<CameraStreamControl>,
+    paused: bool,
     texture: Option<egui::TextureHandle>,
-    next_frame_at: Instant,
+    pacer: FramePacer,
     timestamp: chrono::DateTime<chrono::Utc>,
 
     camera_frame_listener_handle: JoinHandle<anyhow::Result<()>>,
@@ -28,19 +45,39 @@ pub(crate) struct CameraUi {
     camera_fps_stats: Value<FpsStats<300>>,
     camera_fps_snapshot: Option<FpsSnapshot>,
 
-    lag_counter: u64,
+    /// Smoothed time from the frame being captured to it being presented, relies on the clocks of the server and
+    /// the operator UI being in sync.
+    latency: Option<Duration>,
+    low_latency: Value<bool>,
+
+    vision_status: Option<VisionStatus>,
+
+    scale: Option<PixelScale>,
+    measurement: MeasurementOverlay,
+
+    /// `None` unless the camera is on the head, an up camera doesn't move, and its position is not known here.
+    view: Option<ImageView>,
+    /// Of the presented frame, so the coordinates under the pointer match the image, not the current position.
+    head_position: Option<HeadPosition>,
+    /// Where the context menu was opened, in machine coordinates.
+    context_coordinates: Option<Point>,
 }
 
 impl CameraUi {
     pub fn new(
         rx: Receiver<CameraFrame>,
+        control_tx: mpsc::UnboundedSender<CameraStreamControl>,
+        mounting: CameraMounting,
+        calibration: Option<CameraCalibration>,
         camera_frame_listener_handle: JoinHandle<anyhow::Result<()>>,
         shutdown_token: CancellationToken,
     ) -> Self {
         Self {
             rx,
+            control_tx,
+            paused: false,
             texture: None,
-            next_frame_at: Instant::now(),
+            pacer: FramePacer::new(Instant::now()),
             timestamp: Default::default(),
 
             camera_frame_listener_handle,
@@ -50,7 +87,23 @@ impl CameraUi {
             camera_fps_snapshot: None,
             camera_frame_number: 0,
 
-            lag_counter: 0,
+            latency: None,
+            low_latency: Value::new(false),
+
+            vision_status: None,
+
+            scale: calibration.map(|calibration| PixelScale {
+                mm_per_pixel_x: calibration.mm_per_pixel_x as f64,
+                mm_per_pixel_y: calibration.mm_per_pixel_y as f64,
+            }),
+            measurement: MeasurementOverlay::default(),
+
+            view: match mounting {
+                CameraMounting::Down => Some(ImageView::FromAbove),
+                CameraMounting::Up | CameraMounting::Other => None,
+            },
+            head_position: None,
+            context_coordinates: None,
         }
     }
 
@@ -66,18 +119,65 @@ impl CameraUi {
 }
 
 impl CameraUi {
+    /// The most recently presented frame, `None` until the first frame has been received.
+    pub fn texture(&self) -> Option<&egui::TextureHandle> {
+        self.texture.as_ref()
+    }
+
+    pub fn update_vision_status(&mut self, status: VisionStatus) {
+        self.vision_status = status.busy.then_some(status);
+    }
+
+    fn send_control(&self, control: CameraStreamControl) {
+        if let Err(e) = self.control_tx.send(control) {
+            error!("Unable to send camera stream control: {:?}", e);
+        }
+    }
+
+    /// The machine coordinates under `position`, `None` unless the camera is on the head, has been calibrated, and
+    /// the position of the head is known.
+    fn coordinates_at(&self, response: &Response, image_size: Vec2, position: Pos2) -> Option<Point> {
+        let (Some(scale), Some(view), Some(head_position)) = (self.scale, self.view, self.head_position) else {
+            return None;
+        };
+        let center = Point {
+            x: head_position.x,
+            y: head_position.y,
+        };
+        Some(machine_coordinates(
+            response.rect,
+            image_size,
+            position,
+            scale,
+            view,
+            center,
+        ))
+    }
+
+    fn update_latency(&mut self, latency: chrono::TimeDelta) {
+        // negative if the clocks are not in sync
+        let latency = latency.to_std().unwrap_or_default();
+        self.latency = Some(match self.latency {
+            Some(previous) => previous.mul_f32(1.0 - LATENCY_ALPHA) + latency.mul_f32(LATENCY_ALPHA),
+            None => latency,
+        });
+    }
+
     pub fn ui(&mut self, ui: &mut Ui) {
         let now = std::time::Instant::now();
 
+        // skip the pacing when behind, operators jog by camera feedback so a current frame matters more than a smooth
+        // frame rate.
+        let skip_pacing = *self.low_latency.lock().unwrap()
+            && self
+                .latency
+                .is_some_and(|latency| latency > LOW_LATENCY_THRESHOLD);
+
         if let Ok(true) = self.rx.has_changed() {
-            if now > self.next_frame_at {
+            if self.pacer.is_due(now, skip_pacing) {
                 let camera_frame = self.rx.borrow_and_update().clone();
-                self.next_frame_at += camera_frame.frame_interval;
-                if now > self.next_frame_at {
-                    // catch up if we fall behind
-                    self.next_frame_at = now + camera_frame.frame_interval;
-                    self.lag_counter = self.lag_counter.wrapping_add(1);
-                }
+                self.pacer
+                    .presented(now, camera_frame.frame_interval, skip_pacing);
 
                 self.camera_frame_number += 1;
                 if let Ok(snapshot) = self
@@ -93,6 +193,8 @@ impl CameraUi {
                 }
 
                 self.timestamp = (*camera_frame.timestamp).into();
+                self.head_position = camera_frame.head_position;
+                self.update_latency(chrono::Utc::now() - self.timestamp);
 
                 if let Some(tex) = &mut self.texture {
                     tex.set(camera_frame.image, TextureOptions::default());
@@ -107,21 +209,41 @@ impl CameraUi {
         }
 
         // Schedule next repaint at render_after or sooner
-        let repaint_delay = self
-            .next_frame_at
-            .saturating_duration_since(now.into());
+        let repaint_delay = self.pacer.delay(now);
         ui.ctx()
             .request_repaint_after(repaint_delay);
 
+        let mut control = None;
+
         egui::ScrollArea::both()
             //.id_salt(ui.id().with("content-scroll"))
             .show(ui, |ui| {
                 if let Some(tex) = &self.texture {
-                    egui::Image::new(tex)
+                    let response = egui::Image::new(tex)
                         .max_size(ui.available_size())
                         .maintain_aspect_ratio(true)
+                        .sense(Sense::click())
                         .ui(ui);
 
+                    let image_size = tex.size_vec2();
+                    self.measurement
+                        .ui(ui, &response, image_size, self.scale);
+
+                    let hovered_coordinates = response
+                        .hover_pos()
+                        .and_then(|position| self.coordinates_at(&response, image_size, position));
+                    if response.secondary_clicked() {
+                        self.context_coordinates = response
+                            .interact_pointer_pos()
+                            .and_then(|position| self.coordinates_at(&response, image_size, position));
+                    }
+                    // the secondary click clears the measurement instead
+                    if !self.measurement.is_active() {
+                        if let Some(coordinates) = self.context_coordinates {
+                            response.context_menu(|ui| coordinates_menu_ui(ui, coordinates));
+                        }
+                    }
+
                     let mut overlay_ui = ui.new_child(
                         UiBuilder::new()
                             //.id_salt(ui.id().with("overlay"))
@@ -131,11 +253,78 @@ impl CameraUi {
                         egui::Label::new(RichText::new(format!("{}", self.timestamp)).color(Color32::GREEN))
                             .selectable(false),
                     );
+                    overlay_ui.horizontal(|ui| {
+                        let pause_resume_text = match self.paused {
+                            true => tr!("camera-button-resume"),
+                            false => tr!("camera-button-pause"),
+                        };
+                        if ui.button(pause_resume_text).clicked() {
+                            control = Some(match self.paused {
+                                true => CameraStreamControl::Resume,
+                                false => CameraStreamControl::Pause,
+                            });
+                        }
+                        if ui
+                            .add_enabled(self.paused, egui::Button::new(tr!("camera-button-step")))
+                            .clicked()
+                        {
+                            control = Some(CameraStreamControl::Step);
+                        }
+                        ui.separator();
+                        self.measurement.toolbar_ui(ui);
+                    });
+                    if self.paused {
+                        overlay_ui.add(
+                            egui::Label::new(
+                                RichText::new(tr!("camera-overlay-paused")).color(status_color(Status::Warn)),
+                            )
+                            .selectable(false),
+                        );
+                    } else if let Some(latency) = self.latency {
+                        let color = match latency > LOW_LATENCY_THRESHOLD {
+                            true => status_color(Status::Warn),
+                            false => status_color(Status::Ok),
+                        };
+                        overlay_ui.add(
+                            egui::Label::new(
+                                RichText::new(
+                                    tr!("camera-overlay-latency", { latency: formatter().duration(latency) }),
+                                )
+                                .color(color),
+                            )
+                            .selectable(false),
+                        );
+                    }
+                    if let Some(vision_status) = &self.vision_status {
+                        let message = match vision_status.preview_paused {
+                            true => tr!("camera-overlay-vision-busy-preview-paused"),
+                            false => tr!("camera-overlay-vision-busy"),
+                        };
+                        overlay_ui.add(
+                            egui::Label::new(RichText::new(message).color(status_color(Status::Warn)))
+                                .selectable(false),
+                        );
+                    }
+                    if let Some(coordinates) = hovered_coordinates {
+                        overlay_ui.add(
+                            egui::Label::new(RichText::new(format_coordinates(coordinates)).color(Color32::GREEN))
+                                .selectable(false),
+                        );
+                    }
                 } else {
                     ui.label(tr!("camera-message-waiting"));
                 }
             });
 
+        if let Some(control) = control {
+            self.paused = match control {
+                CameraStreamControl::Pause => true,
+                CameraStreamControl::Resume => false,
+                CameraStreamControl::Step => self.paused,
+            };
+            self.send_control(control);
+        }
+
         let fps_stats_id = ui.make_persistent_id(
             ui.id()
                 .with("camera-toolwindow-fps-stats"),
@@ -149,8 +338,13 @@ impl CameraUi {
                     let camera_fps_stats = self.camera_fps_stats.clone();
                     let camera_fps_snapshot = self.camera_fps_snapshot.clone();
                     let camera_frame_number = self.camera_frame_number;
+                    let low_latency = self.low_latency.clone();
 
                     move |ui| {
+                        ui.checkbox(&mut low_latency.lock().unwrap(), tr!("camera-toolwindow-low-latency"))
+                            .on_hover_text(tr!("camera-toolwindow-low-latency-hover", {
+                                threshold: formatter().duration(LOW_LATENCY_THRESHOLD)
+                            }));
                         egui::ScrollArea::both()
                             .id_salt(ui.id().with("tool-window-scroll"))
                             .show(ui, |ui| {
@@ -174,3 +368,33 @@ impl CameraUi {
         });
     }
 }
+
+fn format_coordinates(coordinates: Point) -> String {
+    let formatter = formatter();
+    tr!("camera-overlay-coordinates", {
+        x: formatter.length(coordinates.x, 3),
+        y: formatter.length(coordinates.y, 3)
+    })
+}
+
+/// Copies the coordinates, in millimeters, as a position for the configuration of the server, e.g. a park position,
+/// or as plain numbers for any other field.
+fn coordinates_menu_ui(ui: &mut Ui, coordinates: Point) {
+    ui.label(format_coordinates(coordinates));
+    ui.separator();
+    if ui
+        .button(tr!("camera-menu-copy-position"))
+        .on_hover_text(tr!("camera-menu-copy-position-hover"))
+        .clicked()
+    {
+        ui.ctx()
+            .copy_text(format!("(x: {:.3}, y: {:.3})", coordinates.x, coordinates.y));
+    }
+    if ui
+        .button(tr!("camera-menu-copy-coordinates"))
+        .clicked()
+    {
+        ui.ctx()
+            .copy_text(format!("{:.3}, {:.3}", coordinates.x, coordinates.y));
+    }
+}
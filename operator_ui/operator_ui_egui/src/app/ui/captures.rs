@@ -0,0 +1,479 @@
+use std::collections::{BTreeMap, HashMap};
+
+use egui::{ColorImage, Context, Pos2, Rect, RichText, Stroke, TextureHandle, Ui, Vec2, Widget};
+use egui_i18n::tr;
+use egui_mobius::Value;
+use ergot::Address;
+use ergot::toolkits::tokio_udp::EdgeStack;
+use image::ImageFormat;
+use operator_shared::captures::{AnnotationShape, CaptureAnnotation, CaptureEntry, CaptureKey};
+use tokio::runtime::Handle;
+use tracing::{error, info};
+
+use crate::net::commands::{fetch_capture, fetch_capture_annotations, fetch_capture_thumbnail, list_captures_page};
+use crate::ui_common::style::{Status, status_color};
+
+const THUMBNAIL_SIZE: Vec2 = Vec2::new(160.0, 120.0);
+
+/// Browser for the captures saved by the server, a page at a time, grouped by job and placement.
+///
+/// Only the thumbnails of the listed page are fetched, the full capture and its annotations are fetched when a
+/// capture is selected.
+#[derive(Default)]
+pub(crate) struct CapturesUi {
+    client: Option<CapturesClient>,
+    state: Value<CapturesState>,
+    /// the textures of the thumbnails of the listed page
+    textures: HashMap<CaptureKey, TextureHandle>,
+    /// the texture of the selected capture
+    image_texture: Option<(CaptureKey, TextureHandle)>,
+    selected: Option<CaptureKey>,
+}
+
+struct CapturesClient {
+    stack: EdgeStack,
+    address: Address,
+    runtime: Handle,
+}
+
+#[derive(Default)]
+struct CapturesState {
+    loading: bool,
+    error: Option<String>,
+    /// incremented for each page that is listed, so that the thumbnails of a previous page are discarded
+    page_request: u64,
+    /// the offset of the listed page in the capture list
+    offset: u32,
+    /// the number of captures in the list
+    total: u32,
+    page_size: u32,
+    entries: Vec<CaptureEntry>,
+    /// `None` for a capture without a thumbnail, e.g. it couldn't be decoded by the server
+    thumbnails: HashMap<CaptureKey, Option<ColorImage>>,
+    /// the selected capture
+    image: Option<(CaptureKey, ColorImage)>,
+    annotations: Option<(CaptureKey, Vec<CaptureAnnotation>)>,
+}
+
+impl CapturesUi {
+    /// Must be called from within the tokio runtime.
+    pub fn connect(&mut self, stack: EdgeStack, address: Address) {
+        self.client = Some(CapturesClient {
+            stack,
+            address,
+            runtime: Handle::current(),
+        });
+    }
+
+    /// Lists the page of the capture list at `offset`, then fetches the thumbnails of the page.
+    fn load_page(&mut self, context: &Context, offset: u32) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.textures.clear();
+        let page_request = {
+            let mut state = self.state.lock().unwrap();
+            let page_request = state.page_request + 1;
+            *state = CapturesState {
+                loading: true,
+                page_request,
+                offset,
+                total: state.total,
+                page_size: state.page_size,
+                ..Default::default()
+            };
+            page_request
+        };
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let page = match list_captures_page(stack.clone(), address, offset).await {
+                Ok(page) => page,
+                Err(e) => {
+                    error!("Unable to list captures: {:?}", e);
+                    let mut state = state.lock().unwrap();
+                    if state.page_request == page_request {
+                        state.loading = false;
+                        state.error = Some(format!("{}", e));
+                    }
+                    context.request_repaint();
+                    return;
+                }
+            };
+            info!(
+                "Captures listed. offset: {}, count: {}, total: {}",
+                offset,
+                page.entries.len(),
+                page.total
+            );
+
+            {
+                let mut state = state.lock().unwrap();
+                if state.page_request != page_request {
+                    return;
+                }
+                state.total = page.total;
+                // the first page gives the page size of the server
+                state.page_size = state
+                    .page_size
+                    .max(page.entries.len() as u32);
+                state.entries = page.entries.clone();
+            }
+            context.request_repaint();
+
+            // fetched one at a time, so the operator command endpoint isn't flooded
+            for entry in page.entries {
+                let thumbnail = fetch_capture_thumbnail(stack.clone(), address, &entry.key)
+                    .await
+                    .and_then(|jpeg_bytes| decode(&jpeg_bytes))
+                    .inspect_err(|e| error!("Unable to fetch capture thumbnail. key: {}, error: {:?}", entry.key, e))
+                    .ok();
+
+                let mut state = state.lock().unwrap();
+                if state.page_request != page_request {
+                    return;
+                }
+                state
+                    .thumbnails
+                    .insert(entry.key, thumbnail);
+                context.request_repaint();
+            }
+
+            state.lock().unwrap().loading = false;
+            context.request_repaint();
+        });
+    }
+
+    /// Fetches the full capture and its annotations.
+    fn select(&mut self, context: &Context, key: CaptureKey) {
+        self.selected = Some(key.clone());
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        {
+            let mut state = self.state.lock().unwrap();
+            if state
+                .image
+                .as_ref()
+                .is_some_and(|(image_key, _)| *image_key == key)
+            {
+                return;
+            }
+            state.image = None;
+            state.annotations = None;
+        }
+        self.image_texture = None;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let image = fetch_capture(stack.clone(), address, &key)
+                .await
+                .and_then(|jpeg_bytes| decode(&jpeg_bytes));
+            let annotations = fetch_capture_annotations(stack.clone(), address, &key).await;
+
+            let mut state = state.lock().unwrap();
+            match image {
+                Ok(image) => state.image = Some((key.clone(), image)),
+                Err(e) => {
+                    error!("Unable to fetch capture. key: {}, error: {:?}", key, e);
+                    state.error = Some(format!("{}", e));
+                }
+            }
+            match annotations {
+                Ok(annotations) => state.annotations = Some((key, annotations)),
+                Err(e) => error!("Unable to fetch capture annotations. key: {}, error: {:?}", key, e),
+            }
+            context.request_repaint();
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let state = self.state.clone();
+        let state = state.lock().unwrap();
+
+        // upload the textures of fetched images
+        for (key, thumbnail) in state.thumbnails.iter() {
+            if let Some(image) = thumbnail
+                && !self.textures.contains_key(key)
+            {
+                let texture =
+                    ui.ctx()
+                        .load_texture(format!("capture-thumbnail-{}", key), image.clone(), Default::default());
+                self.textures
+                    .insert(key.clone(), texture);
+            }
+        }
+        if let Some((key, image)) = &state.image
+            && self
+                .image_texture
+                .as_ref()
+                .is_none_or(|(texture_key, _)| texture_key != key)
+        {
+            let texture = ui
+                .ctx()
+                .load_texture(format!("capture-{}", key), image.clone(), Default::default());
+            self.image_texture = Some((key.clone(), texture));
+        }
+
+        let mut load_offset = None;
+        ui.horizontal(|ui| {
+            let enabled = self.client.is_some() && !state.loading;
+            if ui
+                .add_enabled(enabled, egui::Button::new(tr!("captures-button-refresh")))
+                .clicked()
+            {
+                load_offset = Some(state.offset);
+            }
+
+            if state.total > 0 {
+                let page_size = state.page_size.max(1);
+                if ui
+                    .add_enabled(
+                        enabled && state.offset > 0,
+                        egui::Button::new(tr!("captures-button-previous")),
+                    )
+                    .clicked()
+                {
+                    load_offset = Some(state.offset.saturating_sub(page_size));
+                }
+                let first = state.offset + 1;
+                let last = state.offset + state.entries.len() as u32;
+                ui.label(tr!("captures-label-page", { first: first, last: last, total: state.total }));
+                if ui
+                    .add_enabled(
+                        enabled && last < state.total,
+                        egui::Button::new(tr!("captures-button-next")),
+                    )
+                    .clicked()
+                {
+                    load_offset = Some(state.offset + page_size);
+                }
+            }
+
+            if state.loading {
+                ui.spinner();
+            }
+            if let Some(error) = &state.error {
+                ui.label(RichText::new(error).color(status_color(Status::Fault)));
+            }
+        });
+
+        if self.client.is_none() {
+            ui.label(tr!("captures-message-waiting"));
+            return;
+        }
+
+        let mut selection = None;
+        match self.selected.clone() {
+            Some(key) => self.capture_ui(ui, &state, &key),
+            None => selection = self.browser_ui(ui, &state),
+        }
+
+        drop(state);
+        if let Some(offset) = load_offset {
+            self.selected = None;
+            self.load_page(ui.ctx(), offset);
+        }
+        if let Some(key) = selection {
+            self.select(ui.ctx(), key);
+        }
+    }
+
+    /// Returns the capture that was clicked.
+    fn browser_ui(&self, ui: &mut Ui, state: &CapturesState) -> Option<CaptureKey> {
+        if state.entries.is_empty() && !state.loading {
+            ui.label(tr!("captures-message-empty"));
+            return None;
+        }
+
+        let mut jobs: BTreeMap<&str, BTreeMap<&str, Vec<&CaptureEntry>>> = BTreeMap::new();
+        for entry in state.entries.iter() {
+            jobs.entry(&entry.key.job)
+                .or_default()
+                .entry(&entry.key.placement)
+                .or_default()
+                .push(entry);
+        }
+
+        let mut selection = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for (job, placements) in jobs {
+                    egui::CollapsingHeader::new(job)
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            for (placement, entries) in placements {
+                                egui::CollapsingHeader::new(placement)
+                                    .id_salt((job, placement))
+                                    .default_open(true)
+                                    .show(ui, |ui| {
+                                        ui.horizontal_wrapped(|ui| {
+                                            for entry in entries {
+                                                if self.thumbnail_ui(ui, state, entry) {
+                                                    selection = Some(entry.key.clone());
+                                                }
+                                            }
+                                        });
+                                    });
+                            }
+                        });
+                }
+            });
+        selection
+    }
+
+    /// Returns `true` if the thumbnail was clicked.
+    fn thumbnail_ui(&self, ui: &mut Ui, state: &CapturesState, entry: &CaptureEntry) -> bool {
+        let hover_text = format!(
+            "{}",
+            entry
+                .saved_at
+                .format("%Y-%m-%d %H:%M:%S")
+        );
+        ui.vertical(|ui| {
+            ui.set_width(THUMBNAIL_SIZE.x);
+            let clicked = match (self.textures.get(&entry.key), state.thumbnails.get(&entry.key)) {
+                (Some(texture), _) => egui::ImageButton::new(
+                    egui::Image::new(texture)
+                        .max_size(THUMBNAIL_SIZE)
+                        .maintain_aspect_ratio(true),
+                )
+                .ui(ui)
+                .on_hover_text(hover_text)
+                .clicked(),
+                // the capture may still be viewable without a thumbnail
+                (None, Some(None)) => ui
+                    .add_sized(THUMBNAIL_SIZE, egui::Button::new(tr!("captures-message-no-thumbnail")))
+                    .on_hover_text(hover_text)
+                    .clicked(),
+                _ => {
+                    ui.allocate_ui(THUMBNAIL_SIZE, |ui| {
+                        ui.centered_and_justified(|ui| ui.spinner());
+                    });
+                    false
+                }
+            };
+            ui.label(&entry.key.stage);
+            clicked
+        })
+        .inner
+    }
+
+    fn capture_ui(&mut self, ui: &mut Ui, state: &CapturesState, key: &CaptureKey) {
+        ui.horizontal(|ui| {
+            if ui
+                .button(tr!("captures-button-back"))
+                .clicked()
+            {
+                self.selected = None;
+            }
+            ui.label(format!("{}", key));
+        });
+
+        let (Some((_, texture)), Some((_, image))) = (
+            self.image_texture
+                .as_ref()
+                .filter(|(texture_key, _)| texture_key == key),
+            state
+                .image
+                .as_ref()
+                .filter(|(image_key, _)| image_key == key),
+        ) else {
+            ui.spinner();
+            return;
+        };
+
+        let response = egui::Image::new(texture)
+            .max_size(ui.available_size())
+            .maintain_aspect_ratio(true)
+            .ui(ui);
+
+        let Some((_, annotations)) = state
+            .annotations
+            .as_ref()
+            .filter(|(annotations_key, _)| annotations_key == key)
+        else {
+            return;
+        };
+
+        let rect = response.rect;
+        let scale = rect.width() / image.size[0] as f32;
+        let to_screen = |x: f32, y: f32| rect.min + Vec2::new(x, y) * scale;
+
+        let painter = ui.painter_at(rect);
+        for annotation in annotations {
+            let color = match annotation.passed {
+                true => status_color(Status::Ok),
+                false => status_color(Status::Fault),
+            };
+            let stroke = Stroke::new(2.0, color);
+
+            let label_position: Pos2 = match annotation.shape {
+                AnnotationShape::Point {
+                    x,
+                    y,
+                } => {
+                    let center = to_screen(x, y);
+                    painter.line_segment([center - Vec2::X * 6.0, center + Vec2::X * 6.0], stroke);
+                    painter.line_segment([center - Vec2::Y * 6.0, center + Vec2::Y * 6.0], stroke);
+                    center
+                }
+                AnnotationShape::Line {
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                } => {
+                    painter.line_segment([to_screen(x1, y1), to_screen(x2, y2)], stroke);
+                    to_screen(x2, y2)
+                }
+                AnnotationShape::Rectangle {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => {
+                    let min = to_screen(x, y);
+                    let annotation_rect = Rect::from_min_size(min, Vec2::new(width, height) * scale);
+                    painter.rect_stroke(annotation_rect, 0.0, stroke, egui::StrokeKind::Middle);
+                    min
+                }
+                AnnotationShape::Circle {
+                    x,
+                    y,
+                    radius,
+                } => {
+                    let center = to_screen(x, y);
+                    painter.circle_stroke(center, radius * scale, stroke);
+                    center
+                }
+            };
+
+            if let Some(label) = &annotation.label {
+                painter.text(
+                    label_position + Vec2::new(8.0, -8.0),
+                    egui::Align2::LEFT_BOTTOM,
+                    label,
+                    egui::FontId::default(),
+                    color,
+                );
+            }
+        }
+    }
+}
+
+fn decode(jpeg_bytes: &[u8]) -> anyhow::Result<ColorImage> {
+    let image = image::load_from_memory_with_format(jpeg_bytes, ImageFormat::Jpeg)?;
+    let rgba = image.to_rgba8();
+    let (w, h) = (rgba.width() as usize, rgba.height() as usize);
+    Ok(ColorImage::from_rgba_unmultiplied([w, h], &rgba.into_raw()))
+}
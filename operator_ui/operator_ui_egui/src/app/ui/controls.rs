@@ -1,15 +1,32 @@
+use std::time::{Duration, Instant};
+
+use command_pacing::RateLimiter;
 use egui::{Ui, Vec2};
 use egui_i18n::tr;
+use tracing::debug;
+
+/// Changes of the speed are sent at most this often while the slider is dragged.
+const SPEED_SCALE_INTERVAL: Duration = Duration::from_millis(100);
 
-#[derive(Default)]
 pub(crate) struct ControlsUi {
     /// Range: 0.0 to 1.0
     speed_scale: f32,
+    speed_scale_limiter: RateLimiter<f32>,
 
     // XXX
     layout_fail: LayoutFail,
 }
 
+impl Default for ControlsUi {
+    fn default() -> Self {
+        Self {
+            speed_scale: 0.0,
+            speed_scale_limiter: RateLimiter::new(SPEED_SCALE_INTERVAL),
+            layout_fail: LayoutFail::default(),
+        }
+    }
+}
+
 // XXX
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum LayoutFail {
@@ -137,14 +154,33 @@ impl ControlsUi {
 
                 ui.horizontal(|ui| {
                     ui.label("Speed %");
-                    ui.add(
+                    let response = ui.add(
                         egui::Slider::new(&mut self.speed_scale, 0.0..=1.0)
                             .custom_formatter(|it, _range| format!("{:3.0}", it * 100.0)),
                     );
+                    self.pace_speed_scale(ui, response.changed());
                 });
             });
     }
 
+    /// The last value is sent once the interval has passed, even if the slider is no longer changing.
+    fn pace_speed_scale(&mut self, ui: &Ui, changed: bool) {
+        let now = Instant::now();
+        let speed_scale = match changed {
+            true => self
+                .speed_scale_limiter
+                .push(self.speed_scale, now),
+            false => self.speed_scale_limiter.poll(now),
+        };
+        if let Some(speed_scale) = speed_scale {
+            // TODO send the speed to the server, there is no command for it yet
+            debug!("Speed scale changed. speed_scale: {}", speed_scale);
+        }
+        if let Some(delay) = self.speed_scale_limiter.delay(now) {
+            ui.ctx().request_repaint_after(delay);
+        }
+    }
+
     fn draw_jogxy_grid(ui: &mut Ui) {
         #[repr(usize)]
         enum JogDirection {
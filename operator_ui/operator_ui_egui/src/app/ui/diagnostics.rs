@@ -1,10 +1,214 @@
-use egui::Ui;
+use std::time::Duration;
+
+use egui::{Color32, RichText, Ui};
+use egui_i18n::tr;
+use operator_shared::diagnostics::{
+    CameraMemoryReport, CommandLatencyReport, LATENCY_BUCKET_LIMITS_US, RouterNodeReport, RouterReport,
+};
+
+use crate::ui_common::style::{Status, status_color};
+use crate::ui_common::units::formatter;
 
 #[derive(Default)]
-pub(crate) struct DiagnosticsUi {}
+pub(crate) struct DiagnosticsUi {
+    command_latency: Option<CommandLatencyReport>,
+    camera_memory: Option<CameraMemoryReport>,
+    router: Option<RouterReport>,
+}
 
 impl DiagnosticsUi {
+    pub fn update_command_latency(&mut self, report: CommandLatencyReport) {
+        self.command_latency = Some(report);
+    }
+
+    pub fn update_camera_memory(&mut self, report: CameraMemoryReport) {
+        self.camera_memory = Some(report);
+    }
+
+    pub fn update_router_report(&mut self, report: RouterReport) {
+        self.router = Some(report);
+    }
+
     pub fn ui(&mut self, ui: &mut Ui) {
-        ui.label("Diagnostics content");
+        self.command_latency_ui(ui);
+        ui.separator();
+        self.camera_memory_ui(ui);
+        ui.separator();
+        self.router_ui(ui);
+    }
+
+    fn command_latency_ui(&mut self, ui: &mut Ui) {
+        ui.heading(tr!("diagnostics-command-latency-heading"));
+
+        let Some(report) = &self.command_latency else {
+            ui.label(tr!("diagnostics-command-latency-waiting"));
+            return;
+        };
+
+        if report.alarm {
+            ui.label(
+                RichText::new(tr!("diagnostics-command-latency-alarm", {limit: format_us(report.p99_limit_us)}))
+                    .color(status_color(Status::Fault)),
+            );
+        }
+
+        egui::Grid::new("command_latency")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(tr!("diagnostics-command-latency-samples"));
+                ui.label(format!("{}", report.samples));
+                ui.end_row();
+
+                ui.label(tr!("diagnostics-command-latency-lost"));
+                let lost_color = match report.lost {
+                    0 => ui.visuals().text_color(),
+                    _ => status_color(Status::Warn),
+                };
+                ui.label(RichText::new(format!("{}", report.lost)).color(lost_color));
+                ui.end_row();
+
+                ui.label(tr!("diagnostics-command-latency-p50"));
+                ui.label(format_us(report.p50_us));
+                ui.end_row();
+
+                ui.label(tr!("diagnostics-command-latency-p99"));
+                let p99_color = match report.alarm {
+                    true => status_color(Status::Fault),
+                    false => ui.visuals().text_color(),
+                };
+                ui.label(RichText::new(format_us(report.p99_us)).color(p99_color));
+                ui.end_row();
+
+                ui.label(tr!("diagnostics-command-latency-max"));
+                ui.label(format_us(report.max_us));
+                ui.end_row();
+            });
+
+        ui.separator();
+
+        egui::Grid::new("command_latency_histogram")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for (index, count) in report.buckets.iter().enumerate() {
+                    let bucket = match LATENCY_BUCKET_LIMITS_US.get(index) {
+                        Some(limit) => format!("≤ {}", format_us(*limit)),
+                        None => format!("> {}", format_us(*LATENCY_BUCKET_LIMITS_US.last().unwrap())),
+                    };
+                    ui.label(bucket);
+                    ui.label(format!("{}", count));
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn camera_memory_ui(&mut self, ui: &mut Ui) {
+        ui.heading(tr!("diagnostics-camera-memory-heading"));
+
+        let Some(report) = &self.camera_memory else {
+            ui.label(tr!("diagnostics-camera-memory-waiting"));
+            return;
+        };
+
+        egui::Grid::new("camera_memory")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(tr!("diagnostics-camera-memory-camera"));
+                ui.label(tr!("diagnostics-camera-memory-used"));
+                ui.label(tr!("diagnostics-camera-memory-dropped"));
+                ui.label(tr!("diagnostics-camera-memory-evicted"));
+                ui.end_row();
+
+                for usage in &report.cameras {
+                    ui.label(format!("{}", usage.camera));
+                    ui.label(format_usage(usage.bytes, usage.limit_bytes));
+                    let dropped_color = match usage.dropped_frames + usage.evicted_frames {
+                        0 => ui.visuals().text_color(),
+                        _ => status_color(Status::Warn),
+                    };
+                    ui.label(RichText::new(format!("{}", usage.dropped_frames)).color(dropped_color));
+                    ui.label(RichText::new(format!("{}", usage.evicted_frames)).color(dropped_color));
+                    ui.end_row();
+                }
+
+                ui.label(tr!("diagnostics-camera-memory-total"));
+                ui.label(format_usage(report.total_bytes, report.total_limit_bytes));
+                ui.end_row();
+            });
     }
+
+    /// The nodes reachable through each interface of the server's router, for finding out why a node, e.g. an io
+    /// board, doesn't respond.
+    fn router_ui(&mut self, ui: &mut Ui) {
+        ui.heading(tr!("diagnostics-router-heading"));
+
+        let Some(report) = &self.router else {
+            ui.label(tr!("diagnostics-router-waiting"));
+            return;
+        };
+
+        if report.interfaces.is_empty() {
+            ui.label(RichText::new(tr!("diagnostics-router-no-nodes")).color(status_color(Status::Warn)));
+            return;
+        }
+
+        for interface in &report.interfaces {
+            ui.label(tr!("diagnostics-router-interface", {
+                network: interface.network_id,
+                answered: interface.answered,
+                lost: interface.lost
+            }));
+
+            egui::Grid::new(("router_interface", interface.network_id))
+                .num_columns(6)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label(tr!("diagnostics-router-node"));
+                    ui.label(tr!("diagnostics-router-rtt"));
+                    ui.label(tr!("diagnostics-router-max-rtt"));
+                    ui.label(tr!("diagnostics-router-pings"));
+                    ui.label(tr!("diagnostics-router-lost"));
+                    ui.label(tr!("diagnostics-router-lost-in-a-row"));
+                    ui.end_row();
+
+                    for node in &interface.nodes {
+                        let color = node_color(ui, node);
+                        ui.label(RichText::new(format!("{}.{}", interface.network_id, node.node_id)).color(color));
+                        let rtt = match node.rtt_us {
+                            Some(rtt_us) => format_us(rtt_us),
+                            None => tr!("diagnostics-router-unreachable"),
+                        };
+                        ui.label(RichText::new(rtt).color(color));
+                        ui.label(format_us(node.max_rtt_us));
+                        ui.label(format!("{}", node.pings));
+                        ui.label(format!("{}", node.lost));
+                        ui.label(RichText::new(format!("{}", node.lost_in_a_row)).color(color));
+                        ui.end_row();
+                    }
+                });
+        }
+    }
+}
+
+/// Red for a node that stopped answering, orange for one that lost the last ping.
+fn node_color(ui: &Ui, node: &RouterNodeReport) -> Color32 {
+    match node.lost_in_a_row {
+        0 => ui.visuals().text_color(),
+        1 => status_color(Status::Warn),
+        _ => status_color(Status::Fault),
+    }
+}
+
+fn format_usage(bytes: u64, limit_bytes: u64) -> String {
+    format!("{} / {}", format_mib(bytes), format_mib(limit_bytes))
+}
+
+fn format_mib(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+fn format_us(us: u32) -> String {
+    formatter().duration(Duration::from_micros(u64::from(us)))
 }
@@ -0,0 +1,529 @@
+use std::collections::{HashMap, VecDeque};
+
+use egui::{Context, RichText, Ui};
+use egui_i18n::tr;
+use egui_mobius::Value;
+use ergot::Address;
+use ergot::toolkits::tokio_udp::EdgeStack;
+use machine_ids::FeederId;
+use operator_shared::config::{ConfigChange, ConfigError, ConfigField, ConfigFieldError};
+use operator_shared::feeders::{FeederEvent, FeedersStatus, Stock, TapeOrientation};
+use operator_shared::vision::{OrientationError, ScanError, ScanTarget};
+use tokio::runtime::Handle;
+use tracing::{error, info, warn};
+
+use crate::net::commands::{apply_config, scan_code, verify_feeder_orientation};
+use crate::ui_common::style::{Status, status_color};
+
+/// The number of feeder events that are shown.
+const EVENTS_MAX: usize = 10;
+
+/// The remaining parts of each feeder, the operator can change the count, e.g. after loading a reel, and the low-stock
+/// threshold, and verify the orientation of the tape.
+///
+/// Changes are pending until the operator reviews them and applies them together.
+#[derive(Default)]
+pub(crate) struct FeedersUi {
+    client: Option<FeedersClient>,
+    status: Option<FeedersStatus>,
+    /// the changes being entered, by field
+    edits: HashMap<ConfigField, PendingEdit>,
+    /// most recent first
+    events: VecDeque<FeederEvent>,
+    state: Value<FeedersState>,
+}
+
+struct FeedersClient {
+    stack: EdgeStack,
+    address: Address,
+    runtime: Handle,
+}
+
+#[derive(Default)]
+struct FeedersState {
+    busy: bool,
+    message: Option<RichText>,
+    /// the errors of the edits the server rejected, by field
+    field_errors: HashMap<ConfigField, ConfigFieldError>,
+    /// set when the edits have been applied, the edits are then cleared
+    applied: bool,
+}
+
+#[derive(Clone, Copy)]
+struct PendingEdit {
+    /// the value when the operator started editing it
+    old: u32,
+    new: u32,
+}
+
+impl FeedersUi {
+    /// Must be called from within the tokio runtime.
+    pub fn connect(&mut self, stack: EdgeStack, address: Address) {
+        self.client = Some(FeedersClient {
+            stack,
+            address,
+            runtime: Handle::current(),
+        });
+    }
+
+    pub fn update_status(&mut self, status: FeedersStatus) {
+        self.status = Some(status);
+    }
+
+    pub fn add_event(&mut self, event: FeederEvent) {
+        self.events.push_front(event);
+        self.events.truncate(EVENTS_MAX);
+    }
+
+    /// Applies the pending edits, all of them or none of them, the errors of the rejected edits are shown next to
+    /// their fields.
+    fn apply_edits(&mut self, context: &Context, changes: Vec<ConfigChange>) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.busy = true;
+            state.field_errors.clear();
+        }
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let change_count = changes.len();
+            let result = apply_config(stack, address, changes).await;
+
+            let mut field_errors = HashMap::new();
+            let mut applied = false;
+            let message = match result {
+                Ok(Ok(())) => {
+                    info!("Config changes applied. changes: {}", change_count);
+                    applied = true;
+                    None
+                }
+                Ok(Err(ConfigError::Invalid(errors))) => {
+                    warn!("Config changes rejected. errors: {:?}", errors);
+                    field_errors = errors
+                        .into_iter()
+                        .map(|error| (error.field, error.error))
+                        .collect();
+                    Some(RichText::new(tr!("feeders-message-changes-rejected")).color(status_color(Status::Warn)))
+                }
+                Ok(Err(e)) => {
+                    warn!("Config changes rejected. error: {:?}", e);
+                    Some(
+                        RichText::new(tr!("feeders-message-error", { error: format!("{:?}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+                Err(e) => {
+                    error!("Unable to apply config changes. error: {:?}", e);
+                    Some(
+                        RichText::new(tr!("feeders-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = message;
+            state.field_errors = field_errors;
+            state.applied = applied;
+            context.request_repaint();
+        });
+    }
+
+    /// Scans the ID of the reel loaded into the feeder.
+    fn scan_reel(&mut self, context: &Context, feeder: FeederId) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let target = ScanTarget::Reel {
+                feeder: feeder.clone(),
+            };
+            let result = scan_code(stack, address, target).await;
+
+            let message = match result {
+                Ok(Ok(reel)) => {
+                    info!("Reel scanned. feeder: {}, reel: {}", feeder, reel);
+                    None
+                }
+                Ok(Err(ScanError::UnknownFeeder)) => {
+                    warn!("Reel scan rejected, unknown feeder. feeder: {}", feeder);
+                    Some(
+                        RichText::new(tr!("feeders-message-unknown-feeder", { feeder: feeder.as_str() }))
+                            .color(status_color(Status::Warn)),
+                    )
+                }
+                Ok(Err(e)) => {
+                    warn!("Reel scan failed. feeder: {}, error: {:?}", feeder, e);
+                    Some(
+                        RichText::new(tr!("feeders-message-scan-failed", { error: format!("{:?}", e) }))
+                            .color(status_color(Status::Warn)),
+                    )
+                }
+                Err(e) => {
+                    error!("Unable to scan reel. feeder: {}, error: {:?}", feeder, e);
+                    Some(
+                        RichText::new(tr!("feeders-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = message;
+            context.request_repaint();
+        });
+    }
+
+    /// Verifies the orientation of the tape from the polarity mark of the part in the first pocket.
+    fn verify_orientation(&mut self, context: &Context, feeder: FeederId) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = verify_feeder_orientation(stack, address, feeder.clone()).await;
+
+            let message = match result {
+                Ok(Ok(TapeOrientation::Reversed)) => {
+                    warn!("Feeder tape reversed. feeder: {}", feeder);
+                    Some(
+                        RichText::new(tr!("feeders-message-tape-reversed", { feeder: feeder.as_str() }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+                Ok(Ok(orientation)) => {
+                    info!(
+                        "Feeder orientation verified. feeder: {}, orientation: {:?}",
+                        feeder, orientation
+                    );
+                    None
+                }
+                Ok(Err(OrientationError::UnknownFeeder)) => {
+                    warn!("Orientation verification rejected, unknown feeder. feeder: {}", feeder);
+                    Some(
+                        RichText::new(tr!("feeders-message-unknown-feeder", { feeder: feeder.as_str() }))
+                            .color(status_color(Status::Warn)),
+                    )
+                }
+                Ok(Err(e)) => {
+                    warn!("Orientation verification failed. feeder: {}, error: {:?}", feeder, e);
+                    Some(
+                        RichText::new(tr!("feeders-message-verify-failed", { error: format!("{:?}", e) }))
+                            .color(status_color(Status::Warn)),
+                    )
+                }
+                Err(e) => {
+                    error!("Unable to verify orientation. feeder: {}, error: {:?}", feeder, e);
+                    Some(
+                        RichText::new(tr!("feeders-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = message;
+            context.request_repaint();
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let Some(status) = &self.status else {
+            ui.label(tr!("feeders-message-waiting"));
+            return;
+        };
+        if status.feeders.is_empty() {
+            ui.label(tr!("feeders-message-none"));
+            return;
+        }
+
+        let (busy, message, field_errors) = {
+            let mut state = self.state.lock().unwrap();
+            if std::mem::take(&mut state.applied) {
+                self.edits.clear();
+            }
+            (state.busy, state.message.clone(), state.field_errors.clone())
+        };
+        let connected = self.client.is_some();
+
+        let mut scan_clicked = None;
+        let mut verify_clicked = None;
+        egui::Grid::new("feeders")
+            .num_columns(8)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(tr!("feeders-column-feeder"));
+                ui.label(tr!("feeders-column-count"));
+                ui.label(tr!("feeders-column-low-stock-threshold"));
+                ui.label(tr!("feeders-column-stock"));
+                ui.label(tr!("feeders-column-reel"));
+                ui.label(tr!("feeders-column-slot"));
+                ui.label(tr!("feeders-column-orientation"));
+                ui.label("");
+                ui.end_row();
+
+                for feeder in status.feeders.iter() {
+                    let (text, color) = match feeder.stock {
+                        Stock::Ok => (tr!("feeders-stock-ok"), ui.visuals().text_color()),
+                        Stock::Low => (tr!("feeders-stock-low"), status_color(Status::Warn)),
+                        Stock::Out => (tr!("feeders-stock-out"), status_color(Status::Fault)),
+                    };
+
+                    ui.label(feeder.name.as_str());
+                    field_ui(
+                        ui,
+                        &mut self.edits,
+                        &field_errors,
+                        ConfigField::FeederCount(feeder.name.clone()),
+                        feeder.count,
+                        !busy,
+                    );
+                    field_ui(
+                        ui,
+                        &mut self.edits,
+                        &field_errors,
+                        ConfigField::FeederLowStockThreshold(feeder.name.clone()),
+                        feeder.low_stock_threshold,
+                        !busy,
+                    );
+                    ui.label(RichText::new(text).color(color));
+                    ui.label(feeder.reel.as_deref().unwrap_or("-"));
+                    match feeder.slot {
+                        Some(slot) => ui.label(slot.to_string()),
+                        None => ui.label("-"),
+                    };
+                    match feeder.orientation {
+                        None => ui.label("-"),
+                        Some(TapeOrientation::Unverified) => ui.label(
+                            RichText::new(tr!("feeders-orientation-unverified")).color(status_color(Status::Warn)),
+                        ),
+                        Some(TapeOrientation::Correct) => ui.label(tr!("feeders-orientation-correct")),
+                        Some(TapeOrientation::Reversed) => ui.label(
+                            RichText::new(tr!("feeders-orientation-reversed")).color(status_color(Status::Fault)),
+                        ),
+                    };
+
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(connected && !busy, egui::Button::new(tr!("feeders-button-scan-reel")))
+                            .clicked()
+                        {
+                            scan_clicked = Some(feeder.name.clone());
+                        }
+                        if feeder.orientation.is_some()
+                            && ui
+                                .add_enabled(connected && !busy, egui::Button::new(tr!("feeders-button-verify")))
+                                .clicked()
+                        {
+                            verify_clicked = Some(feeder.name.clone());
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+
+        let mut apply_clicked = None;
+        let mut discard_clicked = false;
+        if !self.edits.is_empty() {
+            let changes = pending_changes(status, &self.edits);
+
+            ui.separator();
+            ui.heading(tr!("feeders-pending-heading"));
+            egui::Grid::new("feeders_pending")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label(tr!("feeders-pending-column-field"));
+                    ui.label(tr!("feeders-pending-column-old"));
+                    ui.label(tr!("feeders-pending-column-new"));
+                    ui.end_row();
+
+                    for change in changes.iter() {
+                        ui.label(field_name(&change.field));
+                        ui.label(format!("{}", change.old));
+                        ui.label(RichText::new(format!("{}", change.new)).strong());
+                        ui.end_row();
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(connected && !busy, egui::Button::new(tr!("feeders-button-apply")))
+                    .clicked()
+                {
+                    apply_clicked = Some(changes.clone());
+                }
+                if ui
+                    .add_enabled(!busy, egui::Button::new(tr!("feeders-button-discard")))
+                    .clicked()
+                {
+                    discard_clicked = true;
+                }
+            });
+        }
+
+        if !self.events.is_empty() {
+            ui.separator();
+            for event in self.events.iter() {
+                let text = match event {
+                    FeederEvent::LowStock {
+                        feeder,
+                        count,
+                    } => RichText::new(tr!("feeders-event-low-stock", { feeder: feeder.as_str(), count: count }))
+                        .color(status_color(Status::Warn)),
+                    FeederEvent::OutOfStock {
+                        feeder,
+                    } => RichText::new(tr!("feeders-event-out-of-stock", { feeder: feeder.as_str() }))
+                        .color(status_color(Status::Fault)),
+                    FeederEvent::ReversedTape {
+                        feeder,
+                    } => RichText::new(tr!("feeders-event-reversed-tape", { feeder: feeder.as_str() }))
+                        .color(status_color(Status::Fault)),
+                    FeederEvent::Inserted {
+                        feeder,
+                        slot,
+                    } => RichText::new(tr!("feeders-event-inserted", { feeder: feeder.as_str(), slot: slot })),
+                    FeederEvent::Removed {
+                        feeder,
+                        slot,
+                    } => RichText::new(tr!("feeders-event-removed", { feeder: feeder.as_str(), slot: slot })),
+                    FeederEvent::UnknownInserted {
+                        slot,
+                        identity,
+                    } => RichText::new(tr!("feeders-event-unknown-inserted", {
+                        slot: slot,
+                        identity: format!("{:012x}", identity)
+                    }))
+                    .color(status_color(Status::Warn)),
+                };
+                ui.label(text);
+            }
+        }
+
+        if let Some(message) = message {
+            ui.label(message);
+        }
+
+        if let Some(changes) = apply_clicked {
+            self.apply_edits(ui.ctx(), changes);
+        }
+        if discard_clicked {
+            self.edits.clear();
+            let mut state = self.state.lock().unwrap();
+            state.field_errors.clear();
+            state.message = None;
+        }
+        if let Some(feeder) = scan_clicked {
+            self.scan_reel(ui.ctx(), feeder);
+        }
+        if let Some(feeder) = verify_clicked {
+            self.verify_orientation(ui.ctx(), feeder);
+        }
+    }
+}
+
+/// An editable value, the value it was changed from is shown while the change is pending, and the error of the server
+/// if the change was rejected.
+fn field_ui(
+    ui: &mut Ui,
+    edits: &mut HashMap<ConfigField, PendingEdit>,
+    field_errors: &HashMap<ConfigField, ConfigFieldError>,
+    field: ConfigField,
+    current: u32,
+    enabled: bool,
+) {
+    let edit = edits.get(&field).copied();
+    let mut value = edit.map_or(current, |edit| edit.new);
+
+    ui.horizontal(|ui| {
+        let response = ui.add_enabled(enabled, egui::DragValue::new(&mut value));
+        if let Some(edit) = edit {
+            ui.label(RichText::new(tr!("feeders-field-was", { value: edit.old })).weak());
+        }
+        if let Some(error) = field_errors.get(&field) {
+            ui.label(RichText::new(field_error_text(error)).color(status_color(Status::Fault)));
+        }
+
+        if response.changed() {
+            let old = edit.map_or(current, |edit| edit.old);
+            match value == old {
+                true => {
+                    edits.remove(&field);
+                }
+                false => {
+                    edits.insert(field, PendingEdit {
+                        old,
+                        new: value,
+                    });
+                }
+            }
+        }
+    });
+}
+
+/// In the order the feeders are shown.
+fn pending_changes(status: &FeedersStatus, edits: &HashMap<ConfigField, PendingEdit>) -> Vec<ConfigChange> {
+    status
+        .feeders
+        .iter()
+        .flat_map(|feeder| {
+            [
+                ConfigField::FeederCount(feeder.name.clone()),
+                ConfigField::FeederLowStockThreshold(feeder.name.clone()),
+            ]
+        })
+        .filter_map(|field| {
+            let edit = edits.get(&field)?;
+            Some(ConfigChange {
+                field,
+                old: edit.old,
+                new: edit.new,
+            })
+        })
+        .collect()
+}
+
+fn field_name(field: &ConfigField) -> String {
+    match field {
+        ConfigField::FeederCount(feeder) => tr!("feeders-field-count", { feeder: feeder.as_str() }),
+        ConfigField::FeederLowStockThreshold(feeder) => {
+            tr!("feeders-field-low-stock-threshold", { feeder: feeder.as_str() })
+        }
+    }
+}
+
+fn field_error_text(error: &ConfigFieldError) -> String {
+    match *error {
+        ConfigFieldError::UnknownFeeder => tr!("feeders-field-error-unknown-feeder"),
+        ConfigFieldError::Duplicate => tr!("feeders-field-error-duplicate"),
+        ConfigFieldError::OutOfRange {
+            max,
+        } => tr!("feeders-field-error-out-of-range", { max: max }),
+        ConfigFieldError::Changed {
+            current,
+        } => tr!("feeders-field-error-changed", { current: current }),
+    }
+}
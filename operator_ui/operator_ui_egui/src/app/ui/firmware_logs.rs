@@ -0,0 +1,243 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+use egui::{Color32, RichText, TextStyle, TextWrapMode, Ui};
+use egui_i18n::tr;
+use ergot::Address;
+use ergot::fmtlog::Level;
+
+use crate::ui_common::style::{Status, status_color};
+
+/// The number of log lines that are kept, the oldest lines are discarded first.
+const SCROLLBACK_MAX: usize = 5000;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    #[default]
+    Trace,
+}
+
+impl From<&Level> for LogLevel {
+    fn from(level: &Level) -> Self {
+        match level {
+            Level::Error => LogLevel::Error,
+            Level::Warn => LogLevel::Warn,
+            Level::Info => LogLevel::Info,
+            Level::Debug => LogLevel::Debug,
+            Level::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+impl LogLevel {
+    const ALL: [LogLevel; 5] = [
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Trace,
+    ];
+
+    fn text(&self) -> String {
+        match self {
+            LogLevel::Error => tr!("firmware-logs-level-error"),
+            LogLevel::Warn => tr!("firmware-logs-level-warn"),
+            LogLevel::Info => tr!("firmware-logs-level-info"),
+            LogLevel::Debug => tr!("firmware-logs-level-debug"),
+            LogLevel::Trace => tr!("firmware-logs-level-trace"),
+        }
+    }
+
+    fn color(&self, ui: &Ui) -> Color32 {
+        match self {
+            LogLevel::Error => status_color(Status::Fault),
+            LogLevel::Warn => status_color(Status::Warn),
+            LogLevel::Info => ui.visuals().text_color(),
+            LogLevel::Debug => Color32::LIGHT_BLUE,
+            LogLevel::Trace => Color32::GRAY,
+        }
+    }
+}
+
+struct LogLine {
+    received_at: DateTime<Local>,
+    board: Address,
+    level: LogLevel,
+    message: String,
+}
+
+/// The log messages of the io boards, forwarded by the server, so that firmware can be debugged without a serial
+/// console.
+#[derive(Default)]
+pub(crate) struct FirmwareLogsUi {
+    /// oldest first
+    lines: VecDeque<LogLine>,
+    /// lines received while paused, oldest first, added to the scrollback when resumed
+    pending: VecDeque<LogLine>,
+    paused: bool,
+    /// every board that sent a log message, in the order they were first seen
+    boards: Vec<Address>,
+
+    // filters
+    text_filter: String,
+    /// lines less severe than this level are hidden
+    max_level: LogLevel,
+    /// `None` for all boards
+    board_filter: Option<Address>,
+}
+
+impl FirmwareLogsUi {
+    pub fn add_line(&mut self, board: Address, level: &Level, message: String) {
+        if !self.boards.contains(&board) {
+            self.boards.push(board);
+        }
+
+        let line = LogLine {
+            received_at: Local::now(),
+            board,
+            level: level.into(),
+            message,
+        };
+
+        let lines = match self.paused {
+            true => &mut self.pending,
+            false => &mut self.lines,
+        };
+        lines.push_back(line);
+        if lines.len() > SCROLLBACK_MAX {
+            lines.pop_front();
+        }
+    }
+
+    fn resume(&mut self) {
+        self.paused = false;
+        self.lines.append(&mut self.pending);
+        let excess = self
+            .lines
+            .len()
+            .saturating_sub(SCROLLBACK_MAX);
+        self.lines.drain(..excess);
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        self.controls_ui(ui);
+        ui.separator();
+
+        if self.lines.is_empty() {
+            ui.label(tr!("firmware-logs-message-waiting"));
+            return;
+        }
+
+        let text_filter = self.text_filter.to_lowercase();
+        let visible = self
+            .lines
+            .iter()
+            .filter(|line| line.level <= self.max_level)
+            .filter(|line| {
+                self.board_filter
+                    .is_none_or(|board| line.board == board)
+            })
+            .filter(|line| {
+                text_filter.is_empty()
+                    || line
+                        .message
+                        .to_lowercase()
+                        .contains(&text_filter)
+            })
+            .collect::<Vec<_>>();
+
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        egui::ScrollArea::both()
+            .auto_shrink([false, false])
+            .stick_to_bottom(!self.paused)
+            .show_rows(ui, row_height, visible.len(), |ui, row_range| {
+                for line in &visible[row_range] {
+                    let text = format!(
+                        "{} {} {:<5} {}",
+                        line.received_at.format("%H:%M:%S%.3f"),
+                        format_board(&line.board),
+                        line.level.text(),
+                        line.message
+                    );
+                    ui.add(
+                        egui::Label::new(
+                            RichText::new(text)
+                                .monospace()
+                                .color(line.level.color(ui)),
+                        )
+                        .wrap_mode(TextWrapMode::Extend),
+                    );
+                }
+            });
+    }
+
+    fn controls_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            match self.paused {
+                true => {
+                    if ui
+                        .button(tr!("firmware-logs-button-resume", { count: self.pending.len() }))
+                        .clicked()
+                    {
+                        self.resume();
+                    }
+                }
+                false => {
+                    if ui
+                        .button(tr!("firmware-logs-button-pause"))
+                        .clicked()
+                    {
+                        self.paused = true;
+                    }
+                }
+            }
+            if ui
+                .button(tr!("firmware-logs-button-clear"))
+                .clicked()
+            {
+                self.lines.clear();
+                self.pending.clear();
+            }
+
+            ui.separator();
+
+            ui.label(tr!("firmware-logs-label-level"));
+            egui::ComboBox::from_id_salt("firmware_logs_level")
+                .selected_text(self.max_level.text())
+                .show_ui(ui, |ui| {
+                    for level in LogLevel::ALL {
+                        ui.selectable_value(&mut self.max_level, level, level.text());
+                    }
+                });
+
+            ui.label(tr!("firmware-logs-label-board"));
+            let selected_board = match &self.board_filter {
+                Some(board) => format_board(board),
+                None => tr!("firmware-logs-board-all"),
+            };
+            egui::ComboBox::from_id_salt("firmware_logs_board")
+                .selected_text(selected_board)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.board_filter, None, tr!("firmware-logs-board-all"));
+                    for board in &self.boards {
+                        ui.selectable_value(&mut self.board_filter, Some(*board), format_board(board));
+                    }
+                });
+
+            ui.add(
+                egui::TextEdit::singleline(&mut self.text_filter)
+                    .hint_text(tr!("firmware-logs-hint-filter"))
+                    .desired_width(160.0),
+            );
+        });
+    }
+}
+
+/// The network and node of the board, the port is the same for every message from the board.
+fn format_board(address: &Address) -> String {
+    format!("{}.{}", address.network_id, address.node_id)
+}
@@ -0,0 +1,408 @@
+use std::time::Duration;
+
+use egui::{Context, RichText, Ui};
+use egui_i18n::tr;
+use egui_mobius::Value;
+use ergot::Address;
+use ergot::toolkits::tokio_udp::EdgeStack;
+use machine_ids::JobId;
+use operator_shared::job::{
+    EstimateError, Intervention, InterventionError, InterventionResolution, JobEstimate, JobEvent,
+};
+use tokio::runtime::Handle;
+use tracing::{error, info, warn};
+
+use crate::net::commands::{estimate_job, resolve_intervention};
+use crate::ui_common::style::{Status, status_color};
+use crate::ui_common::units::formatter;
+
+/// The progress of the running job, and the interventions the job is waiting for.  Before a job is started its run
+/// time can be estimated.
+#[derive(Default)]
+pub(crate) struct JobUi {
+    client: Option<JobClient>,
+    progress: Option<JobProgress>,
+    state: Value<JobUiState>,
+}
+
+struct JobClient {
+    stack: EdgeStack,
+    address: Address,
+    runtime: Handle,
+}
+
+struct JobProgress {
+    job: JobId,
+    placements: u32,
+    placed: u32,
+    skipped: u32,
+    /// nothing is moved, see `JobEvent::Started`
+    dry_run: bool,
+    phase: JobPhase,
+}
+
+impl JobProgress {
+    fn new(job: JobId, placements: u32) -> Self {
+        Self {
+            job,
+            placements,
+            placed: 0,
+            skipped: 0,
+            dry_run: false,
+            phase: JobPhase::Running,
+        }
+    }
+}
+
+enum JobPhase {
+    Running,
+    Paused(Intervention),
+    Finished,
+    Aborted,
+}
+
+#[derive(Default)]
+struct JobUiState {
+    busy: bool,
+    message: Option<RichText>,
+    estimating: bool,
+    /// of the selected job, when the operator last requested it
+    estimate: Option<JobEstimate>,
+}
+
+impl JobUi {
+    /// Must be called from within the tokio runtime.
+    pub fn connect(&mut self, stack: EdgeStack, address: Address) {
+        self.client = Some(JobClient {
+            stack,
+            address,
+            runtime: Handle::current(),
+        });
+    }
+
+    pub fn update(&mut self, event: JobEvent) {
+        match event {
+            JobEvent::Started {
+                job,
+                placements,
+                placed,
+                skipped,
+                dry_run,
+            } => {
+                self.progress = Some(JobProgress {
+                    placed,
+                    skipped,
+                    dry_run,
+                    ..JobProgress::new(job, placements)
+                });
+                self.state.lock().unwrap().message = None;
+            }
+            JobEvent::Placed {
+                job, ..
+            } => {
+                self.progress_mut(job).placed += 1;
+            }
+            JobEvent::InterventionRequired(intervention) => {
+                self.progress_mut(intervention.job.clone())
+                    .phase = JobPhase::Paused(intervention);
+            }
+            JobEvent::InterventionResolved {
+                id,
+                resolution,
+            } => {
+                let Some(progress) = &mut self.progress else {
+                    return;
+                };
+                if resolution == InterventionResolution::Skip {
+                    progress.skipped += 1;
+                }
+                if matches!(&progress.phase, JobPhase::Paused(intervention) if intervention.id == id) {
+                    progress.phase = JobPhase::Running;
+                }
+            }
+            JobEvent::Finished {
+                job,
+                placed,
+                skipped,
+            } => {
+                let progress = self.progress_mut(job);
+                progress.placed = placed;
+                progress.skipped = skipped;
+                progress.phase = JobPhase::Finished;
+            }
+            JobEvent::Aborted {
+                job,
+                placed,
+                skipped,
+            } => {
+                let progress = self.progress_mut(job);
+                progress.placed = placed;
+                progress.skipped = skipped;
+                progress.phase = JobPhase::Aborted;
+            }
+        }
+    }
+
+    /// The job may have been started before the ui was connected, in which case the number of placements is unknown.
+    fn progress_mut(&mut self, job: JobId) -> &mut JobProgress {
+        self.progress
+            .get_or_insert_with(|| JobProgress::new(job, 0))
+    }
+
+    fn resolve(&mut self, context: &Context, id: u32, resolution: InterventionResolution) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = resolve_intervention(stack, address, id, resolution).await;
+
+            let message = match result {
+                Ok(Ok(())) => {
+                    info!("Intervention resolved. id: {}, resolution: {:?}", id, resolution);
+                    None
+                }
+                Ok(Err(InterventionError::Stale(current))) => {
+                    warn!(
+                        "Intervention resolution rejected, stale. id: {}, current: {}",
+                        id, current
+                    );
+                    Some(RichText::new(tr!("job-message-stale")).color(status_color(Status::Warn)))
+                }
+                Ok(Err(InterventionError::NoIntervention)) => {
+                    warn!("Intervention resolution rejected, no intervention. id: {}", id);
+                    Some(RichText::new(tr!("job-message-no-intervention")).color(status_color(Status::Warn)))
+                }
+                Err(e) => {
+                    error!("Unable to resolve intervention. id: {}, error: {:?}", id, e);
+                    Some(
+                        RichText::new(tr!("job-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = message;
+            context.request_repaint();
+        });
+    }
+
+    fn estimate(&mut self, context: &Context) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().estimating = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = estimate_job(stack, address).await;
+
+            let (estimate, message) = match result {
+                Ok(Ok(estimate)) => {
+                    info!(
+                        "Job estimated. job: {}, duration_ms: {}, placements: {}",
+                        estimate.job,
+                        estimate.duration_ms,
+                        estimate.placements.len()
+                    );
+                    (Some(estimate), None)
+                }
+                Ok(Err(e)) => {
+                    warn!("Job estimate refused. error: {:?}", e);
+                    let text = match e {
+                        EstimateError::NoJob => tr!("job-estimate-no-job"),
+                        EstimateError::UnknownHead(placement) => {
+                            tr!("job-estimate-unknown-head", { placement: placement })
+                        }
+                        EstimateError::Planning(placement) => tr!("job-estimate-planning", { placement: placement }),
+                    };
+                    (None, Some(RichText::new(text).color(status_color(Status::Warn))))
+                }
+                Err(e) => {
+                    error!("Unable to estimate job. error: {:?}", e);
+                    let text = tr!("job-message-error", { error: format!("{}", e) });
+                    (None, Some(RichText::new(text).color(status_color(Status::Fault))))
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            state.estimating = false;
+            state.estimate = estimate;
+            state.message = message;
+            context.request_repaint();
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let running = self
+            .progress
+            .as_ref()
+            .is_some_and(|progress| matches!(progress.phase, JobPhase::Running | JobPhase::Paused(_)));
+        if !running {
+            self.estimate_ui(ui);
+            ui.separator();
+        }
+
+        self.progress_ui(ui);
+    }
+
+    fn estimate_ui(&mut self, ui: &mut Ui) {
+        let (estimating, estimate) = {
+            let state = self.state.lock().unwrap();
+            (state.estimating, state.estimate.clone())
+        };
+        let connected = self.client.is_some();
+
+        let mut estimate_clicked = false;
+        ui.horizontal(|ui| {
+            estimate_clicked = ui
+                .add_enabled(connected && !estimating, egui::Button::new(tr!("job-button-estimate")))
+                .on_hover_text(tr!("job-button-estimate-hover"))
+                .clicked();
+            if estimating {
+                ui.spinner();
+            }
+        });
+
+        if let Some(estimate) = &estimate {
+            let formatter = formatter();
+            let milliseconds = |ms: u64| formatter.duration(Duration::from_millis(ms));
+            ui.label(tr!("job-estimate-duration", {
+                job: estimate.job.as_str(),
+                duration: milliseconds(estimate.duration_ms)
+            }));
+
+            egui::CollapsingHeader::new(tr!("job-estimate-timeline"))
+                .id_salt("job-estimate-timeline")
+                .show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("job-estimate")
+                                .num_columns(4)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.strong(tr!("job-estimate-placement"));
+                                    ui.strong(tr!("job-estimate-start"));
+                                    ui.strong(tr!("job-estimate-move"));
+                                    ui.strong(tr!("job-estimate-operation"));
+                                    ui.end_row();
+
+                                    for placement in estimate.placements.iter() {
+                                        ui.label(&placement.placement);
+                                        ui.label(milliseconds(placement.start_ms));
+                                        ui.label(milliseconds(placement.move_ms));
+                                        ui.label(milliseconds(placement.operation_ms));
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                });
+        }
+
+        if estimate_clicked {
+            self.estimate(ui.ctx());
+        }
+    }
+
+    fn progress_ui(&mut self, ui: &mut Ui) {
+        let (busy, message) = {
+            let state = self.state.lock().unwrap();
+            (state.busy, state.message.clone())
+        };
+
+        let Some(progress) = &self.progress else {
+            ui.label(tr!("job-message-waiting"));
+            // e.g. from estimating the job
+            if let Some(message) = message {
+                ui.label(message);
+            }
+            return;
+        };
+
+        let connected = self.client.is_some();
+
+        egui::Grid::new("job")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label(tr!("job-name"));
+                ui.label(progress.job.as_str());
+                ui.end_row();
+
+                if progress.dry_run {
+                    ui.label(tr!("job-mode"));
+                    ui.label(RichText::new(tr!("job-mode-dry-run")).color(status_color(Status::Warn)));
+                    ui.end_row();
+                }
+
+                ui.label(tr!("job-placed"));
+                ui.label(format!("{} / {}", progress.placed, progress.placements));
+                ui.end_row();
+
+                ui.label(tr!("job-skipped"));
+                ui.label(format!("{}", progress.skipped));
+                ui.end_row();
+
+                ui.label(tr!("job-state"));
+                let (text, color) = match &progress.phase {
+                    JobPhase::Running => (tr!("job-state-running"), ui.visuals().text_color()),
+                    JobPhase::Paused(_) => (tr!("job-state-paused"), status_color(Status::Warn)),
+                    JobPhase::Finished => (tr!("job-state-finished"), ui.visuals().text_color()),
+                    JobPhase::Aborted => (tr!("job-state-aborted"), status_color(Status::Fault)),
+                };
+                ui.label(RichText::new(text).color(color));
+                ui.end_row();
+            });
+
+        let mut resolution_clicked = None;
+        if let JobPhase::Paused(intervention) = &progress.phase {
+            ui.separator();
+            ui.label(
+                RichText::new(tr!("job-intervention-heading", { placement: &intervention.placement }))
+                    .strong()
+                    .color(status_color(Status::Warn)),
+            );
+            ui.label(tr!("job-intervention-error", { error: &intervention.error }));
+
+            ui.horizontal(|ui| {
+                for resolution in intervention.options.iter() {
+                    let text = match resolution {
+                        InterventionResolution::Retry => tr!("job-button-retry"),
+                        InterventionResolution::Skip => tr!("job-button-skip"),
+                        InterventionResolution::Abort => tr!("job-button-abort"),
+                    };
+                    if ui
+                        .add_enabled(connected && !busy, egui::Button::new(text))
+                        .clicked()
+                    {
+                        resolution_clicked = Some((intervention.id, *resolution));
+                    }
+                }
+
+                if busy {
+                    ui.spinner();
+                }
+            });
+        }
+
+        if let Some(message) = message {
+            ui.label(message);
+        }
+
+        if let Some((id, resolution)) = resolution_clicked {
+            self.resolve(ui.ctx(), id, resolution);
+        }
+    }
+}
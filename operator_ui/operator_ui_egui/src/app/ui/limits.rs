@@ -0,0 +1,338 @@
+use std::time::{Duration, Instant};
+
+use egui::{Color32, Context, RichText, Ui};
+use egui_i18n::tr;
+use egui_mobius::Value;
+use ergot::Address;
+use ergot::toolkits::tokio_udp::EdgeStack;
+use operator_shared::limits::{AxisLimit, LimitOverrideError, LimitOverrideRequest, LimitOverrideStatus};
+use tokio::runtime::Handle;
+use tracing::{error, info, warn};
+
+use crate::net::commands::{clear_axis_limit_override, override_axis_limit};
+use crate::ui_common::style::{Status, status_color};
+
+/// Overriding the limits of an axis for maintenance, only for an operator authorized on the server, and only for a
+/// limited time, see [`LimitsUi::banner`].
+pub(crate) struct LimitsUi {
+    client: Option<LimitsClient>,
+    axis: String,
+    limit: AxisLimit,
+    operator: String,
+    /// cleared once sent, so it isn't left in the form
+    access_code: String,
+    reason: String,
+    duration_s: u32,
+    status: LimitOverrideStatus,
+    /// the countdown continues from when the status was received, until the next status
+    received_at: Instant,
+    state: Value<LimitsState>,
+}
+
+impl Default for LimitsUi {
+    fn default() -> Self {
+        Self {
+            client: None,
+            axis: String::new(),
+            limit: AxisLimit::SafeZGuard,
+            operator: String::new(),
+            access_code: String::new(),
+            reason: String::new(),
+            duration_s: 60,
+            status: LimitOverrideStatus {
+                overrides: vec![],
+            },
+            received_at: Instant::now(),
+            state: Value::default(),
+        }
+    }
+}
+
+struct LimitsClient {
+    stack: EdgeStack,
+    address: Address,
+    runtime: Handle,
+}
+
+#[derive(Default)]
+struct LimitsState {
+    busy: bool,
+    message: Option<RichText>,
+}
+
+enum LimitsAction {
+    Override(LimitOverrideRequest),
+    Clear(String, AxisLimit),
+}
+
+impl LimitsUi {
+    /// Must be called from within the tokio runtime.
+    pub fn connect(&mut self, stack: EdgeStack, address: Address) {
+        self.client = Some(LimitsClient {
+            stack,
+            address,
+            runtime: Handle::current(),
+        });
+    }
+
+    pub fn update_status(&mut self, status: LimitOverrideStatus) {
+        self.status = status;
+        self.received_at = Instant::now();
+    }
+
+    pub fn is_overridden(&self) -> bool {
+        !self.status.overrides.is_empty()
+    }
+
+    fn remaining_s(&self, remaining_s: u32) -> u64 {
+        (remaining_s as u64).saturating_sub(self.received_at.elapsed().as_secs())
+    }
+
+    fn override_limit(&mut self, context: &Context, request: LimitOverrideRequest) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let (axis, limit) = (request.axis.clone(), request.limit);
+            let result = override_axis_limit(stack, address, request).await;
+
+            let message = match result {
+                Ok(Ok(())) => {
+                    info!("Axis limit overridden. axis: {}, limit: {:?}", axis, limit);
+                    None
+                }
+                Ok(Err(e)) => {
+                    warn!(
+                        "Axis limit override refused. axis: {}, limit: {:?}, error: {:?}",
+                        axis, limit, e
+                    );
+                    Some(RichText::new(error_text(&e)).color(status_color(Status::Warn)))
+                }
+                Err(e) => {
+                    error!("Unable to override axis limit. error: {:?}", e);
+                    Some(
+                        RichText::new(tr!("limits-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = message;
+            context.request_repaint();
+        });
+    }
+
+    fn clear(&mut self, context: &Context, axis: String, limit: AxisLimit) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = clear_axis_limit_override(stack, address, axis.clone(), limit).await;
+
+            let message = match result {
+                Ok(Ok(())) => {
+                    info!("Axis limit override cleared. axis: {}, limit: {:?}", axis, limit);
+                    None
+                }
+                Ok(Err(e)) => {
+                    warn!(
+                        "Axis limit override clear refused. axis: {}, limit: {:?}, error: {:?}",
+                        axis, limit, e
+                    );
+                    Some(RichText::new(error_text(&e)).color(status_color(Status::Warn)))
+                }
+                Err(e) => {
+                    error!("Unable to clear axis limit override. error: {:?}", e);
+                    Some(
+                        RichText::new(tr!("limits-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = message;
+            context.request_repaint();
+        });
+    }
+
+    /// Shown above the panels of every viewport while any limit is overridden.
+    pub fn banner(&self, ui: &mut Ui) {
+        for limit_override in self.status.overrides.iter() {
+            ui.label(
+                RichText::new(tr!("limits-banner", {
+                    axis: limit_override.axis.clone(),
+                    limit: limit_text(limit_override.limit),
+                    operator: limit_override.operator.clone(),
+                    remaining: self.remaining_s(limit_override.remaining_s)
+                }))
+                .strong()
+                .color(Color32::WHITE),
+            );
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let (busy, message) = {
+            let state = self.state.lock().unwrap();
+            (state.busy, state.message.clone())
+        };
+        if self.client.is_none() {
+            ui.label(tr!("limits-message-waiting"));
+            return;
+        }
+
+        ui.label(RichText::new(tr!("limits-warning")).color(status_color(Status::Warn)));
+
+        egui::Grid::new("limits")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label(tr!("limits-label-axis"));
+                ui.add(egui::TextEdit::singleline(&mut self.axis).hint_text("Z"));
+                ui.end_row();
+
+                ui.label(tr!("limits-label-limit"));
+                egui::ComboBox::from_id_salt(ui.id().with("limit"))
+                    .selected_text(limit_text(self.limit))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut self.limit,
+                            AxisLimit::SafeZGuard,
+                            limit_text(AxisLimit::SafeZGuard),
+                        );
+                    });
+                ui.end_row();
+
+                ui.label(tr!("limits-label-operator"));
+                ui.text_edit_singleline(&mut self.operator);
+                ui.end_row();
+
+                ui.label(tr!("limits-label-access-code"));
+                ui.add(egui::TextEdit::singleline(&mut self.access_code).password(true));
+                ui.end_row();
+
+                ui.label(tr!("limits-label-reason"));
+                ui.text_edit_singleline(&mut self.reason);
+                ui.end_row();
+
+                ui.label(tr!("limits-label-duration"));
+                ui.add(
+                    egui::DragValue::new(&mut self.duration_s)
+                        .range(1..=3600)
+                        .suffix(" s"),
+                );
+                ui.end_row();
+            });
+
+        let valid = !self.axis.trim().is_empty()
+            && !self.operator.trim().is_empty()
+            && !self.access_code.is_empty()
+            && !self.reason.trim().is_empty();
+
+        let mut action = None;
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!busy && valid, egui::Button::new(tr!("limits-button-override")))
+                .clicked()
+            {
+                let request = LimitOverrideRequest {
+                    axis: self.axis.trim().to_string(),
+                    limit: self.limit,
+                    operator: self.operator.trim().to_string(),
+                    access_code: std::mem::take(&mut self.access_code),
+                    reason: self.reason.trim().to_string(),
+                    duration_s: self.duration_s,
+                };
+                action = Some(LimitsAction::Override(request));
+            }
+            if busy {
+                ui.spinner();
+            }
+        });
+
+        if let Some(message) = message {
+            ui.label(message);
+        }
+
+        ui.separator();
+        if !self.is_overridden() {
+            ui.label(tr!("limits-none"));
+        }
+        egui::Grid::new("limit-overrides")
+            .num_columns(5)
+            .striped(true)
+            .show(ui, |ui| {
+                for limit_override in self.status.overrides.iter() {
+                    ui.label(&limit_override.axis);
+                    ui.label(limit_text(limit_override.limit));
+                    ui.label(tr!("limits-overridden-by", {
+                        operator: limit_override.operator.clone(),
+                        reason: limit_override.reason.clone()
+                    }));
+                    ui.label(
+                        RichText::new(tr!("limits-remaining", {
+                            remaining: self.remaining_s(limit_override.remaining_s)
+                        }))
+                        .color(status_color(Status::Warn)),
+                    );
+                    if ui
+                        .add_enabled(!busy, egui::Button::new(tr!("limits-button-clear")))
+                        .clicked()
+                    {
+                        action = Some(LimitsAction::Clear(limit_override.axis.clone(), limit_override.limit));
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if self.is_overridden() {
+            // the countdown, the server publishes the status every second anyway
+            ui.ctx()
+                .request_repaint_after(Duration::from_secs(1));
+        }
+
+        match action {
+            Some(LimitsAction::Override(request)) => self.override_limit(ui.ctx(), request),
+            Some(LimitsAction::Clear(axis, limit)) => self.clear(ui.ctx(), axis, limit),
+            None => {}
+        }
+    }
+}
+
+fn limit_text(limit: AxisLimit) -> String {
+    match limit {
+        AxisLimit::SafeZGuard => tr!("limits-limit-safe-z-guard"),
+    }
+}
+
+fn error_text(error: &LimitOverrideError) -> String {
+    match error {
+        LimitOverrideError::NotConfigured => tr!("limits-error-not-configured"),
+        LimitOverrideError::Unauthorized => tr!("limits-error-unauthorized"),
+        LimitOverrideError::MissingReason => tr!("limits-error-missing-reason"),
+        LimitOverrideError::UnknownAxis => tr!("limits-error-unknown-axis"),
+        LimitOverrideError::InvalidDuration {
+            max_s,
+        } => tr!("limits-error-invalid-duration", { max: *max_s }),
+        LimitOverrideError::AlreadyOverridden => tr!("limits-error-already-overridden"),
+        LimitOverrideError::NotOverridden => tr!("limits-error-not-overridden"),
+        LimitOverrideError::NotApplied => tr!("limits-error-not-applied"),
+    }
+}
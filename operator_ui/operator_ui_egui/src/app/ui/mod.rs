@@ -1,6 +1,14 @@
 pub mod camera;
+pub mod captures;
 pub mod controls;
 pub mod diagnostics;
+pub mod feeders;
+pub mod firmware_logs;
+pub mod job;
+pub mod limits;
 pub mod plot;
+pub mod readiness;
 pub mod settings;
 pub mod status;
+pub mod templates;
+pub mod test_shots;
@@ -1,10 +1,97 @@
-use egui::Ui;
+use std::collections::VecDeque;
+use std::time::Instant;
 
-#[derive(Default)]
-pub(crate) struct PlotUi {}
+use egui::{Color32, Ui};
+use egui_i18n::tr;
+use egui_plot::{Bar, BarChart, Legend, Line, Plot, PlotPoints};
+use ioboard_shared::vibration::VibrationReport;
+
+/// Number of vibration reports to keep, at 2 reports/second this is ~2 minutes.
+const VIBRATION_HISTORY_LEN: usize = 240;
+
+const AXIS_COLORS: [Color32; 3] = [Color32::RED, Color32::GREEN, Color32::LIGHT_BLUE];
+const AXIS_NAMES: [&str; 3] = ["X", "Y", "Z"];
+
+pub(crate) struct PlotUi {
+    started_at: Instant,
+    /// (seconds since `started_at`, per-axis RMS)
+    vibration_history: VecDeque<(f64, [f32; 3])>,
+    latest_vibration: Option<VibrationReport>,
+}
+
+impl Default for PlotUi {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            vibration_history: VecDeque::with_capacity(VIBRATION_HISTORY_LEN),
+            latest_vibration: None,
+        }
+    }
+}
 
 impl PlotUi {
+    pub fn add_vibration_report(&mut self, report: VibrationReport) {
+        if self.vibration_history.len() >= VIBRATION_HISTORY_LEN {
+            self.vibration_history.pop_front();
+        }
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        self.vibration_history
+            .push_back((elapsed, report.rms));
+        self.latest_vibration = Some(report);
+    }
+
     pub fn ui(&mut self, ui: &mut Ui) {
-        ui.label("Plot content");
+        let Some(latest) = &self.latest_vibration else {
+            ui.label(tr!("plot-vibration-waiting"));
+            return;
+        };
+
+        ui.label(tr!("plot-vibration-rms-title"));
+        Plot::new("vibration_rms")
+            .legend(Legend::default())
+            .height(ui.available_height() / 2.0)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .show(ui, |plot_ui| {
+                for (axis, (name, color)) in AXIS_NAMES
+                    .iter()
+                    .zip(AXIS_COLORS)
+                    .enumerate()
+                {
+                    let points: PlotPoints = self
+                        .vibration_history
+                        .iter()
+                        .map(|(time, rms)| {
+                            [
+                                *time,
+                                rms.get(axis)
+                                    .copied()
+                                    .unwrap_or_default() as f64,
+                            ]
+                        })
+                        .collect();
+                    plot_ui.line(Line::new(*name, points).color(color));
+                }
+            });
+
+        ui.label(tr!("plot-vibration-spectrum-title"));
+        let bars: Vec<Bar> = latest
+            .spectrum
+            .iter()
+            .enumerate()
+            .map(|(bin, amplitude)| {
+                let frequency = latest.spectrum_start + latest.spectrum_step * bin as f32;
+                Bar::new(frequency as f64, *amplitude as f64).width(latest.spectrum_step as f64 * 0.8)
+            })
+            .collect();
+        let chart = BarChart::new("spectrum", bars).color(Color32::GREEN);
+
+        Plot::new("vibration_spectrum")
+            .height(ui.available_height())
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .show(ui, |plot_ui| plot_ui.bar_chart(chart));
     }
 }
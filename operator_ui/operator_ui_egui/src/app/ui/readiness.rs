@@ -0,0 +1,561 @@
+use std::collections::{HashMap, VecDeque};
+
+use egui::{Context, RichText, Ui};
+use egui_i18n::tr;
+use egui_mobius::Value;
+use ergot::Address;
+use ergot::toolkits::tokio_udp::EdgeStack;
+use operator_shared::homing::{AxisHomingState, HomingError, HomingStatus};
+use operator_shared::job::{JobCheckpoint, ResumeChoice, ResumeError};
+use operator_shared::maintenance::{ClogSymptom, MaintenanceEvent};
+use operator_shared::readiness::{CheckState, ReadinessCheck, ReadinessStatus, StartJobError};
+use operator_shared::vision::{ScanError, ScanTarget};
+use tokio::runtime::Handle;
+use tracing::{error, info, warn};
+
+use crate::net::commands::{
+    confirm_resume, fetch_job_checkpoint, home_all, override_readiness_check, scan_code, start_job,
+};
+use crate::ui_common::style::{Status, status_color};
+use crate::ui_common::units::formatter;
+
+/// The number of maintenance events that are shown.
+const MAINTENANCE_EVENTS_MAX: usize = 10;
+
+/// The pre-run checks of the machine, the job can only be started when every check has passed or has been overridden.
+#[derive(Default)]
+pub(crate) struct ReadinessUi {
+    client: Option<ReadinessClient>,
+    status: Option<ReadinessStatus>,
+    /// the override reasons being entered, by check
+    reasons: HashMap<ReadinessCheck, String>,
+    /// most recent first
+    maintenance_events: VecDeque<MaintenanceEvent>,
+    /// `None` until the machine has been homed since the operator UI was started
+    homing: Option<HomingStatus>,
+    state: Value<ReadinessState>,
+}
+
+struct ReadinessClient {
+    stack: EdgeStack,
+    address: Address,
+    runtime: Handle,
+}
+
+#[derive(Default)]
+struct ReadinessState {
+    busy: bool,
+    message: Option<RichText>,
+    /// an interrupted job, the operator must confirm whether to resume or restart it before the job is started
+    checkpoint: Option<JobCheckpoint>,
+}
+
+impl ReadinessUi {
+    /// Must be called from within the tokio runtime.
+    pub fn connect(&mut self, stack: EdgeStack, address: Address) {
+        self.client = Some(ReadinessClient {
+            stack,
+            address,
+            runtime: Handle::current(),
+        });
+    }
+
+    pub fn update_status(&mut self, status: ReadinessStatus) {
+        self.status = Some(status);
+    }
+
+    pub fn update_homing(&mut self, status: HomingStatus) {
+        self.homing = Some(status);
+    }
+
+    pub fn add_maintenance_event(&mut self, event: MaintenanceEvent) {
+        self.maintenance_events
+            .push_front(event);
+        self.maintenance_events
+            .truncate(MAINTENANCE_EVENTS_MAX);
+    }
+
+    pub fn update_checkpoint(&mut self, checkpoint: Option<JobCheckpoint>) {
+        self.state.lock().unwrap().checkpoint = checkpoint;
+    }
+
+    fn confirm_resume(&mut self, context: &Context, choice: ResumeChoice) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = confirm_resume(stack, address, choice).await;
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = match result {
+                Ok(Ok(())) => {
+                    info!("Interrupted job confirmed. choice: {:?}", choice);
+                    if let Some(checkpoint) = &mut state.checkpoint {
+                        checkpoint.confirmed = Some(choice);
+                    }
+                    None
+                }
+                Ok(Err(ResumeError::NoCheckpoint)) => {
+                    warn!(
+                        "Interrupted job confirmation rejected, no checkpoint. choice: {:?}",
+                        choice
+                    );
+                    state.checkpoint = None;
+                    None
+                }
+                Ok(Err(ResumeError::Running)) => {
+                    warn!(
+                        "Interrupted job confirmation rejected, a job is running. choice: {:?}",
+                        choice
+                    );
+                    Some(RichText::new(tr!("readiness-message-running")).color(status_color(Status::Warn)))
+                }
+                Err(e) => {
+                    error!(
+                        "Unable to confirm interrupted job. choice: {:?}, error: {:?}",
+                        choice, e
+                    );
+                    Some(
+                        RichText::new(tr!("readiness-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+            context.request_repaint();
+        });
+    }
+
+    /// Selects the job for the scanned board, the checkpoint is fetched again since it depends on the job.
+    fn scan_board(&mut self, context: &Context) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = scan_code(stack.clone(), address, ScanTarget::Board).await;
+
+            let checkpoint = match &result {
+                Ok(Ok(_)) => match fetch_job_checkpoint(stack, address).await {
+                    Ok(checkpoint) => Some(checkpoint),
+                    Err(e) => {
+                        error!("Unable to fetch job checkpoint. error: {:?}", e);
+                        None
+                    }
+                },
+                _ => None,
+            };
+
+            let mut state = state.lock().unwrap();
+            let message = match result {
+                Ok(Ok(board)) => {
+                    info!("Job selected for board. board: {}", board);
+                    if let Some(checkpoint) = checkpoint {
+                        state.checkpoint = checkpoint;
+                    }
+                    RichText::new(tr!("readiness-message-board-scanned", { board: board }))
+                }
+                Ok(Err(ScanError::UnknownJob(board))) => {
+                    warn!("Scan board failed, no job for board. board: {}", board);
+                    RichText::new(tr!("readiness-message-unknown-board", { board: board }))
+                        .color(status_color(Status::Warn))
+                }
+                Ok(Err(ScanError::JobRunning)) => {
+                    warn!("Scan board failed, a job is running");
+                    RichText::new(tr!("readiness-message-running")).color(status_color(Status::Warn))
+                }
+                Ok(Err(e)) => {
+                    warn!("Scan board failed. error: {:?}", e);
+                    RichText::new(tr!("readiness-message-scan-failed", { error: format!("{:?}", e) }))
+                        .color(status_color(Status::Warn))
+                }
+                Err(e) => {
+                    error!("Unable to scan board. error: {:?}", e);
+                    RichText::new(tr!("readiness-message-error", { error: format!("{}", e) }))
+                        .color(status_color(Status::Fault))
+                }
+            };
+
+            state.busy = false;
+            state.message = Some(message);
+            context.request_repaint();
+        });
+    }
+
+    fn override_check(&mut self, context: &Context, check: ReadinessCheck) {
+        let Some(client) = &self.client else {
+            return;
+        };
+        let Some(reason) = self.reasons.remove(&check) else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = override_readiness_check(stack, address, check, reason).await;
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = match result {
+                Ok(()) => {
+                    info!("Readiness check overridden. check: {:?}", check);
+                    None
+                }
+                Err(e) => {
+                    error!("Unable to override readiness check. check: {:?}, error: {:?}", check, e);
+                    Some(
+                        RichText::new(tr!("readiness-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+            context.request_repaint();
+        });
+    }
+
+    fn home_all(&mut self, context: &Context) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = home_all(stack, address).await;
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = match result {
+                Ok(Ok(())) => {
+                    info!("Homing started");
+                    None
+                }
+                Ok(Err(HomingError::NotConfigured)) => {
+                    warn!("Homing refused, no axes configured");
+                    Some(
+                        RichText::new(tr!("readiness-message-homing-not-configured")).color(status_color(Status::Warn)),
+                    )
+                }
+                Ok(Err(HomingError::Running)) => {
+                    warn!("Homing refused, the machine is busy");
+                    Some(RichText::new(tr!("readiness-message-running")).color(status_color(Status::Warn)))
+                }
+                Err(e) => {
+                    error!("Unable to start homing. error: {:?}", e);
+                    Some(
+                        RichText::new(tr!("readiness-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+            context.request_repaint();
+        });
+    }
+
+    fn start_job(&mut self, context: &Context) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = start_job(stack, address).await;
+
+            let mut state = state.lock().unwrap();
+            let message = match result {
+                Ok(Ok(())) => {
+                    info!("Job started");
+                    state.checkpoint = None;
+                    RichText::new(tr!("readiness-message-job-started"))
+                }
+                Ok(Err(StartJobError::NotReady(checks))) => {
+                    warn!("Start job refused, machine not ready. checks: {:?}", checks);
+                    RichText::new(tr!("readiness-message-not-ready")).color(status_color(Status::Warn))
+                }
+                Ok(Err(StartJobError::NoJob)) => {
+                    warn!("Start job refused, there is no job");
+                    RichText::new(tr!("readiness-message-no-job")).color(status_color(Status::Warn))
+                }
+                Ok(Err(StartJobError::Running)) => {
+                    warn!("Start job refused, a job is already running");
+                    RichText::new(tr!("readiness-message-running")).color(status_color(Status::Warn))
+                }
+                Ok(Err(StartJobError::ResumeUnconfirmed)) => {
+                    warn!("Start job refused, the interrupted job has not been confirmed");
+                    RichText::new(tr!("readiness-message-resume-unconfirmed")).color(status_color(Status::Warn))
+                }
+                Ok(Err(StartJobError::NoMotion)) => {
+                    warn!("Start job refused, no io board follows the moves of the server");
+                    RichText::new(tr!("readiness-message-no-motion")).color(status_color(Status::Warn))
+                }
+                Err(e) => {
+                    error!("Unable to start job. error: {:?}", e);
+                    RichText::new(tr!("readiness-message-error", { error: format!("{}", e) }))
+                        .color(status_color(Status::Fault))
+                }
+            };
+
+            state.busy = false;
+            state.message = Some(message);
+            context.request_repaint();
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let Some(status) = self.status.clone() else {
+            ui.label(tr!("readiness-message-waiting"));
+            return;
+        };
+
+        let (busy, message, checkpoint) = {
+            let state = self.state.lock().unwrap();
+            (state.busy, state.message.clone(), state.checkpoint.clone())
+        };
+        let connected = self.client.is_some();
+
+        let mut override_clicked = None;
+        egui::Grid::new("readiness")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for check_status in status.checks.iter() {
+                    let check = check_status.check;
+                    let name = match check {
+                        ReadinessCheck::Homed => tr!("readiness-check-homed"),
+                        ReadinessCheck::VacuumOk => tr!("readiness-check-vacuum-ok"),
+                        ReadinessCheck::CamerasCalibrated => tr!("readiness-check-cameras-calibrated"),
+                        ReadinessCheck::FeedersVerified => tr!("readiness-check-feeders-verified"),
+                        ReadinessCheck::IoBoardReady => tr!("readiness-check-io-board-ready"),
+                        ReadinessCheck::ServerTasksRunning => tr!("readiness-check-server-tasks-running"),
+                        ReadinessCheck::TemperaturesNormal => tr!("readiness-check-temperatures-normal"),
+                    };
+                    let (text, color) = match check_status.state {
+                        CheckState::Passed => (tr!("readiness-state-passed"), ui.visuals().text_color()),
+                        CheckState::Failed => (tr!("readiness-state-failed"), status_color(Status::Fault)),
+                        CheckState::Unknown => (tr!("readiness-state-unknown"), status_color(Status::Warn)),
+                    };
+
+                    ui.label(name);
+                    ui.label(RichText::new(text).color(color));
+
+                    match (&check_status.override_reason, check_status.state) {
+                        (Some(reason), _) => {
+                            let text = tr!("readiness-overridden", { reason: reason });
+                            ui.label(RichText::new(text).color(status_color(Status::Warn)));
+                        }
+                        (None, CheckState::Passed) => {
+                            ui.label("");
+                        }
+                        (None, _) => {
+                            ui.horizontal(|ui| {
+                                let reason = self.reasons.entry(check).or_default();
+                                ui.add(
+                                    egui::TextEdit::singleline(reason)
+                                        .hint_text(tr!("readiness-override-hint"))
+                                        .desired_width(160.0),
+                                );
+                                let enabled = connected && !busy && !reason.trim().is_empty();
+                                if ui
+                                    .add_enabled(enabled, egui::Button::new(tr!("readiness-button-override")))
+                                    .clicked()
+                                {
+                                    override_clicked = Some(check);
+                                }
+                            });
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(homing) = &self.homing {
+            ui.separator();
+            ui.horizontal_wrapped(|ui| {
+                ui.label(tr!("readiness-homing-heading"));
+                for axis in homing.axes.iter() {
+                    let (text, color) = match axis.state {
+                        AxisHomingState::NotHomed => (tr!("readiness-homing-not-homed"), ui.visuals().text_color()),
+                        AxisHomingState::Homing => (tr!("readiness-homing-homing"), ui.visuals().text_color()),
+                        AxisHomingState::Homed => (tr!("readiness-homing-homed"), status_color(Status::Ok)),
+                        AxisHomingState::Failed => (tr!("readiness-homing-failed"), status_color(Status::Fault)),
+                        AxisHomingState::Skipped => (tr!("readiness-homing-skipped"), status_color(Status::Warn)),
+                    };
+                    ui.label(RichText::new(format!("{}: {}", axis.axis, text)).color(color));
+                }
+                if homing.running {
+                    ui.spinner();
+                }
+            });
+        }
+
+        ui.separator();
+
+        let mut resume_clicked = None;
+        if let Some(checkpoint) = &checkpoint {
+            ui.label(
+                RichText::new(tr!("readiness-checkpoint-heading", {
+                    job: checkpoint.job.as_str(),
+                    completed: checkpoint.placed + checkpoint.skipped,
+                    placements: checkpoint.placements
+                }))
+                .color(status_color(Status::Warn)),
+            );
+            match checkpoint.confirmed {
+                None => {
+                    ui.label(tr!("readiness-checkpoint-homing"));
+                    ui.horizontal(|ui| {
+                        for (choice, text) in [
+                            (ResumeChoice::Resume, tr!("readiness-button-resume")),
+                            (ResumeChoice::Restart, tr!("readiness-button-restart")),
+                        ] {
+                            if ui
+                                .add_enabled(connected && !busy, egui::Button::new(text))
+                                .clicked()
+                            {
+                                resume_clicked = Some(choice);
+                            }
+                        }
+                    });
+                }
+                Some(ResumeChoice::Resume) => {
+                    ui.label(tr!("readiness-checkpoint-resume-confirmed"));
+                }
+                Some(ResumeChoice::Restart) => {
+                    ui.label(tr!("readiness-checkpoint-restart-confirmed"));
+                }
+            }
+            ui.separator();
+        }
+
+        // the operator must confirm how to continue an interrupted job before it can be started
+        let confirmed = checkpoint
+            .as_ref()
+            .is_none_or(|checkpoint| checkpoint.confirmed.is_some());
+        let ready = status.ready();
+        let homing = self
+            .homing
+            .as_ref()
+            .is_some_and(|homing| homing.running);
+        let mut start_clicked = false;
+        let mut scan_clicked = false;
+        let mut home_clicked = false;
+        ui.horizontal(|ui| {
+            home_clicked = ui
+                .add_enabled(
+                    connected && !busy && !homing,
+                    egui::Button::new(tr!("readiness-button-home-all")),
+                )
+                .clicked();
+
+            scan_clicked = ui
+                .add_enabled(
+                    connected && !busy,
+                    egui::Button::new(tr!("readiness-button-scan-board")),
+                )
+                .clicked();
+
+            start_clicked = ui
+                .add_enabled(
+                    connected && ready && confirmed && !busy,
+                    egui::Button::new(tr!("readiness-button-start-job")),
+                )
+                .clicked();
+
+            if busy {
+                ui.spinner();
+            }
+        });
+
+        if !self.maintenance_events.is_empty() {
+            ui.separator();
+            ui.label(tr!("readiness-maintenance-heading"));
+            let formatter = formatter();
+            for event in self.maintenance_events.iter() {
+                let text = match event {
+                    MaintenanceEvent::NozzleClogSuspected {
+                        nozzle,
+                        symptom,
+                    } => {
+                        let text = match symptom {
+                            ClogSymptom::PoorPickupVacuum {
+                                vacuum,
+                            } => {
+                                let vacuum = formatter.pressure(*vacuum as f64, 1);
+                                tr!("readiness-maintenance-poor-pickup-vacuum", { nozzle: nozzle, vacuum: vacuum })
+                            }
+                            ClogSymptom::SlowDecay {
+                                vacuum,
+                            } => {
+                                let vacuum = formatter.pressure(*vacuum as f64, 1);
+                                tr!("readiness-maintenance-slow-decay", { nozzle: nozzle, vacuum: vacuum })
+                            }
+                        };
+                        RichText::new(text).color(status_color(Status::Warn))
+                    }
+                    MaintenanceEvent::NozzleCleaned {
+                        nozzle,
+                    } => RichText::new(tr!("readiness-maintenance-nozzle-cleaned", { nozzle: nozzle })),
+                    MaintenanceEvent::NozzleCleaningFailed {
+                        nozzle,
+                    } => RichText::new(tr!("readiness-maintenance-nozzle-cleaning-failed", { nozzle: nozzle }))
+                        .color(status_color(Status::Fault)),
+                };
+                ui.label(text);
+            }
+        }
+
+        let overridden = status
+            .checks
+            .iter()
+            .any(|check_status| check_status.override_reason.is_some());
+        if overridden {
+            ui.label(RichText::new(tr!("readiness-message-overridden")).color(status_color(Status::Warn)));
+        }
+        if let Some(message) = message {
+            ui.label(message);
+        }
+
+        if let Some(choice) = resume_clicked {
+            self.confirm_resume(ui.ctx(), choice);
+        }
+        if let Some(check) = override_clicked {
+            self.override_check(ui.ctx(), check);
+        }
+        if home_clicked {
+            self.home_all(ui.ctx());
+        }
+        if scan_clicked {
+            self.scan_board(ui.ctx());
+        }
+        if start_clicked {
+            self.start_job(ui.ctx());
+        }
+    }
+}
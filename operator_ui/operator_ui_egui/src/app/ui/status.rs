@@ -1,10 +1,374 @@
-use egui::Ui;
+use std::collections::BTreeMap;
+
+use egui::{Button, Color32, RichText, Ui};
+use egui_i18n::tr;
+use ergot::toolkits::tokio_udp::EdgeStack;
+use ioboard_shared::estop::{EStop, EStopSource};
+use ioboard_shared::load::AxisLoad;
+use ioboard_shared::power::PowerRail;
+use ioboard_shared::safety::{MotionRestriction, SafetyInputState, SafetyStatus};
+use ioboard_shared::self_test::{BoardState, SelfTestFault, SelfTestSensor, SelfTestStatus};
+use ioboard_shared::thermal::{TemperatureSensor, ThermalLevel, ThermalReading};
+use operator_shared::geometry::MachineGeometry;
+use operator_shared::power::PowerReading;
+
+use crate::net::broadcast_estop;
+use crate::ui_common::style::{Status, status_color};
+use crate::ui_common::units::formatter;
 
 #[derive(Default)]
-pub(crate) struct StatusUi {}
+pub(crate) struct StatusUi {
+    /// `None` until the networking has started, the e-stop can't be sent until then
+    stack: Option<EdgeStack>,
+    /// the last e-stop, or reset, could not be sent
+    estop_not_sent: bool,
+    safety: Option<SafetyStatus>,
+    self_test: Option<SelfTestStatus>,
+    /// by axis index
+    loads: BTreeMap<u8, AxisLoad>,
+    temperatures: BTreeMap<TemperatureSensor, ThermalReading>,
+    /// `None` until the first reading, or without a power meter
+    power: Option<PowerReading>,
+    geometry: Option<MachineGeometry>,
+}
 
 impl StatusUi {
+    pub fn connect(&mut self, stack: EdgeStack) {
+        self.stack = Some(stack);
+    }
+
+    pub fn update_safety(&mut self, status: SafetyStatus) {
+        self.safety = Some(status);
+    }
+
+    pub fn update_self_test(&mut self, status: SelfTestStatus) {
+        self.self_test = Some(status);
+    }
+
+    pub fn update_axis_load(&mut self, load: AxisLoad) {
+        self.loads.insert(load.axis, load);
+    }
+
+    pub fn update_temperature(&mut self, reading: ThermalReading) {
+        self.temperatures
+            .insert(reading.sensor, reading);
+    }
+
+    pub fn update_power(&mut self, reading: PowerReading) {
+        self.power = Some(reading);
+    }
+
+    pub fn update_machine_geometry(&mut self, geometry: MachineGeometry) {
+        self.geometry = Some(geometry);
+    }
+
     pub fn ui(&mut self, ui: &mut Ui) {
-        ui.label("Status content");
+        self.self_test_ui(ui);
+        ui.separator();
+        self.safety_ui(ui);
+        ui.separator();
+        self.loads_ui(ui);
+        ui.separator();
+        self.temperatures_ui(ui);
+        ui.separator();
+        self.power_ui(ui);
+        ui.separator();
+        self.geometry_ui(ui);
+    }
+
+    fn self_test_ui(&self, ui: &mut Ui) {
+        ui.heading(tr!("status-self-test-heading"));
+
+        let Some(status) = &self.self_test else {
+            ui.label(tr!("status-self-test-waiting"));
+            return;
+        };
+
+        egui::Grid::new("self_test")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                let (text, color) = match status.state {
+                    BoardState::Ready => (tr!("status-self-test-state-ready"), ui.visuals().text_color()),
+                    BoardState::Degraded => (tr!("status-self-test-state-degraded"), status_color(Status::Fault)),
+                };
+                ui.label(tr!("status-self-test-state"));
+                ui.label(RichText::new(text).color(color));
+                ui.end_row();
+
+                ui.label(tr!("status-self-test-checks"));
+                ui.label(status.checks.to_string());
+                ui.end_row();
+            });
+
+        for fault in status.faults() {
+            ui.label(RichText::new(fault_text(fault)).color(status_color(Status::Fault)));
+        }
+        let unreported = (status.fault_count as usize).saturating_sub(status.faults().count());
+        if unreported > 0 {
+            ui.label(
+                RichText::new(tr!("status-self-test-faults-unreported", {count: unreported}))
+                    .color(status_color(Status::Fault)),
+            );
+        }
+    }
+
+    fn safety_ui(&mut self, ui: &mut Ui) {
+        ui.heading(tr!("status-safety-heading"));
+
+        self.estop_ui(ui);
+
+        let Some(status) = &self.safety else {
+            ui.label(tr!("status-safety-waiting"));
+            return;
+        };
+
+        egui::Grid::new("safety")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for (name, state) in [
+                    (tr!("status-safety-input-door"), status.door),
+                    (tr!("status-safety-input-light-curtain"), status.light_curtain),
+                ] {
+                    let (text, color) = match state {
+                        SafetyInputState::NotConfigured => (
+                            tr!("status-safety-input-not-configured"),
+                            ui.visuals().weak_text_color(),
+                        ),
+                        SafetyInputState::Clear => (tr!("status-safety-input-clear"), ui.visuals().text_color()),
+                        SafetyInputState::Tripped => (tr!("status-safety-input-tripped"), status_color(Status::Warn)),
+                    };
+
+                    ui.label(name);
+                    ui.label(RichText::new(text).color(color));
+                    ui.end_row();
+                }
+
+                let (text, color) = match status.restriction {
+                    MotionRestriction::None => (tr!("status-safety-restriction-none"), ui.visuals().text_color()),
+                    MotionRestriction::ReducedSpeed => (
+                        tr!("status-safety-restriction-reduced-speed", {
+                            percent: format!("{:.0}", status.reduced_speed_factor * 100.0)
+                        }),
+                        status_color(Status::Warn),
+                    ),
+                    MotionRestriction::Paused => (tr!("status-safety-restriction-paused"), status_color(Status::Warn)),
+                    MotionRestriction::EStopped => {
+                        (tr!("status-safety-restriction-estopped"), status_color(Status::EStop))
+                    }
+                };
+
+                ui.label(tr!("status-safety-restriction"));
+                ui.label(RichText::new(text).color(color));
+                ui.end_row();
+
+                let (text, color) = match status.estop {
+                    Some(EStopSource::Server) => {
+                        (tr!("status-safety-estop-latched-server"), status_color(Status::EStop))
+                    }
+                    Some(EStopSource::OperatorUi) => (
+                        tr!("status-safety-estop-latched-operator-ui"),
+                        status_color(Status::EStop),
+                    ),
+                    None => (tr!("status-safety-estop-not-latched"), ui.visuals().text_color()),
+                };
+
+                ui.label(tr!("status-safety-estop"));
+                ui.label(RichText::new(text).color(color));
+                ui.end_row();
+            });
+    }
+
+    /// The e-stop is always enabled once connected, the reset only while an io board reports the machine as e-stopped.
+    fn estop_ui(&mut self, ui: &mut Ui) {
+        let estopped = self
+            .safety
+            .is_some_and(|status| status.estop.is_some() || status.restriction == MotionRestriction::EStopped);
+
+        let mut estop = None;
+        ui.horizontal(|ui| {
+            let stop_button = Button::new(
+                RichText::new(tr!("status-estop-button"))
+                    .strong()
+                    .color(Color32::WHITE),
+            )
+            .fill(status_color(Status::EStop));
+            if ui
+                .add_enabled(self.stack.is_some(), stop_button)
+                .clicked()
+            {
+                estop = Some(EStop::Stop(EStopSource::OperatorUi));
+            }
+
+            if ui
+                .add_enabled(
+                    self.stack.is_some() && estopped,
+                    Button::new(tr!("status-estop-reset-button")),
+                )
+                .clicked()
+            {
+                estop = Some(EStop::Reset(EStopSource::OperatorUi));
+            }
+        });
+
+        if let (Some(estop), Some(stack)) = (estop, &self.stack) {
+            self.estop_not_sent = !broadcast_estop(stack, estop);
+        }
+        if self.estop_not_sent {
+            ui.label(RichText::new(tr!("status-estop-not-sent")).color(status_color(Status::Fault)));
+        }
+    }
+
+    fn loads_ui(&self, ui: &mut Ui) {
+        ui.heading(tr!("status-loads-heading"));
+
+        if self.loads.is_empty() {
+            ui.label(tr!("status-loads-waiting"));
+            return;
+        }
+
+        egui::Grid::new("loads")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for load in self.loads.values() {
+                    ui.label(tr!("status-load-axis", {axis: load.axis}));
+                    match load.moving {
+                        true => ui.add(
+                            egui::ProgressBar::new(load.load)
+                                .desired_width(100.0)
+                                .text(tr!("status-load-load", {percent: format!("{:.0}", load.load * 100.0)})),
+                        ),
+                        false => ui.label(tr!("status-load-standstill")),
+                    };
+                    ui.label(tr!("status-load-current", {percent: format!("{:.0}", load.current * 100.0)}));
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn power_ui(&self, ui: &mut Ui) {
+        ui.heading(tr!("status-power-heading"));
+
+        let Some(power) = &self.power else {
+            ui.label(tr!("status-power-waiting"));
+            return;
+        };
+
+        egui::Grid::new("power")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(tr!("status-power-voltage"));
+                ui.label(format!("{:.1} V", power.volts));
+                ui.end_row();
+
+                ui.label(tr!("status-power-current"));
+                ui.label(format!("{:.2} A", power.amps));
+                ui.end_row();
+
+                ui.label(tr!("status-power-power"));
+                ui.label(format!("{:.0} W", power.watts));
+                ui.end_row();
+
+                ui.label(tr!("status-power-energy"));
+                ui.label(format!("{:.2} kWh", power.energy_wh / 1000.0));
+                ui.end_row();
+            });
+    }
+
+    fn temperatures_ui(&self, ui: &mut Ui) {
+        ui.heading(tr!("status-temperatures-heading"));
+
+        if self.temperatures.is_empty() {
+            ui.label(tr!("status-temperatures-waiting"));
+            return;
+        }
+
+        let formatter = formatter();
+        egui::Grid::new("temperatures")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for reading in self.temperatures.values() {
+                    let name = match reading.sensor {
+                        TemperatureSensor::Driver(axis) => tr!("status-temperature-sensor-driver", {axis: axis}),
+                        TemperatureSensor::Ambient => tr!("status-temperature-sensor-ambient"),
+                    };
+                    let color = match reading.level {
+                        ThermalLevel::Normal => ui.visuals().text_color(),
+                        ThermalLevel::Warning => status_color(Status::Warn),
+                        ThermalLevel::Critical => status_color(Status::Fault),
+                    };
+
+                    ui.label(name);
+                    ui.label(RichText::new(formatter.temperature(reading.temperature as f64, 1)).color(color));
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn geometry_ui(&self, ui: &mut Ui) {
+        ui.heading(tr!("status-geometry-heading"));
+
+        let Some(geometry) = &self.geometry else {
+            ui.label(tr!("status-geometry-waiting"));
+            return;
+        };
+
+        let formatter = formatter();
+        egui::Grid::new("geometry")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(tr!("status-geometry-skew"));
+                ui.label(formatter.angle(geometry.skew_degrees as f64, 3));
+                ui.end_row();
+
+                ui.label(tr!("status-geometry-scale-x"));
+                ui.label(formatter.number(geometry.scale_x as f64, 5));
+                ui.end_row();
+
+                ui.label(tr!("status-geometry-scale-y"));
+                ui.label(formatter.number(geometry.scale_y as f64, 5));
+                ui.end_row();
+            });
+    }
+}
+
+fn fault_text(fault: &SelfTestFault) -> String {
+    let rail_name = |rail: PowerRail| match rail {
+        PowerRail::MotorPower => tr!("status-self-test-rail-motor-power"),
+        PowerRail::VacuumPump => tr!("status-self-test-rail-vacuum-pump"),
+        PowerRail::Lighting => tr!("status-self-test-rail-lighting"),
+    };
+    let sensor_name = |sensor: SelfTestSensor| match sensor {
+        SelfTestSensor::SupplyVoltage => tr!("status-self-test-sensor-supply-voltage"),
+        SelfTestSensor::Vacuum => tr!("status-self-test-sensor-vacuum"),
+    };
+
+    match *fault {
+        SelfTestFault::OutputStuck {
+            rail,
+            enabled: true,
+        } => tr!("status-self-test-fault-output-stuck-off", {rail: rail_name(rail)}),
+        SelfTestFault::OutputStuck {
+            rail,
+            enabled: false,
+        } => tr!("status-self-test-fault-output-stuck-on", {rail: rail_name(rail)}),
+        SelfTestFault::SensorUnreadable(sensor) => {
+            tr!("status-self-test-fault-sensor-unreadable", {sensor: sensor_name(sensor)})
+        }
+        SelfTestFault::SensorOutOfRange {
+            sensor,
+            value,
+        } => tr!("status-self-test-fault-sensor-out-of-range", {
+            sensor: sensor_name(sensor),
+            value: format!("{:.1}", value)
+        }),
+        SelfTestFault::DriverNotResponding {
+            axis,
+        } => tr!("status-self-test-fault-driver-not-responding", {axis: axis}),
     }
 }
@@ -0,0 +1,480 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+
+use egui::{Color32, Context, Pos2, Rect, RichText, Sense, Stroke, Ui, Vec2, Widget};
+use egui_i18n::tr;
+use egui_mobius::Value;
+use ergot::Address;
+use ergot::toolkits::tokio_udp::EdgeStack;
+use machine_ids::CameraId;
+use operator_shared::templates::{TemplateCapture, TemplateCrop, TemplateError, TemplateInfo, TemplateKind};
+use tokio::runtime::Handle;
+use tracing::{error, info, warn};
+
+use crate::app::ui::camera::CameraUi;
+use crate::net::commands::{create_template, delete_template, list_templates, update_template};
+use crate::ui_common::style::{Status, status_color};
+
+const CROP_COLOR: Color32 = Color32::YELLOW;
+
+/// Editor for the vision templates of the parts library, the image of a template is cropped from a camera frame.
+///
+/// The crop is drawn on the live preview of the camera, the server crops the same region from a frame captured when
+/// the template is saved.
+#[derive(Default)]
+pub(crate) struct TemplatesUi {
+    client: Option<TemplatesClient>,
+    state: Value<TemplatesState>,
+    camera: Option<CameraId>,
+    /// In image pixels.
+    crop: Option<Rect>,
+    /// In image pixels.
+    drag_start: Option<Pos2>,
+    name: String,
+    kind: TemplateKind,
+    /// the template being edited, `None` when creating a template
+    editing: Option<TemplateInfo>,
+    /// replace the image of the template being edited with the crop
+    recapture: bool,
+}
+
+struct TemplatesClient {
+    stack: EdgeStack,
+    address: Address,
+    runtime: Handle,
+}
+
+#[derive(Default)]
+struct TemplatesState {
+    busy: bool,
+    message: Option<RichText>,
+    templates: Vec<TemplateInfo>,
+}
+
+enum TemplateAction {
+    Create,
+    Save,
+    Edit(TemplateInfo),
+    CancelEdit,
+    Delete(String),
+    Refresh,
+}
+
+impl TemplatesUi {
+    /// Must be called from within the tokio runtime.
+    pub fn connect(&mut self, stack: EdgeStack, address: Address) {
+        self.client = Some(TemplatesClient {
+            stack,
+            address,
+            runtime: Handle::current(),
+        });
+    }
+
+    /// Runs the action, then re-fetches the template list, the action returns the message to show.
+    fn spawn(&self, context: &Context, action: impl Future<Output = Option<RichText>> + Send + 'static) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let mut message = action.await;
+
+            let templates = match list_templates(stack, address).await {
+                Ok(templates) => Some(templates),
+                Err(e) => {
+                    error!("Unable to list templates. error: {:?}", e);
+                    message = message.or(Some(
+                        RichText::new(tr!("templates-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    ));
+                    None
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = message;
+            if let Some(templates) = templates {
+                state.templates = templates;
+            }
+            context.request_repaint();
+        });
+    }
+
+    fn capture(&self) -> Option<TemplateCapture> {
+        let camera = self.camera?;
+        let crop = self.crop?;
+        Some(TemplateCapture {
+            camera,
+            crop: TemplateCrop {
+                x: crop.min.x.round() as u32,
+                y: crop.min.y.round() as u32,
+                width: crop.width().round() as u32,
+                height: crop.height().round() as u32,
+            },
+        })
+    }
+
+    fn create(&mut self, context: &Context) {
+        let (Some(client), Some(capture)) = (&self.client, self.capture()) else {
+            return;
+        };
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let name = self.name.clone();
+        let kind = self.kind;
+        self.spawn(context, async move {
+            let result = create_template(stack, address, name.clone(), kind, capture).await;
+            saved_message(&name, result)
+        });
+        self.crop = None;
+    }
+
+    fn save(&mut self, context: &Context) {
+        let Some(client) = &self.client else {
+            return;
+        };
+        let Some(editing) = self.editing.take() else {
+            return;
+        };
+        let capture = match self.recapture {
+            true => self.capture(),
+            false => None,
+        };
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let new_name = self.name.clone();
+        let kind = self.kind;
+        self.spawn(context, async move {
+            let result = update_template(stack, address, editing.name, new_name.clone(), kind, capture).await;
+            saved_message(&new_name, result)
+        });
+        self.crop = None;
+        self.recapture = false;
+    }
+
+    fn delete(&mut self, context: &Context, name: String) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        self.spawn(context, async move {
+            match delete_template(stack, address, name.clone()).await {
+                Ok(Ok(())) => {
+                    info!("Template deleted. name: {}", name);
+                    None
+                }
+                Ok(Err(e)) => {
+                    warn!("Template deletion rejected. name: {}, error: {:?}", name, e);
+                    Some(RichText::new(rejected_message(e)).color(status_color(Status::Warn)))
+                }
+                Err(e) => {
+                    error!("Unable to delete template. name: {}, error: {:?}", name, e);
+                    Some(
+                        RichText::new(tr!("templates-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            }
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, camera_uis: &BTreeMap<CameraId, CameraUi>) {
+        if self.client.is_none() {
+            ui.label(tr!("templates-message-waiting"));
+            return;
+        }
+
+        let (busy, message) = {
+            let state = self.state.lock().unwrap();
+            (state.busy, state.message.clone())
+        };
+
+        // the first camera is selected by default, e.g. the only camera
+        if !self
+            .camera
+            .is_some_and(|camera| camera_uis.contains_key(&camera))
+        {
+            self.camera = camera_uis.keys().next().copied();
+            self.crop = None;
+        }
+
+        let mut action = None;
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!busy, egui::Button::new(tr!("templates-button-refresh")))
+                .clicked()
+            {
+                action = Some(TemplateAction::Refresh);
+            }
+            if busy {
+                ui.spinner();
+            }
+        });
+
+        self.editor_ui(ui, camera_uis, busy, &mut action);
+
+        if let Some(message) = message {
+            ui.label(message);
+        }
+
+        ui.separator();
+        self.list_ui(ui, busy, &mut action);
+
+        match action {
+            Some(TemplateAction::Create) => self.create(ui.ctx()),
+            Some(TemplateAction::Save) => self.save(ui.ctx()),
+            Some(TemplateAction::Edit(template)) => {
+                self.name = template.name.clone();
+                self.kind = template.kind;
+                self.recapture = false;
+                self.editing = Some(template);
+            }
+            Some(TemplateAction::CancelEdit) => {
+                self.editing = None;
+                self.recapture = false;
+            }
+            Some(TemplateAction::Delete(name)) => self.delete(ui.ctx(), name),
+            Some(TemplateAction::Refresh) => self.spawn(ui.ctx(), async { None }),
+            None => {}
+        }
+    }
+
+    fn editor_ui(
+        &mut self,
+        ui: &mut Ui,
+        camera_uis: &BTreeMap<CameraId, CameraUi>,
+        busy: bool,
+        action: &mut Option<TemplateAction>,
+    ) {
+        ui.horizontal(|ui| {
+            ui.label(tr!("templates-label-camera"));
+            let selected_text = self
+                .camera
+                .map(|camera| camera.to_string())
+                .unwrap_or_default();
+            egui::ComboBox::from_id_salt("templates-camera")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for camera in camera_uis.keys() {
+                        if ui
+                            .selectable_value(&mut self.camera, Some(*camera), camera.to_string())
+                            .clicked()
+                        {
+                            self.crop = None;
+                        }
+                    }
+                });
+        });
+
+        match self
+            .camera
+            .and_then(|camera| camera_uis.get(&camera))
+            .and_then(CameraUi::texture)
+        {
+            Some(texture) => {
+                let response = egui::Image::new(texture)
+                    .max_size(Vec2::new(ui.available_width(), ui.available_height() / 2.0))
+                    .maintain_aspect_ratio(true)
+                    .sense(Sense::drag())
+                    .ui(ui);
+                self.crop_ui(ui, &response, texture.size_vec2());
+            }
+            None => {
+                ui.label(tr!("templates-message-no-camera"));
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(tr!("templates-label-name"));
+            ui.add(egui::TextEdit::singleline(&mut self.name).desired_width(160.0));
+            for (kind, text) in [
+                (TemplateKind::Fiducial, tr!("templates-kind-fiducial")),
+                (TemplateKind::Part, tr!("templates-kind-part")),
+            ] {
+                ui.selectable_value(&mut self.kind, kind, text);
+            }
+        });
+
+        let has_name = !self.name.trim().is_empty();
+        ui.horizontal(|ui| match &self.editing {
+            Some(editing) => {
+                ui.label(tr!("templates-label-editing", { name: editing.name.clone() }));
+                ui.checkbox(&mut self.recapture, tr!("templates-checkbox-recapture"));
+                let can_save = has_name && (!self.recapture || self.crop.is_some());
+                if ui
+                    .add_enabled(!busy && can_save, egui::Button::new(tr!("templates-button-save")))
+                    .clicked()
+                {
+                    *action = Some(TemplateAction::Save);
+                }
+                if ui
+                    .button(tr!("templates-button-cancel"))
+                    .clicked()
+                {
+                    *action = Some(TemplateAction::CancelEdit);
+                }
+            }
+            None => {
+                let can_create = has_name && self.crop.is_some();
+                if ui
+                    .add_enabled(!busy && can_create, egui::Button::new(tr!("templates-button-create")))
+                    .on_disabled_hover_text(tr!("templates-hint-crop"))
+                    .clicked()
+                {
+                    *action = Some(TemplateAction::Create);
+                }
+            }
+        });
+    }
+
+    /// Dragging on the image sets the crop, a secondary click clears it.
+    fn crop_ui(&mut self, ui: &Ui, response: &egui::Response, image_size: Vec2) {
+        let rect = response.rect;
+        // screen points per image pixel
+        let zoom = rect.width() / image_size.x;
+        let image_rect = Rect::from_min_size(Pos2::ZERO, image_size);
+        let to_image = |position: Pos2| image_rect.clamp(((position - rect.min) / zoom).to_pos2());
+        let to_screen = |point: Pos2| rect.min + point.to_vec2() * zoom;
+
+        if response.drag_started() {
+            self.drag_start = response
+                .interact_pointer_pos()
+                .map(to_image);
+        }
+        if response.dragged() {
+            if let (Some(start), Some(position)) = (self.drag_start, response.interact_pointer_pos()) {
+                let crop = Rect::from_two_pos(start, to_image(position));
+                // an empty crop is refused by the server
+                self.crop = (crop.width() >= 1.0 && crop.height() >= 1.0).then_some(crop);
+            }
+        }
+        if response.drag_stopped() {
+            self.drag_start = None;
+        }
+        if response.secondary_clicked() {
+            self.crop = None;
+        }
+
+        if let Some(crop) = self.crop {
+            let painter = ui.painter_at(rect);
+            painter.rect_stroke(
+                Rect::from_min_max(to_screen(crop.min), to_screen(crop.max)),
+                0.0,
+                Stroke::new(2.0, CROP_COLOR),
+                egui::StrokeKind::Middle,
+            );
+            painter.text(
+                to_screen(crop.min) - Vec2::new(0.0, 4.0),
+                egui::Align2::LEFT_BOTTOM,
+                format!("{:.0} x {:.0}", crop.width(), crop.height()),
+                egui::FontId::default(),
+                CROP_COLOR,
+            );
+        }
+    }
+
+    fn list_ui(&self, ui: &mut Ui, busy: bool, action: &mut Option<TemplateAction>) {
+        let state = self.state.clone();
+        let state = state.lock().unwrap();
+        if state.templates.is_empty() {
+            ui.label(tr!("templates-message-empty"));
+            return;
+        }
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                egui::Grid::new("templates")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label(tr!("templates-column-name"));
+                        ui.label(tr!("templates-column-kind"));
+                        ui.label(tr!("templates-column-size"));
+                        ui.label(tr!("templates-column-camera"));
+                        ui.label(tr!("templates-column-updated"));
+                        ui.label("");
+                        ui.end_row();
+
+                        for template in state.templates.iter() {
+                            let kind = match template.kind {
+                                TemplateKind::Fiducial => tr!("templates-kind-fiducial"),
+                                TemplateKind::Part => tr!("templates-kind-part"),
+                            };
+
+                            ui.label(template.name.as_str());
+                            ui.label(kind);
+                            ui.label(format!("{} x {}", template.width, template.height));
+                            ui.label(template.camera.to_string());
+                            ui.label(format!(
+                                "{}",
+                                template
+                                    .updated_at
+                                    .format("%Y-%m-%d %H:%M:%S")
+                            ));
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .add_enabled(!busy, egui::Button::new(tr!("templates-button-edit")))
+                                    .clicked()
+                                {
+                                    *action = Some(TemplateAction::Edit(template.clone()));
+                                }
+                                if ui
+                                    .add_enabled(!busy, egui::Button::new(tr!("templates-button-delete")))
+                                    .clicked()
+                                {
+                                    *action = Some(TemplateAction::Delete(template.name.clone()));
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
+
+fn saved_message(name: &str, result: anyhow::Result<Result<TemplateInfo, TemplateError>>) -> Option<RichText> {
+    match result {
+        Ok(Ok(template)) => {
+            info!(
+                "Template saved. name: {}, width: {}, height: {}",
+                template.name, template.width, template.height
+            );
+            Some(RichText::new(tr!("templates-message-saved", { name: template.name })).color(status_color(Status::Ok)))
+        }
+        Ok(Err(e)) => {
+            warn!("Template rejected. name: {}, error: {:?}", name, e);
+            Some(RichText::new(rejected_message(e)).color(status_color(Status::Warn)))
+        }
+        Err(e) => {
+            error!("Unable to save template. name: {}, error: {:?}", name, e);
+            Some(
+                RichText::new(tr!("templates-message-error", { error: format!("{}", e) }))
+                    .color(status_color(Status::Fault)),
+            )
+        }
+    }
+}
+
+fn rejected_message(error: TemplateError) -> String {
+    match error {
+        TemplateError::InvalidName => tr!("templates-error-invalid-name"),
+        TemplateError::AlreadyExists => tr!("templates-error-already-exists"),
+        TemplateError::NotFound => tr!("templates-error-not-found"),
+        TemplateError::UnknownCamera => tr!("templates-error-unknown-camera"),
+        TemplateError::CaptureFailed => tr!("templates-error-capture-failed"),
+        TemplateError::InvalidCrop => tr!("templates-error-invalid-crop"),
+        TemplateError::Storage => tr!("templates-error-storage"),
+    }
+}
@@ -0,0 +1,351 @@
+use egui::{Context, RichText, Ui};
+use egui_i18n::tr;
+use egui_mobius::Value;
+use ergot::Address;
+use ergot::toolkits::tokio_udp::EdgeStack;
+use machine_ids::FeederId;
+use operator_shared::test_area::{TEST_PATTERN_SHOTS_MAX, TestPattern, TestShotError, TestShotEvent, TestShotKind};
+use tokio::runtime::Handle;
+use tracing::{error, info, warn};
+
+use crate::net::commands::{clear_test_area, run_test_pattern};
+use crate::ui_common::style::{Status, status_color};
+use crate::ui_common::units::formatter;
+
+/// Test shots in the test area of the machine, e.g. to test dispensing or to verify the pickup from a feeder.
+pub(crate) struct TestShotsUi {
+    client: Option<TestShotsClient>,
+    columns: u32,
+    rows: u32,
+    /// in millimeters, shown in the length unit of the unit system
+    pitch: f32,
+    place: bool,
+    head: String,
+    feeder: String,
+    progress: Option<TestShotProgress>,
+    state: Value<TestShotsState>,
+}
+
+impl Default for TestShotsUi {
+    fn default() -> Self {
+        Self {
+            client: None,
+            columns: 5,
+            rows: 2,
+            pitch: 2.0,
+            place: false,
+            head: String::new(),
+            feeder: String::new(),
+            progress: None,
+            state: Value::default(),
+        }
+    }
+}
+
+struct TestShotsClient {
+    stack: EdgeStack,
+    address: Address,
+    runtime: Handle,
+}
+
+struct TestShotProgress {
+    shots: u32,
+    placed: u32,
+    failed: u32,
+    finished: bool,
+}
+
+#[derive(Default)]
+struct TestShotsState {
+    busy: bool,
+    message: Option<RichText>,
+}
+
+enum TestShotsAction {
+    Run(TestPattern, TestShotKind),
+    Clear,
+}
+
+impl TestShotsUi {
+    /// Must be called from within the tokio runtime.
+    pub fn connect(&mut self, stack: EdgeStack, address: Address) {
+        self.client = Some(TestShotsClient {
+            stack,
+            address,
+            runtime: Handle::current(),
+        });
+    }
+
+    pub fn update(&mut self, event: TestShotEvent) {
+        match event {
+            TestShotEvent::Started {
+                shots,
+            } => {
+                self.progress = Some(TestShotProgress {
+                    shots,
+                    placed: 0,
+                    failed: 0,
+                    finished: false,
+                });
+            }
+            TestShotEvent::Shot {
+                ok, ..
+            } => {
+                // the shots may have been started before the ui was connected
+                let Some(progress) = &mut self.progress else {
+                    return;
+                };
+                match ok {
+                    true => progress.placed += 1,
+                    false => progress.failed += 1,
+                }
+            }
+            TestShotEvent::Finished {
+                placed,
+                failed,
+            } => {
+                let progress = self
+                    .progress
+                    .get_or_insert(TestShotProgress {
+                        shots: placed + failed,
+                        placed,
+                        failed,
+                        finished: true,
+                    });
+                progress.placed = placed;
+                progress.failed = failed;
+                progress.finished = true;
+            }
+        }
+    }
+
+    fn run(&mut self, context: &Context, pattern: TestPattern, kind: TestShotKind) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = run_test_pattern(stack, address, pattern, kind.clone()).await;
+
+            let message = match result {
+                Ok(Ok(shots)) => {
+                    info!(
+                        "Test shots started. shots: {}, pattern: {:?}, kind: {:?}",
+                        shots, pattern, kind
+                    );
+                    None
+                }
+                Ok(Err(e)) => {
+                    warn!(
+                        "Test shots refused. pattern: {:?}, kind: {:?}, error: {:?}",
+                        pattern, kind, e
+                    );
+                    Some(RichText::new(error_text(&e)).color(status_color(Status::Warn)))
+                }
+                Err(e) => {
+                    error!("Unable to run test pattern. error: {:?}", e);
+                    Some(
+                        RichText::new(tr!("test-shots-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = message;
+            context.request_repaint();
+        });
+    }
+
+    fn clear(&mut self, context: &Context) {
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        self.state.lock().unwrap().busy = true;
+
+        let stack = client.stack.clone();
+        let address = client.address;
+        let state = self.state.clone();
+        let context = context.clone();
+        client.runtime.spawn(async move {
+            let result = clear_test_area(stack, address).await;
+
+            let message = match result {
+                Ok(Ok(())) => {
+                    info!("Test area cleared");
+                    Some(RichText::new(tr!("test-shots-message-cleared")))
+                }
+                Ok(Err(e)) => {
+                    warn!("Test area clear refused. error: {:?}", e);
+                    Some(RichText::new(error_text(&e)).color(status_color(Status::Warn)))
+                }
+                Err(e) => {
+                    error!("Unable to clear test area. error: {:?}", e);
+                    Some(
+                        RichText::new(tr!("test-shots-message-error", { error: format!("{}", e) }))
+                            .color(status_color(Status::Fault)),
+                    )
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.message = message;
+            context.request_repaint();
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let (busy, message) = {
+            let state = self.state.lock().unwrap();
+            (state.busy, state.message.clone())
+        };
+        if self.client.is_none() {
+            ui.label(tr!("test-shots-message-waiting"));
+            return;
+        }
+
+        let length_unit = formatter().length_unit();
+        let mm_per_unit = length_unit.mm_per_unit();
+
+        egui::Grid::new("test-shots")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label(tr!("test-shots-label-columns"));
+                ui.add(egui::DragValue::new(&mut self.columns).range(1..=TEST_PATTERN_SHOTS_MAX));
+                ui.end_row();
+
+                ui.label(tr!("test-shots-label-rows"));
+                ui.add(egui::DragValue::new(&mut self.rows).range(1..=TEST_PATTERN_SHOTS_MAX));
+                ui.end_row();
+
+                ui.label(tr!("test-shots-label-pitch"));
+                let pitch = &mut self.pitch;
+                ui.add(
+                    egui::DragValue::from_get_set(|value| {
+                        if let Some(value) = value {
+                            *pitch = (value * mm_per_unit) as f32;
+                        }
+                        *pitch as f64 / mm_per_unit
+                    })
+                    .range(0.0..=f64::MAX)
+                    .speed(0.1)
+                    .suffix(format!(" {}", length_unit.symbol())),
+                );
+                ui.end_row();
+
+                ui.label(tr!("test-shots-label-kind"));
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.place, false, tr!("test-shots-kind-dispense"));
+                    ui.radio_value(&mut self.place, true, tr!("test-shots-kind-place"));
+                });
+                ui.end_row();
+
+                match self.place {
+                    true => {
+                        ui.label(tr!("test-shots-label-feeder"));
+                        ui.text_edit_singleline(&mut self.feeder);
+                    }
+                    false => {
+                        ui.label(tr!("test-shots-label-head"));
+                        ui.text_edit_singleline(&mut self.head);
+                    }
+                }
+                ui.end_row();
+            });
+
+        let shots = self.columns.saturating_mul(self.rows);
+        let selected = match self.place {
+            true => &self.feeder,
+            false => &self.head,
+        };
+        let valid = shots <= TEST_PATTERN_SHOTS_MAX && self.pitch > 0.0 && !selected.trim().is_empty();
+
+        let mut action = None;
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    !busy && valid,
+                    egui::Button::new(tr!("test-shots-button-run", { shots: shots })),
+                )
+                .clicked()
+            {
+                let pattern = TestPattern {
+                    columns: self.columns,
+                    rows: self.rows,
+                    pitch: self.pitch,
+                };
+                let kind = match self.place {
+                    true => TestShotKind::Place {
+                        feeder: FeederId::new(self.feeder.trim()),
+                    },
+                    false => TestShotKind::Dispense {
+                        head: self.head.trim().to_string(),
+                    },
+                };
+                action = Some(TestShotsAction::Run(pattern, kind));
+            }
+            if ui
+                .add_enabled(!busy, egui::Button::new(tr!("test-shots-button-clear")))
+                .on_hover_text(tr!("test-shots-hint-clear"))
+                .clicked()
+            {
+                action = Some(TestShotsAction::Clear);
+            }
+            if busy {
+                ui.spinner();
+            }
+        });
+
+        if let Some(progress) = &self.progress {
+            ui.separator();
+            let state = match progress.finished {
+                true => tr!("test-shots-state-finished"),
+                false => tr!("test-shots-state-running"),
+            };
+            let color = match progress.failed {
+                0 => ui.visuals().text_color(),
+                _ => status_color(Status::Warn),
+            };
+            ui.label(state);
+            ui.label(
+                RichText::new(tr!("test-shots-progress", {
+                    placed: progress.placed,
+                    failed: progress.failed,
+                    shots: progress.shots
+                }))
+                .color(color),
+            );
+        }
+
+        if let Some(message) = message {
+            ui.label(message);
+        }
+
+        match action {
+            Some(TestShotsAction::Run(pattern, kind)) => self.run(ui.ctx(), pattern, kind),
+            Some(TestShotsAction::Clear) => self.clear(ui.ctx()),
+            None => {}
+        }
+    }
+}
+
+fn error_text(error: &TestShotError) -> String {
+    match error {
+        TestShotError::NotConfigured => tr!("test-shots-error-not-configured"),
+        TestShotError::InvalidPattern => tr!("test-shots-error-invalid-pattern"),
+        TestShotError::AreaFull => tr!("test-shots-error-area-full"),
+        TestShotError::Running => tr!("test-shots-error-running"),
+        TestShotError::UnknownFeeder => tr!("test-shots-error-unknown-feeder"),
+        TestShotError::UnknownHead => tr!("test-shots-error-unknown-head"),
+        TestShotError::NoMotion => tr!("test-shots-error-no-motion"),
+    }
+}
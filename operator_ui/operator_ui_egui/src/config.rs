@@ -1,13 +1,21 @@
+use units::UnitSystem;
+
+use crate::ui_common::style::StatusPalette;
+
 #[derive(serde::Deserialize, serde::Serialize, Debug)]
 #[serde(default)] // if we add new fields, give them default values when deserializing old state
 pub struct Config {
     pub language_identifier: String,
+    pub unit_system: UnitSystem,
+    pub status_palette: StatusPalette,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             language_identifier: egui_i18n::get_language(),
+            unit_system: UnitSystem::default(),
+            status_palette: StatusPalette::default(),
         }
     }
 }
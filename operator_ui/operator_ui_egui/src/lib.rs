@@ -7,9 +7,6 @@ const REMOTE_ADDR: &str = "127.0.0.1:8001";
 //const LOCAL_ADDR: &str = "0.0.0.0:5001";
 const LOCAL_ADDR: &str = "0.0.0.0:8002";
 
-// TODO remove `TARGET_FPS` it's value should come from the per-camera FPS configuration on the
-//      server via camera discovery
-const TARGET_FPS: f32 = 30.0;
 const SCHEDULED_FPS_MIN: f32 = 5.0;
 const SCHEDULED_FPS_MAX: f32 = 60.0;
 
@@ -1,32 +1,62 @@
 use std::{pin::pin, time::Duration};
 
+use egui::ViewportId;
 use egui_mobius::Value;
 use ergot::traits::Endpoint;
-use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::well_known::{ErgotFmtRxOwnedTopic, NameRequirement, SocketQuery};
 use ergot::{
     FrameKind,
     toolkits::tokio_udp::{EdgeStack, new_std_queue, new_target_stack},
     topic,
 };
 use ergot::toolkits::tokio_udp::register_edge_target_interface;
-use operator_shared::camera::CameraIdentifier;
+use ioboard_shared::estop::{EStop, EStopTopic};
+use ioboard_shared::load::AxisLoad;
+use ioboard_shared::load_cell::LoadCellSample;
+use ioboard_shared::safety::SafetyStatus;
+use ioboard_shared::self_test::SelfTestStatus;
+use ioboard_shared::thermal::ThermalReading;
+use ioboard_shared::vibration::VibrationReport;
+use operator_shared::camera::CameraLayoutHint;
+use operator_shared::diagnostics::{CameraMemoryReport, CommandLatencyReport, RouterReport};
+use operator_shared::feeders::{FeederEvent, FeedersStatus};
+use operator_shared::homing::HomingStatus;
+use operator_shared::job::JobEvent;
+use operator_shared::limits::LimitOverrideStatus;
+use operator_shared::maintenance::MaintenanceEvent;
+use operator_shared::power::PowerReading;
+use operator_shared::readiness::ReadinessStatus;
+use operator_shared::test_area::TestShotEvent;
+use operator_shared::vision::VisionStatus;
+use retry::{Backoff, BackoffConfig};
 use tokio::sync::broadcast;
 use tokio::{net::UdpSocket, select, time};
 use tracing::{debug, error, info, warn};
 
 use crate::app::{AppState, PaneKind};
 use crate::events::AppEvent;
-use crate::net::commands::{OperatorCommandEndpoint, heartbeat_sender};
+use crate::net::commands::{
+    OperatorCommandEndpoint, fetch_job_checkpoint, fetch_machine_geometry, heartbeat_sender, list_cameras,
+};
 use crate::net::services::basic_services;
 use crate::net::shutdown::app_shutdown_handler;
-use crate::workspace::{ToggleDefinition, WorkspaceError, Workspaces};
-use crate::{LOCAL_ADDR, REMOTE_ADDR, SCHEDULED_FPS_MAX, TARGET_FPS};
+use crate::workspace::{ToggleDefinition, ViewMode, WorkspaceError, Workspaces};
+use crate::{LOCAL_ADDR, REMOTE_ADDR, SCHEDULED_FPS_MAX, SCHEDULED_FPS_MIN};
 
 pub mod camera;
 pub mod commands;
 pub mod services;
 pub mod shutdown;
 
+/// The server may not have started yet, the first attempts are quick, so that the UI connects as soon as it has.
+const DISCOVERY_BACKOFF: BackoffConfig = BackoffConfig {
+    initial: Duration::from_millis(250),
+    max: Duration::from_secs(5),
+    multiplier: 2,
+    jitter: 0.5,
+    max_elapsed: None,
+};
+
 pub async fn ergot_task(
     state: Value<AppState>,
     workspaces: Value<Workspaces>,
@@ -54,13 +84,99 @@ pub async fn ergot_task(
         .await
         .unwrap();
 
+    // the e-stop is a broadcast, it doesn't wait for the server to be discovered
+    state
+        .lock()
+        .unwrap()
+        .connect_estop(stack.clone());
+
     let basic_services_handle = tokio::task::Builder::new()
         .name("ergot/basic-services")
         .spawn(basic_services(stack.clone(), port, app_event_tx.subscribe()))?;
 
-    let yeet_listener_handle = tokio::task::Builder::new()
-        .name("ergot/yeet-listener")
-        .spawn(yeet_listener(stack.clone(), app_event_tx.subscribe()))?;
+    let load_cell_listener_handle = tokio::task::Builder::new()
+        .name("ergot/load-cell-listener")
+        .spawn(load_cell_listener(stack.clone(), app_event_tx.subscribe()))?;
+
+    let vibration_listener_handle = tokio::task::Builder::new()
+        .name("ergot/vibration-listener")
+        .spawn(vibration_listener(
+            stack.clone(),
+            state.clone(),
+            app_event_tx.subscribe(),
+        ))?;
+
+    let thermal_listener_handle = tokio::task::Builder::new()
+        .name("ergot/thermal-listener")
+        .spawn(thermal_listener(stack.clone(), state.clone(), app_event_tx.subscribe()))?;
+
+    let safety_listener_handle = tokio::task::Builder::new()
+        .name("ergot/safety-listener")
+        .spawn(safety_listener(stack.clone(), state.clone(), app_event_tx.subscribe()))?;
+
+    let self_test_listener_handle = tokio::task::Builder::new()
+        .name("ergot/self-test-listener")
+        .spawn(self_test_listener(
+            stack.clone(),
+            state.clone(),
+            app_event_tx.subscribe(),
+        ))?;
+
+    let load_listener_handle = tokio::task::Builder::new()
+        .name("ergot/load-listener")
+        .spawn(load_listener(stack.clone(), state.clone(), app_event_tx.subscribe()))?;
+
+    let power_listener_handle = tokio::task::Builder::new()
+        .name("ergot/power-listener")
+        .spawn(power_listener(stack.clone(), state.clone(), app_event_tx.subscribe()))?;
+
+    let latency_listener_handle = tokio::task::Builder::new()
+        .name("ergot/latency-listener")
+        .spawn(latency_listener(stack.clone(), state.clone(), app_event_tx.subscribe()))?;
+
+    let camera_memory_listener_handle = tokio::task::Builder::new()
+        .name("ergot/camera-memory-listener")
+        .spawn(camera_memory_listener(
+            stack.clone(),
+            state.clone(),
+            app_event_tx.subscribe(),
+        ))?;
+
+    let router_listener_handle = tokio::task::Builder::new()
+        .name("ergot/router-listener")
+        .spawn(router_listener(stack.clone(), state.clone(), app_event_tx.subscribe()))?;
+
+    let firmware_log_listener_handle = tokio::task::Builder::new()
+        .name("ergot/firmware-log-listener")
+        .spawn(firmware_log_listener(
+            stack.clone(),
+            state.clone(),
+            app_event_tx.subscribe(),
+        ))?;
+
+    let feeders_listener_handle = tokio::task::Builder::new()
+        .name("ergot/feeders-listener")
+        .spawn(feeders_listener(stack.clone(), state.clone(), app_event_tx.subscribe()))?;
+
+    let job_listener_handle = tokio::task::Builder::new()
+        .name("ergot/job-listener")
+        .spawn(job_listener(stack.clone(), state.clone(), app_event_tx.subscribe()))?;
+
+    let readiness_listener_handle = tokio::task::Builder::new()
+        .name("ergot/readiness-listener")
+        .spawn(readiness_listener(
+            stack.clone(),
+            state.clone(),
+            app_event_tx.subscribe(),
+        ))?;
+
+    let vision_status_listener_handle = tokio::task::Builder::new()
+        .name("ergot/vision-status-listener")
+        .spawn(vision_status_listener(
+            stack.clone(),
+            state.clone(),
+            app_event_tx.subscribe(),
+        ))?;
 
     let query = SocketQuery {
         key: OperatorCommandEndpoint::REQ_KEY.to_bytes(),
@@ -69,6 +185,7 @@ pub async fn ergot_task(
         broadcast: false,
     };
 
+    let mut backoff = Backoff::from_time(DISCOVERY_BACKOFF);
     let discovery_results = loop {
         let discovery = stack.discovery();
 
@@ -92,7 +209,7 @@ pub async fn ergot_task(
             }
         }
 
-        time::sleep(Duration::from_millis(250)).await;
+        time::sleep(backoff.next_unbounded()).await;
     };
 
     if let Some(discovery_results) = discovery_results {
@@ -107,25 +224,69 @@ pub async fn ergot_task(
             app_event_tx.subscribe(),
         ));
 
-        // TODO enumerate the available cameras from the server
-        let camera_configs = [
-            (CameraIdentifier::new(0), TARGET_FPS),
-            (CameraIdentifier::new(1), SCHEDULED_FPS_MAX),
-            //(CameraIdentifier::new(2), SCHEDULED_FPS_MAX),
-        ];
+        let cameras = list_cameras(stack.clone(), command_endpoint_remote_address)
+            .await
+            .inspect_err(|e| error!("Unable to list cameras: {:?}", e))
+            .unwrap_or_default();
 
-        info!("Starting cameras. ids: {:?}", camera_configs);
-        for (camera_identifier, target_fps) in camera_configs.iter() {
+        match fetch_machine_geometry(stack.clone(), command_endpoint_remote_address).await {
+            Ok(geometry) => state
+                .lock()
+                .unwrap()
+                .update_machine_geometry(geometry),
+            Err(e) => error!("Unable to fetch machine geometry: {:?}", e),
+        }
+
+        match fetch_job_checkpoint(stack.clone(), command_endpoint_remote_address).await {
+            Ok(checkpoint) => state
+                .lock()
+                .unwrap()
+                .update_job_checkpoint(checkpoint),
+            Err(e) => error!("Unable to fetch job checkpoint: {:?}", e),
+        }
+
+        {
+            let app_state = state.lock().unwrap();
+            app_state.connect_captures(stack.clone(), command_endpoint_remote_address);
+            app_state.connect_readiness(stack.clone(), command_endpoint_remote_address);
+            app_state.connect_job(stack.clone(), command_endpoint_remote_address);
+            app_state.connect_feeders(stack.clone(), command_endpoint_remote_address);
+            app_state.connect_templates(stack.clone(), command_endpoint_remote_address);
+            app_state.connect_test_shots(stack.clone(), command_endpoint_remote_address);
+            app_state.connect_limits(stack.clone(), command_endpoint_remote_address);
+        }
+
+        info!(
+            "Starting cameras. ids: {:?}",
+            cameras
+                .iter()
+                .map(|camera| camera.identifier)
+                .collect::<Vec<_>>()
+        );
+        for camera in cameras.iter() {
+            let camera_identifier = &camera.identifier;
+            let target_fps = camera
+                .fps
+                .clamp(SCHEDULED_FPS_MIN, SCHEDULED_FPS_MAX);
             {
                 let app_state = state.lock().unwrap();
                 app_state.add_camera(
                     *camera_identifier,
                     stack.clone(),
                     command_endpoint_remote_address,
-                    *target_fps,
+                    target_fps,
+                    camera.mounting,
+                    camera.calibration,
                 );
             }
 
+            // only used for the initial arrangement, the operator's arrangement is persisted
+            let mode = match camera.layout {
+                CameraLayoutHint::Primary => ViewMode::Tile(ViewportId::ROOT),
+                CameraLayoutHint::Secondary => ViewMode::Window(ViewportId::ROOT),
+                CameraLayoutHint::Hidden => ViewMode::Disabled,
+            };
+
             {
                 let mut workspaces = workspaces.lock().unwrap();
 
@@ -134,6 +295,7 @@ pub async fn ergot_task(
                     kind: PaneKind::Camera {
                         id: camera_identifier.clone(),
                     },
+                    mode,
                 }) {
                     Err(WorkspaceError::DuplicateToggleKey) => {
                         // ignore, we already have a toggle with this key - from a previous session
@@ -171,21 +333,50 @@ pub async fn ergot_task(
 
     info!("Waiting for basic services to finish");
     let _ = basic_services_handle.await;
-    info!("Waiting for yeet listener to finish");
-    let _ = yeet_listener_handle.await;
+    info!("Waiting for load cell listener to finish");
+    let _ = load_cell_listener_handle.await;
+    info!("Waiting for vibration listener to finish");
+    let _ = vibration_listener_handle.await;
+    info!("Waiting for thermal listener to finish");
+    let _ = thermal_listener_handle.await;
+    info!("Waiting for safety listener to finish");
+    let _ = safety_listener_handle.await;
+    info!("Waiting for self test listener to finish");
+    let _ = self_test_listener_handle.await;
+    info!("Waiting for load listener to finish");
+    let _ = load_listener_handle.await;
+    info!("Waiting for power listener to finish");
+    let _ = power_listener_handle.await;
+    info!("Waiting for latency listener to finish");
+    let _ = latency_listener_handle.await;
+    info!("Waiting for camera memory listener to finish");
+    let _ = camera_memory_listener_handle.await;
+    info!("Waiting for router listener to finish");
+    let _ = router_listener_handle.await;
+    info!("Waiting for firmware log listener to finish");
+    let _ = firmware_log_listener_handle.await;
+    info!("Waiting for feeders listener to finish");
+    let _ = feeders_listener_handle.await;
+    info!("Waiting for job listener to finish");
+    let _ = job_listener_handle.await;
+    info!("Waiting for readiness listener to finish");
+    let _ = readiness_listener_handle.await;
+    info!("Waiting for vision status listener to finish");
+    let _ = vision_status_listener_handle.await;
 
     info!("Network task shutdown");
     Ok(())
 }
 
-topic!(YeetTopic, u64, "topic/yeet");
+topic!(LoadCellTopic, LoadCellSample, "topic/ioboard/load_cell");
 
-async fn yeet_listener(stack: EdgeStack, app_event_rx: broadcast::Receiver<AppEvent>) {
+/// The samples of the load cell are only published while the server has begun the stream, the rate is logged.
+async fn load_cell_listener(stack: EdgeStack, app_event_rx: broadcast::Receiver<AppEvent>) {
     let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
 
     let subber = stack
         .topics()
-        .heap_bounded_receiver::<YeetTopic>(64, None);
+        .heap_bounded_receiver::<LoadCellTopic>(64, None);
     let subber = pin!(subber);
     let mut hdl = subber.subscribe();
 
@@ -195,15 +386,447 @@ async fn yeet_listener(stack: EdgeStack, app_event_rx: broadcast::Receiver<AppEv
     loop {
         select! {
             _ = ticker.tick() => {
-                info!("packet rate: {}/{:?}", packets_this_interval, interval);
+                if packets_this_interval > 0 {
+                    info!("load cell sample rate: {}/{:?}", packets_this_interval, interval);
+                }
                 packets_this_interval = 0;
             }
             msg = hdl.recv() => {
                 packets_this_interval += 1;
-                debug!("{}: got {}", msg.hdr, msg.t);
+                debug!("{}: load cell sample, grams: {}, raw: {}", msg.hdr, msg.t.grams, msg.t.raw);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("load cell listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(VibrationTopic, VibrationReport, "topic/ioboard/vibration");
+
+async fn vibration_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<VibrationTopic>(8, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.add_vibration_report(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("vibration listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(ThermalTopic, ThermalReading, "topic/ioboard/thermal");
+
+async fn thermal_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<ThermalTopic>(8, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_temperature(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("thermal listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(SafetyTopic, SafetyStatus, "topic/ioboard/safety");
+
+async fn safety_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<SafetyTopic>(8, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_safety_status(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("safety listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+/// Broadcast directly to the io boards, routed by the server, so that the e-stop doesn't wait for the round trip of a
+/// command.  Returns `false` if it could not be sent.
+pub(crate) fn broadcast_estop(stack: &EdgeStack, estop: EStop) -> bool {
+    match stack
+        .topics()
+        .broadcast::<EStopTopic>(&estop, None)
+    {
+        Ok(()) => {
+            warn!("E-stop broadcast. estop: {:?}", estop);
+            true
+        }
+        Err(e) => {
+            error!("Unable to broadcast e-stop. estop: {:?}, error: {:?}", estop, e);
+            false
+        }
+    }
+}
+
+topic!(SelfTestTopic, SelfTestStatus, "topic/ioboard/self-test");
+
+async fn self_test_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<SelfTestTopic>(4, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_self_test_status(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("self test listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(LoadTopic, AxisLoad, "topic/ioboard/load");
+
+async fn load_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<LoadTopic>(16, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_axis_load(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("load listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(PowerTopic, PowerReading, "topic/operator/power");
+
+async fn power_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<PowerTopic>(4, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_power(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("power listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(
+    CommandLatencyTopic,
+    CommandLatencyReport,
+    "topic/diagnostics/command-latency"
+);
+
+async fn latency_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<CommandLatencyTopic>(4, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_command_latency(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("latency listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(CameraMemoryTopic, CameraMemoryReport, "topic/diagnostics/camera-memory");
+
+async fn camera_memory_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<CameraMemoryTopic>(4, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_camera_memory(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("camera memory listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(RouterReportTopic, RouterReport, "topic/diagnostics/router");
+
+async fn router_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<RouterReportTopic>(4, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_router_report(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("router listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+/// The log messages of the io boards, broadcast on ergot's well-known log topic and forwarded by the server.
+async fn firmware_log_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<ErgotFmtRxOwnedTopic>(64, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.add_firmware_log(msg.hdr.src, &msg.t.level, msg.t.inner);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("firmware log listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(FeedersStatusTopic, FeedersStatus, "topic/operator/feeders");
+topic!(FeederEventTopic, FeederEvent, "topic/operator/feeder-events");
+
+async fn feeders_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let status_subber = stack
+        .topics()
+        .heap_bounded_receiver::<FeedersStatusTopic>(4, None);
+    let status_subber = pin!(status_subber);
+    let mut status_hdl = status_subber.subscribe();
+
+    let event_subber = stack
+        .topics()
+        .heap_bounded_receiver::<FeederEventTopic>(16, None);
+    let event_subber = pin!(event_subber);
+    let mut event_hdl = event_subber.subscribe();
+
+    loop {
+        select! {
+            msg = status_hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_feeders(msg.t);
+            }
+            msg = event_hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.add_feeder_event(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("feeders listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(JobEventTopic, JobEvent, "topic/operator/job");
+topic!(TestShotEventTopic, TestShotEvent, "topic/operator/test-shots");
+
+async fn job_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<JobEventTopic>(16, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    let test_shot_subber = stack
+        .topics()
+        .heap_bounded_receiver::<TestShotEventTopic>(16, None);
+    let test_shot_subber = pin!(test_shot_subber);
+    let mut test_shot_hdl = test_shot_subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_job(msg.t);
+            }
+            msg = test_shot_hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_test_shots(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("job listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(ReadinessTopic, ReadinessStatus, "topic/operator/readiness");
+topic!(HomingStatusTopic, HomingStatus, "topic/operator/homing");
+topic!(MaintenanceTopic, MaintenanceEvent, "topic/operator/maintenance");
+topic!(
+    LimitOverrideTopic,
+    LimitOverrideStatus,
+    "topic/operator/limit-overrides"
+);
+
+async fn readiness_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<ReadinessTopic>(4, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    let maintenance_subber = stack
+        .topics()
+        .heap_bounded_receiver::<MaintenanceTopic>(16, None);
+    let maintenance_subber = pin!(maintenance_subber);
+    let mut maintenance_hdl = maintenance_subber.subscribe();
+
+    let homing_subber = stack
+        .topics()
+        .heap_bounded_receiver::<HomingStatusTopic>(16, None);
+    let homing_subber = pin!(homing_subber);
+    let mut homing_hdl = homing_subber.subscribe();
+
+    let limit_override_subber = stack
+        .topics()
+        .heap_bounded_receiver::<LimitOverrideTopic>(4, None);
+    let limit_override_subber = pin!(limit_override_subber);
+    let mut limit_override_hdl = limit_override_subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_readiness(msg.t);
+            }
+            msg = maintenance_hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.add_maintenance_event(msg.t);
+            }
+            msg = homing_hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_homing(msg.t);
+            }
+            msg = limit_override_hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_limit_overrides(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                info!("readiness listener shutdown requested, stopping");
+                break
+            }
+        }
+    }
+}
+
+topic!(VisionStatusTopic, VisionStatus, "topic/vision/status");
+
+async fn vision_status_listener(stack: EdgeStack, state: Value<AppState>, app_event_rx: broadcast::Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<VisionStatusTopic>(8, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let state = state.lock().unwrap();
+                state.update_vision_status(msg.t);
             }
             _ = &mut app_shutdown_handler => {
-                info!("yeet listener shutdown requested, stopping");
+                info!("vision status listener shutdown requested, stopping");
                 break
             }
         }
@@ -1,4 +1,3 @@
-use std::collections::HashMap;
 use std::pin::pin;
 use std::time::Duration;
 
@@ -7,22 +6,40 @@ use egui::Context;
 use ergot::toolkits::tokio_udp::EdgeStack;
 use ergot::{Address, topic};
 use image::ImageFormat;
-use operator_shared::camera::{CameraCommand, CameraFrameChunk, CameraFrameChunkKind, CameraIdentifier};
+use machine_ids::CameraId;
+use operator_shared::camera::{CameraCommand, CameraFrameChunk, CameraFrameChunkKind, HeadPosition};
 use operator_shared::commands::OperatorCommandRequest;
 use operator_shared::common::TimeStampUTC;
+use operator_shared::frame_assembly::FrameAssembler;
+use stream_pacing::{FpsEstimator, PacingConfig};
 use tokio::select;
+use tokio::sync::mpsc;
 use tokio::sync::watch::Sender;
 use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, trace, warn};
 
-use crate::net::commands::OperatorCommandEndpoint;
-use crate::{SCHEDULED_FPS_MAX, SCHEDULED_FPS_MIN, TARGET_FPS};
+use crate::net::commands::{OperatorCommandEndpoint, capture_camera_frame};
+use crate::{SCHEDULED_FPS_MAX, SCHEDULED_FPS_MIN};
 
-topic!(CameraFrameChunkTopic, CameraFrameChunk, "topic/camera_stream");
+topic!(CameraFrameChunkTopic, CameraFrameChunk<'a>, "topic/camera_stream");
 
 const STREAM_TIMEOUT: Duration = Duration::from_secs(5);
 const STEAM_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// Larger frames are discarded, much larger than the jpeg of any supported camera resolution.
+const MAX_FRAME_BYTES: u32 = 16 * 1024 * 1024;
+/// The chunks are decoded from the receive buffer, without a copy, until they are inserted into the [`FrameAssembler`].
+// FIXME magic number, room for 320 of the 1024 byte chunks of the server
+const CHUNK_BUFFER_BYTES: usize = 320 * 1100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraStreamControl {
+    /// Stop presenting frames, the last frame stays visible.  The stream keeps running, but frames are not decoded.
+    Pause,
+    Resume,
+    /// While paused, present the next frame captured after the request.
+    Step,
+}
 
 pub async fn camera_frame_listener(
     stack: EdgeStack,
@@ -30,47 +47,82 @@ pub async fn camera_frame_listener(
     context: Context,
     remote_address: Address,
     shutdown_token: CancellationToken,
-    camera_identifier: CameraIdentifier,
+    camera_identifier: CameraId,
     target_fps: f32,
+    mut control_rx: mpsc::UnboundedReceiver<CameraStreamControl>,
 ) -> anyhow::Result<()> {
     let command_client = stack
         .endpoints()
         .client::<OperatorCommandEndpoint>(remote_address, None);
     let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
 
-    // FIXME magic number 320
     let subber = stack
         .topics()
-        .bounded_receiver::<CameraFrameChunkTopic, 320>(None);
+        .heap_borrowed_receiver::<CameraFrameChunkTopic>(CHUNK_BUFFER_BYTES, None);
     let subber = pin!(subber);
     let mut hdl = subber.subscribe_unicast();
     let port_id = hdl.port();
 
-    struct InProgressFrame {
-        total_chunks: u32,
-        chunks: Vec<Option<Vec<u8>>>,
-        received_count: u32,
-        start_time: Instant,
-        frame_timestamp: TimeStampUTC,
-        frame_number: u64,
-        frame_interval: Duration,
-    }
-
-    let mut in_progress: HashMap<u64, InProgressFrame> = HashMap::new();
+    // incomplete frames are discarded when a newer frame completes, only a few frames are in flight at a time
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let mut assembly_stats = assembler.stats();
 
-    let mut effective_fps = target_fps;
-    let mut frame_timestamps = std::collections::VecDeque::with_capacity(60);
+    let mut fps_estimator = FpsEstimator::new(target_fps, PacingConfig {
+        min_fps: SCHEDULED_FPS_MIN,
+        max_fps: SCHEDULED_FPS_MAX,
+        ..PacingConfig::default()
+    });
     let mut latest_msg_at = None;
     let mut latest_request_at = None;
 
     let mut ticker = tokio::time::interval(Duration::from_millis(250));
 
+    let mut paused = false;
+    // the first frame number to present after a step
+    let mut step_from: Option<u64> = None;
+    let (step_tx, mut step_rx) = mpsc::unbounded_channel::<anyhow::Result<u64>>();
+
     loop {
         select! {
             _ = shutdown_token.cancelled() => {
                 info!("Frame listener shutdown requested. identifier: {}", camera_identifier);
                 break
             }
+            control = control_rx.recv() => {
+                let Some(control) = control else {
+                    // the camera ui is gone
+                    break
+                };
+                debug!("Camera stream control. identifier: {}, control: {:?}", camera_identifier, control);
+                match control {
+                    CameraStreamControl::Pause => {
+                        paused = true;
+                        step_from = None;
+                    }
+                    CameraStreamControl::Resume => {
+                        paused = false;
+                        step_from = None;
+                    }
+                    CameraStreamControl::Step if paused => {
+                        // don't block receiving the stream, the frame being waited for arrives via the stream
+                        let stack = stack.clone();
+                        let step_tx = step_tx.clone();
+                        tokio::spawn(async move {
+                            let result = capture_camera_frame(stack, remote_address, camera_identifier).await;
+                            let _ = step_tx.send(result);
+                        });
+                    }
+                    CameraStreamControl::Step => {}
+                }
+            }
+            Some(result) = step_rx.recv() => {
+                match result {
+                    // a step result that arrives after resuming is ignored
+                    Ok(frame_number) if paused => step_from = Some(frame_number),
+                    Ok(_) => {}
+                    Err(e) => error!("Error stepping camera stream: {:?}, identifier: {}", e, camera_identifier),
+                }
+            }
             now = ticker.tick() => {
                 let have_recent_message = latest_msg_at
                     .map(|t| now.duration_since(t) <= STREAM_TIMEOUT)
@@ -84,6 +136,8 @@ pub async fn camera_frame_listener(
 
                 if should_send_start {
                     latest_request_at = Some(now);
+                    // the stream may be restarted, e.g. after the server restarted, the frame numbers start again
+                    assembler.reset();
                     debug!("Sending start request. latest_msg_at: {:?}, latest_request_at: {:?}", latest_msg_at, latest_request_at);
                     let result = command_client
                     .request(&OperatorCommandRequest::CameraCommand(
@@ -104,154 +158,89 @@ pub async fn camera_frame_listener(
 
                 latest_msg_at = Some(now);
 
-                let chunk = &msg.t;
-
-                let entry_and_image_chunk = match &chunk.kind {
-                    CameraFrameChunkKind::Meta(frame_meta) => {
-                        // Update timestamps for FPS estimation
-                        frame_timestamps.push_back(frame_meta.frame_timestamp);
-                        if frame_timestamps.len() > (TARGET_FPS * 2.0) as usize {
-                            frame_timestamps.pop_front();
-                        }
-
-                        // Recompute effective FPS
-                        if frame_timestamps.len() >= 2 {
-                            let mut iter = frame_timestamps.iter();
-                            let newest = iter.next_back().unwrap().0;
-                            let previous: chrono::DateTime<chrono::Utc> = iter.next_back().unwrap().0;
-                            let oldest = frame_timestamps.front().unwrap().0;
-
-                            let newest_previous_span = newest - previous;
-                            let total_span = newest - oldest;
-
-                            let frame_count = frame_timestamps.len() - 1;
-                            let measured_fps = frame_count as f64 / total_span.as_seconds_f64();
-
-
-                            // Smooth update using exponential moving average
-                            let alpha = 0.1;
-                            effective_fps = (1.0 - alpha) * effective_fps + alpha * (measured_fps as f32);
-
-                            debug!("measured FPS: {:.1}, effective FPS: {:.1}, total_span: {}ms, span: {}ms",
-                                measured_fps,
-                                effective_fps,
-                                total_span.num_milliseconds(),
-                                newest_previous_span.num_milliseconds(),
-                            );
-                        }
-
-                        // schedule next render
-                        let clamped_fps = effective_fps.clamp(SCHEDULED_FPS_MIN.into(), SCHEDULED_FPS_MAX.into());
-
-                        let frame_interval = Duration::from_secs_f64(1.0 / clamped_fps as f64);
-
-                        in_progress.insert(chunk.frame_number, InProgressFrame {
-                            total_chunks: frame_meta.total_chunks,
-                            chunks: vec![None; frame_meta.total_chunks as usize],
-                            received_count: 0,
-                            start_time: Instant::now(),
-                            frame_timestamp: frame_meta.frame_timestamp.clone(),
-                            frame_number: chunk.frame_number,
-                            frame_interval,
+                let chunk = msg.t;
 
-                        });
-                        continue;
-                    }
-                    CameraFrameChunkKind::ImageChunk(image_chunk) => {
-                        in_progress.get_mut(&chunk.frame_number).map(|entry|(entry, image_chunk))
+                if let CameraFrameChunkKind::Meta(frame_meta) = &chunk.kind {
+                    if let Some(measured_fps) = fps_estimator.update(frame_meta.frame_timestamp.0) {
+                        debug!("measured FPS: {:.1}, effective FPS: {:.1}", measured_fps, fps_estimator.effective_fps());
                     }
-                };
+                }
+
+                let frame_number = chunk.frame_number;
+                if let CameraFrameChunkKind::ImageChunk(image_chunk) = &chunk.kind {
+                    trace!(
+                        "received frame chunk: frame={} chunk={} size={}",
+                        frame_number,
+                        image_chunk.chunk_index + 1,
+                        image_chunk.bytes.len()
+                    );
+                }
 
-                let Some((entry, image_chunk)) = entry_and_image_chunk else {
+                let assembled = assembler.insert(chunk);
+                let stats = assembler.stats();
+                if stats.incomplete != assembly_stats.incomplete || stats.invalid != assembly_stats.invalid {
+                    warn!(
+                        "discarded frames, incomplete: {}, invalid: {}",
+                        stats.incomplete - assembly_stats.incomplete,
+                        stats.invalid - assembly_stats.invalid,
+                    );
+                }
+                assembly_stats = stats;
+                let Some(frame) = assembled else {
                     continue;
                 };
 
-                trace!(
-                    "received frame chunk: frame={} chunk={}/{} size={}",
-                    chunk.frame_number,
-                    image_chunk.chunk_index + 1,
-                    entry.total_chunks,
-                    image_chunk.bytes.len()
-                );
-
-                // Insert chunk if not already present
-                let idx = image_chunk.chunk_index as usize;
-                if idx >= entry.chunks.len() {
-                    trace!("invalid chunk index {} for frame {}", idx, chunk.frame_number);
+                let present = match (paused, step_from) {
+                    (false, _) => true,
+                    (true, Some(from)) if frame.frame_number >= from => {
+                        step_from = None;
+                        true
+                    }
+                    (true, _) => false,
+                };
+                if !present {
+                    trace!("paused, skipping frame {}", frame_number);
                     continue;
                 }
-                if entry.chunks[idx].is_none() {
-                    entry.chunks[idx] = Some(image_chunk.bytes.clone());
-                    entry.received_count += 1;
-                }
 
-                // Check if frame is complete
-                if entry.received_count == entry.total_chunks {
-                    // Reassemble JPEG data in order
-                    let mut jpeg_data = Vec::new();
-                    for c in entry.chunks.iter() {
-                        if let Some(bytes) = c {
-                            jpeg_data.extend_from_slice(bytes);
-                        } else {
-                            // Missing chunk — shouldn’t happen
-                            trace!("missing chunk during reassembly for frame {}", chunk.frame_number);
-                            continue;
-                        }
+                // schedule next render
+                let frame_interval = fps_estimator.frame_interval();
+
+                debug!("received camera frame from server, frame_number: {}, frame_timestamp: {:?}, frame_interval: {}ms", frame_number, frame.frame_timestamp, frame_interval.as_millis());
+
+                // Decode JPEG
+                let before = std::time::Instant::now();
+                match image::load_from_memory_with_format(&frame.jpeg_bytes, ImageFormat::Jpeg) {
+                    Ok(img) => {
+                        let point1 = std::time::Instant::now();
+                        let rgba = img.to_rgba8();
+                        let (w, h) = (rgba.width() as usize, rgba.height() as usize);
+                        let color_image = ColorImage::from_rgba_unmultiplied([w, h], &rgba.into_raw());
+
+                        let camera_frame = CameraFrame {
+                            image: color_image,
+                            timestamp: frame.frame_timestamp,
+                            head_position: frame.head_position,
+                            frame_number: frame.frame_number,
+                            frame_interval,
+                        };
+
+                        let _ = tx_out.send(camera_frame);
+                        context.request_repaint();
+
+                        let after = std::time::Instant::now();
+                        trace!("sent frame to egui, frame_number: {}, size: {} bytes, decoding: {}us, imagegen+send: {}us, total-elapsed: {}us",
+                            frame_number,
+                            frame.jpeg_bytes.len(),
+                            (point1 - before).as_micros(),
+                            (after - point1).as_micros(),
+                            (after - before).as_micros(),
+                        );
                     }
-
-                    debug!("received camera frame from server, frame_number: {}, chunks: {}, frame_timestamp: {:?}, frame_interval: {}ms", chunk.frame_number, entry.total_chunks, entry.frame_timestamp, entry.frame_interval.as_millis());
-
-                    // Decode JPEG
-                    let before = std::time::Instant::now();
-                    match image::load_from_memory_with_format(&jpeg_data, ImageFormat::Jpeg) {
-                        Ok(img) => {
-                            let point1 = std::time::Instant::now();
-                            let rgba = img.to_rgba8();
-                            let (w, h) = (rgba.width() as usize, rgba.height() as usize);
-                            let color_image = ColorImage::from_rgba_unmultiplied([w, h], &rgba.into_raw());
-
-                            let camera_frame = CameraFrame {
-                                image: color_image,
-                                timestamp: entry.frame_timestamp,
-                                frame_number: entry.frame_number,
-                                frame_interval: entry.frame_interval,
-                            };
-
-                            let _ = tx_out.send(camera_frame);
-                            context.request_repaint();
-
-                            let after = std::time::Instant::now();
-                            trace!("sent frame to egui, frame_number: {}, size: {} bytes, decoding: {}us, imagegen+send: {}us, total-elapsed: {}us",
-                                chunk.frame_number,
-                                jpeg_data.len(),
-                                (point1 - before).as_micros(),
-                                (after - point1).as_micros(),
-                                (after - before).as_micros(),
-                            );
-                        }
-                        Err(e) => {
-                            error!("decode error frame {}: {:?}", chunk.frame_number, e);
-                        }
+                    Err(e) => {
+                        error!("decode error frame {}: {:?}", frame_number, e);
                     }
-
-
-                    // Remove the completed frame from tracking
-                    in_progress.remove(&chunk.frame_number);
                 }
-                // drop old frames (stuck/incomplete)
-                in_progress.retain(|frame_num, f| {
-                    if now.duration_since(f.start_time) > Duration::from_secs(1) {
-                        warn!(
-                                "discarding incomplete frame {} (got {}/{})",
-                                frame_num,
-                                f.received_count,
-                                f.total_chunks
-                            );
-                        false
-                    } else {
-                        true
-                    }
-                });
             }
         }
     }
@@ -282,6 +271,8 @@ pub async fn camera_frame_listener(
 pub struct CameraFrame {
     pub image: ColorImage,
     pub timestamp: TimeStampUTC,
+    /// the position of the head at `timestamp`, `None` if the server has no position telemetry
+    pub head_position: Option<HeadPosition>,
     pub frame_number: u64,
     pub frame_interval: Duration,
 }
@@ -291,6 +282,7 @@ impl Default for CameraFrame {
         Self {
             image: Default::default(),
             timestamp: chrono::Utc::now().into(),
+            head_position: None,
             frame_number: 0,
             frame_interval: Duration::from_secs(0),
         }
@@ -1,8 +1,25 @@
 use std::time::Duration;
 
 use ergot::toolkits::tokio_udp::EdgeStack;
-use ergot::{Address, endpoint};
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, endpoint};
+use machine_ids::{CameraId, FeederId};
+use operator_shared::camera::{CameraInfo, CaptureRequest, CaptureResponse};
+use operator_shared::captures::{CaptureAnnotation, CaptureKey, CaptureListPage};
 use operator_shared::commands::{OperatorCommandRequest, OperatorCommandResponse};
+use operator_shared::config::{ConfigChange, ConfigError};
+use operator_shared::feeders::TapeOrientation;
+use operator_shared::geometry::MachineGeometry;
+use operator_shared::homing::HomingError;
+use operator_shared::job::{
+    EstimateError, InterventionError, InterventionResolution, JobCheckpoint, JobEstimate, ResumeChoice, ResumeError,
+};
+use operator_shared::limits::{AxisLimit, LimitOverrideError, LimitOverrideRequest};
+use operator_shared::readiness::{ReadinessCheck, StartJobError};
+use operator_shared::templates::{TemplateCapture, TemplateError, TemplateInfo, TemplateKind};
+use operator_shared::test_area::{TestPattern, TestShotError, TestShotKind};
+use operator_shared::vision::{OrientationError, ScanError, ScanTarget};
 use tokio::sync::broadcast::Receiver;
 use tokio::{select, time};
 use tracing::error;
@@ -17,6 +34,13 @@ endpoint!(
     "topic/operator/command"
 );
 
+endpoint!(
+    OperatorCaptureEndpoint,
+    CaptureRequest,
+    CaptureResponse,
+    "topic/operator/capture"
+);
+
 pub async fn heartbeat_sender(stack: EdgeStack, address: Address, app_event_rx: Receiver<AppEvent>) {
     let mut app_shutdown_handler = Box::pin(app_shutdown_handler(app_event_rx));
 
@@ -68,3 +92,600 @@ async fn heartbeat_loop(stack: EdgeStack, address: Address) {
         index = index.wrapping_add(1);
     }
 }
+
+pub async fn list_cameras(stack: EdgeStack, address: Address) -> anyhow::Result<Vec<CameraInfo>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::ListCameras)
+        .await?
+    {
+        OperatorCommandResponse::Cameras(cameras) => Ok(cameras),
+        response => anyhow::bail!("Unexpected response for list cameras. response: {:?}", response),
+    }
+}
+
+pub async fn fetch_machine_geometry(stack: EdgeStack, address: Address) -> anyhow::Result<MachineGeometry> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::FetchMachineGeometry)
+        .await?
+    {
+        OperatorCommandResponse::MachineGeometry(geometry) => Ok(geometry),
+        response => anyhow::bail!(
+            "Unexpected response for fetch machine geometry. response: {:?}",
+            response
+        ),
+    }
+}
+
+pub async fn override_readiness_check(
+    stack: EdgeStack,
+    address: Address,
+    check: ReadinessCheck,
+    reason: String,
+) -> anyhow::Result<()> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    let request = OperatorCommandRequest::OverrideReadinessCheck {
+        check,
+        reason,
+    };
+    match command_client.request(&request).await? {
+        OperatorCommandResponse::ReadinessOverride(Ok(())) => Ok(()),
+        OperatorCommandResponse::ReadinessOverride(Err(e)) => {
+            anyhow::bail!("Unable to override readiness check. check: {:?}, error: {:?}", check, e)
+        }
+        response => anyhow::bail!("Unexpected response for readiness override. response: {:?}", response),
+    }
+}
+
+/// Returns once homing has started, the progress is published as a `HomingStatus`.
+///
+/// The outer error is a communication error, the inner error is the reason the server refused to start homing.
+pub async fn home_all(stack: EdgeStack, address: Address) -> anyhow::Result<Result<(), HomingError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::HomeAll)
+        .await?
+    {
+        OperatorCommandResponse::HomeAll(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for home all. response: {:?}", response),
+    }
+}
+
+/// The outer error is a communication error, the inner error is the reason the server refused to start the job.
+pub async fn start_job(stack: EdgeStack, address: Address) -> anyhow::Result<Result<(), StartJobError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::StartJob)
+        .await?
+    {
+        OperatorCommandResponse::StartJob(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for start job. response: {:?}", response),
+    }
+}
+
+/// The outer error is a communication error, the inner error is the reason the server rejected the changes, none of
+/// the changes were applied.
+pub async fn apply_config(
+    stack: EdgeStack,
+    address: Address,
+    changes: Vec<ConfigChange>,
+) -> anyhow::Result<Result<(), ConfigError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    let request = OperatorCommandRequest::ApplyConfig(changes);
+    match command_client.request(&request).await? {
+        OperatorCommandResponse::ConfigApplied(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for apply config. response: {:?}", response),
+    }
+}
+
+/// The outer error is a communication error, the inner error is the reason the server refused the pattern.
+///
+/// Returns the number of shots, the progress is published as `TestShotEvent`s.
+pub async fn run_test_pattern(
+    stack: EdgeStack,
+    address: Address,
+    pattern: TestPattern,
+    kind: TestShotKind,
+) -> anyhow::Result<Result<u32, TestShotError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    let request = OperatorCommandRequest::RunTestPattern {
+        pattern,
+        kind,
+    };
+    match command_client.request(&request).await? {
+        OperatorCommandResponse::TestPatternStarted(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for run test pattern. response: {:?}", response),
+    }
+}
+
+/// The outer error is a communication error, the inner error is the reason the server refused to clear the area.
+pub async fn clear_test_area(stack: EdgeStack, address: Address) -> anyhow::Result<Result<(), TestShotError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::ClearTestArea)
+        .await?
+    {
+        OperatorCommandResponse::TestAreaCleared(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for clear test area. response: {:?}", response),
+    }
+}
+
+/// The outer error is a communication error, the inner error is the reason the server refused the override.
+///
+/// The remaining time of the override is published as a `LimitOverrideStatus`.
+pub async fn override_axis_limit(
+    stack: EdgeStack,
+    address: Address,
+    request: LimitOverrideRequest,
+) -> anyhow::Result<Result<(), LimitOverrideError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    // longer than the other commands, the server waits for the io board to disable the limit
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(3), command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::OverrideAxisLimit(request))
+        .await?
+    {
+        OperatorCommandResponse::AxisLimitOverridden(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for override axis limit. response: {:?}", response),
+    }
+}
+
+/// The outer error is a communication error, the inner error is the reason the server refused to clear the override.
+pub async fn clear_axis_limit_override(
+    stack: EdgeStack,
+    address: Address,
+    axis: String,
+    limit: AxisLimit,
+) -> anyhow::Result<Result<(), LimitOverrideError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    let request = OperatorCommandRequest::ClearAxisLimitOverride {
+        axis,
+        limit,
+    };
+    match command_client.request(&request).await? {
+        OperatorCommandResponse::AxisLimitOverrideCleared(result) => Ok(result),
+        response => anyhow::bail!(
+            "Unexpected response for clear axis limit override. response: {:?}",
+            response
+        ),
+    }
+}
+
+/// Returns `None` if there is no interrupted job.
+pub async fn fetch_job_checkpoint(stack: EdgeStack, address: Address) -> anyhow::Result<Option<JobCheckpoint>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::FetchJobCheckpoint)
+        .await?
+    {
+        OperatorCommandResponse::JobCheckpoint(checkpoint) => Ok(checkpoint),
+        response => anyhow::bail!("Unexpected response for fetch job checkpoint. response: {:?}", response),
+    }
+}
+
+/// The outer error is a communication error, the inner error is the reason the server rejected the choice.
+pub async fn confirm_resume(
+    stack: EdgeStack,
+    address: Address,
+    choice: ResumeChoice,
+) -> anyhow::Result<Result<(), ResumeError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::ConfirmResume(choice))
+        .await?
+    {
+        OperatorCommandResponse::ResumeConfirmed(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for confirm resume. response: {:?}", response),
+    }
+}
+
+/// Simulating a long job takes the server a while, and it is simulated again for each page.
+const ESTIMATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fetches the estimate page by page, the outer error is a communication error, the inner error is the reason the
+/// server was unable to simulate the job.
+pub async fn estimate_job(stack: EdgeStack, address: Address) -> anyhow::Result<Result<JobEstimate, EstimateError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(ESTIMATE_TIMEOUT, command_client);
+
+    let mut placements = Vec::new();
+    loop {
+        let request = OperatorCommandRequest::EstimateJob {
+            offset: placements.len() as u32,
+        };
+        let page = match command_client.request(&request).await? {
+            OperatorCommandResponse::JobEstimate(Ok(page)) => page,
+            OperatorCommandResponse::JobEstimate(Err(e)) => return Ok(Err(e)),
+            response => anyhow::bail!("Unexpected response for estimate job. response: {:?}", response),
+        };
+
+        // the job may be replaced between pages, e.g. by scanning a board, an empty page is the end
+        let page_is_empty = page.placements.is_empty();
+        placements.extend(page.placements);
+        if page_is_empty || placements.len() >= page.total as usize {
+            return Ok(Ok(JobEstimate {
+                placements,
+                ..page
+            }));
+        }
+    }
+}
+
+/// The outer error is a communication error, the inner error is the reason the server rejected the resolution.
+pub async fn resolve_intervention(
+    stack: EdgeStack,
+    address: Address,
+    id: u32,
+    resolution: InterventionResolution,
+) -> anyhow::Result<Result<(), InterventionError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    let request = OperatorCommandRequest::ResolveIntervention {
+        id,
+        resolution,
+    };
+    match command_client.request(&request).await? {
+        OperatorCommandResponse::InterventionResolved(result) => Ok(result),
+        response => anyhow::bail!(
+            "Unexpected response for intervention resolution. response: {:?}",
+            response
+        ),
+    }
+}
+
+/// Longer than the server's timeout for a vision frame, the request may also be queued behind other vision requests.
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Requests a frame captured after the request was served, returns the frame number.
+///
+/// The preview stream is not paused and the frame is not saved, it's used to find the next frame in the stream.
+pub async fn capture_camera_frame(
+    stack: EdgeStack,
+    address: Address,
+    camera_identifier: CameraId,
+) -> anyhow::Result<u64> {
+    let capture_address = discover_capture_endpoint(&stack, address).await?;
+    let capture_client = stack
+        .endpoints()
+        .client::<OperatorCaptureEndpoint>(capture_address, None);
+    let capture_client = ergot_util::ClientWrapper::new(CAPTURE_TIMEOUT, capture_client);
+
+    let request = CaptureRequest {
+        camera: camera_identifier,
+        pause_preview: false,
+        save: false,
+    };
+    match capture_client.request(&request).await? {
+        Ok(frame) => Ok(frame.frame_number),
+        Err(e) => anyhow::bail!(
+            "Unable to capture frame. identifier: {}, error: {:?}",
+            camera_identifier,
+            e
+        ),
+    }
+}
+
+/// The capture endpoint is served on the same node as the operator command endpoint at `address`.
+async fn discover_capture_endpoint(stack: &EdgeStack, address: Address) -> anyhow::Result<Address> {
+    let query = SocketQuery {
+        key: OperatorCaptureEndpoint::REQ_KEY.to_bytes(),
+        nash_req: NameRequirement::Any,
+        frame_kind: FrameKind::ENDPOINT_REQ,
+        broadcast: false,
+    };
+    stack
+        .discovery()
+        .discover_sockets(4, Duration::from_secs(1), &query)
+        .await
+        .into_iter()
+        .map(|result| result.address)
+        .find(|capture_address| {
+            capture_address.network_id == address.network_id && capture_address.node_id == address.node_id
+        })
+        .ok_or_else(|| anyhow::anyhow!("Capture endpoint not found. address: {:?}", address))
+}
+
+/// Scans a barcode or QR code with the down camera and applies it to the `target`, returns the scanned code.
+///
+/// The outer error is a communication error, the inner error is the reason the scan failed.
+pub async fn scan_code(
+    stack: EdgeStack,
+    address: Address,
+    target: ScanTarget,
+) -> anyhow::Result<Result<String, ScanError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(CAPTURE_TIMEOUT, command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::ScanCode(target))
+        .await?
+    {
+        OperatorCommandResponse::CodeScanned(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for scan code. response: {:?}", response),
+    }
+}
+
+/// Captures the first pocket of the feeder with the down camera and verifies the orientation of the part.
+///
+/// The outer error is a communication error, the inner error is the reason the orientation could not be verified.
+pub async fn verify_feeder_orientation(
+    stack: EdgeStack,
+    address: Address,
+    feeder: FeederId,
+) -> anyhow::Result<Result<TapeOrientation, OrientationError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(CAPTURE_TIMEOUT, command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::VerifyFeederOrientation {
+            feeder,
+        })
+        .await?
+    {
+        OperatorCommandResponse::FeederOrientation(result) => Ok(result),
+        response => anyhow::bail!(
+            "Unexpected response for verify feeder orientation. response: {:?}",
+            response
+        ),
+    }
+}
+
+/// Fetches a page of the capture list, newest first, starting at `offset`.
+pub async fn list_captures_page(stack: EdgeStack, address: Address, offset: u32) -> anyhow::Result<CaptureListPage> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    let request = OperatorCommandRequest::ListCaptures {
+        offset,
+    };
+    match command_client.request(&request).await? {
+        OperatorCommandResponse::Captures(Ok(page)) => Ok(page),
+        OperatorCommandResponse::Captures(Err(e)) => anyhow::bail!("Unable to list captures. error: {:?}", e),
+        response => anyhow::bail!("Unexpected response for list captures. response: {:?}", response),
+    }
+}
+
+/// Fetches a capture, chunk by chunk, returns the jpeg bytes.
+pub async fn fetch_capture(stack: EdgeStack, address: Address, key: &CaptureKey) -> anyhow::Result<Vec<u8>> {
+    fetch_capture_chunks(stack, address, key, |key, offset| {
+        OperatorCommandRequest::FetchCapture {
+            key,
+            offset,
+        }
+    })
+    .await
+}
+
+/// Fetches the thumbnail made by the server of a capture, chunk by chunk, returns the jpeg bytes.
+pub async fn fetch_capture_thumbnail(stack: EdgeStack, address: Address, key: &CaptureKey) -> anyhow::Result<Vec<u8>> {
+    fetch_capture_chunks(stack, address, key, |key, offset| {
+        OperatorCommandRequest::FetchCaptureThumbnail {
+            key,
+            offset,
+        }
+    })
+    .await
+}
+
+async fn fetch_capture_chunks(
+    stack: EdgeStack,
+    address: Address,
+    key: &CaptureKey,
+    request: impl Fn(CaptureKey, u32) -> OperatorCommandRequest,
+) -> anyhow::Result<Vec<u8>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    let mut bytes = Vec::new();
+    loop {
+        let request = request(key.clone(), bytes.len() as u32);
+        let chunk = match command_client.request(&request).await? {
+            OperatorCommandResponse::CaptureChunk(Ok(chunk)) => chunk,
+            OperatorCommandResponse::CaptureChunk(Err(e)) => {
+                anyhow::bail!("Unable to fetch capture. key: {}, error: {:?}", key, e)
+            }
+            response => anyhow::bail!("Unexpected response for fetch capture. response: {:?}", response),
+        };
+
+        if chunk.bytes.is_empty() {
+            anyhow::bail!("Capture changed while fetching. key: {}", key);
+        }
+        bytes.extend_from_slice(&chunk.bytes);
+        if bytes.len() >= chunk.total_bytes as usize {
+            break;
+        }
+    }
+
+    Ok(bytes)
+}
+
+pub async fn fetch_capture_annotations(
+    stack: EdgeStack,
+    address: Address,
+    key: &CaptureKey,
+) -> anyhow::Result<Vec<CaptureAnnotation>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    let request = OperatorCommandRequest::FetchCaptureAnnotations {
+        key: key.clone(),
+    };
+    match command_client.request(&request).await? {
+        OperatorCommandResponse::CaptureAnnotations(Ok(annotations)) => Ok(annotations),
+        OperatorCommandResponse::CaptureAnnotations(Err(e)) => {
+            anyhow::bail!("Unable to fetch capture annotations. key: {}, error: {:?}", key, e)
+        }
+        response => anyhow::bail!(
+            "Unexpected response for fetch capture annotations. response: {:?}",
+            response
+        ),
+    }
+}
+
+/// Fetches all pages of the template list, ordered by name.
+pub async fn list_templates(stack: EdgeStack, address: Address) -> anyhow::Result<Vec<TemplateInfo>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    let mut templates = Vec::new();
+    loop {
+        let request = OperatorCommandRequest::ListTemplates {
+            offset: templates.len() as u32,
+        };
+        let page = match command_client.request(&request).await? {
+            OperatorCommandResponse::Templates(Ok(page)) => page,
+            OperatorCommandResponse::Templates(Err(e)) => anyhow::bail!("Unable to list templates. error: {:?}", e),
+            response => anyhow::bail!("Unexpected response for list templates. response: {:?}", response),
+        };
+
+        // the list may change between pages, an empty page is the end
+        let page_is_empty = page.templates.is_empty();
+        templates.extend(page.templates);
+        if page_is_empty || templates.len() >= page.total as usize {
+            break;
+        }
+    }
+
+    Ok(templates)
+}
+
+/// Captures the image of a new template.
+///
+/// The outer error is a communication error, the inner error is the reason the template was not created.
+pub async fn create_template(
+    stack: EdgeStack,
+    address: Address,
+    name: String,
+    kind: TemplateKind,
+    capture: TemplateCapture,
+) -> anyhow::Result<Result<TemplateInfo, TemplateError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(CAPTURE_TIMEOUT, command_client);
+
+    let request = OperatorCommandRequest::CreateTemplate {
+        name,
+        kind,
+        capture,
+    };
+    match command_client.request(&request).await? {
+        OperatorCommandResponse::TemplateSaved(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for create template. response: {:?}", response),
+    }
+}
+
+/// Renames a template or changes its kind, the image is re-captured if `capture` is given.
+///
+/// The outer error is a communication error, the inner error is the reason the template was not updated.
+pub async fn update_template(
+    stack: EdgeStack,
+    address: Address,
+    name: String,
+    new_name: String,
+    kind: TemplateKind,
+    capture: Option<TemplateCapture>,
+) -> anyhow::Result<Result<TemplateInfo, TemplateError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(CAPTURE_TIMEOUT, command_client);
+
+    let request = OperatorCommandRequest::UpdateTemplate {
+        name,
+        new_name,
+        kind,
+        capture,
+    };
+    match command_client.request(&request).await? {
+        OperatorCommandResponse::TemplateSaved(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for update template. response: {:?}", response),
+    }
+}
+
+/// The outer error is a communication error, the inner error is the reason the template was not deleted.
+pub async fn delete_template(
+    stack: EdgeStack,
+    address: Address,
+    name: String,
+) -> anyhow::Result<Result<(), TemplateError>> {
+    let command_client = stack
+        .endpoints()
+        .client::<OperatorCommandEndpoint>(address, None);
+    let command_client = ergot_util::ClientWrapper::new(Duration::from_secs(1), command_client);
+
+    match command_client
+        .request(&OperatorCommandRequest::DeleteTemplate {
+            name,
+        })
+        .await?
+    {
+        OperatorCommandResponse::TemplateDeleted(result) => Ok(result),
+        response => anyhow::bail!("Unexpected response for delete template. response: {:?}", response),
+    }
+}
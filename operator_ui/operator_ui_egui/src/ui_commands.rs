@@ -1,10 +1,13 @@
 use egui::{Context, ThemePreference, ViewportId};
 use egui_mobius::Value;
-use tracing::trace;
+use tracing::{trace, warn};
+use units::UnitSystem;
 
 use crate::app::{AppState, PaneKind};
 use crate::config::Config;
 use crate::task::Task;
+use crate::ui_common;
+use crate::ui_common::style::StatusPalette;
 use crate::workspace::{ViewMode, ViewportState, Workspaces};
 
 #[derive(Debug, Clone)]
@@ -12,11 +15,15 @@ pub enum UiCommand {
     #[allow(dead_code)]
     None,
     LanguageChanged(String),
+    UnitSystemChanged(UnitSystem),
+    StatusPaletteChanged(StatusPalette),
     ThemeChanged(ThemePreference),
 
     ViewportUiCommand(ViewportId, ViewportUiCommand),
     CloseViewport(ViewportId),
     ChangeWorkspace(usize),
+    /// saves the arrangement of the active workspace as a new workspace with the name, and switches to it
+    SaveWorkspace(String),
 }
 
 #[derive(Debug, Clone)]
@@ -61,6 +68,16 @@ pub fn handle_command(
                 .language_identifier = language;
             Task::none()
         }
+        UiCommand::UnitSystemChanged(unit_system) => {
+            ui_common::units::set_unit_system(unit_system);
+            config.lock().unwrap().unit_system = unit_system;
+            Task::none()
+        }
+        UiCommand::StatusPaletteChanged(status_palette) => {
+            ui_common::style::set_status_palette(status_palette);
+            config.lock().unwrap().status_palette = status_palette;
+            Task::none()
+        }
         UiCommand::ThemeChanged(theme) => {
             ui_context.set_theme(theme);
             Task::none()
@@ -106,5 +123,20 @@ pub fn handle_command(
             }
             Task::none()
         }
+        UiCommand::SaveWorkspace(name) => {
+            let app_state = app_state.lock().unwrap();
+
+            let mut workspaces = workspaces.lock().unwrap();
+            match workspaces.save_active_as(&name) {
+                Ok(index) => {
+                    app_state
+                        .command_sender
+                        .send(UiCommand::ChangeWorkspace(index))
+                        .expect("sent");
+                }
+                Err(e) => warn!("Unable to save workspace. name: {}, error: {:?}", name, e),
+            }
+            Task::none()
+        }
     }
 }
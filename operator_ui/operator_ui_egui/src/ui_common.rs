@@ -1,3 +1,7 @@
+pub mod measurement;
+pub mod style;
+pub mod units;
+
 pub mod egui_tree {
     use std::fmt::Debug;
 
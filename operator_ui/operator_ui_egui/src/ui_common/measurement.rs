@@ -0,0 +1,268 @@
+//! Measurement tools that can be attached to any image widget, e.g. a camera view.
+//!
+//! Points are kept in image pixels, so measurements stay in place when the widget is resized, and are converted to
+//! millimeters using the calibration of the camera, when available.  Lengths are shown in the configured unit system,
+//! see [`units`](crate::ui_common::units).
+
+use egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Response, Stroke, Ui, Vec2};
+use egui_i18n::tr;
+use machine_geometry::{ImageView, PixelPoint, PixelScale, Point};
+use units::Formatter;
+
+use crate::ui_common::units::formatter;
+
+const MEASUREMENT_COLOR: Color32 = Color32::YELLOW;
+const SCALE_BAR_COLOR: Color32 = Color32::WHITE;
+const MARGIN: f32 = 10.0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MeasurementTool {
+    #[default]
+    None,
+    /// Distance between two points.
+    Ruler,
+    /// Angle between three points, the second point is the vertex.
+    Angle,
+}
+
+impl MeasurementTool {
+    fn required_points(&self) -> usize {
+        match self {
+            MeasurementTool::None => 0,
+            MeasurementTool::Ruler => 2,
+            MeasurementTool::Angle => 3,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MeasurementOverlay {
+    tool: MeasurementTool,
+    /// In image pixels.
+    points: Vec<Pos2>,
+}
+
+impl MeasurementOverlay {
+    /// A tool is selected, secondary clicks on the image clear the measurement.
+    pub fn is_active(&self) -> bool {
+        self.tool != MeasurementTool::None
+    }
+
+    pub fn toolbar_ui(&mut self, ui: &mut Ui) {
+        for (tool, text) in [
+            (MeasurementTool::None, tr!("measurement-tool-none")),
+            (MeasurementTool::Ruler, tr!("measurement-tool-ruler")),
+            (MeasurementTool::Angle, tr!("measurement-tool-angle")),
+        ] {
+            if ui
+                .selectable_label(self.tool == tool, text)
+                .clicked()
+                && self.tool != tool
+            {
+                self.tool = tool;
+                self.points.clear();
+            }
+        }
+    }
+
+    /// Draws the scale bar and the measurement of the current tool over the image.
+    ///
+    /// `response` must be the response of the image widget, which must sense clicks, `image_size` is in pixels.
+    /// Clicking adds a point, once all the points of a measurement have been placed the next click starts a new
+    /// measurement, a secondary click clears the measurement.
+    pub fn ui(&mut self, ui: &Ui, response: &Response, image_size: Vec2, scale: Option<PixelScale>) {
+        let transform = ImageTransform::new(response.rect, image_size);
+        let painter = ui.painter_at(response.rect);
+        let formatter = formatter();
+
+        if let Some(scale) = scale {
+            scale_bar(&painter, response.rect, transform.zoom, scale, &formatter);
+        }
+
+        let required_points = self.tool.required_points();
+        if required_points == 0 {
+            return;
+        }
+
+        match response.interact_pointer_pos() {
+            Some(position) if response.clicked() => {
+                if self.points.len() >= required_points {
+                    self.points.clear();
+                }
+                self.points
+                    .push(transform.to_image(position));
+            }
+            _ => {}
+        }
+        if response.secondary_clicked() {
+            self.points.clear();
+        }
+
+        // preview the next point at the pointer
+        let mut points = self.points.clone();
+        match response.hover_pos() {
+            Some(position) if points.len() < required_points => points.push(transform.to_image(position)),
+            _ => {}
+        }
+
+        let stroke = Stroke::new(2.0, MEASUREMENT_COLOR);
+        let screen_points = points
+            .iter()
+            .map(|point| transform.to_screen(*point))
+            .collect::<Vec<_>>();
+        for pair in screen_points.windows(2) {
+            painter.line_segment([pair[0], pair[1]], stroke);
+        }
+        for point in screen_points.iter() {
+            painter.circle_stroke(*point, 4.0, stroke);
+        }
+
+        let label = match (self.tool, points.as_slice()) {
+            (MeasurementTool::Ruler, [a, b]) => Some((a.lerp(*b, 0.5), format_distance(*b - *a, scale, &formatter))),
+            (MeasurementTool::Angle, [a, vertex, b]) => {
+                let angle = angle_degrees(to_mm(*a - *vertex, scale), to_mm(*b - *vertex, scale));
+                Some((*vertex, formatter.angle(angle as f64, 1)))
+            }
+            _ => None,
+        };
+        if let Some((position, text)) = label {
+            painter.text(
+                transform.to_screen(position) + Vec2::new(MARGIN, -MARGIN),
+                Align2::LEFT_BOTTOM,
+                text,
+                FontId::default(),
+                MEASUREMENT_COLOR,
+            );
+        }
+    }
+}
+
+/// The machine coordinates of a screen position over an image, `center` is the position of the center of the image,
+/// in machine coordinates, e.g. the position of the head for a camera on the head.
+///
+/// `rect` is the rect of the image widget, `image_size` is in pixels.
+pub fn machine_coordinates(
+    rect: Rect,
+    image_size: Vec2,
+    position: Pos2,
+    scale: PixelScale,
+    view: ImageView,
+    center: Point,
+) -> Point {
+    let transform = ImageTransform::new(rect, image_size);
+    let pixels = transform.to_image(position) - (image_size / 2.0).to_pos2();
+    let offset = scale.to_machine(
+        PixelPoint {
+            x: pixels.x as f64,
+            y: pixels.y as f64,
+        },
+        view,
+    );
+    center + offset
+}
+
+/// Maps between image pixels and screen points, the image is drawn with its aspect ratio maintained.
+struct ImageTransform {
+    origin: Pos2,
+    /// screen points per image pixel
+    zoom: f32,
+}
+
+impl ImageTransform {
+    fn new(rect: Rect, image_size: Vec2) -> Self {
+        Self {
+            origin: rect.min,
+            zoom: rect.width() / image_size.x,
+        }
+    }
+
+    fn to_image(&self, position: Pos2) -> Pos2 {
+        ((position - self.origin) / self.zoom).to_pos2()
+    }
+
+    fn to_screen(&self, point: Pos2) -> Pos2 {
+        self.origin + point.to_vec2() * self.zoom
+    }
+}
+
+/// A horizontal bar of a round length, in the length unit of the unit system, about a fifth of the width of the
+/// image, in the bottom left corner.
+fn scale_bar(painter: &Painter, rect: Rect, zoom: f32, scale: PixelScale, formatter: &Formatter) {
+    let mm_per_point = scale.mm_per_pixel_x as f32 / zoom;
+    if !mm_per_point.is_finite() || mm_per_point <= 0.0 {
+        return;
+    }
+
+    let unit = formatter.length_unit();
+    let mm_per_unit = unit.mm_per_unit() as f32;
+    let length = round_length(rect.width() * 0.2 * mm_per_point / mm_per_unit);
+    let length_points = length * mm_per_unit / mm_per_point;
+
+    let start = Pos2::new(rect.left() + MARGIN, rect.bottom() - MARGIN);
+    let end = start + Vec2::new(length_points, 0.0);
+    let tick = Vec2::new(0.0, MARGIN / 2.0);
+
+    let stroke = Stroke::new(2.0, SCALE_BAR_COLOR);
+    painter.line_segment([start, end], stroke);
+    painter.line_segment([start - tick, start], stroke);
+    painter.line_segment([end - tick, end], stroke);
+    painter.text(
+        start.lerp(end, 0.5) - tick,
+        Align2::CENTER_BOTTOM,
+        format!(
+            "{} {}",
+            formatter.number(length as f64, decimals(length)),
+            unit.symbol()
+        ),
+        FontId::default(),
+        SCALE_BAR_COLOR,
+    );
+}
+
+/// The largest 1, 2 or 5 times a power of ten that is not more than `value`.
+fn round_length(value: f32) -> f32 {
+    let magnitude = 10_f32.powf(value.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|step| step * magnitude)
+        .find(|length| *length <= value)
+        .unwrap_or(magnitude)
+}
+
+fn to_mm(pixels: Vec2, scale: Option<PixelScale>) -> Vec2 {
+    match scale {
+        Some(scale) => {
+            let mm = scale.to_mm(PixelPoint {
+                x: pixels.x as f64,
+                y: pixels.y as f64,
+            });
+            Vec2::new(mm.x as f32, mm.y as f32)
+        }
+        None => pixels,
+    }
+}
+
+/// The decimals needed to show a round length, e.g. 1 for 0.5.
+fn decimals(length: f32) -> usize {
+    (-length.log10().floor()).max(0.0) as usize
+}
+
+fn format_distance(pixels: Vec2, scale: Option<PixelScale>, formatter: &Formatter) -> String {
+    match scale {
+        Some(_) => formatter.length(to_mm(pixels, scale).length() as f64, 3),
+        None => tr!("measurement-distance-px", {
+            distance: format!("{:.1}", pixels.length())
+        }),
+    }
+}
+
+/// 0 to 180 degrees.
+fn angle_degrees(a: Vec2, b: Vec2) -> f32 {
+    let angle = (a.angle() - b.angle())
+        .abs()
+        .to_degrees();
+    match angle > 180.0 {
+        true => 360.0 - angle,
+        false => angle,
+    }
+}
@@ -0,0 +1,60 @@
+//! The colors of statuses, e.g. a tripped safety input or a failed check, for the configured [`StatusPalette`].
+//!
+//! Panels use [`status_color`] instead of fixed colors, so that e.g. the status panel, the firmware logs and the
+//! capture annotations all change with the palette.  The palette is process-wide, like the unit system, see
+//! [`units`](super::units).
+
+use std::sync::RwLock;
+
+use egui::Color32;
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StatusPalette {
+    #[default]
+    Standard,
+    /// From the Okabe-Ito palette, distinguishable with the common forms of color blindness, and not only by hue, the
+    /// ok color is a blue instead of a green.
+    ColorBlindSafe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fault,
+    EStop,
+}
+
+impl StatusPalette {
+    pub const fn color(&self, status: Status) -> Color32 {
+        match (self, status) {
+            (StatusPalette::Standard, Status::Ok) => Color32::GREEN,
+            (StatusPalette::Standard, Status::Warn) => Color32::ORANGE,
+            (StatusPalette::Standard, Status::Fault) => Color32::RED,
+            (StatusPalette::Standard, Status::EStop) => Color32::RED,
+            // blue
+            (StatusPalette::ColorBlindSafe, Status::Ok) => Color32::from_rgb(0, 114, 178),
+            // orange
+            (StatusPalette::ColorBlindSafe, Status::Warn) => Color32::from_rgb(230, 159, 0),
+            // vermillion
+            (StatusPalette::ColorBlindSafe, Status::Fault) => Color32::from_rgb(213, 94, 0),
+            // reddish purple, distinct from a fault, since an e-stop is latched until it's reset
+            (StatusPalette::ColorBlindSafe, Status::EStop) => Color32::from_rgb(204, 121, 167),
+        }
+    }
+}
+
+static STATUS_PALETTE: RwLock<StatusPalette> = RwLock::new(StatusPalette::Standard);
+
+pub fn set_status_palette(status_palette: StatusPalette) {
+    *STATUS_PALETTE.write().unwrap() = status_palette;
+}
+
+pub fn get_status_palette() -> StatusPalette {
+    *STATUS_PALETTE.read().unwrap()
+}
+
+/// The color of the status for the current palette, look it up each frame, since the palette may change.
+pub fn status_color(status: Status) -> Color32 {
+    get_status_palette().color(status)
+}
@@ -0,0 +1,25 @@
+//! Formatting of machine quantities for the configured unit system and the current language, see [`units::Formatter`].
+//!
+//! The unit system is process-wide, like the language of [`egui_i18n`].
+
+use std::sync::RwLock;
+
+use units::{Formatter, Locale, UnitSystem};
+
+static UNIT_SYSTEM: RwLock<UnitSystem> = RwLock::new(UnitSystem::Metric);
+
+pub fn set_unit_system(unit_system: UnitSystem) {
+    *UNIT_SYSTEM.write().unwrap() = unit_system;
+}
+
+pub fn get_unit_system() -> UnitSystem {
+    *UNIT_SYSTEM.read().unwrap()
+}
+
+/// A formatter for the current unit system and language, create one per frame, since either may change.
+pub fn formatter() -> Formatter {
+    Formatter::new(
+        get_unit_system(),
+        Locale::from_language_identifier(&egui_i18n::get_language()),
+    )
+}
@@ -10,6 +10,7 @@ use egui_mobius::Value;
 use egui_mobius::types::{Enqueue, ValueGuard};
 use egui_tiles::{ContainerKind, SimplificationOptions, Tabs, Tile, TileId, Tiles, Tree, UiResponse};
 use tracing::{debug, info, trace};
+use units::UnitSystem;
 
 use crate::app::{MIN_TOUCH_SIZE, PaneKind, UiState};
 use crate::fps_stats::egui::show_frame_durations;
@@ -17,7 +18,8 @@ use crate::fps_stats::{FpsSnapshot, FpsStats};
 use crate::ui_commands::{UiCommand, ViewportUiAction, ViewportUiCommand};
 use crate::ui_common::egui::bring_window_to_front;
 use crate::ui_common::egui_tree::{add_pane_to_root, dump_tiles};
-use crate::{LOGO, app};
+use crate::ui_common::style::{Status, StatusPalette, status_color};
+use crate::{LOGO, app, ui_common};
 
 // TODO there's currently no way to re-center off-screen windows, which is needed if they end up off screen
 //      perhaps add a menu items to re-center all windows?
@@ -160,6 +162,9 @@ pub struct ViewportState {
     pub(crate) context: Option<egui::Context>,
     pub(crate) ui_state: Value<UiState>,
 
+    /// the name entered for saving the arrangement as a new layout
+    new_layout_name: String,
+
     fps_stats: FpsStats<300>,
     fps_snapshot: Option<FpsSnapshot>,
     frame_number: u64,
@@ -191,6 +196,8 @@ impl ViewportState {
             context: None,
             ui_state,
 
+            new_layout_name: String::new(),
+
             fps_stats: FpsStats::new(),
             fps_snapshot: None,
             frame_number: 0,
@@ -440,12 +447,70 @@ impl ViewportState {
                                         }
                                     }
                                 });
+
+                            let unit_system = ui_common::units::get_unit_system();
+                            egui::ComboBox::from_id_salt(ui.id().with("unit_system"))
+                                .selected_text(unit_system_text(unit_system))
+                                .show_ui(ui, |ui| {
+                                    for other_unit_system in [UnitSystem::Metric, UnitSystem::Imperial] {
+                                        if ui
+                                            .add(egui::Button::selectable(
+                                                other_unit_system.eq(&unit_system),
+                                                unit_system_text(other_unit_system),
+                                            ))
+                                            .clicked()
+                                        {
+                                            sender
+                                                .send(UiCommand::UnitSystemChanged(other_unit_system))
+                                                .expect("sent");
+                                        }
+                                    }
+                                });
+
+                            let status_palette = ui_common::style::get_status_palette();
+                            egui::ComboBox::from_id_salt(ui.id().with("status_palette"))
+                                .selected_text(status_palette_text(status_palette))
+                                .show_ui(ui, |ui| {
+                                    for other_status_palette in [StatusPalette::Standard, StatusPalette::ColorBlindSafe]
+                                    {
+                                        if ui
+                                            .add(egui::Button::selectable(
+                                                other_status_palette.eq(&status_palette),
+                                                status_palette_text(other_status_palette),
+                                            ))
+                                            .clicked()
+                                        {
+                                            sender
+                                                .send(UiCommand::StatusPaletteChanged(other_status_palette))
+                                                .expect("sent");
+                                        }
+                                    }
+                                });
+
+                            self.layout_ui(ui);
                         },
                     );
                 });
             });
         }
 
+        // on every viewport, so an overridden limit can't go unnoticed
+        {
+            let ui_state = self.ui_state.lock().unwrap();
+            if ui_state.limits_ui.is_overridden() {
+                egui::Panel::top(ui_id.with("limit_override_banner"))
+                    .frame(
+                        // darkened, for the white text of the banner
+                        Frame::NONE
+                            .fill(status_color(Status::Fault).gamma_multiply(0.55))
+                            .inner_margin(4.0),
+                    )
+                    .show_inside(ui, |ui| {
+                        ui_state.limits_ui.banner(ui);
+                    });
+            }
+        }
+
         let panel_fill_color = ctx.global_style().visuals.panel_fill;
         let side_panel_fill_color = panel_fill_color.gamma_multiply(0.9);
 
@@ -789,27 +854,68 @@ impl ViewportState {
         }
 
         if request_workspace_toggle {
-            let mut workspaces = self.workspaces.lock().unwrap();
+            let workspaces = self.workspaces.lock().unwrap();
 
-            if workspaces.count() == 1 {
-                workspaces.clone_active();
+            if workspaces.count() > 1 {
+                self.command_sender
+                    .send(UiCommand::ChangeWorkspace(workspaces.next_index()))
+                    .expect("sent");
             }
-            let index = match workspaces.active_index() {
-                0 => 1,
-                1 => 0,
-                _ => unreachable!(),
-            };
-
-            self.command_sender
-                .send(UiCommand::ChangeWorkspace(index))
-                .expect("sent");
         }
     }
+
+    /// Switching between the layouts of the machine profile, and saving the arrangement as a new layout.
+    fn layout_ui(&mut self, ui: &mut Ui) {
+        let (names, active_index) = {
+            let mut workspaces = self.workspaces.lock().unwrap();
+            (workspaces.names(), workspaces.active_index())
+        };
+
+        egui::ComboBox::from_id_salt(ui.id().with("layout"))
+            .selected_text(tr!("layout-selected", { name: names[active_index].clone() }))
+            .show_ui(ui, |ui| {
+                for (index, name) in names.iter().enumerate() {
+                    if ui
+                        .add(egui::Button::selectable(index == active_index, name.as_str()))
+                        .clicked()
+                    {
+                        self.command_sender
+                            .send(UiCommand::ChangeWorkspace(index))
+                            .expect("sent");
+                    }
+                }
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_layout_name)
+                            .hint_text(tr!("layout-new-name-hint"))
+                            .desired_width(120.0),
+                    );
+                    let name = self.new_layout_name.trim();
+                    let is_valid = !name.is_empty()
+                        && !names
+                            .iter()
+                            .any(|candidate| candidate == name);
+                    if ui
+                        .add_enabled(is_valid, egui::Button::new(tr!("layout-save-as")))
+                        .clicked()
+                    {
+                        self.command_sender
+                            .send(UiCommand::SaveWorkspace(name.to_string()))
+                            .expect("sent");
+                        self.new_layout_name.clear();
+                    }
+                });
+            });
+    }
 }
 
 pub struct ToggleDefinition {
     pub key: &'static str,
     pub kind: PaneKind,
+    pub mode: ViewMode,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
@@ -979,6 +1085,20 @@ impl egui_tiles::Behavior<AppPane> for TreeBehavior {
     }
 }
 
+fn unit_system_text(unit_system: UnitSystem) -> String {
+    match unit_system {
+        UnitSystem::Metric => tr!("unit-system-metric"),
+        UnitSystem::Imperial => tr!("unit-system-imperial"),
+    }
+}
+
+fn status_palette_text(status_palette: StatusPalette) -> String {
+    match status_palette {
+        StatusPalette::Standard => tr!("status-palette-standard"),
+        StatusPalette::ColorBlindSafe => tr!("status-palette-color-blind-safe"),
+    }
+}
+
 fn show_panel_title_and_controls<T>(
     viewport_id: ViewportId,
     kind: &PaneKind,
@@ -1095,10 +1215,12 @@ fn show_panel_controls<T>(
     .inner
 }
 
-/// Persisted
+/// A named arrangement of the panels, e.g. the cameras, jog controls and job, persisted.
 #[derive(serde::Deserialize, serde::Serialize, Clone)]
 #[serde(default)]
 pub struct WorkspaceConfig {
+    /// empty for layouts persisted before they were named, see [`WorkspaceConfig::display_name`]
+    pub(crate) name: String,
     pub(crate) toggle_states: Vec<ToggleState>,
     pub(crate) viewport_tree_configs: HashMap<ViewportId, ViewportTreeConfig>,
     pub(crate) viewport_configs: HashMap<ViewportId, ViewportConfig>,
@@ -1107,6 +1229,13 @@ pub struct WorkspaceConfig {
 impl Default for WorkspaceConfig {
     fn default() -> Self {
         let toggle_states = vec![
+            ToggleState {
+                key: "captures".to_string(),
+                mode: ViewMode::Disabled,
+                kind: PaneKind::Captures,
+                window_position: None,
+                window_size: None,
+            },
             ToggleState {
                 key: "controls".to_string(),
                 mode: ViewMode::Tile(ViewportId::ROOT),
@@ -1121,6 +1250,34 @@ impl Default for WorkspaceConfig {
                 window_position: None,
                 window_size: None,
             },
+            ToggleState {
+                key: "feeders".to_string(),
+                mode: ViewMode::Tile(ViewportId::ROOT),
+                kind: PaneKind::Feeders,
+                window_position: None,
+                window_size: None,
+            },
+            ToggleState {
+                key: "firmware-logs".to_string(),
+                mode: ViewMode::Disabled,
+                kind: PaneKind::FirmwareLogs,
+                window_position: None,
+                window_size: None,
+            },
+            ToggleState {
+                key: "job".to_string(),
+                mode: ViewMode::Tile(ViewportId::ROOT),
+                kind: PaneKind::Job,
+                window_position: None,
+                window_size: None,
+            },
+            ToggleState {
+                key: "limits".to_string(),
+                mode: ViewMode::Disabled,
+                kind: PaneKind::Limits,
+                window_position: None,
+                window_size: None,
+            },
             ToggleState {
                 key: "plot".to_string(),
                 mode: ViewMode::Disabled,
@@ -1128,6 +1285,13 @@ impl Default for WorkspaceConfig {
                 window_position: None,
                 window_size: None,
             },
+            ToggleState {
+                key: "readiness".to_string(),
+                mode: ViewMode::Tile(ViewportId::ROOT),
+                kind: PaneKind::Readiness,
+                window_position: None,
+                window_size: None,
+            },
             ToggleState {
                 key: "settings".to_string(),
                 mode: ViewMode::Window(ViewportId::ROOT),
@@ -1142,9 +1306,24 @@ impl Default for WorkspaceConfig {
                 window_position: None,
                 window_size: None,
             },
+            ToggleState {
+                key: "templates".to_string(),
+                mode: ViewMode::Disabled,
+                kind: PaneKind::Templates,
+                window_position: None,
+                window_size: None,
+            },
+            ToggleState {
+                key: "test-shots".to_string(),
+                mode: ViewMode::Disabled,
+                kind: PaneKind::TestShots,
+                window_position: None,
+                window_size: None,
+            },
         ];
 
         Self {
+            name: String::new(),
             toggle_states,
             viewport_tree_configs: Default::default(),
             viewport_configs: Default::default(),
@@ -1152,18 +1331,118 @@ impl Default for WorkspaceConfig {
     }
 }
 
+/// The layouts a machine profile starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutPreset {
+    /// cameras, jog controls, feeders and the vision panels, for setting up the machine and the job
+    Setup,
+    /// the job and its progress
+    Run,
+    /// diagnostics, firmware logs and plots
+    Debug,
+}
+
+impl LayoutPreset {
+    pub const ALL: [LayoutPreset; 3] = [LayoutPreset::Setup, LayoutPreset::Run, LayoutPreset::Debug];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LayoutPreset::Setup => "Setup",
+            LayoutPreset::Run => "Run",
+            LayoutPreset::Debug => "Debug",
+        }
+    }
+
+    /// The modes of the panels that differ from [`WorkspaceConfig::default`].
+    fn modes(&self) -> &'static [(&'static str, ViewMode)] {
+        match self {
+            LayoutPreset::Setup => &[
+                ("captures", ViewMode::Tile(ViewportId::ROOT)),
+                ("templates", ViewMode::Tile(ViewportId::ROOT)),
+                ("test-shots", ViewMode::Tile(ViewportId::ROOT)),
+                ("job", ViewMode::Disabled),
+            ],
+            LayoutPreset::Run => &[("diagnostics", ViewMode::Disabled), ("settings", ViewMode::Disabled)],
+            LayoutPreset::Debug => &[
+                ("diagnostics", ViewMode::Tile(ViewportId::ROOT)),
+                ("firmware-logs", ViewMode::Tile(ViewportId::ROOT)),
+                ("plot", ViewMode::Tile(ViewportId::ROOT)),
+                ("job", ViewMode::Disabled),
+                ("feeders", ViewMode::Disabled),
+            ],
+        }
+    }
+}
+
+impl WorkspaceConfig {
+    pub fn from_preset(preset: LayoutPreset) -> Self {
+        let mut workspace = Self {
+            name: preset.name().to_string(),
+            ..Self::default()
+        };
+        for (key, mode) in preset.modes() {
+            if let Some(toggle_state) = workspace
+                .toggle_states
+                .iter_mut()
+                .find(|candidate| candidate.key == *key)
+            {
+                toggle_state.mode = *mode;
+            }
+        }
+        workspace
+    }
+
+    pub fn display_name(&self, index: usize) -> String {
+        match self.name.is_empty() {
+            true => tr!("layout-unnamed", { index: index + 1 }),
+            false => self.name.clone(),
+        }
+    }
+}
+
+/// The layouts of a machine profile that isn't selected, persisted.
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ProfileWorkspaces {
+    workspaces: Vec<Value<WorkspaceConfig>>,
+    active_workspace: usize,
+}
+
+impl Default for ProfileWorkspaces {
+    fn default() -> Self {
+        Self {
+            workspaces: LayoutPreset::ALL
+                .iter()
+                .map(|preset| Value::new(WorkspaceConfig::from_preset(*preset)))
+                .collect(),
+            active_workspace: 0,
+        }
+    }
+}
+
+/// The layouts of the selected machine profile, and the layouts of the other profiles.
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)]
 pub struct Workspaces {
     workspaces: Vec<Value<WorkspaceConfig>>,
     active_workspace: usize,
+    /// the selected machine profile, e.g. the address of the machine
+    profile: String,
+    other_profiles: HashMap<String, ProfileWorkspaces>,
 }
 
 impl Default for Workspaces {
     fn default() -> Self {
+        let ProfileWorkspaces {
+            workspaces,
+            active_workspace,
+        } = ProfileWorkspaces::default();
+
         Self {
-            workspaces: vec![Value::new(WorkspaceConfig::default())],
-            active_workspace: 0,
+            workspaces,
+            active_workspace,
+            profile: String::new(),
+            other_profiles: Default::default(),
         }
     }
 }
@@ -1174,6 +1453,7 @@ pub enum WorkspaceError {
     AlreadyActive,
     CannotRemoveActiveWorkspace,
     DuplicateToggleKey,
+    DuplicateName,
 }
 
 impl Workspaces {
@@ -1248,35 +1528,110 @@ impl Workspaces {
         self.workspaces.len()
     }
 
-    pub fn add_toggle(&mut self, toggle: ToggleDefinition) -> Result<(), WorkspaceError> {
-        let duplicate_key = self.workspaces.iter().any(|workspace| {
-            let workspace = workspace.lock().unwrap();
-            workspace
-                .toggle_states
-                .iter()
-                .any(|it| it.kind == toggle.kind && it.key == toggle.key)
-        });
-        if duplicate_key {
-            return Err(WorkspaceError::DuplicateToggleKey);
+    /// The index of the layout after the active one, wrapping around, for quickly switching between the layouts.
+    pub fn next_index(&self) -> usize {
+        (self.active_workspace + 1) % self.workspaces.len()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.workspaces
+            .iter()
+            .enumerate()
+            .map(|(index, workspace)| {
+                workspace
+                    .lock()
+                    .unwrap()
+                    .display_name(index)
+            })
+            .collect()
+    }
+
+    /// Saves the arrangement of the active layout as a new layout, returns its index.
+    pub fn save_active_as(&mut self, name: &str) -> Result<usize, WorkspaceError> {
+        let name = name.trim();
+        if self
+            .names()
+            .iter()
+            .any(|candidate| candidate == name)
+        {
+            return Err(WorkspaceError::DuplicateName);
         }
 
-        let mut workspace = self.active();
+        let index = self.clone_active();
+        self.workspaces[index]
+            .lock()
+            .unwrap()
+            .name = name.to_string();
 
-        let viewport_id = ViewportId::ROOT;
+        Ok(index)
+    }
 
-        let toggle_state = ToggleState {
-            key: toggle.key.to_string(),
-            kind: toggle.kind,
-            mode: ViewMode::Window(viewport_id),
-            window_position: None,
-            window_size: None,
-        };
+    /// Selects the layouts of a machine profile, a profile that wasn't selected before starts with the
+    /// [`LayoutPreset`] layouts.  The layouts of the previous profile are kept.
+    ///
+    /// Selected before the viewports are created, see [`Workspaces::ensure_viewport`].
+    pub fn select_profile(&mut self, profile: &str) {
+        if self.profile == profile {
+            return;
+        }
 
-        workspace
-            .toggle_states
-            .push(toggle_state);
+        let previous_profile = std::mem::replace(&mut self.profile, profile.to_string());
+        // layouts persisted before there were profiles are adopted by the first profile that is selected
+        if !previous_profile.is_empty() {
+            let selected = self
+                .other_profiles
+                .remove(profile)
+                .unwrap_or_default();
+
+            let previous = ProfileWorkspaces {
+                workspaces: std::mem::replace(&mut self.workspaces, selected.workspaces),
+                active_workspace: std::mem::replace(&mut self.active_workspace, selected.active_workspace),
+            };
+            self.other_profiles
+                .insert(previous_profile, previous);
+        }
 
-        Ok(())
+        // e.g. edited
+        if self.workspaces.is_empty() {
+            self.workspaces = ProfileWorkspaces::default().workspaces;
+        }
+        self.active_workspace = self
+            .active_workspace
+            .min(self.workspaces.len() - 1);
+    }
+
+    /// Adds the toggle to each layout of the selected profile that doesn't have it yet, e.g. a camera that was
+    /// added after the layout was saved.
+    pub fn add_toggle(&mut self, toggle: ToggleDefinition) -> Result<(), WorkspaceError> {
+        let mut added = false;
+        for workspace in self.workspaces.iter() {
+            let mut workspace = workspace.lock().unwrap();
+            let duplicate_key = workspace
+                .toggle_states
+                .iter()
+                .any(|it| it.kind == toggle.kind && it.key == toggle.key);
+            if duplicate_key {
+                continue;
+            }
+
+            let toggle_state = ToggleState {
+                key: toggle.key.to_string(),
+                kind: toggle.kind,
+                mode: toggle.mode,
+                window_position: None,
+                window_size: None,
+            };
+
+            workspace
+                .toggle_states
+                .push(toggle_state);
+            added = true;
+        }
+
+        match added {
+            true => Ok(()),
+            false => Err(WorkspaceError::DuplicateToggleKey),
+        }
     }
 }
 
@@ -0,0 +1,435 @@
+//! Accuracy measurement routine, using a calibration plate and the down camera.
+//!
+//! The head is moved over each dot of a grid on the calibration plate, approaching from alternating directions, and
+//! the position of the dot in the image gives the actual position of the head.  From the samples the accuracy,
+//! repeatability, per-axis linearity and the skew of the axes are computed, along with corrections for the
+//! [`AxisCorrections`] of the machine configuration, which are written to a report.
+//!
+//! The corrections are not applied to the configuration automatically, they should be reviewed first.
+
+use std::future::Future;
+
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use machine_geometry::{AffineTransform, Point};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{AccuracyConfig, AxisCorrections, LinearCorrection};
+
+pub mod positioner;
+#[cfg(feature = "machine-vision")]
+pub mod vision;
+
+#[cfg(test)]
+mod tests;
+
+/// Below this range of nominal positions an axis has not been measured, e.g. a single column of dots.
+const MIN_FIT_RANGE: f64 = 1e-6;
+
+/// Moves the head, waiting until it has settled.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait Positioner {
+    fn move_to<'a>(&'a mut self, target: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+}
+
+/// Measures the calibration plate dot nearest the center of the camera.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait DotMeasurer {
+    /// The position of the dot relative to the center of the camera, in machine coordinates, `None` if there is no
+    /// dot.
+    fn measure<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AccuracySample {
+    /// index of the grid point
+    pub point: usize,
+    pub nominal: Point,
+    pub measured: Point,
+}
+
+/// `measured = nominal * scale + offset`, fitted to the mean of the samples of each grid point.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AxisLinearity {
+    pub scale: f64,
+    pub offset: f64,
+    /// largest deviation of a grid point from the fitted line, in millimeters
+    pub linearity_mm: f64,
+}
+
+impl AxisLinearity {
+    /// The correction to apply to the nominal position so that the measured position is the nominal position.
+    pub fn correction(&self) -> LinearCorrection {
+        LinearCorrection {
+            scale: 1.0 / self.scale,
+            offset: -self.offset / self.scale,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccuracyAnalysis {
+    /// largest distance between a measured position and the nominal position, in millimeters
+    pub accuracy_mm: f64,
+    /// largest distance between a sample and the mean of the samples of the same grid point, in millimeters
+    pub repeatability_mm: f64,
+    /// standard deviation of the samples from the mean of the samples of the same grid point, in millimeters
+    pub repeatability_sigma_mm: f64,
+    /// `None` if the axis was not measured
+    pub x: Option<AxisLinearity>,
+    pub y: Option<AxisLinearity>,
+    /// see [`AxisCorrections::skew_degrees`], `None` unless the grid has more than one row and column
+    pub skew_degrees: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccuracyReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub camera: String,
+    pub points: u32,
+    pub repetitions: u32,
+    /// measurements where the dot could not be found
+    pub failed_measurements: u32,
+    pub analysis: AccuracyAnalysis,
+    /// the corrections that were applied while measuring
+    pub applied_corrections: AxisCorrections,
+    /// to be copied to the `axis_corrections` of the machine configuration
+    pub suggested_corrections: AxisCorrections,
+    pub samples: Vec<AccuracySample>,
+}
+
+/// The dots of the calibration plate, row by row.
+pub fn grid_points(config: &AccuracyConfig) -> Vec<Point> {
+    (0..config.rows)
+        .flat_map(|row| {
+            (0..config.columns).map(move |column| Point {
+                x: config.origin_x + column as f64 * config.pitch,
+                y: config.origin_y + row as f64 * config.pitch,
+            })
+        })
+        .collect()
+}
+
+/// The order the grid points are visited in, every other repetition is reversed so that each point is approached
+/// from both directions, which includes backlash in the repeatability.
+pub fn measurement_order(points: usize, repetitions: u32) -> Vec<usize> {
+    (0..repetitions)
+        .flat_map(|repetition| {
+            (0..points).map(move |index| match repetition % 2 {
+                0 => index,
+                _ => points - 1 - index,
+            })
+        })
+        .collect()
+}
+
+/// Visit the grid points, measuring the actual position at each.
+///
+/// Returns the samples, and the number of measurements where the dot could not be found.
+pub async fn measure_grid(
+    positioner: &mut impl Positioner,
+    measurer: &mut impl DotMeasurer,
+    config: &AccuracyConfig,
+) -> anyhow::Result<(Vec<AccuracySample>, u32)> {
+    let points = grid_points(config);
+    if points.is_empty() {
+        bail!(
+            "Empty calibration grid. columns: {}, rows: {}",
+            config.columns,
+            config.rows
+        )
+    }
+    let order = measurement_order(points.len(), config.repetitions);
+
+    let mut samples = Vec::with_capacity(order.len());
+    let mut failed_measurements = 0;
+    for (progress, index) in order.into_iter().enumerate() {
+        let nominal = points[index];
+        positioner.move_to(nominal).await?;
+
+        match measurer.measure().await {
+            Ok(Some(offset)) => {
+                // the dot is at the nominal position, so the head is offset from it in the opposite direction
+                let measured = Point {
+                    x: nominal.x - offset.x,
+                    y: nominal.y - offset.y,
+                };
+                debug!(
+                    "Accuracy sample. point: {}, nominal: {:?}, measured: {:?}, progress: {}",
+                    index, nominal, measured, progress
+                );
+                samples.push(AccuracySample {
+                    point: index,
+                    nominal,
+                    measured,
+                });
+            }
+            Ok(None) => {
+                warn!("Calibration dot not found. point: {}, nominal: {:?}", index, nominal);
+                failed_measurements += 1;
+            }
+            Err(e) => {
+                warn!("Unable to measure calibration dot. point: {}, error: {:?}", index, e);
+                failed_measurements += 1;
+            }
+        }
+    }
+
+    Ok((samples, failed_measurements))
+}
+
+/// Returns `None` if there are no samples.
+pub fn analyze(samples: &[AccuracySample]) -> Option<AccuracyAnalysis> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let accuracy_mm = samples
+        .iter()
+        .map(|sample| {
+            sample
+                .measured
+                .distance(&sample.nominal)
+        })
+        .fold(0.0, f64::max);
+
+    // mean of the samples of each grid point
+    let mut points: Vec<usize> = samples
+        .iter()
+        .map(|sample| sample.point)
+        .collect();
+    points.sort();
+    points.dedup();
+    let means = points
+        .iter()
+        .map(|point| {
+            let point_samples = samples
+                .iter()
+                .filter(|sample| sample.point == *point)
+                .collect::<Vec<_>>();
+            let count = point_samples.len() as f64;
+            let measured = Point {
+                x: point_samples
+                    .iter()
+                    .map(|sample| sample.measured.x)
+                    .sum::<f64>()
+                    / count,
+                y: point_samples
+                    .iter()
+                    .map(|sample| sample.measured.y)
+                    .sum::<f64>()
+                    / count,
+            };
+            (*point, point_samples[0].nominal, measured)
+        })
+        .collect::<Vec<_>>();
+
+    let deviations = samples
+        .iter()
+        .map(|sample| {
+            let (_, _, mean) = means
+                .iter()
+                .find(|(point, _, _)| *point == sample.point)
+                .unwrap();
+            sample.measured.distance(mean)
+        })
+        .collect::<Vec<_>>();
+    let repeatability_mm = deviations
+        .iter()
+        .copied()
+        .fold(0.0, f64::max);
+    let repeatability_sigma_mm = (deviations
+        .iter()
+        .map(|deviation| deviation * deviation)
+        .sum::<f64>()
+        / deviations.len() as f64)
+        .sqrt();
+
+    let x = fit(&means
+        .iter()
+        .map(|(_, nominal, measured)| (nominal.x, measured.x))
+        .collect::<Vec<_>>());
+    let y = fit(&means
+        .iter()
+        .map(|(_, nominal, measured)| (nominal.y, measured.y))
+        .collect::<Vec<_>>());
+    let skew_degrees = fit_affine(
+        &means
+            .iter()
+            .map(|(_, nominal, measured)| (*nominal, *measured))
+            .collect::<Vec<_>>(),
+    )
+    .map(|transform| transform.skew_degrees());
+
+    Some(AccuracyAnalysis {
+        accuracy_mm,
+        repeatability_mm,
+        repeatability_sigma_mm,
+        x,
+        y,
+        skew_degrees,
+    })
+}
+
+/// Least squares fit of `(nominal, measured)` pairs, `None` if the nominal positions do not span a range.
+fn fit(pairs: &[(f64, f64)]) -> Option<AxisLinearity> {
+    let count = pairs.len() as f64;
+    let mean_nominal = pairs
+        .iter()
+        .map(|(nominal, _)| nominal)
+        .sum::<f64>()
+        / count;
+    let mean_measured = pairs
+        .iter()
+        .map(|(_, measured)| measured)
+        .sum::<f64>()
+        / count;
+
+    let (covariance, variance) = pairs
+        .iter()
+        .fold((0.0, 0.0), |(covariance, variance), (nominal, measured)| {
+            let dn = nominal - mean_nominal;
+            (covariance + dn * (measured - mean_measured), variance + dn * dn)
+        });
+
+    let range = pairs
+        .iter()
+        .map(|(nominal, _)| (nominal - mean_nominal).abs())
+        .fold(0.0, f64::max);
+    if range < MIN_FIT_RANGE {
+        return None;
+    }
+
+    let scale = covariance / variance;
+    let offset = mean_measured - scale * mean_nominal;
+    let linearity_mm = pairs
+        .iter()
+        .map(|(nominal, measured)| (measured - (nominal * scale + offset)).abs())
+        .fold(0.0, f64::max);
+
+    Some(AxisLinearity {
+        scale,
+        offset,
+        linearity_mm,
+    })
+}
+
+/// Least squares fit of `measured = transform(nominal)`, `None` if the nominal positions do not span an area.
+fn fit_affine(pairs: &[(Point, Point)]) -> Option<AffineTransform> {
+    let mean_nominal = centroid(
+        pairs
+            .iter()
+            .map(|(nominal, _)| *nominal),
+    );
+    let mean_measured = centroid(
+        pairs
+            .iter()
+            .map(|(_, measured)| *measured),
+    );
+
+    // normal equations of the centered positions, solved for each measured axis
+    let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+    let (mut cxx, mut cyx, mut cxy, mut cyy) = (0.0, 0.0, 0.0, 0.0);
+    for (nominal, measured) in pairs {
+        let (nx, ny) = (nominal.x - mean_nominal.x, nominal.y - mean_nominal.y);
+        let (mx, my) = (measured.x - mean_measured.x, measured.y - mean_measured.y);
+        sxx += nx * nx;
+        sxy += nx * ny;
+        syy += ny * ny;
+        cxx += nx * mx;
+        cyx += ny * mx;
+        cxy += nx * my;
+        cyy += ny * my;
+    }
+
+    let determinant = sxx * syy - sxy * sxy;
+    if sxx < MIN_FIT_RANGE || syy < MIN_FIT_RANGE || determinant <= MIN_FIT_RANGE * sxx * syy {
+        return None;
+    }
+
+    let xx = (cxx * syy - cyx * sxy) / determinant;
+    let xy = (cyx * sxx - cxx * sxy) / determinant;
+    let yx = (cxy * syy - cyy * sxy) / determinant;
+    let yy = (cyy * sxx - cxy * sxy) / determinant;
+
+    Some(AffineTransform {
+        xx,
+        xy,
+        yx,
+        yy,
+        tx: mean_measured.x - xx * mean_nominal.x - xy * mean_nominal.y,
+        ty: mean_measured.y - yx * mean_nominal.x - yy * mean_nominal.y,
+    })
+}
+
+fn centroid(points: impl ExactSizeIterator<Item = Point>) -> Point {
+    let count = points.len() as f64;
+    let sum = points.fold(Point::default(), |sum, point| Point {
+        x: sum.x + point.x,
+        y: sum.y + point.y,
+    });
+    Point {
+        x: sum.x / count,
+        y: sum.y / count,
+    }
+}
+
+/// The applied corrections combined with the corrections for the measured axes, axes that were not measured keep the
+/// applied correction, as does the skew if it was not measured.
+pub fn suggested_corrections(applied: &AxisCorrections, analysis: &AccuracyAnalysis) -> AxisCorrections {
+    let suggest = |applied: &LinearCorrection, linearity: &Option<AxisLinearity>| match linearity {
+        Some(linearity) => applied.combine(&linearity.correction()),
+        None => *applied,
+    };
+
+    AxisCorrections {
+        x: suggest(&applied.x, &analysis.x),
+        y: suggest(&applied.y, &analysis.y),
+        // the skew that remains after the applied correction
+        skew_degrees: applied.skew_degrees + analysis.skew_degrees.unwrap_or(0.0),
+    }
+}
+
+/// Measure the accuracy and build the report, `applied_corrections` must be the corrections used by the positioner.
+pub async fn run_accuracy_routine(
+    positioner: &mut impl Positioner,
+    measurer: &mut impl DotMeasurer,
+    config: &AccuracyConfig,
+    camera: String,
+    applied_corrections: AxisCorrections,
+) -> anyhow::Result<AccuracyReport> {
+    let started_at = Utc::now();
+    info!(
+        "Accuracy measurement started. columns: {}, rows: {}, pitch: {}mm, repetitions: {}",
+        config.columns, config.rows, config.pitch, config.repetitions
+    );
+
+    let (samples, failed_measurements) = measure_grid(positioner, measurer, config).await?;
+    let Some(analysis) = analyze(&samples) else {
+        bail!("No calibration dots were measured. failed: {}", failed_measurements)
+    };
+
+    info!(
+        "Accuracy measured. accuracy: {:.4}mm, repeatability: {:.4}mm, x: {:?}, y: {:?}, skew: {:?}",
+        analysis.accuracy_mm, analysis.repeatability_mm, analysis.x, analysis.y, analysis.skew_degrees
+    );
+
+    Ok(AccuracyReport {
+        started_at,
+        finished_at: Utc::now(),
+        camera,
+        points: config.columns * config.rows,
+        repetitions: config.repetitions,
+        failed_measurements,
+        suggested_corrections: suggested_corrections(&applied_corrections, &analysis),
+        analysis,
+        applied_corrections,
+        samples,
+    })
+}
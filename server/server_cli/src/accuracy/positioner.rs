@@ -0,0 +1,99 @@
+use std::future::Future;
+
+use anyhow::{anyhow, bail};
+use log::{debug, info};
+use machine_geometry::Point;
+use tokio::sync::watch;
+use tokio::time::{self, Duration};
+
+use super::Positioner;
+use crate::config::{AccuracyConfig, AxisCorrections};
+use crate::coordinates::CoordinateTransform;
+use crate::ioboard::batching::CommandBatcher;
+use crate::motion::{AxisMove, plan_setpoints, stream_setpoints};
+use crate::safety::SafetyState;
+
+/// Moves an axis planned by the server, see [`MotionPlanning::Server`](crate::config::MotionPlanning::Server).
+///
+/// FUTURE there is currently only a single server planned axis, which is used as the X axis, the Y axis must already
+///        be at the row of dots being measured.
+pub struct SetpointPositioner {
+    batcher: CommandBatcher,
+    axis: u8,
+    interval: Duration,
+    settle: Duration,
+    config: AccuracyConfig,
+    transform: CoordinateTransform,
+    safety_rx: watch::Receiver<SafetyState>,
+    /// in steps, the axis is assumed to start at zero, the same as the setpoint streamer
+    position: f64,
+}
+
+impl SetpointPositioner {
+    pub fn new(
+        batcher: CommandBatcher,
+        axis: u8,
+        rate_hz: u32,
+        config: &AccuracyConfig,
+        corrections: AxisCorrections,
+        safety_rx: watch::Receiver<SafetyState>,
+    ) -> Self {
+        Self {
+            batcher,
+            axis,
+            interval: Duration::from_micros(1_000_000 / rate_hz.max(1) as u64),
+            settle: Duration::from_millis(config.settle_ms),
+            config: config.clone(),
+            transform: CoordinateTransform::new(&corrections),
+            safety_rx,
+            position: 0.0,
+        }
+    }
+
+    /// `x` in machine coordinates
+    async fn move_x(&mut self, x: f64) -> anyhow::Result<()> {
+        if !self
+            .safety_rx
+            .borrow()
+            .allows_new_moves()
+        {
+            info!("Waiting for the safety state to allow motion, axis: {}", self.axis);
+        }
+        let speed_factor = self
+            .safety_rx
+            .wait_for(SafetyState::allows_new_moves)
+            .await
+            .map_err(|_| anyhow!("Safety listener stopped"))?
+            .speed_factor();
+
+        let steps_per_mm = self.config.steps_per_mm;
+        let axis_move = AxisMove {
+            target: x * steps_per_mm,
+            max_jerk: self.config.max_jerk * steps_per_mm,
+            max_acceleration: self.config.max_acceleration * steps_per_mm * speed_factor,
+            max_velocity: self.config.max_velocity * steps_per_mm * speed_factor,
+        };
+
+        let setpoints = plan_setpoints(self.position, &axis_move, self.interval)
+            .map_err(|e| anyhow!("Unable to plan move. move: {:?}, error: {:?}", axis_move, e))?;
+        debug!("Planned move, axis: {}, setpoints: {}", self.axis, setpoints.len());
+
+        stream_setpoints(&self.batcher, self.axis, &setpoints, self.interval).await;
+        self.position = axis_move.target;
+
+        time::sleep(self.settle).await;
+        Ok(())
+    }
+}
+
+impl Positioner for SetpointPositioner {
+    fn move_to<'a>(&'a mut self, target: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            if self.config.rows > 1 {
+                bail!("Only a single row of dots can be measured, there is no server planned Y axis")
+            }
+            let machine = self.transform.job_to_machine(target);
+            self.move_x(machine.x).await
+        }
+    }
+}
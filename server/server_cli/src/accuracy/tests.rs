@@ -0,0 +1,299 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use machine_geometry::AffineTransform;
+
+use super::{
+    AccuracySample, DotMeasurer, Point, Positioner, analyze, grid_points, measure_grid, measurement_order,
+    suggested_corrections,
+};
+use crate::config::{AccuracyConfig, AxisCorrections, LinearCorrection};
+
+/// Moves a simulated head, the actual position has a linear error in x.
+struct FakePositioner {
+    head: Arc<Mutex<Point>>,
+    error: LinearCorrection,
+}
+
+impl Positioner for FakePositioner {
+    fn move_to<'a>(&'a mut self, target: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            *self.head.lock().unwrap() = Point {
+                x: self.error.apply(target.x),
+                y: target.y,
+            };
+            Ok(())
+        }
+    }
+}
+
+/// Sees the dot nearest to the simulated head, `None` for the dots in `missing`.
+struct FakeMeasurer {
+    head: Arc<Mutex<Point>>,
+    dots: Vec<Point>,
+    missing: Vec<usize>,
+}
+
+impl DotMeasurer for FakeMeasurer {
+    fn measure<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a {
+        async move {
+            let head = *self.head.lock().unwrap();
+            let (index, dot) = self
+                .dots
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    a.distance(&head)
+                        .total_cmp(&b.distance(&head))
+                })
+                .unwrap();
+            if self.missing.contains(&index) {
+                return Ok(None);
+            }
+            Ok(Some(Point {
+                x: dot.x - head.x,
+                y: dot.y - head.y,
+            }))
+        }
+    }
+}
+
+fn sample(point: usize, nominal: (f64, f64), measured: (f64, f64)) -> AccuracySample {
+    AccuracySample {
+        point,
+        nominal: Point {
+            x: nominal.0,
+            y: nominal.1,
+        },
+        measured: Point {
+            x: measured.0,
+            y: measured.1,
+        },
+    }
+}
+
+#[test]
+pub fn grid_points_row_by_row() {
+    // given
+    let config = AccuracyConfig {
+        origin_x: 10.0,
+        origin_y: 20.0,
+        pitch: 5.0,
+        columns: 3,
+        rows: 2,
+        ..AccuracyConfig::default()
+    };
+
+    // when
+    let points = grid_points(&config);
+
+    // then
+    assert_eq!(points.len(), 6);
+    assert_eq!(points[0], Point {
+        x: 10.0,
+        y: 20.0,
+    });
+    assert_eq!(points[2], Point {
+        x: 20.0,
+        y: 20.0,
+    });
+    assert_eq!(points[3], Point {
+        x: 10.0,
+        y: 25.0,
+    });
+}
+
+#[test]
+pub fn measurement_order_alternates_direction() {
+    // when
+    let order = measurement_order(3, 3);
+
+    // then
+    assert_eq!(order, vec![0, 1, 2, 2, 1, 0, 0, 1, 2]);
+}
+
+#[test]
+pub fn analyze_fits_scale_and_offset() {
+    // given
+    let samples = (0..5)
+        .map(|index| {
+            let nominal = index as f64 * 10.0;
+            sample(index, (nominal, 0.0), (nominal * 1.002 + 0.1, 0.0))
+        })
+        .collect::<Vec<_>>();
+
+    // when
+    let analysis = analyze(&samples).unwrap();
+
+    // then
+    let x = analysis.x.unwrap();
+    assert!((x.scale - 1.002).abs() < 1e-9);
+    assert!((x.offset - 0.1).abs() < 1e-9);
+    assert!(x.linearity_mm < 1e-9);
+    assert!((analysis.accuracy_mm - (40.0 * 0.002 + 0.1)).abs() < 1e-9);
+    assert_eq!(analysis.y, None);
+
+    // and the correction cancels the error
+    let correction = x.correction();
+    for nominal in [0.0, 15.0, 40.0] {
+        let actual = correction.apply(nominal) * x.scale + x.offset;
+        assert!((actual - nominal).abs() < 1e-9);
+    }
+}
+
+#[test]
+pub fn analyze_repeatability() {
+    // given
+    let samples = vec![
+        sample(0, (0.0, 0.0), (0.01, 0.0)),
+        sample(0, (0.0, 0.0), (-0.01, 0.0)),
+        sample(1, (10.0, 0.0), (10.0, 0.0)),
+        sample(1, (10.0, 0.0), (10.0, 0.0)),
+    ];
+
+    // when
+    let analysis = analyze(&samples).unwrap();
+
+    // then
+    assert!((analysis.repeatability_mm - 0.01).abs() < 1e-9);
+    assert!((analysis.repeatability_sigma_mm - (0.0002_f64 / 4.0).sqrt()).abs() < 1e-9);
+    assert!((analysis.accuracy_mm - 0.01).abs() < 1e-9);
+}
+
+#[test]
+pub fn analyze_without_samples() {
+    // expect
+    assert_eq!(analyze(&[]), None);
+}
+
+#[test]
+pub fn suggested_corrections_keep_unmeasured_axes() {
+    // given
+    let applied = AxisCorrections {
+        x: LinearCorrection {
+            scale: 1.001,
+            offset: 0.0,
+        },
+        y: LinearCorrection {
+            scale: 0.999,
+            offset: 0.2,
+        },
+    };
+    let samples = (0..3)
+        .map(|index| {
+            let nominal = index as f64 * 10.0;
+            sample(index, (nominal, 0.0), (nominal * 1.0005, 0.0))
+        })
+        .collect::<Vec<_>>();
+    let analysis = analyze(&samples).unwrap();
+
+    // when
+    let suggested = suggested_corrections(&applied, &analysis);
+
+    // then
+    assert!((suggested.x.scale - 1.001 / 1.0005).abs() < 1e-9);
+    assert_eq!(suggested.y, applied.y);
+}
+
+#[test]
+pub fn combined_correction_applies_residual_first() {
+    // given
+    let applied = LinearCorrection {
+        scale: 2.0,
+        offset: 1.0,
+    };
+    let residual = LinearCorrection {
+        scale: 3.0,
+        offset: 4.0,
+    };
+
+    // when
+    let combined = applied.combine(&residual);
+
+    // then
+    assert_eq!(combined.apply(5.0), applied.apply(residual.apply(5.0)));
+}
+
+#[tokio::test]
+pub async fn measure_grid_with_simulated_machine() {
+    // given
+    let config = AccuracyConfig {
+        columns: 5,
+        rows: 1,
+        repetitions: 2,
+        ..AccuracyConfig::default()
+    };
+    let head = Arc::new(Mutex::new(Point::default()));
+    let error = LinearCorrection {
+        scale: 1.001,
+        offset: -0.05,
+    };
+    let mut positioner = FakePositioner {
+        head: head.clone(),
+        error,
+    };
+    let mut measurer = FakeMeasurer {
+        head,
+        dots: grid_points(&config),
+        missing: vec![3],
+    };
+
+    // when
+    let (samples, failed_measurements) = measure_grid(&mut positioner, &mut measurer, &config)
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(samples.len(), 8);
+    assert_eq!(failed_measurements, 2);
+
+    let x = analyze(&samples).unwrap().x.unwrap();
+    assert!((x.scale - error.scale).abs() < 1e-9);
+    assert!((x.offset - error.offset).abs() < 1e-9);
+}
+
+#[test]
+pub fn analyze_measures_skew() {
+    // given
+    let config = AccuracyConfig {
+        columns: 3,
+        rows: 3,
+        ..AccuracyConfig::default()
+    };
+    let geometry = AffineTransform::skewed(0.2);
+    let samples = grid_points(&config)
+        .into_iter()
+        .enumerate()
+        .map(|(index, nominal)| {
+            let measured = geometry.apply(nominal);
+            sample(index, (nominal.x, nominal.y), (measured.x, measured.y))
+        })
+        .collect::<Vec<_>>();
+
+    // when
+    let analysis = analyze(&samples).unwrap();
+
+    // then
+    assert!((analysis.skew_degrees.unwrap() - 0.2).abs() < 1e-9);
+
+    // and the suggested correction includes the skew
+    let suggested = suggested_corrections(&AxisCorrections::default(), &analysis);
+    assert!((suggested.skew_degrees - 0.2).abs() < 1e-9);
+}
+
+#[test]
+pub fn analyze_single_row_has_no_skew() {
+    // given
+    let samples = (0..3)
+        .map(|index| {
+            let nominal = index as f64 * 10.0;
+            sample(index, (nominal, 5.0), (nominal, 5.0))
+        })
+        .collect::<Vec<_>>();
+
+    // when
+    let analysis = analyze(&samples).unwrap();
+
+    // then
+    assert_eq!(analysis.skew_degrees, None);
+}
@@ -0,0 +1,153 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use machine_geometry::{ImageView, Point};
+use machine_ids::CameraId;
+use server_common::camera::{CameraCalibration, CameraDefinition, CameraMounting};
+use server_vision::exposure::Roi;
+use server_vision::fiducial::find_center_dot;
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::watch;
+
+use super::positioner::SetpointPositioner;
+use super::{DotMeasurer, run_accuracy_routine};
+use crate::AppEvent;
+use crate::config::{AccuracyConfig, AxisCorrections};
+use crate::ioboard::batching::CommandBatcher;
+use crate::safety::SafetyState;
+use crate::storage::{StorageImpl, write_report};
+use crate::vision::{VisionCaptureRequest, VisionQueue};
+
+/// Measures using a calibrated down camera, frames are captured via the [`VisionQueue`].
+pub struct VisionMeasurer {
+    vision_queue: VisionQueue,
+    camera: CameraId,
+    calibration: CameraCalibration,
+}
+
+impl VisionMeasurer {
+    pub fn new(vision_queue: VisionQueue, camera: CameraId, calibration: CameraCalibration) -> Self {
+        Self {
+            vision_queue,
+            camera,
+            calibration,
+        }
+    }
+}
+
+impl DotMeasurer for VisionMeasurer {
+    fn measure<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a {
+        async move {
+            let frame = self
+                .vision_queue
+                .capture(VisionCaptureRequest {
+                    camera: self.camera,
+                    pause_preview: true,
+                    // the dot nearest the center is located
+                    metering_roi: Some(Roi::centered(0.25)),
+                })
+                .await?;
+
+            // decoding and contour finding takes longer than is acceptable for the runtime
+            let dot = tokio::task::spawn_blocking(move || find_center_dot(&frame.jpeg_bytes)).await??;
+
+            let scale = self.calibration.pixel_scale();
+            Ok(dot.map(|dot| scale.to_machine(dot.center, ImageView::FromAbove)))
+        }
+    }
+}
+
+/// The first down camera that has a calibration, cameras are identified by index, see
+/// [`camera_definition_for_identifier`](crate::camera::camera_definition_for_identifier).
+fn down_camera(cameras: &[CameraDefinition]) -> Option<(CameraId, &CameraDefinition, CameraCalibration)> {
+    cameras.iter().enumerate().find_map(
+        |(index, definition)| match (definition.mounting, definition.calibration) {
+            (CameraMounting::Down, Some(calibration)) => Some((CameraId::new(index as u8), definition, calibration)),
+            _ => None,
+        },
+    )
+}
+
+/// Runs the accuracy measurement once and writes the report.
+///
+/// When `config.apply_corrections` is set the `axis_corrections` are applied while measuring, which verifies them,
+/// otherwise the uncorrected accuracy is measured.
+#[allow(clippy::too_many_arguments)]
+pub async fn accuracy_runner(
+    batcher: CommandBatcher,
+    axis: u8,
+    rate_hz: u32,
+    cameras: Vec<CameraDefinition>,
+    config: AccuracyConfig,
+    axis_corrections: AxisCorrections,
+    vision_queue: VisionQueue,
+    safety_rx: watch::Receiver<SafetyState>,
+    storage: Arc<StorageImpl>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let Some((camera, definition, calibration)) = down_camera(&cameras) else {
+        error!("Accuracy measurement requires a calibrated down camera");
+        return;
+    };
+    if config.rows > 1 {
+        // FUTURE requires a server planned Y axis, see `SetpointPositioner`
+        error!(
+            "Accuracy measurement of multiple rows is not supported. rows: {}",
+            config.rows
+        );
+        return;
+    }
+
+    let applied_corrections = match config.apply_corrections {
+        true => axis_corrections,
+        false => AxisCorrections::default(),
+    };
+
+    info!(
+        "Accuracy measurement. camera: {}, axis: {}, corrections: {:?}",
+        definition.name, axis, applied_corrections
+    );
+
+    let mut positioner = SetpointPositioner::new(batcher, axis, rate_hz, &config, applied_corrections, safety_rx);
+    let mut measurer = VisionMeasurer::new(vision_queue, camera, calibration);
+
+    let app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let result = select! {
+        _ = app_shutdown_handler => {
+            warn!("Accuracy measurement cancelled by shutdown");
+            return;
+        }
+        result = run_accuracy_routine(
+            &mut positioner,
+            &mut measurer,
+            &config,
+            definition.name.clone(),
+            applied_corrections,
+        ) => result,
+    };
+
+    let report = match result {
+        Ok(report) => report,
+        Err(e) => {
+            error!("Accuracy measurement failed. error: {:?}", e);
+            return;
+        }
+    };
+
+    let name = format!(
+        "accuracy-{}",
+        report
+            .started_at
+            .format("%Y%m%d-%H%M%S")
+    );
+    match write_report(&storage, &config.report_directory, &name, &report).await {
+        Ok(path) => info!(
+            "Accuracy report written. path: {}, accuracy: {:.4}mm, repeatability: {:.4}mm, suggested corrections: {:?}",
+            path, report.analysis.accuracy_mm, report.analysis.repeatability_mm, report.suggested_corrections
+        ),
+        Err(e) => error!("Unable to write accuracy report. error: {:?}", e),
+    }
+}
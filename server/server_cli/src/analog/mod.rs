@@ -0,0 +1,257 @@
+//! The analog input channels of the io board, for ad-hoc sensors, see [`AnalogChannelDefinition`].
+//!
+//! The io board doesn't persist the configuration of its channels, so the channels are compared with the configuration
+//! at an interval, and configured again when they differ, e.g. after the io board restarted, channels that are not in
+//! the configuration are removed.  The readings published by the io board are only logged, they are received by the
+//! operator UI and integrations directly.
+
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, topic};
+use ergot_util::{ClientError, ClientWrapper};
+use ioboard_shared::analog::{
+    ANALOG_CHANNELS_MAX, AnalogChannelConfig, AnalogError, AnalogLabel, AnalogReading, AnalogReply, AnalogRequest,
+};
+use log::{debug, info, warn};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::time;
+
+use crate::AppEvent;
+use crate::config::AnalogChannelDefinition;
+use crate::ioboard::{AnalogEndpoint, CommandSequencer};
+use crate::networking::dead_letter;
+
+#[cfg(test)]
+mod tests;
+
+topic!(AnalogTopic, AnalogReading, "topic/ioboard/analog");
+
+const DISCOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+const REQUEST_ATTEMPTS: u32 = 2;
+
+/// The channels of the io board are compared with the configuration at this interval.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnalogDefinitionError {
+    /// more than [`ANALOG_LABEL_MAX`](ioboard_shared::analog::ANALOG_LABEL_MAX) bytes
+    NameTooLong,
+    /// more than [`ANALOG_LABEL_MAX`](ioboard_shared::analog::ANALOG_LABEL_MAX) bytes
+    UnitsTooLong,
+    InvalidChannel,
+    /// the channel is defined more than once
+    DuplicateChannel,
+}
+
+#[derive(Debug)]
+pub enum AnalogChannelsError {
+    /// no io board has the analog endpoint
+    NoIoBoard,
+    Request(ClientError),
+    Analog(AnalogError),
+    UnexpectedReply(AnalogReply),
+}
+
+/// The configuration of each channel, by channel, the scaling and the sample interval are validated by the io board.
+pub fn channel_configs(
+    definitions: &[AnalogChannelDefinition],
+) -> Result<[Option<AnalogChannelConfig>; ANALOG_CHANNELS_MAX], AnalogDefinitionError> {
+    let mut configs = [None; ANALOG_CHANNELS_MAX];
+    for definition in definitions {
+        let entry = configs
+            .get_mut(definition.channel as usize)
+            .ok_or(AnalogDefinitionError::InvalidChannel)?;
+        if entry.is_some() {
+            return Err(AnalogDefinitionError::DuplicateChannel);
+        }
+        *entry = Some(AnalogChannelConfig {
+            input: definition.input,
+            name: AnalogLabel::new(&definition.name).ok_or(AnalogDefinitionError::NameTooLong)?,
+            units: AnalogLabel::new(&definition.units).ok_or(AnalogDefinitionError::UnitsTooLong)?,
+            scale: definition.scale,
+            offset: definition.offset,
+            sample_interval_ms: definition.sample_interval_ms,
+        });
+    }
+    Ok(configs)
+}
+
+/// The requests that make the `current` channels of the io board match the `configs`.
+pub fn channel_requests(
+    configs: &[Option<AnalogChannelConfig>; ANALOG_CHANNELS_MAX],
+    current: &[Option<AnalogChannelConfig>; ANALOG_CHANNELS_MAX],
+) -> Vec<AnalogRequest> {
+    configs
+        .iter()
+        .zip(current.iter())
+        .enumerate()
+        .filter(|(_, (config, current))| config != current)
+        .map(|(channel, (config, _))| match config {
+            Some(config) => AnalogRequest::Configure {
+                channel: channel as u8,
+                config: *config,
+            },
+            None => AnalogRequest::Remove {
+                channel: channel as u8,
+            },
+        })
+        .collect()
+}
+
+/// The io board is discovered again after a failed request.
+struct AnalogClient {
+    stack: RouterStack,
+    sequencer: Arc<CommandSequencer>,
+    address: Option<Address>,
+}
+
+impl AnalogClient {
+    /// Returns the number of requests that were sent.
+    async fn synchronize(
+        &mut self,
+        configs: &[Option<AnalogChannelConfig>; ANALOG_CHANNELS_MAX],
+    ) -> Result<usize, AnalogChannelsError> {
+        let address = match self.address {
+            Some(address) => address,
+            None => self.discover().await?,
+        };
+        let result = async {
+            let current = match self
+                .request(address, AnalogRequest::Channels)
+                .await?
+            {
+                AnalogReply::Channels {
+                    channels, ..
+                } => channels,
+                reply => return Err(AnalogChannelsError::UnexpectedReply(reply)),
+            };
+            let requests = channel_requests(configs, &current);
+            for request in requests.iter() {
+                self.request(address, *request).await?;
+            }
+            Ok(requests.len())
+        }
+        .await;
+        if let Err(AnalogChannelsError::Request(_)) = &result {
+            self.address = None;
+        }
+        result
+    }
+
+    async fn discover(&mut self) -> Result<Address, AnalogChannelsError> {
+        let query = SocketQuery {
+            key: AnalogEndpoint::REQ_KEY.to_bytes(),
+            nash_req: NameRequirement::Any,
+            frame_kind: FrameKind::ENDPOINT_REQ,
+            broadcast: false,
+        };
+        // FUTURE select the io board, the first io board with the analog endpoint is used
+        let address = self
+            .stack
+            .discovery()
+            .discover_sockets(1, DISCOVERY_TIMEOUT, &query)
+            .await
+            .into_iter()
+            .map(|result| result.address)
+            .next()
+            .ok_or(AnalogChannelsError::NoIoBoard)?;
+        info!("Analog io board discovered. address: {:?}", address);
+        Ok(*self.address.insert(address))
+    }
+
+    async fn request(&self, address: Address, request: AnalogRequest) -> Result<AnalogReply, AnalogChannelsError> {
+        let client = self
+            .stack
+            .endpoints()
+            .client::<AnalogEndpoint>(address, None);
+        let client = ClientWrapper::new(REQUEST_TIMEOUT, client);
+        let request = self.sequencer.sequenced(request);
+        let response = client
+            .request_with_retry(&request, REQUEST_ATTEMPTS)
+            .await
+            .inspect_err(|e| dead_letter::request_failed::<AnalogEndpoint>(address, REQUEST_ATTEMPTS, e))
+            .map_err(AnalogChannelsError::Request)?;
+        response.map_err(AnalogChannelsError::Analog)
+    }
+}
+
+/// Keeps the channels of the io board configured, and logs the readings.
+pub async fn analog_channels(
+    stack: RouterStack,
+    definitions: Vec<AnalogChannelDefinition>,
+    sequencer: Arc<CommandSequencer>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let configs = match channel_configs(&definitions) {
+        Ok(configs) => configs,
+        Err(e) => {
+            warn!(
+                "Invalid analog channels, the channels are not configured. error: {:?}",
+                e
+            );
+            app_shutdown_handler.await;
+            return;
+        }
+    };
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<AnalogTopic>(16, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    let mut client = AnalogClient {
+        stack: stack.clone(),
+        sequencer,
+        address: None,
+    };
+    let mut ticker = time::interval(CHECK_INTERVAL);
+    ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+    let mut failing = false;
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                match client.synchronize(&configs).await {
+                    Ok(requests) => {
+                        if requests > 0 {
+                            info!("Analog channels configured. requests: {}", requests);
+                        }
+                        failing = false;
+                    }
+                    Err(e) => {
+                        match failing {
+                            false => warn!("Unable to configure analog channels. error: {:?}", e),
+                            true => debug!("Unable to configure analog channels. error: {:?}", e),
+                        }
+                        failing = true;
+                    }
+                }
+            }
+            msg = hdl.recv() => {
+                let reading = msg.t;
+                debug!(
+                    "Analog reading. channel: {}, name: {}, value: {} {}, raw: {}",
+                    reading.channel,
+                    reading.name.as_str(),
+                    reading.value,
+                    reading.units.as_str(),
+                    reading.raw
+                );
+            }
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+        }
+    }
+    info!("analog channels shutdown");
+}
@@ -0,0 +1,72 @@
+use ioboard_shared::analog::{ANALOG_CHANNELS_MAX, AnalogLabel, AnalogRequest};
+
+use super::{AnalogDefinitionError, channel_configs, channel_requests};
+use crate::config::AnalogChannelDefinition;
+
+fn definition(channel: u8, name: &str) -> AnalogChannelDefinition {
+    AnalogChannelDefinition {
+        channel,
+        input: 0,
+        name: name.to_string(),
+        units: "l/min".to_string(),
+        scale: 10.0,
+        offset: 0.0,
+        sample_interval_ms: 100,
+    }
+}
+
+#[test]
+pub fn channels_are_configured_by_index() {
+    // when
+    let configs = channel_configs(&[definition(2, "flow")]).unwrap();
+
+    // then
+    let config = configs[2].unwrap();
+    assert_eq!(config.name, AnalogLabel::new("flow").unwrap());
+    assert_eq!(config.units.as_str(), "l/min");
+    assert_eq!(configs.iter().flatten().count(), 1);
+}
+
+#[test]
+pub fn invalid_definitions_are_refused() {
+    // expect
+    assert_eq!(
+        channel_configs(&[definition(ANALOG_CHANNELS_MAX as u8, "flow")]),
+        Err(AnalogDefinitionError::InvalidChannel)
+    );
+    assert_eq!(
+        channel_configs(&[definition(0, "flow"), definition(0, "pressure")]),
+        Err(AnalogDefinitionError::DuplicateChannel)
+    );
+    assert_eq!(
+        channel_configs(&[definition(0, "a name that is too long")]),
+        Err(AnalogDefinitionError::NameTooLong)
+    );
+}
+
+#[test]
+pub fn only_the_channels_that_differ_are_requested() {
+    // given
+    let configs = channel_configs(&[definition(0, "flow"), definition(1, "pressure")]).unwrap();
+    let mut current = channel_configs(&[definition(0, "flow"), definition(3, "potentiometer")]).unwrap();
+
+    // when
+    let requests = channel_requests(&configs, &current);
+
+    // then
+    assert_eq!(requests, vec![
+        AnalogRequest::Configure {
+            channel: 1,
+            config: configs[1].unwrap(),
+        },
+        AnalogRequest::Remove {
+            channel: 3,
+        },
+    ]);
+
+    // and when
+    current = configs;
+
+    // then
+    assert!(channel_requests(&configs, &current).is_empty());
+}
@@ -0,0 +1,738 @@
+//! Bridges selected topics and operator commands to operator UIs outside the machine network, e.g. a laptop on the
+//! office network, over TCP.
+//!
+//! Each frame is a single line of RON.  A client first sends a [`BridgeHello`] with the token of the bridge, see
+//! [`BridgeConfig::token`], nothing is sent to or forwarded for a client before then.  The messages of the topics
+//! matching the configured patterns are sent to every client as a [`BridgeFrame::Message`], and the client sends a
+//! [`BridgeRequest`] for each operator command, which is forwarded to the operator command endpoint of the server if
+//! the command is allowed, the result is sent back as a [`BridgeFrame::Response`].  In read-only mode only the commands
+//! that don't change the machine are forwarded, see [`is_read_only`].
+//!
+//! A bridge without a token may only listen on a loopback address, e.g. for clients connecting via an SSH tunnel, see
+//! [`validate_config`].
+//!
+//! FUTURE TLS, the token and the frames are not encrypted, use a VPN or an SSH tunnel over untrusted networks
+//! FUTURE WebSocket framing, for operator UIs running in a browser
+
+use std::net::SocketAddr;
+use std::pin::{Pin, pin};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::{Endpoint, Topic};
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind};
+use ergot_util::ClientWrapper;
+use log::{debug, error, info, warn};
+use operator_shared::commands::{OperatorCommandRequest, OperatorCommandResponse};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::{Mutex, mpsc};
+use tokio::task::JoinSet;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+
+use crate::AppEvent;
+use crate::config::BridgeConfig;
+use crate::diagnostics::tap::TopicPattern;
+use crate::feeders::{FeederEventTopic, FeedersStatusTopic};
+use crate::homing::HomingStatusTopic;
+use crate::ioboard::IoBoardEventTopic;
+use crate::job::JobEventTopic;
+use crate::limits::LimitOverrideTopic;
+use crate::motion::PositionTopic;
+use crate::networking::dead_letter;
+use crate::nozzles::MaintenanceTopic;
+use crate::operator::OperatorCommandEndpoint;
+use crate::readiness::{ReadinessTopic, SelfTestTopic};
+use crate::resonance::ResonanceSweepTopic;
+use crate::safety::SafetyTopic;
+use crate::test_area::TestShotEventTopic;
+#[cfg(feature = "machine-vision")]
+use crate::vision::VisionStatusTopic;
+
+#[cfg(test)]
+mod tests;
+
+/// Frames are dropped when a client falls behind by more than this, e.g. a slow link.
+const FRAME_QUEUE_SIZE: usize = 256;
+
+/// Longer requests are refused and the client is disconnected, the largest operator commands are well below this.
+pub const REQUEST_LENGTH_MAX: usize = 64 * 1024;
+
+/// A client that doesn't send its hello within this is disconnected.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+const OPERATOR_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const OPERATOR_REQUEST_ATTEMPTS: u32 = 1;
+
+/// Allows every command, in `BridgeConfig::commands`.
+const ANY_COMMAND: &str = "*";
+
+/// The first line sent by a client.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BridgeHello {
+    /// see [`BridgeConfig::token`], ignored if the bridge has no token
+    pub token: String,
+}
+
+/// A request from a client, `id` is chosen by the client and returned with the response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BridgeRequest {
+    pub id: u32,
+    pub request: OperatorCommandRequest,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BridgeError {
+    /// the line could not be decoded as a `BridgeRequest`
+    InvalidRequest(String),
+    /// the command is not in the allow list of the bridge
+    NotAllowed,
+    /// the bridge is read-only, and the command changes the machine
+    ReadOnly,
+    /// the operator command endpoint of the server did not respond
+    Unavailable,
+    /// the hello of the client had the wrong token, the client is disconnected
+    Unauthorized,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BridgeConfigError {
+    /// the bridge has no token, and listens on an address other than a loopback address
+    Unauthenticated(SocketAddr),
+}
+
+/// Anyone who can reach the bridge could otherwise send commands, or watch the machine.
+pub fn validate_config(config: &BridgeConfig) -> Result<(), BridgeConfigError> {
+    let Some(listen) = config.listen else {
+        return Ok(());
+    };
+    let has_token = config
+        .token
+        .as_ref()
+        .is_some_and(|token| !token.is_empty());
+    if !has_token && !listen.ip().is_loopback() {
+        return Err(BridgeConfigError::Unauthenticated(listen));
+    }
+    Ok(())
+}
+
+/// Returns an error if the client may not use the bridge.
+pub fn authenticate(config: &BridgeConfig, hello: &BridgeHello) -> Result<(), BridgeError> {
+    match &config.token {
+        Some(token) if !tokens_equal(token.as_bytes(), hello.token.as_bytes()) => Err(BridgeError::Unauthorized),
+        _ => Ok(()),
+    }
+}
+
+/// The time taken doesn't depend on where the tokens differ, so that the token can't be guessed byte by byte.
+fn tokens_equal(expected: &[u8], actual: &[u8]) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// A frame sent to the clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BridgeFrame<T> {
+    Message {
+        topic: String,
+        message: T,
+    },
+    Response {
+        /// `None` if the id of the request could not be decoded
+        id: Option<u32>,
+        result: Result<OperatorCommandResponse, BridgeError>,
+    },
+}
+
+/// A single line, including the line feed.
+pub fn encode_frame<T: Serialize>(frame: &BridgeFrame<T>) -> Result<String, ron::Error> {
+    let mut line = ron::to_string(frame)?;
+    line.push('\n');
+    Ok(line)
+}
+
+fn response_frame(id: Option<u32>, result: Result<OperatorCommandResponse, BridgeError>) -> Option<String> {
+    let frame = BridgeFrame::<()>::Response {
+        id,
+        result,
+    };
+    encode_frame(&frame)
+        .inspect_err(|e| error!("Unable to encode bridge response. id: {:?}, error: {:?}", id, e))
+        .ok()
+}
+
+/// The name of the command, as given in `BridgeConfig::commands`.
+pub fn command_name(request: &OperatorCommandRequest) -> &'static str {
+    match request {
+        OperatorCommandRequest::Heartbeat(_) => "Heartbeat",
+        OperatorCommandRequest::FetchMachineGeometry => "FetchMachineGeometry",
+        OperatorCommandRequest::HomeAll => "HomeAll",
+        OperatorCommandRequest::OverrideReadinessCheck {
+            ..
+        } => "OverrideReadinessCheck",
+        OperatorCommandRequest::StartJob => "StartJob",
+        OperatorCommandRequest::ResolveIntervention {
+            ..
+        } => "ResolveIntervention",
+        OperatorCommandRequest::FetchJobCheckpoint => "FetchJobCheckpoint",
+        OperatorCommandRequest::ConfirmResume(_) => "ConfirmResume",
+        OperatorCommandRequest::EstimateJob {
+            ..
+        } => "EstimateJob",
+        OperatorCommandRequest::SetFeederCount {
+            ..
+        } => "SetFeederCount",
+        OperatorCommandRequest::RunTestPattern {
+            ..
+        } => "RunTestPattern",
+        OperatorCommandRequest::ClearTestArea => "ClearTestArea",
+        OperatorCommandRequest::SetTopicTap(_) => "SetTopicTap",
+        OperatorCommandRequest::FetchLogLevels => "FetchLogLevels",
+        OperatorCommandRequest::SetLogLevel {
+            ..
+        } => "SetLogLevel",
+        OperatorCommandRequest::ClearLogLevel {
+            ..
+        } => "ClearLogLevel",
+        OperatorCommandRequest::ApplyConfig(_) => "ApplyConfig",
+        OperatorCommandRequest::OverrideAxisLimit(_) => "OverrideAxisLimit",
+        OperatorCommandRequest::ClearAxisLimitOverride {
+            ..
+        } => "ClearAxisLimitOverride",
+        OperatorCommandRequest::RunResonanceSweep(_) => "RunResonanceSweep",
+        OperatorCommandRequest::EStop => "EStop",
+        OperatorCommandRequest::ResetEStop => "ResetEStop",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::CameraCommand(_, _) => "CameraCommand",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::ListCameras => "ListCameras",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::ListCaptures {
+            ..
+        } => "ListCaptures",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::FetchCapture {
+            ..
+        } => "FetchCapture",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::FetchCaptureThumbnail {
+            ..
+        } => "FetchCaptureThumbnail",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::FetchCaptureAnnotations {
+            ..
+        } => "FetchCaptureAnnotations",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::ScanCode(_) => "ScanCode",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::VerifyFeederOrientation {
+            ..
+        } => "VerifyFeederOrientation",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::ListTemplates {
+            ..
+        } => "ListTemplates",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::CreateTemplate {
+            ..
+        } => "CreateTemplate",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::UpdateTemplate {
+            ..
+        } => "UpdateTemplate",
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::DeleteTemplate {
+            ..
+        } => "DeleteTemplate",
+    }
+}
+
+/// `true` for the commands that neither move the machine nor change its state, e.g. fetching the geometry or listing
+/// the captures.  Commands that capture an image, e.g. scanning a code, move the head and are not read-only.
+pub fn is_read_only(request: &OperatorCommandRequest) -> bool {
+    match request {
+        OperatorCommandRequest::Heartbeat(_)
+        | OperatorCommandRequest::FetchMachineGeometry
+        | OperatorCommandRequest::FetchJobCheckpoint
+        | OperatorCommandRequest::FetchLogLevels
+        | OperatorCommandRequest::EstimateJob {
+            ..
+        } => true,
+        #[cfg(feature = "machine-vision")]
+        OperatorCommandRequest::ListCameras
+        | OperatorCommandRequest::ListCaptures {
+            ..
+        }
+        | OperatorCommandRequest::FetchCapture {
+            ..
+        }
+        | OperatorCommandRequest::FetchCaptureThumbnail {
+            ..
+        }
+        | OperatorCommandRequest::FetchCaptureAnnotations {
+            ..
+        }
+        | OperatorCommandRequest::ListTemplates {
+            ..
+        } => true,
+        _ => false,
+    }
+}
+
+/// Returns an error if the request may not be forwarded by the bridge.
+pub fn check_request(config: &BridgeConfig, request: &OperatorCommandRequest) -> Result<(), BridgeError> {
+    let name = command_name(request);
+    let allowed = config
+        .commands
+        .iter()
+        .any(|command| command == ANY_COMMAND || command == name);
+    if !allowed {
+        return Err(BridgeError::NotAllowed);
+    }
+    if config.read_only && !is_read_only(request) {
+        return Err(BridgeError::ReadOnly);
+    }
+    Ok(())
+}
+
+type ForwardFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A topic that can be bridged, the messages are encoded using the message type of the topic.
+pub struct BridgedTopic {
+    pub path: &'static str,
+    forward: fn(RouterStack, mpsc::Sender<String>, CancellationToken) -> ForwardFuture,
+}
+
+impl BridgedTopic {
+    fn of<T>() -> Self
+    where
+        T: Topic + 'static,
+        T::Message: Serialize + DeserializeOwned + Clone + Send + 'static,
+    {
+        Self {
+            path: T::PATH,
+            forward: |stack, frames_tx, cancel| Box::pin(forward_topic::<T>(stack, frames_tx, cancel)),
+        }
+    }
+}
+
+/// The topics of the server that can be bridged, the topics of interest to an operator.
+///
+/// The io board commands and setpoints are left out, they are too frequent for a remote link, they can be inspected
+/// using the topic tap, see `diagnostics::tap`.
+pub fn topic_registry() -> Vec<BridgedTopic> {
+    vec![
+        BridgedTopic::of::<IoBoardEventTopic>(),
+        BridgedTopic::of::<PositionTopic>(),
+        BridgedTopic::of::<SafetyTopic>(),
+        BridgedTopic::of::<SelfTestTopic>(),
+        BridgedTopic::of::<ReadinessTopic>(),
+        BridgedTopic::of::<HomingStatusTopic>(),
+        BridgedTopic::of::<LimitOverrideTopic>(),
+        BridgedTopic::of::<ResonanceSweepTopic>(),
+        BridgedTopic::of::<JobEventTopic>(),
+        BridgedTopic::of::<FeedersStatusTopic>(),
+        BridgedTopic::of::<FeederEventTopic>(),
+        BridgedTopic::of::<MaintenanceTopic>(),
+        BridgedTopic::of::<TestShotEventTopic>(),
+        #[cfg(feature = "machine-vision")]
+        BridgedTopic::of::<VisionStatusTopic>(),
+    ]
+}
+
+/// The topics of the registry that match any of the patterns.
+pub fn bridged_topics(patterns: &[TopicPattern]) -> Vec<BridgedTopic> {
+    topic_registry()
+        .into_iter()
+        .filter(|topic| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.matches(topic.path))
+        })
+        .collect()
+}
+
+async fn forward_topic<T>(stack: RouterStack, frames_tx: mpsc::Sender<String>, cancel: CancellationToken)
+where
+    T: Topic,
+    T::Message: Serialize + DeserializeOwned + Clone + Send + 'static,
+{
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<T>(FRAME_QUEUE_SIZE, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    let mut dropped: u64 = 0;
+    loop {
+        select! {
+            _ = cancel.cancelled() => {
+                break
+            }
+            msg = hdl.recv() => {
+                let frame = BridgeFrame::Message {
+                    topic: T::PATH.to_string(),
+                    message: msg.t,
+                };
+                let line = match encode_frame(&frame) {
+                    Ok(line) => line,
+                    Err(e) => {
+                        warn!("Unable to encode bridged message. topic: {}, error: {:?}", T::PATH, e);
+                        continue
+                    }
+                };
+                match frames_tx.try_send(line) {
+                    Ok(()) => {}
+                    Err(mpsc::error::TrySendError::Full(_)) => {
+                        dropped += 1;
+                        if dropped.is_power_of_two() {
+                            warn!("Bridge client too slow, messages dropped. topic: {}, dropped: {}", T::PATH, dropped);
+                        }
+                    }
+                    Err(mpsc::error::TrySendError::Closed(_)) => break,
+                }
+            }
+        }
+    }
+}
+
+/// Forwards requests to the operator command endpoint of the server, the address of the endpoint is discovered on the
+/// first request.
+struct OperatorClient {
+    stack: RouterStack,
+    address: Mutex<Option<Address>>,
+}
+
+impl OperatorClient {
+    fn new(stack: RouterStack) -> Self {
+        Self {
+            stack,
+            address: Mutex::new(None),
+        }
+    }
+
+    async fn request(&self, request: &OperatorCommandRequest) -> Result<OperatorCommandResponse, BridgeError> {
+        let mut address = self.address.lock().await;
+        let endpoint = match *address {
+            Some(endpoint) => endpoint,
+            None => {
+                let query = SocketQuery {
+                    key: OperatorCommandEndpoint::REQ_KEY.to_bytes(),
+                    nash_req: NameRequirement::Any,
+                    frame_kind: FrameKind::ENDPOINT_REQ,
+                    broadcast: false,
+                };
+                let result = self
+                    .stack
+                    .discovery()
+                    .discover_sockets(4, OPERATOR_REQUEST_TIMEOUT, &query)
+                    .await
+                    .into_iter()
+                    .next()
+                    .ok_or(BridgeError::Unavailable)?;
+                *address = Some(result.address);
+                result.address
+            }
+        };
+        // requests of a client are forwarded concurrently
+        drop(address);
+
+        let client = self
+            .stack
+            .endpoints()
+            .client::<OperatorCommandEndpoint>(endpoint, None);
+        let client = ClientWrapper::new(OPERATOR_REQUEST_TIMEOUT, client);
+        client
+            .request_with_retry(request, OPERATOR_REQUEST_ATTEMPTS)
+            .await
+            .map_err(|e| {
+                warn!(
+                    "Bridged request failed. request: {}, error: {:?}",
+                    command_name(request),
+                    e
+                );
+                dead_letter::request_failed::<OperatorCommandEndpoint>(endpoint, OPERATOR_REQUEST_ATTEMPTS, &e);
+                BridgeError::Unavailable
+            })
+    }
+}
+
+/// Reads a line of at most [`REQUEST_LENGTH_MAX`] bytes, `None` once the client has closed the connection.
+async fn read_request(reader: &mut BufReader<OwnedReadHalf>, line: &mut String) -> std::io::Result<Option<()>> {
+    line.clear();
+    let read = reader
+        .take(REQUEST_LENGTH_MAX as u64 + 1)
+        .read_line(line)
+        .await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.len() > REQUEST_LENGTH_MAX {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "request too long"));
+    }
+    Ok(Some(()))
+}
+
+/// Reads the requests of a client, each request is forwarded by its own task, so that a slow command doesn't hold up
+/// the others.  The connection is cancelled once the client has closed it.
+async fn read_requests(
+    mut reader: BufReader<OwnedReadHalf>,
+    peer: SocketAddr,
+    config: Arc<BridgeConfig>,
+    operator: Arc<OperatorClient>,
+    frames_tx: mpsc::Sender<String>,
+    connection: CancellationToken,
+) {
+    let mut line = String::new();
+    loop {
+        let read = select! {
+            _ = connection.cancelled() => break,
+            read = read_request(&mut reader, &mut line) => read,
+        };
+        match read {
+            Ok(Some(())) => {}
+            Ok(None) => {
+                info!("Bridge client disconnected. peer: {}", peer);
+                break;
+            }
+            Err(e) => {
+                warn!(
+                    "Unable to read bridge request, disconnecting. peer: {}, error: {:?}",
+                    peer, e
+                );
+                break;
+            }
+        }
+
+        let bridge_request = match ron::from_str::<BridgeRequest>(line.trim()) {
+            Ok(bridge_request) => bridge_request,
+            Err(e) => {
+                debug!("Invalid bridge request. peer: {}, error: {:?}", peer, e);
+                if let Some(frame) = response_frame(None, Err(BridgeError::InvalidRequest(e.to_string()))) {
+                    let _ = frames_tx.send(frame).await;
+                }
+                continue;
+            }
+        };
+
+        let (config, operator, frames_tx) = (config.clone(), operator.clone(), frames_tx.clone());
+        tokio::spawn(async move {
+            let BridgeRequest {
+                id,
+                request,
+            } = bridge_request;
+            let result = match check_request(&config, &request) {
+                Ok(()) => operator.request(&request).await,
+                Err(e) => {
+                    info!(
+                        "Bridged request refused. peer: {}, request: {}, error: {:?}",
+                        peer,
+                        command_name(&request),
+                        e
+                    );
+                    Err(e)
+                }
+            };
+            if let Some(frame) = response_frame(Some(id), result) {
+                let _ = frames_tx.send(frame).await;
+            }
+        });
+    }
+    connection.cancel();
+}
+
+/// Reads the hello of the client, see [`BridgeHello`], returns `false` if the client is to be disconnected.
+///
+/// The hello is never logged, it has the token.
+async fn handshake(
+    reader: &mut BufReader<OwnedReadHalf>,
+    writer: &mut OwnedWriteHalf,
+    peer: SocketAddr,
+    config: &BridgeConfig,
+) -> bool {
+    let mut line = String::new();
+    let result = match time::timeout(HELLO_TIMEOUT, read_request(reader, &mut line)).await {
+        Ok(Ok(Some(()))) => match ron::from_str::<BridgeHello>(line.trim()) {
+            Ok(hello) => authenticate(config, &hello),
+            Err(_) => Err(BridgeError::InvalidRequest("Expected a hello".to_string())),
+        },
+        Ok(Ok(None)) => {
+            info!("Bridge client disconnected before its hello. peer: {}", peer);
+            return false;
+        }
+        Ok(Err(e)) => {
+            warn!(
+                "Unable to read bridge hello, disconnecting. peer: {}, error: {:?}",
+                peer, e
+            );
+            return false;
+        }
+        Err(_) => {
+            warn!("No bridge hello received, disconnecting. peer: {}", peer);
+            return false;
+        }
+    };
+
+    let Err(e) = result else {
+        info!("Bridge client authenticated. peer: {}", peer);
+        return true;
+    };
+    warn!("Bridge client refused, disconnecting. peer: {}, error: {:?}", peer, e);
+    if let Some(frame) = response_frame(None, Err(e)) {
+        let _ = writer.write_all(frame.as_bytes()).await;
+    }
+    false
+}
+
+async fn serve_client(
+    stack: RouterStack,
+    socket: TcpStream,
+    peer: SocketAddr,
+    config: Arc<BridgeConfig>,
+    patterns: Arc<Vec<TopicPattern>>,
+    connection: CancellationToken,
+) {
+    let (reader, mut writer) = socket.into_split();
+    let mut reader = BufReader::new(reader);
+    let authenticated = select! {
+        _ = connection.cancelled() => false,
+        authenticated = handshake(&mut reader, &mut writer, peer, &config) => authenticated,
+    };
+    if !authenticated {
+        return;
+    }
+
+    let (frames_tx, mut frames_rx) = mpsc::channel(FRAME_QUEUE_SIZE);
+
+    let mut tasks = JoinSet::new();
+    for topic in bridged_topics(&patterns) {
+        tasks.spawn((topic.forward)(stack.clone(), frames_tx.clone(), connection.clone()));
+    }
+    let operator = Arc::new(OperatorClient::new(stack));
+    tasks.spawn(read_requests(
+        reader,
+        peer,
+        config,
+        operator,
+        frames_tx,
+        connection.clone(),
+    ));
+
+    loop {
+        select! {
+            _ = connection.cancelled() => {
+                break
+            }
+            frame = frames_rx.recv() => {
+                let Some(frame) = frame else {
+                    break
+                };
+                if let Err(e) = writer.write_all(frame.as_bytes()).await {
+                    warn!("Unable to write to bridge client, disconnecting. peer: {}, error: {:?}", peer, e);
+                    break
+                }
+            }
+        }
+    }
+
+    connection.cancel();
+    tasks.join_all().await;
+}
+
+/// Accepts the connections of remote operator UIs until the server shuts down, does nothing if the bridge is not
+/// configured, see [`BridgeConfig::listen`].
+pub async fn bridge_listener(stack: RouterStack, config: BridgeConfig, app_event_rx: Receiver<AppEvent>) {
+    let Some(listen) = config.listen else {
+        info!("Operator bridge disabled");
+        return;
+    };
+
+    let patterns = match config
+        .topics
+        .iter()
+        .map(|pattern| TopicPattern::parse(pattern))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(patterns) => Arc::new(patterns),
+        Err(e) => {
+            error!("Invalid operator bridge topic pattern. error: {:?}", e);
+            return;
+        }
+    };
+
+    let listener = match TcpListener::bind(listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(
+                "Unable to listen for operator bridge clients. address: {}, error: {:?}",
+                listen, e
+            );
+            return;
+        }
+    };
+
+    let paths = bridged_topics(&patterns)
+        .iter()
+        .map(|topic| topic.path)
+        .collect::<Vec<_>>();
+    info!(
+        "Operator bridge listening. address: {}, topics: {:?}, commands: {:?}, read_only: {}, token: {}",
+        listen,
+        paths,
+        config.commands,
+        config.read_only,
+        config.token.is_some()
+    );
+
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+    let config = Arc::new(config);
+    let cancel = CancellationToken::new();
+    let mut clients = JoinSet::new();
+
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            Some(_) = clients.join_next(), if !clients.is_empty() => {}
+            accepted = listener.accept() => {
+                let (socket, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!("Unable to accept operator bridge client. error: {:?}", e);
+                        continue
+                    }
+                };
+                if clients.len() >= config.max_clients {
+                    warn!("Too many bridge clients, refusing. peer: {}, max_clients: {}", peer, config.max_clients);
+                    continue
+                }
+                info!("Bridge client connected. peer: {}", peer);
+                clients.spawn(serve_client(
+                    stack.clone(),
+                    socket,
+                    peer,
+                    config.clone(),
+                    patterns.clone(),
+                    cancel.child_token(),
+                ));
+            }
+        }
+    }
+
+    cancel.cancel();
+    clients.join_all().await;
+    info!("operator bridge shutdown");
+}
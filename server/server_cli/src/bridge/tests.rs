@@ -0,0 +1,196 @@
+use machine_ids::FeederId;
+use operator_shared::commands::{OperatorCommandRequest, OperatorCommandResponse};
+
+use super::{
+    BridgeConfigError, BridgeError, BridgeFrame, BridgeHello, BridgeRequest, authenticate, bridged_topics,
+    check_request, encode_frame, is_read_only, validate_config,
+};
+use crate::config::BridgeConfig;
+use crate::diagnostics::tap::TopicPattern;
+
+fn config(commands: &[&str], read_only: bool) -> BridgeConfig {
+    BridgeConfig {
+        commands: commands
+            .iter()
+            .map(|command| command.to_string())
+            .collect(),
+        read_only,
+        ..BridgeConfig::default()
+    }
+}
+
+#[test]
+pub fn commands_not_in_the_allow_list_are_refused() {
+    // given
+    let config = config(&["FetchMachineGeometry"], false);
+
+    // expect
+    assert_eq!(
+        check_request(&config, &OperatorCommandRequest::FetchMachineGeometry),
+        Ok(())
+    );
+    assert_eq!(
+        check_request(&config, &OperatorCommandRequest::StartJob),
+        Err(BridgeError::NotAllowed)
+    );
+    assert_eq!(
+        check_request(&BridgeConfig::default(), &OperatorCommandRequest::FetchMachineGeometry),
+        Err(BridgeError::NotAllowed)
+    );
+}
+
+#[test]
+pub fn read_only_refuses_commands_that_change_the_machine() {
+    // given
+    let config = config(&["*"], true);
+    let set_count = OperatorCommandRequest::SetFeederCount {
+        feeder: FeederId::new("F1"),
+        count: 100,
+    };
+
+    // expect
+    assert_eq!(
+        check_request(&config, &OperatorCommandRequest::FetchJobCheckpoint),
+        Ok(())
+    );
+    assert_eq!(
+        check_request(&config, &OperatorCommandRequest::EstimateJob {
+            offset: 0,
+        }),
+        Ok(())
+    );
+    assert_eq!(
+        check_request(&config, &OperatorCommandRequest::HomeAll),
+        Err(BridgeError::ReadOnly)
+    );
+    assert_eq!(check_request(&config, &set_count), Err(BridgeError::ReadOnly));
+}
+
+#[test]
+pub fn any_command_is_forwarded_without_read_only() {
+    // given
+    let config = config(&["*"], false);
+
+    // expect
+    assert!(!is_read_only(&OperatorCommandRequest::StartJob));
+    assert_eq!(check_request(&config, &OperatorCommandRequest::StartJob), Ok(()));
+}
+
+#[test]
+pub fn topics_matching_the_patterns_are_bridged() {
+    // given
+    let patterns = vec![TopicPattern::parse("topic/operator/**").unwrap()];
+
+    // when
+    let topics = bridged_topics(&patterns);
+
+    // then
+    let paths = topics
+        .iter()
+        .map(|topic| topic.path)
+        .collect::<Vec<_>>();
+    assert!(paths.contains(&"topic/operator/job"));
+    assert!(paths.contains(&"topic/operator/feeders"));
+    assert!(
+        paths
+            .iter()
+            .all(|path| path.starts_with("topic/operator/"))
+    );
+}
+
+#[test]
+pub fn frames_are_single_lines() {
+    // given
+    let frame = BridgeFrame::Message {
+        topic: "topic/operator/test".to_string(),
+        message: "first\nsecond".to_string(),
+    };
+
+    // when
+    let line = encode_frame(&frame).unwrap();
+
+    // then
+    assert_eq!(line.matches('\n').count(), 1);
+    assert!(line.ends_with('\n'));
+    let decoded = ron::from_str::<BridgeFrame<String>>(line.trim()).unwrap();
+    assert!(matches!(decoded, BridgeFrame::Message { message, .. } if message == "first\nsecond"));
+}
+
+#[test]
+pub fn response_round_trip() {
+    // given
+    let frame = BridgeFrame::<()>::Response {
+        id: Some(7),
+        result: Ok(OperatorCommandResponse::Acknowledged),
+    };
+
+    // when
+    let line = encode_frame(&frame).unwrap();
+    let decoded = ron::from_str::<BridgeFrame<()>>(line.trim()).unwrap();
+
+    // then
+    assert!(matches!(decoded, BridgeFrame::Response {
+        id: Some(7),
+        result: Ok(OperatorCommandResponse::Acknowledged),
+    }));
+}
+
+#[test]
+pub fn request_decoding() {
+    // given
+    let line = "(id: 3, request: Heartbeat(42))";
+
+    // when
+    let request = ron::from_str::<BridgeRequest>(line).unwrap();
+
+    // then
+    assert_eq!(request.id, 3);
+    assert_eq!(request.request, OperatorCommandRequest::Heartbeat(42));
+}
+
+#[test]
+pub fn clients_must_send_the_token_of_the_bridge() {
+    // given
+    let config = BridgeConfig {
+        token: Some("secret".to_string()),
+        ..BridgeConfig::default()
+    };
+    let hello = |token: &str| BridgeHello {
+        token: token.to_string(),
+    };
+
+    // expect
+    assert_eq!(authenticate(&config, &hello("secret")), Ok(()));
+    assert_eq!(authenticate(&config, &hello("secreT")), Err(BridgeError::Unauthorized));
+    assert_eq!(authenticate(&config, &hello("secret2")), Err(BridgeError::Unauthorized));
+    assert_eq!(authenticate(&config, &hello("")), Err(BridgeError::Unauthorized));
+
+    // and without a token, the bridge is limited to loopback addresses
+    assert_eq!(authenticate(&BridgeConfig::default(), &hello("")), Ok(()));
+}
+
+#[test]
+pub fn a_bridge_without_a_token_must_listen_on_a_loopback_address() {
+    // given
+    let config = |listen: &str, token: Option<&str>| BridgeConfig {
+        listen: Some(listen.parse().unwrap()),
+        token: token.map(str::to_string),
+        ..BridgeConfig::default()
+    };
+
+    // expect
+    assert_eq!(validate_config(&BridgeConfig::default()), Ok(()));
+    assert_eq!(validate_config(&config("127.0.0.1:18090", None)), Ok(()));
+    assert_eq!(validate_config(&config("[::1]:18090", None)), Ok(()));
+    assert_eq!(validate_config(&config("0.0.0.0:18090", Some("secret"))), Ok(()));
+    assert_eq!(
+        validate_config(&config("0.0.0.0:18090", None)),
+        Err(BridgeConfigError::Unauthenticated("0.0.0.0:18090".parse().unwrap()))
+    );
+    assert_eq!(
+        validate_config(&config("192.168.1.10:18090", Some(""))),
+        Err(BridgeConfigError::Unauthenticated(
+            "192.168.1.10:18090".parse().unwrap()
+        ))
+    );
+}
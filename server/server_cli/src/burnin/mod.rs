@@ -0,0 +1,447 @@
+//! Burn-in routine, used after building a new machine.
+//!
+//! Random moves across the envelope of an axis, with random limits, are run for a fixed duration while the setpoint
+//! overruns, missed steps, temperatures and io board faults are recorded.  At the end a [`BurnInReport`] is written
+//! to the [`BurnInConfig::report_directory`] of the storage.
+//!
+//! Only axes planned by the server can be driven, see [`MotionPlanning::Server`](crate::config::MotionPlanning).
+
+use std::collections::BTreeMap;
+use std::pin::pin;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use ergot::toolkits::tokio_udp::RouterStack;
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::motion::MotionSetpoint;
+use ioboard_shared::thermal::{TemperatureSensor, ThermalLevel, ThermalReading};
+use log::{debug, error, info, warn};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::watch;
+use tokio::time::{self, Duration, Instant};
+
+use crate::AppEvent;
+use crate::config::BurnInConfig;
+use crate::ioboard::batching::CommandBatcher;
+use crate::ioboard::{IoBoardEventTopic, ThermalTopic};
+use crate::motion::{AxisMove, PositionTopic, STEPS_PER_DEGREE, plan_setpoints, send_setpoint};
+use crate::safety::SafetyState;
+use crate::storage::{StorageImpl, write_report};
+
+#[cfg(test)]
+mod tests;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Generates random moves within the envelope, the same seed generates the same moves.
+pub struct MoveGenerator {
+    rng: StdRng,
+    config: BurnInConfig,
+}
+
+impl MoveGenerator {
+    /// The envelope must not be empty, i.e. `min_position <= max_position`.
+    pub fn new(config: &BurnInConfig, seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            config: config.clone(),
+        }
+    }
+
+    /// The max acceleration and velocity are scaled by `speed_factor`, e.g. for a safety speed reduction.
+    pub fn next_move(&mut self, speed_factor: f64) -> AxisMove {
+        let config = &self.config;
+
+        let target = self
+            .rng
+            .random_range(config.min_position..=config.max_position);
+        let limit_fraction = self.rng.random_range(
+            config
+                .min_limit_fraction
+                .clamp(0.01, 1.0)..=1.0,
+        );
+
+        AxisMove {
+            target: target * STEPS_PER_DEGREE,
+            max_jerk: config.max_jerk * STEPS_PER_DEGREE,
+            max_acceleration: config.max_acceleration * STEPS_PER_DEGREE * limit_fraction * speed_factor,
+            max_velocity: config.max_velocity * STEPS_PER_DEGREE * limit_fraction * speed_factor,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemperatureSummary {
+    pub sensor: TemperatureSensor,
+    /// in degrees celsius
+    pub max_temperature: f32,
+    pub max_level: ThermalLevel,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BurnInReport {
+    pub axis: u8,
+    pub seed: u64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub planned_hours: f64,
+    /// `false` if the routine was stopped before the planned duration
+    pub completed: bool,
+
+    pub moves: u64,
+    pub setpoints: u64,
+    /// setpoints sent more than a setpoint interval late
+    pub overruns: u64,
+    pub max_lateness_us: u64,
+
+    /// moves where the reported position did not match the target
+    pub missed_step_moves: u64,
+    pub max_position_error_steps: u64,
+    /// moves without a position report, e.g. the io board stopped responding
+    pub unverified_moves: u64,
+
+    pub temperatures: Vec<TemperatureSummary>,
+
+    pub crashes: u64,
+    pub thermal_faults: u64,
+    pub undervoltages: u64,
+    pub safety_trips: u64,
+
+    /// no missed steps and no faults, overruns and unverified moves are reported but do not fail the burn-in
+    pub passed: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct BurnInRecorder {
+    moves: u64,
+    setpoints: u64,
+    overruns: u64,
+    max_lateness: Duration,
+    missed_step_moves: u64,
+    max_position_error_steps: u64,
+    unverified_moves: u64,
+    temperatures: BTreeMap<TemperatureSensor, (f32, ThermalLevel)>,
+    crashes: u64,
+    thermal_faults: u64,
+    undervoltages: u64,
+    safety_trips: u64,
+}
+
+impl BurnInRecorder {
+    /// `lateness` is the time between when the setpoint was due and when it was sent.
+    pub fn record_setpoint(&mut self, lateness: Duration, interval: Duration) {
+        self.setpoints += 1;
+        if lateness >= interval {
+            self.overruns += 1;
+        }
+        self.max_lateness = self.max_lateness.max(lateness);
+    }
+
+    /// `reported` is the last position reported after the move, `None` if there were no reports.
+    pub fn record_move(&mut self, target_steps: i64, reported: Option<i64>, tolerance_steps: u64) {
+        self.moves += 1;
+        let Some(reported) = reported else {
+            self.unverified_moves += 1;
+            return;
+        };
+
+        let error = reported.abs_diff(target_steps);
+        if error > tolerance_steps {
+            self.missed_step_moves += 1;
+        }
+        self.max_position_error_steps = self.max_position_error_steps.max(error);
+    }
+
+    pub fn record_temperature(&mut self, reading: &ThermalReading) {
+        let (max_temperature, max_level) = self
+            .temperatures
+            .entry(reading.sensor)
+            .or_insert((reading.temperature, reading.level));
+        *max_temperature = max_temperature.max(reading.temperature);
+        *max_level = (*max_level).max(reading.level);
+    }
+
+    pub fn record_event(&mut self, event: &IoBoardEvent) {
+        match event {
+            IoBoardEvent::AxisCrash {
+                ..
+            }
+            | IoBoardEvent::AxisMotionAnomaly {
+                ..
+            } => self.crashes += 1,
+            IoBoardEvent::ThermalLevelChanged {
+                level: ThermalLevel::Critical,
+                ..
+            }
+            | IoBoardEvent::TemperatureSensorFault {
+                ..
+            } => self.thermal_faults += 1,
+            IoBoardEvent::SupplyUndervoltage {
+                ..
+            } => self.undervoltages += 1,
+            IoBoardEvent::SafetyInputChanged {
+                tripped: true, ..
+            } => self.safety_trips += 1,
+            _ => {}
+        }
+    }
+
+    pub fn moves(&self) -> u64 {
+        self.moves
+    }
+
+    pub fn report(
+        &self,
+        axis: u8,
+        seed: u64,
+        started_at: DateTime<Utc>,
+        finished_at: DateTime<Utc>,
+        planned_hours: f64,
+        completed: bool,
+    ) -> BurnInReport {
+        let temperatures = self
+            .temperatures
+            .iter()
+            .map(|(sensor, (max_temperature, max_level))| TemperatureSummary {
+                sensor: *sensor,
+                max_temperature: *max_temperature,
+                max_level: *max_level,
+            })
+            .collect();
+
+        let passed = completed
+            && self.missed_step_moves == 0
+            && self.crashes == 0
+            && self.thermal_faults == 0
+            && self.undervoltages == 0;
+
+        BurnInReport {
+            axis,
+            seed,
+            started_at,
+            finished_at,
+            planned_hours,
+            completed,
+            moves: self.moves,
+            setpoints: self.setpoints,
+            overruns: self.overruns,
+            max_lateness_us: self.max_lateness.as_micros() as u64,
+            missed_step_moves: self.missed_step_moves,
+            max_position_error_steps: self.max_position_error_steps,
+            unverified_moves: self.unverified_moves,
+            temperatures,
+            crashes: self.crashes,
+            thermal_faults: self.thermal_faults,
+            undervoltages: self.undervoltages,
+            safety_trips: self.safety_trips,
+            passed,
+        }
+    }
+}
+
+/// Runs the burn-in routine on an axis for `duration`, then writes the report.
+///
+/// Used instead of the [`setpoint_streamer`](crate::motion::setpoint_streamer), the safety state is applied the same
+/// way.  When shut down early the report is still written, but the burn-in does not pass.
+#[allow(clippy::too_many_arguments)]
+pub async fn burn_in_runner(
+    stack: RouterStack,
+    batcher: CommandBatcher,
+    axis: u8,
+    rate_hz: u32,
+    config: BurnInConfig,
+    duration: Duration,
+    mut safety_rx: watch::Receiver<SafetyState>,
+    storage: Arc<StorageImpl>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    if config.min_position > config.max_position {
+        error!(
+            "Invalid burn-in envelope. min: {}, max: {}",
+            config.min_position, config.max_position
+        );
+        return;
+    }
+
+    let interval = Duration::from_micros(1_000_000 / rate_hz.max(1) as u64);
+    let settle = Duration::from_millis(config.settle_ms);
+
+    let position_subber = stack
+        .topics()
+        .heap_bounded_receiver::<PositionTopic>(64, None);
+    let position_subber = pin!(position_subber);
+    let mut position_hdl = position_subber.subscribe();
+
+    let thermal_subber = stack
+        .topics()
+        .heap_bounded_receiver::<ThermalTopic>(16, None);
+    let thermal_subber = pin!(thermal_subber);
+    let mut thermal_hdl = thermal_subber.subscribe();
+
+    let event_subber = stack
+        .topics()
+        .heap_bounded_receiver::<IoBoardEventTopic>(16, None);
+    let event_subber = pin!(event_subber);
+    let mut event_hdl = event_subber.subscribe();
+
+    let seed = config.seed.unwrap_or_else(rand::random);
+    let mut generator = MoveGenerator::new(&config, seed);
+    let mut recorder = BurnInRecorder::default();
+
+    let started_at = Utc::now();
+    let deadline = Instant::now() + duration;
+    let mut progress_at = Instant::now() + PROGRESS_INTERVAL;
+
+    info!(
+        "Burn-in started. axis: {}, duration: {:?}, seed: {}, rate: {}Hz",
+        axis, duration, seed, rate_hz
+    );
+
+    let mut position = 0.0;
+    let completed = 'outer: loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break true;
+        }
+        if now >= progress_at {
+            progress_at = now + PROGRESS_INTERVAL;
+            info!(
+                "Burn-in progress. axis: {}, moves: {}, remaining: {:?}",
+                axis,
+                recorder.moves(),
+                deadline - now
+            );
+        }
+
+        if !safety_rx.borrow().allows_new_moves() {
+            info!("Waiting for the safety state to allow motion, axis: {}", axis);
+        }
+        let speed_factor = select! {
+            _ = &mut app_shutdown_handler => {
+                break 'outer false
+            }
+            result = safety_rx.wait_for(SafetyState::allows_new_moves) => match result {
+                Ok(state) => state.speed_factor(),
+                Err(_) => {
+                    error!("Safety listener stopped, axis: {}", axis);
+                    break 'outer false;
+                }
+            }
+        };
+
+        let axis_move = generator.next_move(speed_factor);
+        let setpoints = match plan_setpoints(position, &axis_move, interval) {
+            Ok(setpoints) => setpoints,
+            Err(e) => {
+                error!(
+                    "Unable to plan move, axis: {}, move: {:?}, error: {:?}",
+                    axis, axis_move, e
+                );
+                break false;
+            }
+        };
+        debug!(
+            "Planned burn-in move, axis: {}, move: {:?}, setpoints: {}",
+            axis,
+            axis_move,
+            setpoints.len()
+        );
+
+        let mut reported_position = None;
+        let mut settle_until: Option<Instant> = None;
+        let move_id = batcher.next_move_id();
+        let mut sequence = 0;
+        let mut ticker = time::interval(interval);
+        loop {
+            select! {
+                _ = &mut app_shutdown_handler => {
+                    break 'outer false
+                }
+                scheduled = ticker.tick(), if sequence < setpoints.len() => {
+                    recorder.record_setpoint(scheduled.elapsed(), interval);
+                    send_setpoint(&batcher, &MotionSetpoint {
+                        axis,
+                        move_id,
+                        sequence: sequence as u32,
+                        position: setpoints[sequence],
+                        interval_us: interval.as_micros() as u32,
+                    });
+                    sequence += 1;
+                    if sequence == setpoints.len() {
+                        settle_until = Some(Instant::now() + settle);
+                    }
+                }
+                _ = time::sleep_until(settle_until.unwrap_or_else(Instant::now)), if settle_until.is_some() => {
+                    break
+                }
+                msg = position_hdl.recv() => {
+                    if msg.t.axis == axis {
+                        reported_position = Some(msg.t.position);
+                    }
+                }
+                msg = thermal_hdl.recv() => {
+                    recorder.record_temperature(&msg.t);
+                }
+                msg = event_hdl.recv() => {
+                    recorder.record_event(&msg.t);
+                }
+            }
+        }
+
+        let target_steps = axis_move.target.round() as i64;
+        match reported_position {
+            Some(reported) if reported.abs_diff(target_steps) > config.position_tolerance_steps => warn!(
+                "Burn-in missed steps. axis: {}, target: {}, reported: {}",
+                axis, target_steps, reported
+            ),
+            None => warn!("Burn-in move not verified, no position report. axis: {}", axis),
+            _ => {}
+        }
+        recorder.record_move(target_steps, reported_position, config.position_tolerance_steps);
+        position = axis_move.target;
+    };
+
+    let report = recorder.report(
+        axis,
+        seed,
+        started_at,
+        Utc::now(),
+        duration.as_secs_f64() / 3600.0,
+        completed,
+    );
+
+    match report.passed {
+        true => info!(
+            "Burn-in passed. axis: {}, moves: {}, overruns: {}",
+            axis, report.moves, report.overruns
+        ),
+        false => warn!(
+            "Burn-in failed. axis: {}, completed: {}, moves: {}, missed step moves: {}, crashes: {}, thermal faults: {}, undervoltages: {}",
+            axis,
+            report.completed,
+            report.moves,
+            report.missed_step_moves,
+            report.crashes,
+            report.thermal_faults,
+            report.undervoltages
+        ),
+    }
+
+    let name = format!(
+        "burn-in-axis{}-{}",
+        report.axis,
+        report
+            .started_at
+            .format("%Y%m%d-%H%M%S")
+    );
+    match write_report(&storage, &config.report_directory, &name, &report).await {
+        Ok(path) => info!("Burn-in report written. path: {}", path),
+        Err(e) => error!("Unable to write burn-in report. error: {:?}", e),
+    }
+}
@@ -0,0 +1,177 @@
+use chrono::Utc;
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::motion::MotionAnomaly;
+use ioboard_shared::thermal::{TemperatureSensor, ThermalLevel, ThermalReading};
+use tokio::time::Duration;
+
+use super::{BurnInRecorder, MoveGenerator};
+use crate::config::BurnInConfig;
+use crate::motion::STEPS_PER_DEGREE;
+
+#[test]
+pub fn moves_within_envelope() {
+    // given
+    let config = BurnInConfig {
+        min_position: 90.0,
+        max_position: 180.0,
+        ..BurnInConfig::default()
+    };
+    let mut generator = MoveGenerator::new(&config, 42);
+
+    // when
+    let moves = (0..100)
+        .map(|_| generator.next_move(1.0))
+        .collect::<Vec<_>>();
+
+    // then
+    assert!(moves.iter().all(|axis_move| {
+        axis_move.target >= 90.0 * STEPS_PER_DEGREE && axis_move.target <= 180.0 * STEPS_PER_DEGREE
+    }));
+    assert!(moves.iter().all(|axis_move| {
+        axis_move.max_velocity <= config.max_velocity * STEPS_PER_DEGREE
+            && axis_move.max_velocity >= config.max_velocity * STEPS_PER_DEGREE * config.min_limit_fraction
+    }));
+}
+
+#[test]
+pub fn same_seed_same_moves() {
+    // given
+    let config = BurnInConfig::default();
+    let mut generator_1 = MoveGenerator::new(&config, 7);
+    let mut generator_2 = MoveGenerator::new(&config, 7);
+
+    // when
+    let moves_1 = (0..10)
+        .map(|_| generator_1.next_move(1.0))
+        .collect::<Vec<_>>();
+    let moves_2 = (0..10)
+        .map(|_| generator_2.next_move(1.0))
+        .collect::<Vec<_>>();
+
+    // then
+    assert_eq!(moves_1, moves_2);
+}
+
+#[test]
+pub fn speed_factor_scales_limits() {
+    // given
+    let config = BurnInConfig::default();
+    let mut generator_1 = MoveGenerator::new(&config, 7);
+    let mut generator_2 = MoveGenerator::new(&config, 7);
+
+    // when
+    let full_speed = generator_1.next_move(1.0);
+    let reduced_speed = generator_2.next_move(0.5);
+
+    // then
+    assert_eq!(reduced_speed.target, full_speed.target);
+    assert_eq!(reduced_speed.max_jerk, full_speed.max_jerk);
+    assert_eq!(reduced_speed.max_velocity, full_speed.max_velocity * 0.5);
+    assert_eq!(reduced_speed.max_acceleration, full_speed.max_acceleration * 0.5);
+}
+
+#[test]
+pub fn report_passes_without_faults() {
+    // given
+    let mut recorder = BurnInRecorder::default();
+    let interval = Duration::from_millis(10);
+    recorder.record_setpoint(Duration::from_millis(1), interval);
+    recorder.record_setpoint(Duration::from_millis(12), interval);
+    recorder.record_move(1000, Some(1000), 0);
+    recorder.record_move(2000, None, 0);
+
+    // when
+    let report = recorder.report(0, 1, Utc::now(), Utc::now(), 1.0, true);
+
+    // then
+    assert_eq!(report.moves, 2);
+    assert_eq!(report.setpoints, 2);
+    assert_eq!(report.overruns, 1);
+    assert_eq!(report.max_lateness_us, 12_000);
+    assert_eq!(report.unverified_moves, 1);
+    assert_eq!(report.missed_step_moves, 0);
+    assert!(report.passed);
+}
+
+#[test]
+pub fn report_fails_on_missed_steps() {
+    // given
+    let mut recorder = BurnInRecorder::default();
+    recorder.record_move(1000, Some(1002), 1);
+    recorder.record_move(2000, Some(1999), 1);
+
+    // when
+    let report = recorder.report(0, 1, Utc::now(), Utc::now(), 1.0, true);
+
+    // then
+    assert_eq!(report.missed_step_moves, 1);
+    assert_eq!(report.max_position_error_steps, 2);
+    assert!(!report.passed);
+}
+
+#[test]
+pub fn report_fails_on_faults() {
+    // given
+    let mut recorder = BurnInRecorder::default();
+    recorder.record_event(&IoBoardEvent::AxisCrash {
+        axis: 0,
+        load: 0.9,
+        baseline: 0.2,
+    });
+    recorder.record_event(&IoBoardEvent::AxisMotionAnomaly {
+        axis: 0,
+        anomaly: MotionAnomaly::FollowingError {
+            commanded_steps: 400,
+            measured_steps: 320,
+        },
+    });
+    recorder.record_event(&IoBoardEvent::AxisDerating {
+        axis: 0,
+        factor: 0.8,
+        temperature: 75.0,
+    });
+
+    // when
+    let report = recorder.report(0, 1, Utc::now(), Utc::now(), 1.0, true);
+
+    // then
+    assert_eq!(report.crashes, 2);
+    assert!(!report.passed);
+}
+
+#[test]
+pub fn report_fails_when_not_completed() {
+    // given
+    let recorder = BurnInRecorder::default();
+
+    // when
+    let report = recorder.report(0, 1, Utc::now(), Utc::now(), 1.0, false);
+
+    // then
+    assert!(!report.passed);
+}
+
+#[test]
+pub fn report_max_temperatures() {
+    // given
+    let mut recorder = BurnInRecorder::default();
+    for (temperature, level) in [
+        (40.0, ThermalLevel::Normal),
+        (85.0, ThermalLevel::Warning),
+        (60.0, ThermalLevel::Normal),
+    ] {
+        recorder.record_temperature(&ThermalReading {
+            sensor: TemperatureSensor::Driver(0),
+            temperature,
+            level,
+        });
+    }
+
+    // when
+    let report = recorder.report(0, 1, Utc::now(), Utc::now(), 1.0, true);
+
+    // then
+    assert_eq!(report.temperatures.len(), 1);
+    assert_eq!(report.temperatures[0].max_temperature, 85.0);
+    assert_eq!(report.temperatures[0].max_level, ThermalLevel::Warning);
+}
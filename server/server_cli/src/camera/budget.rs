@@ -0,0 +1,241 @@
+//! A memory budget for the camera frames in flight, see [`FrameBudget`].
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::topic;
+use log::{debug, info};
+use machine_ids::CameraId;
+use operator_shared::diagnostics::{CameraMemoryReport, CameraMemoryUsage};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::time;
+
+use crate::AppEvent;
+use crate::config::CameraMemoryConfig;
+
+topic!(CameraMemoryTopic, CameraMemoryReport, "topic/diagnostics/camera-memory");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FramePriority {
+    /// frames streamed to the operator UIs, dropped first
+    Preview,
+    /// frames captured for the vision routines, never dropped
+    Vision,
+}
+
+struct Reservation {
+    camera: CameraId,
+    bytes: usize,
+    priority: FramePriority,
+    evicted: Arc<AtomicBool>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct CameraCounters {
+    dropped_frames: u64,
+    evicted_frames: u64,
+}
+
+#[derive(Default)]
+struct BudgetState {
+    next_id: u64,
+    /// by id, i.e. oldest first
+    reservations: BTreeMap<u64, Reservation>,
+    /// every camera that reserved a frame, so that cameras without frames in flight are still reported
+    counters: BTreeMap<CameraId, CameraCounters>,
+}
+
+impl BudgetState {
+    /// The bytes reserved for the camera, or for all cameras if `None`.
+    ///
+    /// Evicted frames still use memory until they are aborted, but they are excluded when checking the limits, since
+    /// they are about to be released.
+    fn usage(&self, camera: Option<CameraId>, include_evicted: bool) -> usize {
+        self.reservations
+            .values()
+            .filter(|reservation| {
+                include_evicted
+                    || !reservation
+                        .evicted
+                        .load(Ordering::Relaxed)
+            })
+            .filter(|reservation| camera.is_none_or(|camera| reservation.camera == camera))
+            .map(|reservation| reservation.bytes)
+            .sum()
+    }
+}
+
+/// Limits the memory used by the camera frames in flight, per camera and in total.
+///
+/// Memory is reserved for a frame before it is sent or processed, and released when the [`FrameReservation`] is
+/// dropped.  When a limit would be exceeded preview frames are refused, the streamer drops the frame and sends a later
+/// one, and vision frames, which are never refused, evict the preview frames that are being sent.
+/// Without the limits several lagging operator UIs could each hold on to frames until the server runs out of memory.
+#[derive(Clone)]
+pub struct FrameBudget {
+    config: CameraMemoryConfig,
+    state: Arc<Mutex<BudgetState>>,
+}
+
+impl FrameBudget {
+    pub fn new(config: CameraMemoryConfig) -> Self {
+        Self {
+            config,
+            state: Default::default(),
+        }
+    }
+
+    /// Returns `None` if the frame is a preview frame and it doesn't fit in the budget.
+    pub fn reserve(&self, camera: CameraId, bytes: usize, priority: FramePriority) -> Option<FrameReservation> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .counters
+            .entry(camera)
+            .or_default();
+
+        let fits_camera =
+            |state: &BudgetState| state.usage(Some(camera), false) + bytes <= self.config.per_camera_bytes;
+        let fits_total = |state: &BudgetState| state.usage(None, false) + bytes <= self.config.total_bytes;
+
+        if !(fits_camera(&state) && fits_total(&state)) {
+            match priority {
+                FramePriority::Preview => {
+                    state
+                        .counters
+                        .entry(camera)
+                        .or_default()
+                        .dropped_frames += 1;
+                    return None;
+                }
+                FramePriority::Vision => {
+                    let mut evictable = state
+                        .reservations
+                        .iter()
+                        .filter(|(_id, reservation)| {
+                            reservation.priority < priority
+                                && !reservation
+                                    .evicted
+                                    .load(Ordering::Relaxed)
+                        })
+                        .map(|(_id, reservation)| (reservation.camera, reservation.evicted.clone()))
+                        .collect::<Vec<_>>();
+                    // the frames of this camera first, evicting them helps with both limits, then oldest first
+                    evictable.sort_by_key(|(evicted_camera, _evicted)| *evicted_camera != camera);
+
+                    for (evicted_camera, evicted) in evictable {
+                        if fits_camera(&state) && fits_total(&state) {
+                            break;
+                        }
+                        // evicting the frames of other cameras doesn't help with the limit of this camera
+                        if fits_total(&state) && evicted_camera != camera {
+                            continue;
+                        }
+                        evicted.store(true, Ordering::Relaxed);
+                        state
+                            .counters
+                            .entry(evicted_camera)
+                            .or_default()
+                            .evicted_frames += 1;
+                        debug!(
+                            "Evicted preview frame from the camera memory budget. camera: {}, for_camera: {}",
+                            evicted_camera, camera
+                        );
+                    }
+                }
+            }
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        let evicted = Arc::new(AtomicBool::new(false));
+        state
+            .reservations
+            .insert(id, Reservation {
+                camera,
+                bytes,
+                priority,
+                evicted: evicted.clone(),
+            });
+
+        Some(FrameReservation {
+            id,
+            evicted,
+            state: self.state.clone(),
+        })
+    }
+
+    pub fn report(&self) -> CameraMemoryReport {
+        let state = self.state.lock().unwrap();
+
+        let cameras = state
+            .counters
+            .iter()
+            .map(|(camera, counters)| CameraMemoryUsage {
+                camera: *camera,
+                bytes: state.usage(Some(*camera), true) as u64,
+                limit_bytes: self.config.per_camera_bytes as u64,
+                dropped_frames: counters.dropped_frames,
+                evicted_frames: counters.evicted_frames,
+            })
+            .collect();
+
+        CameraMemoryReport {
+            cameras,
+            total_bytes: state.usage(None, true) as u64,
+            total_limit_bytes: self.config.total_bytes as u64,
+        }
+    }
+}
+
+/// Memory reserved for a frame, see [`FrameBudget::reserve`], released when dropped.
+pub struct FrameReservation {
+    id: u64,
+    evicted: Arc<AtomicBool>,
+    state: Arc<Mutex<BudgetState>>,
+}
+
+impl FrameReservation {
+    /// Whether the frame should be aborted, to make room for a higher priority frame.
+    pub fn is_evicted(&self) -> bool {
+        self.evicted.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for FrameReservation {
+    fn drop(&mut self) {
+        self.state
+            .lock()
+            .unwrap()
+            .reservations
+            .remove(&self.id);
+    }
+}
+
+/// Publishes the usage of the budget every second.
+pub async fn camera_memory_monitor(stack: RouterStack, budget: FrameBudget, app_event_rx: Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+    let mut report_ticker = time::interval(Duration::from_secs(1));
+
+    info!("Camera memory monitor started");
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                info!("camera memory monitor shutdown");
+                break
+            }
+            _ = report_ticker.tick() => {
+                let report = budget.report();
+                if let Err(e) = stack
+                    .topics()
+                    .broadcast::<CameraMemoryTopic>(&report, None)
+                {
+                    debug!("Unable to publish camera memory report, error: {:?}", e);
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,9 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
+use chrono::Utc;
 use ergot::interface_manager::profiles::router::Router;
 use ergot::interface_manager::InterfaceSendError;
 use ergot::interface_manager::interface_impls::tokio_udp::TokioUdpInterface;
@@ -9,25 +11,103 @@ use ergot::net_stack::ArcNetStack;
 use ergot::toolkits::tokio_udp::RouterStack;
 use ergot::{Address, NetStackSendError, topic};
 use log::{debug, error, info, trace};
+use machine_ids::CameraId;
 use mutex::raw_impls::cs::CriticalSectionRawMutex;
 use operator_shared::camera::{
-    CameraFrameChunk, CameraFrameChunkKind, CameraFrameImageChunk, CameraFrameMeta, CameraIdentifier,
+    CameraCalibration, CameraFrameChunk, CameraInfo, CameraLayoutHint, CameraMounting, HeadPosition,
 };
-use server_common::camera::CameraDefinition;
+use operator_shared::frame_assembly::frame_chunks;
+use server_common::camera::{
+    CameraDefinition, CameraLayout, CameraMounting as ConfigCameraMounting, MotionThrottleConfig,
+};
+use server_common::position::{MachinePosition, PositionHistory};
 #[cfg(feature = "machine-vision")]
 use server_vision::{CameraFrame, capture_loop};
-use tokio::sync::{Mutex, broadcast};
+use tokio::sync::{Mutex, broadcast, watch};
 use tokio::{select, time};
 use tokio_util::sync::CancellationToken;
 
 use crate::AppState;
+use crate::camera::budget::{FrameBudget, FramePriority};
+use crate::camera::pacing::ChunkPacer;
+use crate::config::ParkingAxes;
+use crate::supervisor::{RestartPolicy, Supervisor};
+
+pub mod budget;
+pub mod pacing;
+
+#[cfg(test)]
+mod tests;
+
+topic!(CameraFrameChunkTopic, CameraFrameChunk<'a>, "topic/camera_stream");
+
+/// A chunk that the interface keeps refusing for longer than this aborts the frame.
+const CHUNK_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Chooses the interval between streamed frames, the interval is increased while the machine is moving, see
+/// [`MotionThrottleConfig`].
+pub struct StreamThrottle {
+    /// of the requested fps
+    interval: Duration,
+    config: Option<MotionThrottleConfig>,
+    /// when the machine was last seen moving
+    moved_at: Option<time::Instant>,
+}
+
+impl StreamThrottle {
+    pub fn new(target_fps: f32, config: Option<MotionThrottleConfig>) -> Self {
+        Self {
+            interval: Duration::from_secs_f32(1.0 / target_fps),
+            config,
+            moved_at: None,
+        }
+    }
 
-topic!(CameraFrameChunkTopic, CameraFrameChunk, "topic/camera_stream");
+    /// `speed` is the speed of the fastest axis, in steps per second.
+    pub fn interval(&mut self, now: time::Instant, speed: f64) -> Duration {
+        let Some(config) = &self.config else {
+            return self.interval;
+        };
+
+        if speed >= config.speed_threshold {
+            self.moved_at = Some(now);
+        }
+        let restore_delay = Duration::from_millis(config.restore_delay_ms);
+        let moving = self
+            .moved_at
+            .is_some_and(|moved_at| now - moved_at < restore_delay);
+
+        match moving {
+            true => self
+                .interval
+                .max(Duration::from_secs_f32(1.0 / config.fps)),
+            false => self.interval,
+        }
+    }
+}
+
+/// The X and Y position of the head, in machine coordinates, from the positions of the axes, in steps.
+///
+/// `None` unless both axes have a position, the operator UI shows the machine coordinates under the pointer from it.
+pub fn head_position(position: &MachinePosition, axes: &ParkingAxes, steps_per_mm: f64) -> Option<HeadPosition> {
+    let x = position.axes.get(&axes.x)?;
+    let y = position.axes.get(&axes.y)?;
+    Some(HeadPosition {
+        x: x / steps_per_mm,
+        y: y / steps_per_mm,
+    })
+}
 
 pub async fn camera_streamer(
     stack: ArcNetStack<CriticalSectionRawMutex, Router<TokioUdpInterface, rand::rngs::StdRng, 64, 64>>,
+    identifier: CameraId,
     mut rx: broadcast::Receiver<Arc<CameraFrame>>,
+    preview_paused: watch::Receiver<bool>,
+    position_history: PositionHistory,
+    budget: FrameBudget,
     definition: CameraDefinition,
+    head_axes: ParkingAxes,
+    steps_per_mm: f64,
     chunk_size: usize,
     address: Address,
     shutdown_flag: CancellationToken,
@@ -38,7 +118,12 @@ pub async fn camera_streamer(
 
     let mut interval = time::interval(Duration::from_secs(1));
     let mut next_frame_at = time::Instant::now();
-    let target_fps_interval = Duration::from_secs_f32(1.0 / target_fps);
+    let mut throttle = StreamThrottle::new(target_fps, definition.stream_config.motion_throttle);
+    let mut throttled = false;
+    // the interval of the previous frame, the chunks of a frame are spread across it
+    let mut stream_interval = throttle.interval;
+    // chunks are charged at the full chunk size, only the last chunk of a frame is smaller
+    let mut pacer = ChunkPacer::new(time::Instant::now(), chunk_size);
 
     loop {
         select! {
@@ -49,6 +134,11 @@ pub async fn camera_streamer(
                 }
             }
             frame = rx.recv() => {
+                if *preview_paused.borrow() {
+                    // a vision routine is using the camera, see `crate::vision`
+                    continue;
+                }
+
                 let now = time::Instant::now();
                 if now < next_frame_at {
                     // skip this frame, the client requested a lower frame rate.
@@ -69,60 +159,56 @@ pub async fn camera_streamer(
                     },
                 };
 
-                let CameraFrame { frame_number, jpeg_bytes, frame_timestamp } = &*camera_frame;
+                let CameraFrame { frame_number, jpeg_bytes, frame_timestamp, machine_position } = &*camera_frame;
+                let head_position = machine_position
+                    .as_ref()
+                    .and_then(|position| head_position(position, &head_axes, steps_per_mm));
 
-                let total_bytes = jpeg_bytes.len() as u32;
-                let total_chunks = (total_bytes + (chunk_size as u32) - 1) / chunk_size as u32;
+                // the frame, the chunks borrow the bytes of the frame
+                let Some(reservation) = budget.reserve(identifier, jpeg_bytes.len(), FramePriority::Preview) else {
+                    debug!("Dropping frame, camera memory budget exceeded. frame_number: {}, destination: {}", frame_number, address);
+                    continue;
+                };
 
-                trace!("Sending frame, now: {:?}, frame_number: {}, total_chunks: {}, len: {}", now, camera_frame.frame_number, total_chunks, total_bytes);
+                let (meta_chunk, image_chunks) = frame_chunks(*frame_number, (*frame_timestamp).into(), head_position, jpeg_bytes, chunk_size);
+                let total_chunks = image_chunks.len();
+                pacer.start_frame(time::Instant::now(), total_chunks * chunk_size, stream_interval);
 
-                let frame_chunk = CameraFrameChunk {
-                    frame_number: *frame_number,
-                    kind: CameraFrameChunkKind::Meta(CameraFrameMeta {
-                        total_chunks,
-                        total_bytes,
-                        frame_timestamp: (*frame_timestamp).into(),
-                    })
-                };
-                if stack.topics().unicast_borrowed::<CameraFrameChunkTopic>(address, &frame_chunk).is_err() {
+                trace!("Sending frame, now: {:?}, frame_number: {}, total_chunks: {}, len: {}", now, camera_frame.frame_number, total_chunks, jpeg_bytes.len());
+
+                if stack.topics().unicast_borrowed::<CameraFrameChunkTopic>(address, &meta_chunk).is_err() {
                     trace!("Unable to send first frame chunk. frame_number: {}", frame_number);
                     // no point even trying to send the chunks if the first chunk failed, drop the frame
                     continue
                 }
 
                 let mut ok = true;
-                for (chunk_index, chunk) in jpeg_bytes.chunks(chunk_size).enumerate() {
-                    let frame_chunk = CameraFrameChunk {
-                        frame_number: *frame_number,
-                        kind: CameraFrameChunkKind::ImageChunk(CameraFrameImageChunk {
-                            chunk_index: chunk_index as u32,
-                            bytes: chunk.to_vec(),
-                        })
-                    };
+                for (chunk_index, frame_chunk) in image_chunks.iter().enumerate() {
+                    if reservation.is_evicted() {
+                        debug!("Aborting frame, evicted from the camera memory budget. frame_number: {}, chunk: {}/{}", frame_number, chunk_index + 1, total_chunks);
+                        ok = false;
+                        break
+                    }
 
                     let chunk_start_at = time::Instant::now();
-
-                    // IMPORTANT: back-off delay needs to be as short as possible
-                    //            60fps =  16ms total frame time.
-                    //            30fps =  33ms total frame time.
-                    //            25fps =  40ms total frame time.
-                    //            15fps =  66ms total frame time.
-                    //            10fps = 100ms total frame time.
-                    const INITIAL_BACKOFF: Duration = Duration::from_micros(100);
                     let mut retries = 0;
 
                     let result = loop {
-                        match stack.topics().unicast_borrowed::<CameraFrameChunkTopic>(address, &frame_chunk) {
+                        let delay = pacer.delay(time::Instant::now(), chunk_size);
+                        if !delay.is_zero() {
+                            time::sleep(delay).await;
+                        }
+
+                        match stack.topics().unicast_borrowed::<CameraFrameChunkTopic>(address, frame_chunk) {
                             r @ Ok(_) => {
-                                // reset
+                                pacer.sent(time::Instant::now(), chunk_size);
                                 break r
                             }
                             e1 @ Err(NetStackSendError::InterfaceSend(InterfaceSendError::InterfaceFull)) => {
-                                if chunk_start_at.elapsed() > Duration::from_millis(100) {
+                                // the pacer waits for the queue to drain before the chunk is sent again
+                                pacer.interface_full(time::Instant::now());
+                                if chunk_start_at.elapsed() > CHUNK_TIMEOUT {
                                     break e1
-                                } else {
-                                    let backoff = INITIAL_BACKOFF * (1 << retries.min(4));
-                                    time::sleep_until(chunk_start_at + backoff).await;
                                 }
                             }
                             e2@ Err(_) => {
@@ -144,16 +230,25 @@ pub async fn camera_streamer(
                 }
 
                 if ok {
-                    trace!("Frame sent. frame_number: {}", frame_number);
+                    pacer.frame_sent();
+                    trace!("Frame sent. frame_number: {}, drain_rate: {:.0}B/s", frame_number, pacer.drain_rate());
 
                     // if sending the frame failed, we need to send the next-received frame immediately
                     // we only update the `next_frame_at` if the frame was successfully sent.
 
                     let now = time::Instant::now();
-                    next_frame_at += target_fps_interval;
+                    let frame_interval = throttle.interval(now, position_history.max_speed(Utc::now()));
+                    let frame_throttled = frame_interval > throttle.interval;
+                    if frame_throttled != throttled {
+                        debug!("Camera stream throttle changed. throttled: {}, destination: {}", frame_throttled, address);
+                        throttled = frame_throttled;
+                    }
+
+                    stream_interval = frame_interval;
+                    next_frame_at += frame_interval;
                     if now > next_frame_at {
                         // catch up if we fall behind
-                        next_frame_at = now + target_fps_interval;
+                        next_frame_at = now + frame_interval;
                     }
 
                 }
@@ -167,25 +262,345 @@ pub async fn camera_streamer(
 
 pub fn camera_definition_for_identifier<'a>(
     definitions: &'a Vec<CameraDefinition>,
-    identifier: &CameraIdentifier,
+    identifier: &CameraId,
 ) -> Option<&'a CameraDefinition> {
     // for now, just using the identifier as an index
-    let index: u8 = **identifier;
+    let index = identifier.index();
     definitions.get(index as usize)
 }
 
+pub fn camera_infos(definitions: &[CameraDefinition]) -> Vec<CameraInfo> {
+    definitions
+        .iter()
+        .enumerate()
+        .map(|(index, definition)| CameraInfo {
+            // for now, just using the index as the identifier, see `camera_definition_for_identifier`
+            identifier: CameraId::new(index as u8),
+            name: definition.name.clone(),
+            width: definition.width,
+            height: definition.height,
+            fps: definition.fps,
+            mounting: match definition.mounting {
+                ConfigCameraMounting::Up => CameraMounting::Up,
+                ConfigCameraMounting::Down => CameraMounting::Down,
+                ConfigCameraMounting::Other => CameraMounting::Other,
+            },
+            layout: match definition.layout {
+                CameraLayout::Primary => CameraLayoutHint::Primary,
+                CameraLayout::Secondary => CameraLayoutHint::Secondary,
+                CameraLayout::Hidden => CameraLayoutHint::Hidden,
+            },
+            calibration: definition
+                .calibration
+                .map(|calibration| CameraCalibration {
+                    mm_per_pixel_x: calibration.mm_per_pixel_x,
+                    mm_per_pixel_y: calibration.mm_per_pixel_y,
+                }),
+        })
+        .collect()
+}
+
 // must be less than the MTU of the network interface + ip + udp + ergot + chunking overhead
 const CAMERA_CHUNK_SIZE: usize = 1024;
 
-pub struct CameraHandle {
+/// The most the exposure is scaled by, in either direction, over all the adjustments of a capture.
+const EXPOSURE_SCALE_LIMIT: f64 = 16.0;
+
+/// How long a capture keeps running after the last subscriber has gone, avoids restarting the camera when a client
+/// reconnects or a vision routine is followed by another.
+const CAPTURE_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+struct CameraCapture {
+    tx: broadcast::Sender<Arc<CameraFrame>>,
     capture_handle: tokio::task::JoinHandle<()>,
+    shutdown_flag: CancellationToken,
+    preview_paused: watch::Sender<bool>,
+    /// see [`CameraHandle::adjust_exposure`]
+    exposure_scale: watch::Sender<f64>,
+    subscribers: usize,
+    /// incremented each time the subscriber count drops to zero, so that a stale grace period timer does not stop a
+    /// capture that was re-used in the meantime
+    release_generation: u64,
+}
+
+/// Owns the capture loops, a capture loop is started when the first [`CameraHandle`] for a camera is acquired and
+/// stopped after a grace period when the last handle is dropped.
+#[derive(Clone)]
+pub struct CameraCaptures {
+    // std mutex, since handles are released in `Drop`
+    captures: Arc<std::sync::Mutex<HashMap<CameraId, CameraCapture>>>,
+    position_history: PositionHistory,
+    budget: FrameBudget,
+    /// restarts a capture loop that fails, e.g. when the camera is briefly disconnected
+    supervisor: Supervisor,
+}
+
+impl CameraCaptures {
+    /// Captured frames are stamped with the machine position from `position_history`.
+    pub fn new(position_history: PositionHistory, budget: FrameBudget, supervisor: Supervisor) -> Self {
+        Self {
+            captures: Default::default(),
+            position_history,
+            budget,
+            supervisor,
+        }
+    }
+
+    /// Shared by the preview streams and the vision routines of all cameras.
+    pub fn frame_budget(&self) -> FrameBudget {
+        self.budget.clone()
+    }
+
+    /// Used to throttle the preview streams while the machine is moving.
+    pub fn position_history(&self) -> PositionHistory {
+        self.position_history.clone()
+    }
+
+    /// Acquire a handle to the capture for the camera, starting the capture if required.
+    pub fn acquire(&self, identifier: CameraId, camera_definition: &CameraDefinition) -> CameraHandle {
+        let mut captures = self.captures.lock().unwrap();
+
+        // a capture that is no longer supervised, because it failed, is restarted
+        if captures
+            .get(&identifier)
+            .is_some_and(|capture| capture.capture_handle.is_finished())
+        {
+            captures.remove(&identifier);
+        }
+
+        let capture = captures
+            .entry(identifier)
+            .or_insert_with(|| self.start_capture(identifier, camera_definition));
+        capture.subscribers += 1;
+        debug!(
+            "Camera capture acquired. identifier: {}, subscribers: {}",
+            identifier, capture.subscribers
+        );
+
+        CameraHandle {
+            identifier,
+            tx: capture.tx.clone(),
+            preview_paused: capture.preview_paused.clone(),
+            exposure_scale: capture.exposure_scale.clone(),
+            captures: self.clone(),
+        }
+    }
+
+    fn start_capture(&self, identifier: CameraId, camera_definition: &CameraDefinition) -> CameraCapture {
+        info!("Starting camera capture. identifier: {}", identifier);
+
+        // TODO document the '* 2' magic number, try reducing it too.
+        let broadcast_cap = (camera_definition.fps * 2_f32).round() as usize;
+
+        // Create broadcast channel for frames (Arc<Bytes> so we cheaply clone for each client)
+        let (tx, _rx) = broadcast::channel::<Arc<CameraFrame>>(broadcast_cap);
+
+        let shutdown_flag = CancellationToken::new();
+        let exposure_scale = watch::Sender::new(1.0);
+        let capture_handle = self
+            .supervisor
+            .spawn(&format!("camera-{}/capture", identifier), RestartPolicy::OnFailure, {
+                let camera_definition = camera_definition.clone();
+                let shutdown_flag = shutdown_flag.clone();
+                let tx = tx.clone();
+                let position_history = self.position_history.clone();
+                let exposure_scale = exposure_scale.clone();
+                move || {
+                    let capture = capture_loop(
+                        tx.clone(),
+                        camera_definition.clone(),
+                        position_history.clone(),
+                        exposure_scale.subscribe(),
+                        shutdown_flag.clone(),
+                    );
+                    let shutdown_flag = shutdown_flag.clone();
+                    async move {
+                        // stopped while waiting to be restarted
+                        if shutdown_flag.is_cancelled() {
+                            return Ok(());
+                        }
+                        capture.await
+                    }
+                }
+            })
+            .unwrap();
+
+        CameraCapture {
+            tx,
+            capture_handle,
+            shutdown_flag,
+            preview_paused: watch::Sender::new(false),
+            exposure_scale,
+            subscribers: 0,
+            release_generation: 0,
+        }
+    }
+
+    fn release(&self, identifier: CameraId, tx: &broadcast::Sender<Arc<CameraFrame>>) {
+        let mut captures = self.captures.lock().unwrap();
+        // the capture may have been stopped, or replaced after a failure, since the handle was acquired
+        let Some(capture) = captures
+            .get_mut(&identifier)
+            .filter(|capture| capture.tx.same_channel(tx))
+        else {
+            return;
+        };
+
+        capture.subscribers -= 1;
+        debug!(
+            "Camera capture released. identifier: {}, subscribers: {}",
+            identifier, capture.subscribers
+        );
+        if capture.subscribers > 0 {
+            return;
+        }
+
+        capture.release_generation += 1;
+        let release_generation = capture.release_generation;
+
+        let captures = self.clone();
+        tokio::spawn(async move {
+            time::sleep(CAPTURE_GRACE_PERIOD).await;
+            captures
+                .stop_if_unused(identifier, release_generation)
+                .await;
+        });
+    }
+
+    async fn stop_if_unused(&self, identifier: CameraId, release_generation: u64) {
+        let capture = {
+            let mut captures = self.captures.lock().unwrap();
+            match captures.get(&identifier) {
+                Some(capture) if capture.subscribers == 0 && capture.release_generation == release_generation => {
+                    captures.remove(&identifier)
+                }
+                _ => None,
+            }
+        };
+
+        if let Some(capture) = capture {
+            info!("Stopping unused camera capture. identifier: {}", identifier);
+            capture.shutdown_flag.cancel();
+            let _ = capture.capture_handle.await;
+            info!("Camera capture stopped. identifier: {}", identifier);
+        }
+    }
+
+    /// Stop all captures immediately, regardless of subscribers, e.g. on shutdown.
+    pub async fn stop_all(&self) {
+        let captures = {
+            let mut captures = self.captures.lock().unwrap();
+            captures.drain().collect::<Vec<_>>()
+        };
+
+        for (identifier, capture) in captures {
+            info!("Stopping camera capture. identifier: {}", identifier);
+            capture.shutdown_flag.cancel();
+            let _ = capture.capture_handle.await;
+        }
+    }
+}
+
+/// A reference counted subscription to a camera capture, see [`CameraCaptures`].
+pub struct CameraHandle {
+    identifier: CameraId,
+    tx: broadcast::Sender<Arc<CameraFrame>>,
+    preview_paused: watch::Sender<bool>,
+    exposure_scale: watch::Sender<f64>,
+    captures: CameraCaptures,
+}
+
+impl CameraHandle {
+    pub fn identifier(&self) -> CameraId {
+        self.identifier
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<CameraFrame>> {
+        self.tx.subscribe()
+    }
+
+    pub fn preview_paused(&self) -> watch::Receiver<bool> {
+        self.preview_paused.subscribe()
+    }
+
+    /// Pause the preview streams of the camera, they are resumed when the returned guard is dropped.
+    pub fn pause_preview(&self) -> PreviewPause {
+        debug!("Pausing camera preview. identifier: {}", self.identifier);
+        self.preview_paused.send_replace(true);
+        PreviewPause {
+            identifier: self.identifier,
+            preview_paused: self.preview_paused.clone(),
+        }
+    }
+
+    /// Scale the exposure of the camera, e.g. 2.0 doubles it, the capture loop applies it before the next frame.
+    ///
+    /// Adjustments accumulate, and remain until the capture is stopped, the next measurement starts from the
+    /// exposure of the previous one, which is usually the closest.
+    pub fn adjust_exposure(&self, factor: f64) {
+        self.exposure_scale
+            .send_modify(|scale| {
+                *scale = (*scale * factor).clamp(1.0 / EXPOSURE_SCALE_LIMIT, EXPOSURE_SCALE_LIMIT);
+            });
+        debug!(
+            "Camera exposure adjusted. identifier: {}, factor: {:.2}, scale: {:.2}",
+            self.identifier,
+            factor,
+            *self.exposure_scale.borrow()
+        );
+    }
+}
+
+impl Clone for CameraHandle {
+    fn clone(&self) -> Self {
+        {
+            let mut captures = self.captures.captures.lock().unwrap();
+            if let Some(capture) = captures
+                .get_mut(&self.identifier)
+                .filter(|capture| capture.tx.same_channel(&self.tx))
+            {
+                capture.subscribers += 1;
+            }
+        }
+        Self {
+            identifier: self.identifier,
+            tx: self.tx.clone(),
+            preview_paused: self.preview_paused.clone(),
+            exposure_scale: self.exposure_scale.clone(),
+            captures: self.captures.clone(),
+        }
+    }
+}
+
+impl Drop for CameraHandle {
+    fn drop(&mut self) {
+        self.captures
+            .release(self.identifier, &self.tx);
+    }
+}
+
+pub struct PreviewPause {
+    identifier: CameraId,
+    preview_paused: watch::Sender<bool>,
+}
+
+impl Drop for PreviewPause {
+    fn drop(&mut self) {
+        debug!("Resuming camera preview. identifier: {}", self.identifier);
+        self.preview_paused.send_replace(false);
+    }
+}
+
+/// A stream of camera frames to an operator UI.
+pub struct CameraClient {
     streamer_handle: tokio::task::JoinHandle<()>,
     address: Address,
     shutdown_flag: CancellationToken,
+    _camera: CameraHandle,
 }
 
 pub async fn camera_manager(
-    identifier: CameraIdentifier,
+    identifier: CameraId,
     camera_definition: CameraDefinition,
     address: Address,
     app_state: Arc<Mutex<AppState>>,
@@ -195,25 +610,24 @@ pub async fn camera_manager(
 ) {
     let constrained_fps = target_fps.min(camera_definition.fps);
 
-    // TODO document the '* 2' magic number, try reducing it too.
-    let broadcast_cap = (camera_definition.fps * 2_f32).round() as usize;
-
-    // Create broadcast channel for frames (Arc<Bytes> so we cheaply clone for each client)
-    let (tx, rx) = broadcast::channel::<Arc<CameraFrame>>(broadcast_cap);
+    let (camera, position_history, budget, parking) = {
+        let app_state = app_state.lock().await;
+        let camera = app_state
+            .camera_captures
+            .acquire(identifier, &camera_definition);
+        (
+            camera,
+            app_state
+                .camera_captures
+                .position_history(),
+            app_state.camera_captures.frame_budget(),
+            // FUTURE the axes and steps per mm should be part of the axis configuration
+            app_state.config.parking.clone(),
+        )
+    };
+    let rx = camera.subscribe();
+    let preview_paused = camera.preview_paused();
 
-    let capture_handle = tokio::task::Builder::new()
-        .name(&format!("camera-{}/capture", identifier))
-        .spawn({
-            let camera_definition = camera_definition.clone();
-            let shutdown_flag = shutdown_flag.clone();
-            async move {
-                if let Err(e) = capture_loop(tx, camera_definition, shutdown_flag.clone()).await {
-                    error!("capture loop error: {}", e);
-                    shutdown_flag.cancel();
-                }
-            }
-        })
-        .unwrap();
     let streamer_handle = tokio::task::Builder::new()
         .name(&format!("camera-{}/streamer", identifier))
         .spawn({
@@ -223,8 +637,14 @@ pub async fn camera_manager(
             async move {
                 if let Err(e) = camera_streamer(
                     stack,
+                    identifier,
                     rx,
+                    preview_paused,
+                    position_history,
+                    budget,
                     camera_definition,
+                    parking.axes,
+                    parking.steps_per_mm,
                     CAMERA_CHUNK_SIZE,
                     address,
                     shutdown_flag.clone(),
@@ -242,11 +662,11 @@ pub async fn camera_manager(
     {
         let app_state = app_state.lock().await;
         let mut camera_clients = app_state.camera_clients.lock().await;
-        camera_clients.insert(identifier.clone(), CameraHandle {
-            capture_handle,
+        camera_clients.insert(identifier.clone(), CameraClient {
             streamer_handle,
             address,
             shutdown_flag: shutdown_flag.clone(),
+            _camera: camera,
         });
     }
 
@@ -260,9 +680,8 @@ pub async fn camera_manager(
     let mut camera_clients = app_state.camera_clients.lock().await;
 
     if let Some(client) = camera_clients.remove(&identifier) {
-        // wait for the capture first, then the streamer
-        let _ = client.capture_handle.await;
         let _ = client.streamer_handle.await;
+        // dropping the client releases the capture
     }
     info!("Camera manager stopped. identifier: {}", identifier);
 }
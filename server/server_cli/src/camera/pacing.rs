@@ -0,0 +1,148 @@
+//! Pacing the chunks of a camera stream, see [`ChunkPacer`].
+//!
+//! Sending all the chunks of a frame at once fills the send queue of the interface, and the chunks that don't fit are
+//! refused with `InterfaceFull`.  Instead, the chunks are paced by a token bucket, so that they are spread across the
+//! frame interval, and never sent faster than the interface drains its queue.  The drain rate is measured each time
+//! the queue fills up, and is raised a little after each frame that was sent without filling the queue, so that the
+//! pacer follows the link when it gets faster again.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// 100Mbit/s, the drain rate before it has been measured.
+const INITIAL_DRAIN_RATE: f64 = 12_500_000.0;
+/// 2Mbit/s, enough for a few frames per second of a small preview.
+const MIN_DRAIN_RATE: f64 = 250_000.0;
+/// 1Gbit/s
+const MAX_DRAIN_RATE: f64 = 125_000_000.0;
+
+/// The chunks of a frame are sent within this fraction of the frame interval, leaving time for the queue to drain
+/// before the next frame.
+const FRAME_SPREAD: f64 = 0.8;
+
+/// The chunks that may be sent back-to-back, e.g. after the stream was idle.
+const BURST_CHUNKS: f64 = 4.0;
+/// A sleep lasts at least a millisecond, at high rates the bucket holds the chunks of a few sleeps, otherwise the rate
+/// would be limited by the timer.
+const BURST_TIME: Duration = Duration::from_millis(2);
+
+/// The drain rate is measured slightly lower than the rate at which the chunks were accepted, which includes the chunks
+/// still in the queue.
+const DRAIN_RATE_HEADROOM: f64 = 0.9;
+/// When the queue fills up too soon after the previous measurement to measure the rate again.
+const DRAIN_RATE_DECREASE: f64 = 0.75;
+/// After each frame that was sent without filling the queue.
+const DRAIN_RATE_INCREASE: f64 = 1.05;
+
+/// Measurements over a shorter time are too noisy, e.g. a single chunk.
+const MIN_MEASUREMENT_TIME: Duration = Duration::from_millis(2);
+
+/// A token bucket for the chunks sent to a single destination, the rate is in bytes per second.
+pub struct ChunkPacer {
+    /// the rate at which the interface drains its queue, as last measured
+    drain_rate: f64,
+    /// the rate of the current frame
+    rate: f64,
+    tokens: f64,
+    /// the least the bucket holds, see `BURST_TIME`
+    burst: f64,
+    refilled_at: Instant,
+    /// the bytes accepted by the interface since `accepted_since`, the drain rate is measured from them
+    accepted: usize,
+    accepted_since: Instant,
+    /// the queue filled up while the current frame was sent
+    filled: bool,
+}
+
+impl ChunkPacer {
+    pub fn new(now: Instant, chunk_size: usize) -> Self {
+        let burst = BURST_CHUNKS * chunk_size as f64;
+        Self {
+            drain_rate: INITIAL_DRAIN_RATE,
+            rate: INITIAL_DRAIN_RATE,
+            tokens: burst,
+            burst,
+            refilled_at: now,
+            accepted: 0,
+            accepted_since: now,
+            filled: false,
+        }
+    }
+
+    pub fn drain_rate(&self) -> f64 {
+        self.drain_rate
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        let burst = self
+            .burst
+            .max(self.rate * BURST_TIME.as_secs_f64());
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(burst);
+        self.refilled_at = now;
+    }
+
+    /// Called before the chunks of a frame of `frame_bytes` are sent, the chunks are spread across the frame interval,
+    /// unless the interface drains slower than that.
+    pub fn start_frame(&mut self, now: Instant, frame_bytes: usize, frame_interval: Duration) {
+        self.refill(now);
+        let spread = frame_interval.as_secs_f64() * FRAME_SPREAD;
+        let spread_rate = match spread > 0.0 {
+            true => frame_bytes as f64 / spread,
+            false => self.drain_rate,
+        };
+        self.rate = spread_rate.clamp(MIN_DRAIN_RATE, self.drain_rate);
+        // the stream may have been idle since the previous frame
+        self.accepted = 0;
+        self.accepted_since = now;
+        self.filled = false;
+    }
+
+    /// How long to wait before a chunk of `length` bytes is sent.
+    pub fn delay(&mut self, now: Instant, length: usize) -> Duration {
+        self.refill(now);
+        let missing = length as f64 - self.tokens;
+        match missing > 0.0 {
+            true => Duration::from_secs_f64(missing / self.rate),
+            false => Duration::ZERO,
+        }
+    }
+
+    /// The interface accepted a chunk of `length` bytes.
+    pub fn sent(&mut self, now: Instant, length: usize) {
+        self.refill(now);
+        self.tokens -= length as f64;
+        self.accepted += length;
+    }
+
+    /// The interface refused a chunk, its queue is full, the drain rate is measured from the bytes it accepted since
+    /// the start of the frame, or since the queue last filled up.
+    pub fn interface_full(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.accepted_since);
+        self.drain_rate = match elapsed >= MIN_MEASUREMENT_TIME && self.accepted > 0 {
+            true => self.accepted as f64 / elapsed.as_secs_f64() * DRAIN_RATE_HEADROOM,
+            false => self.drain_rate * DRAIN_RATE_DECREASE,
+        }
+        .clamp(MIN_DRAIN_RATE, MAX_DRAIN_RATE);
+        self.rate = self.rate.min(self.drain_rate);
+        // the queue needs to drain at least one chunk before the next is sent
+        self.tokens = self.tokens.min(0.0);
+        self.accepted = 0;
+        self.accepted_since = now;
+        self.filled = true;
+    }
+
+    /// Called once all the chunks of a frame were sent, unless the queue filled up the drain rate is raised, to follow
+    /// a link that got faster.
+    pub fn frame_sent(&mut self) {
+        if self.filled {
+            return;
+        }
+        self.drain_rate = (self.drain_rate * DRAIN_RATE_INCREASE).min(MAX_DRAIN_RATE);
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use machine_ids::CameraId;
+use operator_shared::camera::HeadPosition;
+use server_common::camera::MotionThrottleConfig;
+use server_common::position::MachinePosition;
+use tokio::time::Instant;
+
+use super::budget::{FrameBudget, FramePriority};
+use super::pacing::ChunkPacer;
+use super::{StreamThrottle, head_position};
+use crate::config::{CameraMemoryConfig, ParkingAxes};
+
+fn throttle() -> StreamThrottle {
+    StreamThrottle::new(
+        20.0,
+        Some(MotionThrottleConfig {
+            fps: 5.0,
+            speed_threshold: 100.0,
+            restore_delay_ms: 250,
+        }),
+    )
+}
+
+#[test]
+pub fn requested_fps_while_stationary() {
+    // given
+    let mut throttle = throttle();
+
+    // expect
+    assert_eq!(throttle.interval(Instant::now(), 50.0), Duration::from_millis(50));
+}
+
+#[test]
+pub fn reduced_fps_while_moving_and_restored_after_delay() {
+    // given
+    let mut throttle = throttle();
+    let start = Instant::now();
+
+    // when
+    let moving = throttle.interval(start, 1000.0);
+    let stopping = throttle.interval(start + Duration::from_millis(100), 0.0);
+    let stationary = throttle.interval(start + Duration::from_millis(300), 0.0);
+
+    // then
+    assert_eq!(moving, Duration::from_millis(200));
+    assert_eq!(stopping, Duration::from_millis(200));
+    assert_eq!(stationary, Duration::from_millis(50));
+}
+
+#[test]
+pub fn requested_fps_used_when_lower() {
+    // given
+    let mut throttle = StreamThrottle::new(
+        2.0,
+        Some(MotionThrottleConfig {
+            fps: 5.0,
+            speed_threshold: 100.0,
+            restore_delay_ms: 250,
+        }),
+    );
+
+    // expect
+    assert_eq!(throttle.interval(Instant::now(), 1000.0), Duration::from_millis(500));
+}
+
+#[test]
+pub fn without_config_always_requested_fps() {
+    // given
+    let mut throttle = StreamThrottle::new(20.0, None);
+
+    // expect
+    assert_eq!(throttle.interval(Instant::now(), 1000.0), Duration::from_millis(50));
+}
+
+/// Room for 3 frames per camera, and 4 frames in total, of 100 bytes.
+#[test]
+pub fn head_position_in_millimeters() {
+    // given
+    let position = MachinePosition {
+        axes: BTreeMap::from([(0, 800.0), (1, -400.0), (2, 80.0)]),
+    };
+
+    // when
+    let head_position = head_position(&position, &ParkingAxes::default(), 80.0);
+
+    // then
+    assert_eq!(
+        head_position,
+        Some(HeadPosition {
+            x: 10.0,
+            y: -5.0,
+        })
+    );
+}
+
+#[test]
+pub fn no_head_position_without_both_axes() {
+    // given
+    let position = MachinePosition {
+        axes: BTreeMap::from([(0, 800.0)]),
+    };
+
+    // expect
+    assert_eq!(head_position(&position, &ParkingAxes::default(), 80.0), None);
+}
+
+fn budget() -> FrameBudget {
+    FrameBudget::new(CameraMemoryConfig {
+        per_camera_bytes: 300,
+        total_bytes: 400,
+    })
+}
+
+#[test]
+pub fn frames_are_released_when_the_reservation_is_dropped() {
+    // given
+    let budget = budget();
+    let camera = CameraId::new(0);
+    let reservation = budget.reserve(camera, 100, FramePriority::Preview);
+
+    // when
+    let reserved = budget.report();
+    drop(reservation);
+    let released = budget.report();
+
+    // then
+    assert_eq!(reserved.total_bytes, 100);
+    assert_eq!(reserved.cameras[0].bytes, 100);
+    assert_eq!(released.total_bytes, 0);
+    assert_eq!(released.cameras[0].bytes, 0);
+}
+
+#[test]
+pub fn preview_frames_over_the_camera_limit_are_dropped() {
+    // given
+    let budget = budget();
+    let camera = CameraId::new(0);
+    let _reservations = (0..3)
+        .map(|_| budget.reserve(camera, 100, FramePriority::Preview))
+        .collect::<Vec<_>>();
+
+    // when
+    let reservation = budget.reserve(camera, 100, FramePriority::Preview);
+
+    // then
+    assert!(reservation.is_none());
+    let report = budget.report();
+    assert_eq!(report.total_bytes, 300);
+    assert_eq!(report.cameras[0].dropped_frames, 1);
+}
+
+#[test]
+pub fn preview_frames_over_the_total_limit_are_dropped() {
+    // given
+    let budget = budget();
+    let camera_0 = CameraId::new(0);
+    let camera_1 = CameraId::new(1);
+    let _reservations = [
+        budget.reserve(camera_0, 200, FramePriority::Preview),
+        budget.reserve(camera_1, 200, FramePriority::Preview),
+    ];
+
+    // when
+    let reservation = budget.reserve(camera_1, 1, FramePriority::Preview);
+
+    // then
+    assert!(reservation.is_none());
+    let report = budget.report();
+    assert_eq!(report.cameras[0].dropped_frames, 0);
+    assert_eq!(report.cameras[1].dropped_frames, 1);
+}
+
+#[test]
+pub fn vision_frames_evict_the_oldest_preview_frames() {
+    // given
+    let budget = budget();
+    let camera = CameraId::new(0);
+    let oldest = budget
+        .reserve(camera, 100, FramePriority::Preview)
+        .unwrap();
+    let newer = budget
+        .reserve(camera, 100, FramePriority::Preview)
+        .unwrap();
+    let newest = budget
+        .reserve(camera, 100, FramePriority::Preview)
+        .unwrap();
+
+    // when
+    let reservation = budget.reserve(camera, 150, FramePriority::Vision);
+
+    // then
+    assert!(reservation.is_some());
+    assert!(oldest.is_evicted());
+    assert!(newer.is_evicted());
+    assert!(!newest.is_evicted());
+    let report = budget.report();
+    // the evicted frames use memory until they are aborted
+    assert_eq!(report.total_bytes, 450);
+    assert_eq!(report.cameras[0].evicted_frames, 2);
+}
+
+#[test]
+pub fn vision_frames_only_evict_other_cameras_for_the_total_limit() {
+    // given
+    let budget = budget();
+    let camera_0 = CameraId::new(0);
+    let camera_1 = CameraId::new(1);
+    let other_camera = budget
+        .reserve(camera_1, 100, FramePriority::Preview)
+        .unwrap();
+    let same_camera = budget
+        .reserve(camera_0, 300, FramePriority::Preview)
+        .unwrap();
+
+    // when
+    let reservation = budget.reserve(camera_0, 100, FramePriority::Vision);
+
+    // then
+    assert!(reservation.is_some());
+    assert!(!other_camera.is_evicted());
+    assert!(same_camera.is_evicted());
+}
+
+#[test]
+pub fn vision_frames_are_never_dropped() {
+    // given
+    let budget = budget();
+    let camera = CameraId::new(0);
+    let _vision = budget.reserve(camera, 300, FramePriority::Vision);
+
+    // when
+    let reservation = budget.reserve(camera, 300, FramePriority::Vision);
+
+    // then
+    assert!(reservation.is_some());
+    assert_eq!(budget.report().total_bytes, 600);
+}
+
+#[test]
+pub fn chunks_are_spread_across_the_frame_interval() {
+    // given
+    let now = Instant::now();
+    let mut pacer = ChunkPacer::new(now, 1000);
+
+    // when
+    pacer.start_frame(now, 100_000, Duration::from_millis(100));
+
+    // then
+    // sent within 80% of the interval
+    assert!((pacer.rate() - 1_250_000.0).abs() < 1.0);
+    // the burst is sent back-to-back
+    for _ in 0..4 {
+        assert_eq!(pacer.delay(now, 1000), Duration::ZERO);
+        pacer.sent(now, 1000);
+    }
+    let delay = pacer.delay(now, 1000);
+    assert!(delay > Duration::from_micros(799) && delay < Duration::from_micros(801));
+    assert_eq!(pacer.delay(now + Duration::from_micros(801), 1000), Duration::ZERO);
+}
+
+#[test]
+pub fn drain_rate_is_measured_when_the_queue_fills_up() {
+    // given
+    let now = Instant::now();
+    let mut pacer = ChunkPacer::new(now, 1000);
+    // a frame interval too short to spread the chunks
+    pacer.start_frame(now, 1_000_000, Duration::from_millis(1));
+    for chunk in 0..10 {
+        pacer.sent(now + Duration::from_millis(chunk), 1000);
+    }
+
+    // when
+    pacer.interface_full(now + Duration::from_millis(10));
+
+    // then
+    assert!((pacer.drain_rate() - 900_000.0).abs() < 1.0);
+    assert_eq!(pacer.rate(), pacer.drain_rate());
+    // the queue drains a chunk before the next is sent
+    assert!(pacer.delay(now + Duration::from_millis(10), 1000) > Duration::ZERO);
+}
+
+#[test]
+pub fn drain_rate_is_reduced_when_the_queue_fills_up_too_soon_to_measure() {
+    // given
+    let now = Instant::now();
+    let mut pacer = ChunkPacer::new(now, 1000);
+    pacer.start_frame(now, 1_000_000, Duration::from_millis(1));
+    let initial = pacer.drain_rate();
+
+    // when
+    pacer.interface_full(now);
+
+    // then
+    assert_eq!(pacer.drain_rate(), initial * 0.75);
+}
+
+#[test]
+pub fn drain_rate_is_raised_after_frames_that_did_not_fill_the_queue() {
+    // given
+    let now = Instant::now();
+    let mut pacer = ChunkPacer::new(now, 1000);
+    pacer.start_frame(now, 10_000, Duration::from_millis(100));
+    pacer.interface_full(now);
+    let filled = pacer.drain_rate();
+
+    // when
+    pacer.frame_sent();
+
+    // then
+    assert_eq!(pacer.drain_rate(), filled);
+
+    // when
+    pacer.start_frame(now, 10_000, Duration::from_millis(100));
+    pacer.frame_sent();
+
+    // then
+    assert_eq!(pacer.drain_rate(), filled * 1.05);
+}
@@ -0,0 +1,487 @@
+//! Saved camera frames, e.g. snapshots requested by the operator, or the inputs and outputs of a vision routine that
+//! failed, so that they can be reviewed later.
+//!
+//! Captures are stored under `captures/{job}/{placement}/{stage}.jpg` and removed by age and count, periodically, see
+//! [`capture_retention`] and [`CapturesConfig`].  Vision annotations recorded with a capture are stored alongside it,
+//! in `{stage}.annotations.ron`, and a thumbnail of each capture is stored under
+//! `thumbnails/{job}/{placement}/{stage}.jpg`, so that the operator can browse the captures without fetching each full
+//! capture.
+//!
+//! The vision routines save their input frame when they fail, see [`FailureCaptures`].
+
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use log::{debug, error, info, warn};
+use operator_shared::captures::{
+    AnnotationShape, CaptureAnnotation, CaptureChunk, CaptureEntry, CaptureError, CaptureKey, CaptureListPage,
+    MAX_CAPTURE_ANNOTATIONS,
+};
+use server_vision::CameraFrame;
+use server_vision::thumbnail::jpeg_thumbnail;
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::time::{self, Duration};
+
+use crate::AppEvent;
+use crate::config::CapturesConfig;
+use crate::storage::{Storage, StorageError, StorageImpl};
+
+#[cfg(test)]
+mod tests;
+
+const CAPTURES_PREFIX: &str = "captures/";
+// a separate prefix, so that the thumbnails are not listed as captures
+const THUMBNAILS_PREFIX: &str = "thumbnails/";
+const CAPTURE_EXTENSION: &str = "jpg";
+const ANNOTATIONS_EXTENSION: &str = "annotations.ron";
+
+/// Small enough that a page of entries fits in a single operator response.
+pub const CAPTURE_LIST_PAGE_SIZE: usize = 8;
+
+// must be less than the MTU of the network interface + ip + udp + ergot + overhead, same as the camera stream chunks
+pub const CAPTURE_CHUNK_SIZE: usize = 1024;
+
+/// Thumbnails fit within this size, in pixels, the same size as the thumbnails of the operator UI.
+const THUMBNAIL_SIZE: (u32, u32) = (160, 120);
+const THUMBNAIL_JPEG_QUALITY: u8 = 70;
+
+/// Where the label of a [`failure_annotation`] is drawn, near the top left corner of the frame, in image pixels.
+const FAILURE_LABEL_POSITION: (f32, f32) = (16.0, 32.0);
+
+pub struct CaptureStore {
+    storage: Arc<StorageImpl>,
+    max_count: usize,
+    max_age: TimeDelta,
+    /// the path and bytes of the capture or thumbnail last read by [`CaptureStore::read_chunk`], so that it is only
+    /// fetched from the storage once while its chunks are read
+    fetched: Mutex<Option<(String, Arc<Vec<u8>>)>>,
+}
+
+impl CaptureStore {
+    pub fn new(storage: Arc<StorageImpl>, config: &CapturesConfig) -> Self {
+        Self {
+            storage,
+            max_count: config.max_count,
+            max_age: TimeDelta::hours(config.max_age_hours as i64),
+            fetched: Mutex::new(None),
+        }
+    }
+
+    /// Save a capture and its thumbnail, replacing any capture with the same key.
+    ///
+    /// Only the first [`MAX_CAPTURE_ANNOTATIONS`] annotations are saved.  A capture is still saved when its thumbnail
+    /// can't be created, the operator UI then shows a placeholder.
+    pub async fn save(
+        &self,
+        key: &CaptureKey,
+        jpeg_bytes: &[u8],
+        annotations: &[CaptureAnnotation],
+    ) -> Result<(), CaptureError> {
+        let path = path(key)?;
+
+        self.storage
+            .put(&path, jpeg_bytes)
+            .await
+            .map_err(|e| storage_error(&path, e))?;
+        self.forget(&path);
+
+        let thumbnail_path = thumbnail_path(key)?;
+        // decoding takes longer than is acceptable for the runtime
+        let thumbnail = tokio::task::spawn_blocking({
+            let jpeg_bytes = jpeg_bytes.to_vec();
+            move || {
+                let (width, height) = THUMBNAIL_SIZE;
+                jpeg_thumbnail(&jpeg_bytes, width, height, THUMBNAIL_JPEG_QUALITY)
+            }
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!(e))
+        .and_then(|result| result);
+        match thumbnail {
+            Ok(thumbnail) => {
+                self.storage
+                    .put(&thumbnail_path, &thumbnail)
+                    .await
+                    .map_err(|e| storage_error(&thumbnail_path, e))?;
+            }
+            Err(e) => {
+                warn!("Unable to create capture thumbnail. key: {}, error: {:?}", key, e);
+                // don't leave the thumbnail of a replaced capture
+                match self
+                    .storage
+                    .delete(&thumbnail_path)
+                    .await
+                {
+                    Ok(()) | Err(StorageError::NotFound) => {}
+                    Err(e) => return Err(storage_error(&thumbnail_path, e)),
+                }
+            }
+        }
+        self.forget(&thumbnail_path);
+
+        let annotations_path = annotations_path(key)?;
+        if annotations.is_empty() {
+            // don't leave the annotations of a replaced capture
+            match self
+                .storage
+                .delete(&annotations_path)
+                .await
+            {
+                Ok(()) | Err(StorageError::NotFound) => {}
+                Err(e) => return Err(storage_error(&annotations_path, e)),
+            }
+        } else {
+            if annotations.len() > MAX_CAPTURE_ANNOTATIONS {
+                warn!(
+                    "Too many capture annotations, discarding the excess. key: {}, annotations: {}",
+                    key,
+                    annotations.len()
+                );
+            }
+            let annotations = &annotations[..annotations
+                .len()
+                .min(MAX_CAPTURE_ANNOTATIONS)];
+            let content = ron::to_string(annotations).map_err(|e| {
+                error!("Unable to serialize capture annotations. key: {}, error: {:?}", key, e);
+                CaptureError::Storage
+            })?;
+            self.storage
+                .put(&annotations_path, content.as_bytes())
+                .await
+                .map_err(|e| storage_error(&annotations_path, e))?;
+        }
+        info!(
+            "Capture saved. key: {}, bytes: {}, annotations: {}",
+            key,
+            jpeg_bytes.len(),
+            annotations.len()
+        );
+        Ok(())
+    }
+
+    /// All captures, newest first.
+    pub async fn list(&self) -> Result<Vec<CaptureEntry>, CaptureError> {
+        let objects = self
+            .storage
+            .list(CAPTURES_PREFIX)
+            .await
+            .map_err(|e| storage_error(CAPTURES_PREFIX, e))?;
+
+        // objects that could not have been created from a valid key are skipped
+        let mut entries = objects
+            .into_iter()
+            .filter_map(|object| {
+                let key = key(&object.path)?;
+                Some(CaptureEntry {
+                    key,
+                    total_bytes: object.size as u32,
+                    saved_at: object.modified.into(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        entries.sort_by(|a, b| {
+            (*b.saved_at)
+                .cmp(&*a.saved_at)
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        Ok(entries)
+    }
+
+    pub async fn list_page(&self, offset: usize) -> Result<CaptureListPage, CaptureError> {
+        let entries = self.list().await?;
+        Ok(CaptureListPage {
+            total: entries.len() as u32,
+            entries: entries
+                .into_iter()
+                .skip(offset)
+                .take(CAPTURE_LIST_PAGE_SIZE)
+                .collect(),
+        })
+    }
+
+    pub async fn read_chunk(&self, key: &CaptureKey, offset: usize) -> Result<CaptureChunk, CaptureError> {
+        self.read_object_chunk(path(key)?, offset)
+            .await
+    }
+
+    /// A capture without a thumbnail, e.g. because it couldn't be decoded, is [`CaptureError::NotFound`].
+    pub async fn read_thumbnail_chunk(&self, key: &CaptureKey, offset: usize) -> Result<CaptureChunk, CaptureError> {
+        self.read_object_chunk(thumbnail_path(key)?, offset)
+            .await
+    }
+
+    /// The object is fetched from the storage when its first chunk is read, the following chunks are read from the
+    /// fetched object.
+    async fn read_object_chunk(&self, path: String, offset: usize) -> Result<CaptureChunk, CaptureError> {
+        let fetched = self
+            .fetched
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|(fetched_path, _)| *fetched_path == path)
+            .map(|(_, bytes)| bytes.clone());
+        let bytes = match fetched {
+            Some(bytes) => bytes,
+            None => {
+                let bytes = self
+                    .storage
+                    .get(&path)
+                    .await
+                    .map(Arc::new)
+                    .map_err(|e| match e {
+                        StorageError::NotFound => CaptureError::NotFound,
+                        e => storage_error(&path, e),
+                    })?;
+                *self.fetched.lock().unwrap() = Some((path, bytes.clone()));
+                bytes
+            }
+        };
+
+        let start = offset.min(bytes.len());
+        let end = (start + CAPTURE_CHUNK_SIZE).min(bytes.len());
+
+        Ok(CaptureChunk {
+            total_bytes: bytes.len() as u32,
+            offset: start as u32,
+            bytes: bytes[start..end].to_vec(),
+        })
+    }
+
+    /// A capture without annotations has an empty list.
+    pub async fn read_annotations(&self, key: &CaptureKey) -> Result<Vec<CaptureAnnotation>, CaptureError> {
+        let path = annotations_path(key)?;
+        let bytes = match self.storage.get(&path).await {
+            Ok(bytes) => bytes,
+            Err(StorageError::NotFound) => return Ok(Vec::new()),
+            Err(e) => return Err(storage_error(&path, e)),
+        };
+
+        let content = String::from_utf8_lossy(&bytes);
+        ron::from_str(&content).map_err(|e| {
+            error!("Invalid capture annotations. path: {}, error: {:?}", path, e);
+            CaptureError::Storage
+        })
+    }
+
+    /// Remove captures that are too old, then the oldest captures that exceed the maximum count.
+    ///
+    /// Returns the number of captures removed.
+    pub async fn apply_retention(&self) -> Result<usize, CaptureError> {
+        let entries = self.list().await?;
+        let now = Utc::now();
+
+        let mut removed = 0;
+        for (index, entry) in entries.iter().enumerate() {
+            let age = now - *entry.saved_at;
+            if index < self.max_count && age <= self.max_age {
+                continue;
+            }
+
+            let path = path(&entry.key)?;
+            self.forget(&path);
+            match self.storage.delete(&path).await {
+                Ok(()) => {
+                    debug!("Capture removed. key: {}, age: {}", entry.key, age);
+                    removed += 1;
+                }
+                Err(e) => warn!("Unable to remove capture. path: {}, error: {:?}", path, e),
+            }
+
+            let annotations_path = annotations_path(&entry.key)?;
+            match self
+                .storage
+                .delete(&annotations_path)
+                .await
+            {
+                Ok(()) | Err(StorageError::NotFound) => {}
+                Err(e) => warn!(
+                    "Unable to remove capture annotations. path: {}, error: {:?}",
+                    annotations_path, e
+                ),
+            }
+
+            let thumbnail_path = thumbnail_path(&entry.key)?;
+            self.forget(&thumbnail_path);
+            match self
+                .storage
+                .delete(&thumbnail_path)
+                .await
+            {
+                Ok(()) | Err(StorageError::NotFound) => {}
+                Err(e) => warn!(
+                    "Unable to remove capture thumbnail. path: {}, error: {:?}",
+                    thumbnail_path, e
+                ),
+            }
+        }
+
+        if removed > 0 {
+            info!("Captures removed by retention. count: {}", removed);
+        }
+        Ok(removed)
+    }
+
+    /// Drops the fetched capture if it is the capture at the path, e.g. because it is replaced or removed.
+    fn forget(&self, path: &str) {
+        let mut fetched = self.fetched.lock().unwrap();
+        if fetched
+            .as_ref()
+            .is_some_and(|(fetched_path, _)| fetched_path == path)
+        {
+            *fetched = None;
+        }
+    }
+}
+
+/// Applies the retention limits of the captures at startup, since captures may have expired while the server wasn't
+/// running, then at the retention interval, see [`CapturesConfig`].
+pub async fn capture_retention(store: Arc<CaptureStore>, config: CapturesConfig, app_event_rx: Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let mut ticker = time::interval(Duration::from_secs(config.retention_interval_s.max(1)));
+    ticker.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+
+    info!("Capture retention started");
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                info!("capture retention shutdown");
+                break
+            }
+            _ = ticker.tick() => {
+                // errors are logged by the store, the next interval retries
+                let _ = store.apply_retention().await;
+            }
+        }
+    }
+}
+
+/// Saves the input frame of a vision routine that failed, with the annotations of its result, so that the failure can
+/// be reviewed.
+///
+/// The frames are stored under the routine, by subject, e.g. the camera or the placement, see [`failure_key`].
+#[derive(Clone)]
+pub struct FailureCaptures {
+    store: Arc<CaptureStore>,
+    routine: &'static str,
+}
+
+impl FailureCaptures {
+    pub fn new(store: Arc<CaptureStore>, routine: &'static str) -> Self {
+        Self {
+            store,
+            routine,
+        }
+    }
+
+    /// A capture that can't be saved is only logged, the failure of the routine is what is reported.
+    pub async fn save(&self, subject: &str, frame: &CameraFrame, annotations: &[CaptureAnnotation]) {
+        let key = failure_key(self.routine, subject, &frame.frame_timestamp);
+        if let Err(e) = self
+            .store
+            .save(&key, &frame.jpeg_bytes, annotations)
+            .await
+        {
+            warn!(
+                "Unable to save the capture of a failed vision routine. key: {}, error: {:?}",
+                key, e
+            );
+        }
+    }
+}
+
+/// `{routine}/{subject}/{frame timestamp}.failed`, characters of the subject that are not valid in a key are replaced.
+pub fn failure_key(routine: &str, subject: &str, frame_timestamp: &DateTime<Utc>) -> CaptureKey {
+    let subject = subject
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+            true => c,
+            false => '_',
+        })
+        .collect::<String>();
+
+    CaptureKey {
+        job: routine.to_string(),
+        placement: subject,
+        stage: format!("{}.failed", frame_timestamp.format("%Y%m%dT%H%M%S%.3fZ")),
+    }
+}
+
+/// An annotation with the error of a failed vision routine, for results that have no position in the frame.
+pub fn failure_annotation(error: impl Display) -> CaptureAnnotation {
+    let (x, y) = FAILURE_LABEL_POSITION;
+    CaptureAnnotation {
+        shape: AnnotationShape::Point {
+            x,
+            y,
+        },
+        label: Some(error.to_string()),
+        passed: false,
+    }
+}
+
+fn path(key: &CaptureKey) -> Result<String, CaptureError> {
+    path_with_extension(CAPTURES_PREFIX, key, CAPTURE_EXTENSION)
+}
+
+fn annotations_path(key: &CaptureKey) -> Result<String, CaptureError> {
+    path_with_extension(CAPTURES_PREFIX, key, ANNOTATIONS_EXTENSION)
+}
+
+fn thumbnail_path(key: &CaptureKey) -> Result<String, CaptureError> {
+    path_with_extension(THUMBNAILS_PREFIX, key, CAPTURE_EXTENSION)
+}
+
+fn path_with_extension(prefix: &str, key: &CaptureKey, extension: &str) -> Result<String, CaptureError> {
+    if ![&key.job, &key.placement, &key.stage]
+        .iter()
+        .all(|component| is_valid_component(component))
+    {
+        return Err(CaptureError::InvalidKey);
+    }
+
+    Ok(format!(
+        "{}{}/{}/{}.{}",
+        prefix, key.job, key.placement, key.stage, extension
+    ))
+}
+
+/// The inverse of [`path`], `None` if the path is not a valid capture path.
+fn key(path: &str) -> Option<CaptureKey> {
+    let mut components = path
+        .strip_prefix(CAPTURES_PREFIX)?
+        .strip_suffix(&format!(".{}", CAPTURE_EXTENSION))?
+        .split('/');
+
+    let key = CaptureKey {
+        job: components.next()?.to_string(),
+        placement: components.next()?.to_string(),
+        stage: components.next()?.to_string(),
+    };
+    if components.next().is_some() {
+        return None;
+    }
+
+    // also validates the components
+    path(&key).ok()?;
+    Some(key)
+}
+
+/// Prevents keys from escaping the capture directory, see [`CaptureKey`].
+///
+/// Also used for the names of other stored objects, e.g. templates.
+pub fn is_valid_component(component: &str) -> bool {
+    !component.is_empty()
+        && !component.starts_with('.')
+        && component
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn storage_error(path: &str, e: StorageError) -> CaptureError {
+    error!("Capture storage error. path: {}, error: {:?}", path, e);
+    CaptureError::Storage
+}
@@ -0,0 +1,259 @@
+use std::sync::Arc;
+
+use chrono::{TimeZone, Utc};
+use operator_shared::captures::{AnnotationShape, CaptureAnnotation, CaptureError, CaptureKey};
+use server_vision::CameraFrame;
+
+use super::{CAPTURE_CHUNK_SIZE, CaptureStore, FailureCaptures, failure_annotation, failure_key};
+use crate::config::CapturesConfig;
+use crate::storage::StorageImpl;
+use crate::storage::local::LocalStorage;
+
+fn store(max_count: usize) -> CaptureStore {
+    let directory = std::env::temp_dir().join(format!("captures-test-{:016x}", rand::random::<u64>()));
+    let storage = Arc::new(StorageImpl::Local(LocalStorage::new(directory)));
+    CaptureStore::new(storage, &CapturesConfig {
+        max_count,
+        max_age_hours: 1,
+        retention_interval_s: 60,
+    })
+}
+
+fn key(job: &str, placement: &str, stage: &str) -> CaptureKey {
+    CaptureKey {
+        job: job.to_string(),
+        placement: placement.to_string(),
+        stage: stage.to_string(),
+    }
+}
+
+#[tokio::test]
+pub async fn save_list_and_read() {
+    // given
+    let store = store(10);
+    let key = key("job-1", "R1", "bottom-vision");
+    let bytes = (0..CAPTURE_CHUNK_SIZE + 10)
+        .map(|i| i as u8)
+        .collect::<Vec<_>>();
+
+    // when
+    store
+        .save(&key, &bytes, &[])
+        .await
+        .unwrap();
+
+    // then
+    let entries = store.list().await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].key, key);
+    assert_eq!(entries[0].total_bytes, bytes.len() as u32);
+
+    let first = store.read_chunk(&key, 0).await.unwrap();
+    assert_eq!(first.bytes, bytes[..CAPTURE_CHUNK_SIZE]);
+    let second = store
+        .read_chunk(&key, CAPTURE_CHUNK_SIZE)
+        .await
+        .unwrap();
+    assert_eq!(second.bytes, bytes[CAPTURE_CHUNK_SIZE..]);
+    assert_eq!(second.total_bytes, bytes.len() as u32);
+}
+
+#[tokio::test]
+pub async fn the_chunks_of_a_replaced_capture_are_read_from_the_replacement() {
+    // given
+    let store = store(10);
+    let key = key("job-1", "R1", "bottom-vision");
+    store
+        .save(&key, &[1; CAPTURE_CHUNK_SIZE + 10], &[])
+        .await
+        .unwrap();
+    let _ = store.read_chunk(&key, 0).await.unwrap();
+
+    // when
+    store
+        .save(&key, &[2; 10], &[])
+        .await
+        .unwrap();
+
+    // then
+    let chunk = store.read_chunk(&key, 0).await.unwrap();
+    assert_eq!(chunk.bytes, vec![2; 10]);
+    assert_eq!(chunk.total_bytes, 10);
+}
+
+#[tokio::test]
+pub async fn a_capture_that_cannot_be_decoded_is_saved_without_a_thumbnail() {
+    // given
+    let store = store(10);
+    let key = key("job-1", "R1", "bottom-vision");
+
+    // when
+    store
+        .save(&key, &[0xff, 0xd8], &[])
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(store.list().await.unwrap().len(), 1);
+    assert_eq!(
+        store
+            .read_thumbnail_chunk(&key, 0)
+            .await,
+        Err(CaptureError::NotFound)
+    );
+}
+
+#[tokio::test]
+pub async fn retention_limits_count() {
+    // given
+    let store = store(2);
+
+    // when
+    for stage in ["a", "b", "c"] {
+        store
+            .save(&key("job-1", "R1", stage), &[0xff, 0xd8], &[])
+            .await
+            .unwrap();
+    }
+    let removed = store.apply_retention().await.unwrap();
+
+    // then
+    assert_eq!(removed, 1);
+    assert_eq!(store.list().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+pub async fn keys_cannot_escape_the_directory() {
+    // given
+    let store = store(10);
+
+    // expect
+    for key in [
+        key("..", "R1", "stage"),
+        key("job-1", "R1/../..", "stage"),
+        key("job-1", "", "stage"),
+        key("job-1", "R1", ".hidden"),
+    ] {
+        assert_eq!(store.save(&key, &[], &[]).await, Err(CaptureError::InvalidKey));
+    }
+}
+
+#[tokio::test]
+pub async fn missing_capture() {
+    // given
+    let store = store(10);
+
+    // expect
+    assert_eq!(
+        store
+            .read_chunk(&key("job-1", "R1", "stage"), 0)
+            .await
+            .unwrap_err(),
+        CaptureError::NotFound
+    );
+}
+
+#[tokio::test]
+pub async fn annotations_are_saved_with_the_capture() {
+    // given
+    let store = store(10);
+    let key = key("job-1", "R1", "bottom-vision");
+    let annotations = vec![CaptureAnnotation {
+        shape: AnnotationShape::Circle {
+            x: 320.0,
+            y: 240.0,
+            radius: 12.5,
+        },
+        label: Some("nozzle".to_string()),
+        passed: false,
+    }];
+
+    // when
+    store
+        .save(&key, &[0xff, 0xd8], &annotations)
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(
+        store
+            .read_annotations(&key)
+            .await
+            .unwrap(),
+        annotations
+    );
+    // the annotations are not listed as a capture
+    assert_eq!(store.list().await.unwrap().len(), 1);
+}
+
+#[test]
+pub fn paths_and_keys_round_trip() {
+    // given
+    let key = key("job-1", "R1", "bottom-vision.failed");
+
+    // when
+    let path = super::path(&key).unwrap();
+
+    // then
+    assert_eq!(path, "captures/job-1/R1/bottom-vision.failed.jpg");
+    assert_eq!(super::key(&path), Some(key));
+    assert_eq!(super::key("captures/job-1/R1/extra/stage.jpg"), None);
+}
+
+#[tokio::test]
+pub async fn the_frame_of_a_failed_vision_routine_is_saved_with_its_annotations() {
+    // given
+    let store = Arc::new(store(10));
+    let failures = FailureCaptures::new(store.clone(), "scan");
+    let frame = CameraFrame {
+        frame_number: 42,
+        jpeg_bytes: vec![0xff, 0xd8, 0xff, 0xd9],
+        frame_timestamp: Utc
+            .with_ymd_and_hms(2026, 10, 16, 12, 30, 0)
+            .unwrap(),
+        machine_position: None,
+    };
+
+    // when
+    failures
+        .save("C0", &frame, &[failure_annotation("No code found")])
+        .await;
+
+    // then
+    let key = key("scan", "C0", "20261016T123000.000Z.failed");
+    let entries = store.list().await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].key, key);
+    assert_eq!(
+        store
+            .read_chunk(&key, 0)
+            .await
+            .unwrap()
+            .bytes,
+        frame.jpeg_bytes
+    );
+
+    // and
+    let annotations = store
+        .read_annotations(&key)
+        .await
+        .unwrap();
+    assert_eq!(annotations.len(), 1);
+    assert_eq!(annotations[0].label.as_deref(), Some("No code found"));
+    assert!(!annotations[0].passed);
+}
+
+#[test]
+pub fn the_subject_of_a_failure_is_a_valid_key_component() {
+    // given
+    let timestamp = Utc
+        .with_ymd_and_hms(2026, 10, 16, 12, 30, 0)
+        .unwrap();
+
+    // when
+    let key = failure_key("evidence", "../R1 top", &timestamp);
+
+    // then
+    assert_eq!(key.placement, "___R1_top");
+    assert!(super::path(&key).is_ok());
+}
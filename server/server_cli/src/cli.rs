@@ -10,6 +10,36 @@ pub struct Args {
     #[arg(short = 'c', long = "config", value_name = "PATH", default_value_os = "config.ron")]
     pub config: PathBuf,
 
+    /// Run the burn-in routine for the given number of hours instead of the normal motion, for newly built machines
+    #[arg(long = "burn-in", value_name = "HOURS")]
+    pub burn_in_hours: Option<f64>,
+
+    /// The job to run when the operator starts a job, a RON file
+    #[arg(long = "job", value_name = "PATH")]
+    pub job: Option<PathBuf>,
+
+    /// Measure the accuracy of the machine using the calibration plate and the down camera, then write a report
+    #[arg(long = "measure-accuracy")]
+    pub measure_accuracy: bool,
+
+    /// Measure the runout of the nozzle by rotating it over the up camera, then store the correction, the nozzle must
+    /// already be over the up camera
+    #[arg(long = "measure-runout")]
+    pub measure_runout: bool,
+
+    /// Write a default config, and create the directories it uses, in the directory, then exit, for a fresh install
+    #[arg(long = "init", value_name = "DIRECTORY", num_args = 0..=1, default_missing_value = ".")]
+    pub init: Option<PathBuf>,
+
+    /// Write the log to the file instead of stderr, e.g. when there is no console
+    #[arg(long = "log-file", value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Run as a Windows service, only when started by the service control manager, see `SERVICE.md`
+    #[cfg(windows)]
+    #[arg(long = "service")]
+    pub service: bool,
+
     /// Increase verbosity (-v, -vv, -vvv)
     #[arg(
         short = 'v',
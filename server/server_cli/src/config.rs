@@ -1,10 +1,15 @@
-use std::net::IpAddr;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 
+use machine_geometry::Point;
+use machine_ids::FeederId;
+use operator_shared::diagnostics::LogLevel;
 #[cfg(feature = "mediars-capture")]
 use server_common::camera::MediaRSCameraConfig;
 #[cfg(feature = "opencv-capture")]
 use server_common::camera::OpenCVCameraConfig;
-use server_common::camera::{CameraDefinition, CameraSource, CameraStreamConfig};
+use server_common::camera::{CameraDefinition, CameraLayout, CameraMounting, CameraSource, CameraStreamConfig};
 
 // TODO currently hardcoded.  move to config file.
 pub fn camera_definitions() -> Vec<CameraDefinition> {
@@ -26,10 +31,15 @@ pub fn camera_definitions() -> Vec<CameraDefinition> {
             ],
             stream_config: CameraStreamConfig {
                 jpeg_quality: 95,
+                motion_throttle: None,
             },
             width: 1920,
             height: 1280,
             fps: 30.0,
+            mounting: CameraMounting::Other,
+            layout: CameraLayout::Primary,
+            calibration: None,
+            exposure_metering: None,
         },
         CameraDefinition {
             name: "B&W Global shutter".to_string(),
@@ -47,10 +57,15 @@ pub fn camera_definitions() -> Vec<CameraDefinition> {
             ],
             stream_config: CameraStreamConfig {
                 jpeg_quality: 95,
+                motion_throttle: None,
             },
             width: 640,
             height: 480,
             fps: 100.0,
+            mounting: CameraMounting::Other,
+            layout: CameraLayout::Secondary,
+            calibration: None,
+            exposure_metering: None,
         },
         // CameraDefinition {
         //     name: "Microsoft XBox Vision Live".to_string(),
@@ -68,6 +83,7 @@ pub fn camera_definitions() -> Vec<CameraDefinition> {
         //     ],
         //     stream_config: CameraStreamConfig {
         //         jpeg_quality: 95,
+        //         motion_throttle: None,
         //     },
         //     width: 640,
         //     height: 480,
@@ -93,10 +109,15 @@ pub fn camera_definitions() -> Vec<CameraDefinition> {
             ],
             stream_config: CameraStreamConfig {
                 jpeg_quality: 95,
+                motion_throttle: None,
             },
             width: 800,
             height: 600,
             fps: 30.0,
+            mounting: CameraMounting::Other,
+            layout: CameraLayout::Primary,
+            calibration: None,
+            exposure_metering: None,
         },
         CameraDefinition {
             name: "USB camera 1".to_string(),
@@ -114,10 +135,15 @@ pub fn camera_definitions() -> Vec<CameraDefinition> {
             ],
             stream_config: CameraStreamConfig {
                 jpeg_quality: 95,
+                motion_throttle: None,
             },
             width: 640,
             height: 480,
             fps: 30.0,
+            mounting: CameraMounting::Other,
+            layout: CameraLayout::Secondary,
+            calibration: None,
+            exposure_metering: None,
         },
         CameraDefinition {
             name: "USB camera 2".to_string(),
@@ -135,10 +161,15 @@ pub fn camera_definitions() -> Vec<CameraDefinition> {
             ],
             stream_config: CameraStreamConfig {
                 jpeg_quality: 95,
+                motion_throttle: None,
             },
             width: 640,
             height: 480,
             fps: 30.0,
+            mounting: CameraMounting::Other,
+            layout: CameraLayout::Secondary,
+            calibration: None,
+            exposure_metering: None,
         },
     ];
 
@@ -159,11 +190,1231 @@ pub const OPERATOR_REMOTE_ADDR: &str = "127.0.0.1:8002";
 pub struct Config {
     pub cameras: Vec<CameraDefinition>,
     pub io_boards: Vec<IoBoardDefinition>,
+    #[serde(default)]
+    pub motion: MotionConfig,
+    #[serde(default)]
+    pub command_latency: CommandLatencyConfig,
+    #[serde(default)]
+    pub command_batching: CommandBatchingConfig,
+    #[serde(default)]
+    pub topic_tap: TopicTapConfig,
+    #[serde(default)]
+    pub dead_letters: DeadLetterConfig,
+    #[serde(default)]
+    pub router_inspection: RouterInspectionConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// `None` without a power meter
+    #[serde(default)]
+    pub power_meter: Option<PowerMeterConfig>,
+    /// The analog input channels of the io board, for ad-hoc sensors, see `analog`.
+    #[serde(default)]
+    pub analog_channels: Vec<AnalogChannelDefinition>,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub captures: CapturesConfig,
+    #[serde(default)]
+    pub camera_memory: CameraMemoryConfig,
+    #[serde(default)]
+    pub burn_in: BurnInConfig,
+    #[serde(default)]
+    pub accuracy: AccuracyConfig,
+    /// Measured by the accuracy routine, see `--measure-accuracy`.
+    #[serde(default)]
+    pub axis_corrections: AxisCorrections,
+    #[serde(default)]
+    pub runout: RunoutConfig,
+    #[serde(default)]
+    pub job: JobConfig,
+    #[serde(default)]
+    pub feeders: FeedersConfig,
+    #[serde(default)]
+    pub nozzles: NozzlesConfig,
+    /// The heads in addition to the nozzles, e.g. a paste dispenser.
+    #[serde(default)]
+    pub heads: Vec<HeadDefinition>,
+    /// The parts library, referenced by the feeders.
+    #[serde(default)]
+    pub parts: Vec<PartDefinition>,
+    /// `None` if the machine has no test area, see `test_area::TestArea`.
+    #[serde(default)]
+    pub test_area: Option<TestAreaConfig>,
+    #[serde(default)]
+    pub homing: HomingConfig,
+    #[serde(default)]
+    pub parking: ParkingConfig,
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    #[serde(default)]
+    pub supervisor: SupervisorConfig,
+    #[serde(default)]
+    pub bridge: BridgeConfig,
+    /// The router networks in addition to the control network, e.g. for the cameras, see `segments`.
+    #[serde(default)]
+    pub network_segments: Vec<NetworkSegmentDefinition>,
+}
+
+/// Where captures and reports are stored, see `storage::StorageImpl`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub enum StorageConfig {
+    Local {
+        /// relative to the working directory of the server
+        directory: PathBuf,
+    },
+    S3(S3StorageConfig),
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self::Local {
+            directory: PathBuf::from("artifacts"),
+        }
+    }
+}
+
+/// An S3-compatible bucket, the credentials are read from the environment.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct S3StorageConfig {
+    /// e.g. `https://s3.eu-west-1.amazonaws.com` or `http://minio.local:9000`
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// prepended to all paths, e.g. `machine-1/`, so that several machines can share a bucket
+    #[serde(default)]
+    pub prefix: String,
+    /// required by most self-hosted S3-compatible servers
+    #[serde(default)]
+    pub path_style: bool,
+}
+
+/// Saved camera frames, see `captures::CaptureStore`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct CapturesConfig {
+    /// the oldest captures are removed when there are more than this
+    pub max_count: usize,
+    /// captures older than this are removed
+    pub max_age_hours: u64,
+    /// the retention limits are applied at this interval
+    pub retention_interval_s: u64,
+}
+
+impl Default for CapturesConfig {
+    fn default() -> Self {
+        Self {
+            max_count: 1000,
+            max_age_hours: 24 * 7,
+            retention_interval_s: 600,
+        }
+    }
+}
+
+/// Limits of the memory used by the camera frames in flight, see `camera::budget::FrameBudget`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct CameraMemoryConfig {
+    /// the limit for the frames of each camera, in bytes
+    pub per_camera_bytes: usize,
+    /// the limit for the frames of all cameras, in bytes
+    pub total_bytes: usize,
+}
+
+impl Default for CameraMemoryConfig {
+    fn default() -> Self {
+        Self {
+            per_camera_bytes: 16 * 1024 * 1024,
+            total_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Round-trip latency probes of the io board command path, see `diagnostics::latency_monitor`.
+/// Used by the burn-in routine, see `--burn-in`, positions and limits are in degrees.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct BurnInConfig {
+    /// the envelope of the axis, moves are to random positions between these
+    pub min_position: f64,
+    pub max_position: f64,
+    pub max_jerk: f64,
+    pub max_acceleration: f64,
+    pub max_velocity: f64,
+    /// each move uses a random fraction of the max acceleration and velocity, between this and 1.0
+    pub min_limit_fraction: f64,
+    /// time after each move before the reported position is compared to the target
+    pub settle_ms: u64,
+    /// a difference between the reported position and the target of more than this is a missed step
+    pub position_tolerance_steps: u64,
+    /// the report is written under this path of the storage, see [`StorageConfig`]
+    pub report_directory: String,
+    /// `None` for a random seed, the seed is included in the report so that a run can be repeated
+    pub seed: Option<u64>,
+}
+
+impl Default for BurnInConfig {
+    fn default() -> Self {
+        Self {
+            min_position: 0.0,
+            max_position: 720.0,
+            max_jerk: 5000.0,
+            max_acceleration: 10000.0,
+            max_velocity: 10000.0,
+            min_limit_fraction: 0.25,
+            settle_ms: 250,
+            position_tolerance_steps: 0,
+            report_directory: "burn-in".to_string(),
+            seed: None,
+        }
+    }
+}
+
+/// Used by the accuracy routine, see `--measure-accuracy`, positions and limits are in millimeters.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct AccuracyConfig {
+    /// the position of the first dot of the calibration plate, in machine coordinates
+    pub origin_x: f64,
+    pub origin_y: f64,
+    /// distance between the dots of the calibration plate
+    pub pitch: f64,
+    pub columns: u32,
+    pub rows: u32,
+    /// each dot is measured this many times, approaching from a different dot each time
+    pub repetitions: u32,
+    pub max_jerk: f64,
+    pub max_acceleration: f64,
+    pub max_velocity: f64,
+    /// time after each move before the dot is measured
+    pub settle_ms: u64,
+    /// measure with the configured `axis_corrections` applied, e.g. to verify them, the suggested corrections in the
+    /// report then include the configured corrections
+    pub apply_corrections: bool,
+    /// FUTURE should be part of the axis configuration
+    pub steps_per_mm: f64,
+    /// the report is written under this path of the storage, see [`StorageConfig`]
+    pub report_directory: String,
+}
+
+impl Default for AccuracyConfig {
+    fn default() -> Self {
+        Self {
+            origin_x: 0.0,
+            origin_y: 0.0,
+            pitch: 10.0,
+            columns: 5,
+            rows: 1,
+            repetitions: 3,
+            max_jerk: 5000.0,
+            max_acceleration: 1000.0,
+            max_velocity: 200.0,
+            settle_ms: 500,
+            apply_corrections: false,
+            steps_per_mm: 80.0,
+            report_directory: "accuracy".to_string(),
+        }
+    }
+}
+
+/// Used by the nozzle runout routine, see `--measure-runout`, the nozzle is rotated over the up camera.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct RunoutConfig {
+    /// the nozzle that is measured, and whose correction is applied to the placements
+    ///
+    /// FUTURE a correction for each nozzle, once placements are made with more than one nozzle
+    pub nozzle: u8,
+    /// the io board axis that rotates the nozzle
+    pub rotation_axis: u8,
+    /// the tip is measured at this many angles, evenly spaced over a full turn, at least 3
+    pub samples: u32,
+    /// in degrees
+    pub max_jerk: f64,
+    pub max_acceleration: f64,
+    pub max_velocity: f64,
+    /// time after each rotation before the tip is measured
+    pub settle_ms: u64,
+    /// a larger runout is reported as a worn or bent nozzle, in millimeters, the correction is still stored
+    pub max_runout_mm: f64,
+    /// FUTURE should be part of the axis configuration
+    pub steps_per_degree: f64,
+    /// the measured corrections are stored here, and loaded on startup
+    pub corrections_path: PathBuf,
+}
+
+impl Default for RunoutConfig {
+    fn default() -> Self {
+        Self {
+            nozzle: 0,
+            rotation_axis: 0,
+            samples: 12,
+            max_jerk: 50000.0,
+            max_acceleration: 5000.0,
+            max_velocity: 720.0,
+            settle_ms: 250,
+            max_runout_mm: 0.1,
+            steps_per_degree: 1600.0 / 360.0,
+            corrections_path: PathBuf::from("nozzle-runout.ron"),
+        }
+    }
+}
+
+/// Running jobs, see `--job`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct JobConfig {
+    /// the progress of the running job is saved here after each placement, so that it can be resumed after a crash or
+    /// power loss
+    pub checkpoint_path: PathBuf,
+    /// the job for a scanned board ID is loaded from `<board_id>.ron` in this directory
+    pub jobs_directory: PathBuf,
+    /// a report is written under this path of the storage for each run of a job, see [`StorageConfig`]
+    pub report_directory: String,
+    pub mode: JobMode,
+    pub pick_place: PickPlaceConfig,
+    pub bad_marks: BadMarkConfig,
+    pub evidence: EvidenceConfig,
+    pub simulation: SimulationConfig,
+}
+
+impl Default for JobConfig {
+    fn default() -> Self {
+        Self {
+            checkpoint_path: PathBuf::from("job-checkpoint.ron"),
+            jobs_directory: PathBuf::from("jobs"),
+            report_directory: "job-reports".to_string(),
+            mode: JobMode::default(),
+            pick_place: PickPlaceConfig::default(),
+            bad_marks: BadMarkConfig::default(),
+            evidence: EvidenceConfig::default(),
+            simulation: SimulationConfig::default(),
+        }
+    }
+}
+
+/// How the placements of jobs and test shots are made.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum JobMode {
+    /// the parts are picked from the feeders and placed with the head, see `job::pick_place::PickAndPlacePlacer`, a job
+    /// is refused unless an io board follows the moves planned by the server
+    #[default]
+    Machine,
+    /// nothing is moved and every placement succeeds, e.g. to try out a job, the job events and the report say so
+    DryRun,
+}
+
+/// Picking the parts with a nozzle and placing them, see `job::pick_place::PickAndPlacePlacer`, heights are in
+/// millimeters.  The moves use the motion limits and heights of the `parking` configuration, and the rotation axis and
+/// limits of the `runout` configuration.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct PickPlaceConfig {
+    /// the nozzle that picks the parts
+    pub nozzle: u8,
+    /// of Z with the nozzle tip touching the top of a part in the pocket of a feeder
+    pub pick_z: f64,
+    /// the part-present signal is read this long after the valve is opened
+    pub pick_settle_ms: u64,
+    /// after the valve is closed, before the nozzle is raised off the placed part
+    pub release_ms: u64,
+}
+
+impl Default for PickPlaceConfig {
+    fn default() -> Self {
+        Self {
+            nozzle: 0,
+            pick_z: 0.0,
+            pick_settle_ms: 100,
+            release_ms: 50,
+        }
+    }
+}
+
+/// Estimating the run time of a job before it is started, see `job::simulation::simulate_job`.  The moves are planned
+/// with the motion limits of the `parking` configuration.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    /// picking, aligning and placing a part, excluding the move to the placement
+    ///
+    /// FUTURE simulate the pick, once the feeders have positions
+    pub place_ms: u64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            place_ms: 1500,
+        }
+    }
+}
+
+/// Detecting the bad marks of the boards of a panel with the down camera, see
+/// [`SkipMarks::BadMark`](crate::job::panel::SkipMarks::BadMark).
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct BadMarkConfig {
+    /// the difference in gray level from the board above which a pixel is part of a mark
+    pub contrast: u8,
+    /// the fraction of the mark region, 0.0 to 1.0, that must be covered for the board to be marked bad
+    pub min_coverage: f32,
+}
+
+impl Default for BadMarkConfig {
+    fn default() -> Self {
+        Self {
+            contrast: 60,
+            min_coverage: 0.3,
+        }
+    }
+}
+
+/// Comparing down camera images of the target location before and after each placement, the evidence is written to
+/// the job report, see `job::evidence::EvidencePlacer`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct EvidenceConfig {
+    /// capturing the images takes time, and pauses the preview of the camera
+    pub enabled: bool,
+    /// the difference in gray level above which a pixel has changed
+    pub contrast: u8,
+    /// the images are registered by searching up to this offset, in pixels
+    pub max_shift: u32,
+    /// a placement that changed less of the target region than this, 0.0 to 1.0, is suspect, e.g. the part was not
+    /// placed
+    pub min_changed: f32,
+    /// a placement that changed more of the target region than this, 0.0 to 1.0, is suspect, e.g. neighbouring parts
+    /// were disturbed
+    pub max_changed: f32,
+}
+
+impl Default for EvidenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            contrast: 40,
+            max_shift: 16,
+            min_changed: 0.05,
+            max_changed: 0.8,
+        }
+    }
+}
+
+/// The feeders of the machine, the remaining parts of each feeder are counted, see `feeders::Feeders`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct FeedersConfig {
+    /// a low-stock event is raised when the remaining parts of a feeder drop to this, unless the feeder has its own
+    /// threshold
+    pub low_stock_threshold: u32,
+    pub orientation: OrientationConfig,
+    pub feeders: Vec<FeederDefinition>,
+}
+
+impl Default for FeedersConfig {
+    fn default() -> Self {
+        Self {
+            low_stock_threshold: 20,
+            orientation: OrientationConfig::default(),
+            feeders: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct FeederDefinition {
+    /// referenced by the placements of a job
+    pub name: FeederId,
+    #[serde(default)]
+    pub low_stock_threshold: Option<u32>,
+    /// where the polarity mark of the part is, in the image of the first pocket, when the tape is loaded correctly,
+    /// `None` if the part has no polarity mark
+    #[serde(default)]
+    pub polarity_mark: Option<PolarityCorner>,
+    /// the name of the part in the feeder, see [`PartDefinition`], `None` if the part is not in the parts library
+    #[serde(default)]
+    pub part: Option<String>,
+    /// the serial number of the ID chip of a smart feeder, the definition is loaded when the io board reports the
+    /// feeder inserted into a slot, `None` for feeders without an ID chip
+    #[serde(default)]
+    pub identity: Option<u64>,
+    #[serde(default)]
+    pub calibration: Option<FeederCalibration>,
+    /// the nominal position of the first pocket, in machine coordinates, offset by the `calibration`, `None` if the
+    /// parts of the feeder are not picked by the machine
+    #[serde(default)]
+    pub pick_position: Option<Point>,
+}
+
+/// Measured when the feeder was set up, a smart feeder keeps its calibration in whichever slot it is inserted.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct FeederCalibration {
+    /// of the first pocket from the nominal pick position of the slot, in millimeters
+    pub pick_offset: Point,
+    /// of the parts in the tape, in degrees
+    pub rotation: f64,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct PartDefinition {
+    /// referenced by the feeders
+    pub name: String,
+    /// of the placed part, above the board, in millimeters
+    pub height: f64,
+    /// the expected peak force of the touchdown when the part is picked, `None` if it is not checked
+    #[serde(default)]
+    pub pick_force: Option<ForceRange>,
+    /// the expected peak force of the touchdown when the part is placed, `None` if it is not checked
+    #[serde(default)]
+    pub place_force: Option<ForceRange>,
+}
+
+/// In newtons, see `forces::anomaly`.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct ForceRange {
+    /// a lower peak force is too soft, e.g. the part was not reached
+    pub min: f64,
+    /// a higher peak force is too hard, e.g. the part may be cracked
+    pub max: f64,
+}
+
+/// A quadrant of the image of a pocket, as seen by the down camera.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum PolarityCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Verifying the orientation of the tape in a feeder from the polarity mark of the part in the first pocket, see
+/// `orientation::verify_orientation`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct OrientationConfig {
+    /// the difference in gray level from the body of the part above which a pixel is part of the mark
+    pub contrast: u8,
+    /// the fraction of a quadrant, 0.0 to 1.0, that must be covered for the mark to be in it
+    pub min_coverage: f32,
+    /// the coverage of the quadrant of the mark must be at least this multiple of that of every other quadrant
+    pub min_dominance: f32,
+}
+
+impl Default for OrientationConfig {
+    fn default() -> Self {
+        Self {
+            contrast: 60,
+            min_coverage: 0.05,
+            min_dominance: 2.0,
+        }
+    }
+}
+
+/// Clog detection from the vacuum response of the nozzles, see `nozzles::NozzleMonitor`.  Vacuum levels are in kPa below
+/// ambient pressure.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct NozzlesConfig {
+    /// the vacuum is checked this long after the valve is opened
+    pub pickup_settle_ms: u64,
+    /// with a part present the vacuum should reach this
+    pub pickup_vacuum_min: f32,
+    /// after the valve is closed the vacuum should decay to this within the decay time
+    pub release_vacuum_max: f32,
+    pub release_decay_ms: u64,
+    /// a clog is suspected after this many consecutive abnormal vacuum responses of a nozzle
+    pub clog_threshold: u32,
+    /// `None` if there is no cleaning cycle, the operator has to clean the nozzle
+    pub cleaning: Option<NozzleCleaningConfig>,
+    /// `None` if the part-present thresholds are not calibrated at the start of a job, the io board defaults are used
+    pub calibration: Option<NozzleCalibrationConfig>,
+}
+
+impl Default for NozzlesConfig {
+    fn default() -> Self {
+        Self {
+            pickup_settle_ms: 100,
+            pickup_vacuum_min: 40.0,
+            release_vacuum_max: 5.0,
+            release_decay_ms: 200,
+            clog_threshold: 3,
+            cleaning: None,
+            calibration: None,
+        }
+    }
+}
+
+/// A blow-off cleaning cycle, run when a clog is suspected and no job is running.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct NozzleCleaningConfig {
+    /// in machine coordinates, e.g. over a waste bin
+    pub park_position: Point,
+    pub pulses: u32,
+    pub pulse_ms: u64,
+    /// between pulses
+    pub pause_ms: u64,
+}
+
+/// The vacuum baseline calibration of the nozzles, see `nozzles::calibration`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct NozzleCalibrationConfig {
+    /// in machine coordinates, a soft pad the nozzles are pressed on to block them
+    pub pad_position: Point,
+    /// the vacuum is read this long after the valve is opened
+    pub settle_ms: u64,
+    /// where the threshold is set between the open and the blocked vacuum, 0.0-1.0
+    pub threshold_fraction: f32,
+    /// the blocked vacuum must be at least this much higher than the open vacuum, otherwise the nozzle is likely
+    /// clogged or worn
+    pub min_margin: f32,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct HeadDefinition {
+    /// referenced by the operations of a job
+    pub name: String,
+    pub kind: HeadKind,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[non_exhaustive]
+pub enum HeadKind {
+    Dispenser(DispenserConfig),
+    // FUTURE: drag knife, etc.
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum DispenserMechanism {
+    /// a syringe pressurized with air, retracted with suck-back vacuum
+    #[default]
+    Pneumatic,
+    /// a screw driven by a motor, retracted by reversing the motor
+    Auger,
+}
+
+/// A dispense cycle is pre-pressure, dispense, then retract, see `dispensing::run_dispense_cycle`.  The outputs are
+/// those of the dispenser of the io board.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct DispenserConfig {
+    pub mechanism: DispenserMechanism,
+    /// the pressure is applied before the needle reaches the board, so that the material is flowing when it arrives
+    pub pre_pressure_ms: u64,
+    /// the default for dispense operations that don't have their own
+    pub dispense_ms: u64,
+    /// stops the material flowing after the pressure is removed
+    pub retract_ms: u64,
+}
+
+impl Default for DispenserConfig {
+    fn default() -> Self {
+        Self {
+            mechanism: DispenserMechanism::Pneumatic,
+            pre_pressure_ms: 50,
+            dispense_ms: 100,
+            retract_ms: 50,
+        }
+    }
+}
+
+/// A free part of the bed where test shots are taken, e.g. a glass slide or a strip of tape that is cleared by the
+/// operator.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct TestAreaConfig {
+    /// the corner with the lowest X and Y, in job coordinates
+    pub origin: Point,
+    /// in millimeters
+    pub width: f64,
+    pub height: f64,
+}
+
+/// The axes homed by the operator, see `homing::home_all`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct HomingConfig {
+    /// in the order they are shown to the operator
+    pub axes: Vec<HomingAxisDefinition>,
+    /// an axis that is not homed within this time has failed
+    pub timeout_ms: u64,
+}
+
+impl Default for HomingConfig {
+    fn default() -> Self {
+        let axis = |name: &str, axis: u8, after: &[&str]| HomingAxisDefinition {
+            name: name.to_string(),
+            axis,
+            after: after
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        };
+
+        Self {
+            // Z first, so that the nozzles are raised before X and Y move
+            axes: vec![axis("Z", 2, &[]), axis("X", 0, &["Z"]), axis("Y", 1, &["Z"])],
+            timeout_ms: 30_000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct HomingAxisDefinition {
+    /// shown to the operator, and referenced by `after`
+    pub name: String,
+    /// the axis of the io board
+    pub axis: u8,
+    /// the names of the axes that must be homed first, axes that don't depend on each other are homed in parallel
+    #[serde(default)]
+    pub after: Vec<String>,
+}
+
+/// The operators authorized to override the limits of an axis for maintenance, see `limits`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// no limit can be overridden when empty
+    pub operators: Vec<AuthorizedOperator>,
+    /// the longest a limit can be overridden for, the limit is re-enabled automatically after the requested duration
+    pub max_override_s: u32,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            operators: vec![],
+            max_override_s: 300,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct AuthorizedOperator {
+    /// logged with each override
+    pub name: String,
+    pub access_code: String,
+}
+
+/// Where the head is parked and when, see `parking::parking_runner`, positions and limits are in millimeters.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ParkingConfig {
+    /// referenced by name by the `policy`
+    pub positions: Vec<ParkPosition>,
+    pub policy: ParkingPolicy,
+    /// the height at which the nozzles clear every part on the board, Z is raised to this before X and Y move
+    pub safe_z: f64,
+    /// the io boards refuse X and Y moves while Z is below `safe_z`, the position of Z must be reported, see
+    /// `ioboard_shared::safe_z`
+    ///
+    /// FUTURE enable by default once every machine has a Z axis that reports its position
+    pub guard_xy_moves: bool,
+    pub axes: ParkingAxes,
+    pub max_jerk: f64,
+    pub max_acceleration: f64,
+    pub max_velocity: f64,
+    /// FUTURE should be part of the axis configuration
+    pub steps_per_mm: f64,
+    /// a park, or raising Z, that is not finished within this time has failed
+    pub timeout_ms: u64,
+    pub travel: TravelConfig,
+}
+
+impl Default for ParkingConfig {
+    fn default() -> Self {
+        Self {
+            positions: vec![ParkPosition {
+                name: "home".to_string(),
+                position: Point {
+                    x: 0.0,
+                    y: 0.0,
+                },
+            }],
+            policy: ParkingPolicy::default(),
+            safe_z: 10.0,
+            guard_xy_moves: false,
+            axes: ParkingAxes::default(),
+            max_jerk: 5000.0,
+            max_acceleration: 1000.0,
+            max_velocity: 200.0,
+            steps_per_mm: 80.0,
+            timeout_ms: 30_000,
+            travel: TravelConfig::default(),
+        }
+    }
+}
+
+/// Planning the height of Z for each move between placements, see `travel::TravelPlanner`, heights are in millimeters.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct TravelConfig {
+    /// when disabled every move is made at `safe_z`
+    ///
+    /// FUTURE enable by default once the parts library has the heights of the parts of every feeder, parts that are
+    ///        already on the board before the job is started are not known and must be cleared by `safe_z`
+    pub enabled: bool,
+    /// of the surface of the board, with the nozzle tip touching it
+    pub board_z: f64,
+    /// between the bottom of the nozzle, or the part it carries, and the tallest part under the path of the move
+    pub clearance: f64,
+    /// parts within this distance of the path of the move are under it, at least the radius of the nozzle plus the
+    /// radius of the largest part
+    pub keep_out: f64,
+}
+
+impl Default for TravelConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            board_z: 0.0,
+            clearance: 1.0,
+            keep_out: 10.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct ParkPosition {
+    pub name: String,
+    /// in machine coordinates
+    pub position: Point,
+}
+
+/// What is done when the machine becomes idle, or faults.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ParkingPolicy {
+    /// e.g. an axis crash, so that the nozzles are lifted clear of the board
+    pub on_fault: ParkAction,
+    /// when a job is finished or aborted, not when it is interrupted by a shutdown
+    pub on_job_end: ParkAction,
+    pub on_shutdown: ParkAction,
+}
+
+impl Default for ParkingPolicy {
+    fn default() -> Self {
+        Self {
+            on_fault: ParkAction::RaiseZ,
+            on_job_end: ParkAction::Park("home".to_string()),
+            on_shutdown: ParkAction::Park("home".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum ParkAction {
+    Nothing,
+    /// raise Z to the safe height, X and Y stay where they are
+    RaiseZ,
+    /// raise Z to the safe height, then move X and Y to the named park position
+    Park(String),
+}
+
+/// The io board axes of the head.
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize)]
+pub struct ParkingAxes {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+}
+
+impl Default for ParkingAxes {
+    fn default() -> Self {
+        // the same as the homing axes
+        Self {
+            x: 0,
+            y: 1,
+            z: 2,
+        }
+    }
+}
+
+/// The measured geometry of the machine, see `coordinates::CoordinateTransform`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct AxisCorrections {
+    pub x: LinearCorrection,
+    pub y: LinearCorrection,
+    /// the angle of the Y axis from perpendicular to the X axis, positive when the Y axis leans towards +X, in degrees
+    pub skew_degrees: f64,
+}
+
+/// `corrected = position * scale + offset`
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct LinearCorrection {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl Default for LinearCorrection {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+}
+
+impl LinearCorrection {
+    pub fn apply(&self, position: f64) -> f64 {
+        position * self.scale + self.offset
+    }
+
+    /// A correction that applies `residual` first, then this correction.
+    pub fn combine(&self, residual: &LinearCorrection) -> LinearCorrection {
+        LinearCorrection {
+            scale: self.scale * residual.scale,
+            offset: self.scale * residual.offset + self.offset,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct CommandLatencyConfig {
+    pub probe_interval_ms: u64,
+    /// a probe that is not acknowledged within this time is lost
+    pub probe_timeout_ms: u64,
+    /// the number of probes used for the statistics
+    pub window: usize,
+    /// an alarm is raised when the p99 latency is above this limit
+    pub p99_limit_ms: u64,
+}
+
+impl Default for CommandLatencyConfig {
+    fn default() -> Self {
+        Self {
+            probe_interval_ms: 100,
+            probe_timeout_ms: 500,
+            window: 600,
+            p99_limit_ms: 20,
+        }
+    }
+}
+
+/// Coalescing setpoints and commands sent to the io boards, see `ioboard::batching`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct CommandBatchingConfig {
+    /// commands sent within this time of the first command of a batch are sent with it, in a single frame, 0 sends
+    /// each command in its own frame, a fraction of the setpoint interval
+    pub window_us: u64,
+}
+
+impl Default for CommandBatchingConfig {
+    fn default() -> Self {
+        Self {
+            window_us: 1000,
+        }
+    }
+}
+
+/// Dumping the messages of topics for diagnostics, see `diagnostics::tap`, started and stopped by the operator.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct TopicTapConfig {
+    /// paths of the topics to tap, `*` matches a single segment of a path, a trailing `**` matches the rest of a path,
+    /// e.g. `topic/ioboard/**`
+    pub patterns: Vec<String>,
+    /// the tap stops by itself after this time
+    pub duration_s: u64,
+    /// `None` to dump the messages to the log
+    pub file: Option<PathBuf>,
+}
+
+impl Default for TopicTapConfig {
+    fn default() -> Self {
+        Self {
+            patterns: vec![],
+            duration_s: 60,
+            file: None,
+        }
+    }
+}
+
+/// Recording the messages that could not be delivered, see `networking::dead_letter`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct DeadLetterConfig {
+    /// the dead letters are appended to this file, one per line, `None` to only log a summary
+    pub file: Option<PathBuf>,
+    /// a summary of the dead letters since the last summary is logged at this interval, if there were any
+    pub summary_interval_s: u64,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        Self {
+            file: Some(PathBuf::from("dead-letters.log")),
+            summary_interval_s: 60,
+        }
+    }
+}
+
+/// The sinks of the log in addition to stderr or `--log-file`, and the levels of the modules, see `logging`.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// `None` to not write a rotated log file
+    pub file: Option<RotatingFileConfig>,
+    /// `None` to not log to syslog, unix only
+    pub syslog: Option<SyslogConfig>,
+    /// the levels of the modules and their sub-modules, e.g. `"server_cli::motion": Debug`, ignored if `RUST_LOG` is
+    /// set, the default level is given by `-v`
+    pub modules: BTreeMap<String, LogLevel>,
+}
+
+/// A log file that is rotated when it's too large or too old, the rotated files are renamed to `<path>.1`, `<path>.2`
+/// and so on, the oldest are removed.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct RotatingFileConfig {
+    pub path: PathBuf,
+    pub max_size_bytes: u64,
+    /// the file is rotated this long after it was opened, 0 to only rotate by size
+    pub max_age_hours: u64,
+    /// the number of rotated files to keep
+    pub keep: u32,
+}
+
+impl Default for RotatingFileConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("logs/server.log"),
+            max_size_bytes: 10 * 1024 * 1024,
+            max_age_hours: 24,
+            keep: 5,
+        }
+    }
+}
+
+/// Logging to the local syslog daemon, journald reads the same socket.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SyslogConfig {
+    /// the datagram socket of the daemon
+    pub socket: PathBuf,
+    /// the name of the program in the log, e.g. for filtering
+    pub identifier: String,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            socket: PathBuf::from("/dev/log"),
+            identifier: "makerpnp-server".to_string(),
+        }
+    }
+}
+
+/// The power meter of the machine, for tracking the energy used by each job, see `power`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct PowerMeterConfig {
+    pub meter: PowerMeterKind,
+    /// the meter is read at this interval
+    pub interval_ms: u64,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub enum PowerMeterKind {
+    /// An INA226 on the expansion header of an io board, read via the expansion endpoint, `device` is the index in the
+    /// device table of the io board, the device is a `Generic` device.
+    Ina226 {
+        device: u8,
+        /// the resistance of the current shunt, in ohms
+        shunt_ohms: f64,
+    },
+    /// A meter with a Modbus TCP interface, e.g. a DIN rail meter or a metered socket, the values are read from input
+    /// registers as 32-bit big-endian floats, the convention of most meters, the default registers are those of the
+    /// Eastron SDM series.
+    Modbus {
+        address: SocketAddr,
+        /// usually 1 for a meter that is not behind a gateway
+        unit_id: u8,
+        #[serde(default)]
+        registers: ModbusRegisters,
+    },
+}
+
+/// The addresses of the first of the two input registers of each value.
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ModbusRegisters {
+    pub volts: u16,
+    pub amps: u16,
+    pub watts: u16,
+}
+
+impl Default for ModbusRegisters {
+    fn default() -> Self {
+        Self {
+            volts: 0x0000,
+            amps: 0x0006,
+            watts: 0x000C,
+        }
+    }
+}
+
+/// An analog channel of the io board, the value of a reading is `fraction * scale + offset`, where the fraction is the
+/// raw value relative to the full scale of the ADC, 0.0-1.0, e.g. a 0-10 l/min flow sensor with a 0-3.3V output has
+/// a scale of 10.0.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct AnalogChannelDefinition {
+    /// the index of the channel, 0-7
+    pub channel: u8,
+    /// the index of the ADC input, the inputs are specific to the io board
+    pub input: u8,
+    /// at most 16 bytes
+    pub name: String,
+    /// at most 16 bytes
+    pub units: String,
+    pub scale: f32,
+    #[serde(default)]
+    pub offset: f32,
+    /// at least 10ms
+    pub sample_interval_ms: u32,
+}
+
+/// Pinging the nodes reachable through the router, for the diagnostics of the operator UI, see
+/// `networking::inspection`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct RouterInspectionConfig {
+    /// the nodes are discovered, pinged and reported at this interval
+    pub interval_ms: u64,
+    /// a ping that is not answered within this time is lost
+    pub ping_timeout_ms: u64,
+}
+
+impl Default for RouterInspectionConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 2000,
+            ping_timeout_ms: 500,
+        }
+    }
+}
+
+/// Remote operator UIs, outside the machine network, see `bridge`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct BridgeConfig {
+    /// `None` to disable the bridge
+    pub listen: Option<SocketAddr>,
+    /// sent by each client before anything is forwarded, see `bridge::BridgeHello`, required unless `listen` is a
+    /// loopback address
+    pub token: Option<String>,
+    /// paths of the topics sent to the clients, the same patterns as the topic tap
+    pub topics: Vec<String>,
+    /// names of the operator commands the clients may send, e.g. `FetchMachineGeometry`, `*` allows every command
+    pub commands: Vec<String>,
+    /// only the allowed commands that don't change the machine are forwarded
+    pub read_only: bool,
+    /// further connections are refused
+    pub max_clients: usize,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            listen: None,
+            token: None,
+            topics: vec![],
+            commands: vec![],
+            read_only: true,
+            max_clients: 4,
+        }
+    }
+}
+
+/// A router network of the server, on its own interface, see `segments`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct NetworkSegmentDefinition {
+    /// unique, used in the logs
+    pub name: String,
+    /// the address of the interface of the segment, e.g. of a NIC for the cameras, the port must not be used by the
+    /// control network or by another segment
+    pub local_addr: SocketAddr,
+    pub remote_addr: SocketAddr,
+    /// paths of the topics forwarded from the control network to the segment, the same patterns as the topic tap
+    #[serde(default)]
+    pub export: Vec<String>,
+    /// paths of the topics forwarded from the segment to the control network
+    #[serde(default)]
+    pub import: Vec<String>,
+    /// the operator commands are served on the segment, e.g. the camera streams of an operator UI on the segment
+    #[serde(default)]
+    pub operator_commands: bool,
+}
+
+/// Restarting the long-running tasks of the server when they fail, see `supervisor::Supervisor`.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SupervisorConfig {
+    /// the delay before the first restart, doubled for each further restart until the task has not been restarted
+    /// within the window
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// a task that fails again after this many restarts within the window is not restarted, the machine faults
+    pub max_restarts: u32,
+    pub restart_window_s: u64,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            max_restarts: 5,
+            restart_window_s: 300,
+        }
+    }
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct IoBoardDefinition {
     connection: ConnectionKind,
+    /// sent to the io board, see `motion::commands::planning_configurator`, every io board must have the same planning
+    /// until the server can tell the io boards apart
+    #[serde(default)]
+    pub planning: MotionPlanning,
+}
+
+/// Where the trajectories for the axes of an io board are planned, the io board switches to it at runtime.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum MotionPlanning {
+    /// The io board plans its own trajectories.
+    #[default]
+    OnBoard,
+    /// The server plans the trajectories and streams position setpoints to the io board, for io boards that are too
+    /// small to run the trajectory generator.
+    Server {
+        /// setpoint rate, the io board interpolates between setpoints
+        rate_hz: u32,
+    },
+}
+
+/// The motion of the axes while no job, routine or operator moves them, see `motion::setpoint_streamer` and
+/// `motion::commands::move_commander`.
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct MotionConfig {
+    /// repeated with a pause after the last move, e.g. for running in a new axis, empty to not move the axes, the parking
+    /// and the jobs are disabled while the axes follow it, they would move the same axes
+    pub repeated_trajectory: Vec<TrajectoryMove>,
+}
+
+/// A move of the rotation axis, in degrees, from rest to rest.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct TrajectoryMove {
+    /// absolute position
+    pub target: f64,
+    /// in degrees/s
+    pub max_velocity: f64,
+    /// in degrees/s²
+    pub max_acceleration: f64,
+    /// in degrees/s³
+    pub max_jerk: f64,
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
@@ -0,0 +1,50 @@
+//! The coordinate transform layer between job coordinates and machine axes.
+//!
+//! Job coordinates are where the head should be, machine coordinates are what the axes are commanded to, both in
+//! millimeters.  The difference is the geometry of the machine, measured by the accuracy routine, see
+//! [`AxisCorrections`].  The points and transforms are those of [`machine_geometry`].
+
+use machine_geometry::{AffineTransform, Point};
+
+use crate::config::{AxisCorrections, LinearCorrection};
+
+#[cfg(test)]
+mod tests;
+
+/// The scale and offset corrections of the axes.
+pub fn axes_transform(x: &LinearCorrection, y: &LinearCorrection) -> AffineTransform {
+    AffineTransform::scaled(x.scale, y.scale, Point {
+        x: x.offset,
+        y: y.offset,
+    })
+}
+
+/// Converts job coordinates to machine coordinates.
+///
+/// The skew is removed first, then the per-axis corrections are applied, since the scale and offset errors are errors
+/// of the individual axes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoordinateTransform {
+    job_to_machine: AffineTransform,
+}
+
+impl CoordinateTransform {
+    pub fn new(corrections: &AxisCorrections) -> Self {
+        let axes = axes_transform(&corrections.x, &corrections.y);
+        let deskew = AffineTransform::deskewed(corrections.skew_degrees);
+
+        Self {
+            job_to_machine: axes.after(&deskew),
+        }
+    }
+
+    pub fn job_to_machine(&self, point: Point) -> Point {
+        self.job_to_machine.apply(point)
+    }
+}
+
+impl Default for CoordinateTransform {
+    fn default() -> Self {
+        Self::new(&AxisCorrections::default())
+    }
+}
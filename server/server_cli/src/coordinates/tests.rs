@@ -0,0 +1,110 @@
+use machine_geometry::{AffineTransform, Point};
+
+use super::{CoordinateTransform, axes_transform};
+use crate::config::{AxisCorrections, LinearCorrection};
+
+fn assert_near(actual: Point, expected: Point) {
+    assert!(
+        actual.distance(&expected) < 1e-9,
+        "actual: {:?}, expected: {:?}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+pub fn default_transform_is_identity() {
+    // given
+    let transform = CoordinateTransform::default();
+    let point = Point {
+        x: 12.5,
+        y: -3.0,
+    };
+
+    // expect
+    assert_eq!(transform.job_to_machine(point), point);
+}
+
+#[test]
+pub fn skew_angle_ignores_scale_and_offset() {
+    // given
+    let axis_errors = axes_transform(
+        &LinearCorrection {
+            scale: 1.01,
+            offset: 3.0,
+        },
+        &LinearCorrection {
+            scale: 0.98,
+            offset: -2.0,
+        },
+    );
+    let geometry = AffineTransform::skewed(0.25).after(&axis_errors);
+
+    // expect
+    assert!((geometry.skew_degrees() - 0.25).abs() < 1e-9);
+}
+
+#[test]
+pub fn deskew_cancels_skewed_geometry() {
+    // given
+    let geometry = AffineTransform::skewed(0.3);
+    let transform = CoordinateTransform::new(&AxisCorrections {
+        skew_degrees: 0.3,
+        ..AxisCorrections::default()
+    });
+    let target = Point {
+        x: 100.0,
+        y: 250.0,
+    };
+
+    // when
+    let machine = transform.job_to_machine(target);
+
+    // then
+    assert_near(geometry.apply(machine), target);
+}
+
+#[test]
+pub fn corrections_cancel_machine_geometry() {
+    // given
+    // the axes have scale and offset errors, and the Y axis is skewed
+    let x = LinearCorrection {
+        scale: 1.002,
+        offset: 0.1,
+    };
+    let y = LinearCorrection {
+        scale: 0.997,
+        offset: -0.2,
+    };
+    let geometry = AffineTransform::skewed(-0.15).after(&axes_transform(&x, &y));
+
+    let transform = CoordinateTransform::new(&AxisCorrections {
+        x: LinearCorrection {
+            scale: 1.0 / x.scale,
+            offset: -x.offset / x.scale,
+        },
+        y: LinearCorrection {
+            scale: 1.0 / y.scale,
+            offset: -y.offset / y.scale,
+        },
+        skew_degrees: -0.15,
+    });
+
+    // expect
+    for target in [
+        Point {
+            x: 0.0,
+            y: 0.0,
+        },
+        Point {
+            x: 300.0,
+            y: 0.0,
+        },
+        Point {
+            x: 150.0,
+            y: 400.0,
+        },
+    ] {
+        assert_near(geometry.apply(transform.job_to_machine(target)), target);
+    }
+}
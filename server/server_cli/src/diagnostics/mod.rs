@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{FrameKind, endpoint, topic};
+use ergot_util::ClientWrapper;
+use log::{debug, error, info, warn};
+use operator_shared::diagnostics::{CommandLatencyReport, LATENCY_BUCKET_COUNT, LATENCY_BUCKET_LIMITS_US};
+use retry::{Backoff, BackoffConfig, retry_async};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::time::{self, Instant};
+
+use crate::AppEvent;
+use crate::config::CommandLatencyConfig;
+
+pub mod tap;
+
+#[cfg(test)]
+mod tests;
+
+endpoint!(LatencyProbeEndpoint, u32, u32, "topic/ioboard/latency-probe");
+topic!(
+    CommandLatencyTopic,
+    CommandLatencyReport,
+    "topic/diagnostics/command-latency"
+);
+
+/// The io board may not have started yet, or may be restarting.
+const DISCOVERY_BACKOFF: BackoffConfig = BackoffConfig {
+    initial: Duration::from_secs(1),
+    max: Duration::from_secs(10),
+    multiplier: 2,
+    jitter: 0.5,
+    max_elapsed: None,
+};
+
+/// The p99 is meaningless with only a few samples, so the alarm is not raised until there are at least this many.
+const MIN_ALARM_SAMPLES: usize = 100;
+
+/// Rolling window of command path round-trip latencies.
+pub struct LatencyWindow {
+    capacity: usize,
+    /// `None` for a lost probe
+    samples: VecDeque<Option<Duration>>,
+}
+
+impl LatencyWindow {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            samples: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn record(&mut self, latency: Option<Duration>) {
+        if self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency);
+    }
+
+    /// Lost probes are counted as `timeout` for the percentiles, a link that drops probes is at least as degraded as
+    /// a slow one.
+    pub fn report(&self, timeout: Duration, p99_limit: Duration) -> CommandLatencyReport {
+        let mut latencies = self
+            .samples
+            .iter()
+            .map(|sample| sample.unwrap_or(timeout))
+            .collect::<Vec<_>>();
+        latencies.sort();
+
+        let lost = self
+            .samples
+            .iter()
+            .filter(|sample| sample.is_none())
+            .count();
+
+        let mut buckets = [0_u32; LATENCY_BUCKET_COUNT];
+        for latency in self.samples.iter().flatten() {
+            let latency_us = latency.as_micros();
+            let index = LATENCY_BUCKET_LIMITS_US
+                .iter()
+                .position(|limit| latency_us <= *limit as u128)
+                .unwrap_or(LATENCY_BUCKET_LIMITS_US.len());
+            buckets[index] += 1;
+        }
+
+        let p99 = percentile(&latencies, 0.99);
+
+        CommandLatencyReport {
+            samples: (self.samples.len() - lost) as u32,
+            lost: lost as u32,
+            p50_us: as_micros_u32(percentile(&latencies, 0.50)),
+            p99_us: as_micros_u32(p99),
+            max_us: as_micros_u32(
+                latencies
+                    .last()
+                    .copied()
+                    .unwrap_or_default(),
+            ),
+            buckets,
+            p99_limit_us: as_micros_u32(p99_limit),
+            alarm: latencies.len() >= MIN_ALARM_SAMPLES && p99 > p99_limit,
+        }
+    }
+}
+
+/// `sorted` must be sorted, returns zero when empty.
+fn percentile(sorted: &[Duration], percentile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (percentile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn as_micros_u32(duration: Duration) -> u32 {
+    duration
+        .as_micros()
+        .min(u32::MAX as u128) as u32
+}
+
+/// Probes the round-trip latency of the io board command path and publishes the statistics for the operator UI,
+/// raising an alarm when the p99 latency exceeds the configured limit.
+///
+/// This is an early warning for network degradation, before commands start to time out.
+pub async fn latency_monitor(stack: RouterStack, config: CommandLatencyConfig, app_event_rx: Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let query = SocketQuery {
+        key: LatencyProbeEndpoint::REQ_KEY.to_bytes(),
+        nash_req: NameRequirement::Any,
+        frame_kind: FrameKind::ENDPOINT_REQ,
+        broadcast: false,
+    };
+
+    let mut backoff = Backoff::from_time(DISCOVERY_BACKOFF);
+    let discover = {
+        let (stack, query) = (&stack, &query);
+        move || async move {
+            // TODO probe every io board, currently there is only one
+            let address = stack
+                .discovery()
+                .discover_sockets(4, Duration::from_secs(1), query)
+                .await
+                .first()
+                .map(|result| result.address);
+            if address.is_none() {
+                debug!("Latency probe endpoint not found, retrying");
+            }
+            address.ok_or(())
+        }
+    };
+    let address = select! {
+        _ = &mut app_shutdown_handler => {
+            info!("latency monitor shutdown");
+            return
+        }
+        address = retry_async(&mut backoff, discover) => match address {
+            Ok(address) => address,
+            // the backoff has no max elapsed time
+            Err(()) => return,
+        }
+    };
+    info!("Probing command latency, address: {:?}", address);
+
+    let timeout = Duration::from_millis(config.probe_timeout_ms);
+    let p99_limit = Duration::from_millis(config.p99_limit_ms);
+
+    let client = stack
+        .endpoints()
+        .client::<LatencyProbeEndpoint>(address, None);
+    let client = ClientWrapper::new(timeout, client);
+
+    let mut window = LatencyWindow::new(config.window);
+    let mut alarm = false;
+
+    let mut probe_ticker = time::interval(Duration::from_millis(config.probe_interval_ms));
+    probe_ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    let mut report_ticker = time::interval(Duration::from_secs(1));
+
+    let mut probe: u32 = 0;
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            _ = probe_ticker.tick() => {
+                let started_at = Instant::now();
+                let latency = match client.request(&probe).await {
+                    Ok(echo) if echo == probe => Some(started_at.elapsed()),
+                    Ok(echo) => {
+                        warn!("Unexpected latency probe response, expected: {}, received: {}", probe, echo);
+                        None
+                    }
+                    Err(e) => {
+                        debug!("Latency probe lost, probe: {}, error: {:?}", probe, e);
+                        None
+                    }
+                };
+                window.record(latency);
+                probe = probe.wrapping_add(1);
+            }
+            _ = report_ticker.tick() => {
+                let report = window.report(timeout, p99_limit);
+
+                if report.alarm != alarm {
+                    alarm = report.alarm;
+                    match alarm {
+                        true => error!("Command latency alarm raised, p99: {}us, limit: {}us, lost: {}", report.p99_us, report.p99_limit_us, report.lost),
+                        false => info!("Command latency alarm cleared, p99: {}us", report.p99_us),
+                    }
+                }
+
+                if let Err(e) = stack
+                    .topics()
+                    .broadcast::<CommandLatencyTopic>(&report, None)
+                {
+                    debug!("Unable to publish command latency report, error: {:?}", e);
+                }
+            }
+        }
+    }
+    info!("latency monitor shutdown");
+}
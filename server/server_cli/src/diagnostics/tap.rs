@@ -0,0 +1,329 @@
+//! Dumps the messages of topics for diagnostics, e.g. to see the setpoints sent to an io board without capturing the
+//! network traffic.
+//!
+//! The messages of the topics matching the configured patterns are decoded and written to the log, or to a file, until
+//! the configured duration has passed or the operator stops the tap.
+
+use std::fmt::Debug;
+use std::pin::{Pin, pin};
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Topic;
+use log::{error, info, warn};
+use operator_shared::diagnostics::TopicTapError;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+
+use crate::AppEvent;
+#[cfg(feature = "machine-vision")]
+use crate::camera::budget::CameraMemoryTopic;
+use crate::config::TopicTapConfig;
+use crate::diagnostics::CommandLatencyTopic;
+use crate::feeders::{FeederEventTopic, FeedersStatusTopic};
+use crate::homing::HomingStatusTopic;
+use crate::ioboard::{BatchTopic, IoBoardCommandTopic, IoBoardEventTopic};
+use crate::job::JobEventTopic;
+use crate::limits::LimitOverrideTopic;
+use crate::motion::{PositionTopic, SetpointTopic};
+use crate::networking::LoadCellTopic;
+use crate::networking::inspection::RouterReportTopic;
+use crate::nozzles::MaintenanceTopic;
+use crate::power::PowerTopic;
+use crate::readiness::{ReadinessTopic, SelfTestTopic};
+use crate::resonance::ResonanceSweepTopic;
+use crate::safety::SafetyTopic;
+use crate::test_area::TestShotEventTopic;
+#[cfg(feature = "machine-vision")]
+use crate::vision::VisionStatusTopic;
+
+/// Messages of a topic are dropped when the writer falls behind by more than this.
+const TAP_QUEUE_SIZE: usize = 256;
+
+type TapFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A pattern of topic paths, `*` matches a single segment of a path, and a trailing `**` matches the rest of a path,
+/// e.g. `topic/ioboard/**` matches every io board topic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicPattern {
+    segments: Vec<String>,
+}
+
+impl TopicPattern {
+    pub fn parse(pattern: &str) -> Result<Self, TopicTapError> {
+        let segments = pattern
+            .trim()
+            .split('/')
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+
+        let last = segments.len() - 1;
+        let valid = segments
+            .iter()
+            .enumerate()
+            .all(|(index, segment)| !segment.is_empty() && (segment != "**" || index == last));
+        if !valid {
+            return Err(TopicTapError::InvalidPattern(pattern.to_string()));
+        }
+
+        Ok(Self {
+            segments,
+        })
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let mut path_segments = path.split('/');
+        for segment in &self.segments {
+            let path_segment = path_segments.next();
+            match (segment.as_str(), path_segment) {
+                (_, None) => return false,
+                ("**", Some(_)) => return true,
+                ("*", Some(_)) => {}
+                (literal, Some(path_segment)) if literal == path_segment => {}
+                _ => return false,
+            }
+        }
+        path_segments.next().is_none()
+    }
+}
+
+/// A topic that can be tapped, the messages are decoded using the message type of the topic.
+pub struct TappableTopic {
+    pub path: &'static str,
+    tap: fn(RouterStack, mpsc::Sender<String>, CancellationToken) -> TapFuture,
+}
+
+impl TappableTopic {
+    fn of<T>() -> Self
+    where
+        T: Topic + 'static,
+        T::Message: Debug + Serialize + DeserializeOwned + Clone + Send + 'static,
+    {
+        Self {
+            path: T::PATH,
+            tap: |stack, lines_tx, cancel| Box::pin(tap_topic::<T>(stack, lines_tx, cancel)),
+        }
+    }
+}
+
+/// The topics of the server that can be tapped.
+///
+/// The camera stream is left out, the frames are too large to be dumped.
+pub fn topic_registry() -> Vec<TappableTopic> {
+    vec![
+        TappableTopic::of::<LoadCellTopic>(),
+        TappableTopic::of::<CommandLatencyTopic>(),
+        TappableTopic::of::<RouterReportTopic>(),
+        TappableTopic::of::<IoBoardCommandTopic>(),
+        TappableTopic::of::<BatchTopic>(),
+        TappableTopic::of::<IoBoardEventTopic>(),
+        TappableTopic::of::<SetpointTopic>(),
+        TappableTopic::of::<PositionTopic>(),
+        TappableTopic::of::<SafetyTopic>(),
+        TappableTopic::of::<SelfTestTopic>(),
+        TappableTopic::of::<ReadinessTopic>(),
+        TappableTopic::of::<HomingStatusTopic>(),
+        TappableTopic::of::<LimitOverrideTopic>(),
+        TappableTopic::of::<ResonanceSweepTopic>(),
+        TappableTopic::of::<JobEventTopic>(),
+        TappableTopic::of::<FeedersStatusTopic>(),
+        TappableTopic::of::<FeederEventTopic>(),
+        TappableTopic::of::<MaintenanceTopic>(),
+        TappableTopic::of::<TestShotEventTopic>(),
+        TappableTopic::of::<PowerTopic>(),
+        #[cfg(feature = "machine-vision")]
+        TappableTopic::of::<CameraMemoryTopic>(),
+        #[cfg(feature = "machine-vision")]
+        TappableTopic::of::<VisionStatusTopic>(),
+    ]
+}
+
+/// At most one tap runs at a time, started and stopped by the operator.
+pub struct TopicTap {
+    config: TopicTapConfig,
+    /// cancelled when the tap has stopped
+    running: Option<CancellationToken>,
+}
+
+impl TopicTap {
+    pub fn new(config: TopicTapConfig) -> Self {
+        Self {
+            config,
+            running: None,
+        }
+    }
+
+    /// Returns the topics that match the configured patterns, the tap runs until the token is cancelled, see
+    /// [`topic_tap_runner`].
+    pub fn start(&mut self) -> Result<(Vec<TappableTopic>, CancellationToken), TopicTapError> {
+        if self.is_running() {
+            return Err(TopicTapError::Running);
+        }
+        if self.config.patterns.is_empty() {
+            return Err(TopicTapError::NotConfigured);
+        }
+
+        let patterns = self
+            .config
+            .patterns
+            .iter()
+            .map(|pattern| TopicPattern::parse(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let topics = topic_registry()
+            .into_iter()
+            .filter(|topic| {
+                patterns
+                    .iter()
+                    .any(|pattern| pattern.matches(topic.path))
+            })
+            .collect::<Vec<_>>();
+        if topics.is_empty() {
+            return Err(TopicTapError::NoMatchingTopics);
+        }
+
+        let cancel = CancellationToken::new();
+        self.running = Some(cancel.clone());
+        Ok((topics, cancel))
+    }
+
+    /// Returns `false` if the tap was not running.
+    pub fn stop(&mut self) -> bool {
+        let running = self.is_running();
+        if let Some(cancel) = self.running.take() {
+            cancel.cancel();
+        }
+        running
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+            .as_ref()
+            .is_some_and(|cancel| !cancel.is_cancelled())
+    }
+
+    pub fn config(&self) -> &TopicTapConfig {
+        &self.config
+    }
+}
+
+async fn tap_topic<T>(stack: RouterStack, lines_tx: mpsc::Sender<String>, cancel: CancellationToken)
+where
+    T: Topic,
+    T::Message: Debug + Serialize + DeserializeOwned + Clone + Send + 'static,
+{
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<T>(TAP_QUEUE_SIZE, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            _ = cancel.cancelled() => {
+                break
+            }
+            msg = hdl.recv() => {
+                let line = format!("{} {}: {:?}", T::PATH, msg.hdr, msg.t);
+                if lines_tx.send(line).await.is_err() {
+                    break
+                }
+            }
+        }
+    }
+}
+
+/// Writes the decoded messages of the topics until the duration has passed, the token is cancelled, or the server
+/// shuts down.
+pub async fn topic_tap_runner(
+    stack: RouterStack,
+    topics: Vec<TappableTopic>,
+    config: TopicTapConfig,
+    cancel: CancellationToken,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let mut file = match &config.file {
+        Some(path) => match File::create(path).await {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(e) => {
+                error!("Unable to create topic tap file. path: {:?}, error: {:?}", path, e);
+                cancel.cancel();
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let paths = topics
+        .iter()
+        .map(|topic| topic.path)
+        .collect::<Vec<_>>();
+    info!(
+        "Topic tap started. topics: {:?}, duration: {}s, file: {:?}",
+        paths, config.duration_s, config.file
+    );
+
+    let (lines_tx, mut lines_rx) = mpsc::channel(TAP_QUEUE_SIZE);
+    let tap_handles = topics
+        .iter()
+        .map(|topic| tokio::spawn((topic.tap)(stack.clone(), lines_tx.clone(), cancel.clone())))
+        .collect::<Vec<_>>();
+    drop(lines_tx);
+
+    let deadline = time::sleep(Duration::from_secs(config.duration_s));
+    let mut deadline = pin!(deadline);
+
+    let mut messages: u64 = 0;
+    loop {
+        select! {
+            _ = &mut deadline => {
+                info!("Topic tap duration elapsed. duration: {}s", config.duration_s);
+                break
+            }
+            _ = cancel.cancelled() => {
+                break
+            }
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            line = lines_rx.recv() => {
+                let Some(line) = line else {
+                    break
+                };
+                messages += 1;
+                match &mut file {
+                    Some(file) => {
+                        let line = format!("{} {}\n", chrono::Local::now().to_rfc3339(), line);
+                        if let Err(e) = file.write_all(line.as_bytes()).await {
+                            warn!("Unable to write topic tap file, stopping. error: {:?}", e);
+                            break
+                        }
+                    }
+                    None => info!("Topic tap. {}", line),
+                }
+            }
+        }
+    }
+
+    cancel.cancel();
+    for handle in tap_handles {
+        let _ = handle.await;
+    }
+    let flushed = match &mut file {
+        Some(file) => file.flush().await,
+        None => Ok(()),
+    };
+    if let Err(e) = flushed {
+        warn!("Unable to flush topic tap file. error: {:?}", e);
+    }
+    info!("Topic tap stopped. messages: {}", messages);
+}
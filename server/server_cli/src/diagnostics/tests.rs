@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use operator_shared::diagnostics::TopicTapError;
+
+use super::LatencyWindow;
+use super::tap::{TopicPattern, TopicTap};
+use crate::config::TopicTapConfig;
+
+#[test]
+pub fn latency_window_percentiles() {
+    // given
+    let mut window = LatencyWindow::new(100);
+    for ms in 1..=100 {
+        window.record(Some(Duration::from_millis(ms)));
+    }
+
+    // when
+    let report = window.report(Duration::from_millis(500), Duration::from_millis(200));
+
+    // then
+    assert_eq!(report.samples, 100);
+    assert_eq!(report.lost, 0);
+    assert_eq!(report.p50_us, 50_000);
+    assert_eq!(report.p99_us, 99_000);
+    assert_eq!(report.max_us, 100_000);
+    assert_eq!(report.buckets.iter().sum::<u32>(), 100);
+    assert!(!report.alarm);
+}
+
+#[test]
+pub fn latency_window_alarm_on_lost_probes() {
+    // given
+    let mut window = LatencyWindow::new(200);
+    for _ in 0..195 {
+        window.record(Some(Duration::from_millis(1)));
+    }
+    for _ in 0..5 {
+        window.record(None);
+    }
+
+    // when
+    let report = window.report(Duration::from_millis(500), Duration::from_millis(20));
+
+    // then
+    assert_eq!(report.samples, 195);
+    assert_eq!(report.lost, 5);
+    assert_eq!(report.p99_us, 500_000);
+    assert!(report.alarm);
+}
+
+#[test]
+pub fn latency_window_is_rolling() {
+    // given
+    let mut window = LatencyWindow::new(10);
+    for _ in 0..10 {
+        window.record(Some(Duration::from_millis(100)));
+    }
+
+    // when
+    for _ in 0..10 {
+        window.record(Some(Duration::from_millis(1)));
+    }
+    let report = window.report(Duration::from_millis(500), Duration::from_millis(20));
+
+    // then
+    assert_eq!(report.max_us, 1_000);
+}
+
+#[test]
+pub fn topic_pattern_matches() {
+    // given
+    let namespace = TopicPattern::parse("topic/ioboard/**").unwrap();
+    let wildcard = TopicPattern::parse("topic/*/job").unwrap();
+
+    // expect
+    assert!(namespace.matches("topic/ioboard/safety"));
+    assert!(namespace.matches("topic/ioboard/motion/setpoint"));
+    assert!(!namespace.matches("topic/ioboard"));
+    assert!(!namespace.matches("topic/operator/job"));
+    assert!(wildcard.matches("topic/operator/job"));
+    assert!(!wildcard.matches("topic/operator/job/extra"));
+    assert!(!wildcard.matches("topic/operator/feeders"));
+}
+
+#[test]
+pub fn topic_pattern_invalid() {
+    // expect
+    assert_eq!(
+        TopicPattern::parse(""),
+        Err(TopicTapError::InvalidPattern("".to_string()))
+    );
+    assert_eq!(
+        TopicPattern::parse("topic//job"),
+        Err(TopicTapError::InvalidPattern("topic//job".to_string()))
+    );
+    assert_eq!(
+        TopicPattern::parse("topic/**/job"),
+        Err(TopicTapError::InvalidPattern("topic/**/job".to_string()))
+    );
+}
+
+#[test]
+pub fn topic_tap_selects_matching_topics() {
+    // given
+    let mut topic_tap = TopicTap::new(TopicTapConfig {
+        patterns: vec!["topic/ioboard/**".to_string(), "topic/operator/job".to_string()],
+        ..TopicTapConfig::default()
+    });
+
+    // when
+    let (topics, _cancel) = topic_tap.start().unwrap();
+
+    // then
+    let paths = topics
+        .iter()
+        .map(|topic| topic.path)
+        .collect::<Vec<_>>();
+    assert!(paths.contains(&"topic/ioboard/safety"));
+    assert!(paths.contains(&"topic/ioboard/motion/setpoint"));
+    assert!(paths.contains(&"topic/operator/job"));
+    assert!(!paths.contains(&"topic/operator/feeders"));
+    assert!(topic_tap.is_running());
+}
+
+#[test]
+pub fn topic_tap_refused() {
+    // given
+    let mut not_configured = TopicTap::new(TopicTapConfig::default());
+    let mut no_matching_topics = TopicTap::new(TopicTapConfig {
+        patterns: vec!["topic/unknown/**".to_string()],
+        ..TopicTapConfig::default()
+    });
+
+    // expect
+    assert_eq!(not_configured.start().err(), Some(TopicTapError::NotConfigured));
+    assert_eq!(no_matching_topics.start().err(), Some(TopicTapError::NoMatchingTopics));
+}
+
+#[test]
+pub fn topic_tap_restarted_after_stop() {
+    // given
+    let mut topic_tap = TopicTap::new(TopicTapConfig {
+        patterns: vec!["topic/ioboard/**".to_string()],
+        ..TopicTapConfig::default()
+    });
+    let (_topics, cancel) = topic_tap.start().unwrap();
+    assert_eq!(topic_tap.start().err(), Some(TopicTapError::Running));
+
+    // when
+    let stopped = topic_tap.stop();
+
+    // then
+    assert!(stopped);
+    assert!(cancel.is_cancelled());
+    assert!(!topic_tap.stop());
+    assert!(topic_tap.start().is_ok());
+}
@@ -0,0 +1,203 @@
+//! Paste and glue dispensing with a dispenser head, see [`HeadKind::Dispenser`].
+//!
+//! A dispense cycle is timed by the server: the pressure is applied for the pre-pressure time, so that the material is
+//! flowing when the needle reaches the board, and for the dispense time, then the dispenser is retracted to stop the
+//! flow.  The io board switches the outputs off by itself if they stay on for too long.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind};
+use ergot_util::ClientWrapper;
+use ioboard_shared::dispenser::DispenserRequest;
+use log::{debug, info};
+use tokio::time;
+
+use crate::config::{DispenserConfig, HeadDefinition, HeadKind};
+use crate::ioboard::{CommandSequencer, DispenserEndpoint};
+use crate::job::{Operation, Placement, Placer};
+use crate::networking::dead_letter;
+
+#[cfg(test)]
+mod tests;
+
+const DISPENSER_REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+const DISPENSER_REQUEST_ATTEMPTS: u32 = 3;
+
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait Dispenser {
+    fn set_pressure<'a>(&'a mut self, on: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+
+    /// Starting the retract stops the pressure.
+    fn set_retract<'a>(&'a mut self, on: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+}
+
+/// The config of the dispenser head with the `name`, `None` if there is no such head or it is not a dispenser.
+pub fn dispenser_config<'a>(heads: &'a [HeadDefinition], name: &str) -> Option<&'a DispenserConfig> {
+    heads
+        .iter()
+        .filter(|head| head.name == name)
+        .find_map(|head| match &head.kind {
+            HeadKind::Dispenser(config) => Some(config),
+        })
+}
+
+/// Pre-pressure, dispense for `dispense_ms`, then retract, the needle must already be lowered to the board.
+pub async fn run_dispense_cycle<D: Dispenser>(
+    dispenser: &mut D,
+    config: &DispenserConfig,
+    dispense_ms: u64,
+) -> anyhow::Result<()> {
+    dispenser.set_pressure(true).await?;
+    time::sleep(Duration::from_millis(config.pre_pressure_ms)).await;
+    time::sleep(Duration::from_millis(dispense_ms)).await;
+
+    dispenser.set_retract(true).await?;
+    time::sleep(Duration::from_millis(config.retract_ms)).await;
+    dispenser.set_retract(false).await
+}
+
+/// Uses the dispenser outputs of the io board.
+pub struct IoBoardDispenser {
+    stack: RouterStack,
+    sequencer: Arc<CommandSequencer>,
+    /// discovered on the first request
+    address: Option<Address>,
+}
+
+impl IoBoardDispenser {
+    pub fn new(stack: RouterStack, sequencer: Arc<CommandSequencer>) -> Self {
+        Self {
+            stack,
+            sequencer,
+            address: None,
+        }
+    }
+
+    async fn request(&mut self, request: DispenserRequest) -> anyhow::Result<()> {
+        let address = match self.address {
+            Some(address) => address,
+            None => {
+                let query = SocketQuery {
+                    key: DispenserEndpoint::REQ_KEY.to_bytes(),
+                    nash_req: NameRequirement::Any,
+                    frame_kind: FrameKind::ENDPOINT_REQ,
+                    broadcast: false,
+                };
+                // TODO select the io board of the head, currently there is only one
+                let result = self
+                    .stack
+                    .discovery()
+                    .discover_sockets(4, DISPENSER_REQUEST_TIMEOUT, &query)
+                    .await
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("No io board with a dispenser found"))?;
+                self.address = Some(result.address);
+                result.address
+            }
+        };
+
+        let client = self
+            .stack
+            .endpoints()
+            .client::<DispenserEndpoint>(address, None);
+        let client = ClientWrapper::new(DISPENSER_REQUEST_TIMEOUT, client);
+        let sequenced = self.sequencer.sequenced(request);
+        match client
+            .request_with_retry(&sequenced, DISPENSER_REQUEST_ATTEMPTS)
+            .await
+        {
+            Ok(Ok(status)) => {
+                debug!("Dispenser request done. request: {:?}, status: {:?}", request, status);
+                Ok(())
+            }
+            Ok(Err(e)) => bail!("Dispenser request refused. request: {:?}, error: {:?}", request, e),
+            Err(e) => {
+                dead_letter::request_failed::<DispenserEndpoint>(address, DISPENSER_REQUEST_ATTEMPTS, &e);
+                // the io board may have restarted with a different address
+                self.address = None;
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Dispenser for IoBoardDispenser {
+    fn set_pressure<'a>(&'a mut self, on: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        let request = match on {
+            true => DispenserRequest::StartPressure,
+            false => DispenserRequest::StopPressure,
+        };
+        self.request(request)
+    }
+
+    fn set_retract<'a>(&'a mut self, on: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        let request = match on {
+            true => DispenserRequest::StartRetract,
+            false => DispenserRequest::StopRetract,
+        };
+        self.request(request)
+    }
+}
+
+/// Runs a dispense cycle for dispense operations, once the `placer` has moved the needle to the position of the
+/// operation, other placements are placed by the `placer`.
+pub struct DispensingPlacer<P: Placer, D: Dispenser> {
+    placer: P,
+    dispenser: D,
+    heads: Vec<HeadDefinition>,
+}
+
+impl<P: Placer, D: Dispenser> DispensingPlacer<P, D> {
+    pub fn new(placer: P, dispenser: D, heads: &[HeadDefinition]) -> Self {
+        Self {
+            placer,
+            dispenser,
+            heads: heads.to_vec(),
+        }
+    }
+}
+
+impl<P: Placer + Send, D: Dispenser + Send> Placer for DispensingPlacer<P, D> {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let Operation::Dispense {
+                head,
+                dispense_ms,
+            } = &placement.operation
+            else {
+                return self.placer.place(placement).await;
+            };
+
+            let config =
+                dispenser_config(&self.heads, head).ok_or_else(|| anyhow!("Unknown dispenser head. head: {}", head))?;
+            let dispense_ms = dispense_ms.unwrap_or(config.dispense_ms);
+            self.placer.place(placement).await?;
+            // nothing is dispensed in a dry run, the placer has logged the operation
+            if self.placer.dry_run() {
+                return Ok(());
+            }
+
+            info!(
+                "Dispensing. placement: {}, head: {}, mechanism: {:?}, position: {:?}, dispense_ms: {}",
+                placement.reference, head, config.mechanism, placement.position, dispense_ms
+            );
+            run_dispense_cycle(&mut self.dispenser, config, dispense_ms).await
+        }
+    }
+
+    fn skips(&self, placement: &Placement) -> bool {
+        self.placer.skips(placement)
+    }
+
+    fn dry_run(&self) -> bool {
+        self.placer.dry_run()
+    }
+}
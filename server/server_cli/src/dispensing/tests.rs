@@ -0,0 +1,231 @@
+use std::future::Future;
+use std::time::Duration;
+
+use machine_geometry::Point;
+use tokio::time::Instant;
+
+use super::{Dispenser, DispensingPlacer, run_dispense_cycle};
+use crate::config::{DispenserConfig, DispenserMechanism, HeadDefinition, HeadKind};
+use crate::job::{Operation, Placement, Placer};
+
+#[derive(Debug, PartialEq)]
+enum DispenserCall {
+    Pressure(bool),
+    Retract(bool),
+}
+
+#[derive(Default)]
+struct FakeDispenser {
+    calls: Vec<(Instant, DispenserCall)>,
+}
+
+impl FakeDispenser {
+    fn calls(&self) -> Vec<&DispenserCall> {
+        self.calls
+            .iter()
+            .map(|(_, call)| call)
+            .collect()
+    }
+
+    /// The time from the pressure being applied to the retract being started.
+    fn pressure_duration(&self) -> Duration {
+        let started = self
+            .calls
+            .iter()
+            .find(|(_, call)| *call == DispenserCall::Pressure(true))
+            .unwrap()
+            .0;
+        let retracted = self
+            .calls
+            .iter()
+            .find(|(_, call)| *call == DispenserCall::Retract(true))
+            .unwrap()
+            .0;
+        retracted - started
+    }
+}
+
+impl Dispenser for FakeDispenser {
+    fn set_pressure<'a>(&'a mut self, on: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.calls
+                .push((Instant::now(), DispenserCall::Pressure(on)));
+            Ok(())
+        }
+    }
+
+    fn set_retract<'a>(&'a mut self, on: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.calls
+                .push((Instant::now(), DispenserCall::Retract(on)));
+            Ok(())
+        }
+    }
+}
+
+#[derive(Default)]
+struct FakePlacer {
+    placed: Vec<String>,
+    dry_run: bool,
+}
+
+impl Placer for FakePlacer {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.placed
+                .push(placement.reference.clone());
+            Ok(())
+        }
+    }
+
+    fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+}
+
+fn config(dispense_ms: u64) -> DispenserConfig {
+    DispenserConfig {
+        mechanism: DispenserMechanism::Auger,
+        pre_pressure_ms: 1,
+        dispense_ms,
+        retract_ms: 1,
+    }
+}
+
+fn heads(dispense_ms: u64) -> Vec<HeadDefinition> {
+    vec![HeadDefinition {
+        name: "paste".to_string(),
+        kind: HeadKind::Dispenser(config(dispense_ms)),
+    }]
+}
+
+fn placement(reference: &str, operation: Operation) -> Placement {
+    Placement {
+        reference: reference.to_string(),
+        position: Point {
+            x: 1.0,
+            y: 2.0,
+        },
+        rotation: 0.0,
+        feeder: None,
+        operation,
+        board: None,
+    }
+}
+
+#[tokio::test]
+pub async fn dispense_cycle_applies_pressure_then_retracts() {
+    // given
+    let mut dispenser = FakeDispenser::default();
+
+    // when
+    let result = run_dispense_cycle(&mut dispenser, &config(1), 1).await;
+
+    // then
+    assert!(result.is_ok());
+    assert_eq!(dispenser.calls(), vec![
+        &DispenserCall::Pressure(true),
+        &DispenserCall::Retract(true),
+        &DispenserCall::Retract(false),
+    ]);
+}
+
+#[tokio::test]
+pub async fn dispense_operation_uses_head_dispense_time() {
+    // given
+    let mut placer = DispensingPlacer::new(FakePlacer::default(), FakeDispenser::default(), &heads(30));
+    let operation = Operation::Dispense {
+        head: "paste".to_string(),
+        dispense_ms: None,
+    };
+
+    // when
+    let result = placer
+        .place(&placement("D1", operation))
+        .await;
+
+    // then
+    assert!(result.is_ok());
+    assert!(placer.dispenser.pressure_duration() >= Duration::from_millis(31));
+
+    // and the needle is moved to the position by the inner placer
+    assert_eq!(placer.placer.placed, vec!["D1".to_string()]);
+}
+
+#[tokio::test]
+pub async fn dispense_operation_overrides_dispense_time() {
+    // given
+    let mut placer = DispensingPlacer::new(FakePlacer::default(), FakeDispenser::default(), &heads(1));
+    let operation = Operation::Dispense {
+        head: "paste".to_string(),
+        dispense_ms: Some(30),
+    };
+
+    // when
+    let result = placer
+        .place(&placement("D1", operation))
+        .await;
+
+    // then
+    assert!(result.is_ok());
+    assert!(placer.dispenser.pressure_duration() >= Duration::from_millis(31));
+}
+
+#[tokio::test]
+pub async fn place_operation_uses_inner_placer() {
+    // given
+    let mut placer = DispensingPlacer::new(FakePlacer::default(), FakeDispenser::default(), &heads(1));
+
+    // when
+    let result = placer
+        .place(&placement("R1", Operation::Place))
+        .await;
+
+    // then
+    assert!(result.is_ok());
+    assert_eq!(placer.placer.placed, vec!["R1".to_string()]);
+    assert!(placer.dispenser.calls.is_empty());
+}
+
+#[tokio::test]
+pub async fn unknown_head_fails() {
+    // given
+    let mut placer = DispensingPlacer::new(FakePlacer::default(), FakeDispenser::default(), &heads(1));
+    let operation = Operation::Dispense {
+        head: "glue".to_string(),
+        dispense_ms: None,
+    };
+
+    // when
+    let result = placer
+        .place(&placement("D1", operation))
+        .await;
+
+    // then
+    assert!(result.is_err());
+    assert!(placer.dispenser.calls.is_empty());
+}
+
+#[tokio::test]
+pub async fn nothing_is_dispensed_in_a_dry_run() {
+    // given
+    let inner = FakePlacer {
+        dry_run: true,
+        ..FakePlacer::default()
+    };
+    let mut placer = DispensingPlacer::new(inner, FakeDispenser::default(), &heads(1));
+    let operation = Operation::Dispense {
+        head: "paste".to_string(),
+        dispense_ms: None,
+    };
+
+    // when
+    let result = placer
+        .place(&placement("D1", operation))
+        .await;
+
+    // then
+    assert!(result.is_ok());
+    assert_eq!(placer.placer.placed, vec!["D1".to_string()]);
+    assert!(placer.dispenser.calls.is_empty());
+}
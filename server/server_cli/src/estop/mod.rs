@@ -0,0 +1,53 @@
+//! The e-stop broadcast to the io boards, see `ioboard_shared::estop`.
+//!
+//! The server broadcasts it when requested via the operator command endpoint, e.g. by an integration.  The operator UI
+//! broadcasts it directly, the server routes it to the io boards without handling it, the e-stops seen by the server
+//! are only logged.
+
+use std::pin::pin;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ioboard_shared::estop::{EStop, EStopTopic};
+use log::{info, warn};
+use operator_shared::estop::EStopError;
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+
+use crate::AppEvent;
+
+pub fn publish_estop(stack: &RouterStack, estop: EStop) -> Result<(), EStopError> {
+    stack
+        .topics()
+        .broadcast::<EStopTopic>(&estop, None)
+        .map_err(|e| {
+            warn!("Unable to broadcast e-stop. estop: {:?}, error: {:?}", estop, e);
+            EStopError::NotSent
+        })
+}
+
+/// Logs the e-stops and resets broadcast by the server and the operator UI, the io boards report whether they latched
+/// them, see `io_board_event_listener`.
+pub async fn estop_listener(stack: RouterStack, app_event_rx: Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<EStopTopic>(8, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                match msg.t {
+                    EStop::Stop(source) => warn!("E-stop broadcast. source: {:?}, from: {:?}", source, msg.hdr.src),
+                    EStop::Reset(source) => info!("E-stop reset broadcast. source: {:?}, from: {:?}", source, msg.hdr.src),
+                }
+            }
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+        }
+    }
+    info!("e-stop listener shutdown");
+}
@@ -0,0 +1,463 @@
+//! Feeder part counting.
+//!
+//! The remaining parts of each feeder are decremented on each pick and can be set by the operator, e.g. after loading
+//! a reel.  Low-stock and out-of-stock events are raised when the stock of a feeder changes.
+//!
+//! The count and low-stock threshold of the feeders can also be changed together, in a transaction, see
+//! [`Feeders::apply_config`].
+//!
+//! The orientation of the tape of a feeder whose part has a polarity mark is verified after the reel is loaded, see
+//! [`orientation`](crate::orientation), parts are not picked from a feeder with reversed tape.
+//!
+//! A smart feeder has an ID chip, the io board reports the identity of the feeder when it is inserted into a slot of
+//! the feeder bank, and the feeder definition with that identity is loaded, i.e. its part assignment and calibration,
+//! see [`Feeders::insert`].
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::topic;
+use log::{debug, info, warn};
+use machine_geometry::Point;
+use machine_ids::FeederId;
+use operator_shared::config::{ConfigChange, ConfigError, ConfigField, ConfigFieldError, FieldError};
+use operator_shared::feeders::{FeederError, FeederEvent, FeederStatus, FeedersStatus, Stock, TapeOrientation};
+use tokio::select;
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::Receiver;
+use tokio::time;
+
+use crate::AppEvent;
+use crate::config::{FeederCalibration, FeedersConfig, PolarityCorner};
+
+#[cfg(test)]
+mod tests;
+
+topic!(FeedersStatusTopic, FeedersStatus, "topic/operator/feeders");
+topic!(FeederEventTopic, FeederEvent, "topic/operator/feeder-events");
+
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Larger than the parts on any reel, a larger count or threshold is a typing error.
+pub const FEEDER_COUNT_MAX: u32 = 100_000;
+
+struct Feeder {
+    count: u32,
+    low_stock_threshold: u32,
+    /// the ID of the loaded reel
+    reel: Option<String>,
+    /// `None` if the part has no polarity mark
+    polarity_mark: Option<PolarityCorner>,
+    /// `None` if the part has no polarity mark
+    orientation: Option<TapeOrientation>,
+    /// `None` if the feeder has no ID chip
+    identity: Option<u64>,
+    calibration: Option<FeederCalibration>,
+    /// the nominal position of the first pocket, `None` if the parts are not picked by the machine
+    pick_position: Option<Point>,
+    /// the slot a smart feeder is inserted into
+    slot: Option<u8>,
+}
+
+impl Feeder {
+    fn stock(&self) -> Stock {
+        match self.count {
+            0 => Stock::Out,
+            count if count <= self.low_stock_threshold => Stock::Low,
+            _ => Stock::Ok,
+        }
+    }
+}
+
+pub struct Feeders {
+    /// by name
+    feeders: BTreeMap<FeederId, Feeder>,
+    /// raised since the last call to [`Feeders::take_events`]
+    events: Vec<FeederEvent>,
+}
+
+impl Feeders {
+    /// The counts are restored from `counts`, e.g. those of the checkpoint of an interrupted job, feeders without a
+    /// count are empty until the operator sets the count.
+    pub fn new(config: &FeedersConfig, counts: &BTreeMap<FeederId, u32>) -> Self {
+        let feeders = config
+            .feeders
+            .iter()
+            .map(|definition| {
+                let feeder = Feeder {
+                    count: counts
+                        .get(&definition.name)
+                        .copied()
+                        .unwrap_or(0),
+                    low_stock_threshold: definition
+                        .low_stock_threshold
+                        .unwrap_or(config.low_stock_threshold),
+                    reel: None,
+                    polarity_mark: definition.polarity_mark,
+                    orientation: definition
+                        .polarity_mark
+                        .map(|_| TapeOrientation::Unverified),
+                    identity: definition.identity,
+                    calibration: definition.calibration,
+                    pick_position: definition.pick_position,
+                    slot: None,
+                };
+                (definition.name.clone(), feeder)
+            })
+            .collect();
+
+        Self {
+            feeders,
+            events: vec![],
+        }
+    }
+
+    pub fn set_count(&mut self, name: &FeederId, count: u32) -> Result<(), FeederError> {
+        self.update(name, |feeder| {
+            feeder.count = count;
+            Ok(())
+        })
+        .map(|_| ())
+    }
+
+    /// Every change is validated before any change is applied, so that the changes are applied together or not at all.
+    pub fn apply_config(&mut self, changes: &[ConfigChange]) -> Result<(), ConfigError> {
+        if changes.is_empty() {
+            return Err(ConfigError::NoChanges);
+        }
+
+        let mut fields = HashSet::new();
+        let errors = changes
+            .iter()
+            .filter_map(|change| {
+                let result = match fields.insert(&change.field) {
+                    true => self.validate(change),
+                    false => Err(ConfigFieldError::Duplicate),
+                };
+                result.err().map(|error| FieldError {
+                    field: change.field.clone(),
+                    error,
+                })
+            })
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+
+        for change in changes {
+            let new = change.new;
+            // the feeder was validated above, the update can't fail
+            let _ = match &change.field {
+                ConfigField::FeederCount(name) => self.update(name, |feeder| {
+                    feeder.count = new;
+                    Ok(())
+                }),
+                ConfigField::FeederLowStockThreshold(name) => self.update(name, |feeder| {
+                    feeder.low_stock_threshold = new;
+                    Ok(())
+                }),
+            };
+        }
+        Ok(())
+    }
+
+    fn validate(&self, change: &ConfigChange) -> Result<(), ConfigFieldError> {
+        let (name, value): (&FeederId, fn(&Feeder) -> u32) = match &change.field {
+            ConfigField::FeederCount(name) => (name, |feeder| feeder.count),
+            ConfigField::FeederLowStockThreshold(name) => (name, |feeder| feeder.low_stock_threshold),
+        };
+        let feeder = self
+            .feeders
+            .get(name)
+            .ok_or(ConfigFieldError::UnknownFeeder)?;
+
+        let current = value(feeder);
+        if current != change.old {
+            return Err(ConfigFieldError::Changed {
+                current,
+            });
+        }
+        if change.new > FEEDER_COUNT_MAX {
+            return Err(ConfigFieldError::OutOfRange {
+                max: FEEDER_COUNT_MAX,
+            });
+        }
+        Ok(())
+    }
+
+    /// Called when the ID of the reel loaded into the feeder has been scanned, the count is unchanged.
+    ///
+    /// The orientation of the tape of the new reel has not been verified yet.
+    pub fn assign_reel(&mut self, name: &FeederId, reel: String) -> Result<(), FeederError> {
+        let feeder = self
+            .feeders
+            .get_mut(name)
+            .ok_or(FeederError::UnknownFeeder)?;
+
+        info!(
+            "Reel assigned to feeder. feeder: {}, reel: {}, previous: {:?}",
+            name, reel, feeder.reel
+        );
+        feeder.reel = Some(reel);
+        if feeder.orientation.is_some() {
+            feeder.orientation = Some(TapeOrientation::Unverified);
+        }
+        Ok(())
+    }
+
+    /// Where the polarity mark of the part is when the tape is loaded correctly, `None` if the part has no polarity
+    /// mark.
+    pub fn polarity_mark(&self, name: &FeederId) -> Result<Option<PolarityCorner>, FeederError> {
+        self.feeders
+            .get(name)
+            .map(|feeder| feeder.polarity_mark)
+            .ok_or(FeederError::UnknownFeeder)
+    }
+
+    /// Called when the orientation of the tape has been verified, an event is raised when reversed tape is found.
+    ///
+    /// The orientation of a feeder whose part has no polarity mark is not changed.
+    pub fn set_orientation(&mut self, name: &FeederId, orientation: TapeOrientation) -> Result<(), FeederError> {
+        let feeder = self
+            .feeders
+            .get_mut(name)
+            .ok_or(FeederError::UnknownFeeder)?;
+        let Some(previous) = feeder.orientation else {
+            return Ok(());
+        };
+
+        feeder.orientation = Some(orientation);
+        match orientation {
+            TapeOrientation::Reversed if previous != TapeOrientation::Reversed => {
+                warn!("Feeder tape reversed. feeder: {}", name);
+                self.events
+                    .push(FeederEvent::ReversedTape {
+                        feeder: name.clone(),
+                    });
+            }
+            TapeOrientation::Reversed => warn!("Feeder tape still reversed. feeder: {}", name),
+            _ => info!(
+                "Feeder orientation verified. feeder: {}, orientation: {:?}",
+                name, orientation
+            ),
+        }
+        Ok(())
+    }
+
+    /// Called when the io board reports a smart feeder inserted into the `slot`, returns the feeder with the identity,
+    /// `None` if the identity is not in the configuration of any feeder.
+    ///
+    /// A feeder that was still in the slot is removed, e.g. if its removal was not reported.  The tape may have been
+    /// changed while the feeder was out, so its orientation is verified again.
+    pub fn insert(&mut self, slot: u8, identity: u64) -> Option<FeederId> {
+        self.remove(slot);
+
+        let Some((name, feeder)) = self
+            .feeders
+            .iter_mut()
+            .find(|(_, feeder)| feeder.identity == Some(identity))
+        else {
+            warn!("Unknown feeder inserted. slot: {}, identity: {:x}", slot, identity);
+            self.events
+                .push(FeederEvent::UnknownInserted {
+                    slot,
+                    identity,
+                });
+            return None;
+        };
+
+        if let Some(previous) = feeder.slot {
+            warn!(
+                "Feeder moved without being removed. feeder: {}, previous: {}, slot: {}",
+                name, previous, slot
+            );
+        }
+        info!(
+            "Feeder inserted. feeder: {}, slot: {}, calibration: {:?}",
+            name, slot, feeder.calibration
+        );
+        feeder.slot = Some(slot);
+        if feeder.orientation.is_some() {
+            feeder.orientation = Some(TapeOrientation::Unverified);
+        }
+        self.events.push(FeederEvent::Inserted {
+            feeder: name.clone(),
+            slot,
+        });
+        Some(name.clone())
+    }
+
+    /// Called when the io board reports the smart feeder in the `slot` removed.
+    pub fn remove(&mut self, slot: u8) {
+        let Some((name, feeder)) = self
+            .feeders
+            .iter_mut()
+            .find(|(_, feeder)| feeder.slot == Some(slot))
+        else {
+            return;
+        };
+
+        info!("Feeder removed. feeder: {}, slot: {}", name, slot);
+        feeder.slot = None;
+        self.events.push(FeederEvent::Removed {
+            feeder: name.clone(),
+            slot,
+        });
+    }
+
+    /// The calibration of the feeder, `None` if it has not been calibrated.
+    pub fn calibration(&self, name: &FeederId) -> Result<Option<FeederCalibration>, FeederError> {
+        self.feeders
+            .get(name)
+            .map(|feeder| feeder.calibration)
+            .ok_or(FeederError::UnknownFeeder)
+    }
+
+    /// Where the parts are picked, in machine coordinates, the nominal position of the first pocket offset by the
+    /// calibration, `None` if the parts of the feeder are not picked by the machine.
+    pub fn pick_position(&self, name: &FeederId) -> Result<Option<Point>, FeederError> {
+        let feeder = self
+            .feeders
+            .get(name)
+            .ok_or(FeederError::UnknownFeeder)?;
+        Ok(feeder
+            .pick_position
+            .map(|position| match feeder.calibration {
+                Some(calibration) => position + calibration.pick_offset,
+                None => position,
+            }))
+    }
+
+    /// Takes a part from the feeder, returns the remaining parts.
+    ///
+    /// Parts are not taken from a feeder with reversed tape, unverified tape is picked from.
+    pub fn pick(&mut self, name: &FeederId) -> Result<u32, FeederError> {
+        let reversed = self
+            .feeders
+            .get(name)
+            .is_some_and(|feeder| feeder.orientation == Some(TapeOrientation::Reversed));
+        if reversed {
+            return Err(FeederError::ReversedTape);
+        }
+
+        self.update(name, |feeder| {
+            feeder.count = feeder
+                .count
+                .checked_sub(1)
+                .ok_or(FeederError::OutOfStock)?;
+            Ok(())
+        })
+    }
+
+    pub fn stock(&self, name: &FeederId) -> Result<Stock, FeederError> {
+        self.feeders
+            .get(name)
+            .map(Feeder::stock)
+            .ok_or(FeederError::UnknownFeeder)
+    }
+
+    /// The remaining parts, by feeder.
+    pub fn counts(&self) -> BTreeMap<FeederId, u32> {
+        self.feeders
+            .iter()
+            .map(|(name, feeder)| (name.clone(), feeder.count))
+            .collect()
+    }
+
+    pub fn status(&self) -> FeedersStatus {
+        FeedersStatus {
+            feeders: self
+                .feeders
+                .iter()
+                .map(|(name, feeder)| FeederStatus {
+                    name: name.clone(),
+                    count: feeder.count,
+                    low_stock_threshold: feeder.low_stock_threshold,
+                    stock: feeder.stock(),
+                    reel: feeder.reel.clone(),
+                    orientation: feeder.orientation,
+                    slot: feeder.slot,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<FeederEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Raises an event if the stock of the feeder changed to low or out, returns the remaining parts.
+    fn update(
+        &mut self,
+        name: &FeederId,
+        update_fn: impl FnOnce(&mut Feeder) -> Result<(), FeederError>,
+    ) -> Result<u32, FeederError> {
+        let feeder = self
+            .feeders
+            .get_mut(name)
+            .ok_or(FeederError::UnknownFeeder)?;
+
+        let previous_stock = feeder.stock();
+        update_fn(feeder)?;
+        let stock = feeder.stock();
+
+        if stock != previous_stock {
+            match stock {
+                Stock::Ok => info!("Feeder stock ok. feeder: {}, count: {}", name, feeder.count),
+                Stock::Low => {
+                    warn!("Feeder stock low. feeder: {}, count: {}", name, feeder.count);
+                    self.events.push(FeederEvent::LowStock {
+                        feeder: name.clone(),
+                        count: feeder.count,
+                    });
+                }
+                Stock::Out => {
+                    warn!("Feeder out of stock. feeder: {}", name);
+                    self.events
+                        .push(FeederEvent::OutOfStock {
+                            feeder: name.clone(),
+                        });
+                }
+            }
+        }
+        Ok(feeder.count)
+    }
+}
+
+/// Publishes the [`FeedersStatus`] and the [`FeederEvent`]s for the operator UI.
+pub async fn feeder_monitor(stack: RouterStack, feeders: Arc<Mutex<Feeders>>, app_event_rx: Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let mut ticker = time::interval(PUBLISH_INTERVAL);
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            _ = ticker.tick() => {
+                let (events, status) = {
+                    let mut feeders = feeders.lock().await;
+                    (feeders.take_events(), feeders.status())
+                };
+
+                for event in events {
+                    if let Err(e) = stack
+                        .topics()
+                        .broadcast::<FeederEventTopic>(&event, None)
+                    {
+                        debug!("Unable to publish feeder event, error: {:?}", e);
+                    }
+                }
+                if let Err(e) = stack
+                    .topics()
+                    .broadcast::<FeedersStatusTopic>(&status, None)
+                {
+                    debug!("Unable to publish feeders status, error: {:?}", e);
+                }
+            }
+        }
+    }
+    info!("feeder monitor shutdown");
+}
@@ -0,0 +1,469 @@
+use std::collections::BTreeMap;
+
+use machine_geometry::Point;
+use machine_ids::FeederId;
+use operator_shared::config::{ConfigChange, ConfigError, ConfigField, ConfigFieldError, FieldError};
+use operator_shared::feeders::{FeederError, FeederEvent, FeederStatus, Stock, TapeOrientation};
+
+use super::{FEEDER_COUNT_MAX, Feeders};
+use crate::config::{FeederCalibration, FeederDefinition, FeedersConfig, PolarityCorner};
+
+/// The identity of the ID chip of "F2".
+const SMART_FEEDER: u64 = 0x04a3_1122_3344;
+
+fn calibration() -> FeederCalibration {
+    FeederCalibration {
+        pick_offset: Point {
+            x: 0.2,
+            y: -0.1,
+        },
+        rotation: 0.5,
+    }
+}
+
+fn feeders(counts: &[(&str, u32)]) -> Feeders {
+    let config = FeedersConfig {
+        low_stock_threshold: 2,
+        feeders: vec![
+            FeederDefinition {
+                name: FeederId::new("F1"),
+                low_stock_threshold: None,
+                polarity_mark: None,
+                part: None,
+                identity: None,
+                calibration: None,
+                pick_position: None,
+            },
+            FeederDefinition {
+                name: FeederId::new("F2"),
+                low_stock_threshold: Some(10),
+                polarity_mark: Some(PolarityCorner::TopLeft),
+                part: None,
+                identity: Some(SMART_FEEDER),
+                calibration: Some(calibration()),
+                pick_position: Some(Point {
+                    x: 100.0,
+                    y: 50.0,
+                }),
+            },
+        ],
+        ..FeedersConfig::default()
+    };
+    let counts = counts
+        .iter()
+        .map(|(name, count)| (FeederId::new(*name), *count))
+        .collect::<BTreeMap<_, _>>();
+
+    Feeders::new(&config, &counts)
+}
+
+#[test]
+pub fn counts_restored() {
+    // given
+    let feeders = feeders(&[("F1", 5), ("unknown", 3)]);
+
+    // expect
+    assert_eq!(
+        feeders.counts(),
+        BTreeMap::from([(FeederId::new("F1"), 5), (FeederId::new("F2"), 0)])
+    );
+}
+
+#[test]
+pub fn pick_decrements_count() {
+    // given
+    let mut feeders = feeders(&[("F1", 5)]);
+
+    // when
+    let remaining = feeders.pick(&FeederId::new("F1"));
+
+    // then
+    assert_eq!(remaining, Ok(4));
+    assert_eq!(feeders.stock(&FeederId::new("F1")), Ok(Stock::Ok));
+    assert!(feeders.take_events().is_empty());
+}
+
+#[test]
+pub fn low_stock_raised_once() {
+    // given
+    let mut feeders = feeders(&[("F1", 4)]);
+
+    // when
+    feeders
+        .pick(&FeederId::new("F1"))
+        .unwrap();
+    feeders
+        .pick(&FeederId::new("F1"))
+        .unwrap();
+    feeders
+        .pick(&FeederId::new("F1"))
+        .unwrap();
+
+    // then
+    assert_eq!(feeders.take_events(), vec![FeederEvent::LowStock {
+        feeder: FeederId::new("F1"),
+        count: 2
+    }]);
+    assert_eq!(feeders.stock(&FeederId::new("F1")), Ok(Stock::Low));
+}
+
+#[test]
+pub fn out_of_stock_raised_and_pick_refused() {
+    // given
+    let mut feeders = feeders(&[("F1", 1)]);
+    feeders.take_events();
+
+    // when
+    let last = feeders.pick(&FeederId::new("F1"));
+    let refused = feeders.pick(&FeederId::new("F1"));
+
+    // then
+    assert_eq!(last, Ok(0));
+    assert_eq!(refused, Err(FeederError::OutOfStock));
+    assert_eq!(feeders.take_events(), vec![FeederEvent::OutOfStock {
+        feeder: FeederId::new("F1")
+    }]);
+}
+
+#[test]
+pub fn feeder_threshold_overrides_default() {
+    // given
+    let mut feeders = feeders(&[]);
+
+    // when
+    feeders
+        .set_count(&FeederId::new("F1"), 10)
+        .unwrap();
+    feeders
+        .set_count(&FeederId::new("F2"), 10)
+        .unwrap();
+
+    // then
+    assert_eq!(feeders.stock(&FeederId::new("F1")), Ok(Stock::Ok));
+    assert_eq!(feeders.stock(&FeederId::new("F2")), Ok(Stock::Low));
+}
+
+#[test]
+pub fn set_count_restores_stock() {
+    // given
+    let mut feeders = feeders(&[]);
+    feeders.take_events();
+
+    // when
+    let result = feeders.set_count(&FeederId::new("F1"), 100);
+
+    // then
+    assert_eq!(result, Ok(()));
+    assert_eq!(feeders.stock(&FeederId::new("F1")), Ok(Stock::Ok));
+    assert!(feeders.take_events().is_empty());
+}
+
+#[test]
+pub fn unknown_feeder() {
+    // given
+    let mut feeders = feeders(&[]);
+
+    // expect
+    assert_eq!(feeders.pick(&FeederId::new("F3")), Err(FeederError::UnknownFeeder));
+    assert_eq!(
+        feeders.set_count(&FeederId::new("F3"), 1),
+        Err(FeederError::UnknownFeeder)
+    );
+    assert_eq!(feeders.stock(&FeederId::new("F3")), Err(FeederError::UnknownFeeder));
+}
+
+#[test]
+pub fn assign_reel() {
+    // given
+    let mut feeders = feeders(&[("F1", 5)]);
+
+    // when
+    let result = feeders.assign_reel(&FeederId::new("F1"), "REEL-1".to_string());
+
+    // then
+    assert_eq!(result, Ok(()));
+    assert_eq!(feeders.status().feeders[0], FeederStatus {
+        name: FeederId::new("F1"),
+        count: 5,
+        low_stock_threshold: 2,
+        stock: Stock::Ok,
+        reel: Some("REEL-1".to_string()),
+        orientation: None,
+        slot: None,
+    });
+    assert_eq!(
+        feeders.assign_reel(&FeederId::new("F3"), "REEL-2".to_string()),
+        Err(FeederError::UnknownFeeder)
+    );
+}
+
+#[test]
+pub fn reversed_tape_raised_once_and_pick_refused() {
+    // given
+    let mut feeders = feeders(&[("F2", 50)]);
+
+    // when
+    feeders
+        .set_orientation(&FeederId::new("F2"), TapeOrientation::Reversed)
+        .unwrap();
+    feeders
+        .set_orientation(&FeederId::new("F2"), TapeOrientation::Reversed)
+        .unwrap();
+    let refused = feeders.pick(&FeederId::new("F2"));
+
+    // then
+    assert_eq!(refused, Err(FeederError::ReversedTape));
+    assert_eq!(feeders.counts()["F2"], 50);
+    assert_eq!(feeders.take_events(), vec![FeederEvent::ReversedTape {
+        feeder: FeederId::new("F2")
+    }]);
+}
+
+#[test]
+pub fn verified_tape_is_picked_from() {
+    // given
+    let mut feeders = feeders(&[("F2", 50)]);
+    feeders
+        .set_orientation(&FeederId::new("F2"), TapeOrientation::Reversed)
+        .unwrap();
+
+    // when
+    feeders
+        .set_orientation(&FeederId::new("F2"), TapeOrientation::Correct)
+        .unwrap();
+
+    // then
+    assert_eq!(feeders.pick(&FeederId::new("F2")), Ok(49));
+    assert_eq!(feeders.status().feeders[1].orientation, Some(TapeOrientation::Correct));
+}
+
+#[test]
+pub fn new_reel_is_unverified() {
+    // given
+    let mut feeders = feeders(&[("F2", 50)]);
+    feeders
+        .set_orientation(&FeederId::new("F2"), TapeOrientation::Correct)
+        .unwrap();
+
+    // when
+    feeders
+        .assign_reel(&FeederId::new("F2"), "REEL-2".to_string())
+        .unwrap();
+
+    // then
+    assert_eq!(
+        feeders.status().feeders[1].orientation,
+        Some(TapeOrientation::Unverified)
+    );
+}
+
+#[test]
+pub fn orientation_of_part_without_polarity_mark() {
+    // given
+    let mut feeders = feeders(&[("F1", 5)]);
+
+    // when
+    let result = feeders.set_orientation(&FeederId::new("F1"), TapeOrientation::Reversed);
+
+    // then
+    assert_eq!(result, Ok(()));
+    assert_eq!(feeders.polarity_mark(&FeederId::new("F1")), Ok(None));
+    assert_eq!(feeders.status().feeders[0].orientation, None);
+    assert_eq!(feeders.pick(&FeederId::new("F1")), Ok(4));
+}
+
+#[test]
+pub fn config_changes_applied_together() {
+    // given
+    let mut feeders = feeders(&[("F1", 5), ("F2", 50)]);
+
+    // when
+    let result = feeders.apply_config(&[
+        ConfigChange {
+            field: ConfigField::FeederCount(FeederId::new("F1")),
+            old: 5,
+            new: 100,
+        },
+        ConfigChange {
+            field: ConfigField::FeederLowStockThreshold(FeederId::new("F2")),
+            old: 10,
+            new: 60,
+        },
+    ]);
+
+    // then
+    assert_eq!(result, Ok(()));
+    assert_eq!(feeders.counts()["F1"], 100);
+    assert_eq!(feeders.status().feeders[1].low_stock_threshold, 60);
+    assert_eq!(feeders.take_events(), vec![FeederEvent::LowStock {
+        feeder: FeederId::new("F2"),
+        count: 50
+    }]);
+}
+
+#[test]
+pub fn invalid_config_changes_not_applied() {
+    // given
+    let mut feeders = feeders(&[("F1", 5), ("F2", 50)]);
+    let count = |name: &str, old, new| ConfigChange {
+        field: ConfigField::FeederCount(FeederId::new(name)),
+        old,
+        new,
+    };
+
+    // when
+    let result = feeders.apply_config(&[
+        count("F1", 5, 6),
+        count("F1", 5, 7),
+        count("F2", 49, 40),
+        count("F3", 0, 1),
+        ConfigChange {
+            field: ConfigField::FeederLowStockThreshold(FeederId::new("F2")),
+            old: 10,
+            new: FEEDER_COUNT_MAX + 1,
+        },
+    ]);
+
+    // then
+    assert_eq!(
+        result,
+        Err(ConfigError::Invalid(vec![
+            FieldError {
+                field: ConfigField::FeederCount(FeederId::new("F1")),
+                error: ConfigFieldError::Duplicate,
+            },
+            FieldError {
+                field: ConfigField::FeederCount(FeederId::new("F2")),
+                error: ConfigFieldError::Changed {
+                    current: 50
+                },
+            },
+            FieldError {
+                field: ConfigField::FeederCount(FeederId::new("F3")),
+                error: ConfigFieldError::UnknownFeeder,
+            },
+            FieldError {
+                field: ConfigField::FeederLowStockThreshold(FeederId::new("F2")),
+                error: ConfigFieldError::OutOfRange {
+                    max: FEEDER_COUNT_MAX
+                },
+            },
+        ]))
+    );
+    assert_eq!(
+        feeders.counts(),
+        BTreeMap::from([(FeederId::new("F1"), 5), (FeederId::new("F2"), 50)])
+    );
+    assert_eq!(feeders.apply_config(&[]), Err(ConfigError::NoChanges));
+}
+
+#[test]
+pub fn inserted_smart_feeder_is_loaded() {
+    // given
+    let mut feeders = feeders(&[("F2", 50)]);
+    feeders
+        .set_orientation(&FeederId::new("F2"), TapeOrientation::Correct)
+        .unwrap();
+
+    // when
+    let inserted = feeders.insert(3, SMART_FEEDER);
+
+    // then
+    assert_eq!(inserted, Some(FeederId::new("F2")));
+    assert_eq!(feeders.take_events(), vec![FeederEvent::Inserted {
+        feeder: FeederId::new("F2"),
+        slot: 3,
+    }]);
+    let status = &feeders.status().feeders[1];
+    assert_eq!(status.slot, Some(3));
+    // the tape may have been changed while the feeder was out
+    assert_eq!(status.orientation, Some(TapeOrientation::Unverified));
+    assert_eq!(feeders.calibration(&FeederId::new("F2")), Ok(Some(calibration())));
+    assert_eq!(feeders.calibration(&FeederId::new("F1")), Ok(None));
+}
+
+#[test]
+pub fn unknown_smart_feeder_raises_event() {
+    // given
+    let mut feeders = feeders(&[]);
+
+    // when
+    let inserted = feeders.insert(1, 0x1234);
+
+    // then
+    assert_eq!(inserted, None);
+    assert_eq!(feeders.take_events(), vec![FeederEvent::UnknownInserted {
+        slot: 1,
+        identity: 0x1234,
+    }]);
+    assert!(
+        feeders
+            .status()
+            .feeders
+            .iter()
+            .all(|feeder| feeder.slot.is_none())
+    );
+}
+
+#[test]
+pub fn removed_smart_feeder_leaves_its_slot() {
+    // given
+    let mut feeders = feeders(&[]);
+    feeders.insert(3, SMART_FEEDER);
+    feeders.take_events();
+
+    // when
+    feeders.remove(3);
+    feeders.remove(4);
+
+    // then
+    assert_eq!(feeders.take_events(), vec![FeederEvent::Removed {
+        feeder: FeederId::new("F2"),
+        slot: 3,
+    }]);
+    assert_eq!(feeders.status().feeders[1].slot, None);
+}
+
+#[test]
+pub fn feeder_inserted_into_an_occupied_slot_replaces_it() {
+    // given
+    let mut feeders = feeders(&[]);
+    feeders.insert(3, SMART_FEEDER);
+    feeders.take_events();
+
+    // when
+    // the removal was not reported
+    feeders.insert(3, 0x1234);
+
+    // then
+    assert_eq!(feeders.take_events(), vec![
+        FeederEvent::Removed {
+            feeder: FeederId::new("F2"),
+            slot: 3,
+        },
+        FeederEvent::UnknownInserted {
+            slot: 3,
+            identity: 0x1234,
+        },
+    ]);
+}
+
+#[test]
+pub fn pick_position_is_offset_by_the_calibration() {
+    // given
+    let feeders = feeders(&[]);
+
+    // expect
+    assert_eq!(
+        feeders.pick_position(&FeederId::new("F2")),
+        Ok(Some(Point {
+            x: 100.2,
+            y: 49.9,
+        }))
+    );
+    assert_eq!(feeders.pick_position(&FeederId::new("F1")), Ok(None));
+    assert_eq!(
+        feeders.pick_position(&FeederId::new("unknown")),
+        Err(FeederError::UnknownFeeder)
+    );
+}
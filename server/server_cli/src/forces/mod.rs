@@ -0,0 +1,296 @@
+//! Recording the force of the nozzle load cell during the touchdowns of each placement, see [`ForcePlacer`].
+//!
+//! The io board publishes a [`ForceTrace`] for each touchdown, a short window around the contact.  The traces received
+//! while a part is placed are attached to the placement and written to the job report, and the peak force of each
+//! touchdown is checked against the expected range of the part, see [`PartDefinition::pick_force`].  A touchdown that
+//! was too hard, e.g. a cracked part, or too soft, e.g. the part was not reached, is flagged and logged, the placement
+//! itself does not fail.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ioboard_shared::force::{ForceTrace, Touchdown};
+use log::{debug, info, warn};
+use machine_ids::{BoardId, FeederId};
+use serde::{Deserialize, Serialize};
+use tokio::select;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::{Mutex, broadcast};
+use tokio::time::{self, Instant};
+
+use crate::AppEvent;
+use crate::config::{FeederDefinition, ForceRange, PartDefinition};
+use crate::ioboard::ForceTopic;
+use crate::job::{Operation, Placement, Placer};
+
+#[cfg(test)]
+mod tests;
+
+/// Traces that are not taken by a placer are dropped, e.g. while no job is running.
+pub const FORCE_TRACE_QUEUE_SIZE: usize = 16;
+
+/// A trace is published once its window has ended, the trace of the place touchdown may arrive after the part has
+/// been placed.
+const TRACE_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForceAnomaly {
+    TooSoft,
+    TooHard,
+}
+
+/// A touchdown of a placement, as written to the job report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TouchdownForce {
+    pub nozzle: u8,
+    pub touchdown: Touchdown,
+    /// the highest force of the trace, in N
+    pub peak: f64,
+    /// `None` if the peak is within the expected range, or the part has no expected range
+    pub anomaly: Option<ForceAnomaly>,
+    /// in Hz
+    pub sample_rate: f32,
+    /// the index of the sample at which the contact was detected
+    pub contact: u8,
+    /// in N
+    pub samples: Vec<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlacementForces {
+    pub placement: String,
+    pub board: Option<BoardId>,
+    pub touchdowns: Vec<TouchdownForce>,
+}
+
+/// The forces of the placements of a run, shared with the job runner, which writes them to the job report.
+pub type ForceLog = Arc<Mutex<Vec<PlacementForces>>>;
+
+/// The highest force of the trace, in N, 0.0 for a trace without samples.
+pub fn peak_force(trace: &ForceTrace) -> f64 {
+    trace
+        .samples()
+        .iter()
+        .fold(0.0, |peak, sample| peak.max(*sample as f64))
+}
+
+pub fn anomaly(peak: f64, expected: Option<&ForceRange>) -> Option<ForceAnomaly> {
+    let expected = expected?;
+    if peak < expected.min {
+        Some(ForceAnomaly::TooSoft)
+    } else if peak > expected.max {
+        Some(ForceAnomaly::TooHard)
+    } else {
+        None
+    }
+}
+
+/// The expected forces of the parts, by feeder.
+#[derive(Debug, Clone, Default)]
+pub struct ExpectedForces {
+    by_feeder: HashMap<FeederId, (Option<ForceRange>, Option<ForceRange>)>,
+}
+
+impl ExpectedForces {
+    pub fn new(feeders: &[FeederDefinition], parts: &[PartDefinition]) -> Self {
+        let by_feeder = feeders
+            .iter()
+            .filter_map(|feeder| {
+                let name = feeder.part.as_ref()?;
+                let part = parts
+                    .iter()
+                    .find(|part| part.name == *name)?;
+                Some((feeder.name.clone(), (part.pick_force, part.place_force)))
+            })
+            .collect();
+
+        Self {
+            by_feeder,
+        }
+    }
+
+    /// `None` if the part of the placement is not known, or its force is not checked.
+    pub fn range(&self, placement: &Placement, touchdown: Touchdown) -> Option<&ForceRange> {
+        let (pick, place) = self
+            .by_feeder
+            .get(placement.feeder.as_ref()?)?;
+        match touchdown {
+            Touchdown::Pick => pick.as_ref(),
+            Touchdown::Place => place.as_ref(),
+        }
+    }
+
+    pub fn touchdown_force(&self, placement: &Placement, trace: &ForceTrace) -> TouchdownForce {
+        let peak = peak_force(trace);
+        TouchdownForce {
+            nozzle: trace.nozzle,
+            touchdown: trace.touchdown,
+            peak,
+            anomaly: anomaly(peak, self.range(placement, trace.touchdown)),
+            sample_rate: trace.sample_rate,
+            contact: trace.contact,
+            samples: trace.samples().to_vec(),
+        }
+    }
+}
+
+/// Records the touchdowns of each placed part, dispense operations are placed unchanged.
+pub struct ForcePlacer<P: Placer> {
+    placer: P,
+    traces_rx: broadcast::Receiver<ForceTrace>,
+    expected: ExpectedForces,
+    log: ForceLog,
+}
+
+impl<P: Placer> ForcePlacer<P> {
+    pub fn new(placer: P, traces_rx: broadcast::Receiver<ForceTrace>, expected: ExpectedForces, log: ForceLog) -> Self {
+        Self {
+            placer,
+            traces_rx,
+            expected,
+            log,
+        }
+    }
+
+    /// Traces of touchdowns before the placement, e.g. while the machine was jogged.
+    fn discard_traces(&mut self) {
+        loop {
+            match self.traces_rx.try_recv() {
+                Ok(_) | Err(TryRecvError::Lagged(_)) => continue,
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// The traces received while the part was placed, once the pick touchdown has been seen the place touchdown is
+    /// waited for.  Without a load cell no traces are received, and nothing is waited for.
+    async fn receive_traces(&mut self) -> Vec<ForceTrace> {
+        let mut traces = vec![];
+        loop {
+            match self.traces_rx.try_recv() {
+                Ok(trace) => traces.push(trace),
+                Err(TryRecvError::Lagged(count)) => warn!("Force traces dropped. count: {}", count),
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+
+        let has = |traces: &[ForceTrace], touchdown: Touchdown| {
+            traces
+                .iter()
+                .any(|trace| trace.touchdown == touchdown)
+        };
+        if !has(&traces, Touchdown::Pick) || has(&traces, Touchdown::Place) {
+            return traces;
+        }
+
+        let deadline = Instant::now() + TRACE_TIMEOUT;
+        loop {
+            match time::timeout_at(deadline, self.traces_rx.recv()).await {
+                Ok(Ok(trace)) => {
+                    let placed = trace.touchdown == Touchdown::Place;
+                    traces.push(trace);
+                    if placed {
+                        break;
+                    }
+                }
+                Ok(Err(RecvError::Lagged(count))) => warn!("Force traces dropped. count: {}", count),
+                Ok(Err(RecvError::Closed)) => break,
+                Err(_) => {
+                    debug!("Place touchdown not received");
+                    break;
+                }
+            }
+        }
+        traces
+    }
+}
+
+impl<P: Placer + Send> Placer for ForcePlacer<P> {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            // nothing touches down in a dry run
+            if !matches!(placement.operation, Operation::Place) || self.placer.dry_run() {
+                return self.placer.place(placement).await;
+            }
+
+            self.discard_traces();
+            let result = self.placer.place(placement).await;
+            let traces = self.receive_traces().await;
+            if traces.is_empty() {
+                return result;
+            }
+
+            let touchdowns = traces
+                .iter()
+                .map(|trace| {
+                    self.expected
+                        .touchdown_force(placement, trace)
+                })
+                .collect::<Vec<_>>();
+            for touchdown in touchdowns.iter() {
+                let Some(anomaly) = touchdown.anomaly else {
+                    continue;
+                };
+                warn!(
+                    "Touchdown force out of range. placement: {}, touchdown: {:?} {:?}, peak: {:.2}, expected: {:?}",
+                    placement.reference,
+                    touchdown.touchdown,
+                    anomaly,
+                    touchdown.peak,
+                    self.expected
+                        .range(placement, touchdown.touchdown)
+                );
+            }
+            self.log
+                .lock()
+                .await
+                .push(PlacementForces {
+                    placement: placement.reference.clone(),
+                    board: placement.board,
+                    touchdowns,
+                });
+
+            result
+        }
+    }
+
+    fn skips(&self, placement: &Placement) -> bool {
+        self.placer.skips(placement)
+    }
+
+    fn dry_run(&self) -> bool {
+        self.placer.dry_run()
+    }
+}
+
+/// Listens for the force traces of the io board, the traces are sent to `traces_tx`, see [`ForcePlacer`].
+pub async fn force_listener(
+    stack: RouterStack,
+    traces_tx: broadcast::Sender<ForceTrace>,
+    app_event_rx: broadcast::Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<ForceTopic>(FORCE_TRACE_QUEUE_SIZE, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                // no receivers while no job is running
+                let _ = traces_tx.send(msg.t);
+            }
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+        }
+    }
+    info!("force listener shutdown");
+}
@@ -0,0 +1,233 @@
+use std::future::Future;
+use std::time::Duration;
+
+use ioboard_shared::force::{FORCE_TRACE_SAMPLES, ForceTrace, Touchdown};
+use machine_geometry::Point;
+use machine_ids::FeederId;
+use tokio::sync::broadcast;
+use tokio::time;
+
+use super::{ExpectedForces, ForceAnomaly, ForceLog, ForcePlacer, anomaly, peak_force};
+use crate::config::{FeederDefinition, ForceRange, PartDefinition};
+use crate::job::{Operation, Placement, Placer};
+
+fn trace(touchdown: Touchdown, samples: &[f32]) -> ForceTrace {
+    let mut trace = ForceTrace {
+        nozzle: 0,
+        touchdown,
+        sample_rate: 320.0,
+        contact: 0,
+        sample_count: samples.len() as u8,
+        samples: [0.0; FORCE_TRACE_SAMPLES],
+    };
+    trace.samples[..samples.len()].copy_from_slice(samples);
+    trace
+}
+
+fn placement(feeder: &str, operation: Operation) -> Placement {
+    Placement {
+        reference: "C1".to_string(),
+        position: Point::default(),
+        rotation: 0.0,
+        feeder: Some(FeederId::new(feeder)),
+        operation,
+        board: None,
+    }
+}
+
+/// The parts library has a capacitor in feeder "F1", with expected ranges for both touchdowns.
+fn expected_forces() -> ExpectedForces {
+    let feeders = vec![FeederDefinition {
+        name: FeederId::new("F1"),
+        low_stock_threshold: None,
+        polarity_mark: None,
+        part: Some("0603-100n".to_string()),
+        identity: None,
+        calibration: None,
+        pick_position: None,
+    }];
+    let parts = vec![PartDefinition {
+        name: "0603-100n".to_string(),
+        height: 0.8,
+        pick_force: Some(ForceRange {
+            min: 0.5,
+            max: 2.0,
+        }),
+        place_force: Some(ForceRange {
+            min: 1.0,
+            max: 3.0,
+        }),
+    }];
+    ExpectedForces::new(&feeders, &parts)
+}
+
+/// Publishes the traces while the part is placed.
+struct FakePlacer {
+    traces_tx: broadcast::Sender<ForceTrace>,
+    traces: Vec<ForceTrace>,
+}
+
+impl Placer for FakePlacer {
+    fn place<'a>(&'a mut self, _placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            for trace in self.traces.drain(..) {
+                self.traces_tx.send(trace).unwrap();
+            }
+            Ok(())
+        }
+    }
+}
+
+fn force_placer(traces: Vec<ForceTrace>) -> (ForcePlacer<FakePlacer>, broadcast::Sender<ForceTrace>, ForceLog) {
+    let (traces_tx, traces_rx) = broadcast::channel(16);
+    let log = ForceLog::default();
+    let placer = FakePlacer {
+        traces_tx: traces_tx.clone(),
+        traces,
+    };
+    (
+        ForcePlacer::new(placer, traces_rx, expected_forces(), log.clone()),
+        traces_tx,
+        log,
+    )
+}
+
+#[test]
+pub fn peak_is_the_highest_sample() {
+    // expect
+    assert_eq!(peak_force(&trace(Touchdown::Pick, &[0.0, 0.5, 1.25, 0.75])), 1.25);
+    assert_eq!(peak_force(&trace(Touchdown::Pick, &[])), 0.0);
+}
+
+#[test]
+pub fn anomaly_outside_the_expected_range() {
+    // given
+    let range = ForceRange {
+        min: 1.0,
+        max: 3.0,
+    };
+
+    // expect
+    assert_eq!(anomaly(0.5, Some(&range)), Some(ForceAnomaly::TooSoft));
+    assert_eq!(anomaly(1.0, Some(&range)), None);
+    assert_eq!(anomaly(3.0, Some(&range)), None);
+    assert_eq!(anomaly(3.5, Some(&range)), Some(ForceAnomaly::TooHard));
+    assert_eq!(anomaly(10.0, None), None);
+}
+
+#[test]
+pub fn expected_range_of_the_part_of_the_feeder() {
+    // given
+    let expected = expected_forces();
+
+    // expect
+    let capacitor = placement("F1", Operation::Place);
+    assert_eq!(
+        expected
+            .range(&capacitor, Touchdown::Pick)
+            .map(|range| range.max),
+        Some(2.0)
+    );
+    assert_eq!(
+        expected
+            .range(&capacitor, Touchdown::Place)
+            .map(|range| range.max),
+        Some(3.0)
+    );
+    assert_eq!(
+        expected.range(&placement("F2", Operation::Place), Touchdown::Pick),
+        None
+    );
+}
+
+#[tokio::test]
+pub async fn touchdowns_are_recorded_with_the_placement() {
+    // given
+    let traces = vec![
+        trace(Touchdown::Pick, &[0.1, 1.0, 0.2]),
+        trace(Touchdown::Place, &[0.1, 4.0, 0.2]),
+    ];
+    let (mut placer, _traces_tx, log) = force_placer(traces);
+
+    // when
+    let result = placer
+        .place(&placement("F1", Operation::Place))
+        .await;
+
+    // then
+    assert!(result.is_ok());
+    let log = log.lock().await;
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].placement, "C1");
+    let touchdowns = &log[0].touchdowns;
+    assert_eq!(touchdowns.len(), 2);
+    assert_eq!(
+        (touchdowns[0].touchdown, touchdowns[0].anomaly),
+        (Touchdown::Pick, None)
+    );
+    assert_eq!(
+        (touchdowns[1].touchdown, touchdowns[1].peak, touchdowns[1].anomaly),
+        (Touchdown::Place, 4.0, Some(ForceAnomaly::TooHard))
+    );
+    assert_eq!(touchdowns[1].samples, vec![0.1, 4.0, 0.2]);
+}
+
+#[tokio::test]
+pub async fn late_place_touchdown_is_waited_for() {
+    // given
+    let (mut placer, traces_tx, log) = force_placer(vec![trace(Touchdown::Pick, &[1.0])]);
+    let late = tokio::spawn(async move {
+        time::sleep(Duration::from_millis(50)).await;
+        traces_tx
+            .send(trace(Touchdown::Place, &[2.0]))
+            .unwrap();
+    });
+
+    // when
+    placer
+        .place(&placement("F1", Operation::Place))
+        .await
+        .unwrap();
+
+    // then
+    late.await.unwrap();
+    let log = log.lock().await;
+    assert_eq!(log[0].touchdowns.len(), 2);
+}
+
+#[tokio::test]
+pub async fn earlier_traces_are_discarded() {
+    // given
+    let (mut placer, traces_tx, log) = force_placer(vec![]);
+    traces_tx
+        .send(trace(Touchdown::Pick, &[1.0]))
+        .unwrap();
+
+    // when
+    placer
+        .place(&placement("F1", Operation::Place))
+        .await
+        .unwrap();
+
+    // then
+    // without a load cell nothing is recorded
+    assert!(log.lock().await.is_empty());
+}
+
+#[tokio::test]
+pub async fn dispense_operations_are_not_recorded() {
+    // given
+    let (mut placer, _traces_tx, log) = force_placer(vec![trace(Touchdown::Pick, &[1.0])]);
+
+    // when
+    placer
+        .place(&placement("F1", Operation::Dispense {
+            head: "paste".to_string(),
+            dispense_ms: None,
+        }))
+        .await
+        .unwrap();
+
+    // then
+    assert!(log.lock().await.is_empty());
+}
@@ -0,0 +1,270 @@
+//! Homing every axis, in the order given by the [`HomingConfig`].
+//!
+//! Each axis is homed by its io board, the server only decides the order.  An axis is homed once the axes it is homed
+//! `after` are homed, axes that don't depend on each other are homed in parallel, e.g. Z first, so that the nozzles are
+//! raised, then X and Y together.  When an axis fails the axes that depend on it are skipped, the other axes are still
+//! homed, so the operator can see which axes need attention.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, topic};
+use ergot_util::ClientWrapper;
+use ioboard_shared::homing::HomingRequest;
+use log::{debug, info, warn};
+use operator_shared::homing::{AxisHomingState, AxisHomingStatus, HomingStatus};
+use operator_shared::readiness::{CheckState, ReadinessCheck};
+use tokio::select;
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::Receiver;
+use tokio::task::JoinSet;
+use tokio::time;
+
+use crate::AppEvent;
+use crate::config::{HomingAxisDefinition, HomingConfig};
+use crate::ioboard::{CommandSequencer, HomingEndpoint};
+use crate::job::JobControl;
+use crate::networking::dead_letter;
+use crate::readiness::Readiness;
+
+#[cfg(test)]
+mod tests;
+
+topic!(HomingStatusTopic, HomingStatus, "topic/operator/homing");
+
+/// Only for finding the io board, the homing request is answered once the axis is homed, which takes much longer.
+const HOMING_DISCOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+/// * cloned for each axis that is homed in parallel.
+pub trait AxisHomer: Clone + Send + Sync + 'static {
+    /// Completes once the axis is homed.
+    fn home<'a>(&'a self, axis: u8) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+}
+
+/// Uses the homing endpoint of the io board.
+#[derive(Clone)]
+pub struct IoBoardHomer {
+    stack: RouterStack,
+    sequencer: Arc<CommandSequencer>,
+    timeout: Duration,
+}
+
+impl IoBoardHomer {
+    /// `timeout` is the time allowed for homing an axis.
+    pub fn new(stack: RouterStack, sequencer: Arc<CommandSequencer>, timeout: Duration) -> Self {
+        Self {
+            stack,
+            sequencer,
+            timeout,
+        }
+    }
+
+    async fn discover(&self) -> anyhow::Result<Address> {
+        let query = SocketQuery {
+            key: HomingEndpoint::REQ_KEY.to_bytes(),
+            nash_req: NameRequirement::Any,
+            frame_kind: FrameKind::ENDPOINT_REQ,
+            broadcast: false,
+        };
+        // TODO select the io board of the axis, currently there is only one
+        self.stack
+            .discovery()
+            .discover_sockets(4, HOMING_DISCOVERY_TIMEOUT, &query)
+            .await
+            .into_iter()
+            .next()
+            .map(|result| result.address)
+            .ok_or_else(|| anyhow!("No io board with homing found"))
+    }
+}
+
+impl AxisHomer for IoBoardHomer {
+    fn home<'a>(&'a self, axis: u8) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let address = self.discover().await?;
+
+            let client = self
+                .stack
+                .endpoints()
+                .client::<HomingEndpoint>(address, None);
+            // not retried, a retry after a lost response would home the axis a second time
+            let client = ClientWrapper::new(self.timeout, client);
+            let request = self.sequencer.sequenced(HomingRequest {
+                axis,
+            });
+            let result = client
+                .request_with_retry(&request, 1)
+                .await
+                .inspect_err(|e| dead_letter::request_failed::<HomingEndpoint>(address, 1, e))?;
+            match result {
+                Ok(()) => Ok(()),
+                Err(e) => bail!("Homing refused. axis: {}, error: {:?}", axis, e),
+            }
+        }
+    }
+}
+
+/// Homes the `axes`, returns the final status, `publish` is called whenever the state of an axis changes.
+///
+/// Axes are homed in rounds, each round homes every axis whose dependencies are homed, in parallel.  An axis that
+/// depends on an axis that is not homed, or is not configured, is skipped.
+pub async fn home_all<H: AxisHomer>(
+    homer: &H,
+    axes: &[HomingAxisDefinition],
+    timeout: Duration,
+    publish: &mut impl FnMut(&HomingStatus),
+) -> HomingStatus {
+    let mut status = HomingStatus {
+        axes: axes
+            .iter()
+            .map(|definition| AxisHomingStatus {
+                axis: definition.name.clone(),
+                state: AxisHomingState::NotHomed,
+            })
+            .collect(),
+        running: true,
+    };
+
+    loop {
+        let ready = axes
+            .iter()
+            .enumerate()
+            .filter(|(index, _definition)| status.axes[*index].state == AxisHomingState::NotHomed)
+            .filter(|(_index, definition)| {
+                definition
+                    .after
+                    .iter()
+                    .all(|name| is_homed(&status, name))
+            })
+            .map(|(index, _definition)| index)
+            .collect::<Vec<_>>();
+        if ready.is_empty() {
+            break;
+        }
+
+        let mut homing = JoinSet::new();
+        for index in ready {
+            let definition = &axes[index];
+            info!(
+                "Homing axis. axis: {}, io_board_axis: {}",
+                definition.name, definition.axis
+            );
+            status.axes[index].state = AxisHomingState::Homing;
+
+            let homer = homer.clone();
+            let axis = definition.axis;
+            homing.spawn(async move { (index, time::timeout(timeout, homer.home(axis)).await) });
+        }
+        publish(&status);
+
+        while let Some(result) = homing.join_next().await {
+            let (index, result) = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    // the task panicked, the axes still marked as homing are failed below
+                    warn!("Homing task failed. error: {:?}", e);
+                    continue;
+                }
+            };
+            let name = &axes[index].name;
+            status.axes[index].state = match result {
+                Ok(Ok(())) => {
+                    info!("Axis homed. axis: {}", name);
+                    AxisHomingState::Homed
+                }
+                Ok(Err(e)) => {
+                    warn!("Homing failed. axis: {}, error: {:?}", name, e);
+                    AxisHomingState::Failed
+                }
+                Err(_) => {
+                    warn!("Homing timed out. axis: {}, timeout: {:?}", name, timeout);
+                    AxisHomingState::Failed
+                }
+            };
+            publish(&status);
+        }
+
+        for axis in status.axes.iter_mut() {
+            if axis.state == AxisHomingState::Homing {
+                axis.state = AxisHomingState::Failed;
+            }
+        }
+    }
+
+    for (axis, definition) in status.axes.iter_mut().zip(axes) {
+        if axis.state == AxisHomingState::NotHomed {
+            warn!(
+                "Axis skipped, an axis it depends on was not homed. axis: {}, after: {:?}",
+                axis.axis, definition.after
+            );
+            axis.state = AxisHomingState::Skipped;
+        }
+    }
+    status.running = false;
+    publish(&status);
+
+    status
+}
+
+fn is_homed(status: &HomingStatus, name: &str) -> bool {
+    status
+        .axes
+        .iter()
+        .any(|axis| axis.axis == name && axis.state == AxisHomingState::Homed)
+}
+
+/// Homes the axes and updates the homed readiness check, the caller must have started the homing with
+/// [`JobControl::start_homing`], so that a job can't be started while the machine is homing.
+pub async fn homing_runner<H: AxisHomer>(
+    stack: RouterStack,
+    homer: H,
+    config: HomingConfig,
+    job_control: Arc<Mutex<JobControl>>,
+    readiness: Arc<Mutex<Readiness>>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    // the previous homing is lost as soon as an axis moves
+    readiness
+        .lock()
+        .await
+        .update(ReadinessCheck::Homed, CheckState::Failed);
+
+    let mut publish = |status: &HomingStatus| {
+        if let Err(e) = stack
+            .topics()
+            .broadcast::<HomingStatusTopic>(status, None)
+        {
+            debug!("Unable to publish homing status, error: {:?}", e);
+        }
+    };
+
+    let timeout = Duration::from_millis(config.timeout_ms);
+    select! {
+        _ = &mut app_shutdown_handler => {
+            warn!("Homing interrupted by shutdown.");
+        }
+        status = home_all(&homer, &config.axes, timeout, &mut publish) => {
+            let state = match status.all_homed() {
+                true => CheckState::Passed,
+                false => CheckState::Failed,
+            };
+            readiness
+                .lock()
+                .await
+                .update(ReadinessCheck::Homed, state);
+            info!("Homing finished. all_homed: {}", status.all_homed());
+        }
+    }
+
+    job_control.lock().await.finish();
+    info!("homing runner shutdown");
+}
@@ -0,0 +1,192 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::bail;
+use operator_shared::homing::{AxisHomingState, HomingStatus};
+use tokio::time;
+
+use super::{AxisHomer, home_all};
+use crate::config::{HomingAxisDefinition, HomingConfig};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HomerCall {
+    Started(u8),
+    Finished(u8),
+}
+
+#[derive(Clone, Default)]
+struct FakeHomer {
+    calls: Arc<Mutex<Vec<HomerCall>>>,
+    failing: Vec<u8>,
+    hanging: Vec<u8>,
+}
+
+impl FakeHomer {
+    fn calls(&self) -> Vec<HomerCall> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl AxisHomer for FakeHomer {
+    fn home<'a>(&'a self, axis: u8) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(HomerCall::Started(axis));
+            if self.hanging.contains(&axis) {
+                time::sleep(Duration::from_secs(60)).await;
+            }
+            // give the axes that are homed in parallel a chance to start
+            time::sleep(Duration::from_millis(10)).await;
+            self.calls
+                .lock()
+                .unwrap()
+                .push(HomerCall::Finished(axis));
+            if self.failing.contains(&axis) {
+                bail!("Endstop not found");
+            }
+            Ok(())
+        }
+    }
+}
+
+fn axis(name: &str, axis: u8, after: &[&str]) -> HomingAxisDefinition {
+    HomingAxisDefinition {
+        name: name.to_string(),
+        axis,
+        after: after
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+    }
+}
+
+fn states(status: &HomingStatus) -> Vec<(&str, AxisHomingState)> {
+    status
+        .axes
+        .iter()
+        .map(|axis| (axis.axis.as_str(), axis.state))
+        .collect()
+}
+
+#[tokio::test]
+pub async fn dependencies_are_homed_first_then_the_others_in_parallel() {
+    // given
+    let homer = FakeHomer::default();
+    let axes = HomingConfig::default().axes;
+
+    // when
+    let status = home_all(&homer, &axes, TIMEOUT, &mut |_| {}).await;
+
+    // then
+    assert!(status.all_homed());
+    assert!(!status.running);
+    let calls = homer.calls();
+    // Z, then X and Y together
+    assert_eq!(calls[..2], [HomerCall::Started(2), HomerCall::Finished(2)]);
+    assert!(calls[2..4].contains(&HomerCall::Started(0)));
+    assert!(calls[2..4].contains(&HomerCall::Started(1)));
+}
+
+#[tokio::test]
+pub async fn failed_axis_skips_dependent_axes_only() {
+    // given
+    let homer = FakeHomer {
+        failing: vec![2],
+        ..FakeHomer::default()
+    };
+    let axes = vec![axis("Z", 2, &[]), axis("X", 0, &["Z"]), axis("A", 3, &[])];
+
+    // when
+    let status = home_all(&homer, &axes, TIMEOUT, &mut |_| {}).await;
+
+    // then
+    assert!(!status.all_homed());
+    assert_eq!(states(&status), vec![
+        ("Z", AxisHomingState::Failed),
+        ("X", AxisHomingState::Skipped),
+        ("A", AxisHomingState::Homed),
+    ]);
+    assert!(
+        !homer
+            .calls()
+            .contains(&HomerCall::Started(0))
+    );
+}
+
+#[tokio::test]
+pub async fn axis_not_homed_in_time_fails() {
+    // given
+    let homer = FakeHomer {
+        hanging: vec![0],
+        ..FakeHomer::default()
+    };
+    let axes = vec![axis("X", 0, &[]), axis("Y", 1, &[])];
+
+    // when
+    let status = home_all(&homer, &axes, Duration::from_millis(100), &mut |_| {}).await;
+
+    // then
+    assert_eq!(states(&status), vec![
+        ("X", AxisHomingState::Failed),
+        ("Y", AxisHomingState::Homed)
+    ]);
+}
+
+#[tokio::test]
+pub async fn unknown_or_circular_dependencies_are_skipped() {
+    // given
+    let homer = FakeHomer::default();
+    let axes = vec![axis("X", 0, &["W"]), axis("Y", 1, &["Z"]), axis("Z", 2, &["Y"])];
+
+    // when
+    let status = home_all(&homer, &axes, TIMEOUT, &mut |_| {}).await;
+
+    // then
+    assert_eq!(states(&status), vec![
+        ("X", AxisHomingState::Skipped),
+        ("Y", AxisHomingState::Skipped),
+        ("Z", AxisHomingState::Skipped),
+    ]);
+    assert!(homer.calls().is_empty());
+}
+
+#[tokio::test]
+pub async fn every_state_change_is_published() {
+    // given
+    let homer = FakeHomer::default();
+    let axes = vec![axis("Z", 2, &[]), axis("X", 0, &["Z"])];
+    let mut published = Vec::new();
+
+    // when
+    home_all(&homer, &axes, TIMEOUT, &mut |status| published.push(status.clone())).await;
+
+    // then
+    let published = published
+        .iter()
+        .map(|status| (states(status), status.running))
+        .collect::<Vec<_>>();
+    assert_eq!(published, vec![
+        (
+            vec![("Z", AxisHomingState::Homing), ("X", AxisHomingState::NotHomed)],
+            true
+        ),
+        (
+            vec![("Z", AxisHomingState::Homed), ("X", AxisHomingState::NotHomed)],
+            true
+        ),
+        (
+            vec![("Z", AxisHomingState::Homed), ("X", AxisHomingState::Homing)],
+            true
+        ),
+        (vec![("Z", AxisHomingState::Homed), ("X", AxisHomingState::Homed)], true),
+        (
+            vec![("Z", AxisHomingState::Homed), ("X", AxisHomingState::Homed)],
+            false
+        ),
+    ]);
+}
@@ -0,0 +1,45 @@
+//! Creating a fresh install, see `--init`.
+//!
+//! The default config and the directories it refers to are embedded in the binary, so that a fresh install only
+//! needs the binary.  The default config uses the built-in test pattern instead of a camera, so the operator UI can
+//! be tried before the machine is configured.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+
+#[cfg(test)]
+mod tests;
+
+pub const CONFIG_FILE_NAME: &str = "config.ron";
+
+const DEFAULT_CONFIG: &str = include_str!("../../assets/init/config.ron");
+
+/// The directories of the default config.
+const DIRECTORIES: [&str; 2] = ["artifacts", "jobs"];
+
+/// Writes the default config and creates its directories, in `directory`.
+///
+/// An existing config is not overwritten, so that `--init` can be used to add the directories to an existing install.
+pub fn init(directory: &Path) -> anyhow::Result<()> {
+    for name in DIRECTORIES {
+        let path = directory.join(name);
+        fs::create_dir_all(&path).with_context(|| format!("Unable to create directory. path: {:?}", path))?;
+    }
+
+    let config_path = directory.join(CONFIG_FILE_NAME);
+    if config_path.exists() {
+        println!("Config file already exists, not overwritten. path: {:?}", config_path);
+    } else {
+        fs::write(&config_path, DEFAULT_CONFIG)
+            .with_context(|| format!("Unable to write config file. path: {:?}", config_path))?;
+        println!("Config file created. path: {:?}", config_path);
+    }
+
+    println!(
+        "Initialized. directory: {:?}, run the server in this directory, e.g. `server_cli --config {}`",
+        directory, CONFIG_FILE_NAME
+    );
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::PathBuf;
+
+use super::{CONFIG_FILE_NAME, DEFAULT_CONFIG, DIRECTORIES, init};
+use crate::config::{Config, StorageConfig};
+
+fn directory() -> PathBuf {
+    std::env::temp_dir().join(format!("init-test-{:016x}", rand::random::<u64>()))
+}
+
+#[test]
+pub fn default_config_is_valid() {
+    // when
+    let config = ron::from_str::<Config>(DEFAULT_CONFIG).unwrap();
+
+    // then
+    assert_eq!(config.cameras.len(), 1);
+    assert!(config.io_boards.is_empty());
+    assert!(matches!(config.storage, StorageConfig::Local { .. }));
+}
+
+#[test]
+pub fn directories_of_the_default_config_are_created() {
+    // given
+    let config = ron::from_str::<Config>(DEFAULT_CONFIG).unwrap();
+    let StorageConfig::Local {
+        directory: storage_directory,
+    } = &config.storage
+    else {
+        panic!("expected local storage");
+    };
+
+    // expect
+    for path in [storage_directory, &config.job.jobs_directory] {
+        assert!(
+            DIRECTORIES
+                .iter()
+                .any(|name| path == &PathBuf::from(name)),
+            "path: {:?}",
+            path
+        );
+    }
+}
+
+#[test]
+pub fn init_writes_the_config_and_creates_the_directories() {
+    // given
+    let directory = directory();
+
+    // when
+    init(&directory).unwrap();
+
+    // then
+    assert_eq!(
+        fs::read_to_string(directory.join(CONFIG_FILE_NAME)).unwrap(),
+        DEFAULT_CONFIG
+    );
+    for name in DIRECTORIES {
+        assert!(directory.join(name).is_dir(), "name: {}", name);
+    }
+}
+
+#[test]
+pub fn init_does_not_overwrite_an_existing_config() {
+    // given
+    let directory = directory();
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join(CONFIG_FILE_NAME), "existing").unwrap();
+
+    // when
+    init(&directory).unwrap();
+
+    // then
+    assert_eq!(
+        fs::read_to_string(directory.join(CONFIG_FILE_NAME)).unwrap(),
+        "existing"
+    );
+    assert!(directory.join("jobs").is_dir());
+}
@@ -0,0 +1,183 @@
+//! Coalesces the setpoints and commands sent to the io boards into [`CommandBatch`]es, so that commands sent close
+//! together, e.g. the setpoints of the axes of a coordinated move, share a single frame.
+//!
+//! Senders don't wait for the frame to be sent, the commands are queued for the [`batch_sender`], which sends a batch
+//! once it is full, or once the window of its first command has elapsed, so a command is delayed by at most the
+//! window.  A batch of a single command is sent as the command itself.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ioboard_shared::batch::{BatchedCommand, COMMAND_BATCH_MAX, CommandBatch};
+use log::{debug, error, info, trace};
+use machine_ids::MoveId;
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::time::{self, Duration, Instant};
+
+use super::{BatchTopic, IoBoardCommandTopic};
+use crate::config::CommandBatchingConfig;
+use crate::motion::SetpointTopic;
+use crate::networking::dead_letter;
+
+/// Collects commands until the batch is full or its window has elapsed.
+#[derive(Debug)]
+pub struct Batcher {
+    window: Duration,
+    /// oldest first
+    pending: Vec<BatchedCommand>,
+    /// when the pending commands must be sent, `None` when there are none
+    deadline: Option<Instant>,
+}
+
+impl Batcher {
+    /// A zero `window` sends each command on its own.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: Vec::with_capacity(COMMAND_BATCH_MAX),
+            deadline: None,
+        }
+    }
+
+    /// Returns the commands to send now, if the batch is full, or the window is zero.
+    pub fn push(&mut self, command: BatchedCommand, now: Instant) -> Option<Vec<BatchedCommand>> {
+        self.pending.push(command);
+        if self.deadline.is_none() {
+            self.deadline = Some(now + self.window);
+        }
+
+        match self.pending.len() >= COMMAND_BATCH_MAX || self.window.is_zero() {
+            true => self.take(),
+            false => None,
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns the pending commands, if any.
+    pub fn take(&mut self) -> Option<Vec<BatchedCommand>> {
+        self.deadline = None;
+        match self.pending.is_empty() {
+            true => None,
+            false => Some(std::mem::replace(
+                &mut self.pending,
+                Vec::with_capacity(COMMAND_BATCH_MAX),
+            )),
+        }
+    }
+}
+
+/// Converts the commands to a batch, at most [`COMMAND_BATCH_MAX`] commands.
+pub fn to_batch(commands: &[BatchedCommand]) -> CommandBatch {
+    let mut batch = CommandBatch {
+        commands: [None; COMMAND_BATCH_MAX],
+    };
+    for (entry, command) in batch.commands.iter_mut().zip(commands) {
+        *entry = Some(*command);
+    }
+    batch
+}
+
+/// Queues commands for the [`batch_sender`], cheap to clone, one for each task that sends commands.
+///
+/// Clones share the identifiers of the moves, see [`CommandBatcher::next_move_id`].
+#[derive(Debug, Clone)]
+pub struct CommandBatcher {
+    tx: mpsc::UnboundedSender<BatchedCommand>,
+    next_move_id: Arc<AtomicU32>,
+}
+
+impl CommandBatcher {
+    /// The receiver is for the [`batch_sender`].
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<BatchedCommand>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (
+            Self {
+                tx,
+                // not starting at 0, so the first move after a restart of the server isn't taken for the last move
+                // before it
+                next_move_id: Arc::new(AtomicU32::new(rand::random())),
+            },
+            rx,
+        )
+    }
+
+    /// Does not wait for the command to be sent, failures are only logged, the same as sending the command directly.
+    pub fn send(&self, command: BatchedCommand) {
+        if self.tx.send(command).is_err() {
+            error!("Batch sender stopped, command not sent. command: {:?}", command);
+        }
+    }
+
+    /// For the setpoints of a new move, unique across the tasks that send setpoints.
+    pub fn next_move_id(&self) -> MoveId {
+        MoveId::new(
+            self.next_move_id
+                .fetch_add(1, Ordering::Relaxed),
+        )
+    }
+}
+
+fn send_commands(stack: &RouterStack, commands: &[BatchedCommand]) {
+    trace!("Sending commands. count: {}", commands.len());
+    // TODO address the commands to the io board instead of broadcasting them
+    let result = match commands {
+        [BatchedCommand::Setpoint(setpoint)] => stack
+            .topics()
+            .broadcast::<SetpointTopic>(setpoint, None)
+            .inspect_err(dead_letter::broadcast_failed::<SetpointTopic>),
+        [BatchedCommand::Command(command)] => stack
+            .topics()
+            .broadcast::<IoBoardCommandTopic>(command, None)
+            .inspect_err(dead_letter::broadcast_failed::<IoBoardCommandTopic>),
+        commands => stack
+            .topics()
+            .broadcast::<BatchTopic>(&to_batch(commands), None)
+            .inspect_err(dead_letter::broadcast_failed::<BatchTopic>),
+    };
+    if let Err(e) = result {
+        error!("Unable to send commands. count: {}, error: {:?}", commands.len(), e);
+    }
+}
+
+/// Sends the commands queued by the [`CommandBatcher`]s.
+///
+/// Stops once every [`CommandBatcher`] has been dropped, not on shutdown, so that the tasks that move the machine on
+/// shutdown, e.g. parking the head, can still send setpoints.  The pending commands are sent before it stops.
+pub async fn batch_sender(
+    stack: RouterStack,
+    config: CommandBatchingConfig,
+    mut command_rx: mpsc::UnboundedReceiver<BatchedCommand>,
+) {
+    let mut batcher = Batcher::new(Duration::from_micros(config.window_us));
+    info!("Batch sender started. window: {}us", config.window_us);
+
+    loop {
+        let deadline = batcher.deadline();
+        select! {
+            command = command_rx.recv() => {
+                let Some(command) = command else {
+                    break
+                };
+                if let Some(commands) = batcher.push(command, Instant::now()) {
+                    send_commands(&stack, &commands);
+                }
+            }
+            _ = time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                if let Some(commands) = batcher.take() {
+                    send_commands(&stack, &commands);
+                }
+            }
+        }
+    }
+
+    if let Some(commands) = batcher.take() {
+        debug!("Sending pending commands. count: {}", commands.len());
+        send_commands(&stack, &commands);
+    }
+    info!("batch sender shutdown");
+}
@@ -1,18 +1,150 @@
+use std::collections::BTreeSet;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use ergot::toolkits::tokio_udp::RouterStack;
-use ergot::topic;
-use ioboard_shared::commands::IoBoardCommand;
-use log::info;
+use ergot::{endpoint, topic};
+use ioboard_shared::analog::{AnalogRequest, AnalogResponse};
+use ioboard_shared::batch::{BatchedCommand, CommandBatch};
+use ioboard_shared::commands::{IdempotencyKey, IoBoardCommand, Sequenced};
+use ioboard_shared::dispenser::{DispenserRequest, DispenserResponse};
+use ioboard_shared::events::IoBoardEvent;
+use ioboard_shared::expansion::{ExpansionRequest, ExpansionResponse};
+use ioboard_shared::force::ForceTrace;
+use ioboard_shared::homing::{HomingRequest, HomingResponse};
+use ioboard_shared::motion::{FlushQueueRequest, FlushQueueResponse, MotionCommandRequest, MotionCommandResponse};
+use ioboard_shared::power::{PowerRequest, PowerResponse};
+use ioboard_shared::resonance::{ResonanceSweepRequest, ResonanceSweepResponse};
+use ioboard_shared::safe_z::{SafeZRequest, SafeZResponse};
+use ioboard_shared::thermal::{TemperatureSensor, ThermalLevel, ThermalReading};
+use ioboard_shared::vacuum::{VacuumRequest, VacuumResponse};
+use log::{error, info, warn};
+use operator_shared::readiness::{CheckState, ReadinessCheck};
 use tokio::select;
 use tokio::sync::broadcast::Receiver;
+use tokio::sync::{Mutex, mpsc};
 use tokio::time::Duration;
 
+use self::batching::CommandBatcher;
 use crate::AppEvent;
+use crate::feeders::Feeders;
+use crate::parking::{self, ParkTrigger};
+use crate::readiness::Readiness;
+
+pub mod batching;
+
+#[cfg(test)]
+mod tests;
 
 pub const IOBOARD_TX_BUFFER_SIZE: usize = 4096;
 
 topic!(IoBoardCommandTopic, IoBoardCommand, "topic/ioboard/command");
+topic!(BatchTopic, CommandBatch, "topic/ioboard/batch");
+topic!(IoBoardEventTopic, IoBoardEvent, "topic/ioboard/event");
+topic!(ForceTopic, ForceTrace, "topic/ioboard/force");
+topic!(ThermalTopic, ThermalReading, "topic/ioboard/thermal");
+
+// use `ergot_util::ClientWrapper::request_with_retry` with a `CommandSequencer` for these, so that a request that
+// timed out on a lossy link can be re-sent without being executed twice.
+endpoint!(
+    PowerEndpoint,
+    Sequenced<PowerRequest>,
+    PowerResponse,
+    "topic/ioboard/power"
+);
+endpoint!(
+    VacuumEndpoint,
+    Sequenced<VacuumRequest>,
+    VacuumResponse,
+    "topic/ioboard/vacuum"
+);
+endpoint!(
+    DispenserEndpoint,
+    Sequenced<DispenserRequest>,
+    DispenserResponse,
+    "topic/ioboard/dispenser"
+);
+endpoint!(
+    HomingEndpoint,
+    Sequenced<HomingRequest>,
+    HomingResponse,
+    "topic/ioboard/homing"
+);
+endpoint!(
+    SafeZEndpoint,
+    Sequenced<SafeZRequest>,
+    SafeZResponse,
+    "topic/ioboard/safe-z"
+);
+endpoint!(
+    ExpansionEndpoint,
+    Sequenced<ExpansionRequest>,
+    ExpansionResponse,
+    "topic/ioboard/expansion"
+);
+endpoint!(
+    AnalogEndpoint,
+    Sequenced<AnalogRequest>,
+    AnalogResponse,
+    "topic/ioboard/analog/config"
+);
+endpoint!(
+    FlushQueueEndpoint,
+    Sequenced<FlushQueueRequest>,
+    FlushQueueResponse,
+    "topic/ioboard/motion/flush"
+);
+endpoint!(
+    MotionCommandEndpoint,
+    Sequenced<MotionCommandRequest>,
+    MotionCommandResponse,
+    "topic/ioboard/motion/command"
+);
+endpoint!(
+    ResonanceSweepEndpoint,
+    Sequenced<ResonanceSweepRequest>,
+    ResonanceSweepResponse,
+    "topic/ioboard/resonance-sweep"
+);
+
+/// Generates idempotency keys for io board requests, a single sequencer should be shared by all io board clients.
+pub struct CommandSequencer {
+    session: u32,
+    next_sequence: AtomicU32,
+}
+
+impl CommandSequencer {
+    pub fn new() -> Self {
+        Self {
+            // a new session each time the server starts, so the io boards don't treat new requests as duplicates
+            session: rand::random(),
+            next_sequence: AtomicU32::new(0),
+        }
+    }
+
+    /// Wrap a request with the next key, retries must re-send the returned request, not call this again.
+    pub fn sequenced<T>(&self, request: T) -> Sequenced<T> {
+        let sequence = self
+            .next_sequence
+            .fetch_add(1, Ordering::Relaxed);
+        Sequenced {
+            key: IdempotencyKey {
+                session: self.session,
+                sequence,
+            },
+            request,
+        }
+    }
+}
 
-pub async fn io_board_command_sender(stack: RouterStack, app_event_rx: Receiver<AppEvent>) {
+impl Default for CommandSequencer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub async fn io_board_command_sender(batcher: CommandBatcher, app_event_rx: Receiver<AppEvent>) {
     let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
 
     enum Phase {
@@ -31,11 +163,7 @@ pub async fn io_board_command_sender(stack: RouterStack, app_event_rx: Receiver<
                     }
                     _ = tokio::time::sleep(Duration::from_secs(1)) => {},
                 }
-                let command = IoBoardCommand::Test(ctr);
-                stack
-                    .topics()
-                    .broadcast::<IoBoardCommandTopic>(&command, None)
-                    .unwrap();
+                batcher.send(BatchedCommand::Command(IoBoardCommand::Test(ctr)));
                 ctr += 1;
                 phase = Phase::Two
             }
@@ -46,10 +174,7 @@ pub async fn io_board_command_sender(stack: RouterStack, app_event_rx: Receiver<
                     }
                     _ = tokio::time::sleep(Duration::from_secs(5)) => {},
                 }
-                stack
-                    .topics()
-                    .broadcast::<IoBoardCommandTopic>(&IoBoardCommand::BeginYeetTest, None)
-                    .unwrap();
+                batcher.send(BatchedCommand::Command(IoBoardCommand::BeginLoadCellStream));
                 phase = Phase::Three
             }
             Phase::Three => {
@@ -59,10 +184,7 @@ pub async fn io_board_command_sender(stack: RouterStack, app_event_rx: Receiver<
                     }
                     _ = tokio::time::sleep(Duration::from_secs(5)) => {},
                 }
-                stack
-                    .topics()
-                    .broadcast::<IoBoardCommandTopic>(&IoBoardCommand::EndYeetTest, None)
-                    .unwrap();
+                batcher.send(BatchedCommand::Command(IoBoardCommand::EndLoadCellStream));
 
                 phase = Phase::One
             }
@@ -70,3 +192,135 @@ pub async fn io_board_command_sender(stack: RouterStack, app_event_rx: Receiver<
     }
     info!("io board command sender shutdown");
 }
+
+/// The temperature sensors of the io board that are at the critical level.
+#[derive(Debug, Default)]
+pub struct ThermalFaults {
+    critical: BTreeSet<TemperatureSensor>,
+}
+
+impl ThermalFaults {
+    /// Returns the state of the [`ReadinessCheck::TemperaturesNormal`] check, failed while any sensor is critical.
+    pub fn update(&mut self, sensor: TemperatureSensor, level: ThermalLevel) -> CheckState {
+        match level {
+            ThermalLevel::Critical => self.critical.insert(sensor),
+            ThermalLevel::Normal | ThermalLevel::Warning => self.critical.remove(&sensor),
+        };
+
+        match self.critical.is_empty() {
+            true => CheckState::Passed,
+            false => CheckState::Failed,
+        }
+    }
+}
+
+/// Faults are forwarded to the parking runner, so that Z is raised clear of the board, smart feeders that are inserted
+/// or removed are forwarded to the feeders.
+///
+/// A critical temperature faults the machine: the io board stops motion and disables motor power, so the placement of
+/// a running job fails and the job waits for the operator, the head is parked, and the
+/// [`ReadinessCheck::TemperaturesNormal`] check fails so that no job is started until every sensor has cooled.  A
+/// disconnected or shorted temperature sensor faults the machine the same way, until it reads a temperature again.
+///
+/// A supply undervoltage faults the machine the same way, the head is parked when the fault is reported, and again when
+/// the supply is reported stable, in case the report of the fault was lost.
+pub async fn io_board_event_listener(
+    stack: RouterStack,
+    parking_tx: mpsc::Sender<ParkTrigger>,
+    feeders: Arc<Mutex<Feeders>>,
+    readiness: Arc<Mutex<Readiness>>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<IoBoardEventTopic>(16, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    let mut thermal_faults = ThermalFaults::default();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                match msg.t {
+                    IoBoardEvent::AxisDerating { axis, factor, temperature } if factor < 1.0 => {
+                        warn!("io board axis {} derated, factor: {:.2}, estimated driver temperature: {:.1}C", axis, factor, temperature);
+                    }
+                    IoBoardEvent::AxisDerating { axis, temperature, .. } => {
+                        info!("io board axis {} derating removed, estimated driver temperature: {:.1}C", axis, temperature);
+                    }
+                    IoBoardEvent::ThermalLevelChanged { sensor, level, temperature } => {
+                        match level {
+                            ThermalLevel::Critical => {
+                                error!("Machine fault, io board temperature critical, motion stopped. sensor: {:?}, temperature: {:.1}C", sensor, temperature);
+                                parking::send_trigger(&parking_tx, ParkTrigger::Fault);
+                            }
+                            _ => warn!("io board thermal level changed, sensor: {:?}, level: {:?}, temperature: {:.1}C", sensor, level, temperature),
+                        }
+                        let state = thermal_faults.update(sensor, level);
+                        readiness.lock().await.update(ReadinessCheck::TemperaturesNormal, state);
+                    }
+                    IoBoardEvent::TemperatureSensorFault { sensor, raw } => {
+                        error!("Machine fault, io board temperature sensor disconnected or shorted, motion stopped. sensor: {:?}, raw: {}", sensor, raw);
+                        parking::send_trigger(&parking_tx, ParkTrigger::Fault);
+                        let state = thermal_faults.update(sensor, ThermalLevel::Critical);
+                        readiness.lock().await.update(ReadinessCheck::TemperaturesNormal, state);
+                    }
+                    IoBoardEvent::SupplyFault { voltage } => {
+                        error!("Machine fault, io board supply undervoltage, motion stopped, power rails disabled. voltage: {:.1}V", voltage);
+                        parking::send_trigger(&parking_tx, ParkTrigger::Fault);
+                    }
+                    IoBoardEvent::SupplyUndervoltage { min_voltage, duration_ms } => {
+                        warn!("io board supply stable after undervoltage. min voltage: {:.1}V, duration: {}ms", min_voltage, duration_ms);
+                        // the fault event is often lost, the network is unlikely to be usable during the undervoltage,
+                        // parking again is harmless
+                        parking::send_trigger(&parking_tx, ParkTrigger::Fault);
+                    }
+                    IoBoardEvent::PowerRailInterlocked { rail, interlock } => {
+                        warn!("io board power rail disabled by interlock. rail: {:?}, interlock: {:?}", rail, interlock);
+                    }
+                    IoBoardEvent::SafetyInputChanged { input, tripped: true, policy } => {
+                        warn!("io board safety input tripped. input: {:?}, policy: {:?}", input, policy);
+                    }
+                    IoBoardEvent::SafetyInputChanged { input, tripped: false, .. } => {
+                        info!("io board safety input cleared. input: {:?}", input);
+                    }
+                    IoBoardEvent::AxisCrash { axis, load, baseline } => {
+                        error!("io board axis {} crash detected, motion stopped. load: {:.2}, baseline: {:.2}", axis, load, baseline);
+                        parking::send_trigger(&parking_tx, ParkTrigger::Fault);
+                    }
+                    IoBoardEvent::MoveRefusedBelowSafeZ { axis, z_position } => {
+                        error!("io board axis {} move refused, Z below the safe height. z_position: {:?}", axis, z_position);
+                        parking::send_trigger(&parking_tx, ParkTrigger::Fault);
+                    }
+                    IoBoardEvent::AxisMotionAnomaly { axis, anomaly } => {
+                        error!("io board axis {} motion anomaly detected, motion stopped. anomaly: {:?}", axis, anomaly);
+                        parking::send_trigger(&parking_tx, ParkTrigger::Fault);
+                    }
+                    IoBoardEvent::AxisDriverFault { axis } => {
+                        error!("io board axis {} driver fault, motion stopped", axis);
+                        parking::send_trigger(&parking_tx, ParkTrigger::Fault);
+                    }
+                    IoBoardEvent::FeederInserted { slot, identity } => {
+                        feeders.lock().await.insert(slot, identity);
+                    }
+                    IoBoardEvent::FeederRemoved { slot } => {
+                        feeders.lock().await.remove(slot);
+                    }
+                    IoBoardEvent::EStopChanged { source, latched: true } => {
+                        error!("io board e-stop latched, motion stopped. source: {:?}", source);
+                    }
+                    IoBoardEvent::EStopChanged { source, latched: false } => {
+                        info!("io board e-stop reset. source: {:?}", source);
+                    }
+                }
+            }
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+        }
+    }
+    info!("io board event listener shutdown");
+}
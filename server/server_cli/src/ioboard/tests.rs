@@ -0,0 +1,155 @@
+use ioboard_shared::batch::{BatchedCommand, COMMAND_BATCH_MAX};
+use ioboard_shared::commands::IoBoardCommand;
+use ioboard_shared::motion::MotionSetpoint;
+use ioboard_shared::thermal::{TemperatureSensor, ThermalLevel};
+use machine_ids::MoveId;
+use operator_shared::readiness::CheckState;
+use tokio::time::{Duration, Instant};
+
+use super::ThermalFaults;
+use super::batching::{Batcher, to_batch};
+
+fn setpoint(axis: u8, sequence: u32) -> BatchedCommand {
+    BatchedCommand::Setpoint(MotionSetpoint {
+        axis,
+        move_id: MoveId::new(0),
+        sequence,
+        position: sequence as f64 * 10.0,
+        interval_us: 1000,
+    })
+}
+
+#[test]
+pub fn commands_within_the_window_are_batched() {
+    // given
+    let now = Instant::now();
+    let mut batcher = Batcher::new(Duration::from_micros(1000));
+
+    // when
+    let first = batcher.push(setpoint(0, 0), now);
+    let second = batcher.push(setpoint(1, 0), now + Duration::from_micros(400));
+    let third = batcher.push(
+        BatchedCommand::Command(IoBoardCommand::Test(42)),
+        now + Duration::from_micros(800),
+    );
+
+    // then
+    assert!(first.is_none());
+    assert!(second.is_none());
+    assert!(third.is_none());
+    // the deadline is the window of the first command
+    assert_eq!(batcher.deadline(), Some(now + Duration::from_micros(1000)));
+    assert_eq!(
+        batcher.take(),
+        Some(vec![
+            setpoint(0, 0),
+            setpoint(1, 0),
+            BatchedCommand::Command(IoBoardCommand::Test(42)),
+        ])
+    );
+}
+
+#[test]
+pub fn full_batch_is_sent_immediately() {
+    // given
+    let now = Instant::now();
+    let mut batcher = Batcher::new(Duration::from_micros(1000));
+    for sequence in 0..(COMMAND_BATCH_MAX as u32 - 1) {
+        assert!(
+            batcher
+                .push(setpoint(0, sequence), now)
+                .is_none()
+        );
+    }
+
+    // when
+    let commands = batcher.push(setpoint(0, COMMAND_BATCH_MAX as u32 - 1), now);
+
+    // then
+    let commands = commands.unwrap();
+    assert_eq!(commands.len(), COMMAND_BATCH_MAX);
+    assert_eq!(commands[0], setpoint(0, 0));
+    assert!(batcher.deadline().is_none());
+}
+
+#[test]
+pub fn zero_window_sends_each_command_on_its_own() {
+    // given
+    let now = Instant::now();
+    let mut batcher = Batcher::new(Duration::ZERO);
+
+    // expect
+    assert_eq!(batcher.push(setpoint(0, 0), now), Some(vec![setpoint(0, 0)]));
+    assert_eq!(batcher.push(setpoint(1, 0), now), Some(vec![setpoint(1, 0)]));
+    assert!(batcher.deadline().is_none());
+}
+
+#[test]
+pub fn take_without_pending_commands_gives_nothing() {
+    // given
+    let now = Instant::now();
+    let mut batcher = Batcher::new(Duration::from_micros(1000));
+    let _ = batcher.push(setpoint(0, 0), now);
+    let _ = batcher.take();
+
+    // expect
+    assert!(batcher.take().is_none());
+    assert!(batcher.deadline().is_none());
+}
+
+#[test]
+pub fn batch_keeps_the_order_of_the_commands() {
+    // given
+    let commands = [
+        setpoint(0, 0),
+        BatchedCommand::Command(IoBoardCommand::BeginLoadCellStream),
+        setpoint(1, 0),
+    ];
+
+    // when
+    let batch = to_batch(&commands);
+
+    // then
+    assert_eq!(
+        batch
+            .iter()
+            .copied()
+            .collect::<Vec<_>>(),
+        commands.to_vec()
+    );
+    assert!(
+        batch.commands[commands.len()..]
+            .iter()
+            .all(Option::is_none)
+    );
+}
+
+#[test]
+pub fn the_machine_is_faulted_until_every_critical_sensor_has_cooled() {
+    // given
+    let mut faults = ThermalFaults::default();
+
+    // expect
+    assert_eq!(
+        faults.update(TemperatureSensor::Driver(0), ThermalLevel::Warning),
+        CheckState::Passed
+    );
+    assert_eq!(
+        faults.update(TemperatureSensor::Driver(0), ThermalLevel::Critical),
+        CheckState::Failed
+    );
+    assert_eq!(
+        faults.update(TemperatureSensor::Ambient, ThermalLevel::Critical),
+        CheckState::Failed
+    );
+
+    // and
+    assert_eq!(
+        faults.update(TemperatureSensor::Driver(0), ThermalLevel::Warning),
+        CheckState::Failed
+    );
+    assert_eq!(
+        faults.update(TemperatureSensor::Ambient, ThermalLevel::Normal),
+        CheckState::Passed
+    );
+}
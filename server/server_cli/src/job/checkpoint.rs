@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use log::warn;
+use machine_ids::{FeederId, JobId};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use super::Job;
+
+/// The progress of a job, saved after each placement so that an interrupted job can be resumed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub job: JobId,
+    /// index of the first placement that has been neither placed nor skipped
+    pub next_placement: usize,
+    pub placed: u32,
+    pub skipped: u32,
+    /// the remaining parts, by feeder
+    #[serde(default)]
+    pub feeder_counts: BTreeMap<FeederId, u32>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Checkpoint {
+    /// Before the first placement.
+    pub fn new(job: &JobId) -> Self {
+        Self {
+            job: job.clone(),
+            next_placement: 0,
+            placed: 0,
+            skipped: 0,
+            feeder_counts: BTreeMap::new(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// Returns `None` if the checkpoint is not for the job, e.g. because a different job was given to the server.
+    pub fn for_job(self, job: &Job) -> Option<Checkpoint> {
+        if self.job != job.name {
+            warn!(
+                "Ignoring checkpoint of a different job. job: {}, checkpoint: {}",
+                job.name, self.job
+            );
+            return None;
+        }
+        if self.next_placement > job.placements.len() {
+            warn!(
+                "Ignoring checkpoint beyond the end of the job. job: {}, placements: {}, next_placement: {}",
+                job.name,
+                job.placements.len(),
+                self.next_placement
+            );
+            return None;
+        }
+        Some(self)
+    }
+}
+
+/// Where checkpoints are saved.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait Checkpointer {
+    fn save<'a>(&'a mut self, checkpoint: &'a Checkpoint) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+
+    /// Called when the job has finished or has been aborted, there is nothing to resume.
+    fn clear<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+}
+
+/// A single checkpoint file, written to a temporary file first and then renamed, so that a power loss while saving
+/// leaves the previous checkpoint intact.
+#[derive(Debug, Clone)]
+pub struct CheckpointStore {
+    path: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+        }
+    }
+
+    /// Returns `None` if there is no checkpoint.
+    pub async fn load(&self) -> anyhow::Result<Option<Checkpoint>> {
+        let content = match fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(anyhow!(
+                    "Unable to read checkpoint. path: {:?}, error: {}",
+                    self.path,
+                    e
+                ));
+            }
+        };
+        let checkpoint = ron::from_str::<Checkpoint>(&content)
+            .map_err(|e| anyhow!("Unable to parse checkpoint. path: {:?}, error: {}", self.path, e))?;
+        Ok(Some(checkpoint))
+    }
+
+    fn temporary_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".tmp");
+        PathBuf::from(path)
+    }
+}
+
+impl Checkpointer for CheckpointStore {
+    fn save<'a>(&'a mut self, checkpoint: &'a Checkpoint) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let content = ron::ser::to_string_pretty(checkpoint, ron::ser::PrettyConfig::default())?;
+
+            let temporary_path = self.temporary_path();
+            let mut file = fs::File::create(&temporary_path).await?;
+            file.write_all(content.as_bytes())
+                .await?;
+            // the checkpoint must be on disk before it replaces the previous one
+            file.sync_all().await?;
+            drop(file);
+
+            fs::rename(&temporary_path, &self.path).await?;
+            Ok(())
+        }
+    }
+
+    fn clear<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            match fs::remove_file(&self.path).await {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(anyhow!(
+                    "Unable to remove checkpoint. path: {:?}, error: {}",
+                    self.path,
+                    e
+                )),
+            }
+        }
+    }
+}
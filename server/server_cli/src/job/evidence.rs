@@ -0,0 +1,203 @@
+//! Evidence of each placement, see [`EvidencePlacer`].
+//!
+//! The target location is captured by a down camera before and after the part is placed, and the images are compared,
+//! see [`PlacementDifference`].  A placement that changed too little of the target region, e.g. the part was not
+//! placed, or too much, e.g. neighbouring parts were disturbed, is flagged as suspect for review.  The evidence is
+//! written to the job report, the placement itself does not fail.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use log::{debug, warn};
+use machine_ids::BoardId;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::{Operation, Placement, Placer};
+use crate::config::EvidenceConfig;
+
+/// The difference between the images before and after a placement.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlacementDifference {
+    /// the offset at which the images were registered, in pixels
+    pub shift_x: i32,
+    pub shift_y: i32,
+    /// the fraction of the target region, 0.0 to 1.0, that changed
+    pub changed: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Suspicion {
+    /// less of the target region changed than expected, e.g. the part was not placed
+    TooLittleChange,
+    /// more of the target region changed than expected, e.g. neighbouring parts were disturbed
+    TooMuchChange,
+    /// the images could not be captured or compared
+    Unavailable(String),
+}
+
+/// The evidence of a placement, as written to the job report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlacementEvidence {
+    pub placement: String,
+    pub board: Option<BoardId>,
+    /// `None` if the images could not be captured or compared
+    pub difference: Option<PlacementDifference>,
+    /// `None` unless the placement should be reviewed
+    pub suspicion: Option<Suspicion>,
+}
+
+/// The evidence of the placements of a run, shared with the job runner, which writes it to the job report.
+pub type EvidenceLog = Arc<Mutex<Vec<PlacementEvidence>>>;
+
+pub fn suspicion(difference: &PlacementDifference, config: &EvidenceConfig) -> Option<Suspicion> {
+    if difference.changed < config.min_changed {
+        Some(Suspicion::TooLittleChange)
+    } else if difference.changed > config.max_changed {
+        Some(Suspicion::TooMuchChange)
+    } else {
+        None
+    }
+}
+
+/// Captures the target location of a placement.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait PlacementCamera {
+    type Image: Send;
+
+    fn capture<'a>(
+        &'a mut self,
+        placement: &'a Placement,
+    ) -> impl Future<Output = anyhow::Result<Self::Image>> + Send + 'a;
+
+    fn compare<'a>(
+        &'a mut self,
+        before: Self::Image,
+        after: Self::Image,
+    ) -> impl Future<Output = anyhow::Result<PlacementDifference>> + Send + 'a;
+}
+
+/// Without machine vision there is no camera, see [`MachineCamera`].
+#[cfg(not(feature = "machine-vision"))]
+pub struct NoCamera;
+
+#[cfg(not(feature = "machine-vision"))]
+impl PlacementCamera for NoCamera {
+    type Image = ();
+
+    fn capture<'a>(
+        &'a mut self,
+        _placement: &'a Placement,
+    ) -> impl Future<Output = anyhow::Result<Self::Image>> + Send + 'a {
+        async move { Err(anyhow::anyhow!("No camera")) }
+    }
+
+    fn compare<'a>(
+        &'a mut self,
+        _before: Self::Image,
+        _after: Self::Image,
+    ) -> impl Future<Output = anyhow::Result<PlacementDifference>> + Send + 'a {
+        async move { Err(anyhow::anyhow!("No camera")) }
+    }
+}
+
+#[cfg(feature = "machine-vision")]
+pub type MachineCamera = super::vision::VisionPlacementCamera;
+#[cfg(not(feature = "machine-vision"))]
+pub type MachineCamera = NoCamera;
+
+/// Records the evidence of each placed part, dispense operations, and every placement without a camera, are placed
+/// unchanged.
+pub struct EvidencePlacer<P: Placer, C: PlacementCamera> {
+    placer: P,
+    camera: Option<C>,
+    config: EvidenceConfig,
+    log: EvidenceLog,
+}
+
+impl<P: Placer, C: PlacementCamera> EvidencePlacer<P, C> {
+    pub fn new(placer: P, camera: Option<C>, config: EvidenceConfig, log: EvidenceLog) -> Self {
+        Self {
+            placer,
+            camera,
+            config,
+            log,
+        }
+    }
+}
+
+impl<P: Placer + Send, C: PlacementCamera + Send> Placer for EvidencePlacer<P, C> {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let Some(camera) = self.camera.as_mut() else {
+                return self.placer.place(placement).await;
+            };
+            // nothing is placed in a dry run
+            if !matches!(placement.operation, Operation::Place) || self.placer.dry_run() {
+                return self.placer.place(placement).await;
+            }
+
+            let before = camera.capture(placement).await;
+            let result = self.placer.place(placement).await;
+            if result.is_err() {
+                // the placement is retried, skipped or aborted, the evidence is of the final attempt
+                return result;
+            }
+
+            let difference = match before {
+                Ok(before) => match camera.capture(placement).await {
+                    Ok(after) => camera.compare(before, after).await,
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+
+            let evidence = match difference {
+                Ok(difference) => {
+                    let suspicion = suspicion(&difference, &self.config);
+                    match &suspicion {
+                        Some(suspicion) => warn!(
+                            "Suspect placement. placement: {}, suspicion: {:?}, difference: {:?}",
+                            placement.reference, suspicion, difference
+                        ),
+                        None => debug!(
+                            "Placement evidence. placement: {}, difference: {:?}",
+                            placement.reference, difference
+                        ),
+                    }
+                    PlacementEvidence {
+                        placement: placement.reference.clone(),
+                        board: placement.board,
+                        difference: Some(difference),
+                        suspicion,
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        "Placement evidence unavailable. placement: {}, error: {:?}",
+                        placement.reference, e
+                    );
+                    PlacementEvidence {
+                        placement: placement.reference.clone(),
+                        board: placement.board,
+                        difference: None,
+                        suspicion: Some(Suspicion::Unavailable(format!("{}", e))),
+                    }
+                }
+            };
+            self.log.lock().await.push(evidence);
+
+            result
+        }
+    }
+
+    fn skips(&self, placement: &Placement) -> bool {
+        self.placer.skips(placement)
+    }
+
+    fn dry_run(&self) -> bool {
+        self.placer.dry_run()
+    }
+}
@@ -0,0 +1,823 @@
+//! Job execution, placing each placement of a job in turn.
+//!
+//! When a placement fails irrecoverably the job is paused and an [`Intervention`] is published, the operator chooses
+//! to retry the placement, skip it, or abort the job.  The moves already streamed to the io boards are aborted when
+//! the job is paused, see [`QueueFlusher`].
+
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use chrono::Utc;
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::topic;
+use ioboard_shared::force::ForceTrace;
+use log::{debug, error, info, warn};
+use machine_geometry::Point;
+use machine_ids::{BoardId, FeederId, JobId};
+use operator_shared::feeders::FeederError;
+use operator_shared::homing::HomingError;
+use operator_shared::job::{
+    Intervention, InterventionError, InterventionResolution, JobCheckpoint, JobEvent, ResumeChoice, ResumeError,
+};
+use operator_shared::readiness::StartJobError;
+use operator_shared::resonance::ResonanceSweepError;
+use operator_shared::test_area::TestShotError;
+use serde::{Deserialize, Serialize};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::{Mutex, broadcast, mpsc, oneshot};
+use tokio::time;
+
+use self::checkpoint::{Checkpoint, CheckpointStore, Checkpointer};
+use self::evidence::{EvidenceLog, EvidencePlacer, PlacementCamera};
+use self::panel::{BoardInspector, Panel, PanelInspection, PanelPlacer, inspect_panel};
+use self::pick_place::MachinePickAndPlacePlacer;
+use self::report::{JobReport, write_report};
+use crate::AppEvent;
+use crate::config::{EvidenceConfig, HeadDefinition, JobConfig, NozzleCalibrationConfig};
+use crate::dispensing::{DispensingPlacer, IoBoardDispenser};
+use crate::feeders::Feeders;
+use crate::forces::{ExpectedForces, ForceLog, ForcePlacer};
+use crate::ioboard::CommandSequencer;
+use crate::motion::QueueFlusher;
+use crate::nozzles::calibration::{NozzleCalibrator, calibrate_nozzles};
+use crate::parking::{self, ParkTrigger};
+use crate::power::EnergyCounter;
+use crate::runout::{NozzleRunout, RunoutPlacer};
+use crate::storage::StorageImpl;
+
+pub mod checkpoint;
+pub mod evidence;
+pub mod panel;
+pub mod pick_place;
+pub mod report;
+pub mod simulation;
+#[cfg(feature = "machine-vision")]
+pub mod vision;
+
+#[cfg(test)]
+mod tests;
+
+topic!(JobEventTopic, JobEvent, "topic/operator/job");
+
+pub const RESOLUTIONS: [InterventionResolution; 3] = [
+    InterventionResolution::Retry,
+    InterventionResolution::Skip,
+    InterventionResolution::Abort,
+];
+
+/// The operator UI may not be connected when the intervention is first published.
+const INTERVENTION_REPUBLISH_INTERVAL: Duration = Duration::from_secs(2);
+
+const DRY_RUN_PLACEMENT_DURATION: Duration = Duration::from_millis(500);
+
+/// Loaded from a RON file, see [`Args::job`](crate::cli::Args::job).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub name: JobId,
+    /// in the order they are placed, for a panel those of every board once loaded, see [`panel::expand`]
+    pub placements: Vec<Placement>,
+    /// the job runs across every board of the panel
+    #[serde(default)]
+    pub panel: Option<Panel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Placement {
+    /// e.g. "R1"
+    pub reference: String,
+    /// in job coordinates
+    pub position: Point,
+    /// in degrees
+    pub rotation: f64,
+    /// the feeder the part is picked from, see [`Feeders`]
+    #[serde(default)]
+    pub feeder: Option<FeederId>,
+    #[serde(default)]
+    pub operation: Operation,
+    /// the number of the board of a panel, set when the job is expanded, see [`panel::expand`]
+    #[serde(default)]
+    pub board: Option<BoardId>,
+}
+
+/// What is done at the position of a placement, dispense operations are interleaved with the placements of parts,
+/// e.g. glue dots placed before the part that is glued.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Operation {
+    /// place the part picked from the feeder
+    #[default]
+    Place,
+    /// dispense paste or glue, no part is picked
+    Dispense {
+        /// the name of a dispenser head, see [`HeadDefinition`](crate::config::HeadDefinition)
+        head: String,
+        /// overrides the dispense time of the head
+        #[serde(default)]
+        dispense_ms: Option<u64>,
+    },
+}
+
+/// Places a single placement, or dispenses for a dispense operation, see [`Operation`].
+///
+/// An error means the placement failed irrecoverably, any retries the placer is able to make by itself have already
+/// been made.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait Placer {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+
+    /// Placements that are not placed, e.g. those of a skipped board of a panel, they are skipped without taking a part
+    /// or an intervention.
+    fn skips(&self, _placement: &Placement) -> bool {
+        false
+    }
+
+    /// Nothing is moved, the placements are only logged, see [`DryRunPlacer`].
+    fn dry_run(&self) -> bool {
+        false
+    }
+}
+
+/// The operator of the machine, as seen by the job.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait JobOperator {
+    fn publish(&mut self, event: JobEvent);
+
+    /// Waits for the operator to resolve the failure of the placement.
+    fn intervene<'a>(
+        &'a mut self,
+        job: &'a Job,
+        placement: &'a Placement,
+        error: String,
+    ) -> impl Future<Output = InterventionResolution> + Send + 'a;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum JobOutcome {
+    Finished { placed: u32, skipped: u32 },
+    Aborted { placed: u32, skipped: u32 },
+}
+
+/// Starts with the placement after the last completed placement of the `checkpoint`, if given, and saves a checkpoint
+/// after each placement.
+///
+/// A part is taken from the feeder of the placement before each attempt, a feeder that is out of stock requires an
+/// intervention, the same as a failed placement.
+pub async fn run_job<P: Placer, O: JobOperator, C: Checkpointer>(
+    job: &Job,
+    checkpoint: Option<Checkpoint>,
+    feeders: &Mutex<Feeders>,
+    placer: &mut P,
+    operator: &mut O,
+    checkpointer: &mut C,
+) -> JobOutcome {
+    let mut progress = match checkpoint {
+        Some(checkpoint) => {
+            info!(
+                "Job resumed. job: {}, next_placement: {}, placed: {}, skipped: {}",
+                job.name, checkpoint.next_placement, checkpoint.placed, checkpoint.skipped
+            );
+            checkpoint
+        }
+        None => {
+            info!("Job started. job: {}, placements: {}", job.name, job.placements.len());
+            // the checkpoint of an earlier run that the operator chose to restart
+            clear_checkpoint(checkpointer).await;
+            Checkpoint::new(&job.name)
+        }
+    };
+    operator.publish(JobEvent::Started {
+        job: job.name.clone(),
+        placements: job.placements.len() as u32,
+        placed: progress.placed,
+        skipped: progress.skipped,
+        dry_run: placer.dry_run(),
+    });
+
+    while let Some(placement) = job
+        .placements
+        .get(progress.next_placement)
+    {
+        if placer.skips(placement) {
+            debug!(
+                "Placement skipped by the placer. job: {}, placement: {}",
+                job.name, placement.reference
+            );
+            progress.skipped += 1;
+            progress.next_placement += 1;
+            save_checkpoint(checkpointer, feeders, &mut progress).await;
+            continue;
+        }
+
+        let result = match take_part(feeders, placement).await {
+            Ok(()) => placer.place(placement).await,
+            Err(e) => Err(e),
+        };
+        let error = match result {
+            Ok(()) => {
+                debug!("Placed. job: {}, placement: {}", job.name, placement.reference);
+                operator.publish(JobEvent::Placed {
+                    job: job.name.clone(),
+                    placement: placement.reference.clone(),
+                });
+                progress.placed += 1;
+                progress.next_placement += 1;
+                save_checkpoint(checkpointer, feeders, &mut progress).await;
+                continue;
+            }
+            Err(e) => e,
+        };
+
+        warn!(
+            "Placement failed, waiting for the operator. job: {}, placement: {}, error: {:?}",
+            job.name, placement.reference, error
+        );
+        let resolution = operator
+            .intervene(job, placement, format!("{}", error))
+            .await;
+        info!(
+            "Intervention resolved. job: {}, placement: {}, resolution: {:?}",
+            job.name, placement.reference, resolution
+        );
+
+        match resolution {
+            InterventionResolution::Retry => {}
+            InterventionResolution::Skip => {
+                progress.skipped += 1;
+                progress.next_placement += 1;
+                save_checkpoint(checkpointer, feeders, &mut progress).await;
+            }
+            InterventionResolution::Abort => {
+                warn!(
+                    "Job aborted. job: {}, placed: {}, skipped: {}",
+                    job.name, progress.placed, progress.skipped
+                );
+                clear_checkpoint(checkpointer).await;
+                operator.publish(JobEvent::Aborted {
+                    job: job.name.clone(),
+                    placed: progress.placed,
+                    skipped: progress.skipped,
+                });
+                return JobOutcome::Aborted {
+                    placed: progress.placed,
+                    skipped: progress.skipped,
+                };
+            }
+        }
+    }
+
+    info!(
+        "Job finished. job: {}, placed: {}, skipped: {}",
+        job.name, progress.placed, progress.skipped
+    );
+    clear_checkpoint(checkpointer).await;
+    operator.publish(JobEvent::Finished {
+        job: job.name.clone(),
+        placed: progress.placed,
+        skipped: progress.skipped,
+    });
+    JobOutcome::Finished {
+        placed: progress.placed,
+        skipped: progress.skipped,
+    }
+}
+
+/// Takes a part from the feeder of the placement, if it has one.
+pub async fn take_part(feeders: &Mutex<Feeders>, placement: &Placement) -> anyhow::Result<()> {
+    let Some(feeder) = &placement.feeder else {
+        return Ok(());
+    };
+
+    match feeders.lock().await.pick(feeder) {
+        Ok(remaining) => {
+            debug!("Part taken. feeder: {}, remaining: {}", feeder, remaining);
+            Ok(())
+        }
+        Err(FeederError::OutOfStock) => Err(anyhow!("Feeder out of stock. feeder: {}", feeder)),
+        Err(FeederError::UnknownFeeder) => Err(anyhow!("Unknown feeder. feeder: {}", feeder)),
+        Err(FeederError::ReversedTape) => Err(anyhow!("Feeder tape reversed. feeder: {}", feeder)),
+    }
+}
+
+/// A job that cannot be checkpointed can still be run, it just cannot be resumed.
+async fn save_checkpoint<C: Checkpointer>(checkpointer: &mut C, feeders: &Mutex<Feeders>, progress: &mut Checkpoint) {
+    progress.feeder_counts = feeders.lock().await.counts();
+    progress.updated_at = Utc::now();
+    if let Err(e) = checkpointer.save(progress).await {
+        error!("Unable to save checkpoint. job: {}, error: {:?}", progress.job, e);
+    }
+}
+
+async fn clear_checkpoint<C: Checkpointer>(checkpointer: &mut C) {
+    if let Err(e) = checkpointer.clear().await {
+        error!("Unable to clear checkpoint. error: {:?}", e);
+    }
+}
+
+/// Shared between the operator command listener, which starts jobs and resolves interventions, and the job runner.
+pub struct JobControl {
+    job: Option<Job>,
+    running: bool,
+    /// the progress of an interrupted run of the job
+    checkpoint: Option<Checkpoint>,
+    /// how to continue the interrupted run, confirmed by the operator
+    resume: Option<ResumeChoice>,
+    intervention: Option<PendingIntervention>,
+    next_intervention_id: u32,
+}
+
+struct PendingIntervention {
+    id: u32,
+    resolution_tx: oneshot::Sender<InterventionResolution>,
+}
+
+impl JobControl {
+    /// The `checkpoint` is ignored unless it is for the `job`.
+    pub fn new(job: Option<Job>, checkpoint: Option<Checkpoint>) -> Self {
+        let checkpoint = match (&job, checkpoint) {
+            (Some(job), Some(checkpoint)) => checkpoint.for_job(job),
+            (None, Some(checkpoint)) => {
+                warn!("Ignoring checkpoint, there is no job. checkpoint: {}", checkpoint.job);
+                None
+            }
+            (_, None) => None,
+        };
+
+        Self {
+            job,
+            running: false,
+            checkpoint,
+            resume: None,
+            intervention: None,
+            next_intervention_id: 0,
+        }
+    }
+
+    /// Returns the job to run and the checkpoint to resume it from, the caller must call [`JobControl::finish`] when
+    /// the job has finished.
+    pub fn start(&mut self) -> Result<(Job, Option<Checkpoint>), StartJobError> {
+        if self.running {
+            return Err(StartJobError::Running);
+        }
+        let job = self
+            .job
+            .clone()
+            .ok_or(StartJobError::NoJob)?;
+
+        let checkpoint = match (&self.checkpoint, self.resume) {
+            (Some(_), None) => return Err(StartJobError::ResumeUnconfirmed),
+            (Some(_), Some(ResumeChoice::Resume)) => self.checkpoint.take(),
+            (Some(_), Some(ResumeChoice::Restart)) => {
+                self.checkpoint = None;
+                None
+            }
+            (None, _) => None,
+        };
+        self.resume = None;
+
+        self.running = true;
+        Ok((job, checkpoint))
+    }
+
+    /// Replaces the job, e.g. when the operator scans the ID of a board.  The `checkpoint` is ignored unless it is for
+    /// the `job`.
+    pub fn select(&mut self, job: Job, checkpoint: Option<Checkpoint>) -> Result<(), StartJobError> {
+        if self.running {
+            return Err(StartJobError::Running);
+        }
+
+        self.checkpoint = checkpoint.and_then(|checkpoint| checkpoint.for_job(&job));
+        self.resume = None;
+        self.job = Some(job);
+        Ok(())
+    }
+
+    /// Test shots use the machine the same as a job, jobs and other test shots are refused until
+    /// [`JobControl::finish`] is called.
+    pub fn start_test_shots(&mut self) -> Result<(), TestShotError> {
+        if self.running {
+            return Err(TestShotError::Running);
+        }
+
+        self.running = true;
+        Ok(())
+    }
+
+    /// Homing uses the same running state as a job, so that a job can't be started while the machine is homing.
+    pub fn start_homing(&mut self) -> Result<(), HomingError> {
+        if self.running {
+            return Err(HomingError::Running);
+        }
+
+        self.running = true;
+        Ok(())
+    }
+
+    /// A resonance sweep shakes an axis, jobs, test shots and homing are refused until [`JobControl::finish`] is
+    /// called.
+    pub fn start_resonance_sweep(&mut self) -> Result<(), ResonanceSweepError> {
+        if self.running {
+            return Err(ResonanceSweepError::Running);
+        }
+
+        self.running = true;
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// The selected job, `None` until a job has been loaded or selected.
+    pub fn job(&self) -> Option<&Job> {
+        self.job.as_ref()
+    }
+
+    pub fn finish(&mut self) {
+        self.running = false;
+        self.intervention = None;
+    }
+
+    /// The interrupted run of the job, `None` if there is nothing to resume.
+    pub fn checkpoint(&self) -> Option<JobCheckpoint> {
+        let job = self.job.as_ref()?;
+        let checkpoint = self.checkpoint.as_ref()?;
+
+        Some(JobCheckpoint {
+            job: checkpoint.job.clone(),
+            placements: job.placements.len() as u32,
+            placed: checkpoint.placed,
+            skipped: checkpoint.skipped,
+            confirmed: self.resume,
+        })
+    }
+
+    pub fn confirm_resume(&mut self, choice: ResumeChoice) -> Result<(), ResumeError> {
+        if self.running {
+            return Err(ResumeError::Running);
+        }
+        if self.checkpoint.is_none() {
+            return Err(ResumeError::NoCheckpoint);
+        }
+        self.resume = Some(choice);
+        Ok(())
+    }
+
+    /// Replaces any earlier intervention, the returned receiver gets the resolution.
+    pub fn begin_intervention(&mut self) -> (u32, oneshot::Receiver<InterventionResolution>) {
+        let id = self.next_intervention_id;
+        self.next_intervention_id = self
+            .next_intervention_id
+            .wrapping_add(1);
+
+        let (resolution_tx, resolution_rx) = oneshot::channel();
+        self.intervention = Some(PendingIntervention {
+            id,
+            resolution_tx,
+        });
+        (id, resolution_rx)
+    }
+
+    pub fn resolve(&mut self, id: u32, resolution: InterventionResolution) -> Result<(), InterventionError> {
+        let Some(intervention) = self.intervention.take() else {
+            return Err(InterventionError::NoIntervention);
+        };
+        if intervention.id != id {
+            let current = intervention.id;
+            self.intervention = Some(intervention);
+            return Err(InterventionError::Stale(current));
+        }
+
+        intervention
+            .resolution_tx
+            .send(resolution)
+            .map_err(|_| InterventionError::NoIntervention)
+    }
+}
+
+/// Publishes the job events to the operator UI, and waits for the operator to resolve interventions.
+///
+/// The setpoint queues are flushed before an intervention is published, when there is a `flusher`, so that the axes
+/// are stopped while the job is paused, or aborted.
+pub struct PublishingOperator {
+    stack: RouterStack,
+    job_control: Arc<Mutex<JobControl>>,
+    flusher: Option<QueueFlusher>,
+}
+
+impl PublishingOperator {
+    pub fn new(stack: RouterStack, job_control: Arc<Mutex<JobControl>>, flusher: Option<QueueFlusher>) -> Self {
+        Self {
+            stack,
+            job_control,
+            flusher,
+        }
+    }
+}
+
+impl JobOperator for PublishingOperator {
+    fn publish(&mut self, event: JobEvent) {
+        if let Err(e) = self
+            .stack
+            .topics()
+            .broadcast::<JobEventTopic>(&event, None)
+        {
+            debug!("Unable to publish job event, error: {:?}", e);
+        }
+    }
+
+    fn intervene<'a>(
+        &'a mut self,
+        job: &'a Job,
+        placement: &'a Placement,
+        error: String,
+    ) -> impl Future<Output = InterventionResolution> + Send + 'a {
+        async move {
+            if let Some(flusher) = &self.flusher {
+                let stopped = flusher.flush_all().await;
+                debug!("Job paused, axes stopped. job: {}, axes: {}", job.name, stopped.len());
+            }
+
+            let (id, mut resolution_rx) = self
+                .job_control
+                .lock()
+                .await
+                .begin_intervention();
+
+            let event = JobEvent::InterventionRequired(Intervention {
+                id,
+                job: job.name.clone(),
+                placement: placement.reference.clone(),
+                error,
+                options: RESOLUTIONS.to_vec(),
+            });
+
+            let mut ticker = time::interval(INTERVENTION_REPUBLISH_INTERVAL);
+            let resolution = loop {
+                select! {
+                    result = &mut resolution_rx => {
+                        // the sender is only dropped if the intervention was replaced, which the job runner doesn't do
+                        break result.unwrap_or(InterventionResolution::Abort)
+                    }
+                    _ = ticker.tick() => {
+                        self.publish(event.clone());
+                    }
+                }
+            };
+
+            self.publish(JobEvent::InterventionResolved {
+                id,
+                resolution,
+            });
+            resolution
+        }
+    }
+}
+
+/// Moves nothing, every placement succeeds, only for jobs run in [`JobMode::DryRun`](crate::config::JobMode::DryRun).
+pub struct DryRunPlacer;
+
+impl Placer for DryRunPlacer {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            info!(
+                "Dry run placement. placement: {}, position: {:?}, rotation: {}, operation: {:?}",
+                placement.reference, placement.position, placement.rotation, placement.operation
+            );
+            time::sleep(DRY_RUN_PLACEMENT_DURATION).await;
+            Ok(())
+        }
+    }
+
+    fn dry_run(&self) -> bool {
+        true
+    }
+}
+
+/// The placer at the bottom of the [`machine_placer`], chosen by the [`JobMode`](crate::config::JobMode).
+pub enum HeadPlacer {
+    PickAndPlace(MachinePickAndPlacePlacer),
+    DryRun(DryRunPlacer),
+}
+
+impl Placer for HeadPlacer {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            match self {
+                HeadPlacer::PickAndPlace(placer) => placer.place(placement).await,
+                HeadPlacer::DryRun(placer) => placer.place(placement).await,
+            }
+        }
+    }
+
+    fn dry_run(&self) -> bool {
+        match self {
+            HeadPlacer::PickAndPlace(placer) => placer.dry_run(),
+            HeadPlacer::DryRun(placer) => placer.dry_run(),
+        }
+    }
+}
+
+/// The placer of jobs and test shots, the parts are placed by the `head`, dispense operations use the dispenser of the io
+/// board, placed parts are corrected by the runout of the nozzle, if it has been measured.
+///
+/// The touchdowns of the placed parts are recorded to the `force_log`, see [`ForcePlacer`], and the evidence of the
+/// placed parts to the `evidence_log` when there is a `camera`, see [`EvidencePlacer`].
+#[allow(clippy::too_many_arguments)]
+pub fn machine_placer<C: PlacementCamera>(
+    head: HeadPlacer,
+    stack: RouterStack,
+    sequencer: Arc<CommandSequencer>,
+    heads: &[HeadDefinition],
+    nozzle_runout: Option<NozzleRunout>,
+    traces_rx: broadcast::Receiver<ForceTrace>,
+    expected_forces: ExpectedForces,
+    force_log: ForceLog,
+    camera: Option<C>,
+    evidence_config: EvidenceConfig,
+    evidence_log: EvidenceLog,
+) -> ForcePlacer<DispensingPlacer<EvidencePlacer<RunoutPlacer<HeadPlacer>, C>, IoBoardDispenser>> {
+    ForcePlacer::new(
+        DispensingPlacer::new(
+            EvidencePlacer::new(
+                RunoutPlacer::new(head, nozzle_runout),
+                camera,
+                evidence_config,
+                evidence_log,
+            ),
+            IoBoardDispenser::new(stack, sequencer),
+            heads,
+        ),
+        traces_rx,
+        expected_forces,
+        force_log,
+    )
+}
+
+/// The part-present thresholds of the nozzles are calibrated before the job is run, or resumed, when there is a
+/// `calibration`, see [`calibrate_nozzles`].
+///
+/// The boards of a panel are inspected before the job is run, or resumed, see [`inspect_panel`], and a report is
+/// written to the `storage` when the run ends, see [`JobReport`], with the forces recorded to the `force_log`, and the evidence recorded
+/// to the `evidence_log`, by the placer.
+///
+/// The checkpoint is kept if the job is interrupted by a shutdown, so that the job can be resumed.
+///
+/// When the job ends the head is parked, see [`ParkTrigger::JobEnd`], but not when it is interrupted by a shutdown,
+/// the head is then parked by the shutdown.  The `flusher` stops the axes when the job is paused, see
+/// [`PublishingOperator`].  The energy used while the job runs is counted by the `energy` counter, if there is a power
+/// meter.
+#[allow(clippy::too_many_arguments)]
+pub async fn job_runner<P: Placer + Send, C: NozzleCalibrator, I: BoardInspector>(
+    stack: RouterStack,
+    job: Job,
+    checkpoint: Option<Checkpoint>,
+    config: JobConfig,
+    feeders: Arc<Mutex<Feeders>>,
+    job_control: Arc<Mutex<JobControl>>,
+    placer: P,
+    flusher: Option<QueueFlusher>,
+    force_log: ForceLog,
+    evidence_log: EvidenceLog,
+    energy: Option<EnergyCounter>,
+    calibration: Option<NozzleCalibrationConfig>,
+    mut calibrator: C,
+    mut inspector: I,
+    parking_tx: mpsc::Sender<ParkTrigger>,
+    storage: Arc<StorageImpl>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let mut operator = PublishingOperator::new(stack, job_control.clone(), flusher);
+    let mut checkpoint_store = CheckpointStore::new(config.checkpoint_path.clone());
+
+    let started_at = Utc::now();
+    let mut report = JobReport {
+        job: job.name.clone(),
+        started_at,
+        ended_at: started_at,
+        outcome: None,
+        dry_run: placer.dry_run(),
+        skipped_boards: vec![],
+        error: None,
+        forces: vec![],
+        evidence: vec![],
+        energy: None,
+        nozzle_calibrations: vec![],
+    };
+    if let Some(energy) = &energy {
+        energy.start_job();
+    }
+
+    let calibrations = match &calibration {
+        Some(config) => calibrate_nozzles(&mut calibrator, config).await,
+        None => Ok(vec![]),
+    };
+
+    let inspection = match calibrations {
+        Ok(calibrations) => {
+            report.nozzle_calibrations = calibrations;
+            match &job.panel {
+                Some(panel) => inspect_panel(panel, &mut inspector)
+                    .await
+                    .inspect(|inspection| {
+                        info!(
+                            "Panel inspected. job: {}, boards: {}, skipped: {:?}",
+                            job.name,
+                            panel.boards(),
+                            inspection.skipped()
+                        )
+                    }),
+                None => Ok(PanelInspection::default()),
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    match inspection {
+        Ok(inspection) => {
+            report.skipped_boards = inspection.skipped();
+            let mut placer = PanelPlacer::new(placer, inspection);
+
+            select! {
+                _ = &mut app_shutdown_handler => {
+                    warn!("Job interrupted by shutdown. job: {}", job.name);
+                }
+                outcome = run_job(&job, checkpoint, &feeders, &mut placer, &mut operator, &mut checkpoint_store) => {
+                    debug!("Job runner finished. job: {}, outcome: {:?}", job.name, outcome);
+                    report.outcome = Some(outcome);
+                    parking::send_trigger(&parking_tx, ParkTrigger::JobEnd);
+                }
+            }
+        }
+        Err(e) => {
+            error!(
+                "Unable to calibrate the nozzles or inspect the panel, job not started. job: {}, error: {:?}",
+                job.name, e
+            );
+            let (placed, skipped) = checkpoint.map_or((0, 0), |checkpoint| (checkpoint.placed, checkpoint.skipped));
+            operator.publish(JobEvent::Aborted {
+                job: job.name.clone(),
+                placed,
+                skipped,
+            });
+            report.error = Some(format!("{}", e));
+            parking::send_trigger(&parking_tx, ParkTrigger::JobEnd);
+        }
+    }
+
+    report.ended_at = Utc::now();
+    report.forces = std::mem::take(&mut *force_log.lock().await);
+    report.evidence = std::mem::take(&mut *evidence_log.lock().await);
+    report.energy = energy.and_then(|energy| energy.finish_job());
+    match write_report(&storage, &config.report_directory, &report).await {
+        Ok(path) => info!("Job report written. job: {}, path: {:?}", job.name, path),
+        Err(e) => error!("Unable to write job report. job: {}, error: {:?}", job.name, e),
+    }
+
+    job_control.lock().await.finish();
+    info!("job runner shutdown");
+}
+
+/// The job file for a board ID, `<board_id>.ron` in the `jobs_directory`.
+///
+/// Returns `None` if the board ID is not usable as a file name, e.g. it contains a path separator, the board ID comes
+/// from a scanned code.
+pub fn job_path_for_board(jobs_directory: &Path, board_id: &str) -> Option<PathBuf> {
+    let usable = !board_id.is_empty()
+        && !board_id.starts_with('.')
+        && board_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !usable {
+        return None;
+    }
+
+    Some(jobs_directory.join(format!("{}.ron", board_id)))
+}
+
+pub fn load_job(path: &Path) -> anyhow::Result<Job> {
+    let content =
+        fs::read_to_string(path).map_err(|e| anyhow!("Unable to read job file. path: {:?}, error: {}", path, e))?;
+    let job = ron::from_str::<Job>(&content).map_err(|e| {
+        error!("Error parsing job file: {:?}", e);
+        anyhow!("Unable to load job. path: {:?}", path)
+    })?;
+    if job
+        .panel
+        .as_ref()
+        .is_some_and(|panel| panel.boards() == 0)
+    {
+        bail!("Panel has no boards. path: {:?}", path);
+    }
+
+    Ok(panel::expand(job))
+}
@@ -0,0 +1,383 @@
+//! Panels of boards, a single job runs across every board of the panel.
+//!
+//! The placements of a job are those of a single board, when the job is loaded they are repeated for every board of
+//! the panel, see [`expand`].  Before the job runs each board is inspected: its fiducials are located to correct the
+//! position and rotation of its placements, and boards that are marked bad are skipped, see [`inspect_panel`].
+
+use std::collections::BTreeMap;
+use std::future::Future;
+
+use anyhow::bail;
+use log::{info, warn};
+use machine_geometry::{AffineTransform, Point};
+use machine_ids::BoardId;
+use serde::{Deserialize, Serialize};
+
+use super::{Job, Placement, Placer};
+
+/// The boards are numbered row by row, starting at 1 for the board at the `origin`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Panel {
+    pub columns: u32,
+    pub rows: u32,
+    /// the origin of the first board, in job coordinates
+    #[serde(default)]
+    pub origin: Point,
+    /// from the origin of a board to the origin of the next board in the row (x) and in the column (y)
+    pub pitch: Point,
+    /// added to the grid position of the board, by board number, e.g. for panels with irregular spacing
+    #[serde(default)]
+    pub offsets: BTreeMap<BoardId, Point>,
+    /// in board coordinates, located on every board to correct the position and rotation of its placements
+    #[serde(default)]
+    pub fiducials: Vec<Point>,
+    #[serde(default)]
+    pub skip_marks: SkipMarks,
+}
+
+/// How the boards that must not be placed are marked, e.g. boards that failed testing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum SkipMarks {
+    /// every board is placed
+    #[default]
+    None,
+    /// a board whose fiducials cannot be located is skipped, e.g. the fiducials are covered with ink
+    MissingFiducials,
+    /// a board with a bad mark is skipped
+    BadMark {
+        /// in board coordinates
+        position: Point,
+    },
+}
+
+impl Panel {
+    pub fn boards(&self) -> u32 {
+        self.columns.saturating_mul(self.rows)
+    }
+
+    /// Every board, in order.
+    pub fn board_ids(&self) -> impl Iterator<Item = BoardId> {
+        (1..=self.boards()).map(BoardId::new)
+    }
+
+    /// In job coordinates.
+    pub fn board_origin(&self, board: BoardId) -> Point {
+        let index = board.number().saturating_sub(1);
+        let column = index % self.columns.max(1);
+        let row = index / self.columns.max(1);
+        let offset = self
+            .offsets
+            .get(&board)
+            .copied()
+            .unwrap_or_default();
+
+        Point {
+            x: self.origin.x + column as f64 * self.pitch.x + offset.x,
+            y: self.origin.y + row as f64 * self.pitch.y + offset.y,
+        }
+    }
+
+    /// Board coordinates of the `board` to job coordinates.
+    pub fn board_to_job(&self, board: BoardId, point: Point) -> Point {
+        self.board_origin(board) + point
+    }
+}
+
+/// Repeats the placements for every board of the panel, board by board, jobs without a panel are returned unchanged.
+///
+/// The reference of each placement gets the board number, e.g. "R1#2" is R1 of the second board.
+pub fn expand(job: Job) -> Job {
+    let Some(panel) = &job.panel else {
+        return job;
+    };
+
+    let placements = panel
+        .board_ids()
+        .flat_map(|board| {
+            job.placements
+                .iter()
+                .map(move |placement| Placement {
+                    reference: format!("{}#{}", placement.reference, board),
+                    position: panel.board_to_job(board, placement.position),
+                    board: Some(board),
+                    ..placement.clone()
+                })
+        })
+        .collect();
+
+    Job {
+        placements,
+        ..job
+    }
+}
+
+/// Locates the marks of the boards of a panel.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait BoardInspector {
+    /// Returns the located position of the fiducial, in job coordinates, `None` if it was not found.
+    fn locate_fiducial<'a>(
+        &'a mut self,
+        expected: Point,
+    ) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a;
+
+    fn has_bad_mark<'a>(&'a mut self, position: Point) -> impl Future<Output = anyhow::Result<bool>> + Send + 'a;
+}
+
+/// Finds every fiducial where it should be, and no bad marks, used when there is no down camera.
+pub struct NominalInspector;
+
+impl BoardInspector for NominalInspector {
+    fn locate_fiducial<'a>(
+        &'a mut self,
+        expected: Point,
+    ) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a {
+        async move { Ok(Some(expected)) }
+    }
+
+    fn has_bad_mark<'a>(&'a mut self, _position: Point) -> impl Future<Output = anyhow::Result<bool>> + Send + 'a {
+        async move { Ok(false) }
+    }
+}
+
+/// The inspector of the machine, bad marks are detected with the down camera when there is one.
+pub enum MachineInspector {
+    Nominal(NominalInspector),
+    #[cfg(feature = "machine-vision")]
+    Vision(super::vision::VisionInspector),
+}
+
+impl BoardInspector for MachineInspector {
+    fn locate_fiducial<'a>(
+        &'a mut self,
+        expected: Point,
+    ) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a {
+        async move {
+            match self {
+                MachineInspector::Nominal(inspector) => {
+                    inspector
+                        .locate_fiducial(expected)
+                        .await
+                }
+                #[cfg(feature = "machine-vision")]
+                MachineInspector::Vision(inspector) => {
+                    inspector
+                        .locate_fiducial(expected)
+                        .await
+                }
+            }
+        }
+    }
+
+    fn has_bad_mark<'a>(&'a mut self, position: Point) -> impl Future<Output = anyhow::Result<bool>> + Send + 'a {
+        async move {
+            match self {
+                MachineInspector::Nominal(inspector) => inspector.has_bad_mark(position).await,
+                #[cfg(feature = "machine-vision")]
+                MachineInspector::Vision(inspector) => inspector.has_bad_mark(position).await,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoardInspection {
+    Skipped(SkipReason),
+    /// from the expanded positions of the placements of the board to their corrected positions
+    Corrected(AffineTransform),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SkipReason {
+    BadMark,
+    MissingFiducial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SkippedBoard {
+    pub board: BoardId,
+    pub reason: SkipReason,
+}
+
+/// The inspection of every board of a panel, by board number.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PanelInspection {
+    pub boards: BTreeMap<BoardId, BoardInspection>,
+}
+
+impl PanelInspection {
+    pub fn skipped(&self) -> Vec<SkippedBoard> {
+        self.boards
+            .iter()
+            .filter_map(|(board, inspection)| match inspection {
+                BoardInspection::Skipped(reason) => Some(SkippedBoard {
+                    board: *board,
+                    reason: *reason,
+                }),
+                BoardInspection::Corrected(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// A board with a fiducial that cannot be located is an error, unless boards are marked by covering their fiducials.
+pub async fn inspect_panel<I: BoardInspector>(panel: &Panel, inspector: &mut I) -> anyhow::Result<PanelInspection> {
+    let mut inspection = PanelInspection::default();
+
+    'boards: for board in panel.board_ids() {
+        let bad_mark = match panel.skip_marks {
+            SkipMarks::BadMark {
+                position,
+            } => {
+                inspector
+                    .has_bad_mark(panel.board_to_job(board, position))
+                    .await?
+            }
+            SkipMarks::None | SkipMarks::MissingFiducials => false,
+        };
+        if bad_mark {
+            info!("Board has a bad mark, skipping. board: {}", board);
+            inspection
+                .boards
+                .insert(board, BoardInspection::Skipped(SkipReason::BadMark));
+            continue;
+        }
+
+        let mut expected = vec![];
+        let mut located = vec![];
+        for (index, fiducial) in panel.fiducials.iter().enumerate() {
+            let position = panel.board_to_job(board, *fiducial);
+            match inspector
+                .locate_fiducial(position)
+                .await?
+            {
+                Some(found) => {
+                    expected.push(position);
+                    located.push(found);
+                }
+                None if panel.skip_marks == SkipMarks::MissingFiducials => {
+                    info!(
+                        "Board fiducial missing, skipping. board: {}, fiducial: {}",
+                        board, index
+                    );
+                    inspection
+                        .boards
+                        .insert(board, BoardInspection::Skipped(SkipReason::MissingFiducial));
+                    continue 'boards;
+                }
+                None => bail!("Unable to locate fiducial. board: {}, fiducial: {}", board, index),
+            }
+        }
+
+        let correction = fiducial_correction(&expected, &located);
+        inspection
+            .boards
+            .insert(board, BoardInspection::Corrected(correction));
+    }
+
+    Ok(inspection)
+}
+
+/// The rotation, scale and translation that best maps the `expected` positions of the fiducials to their `located`
+/// positions, in the least squares sense.
+///
+/// A single fiducial only corrects the position, without fiducials there is no correction.
+pub fn fiducial_correction(expected: &[Point], located: &[Point]) -> AffineTransform {
+    let count = expected.len().min(located.len());
+    if count == 0 {
+        return AffineTransform::IDENTITY;
+    }
+
+    let centroid = |points: &[Point]| {
+        let (x, y) = points[..count]
+            .iter()
+            .fold((0.0, 0.0), |(x, y), point| (x + point.x, y + point.y));
+        Point {
+            x: x / count as f64,
+            y: y / count as f64,
+        }
+    };
+    let expected_centroid = centroid(expected);
+    let located_centroid = centroid(located);
+
+    // x' = a * x - b * y + tx, y' = b * x + a * y + ty
+    let (mut dot, mut cross, mut norm) = (0.0, 0.0, 0.0);
+    for (expected, located) in expected.iter().zip(located) {
+        let (ex, ey) = (expected.x - expected_centroid.x, expected.y - expected_centroid.y);
+        let (lx, ly) = (located.x - located_centroid.x, located.y - located_centroid.y);
+        dot += ex * lx + ey * ly;
+        cross += ex * ly - ey * lx;
+        norm += ex * ex + ey * ey;
+    }
+    let (a, b) = match norm > f64::EPSILON {
+        true => (dot / norm, cross / norm),
+        false => (1.0, 0.0),
+    };
+
+    AffineTransform {
+        xx: a,
+        xy: -b,
+        yx: b,
+        yy: a,
+        tx: located_centroid.x - (a * expected_centroid.x - b * expected_centroid.y),
+        ty: located_centroid.y - (b * expected_centroid.x + a * expected_centroid.y),
+    }
+}
+
+/// Corrects the placements of each board of a panel by the fiducials of the board, and skips the placements of
+/// skipped boards, other placements are placed unchanged.
+pub struct PanelPlacer<P: Placer> {
+    placer: P,
+    inspection: PanelInspection,
+}
+
+impl<P: Placer> PanelPlacer<P> {
+    pub fn new(placer: P, inspection: PanelInspection) -> Self {
+        Self {
+            placer,
+            inspection,
+        }
+    }
+
+    fn inspection(&self, placement: &Placement) -> Option<&BoardInspection> {
+        let board = placement.board?;
+        self.inspection.boards.get(&board)
+    }
+}
+
+impl<P: Placer + Send> Placer for PanelPlacer<P> {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let correction = match self.inspection(placement).copied() {
+                Some(BoardInspection::Corrected(correction)) => correction,
+                // the job runner doesn't place the placements that are skipped
+                Some(BoardInspection::Skipped(_)) => bail!("Board skipped. placement: {}", placement.reference),
+                None => {
+                    if let Some(board) = placement.board {
+                        warn!(
+                            "Board was not inspected, placing uncorrected. placement: {}, board: {}",
+                            placement.reference, board
+                        );
+                    }
+                    return self.placer.place(placement).await;
+                }
+            };
+
+            let corrected = Placement {
+                position: correction.apply(placement.position),
+                rotation: placement.rotation + correction.rotation_degrees(),
+                ..placement.clone()
+            };
+            self.placer.place(&corrected).await
+        }
+    }
+
+    fn skips(&self, placement: &Placement) -> bool {
+        matches!(self.inspection(placement), Some(BoardInspection::Skipped(_))) || self.placer.skips(placement)
+    }
+
+    fn dry_run(&self) -> bool {
+        self.placer.dry_run()
+    }
+}
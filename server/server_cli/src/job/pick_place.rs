@@ -0,0 +1,215 @@
+//! Picking the part of each placement from its feeder with the nozzle, and placing it on the board.
+//!
+//! Z is raised to the travel height of each move before X and Y move, see [`TravelPlanner`], the part is rotated while
+//! it is carried, and the part-present signal of the nozzle is checked after the pick, a missed pick fails the
+//! placement, the job runner then asks the operator for an intervention.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use log::{debug, warn};
+use machine_geometry::Point;
+use server_common::position::PositionHistory;
+use tokio::sync::{Mutex, watch};
+use tokio::time::{self, Duration};
+
+use super::{Operation, Placement, Placer};
+use crate::config::{ParkingConfig, PickPlaceConfig};
+use crate::coordinates::CoordinateTransform;
+use crate::feeders::Feeders;
+use crate::ioboard::batching::CommandBatcher;
+use crate::nozzles::{IoBoardVacuum, NozzleVacuum};
+use crate::parking::{HeadMover, SetpointHeadMover};
+use crate::runout::NozzleRotator;
+use crate::runout::rotator::SetpointRotator;
+use crate::safety::SafetyState;
+use crate::travel::{PartHeights, TravelPlanner};
+
+/// What the head needs to follow the setpoints planned by the server, for io boards configured with
+/// [`MotionPlanning::Server`](crate::config::MotionPlanning::Server).
+#[derive(Debug, Clone)]
+pub struct HeadMotion {
+    pub batcher: CommandBatcher,
+    pub rate_hz: u32,
+    pub position_history: PositionHistory,
+    pub safety_rx: watch::Receiver<SafetyState>,
+}
+
+/// Moves the head with setpoints, and uses the nozzle vacuum of the io board.
+pub type MachinePickAndPlacePlacer = PickAndPlacePlacer<SetpointHeadMover, SetpointRotator, IoBoardVacuum>;
+
+/// Picks the part of each placement from its feeder and places it, for dispense operations the needle is lowered at
+/// the position of the operation, the dispensing itself is done by the
+/// [`DispensingPlacer`](crate::dispensing::DispensingPlacer).
+///
+/// Positions of placements are in job coordinates, the pick positions of the feeders are in machine coordinates.
+pub struct PickAndPlacePlacer<M: HeadMover, R: NozzleRotator, V: NozzleVacuum> {
+    mover: M,
+    rotator: R,
+    vacuum: V,
+    feeders: Arc<Mutex<Feeders>>,
+    transform: CoordinateTransform,
+    planner: TravelPlanner,
+    heights: PartHeights,
+    config: PickPlaceConfig,
+    safe_z: f64,
+    /// in machine coordinates, `None` until the first move, the head may be anywhere before it
+    position: Option<Point>,
+}
+
+impl<M: HeadMover, R: NozzleRotator, V: NozzleVacuum> PickAndPlacePlacer<M, R, V> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        mover: M,
+        rotator: R,
+        vacuum: V,
+        feeders: Arc<Mutex<Feeders>>,
+        transform: CoordinateTransform,
+        heights: PartHeights,
+        parking: &ParkingConfig,
+        config: PickPlaceConfig,
+    ) -> Self {
+        Self {
+            mover,
+            rotator,
+            vacuum,
+            feeders,
+            transform,
+            planner: TravelPlanner::new(parking),
+            heights,
+            config,
+            safe_z: parking.safe_z,
+            position: None,
+        }
+    }
+
+    /// Raises Z to the travel height of the move, then moves X and Y, `carried` is the height of the part carried by
+    /// the nozzle, `Some(0.0)` when nothing is carried.
+    async fn travel_to(&mut self, target: Point, carried: Option<f64>) -> anyhow::Result<()> {
+        let z = match self.position {
+            Some(from) => self
+                .planner
+                .travel_z(from, target, carried),
+            None => self.safe_z,
+        };
+        self.mover.move_z(z).await?;
+        // the position is unknown until the move has completed
+        self.position = None;
+        self.mover.move_xy(target).await?;
+        self.position = Some(target);
+        Ok(())
+    }
+
+    async fn pick(&mut self, placement: &Placement) -> anyhow::Result<()> {
+        let feeder = placement
+            .feeder
+            .as_ref()
+            .ok_or_else(|| anyhow!("Placement has no feeder. placement: {}", placement.reference))?;
+        let pick = self
+            .feeders
+            .lock()
+            .await
+            .pick_position(feeder)
+            .map_err(|_| anyhow!("Unknown feeder. feeder: {}", feeder))?
+            .ok_or_else(|| anyhow!("Feeder has no pick position. feeder: {}", feeder))?;
+
+        let nozzle = self.config.nozzle;
+        self.travel_to(pick, Some(0.0)).await?;
+        self.mover
+            .move_z(self.config.pick_z)
+            .await?;
+        self.vacuum
+            .set_valve(nozzle, true)
+            .await?;
+        time::sleep(Duration::from_millis(self.config.pick_settle_ms)).await;
+
+        match self.vacuum.part_present(nozzle).await {
+            Ok(Some(true)) => Ok(()),
+            Ok(None) => {
+                warn!(
+                    "Nozzle has no part-present sensing, the pick is not checked. nozzle: {}, placement: {}",
+                    nozzle, placement.reference
+                );
+                Ok(())
+            }
+            result => {
+                // the nozzle is not left in the feeder while the operator intervenes
+                self.release(nozzle).await?;
+                self.mover.move_z(self.safe_z).await?;
+                match result {
+                    Ok(_) => bail!("No part picked. feeder: {}, nozzle: {}", feeder, nozzle),
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+
+    /// Closes the valve, the nozzle is raised off the part by the next move.
+    async fn release(&mut self, nozzle: u8) -> anyhow::Result<()> {
+        self.vacuum
+            .set_valve(nozzle, false)
+            .await?;
+        time::sleep(Duration::from_millis(self.config.release_ms)).await;
+        Ok(())
+    }
+}
+
+impl<M, R, V> Placer for PickAndPlacePlacer<M, R, V>
+where
+    M: HeadMover + Send,
+    R: NozzleRotator + Send,
+    V: NozzleVacuum + Send,
+{
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let target = self
+                .transform
+                .job_to_machine(placement.position);
+            let height = self.heights.height(placement);
+
+            if let Operation::Dispense {
+                ..
+            } = placement.operation
+            {
+                self.travel_to(target, Some(0.0))
+                    .await?;
+                self.mover
+                    .move_z(self.planner.work_z(height))
+                    .await?;
+                self.planner.place(target, height);
+                return Ok(());
+            }
+
+            self.pick(placement).await?;
+
+            // the parts in the tape may be rotated
+            let tape_rotation = match &placement.feeder {
+                Some(feeder) => self
+                    .feeders
+                    .lock()
+                    .await
+                    .calibration(feeder)
+                    .ok()
+                    .flatten()
+                    .map_or(0.0, |calibration| calibration.rotation),
+                None => 0.0,
+            };
+            self.travel_to(target, height).await?;
+            self.rotator
+                .rotate_to(placement.rotation - tape_rotation)
+                .await?;
+            self.mover
+                .move_z(self.planner.work_z(height))
+                .await?;
+            self.release(self.config.nozzle).await?;
+            self.planner.place(target, height);
+            debug!(
+                "Part placed. placement: {}, position: {:?}",
+                placement.reference, target
+            );
+
+            self.rotator.rotate_to(0.0).await
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use chrono::{DateTime, Utc};
+use machine_ids::JobId;
+use serde::{Deserialize, Serialize};
+
+use super::JobOutcome;
+use super::evidence::PlacementEvidence;
+use super::panel::SkippedBoard;
+use crate::forces::PlacementForces;
+use crate::nozzles::calibration::NozzleCalibration;
+use crate::power::JobEnergy;
+use crate::storage::{self, StorageImpl};
+
+/// Written when a run of a job ends, see [`JobConfig::report_directory`](crate::config::JobConfig::report_directory).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobReport {
+    pub job: JobId,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    /// `None` if the run was interrupted, e.g. by a shutdown, or could not be started
+    pub outcome: Option<JobOutcome>,
+    /// nothing was moved, the placements were only logged, see [`JobMode`](crate::config::JobMode)
+    #[serde(default)]
+    pub dry_run: bool,
+    /// the boards of the panel that were not placed, e.g. because they have a bad mark
+    pub skipped_boards: Vec<SkippedBoard>,
+    /// set when the run could not be started, e.g. the panel could not be inspected
+    pub error: Option<String>,
+    /// the touchdowns of each placed part, empty without a load cell
+    #[serde(default)]
+    pub forces: Vec<PlacementForces>,
+    /// the before and after comparison of each placed part, empty unless enabled, see
+    /// [`EvidenceConfig`](crate::config::EvidenceConfig)
+    #[serde(default)]
+    pub evidence: Vec<PlacementEvidence>,
+    /// the energy used while the job ran, `None` without a power meter
+    #[serde(default)]
+    pub energy: Option<JobEnergy>,
+    /// the part-present thresholds calibrated at the start of the run, empty unless enabled, see
+    /// [`NozzleCalibrationConfig`](crate::config::NozzleCalibrationConfig)
+    #[serde(default)]
+    pub nozzle_calibrations: Vec<NozzleCalibration>,
+}
+
+/// Returns the storage path of the report.
+pub async fn write_report(storage: &StorageImpl, directory: &str, report: &JobReport) -> anyhow::Result<String> {
+    // the job name comes from the job file
+    let name = report
+        .job
+        .as_str()
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || matches!(c, '-' | '_') {
+            true => c,
+            false => '_',
+        })
+        .collect::<String>();
+    let name = format!(
+        "job-{}-{}",
+        name,
+        report
+            .started_at
+            .format("%Y%m%d-%H%M%S")
+    );
+    storage::write_report(storage, directory, &name, report).await
+}
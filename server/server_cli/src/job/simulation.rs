@@ -0,0 +1,140 @@
+//! Simulating a job without the machine, to estimate its run time before it is started.
+//!
+//! The moves between the placements are planned with the same planner as the moves of the machine, see
+//! [`plan_setpoints`], X and Y move at the same time, so a move takes as long as the move of the slower axis.  The head
+//! may be anywhere when the job is started, the simulation starts at the first placement.
+//!
+//! Z is raised to the travel height of each move before X and Y move, and lowered once they have, see
+//! [`TravelPlanner`].
+
+use std::time::Duration;
+
+use machine_geometry::Point;
+use operator_shared::job::{ESTIMATE_PAGE_SIZE, EstimateError, JobEstimate, PlacementEstimate};
+
+use super::{Job, Operation, Placement};
+use crate::config::{HeadDefinition, ParkingConfig, SimulationConfig};
+use crate::coordinates::CoordinateTransform;
+use crate::dispensing::dispenser_config;
+use crate::motion::{AxisMove, plan_setpoints};
+use crate::travel::{PartHeights, TravelPlanner};
+
+/// The duration of a move is estimated to within this, a finer interval makes planning a long job slower.
+const SIMULATION_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Every placement of the job is simulated, including those of boards of a panel that may be skipped once the panel is
+/// inspected.
+///
+/// `motion` gives the motion limits of the axes and the travel heights, the same as for parking the head.
+pub fn simulate_job(
+    job: &Job,
+    config: &SimulationConfig,
+    motion: &ParkingConfig,
+    transform: &CoordinateTransform,
+    heads: &[HeadDefinition],
+    heights: &PartHeights,
+) -> Result<JobEstimate, EstimateError> {
+    let mut planner = TravelPlanner::new(motion);
+    // and the height of Z
+    let mut position: Option<(Point, f64)> = None;
+    let mut elapsed = Duration::ZERO;
+    let mut placements = Vec::with_capacity(job.placements.len());
+
+    for placement in job.placements.iter() {
+        let target = transform.job_to_machine(placement.position);
+        let height = heights.height(placement);
+        let work_z = planner.work_z(height);
+        let move_duration = match position {
+            Some((position, z)) => {
+                let travel_z = planner.travel_z(position, target, height);
+                let durations = (
+                    axis_move_duration(z, travel_z, motion),
+                    move_duration(position, target, motion),
+                    axis_move_duration(travel_z, work_z, motion),
+                );
+                match durations {
+                    (Some(raise), Some(travel), Some(lower)) => raise + travel + lower,
+                    _ => return Err(EstimateError::Planning(placement.reference.clone())),
+                }
+            }
+            None => Duration::ZERO,
+        };
+        let operation_duration = operation_duration(placement, config, heads)?;
+
+        placements.push(PlacementEstimate {
+            placement: placement.reference.clone(),
+            start_ms: elapsed.as_millis() as u64,
+            move_ms: move_duration.as_millis() as u64,
+            operation_ms: operation_duration.as_millis() as u64,
+        });
+        elapsed += move_duration + operation_duration;
+        planner.place(target, height);
+        position = Some((target, work_z));
+    }
+
+    Ok(JobEstimate {
+        job: job.name.clone(),
+        duration_ms: elapsed.as_millis() as u64,
+        total: placements.len() as u32,
+        placements,
+    })
+}
+
+/// The page of the placements of the `estimate` from `offset`, see [`ESTIMATE_PAGE_SIZE`].
+pub fn estimate_page(estimate: JobEstimate, offset: usize) -> JobEstimate {
+    JobEstimate {
+        placements: estimate
+            .placements
+            .into_iter()
+            .skip(offset)
+            .take(ESTIMATE_PAGE_SIZE)
+            .collect(),
+        ..estimate
+    }
+}
+
+/// The duration of the move of the slower of X and Y, `None` if either move could not be planned.
+pub fn move_duration(from: Point, to: Point, motion: &ParkingConfig) -> Option<Duration> {
+    let x = axis_move_duration(from.x, to.x, motion)?;
+    let y = axis_move_duration(from.y, to.y, motion)?;
+    Some(x.max(y))
+}
+
+/// The duration of the move of a single axis, e.g. Z, `None` if the move could not be planned.
+pub fn axis_move_duration(from: f64, to: f64, motion: &ParkingConfig) -> Option<Duration> {
+    if from == to {
+        return Some(Duration::ZERO);
+    }
+
+    let steps_per_mm = motion.steps_per_mm;
+    let axis_move = AxisMove {
+        target: to * steps_per_mm,
+        max_jerk: motion.max_jerk * steps_per_mm,
+        max_acceleration: motion.max_acceleration * steps_per_mm,
+        max_velocity: motion.max_velocity * steps_per_mm,
+    };
+    let setpoints = plan_setpoints(from * steps_per_mm, &axis_move, SIMULATION_INTERVAL).ok()?;
+    Some(SIMULATION_INTERVAL * setpoints.len() as u32)
+}
+
+/// The same as the dispense cycle, see `dispensing::run_dispense_cycle`.
+fn operation_duration(
+    placement: &Placement,
+    config: &SimulationConfig,
+    heads: &[HeadDefinition],
+) -> Result<Duration, EstimateError> {
+    match &placement.operation {
+        Operation::Place => Ok(Duration::from_millis(config.place_ms)),
+        Operation::Dispense {
+            head,
+            dispense_ms,
+        } => {
+            let dispenser =
+                dispenser_config(heads, head).ok_or_else(|| EstimateError::UnknownHead(placement.reference.clone()))?;
+            let dispense_ms = dispense_ms.unwrap_or(dispenser.dispense_ms);
+            Ok(Duration::from_millis(
+                dispenser.pre_pressure_ms + dispense_ms + dispenser.retract_ms,
+            ))
+        }
+    }
+}
@@ -0,0 +1,1584 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use machine_geometry::{AffineTransform, Point};
+use machine_ids::{BoardId, FeederId, JobId};
+use operator_shared::job::{
+    ESTIMATE_PAGE_SIZE, EstimateError, InterventionError, InterventionResolution, JobEvent, ResumeChoice, ResumeError,
+};
+use operator_shared::readiness::StartJobError;
+use tokio::sync::Mutex;
+
+use super::checkpoint::{Checkpoint, CheckpointStore, Checkpointer};
+use super::evidence::{
+    EvidenceLog, EvidencePlacer, PlacementCamera, PlacementDifference, PlacementEvidence, Suspicion,
+};
+use super::panel::{
+    BoardInspection, BoardInspector, Panel, PanelInspection, PanelPlacer, SkipMarks, SkipReason, SkippedBoard, expand,
+    fiducial_correction, inspect_panel,
+};
+use super::pick_place::PickAndPlacePlacer;
+use super::report::{JobReport, write_report};
+use super::simulation::{axis_move_duration, estimate_page, move_duration, simulate_job};
+use super::{Job, JobControl, JobOperator, JobOutcome, Operation, Placement, Placer, job_path_for_board, run_job};
+use crate::config::{
+    DispenserConfig, EvidenceConfig, FeederCalibration, FeederDefinition, FeedersConfig, HeadDefinition, HeadKind,
+    ParkingConfig, PartDefinition, PickPlaceConfig, SimulationConfig,
+};
+use crate::coordinates::CoordinateTransform;
+use crate::feeders::Feeders;
+use crate::nozzles::NozzleVacuum;
+use crate::parking::HeadMover;
+use crate::runout::NozzleRotator;
+use crate::storage::local::LocalStorage;
+use crate::storage::{Storage, StorageImpl};
+use crate::travel::PartHeights;
+
+fn job(references: &[&str]) -> Job {
+    Job {
+        name: JobId::new("job-1"),
+        placements: references
+            .iter()
+            .enumerate()
+            .map(|(index, reference)| Placement {
+                reference: reference.to_string(),
+                position: Point {
+                    x: index as f64 * 10.0,
+                    y: 5.0,
+                },
+                rotation: 0.0,
+                feeder: None,
+                operation: Operation::Place,
+                board: None,
+            })
+            .collect(),
+        panel: None,
+    }
+}
+
+/// A single feeder, "F1", with the given count.
+fn feeders(counts: &[(&str, u32)]) -> Mutex<Feeders> {
+    let config = FeedersConfig {
+        feeders: vec![FeederDefinition {
+            name: FeederId::new("F1"),
+            low_stock_threshold: None,
+            polarity_mark: None,
+            part: None,
+            identity: None,
+            calibration: None,
+            pick_position: None,
+        }],
+        ..FeedersConfig::default()
+    };
+    let counts = counts
+        .iter()
+        .map(|(name, count)| (FeederId::new(*name), *count))
+        .collect::<BTreeMap<_, _>>();
+
+    Mutex::new(Feeders::new(&config, &counts))
+}
+
+fn with_feeder(mut job: Job, feeder: &str) -> Job {
+    for placement in job.placements.iter_mut() {
+        placement.feeder = Some(FeederId::new(feeder));
+    }
+    job
+}
+
+/// Fails each placement the given number of times before it is placed.
+#[derive(Default)]
+struct FakePlacer {
+    failures: HashMap<String, u32>,
+    attempts: Vec<String>,
+    dry_run: bool,
+}
+
+impl FakePlacer {
+    fn failing(failures: &[(&str, u32)]) -> Self {
+        Self {
+            failures: failures
+                .iter()
+                .map(|(reference, count)| (reference.to_string(), *count))
+                .collect(),
+            attempts: vec![],
+            dry_run: false,
+        }
+    }
+}
+
+impl Placer for FakePlacer {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.attempts
+                .push(placement.reference.clone());
+            match self
+                .failures
+                .get_mut(&placement.reference)
+            {
+                Some(remaining) if *remaining > 0 => {
+                    *remaining -= 1;
+                    bail!("part not picked")
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+}
+
+#[derive(Default)]
+struct FakeOperator {
+    resolutions: VecDeque<InterventionResolution>,
+    events: Vec<JobEvent>,
+    interventions: Vec<(String, String)>,
+}
+
+impl FakeOperator {
+    fn resolving(resolutions: &[InterventionResolution]) -> Self {
+        Self {
+            resolutions: resolutions.iter().copied().collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl JobOperator for FakeOperator {
+    fn publish(&mut self, event: JobEvent) {
+        self.events.push(event);
+    }
+
+    fn intervene<'a>(
+        &'a mut self,
+        _job: &'a Job,
+        placement: &'a Placement,
+        error: String,
+    ) -> impl Future<Output = InterventionResolution> + Send + 'a {
+        async move {
+            self.interventions
+                .push((placement.reference.clone(), error));
+            self.resolutions.pop_front().unwrap()
+        }
+    }
+}
+
+#[derive(Default)]
+struct FakeCheckpointer {
+    saved: Vec<Checkpoint>,
+    clears: u32,
+}
+
+impl Checkpointer for FakeCheckpointer {
+    fn save<'a>(&'a mut self, checkpoint: &'a Checkpoint) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.saved.push(checkpoint.clone());
+            Ok(())
+        }
+    }
+
+    fn clear<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.clears += 1;
+            Ok(())
+        }
+    }
+}
+
+fn checkpoint(job: &str, next_placement: usize, placed: u32, skipped: u32) -> Checkpoint {
+    Checkpoint {
+        next_placement,
+        placed,
+        skipped,
+        ..Checkpoint::new(&JobId::new(job))
+    }
+}
+
+#[tokio::test]
+pub async fn places_every_placement() {
+    // given
+    let job = job(&["R1", "R2", "C1"]);
+    let mut placer = FakePlacer::default();
+    let mut checkpointer = FakeCheckpointer::default();
+    let feeders = feeders(&[]);
+    let mut operator = FakeOperator::default();
+
+    // when
+    let outcome = run_job(&job, None, &feeders, &mut placer, &mut operator, &mut checkpointer).await;
+
+    // then
+    assert_eq!(outcome, JobOutcome::Finished {
+        placed: 3,
+        skipped: 0
+    });
+    assert_eq!(placer.attempts, vec!["R1", "R2", "C1"]);
+    assert!(operator.interventions.is_empty());
+    assert_eq!(operator.events.len(), 5);
+}
+
+#[tokio::test]
+pub async fn retry_places_the_same_placement_again() {
+    // given
+    let job = job(&["R1", "R2"]);
+    let mut placer = FakePlacer::failing(&[("R1", 2)]);
+    let mut checkpointer = FakeCheckpointer::default();
+    let feeders = feeders(&[]);
+    let mut operator = FakeOperator::resolving(&[InterventionResolution::Retry, InterventionResolution::Retry]);
+
+    // when
+    let outcome = run_job(&job, None, &feeders, &mut placer, &mut operator, &mut checkpointer).await;
+
+    // then
+    assert_eq!(outcome, JobOutcome::Finished {
+        placed: 2,
+        skipped: 0
+    });
+    assert_eq!(placer.attempts, vec!["R1", "R1", "R1", "R2"]);
+    assert_eq!(operator.interventions, vec![
+        ("R1".to_string(), "part not picked".to_string()),
+        ("R1".to_string(), "part not picked".to_string()),
+    ]);
+}
+
+#[tokio::test]
+pub async fn skip_continues_with_the_next_placement() {
+    // given
+    let job = job(&["R1", "R2", "R3"]);
+    let mut placer = FakePlacer::failing(&[("R2", 1)]);
+    let mut checkpointer = FakeCheckpointer::default();
+    let feeders = feeders(&[]);
+    let mut operator = FakeOperator::resolving(&[InterventionResolution::Skip]);
+
+    // when
+    let outcome = run_job(&job, None, &feeders, &mut placer, &mut operator, &mut checkpointer).await;
+
+    // then
+    assert_eq!(outcome, JobOutcome::Finished {
+        placed: 2,
+        skipped: 1
+    });
+    assert_eq!(placer.attempts, vec!["R1", "R2", "R3"]);
+    assert_eq!(
+        operator.events.last(),
+        Some(&JobEvent::Finished {
+            job: JobId::new("job-1"),
+            placed: 2,
+            skipped: 1,
+        })
+    );
+}
+
+#[tokio::test]
+pub async fn abort_stops_the_job() {
+    // given
+    let job = job(&["R1", "R2", "R3"]);
+    let mut placer = FakePlacer::failing(&[("R2", 1)]);
+    let mut checkpointer = FakeCheckpointer::default();
+    let feeders = feeders(&[]);
+    let mut operator = FakeOperator::resolving(&[InterventionResolution::Abort]);
+
+    // when
+    let outcome = run_job(&job, None, &feeders, &mut placer, &mut operator, &mut checkpointer).await;
+
+    // then
+    assert_eq!(outcome, JobOutcome::Aborted {
+        placed: 1,
+        skipped: 0
+    });
+    assert_eq!(placer.attempts, vec!["R1", "R2"]);
+    assert_eq!(
+        operator.events.last(),
+        Some(&JobEvent::Aborted {
+            job: JobId::new("job-1"),
+            placed: 1,
+            skipped: 0,
+        })
+    );
+}
+
+#[test]
+pub fn start_requires_a_job() {
+    // given
+    let mut job_control = JobControl::new(None, None);
+
+    // expect
+    assert_eq!(job_control.start(), Err(StartJobError::NoJob));
+}
+
+#[test]
+pub fn start_refused_while_running() {
+    // given
+    let mut job_control = JobControl::new(Some(job(&["R1"])), None);
+
+    // when
+    let first = job_control.start();
+    let second = job_control.start();
+    job_control.finish();
+    let third = job_control.start();
+
+    // then
+    assert_eq!(first, Ok((job(&["R1"]), None)));
+    assert_eq!(second, Err(StartJobError::Running));
+    assert_eq!(third, Ok((job(&["R1"]), None)));
+}
+
+#[test]
+pub fn select_replaces_job_and_restores_its_checkpoint() {
+    // given
+    let mut job_control = JobControl::new(Some(job(&["R1"])), None);
+    let mut board_job = job(&["R1", "R2"]);
+    board_job.name = JobId::new("job-2");
+
+    // when
+    let result = job_control.select(board_job.clone(), Some(checkpoint("job-2", 1, 1, 0)));
+
+    // then
+    assert_eq!(result, Ok(()));
+    assert_eq!(
+        job_control
+            .checkpoint()
+            .map(|checkpoint| checkpoint.job),
+        Some(JobId::new("job-2"))
+    );
+    assert_eq!(job_control.start(), Err(StartJobError::ResumeUnconfirmed));
+    job_control
+        .confirm_resume(ResumeChoice::Restart)
+        .unwrap();
+    assert_eq!(job_control.start(), Ok((board_job, None)));
+}
+
+#[test]
+pub fn select_refused_while_running() {
+    // given
+    let mut job_control = JobControl::new(Some(job(&["R1"])), None);
+    job_control.start().unwrap();
+
+    // when
+    let result = job_control.select(job(&["R1", "R2"]), None);
+
+    // then
+    assert_eq!(result, Err(StartJobError::Running));
+    job_control.finish();
+    assert_eq!(job_control.start(), Ok((job(&["R1"]), None)));
+}
+
+#[test]
+pub fn job_path_for_scanned_board_id() {
+    // given
+    let jobs_directory = Path::new("jobs");
+
+    // expect
+    assert_eq!(
+        job_path_for_board(jobs_directory, "BOARD-1_rev.2"),
+        Some(PathBuf::from("jobs").join("BOARD-1_rev.2.ron"))
+    );
+    assert_eq!(job_path_for_board(jobs_directory, ""), None);
+    assert_eq!(job_path_for_board(jobs_directory, ".."), None);
+    assert_eq!(job_path_for_board(jobs_directory, "../board"), None);
+    assert_eq!(job_path_for_board(jobs_directory, "a/b"), None);
+    assert_eq!(job_path_for_board(jobs_directory, "a\\b"), None);
+}
+
+#[test]
+pub fn resolve_sends_resolution() {
+    // given
+    let mut job_control = JobControl::new(None, None);
+    let (id, mut resolution_rx) = job_control.begin_intervention();
+
+    // when
+    let result = job_control.resolve(id, InterventionResolution::Skip);
+
+    // then
+    assert_eq!(result, Ok(()));
+    assert_eq!(resolution_rx.try_recv(), Ok(InterventionResolution::Skip));
+    assert_eq!(
+        job_control.resolve(id, InterventionResolution::Skip),
+        Err(InterventionError::NoIntervention)
+    );
+}
+
+#[test]
+pub fn resolve_rejects_stale_intervention() {
+    // given
+    let mut job_control = JobControl::new(None, None);
+    let (stale_id, _) = job_control.begin_intervention();
+    let (id, mut resolution_rx) = job_control.begin_intervention();
+
+    // when
+    let result = job_control.resolve(stale_id, InterventionResolution::Abort);
+
+    // then
+    assert_eq!(result, Err(InterventionError::Stale(id)));
+    assert!(resolution_rx.try_recv().is_err());
+    assert_eq!(job_control.resolve(id, InterventionResolution::Retry), Ok(()));
+}
+
+#[tokio::test]
+pub async fn checkpoint_saved_after_each_placement() {
+    // given
+    let job = job(&["R1", "R2", "R3"]);
+    let mut placer = FakePlacer::failing(&[("R2", 1)]);
+    let mut checkpointer = FakeCheckpointer::default();
+    let feeders = feeders(&[]);
+    let mut operator = FakeOperator::resolving(&[InterventionResolution::Skip]);
+
+    // when
+    run_job(&job, None, &feeders, &mut placer, &mut operator, &mut checkpointer).await;
+
+    // then
+    let progress = checkpointer
+        .saved
+        .iter()
+        .map(|checkpoint| (checkpoint.next_placement, checkpoint.placed, checkpoint.skipped))
+        .collect::<Vec<_>>();
+    assert_eq!(progress, vec![(1, 1, 0), (2, 1, 1), (3, 2, 1)]);
+    // once for a restarted earlier run, once when finished
+    assert_eq!(checkpointer.clears, 2);
+}
+
+#[tokio::test]
+pub async fn resumes_from_checkpoint() {
+    // given
+    let job = job(&["R1", "R2", "R3"]);
+    let mut placer = FakePlacer::default();
+    let mut checkpointer = FakeCheckpointer::default();
+    let feeders = feeders(&[]);
+    let mut operator = FakeOperator::default();
+
+    // when
+    let outcome = run_job(
+        &job,
+        Some(checkpoint("job-1", 2, 1, 1)),
+        &feeders,
+        &mut placer,
+        &mut operator,
+        &mut checkpointer,
+    )
+    .await;
+
+    // then
+    assert_eq!(outcome, JobOutcome::Finished {
+        placed: 2,
+        skipped: 1
+    });
+    assert_eq!(placer.attempts, vec!["R3"]);
+    assert_eq!(
+        operator.events.first(),
+        Some(&JobEvent::Started {
+            job: JobId::new("job-1"),
+            placements: 3,
+            placed: 1,
+            skipped: 1,
+            dry_run: false,
+        })
+    );
+    assert_eq!(checkpointer.clears, 1);
+}
+
+#[tokio::test]
+pub async fn abort_clears_checkpoint() {
+    // given
+    let job = job(&["R1", "R2"]);
+    let mut placer = FakePlacer::failing(&[("R2", 1)]);
+    let mut checkpointer = FakeCheckpointer::default();
+    let feeders = feeders(&[]);
+    let mut operator = FakeOperator::resolving(&[InterventionResolution::Abort]);
+
+    // when
+    run_job(
+        &job,
+        Some(checkpoint("job-1", 1, 1, 0)),
+        &feeders,
+        &mut placer,
+        &mut operator,
+        &mut checkpointer,
+    )
+    .await;
+
+    // then
+    assert!(checkpointer.saved.is_empty());
+    assert_eq!(checkpointer.clears, 1);
+}
+
+#[test]
+pub fn start_requires_resume_confirmation() {
+    // given
+    let interrupted = checkpoint("job-1", 1, 1, 0);
+    let mut job_control = JobControl::new(Some(job(&["R1", "R2"])), Some(interrupted.clone()));
+
+    // when
+    let unconfirmed = job_control.start();
+    let confirmation = job_control.confirm_resume(ResumeChoice::Resume);
+    let confirmed = job_control.start();
+
+    // then
+    assert_eq!(unconfirmed, Err(StartJobError::ResumeUnconfirmed));
+    assert_eq!(confirmation, Ok(()));
+    assert_eq!(confirmed, Ok((job(&["R1", "R2"]), Some(interrupted))));
+    assert_eq!(job_control.checkpoint(), None);
+}
+
+#[test]
+pub fn restart_discards_checkpoint() {
+    // given
+    let mut job_control = JobControl::new(Some(job(&["R1", "R2"])), Some(checkpoint("job-1", 1, 1, 0)));
+    job_control
+        .confirm_resume(ResumeChoice::Restart)
+        .unwrap();
+
+    // when
+    let result = job_control.start();
+
+    // then
+    assert_eq!(result, Ok((job(&["R1", "R2"]), None)));
+    job_control.finish();
+    assert_eq!(
+        job_control.confirm_resume(ResumeChoice::Resume),
+        Err(ResumeError::NoCheckpoint)
+    );
+}
+
+#[test]
+pub fn checkpoint_of_another_job_is_ignored() {
+    // given
+    let job_control = JobControl::new(Some(job(&["R1"])), Some(checkpoint("job-2", 1, 1, 0)));
+
+    // expect
+    assert_eq!(job_control.checkpoint(), None);
+}
+
+#[test]
+pub fn checkpoint_beyond_the_job_is_ignored() {
+    // given
+    let job_control = JobControl::new(Some(job(&["R1"])), Some(checkpoint("job-1", 2, 2, 0)));
+
+    // expect
+    assert_eq!(job_control.checkpoint(), None);
+}
+
+#[tokio::test]
+pub async fn checkpoint_store_save_load_and_clear() {
+    // given
+    let directory = std::env::temp_dir().join(format!("checkpoint-test-{:016x}", rand::random::<u64>()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let mut store = CheckpointStore::new(directory.join("checkpoint.ron"));
+    let checkpoint = checkpoint("job-1", 2, 1, 1);
+
+    // when
+    store.save(&checkpoint).await.unwrap();
+    let loaded = store.load().await.unwrap();
+    store.clear().await.unwrap();
+
+    // then
+    assert_eq!(loaded, Some(checkpoint));
+    assert_eq!(store.load().await.unwrap(), None);
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[tokio::test]
+pub async fn pick_decrements_feeder_count() {
+    // given
+    let job = with_feeder(job(&["R1", "R2"]), "F1");
+    let mut placer = FakePlacer::failing(&[("R1", 1)]);
+    let mut checkpointer = FakeCheckpointer::default();
+    let feeders = feeders(&[("F1", 10)]);
+    let mut operator = FakeOperator::resolving(&[InterventionResolution::Retry]);
+
+    // when
+    run_job(&job, None, &feeders, &mut placer, &mut operator, &mut checkpointer).await;
+
+    // then
+    // a part is taken for each attempt, including the failed attempt
+    assert_eq!(feeders.lock().await.counts()["F1"], 7);
+    let saved_counts = checkpointer
+        .saved
+        .iter()
+        .map(|checkpoint| checkpoint.feeder_counts["F1"])
+        .collect::<Vec<_>>();
+    assert_eq!(saved_counts, vec![8, 7]);
+}
+
+#[tokio::test]
+pub async fn out_of_stock_feeder_requires_intervention() {
+    // given
+    let job = with_feeder(job(&["R1", "R2", "R3"]), "F1");
+    let mut placer = FakePlacer::default();
+    let mut checkpointer = FakeCheckpointer::default();
+    let feeders = feeders(&[("F1", 1)]);
+    let mut operator = FakeOperator::resolving(&[InterventionResolution::Skip, InterventionResolution::Abort]);
+
+    // when
+    let outcome = run_job(&job, None, &feeders, &mut placer, &mut operator, &mut checkpointer).await;
+
+    // then
+    assert_eq!(outcome, JobOutcome::Aborted {
+        placed: 1,
+        skipped: 1
+    });
+    assert_eq!(placer.attempts, vec!["R1"]);
+    assert_eq!(operator.interventions, vec![
+        ("R2".to_string(), "Feeder out of stock. feeder: F1".to_string()),
+        ("R3".to_string(), "Feeder out of stock. feeder: F1".to_string()),
+    ]);
+}
+
+/// Two boards side by side, the second board is 1mm higher than the grid position.
+fn panel(fiducials: &[Point], skip_marks: SkipMarks) -> Panel {
+    Panel {
+        columns: 2,
+        rows: 1,
+        origin: Point {
+            x: 10.0,
+            y: 10.0,
+        },
+        pitch: Point {
+            x: 50.0,
+            y: 0.0,
+        },
+        offsets: BTreeMap::from([(BoardId::new(2), Point {
+            x: 0.0,
+            y: 1.0,
+        })]),
+        fiducials: fiducials.to_vec(),
+        skip_marks,
+    }
+}
+
+fn assert_near(actual: Point, expected: Point) {
+    assert!(
+        actual.distance(&expected) < 1e-9,
+        "actual: {:?}, expected: {:?}",
+        actual,
+        expected
+    );
+}
+
+/// Locates fiducials displaced by the `offset`, positions are in job coordinates.
+#[derive(Default)]
+struct FakeInspector {
+    offset: Point,
+    bad_marks: Vec<Point>,
+    missing_fiducials: Vec<Point>,
+}
+
+impl BoardInspector for FakeInspector {
+    fn locate_fiducial<'a>(
+        &'a mut self,
+        expected: Point,
+    ) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a {
+        async move {
+            if self
+                .missing_fiducials
+                .contains(&expected)
+            {
+                return Ok(None);
+            }
+            Ok(Some(Point {
+                x: expected.x + self.offset.x,
+                y: expected.y + self.offset.y,
+            }))
+        }
+    }
+
+    fn has_bad_mark<'a>(&'a mut self, position: Point) -> impl Future<Output = anyhow::Result<bool>> + Send + 'a {
+        async move { Ok(self.bad_marks.contains(&position)) }
+    }
+}
+
+#[test]
+pub fn expand_repeats_placements_for_every_board() {
+    // given
+    let mut job = job(&["R1", "R2"]);
+    job.panel = Some(panel(&[], SkipMarks::None));
+
+    // when
+    let expanded = expand(job);
+
+    // then
+    let placements = expanded
+        .placements
+        .iter()
+        .map(|placement| {
+            (
+                placement.reference.as_str(),
+                placement.position.x,
+                placement.position.y,
+                placement.board,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(placements, vec![
+        ("R1#1", 10.0, 15.0, Some(BoardId::new(1))),
+        ("R2#1", 20.0, 15.0, Some(BoardId::new(1))),
+        ("R1#2", 60.0, 16.0, Some(BoardId::new(2))),
+        ("R2#2", 70.0, 16.0, Some(BoardId::new(2))),
+    ]);
+}
+
+#[test]
+pub fn expand_without_panel_is_unchanged() {
+    // given
+    let job = job(&["R1", "R2"]);
+
+    // expect
+    assert_eq!(expand(job.clone()), job);
+}
+
+#[test]
+pub fn fiducial_correction_rotates_and_translates() {
+    // given
+    let expected = [
+        Point {
+            x: 0.0,
+            y: 0.0,
+        },
+        Point {
+            x: 10.0,
+            y: 0.0,
+        },
+    ];
+    // rotated by 90 degrees and moved by 5mm in x and y
+    let located = [
+        Point {
+            x: 5.0,
+            y: 5.0,
+        },
+        Point {
+            x: 5.0,
+            y: 15.0,
+        },
+    ];
+
+    // when
+    let correction = fiducial_correction(&expected, &located);
+
+    // then
+    assert_near(
+        correction.apply(Point {
+            x: 0.0,
+            y: 10.0,
+        }),
+        Point {
+            x: -5.0,
+            y: 5.0,
+        },
+    );
+    let rotation = correction
+        .yx
+        .atan2(correction.xx)
+        .to_degrees();
+    assert!((rotation - 90.0).abs() < 1e-9);
+}
+
+#[test]
+pub fn fiducial_correction_without_fiducials_is_identity() {
+    // expect
+    assert_eq!(fiducial_correction(&[], &[]), AffineTransform::IDENTITY);
+}
+
+#[tokio::test]
+pub async fn inspect_panel_corrects_each_board_by_its_fiducials() {
+    // given
+    let fiducials = [
+        Point {
+            x: 0.0,
+            y: 0.0,
+        },
+        Point {
+            x: 40.0,
+            y: 30.0,
+        },
+    ];
+    let panel = panel(&fiducials, SkipMarks::None);
+    let mut inspector = FakeInspector {
+        offset: Point {
+            x: 0.5,
+            y: -0.25,
+        },
+        ..FakeInspector::default()
+    };
+
+    // when
+    let inspection = inspect_panel(&panel, &mut inspector)
+        .await
+        .unwrap();
+
+    // then
+    let Some(BoardInspection::Corrected(correction)) = inspection.boards.get(&BoardId::new(2)) else {
+        panic!("board not corrected. inspection: {:?}", inspection);
+    };
+    assert_near(
+        correction.apply(Point {
+            x: 70.0,
+            y: 16.0,
+        }),
+        Point {
+            x: 70.5,
+            y: 15.75,
+        },
+    );
+    assert!(inspection.skipped().is_empty());
+}
+
+#[tokio::test]
+pub async fn inspect_panel_skips_boards_with_a_bad_mark() {
+    // given
+    let skip_marks = SkipMarks::BadMark {
+        position: Point {
+            x: 2.0,
+            y: 3.0,
+        },
+    };
+    let panel = panel(&[], skip_marks);
+    let mut inspector = FakeInspector {
+        bad_marks: vec![Point {
+            x: 62.0,
+            y: 14.0,
+        }],
+        ..FakeInspector::default()
+    };
+
+    // when
+    let inspection = inspect_panel(&panel, &mut inspector)
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(inspection.skipped(), vec![SkippedBoard {
+        board: BoardId::new(2),
+        reason: SkipReason::BadMark,
+    }]);
+}
+
+#[tokio::test]
+pub async fn inspect_panel_skips_boards_with_missing_fiducials() {
+    // given
+    let fiducials = [Point {
+        x: 1.0,
+        y: 1.0,
+    }];
+    let missing_fiducials = vec![Point {
+        x: 11.0,
+        y: 11.0,
+    }];
+
+    // when
+    let skipped = inspect_panel(&panel(&fiducials, SkipMarks::MissingFiducials), &mut FakeInspector {
+        missing_fiducials: missing_fiducials.clone(),
+        ..FakeInspector::default()
+    })
+    .await;
+    let failed = inspect_panel(&panel(&fiducials, SkipMarks::None), &mut FakeInspector {
+        missing_fiducials,
+        ..FakeInspector::default()
+    })
+    .await;
+
+    // then
+    assert_eq!(skipped.unwrap().skipped(), vec![SkippedBoard {
+        board: BoardId::new(1),
+        reason: SkipReason::MissingFiducial,
+    }]);
+    assert!(failed.is_err());
+}
+
+#[tokio::test]
+pub async fn skipped_board_takes_no_parts() {
+    // given
+    let mut job = with_feeder(job(&["R1", "R2"]), "F1");
+    job.panel = Some(panel(&[], SkipMarks::None));
+    let job = expand(job);
+    let inspection = PanelInspection {
+        boards: BTreeMap::from([
+            (BoardId::new(1), BoardInspection::Skipped(SkipReason::BadMark)),
+            (BoardId::new(2), BoardInspection::Corrected(AffineTransform::IDENTITY)),
+        ]),
+    };
+    let mut placer = PanelPlacer::new(FakePlacer::default(), inspection);
+    let mut checkpointer = FakeCheckpointer::default();
+    let feeders = feeders(&[("F1", 10)]);
+    let mut operator = FakeOperator::default();
+
+    // when
+    let outcome = run_job(&job, None, &feeders, &mut placer, &mut operator, &mut checkpointer).await;
+
+    // then
+    assert_eq!(outcome, JobOutcome::Finished {
+        placed: 2,
+        skipped: 2
+    });
+    assert!(operator.interventions.is_empty());
+    assert_eq!(feeders.lock().await.counts()["F1"], 8);
+    assert_eq!(checkpointer.saved.len(), 4);
+}
+
+#[tokio::test]
+pub async fn report_written_with_skipped_boards() {
+    // given
+    let directory = std::env::temp_dir().join(format!("job-report-test-{:016x}", rand::random::<u64>()));
+    let storage = StorageImpl::Local(LocalStorage::new(directory.clone()));
+    let started_at = chrono::Utc::now();
+    let report = JobReport {
+        job: JobId::new("panel/1"),
+        started_at,
+        ended_at: started_at,
+        outcome: Some(JobOutcome::Finished {
+            placed: 2,
+            skipped: 2,
+        }),
+        dry_run: false,
+        skipped_boards: vec![SkippedBoard {
+            board: BoardId::new(1),
+            reason: SkipReason::BadMark,
+        }],
+        error: None,
+        forces: vec![],
+        evidence: vec![PlacementEvidence {
+            placement: "R1".to_string(),
+            board: Some(BoardId::new(2)),
+            difference: Some(PlacementDifference {
+                shift_x: 1,
+                shift_y: -2,
+                changed: 0.01,
+            }),
+            suspicion: Some(Suspicion::TooLittleChange),
+        }],
+        energy: None,
+        nozzle_calibrations: vec![],
+    };
+
+    // when
+    let path = write_report(&storage, "job-reports", &report)
+        .await
+        .unwrap();
+
+    // then
+    // the path separator in the job name is replaced
+    assert!(path.starts_with("job-reports/job-panel_1-"), "path: {}", path);
+    let content = storage.get(&path).await.unwrap();
+    assert_eq!(
+        ron::from_str::<JobReport>(&String::from_utf8(content).unwrap()).unwrap(),
+        report
+    );
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+fn simulation_config() -> SimulationConfig {
+    SimulationConfig {
+        place_ms: 1000,
+    }
+}
+
+#[test]
+pub fn simulation_starts_at_the_first_placement() {
+    // given
+    let job = job(&["R1", "R2", "R3"]);
+    let motion = ParkingConfig::default();
+
+    // when
+    let estimate = simulate_job(
+        &job,
+        &simulation_config(),
+        &motion,
+        &CoordinateTransform::default(),
+        &[],
+        &PartHeights::default(),
+    )
+    .unwrap();
+
+    // then
+    let placements = &estimate.placements;
+    assert_eq!(placements.len(), 3);
+    assert_eq!((placements[0].start_ms, placements[0].move_ms), (0, 0));
+
+    // the placements are 10mm apart, the heights of the parts are unknown so Z is raised to the safe height
+    let xy = move_duration(
+        Point {
+            x: 0.0,
+            y: 5.0,
+        },
+        Point {
+            x: 10.0,
+            y: 5.0,
+        },
+        &motion,
+    )
+    .unwrap();
+    let z = axis_move_duration(0.0, motion.safe_z, &motion).unwrap();
+    let move_ms = (xy + 2 * z).as_millis() as u64;
+    assert!(move_ms > 0);
+    assert_eq!(placements[1].move_ms, move_ms);
+    assert_eq!(placements[1].start_ms, 1000);
+    assert_eq!(placements[2].start_ms, 1000 + move_ms + 1000);
+    assert_eq!(estimate.duration_ms, 3000 + 2 * move_ms);
+}
+
+#[test]
+pub fn simulated_move_takes_as_long_as_the_slower_axis() {
+    // given
+    let motion = ParkingConfig::default();
+    let origin = Point::default();
+    let along_x = Point {
+        x: 100.0,
+        y: 0.0,
+    };
+    let diagonal = Point {
+        x: 100.0,
+        y: 50.0,
+    };
+
+    // when
+    let x_only = move_duration(origin, along_x, &motion).unwrap();
+    let both = move_duration(origin, diagonal, &motion).unwrap();
+
+    // then
+    assert_eq!(both, x_only);
+    assert_eq!(move_duration(origin, origin, &motion), Some(Duration::ZERO));
+}
+
+#[test]
+pub fn simulated_dispense_uses_the_dispense_cycle_of_the_head() {
+    // given
+    let mut job = job(&["D1", "D2"]);
+    job.placements[0].operation = Operation::Dispense {
+        head: "paste".to_string(),
+        dispense_ms: None,
+    };
+    job.placements[1].operation = Operation::Dispense {
+        head: "paste".to_string(),
+        dispense_ms: Some(500),
+    };
+    let heads = vec![HeadDefinition {
+        name: "paste".to_string(),
+        kind: HeadKind::Dispenser(DispenserConfig {
+            pre_pressure_ms: 50,
+            dispense_ms: 100,
+            retract_ms: 25,
+            ..DispenserConfig::default()
+        }),
+    }];
+
+    // when
+    let estimate = simulate_job(
+        &job,
+        &simulation_config(),
+        &ParkingConfig::default(),
+        &CoordinateTransform::default(),
+        &heads,
+        &PartHeights::default(),
+    )
+    .unwrap();
+
+    // then
+    assert_eq!(estimate.placements[0].operation_ms, 175);
+    assert_eq!(estimate.placements[1].operation_ms, 575);
+}
+
+#[test]
+pub fn simulation_fails_for_an_unknown_head() {
+    // given
+    let mut job = job(&["D1"]);
+    job.placements[0].operation = Operation::Dispense {
+        head: "glue".to_string(),
+        dispense_ms: None,
+    };
+
+    // when
+    let result = simulate_job(
+        &job,
+        &simulation_config(),
+        &ParkingConfig::default(),
+        &CoordinateTransform::default(),
+        &[],
+        &PartHeights::default(),
+    );
+
+    // then
+    assert_eq!(result, Err(EstimateError::UnknownHead("D1".to_string())));
+}
+
+#[test]
+pub fn simulated_moves_between_short_parts_are_shorter() {
+    // given
+    let job = with_feeder(job(&["R1", "R2", "R3"]), "F1");
+    let feeders = vec![FeederDefinition {
+        name: FeederId::new("F1"),
+        low_stock_threshold: None,
+        polarity_mark: None,
+        part: Some("0402".to_string()),
+        identity: None,
+        calibration: None,
+        pick_position: None,
+    }];
+    let parts = vec![PartDefinition {
+        name: "0402".to_string(),
+        height: 0.35,
+        pick_force: None,
+        place_force: None,
+    }];
+    let heights = PartHeights::new(&feeders, &parts);
+    let mut motion = ParkingConfig::default();
+    let simulate = |motion: &ParkingConfig| {
+        simulate_job(
+            &job,
+            &simulation_config(),
+            motion,
+            &CoordinateTransform::default(),
+            &[],
+            &heights,
+        )
+        .unwrap()
+    };
+
+    // when
+    let at_safe_z = simulate(&motion);
+    motion.travel.enabled = true;
+    let planned = simulate(&motion);
+
+    // then
+    assert!(planned.duration_ms < at_safe_z.duration_ms);
+    assert!(planned.placements[1].move_ms < at_safe_z.placements[1].move_ms);
+}
+
+#[test]
+pub fn estimate_is_paged() {
+    // given
+    let references = (0..40)
+        .map(|index| format!("R{}", index))
+        .collect::<Vec<_>>();
+    let references = references
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let estimate = simulate_job(
+        &job(&references),
+        &simulation_config(),
+        &ParkingConfig::default(),
+        &CoordinateTransform::default(),
+        &[],
+        &PartHeights::default(),
+    )
+    .unwrap();
+
+    // when
+    let first = estimate_page(estimate.clone(), 0);
+    let last = estimate_page(estimate.clone(), ESTIMATE_PAGE_SIZE);
+
+    // then
+    assert_eq!((first.total, first.placements.len()), (40, ESTIMATE_PAGE_SIZE));
+    assert_eq!((last.total, last.placements.len()), (40, 40 - ESTIMATE_PAGE_SIZE));
+    assert_eq!(last.placements[0], estimate.placements[ESTIMATE_PAGE_SIZE]);
+    assert_eq!(last.duration_ms, estimate.duration_ms);
+}
+
+/// Each image is the number of the capture, the difference of each comparison is taken from `changes`.
+struct FakeCamera {
+    captures: u32,
+    fail_captures: bool,
+    changes: VecDeque<f32>,
+}
+
+impl FakeCamera {
+    fn new(changes: &[f32]) -> Self {
+        Self {
+            captures: 0,
+            fail_captures: false,
+            changes: changes.iter().copied().collect(),
+        }
+    }
+}
+
+impl PlacementCamera for FakeCamera {
+    type Image = u32;
+
+    fn capture<'a>(
+        &'a mut self,
+        _placement: &'a Placement,
+    ) -> impl Future<Output = anyhow::Result<Self::Image>> + Send + 'a {
+        async move {
+            if self.fail_captures {
+                bail!("camera disconnected")
+            }
+            self.captures += 1;
+            Ok(self.captures)
+        }
+    }
+
+    fn compare<'a>(
+        &'a mut self,
+        before: Self::Image,
+        after: Self::Image,
+    ) -> impl Future<Output = anyhow::Result<PlacementDifference>> + Send + 'a {
+        async move {
+            assert_eq!(after, before + 1);
+            Ok(PlacementDifference {
+                shift_x: 0,
+                shift_y: 0,
+                changed: self.changes.pop_front().unwrap(),
+            })
+        }
+    }
+}
+
+#[tokio::test]
+pub async fn suspect_placements_are_flagged() {
+    // given
+    let log = EvidenceLog::default();
+    let mut placer = EvidencePlacer::new(
+        FakePlacer::default(),
+        Some(FakeCamera::new(&[0.01, 0.3, 0.95])),
+        EvidenceConfig::default(),
+        log.clone(),
+    );
+    let job = job(&["R1", "R2", "R3"]);
+
+    // when
+    for placement in job.placements.iter() {
+        placer.place(placement).await.unwrap();
+    }
+
+    // then
+    let suspicions = log
+        .lock()
+        .await
+        .iter()
+        .map(|evidence| (evidence.placement.clone(), evidence.suspicion.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(suspicions, vec![
+        ("R1".to_string(), Some(Suspicion::TooLittleChange)),
+        ("R2".to_string(), None),
+        ("R3".to_string(), Some(Suspicion::TooMuchChange)),
+    ]);
+}
+
+#[tokio::test]
+pub async fn evidence_is_unavailable_when_the_camera_fails() {
+    // given
+    let log = EvidenceLog::default();
+    let mut camera = FakeCamera::new(&[]);
+    camera.fail_captures = true;
+    let mut placer = EvidencePlacer::new(
+        FakePlacer::default(),
+        Some(camera),
+        EvidenceConfig::default(),
+        log.clone(),
+    );
+    let job = job(&["R1"]);
+
+    // when
+    let result = placer.place(&job.placements[0]).await;
+
+    // then
+    // the placement itself does not fail
+    assert!(result.is_ok());
+    let log = log.lock().await;
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].difference, None);
+    assert!(matches!(log[0].suspicion, Some(Suspicion::Unavailable(_))));
+}
+
+#[tokio::test]
+pub async fn no_evidence_for_failed_attempts_or_dispense_operations() {
+    // given
+    let log = EvidenceLog::default();
+    let mut placer = EvidencePlacer::new(
+        FakePlacer::failing(&[("R1", 1)]),
+        Some(FakeCamera::new(&[0.3])),
+        EvidenceConfig::default(),
+        log.clone(),
+    );
+    let mut job = job(&["R1", "G1"]);
+    job.placements[1].operation = Operation::Dispense {
+        head: "glue".to_string(),
+        dispense_ms: None,
+    };
+
+    // when
+    let first_attempt = placer.place(&job.placements[0]).await;
+    let dispensed = placer.place(&job.placements[1]).await;
+
+    // then
+    assert!(first_attempt.is_err());
+    assert!(dispensed.is_ok());
+    assert!(log.lock().await.is_empty());
+}
+
+#[tokio::test]
+pub async fn no_evidence_in_a_dry_run() {
+    // given
+    let log = EvidenceLog::default();
+    let inner = FakePlacer {
+        dry_run: true,
+        ..FakePlacer::default()
+    };
+    let mut placer = EvidencePlacer::new(
+        inner,
+        Some(FakeCamera::new(&[0.3])),
+        EvidenceConfig::default(),
+        log.clone(),
+    );
+    let job = job(&["R1"]);
+
+    // when
+    let result = placer.place(&job.placements[0]).await;
+
+    // then
+    assert!(result.is_ok());
+    assert!(placer.dry_run());
+    assert!(log.lock().await.is_empty());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HeadAction {
+    Z(f64),
+    Xy(Point),
+    Rotate(f64),
+    Valve(bool),
+}
+
+/// The mover, rotator and vacuum of the head, the clones share the log of the actions.
+#[derive(Clone)]
+struct FakeHead {
+    log: Arc<std::sync::Mutex<Vec<HeadAction>>>,
+    part_present: Option<bool>,
+}
+
+impl FakeHead {
+    fn new(part_present: Option<bool>) -> Self {
+        Self {
+            log: Arc::default(),
+            part_present,
+        }
+    }
+
+    fn actions(&self) -> Vec<HeadAction> {
+        self.log.lock().unwrap().clone()
+    }
+
+    fn record(&self, action: HeadAction) {
+        self.log.lock().unwrap().push(action);
+    }
+}
+
+impl HeadMover for FakeHead {
+    fn move_z<'a>(&'a mut self, z: f64) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.record(HeadAction::Z(z));
+            Ok(())
+        }
+    }
+
+    fn move_xy<'a>(&'a mut self, target: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.record(HeadAction::Xy(target));
+            Ok(())
+        }
+    }
+}
+
+impl NozzleRotator for FakeHead {
+    fn rotate_to<'a>(&'a mut self, angle: f64) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.record(HeadAction::Rotate(angle));
+            Ok(())
+        }
+    }
+}
+
+impl NozzleVacuum for FakeHead {
+    fn set_valve<'a>(&'a mut self, _nozzle: u8, open: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.record(HeadAction::Valve(open));
+            Ok(())
+        }
+    }
+
+    fn part_present<'a>(&'a mut self, _nozzle: u8) -> impl Future<Output = anyhow::Result<Option<bool>>> + Send + 'a {
+        async move { Ok(self.part_present) }
+    }
+}
+
+/// "F1" picks parts 0.5mm high from (100, 50), rotated by 1 degree in the tape, "F2" has no pick position.
+fn pick_and_place_placer(head: &FakeHead) -> PickAndPlacePlacer<FakeHead, FakeHead, FakeHead> {
+    let definitions = vec![
+        FeederDefinition {
+            name: FeederId::new("F1"),
+            low_stock_threshold: None,
+            polarity_mark: None,
+            part: Some("0402".to_string()),
+            identity: None,
+            calibration: Some(FeederCalibration {
+                pick_offset: Point {
+                    x: 0.0,
+                    y: 0.0,
+                },
+                rotation: 1.0,
+            }),
+            pick_position: Some(Point {
+                x: 100.0,
+                y: 50.0,
+            }),
+        },
+        FeederDefinition {
+            name: FeederId::new("F2"),
+            low_stock_threshold: None,
+            polarity_mark: None,
+            part: None,
+            identity: None,
+            calibration: None,
+            pick_position: None,
+        },
+    ];
+    let parts = vec![PartDefinition {
+        name: "0402".to_string(),
+        height: 0.5,
+        pick_force: None,
+        place_force: None,
+    }];
+    let heights = PartHeights::new(&definitions, &parts);
+    let feeders = Feeders::new(
+        &FeedersConfig {
+            feeders: definitions,
+            ..FeedersConfig::default()
+        },
+        &BTreeMap::new(),
+    );
+    let config = PickPlaceConfig {
+        nozzle: 0,
+        pick_z: 2.0,
+        pick_settle_ms: 0,
+        release_ms: 0,
+    };
+
+    PickAndPlacePlacer::new(
+        head.clone(),
+        head.clone(),
+        head.clone(),
+        Arc::new(Mutex::new(feeders)),
+        CoordinateTransform::default(),
+        heights,
+        &ParkingConfig::default(),
+        config,
+    )
+}
+
+fn placement_from(feeder: &str) -> Placement {
+    Placement {
+        reference: "R1".to_string(),
+        position: Point {
+            x: 10.0,
+            y: 5.0,
+        },
+        rotation: 90.0,
+        feeder: Some(FeederId::new(feeder)),
+        operation: Operation::Place,
+        board: None,
+    }
+}
+
+#[tokio::test]
+pub async fn the_part_is_picked_from_the_feeder_and_placed() {
+    // given
+    let head = FakeHead::new(Some(true));
+    let mut placer = pick_and_place_placer(&head);
+
+    // when
+    let result = placer
+        .place(&placement_from("F1"))
+        .await;
+
+    // then
+    assert!(result.is_ok());
+    assert_eq!(head.actions(), vec![
+        HeadAction::Z(10.0),
+        HeadAction::Xy(Point {
+            x: 100.0,
+            y: 50.0
+        }),
+        HeadAction::Z(2.0),
+        HeadAction::Valve(true),
+        HeadAction::Z(10.0),
+        HeadAction::Xy(Point {
+            x: 10.0,
+            y: 5.0
+        }),
+        HeadAction::Rotate(89.0),
+        HeadAction::Z(0.5),
+        HeadAction::Valve(false),
+        HeadAction::Rotate(0.0),
+    ]);
+    assert!(!placer.dry_run());
+}
+
+#[tokio::test]
+pub async fn a_missed_pick_fails_the_placement() {
+    // given
+    let head = FakeHead::new(Some(false));
+    let mut placer = pick_and_place_placer(&head);
+
+    // when
+    let result = placer
+        .place(&placement_from("F1"))
+        .await;
+
+    // then
+    assert!(result.is_err());
+    // the valve is closed and the nozzle raised out of the feeder, the head doesn't move to the placement
+    assert_eq!(head.actions()[3..], [
+        HeadAction::Valve(true),
+        HeadAction::Valve(false),
+        HeadAction::Z(10.0),
+    ]);
+}
+
+#[tokio::test]
+pub async fn a_feeder_without_a_pick_position_fails_before_moving() {
+    // given
+    let head = FakeHead::new(Some(true));
+    let mut placer = pick_and_place_placer(&head);
+
+    // when
+    let result = placer
+        .place(&placement_from("F2"))
+        .await;
+
+    // then
+    assert!(result.is_err());
+    assert!(head.actions().is_empty());
+}
+
+#[tokio::test]
+pub async fn the_needle_is_lowered_at_the_position_of_a_dispense_operation() {
+    // given
+    let head = FakeHead::new(None);
+    let mut placer = pick_and_place_placer(&head);
+    let placement = Placement {
+        feeder: None,
+        operation: Operation::Dispense {
+            head: "paste".to_string(),
+            dispense_ms: None,
+        },
+        ..placement_from("F1")
+    };
+
+    // when
+    let result = placer.place(&placement).await;
+
+    // then
+    assert!(result.is_ok());
+    assert_eq!(head.actions(), vec![
+        HeadAction::Z(10.0),
+        HeadAction::Xy(Point {
+            x: 10.0,
+            y: 5.0
+        }),
+        HeadAction::Z(0.0),
+    ]);
+}
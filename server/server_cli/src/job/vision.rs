@@ -0,0 +1,192 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use log::{debug, warn};
+use machine_geometry::Point;
+use machine_ids::CameraId;
+use server_common::camera::{CameraDefinition, CameraMounting};
+use server_vision::CameraFrame;
+use server_vision::bad_mark::bad_mark_coverage;
+use server_vision::exposure::Roi;
+use server_vision::placement::placement_difference;
+
+use super::Placement;
+use super::evidence::{PlacementCamera, PlacementDifference};
+use super::panel::BoardInspector;
+use crate::captures::{FailureCaptures, failure_annotation};
+use crate::config::{BadMarkConfig, EvidenceConfig};
+use crate::vision::{VisionCaptureRequest, VisionQueue};
+
+/// Detects bad marks with a down camera, frames are captured via the [`VisionQueue`], a frame that can't be checked
+/// is saved to the `failures`.
+///
+/// FUTURE move the camera over the position of each mark, and locate the fiducials, there is no XY motion yet.
+pub struct VisionInspector {
+    vision_queue: VisionQueue,
+    camera: CameraId,
+    config: BadMarkConfig,
+    failures: FailureCaptures,
+}
+
+impl VisionInspector {
+    pub fn new(vision_queue: VisionQueue, camera: CameraId, config: BadMarkConfig, failures: FailureCaptures) -> Self {
+        Self {
+            vision_queue,
+            camera,
+            config,
+            failures,
+        }
+    }
+}
+
+impl BoardInspector for VisionInspector {
+    fn locate_fiducial<'a>(
+        &'a mut self,
+        expected: Point,
+    ) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a {
+        async move { Ok(Some(expected)) }
+    }
+
+    fn has_bad_mark<'a>(&'a mut self, position: Point) -> impl Future<Output = anyhow::Result<bool>> + Send + 'a {
+        async move {
+            let frame = self
+                .vision_queue
+                .capture(VisionCaptureRequest {
+                    camera: self.camera,
+                    pause_preview: true,
+                    // the center region, where the mark is, see `bad_mark_coverage`
+                    metering_roi: Some(Roi::centered(0.5)),
+                })
+                .await?;
+
+            let contrast = self.config.contrast;
+            // decoding takes longer than is acceptable for the runtime
+            let coverage = tokio::task::spawn_blocking({
+                let frame = frame.clone();
+                move || bad_mark_coverage(&frame.jpeg_bytes, contrast)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+            .and_then(|result| result);
+            let coverage = match coverage {
+                Ok(coverage) => coverage,
+                Err(e) => {
+                    warn!(
+                        "Unable to check for a bad mark. position: {:?}, error: {:?}",
+                        position, e
+                    );
+                    self.failures
+                        .save(&self.camera.to_string(), &frame, &[failure_annotation(&e)])
+                        .await;
+                    return Err(e);
+                }
+            };
+
+            let marked = coverage >= self.config.min_coverage;
+            debug!(
+                "Bad mark checked. position: {:?}, coverage: {:.2}, marked: {}",
+                position, coverage, marked
+            );
+            Ok(marked)
+        }
+    }
+}
+
+/// Captures the target location of each placement with a down camera, frames are captured via the [`VisionQueue`], the
+/// frame after the placement is saved to the `failures` when the frames can't be compared.
+///
+/// FUTURE move the camera over the position of the placement, there is no XY motion yet.
+pub struct VisionPlacementCamera {
+    vision_queue: VisionQueue,
+    camera: CameraId,
+    config: EvidenceConfig,
+    failures: FailureCaptures,
+}
+
+impl VisionPlacementCamera {
+    pub fn new(vision_queue: VisionQueue, camera: CameraId, config: EvidenceConfig, failures: FailureCaptures) -> Self {
+        Self {
+            vision_queue,
+            camera,
+            config,
+            failures,
+        }
+    }
+}
+
+/// A frame of the target location of a placement.
+pub struct PlacementFrame {
+    placement: String,
+    frame: Arc<CameraFrame>,
+}
+
+impl PlacementCamera for VisionPlacementCamera {
+    type Image = PlacementFrame;
+
+    fn capture<'a>(
+        &'a mut self,
+        placement: &'a Placement,
+    ) -> impl Future<Output = anyhow::Result<Self::Image>> + Send + 'a {
+        async move {
+            let frame = self
+                .vision_queue
+                .capture(VisionCaptureRequest {
+                    camera: self.camera,
+                    pause_preview: true,
+                    // the target region, see `placement_difference`
+                    metering_roi: Some(Roi::centered(0.5)),
+                })
+                .await?;
+            Ok(PlacementFrame {
+                placement: placement.reference.clone(),
+                frame,
+            })
+        }
+    }
+
+    fn compare<'a>(
+        &'a mut self,
+        before: Self::Image,
+        after: Self::Image,
+    ) -> impl Future<Output = anyhow::Result<PlacementDifference>> + Send + 'a {
+        async move {
+            let (contrast, max_shift) = (self.config.contrast, self.config.max_shift);
+            // decoding and registering takes longer than is acceptable for the runtime
+            let difference = tokio::task::spawn_blocking({
+                let (before, after) = (before.frame, after.frame.clone());
+                move || placement_difference(&before.jpeg_bytes, &after.jpeg_bytes, contrast, max_shift)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+            .and_then(|result| result);
+            let difference = match difference {
+                Ok(difference) => difference,
+                Err(e) => {
+                    warn!(
+                        "Unable to compare the placement frames. placement: {}, error: {:?}",
+                        after.placement, e
+                    );
+                    self.failures
+                        .save(&after.placement, &after.frame, &[failure_annotation(&e)])
+                        .await;
+                    return Err(e);
+                }
+            };
+
+            Ok(PlacementDifference {
+                shift_x: difference.shift_x,
+                shift_y: difference.shift_y,
+                changed: difference.changed,
+            })
+        }
+    }
+}
+
+/// The first down camera, cameras are identified by index, see
+/// [`camera_definition_for_identifier`](crate::camera::camera_definition_for_identifier).
+pub fn bad_mark_camera(cameras: &[CameraDefinition]) -> Option<CameraId> {
+    cameras
+        .iter()
+        .position(|definition| matches!(definition.mounting, CameraMounting::Down))
+        .map(|index| CameraId::new(index as u8))
+}
@@ -0,0 +1,376 @@
+//! Overriding the limits of an axis for maintenance, e.g. disabling the safe-Z guard to move a nozzle over a part during
+//! calibration.
+//!
+//! Only the operators authorized in the [`MaintenanceConfig`] can override a limit, and only for a limited time.  The
+//! limit is re-enabled once the time has passed, when the operator clears the override, or when the server shuts down,
+//! see [`limit_override_runner`].  Each override, and each re-enable, is logged with the operator and the reason.
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{FrameKind, topic};
+use ergot_util::ClientWrapper;
+use ioboard_shared::safe_z::SafeZRequest;
+use log::{debug, error, warn};
+use operator_shared::limits::{
+    AxisLimit, LimitOverride, LimitOverrideError, LimitOverrideRequest, LimitOverrideStatus,
+};
+use tokio::select;
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::Receiver;
+use tokio::time::{self, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::AppEvent;
+use crate::config::{HomingAxisDefinition, MaintenanceConfig};
+use crate::ioboard::{CommandSequencer, SafeZEndpoint};
+use crate::networking::dead_letter;
+
+#[cfg(test)]
+mod tests;
+
+topic!(
+    LimitOverrideTopic,
+    LimitOverrideStatus,
+    "topic/operator/limit-overrides"
+);
+
+/// The countdown of the overrides is published at this interval.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+const OVERRIDE_REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+const OVERRIDE_REQUEST_ATTEMPTS: usize = 3;
+
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait LimitOverrider: Send + 'static {
+    /// `overridden` is `false` to re-enable the limit.
+    fn set_overridden<'a>(
+        &'a mut self,
+        axis: u8,
+        limit: AxisLimit,
+        overridden: bool,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+}
+
+/// Uses the safe-Z endpoint of the io board.
+pub struct IoBoardLimitOverrider {
+    stack: RouterStack,
+    sequencer: Arc<CommandSequencer>,
+}
+
+impl IoBoardLimitOverrider {
+    pub fn new(stack: RouterStack, sequencer: Arc<CommandSequencer>) -> Self {
+        Self {
+            stack,
+            sequencer,
+        }
+    }
+}
+
+impl LimitOverrider for IoBoardLimitOverrider {
+    fn set_overridden<'a>(
+        &'a mut self,
+        axis: u8,
+        limit: AxisLimit,
+        overridden: bool,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let request = match limit {
+                AxisLimit::SafeZGuard => SafeZRequest::Override(overridden),
+            };
+
+            let query = SocketQuery {
+                key: SafeZEndpoint::REQ_KEY.to_bytes(),
+                nash_req: NameRequirement::Any,
+                frame_kind: FrameKind::ENDPOINT_REQ,
+                broadcast: false,
+            };
+            // TODO select the io board of the axis, currently there is only one
+            let address = self
+                .stack
+                .discovery()
+                .discover_sockets(4, OVERRIDE_REQUEST_TIMEOUT, &query)
+                .await
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("Safe-Z endpoint not found. axis: {}", axis))?
+                .address;
+
+            let client = self
+                .stack
+                .endpoints()
+                .client::<SafeZEndpoint>(address, None);
+            let client = ClientWrapper::new(OVERRIDE_REQUEST_TIMEOUT, client);
+            let status = client
+                .request_with_retry(&self.sequencer.sequenced(request), OVERRIDE_REQUEST_ATTEMPTS)
+                .await
+                .inspect_err(|e| dead_letter::request_failed::<SafeZEndpoint>(address, OVERRIDE_REQUEST_ATTEMPTS, e))?;
+            if status.overridden != overridden {
+                bail!(
+                    "Safe-Z guard override not applied. axis: {}, status: {:?}",
+                    axis,
+                    status
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+struct ActiveOverride {
+    axis: String,
+    limit: AxisLimit,
+    operator: String,
+    reason: String,
+    expires_at: Instant,
+    /// cancelled when the operator clears the override
+    cancel: CancellationToken,
+}
+
+/// An override that has been granted, the limit must then be disabled, and re-enabled once `duration` has passed or
+/// `cancel` is cancelled.
+#[derive(Debug)]
+pub struct GrantedOverride {
+    pub axis: String,
+    /// the axis of the io board
+    pub board_axis: u8,
+    pub limit: AxisLimit,
+    pub duration: Duration,
+    pub cancel: CancellationToken,
+}
+
+/// The overrides that are active, shared between the operator command listener and the runners of the overrides.
+pub struct LimitOverrides {
+    config: MaintenanceConfig,
+    /// the axes that can be overridden, the same names the operator sees when homing
+    axes: Vec<HomingAxisDefinition>,
+    active: Vec<ActiveOverride>,
+}
+
+impl LimitOverrides {
+    pub fn new(config: MaintenanceConfig, axes: Vec<HomingAxisDefinition>) -> Self {
+        Self {
+            config,
+            axes,
+            active: vec![],
+        }
+    }
+
+    pub fn grant(
+        &mut self,
+        now: Instant,
+        request: &LimitOverrideRequest,
+    ) -> Result<GrantedOverride, LimitOverrideError> {
+        if self.config.operators.is_empty() {
+            return Err(LimitOverrideError::NotConfigured);
+        }
+        let authorized = self
+            .config
+            .operators
+            .iter()
+            .any(|operator| operator.name == request.operator && operator.access_code == request.access_code);
+        if !authorized {
+            return Err(LimitOverrideError::Unauthorized);
+        }
+        if request.reason.trim().is_empty() {
+            return Err(LimitOverrideError::MissingReason);
+        }
+        let board_axis = self
+            .axes
+            .iter()
+            .find(|axis| axis.name == request.axis)
+            .ok_or(LimitOverrideError::UnknownAxis)?
+            .axis;
+        if request.duration_s == 0 || request.duration_s > self.config.max_override_s {
+            return Err(LimitOverrideError::InvalidDuration {
+                max_s: self.config.max_override_s,
+            });
+        }
+        if self.is_overridden(&request.axis, request.limit) {
+            return Err(LimitOverrideError::AlreadyOverridden);
+        }
+
+        let duration = Duration::from_secs(request.duration_s as u64);
+        let cancel = CancellationToken::new();
+        self.active.push(ActiveOverride {
+            axis: request.axis.clone(),
+            limit: request.limit,
+            operator: request.operator.clone(),
+            reason: request.reason.trim().to_string(),
+            expires_at: now + duration,
+            cancel: cancel.clone(),
+        });
+
+        Ok(GrantedOverride {
+            axis: request.axis.clone(),
+            board_axis,
+            limit: request.limit,
+            duration,
+            cancel,
+        })
+    }
+
+    /// Ends the override early, it is removed once the limit has been re-enabled, see [`LimitOverrides::remove`].
+    pub fn clear(&mut self, axis: &str, limit: AxisLimit) -> Result<(), LimitOverrideError> {
+        let active = self
+            .active
+            .iter()
+            .find(|active| active.axis == axis && active.limit == limit)
+            .ok_or(LimitOverrideError::NotOverridden)?;
+        active.cancel.cancel();
+        Ok(())
+    }
+
+    /// Called once the limit has been re-enabled, or if it could not be disabled.
+    pub fn remove(&mut self, axis: &str, limit: AxisLimit) {
+        self.active
+            .retain(|active| active.axis != axis || active.limit != limit);
+    }
+
+    pub fn is_overridden(&self, axis: &str, limit: AxisLimit) -> bool {
+        self.active
+            .iter()
+            .any(|active| active.axis == axis && active.limit == limit)
+    }
+
+    pub fn status(&self, now: Instant) -> LimitOverrideStatus {
+        let overrides = self
+            .active
+            .iter()
+            .map(|active| LimitOverride {
+                axis: active.axis.clone(),
+                limit: active.limit,
+                operator: active.operator.clone(),
+                reason: active.reason.clone(),
+                // rounded up, so the countdown only shows 0 once the limit is being re-enabled
+                remaining_s: active
+                    .expires_at
+                    .saturating_duration_since(now)
+                    .as_millis()
+                    .div_ceil(1000) as u32,
+            })
+            .collect();
+
+        LimitOverrideStatus {
+            overrides,
+        }
+    }
+}
+
+/// Grants the override and disables the limit, a [`limit_override_runner`] is spawned to re-enable it.
+///
+/// The override is not granted if the limit could not be disabled.
+pub async fn override_limit<O: LimitOverrider>(
+    stack: &RouterStack,
+    overrides: &Arc<Mutex<LimitOverrides>>,
+    mut overrider: O,
+    request: &LimitOverrideRequest,
+    app_event_rx: Receiver<AppEvent>,
+) -> Result<(), LimitOverrideError> {
+    let granted = overrides
+        .lock()
+        .await
+        .grant(Instant::now(), request)?;
+
+    if let Err(e) = overrider
+        .set_overridden(granted.board_axis, granted.limit, true)
+        .await
+    {
+        error!(
+            "Unable to override axis limit. axis: {}, limit: {:?}, error: {:?}",
+            granted.axis, granted.limit, e
+        );
+        overrides
+            .lock()
+            .await
+            .remove(&granted.axis, granted.limit);
+        return Err(LimitOverrideError::NotApplied);
+    }
+
+    publish(
+        stack,
+        &overrides
+            .lock()
+            .await
+            .status(Instant::now()),
+    );
+    // not awaited on shutdown, the same as the job runner, the limit is re-enabled as soon as the shutdown starts
+    tokio::spawn(limit_override_runner(
+        stack.clone(),
+        overrides.clone(),
+        overrider,
+        granted,
+        app_event_rx,
+    ));
+    Ok(())
+}
+
+/// Publishes the countdown of the override, and re-enables the limit once the duration has passed, the override is
+/// cleared, or the server shuts down.
+pub async fn limit_override_runner<O: LimitOverrider>(
+    stack: RouterStack,
+    overrides: Arc<Mutex<LimitOverrides>>,
+    mut overrider: O,
+    granted: GrantedOverride,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let deadline = time::sleep(granted.duration);
+    let mut deadline = pin!(deadline);
+    let mut status_interval = time::interval_at(Instant::now() + STATUS_INTERVAL, STATUS_INTERVAL);
+
+    let ended_by = loop {
+        select! {
+            _ = &mut deadline => {
+                break "duration elapsed"
+            }
+            _ = granted.cancel.cancelled() => {
+                break "cleared by the operator"
+            }
+            _ = &mut app_shutdown_handler => {
+                break "shutdown"
+            }
+            _ = status_interval.tick() => {
+                let status = overrides.lock().await.status(Instant::now());
+                publish(&stack, &status);
+            }
+        }
+    };
+
+    match overrider
+        .set_overridden(granted.board_axis, granted.limit, false)
+        .await
+    {
+        Ok(()) => warn!(
+            "Axis limit re-enabled. axis: {}, limit: {:?}, ended by: {}",
+            granted.axis, granted.limit, ended_by
+        ),
+        Err(e) => error!(
+            "Unable to re-enable axis limit, it is still disabled on the io board. axis: {}, limit: {:?}, ended by: {}, error: {:?}",
+            granted.axis, granted.limit, ended_by, e
+        ),
+    }
+
+    let status = {
+        let mut overrides = overrides.lock().await;
+        overrides.remove(&granted.axis, granted.limit);
+        overrides.status(Instant::now())
+    };
+    publish(&stack, &status);
+}
+
+fn publish(stack: &RouterStack, status: &LimitOverrideStatus) {
+    if let Err(e) = stack
+        .topics()
+        .broadcast::<LimitOverrideTopic>(status, None)
+    {
+        debug!("Unable to publish limit override status, error: {:?}", e);
+    }
+}
@@ -0,0 +1,206 @@
+use operator_shared::limits::{AxisLimit, LimitOverrideError, LimitOverrideRequest};
+use tokio::time::{Duration, Instant};
+
+use super::LimitOverrides;
+use crate::config::{AuthorizedOperator, HomingAxisDefinition, MaintenanceConfig};
+
+fn overrides() -> LimitOverrides {
+    let config = MaintenanceConfig {
+        operators: vec![AuthorizedOperator {
+            name: "maintenance".to_string(),
+            access_code: "1234".to_string(),
+        }],
+        max_override_s: 300,
+    };
+    let axes = vec![HomingAxisDefinition {
+        name: "X".to_string(),
+        axis: 0,
+        after: vec![],
+    }];
+    LimitOverrides::new(config, axes)
+}
+
+fn request() -> LimitOverrideRequest {
+    LimitOverrideRequest {
+        axis: "X".to_string(),
+        limit: AxisLimit::SafeZGuard,
+        operator: "maintenance".to_string(),
+        access_code: "1234".to_string(),
+        reason: " nozzle calibration ".to_string(),
+        duration_s: 60,
+    }
+}
+
+#[test]
+pub fn override_is_granted_for_the_axis_of_the_io_board() {
+    // given
+    let mut overrides = overrides();
+    let now = Instant::now();
+
+    // when
+    let granted = overrides
+        .grant(now, &request())
+        .unwrap();
+
+    // then
+    assert_eq!(granted.board_axis, 0);
+    assert_eq!(granted.duration, Duration::from_secs(60));
+    assert!(overrides.is_overridden("X", AxisLimit::SafeZGuard));
+
+    // and
+    let status = overrides.status(now + Duration::from_millis(500));
+    assert_eq!(status.overrides.len(), 1);
+    assert_eq!(status.overrides[0].operator, "maintenance");
+    assert_eq!(status.overrides[0].reason, "nozzle calibration");
+    assert_eq!(status.overrides[0].remaining_s, 60);
+}
+
+#[test]
+pub fn override_is_refused_without_authorization() {
+    // given
+    let mut overrides = overrides();
+    let unauthorized = [
+        LimitOverrideRequest {
+            access_code: "4321".to_string(),
+            ..request()
+        },
+        LimitOverrideRequest {
+            operator: "visitor".to_string(),
+            ..request()
+        },
+    ];
+
+    for request in unauthorized {
+        // when
+        let result = overrides.grant(Instant::now(), &request);
+
+        // then
+        assert_eq!(result.unwrap_err(), LimitOverrideError::Unauthorized);
+    }
+    assert!(!overrides.is_overridden("X", AxisLimit::SafeZGuard));
+}
+
+#[test]
+pub fn override_is_refused_without_authorized_operators() {
+    // given
+    let mut overrides = LimitOverrides::new(MaintenanceConfig::default(), vec![]);
+
+    // when
+    let result = overrides.grant(Instant::now(), &request());
+
+    // then
+    assert_eq!(result.unwrap_err(), LimitOverrideError::NotConfigured);
+}
+
+#[test]
+pub fn invalid_override_is_refused() {
+    // given
+    let mut overrides = overrides();
+    let cases = [
+        (
+            LimitOverrideRequest {
+                reason: "  ".to_string(),
+                ..request()
+            },
+            LimitOverrideError::MissingReason,
+        ),
+        (
+            LimitOverrideRequest {
+                axis: "W".to_string(),
+                ..request()
+            },
+            LimitOverrideError::UnknownAxis,
+        ),
+        (
+            LimitOverrideRequest {
+                duration_s: 0,
+                ..request()
+            },
+            LimitOverrideError::InvalidDuration {
+                max_s: 300,
+            },
+        ),
+        (
+            LimitOverrideRequest {
+                duration_s: 301,
+                ..request()
+            },
+            LimitOverrideError::InvalidDuration {
+                max_s: 300,
+            },
+        ),
+    ];
+
+    for (request, expected) in cases {
+        // when
+        let result = overrides.grant(Instant::now(), &request);
+
+        // then
+        assert_eq!(result.unwrap_err(), expected);
+    }
+}
+
+#[test]
+pub fn overridden_limit_is_not_overridden_again() {
+    // given
+    let mut overrides = overrides();
+    overrides
+        .grant(Instant::now(), &request())
+        .unwrap();
+
+    // when
+    let result = overrides.grant(Instant::now(), &request());
+
+    // then
+    assert_eq!(result.unwrap_err(), LimitOverrideError::AlreadyOverridden);
+}
+
+#[test]
+pub fn cleared_override_is_removed_once_re_enabled() {
+    // given
+    let mut overrides = overrides();
+    let granted = overrides
+        .grant(Instant::now(), &request())
+        .unwrap();
+
+    // when
+    overrides
+        .clear("X", AxisLimit::SafeZGuard)
+        .unwrap();
+
+    // then the runner re-enables the limit
+    assert!(granted.cancel.is_cancelled());
+    assert!(overrides.is_overridden("X", AxisLimit::SafeZGuard));
+
+    // when
+    overrides.remove("X", AxisLimit::SafeZGuard);
+
+    // then
+    assert!(!overrides.is_overridden("X", AxisLimit::SafeZGuard));
+    assert!(
+        overrides
+            .status(Instant::now())
+            .overrides
+            .is_empty()
+    );
+    assert_eq!(
+        overrides.clear("X", AxisLimit::SafeZGuard),
+        Err(LimitOverrideError::NotOverridden)
+    );
+}
+
+#[test]
+pub fn countdown_stops_at_zero() {
+    // given
+    let mut overrides = overrides();
+    let now = Instant::now();
+    overrides
+        .grant(now, &request())
+        .unwrap();
+
+    // when
+    let status = overrides.status(now + Duration::from_secs(61));
+
+    // then
+    assert_eq!(status.overrides[0].remaining_s, 0);
+}
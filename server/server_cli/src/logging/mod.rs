@@ -0,0 +1,500 @@
+//! The log of the server, written to stderr or to `--log-file`, and to the sinks of the [`LoggingConfig`].
+//!
+//! Logging starts before the config is loaded, see [`init`], the sinks of the config are added and the levels of its
+//! modules applied once it's loaded, see [`configure`].  The levels can be changed while the server runs, see
+//! [`set_level`], e.g. to debug a single module without restarting the machine.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{Level, LevelFilter, Log, Metadata, Record, warn};
+use operator_shared::diagnostics::{LogLevel, LogLevelError, LogLevels, ModuleLogLevel};
+
+#[cfg(unix)]
+use crate::config::SyslogConfig;
+use crate::config::{LoggingConfig, RotatingFileConfig};
+
+#[cfg(test)]
+mod tests;
+
+/// The syslog facility of the messages, `daemon`.
+const SYSLOG_FACILITY: u8 = 3;
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// The default level, and the levels of the modules and their sub-modules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleLevels {
+    default: LevelFilter,
+    modules: BTreeMap<String, LevelFilter>,
+}
+
+impl ModuleLevels {
+    pub fn new(default: LevelFilter) -> Self {
+        Self {
+            default,
+            modules: BTreeMap::new(),
+        }
+    }
+
+    /// Parses the directives of `RUST_LOG`, e.g. `info,server_cli::motion=debug`, a directive without a module is the
+    /// default level, the default level is `default` if there is no such directive.
+    pub fn parse(spec: &str, default: LevelFilter) -> Result<Self, String> {
+        let mut levels = Self::new(default);
+        for directive in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|directive| !directive.is_empty())
+        {
+            let invalid = || format!("Invalid log directive. directive: {}", directive);
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    let level = level
+                        .trim()
+                        .parse::<LevelFilter>()
+                        .map_err(|_| invalid())?;
+                    levels
+                        .set(Some(module.trim()), level)
+                        .map_err(|_| invalid())?;
+                }
+                // a bare module enables all of its levels, the same as `env_logger`
+                None => match directive.parse::<LevelFilter>() {
+                    Ok(level) => levels.default = level,
+                    Err(_) => levels
+                        .set(Some(directive), LevelFilter::Trace)
+                        .map_err(|_| invalid())?,
+                },
+            }
+        }
+        Ok(levels)
+    }
+
+    /// The level of the longest module that `target` is in, otherwise the default level.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| is_in_module(target, module))
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    /// The most verbose of the levels, messages that are more verbose are discarded by the `log` macros.
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules
+            .values()
+            .copied()
+            .fold(self.default, LevelFilter::max)
+    }
+
+    /// Sets the default level if `module` is `None`.
+    pub fn set(&mut self, module: Option<&str>, level: LevelFilter) -> Result<(), LogLevelError> {
+        match module {
+            None => self.default = level,
+            Some(module) => {
+                validate_module(module)?;
+                self.modules
+                    .insert(module.to_string(), level);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self, module: &str) -> Result<(), LogLevelError> {
+        validate_module(module)?;
+        match self.modules.remove(module) {
+            Some(_) => Ok(()),
+            None => Err(LogLevelError::UnknownModule(module.to_string())),
+        }
+    }
+
+    pub fn to_log_levels(&self) -> LogLevels {
+        LogLevels {
+            default: log_level(self.default),
+            modules: self
+                .modules
+                .iter()
+                .map(|(module, level)| ModuleLogLevel {
+                    module: module.clone(),
+                    level: log_level(*level),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// `true` if `target` is `module` or one of its sub-modules, `server_cli::motion` is not in `server_cli::mo`.
+fn is_in_module(target: &str, module: &str) -> bool {
+    match target.strip_prefix(module) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+/// A module path, e.g. `server_cli::motion`, the module doesn't have to exist.
+fn validate_module(module: &str) -> Result<(), LogLevelError> {
+    let valid = module.split("::").all(|segment| {
+        !segment.is_empty()
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    });
+    match valid {
+        true => Ok(()),
+        false => Err(LogLevelError::InvalidModule(module.to_string())),
+    }
+}
+
+pub fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Off => LevelFilter::Off,
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warn => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+        LogLevel::Trace => LevelFilter::Trace,
+    }
+}
+
+pub fn log_level(level: LevelFilter) -> LogLevel {
+    match level {
+        LevelFilter::Off => LogLevel::Off,
+        LevelFilter::Error => LogLevel::Error,
+        LevelFilter::Warn => LogLevel::Warn,
+        LevelFilter::Info => LogLevel::Info,
+        LevelFilter::Debug => LogLevel::Debug,
+        LevelFilter::Trace => LogLevel::Trace,
+    }
+}
+
+/// A single line, including the line feed, e.g. `[2025-01-01T12:00:00Z INFO  server_cli::motion] message`, the
+/// same format as `env_logger`.
+pub fn format_line(record: &Record, timestamp: DateTime<Utc>) -> String {
+    format!(
+        "[{} {:<5} {}] {}\n",
+        timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+        record.level(),
+        record.target(),
+        record.args()
+    )
+}
+
+/// A message for the syslog socket, the daemon adds the timestamp and the host name.
+pub fn syslog_message(record: &Record, identifier: &str, pid: u32) -> String {
+    let severity = match record.level() {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    };
+    format!(
+        "<{}>{}[{}]: {}: {}",
+        SYSLOG_FACILITY * 8 + severity,
+        identifier,
+        pid,
+        record.target(),
+        record.args()
+    )
+}
+
+trait Sink: Send {
+    /// `line` is the formatted record, see [`format_line`].
+    fn write(&mut self, record: &Record, line: &str) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Stderr, or the `--log-file`.
+struct StreamSink(Box<dyn Write + Send>);
+
+impl Sink for StreamSink {
+    fn write(&mut self, _record: &Record, line: &str) -> io::Result<()> {
+        self.0.write_all(line.as_bytes())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// See [`RotatingFileConfig`], the file is opened again on the next line after a failed write.
+pub struct RotatingFile {
+    config: RotatingFileConfig,
+    file: Option<File>,
+    size: u64,
+    opened_at: SystemTime,
+}
+
+impl RotatingFile {
+    pub fn new(config: RotatingFileConfig) -> Self {
+        Self {
+            config,
+            file: None,
+            size: 0,
+            opened_at: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    pub fn write_line(&mut self, line: &str, now: SystemTime) -> io::Result<()> {
+        if self.file.is_some() && self.is_due(line.len() as u64, now) {
+            self.file = None;
+            self.rotate()?;
+        }
+
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.open(now)?,
+        };
+        let result = file.write_all(line.as_bytes());
+        match &result {
+            Ok(()) => self.size += line.len() as u64,
+            Err(_) => self.file = None,
+        }
+        result
+    }
+
+    /// A line longer than the size limit is still written, to an empty file.
+    fn is_due(&self, length: u64, now: SystemTime) -> bool {
+        let too_large = self.size > 0 && self.size + length > self.config.max_size_bytes;
+        let too_old = self.config.max_age_hours > 0
+            && now
+                .duration_since(self.opened_at)
+                .unwrap_or_default()
+                >= Duration::from_secs(self.config.max_age_hours * 60 * 60);
+        too_large || too_old
+    }
+
+    /// `<path>.<n>` is renamed to `<path>.<n+1>`, the oldest is overwritten, and `<path>` is renamed to `<path>.1`.
+    fn rotate(&self) -> io::Result<()> {
+        let path = &self.config.path;
+        if self.config.keep == 0 {
+            return fs::remove_file(path);
+        }
+        for index in (1..self.config.keep).rev() {
+            let from = rotated_path(path, index);
+            if from.exists() {
+                fs::rename(&from, rotated_path(path, index + 1))?;
+            }
+        }
+        fs::rename(path, rotated_path(path, 1))
+    }
+
+    fn open(&mut self, now: SystemTime) -> io::Result<&mut File> {
+        if let Some(directory) = self.config.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.path)?;
+        // a file left by the previous run is appended to, its age is counted from now
+        self.size = file.metadata()?.len();
+        self.opened_at = now;
+        Ok(self.file.insert(file))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn rotated_path(path: &Path, index: u32) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", index));
+    PathBuf::from(rotated)
+}
+
+impl Sink for RotatingFile {
+    fn write(&mut self, _record: &Record, line: &str) -> io::Result<()> {
+        self.write_line(line, SystemTime::now())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        RotatingFile::flush(self)
+    }
+}
+
+#[cfg(unix)]
+struct SyslogSink {
+    config: SyslogConfig,
+    socket: std::os::unix::net::UnixDatagram,
+    pid: u32,
+}
+
+#[cfg(unix)]
+impl SyslogSink {
+    fn new(config: SyslogConfig) -> io::Result<Self> {
+        Ok(Self {
+            config,
+            socket: std::os::unix::net::UnixDatagram::unbound()?,
+            pid: std::process::id(),
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Sink for SyslogSink {
+    fn write(&mut self, record: &Record, _line: &str) -> io::Result<()> {
+        let message = syslog_message(record, &self.config.identifier, self.pid);
+        self.socket
+            .send_to(message.as_bytes(), &self.config.socket)
+            .map(|_| ())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Logger {
+    levels: RwLock<ModuleLevels>,
+    sinks: Mutex<Vec<Box<dyn Sink>>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level()
+            <= self
+                .levels
+                .read()
+                .unwrap()
+                .level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_line(record, Utc::now());
+        for sink in self.sinks.lock().unwrap().iter_mut() {
+            // there is nowhere to report a failed write to, the other sinks are still written to
+            let _ = sink.write(record, &line);
+        }
+    }
+
+    fn flush(&self) {
+        for sink in self.sinks.lock().unwrap().iter_mut() {
+            let _ = sink.flush();
+        }
+    }
+}
+
+/// Logging is initialized before the config is loaded, and before anything can change the levels.
+fn logger() -> &'static Logger {
+    LOGGER.get().expect("initialized")
+}
+
+/// Logs to stderr, or to `log_file` if given, e.g. when there is no console.
+///
+/// The level is given by `verbosity_level`, unless `RUST_LOG` is set.
+pub fn init(verbosity_level: u8, log_file: Option<&Path>) -> anyhow::Result<()> {
+    let default = match verbosity_level {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    let (levels, invalid_spec) = match std::env::var("RUST_LOG") {
+        Ok(spec) => match ModuleLevels::parse(&spec, default) {
+            Ok(levels) => (levels, None),
+            Err(e) => (ModuleLevels::new(default), Some(e)),
+        },
+        Err(_) => (ModuleLevels::new(default), None),
+    };
+
+    let sink: Box<dyn Sink> = match log_file {
+        Some(log_file) => {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log_file)
+                .map_err(|e| anyhow::format_err!("Unable to open log file. path: {:?}, error: {}", log_file, e))?;
+            Box::new(StreamSink(Box::new(file)))
+        }
+        None => Box::new(StreamSink(Box::new(io::stderr()))),
+    };
+
+    log::set_max_level(levels.max_level());
+    let logger = LOGGER.get_or_init(|| Logger {
+        levels: RwLock::new(levels),
+        sinks: Mutex::new(vec![sink]),
+    });
+    log::set_logger(logger).map_err(|e| anyhow::format_err!("Unable to initialize logging. error: {}", e))?;
+
+    if let Some(e) = invalid_spec {
+        warn!("RUST_LOG ignored. {}", e);
+    }
+    Ok(())
+}
+
+/// Adds the sinks of the config, and applies the levels of its modules unless `RUST_LOG` is set.
+pub fn configure(config: &LoggingConfig) -> anyhow::Result<()> {
+    let logger = logger();
+
+    if std::env::var_os("RUST_LOG").is_none() {
+        let mut levels = logger.levels.write().unwrap();
+        for (module, level) in &config.modules {
+            levels
+                .set(Some(module), level_filter(*level))
+                .map_err(|e| anyhow::format_err!("Invalid logging config. error: {:?}", e))?;
+        }
+        log::set_max_level(levels.max_level());
+    }
+
+    let mut sinks: Vec<Box<dyn Sink>> = vec![];
+    if let Some(file) = &config.file {
+        let mut rotating_file = RotatingFile::new(file.clone());
+        rotating_file
+            .open(SystemTime::now())
+            .map_err(|e| anyhow::format_err!("Unable to open log file. path: {:?}, error: {}", file.path, e))?;
+        sinks.push(Box::new(rotating_file));
+    }
+    if let Some(syslog) = &config.syslog {
+        #[cfg(unix)]
+        sinks.push(Box::new(SyslogSink::new(syslog.clone())?));
+        #[cfg(not(unix))]
+        warn!("Syslog is only supported on unix, ignored. socket: {:?}", syslog.socket);
+    }
+    logger
+        .sinks
+        .lock()
+        .unwrap()
+        .extend(sinks);
+    Ok(())
+}
+
+pub fn levels() -> LogLevels {
+    logger()
+        .levels
+        .read()
+        .unwrap()
+        .to_log_levels()
+}
+
+/// Changes the level until the server restarts, the default level if `module` is `None`, returns the levels after the
+/// change.
+pub fn set_level(module: Option<&str>, level: LogLevel) -> Result<LogLevels, LogLevelError> {
+    update_levels(|levels| levels.set(module, level_filter(level)))
+}
+
+/// Removes the level of the module, the level of its parent module applies again.
+pub fn clear_level(module: &str) -> Result<LogLevels, LogLevelError> {
+    update_levels(|levels| levels.clear(module))
+}
+
+fn update_levels(
+    update: impl FnOnce(&mut ModuleLevels) -> Result<(), LogLevelError>,
+) -> Result<LogLevels, LogLevelError> {
+    let mut levels = logger().levels.write().unwrap();
+    update(&mut levels)?;
+    log::set_max_level(levels.max_level());
+    Ok(levels.to_log_levels())
+}
@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use chrono::{TimeZone, Utc};
+use log::{Level, LevelFilter, Record};
+use operator_shared::diagnostics::{LogLevel, LogLevelError, ModuleLogLevel};
+
+use super::{ModuleLevels, RotatingFile, format_line, rotated_path, syslog_message};
+use crate::config::RotatingFileConfig;
+
+fn directory() -> PathBuf {
+    std::env::temp_dir().join(format!("logging-test-{:016x}", rand::random::<u64>()))
+}
+
+#[test]
+pub fn parses_the_directives_of_rust_log() {
+    // when
+    let levels = ModuleLevels::parse(
+        "info, server_cli::motion=debug,server_cli::networking",
+        LevelFilter::Warn,
+    )
+    .unwrap();
+
+    // then
+    let log_levels = levels.to_log_levels();
+    assert_eq!(log_levels.default, LogLevel::Info);
+    assert_eq!(log_levels.modules, vec![
+        ModuleLogLevel {
+            module: "server_cli::motion".to_string(),
+            level: LogLevel::Debug,
+        },
+        ModuleLogLevel {
+            module: "server_cli::networking".to_string(),
+            level: LogLevel::Trace,
+        },
+    ]);
+}
+
+#[test]
+pub fn invalid_directive_is_rejected() {
+    // expect
+    assert!(ModuleLevels::parse("server_cli::motion=loud", LevelFilter::Warn).is_err());
+    assert!(ModuleLevels::parse("server_cli:::motion", LevelFilter::Warn).is_err());
+}
+
+#[test]
+pub fn longest_matching_module_applies() {
+    // given
+    let mut levels = ModuleLevels::new(LevelFilter::Warn);
+    levels
+        .set(Some("server_cli"), LevelFilter::Info)
+        .unwrap();
+    levels
+        .set(Some("server_cli::motion"), LevelFilter::Trace)
+        .unwrap();
+
+    // expect
+    assert_eq!(levels.level_for("server_cli::motion::planner"), LevelFilter::Trace);
+    assert_eq!(levels.level_for("server_cli::motion"), LevelFilter::Trace);
+    // not a sub-module of `server_cli::motion`
+    assert_eq!(levels.level_for("server_cli::motion_test"), LevelFilter::Info);
+    assert_eq!(levels.level_for("ergot::net_stack"), LevelFilter::Warn);
+    assert_eq!(levels.max_level(), LevelFilter::Trace);
+}
+
+#[test]
+pub fn cleared_module_uses_the_level_of_its_parent() {
+    // given
+    let mut levels = ModuleLevels::new(LevelFilter::Warn);
+    levels
+        .set(Some("server_cli::motion"), LevelFilter::Debug)
+        .unwrap();
+
+    // when
+    levels
+        .clear("server_cli::motion")
+        .unwrap();
+
+    // then
+    assert_eq!(levels.level_for("server_cli::motion"), LevelFilter::Warn);
+    assert_eq!(
+        levels.clear("server_cli::motion"),
+        Err(LogLevelError::UnknownModule("server_cli::motion".to_string()))
+    );
+}
+
+#[test]
+pub fn invalid_module_is_rejected() {
+    // given
+    let mut levels = ModuleLevels::new(LevelFilter::Warn);
+
+    // when
+    let result = levels.set(Some("server cli"), LevelFilter::Debug);
+
+    // then
+    assert_eq!(result, Err(LogLevelError::InvalidModule("server cli".to_string())));
+    assert_eq!(levels, ModuleLevels::new(LevelFilter::Warn));
+}
+
+#[test]
+pub fn line_has_the_format_of_env_logger() {
+    // given
+    let timestamp = Utc
+        .with_ymd_and_hms(2025, 1, 2, 3, 4, 5)
+        .unwrap();
+
+    // when
+    let line = format_line(
+        &Record::builder()
+            .args(format_args!("parked"))
+            .level(Level::Info)
+            .target("server_cli::parking")
+            .build(),
+        timestamp,
+    );
+
+    // then
+    assert_eq!(line, "[2025-01-02T03:04:05Z INFO  server_cli::parking] parked\n");
+}
+
+#[test]
+pub fn syslog_message_has_the_priority_of_the_level() {
+    // when
+    let message = syslog_message(
+        &Record::builder()
+            .args(format_args!("no response"))
+            .level(Level::Warn)
+            .target("server_cli::ioboard")
+            .build(),
+        "makerpnp-server",
+        42,
+    );
+
+    // then
+    // daemon facility (3) * 8 + warning severity (4)
+    assert_eq!(message, "<28>makerpnp-server[42]: server_cli::ioboard: no response");
+}
+
+#[test]
+pub fn file_is_rotated_when_too_large() {
+    // given
+    let directory = directory();
+    let path = directory.join("server.log");
+    let mut file = RotatingFile::new(RotatingFileConfig {
+        path: path.clone(),
+        max_size_bytes: 10,
+        max_age_hours: 0,
+        keep: 2,
+    });
+    let now = SystemTime::now();
+
+    // when
+    for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+        file.write_line(line, now).unwrap();
+    }
+    file.flush().unwrap();
+
+    // then
+    assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+    assert_eq!(fs::read_to_string(rotated_path(&path, 1)).unwrap(), "third\n");
+    assert_eq!(fs::read_to_string(rotated_path(&path, 2)).unwrap(), "second\n");
+    // only `keep` rotated files are kept
+    assert!(!rotated_path(&path, 3).exists());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+pub fn file_is_rotated_when_too_old() {
+    // given
+    let directory = directory();
+    let path = directory.join("server.log");
+    let mut file = RotatingFile::new(RotatingFileConfig {
+        path: path.clone(),
+        max_size_bytes: 1024,
+        max_age_hours: 24,
+        keep: 1,
+    });
+    let opened_at = SystemTime::now();
+    file.write_line("yesterday\n", opened_at)
+        .unwrap();
+
+    // when
+    file.write_line("today\n", opened_at + Duration::from_secs(24 * 60 * 60))
+        .unwrap();
+    file.flush().unwrap();
+
+    // then
+    assert_eq!(fs::read_to_string(&path).unwrap(), "today\n");
+    assert_eq!(fs::read_to_string(rotated_path(&path, 1)).unwrap(), "yesterday\n");
+
+    fs::remove_dir_all(&directory).unwrap();
+}
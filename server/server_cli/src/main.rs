@@ -1,39 +1,127 @@
 use std::collections::HashMap;
 use std::fs;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::bail;
 #[cfg(feature = "machine-vision")]
-use camera::CameraHandle;
+use camera::budget::FrameBudget;
+#[cfg(feature = "machine-vision")]
+use camera::{CameraCaptures, CameraClient};
+#[cfg(feature = "machine-vision")]
+use captures::CaptureStore;
 use clap::Parser;
 use config::{IO_BOARD_LOCAL_ADDR, IO_BOARD_REMOTE_ADDR, OPERATOR_LOCAL_ADDR, OPERATOR_REMOTE_ADDR};
 use ergot::toolkits::tokio_udp::{RouterStack, register_router_interface};
-use ioboard::IOBOARD_TX_BUFFER_SIZE;
+use ioboard::{CommandSequencer, IOBOARD_TX_BUFFER_SIZE};
+use ioboard_shared::force::ForceTrace;
 use log::info;
+use machine_ids::CameraId;
 use networking::UDP_OVER_ETH_ERGOT_PAYLOAD_SIZE_MAX;
 use operator::OPERATOR_TX_BUFFER_SIZE;
-use operator_shared::camera::CameraIdentifier;
+use operator_shared::readiness::{CheckState, ReadinessCheck};
+use server_common::position::PositionHistory;
+use storage::StorageImpl;
+#[cfg(feature = "machine-vision")]
+use templates::TemplateStore;
+use tokio::net::UdpSocket;
 use tokio::sync::broadcast::Receiver;
-use tokio::sync::{Mutex, broadcast};
-use tokio::{net::UdpSocket, signal};
+use tokio::sync::{Mutex, broadcast, mpsc, watch};
+#[cfg(feature = "machine-vision")]
+use vision::VisionQueue;
 
-use crate::config::Config;
+use crate::config::{Config, MotionPlanning};
+use crate::diagnostics::tap::TopicTap;
+use crate::feeders::Feeders;
+use crate::forces::FORCE_TRACE_QUEUE_SIZE;
+use crate::ioboard::batching::CommandBatcher;
+use crate::job::JobControl;
+use crate::job::checkpoint::CheckpointStore;
+use crate::job::pick_place::HeadMotion;
+use crate::limits::LimitOverrides;
+use crate::motion::commands::MotionCommander;
+use crate::parking::{ParkTrigger, SetpointHeadMover};
+use crate::power::EnergyCounter;
+use crate::readiness::Readiness;
+use crate::runout::{NozzleRunout, RunoutStore};
+use crate::safety::SafetyState;
+use crate::segments::NetworkSegment;
+use crate::supervisor::{RestartPolicy, Supervisor};
+use crate::test_area::TestArea;
 
+pub mod accuracy;
+pub mod analog;
+pub mod bridge;
+pub mod burnin;
 #[cfg(feature = "machine-vision")]
 pub mod camera;
+#[cfg(feature = "machine-vision")]
+pub mod captures;
+pub mod coordinates;
+pub mod diagnostics;
+pub mod dispensing;
+pub mod estop;
+pub mod feeders;
+pub mod forces;
+pub mod homing;
+pub mod init;
 pub mod ioboard;
+pub mod job;
+pub mod limits;
+pub mod logging;
+pub mod motion;
 pub mod networking;
+pub mod nozzles;
 pub mod operator;
+pub mod orientation;
+pub mod parking;
+pub mod power;
+pub mod readiness;
+pub mod resonance;
+pub mod runout;
+pub mod safety;
+#[cfg(feature = "machine-vision")]
+pub mod scanning;
+pub mod segments;
+pub mod service;
+pub mod storage;
+pub mod supervisor;
+#[cfg(feature = "machine-vision")]
+pub mod templates;
+pub mod test_area;
+pub mod travel;
+#[cfg(feature = "machine-vision")]
+pub mod vision;
 
 pub mod cli;
 pub mod config;
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
     let args = cli::Args::parse();
 
-    init_logging(args.verbosity_level);
+    #[cfg(windows)]
+    if args.service {
+        return service::windows::run(args);
+    }
+
+    logging::init(args.verbosity_level, args.log_file.as_deref())?;
+
+    if let Some(directory) = &args.init {
+        return init::init(directory);
+    }
 
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(run(args, service::notify_ready, async {
+        service::shutdown_signal().await;
+        service::notify_stopping();
+    }))
+}
+
+/// Runs the server until `shutdown` completes, `on_ready` is called once the server has started.
+async fn run(args: cli::Args, on_ready: impl FnOnce(), shutdown: impl Future<Output = ()>) -> anyhow::Result<()> {
     console_subscriber::init();
 
     #[cfg(feature = "machine-vision")]
@@ -51,11 +139,37 @@ async fn main() -> anyhow::Result<()> {
     else {
         bail!("Unable to load config. filename: {:?}", confile_filename)
     };
+    logging::configure(&config.logging)?;
+    parking::validate(&config.parking)?;
+
+    let job = match &args.job {
+        Some(path) => Some(job::load_job(path)?),
+        None => None,
+    };
 
     // Create event channel
     let (app_event_tx, app_event_rx) = broadcast::channel::<AppEvent>(16);
     drop(app_event_rx);
 
+    // a task that fails repeatedly parks the head, and fails a readiness check
+    let (parking_tx, parking_rx) = mpsc::channel(parking::TRIGGER_QUEUE_SIZE);
+    let readiness = Arc::new(Mutex::new(Readiness::new()));
+    readiness
+        .lock()
+        .await
+        .update(ReadinessCheck::ServerTasksRunning, CheckState::Passed);
+    // until the io board reports a critical temperature
+    readiness
+        .lock()
+        .await
+        .update(ReadinessCheck::TemperaturesNormal, CheckState::Passed);
+    let supervisor = Supervisor::new(
+        config.supervisor.clone(),
+        readiness.clone(),
+        parking_tx.clone(),
+        app_event_tx.clone(),
+    );
+
     let stack: RouterStack = RouterStack::new();
 
     let io_board_udp_socket = UdpSocket::bind(IO_BOARD_LOCAL_ADDR)
@@ -116,38 +230,584 @@ async fn main() -> anyhow::Result<()> {
     .await
     .unwrap();
 
-    let basic_services_handle = tokio::task::Builder::new()
-        .name("ergot/basic-services")
-        .spawn(networking::basic_services(
-            stack.clone(),
-            0_u16,
+    segments::validate_segments(&config.network_segments, &[
+        IO_BOARD_LOCAL_ADDR.parse()?,
+        OPERATOR_LOCAL_ADDR.parse()?,
+    ])
+    .map_err(|e| anyhow::format_err!("Invalid network segments. error: {:?}", e))?;
+    bridge::validate_config(&config.bridge)
+        .map_err(|e| anyhow::format_err!("Invalid operator bridge. error: {:?}", e))?;
+    let machine_planning = motion::commands::machine_planning(&config.io_boards)?;
+    let mut network_segments = Vec::with_capacity(config.network_segments.len());
+    for definition in config.network_segments.iter() {
+        network_segments.push(NetworkSegment::start(definition.clone()).await?);
+    }
+
+    let mut segment_handles = Vec::with_capacity(network_segments.len() * 2);
+    for segment in network_segments.iter() {
+        segment_handles.push(supervisor.spawn(
+            &format!("ergot/segment-router/{}", segment.definition.name),
+            RestartPolicy::Always,
+            {
+                let (stack, segment_stack, app_event_tx) = (stack.clone(), segment.stack.clone(), app_event_tx.clone());
+                let definition = segment.definition.clone();
+                move || {
+                    segments::segment_router(
+                        stack.clone(),
+                        segment_stack.clone(),
+                        definition.clone(),
+                        app_event_tx.subscribe(),
+                    )
+                }
+            },
+        )?);
+        // discovery and ping on the segment
+        segment_handles.push(supervisor.spawn(
+            &format!("ergot/segment-basic-services/{}", segment.definition.name),
+            RestartPolicy::Always,
+            {
+                let (segment_stack, app_event_tx) = (segment.stack.clone(), app_event_tx.clone());
+                move || networking::basic_services(segment_stack.clone(), 0_u16, app_event_tx.subscribe())
+            },
+        )?);
+    }
+
+    let basic_services_handle = supervisor.spawn("ergot/basic-services", RestartPolicy::Always, {
+        let (stack, app_event_tx) = (stack.clone(), app_event_tx.clone());
+        move || networking::basic_services(stack.clone(), 0_u16, app_event_tx.subscribe())
+    })?;
+    let load_cell_listener_handle = supervisor.spawn("ergot/load-cell-listener", RestartPolicy::Always, {
+        let (stack, app_event_tx) = (stack.clone(), app_event_tx.clone());
+        move || networking::load_cell_listener(stack.clone(), app_event_tx.subscribe())
+    })?;
+
+    // owns the receiver of the dead letters, it can't be restarted
+    let dead_letter_writer_handle = supervisor.spawn_once(
+        "ergot/dead-letter-writer",
+        networking::dead_letter::dead_letter_writer(
+            config.dead_letters.clone(),
+            networking::dead_letter::install().expect("installed once"),
             app_event_tx.subscribe(),
-        ))?;
-    let yeet_listener_handle = tokio::task::Builder::new()
-        .name("ergot/yeet-listener")
-        .spawn(networking::yeet_listener(stack.clone(), app_event_tx.subscribe()))?;
+        ),
+    )?;
+
+    let latency_monitor_handle = supervisor.spawn("io-board/latency-monitor", RestartPolicy::Always, {
+        let (stack, app_event_tx) = (stack.clone(), app_event_tx.clone());
+        let config = config.command_latency.clone();
+        move || diagnostics::latency_monitor(stack.clone(), config.clone(), app_event_tx.subscribe())
+    })?;
+    let router_inspector_handle = supervisor.spawn("ergot/router-inspector", RestartPolicy::Always, {
+        let (stack, app_event_tx) = (stack.clone(), app_event_tx.clone());
+        let config = config.router_inspection.clone();
+        move || networking::inspection::router_inspector(stack.clone(), config.clone(), app_event_tx.subscribe())
+    })?;
+
+    let (command_batcher, command_rx) = CommandBatcher::new();
+    // owns the receiver of the commands, it can't be restarted
+    let batch_sender_handle = supervisor.spawn_once(
+        "io-board/batch-sender",
+        ioboard::batching::batch_sender(stack.clone(), config.command_batching.clone(), command_rx),
+    )?;
+
+    let (safety_tx, safety_rx) = watch::channel(SafetyState::UNKNOWN);
+    let safety_listener_handle = supervisor.spawn("io-board/safety-listener", RestartPolicy::Always, {
+        let (stack, app_event_tx) = (stack.clone(), app_event_tx.clone());
+        move || safety::safety_listener(stack.clone(), safety_tx.clone(), app_event_tx.subscribe())
+    })?;
+
+    let estop_listener_handle = supervisor.spawn("io-board/estop-listener", RestartPolicy::Always, {
+        let (stack, app_event_tx) = (stack.clone(), app_event_tx.clone());
+        move || estop::estop_listener(stack.clone(), app_event_tx.subscribe())
+    })?;
+
+    // FUTURE each board should have its own axes, currently all io boards have a single axis
+    let server_planned_rates = config
+        .io_boards
+        .iter()
+        .filter_map(|io_board| match io_board.planning {
+            MotionPlanning::OnBoard => None,
+            MotionPlanning::Server {
+                rate_hz,
+            } => Some(rate_hz),
+        })
+        .collect::<Vec<_>>();
+
+    #[cfg(not(feature = "machine-vision"))]
+    if args.measure_accuracy {
+        bail!("Accuracy measurement requires machine vision")
+    }
+    #[cfg(not(feature = "machine-vision"))]
+    if args.measure_runout {
+        bail!("Runout measurement requires machine vision")
+    }
+    let routines = [args.measure_accuracy, args.measure_runout, args.burn_in_hours.is_some()];
+    if routines
+        .iter()
+        .filter(|routine| **routine)
+        .count()
+        > 1
+    {
+        bail!("Only one of the accuracy measurement, the runout measurement and the burn-in can be run at a time")
+    }
+
+    let storage = StorageImpl::build(&config.storage)
+        .map_err(|e| anyhow::format_err!("Unable to create storage. error: {:?}", e))?;
+    let storage = Arc::new(storage);
+
+    #[cfg(feature = "machine-vision")]
+    let (vision_queue, vision_queue_rx) = VisionQueue::new();
+
+    // the axes only move when requested without a repeated trajectory
+    let repeated = !config
+        .motion
+        .repeated_trajectory
+        .is_empty();
+    let setpoint_streamer_handles = match args.burn_in_hours {
+        #[cfg(feature = "machine-vision")]
+        None if args.measure_accuracy => {
+            if server_planned_rates.is_empty() {
+                bail!("Accuracy measurement requires an io board with server motion planning")
+            }
+            server_planned_rates
+                .iter()
+                .map(|rate_hz| {
+                    supervisor.spawn_once(
+                        "vision/accuracy",
+                        accuracy::vision::accuracy_runner(
+                            command_batcher.clone(),
+                            0,
+                            *rate_hz,
+                            config.cameras.clone(),
+                            config.accuracy.clone(),
+                            config.axis_corrections,
+                            vision_queue.clone(),
+                            safety_rx.clone(),
+                            storage.clone(),
+                            app_event_tx.subscribe(),
+                        ),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        #[cfg(feature = "machine-vision")]
+        None if args.measure_runout => {
+            // the nozzle is rotated by the first io board with server motion planning
+            let Some(rate_hz) = server_planned_rates.first() else {
+                bail!("Runout measurement requires an io board with server motion planning")
+            };
+            vec![supervisor.spawn_once(
+                "vision/runout",
+                runout::vision::runout_runner(
+                    command_batcher.clone(),
+                    *rate_hz,
+                    config.cameras.clone(),
+                    config.runout.clone(),
+                    vision_queue.clone(),
+                    safety_rx.clone(),
+                    app_event_tx.subscribe(),
+                ),
+            )?]
+        }
+        None if !repeated => vec![],
+        None => server_planned_rates
+            .iter()
+            .map(|rate_hz| {
+                // not restarted, a restarted streamer would plan from the start position, not the current one
+                supervisor.spawn_once(
+                    "io-board/setpoint-streamer",
+                    motion::setpoint_streamer(
+                        command_batcher.clone(),
+                        0,
+                        *rate_hz,
+                        config
+                            .motion
+                            .repeated_trajectory
+                            .clone(),
+                        safety_rx.clone(),
+                        app_event_tx.subscribe(),
+                    ),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(hours) => {
+            if !hours.is_finite() || hours <= 0.0 {
+                bail!("Invalid burn-in duration. hours: {}", hours)
+            }
+            if server_planned_rates.is_empty() {
+                bail!("Burn-in requires an io board with server motion planning")
+            }
+            server_planned_rates
+                .iter()
+                .map(|rate_hz| {
+                    supervisor.spawn_once(
+                        "io-board/burn-in",
+                        burnin::burn_in_runner(
+                            stack.clone(),
+                            command_batcher.clone(),
+                            0,
+                            *rate_hz,
+                            config.burn_in.clone(),
+                            Duration::from_secs_f64(hours * 3600.0),
+                            safety_rx.clone(),
+                            storage.clone(),
+                            app_event_tx.subscribe(),
+                        ),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    #[cfg(feature = "machine-vision")]
+    let (capture_store, template_store) = {
+        let capture_store = CaptureStore::new(storage.clone(), &config.captures);
+        let template_store = TemplateStore::new(storage.clone());
+        // a fresh install has no templates
+        let _ = template_store
+            .install_defaults()
+            .await
+            .inspect_err(|e| log::warn!("Unable to install the default templates. error: {:?}", e));
+        (Arc::new(capture_store), Arc::new(template_store))
+    };
+    // captures may have expired while the server wasn't running, the retention is applied at startup
+    #[cfg(feature = "machine-vision")]
+    let capture_retention_handle = supervisor.spawn("captures/retention", RestartPolicy::Always, {
+        let (capture_store, app_event_tx) = (capture_store.clone(), app_event_tx.clone());
+        let config = config.captures.clone();
+        move || captures::capture_retention(capture_store.clone(), config.clone(), app_event_tx.subscribe())
+    })?;
+
+    let position_history = PositionHistory::default();
+    let position_listener_handle = supervisor.spawn("io-board/position-listener", RestartPolicy::Always, {
+        let (stack, position_history, app_event_tx) = (stack.clone(), position_history.clone(), app_event_tx.clone());
+        move || motion::position_listener(stack.clone(), position_history.clone(), app_event_tx.subscribe())
+    })?;
+
+    let (force_tx, _) = broadcast::channel::<ForceTrace>(FORCE_TRACE_QUEUE_SIZE);
+    let force_listener_handle = supervisor.spawn("io-board/force-listener", RestartPolicy::Always, {
+        let (stack, force_tx, app_event_tx) = (stack.clone(), force_tx.clone(), app_event_tx.clone());
+        move || forces::force_listener(stack.clone(), force_tx.clone(), app_event_tx.subscribe())
+    })?;
+
+    let command_sequencer = Arc::new(CommandSequencer::new());
+
+    let planning_configurator_handle = match machine_planning {
+        Some(planning) => Some(
+            supervisor.spawn("io-board/planning-configurator", RestartPolicy::Always, {
+                let commander = MotionCommander::new(stack.clone(), command_sequencer.clone());
+                let app_event_tx = app_event_tx.clone();
+                move || {
+                    motion::commands::planning_configurator(commander.clone(), 0, planning, app_event_tx.subscribe())
+                }
+            })?,
+        ),
+        None => None,
+    };
+
+    // moves are only queued while no routine has the axes
+    let on_board_planned = config
+        .io_boards
+        .iter()
+        .any(|io_board| io_board.planning == MotionPlanning::OnBoard);
+    let move_commander_handle = match on_board_planned && repeated && !routines.contains(&true) {
+        true => {
+            let commander = MotionCommander::new(stack.clone(), command_sequencer.clone());
+            // not restarted, for the same reason as the setpoint streamer
+            Some(
+                supervisor.spawn_once(
+                    "io-board/move-commander",
+                    motion::commands::move_commander(
+                        commander,
+                        command_batcher.clone(),
+                        0,
+                        config
+                            .motion
+                            .repeated_trajectory
+                            .clone(),
+                        safety_rx.clone(),
+                        app_event_tx.subscribe(),
+                    ),
+                )?,
+            )
+        }
+        false => None,
+    };
+
+    // the burn-in, the accuracy and runout routines, and the setpoint streamer of the repeated trajectory move the axes
+    // themselves, their setpoints would interleave with the setpoints of the parking and the jobs
+    let parking_rate_hz = server_planned_rates
+        .first()
+        .copied()
+        .filter(|_| args.burn_in_hours.is_none() && !args.measure_accuracy && !args.measure_runout && !repeated);
+    let parking_runner_handle = match parking_rate_hz {
+        Some(rate_hz) => {
+            let mover = SetpointHeadMover::new(
+                command_batcher.clone(),
+                rate_hz,
+                &config.parking,
+                position_history.clone(),
+                safety_rx.clone(),
+            );
+            // owns the receiver of the triggers, it can't be restarted
+            Some(supervisor.spawn_once(
+                "io-board/parking-runner",
+                parking::parking_runner(
+                    stack.clone(),
+                    mover,
+                    config.parking.clone(),
+                    command_sequencer.clone(),
+                    parking_rx,
+                    app_event_tx.subscribe(),
+                ),
+            )?)
+        }
+        None => {
+            info!("Parking disabled, requires an io board with server motion planning, and no repeated trajectory");
+            None
+        }
+    };
+    // the parts are placed with the same moves as the parking
+    let head_motion = parking_rate_hz.map(|rate_hz| HeadMotion {
+        batcher: command_batcher.clone(),
+        rate_hz,
+        position_history: position_history.clone(),
+        safety_rx: safety_rx.clone(),
+    });
+
+    let checkpoint = CheckpointStore::new(config.job.checkpoint_path.clone())
+        .load()
+        .await?;
+    if let Some(checkpoint) = &checkpoint {
+        info!(
+            "Found checkpoint of an interrupted job. job: {}, next_placement: {}, updated_at: {}",
+            checkpoint.job, checkpoint.next_placement, checkpoint.updated_at
+        );
+    }
+    // the counts of an interrupted job are restored even if the job is not resumed, they are the counts of the parts
+    // still in the feeders
+    let feeder_counts = checkpoint
+        .as_ref()
+        .map(|checkpoint| checkpoint.feeder_counts.clone())
+        .unwrap_or_default();
+    let feeders = Arc::new(Mutex::new(Feeders::new(&config.feeders, &feeder_counts)));
+    let feeder_monitor_handle = supervisor.spawn("operator/feeder-monitor", RestartPolicy::Always, {
+        let (stack, feeders, app_event_tx) = (stack.clone(), feeders.clone(), app_event_tx.clone());
+        move || feeders::feeder_monitor(stack.clone(), feeders.clone(), app_event_tx.subscribe())
+    })?;
+
+    let readiness_monitor_handle = supervisor.spawn("operator/readiness-monitor", RestartPolicy::Always, {
+        let (stack, readiness, feeders, app_event_tx) =
+            (stack.clone(), readiness.clone(), feeders.clone(), app_event_tx.clone());
+        let (cameras, command_sequencer) = (config.cameras.clone(), command_sequencer.clone());
+        move || {
+            readiness::readiness_monitor(
+                stack.clone(),
+                readiness.clone(),
+                cameras.clone(),
+                command_sequencer.clone(),
+                feeders.clone(),
+                app_event_tx.subscribe(),
+            )
+        }
+    })?;
+
+    let job_control = Arc::new(Mutex::new(JobControl::new(job, checkpoint)));
+
+    let nozzle_monitor_handle = supervisor.spawn("io-board/nozzle-monitor", RestartPolicy::Always, {
+        let (stack, job_control, app_event_tx) = (stack.clone(), job_control.clone(), app_event_tx.clone());
+        let (config, command_sequencer) = (config.nozzles.clone(), command_sequencer.clone());
+        move || {
+            nozzles::nozzle_monitor(
+                stack.clone(),
+                config.clone(),
+                job_control.clone(),
+                command_sequencer.clone(),
+                app_event_tx.subscribe(),
+            )
+        }
+    })?;
+
+    let (energy, power_meter_handle) = match &config.power_meter {
+        Some(power_meter) => {
+            let energy = EnergyCounter::default();
+            let handle = supervisor.spawn("operator/power-meter", RestartPolicy::Always, {
+                let (stack, command_sequencer, app_event_tx) =
+                    (stack.clone(), command_sequencer.clone(), app_event_tx.clone());
+                let (config, energy) = (power_meter.clone(), energy.clone());
+                move || {
+                    power::power_meter(
+                        stack.clone(),
+                        config.clone(),
+                        command_sequencer.clone(),
+                        energy.clone(),
+                        app_event_tx.subscribe(),
+                    )
+                }
+            })?;
+            (Some(energy), Some(handle))
+        }
+        None => (None, None),
+    };
+
+    let analog_channels_handle = match config.analog_channels.is_empty() {
+        true => None,
+        false => Some(supervisor.spawn("io-board/analog-channels", RestartPolicy::Always, {
+            let (stack, command_sequencer, app_event_tx) =
+                (stack.clone(), command_sequencer.clone(), app_event_tx.clone());
+            let definitions = config.analog_channels.clone();
+            move || {
+                analog::analog_channels(
+                    stack.clone(),
+                    definitions.clone(),
+                    command_sequencer.clone(),
+                    app_event_tx.subscribe(),
+                )
+            }
+        })?),
+    };
+
+    let test_area = Arc::new(Mutex::new(TestArea::new(config.test_area.clone())));
+    let topic_tap = Arc::new(Mutex::new(TopicTap::new(config.topic_tap.clone())));
+    let limit_overrides = Arc::new(Mutex::new(LimitOverrides::new(
+        config.maintenance.clone(),
+        config.homing.axes.clone(),
+    )));
+    let bridge_config = config.bridge.clone();
+
+    let nozzle_runout = RunoutStore::new(config.runout.corrections_path.clone())
+        .load()
+        .await?
+        .nozzles
+        .remove(&config.runout.nozzle);
+    match &nozzle_runout {
+        Some(runout) => info!(
+            "Nozzle runout correction loaded. nozzle: {}, runout: {:.4}mm, measured_at: {}",
+            runout.nozzle, runout.fit.radius_mm, runout.measured_at
+        ),
+        None => info!(
+            "Nozzle runout not measured, placements are not corrected. nozzle: {}",
+            config.runout.nozzle
+        ),
+    }
+
+    #[cfg(feature = "machine-vision")]
+    let frame_budget = FrameBudget::new(config.camera_memory.clone());
+    #[cfg(feature = "machine-vision")]
+    let camera_memory_monitor_handle = supervisor.spawn("camera/memory-monitor", RestartPolicy::Always, {
+        let (stack, frame_budget, app_event_tx) = (stack.clone(), frame_budget.clone(), app_event_tx.clone());
+        move || camera::budget::camera_memory_monitor(stack.clone(), frame_budget.clone(), app_event_tx.subscribe())
+    })?;
 
     let app_state = Arc::new(Mutex::new(AppState {
         config,
+        readiness,
+        job_control,
+        feeders: feeders.clone(),
+        test_area,
+        topic_tap,
+        limit_overrides,
+        nozzle_runout,
+        command_sequencer,
+        head_motion,
+        parking_tx: parking_tx.clone(),
         event_tx: app_event_tx.clone(),
+        force_tx,
+        energy,
+        storage,
         #[cfg(feature = "machine-vision")]
         camera_clients: Arc::new(Mutex::new(HashMap::new())),
+        #[cfg(feature = "machine-vision")]
+        camera_captures: CameraCaptures::new(position_history, frame_budget, supervisor.clone()),
+        #[cfg(feature = "machine-vision")]
+        vision_queue,
+        #[cfg(feature = "machine-vision")]
+        capture_store,
+        #[cfg(feature = "machine-vision")]
+        template_store,
     }));
 
-    // TODO give the app_state to these tasks
-    let ioboard_command_sender_handle = tokio::task::Builder::new()
-        .name("io-board/command-sender")
-        .spawn(ioboard::io_board_command_sender(
+    #[cfg(feature = "machine-vision")]
+    // owns the receiver of the requests, it can't be restarted
+    let vision_arbiter_handle = supervisor.spawn_once(
+        "vision/arbiter",
+        vision::vision_arbiter(
             stack.clone(),
+            app_state.clone(),
+            vision_queue_rx,
             app_event_tx.subscribe(),
-        ))?;
+        ),
+    )?;
+
+    // TODO give the app_state to these tasks
+    let ioboard_command_sender_handle = supervisor.spawn("io-board/command-sender", RestartPolicy::Always, {
+        let (command_batcher, app_event_tx) = (command_batcher.clone(), app_event_tx.clone());
+        move || ioboard::io_board_command_sender(command_batcher.clone(), app_event_tx.subscribe())
+    })?;
+    // the batch sender stops once the tasks that send commands have stopped
+    drop(command_batcher);
+
+    let ioboard_event_listener_handle = supervisor.spawn("io-board/event-listener", RestartPolicy::Always, {
+        let (stack, feeders, readiness, app_event_tx) =
+            (stack.clone(), feeders.clone(), readiness.clone(), app_event_tx.clone());
+        move || {
+            ioboard::io_board_event_listener(
+                stack.clone(),
+                parking_tx.clone(),
+                feeders.clone(),
+                readiness.clone(),
+                app_event_tx.subscribe(),
+            )
+        }
+    })?;
 
-    let operator_listener_handle = tokio::task::Builder::new()
-        .name("operator/command-listener")
-        .spawn(operator::operator_listener(stack.clone(), app_state))?;
+    let bridge_listener_handle = supervisor.spawn("operator/bridge", RestartPolicy::Always, {
+        let (stack, app_event_tx) = (stack.clone(), app_event_tx.clone());
+        let config = bridge_config;
+        move || bridge::bridge_listener(stack.clone(), config.clone(), app_event_tx.subscribe())
+    })?;
 
-    // Wait for Ctrl+C
-    let _ = signal::ctrl_c().await;
+    let operator_listener_handle = supervisor.spawn("operator/command-listener", RestartPolicy::Always, {
+        let (stack, app_state) = (stack.clone(), app_state.clone());
+        move || operator::operator_listener(stack.clone(), app_state.clone())
+    })?;
+
+    #[cfg(feature = "machine-vision")]
+    let capture_listener_handle = supervisor.spawn("operator/capture-listener", RestartPolicy::Always, {
+        let (stack, app_state) = (stack.clone(), app_state.clone());
+        move || operator::capture_listener(stack.clone(), app_state.clone())
+    })?;
+
+    for segment in network_segments
+        .iter()
+        .filter(|segment| segment.definition.operator_commands)
+    {
+        segment_handles.push(supervisor.spawn(
+            &format!("operator/segment-command-listener/{}", segment.definition.name),
+            RestartPolicy::Always,
+            {
+                let (segment_stack, app_state) = (segment.stack.clone(), app_state.clone());
+                move || operator::operator_listener(segment_stack.clone(), app_state.clone())
+            },
+        )?);
+        #[cfg(feature = "machine-vision")]
+        segment_handles.push(supervisor.spawn(
+            &format!("operator/segment-capture-listener/{}", segment.definition.name),
+            RestartPolicy::Always,
+            {
+                let (segment_stack, app_state) = (segment.stack.clone(), app_state.clone());
+                move || operator::capture_listener(segment_stack.clone(), app_state.clone())
+            },
+        )?);
+    }
+
+    // the batch sender stops once the tasks holding the app state, and its command batcher, have stopped
+    drop(app_state);
+
+    info!("Server started");
+    on_ready();
+
+    // Wait for Ctrl+C, SIGTERM, or a request to stop the service
+    shutdown.await;
 
     app_event_tx
         .send(AppEvent::Shutdown)
@@ -156,9 +816,53 @@ async fn main() -> anyhow::Result<()> {
     info!("Shut down requested, exiting");
 
     let _ = ioboard_command_sender_handle.await;
+    let _ = ioboard_event_listener_handle.await;
     let _ = operator_listener_handle.await;
+    #[cfg(feature = "machine-vision")]
+    let _ = capture_listener_handle.await;
+    let _ = bridge_listener_handle.await;
+    for handle in segment_handles {
+        let _ = handle.await;
+    }
+    #[cfg(feature = "machine-vision")]
+    let _ = vision_arbiter_handle.await;
+    #[cfg(feature = "machine-vision")]
+    let _ = camera_memory_monitor_handle.await;
+    #[cfg(feature = "machine-vision")]
+    let _ = capture_retention_handle.await;
     let _ = basic_services_handle.await;
-    let _ = yeet_listener_handle.await;
+    let _ = load_cell_listener_handle.await;
+    let _ = dead_letter_writer_handle.await;
+    let _ = latency_monitor_handle.await;
+    let _ = router_inspector_handle.await;
+    let _ = position_listener_handle.await;
+    let _ = force_listener_handle.await;
+    let _ = safety_listener_handle.await;
+    let _ = estop_listener_handle.await;
+    let _ = readiness_monitor_handle.await;
+    let _ = feeder_monitor_handle.await;
+    let _ = nozzle_monitor_handle.await;
+    // parks the head on shutdown
+    if let Some(handle) = parking_runner_handle {
+        let _ = handle.await;
+    }
+    if let Some(handle) = power_meter_handle {
+        let _ = handle.await;
+    }
+    if let Some(handle) = analog_channels_handle {
+        let _ = handle.await;
+    }
+    for handle in setpoint_streamer_handles {
+        let _ = handle.await;
+    }
+    if let Some(handle) = move_commander_handle {
+        let _ = handle.await;
+    }
+    if let Some(handle) = planning_configurator_handle {
+        let _ = handle.await;
+    }
+    // sends the commands of the tasks above that are still pending
+    let _ = batch_sender_handle.await;
 
     info!("Shutdown complete");
     Ok(())
@@ -166,9 +870,36 @@ async fn main() -> anyhow::Result<()> {
 
 pub struct AppState {
     config: Config,
+    readiness: Arc<Mutex<Readiness>>,
+    job_control: Arc<Mutex<JobControl>>,
+    feeders: Arc<Mutex<Feeders>>,
+    test_area: Arc<Mutex<TestArea>>,
+    topic_tap: Arc<Mutex<TopicTap>>,
+    limit_overrides: Arc<Mutex<LimitOverrides>>,
+    /// measured by `--measure-runout`, `None` if the nozzle has not been measured
+    nozzle_runout: Option<NozzleRunout>,
+    command_sequencer: Arc<CommandSequencer>,
+    /// `None` unless an io board follows the moves planned by the server, and no routine or repeated trajectory moves the
+    /// axes, the parts can then only be placed in a dry run
+    head_motion: Option<HeadMotion>,
+    parking_tx: mpsc::Sender<ParkTrigger>,
     event_tx: broadcast::Sender<AppEvent>,
+    /// the force traces of the touchdowns, see `forces::force_listener`
+    force_tx: broadcast::Sender<ForceTrace>,
+    /// `None` without a power meter, see `power::power_meter`
+    energy: Option<EnergyCounter>,
+    /// where the reports and captures are stored, see `StorageConfig`
+    storage: Arc<StorageImpl>,
+    #[cfg(feature = "machine-vision")]
+    camera_clients: Arc<Mutex<HashMap<CameraId, CameraClient>>>,
+    #[cfg(feature = "machine-vision")]
+    camera_captures: CameraCaptures,
+    #[cfg(feature = "machine-vision")]
+    vision_queue: VisionQueue,
     #[cfg(feature = "machine-vision")]
-    camera_clients: Arc<Mutex<HashMap<CameraIdentifier, CameraHandle>>>,
+    capture_store: Arc<CaptureStore>,
+    #[cfg(feature = "machine-vision")]
+    template_store: Arc<TemplateStore>,
 }
 
 async fn app_shutdown_handler(mut receiver: Receiver<AppEvent>) {
@@ -187,21 +918,3 @@ async fn app_shutdown_handler(mut receiver: Receiver<AppEvent>) {
 pub enum AppEvent {
     Shutdown,
 }
-
-fn init_logging(verbosity_level: u8) {
-    let mut builder = env_logger::Builder::from_default_env();
-
-    // Only override the default filter if RUST_LOG is NOT set
-    if std::env::var_os("RUST_LOG").is_none() {
-        let level = match verbosity_level {
-            0 => log::LevelFilter::Warn,
-            1 => log::LevelFilter::Info,
-            2 => log::LevelFilter::Debug,
-            _ => log::LevelFilter::Trace,
-        };
-
-        builder.filter_level(level);
-    }
-
-    builder.init();
-}
@@ -0,0 +1,320 @@
+//! Moves for io boards configured with [`MotionPlanning::OnBoard`](crate::config::MotionPlanning::OnBoard).
+//!
+//! The io board plans the trajectory of each move itself, the server queues the moves, see [`MotionCommand`].
+//!
+//! The io boards are also switched to the planning of the io board definitions here, for both planning modes, see
+//! [`planning_configurator`].
+
+use std::sync::Arc;
+
+use ergot::Address;
+use ergot::FrameKind;
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot_util::{ClientError, ClientWrapper};
+use ioboard_shared::motion::{
+    MotionCommand, MotionCommandError, MotionCommandRequest, MotionCommandResponse, MotionPlanning, QueuedMove,
+};
+use log::{debug, error, info, trace, warn};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::watch;
+use tokio::time::{self, Duration};
+
+use super::{REPEAT_PAUSE, STEPS_PER_DEGREE};
+use crate::AppEvent;
+use crate::config::{self, TrajectoryMove};
+use crate::ioboard::batching::CommandBatcher;
+use crate::ioboard::{CommandSequencer, MotionCommandEndpoint};
+use crate::networking::dead_letter;
+use crate::safety::SafetyState;
+
+const COMMAND_DISCOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+/// An abort is answered once the axis has stopped.
+const COMMAND_REQUEST_TIMEOUT: Duration = Duration::from_millis(1000);
+const COMMAND_REQUEST_ATTEMPTS: u32 = 3;
+
+/// How often the queues are checked while waiting for the moves to finish.
+const QUEUE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The planning is re-sent at this interval, so that an io board that restarted with the planning of its firmware is
+/// switched again.
+const PLANNING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Sends motion commands to the io boards that plan their own trajectories.
+///
+/// Commands are idempotent, a request is retried with the same key, so a move is never queued twice.
+#[derive(Clone)]
+pub struct MotionCommander {
+    stack: RouterStack,
+    sequencer: Arc<CommandSequencer>,
+}
+
+impl MotionCommander {
+    pub fn new(stack: RouterStack, sequencer: Arc<CommandSequencer>) -> Self {
+        Self {
+            stack,
+            sequencer,
+        }
+    }
+
+    /// The io boards that take motion commands, including the boards that follow setpoints, which refuse them.
+    pub async fn discover(&self) -> Vec<Address> {
+        let query = SocketQuery {
+            key: MotionCommandEndpoint::REQ_KEY.to_bytes(),
+            nash_req: NameRequirement::Any,
+            frame_kind: FrameKind::ENDPOINT_REQ,
+            broadcast: false,
+        };
+        self.stack
+            .discovery()
+            .discover_sockets(4, COMMAND_DISCOVERY_TIMEOUT, &query)
+            .await
+            .into_iter()
+            .map(|result| result.address)
+            .collect()
+    }
+
+    /// Queue a move after the moves already queued.
+    pub async fn queue_move(
+        &self,
+        address: Address,
+        axis: u8,
+        queued_move: QueuedMove,
+    ) -> Result<MotionCommandResponse, ClientError> {
+        self.request(address, axis, MotionCommand::Move(queued_move))
+            .await
+    }
+
+    pub async fn queue_depth(&self, address: Address, axis: u8) -> Result<MotionCommandResponse, ClientError> {
+        self.request(address, axis, MotionCommand::QueueDepth)
+            .await
+    }
+
+    /// Discard the queued moves, the move in progress is finished.
+    pub async fn flush(&self, address: Address, axis: u8) -> Result<MotionCommandResponse, ClientError> {
+        self.request(address, axis, MotionCommand::Flush)
+            .await
+    }
+
+    /// Discard the queued moves and brake the move in progress to a stop, the response has the stop position.
+    pub async fn abort(&self, address: Address, axis: u8) -> Result<MotionCommandResponse, ClientError> {
+        self.request(address, axis, MotionCommand::Abort)
+            .await
+    }
+
+    /// Switch where the trajectories of the axis are planned, refused while the axis is moving.
+    pub async fn set_planning(
+        &self,
+        address: Address,
+        axis: u8,
+        planning: MotionPlanning,
+    ) -> Result<MotionCommandResponse, ClientError> {
+        self.request(address, axis, MotionCommand::SetPlanning(planning))
+            .await
+    }
+
+    async fn request(
+        &self,
+        address: Address,
+        axis: u8,
+        command: MotionCommand,
+    ) -> Result<MotionCommandResponse, ClientError> {
+        let client = self
+            .stack
+            .endpoints()
+            .client::<MotionCommandEndpoint>(address, None);
+        let client = ClientWrapper::new(COMMAND_REQUEST_TIMEOUT, client);
+        let request = self
+            .sequencer
+            .sequenced(MotionCommandRequest {
+                axis,
+                command,
+            });
+        client
+            .request_with_retry(&request, COMMAND_REQUEST_ATTEMPTS)
+            .await
+            .inspect_err(|e| {
+                warn!(
+                    "Unable to send motion command. axis: {}, address: {:?}, error: {:?}",
+                    axis, address, e
+                );
+                dead_letter::request_failed::<MotionCommandEndpoint>(address, COMMAND_REQUEST_ATTEMPTS, e);
+            })
+    }
+}
+
+/// The planning of the io board, the setpoint rate is only used by the server.
+pub fn board_planning(planning: config::MotionPlanning) -> MotionPlanning {
+    match planning {
+        config::MotionPlanning::OnBoard => MotionPlanning::OnBoard,
+        config::MotionPlanning::Server {
+            ..
+        } => MotionPlanning::Server,
+    }
+}
+
+/// The planning of the io board definitions, `None` without io boards.
+///
+/// FUTURE the io boards can't be told apart yet, so every io board must have the same planning.
+pub fn machine_planning(io_boards: &[config::IoBoardDefinition]) -> anyhow::Result<Option<MotionPlanning>> {
+    let mut plannings = io_boards
+        .iter()
+        .map(|io_board| board_planning(io_board.planning));
+    let Some(planning) = plannings.next() else {
+        return Ok(None);
+    };
+    if plannings.any(|other| other != planning) {
+        anyhow::bail!("Every io board must have the same motion planning")
+    }
+    Ok(Some(planning))
+}
+
+/// Switches the axis of every io board to the planning of the io board definitions, see [`MotionCommand::SetPlanning`].
+///
+/// An io board starts with the planning of its firmware, the planning is re-sent periodically, so that an io board that
+/// restarted, or that refused the switch while its axis was moving, is switched too.
+pub async fn planning_configurator(
+    commander: MotionCommander,
+    axis: u8,
+    planning: MotionPlanning,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    info!(
+        "Planning configurator started, axis: {}, planning: {:?}",
+        axis, planning
+    );
+
+    let mut interval = time::interval(PLANNING_INTERVAL);
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => break,
+            _ = interval.tick() => {}
+        }
+
+        let addresses = select! {
+            _ = &mut app_shutdown_handler => break,
+            addresses = commander.discover() => addresses,
+        };
+        for address in addresses {
+            match commander
+                .set_planning(address, axis, planning)
+                .await
+            {
+                Ok(Ok(_)) => trace!("Planning set. axis: {}, address: {:?}", axis, address),
+                Ok(Err(MotionCommandError::UnknownAxis)) => {
+                    trace!("Axis not on io board. axis: {}, address: {:?}", axis, address);
+                }
+                Ok(Err(e)) => warn!(
+                    "Planning refused. axis: {}, address: {:?}, error: {:?}",
+                    axis, address, e
+                ),
+                // already logged
+                Err(_) => {}
+            }
+        }
+    }
+    info!("Planning configurator shutdown, axis: {}", axis);
+}
+
+/// Queues the configured trajectory on the io boards that plan their own trajectories, repeatedly, see
+/// [`MotionConfig`](crate::config::MotionConfig).
+///
+/// Moves are not queued while the safety state does not allow them, the io board applies the speed reduction itself.
+pub async fn move_commander(
+    commander: MotionCommander,
+    batcher: CommandBatcher,
+    axis: u8,
+    trajectory: Vec<TrajectoryMove>,
+    mut safety_rx: watch::Receiver<SafetyState>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    info!("Move commander started, axis: {}", axis);
+
+    'outer: loop {
+        // discovered each time, so that an io board that restarted is found again
+        let addresses = select! {
+            _ = &mut app_shutdown_handler => break,
+            addresses = commander.discover() => addresses,
+        };
+        if addresses.is_empty() {
+            debug!("No io board takes motion commands, axis: {}", axis);
+        }
+
+        for trajectory_move in &trajectory {
+            if !safety_rx.borrow().allows_new_moves() {
+                info!("Waiting for the safety state to allow motion, axis: {}", axis);
+            }
+            select! {
+                _ = &mut app_shutdown_handler => {
+                    break 'outer
+                }
+                result = safety_rx.wait_for(SafetyState::allows_new_moves) => if result.is_err() {
+                    error!("Safety listener stopped, axis: {}", axis);
+                    break 'outer;
+                }
+            }
+
+            let queued_move = QueuedMove {
+                move_id: batcher.next_move_id(),
+                target: trajectory_move.target * STEPS_PER_DEGREE,
+                max_velocity: trajectory_move.max_velocity * STEPS_PER_DEGREE,
+                max_acceleration: trajectory_move.max_acceleration * STEPS_PER_DEGREE,
+                max_jerk: trajectory_move.max_jerk * STEPS_PER_DEGREE,
+            };
+            for address in &addresses {
+                match commander
+                    .queue_move(*address, axis, queued_move)
+                    .await
+                {
+                    Ok(Ok(status)) => debug!(
+                        "Move queued. axis: {}, move: {}, depth: {}",
+                        axis, queued_move.move_id, status.depth
+                    ),
+                    Ok(Err(MotionCommandError::UnknownAxis | MotionCommandError::ServerPlanned)) => {
+                        trace!("Io board doesn't plan the axis. axis: {}, address: {:?}", axis, address);
+                    }
+                    Ok(Err(e)) => warn!("Move refused. axis: {}, address: {:?}, error: {:?}", axis, address, e),
+                    // already logged
+                    Err(_) => {}
+                }
+            }
+        }
+
+        // the trajectory is only repeated once the moves have finished, so that the queues don't overflow
+        loop {
+            select! {
+                _ = &mut app_shutdown_handler => {
+                    break 'outer
+                }
+                _ = time::sleep(QUEUE_POLL_INTERVAL) => {}
+            }
+
+            let mut moving = false;
+            for address in &addresses {
+                if let Ok(Ok(status)) = commander
+                    .queue_depth(*address, axis)
+                    .await
+                {
+                    moving |= status.depth > 0 || status.current_move.is_some();
+                }
+            }
+            if !moving {
+                break;
+            }
+        }
+
+        select! {
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            _ = time::sleep(REPEAT_PAUSE) => {},
+        }
+    }
+    info!("Move commander shutdown, axis: {}", axis);
+}
@@ -0,0 +1,342 @@
+//! Server-side motion planning, for io boards configured with
+//! [`MotionPlanning::Server`](crate::config::MotionPlanning::Server).
+//!
+//! The trajectory is planned here and sampled at the setpoint rate, the io board interpolates between the setpoints.
+//! The setpoints already streamed are aborted by flushing the queue of the io board, see [`QueueFlusher`].
+//!
+//! Io boards that plan their own trajectories are sent moves instead, see [`commands`], the io boards are switched to
+//! the planning of their definition at runtime, see [`commands::planning_configurator`].
+
+use std::pin::pin;
+use std::sync::Arc;
+
+use chrono::Utc;
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, topic};
+use ergot_util::{ClientError, ClientWrapper};
+use ioboard_shared::batch::BatchedCommand;
+use ioboard_shared::motion::{
+    FlushQueueError, FlushQueueRequest, FlushQueueResponse, MotionSetpoint, PositionReport, QueueFlushed,
+};
+use log::{debug, error, info, trace, warn};
+use rsruckig::prelude::*;
+use server_common::position::PositionHistory;
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::watch;
+use tokio::time::{self, Duration};
+
+use crate::AppEvent;
+use crate::config::TrajectoryMove;
+use crate::ioboard::batching::CommandBatcher;
+use crate::ioboard::{CommandSequencer, FlushQueueEndpoint};
+use crate::networking::dead_letter;
+use crate::safety::SafetyState;
+
+pub mod commands;
+
+#[cfg(test)]
+mod tests;
+
+topic!(SetpointTopic, MotionSetpoint, "topic/ioboard/motion/setpoint");
+topic!(PositionTopic, PositionReport, "topic/ioboard/motion/position");
+
+const FLUSH_DISCOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+/// The response is sent once the axis has stopped.
+const FLUSH_REQUEST_TIMEOUT: Duration = Duration::from_millis(1000);
+const FLUSH_REQUEST_ATTEMPTS: u32 = 3;
+
+/// NEMA 17 = 200 full steps/revolution, 8 x micro-stepping
+///
+/// FUTURE should be part of the axis configuration
+pub const STEPS_PER_DEGREE: f64 = (200.0 * 8.0) / 360.0;
+
+/// The pause after each repetition of the configured trajectory.
+const REPEAT_PAUSE: Duration = Duration::from_secs(5);
+
+/// A single-axis move, all values are in steps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisMove {
+    pub target: f64,
+    pub max_jerk: f64,
+    pub max_acceleration: f64,
+    pub max_velocity: f64,
+}
+
+/// Plan a move from rest at `start` to rest at the target, returns the positions sampled every `interval`.
+///
+/// The last position is always the target.
+pub fn plan_setpoints(start: f64, axis_move: &AxisMove, interval: Duration) -> Result<Vec<f64>, RuckigError> {
+    let mut ruckig = Ruckig::<1, ThrowErrorHandler>::new(None, interval.as_secs_f64());
+
+    let mut input = InputParameter::<1>::new(None);
+    let mut output = OutputParameter::<1>::new(None);
+
+    input.current_position = daov_stack![start];
+    input.target_position = daov_stack![axis_move.target];
+    input.target_velocity = daov_stack![0.0];
+    input.target_acceleration = daov_stack![0.0];
+
+    input.max_jerk = daov_stack![axis_move.max_jerk];
+    input.max_acceleration = daov_stack![axis_move.max_acceleration];
+    input.max_velocity = daov_stack![axis_move.max_velocity];
+
+    let mut setpoints = Vec::new();
+    loop {
+        let result = ruckig.update(&input, &mut output)?;
+        output.pass_to_input(&mut input);
+        setpoints.push(output.new_position[0]);
+
+        if matches!(result, RuckigResult::Finished) {
+            break;
+        }
+    }
+
+    Ok(setpoints)
+}
+
+/// Plans the configured trajectory and streams the setpoints, repeatedly, for an io board that does not plan its own
+/// motion, see [`MotionConfig`](crate::config::MotionConfig).
+///
+/// Moves are not started while the safety state does not allow them, and are planned with a reduced max velocity and
+/// acceleration while the speed is reduced, the same as the on-board planner.
+pub async fn setpoint_streamer(
+    batcher: CommandBatcher,
+    axis: u8,
+    rate_hz: u32,
+    trajectory: Vec<TrajectoryMove>,
+    mut safety_rx: watch::Receiver<SafetyState>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let interval = Duration::from_micros(1_000_000 / rate_hz.max(1) as u64);
+
+    let steps_per_unit = STEPS_PER_DEGREE;
+
+    info!(
+        "Setpoint streamer started, axis: {}, rate: {}Hz, interval: {:?}",
+        axis, rate_hz, interval
+    );
+
+    let mut position = 0.0;
+    'outer: loop {
+        for trajectory_move in &trajectory {
+            if !safety_rx.borrow().allows_new_moves() {
+                info!("Waiting for the safety state to allow motion, axis: {}", axis);
+            }
+            let speed_factor = select! {
+                _ = &mut app_shutdown_handler => {
+                    break 'outer
+                }
+                result = safety_rx.wait_for(SafetyState::allows_new_moves) => match result {
+                    Ok(state) => state.speed_factor(),
+                    Err(_) => {
+                        error!("Safety listener stopped, axis: {}", axis);
+                        break 'outer;
+                    }
+                }
+            };
+
+            let axis_move = AxisMove {
+                target: trajectory_move.target * steps_per_unit,
+                max_jerk: trajectory_move.max_jerk * steps_per_unit,
+                max_acceleration: trajectory_move.max_acceleration * steps_per_unit * speed_factor,
+                max_velocity: trajectory_move.max_velocity * steps_per_unit * speed_factor,
+            };
+
+            let setpoints = match plan_setpoints(position, &axis_move, interval) {
+                Ok(setpoints) => setpoints,
+                Err(e) => {
+                    error!(
+                        "Unable to plan move, axis: {}, move: {:?}, error: {:?}",
+                        axis, axis_move, e
+                    );
+                    break 'outer;
+                }
+            };
+            debug!("Planned move, axis: {}, setpoints: {}", axis, setpoints.len());
+
+            let move_id = batcher.next_move_id();
+            let mut ticker = time::interval(interval);
+            for (sequence, setpoint) in setpoints.iter().enumerate() {
+                select! {
+                    _ = &mut app_shutdown_handler => {
+                        break 'outer
+                    }
+                    _ = ticker.tick() => {}
+                }
+
+                send_setpoint(&batcher, &MotionSetpoint {
+                    axis,
+                    move_id,
+                    sequence: sequence as u32,
+                    position: *setpoint,
+                    interval_us: interval.as_micros() as u32,
+                });
+            }
+            position = axis_move.target;
+        }
+
+        select! {
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            _ = time::sleep(REPEAT_PAUSE) => {},
+        }
+    }
+    info!("setpoint streamer shutdown, axis: {}", axis);
+}
+
+/// Failures are only logged, the io board interpolates over a missing setpoint.
+///
+/// Setpoints of other axes sent at the same time are sent in the same frame, see [`CommandBatcher`].
+pub fn send_setpoint(batcher: &CommandBatcher, setpoint: &MotionSetpoint) {
+    batcher.send(BatchedCommand::Setpoint(*setpoint));
+}
+
+/// Stream the setpoints of a planned move, returns once the last setpoint has been queued.
+pub async fn stream_setpoints(batcher: &CommandBatcher, axis: u8, setpoints: &[f64], interval: Duration) {
+    let move_id = batcher.next_move_id();
+    let mut ticker = time::interval(interval);
+    for (sequence, setpoint) in setpoints.iter().enumerate() {
+        ticker.tick().await;
+        send_setpoint(batcher, &MotionSetpoint {
+            axis,
+            move_id,
+            sequence: sequence as u32,
+            position: *setpoint,
+            interval_us: interval.as_micros() as u32,
+        });
+    }
+}
+
+/// Flushes the setpoint queues of the axes, e.g. when a job is paused or aborted, so that the axes brake to a stop
+/// instead of finishing the moves already streamed, see [`FlushQueueRequest`].
+///
+/// The io boards also publish the stop positions as position reports, so the next move is planned from where the axis
+/// stopped, see [`position_listener`].  Flushes are idempotent, a request is retried with the same key.
+#[derive(Clone)]
+pub struct QueueFlusher {
+    stack: RouterStack,
+    sequencer: Arc<CommandSequencer>,
+    axes: Vec<u8>,
+    /// in steps/s²
+    max_deceleration: f64,
+}
+
+impl QueueFlusher {
+    pub fn new(stack: RouterStack, sequencer: Arc<CommandSequencer>, axes: Vec<u8>, max_deceleration: f64) -> Self {
+        Self {
+            stack,
+            sequencer,
+            axes,
+            max_deceleration,
+        }
+    }
+
+    /// Flushes the axes on every io board that follows setpoints, returns the axes that were stopped.
+    ///
+    /// Each io board is asked to flush each axis, the boards that don't have the axis refuse.
+    pub async fn flush_all(&self) -> Vec<QueueFlushed> {
+        let query = SocketQuery {
+            key: FlushQueueEndpoint::REQ_KEY.to_bytes(),
+            nash_req: NameRequirement::Any,
+            frame_kind: FrameKind::ENDPOINT_REQ,
+            broadcast: false,
+        };
+        let addresses = self
+            .stack
+            .discovery()
+            .discover_sockets(4, FLUSH_DISCOVERY_TIMEOUT, &query)
+            .await
+            .into_iter()
+            .map(|result| result.address)
+            .collect::<Vec<_>>();
+        if addresses.is_empty() {
+            debug!("No io board follows setpoints, nothing to flush");
+        }
+
+        let mut stopped = vec![];
+        for address in addresses {
+            for axis in &self.axes {
+                match self.flush(address, *axis).await {
+                    Ok(Ok(flushed)) => {
+                        info!(
+                            "Setpoint queue flushed. axis: {}, position: {}, discarded: {}, move: {:?}, sequence: {:?}",
+                            flushed.axis,
+                            flushed.position,
+                            flushed.discarded,
+                            flushed.interrupted_move,
+                            flushed.last_sequence
+                        );
+                        stopped.push(flushed);
+                    }
+                    Ok(Err(FlushQueueError::UnknownAxis)) => {
+                        trace!("Axis not on io board. axis: {}, address: {:?}", axis, address);
+                    }
+                    Ok(Err(e)) => warn!("Setpoint queue flush failed. axis: {}, error: {:?}", axis, e),
+                    Err(e) => {
+                        warn!(
+                            "Unable to flush setpoint queue. axis: {}, address: {:?}, error: {:?}",
+                            axis, address, e
+                        );
+                        dead_letter::request_failed::<FlushQueueEndpoint>(address, FLUSH_REQUEST_ATTEMPTS, &e);
+                    }
+                }
+            }
+        }
+        stopped
+    }
+
+    async fn flush(&self, address: Address, axis: u8) -> Result<FlushQueueResponse, ClientError> {
+        let client = self
+            .stack
+            .endpoints()
+            .client::<FlushQueueEndpoint>(address, None);
+        let client = ClientWrapper::new(FLUSH_REQUEST_TIMEOUT, client);
+        let request = self
+            .sequencer
+            .sequenced(FlushQueueRequest {
+                axis,
+                max_deceleration: self.max_deceleration,
+            });
+        client
+            .request_with_retry(&request, FLUSH_REQUEST_ATTEMPTS)
+            .await
+    }
+}
+
+/// Records the position telemetry from the io boards.
+///
+/// Reports are timestamped on arrival, so the history lags the actual position by the network latency, which is
+/// small compared to the camera frame interval.
+pub async fn position_listener(
+    stack: RouterStack,
+    position_history: PositionHistory,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<PositionTopic>(64, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                let PositionReport { axis, position } = msg.t;
+                trace!("Position report, axis: {}, position: {}", axis, position);
+                position_history.record(axis, Utc::now(), position as f64);
+            }
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+        }
+    }
+    info!("position listener shutdown");
+}
@@ -0,0 +1,73 @@
+use ioboard_shared::motion::MotionPlanning;
+use tokio::time::Duration;
+
+use super::commands::machine_planning;
+use super::{AxisMove, plan_setpoints};
+use crate::config::IoBoardDefinition;
+
+#[test]
+pub fn setpoints_end_at_target() {
+    // given
+    let axis_move = AxisMove {
+        target: 2400.0,
+        max_jerk: 22_000.0,
+        max_acceleration: 44_000.0,
+        max_velocity: 44_000.0,
+    };
+
+    // when
+    let setpoints = plan_setpoints(0.0, &axis_move, Duration::from_millis(10)).unwrap();
+
+    // then
+    assert_eq!(*setpoints.last().unwrap(), 2400.0);
+    assert!(
+        setpoints
+            .windows(2)
+            .all(|pair| pair[1] >= pair[0])
+    );
+}
+
+#[test]
+pub fn setpoints_reversed_move() {
+    // given
+    let axis_move = AxisMove {
+        target: -100.0,
+        max_jerk: 22_000.0,
+        max_acceleration: 44_000.0,
+        max_velocity: 44_000.0,
+    };
+
+    // when
+    let setpoints = plan_setpoints(100.0, &axis_move, Duration::from_millis(10)).unwrap();
+
+    // then
+    assert_eq!(*setpoints.last().unwrap(), -100.0);
+    assert!(
+        setpoints
+            .iter()
+            .all(|position| *position <= 100.0 && *position >= -100.0)
+    );
+}
+
+fn io_board(planning: &str) -> IoBoardDefinition {
+    ron::from_str(&format!(
+        "IoBoardDefinition(connection: IpUdp(address: \"192.168.18.41\", port: 8000), planning: {})",
+        planning
+    ))
+    .unwrap()
+}
+
+#[test]
+pub fn every_io_board_must_have_the_same_planning() {
+    // expect
+    assert_eq!(machine_planning(&[]).unwrap(), None);
+    assert_eq!(
+        machine_planning(&[io_board("OnBoard"), io_board("OnBoard")]).unwrap(),
+        Some(MotionPlanning::OnBoard)
+    );
+    assert_eq!(
+        machine_planning(&[io_board("Server(rate_hz: 100)"), io_board("Server(rate_hz: 50)")]).unwrap(),
+        Some(MotionPlanning::Server)
+    );
+    assert!(machine_planning(&[io_board("OnBoard"), io_board("Server(rate_hz: 100)")]).is_err());
+}
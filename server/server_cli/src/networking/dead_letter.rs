@@ -0,0 +1,252 @@
+//! Recording the messages that could not be delivered, so that protocol mismatches and routing errors are visible
+//! instead of silent.
+//!
+//! A dead letter is recorded when a request still fails after its retries, e.g. the io board has no socket for the
+//! endpoint, or when the stack refuses to send a broadcast, e.g. there is no route, see [`request_failed`] and
+//! [`broadcast_failed`].  Each dead letter has the decoded header of the message, the path and key of the endpoint or
+//! topic, and the destination, a key that differs between the server and the io board means they were built from
+//! different versions of the shared crates.
+//!
+//! The dead letters are written to a file, one RON line each, and a summary is logged, see [`dead_letter_writer`].
+//!
+//! FUTURE frames that arrive at the router for a port without a socket are dropped by ergot, and are only visible to
+//! the sender, as a failed request.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use ergot::Address;
+use ergot::traits::{Endpoint, Topic};
+use ergot_util::ClientError;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc;
+use tokio::time;
+
+use crate::AppEvent;
+use crate::config::DeadLetterConfig;
+
+/// Dead letters are dropped, and counted, when the writer falls behind by more than this.
+const DEAD_LETTER_QUEUE_SIZE: usize = 256;
+
+/// Installed once by [`install`], dead letters recorded before, e.g. in tests, are only logged.
+static DEAD_LETTER_TX: OnceLock<mpsc::Sender<DeadLetter>> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DeadLetterKind {
+    /// a request to an endpoint that failed after every attempt
+    Request { attempts: u32 },
+    /// a message of a topic that the stack refused to send
+    Broadcast,
+}
+
+/// The decoded header of an undeliverable message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetterHeader {
+    /// the path of the endpoint or topic
+    pub path: String,
+    /// the key of the request or message, in hex
+    pub key: String,
+    /// `None` for a broadcast
+    pub destination: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub at: DateTime<Utc>,
+    pub kind: DeadLetterKind,
+    pub header: DeadLetterHeader,
+    pub error: String,
+}
+
+impl DeadLetter {
+    pub fn request<E: Endpoint>(destination: Address, attempts: u32, error: &ClientError) -> Self {
+        Self {
+            at: Utc::now(),
+            kind: DeadLetterKind::Request {
+                attempts,
+            },
+            header: DeadLetterHeader {
+                path: E::PATH.to_string(),
+                key: key_hex(&E::REQ_KEY.to_bytes()),
+                destination: Some(address_text(&destination)),
+            },
+            error: format!("{:?}", error),
+        }
+    }
+
+    pub fn broadcast<T: Topic>(error: &impl Debug) -> Self {
+        Self {
+            at: Utc::now(),
+            kind: DeadLetterKind::Broadcast,
+            header: DeadLetterHeader {
+                path: T::PATH.to_string(),
+                key: key_hex(&T::TOPIC_KEY.to_bytes()),
+                destination: None,
+            },
+            error: format!("{:?}", error),
+        }
+    }
+}
+
+pub fn key_hex(key: &[u8]) -> String {
+    key.iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// `<network>.<node>:<port>`
+pub fn address_text(address: &Address) -> String {
+    format!("{}.{}:{}", address.network_id, address.node_id, address.port_id)
+}
+
+/// Returns the receiver of the dead letters for the [`dead_letter_writer`], `None` if already installed.
+pub fn install() -> Option<mpsc::Receiver<DeadLetter>> {
+    let (dead_letter_tx, dead_letter_rx) = mpsc::channel(DEAD_LETTER_QUEUE_SIZE);
+    DEAD_LETTER_TX
+        .set(dead_letter_tx)
+        .ok()
+        .map(|_| dead_letter_rx)
+}
+
+pub fn record(dead_letter: DeadLetter) {
+    let Some(dead_letter_tx) = DEAD_LETTER_TX.get() else {
+        debug!("Dead letter. {:?}", dead_letter);
+        return;
+    };
+    if let Err(mpsc::error::TrySendError::Full(dead_letter)) = dead_letter_tx.try_send(dead_letter) {
+        warn!("Dead letter dropped, queue full. {:?}", dead_letter);
+    }
+}
+
+/// Records a request to the endpoint `E` that failed after every attempt.
+pub fn request_failed<E: Endpoint>(destination: Address, attempts: u32, error: &ClientError) {
+    record(DeadLetter::request::<E>(destination, attempts, error));
+}
+
+/// Records a message of the topic `T` that the stack refused to send.
+pub fn broadcast_failed<T: Topic>(error: &impl Debug) {
+    record(DeadLetter::broadcast::<T>(error));
+}
+
+/// A single line, so that the file can be read while it is written, and grepped.
+pub fn encode_line(dead_letter: &DeadLetter) -> Result<String, ron::Error> {
+    let mut line = ron::to_string(dead_letter)?;
+    line.push('\n');
+    Ok(line)
+}
+
+/// The dead letters since the last summary, by path.
+#[derive(Debug, Default)]
+pub struct DeadLetterCounts {
+    by_path: BTreeMap<String, u64>,
+}
+
+impl DeadLetterCounts {
+    pub fn add(&mut self, dead_letter: &DeadLetter) {
+        *self
+            .by_path
+            .entry(dead_letter.header.path.clone())
+            .or_default() += 1;
+    }
+
+    /// `None` if there were no dead letters since the last summary.
+    pub fn take_summary(&mut self) -> Option<BTreeMap<String, u64>> {
+        match self.by_path.is_empty() {
+            true => None,
+            false => Some(std::mem::take(&mut self.by_path)),
+        }
+    }
+}
+
+async fn open_file(config: &DeadLetterConfig) -> Option<BufWriter<File>> {
+    let path = config.file.as_ref()?;
+    match OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+    {
+        Ok(file) => Some(BufWriter::new(file)),
+        Err(e) => {
+            error!(
+                "Unable to open dead letter file, logging only. path: {:?}, error: {:?}",
+                path, e
+            );
+            None
+        }
+    }
+}
+
+/// Appends the dead letters to the file, if configured, and logs a summary of the dead letters at the summary
+/// interval, each dead letter is logged at debug level.
+pub async fn dead_letter_writer(
+    config: DeadLetterConfig,
+    mut dead_letter_rx: mpsc::Receiver<DeadLetter>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let mut file = open_file(&config).await;
+    let mut counts = DeadLetterCounts::default();
+
+    let mut ticker = time::interval(Duration::from_secs(config.summary_interval_s.max(1)));
+    ticker.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+
+    loop {
+        select! {
+            _ = ticker.tick() => {
+                if let Some(summary) = counts.take_summary() {
+                    warn!("Undeliverable messages. counts: {:?}, file: {:?}", summary, config.file);
+                }
+                let flushed = match &mut file {
+                    Some(file) => file.flush().await,
+                    None => Ok(()),
+                };
+                if let Err(e) = flushed {
+                    warn!("Unable to write dead letter file. error: {:?}", e);
+                }
+            }
+            dead_letter = dead_letter_rx.recv() => {
+                let Some(dead_letter) = dead_letter else {
+                    break
+                };
+                debug!("Dead letter. {:?}", dead_letter);
+                counts.add(&dead_letter);
+
+                let Some(writer) = &mut file else {
+                    continue
+                };
+                let result = match encode_line(&dead_letter) {
+                    Ok(line) => writer.write_all(line.as_bytes()).await,
+                    Err(e) => {
+                        warn!("Unable to encode dead letter. error: {:?}", e);
+                        continue
+                    }
+                };
+                if let Err(e) = result {
+                    warn!("Unable to write dead letter file, logging only. error: {:?}", e);
+                    file = None;
+                }
+            }
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+        }
+    }
+
+    if let Some(summary) = counts.take_summary() {
+        warn!("Undeliverable messages. counts: {:?}, file: {:?}", summary, config.file);
+    }
+    if let Some(file) = &mut file {
+        let _ = file.flush().await;
+    }
+    info!("dead letter writer shutdown");
+}
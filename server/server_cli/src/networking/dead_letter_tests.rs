@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use ergot::Address;
+use ergot_util::ClientError;
+
+use super::LoadCellTopic;
+use super::dead_letter::{DeadLetter, DeadLetterCounts, DeadLetterKind, address_text, encode_line, key_hex};
+use crate::ioboard::VacuumEndpoint;
+
+fn address() -> Address {
+    Address {
+        network_id: 1,
+        node_id: 2,
+        port_id: 3,
+    }
+}
+
+#[test]
+pub fn failed_request_has_the_decoded_header() {
+    // given
+    let error = ClientError::Timeout(Duration::from_millis(100));
+
+    // when
+    let dead_letter = DeadLetter::request::<VacuumEndpoint>(address(), 3, &error);
+
+    // then
+    assert_eq!(dead_letter.kind, DeadLetterKind::Request {
+        attempts: 3,
+    });
+    assert_eq!(dead_letter.header.destination, Some("1.2:3".to_string()));
+    assert_eq!(dead_letter.header.key.len(), 16);
+    assert!(dead_letter.error.contains("Timeout"));
+}
+
+#[test]
+pub fn broadcast_has_no_destination() {
+    // when
+    let dead_letter = DeadLetter::broadcast::<LoadCellTopic>(&"no route");
+
+    // then
+    assert_eq!(dead_letter.kind, DeadLetterKind::Broadcast);
+    assert_eq!(dead_letter.header.path, "topic/ioboard/load_cell");
+    assert_eq!(dead_letter.header.destination, None);
+}
+
+#[test]
+pub fn lines_round_trip() {
+    // given
+    let dead_letter = DeadLetter::broadcast::<LoadCellTopic>(&"first\nsecond");
+
+    // when
+    let line = encode_line(&dead_letter).unwrap();
+
+    // then
+    assert_eq!(line.matches('\n').count(), 1);
+    assert_eq!(ron::from_str::<DeadLetter>(line.trim()).unwrap(), dead_letter);
+}
+
+#[test]
+pub fn summary_is_counted_by_path_and_reset() {
+    // given
+    let mut counts = DeadLetterCounts::default();
+    counts.add(&DeadLetter::broadcast::<LoadCellTopic>(&"no route"));
+    counts.add(&DeadLetter::broadcast::<LoadCellTopic>(&"no route"));
+
+    // when
+    let summary = counts.take_summary();
+
+    // then
+    assert_eq!(summary.unwrap()["topic/ioboard/load_cell"], 2);
+    assert_eq!(counts.take_summary(), None);
+}
+
+#[test]
+pub fn header_formatting() {
+    // expect
+    assert_eq!(key_hex(&[0x01, 0xab, 0x00]), "01ab00");
+    assert_eq!(address_text(&address()), "1.2:3");
+}
@@ -0,0 +1,203 @@
+//! Inspecting the router of the server, the nodes reachable through each interface and their round-trip times, for
+//! troubleshooting a node that doesn't respond, e.g. an io board, see [`router_inspector`].
+//!
+//! Every ergot node answers pings, so the nodes are found by discovering the ping endpoint, and each node is pinged
+//! once per interval.  A node that is no longer discovered is still reported, with its lost pings, until the server
+//! restarts.
+//!
+//! FUTURE the throughput and error counters of the interfaces themselves, ergot doesn't expose the counters of the
+//! interfaces of the router yet.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{ErgotPingEndpoint, NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, topic};
+use ergot_util::ClientWrapper;
+use log::{debug, info};
+use operator_shared::diagnostics::{RouterInterfaceReport, RouterNodeReport, RouterReport};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::time::{self, Instant};
+
+use crate::AppEvent;
+use crate::config::RouterInspectionConfig;
+
+topic!(RouterReportTopic, RouterReport, "topic/diagnostics/router");
+
+const DISCOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The discovery stops once this many nodes have answered.
+const DISCOVERY_MAX: usize = 32;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeStats {
+    /// `None` if the last ping was lost
+    rtt: Option<Duration>,
+    max_rtt: Duration,
+    pings: u64,
+    lost: u64,
+    lost_in_a_row: u32,
+    /// since the last report
+    interval_answered: u32,
+    interval_lost: u32,
+}
+
+/// The statistics of the nodes, by network and node.
+#[derive(Debug, Default)]
+pub struct RouterStats {
+    nodes: BTreeMap<(u16, u8), NodeStats>,
+}
+
+impl RouterStats {
+    /// Returns every known node, `(network_id, node_id)`, the nodes discovered earlier are pinged too, so that a node
+    /// that stopped responding is reported as such.
+    pub fn discovered(&mut self, addresses: &[Address]) -> Vec<(u16, u8)> {
+        for address in addresses {
+            self.nodes
+                .entry((address.network_id, address.node_id))
+                .or_default();
+        }
+        self.nodes.keys().copied().collect()
+    }
+
+    /// `rtt` is `None` for a lost ping.
+    pub fn record_ping(&mut self, node: (u16, u8), rtt: Option<Duration>) {
+        let stats = self.nodes.entry(node).or_default();
+        stats.pings += 1;
+        stats.rtt = rtt;
+        match rtt {
+            Some(rtt) => {
+                stats.max_rtt = stats.max_rtt.max(rtt);
+                stats.lost_in_a_row = 0;
+                stats.interval_answered += 1;
+            }
+            None => {
+                stats.lost += 1;
+                stats.lost_in_a_row += 1;
+                stats.interval_lost += 1;
+            }
+        }
+    }
+
+    /// The interfaces are ordered by network, and the nodes of each interface by node, the interval counts are reset.
+    pub fn take_report(&mut self) -> RouterReport {
+        let mut interfaces: Vec<RouterInterfaceReport> = vec![];
+        for ((network_id, node_id), stats) in self.nodes.iter_mut() {
+            if interfaces
+                .last()
+                .is_none_or(|interface| interface.network_id != *network_id)
+            {
+                interfaces.push(RouterInterfaceReport {
+                    network_id: *network_id,
+                    nodes: vec![],
+                    answered: 0,
+                    lost: 0,
+                });
+            }
+            let interface = interfaces.last_mut().unwrap();
+
+            interface.answered += std::mem::take(&mut stats.interval_answered);
+            interface.lost += std::mem::take(&mut stats.interval_lost);
+            interface.nodes.push(RouterNodeReport {
+                node_id: *node_id,
+                rtt_us: stats.rtt.map(as_micros_u32),
+                max_rtt_us: as_micros_u32(stats.max_rtt),
+                pings: stats.pings,
+                lost: stats.lost,
+                lost_in_a_row: stats.lost_in_a_row,
+            });
+        }
+
+        RouterReport {
+            interfaces,
+        }
+    }
+}
+
+fn as_micros_u32(duration: Duration) -> u32 {
+    duration
+        .as_micros()
+        .min(u32::MAX as u128) as u32
+}
+
+/// Discovers and pings the nodes reachable through the router at the configured interval, and publishes a
+/// [`RouterReport`] for the operator UI after each round.
+pub async fn router_inspector(stack: RouterStack, config: RouterInspectionConfig, app_event_rx: Receiver<AppEvent>) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let query = SocketQuery {
+        key: ErgotPingEndpoint::REQ_KEY.to_bytes(),
+        nash_req: NameRequirement::Any,
+        frame_kind: FrameKind::ENDPOINT_REQ,
+        broadcast: false,
+    };
+    let timeout = Duration::from_millis(config.ping_timeout_ms);
+
+    let mut stats = RouterStats::default();
+    let mut ticker = time::interval(Duration::from_millis(config.interval_ms.max(1)));
+    ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+    let mut ping: u32 = 0;
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            _ = ticker.tick() => {}
+        }
+
+        let discovered = stack
+            .discovery()
+            .discover_sockets(DISCOVERY_MAX, DISCOVERY_TIMEOUT, &query)
+            .await
+            .into_iter()
+            .map(|result| result.address)
+            .collect::<Vec<_>>();
+        for node in stats.discovered(&discovered) {
+            let Some(address) = discovered
+                .iter()
+                .find(|address| (address.network_id, address.node_id) == node)
+                .copied()
+            else {
+                // no longer discovered, counted as lost
+                stats.record_ping(node, None);
+                continue;
+            };
+
+            let client = stack
+                .endpoints()
+                .client::<ErgotPingEndpoint>(address, None);
+            let client = ClientWrapper::new(timeout, client);
+
+            let started_at = Instant::now();
+            let rtt = match client.request(&ping).await {
+                Ok(echo) if echo == ping => Some(started_at.elapsed()),
+                Ok(echo) => {
+                    debug!(
+                        "Unexpected ping response. address: {:?}, expected: {}, received: {}",
+                        address, ping, echo
+                    );
+                    None
+                }
+                Err(e) => {
+                    debug!("Ping lost. address: {:?}, error: {:?}", address, e);
+                    None
+                }
+            };
+            stats.record_ping(node, rtt);
+            ping = ping.wrapping_add(1);
+        }
+
+        let report = stats.take_report();
+        if let Err(e) = stack
+            .topics()
+            .broadcast::<RouterReportTopic>(&report, None)
+        {
+            debug!("Unable to publish router report, error: {:?}", e);
+        }
+    }
+    info!("router inspector shutdown");
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use ergot::Address;
+
+use super::inspection::RouterStats;
+
+fn address(network_id: u16, node_id: u8) -> Address {
+    Address {
+        network_id,
+        node_id,
+        port_id: 7,
+    }
+}
+
+#[test]
+pub fn report_groups_the_nodes_by_network() {
+    // given
+    let mut stats = RouterStats::default();
+    let nodes = stats.discovered(&[address(2, 1), address(1, 2), address(1, 1)]);
+
+    // when
+    let report = stats.take_report();
+
+    // then
+    assert_eq!(nodes, vec![(1, 1), (1, 2), (2, 1)]);
+    assert_eq!(
+        report
+            .interfaces
+            .iter()
+            .map(|interface| interface.network_id)
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(
+        report.interfaces[0]
+            .nodes
+            .iter()
+            .map(|node| node.node_id)
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+}
+
+#[test]
+pub fn interval_counts_reset_after_a_report() {
+    // given
+    let mut stats = RouterStats::default();
+    stats.record_ping((1, 2), Some(Duration::from_micros(800)));
+    stats.record_ping((1, 2), None);
+
+    // when
+    let first = stats.take_report();
+    let second = stats.take_report();
+
+    // then
+    assert_eq!(first.interfaces[0].answered, 1);
+    assert_eq!(first.interfaces[0].lost, 1);
+    assert_eq!(second.interfaces[0].answered, 0);
+    assert_eq!(second.interfaces[0].lost, 0);
+    // the totals are kept
+    assert_eq!(second.interfaces[0].nodes[0].pings, 2);
+    assert_eq!(second.interfaces[0].nodes[0].lost, 1);
+}
+
+#[test]
+pub fn an_answered_ping_resets_the_lost_in_a_row() {
+    // given
+    let mut stats = RouterStats::default();
+    stats.record_ping((1, 2), Some(Duration::from_micros(2_000)));
+    stats.record_ping((1, 2), None);
+    stats.record_ping((1, 2), None);
+    let lost = stats.take_report().interfaces[0].nodes[0];
+
+    // when
+    stats.record_ping((1, 2), Some(Duration::from_micros(500)));
+    let answered = stats.take_report().interfaces[0].nodes[0];
+
+    // then
+    assert_eq!(lost.lost_in_a_row, 2);
+    assert_eq!(lost.rtt_us, None);
+    assert_eq!(answered.lost_in_a_row, 0);
+    assert_eq!(answered.rtt_us, Some(500));
+    assert_eq!(answered.max_rtt_us, 2_000);
+}
+
+#[test]
+pub fn nodes_that_are_no_longer_discovered_are_kept() {
+    // given
+    let mut stats = RouterStats::default();
+    stats.discovered(&[address(1, 1), address(1, 2)]);
+
+    // when
+    let nodes = stats.discovered(&[address(1, 1)]);
+
+    // then
+    assert_eq!(nodes, vec![(1, 1), (1, 2)]);
+}
@@ -6,7 +6,7 @@ use ergot::toolkits::tokio_udp::RouterStack;
 use ergot::topic;
 use ergot::well_known::DeviceInfo;
 use ergot::wire_frames::MAX_HDR_ENCODED_SIZE;
-use ioboard_shared::yeet::Yeet;
+use ioboard_shared::load_cell::LoadCellSample;
 use log::{debug, info, warn};
 use tokio::sync::broadcast::Receiver;
 use tokio::time::interval;
@@ -14,8 +14,19 @@ use tokio::{select, time};
 
 use crate::AppEvent;
 
+pub mod dead_letter;
+pub mod inspection;
+
+#[cfg(test)]
+mod dead_letter_tests;
+#[cfg(test)]
+mod inspection_tests;
 #[cfg(test)]
 mod sanity_tests;
+#[cfg(test)]
+mod simulation;
+#[cfg(test)]
+mod simulation_tests;
 
 pub const UDP_OVER_ETH_MTU: usize = 1500;
 pub const IP_OVERHEAD_SIZE: usize = 20;
@@ -23,7 +34,7 @@ pub const UDP_OVERHEAD_SIZE: usize = 8;
 pub const UDP_OVER_ETH_ERGOT_FRAME_SIZE_MAX: usize = UDP_OVER_ETH_MTU - IP_OVERHEAD_SIZE - UDP_OVERHEAD_SIZE;
 pub const UDP_OVER_ETH_ERGOT_PAYLOAD_SIZE_MAX: usize = UDP_OVER_ETH_ERGOT_FRAME_SIZE_MAX - MAX_HDR_ENCODED_SIZE;
 
-topic!(YeetTopic, Yeet, "topic/yeet");
+topic!(LoadCellTopic, LoadCellSample, "topic/ioboard/load_cell");
 
 pub async fn basic_services(stack: RouterStack, port: u16, app_event_rx: Receiver<AppEvent>) {
     let info = DeviceInfo {
@@ -96,12 +107,14 @@ async fn do_device_discovery(stack: RouterStack) {
     }
 }
 
-pub async fn yeet_listener(stack: RouterStack, app_event_rx: Receiver<AppEvent>) {
+/// Logs the rate of the samples of the load cell, the io board only publishes them while the stream is begun, see
+/// [`IoBoardCommand::BeginLoadCellStream`](ioboard_shared::commands::IoBoardCommand::BeginLoadCellStream).
+pub async fn load_cell_listener(stack: RouterStack, app_event_rx: Receiver<AppEvent>) {
     let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
 
     let subber = stack
         .topics()
-        .heap_bounded_receiver::<YeetTopic>(64, None);
+        .heap_bounded_receiver::<LoadCellTopic>(64, None);
     let subber = pin!(subber);
     let mut hdl = subber.subscribe();
 
@@ -114,16 +127,21 @@ pub async fn yeet_listener(stack: RouterStack, app_event_rx: Receiver<AppEvent>)
         select! {
             now = ticker.tick() => {
                 let interval = now - last_tick_at;
-                info!("packet rate: {}/{:?}", packets_this_interval, interval);
+                if packets_this_interval > 0 {
+                    info!("load cell sample rate: {}/{:?}", packets_this_interval, interval);
+                }
                 packets_this_interval = 0;
                 last_tick_at = now;
             }
             msg = hdl.recv() => {
                 packets_this_interval += 1;
-                debug!("{}: got {}", msg.hdr, msg.t);
+                debug!(
+                    "{}: load cell sample. grams: {}, raw: {}, timestamp: {}",
+                    msg.hdr, msg.t.grams, msg.t.raw, msg.t.timestamp
+                );
             }
             _ = &mut app_shutdown_handler => {
-                info!("yeet listener shutdown requested, stopping");
+                info!("load cell listener shutdown requested, stopping");
                 break
             }
         }
@@ -0,0 +1,182 @@
+//! A simulated network link, for testing protocols over links that lose, reorder, duplicate and delay packets.
+//!
+//! The link is a pair of UDP relays on the loopback interface, each end of the link is a connected UDP socket that
+//! can be registered with an ergot stack as usual, see [`LossyLink::connect`].
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+
+/// Reordered packets are held back by this much more than the latency and jitter, so they arrive after the packets
+/// sent after them.
+const REORDER_DELAY: Duration = Duration::from_millis(5);
+
+/// Applied to each packet, in each direction, independently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkConditions {
+    /// the probability of a packet being dropped
+    pub loss: f64,
+    /// the probability of a packet being sent twice
+    pub duplication: f64,
+    /// the probability of a packet being held back, so that it arrives after the packets sent after it
+    pub reordering: f64,
+    pub latency: Duration,
+    /// a random delay, up to this, added to the latency of each packet
+    pub jitter: Duration,
+}
+
+impl LinkConditions {
+    pub const PERFECT: LinkConditions = LinkConditions {
+        loss: 0.0,
+        duplication: 0.0,
+        reordering: 0.0,
+        latency: Duration::ZERO,
+        jitter: Duration::ZERO,
+    };
+}
+
+impl Default for LinkConditions {
+    fn default() -> Self {
+        Self::PERFECT
+    }
+}
+
+/// Counts of the packets relayed by a link, in both directions.
+#[derive(Debug, Default)]
+pub struct LinkStats {
+    pub forwarded: AtomicU64,
+    pub dropped: AtomicU64,
+    pub duplicated: AtomicU64,
+    pub reordered: AtomicU64,
+}
+
+/// The relay tasks are stopped when the link is dropped.
+pub struct LossyLink {
+    stats: Arc<LinkStats>,
+    relays: Vec<JoinHandle<()>>,
+}
+
+impl LossyLink {
+    /// Returns the link and the sockets of its two ends, the randomness of the link is seeded, so a failing test can
+    /// be repeated.
+    pub async fn connect(conditions: LinkConditions, seed: u64) -> anyhow::Result<(LossyLink, UdpSocket, UdpSocket)> {
+        let (end_a, relay_a) = connected_pair().await?;
+        let (end_b, relay_b) = connected_pair().await?;
+
+        let relay_a = Arc::new(relay_a);
+        let relay_b = Arc::new(relay_b);
+        let stats = Arc::new(LinkStats::default());
+
+        let relays = vec![
+            tokio::spawn(relay(
+                relay_a.clone(),
+                relay_b.clone(),
+                conditions,
+                StdRng::seed_from_u64(seed),
+                stats.clone(),
+            )),
+            tokio::spawn(relay(
+                relay_b,
+                relay_a,
+                conditions,
+                StdRng::seed_from_u64(seed.wrapping_add(1)),
+                stats.clone(),
+            )),
+        ];
+
+        let link = LossyLink {
+            stats,
+            relays,
+        };
+
+        Ok((link, end_a, end_b))
+    }
+
+    pub fn stats(&self) -> &LinkStats {
+        &self.stats
+    }
+}
+
+impl Drop for LossyLink {
+    fn drop(&mut self) {
+        for relay in &self.relays {
+            relay.abort();
+        }
+    }
+}
+
+/// An end of the link, and the relay socket it's connected to.
+async fn connected_pair() -> anyhow::Result<(UdpSocket, UdpSocket)> {
+    let end = UdpSocket::bind("127.0.0.1:0").await?;
+    let relay = UdpSocket::bind("127.0.0.1:0").await?;
+    end.connect(relay.local_addr()?).await?;
+    relay.connect(end.local_addr()?).await?;
+
+    Ok((end, relay))
+}
+
+/// Forwards the packets received by `from` to the end connected to `to`.
+async fn relay(
+    from: Arc<UdpSocket>,
+    to: Arc<UdpSocket>,
+    conditions: LinkConditions,
+    mut rng: StdRng,
+    stats: Arc<LinkStats>,
+) {
+    let mut buffer = vec![0_u8; 65536];
+    loop {
+        let Ok(length) = from.recv(&mut buffer).await else {
+            continue;
+        };
+        let packet = buffer[..length].to_vec();
+
+        if rng.random_bool(conditions.loss) {
+            stats
+                .dropped
+                .fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+
+        let copies = match rng.random_bool(conditions.duplication) {
+            true => {
+                stats
+                    .duplicated
+                    .fetch_add(1, Ordering::Relaxed);
+                2
+            }
+            false => 1,
+        };
+
+        for _ in 0..copies {
+            let mut delay = conditions.latency
+                + conditions
+                    .jitter
+                    .mul_f64(rng.random_range(0.0..=1.0));
+            if rng.random_bool(conditions.reordering) {
+                stats
+                    .reordered
+                    .fetch_add(1, Ordering::Relaxed);
+                delay += conditions.jitter + REORDER_DELAY;
+            }
+            stats
+                .forwarded
+                .fetch_add(1, Ordering::Relaxed);
+
+            if delay.is_zero() {
+                let _ = to.send(&packet).await;
+            } else {
+                let to = to.clone();
+                let packet = packet.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let _ = to.send(&packet).await;
+                });
+            }
+        }
+    }
+}
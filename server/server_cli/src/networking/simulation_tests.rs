@@ -0,0 +1,410 @@
+//! Tests of the protocols between the server, the operator UI and the io boards over a lossy link, see
+//! [`super::simulation`].
+
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::{
+    EdgeStack, RouterStack, new_std_queue, new_target_stack, register_edge_target_interface, register_router_interface,
+};
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, endpoint};
+use ergot_util::ClientWrapper;
+use ioboard_shared::commands::{IdempotencyKey, Sequenced};
+use ioboard_shared::motion::{
+    MotionCommand, MotionCommandRequest, MotionCommandResponse, MotionQueueStatus, QueuedMove,
+};
+use machine_ids::MoveId;
+use operator_shared::frame_assembly::{AssembledFrame, FrameAssembler, frame_chunks};
+use tokio::sync::mpsc;
+use tokio::time;
+
+use super::UDP_OVER_ETH_ERGOT_PAYLOAD_SIZE_MAX;
+use super::simulation::{LinkConditions, LossyLink};
+use crate::camera::CameraFrameChunkTopic;
+use crate::ioboard::{CommandSequencer, MotionCommandEndpoint};
+use crate::motion::commands::MotionCommander;
+
+const TX_BUFFER_SIZE: usize = 4096;
+const CHUNK_SIZE: usize = 1024;
+const FRAMES: u64 = 20;
+const MAX_FRAME_BYTES: u32 = 64 * 1024;
+/// Frames are sent one at a time, the link doesn't reorder chunks of different frames.
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(100);
+const REQUEST_ATTEMPTS: usize = 10;
+
+// the port of the subscriber, same as the port in `CameraCommand::StartStreaming`
+endpoint!(StreamEndpoint, u8, (), "topic/test/stream");
+
+/// The router is the server, the edge is either the operator UI or an io board.
+async fn stacks(conditions: LinkConditions, seed: u64) -> (LossyLink, RouterStack, EdgeStack) {
+    let (link, router_socket, edge_socket) = LossyLink::connect(conditions, seed)
+        .await
+        .unwrap();
+
+    let router: RouterStack = RouterStack::new();
+    register_router_interface(
+        &router,
+        router_socket,
+        UDP_OVER_ETH_ERGOT_PAYLOAD_SIZE_MAX as _,
+        TX_BUFFER_SIZE,
+    )
+    .await
+    .unwrap();
+
+    let queue = new_std_queue(4096);
+    let edge: EdgeStack = new_target_stack(&queue, 1024);
+    register_edge_target_interface(&edge, edge_socket, &queue, None, None)
+        .await
+        .unwrap();
+
+    (link, router, edge)
+}
+
+fn query<E: Endpoint>() -> SocketQuery {
+    SocketQuery {
+        key: E::REQ_KEY.to_bytes(),
+        nash_req: NameRequirement::Any,
+        frame_kind: FrameKind::ENDPOINT_REQ,
+        broadcast: false,
+    }
+}
+
+fn jpeg_bytes(frame_number: u64) -> Vec<u8> {
+    // a few chunks, with a partial last chunk
+    (0..CHUNK_SIZE * 3 + 100)
+        .map(|index| (index as u64 + frame_number) as u8)
+        .collect()
+}
+
+/// Streams frames from the server to the operator UI, returns the frames the operator UI assembled.
+async fn stream_frames(conditions: LinkConditions, seed: u64) -> Vec<AssembledFrame> {
+    let (_link, router, edge) = stacks(conditions, seed).await;
+
+    let query_handler = tokio::spawn({
+        let router = router.clone();
+        async move {
+            router
+                .services()
+                .socket_query_handler::<4>()
+                .await
+        }
+    });
+
+    // the server learns the address of the subscriber from the request, like a `StartStreaming` camera command
+    let (address_tx, mut address_rx) = mpsc::channel::<Address>(1);
+    let stream_server = tokio::spawn({
+        let router = router.clone();
+        async move {
+            let server = router
+                .endpoints()
+                .bounded_server::<StreamEndpoint, 2>(None);
+            let server = pin!(server);
+            let mut hdl = server.attach();
+            loop {
+                let _ = hdl
+                    .serve_full(async |msg| {
+                        let address = Address {
+                            network_id: msg.hdr.src.network_id,
+                            node_id: msg.hdr.src.node_id,
+                            port_id: msg.t,
+                        };
+                        let _ = address_tx.try_send(address);
+                    })
+                    .await;
+            }
+        }
+    });
+
+    // the chunks are decoded from the receive buffer, without a copy, room for 320 chunks
+    let subber = edge
+        .topics()
+        .heap_borrowed_receiver::<CameraFrameChunkTopic>(320 * (CHUNK_SIZE + 32), None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe_unicast();
+
+    // retry, the discovery request or its response may be lost
+    let mut server_address = None;
+    for _ in 0..REQUEST_ATTEMPTS {
+        server_address = edge
+            .discovery()
+            .discover_sockets(4, REQUEST_TIMEOUT, &query::<StreamEndpoint>())
+            .await
+            .into_iter()
+            .next()
+            .map(|result| result.address);
+        if server_address.is_some() {
+            break;
+        }
+    }
+    let server_address = server_address.expect("stream endpoint discovered");
+    let client = edge
+        .endpoints()
+        .client::<StreamEndpoint>(server_address, None);
+    ClientWrapper::new(REQUEST_TIMEOUT, client)
+        .request_with_retry(&hdl.port(), REQUEST_ATTEMPTS)
+        .await
+        .unwrap();
+    let address = address_rx.recv().await.unwrap();
+
+    let streamer = tokio::spawn({
+        let router = router.clone();
+        async move {
+            for frame_number in 0..FRAMES {
+                let jpeg_bytes = jpeg_bytes(frame_number);
+                let (meta, image_chunks) =
+                    frame_chunks(frame_number, chrono::Utc::now().into(), None, &jpeg_bytes, CHUNK_SIZE);
+                for chunk in std::iter::once(&meta).chain(&image_chunks) {
+                    let _ = router
+                        .topics()
+                        .unicast_borrowed::<CameraFrameChunkTopic>(address, chunk);
+                    tokio::task::yield_now().await;
+                }
+                time::sleep(FRAME_INTERVAL).await;
+            }
+        }
+    });
+
+    let mut assembler = FrameAssembler::new(4, MAX_FRAME_BYTES);
+    let mut frames = vec![];
+    let deadline = time::Instant::now() + FRAME_INTERVAL * (FRAMES as u32 + 10);
+    while let Ok(msg) = time::timeout_at(deadline, hdl.recv()).await {
+        if let Some(frame) = assembler.insert(msg.t) {
+            frames.push(frame);
+        }
+        if frames
+            .last()
+            .is_some_and(|frame| frame.frame_number == FRAMES - 1)
+        {
+            break;
+        }
+    }
+
+    streamer.abort();
+    stream_server.abort();
+    query_handler.abort();
+
+    frames
+}
+
+fn assert_frames_intact(frames: &[AssembledFrame]) {
+    for frame in frames {
+        assert_eq!(
+            frame.jpeg_bytes,
+            jpeg_bytes(frame.frame_number),
+            "frame: {}",
+            frame.frame_number
+        );
+    }
+    assert!(
+        frames
+            .windows(2)
+            .all(|pair| pair[0].frame_number < pair[1].frame_number)
+    );
+}
+
+#[tokio::test]
+pub async fn camera_frames_over_perfect_link() {
+    // when
+    let frames = stream_frames(LinkConditions::PERFECT, 1).await;
+
+    // then
+    assert_eq!(frames.len(), FRAMES as usize);
+    assert_frames_intact(&frames);
+}
+
+#[tokio::test]
+pub async fn camera_frames_survive_duplication_reordering_and_latency() {
+    // given
+    let conditions = LinkConditions {
+        duplication: 0.2,
+        reordering: 0.2,
+        latency: Duration::from_millis(2),
+        jitter: Duration::from_millis(3),
+        ..LinkConditions::PERFECT
+    };
+
+    // when
+    let frames = stream_frames(conditions, 2).await;
+
+    // then
+    assert_eq!(frames.len(), FRAMES as usize);
+    assert_frames_intact(&frames);
+}
+
+#[tokio::test]
+pub async fn camera_frames_with_lost_chunks_are_dropped_not_corrupted() {
+    // given
+    let conditions = LinkConditions {
+        loss: 0.1,
+        reordering: 0.1,
+        latency: Duration::from_millis(1),
+        ..LinkConditions::PERFECT
+    };
+
+    // when
+    let frames = stream_frames(conditions, 3).await;
+
+    // then
+    assert!(!frames.is_empty());
+    assert_frames_intact(&frames);
+}
+
+/// Queues moves like an io board that plans its own trajectories, a retried command is answered with the original
+/// response instead of being executed again, see `DuplicateFilter` of the io board.
+#[derive(Default)]
+struct FakeMotionBoard {
+    responses: Vec<(IdempotencyKey, MotionCommandResponse)>,
+    queued: Vec<MoveId>,
+}
+
+impl FakeMotionBoard {
+    fn handle(&mut self, request: &Sequenced<MotionCommandRequest>) -> MotionCommandResponse {
+        if let Some((_, response)) = self
+            .responses
+            .iter()
+            .find(|(key, _)| *key == request.key)
+        {
+            return *response;
+        }
+        if let MotionCommand::Move(queued_move) = request.request.command {
+            self.queued.push(queued_move.move_id);
+        }
+        let response = Ok(MotionQueueStatus {
+            axis: request.request.axis,
+            depth: self.queued.len() as u32,
+            capacity: 64,
+            current_move: None,
+            discarded: 0,
+            position: 0,
+        });
+        self.responses
+            .push((request.key, response));
+        response
+    }
+}
+
+fn queued_move(index: u32) -> QueuedMove {
+    QueuedMove {
+        move_id: MoveId::new(index),
+        target: index as f64 * 100.0,
+        max_velocity: 1000.0,
+        max_acceleration: 10_000.0,
+        max_jerk: 100_000.0,
+    }
+}
+
+/// Queues moves from the server on an io board, returns the moves the io board acknowledged and the fake io board.
+async fn queue_moves(conditions: LinkConditions, seed: u64, moves: u32) -> (Vec<MoveId>, FakeMotionBoard) {
+    let (_link, router, edge) = stacks(conditions, seed).await;
+
+    let board = Arc::new(Mutex::new(FakeMotionBoard::default()));
+    let motion_server = tokio::spawn({
+        let edge = edge.clone();
+        let board = board.clone();
+        async move {
+            let server = edge
+                .endpoints()
+                .bounded_server::<MotionCommandEndpoint, 2>(None);
+            let server = pin!(server);
+            let mut hdl = server.attach();
+            loop {
+                let _ = hdl
+                    .serve(async |request: &Sequenced<MotionCommandRequest>| board.lock().unwrap().handle(request))
+                    .await;
+            }
+        }
+    });
+    let query_handler = tokio::spawn({
+        let edge = edge.clone();
+        async move {
+            edge.services()
+                .socket_query_handler::<4>()
+                .await
+        }
+    });
+
+    let commander = MotionCommander::new(router, Arc::new(CommandSequencer::new()));
+
+    // retry, the discovery request or its response may be lost
+    let mut address = None;
+    for _ in 0..REQUEST_ATTEMPTS {
+        address = commander
+            .discover()
+            .await
+            .into_iter()
+            .next();
+        if address.is_some() {
+            break;
+        }
+    }
+    let address = address.expect("motion command endpoint discovered");
+
+    let mut acknowledged = vec![];
+    for index in 0..moves {
+        let queued_move = queued_move(index);
+        if let Ok(Ok(_)) = commander
+            .queue_move(address, 0, queued_move)
+            .await
+        {
+            acknowledged.push(queued_move.move_id);
+        }
+    }
+
+    motion_server.abort();
+    query_handler.abort();
+    let _ = motion_server.await;
+
+    let board = Arc::into_inner(board)
+        .unwrap()
+        .into_inner()
+        .unwrap();
+    (acknowledged, board)
+}
+
+#[tokio::test]
+pub async fn moves_over_perfect_link_are_queued_once() {
+    // when
+    let (acknowledged, board) = queue_moves(LinkConditions::PERFECT, 4, 10).await;
+
+    // then
+    let expected = (0..10)
+        .map(MoveId::new)
+        .collect::<Vec<_>>();
+    assert_eq!(acknowledged, expected);
+    assert_eq!(board.queued, expected);
+}
+
+#[tokio::test]
+pub async fn retried_moves_are_not_queued_twice() {
+    // given
+    let conditions = LinkConditions {
+        loss: 0.2,
+        duplication: 0.2,
+        reordering: 0.1,
+        latency: Duration::from_millis(2),
+        jitter: Duration::from_millis(2),
+    };
+
+    // when
+    let (acknowledged, board) = queue_moves(conditions, 5, 10).await;
+
+    // then, the moves are queued in the order they were sent, none twice, even though commands and responses were
+    // lost and duplicated
+    assert!(
+        board
+            .queued
+            .windows(2)
+            .all(|pair| pair[0] < pair[1])
+    );
+    // a move is queued even if its response was lost, the server then doesn't know it was queued
+    assert!(!acknowledged.is_empty());
+    assert!(
+        acknowledged
+            .iter()
+            .all(|move_id| board.queued.contains(move_id))
+    );
+}
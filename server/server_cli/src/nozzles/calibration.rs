@@ -0,0 +1,225 @@
+//! Vacuum baseline calibration of the nozzles, run at the start of each run of a job.
+//!
+//! The vacuum of an open nozzle, and of a nozzle blocked by pressing it on the calibration pad, drifts as the nozzle
+//! wears and with the temperature, so the part-present threshold of each nozzle is set between the two readings, see
+//! [`part_present_threshold`].
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use ergot::toolkits::tokio_udp::RouterStack;
+use ioboard_shared::vacuum::{NozzleRequest, VacuumRequest, VacuumStatus};
+use log::info;
+use machine_geometry::Point;
+use serde::{Deserialize, Serialize};
+use tokio::time;
+
+use super::IoBoardVacuum;
+use crate::config::NozzleCalibrationConfig;
+use crate::ioboard::CommandSequencer;
+
+/// The readings of a nozzle and the threshold set from them, vacuum levels are in kPa.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NozzleCalibration {
+    pub nozzle: u8,
+    pub open_vacuum: f32,
+    pub blocked_vacuum: f32,
+    pub part_present_threshold: f32,
+}
+
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait NozzleCalibrator {
+    fn nozzle_count<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<u8>> + Send + 'a;
+
+    /// `position` in machine coordinates
+    fn move_to<'a>(&'a mut self, position: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+
+    /// Lowers the nozzle onto the pad, blocking it, or raises it clear of the pad.
+    fn press<'a>(&'a mut self, nozzle: u8, pressed: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+
+    fn set_valve<'a>(&'a mut self, nozzle: u8, open: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+
+    /// Returns the nozzle vacuum, in kPa.
+    fn read_vacuum<'a>(&'a mut self, nozzle: u8) -> impl Future<Output = anyhow::Result<f32>> + Send + 'a;
+
+    fn set_part_present_threshold<'a>(
+        &'a mut self,
+        nozzle: u8,
+        threshold: f32,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+}
+
+/// The threshold is at the `threshold_fraction` of the way from the open to the blocked vacuum.
+///
+/// Fails when the readings are too close to tell a part from an open nozzle, e.g. the nozzle is clogged, or worn so
+/// that it doesn't seal on the pad.
+pub fn part_present_threshold(
+    config: &NozzleCalibrationConfig,
+    open_vacuum: f32,
+    blocked_vacuum: f32,
+) -> anyhow::Result<f32> {
+    let margin = blocked_vacuum - open_vacuum;
+    if margin.is_nan() || margin < config.min_margin {
+        bail!(
+            "Nozzle vacuum margin too small. open: {}, blocked: {}, min_margin: {}",
+            open_vacuum,
+            blocked_vacuum,
+            config.min_margin
+        );
+    }
+
+    Ok(open_vacuum + margin * config.threshold_fraction)
+}
+
+/// Measures each nozzle open and blocked at the calibration pad, and sets its part-present threshold.
+///
+/// Stops at the first nozzle that can't be calibrated, the thresholds of the nozzles before it have already been set.
+pub async fn calibrate_nozzles<C: NozzleCalibrator>(
+    calibrator: &mut C,
+    config: &NozzleCalibrationConfig,
+) -> anyhow::Result<Vec<NozzleCalibration>> {
+    let nozzle_count = calibrator.nozzle_count().await?;
+    calibrator
+        .move_to(config.pad_position)
+        .await?;
+
+    let mut calibrations = vec![];
+    for nozzle in 0..nozzle_count {
+        let open_vacuum = measure(calibrator, nozzle, config).await?;
+
+        calibrator.press(nozzle, true).await?;
+        let blocked_vacuum = measure(calibrator, nozzle, config).await;
+        // raised even if the measurement failed
+        calibrator.press(nozzle, false).await?;
+        let blocked_vacuum = blocked_vacuum?;
+
+        let threshold = part_present_threshold(config, open_vacuum, blocked_vacuum)
+            .map_err(|e| anyhow!("Unable to calibrate nozzle. nozzle: {}, error: {}", nozzle, e))?;
+        calibrator
+            .set_part_present_threshold(nozzle, threshold)
+            .await?;
+
+        info!(
+            "Nozzle calibrated. nozzle: {}, open: {}, blocked: {}, threshold: {}",
+            nozzle, open_vacuum, blocked_vacuum, threshold
+        );
+        calibrations.push(NozzleCalibration {
+            nozzle,
+            open_vacuum,
+            blocked_vacuum,
+            part_present_threshold: threshold,
+        });
+    }
+    Ok(calibrations)
+}
+
+/// Reads the vacuum once it has settled after the valve is opened, the valve is closed again.
+async fn measure<C: NozzleCalibrator>(
+    calibrator: &mut C,
+    nozzle: u8,
+    config: &NozzleCalibrationConfig,
+) -> anyhow::Result<f32> {
+    calibrator
+        .set_valve(nozzle, true)
+        .await?;
+    time::sleep(Duration::from_millis(config.settle_ms)).await;
+    let vacuum = calibrator.read_vacuum(nozzle).await;
+    calibrator
+        .set_valve(nozzle, false)
+        .await?;
+    vacuum
+}
+
+/// Calibrates using the nozzle manifold of the io board, the io board is discovered on first use.
+pub struct IoBoardNozzleCalibrator {
+    vacuum: IoBoardVacuum,
+}
+
+impl IoBoardNozzleCalibrator {
+    pub fn new(stack: RouterStack, sequencer: Arc<CommandSequencer>) -> Self {
+        Self {
+            vacuum: IoBoardVacuum::new(stack, sequencer),
+        }
+    }
+
+    async fn request(&mut self, request: VacuumRequest) -> anyhow::Result<VacuumStatus> {
+        self.vacuum.request(request).await
+    }
+}
+
+impl NozzleCalibrator for IoBoardNozzleCalibrator {
+    fn nozzle_count<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<u8>> + Send + 'a {
+        async move {
+            let status = self
+                .request(VacuumRequest::Status)
+                .await?;
+            Ok(status.nozzle_count)
+        }
+    }
+
+    fn move_to<'a>(&'a mut self, position: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            // FUTURE there is no server planned X/Y motion yet, see `NozzleCleaner`, the head must already be at the
+            //        calibration pad
+            info!(
+                "Nozzle calibration requires the head at the calibration pad. position: {:?}",
+                position
+            );
+            Ok(())
+        }
+    }
+
+    fn press<'a>(&'a mut self, nozzle: u8, pressed: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            // FUTURE there is no server planned Z motion of the nozzles yet
+            info!(
+                "Nozzle calibration requires the nozzle pressed on the pad. nozzle: {}, pressed: {}",
+                nozzle, pressed
+            );
+            Ok(())
+        }
+    }
+
+    fn set_valve<'a>(&'a mut self, nozzle: u8, open: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let request = match open {
+                true => NozzleRequest::OpenValve,
+                false => NozzleRequest::CloseValve,
+            };
+            self.request(VacuumRequest::Nozzle(nozzle, request))
+                .await?;
+            Ok(())
+        }
+    }
+
+    fn read_vacuum<'a>(&'a mut self, nozzle: u8) -> impl Future<Output = anyhow::Result<f32>> + Send + 'a {
+        async move {
+            let status = self
+                .request(VacuumRequest::Status)
+                .await?;
+            status
+                .nozzles
+                .get(nozzle as usize)
+                .and_then(|nozzle| nozzle.vacuum)
+                .ok_or_else(|| anyhow!("Nozzle vacuum unavailable. nozzle: {}", nozzle))
+        }
+    }
+
+    fn set_part_present_threshold<'a>(
+        &'a mut self,
+        nozzle: u8,
+        threshold: f32,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.request(VacuumRequest::Nozzle(
+                nozzle,
+                NozzleRequest::SetPartPresentThreshold(threshold),
+            ))
+            .await?;
+            Ok(())
+        }
+    }
+}
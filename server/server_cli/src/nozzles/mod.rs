@@ -0,0 +1,510 @@
+//! Nozzle clog detection, from the vacuum response of each nozzle.
+//!
+//! A clogged nozzle restricts the airflow, so the pickup vacuum stays low even though a part is detected, or the
+//! vacuum decays slowly after the valve is closed.  After several consecutive abnormal pick cycles a
+//! [`MaintenanceEvent`] is raised and, if configured, a blow-off cleaning cycle is run at the park position.
+//!
+//! The part-present threshold of each nozzle is calibrated at the start of a job, see [`calibration`].
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, topic};
+use ergot_util::ClientWrapper;
+use ioboard_shared::vacuum::{MAX_NOZZLES, NozzleRequest, NozzleStatus, VacuumRequest, VacuumStatus};
+use log::{debug, error, info, warn};
+use machine_geometry::Point;
+use operator_shared::maintenance::{ClogSymptom, MaintenanceEvent};
+use tokio::select;
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::Receiver;
+use tokio::time::{self, Instant};
+
+use crate::AppEvent;
+use crate::config::{NozzleCleaningConfig, NozzlesConfig};
+use crate::ioboard::{CommandSequencer, VacuumEndpoint};
+use crate::job::JobControl;
+use crate::networking::dead_letter;
+
+pub mod calibration;
+
+#[cfg(test)]
+mod tests;
+
+topic!(MaintenanceTopic, MaintenanceEvent, "topic/operator/maintenance");
+
+/// Short enough to see the vacuum decay after the valve is closed.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const VACUUM_REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+const VACUUM_REQUEST_ATTEMPTS: u32 = 3;
+
+/// A pick cycle of a nozzle, from opening the valve until the vacuum has decayed after closing it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Idle,
+    /// the valve was opened at `since`, the pickup vacuum is checked once it has settled
+    Picking {
+        since: Instant,
+    },
+    Holding {
+        symptom: Option<ClogSymptom>,
+    },
+    /// the valve was closed at `since`
+    Releasing {
+        since: Instant,
+        symptom: Option<ClogSymptom>,
+    },
+}
+
+/// The outcome of a completed pick cycle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Verdict {
+    Normal,
+    Abnormal(ClogSymptom),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct NozzleState {
+    phase: Phase,
+    /// consecutive abnormal pick cycles
+    abnormal: u32,
+}
+
+pub struct NozzleMonitor {
+    config: NozzlesConfig,
+    nozzles: [NozzleState; MAX_NOZZLES],
+}
+
+impl NozzleMonitor {
+    pub fn new(config: NozzlesConfig) -> Self {
+        Self {
+            config,
+            nozzles: [NozzleState {
+                phase: Phase::Idle,
+                abnormal: 0,
+            }; MAX_NOZZLES],
+        }
+    }
+
+    /// Call with each vacuum status, returns an event for each nozzle a clog is suspected for.
+    pub fn update(&mut self, now: Instant, status: &VacuumStatus) -> Vec<MaintenanceEvent> {
+        let mut events = vec![];
+
+        let nozzle_count = (status.nozzle_count as usize).min(MAX_NOZZLES);
+        for (index, nozzle) in status.nozzles[..nozzle_count]
+            .iter()
+            .enumerate()
+        {
+            let state = &mut self.nozzles[index];
+            let (phase, verdict) = next_phase(&self.config, state.phase, nozzle, now);
+            state.phase = phase;
+
+            match verdict {
+                None => {}
+                Some(Verdict::Normal) => state.abnormal = 0,
+                Some(Verdict::Abnormal(symptom)) => {
+                    state.abnormal += 1;
+                    debug!(
+                        "Abnormal nozzle vacuum. nozzle: {}, symptom: {:?}, consecutive: {}",
+                        index, symptom, state.abnormal
+                    );
+                    if state.abnormal >= self.config.clog_threshold {
+                        warn!("Nozzle clog suspected. nozzle: {}, symptom: {:?}", index, symptom);
+                        state.abnormal = 0;
+                        events.push(MaintenanceEvent::NozzleClogSuspected {
+                            nozzle: index as u8,
+                            symptom,
+                        });
+                    }
+                }
+            }
+        }
+        events
+    }
+}
+
+/// Returns the verdict when a pick cycle has completed.
+fn next_phase(config: &NozzlesConfig, phase: Phase, nozzle: &NozzleStatus, now: Instant) -> (Phase, Option<Verdict>) {
+    // blowing off, e.g. cleaning, is not a pick cycle
+    if nozzle.blow_off {
+        return (Phase::Idle, None);
+    }
+
+    let settle = Duration::from_millis(config.pickup_settle_ms);
+    let decay = Duration::from_millis(config.release_decay_ms);
+
+    match (phase, nozzle.valve_open) {
+        (
+            Phase::Idle
+            | Phase::Releasing {
+                ..
+            },
+            true,
+        ) => (
+            Phase::Picking {
+                since: now,
+            },
+            None,
+        ),
+        (
+            Phase::Picking {
+                ..
+            },
+            false,
+        ) => (
+            Phase::Releasing {
+                since: now,
+                symptom: None,
+            },
+            None,
+        ),
+        (
+            Phase::Holding {
+                symptom,
+            },
+            false,
+        ) => (
+            Phase::Releasing {
+                since: now,
+                symptom,
+            },
+            None,
+        ),
+        (
+            Phase::Picking {
+                since,
+            },
+            true,
+        ) if now - since >= settle => {
+            let symptom = match (nozzle.part_present, nozzle.vacuum) {
+                (Some(true), Some(vacuum)) if vacuum < config.pickup_vacuum_min => {
+                    Some(ClogSymptom::PoorPickupVacuum {
+                        vacuum,
+                    })
+                }
+                // without a part, e.g. a missed pick, the pickup vacuum says nothing about the nozzle
+                _ => None,
+            };
+            (
+                Phase::Holding {
+                    symptom,
+                },
+                None,
+            )
+        }
+        (
+            Phase::Releasing {
+                since,
+                symptom,
+            },
+            false,
+        ) => match nozzle.vacuum {
+            Some(vacuum) if vacuum <= config.release_vacuum_max => {
+                let verdict = match symptom {
+                    Some(symptom) => Verdict::Abnormal(symptom),
+                    None => Verdict::Normal,
+                };
+                (Phase::Idle, Some(verdict))
+            }
+            Some(vacuum) if now - since >= decay => (
+                Phase::Idle,
+                Some(Verdict::Abnormal(ClogSymptom::SlowDecay {
+                    vacuum,
+                })),
+            ),
+            Some(_) => (phase, None),
+            None => (Phase::Idle, None),
+        },
+        (phase, _) => (phase, None),
+    }
+}
+
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait NozzleCleaner {
+    /// `position` in machine coordinates
+    fn move_to<'a>(&'a mut self, position: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+
+    fn blow_off<'a>(&'a mut self, nozzle: u8, on: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+}
+
+/// Moves to the park position, then blows off the nozzle in pulses.
+pub async fn run_cleaning_cycle<C: NozzleCleaner>(
+    cleaner: &mut C,
+    nozzle: u8,
+    config: &NozzleCleaningConfig,
+) -> anyhow::Result<()> {
+    cleaner
+        .move_to(config.park_position)
+        .await?;
+
+    for pulse in 0..config.pulses {
+        if pulse > 0 {
+            time::sleep(Duration::from_millis(config.pause_ms)).await;
+        }
+        cleaner.blow_off(nozzle, true).await?;
+        time::sleep(Duration::from_millis(config.pulse_ms)).await;
+        cleaner.blow_off(nozzle, false).await?;
+    }
+    Ok(())
+}
+
+/// Blows off using the nozzle manifold of the io board.
+pub struct IoBoardNozzleCleaner {
+    stack: RouterStack,
+    address: Address,
+    sequencer: Arc<CommandSequencer>,
+}
+
+impl NozzleCleaner for IoBoardNozzleCleaner {
+    fn move_to<'a>(&'a mut self, position: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            // FUTURE there is no server planned X/Y motion yet, see `SetpointPositioner`, the head must already be at
+            //        the park position
+            info!(
+                "Nozzle cleaning requires the head at the park position. position: {:?}",
+                position
+            );
+            Ok(())
+        }
+    }
+
+    fn blow_off<'a>(&'a mut self, nozzle: u8, on: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let request = match on {
+                true => NozzleRequest::StartBlowOff,
+                false => NozzleRequest::StopBlowOff,
+            };
+            let client = self
+                .stack
+                .endpoints()
+                .client::<VacuumEndpoint>(self.address, None);
+            let client = ClientWrapper::new(VACUUM_REQUEST_TIMEOUT, client);
+            let request = self
+                .sequencer
+                .sequenced(VacuumRequest::Nozzle(nozzle, request));
+            if let Err(e) = client
+                .request_with_retry(&request, VACUUM_REQUEST_ATTEMPTS)
+                .await
+                .inspect_err(|e| {
+                    dead_letter::request_failed::<VacuumEndpoint>(self.address, VACUUM_REQUEST_ATTEMPTS, e)
+                })?
+            {
+                bail!("Blow off refused. nozzle: {}, on: {}, error: {:?}", nozzle, on, e);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait NozzleVacuum {
+    fn set_valve<'a>(&'a mut self, nozzle: u8, open: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+
+    /// `None` if the nozzle has no part-present sensing
+    fn part_present<'a>(&'a mut self, nozzle: u8) -> impl Future<Output = anyhow::Result<Option<bool>>> + Send + 'a;
+}
+
+/// Requests to the vacuum endpoint of the io board, which is discovered on first use.
+pub struct IoBoardVacuum {
+    stack: RouterStack,
+    address: Option<Address>,
+    sequencer: Arc<CommandSequencer>,
+}
+
+impl IoBoardVacuum {
+    pub fn new(stack: RouterStack, sequencer: Arc<CommandSequencer>) -> Self {
+        Self {
+            stack,
+            address: None,
+            sequencer,
+        }
+    }
+
+    pub async fn request(&mut self, request: VacuumRequest) -> anyhow::Result<VacuumStatus> {
+        let address = match self.address {
+            Some(address) => address,
+            None => {
+                let query = SocketQuery {
+                    key: VacuumEndpoint::REQ_KEY.to_bytes(),
+                    nash_req: NameRequirement::Any,
+                    frame_kind: FrameKind::ENDPOINT_REQ,
+                    broadcast: false,
+                };
+                // TODO use the nozzles of every io board, currently there is only one
+                let result = self
+                    .stack
+                    .discovery()
+                    .discover_sockets(4, VACUUM_REQUEST_TIMEOUT, &query)
+                    .await
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("Vacuum endpoint not found"))?;
+                *self.address.insert(result.address)
+            }
+        };
+
+        let client = self
+            .stack
+            .endpoints()
+            .client::<VacuumEndpoint>(address, None);
+        let client = ClientWrapper::new(VACUUM_REQUEST_TIMEOUT, client);
+        let sequenced = self.sequencer.sequenced(request);
+        let response = client
+            .request_with_retry(&sequenced, VACUUM_REQUEST_ATTEMPTS)
+            .await
+            .inspect_err(|e| dead_letter::request_failed::<VacuumEndpoint>(address, VACUUM_REQUEST_ATTEMPTS, e));
+        if response.is_err() {
+            // the io board may have restarted with a different address
+            self.address = None;
+        }
+        response?.map_err(|e| anyhow!("Vacuum request refused. request: {:?}, error: {:?}", request, e))
+    }
+}
+
+impl NozzleVacuum for IoBoardVacuum {
+    fn set_valve<'a>(&'a mut self, nozzle: u8, open: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let request = match open {
+                true => NozzleRequest::OpenValve,
+                false => NozzleRequest::CloseValve,
+            };
+            self.request(VacuumRequest::Nozzle(nozzle, request))
+                .await?;
+            Ok(())
+        }
+    }
+
+    fn part_present<'a>(&'a mut self, nozzle: u8) -> impl Future<Output = anyhow::Result<Option<bool>>> + Send + 'a {
+        async move {
+            let status = self
+                .request(VacuumRequest::Status)
+                .await?;
+            let nozzle = status
+                .nozzles
+                .get(nozzle as usize)
+                .ok_or_else(|| anyhow!("Unknown nozzle. nozzle: {}", nozzle))?;
+            Ok(nozzle.part_present)
+        }
+    }
+}
+
+/// Watches the vacuum response of the nozzles and publishes [`MaintenanceEvent`]s for the operator UI.
+///
+/// The cleaning cycle is only run while no job is running, since it moves the head.
+pub async fn nozzle_monitor(
+    stack: RouterStack,
+    config: NozzlesConfig,
+    job_control: Arc<Mutex<JobControl>>,
+    sequencer: Arc<CommandSequencer>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let query = SocketQuery {
+        key: VacuumEndpoint::REQ_KEY.to_bytes(),
+        nash_req: NameRequirement::Any,
+        frame_kind: FrameKind::ENDPOINT_REQ,
+        broadcast: false,
+    };
+    let mut vacuum_address: Option<Address> = None;
+
+    let cleaning = config.cleaning.clone();
+    let mut monitor = NozzleMonitor::new(config);
+
+    let mut ticker = time::interval(POLL_INTERVAL);
+    ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            _ = ticker.tick() => {
+                let address = match vacuum_address {
+                    Some(address) => address,
+                    None => {
+                        // TODO monitor the nozzles of every io board, currently there is only one
+                        let Some(result) = stack
+                            .discovery()
+                            .discover_sockets(4, VACUUM_REQUEST_TIMEOUT, &query)
+                            .await
+                            .into_iter()
+                            .next()
+                        else {
+                            continue
+                        };
+                        vacuum_address = Some(result.address);
+                        result.address
+                    }
+                };
+
+                let client = stack
+                    .endpoints()
+                    .client::<VacuumEndpoint>(address, None);
+                let client = ClientWrapper::new(VACUUM_REQUEST_TIMEOUT, client);
+                let request = sequencer.sequenced(VacuumRequest::Status);
+                let status = match client.request_with_retry(&request, VACUUM_REQUEST_ATTEMPTS).await {
+                    Ok(Ok(status)) => status,
+                    Ok(Err(e)) => {
+                        debug!("Vacuum status refused. error: {:?}", e);
+                        continue
+                    }
+                    Err(e) => {
+                        debug!("Unable to request vacuum status. error: {:?}", e);
+                        dead_letter::request_failed::<VacuumEndpoint>(address, VACUUM_REQUEST_ATTEMPTS, &e);
+                        // the io board may have restarted with a different address
+                        vacuum_address = None;
+                        continue
+                    }
+                };
+
+                for event in monitor.update(Instant::now(), &status) {
+                    publish(&stack, &event);
+
+                    let (MaintenanceEvent::NozzleClogSuspected { nozzle, .. }, Some(cleaning)) = (event, &cleaning)
+                    else {
+                        continue
+                    };
+                    if job_control.lock().await.is_running() {
+                        info!("Nozzle cleaning skipped, a job is running. nozzle: {}", nozzle);
+                        continue
+                    }
+
+                    let mut cleaner = IoBoardNozzleCleaner {
+                        stack: stack.clone(),
+                        address,
+                        sequencer: sequencer.clone(),
+                    };
+                    let event = match run_cleaning_cycle(&mut cleaner, nozzle, cleaning).await {
+                        Ok(()) => {
+                            info!("Nozzle cleaned. nozzle: {}", nozzle);
+                            MaintenanceEvent::NozzleCleaned { nozzle }
+                        }
+                        Err(e) => {
+                            error!("Nozzle cleaning failed. nozzle: {}, error: {:?}", nozzle, e);
+                            MaintenanceEvent::NozzleCleaningFailed { nozzle }
+                        }
+                    };
+                    publish(&stack, &event);
+                }
+            }
+        }
+    }
+    info!("nozzle monitor shutdown");
+}
+
+fn publish(stack: &RouterStack, event: &MaintenanceEvent) {
+    if let Err(e) = stack
+        .topics()
+        .broadcast::<MaintenanceTopic>(event, None)
+    {
+        debug!("Unable to publish maintenance event, error: {:?}", e);
+    }
+}
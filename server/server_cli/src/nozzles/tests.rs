@@ -0,0 +1,346 @@
+use std::future::Future;
+use std::time::Duration;
+
+use ioboard_shared::vacuum::{NozzleStatus, VacuumStatus};
+use machine_geometry::Point;
+use operator_shared::maintenance::{ClogSymptom, MaintenanceEvent};
+use tokio::time::Instant;
+
+use super::calibration::{NozzleCalibration, NozzleCalibrator, calibrate_nozzles, part_present_threshold};
+use super::{NozzleCleaner, NozzleMonitor, run_cleaning_cycle};
+use crate::config::{NozzleCalibrationConfig, NozzleCleaningConfig, NozzlesConfig};
+
+/// A single nozzle.
+fn status(valve_open: bool, vacuum: f32, part_present: Option<bool>) -> VacuumStatus {
+    let mut status = VacuumStatus {
+        nozzle_count: 1,
+        ..VacuumStatus::default()
+    };
+    status.nozzles[0] = NozzleStatus {
+        valve_open,
+        blow_off: false,
+        vacuum: Some(vacuum),
+        part_present,
+    };
+    status
+}
+
+fn monitor(clog_threshold: u32) -> NozzleMonitor {
+    NozzleMonitor::new(NozzlesConfig {
+        clog_threshold,
+        ..NozzlesConfig::default()
+    })
+}
+
+/// Picks a part, releases it 50ms after the pickup vacuum is checked, and waits `release_ms` for the vacuum to decay,
+/// using the default config.  Returns the time the cycle ended and the raised events.
+fn pick_cycle(
+    monitor: &mut NozzleMonitor,
+    start: Instant,
+    pickup_vacuum: f32,
+    part_present: bool,
+    release_vacuum: f32,
+    release_ms: u64,
+) -> (Instant, Vec<MaintenanceEvent>) {
+    let opened = start;
+    let settled = opened + Duration::from_millis(100);
+    let closed = settled + Duration::from_millis(50);
+    let released = closed + Duration::from_millis(release_ms);
+
+    let mut events = vec![];
+    events.extend(monitor.update(opened, &status(true, 0.0, Some(false))));
+    events.extend(monitor.update(settled, &status(true, pickup_vacuum, Some(part_present))));
+    events.extend(monitor.update(closed, &status(false, pickup_vacuum, None)));
+    events.extend(monitor.update(released, &status(false, release_vacuum, None)));
+
+    (released + Duration::from_millis(50), events)
+}
+
+#[test]
+pub fn poor_pickup_vacuum_suspects_clog() {
+    // given
+    let mut monitor = monitor(2);
+    let start = Instant::now();
+
+    // when
+    let (next, first) = pick_cycle(&mut monitor, start, 30.0, true, 1.0, 50);
+    let (_, second) = pick_cycle(&mut monitor, next, 25.0, true, 1.0, 50);
+
+    // then
+    assert!(first.is_empty());
+    assert_eq!(second, vec![MaintenanceEvent::NozzleClogSuspected {
+        nozzle: 0,
+        symptom: ClogSymptom::PoorPickupVacuum {
+            vacuum: 25.0
+        },
+    }]);
+}
+
+#[test]
+pub fn slow_decay_suspects_clog() {
+    // given
+    let mut monitor = monitor(1);
+
+    // when
+    let (_, events) = pick_cycle(&mut monitor, Instant::now(), 60.0, true, 20.0, 250);
+
+    // then
+    assert_eq!(events, vec![MaintenanceEvent::NozzleClogSuspected {
+        nozzle: 0,
+        symptom: ClogSymptom::SlowDecay {
+            vacuum: 20.0
+        },
+    }]);
+}
+
+#[test]
+pub fn normal_pick_cycle_resets_count() {
+    // given
+    let mut monitor = monitor(2);
+    let start = Instant::now();
+
+    // when
+    let (next, mut events) = pick_cycle(&mut monitor, start, 30.0, true, 1.0, 50);
+    let (next, normal) = pick_cycle(&mut monitor, next, 60.0, true, 1.0, 50);
+    let (_, abnormal) = pick_cycle(&mut monitor, next, 30.0, true, 1.0, 50);
+    events.extend(normal);
+    events.extend(abnormal);
+
+    // then
+    assert!(events.is_empty());
+}
+
+#[test]
+pub fn missed_pick_is_not_abnormal() {
+    // given
+    let mut monitor = monitor(1);
+
+    // when
+    let (_, events) = pick_cycle(&mut monitor, Instant::now(), 5.0, false, 1.0, 50);
+
+    // then
+    assert!(events.is_empty());
+}
+
+#[derive(Debug, PartialEq)]
+enum CleanerCall {
+    MoveTo(Point),
+    BlowOff(u8, bool),
+}
+
+#[derive(Default)]
+struct FakeCleaner {
+    calls: Vec<CleanerCall>,
+}
+
+impl NozzleCleaner for FakeCleaner {
+    fn move_to<'a>(&'a mut self, position: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.calls
+                .push(CleanerCall::MoveTo(position));
+            Ok(())
+        }
+    }
+
+    fn blow_off<'a>(&'a mut self, nozzle: u8, on: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.calls
+                .push(CleanerCall::BlowOff(nozzle, on));
+            Ok(())
+        }
+    }
+}
+
+#[tokio::test]
+pub async fn cleaning_cycle_blows_off_at_park_position() {
+    // given
+    let park_position = Point {
+        x: 10.0,
+        y: 20.0,
+    };
+    let config = NozzleCleaningConfig {
+        park_position,
+        pulses: 2,
+        pulse_ms: 1,
+        pause_ms: 1,
+    };
+    let mut cleaner = FakeCleaner::default();
+
+    // when
+    let result = run_cleaning_cycle(&mut cleaner, 1, &config).await;
+
+    // then
+    assert!(result.is_ok());
+    assert_eq!(cleaner.calls, vec![
+        CleanerCall::MoveTo(park_position),
+        CleanerCall::BlowOff(1, true),
+        CleanerCall::BlowOff(1, false),
+        CleanerCall::BlowOff(1, true),
+        CleanerCall::BlowOff(1, false),
+    ]);
+}
+
+#[derive(Debug, PartialEq)]
+enum CalibratorCall {
+    MoveTo(Point),
+    Press(u8, bool),
+    Threshold(u8, f32),
+}
+
+/// The `(open, blocked)` vacuum of each nozzle.
+struct FakeCalibrator {
+    vacuums: Vec<(f32, f32)>,
+    pressed: Option<u8>,
+    open_valves: Vec<u8>,
+    calls: Vec<CalibratorCall>,
+}
+
+impl FakeCalibrator {
+    fn new(vacuums: &[(f32, f32)]) -> Self {
+        Self {
+            vacuums: vacuums.to_vec(),
+            pressed: None,
+            open_valves: vec![],
+            calls: vec![],
+        }
+    }
+}
+
+impl NozzleCalibrator for FakeCalibrator {
+    fn nozzle_count<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<u8>> + Send + 'a {
+        async move { Ok(self.vacuums.len() as u8) }
+    }
+
+    fn move_to<'a>(&'a mut self, position: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.calls
+                .push(CalibratorCall::MoveTo(position));
+            Ok(())
+        }
+    }
+
+    fn press<'a>(&'a mut self, nozzle: u8, pressed: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.pressed = pressed.then_some(nozzle);
+            self.calls
+                .push(CalibratorCall::Press(nozzle, pressed));
+            Ok(())
+        }
+    }
+
+    fn set_valve<'a>(&'a mut self, nozzle: u8, open: bool) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.open_valves
+                .retain(|valve| *valve != nozzle);
+            if open {
+                self.open_valves.push(nozzle);
+            }
+            Ok(())
+        }
+    }
+
+    fn read_vacuum<'a>(&'a mut self, nozzle: u8) -> impl Future<Output = anyhow::Result<f32>> + Send + 'a {
+        async move {
+            assert!(self.open_valves.contains(&nozzle));
+            let (open, blocked) = self.vacuums[nozzle as usize];
+            match self.pressed == Some(nozzle) {
+                true => Ok(blocked),
+                false => Ok(open),
+            }
+        }
+    }
+
+    fn set_part_present_threshold<'a>(
+        &'a mut self,
+        nozzle: u8,
+        threshold: f32,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.calls
+                .push(CalibratorCall::Threshold(nozzle, threshold));
+            Ok(())
+        }
+    }
+}
+
+fn calibration_config() -> NozzleCalibrationConfig {
+    NozzleCalibrationConfig {
+        pad_position: Point {
+            x: 5.0,
+            y: 15.0,
+        },
+        settle_ms: 1,
+        threshold_fraction: 0.25,
+        min_margin: 20.0,
+    }
+}
+
+#[test]
+pub fn part_present_threshold_is_between_the_open_and_blocked_vacuum() {
+    // given
+    let config = calibration_config();
+
+    // when
+    let threshold = part_present_threshold(&config, 10.0, 70.0);
+    let worn = part_present_threshold(&config, 10.0, 25.0);
+    let unknown = part_present_threshold(&config, f32::NAN, 70.0);
+
+    // then
+    assert_eq!(threshold.unwrap(), 25.0);
+    assert!(worn.is_err());
+    assert!(unknown.is_err());
+}
+
+#[tokio::test]
+pub async fn calibration_sets_the_threshold_of_each_nozzle() {
+    // given
+    let config = calibration_config();
+    let mut calibrator = FakeCalibrator::new(&[(10.0, 70.0), (20.0, 60.0)]);
+
+    // when
+    let calibrations = calibrate_nozzles(&mut calibrator, &config)
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(calibrations, vec![
+        NozzleCalibration {
+            nozzle: 0,
+            open_vacuum: 10.0,
+            blocked_vacuum: 70.0,
+            part_present_threshold: 25.0,
+        },
+        NozzleCalibration {
+            nozzle: 1,
+            open_vacuum: 20.0,
+            blocked_vacuum: 60.0,
+            part_present_threshold: 30.0,
+        },
+    ]);
+    assert_eq!(calibrator.calls, vec![
+        CalibratorCall::MoveTo(config.pad_position),
+        CalibratorCall::Press(0, true),
+        CalibratorCall::Press(0, false),
+        CalibratorCall::Threshold(0, 25.0),
+        CalibratorCall::Press(1, true),
+        CalibratorCall::Press(1, false),
+        CalibratorCall::Threshold(1, 30.0),
+    ]);
+    assert!(calibrator.open_valves.is_empty());
+}
+
+#[tokio::test]
+pub async fn calibration_fails_for_a_worn_nozzle() {
+    // given
+    let config = calibration_config();
+    let mut calibrator = FakeCalibrator::new(&[(10.0, 70.0), (20.0, 30.0), (10.0, 70.0)]);
+
+    // when
+    let result = calibrate_nozzles(&mut calibrator, &config).await;
+
+    // then
+    assert!(result.is_err());
+    // the thresholds of the nozzles after the worn one are not set
+    assert_eq!(calibrator.calls.last(), Some(&CalibratorCall::Press(1, false)));
+    assert!(calibrator.open_valves.is_empty());
+}
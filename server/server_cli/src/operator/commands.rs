@@ -0,0 +1,927 @@
+//! The handlers of the operator commands, one per command, see [`operator_listener`](super::operator_listener).
+//!
+//! Commands that run for a while, e.g. a job or homing, are started as a task and answered immediately, their progress
+//! is published on a topic.
+
+#[cfg(feature = "machine-vision")]
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ergot::Address;
+use ergot::toolkits::tokio_udp::RouterStack;
+use ioboard_shared::estop::{EStop, EStopSource};
+use log::{info, warn};
+#[cfg(feature = "machine-vision")]
+use machine_ids::CameraId;
+use machine_ids::FeederId;
+#[cfg(feature = "machine-vision")]
+use operator_shared::camera::{
+    CameraCommandError, CameraCommandErrorCode, CameraStreamerCommandResult, CaptureRequest, CaptureResponse,
+    CapturedFrame,
+};
+#[cfg(feature = "machine-vision")]
+use operator_shared::captures::CaptureKey;
+use operator_shared::commands::OperatorCommandResponse;
+use operator_shared::config::ConfigChange;
+use operator_shared::diagnostics::LogLevel;
+use operator_shared::homing::HomingError;
+use operator_shared::job::{EstimateError, InterventionResolution, ResumeChoice};
+use operator_shared::limits::{AxisLimit, LimitOverrideRequest};
+use operator_shared::readiness::{ReadinessCheck, StartJobError};
+use operator_shared::resonance::ResonanceSweepSettings;
+#[cfg(feature = "machine-vision")]
+use operator_shared::templates::{TemplateCapture, TemplateKind};
+use operator_shared::test_area::{TestPattern, TestShotError, TestShotKind};
+#[cfg(feature = "machine-vision")]
+use operator_shared::vision::ScanTarget;
+use tokio::sync::Mutex;
+#[cfg(feature = "machine-vision")]
+use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "machine-vision")]
+use super::CameraManagers;
+use super::{app_placer, job_flusher, machine_geometry, machine_inspector, start_test_pattern};
+use crate::AppState;
+#[cfg(feature = "machine-vision")]
+use crate::camera::{CameraClient, camera_definition_for_identifier, camera_infos, camera_manager};
+#[cfg(feature = "machine-vision")]
+use crate::captures::CaptureStore;
+use crate::coordinates::CoordinateTransform;
+use crate::diagnostics::tap::topic_tap_runner;
+use crate::estop::publish_estop;
+use crate::forces::ForceLog;
+use crate::homing::{IoBoardHomer, homing_runner};
+use crate::job::evidence::EvidenceLog;
+use crate::job::job_runner;
+use crate::job::simulation::{estimate_page, simulate_job};
+use crate::limits::{IoBoardLimitOverrider, override_limit};
+use crate::logging;
+use crate::nozzles::calibration::IoBoardNozzleCalibrator;
+#[cfg(feature = "machine-vision")]
+use crate::orientation;
+use crate::resonance::{IoBoardSweeper, resonance_sweep_runner, sweep_request};
+#[cfg(feature = "machine-vision")]
+use crate::scanning;
+#[cfg(feature = "machine-vision")]
+use crate::templates;
+use crate::test_area::test_shot_runner;
+use crate::travel::PartHeights;
+#[cfg(feature = "machine-vision")]
+use crate::vision::VisionCaptureRequest;
+
+pub async fn home_all(
+    stack: &RouterStack,
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+) -> OperatorCommandResponse {
+    let (job_control, readiness, config, homer, app_event_rx) = {
+        let app_state = app_state.lock().await;
+        let config = app_state.config.homing.clone();
+        let homer = IoBoardHomer::new(
+            stack.clone(),
+            app_state.command_sequencer.clone(),
+            Duration::from_millis(config.timeout_ms),
+        );
+        (
+            app_state.job_control.clone(),
+            app_state.readiness.clone(),
+            config,
+            homer,
+            app_state.event_tx.subscribe(),
+        )
+    };
+    let result = match config.axes.is_empty() {
+        true => Err(HomingError::NotConfigured),
+        false => job_control.lock().await.start_homing(),
+    };
+    match &result {
+        Ok(()) => {
+            info!("Starting homing. source: {:?}", source);
+            // not awaited on shutdown, the same as the job runner
+            tokio::spawn(homing_runner(
+                stack.clone(),
+                homer,
+                config,
+                job_control,
+                readiness,
+                app_event_rx,
+            ));
+        }
+        Err(e) => warn!("Homing refused. error: {:?}", e),
+    }
+    OperatorCommandResponse::HomeAll(result)
+}
+
+pub async fn override_readiness_check(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    check: ReadinessCheck,
+    reason: &str,
+) -> OperatorCommandResponse {
+    let readiness = app_state.lock().await.readiness.clone();
+    let result = readiness
+        .lock()
+        .await
+        .override_check(check, reason);
+    match &result {
+        Ok(()) => warn!(
+            "Readiness check overridden. check: {:?}, reason: {}, source: {:?}",
+            check,
+            reason.trim(),
+            source
+        ),
+        Err(e) => warn!("Readiness check override rejected. check: {:?}, error: {:?}", check, e),
+    }
+    OperatorCommandResponse::ReadinessOverride(result)
+}
+
+pub async fn start_job(
+    stack: &RouterStack,
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+) -> OperatorCommandResponse {
+    let readiness = app_state.lock().await.readiness.clone();
+    let blocking_checks = readiness.lock().await.blocking_checks();
+    if !blocking_checks.is_empty() {
+        warn!("Start job refused, machine not ready. checks: {:?}", blocking_checks);
+        return OperatorCommandResponse::StartJob(Err(StartJobError::NotReady(blocking_checks)));
+    }
+
+    let force_log = ForceLog::default();
+    let evidence_log = EvidenceLog::default();
+    let (
+        job_control,
+        job_config,
+        feeders,
+        placer,
+        flusher,
+        energy,
+        calibration,
+        calibrator,
+        inspector,
+        parking_tx,
+        storage,
+        app_event_rx,
+    ) = {
+        let app_state = app_state.lock().await;
+        let placer = app_placer(stack, &app_state, force_log.clone(), evidence_log.clone());
+        let calibrator = IoBoardNozzleCalibrator::new(stack.clone(), app_state.command_sequencer.clone());
+        (
+            app_state.job_control.clone(),
+            app_state.config.job.clone(),
+            app_state.feeders.clone(),
+            placer,
+            job_flusher(stack, &app_state),
+            app_state.energy.clone(),
+            app_state
+                .config
+                .nozzles
+                .calibration
+                .clone(),
+            calibrator,
+            machine_inspector(&app_state),
+            app_state.parking_tx.clone(),
+            app_state.storage.clone(),
+            app_state.event_tx.subscribe(),
+        )
+    };
+    let result = match placer {
+        Some(placer) => job_control
+            .lock()
+            .await
+            .start()
+            .map(|(job, checkpoint)| (job, checkpoint, placer)),
+        None => Err(StartJobError::NoMotion),
+    };
+    let result = match result {
+        Ok((job, checkpoint, placer)) => {
+            info!(
+                "Starting job. job: {}, resume: {}, source: {:?}",
+                job.name,
+                checkpoint.is_some(),
+                source
+            );
+            // not awaited on shutdown, the same as the camera managers
+            tokio::spawn(job_runner(
+                stack.clone(),
+                job,
+                checkpoint,
+                job_config,
+                feeders,
+                job_control,
+                placer,
+                flusher,
+                force_log,
+                evidence_log,
+                energy,
+                calibration,
+                calibrator,
+                inspector,
+                parking_tx,
+                storage,
+                app_event_rx,
+            ));
+            Ok(())
+        }
+        Err(e) => {
+            warn!("Start job refused. error: {:?}", e);
+            Err(e)
+        }
+    };
+    OperatorCommandResponse::StartJob(result)
+}
+
+pub async fn resolve_intervention(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    id: u32,
+    resolution: InterventionResolution,
+) -> OperatorCommandResponse {
+    let job_control = app_state
+        .lock()
+        .await
+        .job_control
+        .clone();
+    let result = job_control
+        .lock()
+        .await
+        .resolve(id, resolution);
+    match &result {
+        Ok(()) => info!(
+            "Intervention resolution received. id: {}, resolution: {:?}, source: {:?}",
+            id, resolution, source
+        ),
+        Err(e) => warn!(
+            "Intervention resolution rejected. id: {}, resolution: {:?}, error: {:?}",
+            id, resolution, e
+        ),
+    }
+    OperatorCommandResponse::InterventionResolved(result)
+}
+
+pub async fn fetch_job_checkpoint(app_state: &Arc<Mutex<AppState>>) -> OperatorCommandResponse {
+    let job_control = app_state
+        .lock()
+        .await
+        .job_control
+        .clone();
+    let checkpoint = job_control.lock().await.checkpoint();
+    OperatorCommandResponse::JobCheckpoint(checkpoint)
+}
+
+pub async fn confirm_resume(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    choice: ResumeChoice,
+) -> OperatorCommandResponse {
+    let job_control = app_state
+        .lock()
+        .await
+        .job_control
+        .clone();
+    let result = job_control
+        .lock()
+        .await
+        .confirm_resume(choice);
+    match &result {
+        Ok(()) => warn!("Interrupted job confirmed. choice: {:?}, source: {:?}", choice, source),
+        Err(e) => warn!(
+            "Interrupted job confirmation rejected. choice: {:?}, error: {:?}",
+            choice, e
+        ),
+    }
+    OperatorCommandResponse::ResumeConfirmed(result)
+}
+
+pub async fn estimate_job(app_state: &Arc<Mutex<AppState>>, source: &Address, offset: u32) -> OperatorCommandResponse {
+    let (job_control, simulation, motion, transform, heads, heights) = {
+        let app_state = app_state.lock().await;
+        let config = &app_state.config;
+        let heights = PartHeights::new(&config.feeders.feeders, &config.parts);
+        (
+            app_state.job_control.clone(),
+            config.job.simulation.clone(),
+            config.parking.clone(),
+            CoordinateTransform::new(&config.axis_corrections),
+            config.heads.clone(),
+            heights,
+        )
+    };
+    let job = job_control.lock().await.job().cloned();
+    let result = match job {
+        Some(job) => simulate_job(&job, &simulation, &motion, &transform, &heads, &heights)
+            .map(|estimate| estimate_page(estimate, offset as usize)),
+        None => Err(EstimateError::NoJob),
+    };
+    match &result {
+        Ok(estimate) => info!(
+            "Job simulated. job: {}, duration_ms: {}, placements: {}, offset: {}, source: {:?}",
+            estimate.job, estimate.duration_ms, estimate.total, offset, source
+        ),
+        Err(e) => warn!("Job simulation failed. error: {:?}", e),
+    }
+    OperatorCommandResponse::JobEstimate(result)
+}
+
+pub async fn set_feeder_count(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    feeder: &FeederId,
+    count: u32,
+) -> OperatorCommandResponse {
+    let feeders = app_state.lock().await.feeders.clone();
+    let result = feeders
+        .lock()
+        .await
+        .set_count(feeder, count);
+    match &result {
+        Ok(()) => info!(
+            "Feeder count set. feeder: {}, count: {}, source: {:?}",
+            feeder, count, source
+        ),
+        Err(e) => warn!("Feeder count rejected. feeder: {}, error: {:?}", feeder, e),
+    }
+    OperatorCommandResponse::FeederCount(result)
+}
+
+pub async fn run_test_pattern(
+    stack: &RouterStack,
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    pattern: &TestPattern,
+    kind: &TestShotKind,
+) -> OperatorCommandResponse {
+    let (job_control, test_area, feeders, heads, placer, app_event_rx) = {
+        let app_state = app_state.lock().await;
+        // the forces of test shots are not reported
+        let placer = app_placer(stack, &app_state, ForceLog::default(), EvidenceLog::default());
+        (
+            app_state.job_control.clone(),
+            app_state.test_area.clone(),
+            app_state.feeders.clone(),
+            app_state.config.heads.clone(),
+            placer,
+            app_state.event_tx.subscribe(),
+        )
+    };
+    let result = match placer {
+        Some(placer) => start_test_pattern(&job_control, &test_area, &feeders, &heads, pattern, kind)
+            .await
+            .map(|positions| (positions, placer)),
+        None => Err(TestShotError::NoMotion),
+    };
+    match result {
+        Ok((positions, placer)) => {
+            info!(
+                "Starting test shots. pattern: {:?}, kind: {:?}, source: {:?}",
+                pattern, kind, source
+            );
+            let shots = positions.len() as u32;
+            // not awaited on shutdown, the same as the job runner
+            tokio::spawn(test_shot_runner(
+                stack.clone(),
+                positions,
+                kind.clone(),
+                feeders,
+                job_control,
+                placer,
+                app_event_rx,
+            ));
+            OperatorCommandResponse::TestPatternStarted(Ok(shots))
+        }
+        Err(e) => {
+            warn!(
+                "Test shots refused. pattern: {:?}, kind: {:?}, error: {:?}",
+                pattern, kind, e
+            );
+            OperatorCommandResponse::TestPatternStarted(Err(e))
+        }
+    }
+}
+
+pub async fn clear_test_area(app_state: &Arc<Mutex<AppState>>, source: &Address) -> OperatorCommandResponse {
+    let (job_control, test_area) = {
+        let app_state = app_state.lock().await;
+        (app_state.job_control.clone(), app_state.test_area.clone())
+    };
+    // the shots of a running pattern are still being taken in the used rows
+    let result = match job_control.lock().await.is_running() {
+        true => Err(TestShotError::Running),
+        false => {
+            test_area.lock().await.clear();
+            Ok(())
+        }
+    };
+    match &result {
+        Ok(()) => info!("Test area cleared. source: {:?}", source),
+        Err(e) => warn!("Test area clear rejected. error: {:?}", e),
+    }
+    OperatorCommandResponse::TestAreaCleared(result)
+}
+
+pub async fn set_topic_tap(
+    stack: &RouterStack,
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    enabled: bool,
+) -> OperatorCommandResponse {
+    let (topic_tap, app_event_rx) = {
+        let app_state = app_state.lock().await;
+        (app_state.topic_tap.clone(), app_state.event_tx.subscribe())
+    };
+    let mut topic_tap = topic_tap.lock().await;
+    let result = match enabled {
+        true => topic_tap
+            .start()
+            .map(|(topics, cancel)| {
+                info!("Starting topic tap. source: {:?}", source);
+                // not awaited on shutdown, the same as the job runner
+                tokio::spawn(topic_tap_runner(
+                    stack.clone(),
+                    topics,
+                    topic_tap.config().clone(),
+                    cancel,
+                    app_event_rx,
+                ));
+            }),
+        false => {
+            if topic_tap.stop() {
+                info!("Topic tap stopped by the operator. source: {:?}", source);
+            }
+            Ok(())
+        }
+    };
+    if let Err(e) = &result {
+        warn!("Topic tap refused. error: {:?}", e);
+    }
+    OperatorCommandResponse::TopicTap(result)
+}
+
+pub fn set_log_level(source: &Address, module: Option<&str>, level: LogLevel) -> OperatorCommandResponse {
+    let result = logging::set_level(module, level);
+    match &result {
+        Ok(_) => info!(
+            "Log level changed. module: {:?}, level: {:?}, source: {:?}",
+            module, level, source
+        ),
+        Err(e) => warn!("Log level change rejected. error: {:?}", e),
+    }
+    OperatorCommandResponse::LogLevels(result)
+}
+
+pub fn clear_log_level(source: &Address, module: &str) -> OperatorCommandResponse {
+    let result = logging::clear_level(module);
+    match &result {
+        Ok(_) => info!("Log level cleared. module: {}, source: {:?}", module, source),
+        Err(e) => warn!("Log level clear rejected. error: {:?}", e),
+    }
+    OperatorCommandResponse::LogLevels(result)
+}
+
+pub async fn apply_config(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    changes: &[ConfigChange],
+) -> OperatorCommandResponse {
+    let feeders = app_state.lock().await.feeders.clone();
+    let result = feeders
+        .lock()
+        .await
+        .apply_config(changes);
+    match &result {
+        Ok(()) => info!("Config changes applied. changes: {:?}, source: {:?}", changes, source),
+        Err(e) => warn!("Config changes rejected. error: {:?}", e),
+    }
+    OperatorCommandResponse::ConfigApplied(result)
+}
+
+pub async fn override_axis_limit(
+    stack: &RouterStack,
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    request: &LimitOverrideRequest,
+) -> OperatorCommandResponse {
+    let (limit_overrides, overrider, app_event_rx) = {
+        let app_state = app_state.lock().await;
+        let overrider = IoBoardLimitOverrider::new(stack.clone(), app_state.command_sequencer.clone());
+        (
+            app_state.limit_overrides.clone(),
+            overrider,
+            app_state.event_tx.subscribe(),
+        )
+    };
+    let result = override_limit(stack, &limit_overrides, overrider, request, app_event_rx).await;
+    // the access code is never logged
+    match &result {
+        Ok(()) => warn!(
+            "Axis limit overridden. axis: {}, limit: {:?}, operator: {}, reason: {}, duration: {}s, source: {:?}",
+            request.axis,
+            request.limit,
+            request.operator,
+            request.reason.trim(),
+            request.duration_s,
+            source
+        ),
+        Err(e) => warn!(
+            "Axis limit override refused. axis: {}, limit: {:?}, operator: {}, error: {:?}, source: {:?}",
+            request.axis, request.limit, request.operator, e, source
+        ),
+    }
+    OperatorCommandResponse::AxisLimitOverridden(result)
+}
+
+pub async fn clear_axis_limit_override(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    axis: &str,
+    limit: AxisLimit,
+) -> OperatorCommandResponse {
+    let limit_overrides = app_state
+        .lock()
+        .await
+        .limit_overrides
+        .clone();
+    let result = limit_overrides
+        .lock()
+        .await
+        .clear(axis, limit);
+    match &result {
+        Ok(()) => info!(
+            "Axis limit override cleared by the operator. axis: {}, limit: {:?}, source: {:?}",
+            axis, limit, source
+        ),
+        Err(e) => warn!(
+            "Axis limit override clear rejected. axis: {}, limit: {:?}, error: {:?}",
+            axis, limit, e
+        ),
+    }
+    OperatorCommandResponse::AxisLimitOverrideCleared(result)
+}
+
+pub async fn run_resonance_sweep(
+    stack: &RouterStack,
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    settings: &ResonanceSweepSettings,
+) -> OperatorCommandResponse {
+    let (job_control, request, sweeper, app_event_rx) = {
+        let app_state = app_state.lock().await;
+        let request = sweep_request(
+            settings,
+            &app_state.config.homing.axes,
+            app_state.config.parking.steps_per_mm,
+        );
+        let sweeper = IoBoardSweeper::new(stack.clone(), app_state.command_sequencer.clone());
+        (
+            app_state.job_control.clone(),
+            request,
+            sweeper,
+            app_state.event_tx.subscribe(),
+        )
+    };
+    let result = match request {
+        Ok(request) => job_control
+            .lock()
+            .await
+            .start_resonance_sweep()
+            .map(|()| request),
+        Err(e) => Err(e),
+    };
+    let result = match result {
+        Ok(request) => {
+            info!(
+                "Starting resonance sweep. axis: {}, request: {:?}, source: {:?}",
+                settings.axis, request, source
+            );
+            // not awaited on shutdown, the same as homing
+            tokio::spawn(resonance_sweep_runner(
+                stack.clone(),
+                sweeper,
+                settings.axis.clone(),
+                request,
+                job_control,
+                app_event_rx,
+            ));
+            Ok(())
+        }
+        Err(e) => {
+            warn!("Resonance sweep refused. axis: {}, error: {:?}", settings.axis, e);
+            Err(e)
+        }
+    };
+    OperatorCommandResponse::ResonanceSweepStarted(result)
+}
+
+pub fn estop(stack: &RouterStack, source: &Address) -> OperatorCommandResponse {
+    warn!("E-stop requested. source: {:?}", source);
+    OperatorCommandResponse::EStop(publish_estop(stack, EStop::Stop(EStopSource::Server)))
+}
+
+pub fn reset_estop(stack: &RouterStack, source: &Address) -> OperatorCommandResponse {
+    info!("E-stop reset requested. source: {:?}", source);
+    OperatorCommandResponse::EStop(publish_estop(stack, EStop::Reset(EStopSource::Server)))
+}
+
+pub async fn fetch_machine_geometry(app_state: &Arc<Mutex<AppState>>) -> OperatorCommandResponse {
+    let app_state = app_state.lock().await;
+    OperatorCommandResponse::MachineGeometry(machine_geometry(&app_state.config.axis_corrections))
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn list_cameras(app_state: &Arc<Mutex<AppState>>) -> OperatorCommandResponse {
+    let app_state = app_state.lock().await;
+    OperatorCommandResponse::Cameras(camera_infos(&app_state.config.cameras))
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn list_captures(app_state: &Arc<Mutex<AppState>>, offset: u32) -> OperatorCommandResponse {
+    let capture_store = app_state
+        .lock()
+        .await
+        .capture_store
+        .clone();
+    OperatorCommandResponse::Captures(
+        capture_store
+            .list_page(offset as usize)
+            .await,
+    )
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn fetch_capture(app_state: &Arc<Mutex<AppState>>, key: &CaptureKey, offset: u32) -> OperatorCommandResponse {
+    let capture_store = app_state
+        .lock()
+        .await
+        .capture_store
+        .clone();
+    OperatorCommandResponse::CaptureChunk(
+        capture_store
+            .read_chunk(key, offset as usize)
+            .await,
+    )
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn fetch_capture_thumbnail(
+    app_state: &Arc<Mutex<AppState>>,
+    key: &CaptureKey,
+    offset: u32,
+) -> OperatorCommandResponse {
+    let capture_store = app_state
+        .lock()
+        .await
+        .capture_store
+        .clone();
+    OperatorCommandResponse::CaptureChunk(
+        capture_store
+            .read_thumbnail_chunk(key, offset as usize)
+            .await,
+    )
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn fetch_capture_annotations(app_state: &Arc<Mutex<AppState>>, key: &CaptureKey) -> OperatorCommandResponse {
+    let capture_store = app_state
+        .lock()
+        .await
+        .capture_store
+        .clone();
+    OperatorCommandResponse::CaptureAnnotations(
+        capture_store
+            .read_annotations(key)
+            .await,
+    )
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn scan_code(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    target: &ScanTarget,
+) -> OperatorCommandResponse {
+    let result = scanning::scan(app_state, target).await;
+    match &result {
+        Ok(code) => info!(
+            "Code scanned. target: {:?}, code: {}, source: {:?}",
+            target, code, source
+        ),
+        Err(e) => warn!("Scan failed. target: {:?}, error: {:?}", target, e),
+    }
+    OperatorCommandResponse::CodeScanned(result)
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn verify_feeder_orientation(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    feeder: &FeederId,
+) -> OperatorCommandResponse {
+    let result = orientation::vision::verify(app_state, feeder).await;
+    match &result {
+        Ok(orientation) => info!(
+            "Feeder orientation verified. feeder: {}, orientation: {:?}, source: {:?}",
+            feeder, orientation, source
+        ),
+        Err(e) => warn!(
+            "Feeder orientation verification failed. feeder: {}, error: {:?}",
+            feeder, e
+        ),
+    }
+    OperatorCommandResponse::FeederOrientation(result)
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn list_templates(app_state: &Arc<Mutex<AppState>>, offset: u32) -> OperatorCommandResponse {
+    let template_store = app_state
+        .lock()
+        .await
+        .template_store
+        .clone();
+    OperatorCommandResponse::Templates(
+        template_store
+            .list_page(offset as usize)
+            .await,
+    )
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn create_template(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    name: &str,
+    kind: TemplateKind,
+    capture: &TemplateCapture,
+) -> OperatorCommandResponse {
+    let result = templates::create_template(app_state, name, kind, capture).await;
+    match &result {
+        Ok(_) => info!(
+            "Template created. name: {}, kind: {:?}, source: {:?}",
+            name, kind, source
+        ),
+        Err(e) => warn!("Template creation failed. name: {}, error: {:?}", name, e),
+    }
+    OperatorCommandResponse::TemplateSaved(result)
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn update_template(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    name: &str,
+    new_name: &str,
+    kind: TemplateKind,
+    capture: Option<&TemplateCapture>,
+) -> OperatorCommandResponse {
+    let result = templates::update_template(app_state, name, new_name, kind, capture).await;
+    match &result {
+        Ok(_) => info!(
+            "Template updated. name: {}, new_name: {}, source: {:?}",
+            name, new_name, source
+        ),
+        Err(e) => warn!("Template update failed. name: {}, error: {:?}", name, e),
+    }
+    OperatorCommandResponse::TemplateSaved(result)
+}
+
+#[cfg(feature = "machine-vision")]
+pub async fn delete_template(
+    app_state: &Arc<Mutex<AppState>>,
+    source: &Address,
+    name: &str,
+) -> OperatorCommandResponse {
+    let template_store = app_state
+        .lock()
+        .await
+        .template_store
+        .clone();
+    let result = template_store.delete(name).await;
+    match &result {
+        Ok(()) => info!("Template deleted. name: {}, source: {:?}", name, source),
+        Err(e) => warn!("Template deletion failed. name: {}, error: {:?}", name, e),
+    }
+    OperatorCommandResponse::TemplateDeleted(result)
+}
+
+/// It's possible that there is a queue of 'start streaming' requests for the same camera, e.g. when starting the
+/// camera takes longer than the timeout of the request, so the app state is locked while the camera is started.
+#[cfg(feature = "machine-vision")]
+#[allow(clippy::too_many_arguments)]
+pub async fn start_streaming(
+    stack: &RouterStack,
+    app_state: &Arc<Mutex<AppState>>,
+    clients: &Mutex<HashMap<CameraId, CameraClient>>,
+    camera_managers: &mut CameraManagers,
+    source: &Address,
+    identifier: CameraId,
+    port_id: u8,
+    fps: f32,
+) -> OperatorCommandResponse {
+    let app_state_clone = app_state.clone();
+    let app_state = app_state.lock().await;
+
+    let Some(camera_definition) = camera_definition_for_identifier(&app_state.config.cameras, &identifier) else {
+        return OperatorCommandResponse::CameraCommandResult(Err(CameraCommandError::new(
+            CameraCommandErrorCode::InvalidIdentifier,
+        )));
+    };
+    if clients
+        .lock()
+        .await
+        .contains_key(&identifier)
+    {
+        return OperatorCommandResponse::CameraCommandResult(Err(CameraCommandError::new(
+            CameraCommandErrorCode::Busy,
+        )));
+    }
+
+    let address = Address {
+        network_id: source.network_id,
+        node_id: source.node_id,
+        port_id,
+    };
+
+    let camera_shutdown_flag = CancellationToken::new();
+    let camera_manager = tokio::spawn(camera_manager(
+        identifier,
+        camera_definition.clone(),
+        address,
+        app_state_clone,
+        fps,
+        camera_shutdown_flag.clone(),
+        stack.clone(),
+    ));
+    camera_managers.insert(identifier, (camera_manager, camera_shutdown_flag));
+
+    // explict drop to keep the lock for longer.
+    drop(app_state);
+
+    OperatorCommandResponse::CameraCommandResult(Ok(CameraStreamerCommandResult::Acknowledged))
+}
+
+/// The camera manager is shut down by a task, the response is sent immediately.
+#[cfg(feature = "machine-vision")]
+pub fn stop_streaming(
+    camera_managers: &mut CameraManagers,
+    identifier: CameraId,
+    port_id: u8,
+) -> OperatorCommandResponse {
+    let Some((handle, shutdown_flag)) = camera_managers.remove(&identifier) else {
+        return OperatorCommandResponse::CameraCommandResult(Err(CameraCommandError::new(
+            CameraCommandErrorCode::NotStreaming,
+        )));
+    };
+
+    tokio::spawn(async move {
+        info!("Stopping camera. identifier: {}. port_id: {}", identifier, port_id);
+        shutdown_flag.cancel();
+        let _ = handle.await;
+        info!("Camera stopped. identifier: {}. port_id: {}", identifier, port_id);
+    });
+
+    OperatorCommandResponse::CameraCommandResult(Ok(CameraStreamerCommandResult::Acknowledged))
+}
+
+/// Captures a frame via the vision queue, see [`capture_listener`](super::capture_listener).
+#[cfg(feature = "machine-vision")]
+pub async fn capture(app_state: &Arc<Mutex<AppState>>, source: &Address, request: &CaptureRequest) -> CaptureResponse {
+    info!(
+        "Capture requested. camera: {}, pause_preview: {}, save: {}, source: {:?}",
+        request.camera, request.pause_preview, request.save, source
+    );
+    let (vision_queue, capture_store) = {
+        let app_state = app_state.lock().await;
+        (app_state.vision_queue.clone(), app_state.capture_store.clone())
+    };
+
+    let frame = vision_queue
+        .capture(VisionCaptureRequest {
+            camera: request.camera,
+            pause_preview: request.pause_preview,
+            metering_roi: None,
+        })
+        .await
+        .map_err(|e| {
+            warn!("Capture failed. identifier: {}, error: {:?}", request.camera, e);
+            CameraCommandError::new(CameraCommandErrorCode::CaptureFailed)
+        })?;
+
+    if request.save {
+        save_snapshot(&capture_store, request.camera, &frame).await;
+    }
+    Ok(CapturedFrame {
+        frame_number: frame.frame_number,
+        frame_timestamp: frame.frame_timestamp.into(),
+    })
+}
+
+#[cfg(feature = "machine-vision")]
+async fn save_snapshot(capture_store: &CaptureStore, identifier: CameraId, frame: &server_vision::CameraFrame) {
+    let key = CaptureKey::snapshot(identifier, &frame.frame_timestamp);
+    if let Err(e) = capture_store
+        .save(&key, &frame.jpeg_bytes, &[])
+        .await
+    {
+        warn!("Unable to save snapshot. key: {}, error: {:?}", key, e);
+    }
+}
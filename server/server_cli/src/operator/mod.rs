@@ -3,20 +3,49 @@ use std::pin::pin;
 use std::sync::Arc;
 use std::time::Duration;
 
+use ergot::endpoint;
 use ergot::toolkits::tokio_udp::RouterStack;
-use ergot::{Address, endpoint};
 use log::{error, info, warn};
-use operator_shared::camera::{
-    CameraCommand, CameraCommandError, CameraCommandErrorCode, CameraIdentifier, CameraStreamerCommandResult,
-};
+use machine_geometry::Point;
+use machine_ids::CameraId;
+use operator_shared::camera::CameraCommand;
+#[cfg(feature = "machine-vision")]
+use operator_shared::camera::{CaptureRequest, CaptureResponse};
 use operator_shared::commands::{OperatorCommandRequest, OperatorCommandResponse};
+use operator_shared::geometry::MachineGeometry;
+use operator_shared::test_area::{TestPattern, TestShotError, TestShotKind};
 use tokio::select;
 use tokio::sync::Mutex;
-use tokio_util::sync::CancellationToken;
 
 use crate::AppState;
 #[cfg(feature = "machine-vision")]
-use crate::camera::{CameraHandle, camera_definition_for_identifier, camera_manager};
+use crate::camera::CameraClient;
+#[cfg(feature = "machine-vision")]
+use crate::captures::FailureCaptures;
+use crate::config::{AxisCorrections, HeadDefinition, JobMode, MotionPlanning};
+use crate::coordinates::CoordinateTransform;
+use crate::dispensing::dispenser_config;
+use crate::feeders::Feeders;
+use crate::forces::{ExpectedForces, ForceLog};
+use crate::job::evidence::{EvidenceLog, MachineCamera};
+use crate::job::panel::{MachineInspector, NominalInspector};
+use crate::job::pick_place::{MachinePickAndPlacePlacer, PickAndPlacePlacer};
+#[cfg(feature = "machine-vision")]
+use crate::job::vision::{VisionInspector, VisionPlacementCamera, bad_mark_camera};
+use crate::job::{DryRunPlacer, HeadPlacer, JobControl, Placer, machine_placer};
+use crate::logging;
+use crate::motion::QueueFlusher;
+use crate::nozzles::IoBoardVacuum;
+use crate::parking::SetpointHeadMover;
+use crate::runout::rotator::SetpointRotator;
+use crate::test_area::TestArea;
+use crate::travel::PartHeights;
+
+mod commands;
+
+/// The camera managers started by the operator, by camera, see [`commands::start_streaming`].
+#[cfg(feature = "machine-vision")]
+type CameraManagers = HashMap<CameraId, (tokio::task::JoinHandle<()>, tokio_util::sync::CancellationToken)>;
 
 // TODO configure these more appropriately.
 //      for the operator TX we need to send camera streams and the broadcast packets from the IO boards,
@@ -30,6 +59,14 @@ endpoint!(
     "topic/operator/command"
 );
 
+#[cfg(feature = "machine-vision")]
+endpoint!(
+    OperatorCaptureEndpoint,
+    CaptureRequest,
+    CaptureResponse,
+    "topic/operator/capture"
+);
+
 pub async fn operator_listener(stack: RouterStack, app_state: Arc<Mutex<AppState>>) {
     let app_event_rx = {
         let app_state = app_state.lock().await;
@@ -40,7 +77,7 @@ pub async fn operator_listener(stack: RouterStack, app_state: Arc<Mutex<AppState
     #[cfg(feature = "machine-vision")]
     let (mut camera_managers, clients) = {
         let app_state = app_state.lock().await;
-        let clients: Arc<Mutex<HashMap<CameraIdentifier, CameraHandle>>> = app_state.camera_clients.clone();
+        let clients: Arc<Mutex<HashMap<CameraId, CameraClient>>> = app_state.camera_clients.clone();
 
         let mut camera_managers = HashMap::new();
 
@@ -79,76 +116,57 @@ pub async fn operator_listener(stack: RouterStack, app_state: Arc<Mutex<AppState
                         info!("heartbeat received from: {:?}, value: {}", msg.hdr.src, value);
                         OperatorCommandResponse::Acknowledged
                     }
+                    OperatorCommandRequest::HomeAll => commands::home_all(&stack, &app_state, source).await,
+                    OperatorCommandRequest::OverrideReadinessCheck { check, reason } => commands::override_readiness_check(&app_state, source, *check, reason).await,
+                    OperatorCommandRequest::StartJob => commands::start_job(&stack, &app_state, source).await,
+                    OperatorCommandRequest::ResolveIntervention { id, resolution } => commands::resolve_intervention(&app_state, source, *id, *resolution).await,
+                    OperatorCommandRequest::FetchJobCheckpoint => commands::fetch_job_checkpoint(&app_state).await,
+                    OperatorCommandRequest::ConfirmResume(choice) => commands::confirm_resume(&app_state, source, *choice).await,
+                    OperatorCommandRequest::EstimateJob { offset } => commands::estimate_job(&app_state, source, *offset).await,
+                    OperatorCommandRequest::SetFeederCount { feeder, count } => commands::set_feeder_count(&app_state, source, feeder, *count).await,
+                    OperatorCommandRequest::RunTestPattern { pattern, kind } => commands::run_test_pattern(&stack, &app_state, source, pattern, kind).await,
+                    OperatorCommandRequest::ClearTestArea => commands::clear_test_area(&app_state, source).await,
+                    OperatorCommandRequest::SetTopicTap(enabled) => commands::set_topic_tap(&stack, &app_state, source, *enabled).await,
+                    OperatorCommandRequest::FetchLogLevels => OperatorCommandResponse::LogLevels(Ok(logging::levels())),
+                    OperatorCommandRequest::SetLogLevel { module, level } => commands::set_log_level(source, module.as_deref(), *level),
+                    OperatorCommandRequest::ClearLogLevel { module } => commands::clear_log_level(source, module),
+                    OperatorCommandRequest::ApplyConfig(changes) => commands::apply_config(&app_state, source, changes).await,
+                    OperatorCommandRequest::OverrideAxisLimit(request) => commands::override_axis_limit(&stack, &app_state, source, request).await,
+                    OperatorCommandRequest::ClearAxisLimitOverride { axis, limit } => commands::clear_axis_limit_override(&app_state, source, axis, *limit).await,
+                    OperatorCommandRequest::RunResonanceSweep(settings) => commands::run_resonance_sweep(&stack, &app_state, source, settings).await,
+                    OperatorCommandRequest::EStop => commands::estop(&stack, source),
+                    OperatorCommandRequest::ResetEStop => commands::reset_estop(&stack, source),
+                    OperatorCommandRequest::FetchMachineGeometry => commands::fetch_machine_geometry(&app_state).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::ListCameras => commands::list_cameras(&app_state).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::ListCaptures { offset } => commands::list_captures(&app_state, *offset).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::FetchCapture { key, offset } => commands::fetch_capture(&app_state, key, *offset).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::FetchCaptureThumbnail { key, offset } => commands::fetch_capture_thumbnail(&app_state, key, *offset).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::FetchCaptureAnnotations { key } => commands::fetch_capture_annotations(&app_state, key).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::ScanCode(target) => commands::scan_code(&app_state, source, target).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::VerifyFeederOrientation { feeder } => commands::verify_feeder_orientation(&app_state, source, feeder).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::ListTemplates { offset } => commands::list_templates(&app_state, *offset).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::CreateTemplate { name, kind, capture } => commands::create_template(&app_state, source, name, *kind, capture).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::UpdateTemplate { name, new_name, kind, capture } => commands::update_template(&app_state, source, name, new_name, *kind, capture.as_ref()).await,
+                    #[cfg(feature = "machine-vision")]
+                    OperatorCommandRequest::DeleteTemplate { name } => commands::delete_template(&app_state, source, name).await,
                     #[cfg(feature = "machine-vision")]
                     OperatorCommandRequest::CameraCommand(identifier, camera_command) => {
                         info!("camera command received from: {:?}, identifier: {}, command: {:?}", msg.hdr.src, identifier, camera_command);
                         match camera_command {
                             CameraCommand::StartStreaming { port_id, fps } => {
-
-                                // It's possible that we have a queue of 'start streaming' requests for the same camera, so we need to
-                                // handle repeated requests to start the same camera, so we lock the app_state during init.
-                                // this can happen if starting the camera takes longer than the timeout for the 'StartStreaming' response to be sent
-                                // or due to tokio scheduling this function again at the `.await`'s below or in the start camera code.
-
-                                let app_state_clone = app_state.clone();
-                                let app_state = app_state.lock().await;
-
-                                let camera_definition = {
-                                    let Some(camera_definition) = camera_definition_for_identifier(&app_state.config.cameras, identifier) else {
-                                        return OperatorCommandResponse::CameraCommandResult(
-                                            Err(CameraCommandError::new(CameraCommandErrorCode::InvalidIdentifier))
-                                        )
-                                    };
-
-                                    let clients = clients.lock().await;
-                                    if clients.contains_key(&identifier) {
-                                        return OperatorCommandResponse::CameraCommandResult(
-                                            Err(CameraCommandError::new(CameraCommandErrorCode::Busy))
-                                        )
-                                    }
-                                    camera_definition.clone()
-                                };
-
-                                let address = Address {
-                                    network_id: source.network_id,
-                                    node_id: source.node_id,
-                                    port_id: *port_id
-                                };
-
-                                let camera_shutdown_flag = CancellationToken::new();
-                                let camera_manager = tokio::spawn(camera_manager(*identifier, camera_definition, address, app_state_clone, *fps, camera_shutdown_flag.clone(), stack.clone()));
-                                camera_managers.insert(*identifier, (camera_manager, camera_shutdown_flag));
-
-                                // explict drop to keep the lock for longer.
-                                drop(app_state);
-
-                                OperatorCommandResponse::CameraCommandResult(
-                                    Ok(CameraStreamerCommandResult::Acknowledged)
-                                )
+                                commands::start_streaming(&stack, &app_state, &clients, &mut camera_managers, source, *identifier, *port_id, *fps).await
                             }
-                            CameraCommand::StopStreaming { port_id } => {
-                                if let Some((handle, shutdown_flag)) = camera_managers.remove(&identifier) {
-                                    // spawn a task to shutdown the camera manager, then respond immediately.
-                                    tokio::spawn({
-                                        let port_id = *port_id;
-                                        let identifier = *identifier;
-                                        async move {
-                                            info!("Stopping camera. identifier: {}. port_id: {}", identifier, port_id);
-                                            shutdown_flag.cancel();
-                                            let _ = handle.await;
-                                            info!("Camera stopped. identifier: {}. port_id: {}", identifier, port_id);
-                                        }
-                                    });
-
-                                    OperatorCommandResponse::CameraCommandResult(
-                                        Ok(CameraStreamerCommandResult::Acknowledged)
-                                    )
-                                } else {
-                                    OperatorCommandResponse::CameraCommandResult(
-                                        Err(CameraCommandError::new(CameraCommandErrorCode::NotStreaming))
-                                    )
-                                }
-                            },
+                            CameraCommand::StopStreaming { port_id } => commands::stop_streaming(&mut camera_managers, *identifier, *port_id),
                         }
                     }
                 }
@@ -168,7 +186,215 @@ pub async fn operator_listener(stack: RouterStack, app_state: Arc<Mutex<AppState
             shutdown_flag.cancel();
             let _ = handle.await;
         }
+
+        // don't wait for the grace period of the captures
+        let camera_captures = app_state
+            .lock()
+            .await
+            .camera_captures
+            .clone();
+        camera_captures.stop_all().await;
     }
 
     info!("Operator command server stopped");
 }
+
+/// Serves the [`OperatorCaptureEndpoint`], frames are captured by this task rather than by the [`operator_listener`], so
+/// that the operator commands, and the heartbeats, are not held up while waiting for a frame.
+#[cfg(feature = "machine-vision")]
+pub async fn capture_listener(stack: RouterStack, app_state: Arc<Mutex<AppState>>) {
+    let app_event_rx = app_state
+        .lock()
+        .await
+        .event_tx
+        .subscribe();
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    // the captures are queued by the vision queue anyway, the same size as the command server
+    let server_socket = stack
+        .endpoints()
+        .bounded_server::<OperatorCaptureEndpoint, 3>(None);
+    let server_socket = pin!(server_socket);
+    let mut hdl = server_socket.attach();
+
+    info!("Operator capture server, port_id: {}", hdl.port());
+
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                info!("operator shutdown requested, stopping capture server");
+                break
+            }
+            r = hdl.serve_full(async |msg| commands::capture(&app_state, &msg.hdr.src, &msg.t).await) => {
+                if let Err(e) = r {
+                    error!("Error sending capture response. e: {:?}", e);
+                }
+            }
+        }
+    }
+
+    info!("Operator capture server stopped");
+}
+
+/// Returns the positions of the shots, the caller must run them, see
+/// [`test_shot_runner`](crate::test_area::test_shot_runner).
+///
+/// The feeder or head is checked before the test area is used, so that a refused pattern does not use any rows.
+async fn start_test_pattern(
+    job_control: &Mutex<JobControl>,
+    test_area: &Mutex<TestArea>,
+    feeders: &Mutex<Feeders>,
+    heads: &[HeadDefinition],
+    pattern: &TestPattern,
+    kind: &TestShotKind,
+) -> Result<Vec<Point>, TestShotError> {
+    match kind {
+        TestShotKind::Place {
+            feeder,
+        } => {
+            feeders
+                .lock()
+                .await
+                .stock(feeder)
+                .map_err(|_| TestShotError::UnknownFeeder)?;
+        }
+        TestShotKind::Dispense {
+            head,
+        } => {
+            dispenser_config(heads, head).ok_or(TestShotError::UnknownHead)?;
+        }
+    }
+
+    let mut job_control = job_control.lock().await;
+    if job_control.is_running() {
+        return Err(TestShotError::Running);
+    }
+    let positions = test_area.lock().await.plan(pattern)?;
+    job_control.start_test_shots()?;
+    Ok(positions)
+}
+
+/// See [`machine_placer`], the forces are checked against the parts library.
+///
+/// `None` in [`JobMode::Machine`] unless an io board follows the moves planned by the server, the parts can't be placed.
+fn app_placer(
+    stack: &RouterStack,
+    app_state: &AppState,
+    force_log: ForceLog,
+    evidence_log: EvidenceLog,
+) -> Option<impl Placer + Send + use<>> {
+    let config = &app_state.config;
+    let head = match config.job.mode {
+        JobMode::Machine => HeadPlacer::PickAndPlace(head_placer(stack, app_state)?),
+        JobMode::DryRun => HeadPlacer::DryRun(DryRunPlacer),
+    };
+    Some(machine_placer(
+        head,
+        stack.clone(),
+        app_state.command_sequencer.clone(),
+        &config.heads,
+        app_state.nozzle_runout.clone(),
+        app_state.force_tx.subscribe(),
+        ExpectedForces::new(&config.feeders.feeders, &config.parts),
+        force_log,
+        evidence_camera(app_state),
+        config.job.evidence.clone(),
+        evidence_log,
+    ))
+}
+
+/// Picks and places the parts with the nozzle of the pick and place configuration, `None` unless an io board follows
+/// the moves planned by the server.
+fn head_placer(stack: &RouterStack, app_state: &AppState) -> Option<MachinePickAndPlacePlacer> {
+    let motion = app_state.head_motion.as_ref()?;
+    let config = &app_state.config;
+    let mover = SetpointHeadMover::new(
+        motion.batcher.clone(),
+        motion.rate_hz,
+        &config.parking,
+        motion.position_history.clone(),
+        motion.safety_rx.clone(),
+    );
+    let rotator = SetpointRotator::new(
+        motion.batcher.clone(),
+        motion.rate_hz,
+        &config.runout,
+        motion.safety_rx.clone(),
+    );
+    Some(PickAndPlacePlacer::new(
+        mover,
+        rotator,
+        IoBoardVacuum::new(stack.clone(), app_state.command_sequencer.clone()),
+        app_state.feeders.clone(),
+        CoordinateTransform::new(&config.axis_corrections),
+        PartHeights::new(&config.feeders.feeders, &config.parts),
+        &config.parking,
+        config.job.pick_place.clone(),
+    ))
+}
+
+/// The axes of the head are stopped when a job is paused, braking at the acceleration limit of the parking moves, see
+/// [`QueueFlusher`], `None` unless an io board follows setpoints planned by the server.
+fn job_flusher(stack: &RouterStack, app_state: &AppState) -> Option<QueueFlusher> {
+    let config = &app_state.config;
+    let server_planned = config
+        .io_boards
+        .iter()
+        .any(|io_board| matches!(io_board.planning, MotionPlanning::Server { .. }));
+    if !server_planned {
+        return None;
+    }
+
+    let parking = &config.parking;
+    Some(QueueFlusher::new(
+        stack.clone(),
+        app_state.command_sequencer.clone(),
+        vec![parking.axes.x, parking.axes.y, parking.axes.z],
+        parking.max_acceleration * parking.steps_per_mm,
+    ))
+}
+
+/// The evidence of the placements is captured with the first down camera, if enabled, see
+/// [`EvidencePlacer`](crate::job::evidence::EvidencePlacer).
+fn evidence_camera(app_state: &AppState) -> Option<MachineCamera> {
+    if !app_state.config.job.evidence.enabled {
+        return None;
+    }
+
+    #[cfg(feature = "machine-vision")]
+    if let Some(camera) = bad_mark_camera(&app_state.config.cameras) {
+        return Some(VisionPlacementCamera::new(
+            app_state.vision_queue.clone(),
+            camera,
+            app_state.config.job.evidence.clone(),
+            FailureCaptures::new(app_state.capture_store.clone(), "evidence"),
+        ));
+    }
+
+    warn!("Placement evidence enabled, but there is no down camera");
+    None
+}
+
+/// Bad marks are detected with the first down camera, if there is one.
+#[cfg_attr(not(feature = "machine-vision"), allow(unused_variables))]
+fn machine_inspector(app_state: &AppState) -> MachineInspector {
+    #[cfg(feature = "machine-vision")]
+    if let Some(camera) = bad_mark_camera(&app_state.config.cameras) {
+        return MachineInspector::Vision(VisionInspector::new(
+            app_state.vision_queue.clone(),
+            camera,
+            app_state.config.job.bad_marks.clone(),
+            FailureCaptures::new(app_state.capture_store.clone(), "bad-mark"),
+        ));
+    }
+
+    MachineInspector::Nominal(NominalInspector)
+}
+
+fn machine_geometry(corrections: &AxisCorrections) -> MachineGeometry {
+    MachineGeometry {
+        skew_degrees: corrections.skew_degrees as f32,
+        scale_x: corrections.x.scale as f32,
+        scale_y: corrections.y.scale as f32,
+    }
+}
@@ -0,0 +1,101 @@
+//! Verifying the orientation of the tape of a feeder.
+//!
+//! Reversed tape, or a reel of parts taped the wrong way round, places every part of the feeder rotated.  After a reel
+//! is loaded the first pocket is captured and the polarity mark of the part is located, if the mark is not in the
+//! corner given by the feeder definition, see [`FeederDefinition::polarity_mark`], the tape is reversed and parts are
+//! not picked from the feeder.
+//!
+//! [`FeederDefinition::polarity_mark`]: crate::config::FeederDefinition::polarity_mark
+
+use std::future::Future;
+
+use log::{debug, info, warn};
+use machine_ids::FeederId;
+use operator_shared::feeders::TapeOrientation;
+use operator_shared::vision::OrientationError;
+use tokio::sync::Mutex;
+
+use crate::config::{OrientationConfig, PolarityCorner};
+use crate::feeders::Feeders;
+
+#[cfg(feature = "machine-vision")]
+pub mod vision;
+
+#[cfg(test)]
+mod tests;
+
+/// Inspects the first pocket of a feeder.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait PocketInspector {
+    /// The fraction of each corner of the part covered by its polarity mark, see [`mark_corner`].
+    fn mark_coverage<'a>(
+        &'a mut self,
+    ) -> impl Future<Output = Result<[(PolarityCorner, f32); 4], OrientationError>> + Send + 'a;
+}
+
+/// The corner the polarity mark is in, `None` if no corner is covered enough, or if no corner stands out, e.g. an
+/// empty pocket or the part is not centered in the image.
+pub fn mark_corner(coverage: &[(PolarityCorner, f32)], config: &OrientationConfig) -> Option<PolarityCorner> {
+    let (corner, most) = coverage
+        .iter()
+        .copied()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    if most < config.min_coverage {
+        return None;
+    }
+
+    let dominant = coverage
+        .iter()
+        .filter(|(other, _)| *other != corner)
+        .all(|(_, other_coverage)| most >= other_coverage * config.min_dominance);
+    match dominant {
+        true => Some(corner),
+        false => None,
+    }
+}
+
+/// Any corner but the expected one is reversed, a part rotated by 90 degrees is as wrong as one rotated by 180.
+pub fn orientation_of(expected: PolarityCorner, found: PolarityCorner) -> TapeOrientation {
+    match expected == found {
+        true => TapeOrientation::Correct,
+        false => TapeOrientation::Reversed,
+    }
+}
+
+/// Inspects the first pocket of the feeder and records the orientation of the tape, see [`Feeders::set_orientation`].
+pub async fn verify_orientation(
+    feeders: &Mutex<Feeders>,
+    inspector: &mut impl PocketInspector,
+    config: &OrientationConfig,
+    feeder: &FeederId,
+) -> Result<TapeOrientation, OrientationError> {
+    let expected = feeders
+        .lock()
+        .await
+        .polarity_mark(feeder)
+        .map_err(|_| OrientationError::UnknownFeeder)?
+        .ok_or(OrientationError::NoPolarityMark)?;
+
+    let coverage = inspector.mark_coverage().await?;
+    debug!("Polarity mark coverage. feeder: {}, coverage: {:?}", feeder, coverage);
+
+    let Some(found) = mark_corner(&coverage, config) else {
+        warn!("Polarity mark not found. feeder: {}, coverage: {:?}", feeder, coverage);
+        return Err(OrientationError::MarkNotFound);
+    };
+
+    let orientation = orientation_of(expected, found);
+    info!(
+        "Feeder orientation checked. feeder: {}, expected: {:?}, found: {:?}, orientation: {:?}",
+        feeder, expected, found, orientation
+    );
+    feeders
+        .lock()
+        .await
+        .set_orientation(feeder, orientation)
+        .map_err(|_| OrientationError::UnknownFeeder)?;
+
+    Ok(orientation)
+}
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+
+use machine_ids::FeederId;
+use operator_shared::feeders::{FeederError, FeederEvent, TapeOrientation};
+use operator_shared::vision::OrientationError;
+use tokio::sync::Mutex;
+
+use super::{PocketInspector, mark_corner, verify_orientation};
+use crate::config::{FeederDefinition, FeedersConfig, OrientationConfig, PolarityCorner};
+use crate::feeders::Feeders;
+
+struct FakeInspector {
+    coverage: [(PolarityCorner, f32); 4],
+    inspections: u32,
+}
+
+impl FakeInspector {
+    /// The mark covers half of the corner, the other corners are clean.
+    fn with_mark(corner: PolarityCorner) -> Self {
+        let coverage = [
+            PolarityCorner::TopLeft,
+            PolarityCorner::TopRight,
+            PolarityCorner::BottomLeft,
+            PolarityCorner::BottomRight,
+        ]
+        .map(|other| match other == corner {
+            true => (other, 0.5),
+            false => (other, 0.01),
+        });
+        Self {
+            coverage,
+            inspections: 0,
+        }
+    }
+}
+
+impl PocketInspector for FakeInspector {
+    fn mark_coverage<'a>(
+        &'a mut self,
+    ) -> impl Future<Output = Result<[(PolarityCorner, f32); 4], OrientationError>> + Send + 'a {
+        async move {
+            self.inspections += 1;
+            Ok(self.coverage)
+        }
+    }
+}
+
+/// "F1" has a part with its polarity mark in the top left corner, "F2" a part without a polarity mark.
+fn feeders() -> Mutex<Feeders> {
+    let config = FeedersConfig {
+        feeders: vec![
+            FeederDefinition {
+                name: FeederId::new("F1"),
+                low_stock_threshold: None,
+                polarity_mark: Some(PolarityCorner::TopLeft),
+                part: None,
+                identity: None,
+                calibration: None,
+                pick_position: None,
+            },
+            FeederDefinition {
+                name: FeederId::new("F2"),
+                low_stock_threshold: None,
+                polarity_mark: None,
+                part: None,
+                identity: None,
+                calibration: None,
+                pick_position: None,
+            },
+        ],
+        ..FeedersConfig::default()
+    };
+    let counts = BTreeMap::from([(FeederId::new("F1"), 100), (FeederId::new("F2"), 100)]);
+    Mutex::new(Feeders::new(&config, &counts))
+}
+
+#[test]
+pub fn mark_in_the_dominant_corner() {
+    // given
+    let config = OrientationConfig::default();
+    let coverage = [
+        (PolarityCorner::TopLeft, 0.02),
+        (PolarityCorner::TopRight, 0.03),
+        (PolarityCorner::BottomLeft, 0.01),
+        (PolarityCorner::BottomRight, 0.4),
+    ];
+
+    // expect
+    assert_eq!(mark_corner(&coverage, &config), Some(PolarityCorner::BottomRight));
+}
+
+#[test]
+pub fn no_mark_without_a_dominant_corner() {
+    // given
+    let config = OrientationConfig::default();
+    let empty_pocket = [
+        (PolarityCorner::TopLeft, 0.0),
+        (PolarityCorner::TopRight, 0.01),
+        (PolarityCorner::BottomLeft, 0.0),
+        (PolarityCorner::BottomRight, 0.02),
+    ];
+    let off_center = [
+        (PolarityCorner::TopLeft, 0.4),
+        (PolarityCorner::TopRight, 0.3),
+        (PolarityCorner::BottomLeft, 0.0),
+        (PolarityCorner::BottomRight, 0.0),
+    ];
+
+    // expect
+    assert_eq!(mark_corner(&empty_pocket, &config), None);
+    assert_eq!(mark_corner(&off_center, &config), None);
+}
+
+#[tokio::test]
+pub async fn correct_tape_is_verified() {
+    // given
+    let feeders = feeders();
+    let mut inspector = FakeInspector::with_mark(PolarityCorner::TopLeft);
+
+    // when
+    let result = verify_orientation(
+        &feeders,
+        &mut inspector,
+        &OrientationConfig::default(),
+        &FeederId::new("F1"),
+    )
+    .await;
+
+    // then
+    assert_eq!(result, Ok(TapeOrientation::Correct));
+    let mut feeders = feeders.lock().await;
+    assert_eq!(feeders.status().feeders[0].orientation, Some(TapeOrientation::Correct));
+    assert!(feeders.take_events().is_empty());
+}
+
+#[tokio::test]
+pub async fn reversed_tape_is_flagged() {
+    // given
+    let feeders = feeders();
+    let mut inspector = FakeInspector::with_mark(PolarityCorner::BottomRight);
+
+    // when
+    let result = verify_orientation(
+        &feeders,
+        &mut inspector,
+        &OrientationConfig::default(),
+        &FeederId::new("F1"),
+    )
+    .await;
+
+    // then
+    assert_eq!(result, Ok(TapeOrientation::Reversed));
+    let mut feeders = feeders.lock().await;
+    assert_eq!(feeders.take_events(), vec![FeederEvent::ReversedTape {
+        feeder: FeederId::new("F1")
+    }]);
+    assert_eq!(feeders.pick(&FeederId::new("F1")), Err(FeederError::ReversedTape));
+}
+
+#[tokio::test]
+pub async fn orientation_unchanged_when_the_mark_is_not_found() {
+    // given
+    let feeders = feeders();
+    let mut inspector = FakeInspector {
+        coverage: [
+            (PolarityCorner::TopLeft, 0.0),
+            (PolarityCorner::TopRight, 0.0),
+            (PolarityCorner::BottomLeft, 0.0),
+            (PolarityCorner::BottomRight, 0.0),
+        ],
+        inspections: 0,
+    };
+
+    // when
+    let result = verify_orientation(
+        &feeders,
+        &mut inspector,
+        &OrientationConfig::default(),
+        &FeederId::new("F1"),
+    )
+    .await;
+
+    // then
+    assert_eq!(result, Err(OrientationError::MarkNotFound));
+    assert_eq!(
+        feeders.lock().await.status().feeders[0].orientation,
+        Some(TapeOrientation::Unverified)
+    );
+}
+
+#[tokio::test]
+pub async fn nothing_to_verify() {
+    // given
+    let feeders = feeders();
+    let mut inspector = FakeInspector::with_mark(PolarityCorner::TopLeft);
+    let config = OrientationConfig::default();
+
+    // when
+    let no_mark = verify_orientation(&feeders, &mut inspector, &config, &FeederId::new("F2")).await;
+    let unknown = verify_orientation(&feeders, &mut inspector, &config, &FeederId::new("F3")).await;
+
+    // then
+    assert_eq!(no_mark, Err(OrientationError::NoPolarityMark));
+    assert_eq!(unknown, Err(OrientationError::UnknownFeeder));
+    // the pocket is not captured
+    assert_eq!(inspector.inspections, 0);
+}
@@ -0,0 +1,119 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use log::warn;
+use machine_ids::{CameraId, FeederId};
+use operator_shared::feeders::TapeOrientation;
+use operator_shared::vision::OrientationError;
+use server_common::camera::{CameraDefinition, CameraMounting};
+use server_vision::polarity::polarity_coverage;
+use tokio::sync::Mutex;
+
+use super::{PocketInspector, verify_orientation};
+use crate::AppState;
+use crate::captures::{FailureCaptures, failure_annotation};
+use crate::config::PolarityCorner;
+use crate::vision::{VisionCaptureRequest, VisionQueue};
+
+/// Captures the pocket with a down camera, frames are captured via the [`VisionQueue`], a frame the polarity mark
+/// can't be found in is saved to the `failures`.
+///
+/// FUTURE move the camera over the first pocket of the feeder, there is no XY motion yet, the operator positions the
+/// camera.
+pub struct VisionPocketInspector {
+    vision_queue: VisionQueue,
+    camera: CameraId,
+    contrast: u8,
+    failures: FailureCaptures,
+}
+
+impl VisionPocketInspector {
+    pub fn new(vision_queue: VisionQueue, camera: CameraId, contrast: u8, failures: FailureCaptures) -> Self {
+        Self {
+            vision_queue,
+            camera,
+            contrast,
+            failures,
+        }
+    }
+}
+
+impl PocketInspector for VisionPocketInspector {
+    fn mark_coverage<'a>(
+        &'a mut self,
+    ) -> impl Future<Output = Result<[(PolarityCorner, f32); 4], OrientationError>> + Send + 'a {
+        async move {
+            let camera = self.camera;
+            let frame = self
+                .vision_queue
+                .capture(VisionCaptureRequest {
+                    camera,
+                    pause_preview: true,
+                    metering_roi: None,
+                })
+                .await
+                .map_err(|e| {
+                    warn!("Pocket capture failed. camera: {}, error: {:?}", camera, e);
+                    OrientationError::CaptureFailed
+                })?;
+
+            let contrast = self.contrast;
+            // decoding takes longer than is acceptable for the runtime
+            let coverage = tokio::task::spawn_blocking({
+                let frame = frame.clone();
+                move || polarity_coverage(&frame.jpeg_bytes, contrast)
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+            .and_then(|result| result);
+            let coverage = match coverage {
+                Ok(coverage) => coverage,
+                Err(e) => {
+                    warn!("Unable to find the polarity mark. camera: {}, error: {:?}", camera, e);
+                    self.failures
+                        .save(&camera.to_string(), &frame, &[failure_annotation(&e)])
+                        .await;
+                    return Err(OrientationError::CaptureFailed);
+                }
+            };
+
+            Ok([
+                (PolarityCorner::TopLeft, coverage.top_left),
+                (PolarityCorner::TopRight, coverage.top_right),
+                (PolarityCorner::BottomLeft, coverage.bottom_left),
+                (PolarityCorner::BottomRight, coverage.bottom_right),
+            ])
+        }
+    }
+}
+
+/// Verifies the orientation of the tape of the feeder with the first down camera.
+pub async fn verify(app_state: &Arc<Mutex<AppState>>, feeder: &FeederId) -> Result<TapeOrientation, OrientationError> {
+    let (vision_queue, camera, config, feeders, failures) = {
+        let app_state = app_state.lock().await;
+        let camera = pocket_camera(&app_state.config.cameras).ok_or(OrientationError::NoCamera)?;
+        (
+            app_state.vision_queue.clone(),
+            camera,
+            app_state
+                .config
+                .feeders
+                .orientation
+                .clone(),
+            app_state.feeders.clone(),
+            FailureCaptures::new(app_state.capture_store.clone(), "orientation"),
+        )
+    };
+
+    let mut inspector = VisionPocketInspector::new(vision_queue, camera, config.contrast, failures);
+    verify_orientation(&feeders, &mut inspector, &config, feeder).await
+}
+
+/// The first down camera, cameras are identified by index, see
+/// [`camera_definition_for_identifier`](crate::camera::camera_definition_for_identifier).
+fn pocket_camera(cameras: &[CameraDefinition]) -> Option<CameraId> {
+    cameras
+        .iter()
+        .position(|definition| matches!(definition.mounting, CameraMounting::Down))
+        .map(|index| CameraId::new(index as u8))
+}
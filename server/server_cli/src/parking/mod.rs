@@ -0,0 +1,339 @@
+//! Parking the head, and raising Z to the safe height, when the machine becomes idle or faults.
+//!
+//! The [`ParkingPolicy`] gives the [`ParkAction`] for each [`ParkTrigger`], the triggers are sent by the job runner
+//! when a job ends, by the io board event listener when an io board reports a fault, and by the shutdown of the
+//! server.  Z is always raised to the safe height before X and Y move, the io boards can also be configured to refuse
+//! X and Y moves while Z is below the safe height, see `ioboard_shared::safe_z`.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use chrono::Utc;
+use ergot::FrameKind;
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot_util::ClientWrapper;
+use ioboard_shared::safe_z::{SafeZConfig, SafeZRequest};
+use log::{debug, error, info, warn};
+use machine_geometry::Point;
+use server_common::position::PositionHistory;
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{self, Duration};
+
+use crate::AppEvent;
+use crate::config::{ParkAction, ParkingConfig, ParkingPolicy};
+use crate::ioboard::batching::CommandBatcher;
+use crate::ioboard::{CommandSequencer, SafeZEndpoint};
+use crate::motion::{AxisMove, plan_setpoints, stream_setpoints};
+use crate::networking::dead_letter;
+use crate::safety::SafetyState;
+
+#[cfg(test)]
+mod tests;
+
+/// Triggers that arrive while a park is running are queued, further triggers are dropped.
+pub const TRIGGER_QUEUE_SIZE: usize = 4;
+
+/// The guards are configured again periodically, an io board that restarted has lost its configuration.
+const GUARD_CONFIGURE_INTERVAL: Duration = Duration::from_secs(10);
+
+const GUARD_DISCOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+const GUARD_REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+const GUARD_REQUEST_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParkTrigger {
+    Fault,
+    JobEnd,
+    Shutdown,
+}
+
+pub fn action_for(policy: &ParkingPolicy, trigger: ParkTrigger) -> &ParkAction {
+    match trigger {
+        ParkTrigger::Fault => &policy.on_fault,
+        ParkTrigger::JobEnd => &policy.on_job_end,
+        ParkTrigger::Shutdown => &policy.on_shutdown,
+    }
+}
+
+/// Moves the head, positions are in millimeters, in machine coordinates.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait HeadMover {
+    /// Completes once Z is at the height.
+    fn move_z<'a>(&'a mut self, z: f64) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+    /// Completes once X and Y are at the target, Z is not moved.
+    fn move_xy<'a>(&'a mut self, target: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+}
+
+/// Does the `action`, X and Y are only moved once Z has reached the safe height.
+///
+/// An unknown park position fails before anything is moved.
+pub async fn park<M: HeadMover>(mover: &mut M, config: &ParkingConfig, action: &ParkAction) -> anyhow::Result<()> {
+    let target = match action {
+        ParkAction::Nothing => return Ok(()),
+        ParkAction::RaiseZ => None,
+        ParkAction::Park(name) => {
+            let position = config
+                .positions
+                .iter()
+                .find(|position| position.name == *name)
+                .ok_or_else(|| anyhow!("Unknown park position. name: {}", name))?;
+            Some(position.position)
+        }
+    };
+
+    mover
+        .move_z(config.safe_z)
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "Unable to raise Z to the safe height. safe_z: {}, error: {:?}",
+                config.safe_z,
+                e
+            )
+        })?;
+
+    if let Some(target) = target {
+        mover.move_xy(target).await?;
+    }
+    Ok(())
+}
+
+/// Moves the axes of the head with setpoints, for io boards configured with
+/// [`MotionPlanning::Server`](crate::config::MotionPlanning::Server).
+///
+/// Not used while the setpoint streamer follows the repeated trajectory, see
+/// [`MotionConfig`](crate::config::MotionConfig), the setpoints of both would interleave.
+pub struct SetpointHeadMover {
+    batcher: CommandBatcher,
+    interval: Duration,
+    config: ParkingConfig,
+    position_history: PositionHistory,
+    safety_rx: watch::Receiver<SafetyState>,
+}
+
+impl SetpointHeadMover {
+    pub fn new(
+        batcher: CommandBatcher,
+        rate_hz: u32,
+        config: &ParkingConfig,
+        position_history: PositionHistory,
+        safety_rx: watch::Receiver<SafetyState>,
+    ) -> Self {
+        Self {
+            batcher,
+            interval: Duration::from_micros(1_000_000 / rate_hz.max(1) as u64),
+            config: config.clone(),
+            position_history,
+            safety_rx,
+        }
+    }
+
+    /// Plans the move, waiting until the safety state allows it, returns the setpoints of the move.
+    async fn plan(&mut self, axis: u8, target: f64) -> anyhow::Result<Vec<f64>> {
+        if !self
+            .safety_rx
+            .borrow()
+            .allows_new_moves()
+        {
+            info!("Waiting for the safety state to allow motion, axis: {}", axis);
+        }
+        let speed_factor = self
+            .safety_rx
+            .wait_for(SafetyState::allows_new_moves)
+            .await
+            .map_err(|_| anyhow!("Safety listener stopped"))?
+            .speed_factor();
+
+        // the axes only report their position while moving, an axis that hasn't moved is still at zero
+        let start = self
+            .position_history
+            .position_at(Utc::now())
+            .and_then(|position| position.axes.get(&axis).copied())
+            .unwrap_or(0.0);
+
+        let steps_per_mm = self.config.steps_per_mm;
+        let axis_move = AxisMove {
+            target: target * steps_per_mm,
+            max_jerk: self.config.max_jerk * steps_per_mm,
+            max_acceleration: self.config.max_acceleration * steps_per_mm * speed_factor,
+            max_velocity: self.config.max_velocity * steps_per_mm * speed_factor,
+        };
+
+        let setpoints = plan_setpoints(start, &axis_move, self.interval).map_err(|e| {
+            anyhow!(
+                "Unable to plan move. axis: {}, move: {:?}, error: {:?}",
+                axis,
+                axis_move,
+                e
+            )
+        })?;
+        debug!("Planned move, axis: {}, setpoints: {}", axis, setpoints.len());
+        Ok(setpoints)
+    }
+}
+
+impl HeadMover for SetpointHeadMover {
+    fn move_z<'a>(&'a mut self, z: f64) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let axis = self.config.axes.z;
+            let setpoints = self.plan(axis, z).await?;
+            stream_setpoints(&self.batcher, axis, &setpoints, self.interval).await;
+            Ok(())
+        }
+    }
+
+    fn move_xy<'a>(&'a mut self, target: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let axes = self.config.axes;
+            let x_setpoints = self.plan(axes.x, target.x).await?;
+            let y_setpoints = self.plan(axes.y, target.y).await?;
+            tokio::join!(
+                stream_setpoints(&self.batcher, axes.x, &x_setpoints, self.interval),
+                stream_setpoints(&self.batcher, axes.y, &y_setpoints, self.interval),
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Configures the safe-Z guard of every io board that has one.
+async fn configure_safe_z_guards(stack: &RouterStack, sequencer: &CommandSequencer, config: &ParkingConfig) {
+    let query = SocketQuery {
+        key: SafeZEndpoint::REQ_KEY.to_bytes(),
+        nash_req: NameRequirement::Any,
+        frame_kind: FrameKind::ENDPOINT_REQ,
+        broadcast: false,
+    };
+    let addresses = stack
+        .discovery()
+        .discover_sockets(4, GUARD_DISCOVERY_TIMEOUT, &query)
+        .await
+        .into_iter()
+        .map(|result| result.address);
+
+    let request = SafeZRequest::Configure(SafeZConfig {
+        z_axis: config.axes.z,
+        safe_height: (config.safe_z * config.steps_per_mm).round() as i64,
+    });
+    for address in addresses {
+        let client = stack
+            .endpoints()
+            .client::<SafeZEndpoint>(address, None);
+        let client = ClientWrapper::new(GUARD_REQUEST_TIMEOUT, client);
+        match client
+            .request_with_retry(&sequencer.sequenced(request), GUARD_REQUEST_ATTEMPTS)
+            .await
+        {
+            Ok(status) => debug!("Safe-Z guard configured. address: {:?}, status: {:?}", address, status),
+            Err(e) => {
+                warn!(
+                    "Unable to configure safe-Z guard. address: {:?}, error: {:?}",
+                    address, e
+                );
+                dead_letter::request_failed::<SafeZEndpoint>(address, GUARD_REQUEST_ATTEMPTS, &e);
+            }
+        }
+    }
+}
+
+async fn run_trigger<M: HeadMover>(mover: &mut M, config: &ParkingConfig, trigger: ParkTrigger) {
+    let action = action_for(&config.policy, trigger);
+    if *action == ParkAction::Nothing {
+        return;
+    }
+
+    info!("Parking. trigger: {:?}, action: {:?}", trigger, action);
+    let timeout = Duration::from_millis(config.timeout_ms);
+    match time::timeout(timeout, park(mover, config, action)).await {
+        Ok(Ok(())) => info!("Parked. trigger: {:?}, action: {:?}", trigger, action),
+        Ok(Err(e)) => error!(
+            "Parking failed. trigger: {:?}, action: {:?}, error: {:?}",
+            trigger, action, e
+        ),
+        Err(_) => error!(
+            "Parking timed out. trigger: {:?}, action: {:?}, timeout: {:?}",
+            trigger, action, timeout
+        ),
+    }
+}
+
+/// Does the action of the policy for each trigger, one at a time, and the shutdown action when the server shuts down.
+///
+/// Awaited on shutdown, so that the head is parked before the server exits.
+pub async fn parking_runner<M: HeadMover>(
+    stack: RouterStack,
+    mut mover: M,
+    config: ParkingConfig,
+    sequencer: Arc<CommandSequencer>,
+    mut trigger_rx: mpsc::Receiver<ParkTrigger>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    info!(
+        "Parking runner started. policy: {:?}, guard_xy_moves: {}",
+        config.policy, config.guard_xy_moves
+    );
+
+    let mut guard_interval = time::interval(GUARD_CONFIGURE_INTERVAL);
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                run_trigger(&mut mover, &config, ParkTrigger::Shutdown).await;
+                break
+            }
+            trigger = trigger_rx.recv() => {
+                let Some(trigger) = trigger else {
+                    break
+                };
+                run_trigger(&mut mover, &config, trigger).await;
+            }
+            _ = guard_interval.tick(), if config.guard_xy_moves => {
+                configure_safe_z_guards(&stack, &sequencer, &config).await;
+            }
+        }
+    }
+    info!("parking runner shutdown");
+}
+
+/// For the tasks that send triggers, a trigger is dropped if the queue is full, the parking runner is still busy with
+/// the earlier triggers.
+pub fn send_trigger(trigger_tx: &mpsc::Sender<ParkTrigger>, trigger: ParkTrigger) {
+    match trigger_tx.try_send(trigger) {
+        Ok(()) => {}
+        Err(mpsc::error::TrySendError::Full(_)) => warn!("Parking trigger dropped, queue full. trigger: {:?}", trigger),
+        // the parking runner isn't running, e.g. during a burn-in
+        Err(mpsc::error::TrySendError::Closed(_)) => {
+            debug!("Parking disabled, trigger ignored. trigger: {:?}", trigger)
+        }
+    }
+}
+
+/// Fails if a park position referenced by the policy is not configured, so that a typo is found on startup, not when
+/// the machine faults.
+pub fn validate(config: &ParkingConfig) -> anyhow::Result<()> {
+    for trigger in [ParkTrigger::Fault, ParkTrigger::JobEnd, ParkTrigger::Shutdown] {
+        let ParkAction::Park(name) = action_for(&config.policy, trigger) else {
+            continue;
+        };
+        if !config
+            .positions
+            .iter()
+            .any(|position| position.name == *name)
+        {
+            bail!(
+                "Unknown park position in parking policy. trigger: {:?}, name: {}",
+                trigger,
+                name
+            )
+        }
+    }
+    Ok(())
+}
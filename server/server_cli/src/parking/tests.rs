@@ -0,0 +1,154 @@
+use std::future::Future;
+
+use anyhow::bail;
+use machine_geometry::Point;
+
+use super::{HeadMover, ParkTrigger, action_for, park, validate};
+use crate::config::{ParkAction, ParkingConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Move {
+    Z(f64),
+    Xy(Point),
+}
+
+#[derive(Default)]
+struct FakeMover {
+    moves: Vec<Move>,
+    z_fails: bool,
+}
+
+impl HeadMover for FakeMover {
+    fn move_z<'a>(&'a mut self, z: f64) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            if self.z_fails {
+                bail!("Z axis not responding");
+            }
+            self.moves.push(Move::Z(z));
+            Ok(())
+        }
+    }
+
+    fn move_xy<'a>(&'a mut self, target: Point) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.moves.push(Move::Xy(target));
+            Ok(())
+        }
+    }
+}
+
+fn config() -> ParkingConfig {
+    let mut config = ParkingConfig::default();
+    config.positions[0].position = Point {
+        x: 200.0,
+        y: 150.0,
+    };
+    config
+}
+
+#[tokio::test]
+pub async fn park_raises_z_before_moving_xy() {
+    // given
+    let config = config();
+    let mut mover = FakeMover::default();
+
+    // when
+    let result = park(&mut mover, &config, &ParkAction::Park("home".to_string())).await;
+
+    // then
+    assert!(result.is_ok());
+    assert_eq!(mover.moves, vec![
+        Move::Z(config.safe_z),
+        Move::Xy(Point {
+            x: 200.0,
+            y: 150.0
+        }),
+    ]);
+}
+
+#[tokio::test]
+pub async fn raise_z_does_not_move_xy() {
+    // given
+    let config = config();
+    let mut mover = FakeMover::default();
+
+    // when
+    let result = park(&mut mover, &config, &ParkAction::RaiseZ).await;
+
+    // then
+    assert!(result.is_ok());
+    assert_eq!(mover.moves, vec![Move::Z(config.safe_z)]);
+}
+
+#[tokio::test]
+pub async fn nothing_does_not_move() {
+    // given
+    let config = config();
+    let mut mover = FakeMover::default();
+
+    // when
+    let result = park(&mut mover, &config, &ParkAction::Nothing).await;
+
+    // then
+    assert!(result.is_ok());
+    assert!(mover.moves.is_empty());
+}
+
+#[tokio::test]
+pub async fn xy_not_moved_when_z_can_not_be_raised() {
+    // given
+    let config = config();
+    let mut mover = FakeMover {
+        z_fails: true,
+        ..FakeMover::default()
+    };
+
+    // when
+    let result = park(&mut mover, &config, &ParkAction::Park("home".to_string())).await;
+
+    // then
+    assert!(result.is_err());
+    assert!(mover.moves.is_empty());
+}
+
+#[tokio::test]
+pub async fn unknown_park_position_fails_without_moving() {
+    // given
+    let config = config();
+    let mut mover = FakeMover::default();
+
+    // when
+    let result = park(&mut mover, &config, &ParkAction::Park("maintenance".to_string())).await;
+
+    // then
+    assert!(result.is_err());
+    assert!(mover.moves.is_empty());
+}
+
+#[test]
+pub fn policy_gives_the_action_for_each_trigger() {
+    // given
+    let config = ParkingConfig::default();
+
+    // expect
+    assert_eq!(action_for(&config.policy, ParkTrigger::Fault), &ParkAction::RaiseZ);
+    assert_eq!(
+        action_for(&config.policy, ParkTrigger::JobEnd),
+        &ParkAction::Park("home".to_string())
+    );
+    assert_eq!(
+        action_for(&config.policy, ParkTrigger::Shutdown),
+        &ParkAction::Park("home".to_string())
+    );
+}
+
+#[test]
+pub fn policy_referencing_an_unknown_park_position_is_invalid() {
+    // given
+    let mut config = ParkingConfig::default();
+    config.policy.on_shutdown = ParkAction::Park("maintenance".to_string());
+
+    // expect
+    assert!(validate(&ParkingConfig::default()).is_ok());
+    assert!(validate(&config).is_err());
+}
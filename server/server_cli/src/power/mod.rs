@@ -0,0 +1,454 @@
+//! The power meter of the machine, for duty-cycle and cost tracking, see [`PowerMeterConfig`].
+//!
+//! The meter is read at the configured interval, each reading is published as a [`PowerReading`], and the energy is
+//! integrated from the power of the readings.  The energy used while a job runs is written to the job report, see
+//! [`EnergyCounter::start_job`].
+//!
+//! The time between readings that are too far apart, e.g. while the meter did not respond, is not integrated, the
+//! energy used during such a gap is unknown and is missing from the totals.
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, topic};
+use ergot_util::{ClientError, ClientWrapper};
+use ioboard_shared::expansion::{ExpansionError, ExpansionReply, ExpansionRequest};
+use log::{debug, info, warn};
+use operator_shared::power::PowerReading;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::time::{self, Instant};
+
+use crate::AppEvent;
+use crate::config::{ModbusRegisters, PowerMeterConfig, PowerMeterKind};
+use crate::ioboard::{CommandSequencer, ExpansionEndpoint};
+use crate::networking::dead_letter;
+
+#[cfg(test)]
+mod tests;
+
+topic!(PowerTopic, PowerReading, "topic/operator/power");
+
+const EXPANSION_DISCOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+const EXPANSION_REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+const EXPANSION_REQUEST_ATTEMPTS: u32 = 2;
+
+const MODBUS_TIMEOUT: Duration = Duration::from_secs(1);
+/// Read input registers.
+const MODBUS_READ_INPUT_REGISTERS: u8 = 0x04;
+/// The function code of an exception response has this bit set.
+const MODBUS_EXCEPTION_FLAG: u8 = 0x80;
+/// The MBAP header, up to and including the unit id.
+const MODBUS_HEADER_SIZE: usize = 7;
+/// The longest response of a read, 125 registers.
+const MODBUS_PDU_MAX: usize = 253;
+
+const INA226_SHUNT_VOLTAGE_REGISTER: u16 = 0x01;
+const INA226_BUS_VOLTAGE_REGISTER: u16 = 0x02;
+/// in V per bit
+const INA226_SHUNT_VOLTAGE_LSB: f64 = 2.5e-6;
+const INA226_BUS_VOLTAGE_LSB: f64 = 1.25e-3;
+
+/// Readings further apart than this many intervals are not integrated.
+const GAP_INTERVALS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub volts: f64,
+    pub amps: f64,
+    pub watts: f64,
+}
+
+#[derive(Debug)]
+pub enum PowerMeterError {
+    /// no io board has the expansion endpoint
+    NoIoBoard,
+    Request(ClientError),
+    Expansion(ExpansionError),
+    /// the io board replied with something other than the register data
+    UnexpectedReply(ExpansionReply),
+    Modbus(ModbusError),
+    Io(io::Error),
+    Timeout,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModbusError {
+    /// the exception code of the meter
+    Exception(u8),
+    /// e.g. the response to another request, or a truncated frame
+    InvalidResponse,
+}
+
+/// The energy used by a job, as written to the job report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobEnergy {
+    pub energy_wh: f64,
+    /// over the metered time
+    pub average_watts: f64,
+    pub peak_watts: f64,
+    /// the time covered by the readings, in seconds, shorter than the job if readings were missing
+    pub metered_s: f64,
+}
+
+#[derive(Debug, Default)]
+struct EnergyTotals {
+    energy_wh: f64,
+    /// `None` while no job is running
+    job: Option<JobEnergy>,
+}
+
+/// The energy used since the server started, and by the running job, shared by the power meter and the job runner.
+#[derive(Debug, Clone, Default)]
+pub struct EnergyCounter(Arc<Mutex<EnergyTotals>>);
+
+impl EnergyCounter {
+    /// Integrates `watts` over `duration`, returns the energy used since the server started, in Wh.
+    pub fn record(&self, watts: f64, duration: Duration) -> f64 {
+        let energy_wh = watts * duration.as_secs_f64() / 3600.0;
+        let mut totals = self.0.lock().unwrap();
+        totals.energy_wh += energy_wh;
+        if let Some(job) = &mut totals.job {
+            job.energy_wh += energy_wh;
+            job.metered_s += duration.as_secs_f64();
+            job.peak_watts = job.peak_watts.max(watts);
+        }
+        totals.energy_wh
+    }
+
+    /// Starts counting the energy of a job, a job that was not finished is discarded.
+    pub fn start_job(&self) {
+        self.0.lock().unwrap().job = Some(JobEnergy::default());
+    }
+
+    /// `None` if no job was started.
+    pub fn finish_job(&self) -> Option<JobEnergy> {
+        let mut job = self.0.lock().unwrap().job.take()?;
+        if job.metered_s > 0.0 {
+            job.average_watts = job.energy_wh * 3600.0 / job.metered_s;
+        }
+        Some(job)
+    }
+}
+
+/// The shunt and bus voltage registers are 16-bit big-endian, the current is calculated from the shunt voltage, so the
+/// calibration register of the INA226 doesn't have to be programmed.
+pub fn ina226_measurement(shunt_voltage: [u8; 2], bus_voltage: [u8; 2], shunt_ohms: f64) -> Measurement {
+    let shunt_volts = f64::from(i16::from_be_bytes(shunt_voltage)) * INA226_SHUNT_VOLTAGE_LSB;
+    let volts = f64::from(u16::from_be_bytes(bus_voltage)) * INA226_BUS_VOLTAGE_LSB;
+    let amps = shunt_volts / shunt_ohms;
+    Measurement {
+        volts,
+        amps,
+        watts: volts * amps,
+    }
+}
+
+/// A Modbus TCP request that reads `count` input registers from `register`.
+pub fn modbus_read_request(transaction: u16, unit_id: u8, register: u16, count: u16) -> [u8; 12] {
+    let mut frame = [0; 12];
+    frame[0..2].copy_from_slice(&transaction.to_be_bytes());
+    // protocol 0, Modbus
+    frame[2..4].copy_from_slice(&0_u16.to_be_bytes());
+    // the bytes after the length, unit id included
+    frame[4..6].copy_from_slice(&6_u16.to_be_bytes());
+    frame[6] = unit_id;
+    frame[7] = MODBUS_READ_INPUT_REGISTERS;
+    frame[8..10].copy_from_slice(&register.to_be_bytes());
+    frame[10..12].copy_from_slice(&count.to_be_bytes());
+    frame
+}
+
+/// The bytes after the length of the MBAP header, `None` if the header is invalid.
+pub fn modbus_remaining_length(header: &[u8; MODBUS_HEADER_SIZE]) -> Option<usize> {
+    let length = usize::from(u16::from_be_bytes([header[4], header[5]]));
+    // the length includes the unit id, which is part of the header
+    match length {
+        2..=MODBUS_PDU_MAX => Some(length - 1),
+        _ => None,
+    }
+}
+
+/// Returns the register bytes of the response to [`modbus_read_request`], `frame` is the whole response.
+pub fn modbus_read_response(frame: &[u8], transaction: u16, unit_id: u8) -> Result<&[u8], ModbusError> {
+    let (header, pdu) = frame
+        .split_at_checked(MODBUS_HEADER_SIZE)
+        .ok_or(ModbusError::InvalidResponse)?;
+    if header[0..2] != transaction.to_be_bytes() || header[6] != unit_id {
+        return Err(ModbusError::InvalidResponse);
+    }
+    match pdu {
+        [function, code] if *function == MODBUS_READ_INPUT_REGISTERS | MODBUS_EXCEPTION_FLAG => {
+            Err(ModbusError::Exception(*code))
+        }
+        [MODBUS_READ_INPUT_REGISTERS, length, data @ ..] if usize::from(*length) == data.len() => Ok(data),
+        _ => Err(ModbusError::InvalidResponse),
+    }
+}
+
+/// Two registers, the high word first.
+pub fn modbus_float(data: &[u8]) -> Result<f32, ModbusError> {
+    let bytes: [u8; 4] = data
+        .try_into()
+        .map_err(|_| ModbusError::InvalidResponse)?;
+    Ok(f32::from_be_bytes(bytes))
+}
+
+/// An INA226 on the expansion header of an io board, the io board is discovered again after a failed request.
+struct Ina226Meter {
+    stack: RouterStack,
+    sequencer: Arc<CommandSequencer>,
+    device: u8,
+    shunt_ohms: f64,
+    address: Option<Address>,
+}
+
+impl Ina226Meter {
+    async fn read(&mut self) -> Result<Measurement, PowerMeterError> {
+        let address = match self.address {
+            Some(address) => address,
+            None => self.discover().await?,
+        };
+        let result: Result<_, PowerMeterError> = async {
+            let shunt_voltage = self
+                .read_register(address, INA226_SHUNT_VOLTAGE_REGISTER)
+                .await?;
+            let bus_voltage = self
+                .read_register(address, INA226_BUS_VOLTAGE_REGISTER)
+                .await?;
+            Ok(ina226_measurement(shunt_voltage, bus_voltage, self.shunt_ohms))
+        }
+        .await;
+        if let Err(PowerMeterError::Request(_)) = &result {
+            self.address = None;
+        }
+        result
+    }
+
+    async fn discover(&mut self) -> Result<Address, PowerMeterError> {
+        let query = SocketQuery {
+            key: ExpansionEndpoint::REQ_KEY.to_bytes(),
+            nash_req: NameRequirement::Any,
+            frame_kind: FrameKind::ENDPOINT_REQ,
+            broadcast: false,
+        };
+        // FUTURE select the io board, the first io board with an expansion header is used
+        let address = self
+            .stack
+            .discovery()
+            .discover_sockets(1, EXPANSION_DISCOVERY_TIMEOUT, &query)
+            .await
+            .into_iter()
+            .map(|result| result.address)
+            .next()
+            .ok_or(PowerMeterError::NoIoBoard)?;
+        info!("Power meter io board discovered. address: {:?}", address);
+        Ok(*self.address.insert(address))
+    }
+
+    async fn read_register(&self, address: Address, register: u16) -> Result<[u8; 2], PowerMeterError> {
+        let client = self
+            .stack
+            .endpoints()
+            .client::<ExpansionEndpoint>(address, None);
+        let client = ClientWrapper::new(EXPANSION_REQUEST_TIMEOUT, client);
+        let request = self
+            .sequencer
+            .sequenced(ExpansionRequest::Read {
+                device: self.device,
+                register,
+                length: 2,
+            });
+        let response = client
+            .request_with_retry(&request, EXPANSION_REQUEST_ATTEMPTS)
+            .await
+            .inspect_err(|e| dead_letter::request_failed::<ExpansionEndpoint>(address, EXPANSION_REQUEST_ATTEMPTS, e))
+            .map_err(PowerMeterError::Request)?;
+        match response.map_err(PowerMeterError::Expansion)? {
+            ExpansionReply::Data(data) => data
+                .bytes()
+                .try_into()
+                .map_err(|_| PowerMeterError::UnexpectedReply(ExpansionReply::Data(data))),
+            reply => Err(PowerMeterError::UnexpectedReply(reply)),
+        }
+    }
+}
+
+/// A Modbus TCP meter, the connection is opened again after a failed request.
+struct ModbusMeter {
+    address: SocketAddr,
+    unit_id: u8,
+    registers: ModbusRegisters,
+    stream: Option<TcpStream>,
+    transaction: u16,
+}
+
+impl ModbusMeter {
+    async fn read(&mut self) -> Result<Measurement, PowerMeterError> {
+        let registers = self.registers;
+        let result: Result<_, PowerMeterError> = async {
+            Ok(Measurement {
+                volts: f64::from(self.read_float(registers.volts).await?),
+                amps: f64::from(self.read_float(registers.amps).await?),
+                watts: f64::from(self.read_float(registers.watts).await?),
+            })
+        }
+        .await;
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+
+    async fn read_float(&mut self, register: u16) -> Result<f32, PowerMeterError> {
+        self.transaction = self.transaction.wrapping_add(1);
+        let (transaction, unit_id) = (self.transaction, self.unit_id);
+
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                let stream = time::timeout(MODBUS_TIMEOUT, TcpStream::connect(self.address))
+                    .await
+                    .map_err(|_| PowerMeterError::Timeout)?
+                    .map_err(PowerMeterError::Io)?;
+                self.stream.insert(stream)
+            }
+        };
+
+        let frame = time::timeout(MODBUS_TIMEOUT, async {
+            stream
+                .write_all(&modbus_read_request(transaction, unit_id, register, 2))
+                .await?;
+            let mut header = [0; MODBUS_HEADER_SIZE];
+            stream.read_exact(&mut header).await?;
+            let length = modbus_remaining_length(&header)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid Modbus header"))?;
+            let mut frame = header.to_vec();
+            frame.resize(MODBUS_HEADER_SIZE + length, 0);
+            stream
+                .read_exact(&mut frame[MODBUS_HEADER_SIZE..])
+                .await?;
+            Ok::<_, io::Error>(frame)
+        })
+        .await
+        .map_err(|_| PowerMeterError::Timeout)?
+        .map_err(PowerMeterError::Io)?;
+
+        let data = modbus_read_response(&frame, transaction, unit_id).map_err(PowerMeterError::Modbus)?;
+        modbus_float(data).map_err(PowerMeterError::Modbus)
+    }
+}
+
+enum PowerMeter {
+    Ina226(Ina226Meter),
+    Modbus(ModbusMeter),
+}
+
+impl PowerMeter {
+    fn new(stack: RouterStack, kind: &PowerMeterKind, sequencer: Arc<CommandSequencer>) -> Self {
+        match kind {
+            PowerMeterKind::Ina226 {
+                device,
+                shunt_ohms,
+            } => Self::Ina226(Ina226Meter {
+                stack,
+                sequencer,
+                device: *device,
+                shunt_ohms: *shunt_ohms,
+                address: None,
+            }),
+            PowerMeterKind::Modbus {
+                address,
+                unit_id,
+                registers,
+            } => Self::Modbus(ModbusMeter {
+                address: *address,
+                unit_id: *unit_id,
+                registers: *registers,
+                stream: None,
+                transaction: 0,
+            }),
+        }
+    }
+
+    async fn read(&mut self) -> Result<Measurement, PowerMeterError> {
+        match self {
+            Self::Ina226(meter) => meter.read().await,
+            Self::Modbus(meter) => meter.read().await,
+        }
+    }
+}
+
+/// Reads the meter at the configured interval, records the energy to the `energy` counter, and publishes each reading.
+pub async fn power_meter(
+    stack: RouterStack,
+    config: PowerMeterConfig,
+    sequencer: Arc<CommandSequencer>,
+    energy: EnergyCounter,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let mut meter = PowerMeter::new(stack.clone(), &config.meter, sequencer);
+    let interval = Duration::from_millis(config.interval_ms.max(1));
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+    let mut last_reading_at: Option<Instant> = None;
+    let mut failing = false;
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            _ = ticker.tick() => {}
+        }
+
+        let measurement = match meter.read().await {
+            Ok(measurement) => measurement,
+            Err(e) => {
+                match failing {
+                    false => warn!("Unable to read power meter. error: {:?}", e),
+                    true => debug!("Unable to read power meter. error: {:?}", e),
+                }
+                failing = true;
+                last_reading_at = None;
+                continue;
+            }
+        };
+        if failing {
+            info!("Power meter read again");
+            failing = false;
+        }
+
+        let now = Instant::now();
+        let duration = last_reading_at
+            .map(|last_reading_at| now - last_reading_at)
+            .filter(|duration| *duration <= interval * GAP_INTERVALS)
+            .unwrap_or_default();
+        last_reading_at = Some(now);
+        let energy_wh = energy.record(measurement.watts, duration);
+
+        let reading = PowerReading {
+            volts: measurement.volts as f32,
+            amps: measurement.amps as f32,
+            watts: measurement.watts as f32,
+            energy_wh,
+        };
+        if let Err(e) = stack
+            .topics()
+            .broadcast::<PowerTopic>(&reading, None)
+        {
+            debug!("Unable to publish power reading, error: {:?}", e);
+        }
+    }
+    info!("power meter shutdown");
+}
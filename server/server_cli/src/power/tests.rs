@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use super::{
+    EnergyCounter, ModbusError, ina226_measurement, modbus_float, modbus_read_request, modbus_read_response,
+    modbus_remaining_length,
+};
+
+#[test]
+pub fn ina226_power_is_calculated_from_the_shunt_voltage() {
+    // given
+    // 24V, 1.25mV per bit
+    let bus_voltage = 19_200_u16.to_be_bytes();
+    // 2A through 2mΩ is 4mV, 2.5µV per bit
+    let shunt_voltage = 1_600_i16.to_be_bytes();
+
+    // when
+    let measurement = ina226_measurement(shunt_voltage, bus_voltage, 0.002);
+
+    // then
+    assert!((measurement.volts - 24.0).abs() < 1e-9);
+    assert!((measurement.amps - 2.0).abs() < 1e-9);
+    assert!((measurement.watts - 48.0).abs() < 1e-9);
+}
+
+#[test]
+pub fn ina226_reverse_current_is_negative() {
+    // when
+    let measurement = ina226_measurement((-1_600_i16).to_be_bytes(), 19_200_u16.to_be_bytes(), 0.002);
+
+    // then
+    assert!((measurement.amps + 2.0).abs() < 1e-9);
+}
+
+#[test]
+pub fn modbus_request_reads_input_registers() {
+    // when
+    let frame = modbus_read_request(0x0102, 1, 0x000C, 2);
+
+    // then
+    assert_eq!(frame, [
+        0x01, 0x02, 0x00, 0x00, 0x00, 0x06, 0x01, 0x04, 0x00, 0x0C, 0x00, 0x02
+    ]);
+}
+
+#[test]
+pub fn modbus_response_has_the_register_data() {
+    // given
+    let mut frame = vec![0x01, 0x02, 0x00, 0x00, 0x00, 0x07, 0x01, 0x04, 0x04];
+    frame.extend_from_slice(&230.5_f32.to_be_bytes());
+    let header = frame[..7].try_into().unwrap();
+
+    // when
+    let data = modbus_read_response(&frame, 0x0102, 1).unwrap();
+
+    // then
+    assert_eq!(modbus_remaining_length(&header), Some(6));
+    assert_eq!(modbus_float(data), Ok(230.5));
+}
+
+#[test]
+pub fn modbus_exception_is_returned() {
+    // given
+    // illegal data address
+    let frame = [0x01, 0x02, 0x00, 0x00, 0x00, 0x03, 0x01, 0x84, 0x02];
+
+    // expect
+    assert_eq!(
+        modbus_read_response(&frame, 0x0102, 1),
+        Err(ModbusError::Exception(0x02))
+    );
+}
+
+#[test]
+pub fn modbus_response_to_another_request_is_rejected() {
+    // given
+    let frame = [
+        0x01, 0x03, 0x00, 0x00, 0x00, 0x07, 0x01, 0x04, 0x04, 0x43, 0x66, 0x80, 0x00,
+    ];
+
+    // expect
+    assert_eq!(
+        modbus_read_response(&frame, 0x0102, 1),
+        Err(ModbusError::InvalidResponse)
+    );
+    // the byte count doesn't match the data
+    assert_eq!(
+        modbus_read_response(&frame[..12], 0x0103, 1),
+        Err(ModbusError::InvalidResponse)
+    );
+}
+
+#[test]
+pub fn job_energy_is_counted_while_the_job_runs() {
+    // given
+    let energy = EnergyCounter::default();
+    energy.record(100.0, Duration::from_secs(3600));
+    energy.start_job();
+
+    // when
+    energy.record(200.0, Duration::from_secs(1800));
+    let total_wh = energy.record(400.0, Duration::from_secs(1800));
+    let job = energy.finish_job().unwrap();
+
+    // then
+    assert!((total_wh - 400.0).abs() < 1e-9);
+    assert!((job.energy_wh - 300.0).abs() < 1e-9);
+    assert!((job.average_watts - 300.0).abs() < 1e-9);
+    assert_eq!(job.peak_watts, 400.0);
+    assert_eq!(job.metered_s, 3600.0);
+    // finished once
+    assert_eq!(energy.finish_job(), None);
+}
+
+#[test]
+pub fn job_without_readings_has_no_average() {
+    // given
+    let energy = EnergyCounter::default();
+    energy.start_job();
+
+    // when
+    let job = energy.finish_job().unwrap();
+
+    // then
+    assert_eq!(job.energy_wh, 0.0);
+    assert_eq!(job.average_watts, 0.0);
+}
@@ -0,0 +1,331 @@
+//! Machine readiness, the pre-run checks that must pass, or be overridden by the operator, before a job is started.
+
+use std::collections::HashMap;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, topic};
+use ergot_util::ClientWrapper;
+use ioboard_shared::self_test::{BoardState, SelfTestStatus};
+use ioboard_shared::vacuum::{VacuumRequest, VacuumResponse};
+use log::{debug, info, warn};
+use operator_shared::feeders::{FeedersStatus, TapeOrientation};
+use operator_shared::readiness::{CheckState, ReadinessCheck, ReadinessCheckStatus, ReadinessError, ReadinessStatus};
+use server_common::camera::{CameraDefinition, CameraMounting};
+use tokio::select;
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::Receiver;
+use tokio::time;
+
+use crate::AppEvent;
+use crate::feeders::Feeders;
+use crate::ioboard::{CommandSequencer, VacuumEndpoint};
+use crate::networking::dead_letter;
+
+#[cfg(test)]
+mod tests;
+
+topic!(ReadinessTopic, ReadinessStatus, "topic/operator/readiness");
+topic!(SelfTestTopic, SelfTestStatus, "topic/ioboard/self-test");
+
+/// In the order they are shown to the operator.
+pub const CHECKS: [ReadinessCheck; 7] = [
+    ReadinessCheck::ServerTasksRunning,
+    ReadinessCheck::IoBoardReady,
+    ReadinessCheck::TemperaturesNormal,
+    ReadinessCheck::Homed,
+    ReadinessCheck::VacuumOk,
+    ReadinessCheck::CamerasCalibrated,
+    ReadinessCheck::FeedersVerified,
+];
+
+/// While the pump is running the vacuum must be at least this fraction of the setpoint.
+const VACUUM_SETPOINT_FRACTION: f32 = 0.8;
+
+const VACUUM_REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+const VACUUM_REQUEST_ATTEMPTS: u32 = 3;
+
+/// Several times the interval the io board publishes the self test status at.
+const SELF_TEST_STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state of the checks, and the overrides given by the operator.
+pub struct Readiness {
+    states: HashMap<ReadinessCheck, CheckState>,
+    /// the reason given by the operator, by check
+    overrides: HashMap<ReadinessCheck, String>,
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self {
+            states: CHECKS
+                .iter()
+                .map(|check| (*check, CheckState::Unknown))
+                .collect(),
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Returns `true` if the state changed.
+    ///
+    /// An override only applies to the state it was given for, a check that passes, or whose state changes otherwise,
+    /// e.g. from unknown to failed, loses its override, so that it has to be overridden again.
+    pub fn update(&mut self, check: ReadinessCheck, state: CheckState) -> bool {
+        let previous = self.states.insert(check, state);
+        let changed = previous != Some(state);
+
+        if (changed || state == CheckState::Passed)
+            && let Some(reason) = self.overrides.remove(&check)
+        {
+            match state {
+                CheckState::Passed => info!(
+                    "Readiness check passed, override removed. check: {:?}, reason: {}",
+                    check, reason
+                ),
+                _ => warn!(
+                    "Readiness check changed, override removed. check: {:?}, previous: {:?}, state: {:?}, reason: {}",
+                    check, previous, state, reason
+                ),
+            }
+        }
+        changed
+    }
+
+    pub fn override_check(&mut self, check: ReadinessCheck, reason: &str) -> Result<(), ReadinessError> {
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(ReadinessError::MissingReason);
+        }
+
+        self.overrides
+            .insert(check, reason.to_string());
+        Ok(())
+    }
+
+    pub fn status(&self) -> ReadinessStatus {
+        ReadinessStatus {
+            checks: CHECKS
+                .iter()
+                .map(|check| ReadinessCheckStatus {
+                    check: *check,
+                    state: self
+                        .states
+                        .get(check)
+                        .copied()
+                        .unwrap_or(CheckState::Unknown),
+                    override_reason: self.overrides.get(check).cloned(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The checks that prevent a job from being started, empty when the machine is ready.
+    pub fn blocking_checks(&self) -> Vec<ReadinessCheck> {
+        self.status()
+            .checks
+            .into_iter()
+            .filter(|status| !status.satisfied())
+            .map(|status| status.check)
+            .collect()
+    }
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The cameras used for vision must be calibrated, and there must be at least one down camera.
+pub fn cameras_state(cameras: &[CameraDefinition]) -> CheckState {
+    let vision_cameras = cameras
+        .iter()
+        .filter(|camera| matches!(camera.mounting, CameraMounting::Up | CameraMounting::Down))
+        .collect::<Vec<_>>();
+
+    let has_down_camera = vision_cameras
+        .iter()
+        .any(|camera| matches!(camera.mounting, CameraMounting::Down));
+    let all_calibrated = vision_cameras
+        .iter()
+        .all(|camera| camera.calibration.is_some());
+
+    match has_down_camera && all_calibrated {
+        true => CheckState::Passed,
+        false => CheckState::Failed,
+    }
+}
+
+/// The vacuum sensor must be readable and, while the pump is running, the vacuum must be near the setpoint.
+pub fn vacuum_state(response: &VacuumResponse) -> CheckState {
+    match response {
+        Ok(status) => match (status.vacuum, status.setpoint) {
+            (None, _) => CheckState::Failed,
+            (Some(vacuum), Some(setpoint)) if vacuum < setpoint * VACUUM_SETPOINT_FRACTION => CheckState::Failed,
+            (Some(_), _) => CheckState::Passed,
+        },
+        Err(_) => CheckState::Failed,
+    }
+}
+
+/// The tape of no feeder may be reversed, and the orientation of the tape of every feeder whose part has a polarity
+/// mark must have been verified.
+pub fn feeders_state(status: &FeedersStatus) -> CheckState {
+    let orientations = status
+        .feeders
+        .iter()
+        .filter_map(|feeder| feeder.orientation);
+
+    let mut state = CheckState::Passed;
+    for orientation in orientations {
+        match orientation {
+            TapeOrientation::Reversed => return CheckState::Failed,
+            TapeOrientation::Unverified => state = CheckState::Unknown,
+            TapeOrientation::Correct => {}
+        }
+    }
+    state
+}
+
+/// The io board must have passed its power-on self test, `status` is `None` when no recent status has been received,
+/// e.g. the board is still booting.
+pub fn self_test_state(status: Option<&SelfTestStatus>) -> CheckState {
+    match status.map(|status| status.state) {
+        None => CheckState::Unknown,
+        Some(BoardState::Ready) => CheckState::Passed,
+        Some(BoardState::Degraded) => CheckState::Failed,
+    }
+}
+
+/// Evaluates the checks and publishes the [`ReadinessStatus`] for the operator UI.
+pub async fn readiness_monitor(
+    stack: RouterStack,
+    readiness: Arc<Mutex<Readiness>>,
+    cameras: Vec<CameraDefinition>,
+    sequencer: Arc<CommandSequencer>,
+    feeders: Arc<Mutex<Feeders>>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    // the camera configuration does not change while the server is running
+    // the homed state is updated by the homing runner, see `homing::homing_runner`
+    update(&readiness, ReadinessCheck::CamerasCalibrated, cameras_state(&cameras)).await;
+
+    let query = SocketQuery {
+        key: VacuumEndpoint::REQ_KEY.to_bytes(),
+        nash_req: NameRequirement::Any,
+        frame_kind: FrameKind::ENDPOINT_REQ,
+        broadcast: false,
+    };
+    let mut vacuum_address: Option<Address> = None;
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<SelfTestTopic>(4, None);
+    let subber = pin!(subber);
+    let mut self_test_hdl = subber.subscribe();
+    let mut self_test: Option<(SelfTestStatus, time::Instant)> = None;
+
+    let mut ticker = time::interval(Duration::from_secs(1));
+    loop {
+        select! {
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            msg = self_test_hdl.recv() => {
+                let status = msg.t;
+                if self_test.map(|(previous, _)| previous) != Some(status) {
+                    log_self_test(&status);
+                }
+                self_test = Some((status, time::Instant::now()));
+            }
+            _ = ticker.tick() => {
+                // the board runs the self test again when it restarts, an old result no longer applies
+                let self_test_status = self_test
+                    .filter(|(_, received_at)| received_at.elapsed() <= SELF_TEST_STATUS_TIMEOUT)
+                    .map(|(status, _)| status);
+                update(&readiness, ReadinessCheck::IoBoardReady, self_test_state(self_test_status.as_ref())).await;
+
+                if vacuum_address.is_none() {
+                    // TODO check the vacuum of every io board, currently there is only one
+                    vacuum_address = stack
+                        .discovery()
+                        .discover_sockets(4, VACUUM_REQUEST_TIMEOUT, &query)
+                        .await
+                        .first()
+                        .map(|result| result.address);
+                }
+
+                let vacuum = match vacuum_address {
+                    None => {
+                        debug!("Vacuum endpoint not found");
+                        CheckState::Unknown
+                    }
+                    Some(address) => {
+                        let client = stack
+                            .endpoints()
+                            .client::<VacuumEndpoint>(address, None);
+                        let client = ClientWrapper::new(VACUUM_REQUEST_TIMEOUT, client);
+                        let request = sequencer.sequenced(VacuumRequest::Status);
+                        match client.request_with_retry(&request, VACUUM_REQUEST_ATTEMPTS).await {
+                            Ok(response) => vacuum_state(&response),
+                            Err(e) => {
+                                debug!("Unable to request vacuum status. error: {:?}", e);
+                                dead_letter::request_failed::<VacuumEndpoint>(address, VACUUM_REQUEST_ATTEMPTS, &e);
+                                // the io board may have restarted with a different address
+                                vacuum_address = None;
+                                CheckState::Failed
+                            }
+                        }
+                    }
+                };
+                update(&readiness, ReadinessCheck::VacuumOk, vacuum).await;
+
+                let feeders_status = feeders.lock().await.status();
+                update(&readiness, ReadinessCheck::FeedersVerified, feeders_state(&feeders_status)).await;
+
+                let status = readiness.lock().await.status();
+                if let Err(e) = stack
+                    .topics()
+                    .broadcast::<ReadinessTopic>(&status, None)
+                {
+                    debug!("Unable to publish readiness status, error: {:?}", e);
+                }
+            }
+        }
+    }
+    info!("readiness monitor shutdown");
+}
+
+fn log_self_test(status: &SelfTestStatus) {
+    match status.state {
+        BoardState::Ready => info!("Io board self test passed. checks: {}", status.checks),
+        BoardState::Degraded => {
+            warn!(
+                "Io board self test failed, board degraded. checks: {}, faults: {}",
+                status.checks, status.fault_count
+            );
+            for fault in status.faults() {
+                warn!("Io board self test fault. fault: {:?}", fault);
+            }
+        }
+    }
+}
+
+async fn update(readiness: &Mutex<Readiness>, check: ReadinessCheck, state: CheckState) {
+    if readiness
+        .lock()
+        .await
+        .update(check, state)
+    {
+        match state {
+            CheckState::Passed => info!("Readiness check changed. check: {:?}, state: {:?}", check, state),
+            _ => warn!("Readiness check changed. check: {:?}, state: {:?}", check, state),
+        }
+    }
+}
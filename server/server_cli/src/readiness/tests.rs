@@ -0,0 +1,264 @@
+use ioboard_shared::self_test::{BoardState, SELF_TEST_FAULTS_MAX, SelfTestFault, SelfTestStatus};
+use ioboard_shared::vacuum::{VacuumError, VacuumStatus};
+use operator_shared::feeders::{FeederStatus, FeedersStatus, Stock, TapeOrientation};
+use operator_shared::readiness::{CheckState, ReadinessCheck, ReadinessError};
+use server_common::camera::{CameraCalibration, CameraDefinition, CameraLayout, CameraMounting, CameraStreamConfig};
+
+use super::{CHECKS, Readiness, cameras_state, feeders_state, self_test_state, vacuum_state};
+
+fn camera(mounting: CameraMounting, calibrated: bool) -> CameraDefinition {
+    CameraDefinition {
+        name: "camera".to_string(),
+        sources: vec![],
+        stream_config: CameraStreamConfig {
+            jpeg_quality: 70,
+            motion_throttle: None,
+        },
+        width: 640,
+        height: 480,
+        fps: 30.0,
+        mounting,
+        layout: CameraLayout::Primary,
+        calibration: match calibrated {
+            true => Some(CameraCalibration {
+                mm_per_pixel_x: 0.02,
+                mm_per_pixel_y: 0.02,
+            }),
+            false => None,
+        },
+        exposure_metering: None,
+    }
+}
+
+fn pass_all(readiness: &mut Readiness) {
+    for check in CHECKS {
+        readiness.update(check, CheckState::Passed);
+    }
+}
+
+#[test]
+pub fn not_ready_initially() {
+    // given
+    let readiness = Readiness::new();
+
+    // expect
+    assert!(!readiness.status().ready());
+    assert_eq!(readiness.blocking_checks(), CHECKS.to_vec());
+}
+
+#[test]
+pub fn ready_when_all_checks_pass() {
+    // given
+    let mut readiness = Readiness::new();
+
+    // when
+    pass_all(&mut readiness);
+
+    // then
+    assert!(readiness.status().ready());
+    assert!(readiness.blocking_checks().is_empty());
+}
+
+#[test]
+pub fn override_satisfies_check() {
+    // given
+    let mut readiness = Readiness::new();
+    pass_all(&mut readiness);
+    readiness.update(ReadinessCheck::FeedersVerified, CheckState::Unknown);
+
+    // when
+    let result = readiness.override_check(ReadinessCheck::FeedersVerified, " feeders checked by hand ");
+
+    // then
+    assert_eq!(result, Ok(()));
+    assert!(readiness.status().ready());
+    let status = readiness.status();
+    let feeders = status
+        .checks
+        .iter()
+        .find(|status| status.check == ReadinessCheck::FeedersVerified)
+        .unwrap();
+    assert_eq!(feeders.override_reason.as_deref(), Some("feeders checked by hand"));
+}
+
+#[test]
+pub fn override_requires_reason() {
+    // given
+    let mut readiness = Readiness::new();
+
+    // when
+    let result = readiness.override_check(ReadinessCheck::Homed, "  ");
+
+    // then
+    assert_eq!(result, Err(ReadinessError::MissingReason));
+    assert!(
+        readiness
+            .blocking_checks()
+            .contains(&ReadinessCheck::Homed)
+    );
+}
+
+#[test]
+pub fn passing_check_removes_override() {
+    // given
+    let mut readiness = Readiness::new();
+    pass_all(&mut readiness);
+    readiness.update(ReadinessCheck::VacuumOk, CheckState::Failed);
+    readiness
+        .override_check(ReadinessCheck::VacuumOk, "sensor replaced")
+        .unwrap();
+
+    // when
+    readiness.update(ReadinessCheck::VacuumOk, CheckState::Passed);
+    readiness.update(ReadinessCheck::VacuumOk, CheckState::Failed);
+
+    // then
+    assert_eq!(readiness.blocking_checks(), vec![ReadinessCheck::VacuumOk]);
+}
+
+#[test]
+pub fn changed_check_removes_override() {
+    // given
+    let mut readiness = Readiness::new();
+    pass_all(&mut readiness);
+    readiness.update(ReadinessCheck::FeedersVerified, CheckState::Unknown);
+    readiness
+        .override_check(ReadinessCheck::FeedersVerified, "feeders checked by hand")
+        .unwrap();
+
+    // when
+    readiness.update(ReadinessCheck::FeedersVerified, CheckState::Unknown);
+
+    // then
+    assert!(readiness.status().ready());
+
+    // when
+    readiness.update(ReadinessCheck::FeedersVerified, CheckState::Failed);
+
+    // then
+    assert_eq!(readiness.blocking_checks(), vec![ReadinessCheck::FeedersVerified]);
+    assert!(
+        readiness
+            .status()
+            .checks
+            .iter()
+            .all(|status| status.override_reason.is_none())
+    );
+}
+
+#[test]
+pub fn update_reports_changes() {
+    // given
+    let mut readiness = Readiness::new();
+
+    // expect
+    assert!(!readiness.update(ReadinessCheck::Homed, CheckState::Unknown));
+    assert!(readiness.update(ReadinessCheck::Homed, CheckState::Failed));
+    assert!(!readiness.update(ReadinessCheck::Homed, CheckState::Failed));
+}
+
+#[test]
+pub fn cameras_require_calibrated_down_camera() {
+    // expect
+    assert_eq!(cameras_state(&[]), CheckState::Failed);
+    assert_eq!(
+        cameras_state(&[camera(CameraMounting::Down, false)]),
+        CheckState::Failed
+    );
+    assert_eq!(
+        cameras_state(&[camera(CameraMounting::Down, true), camera(CameraMounting::Up, false)]),
+        CheckState::Failed
+    );
+    assert_eq!(
+        cameras_state(&[
+            camera(CameraMounting::Down, true),
+            camera(CameraMounting::Up, true),
+            camera(CameraMounting::Other, false)
+        ]),
+        CheckState::Passed
+    );
+}
+
+#[test]
+pub fn vacuum_near_setpoint() {
+    // given
+    let status = |setpoint, vacuum| {
+        Ok(VacuumStatus {
+            setpoint,
+            vacuum,
+            ..VacuumStatus::default()
+        })
+    };
+
+    // expect
+    assert_eq!(vacuum_state(&status(None, Some(0.0))), CheckState::Passed);
+    assert_eq!(vacuum_state(&status(Some(50.0), Some(45.0))), CheckState::Passed);
+    assert_eq!(vacuum_state(&status(Some(50.0), Some(20.0))), CheckState::Failed);
+    assert_eq!(vacuum_state(&status(None, None)), CheckState::Failed);
+    assert_eq!(vacuum_state(&Err(VacuumError::InvalidNozzle(0))), CheckState::Failed);
+}
+
+#[test]
+pub fn io_board_must_pass_self_test() {
+    // given
+    let ready = SelfTestStatus {
+        state: BoardState::Ready,
+        checks: 4,
+        fault_count: 0,
+        faults: [None; SELF_TEST_FAULTS_MAX],
+    };
+    let mut degraded = SelfTestStatus {
+        state: BoardState::Degraded,
+        fault_count: 1,
+        ..ready
+    };
+    degraded.faults[0] = Some(SelfTestFault::DriverNotResponding {
+        axis: 0,
+    });
+
+    // expect
+    assert_eq!(self_test_state(None), CheckState::Unknown);
+    assert_eq!(self_test_state(Some(&ready)), CheckState::Passed);
+    assert_eq!(self_test_state(Some(&degraded)), CheckState::Failed);
+}
+
+#[test]
+pub fn feeders_require_verified_tape() {
+    // given
+    let status = |orientations: &[Option<TapeOrientation>]| FeedersStatus {
+        feeders: orientations
+            .iter()
+            .enumerate()
+            .map(|(index, orientation)| FeederStatus {
+                name: format!("F{}", index + 1),
+                count: 100,
+                low_stock_threshold: 20,
+                stock: Stock::Ok,
+                reel: None,
+                orientation: *orientation,
+                slot: None,
+            })
+            .collect(),
+    };
+
+    // expect
+    assert_eq!(feeders_state(&status(&[])), CheckState::Passed);
+    assert_eq!(
+        feeders_state(&status(&[None, Some(TapeOrientation::Correct)])),
+        CheckState::Passed
+    );
+    assert_eq!(
+        feeders_state(&status(&[
+            Some(TapeOrientation::Unverified),
+            Some(TapeOrientation::Correct)
+        ])),
+        CheckState::Unknown
+    );
+    assert_eq!(
+        feeders_state(&status(&[
+            Some(TapeOrientation::Unverified),
+            Some(TapeOrientation::Reversed)
+        ])),
+        CheckState::Failed
+    );
+}
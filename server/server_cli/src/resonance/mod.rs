@@ -0,0 +1,223 @@
+//! Resonance sweeps, shaking an axis at a range of frequencies to find its resonance frequency, for the input shaper of
+//! the axis, see [`ResonanceSweepSettings`].
+//!
+//! The sweep is run by the io board of the axis, while the axis is at rest, the server only converts the settings of
+//! the operator and publishes the result.  The sweep uses the machine the same as a job, so jobs, test shots and homing
+//! are refused while it runs.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::traits::Endpoint;
+use ergot::well_known::{NameRequirement, SocketQuery};
+use ergot::{Address, FrameKind, topic};
+use ergot_util::ClientWrapper;
+use ioboard_shared::resonance::{
+    ResonanceSweepError as IoBoardSweepError, ResonanceSweepRequest, ResonanceSweepResponse,
+};
+use log::{debug, info, warn};
+use operator_shared::resonance::{
+    FrequencyResponse, ResonanceSweepError, ResonanceSweepResult, ResonanceSweepSettings, ResonanceSweepStatus,
+};
+use tokio::select;
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::Receiver;
+
+use crate::AppEvent;
+use crate::config::HomingAxisDefinition;
+use crate::ioboard::{CommandSequencer, ResonanceSweepEndpoint};
+use crate::job::JobControl;
+use crate::networking::dead_letter;
+
+#[cfg(test)]
+mod tests;
+
+topic!(
+    ResonanceSweepTopic,
+    ResonanceSweepStatus,
+    "topic/operator/resonance-sweep"
+);
+
+/// Only for finding the io board, the sweep request is answered once the sweep has finished, which takes much longer.
+const SWEEP_DISCOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The io board lets the axis come to rest for 250ms after each frequency, with some margin for the round trip.
+const SWEEP_SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait AxisSweeper: Send + Sync + 'static {
+    /// Completes once the io board has swept the axis, or refused the sweep.
+    fn sweep<'a>(
+        &'a self,
+        request: ResonanceSweepRequest,
+    ) -> impl Future<Output = anyhow::Result<ResonanceSweepResponse>> + Send + 'a;
+}
+
+/// Uses the resonance sweep endpoint of the io board.
+pub struct IoBoardSweeper {
+    stack: RouterStack,
+    sequencer: Arc<CommandSequencer>,
+}
+
+impl IoBoardSweeper {
+    pub fn new(stack: RouterStack, sequencer: Arc<CommandSequencer>) -> Self {
+        Self {
+            stack,
+            sequencer,
+        }
+    }
+
+    async fn discover(&self) -> anyhow::Result<Address> {
+        let query = SocketQuery {
+            key: ResonanceSweepEndpoint::REQ_KEY.to_bytes(),
+            nash_req: NameRequirement::Any,
+            frame_kind: FrameKind::ENDPOINT_REQ,
+            broadcast: false,
+        };
+        // TODO select the io board of the axis, currently there is only one
+        self.stack
+            .discovery()
+            .discover_sockets(4, SWEEP_DISCOVERY_TIMEOUT, &query)
+            .await
+            .into_iter()
+            .next()
+            .map(|result| result.address)
+            .ok_or_else(|| anyhow!("No io board with resonance sweeps found"))
+    }
+}
+
+impl AxisSweeper for IoBoardSweeper {
+    fn sweep<'a>(
+        &'a self,
+        request: ResonanceSweepRequest,
+    ) -> impl Future<Output = anyhow::Result<ResonanceSweepResponse>> + Send + 'a {
+        async move {
+            let address = self.discover().await?;
+
+            let client = self
+                .stack
+                .endpoints()
+                .client::<ResonanceSweepEndpoint>(address, None);
+            // not retried, a retry while the axis is still being swept would be queued behind the sweep
+            let client = ClientWrapper::new(sweep_duration(&request), client);
+            let request = self.sequencer.sequenced(request);
+            client
+                .request_with_retry(&request, 1)
+                .await
+                .inspect_err(|e| dead_letter::request_failed::<ResonanceSweepEndpoint>(address, 1, e))
+        }
+    }
+}
+
+/// The longest the io board can take to sweep the axis.
+pub fn sweep_duration(request: &ResonanceSweepRequest) -> Duration {
+    (Duration::from_millis(request.duration_ms as u64) + SWEEP_SETTLE_TIME) * request.frequencies as u32
+}
+
+/// Converts the settings of the operator to the request of the io board, `steps_per_mm` converts the amplitude.
+pub fn sweep_request(
+    settings: &ResonanceSweepSettings,
+    axes: &[HomingAxisDefinition],
+    steps_per_mm: f64,
+) -> Result<ResonanceSweepRequest, ResonanceSweepError> {
+    let axis = axes
+        .iter()
+        .find(|definition| definition.name == settings.axis)
+        .ok_or(ResonanceSweepError::UnknownAxis)?
+        .axis;
+
+    let request = ResonanceSweepRequest {
+        axis,
+        start_frequency: settings.start_frequency,
+        frequency_step: settings.frequency_step,
+        frequencies: settings.frequencies,
+        amplitude_steps: (settings.amplitude as f64 * steps_per_mm) as f32,
+        duration_ms: settings.duration_ms,
+    };
+
+    // the io board checks the request too, this avoids a round trip for the mistakes of the operator
+    if !request.is_valid() {
+        return Err(ResonanceSweepError::InvalidSweep);
+    }
+    Ok(request)
+}
+
+/// Sweeps the axis, the responses are paired with the frequencies of the request.
+pub async fn sweep<S: AxisSweeper>(
+    sweeper: &S,
+    request: ResonanceSweepRequest,
+) -> Result<ResonanceSweepResult, ResonanceSweepError> {
+    let response = sweeper
+        .sweep(request)
+        .await
+        .map_err(|e| {
+            warn!("Resonance sweep not answered. axis: {}, error: {:?}", request.axis, e);
+            ResonanceSweepError::NotAnswered
+        })?;
+
+    let sweep = response.map_err(|e| match e {
+        IoBoardSweepError::InvalidAxis => ResonanceSweepError::UnknownAxis,
+        IoBoardSweepError::InvalidSweep => ResonanceSweepError::InvalidSweep,
+        IoBoardSweepError::Unsupported => ResonanceSweepError::Unsupported,
+        IoBoardSweepError::MotionNotAllowed => ResonanceSweepError::MotionNotAllowed,
+    })?;
+
+    Ok(ResonanceSweepResult {
+        peak_frequency: sweep.peak_frequency,
+        responses: sweep
+            .responses
+            .iter()
+            .take(request.frequencies as usize)
+            .enumerate()
+            .map(|(index, response)| FrequencyResponse {
+                frequency: request.frequency(index),
+                response: *response,
+            })
+            .collect(),
+    })
+}
+
+/// Sweeps the axis and publishes the result, the caller must have started the sweep with
+/// [`JobControl::start_resonance_sweep`], so that a job can't be started while the axis is swept.
+pub async fn resonance_sweep_runner<S: AxisSweeper>(
+    stack: RouterStack,
+    sweeper: S,
+    axis: String,
+    request: ResonanceSweepRequest,
+    job_control: Arc<Mutex<JobControl>>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    select! {
+        _ = &mut app_shutdown_handler => {
+            warn!("Resonance sweep interrupted by shutdown.");
+        }
+        result = sweep(&sweeper, request) => {
+            match &result {
+                Ok(result) => info!(
+                    "Resonance sweep finished. axis: {}, peak_frequency: {} Hz, responses: {:?}",
+                    axis, result.peak_frequency, result.responses
+                ),
+                Err(e) => warn!("Resonance sweep failed. axis: {}, error: {:?}", axis, e),
+            }
+            let status = ResonanceSweepStatus {
+                axis,
+                result,
+            };
+            if let Err(e) = stack
+                .topics()
+                .broadcast::<ResonanceSweepTopic>(&status, None)
+            {
+                debug!("Unable to publish resonance sweep status, error: {:?}", e);
+            }
+        }
+    }
+
+    job_control.lock().await.finish();
+    info!("resonance sweep runner shutdown");
+}
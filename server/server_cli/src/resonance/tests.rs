@@ -0,0 +1,188 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use ioboard_shared::resonance::{
+    MAX_SWEEP_FREQUENCIES, ResonanceSweep, ResonanceSweepError as IoBoardSweepError, ResonanceSweepRequest,
+    ResonanceSweepResponse,
+};
+use operator_shared::resonance::{FrequencyResponse, ResonanceSweepError, ResonanceSweepSettings};
+
+use super::{AxisSweeper, sweep, sweep_request};
+use crate::config::HomingAxisDefinition;
+
+#[derive(Clone)]
+struct FakeSweeper {
+    requests: Arc<Mutex<Vec<ResonanceSweepRequest>>>,
+    /// `None` for an io board that doesn't answer
+    response: Option<ResonanceSweepResponse>,
+}
+
+impl FakeSweeper {
+    fn new(response: Option<ResonanceSweepResponse>) -> Self {
+        Self {
+            requests: Arc::new(Mutex::new(Vec::new())),
+            response,
+        }
+    }
+}
+
+impl AxisSweeper for FakeSweeper {
+    fn sweep<'a>(
+        &'a self,
+        request: ResonanceSweepRequest,
+    ) -> impl Future<Output = anyhow::Result<ResonanceSweepResponse>> + Send + 'a {
+        async move {
+            self.requests
+                .lock()
+                .unwrap()
+                .push(request);
+            match self.response {
+                Some(response) => Ok(response),
+                None => bail!("Timeout"),
+            }
+        }
+    }
+}
+
+fn axes() -> Vec<HomingAxisDefinition> {
+    vec![
+        HomingAxisDefinition {
+            name: "Z".to_string(),
+            axis: 2,
+            after: vec![],
+        },
+        HomingAxisDefinition {
+            name: "X".to_string(),
+            axis: 0,
+            after: vec!["Z".to_string()],
+        },
+    ]
+}
+
+fn settings(axis: &str) -> ResonanceSweepSettings {
+    ResonanceSweepSettings {
+        axis: axis.to_string(),
+        start_frequency: 20.0,
+        frequency_step: 10.0,
+        frequencies: 3,
+        amplitude: 0.5,
+        duration_ms: 1000,
+    }
+}
+
+fn request() -> ResonanceSweepRequest {
+    sweep_request(&settings("X"), &axes(), 80.0).unwrap()
+}
+
+#[test]
+pub fn the_settings_are_converted_to_the_axis_and_steps_of_the_io_board() {
+    // when
+    let request = sweep_request(&settings("X"), &axes(), 80.0);
+
+    // then
+    assert_eq!(
+        request,
+        Ok(ResonanceSweepRequest {
+            axis: 0,
+            start_frequency: 20.0,
+            frequency_step: 10.0,
+            frequencies: 3,
+            amplitude_steps: 40.0,
+            duration_ms: 1000,
+        })
+    );
+}
+
+#[test]
+pub fn unknown_axes_are_refused() {
+    // expect
+    assert_eq!(
+        sweep_request(&settings("Y"), &axes(), 80.0),
+        Err(ResonanceSweepError::UnknownAxis)
+    );
+}
+
+#[test]
+pub fn invalid_sweeps_are_refused() {
+    // given
+    let no_frequencies = ResonanceSweepSettings {
+        frequencies: 0,
+        ..settings("X")
+    };
+    let too_many_frequencies = ResonanceSweepSettings {
+        frequencies: MAX_SWEEP_FREQUENCIES as u8 + 1,
+        ..settings("X")
+    };
+    let below_zero = ResonanceSweepSettings {
+        frequency_step: -15.0,
+        ..settings("X")
+    };
+    let no_amplitude = ResonanceSweepSettings {
+        amplitude: 0.0,
+        ..settings("X")
+    };
+
+    // expect
+    for settings in [no_frequencies, too_many_frequencies, below_zero, no_amplitude] {
+        assert_eq!(
+            sweep_request(&settings, &axes(), 80.0),
+            Err(ResonanceSweepError::InvalidSweep),
+            "settings: {:?}",
+            settings
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn the_responses_are_paired_with_their_frequencies() {
+    // given
+    let mut responses = [0.0; MAX_SWEEP_FREQUENCIES];
+    responses[..3].copy_from_slice(&[0.1, 0.4, 0.2]);
+    let sweeper = FakeSweeper::new(Some(Ok(ResonanceSweep {
+        peak_frequency: 30.0,
+        responses,
+    })));
+
+    // when
+    let result = sweep(&sweeper, request())
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(result.peak_frequency, 30.0);
+    assert_eq!(result.responses, vec![
+        FrequencyResponse {
+            frequency: 20.0,
+            response: 0.1,
+        },
+        FrequencyResponse {
+            frequency: 30.0,
+            response: 0.4,
+        },
+        FrequencyResponse {
+            frequency: 40.0,
+            response: 0.2,
+        },
+    ]);
+
+    // and
+    assert_eq!(*sweeper.requests.lock().unwrap(), vec![request()]);
+}
+
+#[tokio::test]
+pub async fn the_errors_of_the_io_board_are_returned() {
+    // given
+    let unsupported = FakeSweeper::new(Some(Err(IoBoardSweepError::Unsupported)));
+    let not_answered = FakeSweeper::new(None);
+
+    // expect
+    assert_eq!(
+        sweep(&unsupported, request()).await,
+        Err(ResonanceSweepError::Unsupported)
+    );
+    assert_eq!(
+        sweep(&not_answered, request()).await,
+        Err(ResonanceSweepError::NotAnswered)
+    );
+}
@@ -0,0 +1,391 @@
+//! Nozzle runout measurement, using the up camera.
+//!
+//! A nozzle whose tip is not concentric with its rotation axis moves the tip on a circle as it rotates, so a part
+//! rotated by the nozzle is placed off its position.  The nozzle is rotated through a full turn over the up camera and
+//! the position of the tip is measured at each angle, a circle fitted to the positions gives the center of rotation,
+//! the runout, and the angle of the tip.  The [`NozzleRunout`] is stored, see [`RunoutStore`], and corrects the
+//! position of each placement for its rotation, see [`RunoutPlacer`].
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use machine_geometry::Point;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::config::RunoutConfig;
+use crate::job::{Operation, Placement, Placer};
+
+pub mod rotator;
+#[cfg(feature = "machine-vision")]
+pub mod vision;
+
+#[cfg(test)]
+mod tests;
+
+/// A circle can't be fitted to fewer tip positions.
+pub const MIN_SAMPLES: usize = 3;
+
+/// Below this the tip positions are on a line, or all the same, there is no circle.
+const MIN_FIT_DETERMINANT: f64 = 1e-12;
+
+/// Tip positions closer than this to each other are the same position, the nozzle has no measurable runout.
+const SAME_POSITION_MM: f64 = 1e-6;
+
+/// Rotates the nozzle, waiting until it has settled.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait NozzleRotator {
+    /// `angle` in degrees, absolute.
+    fn rotate_to<'a>(&'a mut self, angle: f64) -> impl Future<Output = anyhow::Result<()>> + Send + 'a;
+}
+
+/// Locates the nozzle tip in the up camera.
+///
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`.
+pub trait TipLocator {
+    /// The position of the tip relative to the center of the camera, in machine coordinates, `None` if the tip could
+    /// not be found.
+    fn locate<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RunoutSample {
+    /// in degrees
+    pub angle: f64,
+    pub tip: Point,
+}
+
+/// The circle the tip moves on as the nozzle rotates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RunoutFit {
+    /// the rotation axis, relative to the center of the camera
+    pub center: Point,
+    /// the distance of the tip from the rotation axis, in millimeters
+    pub radius_mm: f64,
+    /// the angle of the tip around the rotation axis when the nozzle is at 0 degrees, in degrees
+    pub phase_degrees: f64,
+    /// the tip moves clockwise, in machine coordinates, as the nozzle angle increases
+    pub clockwise: bool,
+    /// largest distance of a tip position from the fitted circle, in millimeters
+    pub residual_mm: f64,
+}
+
+impl RunoutFit {
+    /// The position of the tip relative to the rotation axis, at the nozzle angle, in degrees.
+    pub fn offset_at(&self, angle: f64) -> Point {
+        let direction = match self.clockwise {
+            true => -1.0,
+            false => 1.0,
+        };
+        let tip_angle = (self.phase_degrees + direction * angle).to_radians();
+        Point {
+            x: self.radius_mm * tip_angle.cos(),
+            y: self.radius_mm * tip_angle.sin(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NozzleRunout {
+    pub nozzle: u8,
+    pub measured_at: DateTime<Utc>,
+    pub fit: RunoutFit,
+    pub samples: Vec<RunoutSample>,
+}
+
+impl NozzleRunout {
+    /// How far the tip is from where it is at 0 degrees, at the nozzle angle, in degrees.
+    ///
+    /// The head is positioned for the tip at 0 degrees, e.g. by the nozzle offset, so a part rotated by the nozzle is
+    /// off by this, placements are corrected by subtracting it.
+    pub fn correction_at(&self, angle: f64) -> Point {
+        self.fit.offset_at(angle) - self.fit.offset_at(0.0)
+    }
+}
+
+/// Least squares fit of a circle to the tip positions, `None` if there are fewer than [`MIN_SAMPLES`] or they are on a
+/// line.  Tip positions that are all the same are a nozzle without runout.
+pub fn fit_runout(samples: &[RunoutSample]) -> Option<RunoutFit> {
+    if samples.len() < MIN_SAMPLES {
+        return None;
+    }
+
+    // algebraic fit of `x² + y² + d·x + e·y + f = 0` to the positions relative to their centroid, where the sums of x
+    // and y are zero, so `f` is independent of `d` and `e`
+    let count = samples.len() as f64;
+    let mean = Point {
+        x: samples
+            .iter()
+            .map(|sample| sample.tip.x)
+            .sum::<f64>()
+            / count,
+        y: samples
+            .iter()
+            .map(|sample| sample.tip.y)
+            .sum::<f64>()
+            / count,
+    };
+    let (mut sxx, mut sxy, mut syy, mut sxz, mut syz, mut sz) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+    for sample in samples {
+        let (x, y) = (sample.tip.x - mean.x, sample.tip.y - mean.y);
+        let z = x * x + y * y;
+        sxx += x * x;
+        sxy += x * y;
+        syy += y * y;
+        sxz += x * z;
+        syz += y * z;
+        sz += z;
+    }
+
+    let determinant = sxx * syy - sxy * sxy;
+    if determinant <= MIN_FIT_DETERMINANT {
+        let spread = samples
+            .iter()
+            .map(|sample| sample.tip.distance(&mean))
+            .fold(0.0, f64::max);
+        return match spread < SAME_POSITION_MM {
+            true => Some(RunoutFit {
+                center: mean,
+                radius_mm: 0.0,
+                phase_degrees: 0.0,
+                clockwise: false,
+                residual_mm: spread,
+            }),
+            false => None,
+        };
+    }
+    let d = -(sxz * syy - syz * sxy) / determinant;
+    let e = -(syz * sxx - sxz * sxy) / determinant;
+    let f = -sz / count;
+
+    let center = Point {
+        x: mean.x - d / 2.0,
+        y: mean.y - e / 2.0,
+    };
+    let radius_mm = ((d * d + e * e) / 4.0 - f)
+        .max(0.0)
+        .sqrt();
+
+    let residual_mm = samples
+        .iter()
+        .map(|sample| (sample.tip.distance(&center) - radius_mm).abs())
+        .fold(0.0, f64::max);
+
+    // the tip angle less the nozzle angle is the phase for every sample, for the direction the tip moves in, the
+    // direction where the phases agree best is the one the tip moves in
+    let fit_phase = |direction: f64| {
+        let (sin, cos) = samples
+            .iter()
+            .map(|sample| {
+                let tip_angle = (sample.tip.y - center.y).atan2(sample.tip.x - center.x);
+                tip_angle - direction * sample.angle.to_radians()
+            })
+            .fold((0.0_f64, 0.0_f64), |(sin, cos), phase| {
+                (sin + phase.sin(), cos + phase.cos())
+            });
+        (sin.atan2(cos), sin.hypot(cos))
+    };
+    let (counter_clockwise_phase, counter_clockwise_agreement) = fit_phase(1.0);
+    let (clockwise_phase, clockwise_agreement) = fit_phase(-1.0);
+    let clockwise = clockwise_agreement > counter_clockwise_agreement;
+    let phase = match clockwise {
+        true => clockwise_phase,
+        false => counter_clockwise_phase,
+    };
+
+    Some(RunoutFit {
+        center,
+        radius_mm,
+        phase_degrees: phase.to_degrees().rem_euclid(360.0),
+        clockwise,
+        residual_mm,
+    })
+}
+
+/// The angles the tip is measured at, evenly spaced over a full turn.
+pub fn measurement_angles(samples: u32) -> Vec<f64> {
+    (0..samples)
+        .map(|index| index as f64 * 360.0 / samples as f64)
+        .collect()
+}
+
+/// Rotate the nozzle through a full turn, measuring the tip at each angle, then fit the runout.
+///
+/// The nozzle is rotated back to 0 degrees afterwards.
+pub async fn measure_runout(
+    rotator: &mut impl NozzleRotator,
+    locator: &mut impl TipLocator,
+    config: &RunoutConfig,
+) -> anyhow::Result<NozzleRunout> {
+    if (config.samples as usize) < MIN_SAMPLES {
+        bail!(
+            "Too few runout samples. samples: {}, min: {}",
+            config.samples,
+            MIN_SAMPLES
+        )
+    }
+    info!(
+        "Runout measurement started. nozzle: {}, samples: {}",
+        config.nozzle, config.samples
+    );
+
+    let mut samples = Vec::with_capacity(config.samples as usize);
+    for angle in measurement_angles(config.samples) {
+        rotator.rotate_to(angle).await?;
+        match locator.locate().await {
+            Ok(Some(tip)) => {
+                debug!("Runout sample. angle: {}, tip: {:?}", angle, tip);
+                samples.push(RunoutSample {
+                    angle,
+                    tip,
+                });
+            }
+            Ok(None) => warn!("Nozzle tip not found. angle: {}", angle),
+            Err(e) => warn!("Unable to locate nozzle tip. angle: {}, error: {:?}", angle, e),
+        }
+    }
+    rotator.rotate_to(0.0).await?;
+
+    let fit = fit_runout(&samples).ok_or_else(|| {
+        anyhow!(
+            "Unable to fit the runout, too few tip positions. measured: {}, samples: {}",
+            samples.len(),
+            config.samples
+        )
+    })?;
+
+    info!(
+        "Runout measured. nozzle: {}, runout: {:.4}mm, phase: {:.1}, center: {:?}, residual: {:.4}mm",
+        config.nozzle, fit.radius_mm, fit.phase_degrees, fit.center, fit.residual_mm
+    );
+    if fit.radius_mm > config.max_runout_mm {
+        warn!(
+            "Nozzle runout above the maximum, the nozzle may be bent or worn. nozzle: {}, runout: {:.4}mm, max: {:.4}mm",
+            config.nozzle, fit.radius_mm, config.max_runout_mm
+        );
+    }
+
+    Ok(NozzleRunout {
+        nozzle: config.nozzle,
+        measured_at: Utc::now(),
+        fit,
+        samples,
+    })
+}
+
+/// The runout of each measured nozzle.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunoutCorrections {
+    pub nozzles: BTreeMap<u8, NozzleRunout>,
+}
+
+/// A single file, written to a temporary file first and then renamed, so that a failed write leaves the previous
+/// corrections intact.
+#[derive(Debug, Clone)]
+pub struct RunoutStore {
+    path: PathBuf,
+}
+
+impl RunoutStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+        }
+    }
+
+    /// No corrections if nothing has been measured yet.
+    pub async fn load(&self) -> anyhow::Result<RunoutCorrections> {
+        let content = match fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(RunoutCorrections::default()),
+            Err(e) => {
+                return Err(anyhow!(
+                    "Unable to read runout corrections. path: {:?}, error: {}",
+                    self.path,
+                    e
+                ));
+            }
+        };
+        ron::from_str::<RunoutCorrections>(&content).map_err(|e| {
+            anyhow!(
+                "Unable to parse runout corrections. path: {:?}, error: {}",
+                self.path,
+                e
+            )
+        })
+    }
+
+    /// Replaces the runout of the nozzle, the other nozzles are kept.
+    pub async fn store(&self, runout: NozzleRunout) -> anyhow::Result<()> {
+        let mut corrections = self.load().await?;
+        corrections
+            .nozzles
+            .insert(runout.nozzle, runout);
+        let content = ron::ser::to_string_pretty(&corrections, ron::ser::PrettyConfig::default())?;
+
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".tmp");
+        let mut file = fs::File::create(&temporary_path).await?;
+        file.write_all(content.as_bytes())
+            .await?;
+        file.sync_all().await?;
+        drop(file);
+
+        fs::rename(&temporary_path, &self.path).await?;
+        Ok(())
+    }
+}
+
+/// Corrects the position of each placed part by the runout of the nozzle at the rotation of the placement, dispense
+/// operations are placed unchanged.
+pub struct RunoutPlacer<P: Placer> {
+    placer: P,
+    /// `None` if the nozzle has not been measured
+    runout: Option<NozzleRunout>,
+}
+
+impl<P: Placer> RunoutPlacer<P> {
+    pub fn new(placer: P, runout: Option<NozzleRunout>) -> Self {
+        Self {
+            placer,
+            runout,
+        }
+    }
+}
+
+impl<P: Placer + Send> Placer for RunoutPlacer<P> {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let Some(runout) = &self.runout else {
+                return self.placer.place(placement).await;
+            };
+            if !matches!(placement.operation, Operation::Place) {
+                return self.placer.place(placement).await;
+            }
+
+            let correction = runout.correction_at(placement.rotation);
+            let corrected = Placement {
+                position: placement.position - correction,
+                ..placement.clone()
+            };
+            self.placer.place(&corrected).await
+        }
+    }
+
+    fn skips(&self, placement: &Placement) -> bool {
+        self.placer.skips(placement)
+    }
+
+    fn dry_run(&self) -> bool {
+        self.placer.dry_run()
+    }
+}
@@ -0,0 +1,81 @@
+use std::future::Future;
+
+use anyhow::anyhow;
+use log::{debug, info};
+use tokio::sync::watch;
+use tokio::time::{self, Duration};
+
+use super::NozzleRotator;
+use crate::config::RunoutConfig;
+use crate::ioboard::batching::CommandBatcher;
+use crate::motion::{AxisMove, plan_setpoints, stream_setpoints};
+use crate::safety::SafetyState;
+
+/// Rotates the nozzle with an axis planned by the server, see
+/// [`MotionPlanning::Server`](crate::config::MotionPlanning::Server).
+pub struct SetpointRotator {
+    batcher: CommandBatcher,
+    interval: Duration,
+    settle: Duration,
+    config: RunoutConfig,
+    safety_rx: watch::Receiver<SafetyState>,
+    /// in steps, the axis is assumed to start at zero, the same as the setpoint streamer
+    position: f64,
+}
+
+impl SetpointRotator {
+    pub fn new(
+        batcher: CommandBatcher,
+        rate_hz: u32,
+        config: &RunoutConfig,
+        safety_rx: watch::Receiver<SafetyState>,
+    ) -> Self {
+        Self {
+            batcher,
+            interval: Duration::from_micros(1_000_000 / rate_hz.max(1) as u64),
+            settle: Duration::from_millis(config.settle_ms),
+            config: config.clone(),
+            safety_rx,
+            position: 0.0,
+        }
+    }
+}
+
+impl NozzleRotator for SetpointRotator {
+    fn rotate_to<'a>(&'a mut self, angle: f64) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            let axis = self.config.rotation_axis;
+            if !self
+                .safety_rx
+                .borrow()
+                .allows_new_moves()
+            {
+                info!("Waiting for the safety state to allow motion, axis: {}", axis);
+            }
+            let speed_factor = self
+                .safety_rx
+                .wait_for(SafetyState::allows_new_moves)
+                .await
+                .map_err(|_| anyhow!("Safety listener stopped"))?
+                .speed_factor();
+
+            let steps_per_degree = self.config.steps_per_degree;
+            let axis_move = AxisMove {
+                target: angle * steps_per_degree,
+                max_jerk: self.config.max_jerk * steps_per_degree,
+                max_acceleration: self.config.max_acceleration * steps_per_degree * speed_factor,
+                max_velocity: self.config.max_velocity * steps_per_degree * speed_factor,
+            };
+
+            let setpoints = plan_setpoints(self.position, &axis_move, self.interval)
+                .map_err(|e| anyhow!("Unable to plan rotation. move: {:?}, error: {:?}", axis_move, e))?;
+            debug!("Planned rotation, axis: {}, setpoints: {}", axis, setpoints.len());
+
+            stream_setpoints(&self.batcher, axis, &setpoints, self.interval).await;
+            self.position = axis_move.target;
+
+            time::sleep(self.settle).await;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,367 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use machine_geometry::Point;
+
+use super::{
+    NozzleRotator, NozzleRunout, RunoutFit, RunoutPlacer, RunoutSample, RunoutStore, TipLocator, fit_runout,
+    measure_runout,
+};
+use crate::config::RunoutConfig;
+use crate::job::{Operation, Placement, Placer};
+
+/// The tip of a simulated nozzle, on a circle around `center`.
+#[derive(Debug, Clone, Copy)]
+struct Nozzle {
+    center: Point,
+    radius: f64,
+    /// in degrees
+    phase: f64,
+    clockwise: bool,
+}
+
+impl Nozzle {
+    fn tip_at(&self, angle: f64) -> Point {
+        let direction = match self.clockwise {
+            true => -1.0,
+            false => 1.0,
+        };
+        let tip_angle = (self.phase + direction * angle).to_radians();
+        Point {
+            x: self.center.x + self.radius * tip_angle.cos(),
+            y: self.center.y + self.radius * tip_angle.sin(),
+        }
+    }
+
+    fn samples(&self, angles: &[f64]) -> Vec<RunoutSample> {
+        angles
+            .iter()
+            .map(|angle| RunoutSample {
+                angle: *angle,
+                tip: self.tip_at(*angle),
+            })
+            .collect()
+    }
+}
+
+fn nozzle(clockwise: bool) -> Nozzle {
+    Nozzle {
+        center: Point {
+            x: 0.3,
+            y: -0.2,
+        },
+        radius: 0.05,
+        phase: 30.0,
+        clockwise,
+    }
+}
+
+struct FakeRotator {
+    angle: Arc<Mutex<f64>>,
+    rotations: Vec<f64>,
+}
+
+impl NozzleRotator for FakeRotator {
+    fn rotate_to<'a>(&'a mut self, angle: f64) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            *self.angle.lock().unwrap() = angle;
+            self.rotations.push(angle);
+            Ok(())
+        }
+    }
+}
+
+/// Sees the tip of the nozzle at the angle of the rotator, `None` at the angles in `missing`.
+struct FakeLocator {
+    angle: Arc<Mutex<f64>>,
+    nozzle: Nozzle,
+    missing: Vec<f64>,
+}
+
+impl TipLocator for FakeLocator {
+    fn locate<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a {
+        async move {
+            let angle = *self.angle.lock().unwrap();
+            if self.missing.contains(&angle) {
+                return Ok(None);
+            }
+            Ok(Some(self.nozzle.tip_at(angle)))
+        }
+    }
+}
+
+#[derive(Default)]
+struct FakePlacer {
+    placed: Vec<Placement>,
+}
+
+impl Placer for FakePlacer {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            self.placed.push(placement.clone());
+            Ok(())
+        }
+    }
+}
+
+fn placement(rotation: f64, operation: Operation) -> Placement {
+    Placement {
+        reference: "U1".to_string(),
+        position: Point {
+            x: 50.0,
+            y: 40.0,
+        },
+        rotation,
+        feeder: None,
+        operation,
+        board: None,
+    }
+}
+
+fn runout(nozzle: Nozzle) -> NozzleRunout {
+    NozzleRunout {
+        nozzle: 0,
+        measured_at: Utc::now(),
+        fit: RunoutFit {
+            center: nozzle.center,
+            radius_mm: nozzle.radius,
+            phase_degrees: nozzle.phase,
+            clockwise: nozzle.clockwise,
+            residual_mm: 0.0,
+        },
+        samples: vec![],
+    }
+}
+
+fn assert_near(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() < 1e-9,
+        "actual: {}, expected: {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+pub fn fit_finds_the_runout_circle() {
+    for clockwise in [false, true] {
+        // given
+        let nozzle = nozzle(clockwise);
+        let samples = nozzle.samples(&[0.0, 90.0, 180.0, 270.0]);
+
+        // when
+        let fit = fit_runout(&samples).unwrap();
+
+        // then
+        assert_near(fit.center.x, nozzle.center.x);
+        assert_near(fit.center.y, nozzle.center.y);
+        assert_near(fit.radius_mm, nozzle.radius);
+        assert_near(fit.phase_degrees, nozzle.phase);
+        assert_eq!(fit.clockwise, clockwise);
+        assert!(fit.residual_mm < 1e-9);
+    }
+}
+
+#[test]
+pub fn fit_of_a_partial_turn() {
+    // given
+    let nozzle = nozzle(false);
+    let samples = nozzle.samples(&[0.0, 45.0, 90.0]);
+
+    // when
+    let fit = fit_runout(&samples).unwrap();
+
+    // then
+    assert_near(fit.radius_mm, nozzle.radius);
+    assert_near(fit.phase_degrees, nozzle.phase);
+}
+
+#[test]
+pub fn fit_without_runout() {
+    // given
+    let samples = Nozzle {
+        radius: 0.0,
+        ..nozzle(false)
+    }
+    .samples(&[0.0, 120.0, 240.0]);
+
+    // when
+    let fit = fit_runout(&samples).unwrap();
+
+    // then
+    assert_eq!(fit.radius_mm, 0.0);
+    assert_near(fit.center.x, 0.3);
+    assert_near(fit.center.y, -0.2);
+}
+
+#[test]
+pub fn fit_requires_a_circle() {
+    // given
+    let too_few = nozzle(false).samples(&[0.0, 180.0]);
+    let on_a_line = [0.0, 1.0, 2.0]
+        .iter()
+        .map(|x| RunoutSample {
+            angle: x * 90.0,
+            tip: Point {
+                x: *x,
+                y: *x,
+            },
+        })
+        .collect::<Vec<_>>();
+
+    // expect
+    assert_eq!(fit_runout(&too_few), None);
+    assert_eq!(fit_runout(&on_a_line), None);
+}
+
+#[test]
+pub fn correction_is_relative_to_the_tip_at_zero_degrees() {
+    // given
+    let runout = runout(Nozzle {
+        phase: 0.0,
+        ..nozzle(false)
+    });
+
+    // when
+    let at_zero = runout.correction_at(0.0);
+    let at_half_turn = runout.correction_at(180.0);
+    let at_quarter_turn = runout.correction_at(90.0);
+
+    // then
+    assert_near(at_zero.x, 0.0);
+    assert_near(at_zero.y, 0.0);
+    assert_near(at_half_turn.x, -0.1);
+    assert_near(at_half_turn.y, 0.0);
+    assert_near(at_quarter_turn.x, -0.05);
+    assert_near(at_quarter_turn.y, 0.05);
+}
+
+#[tokio::test]
+pub async fn measure_runout_rotates_a_full_turn_and_back() {
+    // given
+    let nozzle = nozzle(true);
+    let angle = Arc::new(Mutex::new(0.0));
+    let mut rotator = FakeRotator {
+        angle: angle.clone(),
+        rotations: vec![],
+    };
+    let mut locator = FakeLocator {
+        angle,
+        nozzle,
+        missing: vec![90.0],
+    };
+    let config = RunoutConfig {
+        samples: 4,
+        ..RunoutConfig::default()
+    };
+
+    // when
+    let runout = measure_runout(&mut rotator, &mut locator, &config)
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(rotator.rotations, vec![0.0, 90.0, 180.0, 270.0, 0.0]);
+    // the tip was not found at 90 degrees
+    assert_eq!(
+        runout
+            .samples
+            .iter()
+            .map(|sample| sample.angle)
+            .collect::<Vec<_>>(),
+        vec![0.0, 180.0, 270.0]
+    );
+    assert_near(runout.fit.radius_mm, nozzle.radius);
+    assert!(runout.fit.clockwise);
+}
+
+#[tokio::test]
+pub async fn measure_runout_fails_without_enough_tip_positions() {
+    // given
+    let angle = Arc::new(Mutex::new(0.0));
+    let mut rotator = FakeRotator {
+        angle: angle.clone(),
+        rotations: vec![],
+    };
+    let mut locator = FakeLocator {
+        angle,
+        nozzle: nozzle(false),
+        missing: vec![0.0, 120.0],
+    };
+    let config = RunoutConfig {
+        samples: 3,
+        ..RunoutConfig::default()
+    };
+
+    // when
+    let result = measure_runout(&mut rotator, &mut locator, &config).await;
+
+    // then
+    assert!(result.is_err());
+    // the nozzle is still rotated back
+    assert_eq!(rotator.rotations.last(), Some(&0.0));
+}
+
+#[tokio::test]
+pub async fn runout_placer_corrects_placed_parts_only() {
+    // given
+    let runout = runout(Nozzle {
+        phase: 0.0,
+        ..nozzle(false)
+    });
+    let mut placer = RunoutPlacer::new(FakePlacer::default(), Some(runout));
+    let dispense = Operation::Dispense {
+        head: "paste".to_string(),
+        dispense_ms: None,
+    };
+
+    // when
+    placer
+        .place(&placement(180.0, Operation::Place))
+        .await
+        .unwrap();
+    placer
+        .place(&placement(180.0, dispense))
+        .await
+        .unwrap();
+
+    // then
+    let placed = &placer.placer.placed;
+    assert_near(placed[0].position.x, 50.1);
+    assert_near(placed[0].position.y, 40.0);
+    assert_eq!(placed[0].rotation, 180.0);
+    assert_eq!(placed[1].position, placement(180.0, Operation::Place).position);
+}
+
+#[tokio::test]
+pub async fn runout_store_keeps_other_nozzles() {
+    // given
+    let directory = std::env::temp_dir().join(format!("runout-test-{:016x}", rand::random::<u64>()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let store = RunoutStore::new(directory.join("runout.ron"));
+    let first = runout(nozzle(false));
+    let second = NozzleRunout {
+        nozzle: 1,
+        ..runout(nozzle(true))
+    };
+
+    // when
+    let empty = store.load().await.unwrap();
+    store
+        .store(first.clone())
+        .await
+        .unwrap();
+    store
+        .store(second.clone())
+        .await
+        .unwrap();
+    let loaded = store.load().await.unwrap();
+
+    // then
+    assert!(empty.nozzles.is_empty());
+    assert_eq!(loaded.nozzles.get(&0), Some(&first));
+    assert_eq!(loaded.nozzles.get(&1), Some(&second));
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,128 @@
+use std::future::Future;
+
+use log::{error, info, warn};
+use machine_geometry::{ImageView, Point};
+use machine_ids::CameraId;
+use server_common::camera::{CameraCalibration, CameraDefinition, CameraMounting};
+use server_vision::fiducial::find_center_dot;
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::watch;
+
+use super::rotator::SetpointRotator;
+use super::{RunoutStore, TipLocator, measure_runout};
+use crate::AppEvent;
+use crate::config::RunoutConfig;
+use crate::ioboard::batching::CommandBatcher;
+use crate::safety::SafetyState;
+use crate::vision::{VisionCaptureRequest, VisionQueue};
+
+/// Locates the tip using a calibrated up camera, frames are captured via the [`VisionQueue`].
+///
+/// The tip is found by its bore, the dark dot nearest the center of the image.
+pub struct VisionTipLocator {
+    vision_queue: VisionQueue,
+    camera: CameraId,
+    calibration: CameraCalibration,
+}
+
+impl VisionTipLocator {
+    pub fn new(vision_queue: VisionQueue, camera: CameraId, calibration: CameraCalibration) -> Self {
+        Self {
+            vision_queue,
+            camera,
+            calibration,
+        }
+    }
+}
+
+impl TipLocator for VisionTipLocator {
+    fn locate<'a>(&'a mut self) -> impl Future<Output = anyhow::Result<Option<Point>>> + Send + 'a {
+        async move {
+            let frame = self
+                .vision_queue
+                .capture(VisionCaptureRequest {
+                    camera: self.camera,
+                    pause_preview: true,
+                    metering_roi: None,
+                })
+                .await?;
+
+            // decoding and contour finding takes longer than is acceptable for the runtime
+            let dot = tokio::task::spawn_blocking(move || find_center_dot(&frame.jpeg_bytes)).await??;
+
+            // FUTURE the orientation of the camera should be part of its calibration
+            let scale = self.calibration.pixel_scale();
+            Ok(dot.map(|dot| scale.to_machine(dot.center, ImageView::FromBelow)))
+        }
+    }
+}
+
+/// The first up camera that has a calibration, cameras are identified by index, see
+/// [`camera_definition_for_identifier`](crate::camera::camera_definition_for_identifier).
+fn up_camera(cameras: &[CameraDefinition]) -> Option<(CameraId, &CameraDefinition, CameraCalibration)> {
+    cameras.iter().enumerate().find_map(
+        |(index, definition)| match (definition.mounting, definition.calibration) {
+            (CameraMounting::Up, Some(calibration)) => Some((CameraId::new(index as u8), definition, calibration)),
+            _ => None,
+        },
+    )
+}
+
+/// Measures the runout of the nozzle once and stores it, the nozzle must already be over the up camera.
+///
+/// The stored runout is applied to the placements the next time the server is started.
+pub async fn runout_runner(
+    batcher: CommandBatcher,
+    rate_hz: u32,
+    cameras: Vec<CameraDefinition>,
+    config: RunoutConfig,
+    vision_queue: VisionQueue,
+    safety_rx: watch::Receiver<SafetyState>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let Some((camera, definition, calibration)) = up_camera(&cameras) else {
+        error!("Runout measurement requires a calibrated up camera");
+        return;
+    };
+
+    info!(
+        "Runout measurement. camera: {}, nozzle: {}, axis: {}",
+        definition.name, config.nozzle, config.rotation_axis
+    );
+
+    let mut rotator = SetpointRotator::new(batcher, rate_hz, &config, safety_rx);
+    let mut locator = VisionTipLocator::new(vision_queue, camera, calibration);
+
+    let app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let result = select! {
+        _ = app_shutdown_handler => {
+            warn!("Runout measurement cancelled by shutdown");
+            return;
+        }
+        result = measure_runout(&mut rotator, &mut locator, &config) => result,
+    };
+
+    let runout = match result {
+        Ok(runout) => runout,
+        Err(e) => {
+            error!("Runout measurement failed. error: {:?}", e);
+            return;
+        }
+    };
+
+    let runout_mm = runout.fit.radius_mm;
+    match RunoutStore::new(config.corrections_path.clone())
+        .store(runout)
+        .await
+    {
+        Ok(()) => info!(
+            "Runout stored. path: {}, nozzle: {}, runout: {:.4}mm",
+            config.corrections_path.display(),
+            config.nozzle,
+            runout_mm
+        ),
+        Err(e) => error!("Unable to store runout. error: {:?}", e),
+    }
+}
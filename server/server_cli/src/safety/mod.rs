@@ -0,0 +1,138 @@
+//! Mirrors the safety status published by the io board, so that the server-side motion planner applies the same
+//! restrictions as the on-board planner, see `ioboard_main::safety`.
+//!
+//! The io board publishes the status periodically, if it stops arriving the server can no longer tell whether a
+//! safety input is tripped, so motion is paused until it arrives again.
+
+use std::pin::pin;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::topic;
+use ioboard_shared::safety::{MotionRestriction, SafetyStatus};
+use log::{info, warn};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::watch;
+use tokio::time::{self, Duration, Instant};
+
+use crate::AppEvent;
+
+#[cfg(test)]
+mod tests;
+
+topic!(SafetyTopic, SafetyStatus, "topic/ioboard/safety");
+
+/// Several times the interval the io board publishes the status at.
+pub const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SafetyState {
+    pub restriction: MotionRestriction,
+    /// `None` until the first status has been received, or when the last status is stale.
+    pub status: Option<SafetyStatus>,
+}
+
+impl SafetyState {
+    /// The state before any status has been received.
+    pub const UNKNOWN: SafetyState = SafetyState {
+        restriction: MotionRestriction::Paused,
+        status: None,
+    };
+
+    /// Scale to apply to the max velocity and acceleration, 1.0 unless the speed is reduced.
+    pub fn speed_factor(&self) -> f64 {
+        match (self.restriction, self.status) {
+            (MotionRestriction::ReducedSpeed, Some(status)) => status.reduced_speed_factor as f64,
+            _ => 1.0,
+        }
+    }
+
+    pub fn allows_new_moves(&self) -> bool {
+        matches!(
+            self.restriction,
+            MotionRestriction::None | MotionRestriction::ReducedSpeed
+        )
+    }
+}
+
+impl Default for SafetyState {
+    fn default() -> Self {
+        Self::UNKNOWN
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SafetyMirror {
+    last_status: Option<(SafetyStatus, Instant)>,
+}
+
+impl SafetyMirror {
+    pub fn update(&mut self, status: SafetyStatus, now: Instant) {
+        self.last_status = Some((status, now));
+    }
+
+    pub fn state(&self, now: Instant) -> SafetyState {
+        match self.last_status {
+            Some((status, received_at)) if now.duration_since(received_at) <= STATUS_TIMEOUT => SafetyState {
+                restriction: status.restriction,
+                status: Some(status),
+            },
+            _ => SafetyState::UNKNOWN,
+        }
+    }
+}
+
+/// Listens for the safety status of the io board, the current state is sent to `state_tx`.
+pub async fn safety_listener(
+    stack: RouterStack,
+    state_tx: watch::Sender<SafetyState>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let subber = stack
+        .topics()
+        .heap_bounded_receiver::<SafetyTopic>(16, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    let mut mirror = SafetyMirror::default();
+    let mut ticker = time::interval(Duration::from_millis(500));
+
+    loop {
+        select! {
+            msg = hdl.recv() => {
+                mirror.update(msg.t, Instant::now());
+            }
+            _ = ticker.tick() => {}
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+        }
+
+        let state = mirror.state(Instant::now());
+        state_tx.send_if_modified(|current| {
+            if *current == state {
+                return false;
+            }
+            if current.restriction != state.restriction {
+                match (state.restriction, state.status) {
+                    (MotionRestriction::None, _) => info!("Motion restriction cleared"),
+                    (restriction, Some(status)) => warn!(
+                        "Motion restricted by safety input. restriction: {:?}, door: {:?}, light curtain: {:?}",
+                        restriction, status.door, status.light_curtain
+                    ),
+                    (restriction, None) => {
+                        warn!(
+                            "Safety status unavailable, motion restricted. restriction: {:?}",
+                            restriction
+                        )
+                    }
+                }
+            }
+            *current = state;
+            true
+        });
+    }
+    info!("safety listener shutdown");
+}
@@ -0,0 +1,85 @@
+use ioboard_shared::safety::{MotionRestriction, SafetyInputState, SafetyStatus};
+use tokio::time::{Duration, Instant};
+
+use super::{STATUS_TIMEOUT, SafetyMirror, SafetyState};
+
+fn door_open_status(restriction: MotionRestriction) -> SafetyStatus {
+    SafetyStatus {
+        door: SafetyInputState::Tripped,
+        light_curtain: SafetyInputState::NotConfigured,
+        restriction,
+        reduced_speed_factor: 0.25,
+        estop: None,
+    }
+}
+
+#[test]
+pub fn unknown_until_status_received() {
+    // given
+    let mirror = SafetyMirror::default();
+
+    // when
+    let state = mirror.state(Instant::now());
+
+    // then
+    assert_eq!(state, SafetyState::UNKNOWN);
+    assert!(!state.allows_new_moves());
+}
+
+#[test]
+pub fn reduced_speed_applies_factor() {
+    // given
+    let mut mirror = SafetyMirror::default();
+    let now = Instant::now();
+    mirror.update(door_open_status(MotionRestriction::ReducedSpeed), now);
+
+    // when
+    let state = mirror.state(now);
+
+    // then
+    assert!(state.allows_new_moves());
+    assert_eq!(state.speed_factor(), 0.25);
+}
+
+#[test]
+pub fn factor_ignored_when_not_reduced() {
+    // given
+    let mut mirror = SafetyMirror::default();
+    let now = Instant::now();
+    mirror.update(door_open_status(MotionRestriction::None), now);
+
+    // when
+    let state = mirror.state(now);
+
+    // then
+    assert!(state.allows_new_moves());
+    assert_eq!(state.speed_factor(), 1.0);
+}
+
+#[test]
+pub fn paused_does_not_allow_new_moves() {
+    // given
+    let mut mirror = SafetyMirror::default();
+    let now = Instant::now();
+    mirror.update(door_open_status(MotionRestriction::Paused), now);
+
+    // when
+    let state = mirror.state(now);
+
+    // then
+    assert!(!state.allows_new_moves());
+}
+
+#[test]
+pub fn stale_status_pauses_motion() {
+    // given
+    let mut mirror = SafetyMirror::default();
+    let now = Instant::now();
+    mirror.update(door_open_status(MotionRestriction::None), now);
+
+    // when
+    let state = mirror.state(now + STATUS_TIMEOUT + Duration::from_millis(1));
+
+    // then
+    assert_eq!(state, SafetyState::UNKNOWN);
+}
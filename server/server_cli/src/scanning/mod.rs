@@ -0,0 +1,154 @@
+//! Scanning barcodes and QR codes with the down camera.
+//!
+//! The ID of a board selects the job for the board, see [`job_path_for_board`], and the ID of a reel is assigned to
+//! the feeder the reel was loaded into.
+
+use std::sync::Arc;
+
+use log::{debug, info, warn};
+use machine_ids::CameraId;
+use operator_shared::vision::{ScanError, ScanTarget};
+use server_common::camera::{CameraDefinition, CameraMounting};
+use server_vision::barcode::decode_codes;
+use tokio::sync::Mutex;
+
+use crate::AppState;
+use crate::captures::{FailureCaptures, failure_annotation};
+use crate::job::checkpoint::CheckpointStore;
+use crate::job::{job_path_for_board, load_job};
+use crate::vision::{VisionCaptureRequest, VisionQueue};
+
+/// Scans a code and applies it to the `target`, returns the scanned code.
+pub async fn scan(app_state: &Arc<Mutex<AppState>>, target: &ScanTarget) -> Result<String, ScanError> {
+    let (vision_queue, camera, failures) = {
+        let app_state = app_state.lock().await;
+        let camera = down_camera(&app_state.config.cameras).ok_or(ScanError::NoCamera)?;
+        let failures = FailureCaptures::new(app_state.capture_store.clone(), "scan");
+        (app_state.vision_queue.clone(), camera, failures)
+    };
+
+    let code = scan_code(&vision_queue, camera, &failures).await?;
+
+    match target {
+        ScanTarget::Board => select_job(app_state, &code).await?,
+        ScanTarget::Reel {
+            feeder,
+        } => {
+            let feeders = app_state.lock().await.feeders.clone();
+            feeders
+                .lock()
+                .await
+                .assign_reel(feeder, code.clone())
+                .map_err(|_| ScanError::UnknownFeeder)?;
+        }
+    }
+
+    Ok(code)
+}
+
+/// The first down camera, cameras are identified by index, see
+/// [`camera_definition_for_identifier`](crate::camera::camera_definition_for_identifier).
+fn down_camera(cameras: &[CameraDefinition]) -> Option<CameraId> {
+    cameras
+        .iter()
+        .position(|definition| matches!(definition.mounting, CameraMounting::Down))
+        .map(|index| CameraId::new(index as u8))
+}
+
+/// The frame is saved when no code is found in it, see [`FailureCaptures`].
+async fn scan_code(
+    vision_queue: &VisionQueue,
+    camera: CameraId,
+    failures: &FailureCaptures,
+) -> Result<String, ScanError> {
+    let frame = vision_queue
+        .capture(VisionCaptureRequest {
+            camera,
+            pause_preview: true,
+            metering_roi: None,
+        })
+        .await
+        .map_err(|e| {
+            warn!("Scan capture failed. camera: {}, error: {:?}", camera, e);
+            ScanError::CaptureFailed
+        })?;
+
+    // decoding takes longer than is acceptable for the runtime
+    let decoded = tokio::task::spawn_blocking({
+        let frame = frame.clone();
+        move || decode_codes(&frame.jpeg_bytes)
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!(e))
+    .and_then(|result| result);
+    let codes = match decoded {
+        Ok(codes) => codes,
+        Err(e) => {
+            warn!("Unable to decode codes. camera: {}, error: {:?}", camera, e);
+            failures
+                .save(&camera.to_string(), &frame, &[failure_annotation(&e)])
+                .await;
+            return Err(ScanError::CaptureFailed);
+        }
+    };
+
+    debug!("Codes decoded. camera: {}, codes: {:?}", camera, codes);
+
+    // FUTURE let the operator pick when there are several codes in view
+    let Some(code) = codes.into_iter().next() else {
+        failures
+            .save(&camera.to_string(), &frame, &[failure_annotation("No code found")])
+            .await;
+        return Err(ScanError::NoCode);
+    };
+    info!("Code scanned. code: {}, format: {}", code.text, code.format);
+
+    Ok(code.text)
+}
+
+/// The checkpoint of an interrupted run of the job is restored, so that the operator can resume it.
+async fn select_job(app_state: &Arc<Mutex<AppState>>, board_id: &str) -> Result<(), ScanError> {
+    let (jobs_directory, checkpoint_store, job_control) = {
+        let app_state = app_state.lock().await;
+        (
+            app_state
+                .config
+                .job
+                .jobs_directory
+                .clone(),
+            CheckpointStore::new(
+                app_state
+                    .config
+                    .job
+                    .checkpoint_path
+                    .clone(),
+            ),
+            app_state.job_control.clone(),
+        )
+    };
+
+    let unknown_job = || ScanError::UnknownJob(board_id.to_string());
+    let path = job_path_for_board(&jobs_directory, board_id).ok_or_else(unknown_job)?;
+    let job = load_job(&path).map_err(|e| {
+        warn!("No job for board. board: {}, error: {:?}", board_id, e);
+        unknown_job()
+    })?;
+
+    let checkpoint = checkpoint_store
+        .load()
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Unable to load checkpoint. error: {:?}", e);
+            None
+        });
+
+    let name = job.name.clone();
+    job_control
+        .lock()
+        .await
+        .select(job, checkpoint)
+        .map_err(|_| ScanError::JobRunning)?;
+    info!("Job selected for board. board: {}, job: {}", board_id, name);
+
+    Ok(())
+}
@@ -0,0 +1,342 @@
+//! Separate router networks of the server, network segments, each on its own interface, e.g. a NIC for the cameras,
+//! so that the camera streams don't delay the control traffic of the io boards, see [`NetworkSegmentDefinition`].
+//!
+//! Each segment has its own router, nothing is routed between a segment and the control network, the network of the
+//! io boards and the operator UI, except the topics that match the routing policy of the segment.  The messages of the
+//! `export` topics are forwarded from the control network to the segment, the messages of the `import` topics from the
+//! segment to the control network.  A topic can't be forwarded both ways, the forwarded messages would loop.
+//!
+//! A segment can serve the operator commands, so that an operator UI on the segment starts the camera streams on the
+//! segment, the frames of the streams are then only sent on the segment.
+//!
+//! A forwarded message is published again by the server, the source of the message is the server, not the origin.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::pin::{Pin, pin};
+
+use ergot::toolkits::tokio_udp::{RouterStack, register_router_interface};
+use ergot::traits::Topic;
+use ioboard_shared::estop::EStopTopic;
+use log::{info, warn};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio::net::UdpSocket;
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio_util::sync::CancellationToken;
+
+use crate::AppEvent;
+use crate::analog::AnalogTopic;
+#[cfg(feature = "machine-vision")]
+use crate::camera::budget::CameraMemoryTopic;
+use crate::config::NetworkSegmentDefinition;
+use crate::diagnostics::CommandLatencyTopic;
+use crate::diagnostics::tap::TopicPattern;
+use crate::feeders::{FeederEventTopic, FeedersStatusTopic};
+use crate::homing::HomingStatusTopic;
+use crate::ioboard::IoBoardEventTopic;
+use crate::job::JobEventTopic;
+use crate::limits::LimitOverrideTopic;
+use crate::motion::PositionTopic;
+use crate::networking::inspection::RouterReportTopic;
+use crate::networking::{LoadCellTopic, UDP_OVER_ETH_ERGOT_PAYLOAD_SIZE_MAX, dead_letter};
+use crate::nozzles::MaintenanceTopic;
+use crate::operator::OPERATOR_TX_BUFFER_SIZE;
+use crate::power::PowerTopic;
+use crate::readiness::{ReadinessTopic, SelfTestTopic};
+use crate::resonance::ResonanceSweepTopic;
+use crate::safety::SafetyTopic;
+use crate::test_area::TestShotEventTopic;
+#[cfg(feature = "machine-vision")]
+use crate::vision::VisionStatusTopic;
+
+#[cfg(test)]
+mod tests;
+
+/// Messages of a topic are dropped when the other network falls behind by more than this.
+const FORWARD_QUEUE_SIZE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SegmentDefinitionError {
+    /// the segment is defined more than once
+    DuplicateName(String),
+    /// the port of the local address is used by another segment, or by the control network
+    DuplicateAddress(SocketAddr),
+    InvalidPattern(String),
+    /// the path of a topic matches both an `export` and an `import` pattern of the segment
+    ForwardedBothWays(&'static str),
+}
+
+type ForwardFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A topic that can be forwarded between the networks, the messages are decoded using the message type of the topic.
+pub struct SegmentTopic {
+    pub path: &'static str,
+    forward: fn(RouterStack, RouterStack, CancellationToken) -> ForwardFuture,
+}
+
+impl SegmentTopic {
+    fn of<T>() -> Self
+    where
+        T: Topic + 'static,
+        T::Message: Serialize + DeserializeOwned + Clone + Send + 'static,
+    {
+        Self {
+            path: T::PATH,
+            forward: |from, to, cancel| Box::pin(forward_topic::<T>(from, to, cancel)),
+        }
+    }
+}
+
+/// The topics that can be forwarded, the status topics and the e-stop.
+///
+/// The io board commands and setpoints are left out, the io boards are on the control network.  The camera frames are
+/// left out too, a stream is sent on the network of the operator UI that started it.
+pub fn topic_registry() -> Vec<SegmentTopic> {
+    vec![
+        SegmentTopic::of::<EStopTopic>(),
+        SegmentTopic::of::<IoBoardEventTopic>(),
+        SegmentTopic::of::<PositionTopic>(),
+        SegmentTopic::of::<SafetyTopic>(),
+        SegmentTopic::of::<SelfTestTopic>(),
+        SegmentTopic::of::<ReadinessTopic>(),
+        SegmentTopic::of::<HomingStatusTopic>(),
+        SegmentTopic::of::<LimitOverrideTopic>(),
+        SegmentTopic::of::<ResonanceSweepTopic>(),
+        SegmentTopic::of::<JobEventTopic>(),
+        SegmentTopic::of::<FeedersStatusTopic>(),
+        SegmentTopic::of::<FeederEventTopic>(),
+        SegmentTopic::of::<MaintenanceTopic>(),
+        SegmentTopic::of::<TestShotEventTopic>(),
+        SegmentTopic::of::<PowerTopic>(),
+        SegmentTopic::of::<AnalogTopic>(),
+        SegmentTopic::of::<LoadCellTopic>(),
+        SegmentTopic::of::<CommandLatencyTopic>(),
+        SegmentTopic::of::<RouterReportTopic>(),
+        #[cfg(feature = "machine-vision")]
+        SegmentTopic::of::<CameraMemoryTopic>(),
+        #[cfg(feature = "machine-vision")]
+        SegmentTopic::of::<VisionStatusTopic>(),
+    ]
+}
+
+/// The topics forwarded between a segment and the control network.
+pub struct SegmentRoutes {
+    /// from the control network to the segment
+    pub export: Vec<SegmentTopic>,
+    /// from the segment to the control network
+    pub import: Vec<SegmentTopic>,
+}
+
+fn matching_topics(patterns: &[String]) -> Result<Vec<SegmentTopic>, SegmentDefinitionError> {
+    let patterns = patterns
+        .iter()
+        .map(|pattern| {
+            TopicPattern::parse(pattern).map_err(|_| SegmentDefinitionError::InvalidPattern(pattern.clone()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(topic_registry()
+        .into_iter()
+        .filter(|topic| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.matches(topic.path))
+        })
+        .collect())
+}
+
+pub fn segment_routes(definition: &NetworkSegmentDefinition) -> Result<SegmentRoutes, SegmentDefinitionError> {
+    let export = matching_topics(&definition.export)?;
+    let import = matching_topics(&definition.import)?;
+    if let Some(topic) = export.iter().find(|exported| {
+        import
+            .iter()
+            .any(|imported| imported.path == exported.path)
+    }) {
+        return Err(SegmentDefinitionError::ForwardedBothWays(topic.path));
+    }
+    Ok(SegmentRoutes {
+        export,
+        import,
+    })
+}
+
+/// Two sockets can't be bound to the same port, unless both are bound to different addresses.
+fn conflicts(a: &SocketAddr, b: &SocketAddr) -> bool {
+    a.port() == b.port() && (a.ip() == b.ip() || a.ip().is_unspecified() || b.ip().is_unspecified())
+}
+
+/// The names of the segments are unique, the local addresses don't conflict, and the routes of each segment are
+/// valid, `control_addresses` are the local addresses of the control network.
+pub fn validate_segments(
+    definitions: &[NetworkSegmentDefinition],
+    control_addresses: &[SocketAddr],
+) -> Result<(), SegmentDefinitionError> {
+    let mut names = HashSet::new();
+    let mut addresses = control_addresses.to_vec();
+    for definition in definitions {
+        if !names.insert(definition.name.as_str()) {
+            return Err(SegmentDefinitionError::DuplicateName(definition.name.clone()));
+        }
+        if addresses
+            .iter()
+            .any(|address| conflicts(address, &definition.local_addr))
+        {
+            return Err(SegmentDefinitionError::DuplicateAddress(definition.local_addr));
+        }
+        addresses.push(definition.local_addr);
+        segment_routes(definition)?;
+    }
+    Ok(())
+}
+
+/// A segment, with its own router, see [`segment_router`].
+pub struct NetworkSegment {
+    pub definition: NetworkSegmentDefinition,
+    pub stack: RouterStack,
+}
+
+impl NetworkSegment {
+    /// Binds the local address of the segment, and registers the interface with the router of the segment.
+    pub async fn start(definition: NetworkSegmentDefinition) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind(definition.local_addr)
+            .await
+            .map_err(|e| {
+                anyhow::format_err!(
+                    "Unable to create local UDP socket for network segment. name: {}, address: {}, error: {}",
+                    definition.name,
+                    definition.local_addr,
+                    e
+                )
+            })?;
+        socket
+            .connect(definition.remote_addr)
+            .await
+            .map_err(|e| {
+                anyhow::format_err!(
+                    "Unable to create remote UDP socket for network segment. name: {}, address: {}, error: {}",
+                    definition.name,
+                    definition.remote_addr,
+                    e
+                )
+            })?;
+
+        let stack = RouterStack::new();
+        // the segments are for the traffic of the operator UI, e.g. the camera streams
+        register_router_interface(
+            &stack,
+            socket,
+            UDP_OVER_ETH_ERGOT_PAYLOAD_SIZE_MAX as _,
+            OPERATOR_TX_BUFFER_SIZE,
+        )
+        .await
+        .map_err(|e| {
+            anyhow::format_err!(
+                "Unable to register network segment interface. name: {}, error: {:?}",
+                definition.name,
+                e
+            )
+        })?;
+
+        info!(
+            "Network segment started. name: {}, local: {}, remote: {}, export: {:?}, import: {:?}, operator_commands: {}",
+            definition.name,
+            definition.local_addr,
+            definition.remote_addr,
+            definition.export,
+            definition.import,
+            definition.operator_commands
+        );
+        Ok(Self {
+            definition,
+            stack,
+        })
+    }
+}
+
+async fn forward_topic<T>(from: RouterStack, to: RouterStack, cancel: CancellationToken)
+where
+    T: Topic,
+    T::Message: Serialize + DeserializeOwned + Clone + Send + 'static,
+{
+    let subber = from
+        .topics()
+        .heap_bounded_receiver::<T>(FORWARD_QUEUE_SIZE, None);
+    let subber = pin!(subber);
+    let mut hdl = subber.subscribe();
+
+    loop {
+        select! {
+            _ = cancel.cancelled() => {
+                break
+            }
+            msg = hdl.recv() => {
+                if let Err(e) = to.topics().broadcast::<T>(&msg.t, None) {
+                    dead_letter::broadcast_failed::<T>(&e);
+                }
+            }
+        }
+    }
+}
+
+/// Forwards the topics of the routes of the segment between the segment and the control network, until the server
+/// shuts down.
+pub async fn segment_router(
+    control: RouterStack,
+    segment: RouterStack,
+    definition: NetworkSegmentDefinition,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let routes = match segment_routes(&definition) {
+        Ok(routes) => routes,
+        Err(e) => {
+            warn!(
+                "Invalid network segment routes, nothing is forwarded. name: {}, error: {:?}",
+                definition.name, e
+            );
+            crate::app_shutdown_handler(app_event_rx).await;
+            return;
+        }
+    };
+
+    let cancel = CancellationToken::new();
+    let mut forwarders = Vec::with_capacity(routes.export.len() + routes.import.len());
+    for topic in routes.export.iter() {
+        forwarders.push(tokio::spawn((topic.forward)(
+            control.clone(),
+            segment.clone(),
+            cancel.clone(),
+        )));
+    }
+    for topic in routes.import.iter() {
+        forwarders.push(tokio::spawn((topic.forward)(
+            segment.clone(),
+            control.clone(),
+            cancel.clone(),
+        )));
+    }
+    info!(
+        "Network segment routes. name: {}, export: {:?}, import: {:?}",
+        definition.name,
+        routes
+            .export
+            .iter()
+            .map(|topic| topic.path)
+            .collect::<Vec<_>>(),
+        routes
+            .import
+            .iter()
+            .map(|topic| topic.path)
+            .collect::<Vec<_>>()
+    );
+
+    crate::app_shutdown_handler(app_event_rx).await;
+
+    cancel.cancel();
+    for forwarder in forwarders {
+        let _ = forwarder.await;
+    }
+    info!("network segment router shutdown. name: {}", definition.name);
+}
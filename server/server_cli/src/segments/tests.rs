@@ -0,0 +1,144 @@
+use std::net::SocketAddr;
+
+use super::{SegmentDefinitionError, segment_routes, validate_segments};
+use crate::config::NetworkSegmentDefinition;
+
+fn definition(name: &str, local_addr: &str, export: &[&str], import: &[&str]) -> NetworkSegmentDefinition {
+    NetworkSegmentDefinition {
+        name: name.to_string(),
+        local_addr: local_addr.parse().unwrap(),
+        remote_addr: "192.168.20.255:8003".parse().unwrap(),
+        export: export
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect(),
+        import: import
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect(),
+        operator_commands: false,
+    }
+}
+
+fn control_addresses() -> Vec<SocketAddr> {
+    vec!["0.0.0.0:8000".parse().unwrap(), "0.0.0.0:8001".parse().unwrap()]
+}
+
+#[test]
+pub fn the_patterns_select_the_forwarded_topics() {
+    // given
+    let definition = definition("cameras", "192.168.20.1:8003", &["topic/operator/**"], &["topic/estop"]);
+
+    // when
+    let routes = segment_routes(&definition).unwrap();
+
+    // then
+    let exported = routes
+        .export
+        .iter()
+        .map(|topic| topic.path)
+        .collect::<Vec<_>>();
+    assert!(exported.contains(&"topic/operator/job"));
+    assert!(exported.contains(&"topic/operator/readiness"));
+    assert!(
+        exported
+            .iter()
+            .all(|path| path.starts_with("topic/operator/"))
+    );
+    let imported = routes
+        .import
+        .iter()
+        .map(|topic| topic.path)
+        .collect::<Vec<_>>();
+    assert_eq!(imported, vec!["topic/estop"]);
+}
+
+#[test]
+pub fn nothing_is_forwarded_without_patterns() {
+    // given
+    let definition = definition("cameras", "192.168.20.1:8003", &[], &[]);
+
+    // when
+    let routes = segment_routes(&definition).unwrap();
+
+    // then
+    assert!(routes.export.is_empty());
+    assert!(routes.import.is_empty());
+}
+
+#[test]
+pub fn a_topic_forwarded_both_ways_is_refused() {
+    // given
+    let definition = definition("cameras", "192.168.20.1:8003", &["topic/**"], &["topic/estop"]);
+
+    // expect
+    assert_eq!(
+        segment_routes(&definition).err(),
+        Some(SegmentDefinitionError::ForwardedBothWays("topic/estop"))
+    );
+}
+
+#[test]
+pub fn invalid_patterns_are_refused() {
+    // given
+    let definition = definition("cameras", "192.168.20.1:8003", &["topic/**/job"], &[]);
+
+    // expect
+    assert_eq!(
+        segment_routes(&definition).err(),
+        Some(SegmentDefinitionError::InvalidPattern("topic/**/job".to_string()))
+    );
+}
+
+#[test]
+pub fn segments_with_the_same_name_are_refused() {
+    // given
+    let definitions = [
+        definition("cameras", "192.168.20.1:8003", &[], &[]),
+        definition("cameras", "192.168.21.1:8003", &[], &[]),
+    ];
+
+    // expect
+    assert_eq!(
+        validate_segments(&definitions, &control_addresses()),
+        Err(SegmentDefinitionError::DuplicateName("cameras".to_string()))
+    );
+}
+
+#[test]
+pub fn the_ports_of_the_control_network_are_refused() {
+    // given
+    let definitions = [definition("cameras", "192.168.20.1:8001", &[], &[])];
+
+    // expect
+    assert_eq!(
+        validate_segments(&definitions, &control_addresses()),
+        Err(SegmentDefinitionError::DuplicateAddress(
+            "192.168.20.1:8001".parse().unwrap()
+        ))
+    );
+}
+
+#[test]
+pub fn segments_may_share_a_port_on_different_interfaces() {
+    // given
+    let definitions = [
+        definition("cameras", "192.168.20.1:8003", &[], &[]),
+        definition("feeders", "192.168.21.1:8003", &[], &[]),
+    ];
+
+    // expect
+    assert_eq!(validate_segments(&definitions, &control_addresses()), Ok(()));
+
+    // and
+    let definitions = [
+        definition("cameras", "192.168.20.1:8003", &[], &[]),
+        definition("feeders", "0.0.0.0:8003", &[], &[]),
+    ];
+    assert_eq!(
+        validate_segments(&definitions, &control_addresses()),
+        Err(SegmentDefinitionError::DuplicateAddress(
+            "0.0.0.0:8003".parse().unwrap()
+        ))
+    );
+}
@@ -0,0 +1,55 @@
+//! Running the server as a service, a systemd service on Linux, or a Windows service, see `SERVICE.md`.
+//!
+//! A systemd service is an ordinary process, systemd is notified when the server is ready and when it is stopping,
+//! and stops the server with SIGTERM.  A Windows service is started by the service control manager, which stops the
+//! server with a control request instead of a signal, see [`windows`].
+
+use log::{info, warn};
+#[cfg(unix)]
+use tokio::select;
+use tokio::signal;
+
+#[cfg(windows)]
+pub mod windows;
+
+/// Completes on Ctrl+C, or on SIGTERM, e.g. when systemd stops the service.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::SignalKind;
+
+        match signal::unix::signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                select! {
+                    _ = signal::ctrl_c() => info!("Ctrl+C received"),
+                    _ = terminate.recv() => info!("SIGTERM received"),
+                }
+                return;
+            }
+            Err(e) => warn!("Unable to handle SIGTERM, only handling Ctrl+C. error: {}", e),
+        }
+    }
+
+    match signal::ctrl_c().await {
+        Ok(()) => info!("Ctrl+C received"),
+        Err(e) => warn!("Unable to handle Ctrl+C. error: {}", e),
+    }
+}
+
+/// Notifies systemd that the server has started, for `Type=notify` units.
+///
+/// Does nothing when the server was not started by systemd.
+pub fn notify_ready() {
+    #[cfg(unix)]
+    if let Err(e) = sd_notify::notify(false, &[sd_notify::NotifyState::Ready]) {
+        warn!("Unable to notify systemd. error: {}", e);
+    }
+}
+
+/// Notifies systemd that the server is stopping, so that a slow shutdown isn't mistaken for a hung server.
+pub fn notify_stopping() {
+    #[cfg(unix)]
+    if let Err(e) = sd_notify::notify(false, &[sd_notify::NotifyState::Stopping]) {
+        warn!("Unable to notify systemd. error: {}", e);
+    }
+}
@@ -0,0 +1,144 @@
+//! The Windows service, see `SERVICE.md`.
+//!
+//! The service control manager starts the process with `--service`, the process hands its main thread to the service
+//! dispatcher, which calls [`service_main`] on another thread.  Stop requests are received by the control handler.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{error, info};
+use tokio_util::sync::CancellationToken;
+use windows_service::service::{
+    ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle};
+use windows_service::{define_windows_service, service_dispatcher};
+
+use crate::cli::Args;
+
+/// Must match the name the service was created with.
+pub const SERVICE_NAME: &str = "makerpnp-server";
+
+/// The log file, in the directory of the config file, unless `--log-file` is given.
+const DEFAULT_LOG_FILE: &str = "server_cli.log";
+
+/// How long the service control manager waits for the server to start or stop before reporting a failure.
+const PENDING_WAIT_HINT: Duration = Duration::from_secs(30);
+
+/// The arguments of the process, taken by [`service_main`], the service dispatcher doesn't pass them through.
+static ARGS: Mutex<Option<Args>> = Mutex::new(None);
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Blocks until the service has stopped.
+pub fn run(args: Args) -> anyhow::Result<()> {
+    *ARGS.lock().unwrap() = Some(args);
+
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main).map_err(|e| {
+        anyhow::format_err!(
+            "Unable to start the service dispatcher, `--service` is only for the service control manager. error: {}",
+            e
+        )
+    })
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    let Some(args) = ARGS.lock().unwrap().take() else {
+        return;
+    };
+
+    // there is no console, nowhere to report the error if logging can't be initialized
+    if let Err(e) = run_service(args) {
+        error!("Service error: {:?}", e);
+    }
+}
+
+fn run_service(mut args: Args) -> anyhow::Result<()> {
+    // services are started in the system directory, paths in the config are relative to the config file instead
+    let config_directory = args
+        .config
+        .parent()
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    if !config_directory.as_os_str().is_empty() {
+        std::env::set_current_dir(&config_directory)?;
+        if let Some(file_name) = args.config.file_name() {
+            args.config = PathBuf::from(file_name);
+        }
+    }
+
+    let log_file = args
+        .log_file
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_LOG_FILE));
+    crate::logging::init(args.verbosity_level, Some(&log_file))?;
+
+    let stop = CancellationToken::new();
+    let status_handle = service_control_handler::register(SERVICE_NAME, {
+        let stop = stop.clone();
+        move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                stop.cancel();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        }
+    })?;
+    set_status(&status_handle, ServiceState::StartPending, ServiceExitCode::NO_ERROR)?;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+
+    let result = runtime.block_on(crate::run(
+        args,
+        || {
+            if let Err(e) = set_status(&status_handle, ServiceState::Running, ServiceExitCode::NO_ERROR) {
+                error!("Unable to report the service as running. error: {}", e);
+            }
+        },
+        async {
+            stop.cancelled().await;
+            info!("Service stop requested");
+            if let Err(e) = set_status(&status_handle, ServiceState::StopPending, ServiceExitCode::NO_ERROR) {
+                error!("Unable to report the service as stopping. error: {}", e);
+            }
+        },
+    ));
+
+    let exit_code = match &result {
+        Ok(()) => ServiceExitCode::NO_ERROR,
+        Err(_) => ServiceExitCode::ServiceSpecific(1),
+    };
+    set_status(&status_handle, ServiceState::Stopped, exit_code)?;
+
+    result
+}
+
+fn set_status(
+    status_handle: &ServiceStatusHandle,
+    state: ServiceState,
+    exit_code: ServiceExitCode,
+) -> windows_service::Result<()> {
+    let controls_accepted = match state {
+        ServiceState::Running => ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        _ => ServiceControlAccept::empty(),
+    };
+    let wait_hint = match state {
+        ServiceState::StartPending | ServiceState::StopPending => PENDING_WAIT_HINT,
+        _ => Duration::default(),
+    };
+
+    status_handle.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted,
+        exit_code,
+        checkpoint: 0,
+        wait_hint,
+        process_id: None,
+    })
+}
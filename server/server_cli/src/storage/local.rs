@@ -0,0 +1,111 @@
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use tokio::fs;
+
+use crate::storage::{Storage, StorageError, StoredObject};
+
+/// Stores objects as files, relative to a directory.
+pub struct LocalStorage {
+    directory: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+        }
+    }
+
+    fn full_path(&self, path: &str) -> PathBuf {
+        self.directory.join(path)
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put<'a>(&'a self, path: &'a str, bytes: &'a [u8]) -> impl Future<Output = Result<(), StorageError>> + Send + 'a {
+        async move {
+            let full_path = self.full_path(path);
+            if let Some(parent) = full_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(&full_path, bytes).await?;
+            Ok(())
+        }
+    }
+
+    fn get<'a>(&'a self, path: &'a str) -> impl Future<Output = Result<Vec<u8>, StorageError>> + Send + 'a {
+        async move { Ok(fs::read(self.full_path(path)).await?) }
+    }
+
+    fn list<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Future<Output = Result<Vec<StoredObject>, StorageError>> + Send + 'a {
+        async move {
+            let mut objects = Vec::new();
+
+            // iterative, since async recursion requires boxing
+            let mut pending = vec![(self.directory.clone(), String::new())];
+            while let Some((directory, relative)) = pending.pop() {
+                let mut read_dir = match fs::read_dir(&directory).await {
+                    Ok(read_dir) => read_dir,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e.into()),
+                };
+
+                while let Some(entry) = read_dir.next_entry().await? {
+                    let Ok(name) = entry.file_name().into_string() else {
+                        continue;
+                    };
+                    let path = format!("{}{}", relative, name);
+
+                    let metadata = entry.metadata().await?;
+                    if metadata.is_dir() {
+                        // only descend into directories that can contain matching objects
+                        let directory_path = format!("{}/", path);
+                        if directory_path.starts_with(prefix) || prefix.starts_with(&directory_path) {
+                            pending.push((entry.path(), directory_path));
+                        }
+                    } else if metadata.is_file() && path.starts_with(prefix) {
+                        objects.push(StoredObject {
+                            path,
+                            size: metadata.len(),
+                            modified: DateTime::<Utc>::from(metadata.modified()?),
+                        });
+                    }
+                }
+            }
+
+            Ok(objects)
+        }
+    }
+
+    fn delete<'a>(&'a self, path: &'a str) -> impl Future<Output = Result<(), StorageError>> + Send + 'a {
+        async move {
+            let full_path = self.full_path(path);
+            fs::remove_file(&full_path).await?;
+
+            // remove the parent directories once they are empty, failure just means they're not empty
+            let mut parent = full_path.parent();
+            while let Some(directory) = parent {
+                if directory == self.directory || fs::remove_dir(directory).await.is_err() {
+                    break;
+                }
+                parent = directory.parent();
+            }
+            Ok(())
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => StorageError::NotFound,
+            _ => StorageError::Io(e),
+        }
+    }
+}
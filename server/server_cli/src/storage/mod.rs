@@ -0,0 +1,132 @@
+//! Storage for traceability artifacts, e.g. captures and reports, see [`StorageConfig`].
+//!
+//! Paths are relative and `/` separated, e.g. `captures/{job}/{placement}/{stage}.jpg`, callers are responsible for
+//! validating any path components that come from operators.
+
+use std::future::Future;
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::Serialize;
+
+use crate::config::StorageConfig;
+
+pub mod local;
+pub mod s3;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredObject {
+    pub path: String,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    NotFound,
+    Io(std::io::Error),
+    S3(::s3::error::S3Error),
+}
+
+/// Notes:
+/// * not object-safe, since it returns `impl Future<...>`, use [`StorageImpl`] to select an implementation at runtime.
+pub trait Storage {
+    /// Store an object, replacing any object with the same path.
+    fn put<'a>(&'a self, path: &'a str, bytes: &'a [u8]) -> impl Future<Output = Result<(), StorageError>> + Send + 'a;
+
+    fn get<'a>(&'a self, path: &'a str) -> impl Future<Output = Result<Vec<u8>, StorageError>> + Send + 'a;
+
+    /// All objects with paths starting with the prefix, recursively, in no particular order.
+    fn list<'a>(&'a self, prefix: &'a str)
+    -> impl Future<Output = Result<Vec<StoredObject>, StorageError>> + Send + 'a;
+
+    fn delete<'a>(&'a self, path: &'a str) -> impl Future<Output = Result<(), StorageError>> + Send + 'a;
+}
+
+pub enum StorageImpl {
+    Local(local::LocalStorage),
+    S3(s3::S3Storage),
+}
+
+impl StorageImpl {
+    pub fn build(config: &StorageConfig) -> Result<Self, StorageError> {
+        let storage = match config {
+            StorageConfig::Local {
+                directory,
+            } => {
+                info!("Using local storage. directory: {:?}", directory);
+                StorageImpl::Local(local::LocalStorage::new(directory.clone()))
+            }
+            StorageConfig::S3(s3_config) => {
+                info!(
+                    "Using S3 storage. endpoint: {}, bucket: {}, prefix: {:?}",
+                    s3_config.endpoint, s3_config.bucket, s3_config.prefix
+                );
+                StorageImpl::S3(s3::S3Storage::new(s3_config)?)
+            }
+        };
+        Ok(storage)
+    }
+}
+
+impl Storage for StorageImpl {
+    fn put<'a>(&'a self, path: &'a str, bytes: &'a [u8]) -> impl Future<Output = Result<(), StorageError>> + Send + 'a {
+        async move {
+            match self {
+                StorageImpl::Local(storage) => storage.put(path, bytes).await,
+                StorageImpl::S3(storage) => storage.put(path, bytes).await,
+            }
+        }
+    }
+
+    fn get<'a>(&'a self, path: &'a str) -> impl Future<Output = Result<Vec<u8>, StorageError>> + Send + 'a {
+        async move {
+            match self {
+                StorageImpl::Local(storage) => storage.get(path).await,
+                StorageImpl::S3(storage) => storage.get(path).await,
+            }
+        }
+    }
+
+    fn list<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Future<Output = Result<Vec<StoredObject>, StorageError>> + Send + 'a {
+        async move {
+            match self {
+                StorageImpl::Local(storage) => storage.list(prefix).await,
+                StorageImpl::S3(storage) => storage.list(prefix).await,
+            }
+        }
+    }
+
+    fn delete<'a>(&'a self, path: &'a str) -> impl Future<Output = Result<(), StorageError>> + Send + 'a {
+        async move {
+            match self {
+                StorageImpl::Local(storage) => storage.delete(path).await,
+                StorageImpl::S3(storage) => storage.delete(path).await,
+            }
+        }
+    }
+}
+
+/// Writes a report to `{directory}/{name}.ron`, replacing any report with the same name, returns the path of the report.
+pub async fn write_report<R: Serialize>(
+    storage: &StorageImpl,
+    directory: &str,
+    name: &str,
+    report: &R,
+) -> anyhow::Result<String> {
+    let path = format!("{}/{}.ron", directory, name);
+    let content = ron::ser::to_string_pretty(report, ron::ser::PrettyConfig::default())?;
+    storage
+        .put(&path, content.as_bytes())
+        .await
+        .map_err(|e| anyhow!("Unable to store report. path: {}, error: {:?}", path, e))?;
+
+    Ok(path)
+}
@@ -0,0 +1,118 @@
+use std::future::Future;
+
+use ::s3::creds::Credentials;
+use ::s3::error::S3Error;
+use ::s3::{Bucket, Region};
+use chrono::{DateTime, Utc};
+use log::warn;
+
+use crate::config::S3StorageConfig;
+use crate::storage::{Storage, StorageError, StoredObject};
+
+/// Stores objects in an S3-compatible bucket, e.g. AWS S3 or MinIO.
+///
+/// The credentials are not part of the config, they are read from the environment, e.g. `AWS_ACCESS_KEY_ID` and
+/// `AWS_SECRET_ACCESS_KEY`, or the AWS credentials file.
+pub struct S3Storage {
+    bucket: Box<Bucket>,
+    /// prepended to all paths, so that several machines can share a bucket
+    prefix: String,
+}
+
+impl S3Storage {
+    pub fn new(config: &S3StorageConfig) -> Result<Self, StorageError> {
+        let region = Region::Custom {
+            region: config.region.clone(),
+            endpoint: config.endpoint.clone(),
+        };
+        let credentials = Credentials::default().map_err(S3Error::from)?;
+
+        let mut bucket = Bucket::new(&config.bucket, region, credentials)?;
+        if config.path_style {
+            bucket = bucket.with_path_style();
+        }
+
+        Ok(Self {
+            bucket,
+            prefix: config.prefix.clone(),
+        })
+    }
+
+    fn key(&self, path: &str) -> String {
+        format!("{}{}", self.prefix, path)
+    }
+}
+
+impl Storage for S3Storage {
+    fn put<'a>(&'a self, path: &'a str, bytes: &'a [u8]) -> impl Future<Output = Result<(), StorageError>> + Send + 'a {
+        async move {
+            self.bucket
+                .put_object(self.key(path), bytes)
+                .await?;
+            Ok(())
+        }
+    }
+
+    fn get<'a>(&'a self, path: &'a str) -> impl Future<Output = Result<Vec<u8>, StorageError>> + Send + 'a {
+        async move {
+            let response = self
+                .bucket
+                .get_object(self.key(path))
+                .await?;
+            Ok(response.bytes().to_vec())
+        }
+    }
+
+    fn list<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Future<Output = Result<Vec<StoredObject>, StorageError>> + Send + 'a {
+        async move {
+            let results = self
+                .bucket
+                .list(self.key(prefix), None)
+                .await?;
+
+            let objects = results
+                .into_iter()
+                .flat_map(|result| result.contents)
+                .filter_map(|object| {
+                    let path = object
+                        .key
+                        .strip_prefix(&self.prefix)?
+                        .to_string();
+                    let modified = DateTime::parse_from_rfc3339(&object.last_modified)
+                        .inspect_err(|e| warn!("Invalid object modification time. key: {}, error: {:?}", object.key, e))
+                        .ok()?
+                        .with_timezone(&Utc);
+
+                    Some(StoredObject {
+                        path,
+                        size: object.size,
+                        modified,
+                    })
+                })
+                .collect();
+
+            Ok(objects)
+        }
+    }
+
+    fn delete<'a>(&'a self, path: &'a str) -> impl Future<Output = Result<(), StorageError>> + Send + 'a {
+        async move {
+            self.bucket
+                .delete_object(self.key(path))
+                .await?;
+            Ok(())
+        }
+    }
+}
+
+impl From<S3Error> for StorageError {
+    fn from(e: S3Error) -> Self {
+        match e {
+            S3Error::HttpFailWithBody(404, _) => StorageError::NotFound,
+            e => StorageError::S3(e),
+        }
+    }
+}
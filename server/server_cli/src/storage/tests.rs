@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use super::local::LocalStorage;
+use super::{Storage, StorageError, StorageImpl, write_report};
+
+fn local_storage() -> LocalStorage {
+    LocalStorage::new(std::env::temp_dir().join(format!("storage-test-{:016x}", rand::random::<u64>())))
+}
+
+#[tokio::test]
+pub async fn local_put_list_get_delete() {
+    // given
+    let storage = local_storage();
+
+    // when
+    storage
+        .put("captures/job-1/R1/a.jpg", &[1, 2, 3])
+        .await
+        .unwrap();
+    storage
+        .put("reports/job-1.json", &[4])
+        .await
+        .unwrap();
+
+    // then
+    let objects = storage.list("captures/").await.unwrap();
+    assert_eq!(objects.len(), 1);
+    assert_eq!(objects[0].path, "captures/job-1/R1/a.jpg");
+    assert_eq!(objects[0].size, 3);
+
+    assert_eq!(
+        storage
+            .get("captures/job-1/R1/a.jpg")
+            .await
+            .unwrap(),
+        vec![1, 2, 3]
+    );
+
+    storage
+        .delete("captures/job-1/R1/a.jpg")
+        .await
+        .unwrap();
+    assert!(matches!(
+        storage
+            .get("captures/job-1/R1/a.jpg")
+            .await,
+        Err(StorageError::NotFound)
+    ));
+    assert!(
+        storage
+            .list("captures/")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+pub async fn local_list_missing_directory() {
+    // given
+    let storage = local_storage();
+
+    // expect
+    assert!(
+        storage
+            .list("")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+pub async fn reports_are_written_to_their_directory() {
+    // given
+    let storage = StorageImpl::Local(local_storage());
+    let report = BTreeMap::from([("moves".to_string(), 42)]);
+
+    // when
+    let path = write_report(&storage, "burn-in", "burn-in-axis0", &report)
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(path, "burn-in/burn-in-axis0.ron");
+    let content = String::from_utf8(storage.get(&path).await.unwrap()).unwrap();
+    assert_eq!(ron::from_str::<BTreeMap<String, u32>>(&content).unwrap(), report);
+}
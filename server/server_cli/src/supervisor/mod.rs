@@ -0,0 +1,261 @@
+//! Supervision of the long-running tasks of the server, e.g. the listeners, the command handlers and the capture loops.
+//!
+//! A supervised task is spawned from a factory, so that it can be spawned again when it fails, see [`RestartPolicy`].
+//! A task fails when it returns an error or panics, and a task that should run until shutdown also fails when it
+//! stops.  Failed tasks are restarted after a backoff, a task that keeps failing is not restarted and the machine
+//! faults instead: the head is parked, and the [`ReadinessCheck::ServerTasksRunning`] check fails, so that no job is
+//! started until the server has been restarted.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use operator_shared::readiness::{CheckState, ReadinessCheck};
+use retry::{Backoff, BackoffConfig};
+use tokio::select;
+use tokio::sync::{Mutex, broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+
+use crate::AppEvent;
+use crate::config::SupervisorConfig;
+use crate::parking::{self, ParkTrigger};
+use crate::readiness::Readiness;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestartPolicy {
+    /// For tasks that are run once, e.g. a routine, or that own a receiver that can't be created again, a failure
+    /// faults the machine, see [`Supervisor::spawn_once`].
+    Never,
+    /// For tasks that stop once their work is done, e.g. a capture that is stopped when it is no longer used.
+    OnFailure,
+    /// For tasks that run until shutdown, stopping before it is a failure.
+    Always,
+}
+
+/// The value returned by a supervised task, a task that returns an error has failed.
+pub trait TaskOutput {
+    fn into_result(self) -> Result<(), String>;
+}
+
+impl TaskOutput for () {
+    fn into_result(self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<E: Debug> TaskOutput for Result<(), E> {
+    fn into_result(self) -> Result<(), String> {
+        self.map_err(|e| format!("{:?}", e))
+    }
+}
+
+/// The restarts of a task, decides whether a failed task is restarted, and after what delay.
+pub struct RestartTracker {
+    config: SupervisorConfig,
+    backoff: Backoff,
+    /// all the restarts, including those outside the window
+    restarts: u32,
+    /// when the task was restarted, within the window
+    recent: VecDeque<Instant>,
+}
+
+impl RestartTracker {
+    pub fn new(config: SupervisorConfig) -> Self {
+        let backoff = BackoffConfig {
+            initial: Duration::from_millis(config.initial_backoff_ms),
+            max: Duration::from_millis(config.max_backoff_ms),
+            multiplier: 2,
+            // the tasks are restarted by the same server, there are no clients to spread out
+            jitter: 0.0,
+            max_elapsed: None,
+        };
+        Self {
+            config,
+            // without jitter the seed is not used
+            backoff: Backoff::new(backoff, 1),
+            restarts: 0,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// Returns the delay before the failed task is restarted, `None` if it has been restarted too often within the
+    /// window and must not be restarted again.
+    pub fn restart(&mut self, now: Instant) -> Option<Duration> {
+        let window = Duration::from_secs(self.config.restart_window_s);
+        while self
+            .recent
+            .front()
+            .is_some_and(|restarted_at| now - *restarted_at > window)
+        {
+            self.recent.pop_front();
+        }
+
+        if self.recent.len() >= self.config.max_restarts as usize {
+            return None;
+        }
+
+        // the task ran for the whole window since it was last restarted
+        if self.recent.is_empty() {
+            self.backoff.reset();
+        }
+        let backoff = self.backoff.next_unbounded();
+        self.recent.push_back(now);
+        self.restarts += 1;
+        Some(backoff)
+    }
+}
+
+/// Spawns the supervised tasks, see the module documentation.
+#[derive(Clone)]
+pub struct Supervisor {
+    config: SupervisorConfig,
+    readiness: Arc<Mutex<Readiness>>,
+    parking_tx: mpsc::Sender<ParkTrigger>,
+    event_tx: broadcast::Sender<AppEvent>,
+}
+
+impl Supervisor {
+    pub fn new(
+        config: SupervisorConfig,
+        readiness: Arc<Mutex<Readiness>>,
+        parking_tx: mpsc::Sender<ParkTrigger>,
+        event_tx: broadcast::Sender<AppEvent>,
+    ) -> Self {
+        Self {
+            config,
+            readiness,
+            parking_tx,
+            event_tx,
+        }
+    }
+
+    /// Spawns a task named `name`, `factory` is called to create each run of the task.
+    ///
+    /// The returned handle completes once the task is no longer supervised, i.e. it has completed, the server is
+    /// shutting down, or it has failed and is not restarted.
+    pub fn spawn<F, Fut>(&self, name: &str, policy: RestartPolicy, factory: F) -> std::io::Result<JoinHandle<()>>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: TaskOutput + Send + 'static,
+    {
+        let supervisor = self.clone();
+        let name = name.to_string();
+        tokio::task::Builder::new()
+            .name(&format!("{}/supervisor", name))
+            .spawn(async move {
+                supervisor
+                    .supervise(name, policy, factory)
+                    .await
+            })
+    }
+
+    /// Spawns a task that is run once, see [`RestartPolicy::Never`].
+    pub fn spawn_once<Fut>(&self, name: &str, future: Fut) -> std::io::Result<JoinHandle<()>>
+    where
+        Fut: Future + Send + 'static,
+        Fut::Output: TaskOutput + Send + 'static,
+    {
+        let mut future = Some(future);
+        self.spawn(name, RestartPolicy::Never, move || {
+            future
+                .take()
+                .expect("task is not restarted")
+        })
+    }
+
+    async fn supervise<F, Fut>(self, name: String, policy: RestartPolicy, mut factory: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future + Send + 'static,
+        Fut::Output: TaskOutput + Send + 'static,
+    {
+        // subscribed before the first run, the tasks stop on shutdown and the shutdown must be seen when they do
+        let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(self.event_tx.subscribe()));
+        let mut tracker = RestartTracker::new(self.config.clone());
+
+        loop {
+            let result = match tokio::task::Builder::new()
+                .name(&name)
+                .spawn(factory())
+            {
+                // a panic is returned as an error
+                Ok(handle) => match handle.await {
+                    Ok(output) => output.into_result(),
+                    Err(e) => Err(format!("{}", e)),
+                },
+                Err(e) => Err(format!("Unable to spawn task, error: {:?}", e)),
+            };
+
+            let shutdown = select! {
+                biased;
+                _ = &mut app_shutdown_handler => true,
+                _ = std::future::ready(()) => false,
+            };
+            if shutdown {
+                debug!("Supervised task stopped on shutdown. task: {}", name);
+                break;
+            }
+
+            let error = match (policy, result) {
+                (RestartPolicy::Never | RestartPolicy::OnFailure, Ok(())) => {
+                    debug!("Supervised task completed. task: {}", name);
+                    break;
+                }
+                (RestartPolicy::Always, Ok(())) => "Stopped before shutdown".to_string(),
+                (_, Err(error)) => error,
+            };
+
+            let backoff = match policy {
+                RestartPolicy::Never => None,
+                RestartPolicy::OnFailure | RestartPolicy::Always => tracker.restart(Instant::now()),
+            };
+            let Some(backoff) = backoff else {
+                error!(
+                    "Supervised task failed, not restarted. task: {}, restarts: {}, error: {}",
+                    name,
+                    tracker.restarts(),
+                    error
+                );
+                self.fault(&name).await;
+                break;
+            };
+
+            warn!(
+                "Supervised task failed, restarting. task: {}, restarts: {}, backoff: {:?}, error: {}",
+                name,
+                tracker.restarts(),
+                backoff,
+                error
+            );
+            select! {
+                _ = &mut app_shutdown_handler => {
+                    break
+                }
+                _ = time::sleep(backoff) => {}
+            }
+            info!("Restarting supervised task. task: {}", name);
+        }
+    }
+
+    /// The head is parked, and no job is started until the server has been restarted, or the operator overrides the
+    /// readiness check.
+    async fn fault(&self, name: &str) {
+        error!("Machine fault, a server task has failed. task: {}", name);
+        parking::send_trigger(&self.parking_tx, ParkTrigger::Fault);
+        self.readiness
+            .lock()
+            .await
+            .update(ReadinessCheck::ServerTasksRunning, CheckState::Failed);
+    }
+}
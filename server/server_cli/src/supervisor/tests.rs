@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use super::RestartTracker;
+use crate::config::SupervisorConfig;
+
+fn tracker() -> RestartTracker {
+    RestartTracker::new(SupervisorConfig {
+        initial_backoff_ms: 100,
+        max_backoff_ms: 500,
+        max_restarts: 4,
+        restart_window_s: 60,
+    })
+}
+
+#[test]
+pub fn backoff_doubled_up_to_limit() {
+    // given
+    let mut tracker = tracker();
+    let now = Instant::now();
+
+    // when
+    let backoffs = (0..4)
+        .map(|_| tracker.restart(now))
+        .collect::<Vec<_>>();
+
+    // then
+    assert_eq!(backoffs, vec![
+        Some(Duration::from_millis(100)),
+        Some(Duration::from_millis(200)),
+        Some(Duration::from_millis(400)),
+        Some(Duration::from_millis(500)),
+    ]);
+    assert_eq!(tracker.restarts(), 4);
+}
+
+#[test]
+pub fn not_restarted_after_max_restarts_within_window() {
+    // given
+    let mut tracker = tracker();
+    let now = Instant::now();
+    for _ in 0..4 {
+        tracker.restart(now);
+    }
+
+    // expect
+    assert_eq!(tracker.restart(now + Duration::from_secs(30)), None);
+}
+
+#[test]
+pub fn restarts_outside_window_forgotten() {
+    // given
+    let mut tracker = tracker();
+    let now = Instant::now();
+    for _ in 0..4 {
+        tracker.restart(now);
+    }
+
+    // when
+    let backoff = tracker.restart(now + Duration::from_secs(61));
+
+    // then
+    assert_eq!(backoff, Some(Duration::from_millis(100)));
+    assert_eq!(tracker.restarts(), 5);
+}
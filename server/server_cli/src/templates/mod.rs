@@ -0,0 +1,441 @@
+//! The vision templates of the parts library, e.g. fiducial models and part templates.
+//!
+//! Templates are stored under `library/templates/{name}.png`, with the details of the template in `{name}.ron`, in
+//! the same storage as the captures.  The images are cropped from a frame captured by a camera, see
+//! [`create_template`].
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use machine_ids::CameraId;
+use operator_shared::templates::{
+    TEMPLATE_LIST_PAGE_SIZE, TemplateCapture, TemplateCrop, TemplateError, TemplateInfo, TemplateKind, TemplateListPage,
+};
+use serde::{Deserialize, Serialize};
+use server_vision::template::{CropRect, CroppedImage, crop_jpeg};
+use tokio::sync::Mutex;
+
+use crate::AppState;
+use crate::camera::camera_definition_for_identifier;
+use crate::captures::is_valid_component;
+use crate::storage::{Storage, StorageError, StorageImpl};
+use crate::vision::VisionCaptureRequest;
+
+#[cfg(test)]
+mod tests;
+
+const TEMPLATES_PREFIX: &str = "library/templates/";
+const IMAGE_EXTENSION: &str = "png";
+const RECORD_EXTENSION: &str = "ron";
+
+/// A template embedded in the server, see [`TemplateStore::install_defaults`].
+struct DefaultTemplate {
+    name: &'static str,
+    kind: TemplateKind,
+    width: u32,
+    height: u32,
+    png_bytes: &'static [u8],
+}
+
+const DEFAULT_TEMPLATES: [DefaultTemplate; 1] = [DefaultTemplate {
+    // a round fiducial mark, a light dot on a dark background
+    name: "fiducial-dot",
+    kind: TemplateKind::Fiducial,
+    width: 64,
+    height: 64,
+    png_bytes: include_bytes!("../../assets/templates/fiducial-dot.png"),
+}];
+
+/// The details of a template, stored alongside its image.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TemplateRecord {
+    kind: TemplateKind,
+    camera: CameraId,
+    /// the region of the captured frame
+    crop: TemplateCrop,
+    width: u32,
+    height: u32,
+    updated_at: DateTime<Utc>,
+}
+
+impl TemplateRecord {
+    fn new(kind: TemplateKind, capture: &TemplateCapture, image: &CroppedImage) -> Self {
+        Self {
+            kind,
+            camera: capture.camera,
+            crop: capture.crop,
+            width: image.width,
+            height: image.height,
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn info(&self, name: &str) -> TemplateInfo {
+        TemplateInfo {
+            name: name.to_string(),
+            kind: self.kind,
+            camera: self.camera,
+            width: self.width,
+            height: self.height,
+            updated_at: self.updated_at.into(),
+        }
+    }
+}
+
+pub struct TemplateStore {
+    storage: Arc<StorageImpl>,
+}
+
+impl TemplateStore {
+    pub fn new(storage: Arc<StorageImpl>) -> Self {
+        Self {
+            storage,
+        }
+    }
+
+    /// All templates, ordered by name.
+    pub async fn list(&self) -> Result<Vec<TemplateInfo>, TemplateError> {
+        let objects = self
+            .storage
+            .list(TEMPLATES_PREFIX)
+            .await
+            .map_err(|e| storage_error(TEMPLATES_PREFIX, e))?;
+
+        // objects that could not have been created from a valid name are skipped
+        let mut names = objects
+            .into_iter()
+            .filter_map(|object| name(&object.path))
+            .collect::<Vec<_>>();
+        names.sort();
+
+        let mut templates = Vec::with_capacity(names.len());
+        for name in names {
+            match self.read_record(&name).await {
+                Ok(record) => templates.push(record.info(&name)),
+                // e.g. deleted since it was listed
+                Err(TemplateError::NotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(templates)
+    }
+
+    /// Installs the embedded default templates if the library is empty, e.g. on a fresh install, so that boards with
+    /// common fiducials can be run before any templates have been captured.
+    pub async fn install_defaults(&self) -> Result<(), TemplateError> {
+        if !self.list().await?.is_empty() {
+            return Ok(());
+        }
+
+        for template in DEFAULT_TEMPLATES {
+            let record = TemplateRecord {
+                kind: template.kind,
+                camera: CameraId::new(0),
+                crop: TemplateCrop {
+                    x: 0,
+                    y: 0,
+                    width: template.width,
+                    height: template.height,
+                },
+                width: template.width,
+                height: template.height,
+                updated_at: Utc::now(),
+            };
+            self.write(template.name, &record, Some(template.png_bytes))
+                .await?;
+            info!("Default template installed. name: {}", template.name);
+        }
+        Ok(())
+    }
+
+    pub async fn list_page(&self, offset: usize) -> Result<TemplateListPage, TemplateError> {
+        let templates = self.list().await?;
+        Ok(TemplateListPage {
+            total: templates.len() as u32,
+            templates: templates
+                .into_iter()
+                .skip(offset)
+                .take(TEMPLATE_LIST_PAGE_SIZE)
+                .collect(),
+        })
+    }
+
+    pub async fn create(
+        &self,
+        name: &str,
+        kind: TemplateKind,
+        capture: &TemplateCapture,
+        image: &CroppedImage,
+    ) -> Result<TemplateInfo, TemplateError> {
+        check_name(name)?;
+        if self.exists(name).await? {
+            return Err(TemplateError::AlreadyExists);
+        }
+
+        let record = TemplateRecord::new(kind, capture, image);
+        self.write(name, &record, Some(&image.png_bytes))
+            .await?;
+        info!(
+            "Template created. name: {}, kind: {:?}, camera: {}, width: {}, height: {}",
+            name, kind, capture.camera, image.width, image.height
+        );
+
+        Ok(record.info(name))
+    }
+
+    /// Renames the template and changes its kind, the image is replaced if `captured` is given.
+    pub async fn update(
+        &self,
+        name: &str,
+        new_name: &str,
+        kind: TemplateKind,
+        captured: Option<(&TemplateCapture, &CroppedImage)>,
+    ) -> Result<TemplateInfo, TemplateError> {
+        check_name(name)?;
+        check_name(new_name)?;
+        let previous = self.read_record(name).await?;
+        let renamed = name != new_name;
+        if renamed && self.exists(new_name).await? {
+            return Err(TemplateError::AlreadyExists);
+        }
+
+        let (record, png_bytes) = match captured {
+            Some((capture, image)) => (TemplateRecord::new(kind, capture, image), Some(image.png_bytes.clone())),
+            None => {
+                let record = TemplateRecord {
+                    kind,
+                    updated_at: Utc::now(),
+                    ..previous
+                };
+                // the image moves with the record
+                let png_bytes = match renamed {
+                    true => Some(self.read_image(name).await?),
+                    false => None,
+                };
+                (record, png_bytes)
+            }
+        };
+
+        self.write(new_name, &record, png_bytes.as_deref())
+            .await?;
+        if renamed {
+            self.remove(name).await?;
+        }
+        info!(
+            "Template updated. name: {}, new_name: {}, kind: {:?}, recaptured: {}",
+            name,
+            new_name,
+            kind,
+            captured.is_some()
+        );
+
+        Ok(record.info(new_name))
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<(), TemplateError> {
+        check_name(name)?;
+        if !self.exists(name).await? {
+            return Err(TemplateError::NotFound);
+        }
+
+        self.remove(name).await?;
+        info!("Template deleted. name: {}", name);
+        Ok(())
+    }
+
+    async fn exists(&self, name: &str) -> Result<bool, TemplateError> {
+        match self.read_record(name).await {
+            Ok(_) => Ok(true),
+            Err(TemplateError::NotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read_record(&self, name: &str) -> Result<TemplateRecord, TemplateError> {
+        let path = path(name, RECORD_EXTENSION);
+        let bytes = self
+            .storage
+            .get(&path)
+            .await
+            .map_err(|e| not_found_or_storage_error(&path, e))?;
+
+        let content = String::from_utf8_lossy(&bytes);
+        ron::from_str(&content).map_err(|e| {
+            error!("Invalid template. path: {}, error: {:?}", path, e);
+            TemplateError::Storage
+        })
+    }
+
+    async fn read_image(&self, name: &str) -> Result<Vec<u8>, TemplateError> {
+        let path = path(name, IMAGE_EXTENSION);
+        self.storage
+            .get(&path)
+            .await
+            .map_err(|e| not_found_or_storage_error(&path, e))
+    }
+
+    /// The image is unchanged if `png_bytes` is `None`.
+    ///
+    /// The image is written first, so that a listed template always has an image.
+    async fn write(&self, name: &str, record: &TemplateRecord, png_bytes: Option<&[u8]>) -> Result<(), TemplateError> {
+        if let Some(png_bytes) = png_bytes {
+            let image_path = path(name, IMAGE_EXTENSION);
+            self.storage
+                .put(&image_path, png_bytes)
+                .await
+                .map_err(|e| storage_error(&image_path, e))?;
+        }
+
+        let record_path = path(name, RECORD_EXTENSION);
+        let content = ron::to_string(record).map_err(|e| {
+            error!("Unable to serialize template. name: {}, error: {:?}", name, e);
+            TemplateError::Storage
+        })?;
+        self.storage
+            .put(&record_path, content.as_bytes())
+            .await
+            .map_err(|e| storage_error(&record_path, e))
+    }
+
+    /// The record is removed first, so that a listed template always has an image.
+    async fn remove(&self, name: &str) -> Result<(), TemplateError> {
+        for extension in [RECORD_EXTENSION, IMAGE_EXTENSION] {
+            let path = path(name, extension);
+            match self.storage.delete(&path).await {
+                Ok(()) | Err(StorageError::NotFound) => {}
+                Err(e) => return Err(storage_error(&path, e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Captures the image of the template, then adds it to the library.
+pub async fn create_template(
+    app_state: &Arc<Mutex<AppState>>,
+    name: &str,
+    kind: TemplateKind,
+    capture: &TemplateCapture,
+) -> Result<TemplateInfo, TemplateError> {
+    // don't capture for a request that will be refused
+    check_name(name)?;
+
+    let image = capture_image(app_state, capture).await?;
+    let template_store = app_state
+        .lock()
+        .await
+        .template_store
+        .clone();
+    template_store
+        .create(name, kind, capture, &image)
+        .await
+}
+
+/// Re-captures the image of the template if `capture` is given.
+pub async fn update_template(
+    app_state: &Arc<Mutex<AppState>>,
+    name: &str,
+    new_name: &str,
+    kind: TemplateKind,
+    capture: Option<&TemplateCapture>,
+) -> Result<TemplateInfo, TemplateError> {
+    check_name(name)?;
+    check_name(new_name)?;
+
+    let image = match capture {
+        Some(capture) => Some(capture_image(app_state, capture).await?),
+        None => None,
+    };
+    let template_store = app_state
+        .lock()
+        .await
+        .template_store
+        .clone();
+    template_store
+        .update(name, new_name, kind, capture.zip(image.as_ref()))
+        .await
+}
+
+async fn capture_image(
+    app_state: &Arc<Mutex<AppState>>,
+    capture: &TemplateCapture,
+) -> Result<CroppedImage, TemplateError> {
+    let vision_queue = {
+        let app_state = app_state.lock().await;
+        camera_definition_for_identifier(&app_state.config.cameras, &capture.camera)
+            .ok_or(TemplateError::UnknownCamera)?;
+        app_state.vision_queue.clone()
+    };
+
+    let frame = vision_queue
+        .capture(VisionCaptureRequest {
+            camera: capture.camera,
+            pause_preview: true,
+            metering_roi: None,
+        })
+        .await
+        .map_err(|e| {
+            warn!("Template capture failed. camera: {}, error: {:?}", capture.camera, e);
+            TemplateError::CaptureFailed
+        })?;
+
+    let crop = capture.crop;
+    let rect = CropRect {
+        x: crop.x,
+        y: crop.y,
+        width: crop.width,
+        height: crop.height,
+    };
+    // decoding and encoding takes longer than is acceptable for the runtime
+    let image = tokio::task::spawn_blocking(move || crop_jpeg(&frame.jpeg_bytes, rect))
+        .await
+        .map_err(|e| anyhow::anyhow!(e))
+        .and_then(|result| result)
+        .map_err(|e| {
+            warn!("Unable to crop template. camera: {}, error: {:?}", capture.camera, e);
+            TemplateError::CaptureFailed
+        })?;
+
+    image.ok_or_else(|| {
+        warn!(
+            "Template crop outside of the frame. camera: {}, crop: {:?}",
+            capture.camera, crop
+        );
+        TemplateError::InvalidCrop
+    })
+}
+
+fn check_name(name: &str) -> Result<(), TemplateError> {
+    match is_valid_component(name) {
+        true => Ok(()),
+        false => Err(TemplateError::InvalidName),
+    }
+}
+
+fn path(name: &str, extension: &str) -> String {
+    format!("{}{}.{}", TEMPLATES_PREFIX, name, extension)
+}
+
+/// The name of the template of a record path, `None` if the path is not a valid record path.
+fn name(path: &str) -> Option<String> {
+    let name = path
+        .strip_prefix(TEMPLATES_PREFIX)?
+        .strip_suffix(&format!(".{}", RECORD_EXTENSION))?;
+    if !is_valid_component(name) {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+fn not_found_or_storage_error(path: &str, e: StorageError) -> TemplateError {
+    match e {
+        StorageError::NotFound => TemplateError::NotFound,
+        e => storage_error(path, e),
+    }
+}
+
+fn storage_error(path: &str, e: StorageError) -> TemplateError {
+    error!("Template storage error. path: {}, error: {:?}", path, e);
+    TemplateError::Storage
+}
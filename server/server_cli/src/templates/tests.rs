@@ -0,0 +1,250 @@
+use std::sync::Arc;
+
+use machine_ids::CameraId;
+use operator_shared::templates::{TemplateCapture, TemplateCrop, TemplateError, TemplateKind};
+use server_vision::template::CroppedImage;
+
+use super::{IMAGE_EXTENSION, TemplateStore, path};
+use crate::storage::local::LocalStorage;
+use crate::storage::{Storage, StorageImpl};
+
+fn store() -> (TemplateStore, Arc<StorageImpl>) {
+    let directory = std::env::temp_dir().join(format!("templates-test-{:016x}", rand::random::<u64>()));
+    let storage = Arc::new(StorageImpl::Local(LocalStorage::new(directory)));
+    (TemplateStore::new(storage.clone()), storage)
+}
+
+fn capture() -> TemplateCapture {
+    TemplateCapture {
+        camera: CameraId::new(0),
+        crop: TemplateCrop {
+            x: 10,
+            y: 20,
+            width: 30,
+            height: 40,
+        },
+    }
+}
+
+fn image(png_bytes: &[u8]) -> CroppedImage {
+    CroppedImage {
+        png_bytes: png_bytes.to_vec(),
+        width: 30,
+        height: 40,
+    }
+}
+
+#[tokio::test]
+pub async fn create_and_list() {
+    // given
+    let (store, storage) = store();
+
+    // when
+    let created = store
+        .create("fiducial-1", TemplateKind::Fiducial, &capture(), &image(&[1, 2, 3]))
+        .await
+        .unwrap();
+    store
+        .create("0402", TemplateKind::Part, &capture(), &image(&[4]))
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(created.name, "fiducial-1");
+    assert_eq!(created.kind, TemplateKind::Fiducial);
+    assert_eq!((created.width, created.height), (30, 40));
+
+    let templates = store.list().await.unwrap();
+    let names = templates
+        .iter()
+        .map(|template| template.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["0402", "fiducial-1"]);
+
+    let png_bytes = storage
+        .get(&path("fiducial-1", IMAGE_EXTENSION))
+        .await
+        .unwrap();
+    assert_eq!(png_bytes, vec![1, 2, 3]);
+}
+
+#[tokio::test]
+pub async fn create_refused_for_existing_or_invalid_name() {
+    // given
+    let (store, _storage) = store();
+    store
+        .create("fiducial-1", TemplateKind::Fiducial, &capture(), &image(&[1]))
+        .await
+        .unwrap();
+
+    // when
+    let existing = store
+        .create("fiducial-1", TemplateKind::Part, &capture(), &image(&[2]))
+        .await;
+    let invalid = store
+        .create("../fiducial-2", TemplateKind::Fiducial, &capture(), &image(&[3]))
+        .await;
+
+    // then
+    assert_eq!(existing.unwrap_err(), TemplateError::AlreadyExists);
+    assert_eq!(invalid.unwrap_err(), TemplateError::InvalidName);
+    assert_eq!(store.list().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+pub async fn rename_moves_image() {
+    // given
+    let (store, storage) = store();
+    store
+        .create("fiducial-1", TemplateKind::Fiducial, &capture(), &image(&[1, 2, 3]))
+        .await
+        .unwrap();
+
+    // when
+    let updated = store
+        .update("fiducial-1", "sot23", TemplateKind::Part, None)
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(updated.name, "sot23");
+    assert_eq!(updated.kind, TemplateKind::Part);
+
+    let templates = store.list().await.unwrap();
+    assert_eq!(templates.len(), 1);
+    assert_eq!(templates[0].name, "sot23");
+
+    let png_bytes = storage
+        .get(&path("sot23", IMAGE_EXTENSION))
+        .await
+        .unwrap();
+    assert_eq!(png_bytes, vec![1, 2, 3]);
+    assert!(
+        storage
+            .get(&path("fiducial-1", IMAGE_EXTENSION))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+pub async fn update_replaces_image() {
+    // given
+    let (store, storage) = store();
+    store
+        .create("fiducial-1", TemplateKind::Fiducial, &capture(), &image(&[1, 2, 3]))
+        .await
+        .unwrap();
+    let recaptured = CroppedImage {
+        png_bytes: vec![4, 5],
+        width: 50,
+        height: 60,
+    };
+
+    // when
+    let updated = store
+        .update(
+            "fiducial-1",
+            "fiducial-1",
+            TemplateKind::Fiducial,
+            Some((&capture(), &recaptured)),
+        )
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!((updated.width, updated.height), (50, 60));
+    let png_bytes = storage
+        .get(&path("fiducial-1", IMAGE_EXTENSION))
+        .await
+        .unwrap();
+    assert_eq!(png_bytes, vec![4, 5]);
+}
+
+#[tokio::test]
+pub async fn update_refused_for_unknown_or_existing_name() {
+    // given
+    let (store, _storage) = store();
+    for name in ["fiducial-1", "fiducial-2"] {
+        store
+            .create(name, TemplateKind::Fiducial, &capture(), &image(&[1]))
+            .await
+            .unwrap();
+    }
+
+    // when
+    let unknown = store
+        .update("fiducial-3", "fiducial-4", TemplateKind::Fiducial, None)
+        .await;
+    let existing = store
+        .update("fiducial-1", "fiducial-2", TemplateKind::Fiducial, None)
+        .await;
+
+    // then
+    assert_eq!(unknown.unwrap_err(), TemplateError::NotFound);
+    assert_eq!(existing.unwrap_err(), TemplateError::AlreadyExists);
+}
+
+#[tokio::test]
+pub async fn delete() {
+    // given
+    let (store, storage) = store();
+    store
+        .create("fiducial-1", TemplateKind::Fiducial, &capture(), &image(&[1]))
+        .await
+        .unwrap();
+
+    // when
+    let deleted = store.delete("fiducial-1").await;
+    let unknown = store.delete("fiducial-1").await;
+
+    // then
+    assert_eq!(deleted, Ok(()));
+    assert_eq!(unknown, Err(TemplateError::NotFound));
+    assert!(store.list().await.unwrap().is_empty());
+    assert!(
+        storage
+            .get(&path("fiducial-1", IMAGE_EXTENSION))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+pub async fn defaults_installed_in_an_empty_library() {
+    // given
+    let (store, storage) = store();
+
+    // when
+    store.install_defaults().await.unwrap();
+
+    // then
+    let templates = store.list().await.unwrap();
+    assert_eq!(templates.len(), 1);
+    assert_eq!(templates[0].name, "fiducial-dot");
+    assert_eq!(templates[0].kind, TemplateKind::Fiducial);
+    assert!(
+        storage
+            .get(&path("fiducial-dot", IMAGE_EXTENSION))
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+pub async fn defaults_not_installed_in_a_library_with_templates() {
+    // given
+    let (store, _storage) = store();
+    store
+        .create("fiducial-1", TemplateKind::Fiducial, &capture(), &image(&[1]))
+        .await
+        .unwrap();
+
+    // when
+    store.install_defaults().await.unwrap();
+
+    // then
+    let templates = store.list().await.unwrap();
+    assert_eq!(templates.len(), 1);
+    assert_eq!(templates[0].name, "fiducial-1");
+}
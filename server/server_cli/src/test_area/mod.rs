@@ -0,0 +1,192 @@
+//! Test shots in a dedicated area of the bed.
+//!
+//! The operator chooses a [`TestPattern`], the positions of the shots are generated from the first free row of the
+//! test area, so that consecutive patterns don't overlap until the operator clears the area.  Each shot is placed by
+//! the same [`Placer`] as the placements of a job, but a failed shot is only counted, there are no interventions.
+
+use std::sync::Arc;
+
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::topic;
+use log::{debug, info, warn};
+use machine_geometry::Point;
+use operator_shared::test_area::{TEST_PATTERN_SHOTS_MAX, TestPattern, TestShotError, TestShotEvent, TestShotKind};
+use tokio::select;
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::Receiver;
+
+use crate::AppEvent;
+use crate::config::TestAreaConfig;
+use crate::feeders::Feeders;
+use crate::job::{JobControl, Operation, Placement, Placer, take_part};
+
+#[cfg(test)]
+mod tests;
+
+topic!(TestShotEventTopic, TestShotEvent, "topic/operator/test-shots");
+
+/// The positions of the shots of the `pattern`, row by row, starting at `origin`.
+pub fn grid_positions(origin: Point, pattern: &TestPattern) -> Vec<Point> {
+    let pitch = pattern.pitch as f64;
+    (0..pattern.rows)
+        .flat_map(|row| {
+            (0..pattern.columns).map(move |column| Point {
+                x: origin.x + column as f64 * pitch,
+                y: origin.y + row as f64 * pitch,
+            })
+        })
+        .collect()
+}
+
+pub struct TestArea {
+    config: Option<TestAreaConfig>,
+    /// the height of the area used by earlier patterns, in millimeters
+    used: f64,
+}
+
+impl TestArea {
+    pub fn new(config: Option<TestAreaConfig>) -> Self {
+        Self {
+            config,
+            used: 0.0,
+        }
+    }
+
+    /// Returns the positions of the shots and marks the rows of the pattern as used.
+    pub fn plan(&mut self, pattern: &TestPattern) -> Result<Vec<Point>, TestShotError> {
+        let config = self
+            .config
+            .as_ref()
+            .ok_or(TestShotError::NotConfigured)?;
+
+        let shots = pattern
+            .columns
+            .checked_mul(pattern.rows)
+            .unwrap_or(u32::MAX);
+        if shots == 0 || shots > TEST_PATTERN_SHOTS_MAX || pattern.pitch <= 0.0 || !pattern.pitch.is_finite() {
+            return Err(TestShotError::InvalidPattern);
+        }
+
+        let pitch = pattern.pitch as f64;
+        let width = (pattern.columns - 1) as f64 * pitch;
+        let height = (pattern.rows - 1) as f64 * pitch;
+        if width > config.width || self.used + height > config.height {
+            return Err(TestShotError::AreaFull);
+        }
+
+        let origin = Point {
+            x: config.origin.x,
+            y: config.origin.y + self.used,
+        };
+        // the next pattern starts a pitch after the last row
+        self.used += height + pitch;
+
+        Ok(grid_positions(origin, pattern))
+    }
+
+    pub fn clear(&mut self) {
+        self.used = 0.0;
+    }
+}
+
+/// Places a shot at each position, returns the number of placed and failed shots.
+///
+/// For [`TestShotKind::Place`] a part is taken from the feeder before each shot.
+pub async fn run_test_shots<P: Placer>(
+    positions: &[Point],
+    kind: &TestShotKind,
+    feeders: &Mutex<Feeders>,
+    placer: &mut P,
+    publish: &mut (impl FnMut(TestShotEvent) + Send),
+) -> (u32, u32) {
+    let (feeder, operation) = match kind {
+        TestShotKind::Dispense {
+            head,
+        } => (None, Operation::Dispense {
+            head: head.clone(),
+            dispense_ms: None,
+        }),
+        TestShotKind::Place {
+            feeder,
+        } => (Some(feeder.clone()), Operation::Place),
+    };
+
+    info!("Test shots started. shots: {}, kind: {:?}", positions.len(), kind);
+    publish(TestShotEvent::Started {
+        shots: positions.len() as u32,
+    });
+
+    let (mut placed, mut failed) = (0, 0);
+    for (index, position) in positions.iter().enumerate() {
+        let placement = Placement {
+            reference: format!("TEST{}", index + 1),
+            position: *position,
+            rotation: 0.0,
+            feeder: feeder.clone(),
+            operation: operation.clone(),
+            board: None,
+        };
+        let result = match take_part(feeders, &placement).await {
+            Ok(()) => placer.place(&placement).await,
+            Err(e) => Err(e),
+        };
+
+        let ok = match result {
+            Ok(()) => {
+                placed += 1;
+                true
+            }
+            Err(e) => {
+                warn!(
+                    "Test shot failed. shot: {}, position: {:?}, error: {:?}",
+                    placement.reference, position, e
+                );
+                failed += 1;
+                false
+            }
+        };
+        publish(TestShotEvent::Shot {
+            index: index as u32,
+            ok,
+        });
+    }
+
+    info!("Test shots finished. placed: {}, failed: {}", placed, failed);
+    publish(TestShotEvent::Finished {
+        placed,
+        failed,
+    });
+    (placed, failed)
+}
+
+/// The caller must have started the test shots, see [`JobControl::start_test_shots`].
+pub async fn test_shot_runner<P: Placer>(
+    stack: RouterStack,
+    positions: Vec<Point>,
+    kind: TestShotKind,
+    feeders: Arc<Mutex<Feeders>>,
+    job_control: Arc<Mutex<JobControl>>,
+    mut placer: P,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    let mut publish = |event: TestShotEvent| {
+        if let Err(e) = stack
+            .topics()
+            .broadcast::<TestShotEventTopic>(&event, None)
+        {
+            debug!("Unable to publish test shot event, error: {:?}", e);
+        }
+    };
+
+    select! {
+        _ = &mut app_shutdown_handler => {
+            warn!("Test shots interrupted by shutdown.");
+        }
+        _ = run_test_shots(&positions, &kind, &feeders, &mut placer, &mut publish) => {}
+    }
+
+    job_control.lock().await.finish();
+    info!("test shot runner shutdown");
+}
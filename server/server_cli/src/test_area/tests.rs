@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+
+use anyhow::bail;
+use machine_geometry::Point;
+use machine_ids::FeederId;
+use operator_shared::test_area::{TestPattern, TestShotError, TestShotEvent, TestShotKind};
+use tokio::sync::Mutex;
+
+use super::{TestArea, grid_positions, run_test_shots};
+use crate::config::{FeederDefinition, FeedersConfig, TestAreaConfig};
+use crate::feeders::Feeders;
+use crate::job::{Operation, Placement, Placer};
+
+fn pattern(columns: u32, rows: u32, pitch: f32) -> TestPattern {
+    TestPattern {
+        columns,
+        rows,
+        pitch,
+    }
+}
+
+fn test_area() -> TestArea {
+    TestArea::new(Some(TestAreaConfig {
+        origin: Point {
+            x: 100.0,
+            y: 50.0,
+        },
+        width: 20.0,
+        height: 10.0,
+    }))
+}
+
+/// Fails the placements with the given references.
+#[derive(Default)]
+struct TestPlacer {
+    failing: Vec<String>,
+    placed: Vec<Placement>,
+}
+
+impl Placer for TestPlacer {
+    fn place<'a>(&'a mut self, placement: &'a Placement) -> impl Future<Output = anyhow::Result<()>> + Send + 'a {
+        async move {
+            if self
+                .failing
+                .contains(&placement.reference)
+            {
+                bail!("Placement failed. placement: {}", placement.reference)
+            }
+            self.placed.push(placement.clone());
+            Ok(())
+        }
+    }
+}
+
+#[test]
+pub fn grid_positions_row_by_row() {
+    // given
+    let origin = Point {
+        x: 10.0,
+        y: 20.0,
+    };
+
+    // when
+    let positions = grid_positions(origin, &pattern(3, 2, 2.5));
+
+    // then
+    assert_eq!(positions, vec![
+        Point {
+            x: 10.0,
+            y: 20.0
+        },
+        Point {
+            x: 12.5,
+            y: 20.0
+        },
+        Point {
+            x: 15.0,
+            y: 20.0
+        },
+        Point {
+            x: 10.0,
+            y: 22.5
+        },
+        Point {
+            x: 12.5,
+            y: 22.5
+        },
+        Point {
+            x: 15.0,
+            y: 22.5
+        },
+    ]);
+}
+
+#[test]
+pub fn consecutive_patterns_do_not_overlap() {
+    // given
+    let mut area = test_area();
+
+    // when
+    let first = area.plan(&pattern(2, 2, 4.0)).unwrap();
+    let second = area.plan(&pattern(1, 1, 4.0)).unwrap();
+
+    // then
+    assert_eq!(
+        first.last(),
+        Some(&Point {
+            x: 104.0,
+            y: 54.0
+        })
+    );
+    assert_eq!(second, vec![Point {
+        x: 100.0,
+        y: 58.0
+    }]);
+}
+
+#[test]
+pub fn area_full_until_cleared() {
+    // given
+    let mut area = test_area();
+    area.plan(&pattern(1, 3, 4.0)).unwrap();
+
+    // when
+    let full = area.plan(&pattern(1, 1, 4.0));
+    area.clear();
+    let cleared = area.plan(&pattern(1, 1, 4.0));
+
+    // then
+    assert_eq!(full, Err(TestShotError::AreaFull));
+    assert_eq!(
+        cleared,
+        Ok(vec![Point {
+            x: 100.0,
+            y: 50.0
+        }])
+    );
+}
+
+#[test]
+pub fn pattern_must_fit_and_be_valid() {
+    // given
+    let mut area = test_area();
+
+    // expect
+    assert_eq!(area.plan(&pattern(6, 1, 5.0)), Err(TestShotError::AreaFull));
+    assert_eq!(area.plan(&pattern(0, 1, 5.0)), Err(TestShotError::InvalidPattern));
+    assert_eq!(area.plan(&pattern(1, 1, 0.0)), Err(TestShotError::InvalidPattern));
+    assert_eq!(area.plan(&pattern(1, 1, f32::NAN)), Err(TestShotError::InvalidPattern));
+    assert_eq!(area.plan(&pattern(100, 100, 0.01)), Err(TestShotError::InvalidPattern));
+    assert_eq!(
+        TestArea::new(None).plan(&pattern(1, 1, 1.0)),
+        Err(TestShotError::NotConfigured)
+    );
+}
+
+#[tokio::test]
+pub async fn failed_shots_are_counted() {
+    // given
+    let positions = grid_positions(Point::default(), &pattern(3, 1, 1.0));
+    let feeders = Mutex::new(Feeders::new(&FeedersConfig::default(), &BTreeMap::new()));
+    let mut placer = TestPlacer {
+        failing: vec!["TEST2".to_string()],
+        ..TestPlacer::default()
+    };
+    let kind = TestShotKind::Dispense {
+        head: "paste".to_string(),
+    };
+    let mut events = vec![];
+
+    // when
+    let result = run_test_shots(&positions, &kind, &feeders, &mut placer, &mut |event| {
+        events.push(event)
+    })
+    .await;
+
+    // then
+    assert_eq!(result, (2, 1));
+    assert_eq!(events, vec![
+        TestShotEvent::Started {
+            shots: 3
+        },
+        TestShotEvent::Shot {
+            index: 0,
+            ok: true
+        },
+        TestShotEvent::Shot {
+            index: 1,
+            ok: false
+        },
+        TestShotEvent::Shot {
+            index: 2,
+            ok: true
+        },
+        TestShotEvent::Finished {
+            placed: 2,
+            failed: 1
+        },
+    ]);
+}
+
+#[tokio::test]
+pub async fn place_takes_parts_from_the_feeder() {
+    // given
+    let positions = grid_positions(Point::default(), &pattern(3, 1, 1.0));
+    let config = FeedersConfig {
+        low_stock_threshold: 0,
+        feeders: vec![FeederDefinition {
+            name: FeederId::new("F1"),
+            low_stock_threshold: None,
+            polarity_mark: None,
+            part: None,
+            identity: None,
+            calibration: None,
+            pick_position: None,
+        }],
+        ..FeedersConfig::default()
+    };
+    let feeders = Mutex::new(Feeders::new(&config, &BTreeMap::from([(FeederId::new("F1"), 2)])));
+    let mut placer = TestPlacer::default();
+    let kind = TestShotKind::Place {
+        feeder: FeederId::new("F1"),
+    };
+
+    // when
+    let result = run_test_shots(&positions, &kind, &feeders, &mut placer, &mut |_| {}).await;
+
+    // then
+    assert_eq!(result, (2, 1));
+    assert_eq!(feeders.lock().await.counts()["F1"], 0);
+    assert!(
+        placer.placed.iter().all(|placement| {
+            placement.feeder == Some(FeederId::new("F1")) && placement.operation == Operation::Place
+        })
+    );
+}
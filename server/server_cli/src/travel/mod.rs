@@ -0,0 +1,136 @@
+//! Planning the height of Z for each move between placements, so that the nozzle only hops over the tall parts that are
+//! under the path of the move, instead of raising to the safe height for every move.
+//!
+//! The heights of the parts come from the parts library, via the feeder of each placement, see [`PartHeights`].  A
+//! move clears every part that has already been placed near its path, with the part carried by the nozzle hanging
+//! below it.  Moves are never made above the safe height, and a part whose height is unknown is cleared at the safe
+//! height.
+
+use std::collections::HashMap;
+
+use machine_geometry::Point;
+use machine_ids::FeederId;
+
+use crate::config::{FeederDefinition, ParkingConfig, PartDefinition, TravelConfig};
+use crate::job::{Operation, Placement};
+
+#[cfg(test)]
+mod tests;
+
+/// The heights of the parts, by feeder.
+#[derive(Debug, Clone, Default)]
+pub struct PartHeights {
+    by_feeder: HashMap<FeederId, f64>,
+}
+
+impl PartHeights {
+    /// Feeders without a part, or with a part that is not in the parts library, have no height.
+    pub fn new(feeders: &[FeederDefinition], parts: &[PartDefinition]) -> Self {
+        let by_feeder = feeders
+            .iter()
+            .filter_map(|feeder| {
+                let name = feeder.part.as_ref()?;
+                let part = parts
+                    .iter()
+                    .find(|part| part.name == *name)?;
+                Some((feeder.name.clone(), part.height))
+            })
+            .collect();
+
+        Self {
+            by_feeder,
+        }
+    }
+
+    /// The height of the placed part, above the board, `None` if it is unknown.
+    ///
+    /// Dispensed paste or glue is assumed to be flat.
+    pub fn height(&self, placement: &Placement) -> Option<f64> {
+        match placement.operation {
+            Operation::Place => placement
+                .feeder
+                .as_ref()
+                .and_then(|feeder| self.by_feeder.get(feeder).copied()),
+            Operation::Dispense {
+                ..
+            } => Some(0.0),
+        }
+    }
+}
+
+/// A part on the board, in machine coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PlacedPart {
+    position: Point,
+    /// `None` if it is unknown
+    height: Option<f64>,
+}
+
+/// Plans the moves of a job, the parts are added as they are placed.
+#[derive(Debug, Clone)]
+pub struct TravelPlanner {
+    config: TravelConfig,
+    safe_z: f64,
+    placed: Vec<PlacedPart>,
+}
+
+impl TravelPlanner {
+    pub fn new(config: &ParkingConfig) -> Self {
+        Self {
+            config: config.travel.clone(),
+            safe_z: config.safe_z,
+            placed: vec![],
+        }
+    }
+
+    /// `position` is in machine coordinates, `height` is `None` if it is unknown.
+    pub fn place(&mut self, position: Point, height: Option<f64>) {
+        self.placed.push(PlacedPart {
+            position,
+            height,
+        });
+    }
+
+    /// The height of Z for the move from `from` to `to`, `carried` is the height of the part carried by the nozzle,
+    /// `None` if it is unknown.
+    pub fn travel_z(&self, from: Point, to: Point, carried: Option<f64>) -> f64 {
+        if !self.config.enabled {
+            return self.safe_z;
+        }
+        let Some(carried) = carried else {
+            return self.safe_z;
+        };
+
+        // the board itself, when no part is under the path
+        let tallest = self
+            .placed
+            .iter()
+            .filter(|part| distance_to_segment(part.position, from, to) <= self.config.keep_out)
+            .map(|part| part.height.unwrap_or(f64::INFINITY))
+            .fold(0.0, f64::max);
+
+        (self.config.board_z + tallest + carried + self.config.clearance).min(self.safe_z)
+    }
+
+    /// The height of Z when placing a part of the `height`, or dispensing, `None` if the height is unknown.
+    pub fn work_z(&self, height: Option<f64>) -> f64 {
+        self.config.board_z + height.unwrap_or(0.0)
+    }
+}
+
+/// The distance from the `point` to the closest point of the segment from `a` to `b`.
+pub fn distance_to_segment(point: Point, a: Point, b: Point) -> f64 {
+    let segment = b - a;
+    let length_squared = segment.x * segment.x + segment.y * segment.y;
+    if length_squared == 0.0 {
+        return point.distance(&a);
+    }
+
+    let offset = point - a;
+    let t = ((offset.x * segment.x + offset.y * segment.y) / length_squared).clamp(0.0, 1.0);
+    let closest = Point {
+        x: a.x + t * segment.x,
+        y: a.y + t * segment.y,
+    };
+    point.distance(&closest)
+}
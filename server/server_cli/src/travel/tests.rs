@@ -0,0 +1,171 @@
+use machine_geometry::Point;
+use machine_ids::FeederId;
+
+use super::{PartHeights, TravelPlanner, distance_to_segment};
+use crate::config::{FeederDefinition, ParkingConfig, PartDefinition, TravelConfig};
+use crate::job::{Operation, Placement};
+
+fn point(x: f64, y: f64) -> Point {
+    Point {
+        x,
+        y,
+    }
+}
+
+fn planner() -> TravelPlanner {
+    let config = ParkingConfig {
+        safe_z: 20.0,
+        travel: TravelConfig {
+            enabled: true,
+            board_z: 2.0,
+            clearance: 1.0,
+            keep_out: 5.0,
+        },
+        ..ParkingConfig::default()
+    };
+    TravelPlanner::new(&config)
+}
+
+fn placement(feeder: Option<&str>, operation: Operation) -> Placement {
+    Placement {
+        reference: "R1".to_string(),
+        position: Point::default(),
+        rotation: 0.0,
+        feeder: feeder.map(FeederId::new),
+        operation,
+        board: None,
+    }
+}
+
+#[test]
+pub fn distance_to_the_closest_point_of_the_segment() {
+    // given
+    let a = point(0.0, 0.0);
+    let b = point(10.0, 0.0);
+
+    // expect
+    assert_eq!(distance_to_segment(point(5.0, 3.0), a, b), 3.0);
+    assert_eq!(distance_to_segment(point(-4.0, 3.0), a, b), 5.0);
+    assert_eq!(distance_to_segment(point(13.0, 4.0), a, b), 5.0);
+    assert_eq!(distance_to_segment(point(3.0, 4.0), a, a), 5.0);
+}
+
+#[test]
+pub fn travel_clears_the_board_and_the_carried_part() {
+    // given
+    let planner = planner();
+
+    // when
+    let z = planner.travel_z(point(0.0, 0.0), point(100.0, 0.0), Some(0.5));
+
+    // then
+    // board, carried part, clearance
+    assert_eq!(z, 2.0 + 0.5 + 1.0);
+}
+
+#[test]
+pub fn travel_hops_over_tall_parts_under_the_path_only() {
+    // given
+    let mut planner = planner();
+    planner.place(point(50.0, 4.0), Some(6.0));
+    planner.place(point(50.0, 20.0), Some(12.0));
+
+    // when
+    let over = planner.travel_z(point(0.0, 0.0), point(100.0, 0.0), Some(0.5));
+    let beside = planner.travel_z(point(0.0, 30.0), point(100.0, 30.0), Some(0.5));
+
+    // then
+    assert_eq!(over, 2.0 + 6.0 + 0.5 + 1.0);
+    // the tall part is 10mm from the path, outside the keep out
+    assert_eq!(beside, 2.0 + 0.5 + 1.0);
+}
+
+#[test]
+pub fn travel_never_above_the_safe_height() {
+    // given
+    let mut planner = planner();
+    planner.place(point(50.0, 0.0), Some(30.0));
+
+    // when
+    let z = planner.travel_z(point(0.0, 0.0), point(100.0, 0.0), Some(0.5));
+
+    // then
+    assert_eq!(z, 20.0);
+}
+
+#[test]
+pub fn unknown_heights_travel_at_the_safe_height() {
+    // given
+    let mut planner = planner();
+    let carrying_unknown = planner.travel_z(point(0.0, 0.0), point(100.0, 0.0), None);
+    planner.place(point(50.0, 0.0), None);
+
+    // when
+    let over_unknown = planner.travel_z(point(0.0, 0.0), point(100.0, 0.0), Some(0.5));
+
+    // then
+    assert_eq!(carrying_unknown, 20.0);
+    assert_eq!(over_unknown, 20.0);
+}
+
+#[test]
+pub fn disabled_travel_is_at_the_safe_height() {
+    // given
+    let config = ParkingConfig {
+        safe_z: 20.0,
+        ..ParkingConfig::default()
+    };
+    let planner = TravelPlanner::new(&config);
+
+    // when
+    let z = planner.travel_z(point(0.0, 0.0), point(100.0, 0.0), Some(0.5));
+
+    // then
+    assert_eq!(z, 20.0);
+}
+
+#[test]
+pub fn part_heights_from_the_parts_library() {
+    // given
+    let feeders = vec![
+        FeederDefinition {
+            name: FeederId::new("F1"),
+            low_stock_threshold: None,
+            polarity_mark: None,
+            part: Some("electrolytic".to_string()),
+            identity: None,
+            calibration: None,
+            pick_position: None,
+        },
+        FeederDefinition {
+            name: FeederId::new("F2"),
+            low_stock_threshold: None,
+            polarity_mark: None,
+            part: Some("unknown".to_string()),
+            identity: None,
+            calibration: None,
+            pick_position: None,
+        },
+    ];
+    let parts = vec![PartDefinition {
+        name: "electrolytic".to_string(),
+        height: 5.8,
+        pick_force: None,
+        place_force: None,
+    }];
+
+    // when
+    let heights = PartHeights::new(&feeders, &parts);
+
+    // then
+    assert_eq!(heights.height(&placement(Some("F1"), Operation::Place)), Some(5.8));
+    assert_eq!(heights.height(&placement(Some("F2"), Operation::Place)), None);
+    assert_eq!(heights.height(&placement(None, Operation::Place)), None);
+    assert_eq!(
+        heights.height(&placement(None, Operation::Dispense {
+            head: "paste".to_string(),
+            dispense_ms: None,
+        })),
+        Some(0.0)
+    );
+}
@@ -0,0 +1,272 @@
+//! Arbitration between vision routines and the preview streams, which share the cameras.
+//!
+//! Vision capture requests are queued and served one at a time.  While a request is being served the preview streams
+//! of the camera can be paused, so the streamer isn't competing with the measurement, and a [`VisionStatus`] is
+//! published so the operator UI can show that the camera is busy.  Streaming resumes afterwards.
+//!
+//! A request can meter the exposure over a region of interest before the frame is captured, see
+//! [`ExposureMeteringConfig`].
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow, bail};
+use ergot::toolkits::tokio_udp::RouterStack;
+use ergot::topic;
+use log::{debug, info, warn};
+use machine_ids::CameraId;
+use operator_shared::vision::VisionStatus;
+use server_common::camera::ExposureMeteringConfig;
+use server_vision::CameraFrame;
+use server_vision::exposure::{Roi, roi_brightness};
+use tokio::select;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::{Mutex, broadcast, mpsc, oneshot};
+use tokio::time;
+
+use crate::camera::budget::{FramePriority, FrameReservation};
+use crate::camera::{CameraHandle, camera_definition_for_identifier};
+use crate::{AppEvent, AppState};
+
+#[cfg(test)]
+mod tests;
+
+topic!(VisionStatusTopic, VisionStatus, "topic/vision/status");
+
+const VISION_QUEUE_SIZE: usize = 16;
+
+/// Includes the time to start the capture, if the camera wasn't already in use.
+const FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VisionCaptureRequest {
+    pub camera: CameraId,
+    /// pause the preview streams of the camera until the frame has been captured
+    pub pause_preview: bool,
+    /// the region the exposure is metered over, `None`, or a camera without an [`ExposureMeteringConfig`], to
+    /// capture with the exposure the camera is using
+    pub metering_roi: Option<Roi>,
+}
+
+pub struct QueuedVisionRequest {
+    request: VisionCaptureRequest,
+    response_tx: oneshot::Sender<Result<Arc<CameraFrame>>>,
+}
+
+/// Submits requests to the [`vision_arbiter`].
+#[derive(Clone)]
+pub struct VisionQueue {
+    tx: mpsc::Sender<QueuedVisionRequest>,
+}
+
+impl VisionQueue {
+    pub fn new() -> (Self, mpsc::Receiver<QueuedVisionRequest>) {
+        let (tx, rx) = mpsc::channel(VISION_QUEUE_SIZE);
+        let queue = Self {
+            tx,
+        };
+        (queue, rx)
+    }
+
+    /// Queue a request and wait for a frame that was captured after the request was served.
+    pub async fn capture(&self, request: VisionCaptureRequest) -> Result<Arc<CameraFrame>> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.tx
+            .send(QueuedVisionRequest {
+                request,
+                response_tx,
+            })
+            .await
+            .map_err(|_| anyhow!("Vision arbiter stopped"))?;
+
+        response_rx
+            .await
+            .map_err(|_| anyhow!("Vision arbiter stopped"))?
+    }
+}
+
+pub async fn vision_arbiter(
+    stack: RouterStack,
+    app_state: Arc<Mutex<AppState>>,
+    mut rx: mpsc::Receiver<QueuedVisionRequest>,
+    app_event_rx: Receiver<AppEvent>,
+) {
+    let mut app_shutdown_handler = Box::pin(crate::app_shutdown_handler(app_event_rx));
+
+    info!("Vision arbiter started");
+    loop {
+        let queued_request = select! {
+            _ = &mut app_shutdown_handler => {
+                break
+            }
+            queued_request = rx.recv() => match queued_request {
+                Some(queued_request) => queued_request,
+                None => break,
+            }
+        };
+
+        let QueuedVisionRequest {
+            request,
+            response_tx,
+        } = queued_request;
+        debug!("Serving vision request. request: {:?}, queued: {}", request, rx.len());
+
+        let mut status = VisionStatus {
+            camera: request.camera,
+            busy: true,
+            preview_paused: request.pause_preview,
+            queued: rx.len() as u32,
+        };
+        publish_status(&stack, &status);
+
+        let result = select! {
+            _ = &mut app_shutdown_handler => {
+                // the pending response is dropped, the requester gets an error
+                break
+            }
+            result = serve_request(&app_state, &request) => result,
+        };
+        // the frame is accounted for until it has been handed to the requester
+        let (result, _reservation) = match result {
+            Ok((frame, reservation)) => (Ok(frame), Some(reservation)),
+            Err(e) => (Err(e), None),
+        };
+
+        status.busy = false;
+        status.preview_paused = false;
+        status.queued = rx.len() as u32;
+        publish_status(&stack, &status);
+
+        if let Err(e) = &result {
+            warn!("Vision request failed. request: {:?}, error: {:?}", request, e);
+        }
+        // the requester may have given up waiting
+        let _ = response_tx.send(result);
+    }
+    info!("Vision arbiter shutdown");
+}
+
+async fn serve_request(
+    app_state: &Arc<Mutex<AppState>>,
+    request: &VisionCaptureRequest,
+) -> Result<(Arc<CameraFrame>, FrameReservation)> {
+    let (camera, budget, metering) = {
+        let app_state = app_state.lock().await;
+        let Some(camera_definition) = camera_definition_for_identifier(&app_state.config.cameras, &request.camera)
+        else {
+            bail!("Invalid camera identifier. identifier: {}", request.camera)
+        };
+        let camera = app_state
+            .camera_captures
+            .acquire(request.camera, camera_definition);
+        (
+            camera,
+            app_state.camera_captures.frame_budget(),
+            camera_definition.exposure_metering,
+        )
+    };
+
+    // subscribe before pausing, so that any frame received was captured after the request was served
+    let mut rx = camera.subscribe();
+    let _preview_pause = request
+        .pause_preview
+        .then(|| camera.pause_preview());
+
+    if let (Some(roi), Some(config)) = (request.metering_roi, metering) {
+        meter_exposure(&camera, &mut rx, roi, &config).await?;
+    }
+
+    let frame = next_frame(&mut rx, request.camera).await?;
+
+    debug!(
+        "Vision frame captured. identifier: {}, frame_number: {}",
+        request.camera, frame.frame_number
+    );
+
+    // a vision frame is never refused, preview frames being sent are evicted instead
+    let reservation = budget
+        .reserve(request.camera, frame.jpeg_bytes.len(), FramePriority::Vision)
+        .ok_or_else(|| anyhow!("Camera memory budget exceeded. identifier: {}", request.camera))?;
+
+    Ok((frame, reservation))
+}
+
+/// Adjusts the exposure once, if the brightness of the region is too far from the target, and waits for the
+/// adjustment to be applied.
+async fn meter_exposure(
+    camera: &CameraHandle,
+    rx: &mut broadcast::Receiver<Arc<CameraFrame>>,
+    roi: Roi,
+    config: &ExposureMeteringConfig,
+) -> Result<()> {
+    let frame = next_frame(rx, camera.identifier()).await?;
+    // decoding takes longer than is acceptable for the runtime
+    let brightness = tokio::task::spawn_blocking(move || roi_brightness(&frame.jpeg_bytes, &roi)).await??;
+
+    let Some(factor) = exposure_adjustment(brightness, config) else {
+        debug!(
+            "Exposure metered. identifier: {}, brightness: {:.1}",
+            camera.identifier(),
+            brightness
+        );
+        return Ok(());
+    };
+    info!(
+        "Adjusting exposure. identifier: {}, brightness: {:.1}, target: {:.1}, factor: {:.2}",
+        camera.identifier(),
+        brightness,
+        config.target_brightness,
+        factor
+    );
+    camera.adjust_exposure(factor);
+
+    // frames already received were captured before the adjustment
+    *rx = camera.subscribe();
+    for _ in 0..config.settle_frames {
+        next_frame(rx, camera.identifier()).await?;
+    }
+    Ok(())
+}
+
+/// The factor to scale the exposure by, `None` if the brightness is within the tolerance of the target.
+///
+/// The brightness is assumed to be proportional to the exposure, which holds until the region saturates, so a
+/// saturated region is adjusted by less than it needs.
+pub fn exposure_adjustment(brightness: f64, config: &ExposureMeteringConfig) -> Option<f64> {
+    if (brightness - config.target_brightness).abs() <= config.tolerance {
+        return None;
+    }
+
+    let max = config.max_adjustment.max(1.0);
+    let factor = match brightness > 0.0 {
+        true => config.target_brightness / brightness,
+        // a black region has no brightness to scale
+        false => max,
+    };
+    Some(factor.clamp(1.0 / max, max))
+}
+
+async fn next_frame(rx: &mut broadcast::Receiver<Arc<CameraFrame>>, camera: CameraId) -> Result<Arc<CameraFrame>> {
+    time::timeout(FRAME_TIMEOUT, async {
+        loop {
+            match rx.recv().await {
+                Ok(frame) => break Ok(frame),
+                Err(broadcast::error::RecvError::Lagged(skipped_frames)) => {
+                    debug!("lagged, trying to get next frame.  skipped: {}", skipped_frames);
+                }
+                Err(broadcast::error::RecvError::Closed) => break Err(anyhow!("Camera capture stopped")),
+            }
+        }
+    })
+    .await
+    .map_err(|_| anyhow!("Timeout waiting for frame. identifier: {}", camera))?
+}
+
+fn publish_status(stack: &RouterStack, status: &VisionStatus) {
+    if let Err(e) = stack
+        .topics()
+        .broadcast::<VisionStatusTopic>(status, None)
+    {
+        debug!("Unable to publish vision status, error: {:?}", e);
+    }
+}
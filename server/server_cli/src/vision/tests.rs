@@ -0,0 +1,34 @@
+use server_common::camera::ExposureMeteringConfig;
+
+use super::exposure_adjustment;
+
+fn config() -> ExposureMeteringConfig {
+    ExposureMeteringConfig {
+        target_brightness: 100.0,
+        tolerance: 10.0,
+        max_adjustment: 4.0,
+        settle_frames: 3,
+    }
+}
+
+#[test]
+pub fn no_adjustment_within_tolerance() {
+    // expect
+    assert_eq!(exposure_adjustment(100.0, &config()), None);
+    assert_eq!(exposure_adjustment(90.0, &config()), None);
+    assert_eq!(exposure_adjustment(110.0, &config()), None);
+}
+
+#[test]
+pub fn exposure_scaled_towards_target() {
+    // expect
+    assert_eq!(exposure_adjustment(50.0, &config()), Some(2.0));
+    assert_eq!(exposure_adjustment(200.0, &config()), Some(0.5));
+}
+
+#[test]
+pub fn adjustment_limited() {
+    // expect
+    assert_eq!(exposure_adjustment(10.0, &config()), Some(4.0));
+    assert_eq!(exposure_adjustment(0.0, &config()), Some(4.0));
+}
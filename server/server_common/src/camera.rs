@@ -1,3 +1,5 @@
+use machine_geometry::{ImageView, PixelScale};
+
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct CameraDefinition {
     pub name: String,
@@ -8,6 +10,77 @@ pub struct CameraDefinition {
     pub width: u32,
     pub height: u32,
     pub fps: f32,
+
+    #[serde(default)]
+    pub mounting: CameraMounting,
+    #[serde(default)]
+    pub layout: CameraLayout,
+    /// Absent for uncalibrated cameras, measurements are then shown in pixels.
+    #[serde(default)]
+    pub calibration: Option<CameraCalibration>,
+    /// Absent to measure with the exposure the camera is using, see [`ExposureMeteringConfig`].
+    #[serde(default)]
+    pub exposure_metering: Option<ExposureMeteringConfig>,
+}
+
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub enum CameraMounting {
+    /// Looking up, e.g. for inspecting picked parts.
+    Up,
+    /// Looking down, e.g. a head camera.
+    Down,
+    #[default]
+    Other,
+}
+
+impl CameraMounting {
+    /// `None` for other cameras, their images can't be converted to machine coordinates.
+    pub fn image_view(&self) -> Option<ImageView> {
+        match self {
+            CameraMounting::Up => Some(ImageView::FromBelow),
+            CameraMounting::Down => Some(ImageView::FromAbove),
+            CameraMounting::Other => None,
+        }
+    }
+}
+
+/// A hint for the operator UI.
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub enum CameraLayout {
+    Primary,
+    #[default]
+    Secondary,
+    Hidden,
+}
+
+/// The scale of the image at the focal plane, e.g. at the top of the PCB for a down camera.
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize)]
+pub struct CameraCalibration {
+    pub mm_per_pixel_x: f32,
+    pub mm_per_pixel_y: f32,
+}
+
+impl CameraCalibration {
+    pub fn pixel_scale(&self) -> PixelScale {
+        PixelScale {
+            mm_per_pixel_x: self.mm_per_pixel_x as f64,
+            mm_per_pixel_y: self.mm_per_pixel_y as f64,
+        }
+    }
+}
+
+/// Before a measurement the brightness of the region of interest is metered, and if it is too far from the target the
+/// exposure of the camera is adjusted once, e.g. for a fiducial in the shadow of a part, or near a reflective one.
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize)]
+pub struct ExposureMeteringConfig {
+    /// the mean brightness of the region, 0 - 255
+    pub target_brightness: f64,
+    /// no adjustment is made while the brightness is within this of the target
+    pub tolerance: f64,
+    /// the largest factor the exposure is scaled by, in either direction, e.g. 4.0 for 1/4 - 4x
+    pub max_adjustment: f64,
+    /// the frames that are skipped after an adjustment, while the camera applies it
+    pub settle_frames: u32,
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
@@ -18,6 +91,21 @@ pub struct CameraStreamConfig {
     ///       image quality only affects the stream and NOT the CV pipeline.
     pub jpeg_quality: u8,
     // TODO maybe support resizing on the server before sending.
+    /// `None` to stream at the requested fps regardless of motion.
+    #[serde(default)]
+    pub motion_throttle: Option<MotionThrottleConfig>,
+}
+
+/// Reduces the stream fps while the machine is moving, freeing network and CPU for motion-critical traffic.
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize)]
+pub struct MotionThrottleConfig {
+    /// the stream fps while moving, the requested fps is used if it is lower
+    pub fps: f32,
+    /// the machine is moving while an axis is faster than this, in steps per second
+    pub speed_threshold: f64,
+    /// the requested fps is restored once the machine has been stationary for this long, so the stream doesn't
+    /// alternate during a sequence of short moves
+    pub restore_delay_ms: u64,
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
@@ -25,6 +113,8 @@ pub struct CameraStreamConfig {
 pub enum CameraSource {
     OpenCV(OpenCVCameraConfig),
     MediaRS(MediaRSCameraConfig),
+    /// A built-in test pattern, for trying the server and the operator UI without a camera.
+    TestPattern,
     // TODO other sources could be a camera on an H7 MCU via Ergot...
 }
 
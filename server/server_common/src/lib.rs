@@ -1 +1,2 @@
 pub mod camera;
+pub mod position;
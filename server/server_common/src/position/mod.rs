@@ -0,0 +1,109 @@
+//! Machine position history, so that a position can be looked up for the time a camera frame was captured.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+#[cfg(test)]
+mod tests;
+
+/// 2.5 seconds of history at the io board's 100Hz report rate, camera frames are much more recent than that.
+const HISTORY_SIZE: usize = 256;
+
+/// Telemetry is only published while an axis is moving, an axis without a more recent sample is stationary.
+const STATIONARY_AFTER: TimeDelta = TimeDelta::milliseconds(50);
+
+/// The position of each axis, in steps, by axis index.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MachinePosition {
+    pub axes: BTreeMap<u8, f64>,
+}
+
+/// Recent position telemetry, shared between the telemetry listener and the camera captures.
+#[derive(Debug, Clone, Default)]
+pub struct PositionHistory {
+    axes: Arc<Mutex<BTreeMap<u8, VecDeque<(DateTime<Utc>, f64)>>>>,
+}
+
+impl PositionHistory {
+    /// Samples must be recorded in timestamp order per axis, out-of-order samples are ignored.
+    pub fn record(&self, axis: u8, timestamp: DateTime<Utc>, position: f64) {
+        let mut axes = self.axes.lock().unwrap();
+        let samples = axes.entry(axis).or_default();
+
+        if samples
+            .back()
+            .is_some_and(|(latest, _)| timestamp < *latest)
+        {
+            return;
+        }
+
+        if samples.len() >= HISTORY_SIZE {
+            samples.pop_front();
+        }
+        samples.push_back((timestamp, position));
+    }
+
+    /// The position of each axis at `timestamp`, linearly interpolated between the samples either side of it.
+    ///
+    /// After the latest sample the latest position is used, telemetry is only published while an axis is moving.
+    /// Axes without a sample before `timestamp` are omitted, `None` is returned when no axis has one.
+    pub fn position_at(&self, timestamp: DateTime<Utc>) -> Option<MachinePosition> {
+        let axes = self.axes.lock().unwrap();
+
+        let axes = axes
+            .iter()
+            .filter_map(|(axis, samples)| interpolate(samples, timestamp).map(|position| (*axis, position)))
+            .collect::<BTreeMap<_, _>>();
+
+        match axes.is_empty() {
+            true => None,
+            false => Some(MachinePosition {
+                axes,
+            }),
+        }
+    }
+
+    /// The speed of the fastest axis at `now`, in steps per second, from the latest two samples of each axis.
+    pub fn max_speed(&self, now: DateTime<Utc>) -> f64 {
+        let axes = self.axes.lock().unwrap();
+
+        axes.values()
+            .filter_map(|samples| latest_speed(samples, now))
+            .fold(0.0, f64::max)
+    }
+}
+
+fn latest_speed(samples: &VecDeque<(DateTime<Utc>, f64)>, now: DateTime<Utc>) -> Option<f64> {
+    let mut latest = samples.iter().rev();
+    let (latest_timestamp, latest_position) = latest.next()?;
+    if now - *latest_timestamp > STATIONARY_AFTER {
+        return None;
+    }
+    let (previous_timestamp, previous_position) = latest.next()?;
+
+    let span = (*latest_timestamp - *previous_timestamp).num_microseconds()? as f64;
+    if span <= 0.0 {
+        return None;
+    }
+    Some((latest_position - previous_position).abs() / span * 1_000_000.0)
+}
+
+fn interpolate(samples: &VecDeque<(DateTime<Utc>, f64)>, timestamp: DateTime<Utc>) -> Option<f64> {
+    // index of the first sample after the timestamp
+    let index = samples.partition_point(|(sample_timestamp, _)| *sample_timestamp <= timestamp);
+    if index == 0 {
+        return None;
+    }
+
+    let (before_timestamp, before_position) = samples[index - 1];
+    let Some((after_timestamp, after_position)) = samples.get(index).copied() else {
+        return Some(before_position);
+    };
+
+    let span = (after_timestamp - before_timestamp).num_microseconds()? as f64;
+    let offset = (timestamp - before_timestamp).num_microseconds()? as f64;
+
+    Some(before_position + (after_position - before_position) * (offset / span))
+}
@@ -0,0 +1,97 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use super::PositionHistory;
+
+fn at(start: DateTime<Utc>, millis: i64) -> DateTime<Utc> {
+    start + TimeDelta::milliseconds(millis)
+}
+
+#[test]
+pub fn interpolates_between_samples() {
+    // given
+    let start = Utc::now();
+    let history = PositionHistory::default();
+    history.record(0, at(start, 0), 100.0);
+    history.record(0, at(start, 10), 200.0);
+
+    // when
+    let position = history.position_at(at(start, 5));
+
+    // then
+    let position = position.expect("position");
+    assert_eq!(position.axes.get(&0), Some(&150.0));
+}
+
+#[test]
+pub fn holds_latest_position_after_last_sample() {
+    // given
+    let start = Utc::now();
+    let history = PositionHistory::default();
+    history.record(0, at(start, 0), 100.0);
+    history.record(0, at(start, 10), 200.0);
+
+    // when
+    let position = history.position_at(at(start, 1000));
+
+    // then
+    assert_eq!(position.expect("position").axes.get(&0), Some(&200.0));
+}
+
+#[test]
+pub fn no_position_before_first_sample() {
+    // given
+    let start = Utc::now();
+    let history = PositionHistory::default();
+    history.record(0, at(start, 10), 100.0);
+
+    // when
+    let position = history.position_at(at(start, 0));
+
+    // then
+    assert_eq!(position, None);
+}
+
+#[test]
+pub fn ignores_out_of_order_samples() {
+    // given
+    let start = Utc::now();
+    let history = PositionHistory::default();
+    history.record(0, at(start, 10), 100.0);
+    history.record(0, at(start, 0), 0.0);
+
+    // when
+    let position = history.position_at(at(start, 20));
+
+    // then
+    assert_eq!(position.expect("position").axes.get(&0), Some(&100.0));
+}
+
+#[test]
+pub fn max_speed_of_fastest_axis() {
+    // given
+    let start = Utc::now();
+    let history = PositionHistory::default();
+    history.record(0, at(start, 0), 0.0);
+    history.record(0, at(start, 10), 10.0);
+    history.record(1, at(start, 0), 100.0);
+    history.record(1, at(start, 10), 80.0);
+
+    // when
+    let speed = history.max_speed(at(start, 20));
+
+    // then
+    assert_eq!(speed, 2000.0);
+}
+
+#[test]
+pub fn stationary_without_recent_samples() {
+    // given
+    let start = Utc::now();
+    let history = PositionHistory::default();
+    history.record(0, at(start, 0), 0.0);
+    history.record(0, at(start, 10), 10.0);
+
+    // expect
+    assert_eq!(history.max_speed(at(start, 100)), 0.0);
+    assert_eq!(PositionHistory::default().max_speed(start), 0.0);
+}
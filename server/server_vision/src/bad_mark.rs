@@ -0,0 +1,56 @@
+//! Detecting the bad mark of a board, e.g. an ink dot or a sticker on a board of a panel that failed testing.
+
+use anyhow::anyhow;
+use opencv::core::Vector;
+use opencv::imgcodecs;
+use opencv::prelude::*;
+
+/// The fraction of the center region of the image that differs from the background of the board.
+///
+/// The center region is half the width and half the height of the image, the rest of the image is assumed to be
+/// mostly unmarked board, so the median of the whole image is the background.  Both dark marks, e.g. ink, and light
+/// marks, e.g. stickers, are covered.
+///
+/// `contrast` is the difference in gray level from the background above which a pixel is part of the mark.
+pub fn bad_mark_coverage(jpeg_bytes: &[u8], contrast: u8) -> anyhow::Result<f32> {
+    let buffer = Vector::<u8>::from_slice(jpeg_bytes);
+    let image = imgcodecs::imdecode(&buffer, imgcodecs::IMREAD_GRAYSCALE)?;
+    if image.empty() {
+        return Err(anyhow!("Unable to decode image"));
+    }
+
+    // a decoded image is continuous, one byte per pixel
+    let luma = image.data_bytes()?;
+    let columns = image.cols() as usize;
+    let rows = image.rows() as usize;
+
+    let mut histogram = [0_usize; 256];
+    for value in luma {
+        histogram[*value as usize] += 1;
+    }
+    let mut remaining = luma.len() / 2;
+    let background = histogram
+        .iter()
+        .position(|count| {
+            let median = remaining < *count;
+            remaining = remaining.saturating_sub(*count);
+            median
+        })
+        .unwrap_or(0) as u8;
+
+    let (left, right) = (columns / 4, columns - columns / 4);
+    let (top, bottom) = (rows / 4, rows - rows / 4);
+    let mut marked = 0_usize;
+    for row in top..bottom {
+        marked += luma[row * columns + left..row * columns + right]
+            .iter()
+            .filter(|value| value.abs_diff(background) > contrast)
+            .count();
+    }
+
+    let region = (right - left) * (bottom - top);
+    match region {
+        0 => Ok(0.0),
+        _ => Ok(marked as f32 / region as f32),
+    }
+}
@@ -0,0 +1,40 @@
+//! Decoding barcodes and QR codes, e.g. the ID of a board or the ID of a reel.
+
+use anyhow::anyhow;
+use opencv::core::Vector;
+use opencv::imgcodecs;
+use opencv::prelude::*;
+use rxing::Exceptions;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedCode {
+    pub text: String,
+    /// e.g. `QR_CODE` or `CODE_128`
+    pub format: String,
+}
+
+/// Decode every barcode and QR code in the image, returns an empty list if there are none.
+pub fn decode_codes(jpeg_bytes: &[u8]) -> anyhow::Result<Vec<DecodedCode>> {
+    let buffer = Vector::<u8>::from_slice(jpeg_bytes);
+    let image = imgcodecs::imdecode(&buffer, imgcodecs::IMREAD_GRAYSCALE)?;
+    if image.empty() {
+        return Err(anyhow!("Unable to decode image"));
+    }
+
+    // a decoded image is continuous, one byte per pixel
+    let luma = image.data_bytes()?.to_vec();
+
+    let results = match rxing::helpers::detect_multiple_in_luma(luma, image.cols() as u32, image.rows() as u32) {
+        Ok(results) => results,
+        Err(Exceptions::NotFoundException(_)) => return Ok(vec![]),
+        Err(e) => return Err(anyhow!("Unable to decode codes. error: {:?}", e)),
+    };
+
+    Ok(results
+        .iter()
+        .map(|result| DecodedCode {
+            text: result.getText().to_string(),
+            format: format!("{:?}", result.getBarcodeFormat()),
+        })
+        .collect())
+}
@@ -0,0 +1,64 @@
+//! Metering the brightness of a region of interest, so the exposure can be adjusted before a measurement.
+
+use anyhow::anyhow;
+use opencv::core::Vector;
+use opencv::imgcodecs;
+use opencv::prelude::*;
+
+/// A region of an image, relative to the size of the image, 0.0 - 1.0, so it doesn't depend on the resolution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Roi {
+    /// of the left edge
+    pub x: f64,
+    /// of the top edge
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Roi {
+    /// A region in the center of the image, `size` is the fraction of the width and height of the image.
+    pub fn centered(size: f64) -> Self {
+        let size = size.clamp(0.0, 1.0);
+        Self {
+            x: (1.0 - size) / 2.0,
+            y: (1.0 - size) / 2.0,
+            width: size,
+            height: size,
+        }
+    }
+}
+
+/// The mean gray level of the region, 0 - 255.
+///
+/// The region is clipped to the image, an error is returned if nothing of it remains.
+pub fn roi_brightness(jpeg_bytes: &[u8], roi: &Roi) -> anyhow::Result<f64> {
+    let buffer = Vector::<u8>::from_slice(jpeg_bytes);
+    let image = imgcodecs::imdecode(&buffer, imgcodecs::IMREAD_GRAYSCALE)?;
+    if image.empty() {
+        return Err(anyhow!("Unable to decode image"));
+    }
+
+    // a decoded image is continuous, one byte per pixel
+    let luma = image.data_bytes()?;
+    let columns = image.cols() as usize;
+    let rows = image.rows() as usize;
+
+    let edge = |fraction: f64, size: usize| ((fraction.clamp(0.0, 1.0) * size as f64).round() as usize).min(size);
+    let (left, right) = (edge(roi.x, columns), edge(roi.x + roi.width, columns));
+    let (top, bottom) = (edge(roi.y, rows), edge(roi.y + roi.height, rows));
+    if left >= right || top >= bottom {
+        return Err(anyhow!("Region of interest outside the image. roi: {:?}", roi));
+    }
+
+    let mut sum = 0_u64;
+    for row in top..bottom {
+        sum += luma[row * columns + left..row * columns + right]
+            .iter()
+            .map(|value| *value as u64)
+            .sum::<u64>();
+    }
+
+    let region = (right - left) * (bottom - top);
+    Ok(sum as f64 / region as f64)
+}
@@ -0,0 +1,86 @@
+//! Locating the dots of a calibration plate, dark dots on a light background.
+
+use anyhow::anyhow;
+use machine_geometry::PixelPoint;
+use opencv::core::{Mat, Point, Size, Vector};
+use opencv::prelude::*;
+use opencv::{imgcodecs, imgproc};
+
+/// Contours smaller than this are noise, e.g. dust on the plate.
+const MIN_DOT_AREA: f64 = 50.0;
+
+/// 1.0 for a perfect circle, lower values are rejected, e.g. the edge of the plate or a partially visible dot.
+const MIN_CIRCULARITY: f64 = 0.75;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DotLocation {
+    /// relative to the center of the image
+    pub center: PixelPoint,
+    /// in pixels
+    pub diameter: f64,
+}
+
+/// Find the dot nearest the center of the image, returns `None` if there is no dot.
+pub fn find_center_dot(jpeg_bytes: &[u8]) -> anyhow::Result<Option<DotLocation>> {
+    let buffer = Vector::<u8>::from_slice(jpeg_bytes);
+    let image = imgcodecs::imdecode(&buffer, imgcodecs::IMREAD_GRAYSCALE)?;
+    if image.empty() {
+        return Err(anyhow!("Unable to decode image"));
+    }
+
+    let mut blurred = Mat::default();
+    imgproc::gaussian_blur_def(&image, &mut blurred, Size::new(5, 5), 0.0)?;
+
+    // otsu picks the threshold between the dots and the background, inverted so the dots are the foreground
+    let mut binary = Mat::default();
+    imgproc::threshold(
+        &blurred,
+        &mut binary,
+        0.0,
+        255.0,
+        imgproc::THRESH_BINARY_INV | imgproc::THRESH_OTSU,
+    )?;
+
+    let mut contours = Vector::<Vector<Point>>::new();
+    imgproc::find_contours_def(
+        &binary,
+        &mut contours,
+        imgproc::RETR_EXTERNAL,
+        imgproc::CHAIN_APPROX_NONE,
+    )?;
+
+    let center_x = image.cols() as f64 / 2.0;
+    let center_y = image.rows() as f64 / 2.0;
+
+    let mut nearest: Option<DotLocation> = None;
+    for contour in contours.iter() {
+        let area = imgproc::contour_area_def(&contour)?;
+        if area < MIN_DOT_AREA {
+            continue;
+        }
+        let perimeter = imgproc::arc_length(&contour, true)?;
+        let circularity = 4.0 * std::f64::consts::PI * area / (perimeter * perimeter);
+        if circularity < MIN_CIRCULARITY {
+            continue;
+        }
+
+        let moments = imgproc::moments_def(&contour)?;
+        if moments.m00 == 0.0 {
+            continue;
+        }
+        let dot = DotLocation {
+            center: PixelPoint {
+                x: moments.m10 / moments.m00 - center_x,
+                y: moments.m01 / moments.m00 - center_y,
+            },
+            diameter: 2.0 * (area / std::f64::consts::PI).sqrt(),
+        };
+
+        match &nearest {
+            Some(current) if current.center.distance_from_center() <= dot.center.distance_from_center() => {}
+            _ => nearest = Some(dot),
+        }
+    }
+
+    Ok(nearest)
+}
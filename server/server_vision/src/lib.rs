@@ -5,19 +5,31 @@ use chrono::DateTime;
 use log::{debug, error, info};
 use opencv::{imgcodecs, imgcodecs::ImwriteFlags, prelude::*};
 use server_common::camera::{CameraDefinition, CameraSource};
-use tokio::sync::broadcast;
+use server_common::position::{MachinePosition, PositionHistory};
+use tokio::sync::{broadcast, watch};
 use tokio::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
 
+pub mod bad_mark;
+pub mod barcode;
+pub mod exposure;
+pub mod fiducial;
 #[cfg(feature = "mediars-capture")]
 pub mod mediars_capture;
 #[cfg(feature = "opencv-capture")]
 pub mod opencv_capture;
+pub mod placement;
+pub mod polarity;
+pub mod template;
+pub mod test_pattern;
+pub mod thumbnail;
 
 pub struct CameraFrame {
     pub frame_number: u64,
     pub jpeg_bytes: Vec<u8>,
     pub frame_timestamp: DateTime<chrono::Utc>,
+    /// the machine position at `frame_timestamp`, if there is position telemetry
+    pub machine_position: Option<MachinePosition>,
 }
 
 pub fn dump_cameras() -> anyhow::Result<()> {
@@ -31,12 +43,16 @@ pub fn dump_cameras() -> anyhow::Result<()> {
     Ok::<(), anyhow::Error>(())
 }
 
+/// `exposure_scale` is the exposure relative to the exposure the camera started with, e.g. 2.0 for twice the exposure
+/// time, it is applied before the next frame is captured.
 pub async fn capture_loop(
     tx: broadcast::Sender<Arc<CameraFrame>>,
     camera_definition: CameraDefinition,
+    position_history: PositionHistory,
+    exposure_scale: watch::Receiver<f64>,
     shutdown_flag: CancellationToken,
 ) -> anyhow::Result<()> {
-    let (source_index, capture_loop) = make_capture_loop(&camera_definition, shutdown_flag)?;
+    let (source_index, capture_loop) = make_capture_loop(&camera_definition, exposure_scale, shutdown_flag)?;
 
     let callback = {
         let camera_definition = camera_definition.clone();
@@ -67,6 +83,7 @@ pub async fn capture_loop(
                     frame_number,
                     jpeg_bytes: buf.to_vec(),
                     frame_timestamp,
+                    machine_position: position_history.position_at(frame_timestamp),
                 };
 
                 let camera_frame_arc = Arc::new(camera_frame);
@@ -97,13 +114,14 @@ pub async fn capture_loop(
         VideoCaptureImpl::MediaRS(mut loop_impl) => loop_impl.run(callback).await,
         #[cfg(feature = "opencv-capture")]
         VideoCaptureImpl::OpenCV(mut loop_impl) => loop_impl.run(callback).await,
+        VideoCaptureImpl::TestPattern(mut loop_impl) => loop_impl.run(callback).await,
         // #[cfg(not(any(feature = "mediars-capture", feature = "opencv-capture")))]
         // compile_error!("No camera capture implementation available") => {
         //     unreachable!()
         // }
     };
 
-    if let Err(e) = result {
+    if let Err(e) = &result {
         error!("Error in camera capture loop: {:?}", e);
     }
 
@@ -112,11 +130,12 @@ pub async fn capture_loop(
         camera_definition.sources[source_index]
     );
 
-    Ok(())
+    result
 }
 
 fn make_capture_loop(
     camera_definition: &CameraDefinition,
+    exposure_scale: watch::Receiver<f64>,
     shutdown_flag: CancellationToken,
 ) -> anyhow::Result<(usize, VideoCaptureImpl)> {
     camera_definition
@@ -125,21 +144,38 @@ fn make_capture_loop(
         .enumerate()
         .find_map(|(index, source)| match source {
             #[cfg(feature = "opencv-capture")]
-            CameraSource::OpenCV(_) => {
-                opencv_capture::OpenCVCameraLoop::build(&camera_definition, shutdown_flag.clone())
-                    .map(VideoCaptureImpl::OpenCV)
-                    .inspect_err(|e| error!("OpenCV camera error: {:?}", e.to_string()))
-                    .map(|it| (index, it))
-                    .ok()
-            }
+            CameraSource::OpenCV(_) => opencv_capture::OpenCVCameraLoop::build(
+                &camera_definition,
+                exposure_scale.clone(),
+                shutdown_flag.clone(),
+            )
+            .map(VideoCaptureImpl::OpenCV)
+            .inspect_err(|e| error!("OpenCV camera error: {:?}", e.to_string()))
+            .map(|it| (index, it))
+            .ok(),
             #[cfg(feature = "mediars-capture")]
             CameraSource::MediaRS(_) => {
+                // FUTURE control the exposure, the camera is configured via a dictionary of options that doesn't
+                //        include it
+                if camera_definition
+                    .exposure_metering
+                    .is_some()
+                {
+                    log::warn!("Exposure metering is unsupported for MediaRS cameras, the exposure is not adjusted");
+                }
                 mediars_capture::MediaRSCameraLoop::build(&camera_definition, shutdown_flag.clone())
                     .map(VideoCaptureImpl::MediaRS)
                     .inspect_err(|e| error!("MediaRS camera error: {:?}", e.to_string()))
                     .map(|it| (index, it))
                     .ok()
             }
+            CameraSource::TestPattern => {
+                test_pattern::TestPatternLoop::build(&camera_definition, exposure_scale.clone(), shutdown_flag.clone())
+                    .map(VideoCaptureImpl::TestPattern)
+                    .inspect_err(|e| error!("Test pattern error: {:?}", e.to_string()))
+                    .map(|it| (index, it))
+                    .ok()
+            }
             _ => None,
         })
         .ok_or(anyhow!("No usable camera source found in camera definition"))
@@ -168,4 +204,5 @@ enum VideoCaptureImpl {
     MediaRS(mediars_capture::MediaRSCameraLoop),
     #[cfg(feature = "opencv-capture")]
     OpenCV(opencv_capture::OpenCVCameraLoop),
+    TestPattern(test_pattern::TestPatternLoop),
 }
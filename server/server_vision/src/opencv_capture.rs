@@ -1,11 +1,12 @@
 use std::time::Duration;
 
 use chrono::DateTime;
-use log::{error, info};
+use log::{error, info, warn};
 use opencv::core::Mat;
 use opencv::videoio::{VideoCapture, VideoWriter};
 use opencv::{prelude::*, videoio};
 use server_common::camera::{CameraDefinition, CameraSource};
+use tokio::sync::watch;
 use tokio::time;
 use tokio::time::Instant;
 use tokio_util::sync::CancellationToken;
@@ -15,11 +16,18 @@ use crate::VideoCaptureLoop;
 pub struct OpenCVCameraLoop {
     fps: f32,
     cam: VideoCapture,
+    /// the exposure the camera started with, in the units of the backend
+    base_exposure: f64,
+    exposure_scale: watch::Receiver<f64>,
     shutdown_flag: CancellationToken,
 }
 
 impl OpenCVCameraLoop {
-    pub fn build(camera_definition: &CameraDefinition, shutdown_flag: CancellationToken) -> anyhow::Result<Self> {
+    pub fn build(
+        camera_definition: &CameraDefinition,
+        exposure_scale: watch::Receiver<f64>,
+        shutdown_flag: CancellationToken,
+    ) -> anyhow::Result<Self> {
         let Some((source_index, open_cv_camera_config)) = camera_definition
             .sources
             .iter()
@@ -73,12 +81,37 @@ impl OpenCVCameraLoop {
             open_cv_camera_config.index, configured_fps
         );
 
+        let base_exposure = cam.get(videoio::CAP_PROP_EXPOSURE)?;
+        info!(
+            "OpenCVCamera: {}, Exposure: {}",
+            open_cv_camera_config.index, base_exposure
+        );
+
         Ok(Self {
             fps: configured_fps,
             cam,
+            base_exposure,
+            exposure_scale,
             shutdown_flag,
         })
     }
+
+    /// The units of the exposure depend on the backend, V4L2 uses 100µs units, DirectShow and MSMF use log2 seconds,
+    /// which are negative.
+    fn apply_exposure(&mut self, scale: f64) -> opencv::Result<()> {
+        let exposure = match self.base_exposure {
+            base if base < 0.0 => base + scale.log2(),
+            base => base * scale,
+        };
+
+        // otherwise the auto exposure undoes the adjustment, 0.25 is manual exposure for V4L2
+        self.cam
+            .set(videoio::CAP_PROP_AUTO_EXPOSURE, 0.25)?;
+        self.cam
+            .set(videoio::CAP_PROP_EXPOSURE, exposure)?;
+        info!("Exposure adjusted. scale: {:.2}, exposure: {}", scale, exposure);
+        Ok(())
+    }
 }
 
 impl VideoCaptureLoop for OpenCVCameraLoop {
@@ -100,6 +133,17 @@ impl VideoCaptureLoop for OpenCVCameraLoop {
             loop {
                 interval.tick().await;
 
+                if self
+                    .exposure_scale
+                    .has_changed()
+                    .unwrap_or(false)
+                {
+                    let scale = *self.exposure_scale.borrow_and_update();
+                    if let Err(e) = self.apply_exposure(scale) {
+                        warn!("Unable to adjust exposure. scale: {:.2}, error: {:?}", scale, e);
+                    }
+                }
+
                 let frame_timestamp = chrono::Utc::now();
                 let frame_instant = Instant::now();
 
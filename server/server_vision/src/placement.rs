@@ -0,0 +1,112 @@
+//! Comparing the images of the target location of a placement, taken before and after the part was placed.
+
+use anyhow::anyhow;
+use opencv::core::Vector;
+use opencv::imgcodecs;
+use opencv::prelude::*;
+
+/// The difference between the before and after images of a placement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlacementDifference {
+    /// the offset of the after image from the before image, in pixels, e.g. when the board moved or the camera
+    /// settled differently
+    pub shift_x: i32,
+    pub shift_y: i32,
+    /// the fraction of the center region, 0.0 to 1.0, that changed once the images are registered, a placed part
+    /// covers some of the region
+    pub changed: f32,
+}
+
+/// Registers the after image to the before image, by searching the offset up to `max_shift` pixels in each direction
+/// at which the images differ least, then measures the fraction of the center region that changed by more than
+/// `contrast` gray levels.
+///
+/// The center region is half the width and half the height of the image, the target location of the placement is
+/// assumed to be at the center, the same as for [`bad_mark_coverage`](crate::bad_mark::bad_mark_coverage).
+pub fn placement_difference(
+    before_jpeg: &[u8],
+    after_jpeg: &[u8],
+    contrast: u8,
+    max_shift: u32,
+) -> anyhow::Result<PlacementDifference> {
+    let before = decode_gray(before_jpeg)?;
+    let after = decode_gray(after_jpeg)?;
+    if before.cols() != after.cols() || before.rows() != after.rows() {
+        return Err(anyhow!(
+            "Image sizes differ. before: {}x{}, after: {}x{}",
+            before.cols(),
+            before.rows(),
+            after.cols(),
+            after.rows()
+        ));
+    }
+
+    // a decoded image is continuous, one byte per pixel
+    let before_luma = before.data_bytes()?;
+    let after_luma = after.data_bytes()?;
+    let columns = before.cols() as usize;
+    let rows = before.rows() as usize;
+
+    // the shifted center region must stay within the image
+    let max_shift = (max_shift as usize)
+        .min(columns / 4)
+        .min(rows / 4) as i32;
+    let (left, right) = (columns / 4, columns - columns / 4);
+    let (top, bottom) = (rows / 4, rows - rows / 4);
+    let region = (right - left) * (bottom - top);
+    if region == 0 {
+        return Err(anyhow!("Image too small. size: {}x{}", columns, rows));
+    }
+
+    let pixel = |luma: &[u8], column: usize, row: usize, dx: i32, dy: i32| {
+        let column = (column as i32 + dx) as usize;
+        let row = (row as i32 + dy) as usize;
+        luma[row * columns + column]
+    };
+
+    // the coarse search samples every 4th pixel, registration doesn't need the full resolution
+    let mut best = (0, 0, u64::MAX);
+    for dy in -max_shift..=max_shift {
+        for dx in -max_shift..=max_shift {
+            let mut sum = 0_u64;
+            for row in (top..bottom).step_by(4) {
+                for column in (left..right).step_by(4) {
+                    let before = pixel(before_luma, column, row, 0, 0);
+                    let after = pixel(after_luma, column, row, dx, dy);
+                    sum += before.abs_diff(after) as u64;
+                }
+            }
+            // the smallest shift wins a tie
+            if sum < best.2 || (sum == best.2 && dx.abs() + dy.abs() < best.0.abs() + best.1.abs()) {
+                best = (dx, dy, sum);
+            }
+        }
+    }
+    let (shift_x, shift_y, _) = best;
+
+    let mut changed = 0_usize;
+    for row in top..bottom {
+        for column in left..right {
+            let before = pixel(before_luma, column, row, 0, 0);
+            let after = pixel(after_luma, column, row, shift_x, shift_y);
+            if before.abs_diff(after) > contrast {
+                changed += 1;
+            }
+        }
+    }
+
+    Ok(PlacementDifference {
+        shift_x,
+        shift_y,
+        changed: changed as f32 / region as f32,
+    })
+}
+
+fn decode_gray(jpeg_bytes: &[u8]) -> anyhow::Result<Mat> {
+    let buffer = Vector::<u8>::from_slice(jpeg_bytes);
+    let image = imgcodecs::imdecode(&buffer, imgcodecs::IMREAD_GRAYSCALE)?;
+    if image.empty() {
+        return Err(anyhow!("Unable to decode image"));
+    }
+    Ok(image)
+}
@@ -0,0 +1,78 @@
+//! Finding the polarity mark of a part in the pocket of a tape, e.g. the pin 1 dot of an IC or the band of a diode.
+
+use anyhow::anyhow;
+use opencv::core::Vector;
+use opencv::imgcodecs;
+use opencv::prelude::*;
+
+/// The fraction of each quadrant of the part that differs from the body of the part, in image coordinates, the top of
+/// the image is the top left quadrant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolarityCoverage {
+    pub top_left: f32,
+    pub top_right: f32,
+    pub bottom_left: f32,
+    pub bottom_right: f32,
+}
+
+/// The coverage of the polarity mark in each quadrant of the center region of the image.
+///
+/// The center region is half the width and half the height of the image, the camera is centered over the pocket so
+/// the region is mostly the body of the part, the median of the region is the body.  Both dark marks, e.g. a dot
+/// molded into the body, and light marks, e.g. a printed band, are covered.
+///
+/// `contrast` is the difference in gray level from the body above which a pixel is part of the mark.
+pub fn polarity_coverage(jpeg_bytes: &[u8], contrast: u8) -> anyhow::Result<PolarityCoverage> {
+    let buffer = Vector::<u8>::from_slice(jpeg_bytes);
+    let image = imgcodecs::imdecode(&buffer, imgcodecs::IMREAD_GRAYSCALE)?;
+    if image.empty() {
+        return Err(anyhow!("Unable to decode image"));
+    }
+
+    // a decoded image is continuous, one byte per pixel
+    let luma = image.data_bytes()?;
+    let columns = image.cols() as usize;
+    let rows = image.rows() as usize;
+
+    let (left, right) = (columns / 4, columns - columns / 4);
+    let (top, bottom) = (rows / 4, rows - rows / 4);
+    let (center_column, center_row) = (columns / 2, rows / 2);
+
+    let mut histogram = [0_usize; 256];
+    for row in top..bottom {
+        for value in &luma[row * columns + left..row * columns + right] {
+            histogram[*value as usize] += 1;
+        }
+    }
+    let mut remaining = (right - left) * (bottom - top) / 2;
+    let body = histogram
+        .iter()
+        .position(|count| {
+            let median = remaining < *count;
+            remaining = remaining.saturating_sub(*count);
+            median
+        })
+        .unwrap_or(0) as u8;
+
+    let coverage = |(row_start, row_end): (usize, usize), (column_start, column_end): (usize, usize)| {
+        let mut marked = 0_usize;
+        for row in row_start..row_end {
+            marked += luma[row * columns + column_start..row * columns + column_end]
+                .iter()
+                .filter(|value| value.abs_diff(body) > contrast)
+                .count();
+        }
+        let quadrant = (row_end - row_start) * (column_end - column_start);
+        match quadrant {
+            0 => 0.0,
+            _ => marked as f32 / quadrant as f32,
+        }
+    };
+
+    Ok(PolarityCoverage {
+        top_left: coverage((top, center_row), (left, center_column)),
+        top_right: coverage((top, center_row), (center_column, right)),
+        bottom_left: coverage((center_row, bottom), (left, center_column)),
+        bottom_right: coverage((center_row, bottom), (center_column, right)),
+    })
+}
@@ -0,0 +1,110 @@
+//! Cropping vision templates, e.g. a fiducial model or the template of a part, from a camera frame, and finding them
+//! in later frames.
+
+use anyhow::anyhow;
+use machine_geometry::PixelPoint;
+use opencv::core::{Mat, Point, Rect, Vector};
+use opencv::prelude::*;
+use opencv::{core, imgcodecs, imgproc};
+
+/// The region of the frame, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CroppedImage {
+    /// lossless, so that matching isn't affected by compression artifacts
+    pub png_bytes: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Crop the region from the image, returns `None` if the region is empty or extends beyond the image.
+pub fn crop_jpeg(jpeg_bytes: &[u8], rect: CropRect) -> anyhow::Result<Option<CroppedImage>> {
+    let buffer = Vector::<u8>::from_slice(jpeg_bytes);
+    let image = imgcodecs::imdecode(&buffer, imgcodecs::IMREAD_COLOR)?;
+    if image.empty() {
+        return Err(anyhow!("Unable to decode image"));
+    }
+
+    let fits = |offset: u32, length: u32, size: i32| {
+        length > 0
+            && offset
+                .checked_add(length)
+                .is_some_and(|end| end <= size as u32)
+    };
+    if !fits(rect.x, rect.width, image.cols()) || !fits(rect.y, rect.height, image.rows()) {
+        return Ok(None);
+    }
+
+    let region = Mat::roi(
+        &image,
+        Rect::new(rect.x as i32, rect.y as i32, rect.width as i32, rect.height as i32),
+    )?;
+
+    let mut buf = Vector::<u8>::new();
+    let params = Vector::<i32>::new();
+    imgcodecs::imencode(".png", &region, &mut buf, &params)?;
+
+    Ok(Some(CroppedImage {
+        png_bytes: buf.to_vec(),
+        width: rect.width,
+        height: rect.height,
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemplateMatch {
+    /// the center of the matched region, relative to the center of the image
+    pub center: PixelPoint,
+    /// normalized correlation, 1.0 for an exact match
+    pub score: f64,
+}
+
+/// Find the region of the image that best matches the template, returns `None` if the best match scores less than
+/// `min_score` or the template is larger than the image.
+///
+/// Both are compared in grayscale, so that a template still matches when the lighting color differs.
+pub fn match_template(jpeg_bytes: &[u8], template_png: &[u8], min_score: f64) -> anyhow::Result<Option<TemplateMatch>> {
+    let image = imgcodecs::imdecode(&Vector::<u8>::from_slice(jpeg_bytes), imgcodecs::IMREAD_GRAYSCALE)?;
+    if image.empty() {
+        return Err(anyhow!("Unable to decode image"));
+    }
+    let template = imgcodecs::imdecode(&Vector::<u8>::from_slice(template_png), imgcodecs::IMREAD_GRAYSCALE)?;
+    if template.empty() {
+        return Err(anyhow!("Unable to decode template"));
+    }
+    if template.cols() > image.cols() || template.rows() > image.rows() {
+        return Ok(None);
+    }
+
+    let mut scores = Mat::default();
+    imgproc::match_template_def(&image, &template, &mut scores, imgproc::TM_CCOEFF_NORMED)?;
+
+    let mut max_score = 0.0;
+    let mut max_location = Point::default();
+    core::min_max_loc(
+        &scores,
+        None,
+        Some(&mut max_score),
+        None,
+        Some(&mut max_location),
+        &core::no_array(),
+    )?;
+    if max_score < min_score {
+        return Ok(None);
+    }
+
+    Ok(Some(TemplateMatch {
+        center: PixelPoint {
+            x: max_location.x as f64 + template.cols() as f64 / 2.0 - image.cols() as f64 / 2.0,
+            y: max_location.y as f64 + template.rows() as f64 / 2.0 - image.rows() as f64 / 2.0,
+        },
+        score: max_score,
+    }))
+}
@@ -0,0 +1,125 @@
+//! A built-in test pattern source, see [`CameraSource::TestPattern`].
+
+use std::time::Duration;
+
+use chrono::DateTime;
+use log::{error, info};
+use opencv::core::{Mat, Point, Scalar, Size, Vector};
+use opencv::prelude::*;
+use opencv::{imgcodecs, imgproc};
+use server_common::camera::{CameraDefinition, CameraSource};
+use tokio::sync::watch;
+use tokio::time;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::VideoCaptureLoop;
+
+const TEST_PATTERN_PNG: &[u8] = include_bytes!("../assets/test-pattern.png");
+
+pub struct TestPatternLoop {
+    fps: f32,
+    /// scaled to the size of the camera
+    pattern: Mat,
+    /// simulated by scaling the pattern, so that exposure metering can be tried without a camera
+    exposure_scale: watch::Receiver<f64>,
+    shutdown_flag: CancellationToken,
+}
+
+impl TestPatternLoop {
+    pub fn build(
+        camera_definition: &CameraDefinition,
+        exposure_scale: watch::Receiver<f64>,
+        shutdown_flag: CancellationToken,
+    ) -> anyhow::Result<Self> {
+        if !camera_definition
+            .sources
+            .iter()
+            .any(|source| matches!(source, CameraSource::TestPattern))
+        {
+            anyhow::bail!("Not a test pattern camera")
+        }
+
+        let decoded = imgcodecs::imdecode(&Vector::<u8>::from_slice(TEST_PATTERN_PNG), imgcodecs::IMREAD_COLOR)?;
+        let mut pattern = Mat::default();
+        imgproc::resize(
+            &decoded,
+            &mut pattern,
+            Size::new(camera_definition.width as i32, camera_definition.height as i32),
+            0.0,
+            0.0,
+            imgproc::INTER_LINEAR,
+        )?;
+
+        info!(
+            "TestPattern: {}, width: {}, height: {}, fps: {}",
+            camera_definition.name, camera_definition.width, camera_definition.height, camera_definition.fps
+        );
+
+        Ok(Self {
+            fps: camera_definition.fps,
+            pattern,
+            exposure_scale,
+            shutdown_flag,
+        })
+    }
+}
+
+impl VideoCaptureLoop for TestPatternLoop {
+    fn run<F>(&mut self, f: F) -> impl Future<Output = anyhow::Result<()>> + Send + '_
+    where
+        F: for<'a> Fn(&'a Mat, DateTime<chrono::Utc>, Instant, Duration, u64) -> Result<(), ()> + Send + Sync + 'static,
+    {
+        async move {
+            let mut frame_number = 0_u64;
+
+            let period = Duration::from_secs_f64(1.0 / self.fps as f64);
+
+            let mut interval = time::interval(period);
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+
+            let mut previous_frame_at = Instant::now();
+
+            loop {
+                interval.tick().await;
+
+                let frame_timestamp = chrono::Utc::now();
+                let frame_instant = Instant::now();
+
+                frame_number += 1;
+
+                let mut frame_mat = Mat::default();
+                let scale = *self.exposure_scale.borrow();
+                self.pattern
+                    .convert_to(&mut frame_mat, -1, scale, 0.0)?;
+
+                // the frame number is drawn on the pattern, so that a frozen stream is obvious
+                imgproc::put_text(
+                    &mut frame_mat,
+                    &format!("{}", frame_number),
+                    Point::new(16, 48),
+                    imgproc::FONT_HERSHEY_SIMPLEX,
+                    1.5,
+                    Scalar::new(255.0, 255.0, 255.0, 0.0),
+                    3,
+                    imgproc::LINE_AA,
+                    false,
+                )?;
+
+                let frame_duration = frame_instant - previous_frame_at;
+                previous_frame_at = frame_instant;
+
+                let result = f(&frame_mat, frame_timestamp, frame_instant, frame_duration, frame_number);
+                if result.is_err() {
+                    error!("Test pattern frame processing error: {:?}", result);
+                }
+
+                if self.shutdown_flag.is_cancelled() {
+                    break;
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Thumbnails of camera frames, so that a list of frames can be shown without transferring every full frame.
+
+use anyhow::anyhow;
+use opencv::core::{Mat, Size, Vector};
+use opencv::prelude::*;
+use opencv::{imgcodecs, imgproc};
+
+/// Scales the image down to fit within `max_width` x `max_height`, keeping the aspect ratio, images that already fit
+/// are re-encoded without scaling.
+pub fn jpeg_thumbnail(jpeg_bytes: &[u8], max_width: u32, max_height: u32, quality: u8) -> anyhow::Result<Vec<u8>> {
+    let buffer = Vector::<u8>::from_slice(jpeg_bytes);
+    let image = imgcodecs::imdecode(&buffer, imgcodecs::IMREAD_COLOR)?;
+    if image.empty() {
+        return Err(anyhow!("Unable to decode image"));
+    }
+
+    let scale = f64::min(
+        max_width as f64 / image.cols() as f64,
+        max_height as f64 / image.rows() as f64,
+    )
+    .min(1.0);
+    let size = Size::new(
+        ((image.cols() as f64 * scale).round() as i32).max(1),
+        ((image.rows() as f64 * scale).round() as i32).max(1),
+    );
+
+    let mut thumbnail = Mat::default();
+    // area interpolation avoids the aliasing of the other methods when shrinking
+    imgproc::resize(&image, &mut thumbnail, size, 0.0, 0.0, imgproc::INTER_AREA)?;
+
+    let mut buf = Vector::<u8>::new();
+    let params = Vector::<i32>::from_slice(&[imgcodecs::IMWRITE_JPEG_QUALITY, quality as i32]);
+    imgcodecs::imencode(".jpg", &thumbnail, &mut buf, &params)?;
+
+    Ok(buf.to_vec())
+}
@@ -0,0 +1,205 @@
+//! Running the reference cases, and comparing the results with a baseline.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use machine_geometry::PixelPoint;
+use serde::{Deserialize, Serialize};
+use server_vision::{fiducial, placement, template};
+
+use crate::reference::{Check, CheckKind, ReferenceCase, ReferenceSet};
+
+/// Matches scoring less than this are treated as not found.
+pub const MIN_TEMPLATE_SCORE: f64 = 0.8;
+
+/// Differences smaller than this are not a regression, the accuracy of an unchanged algorithm is reproducible but the
+/// JPEG decoder of another OpenCV build may round differently.
+pub const ERROR_MARGIN_PX: f64 = 0.05;
+
+/// Only used for the changed fraction of the placement, which the benchmark doesn't check.
+const ALIGNMENT_CONTRAST: u8 = 40;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// the distance from the ground truth, in pixels, 0.0 when correctly not found
+    Measured(f64),
+    /// found when the image doesn't have it, or not found when it does
+    Mismatch,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseResult {
+    /// `<set>/<case>`
+    pub key: String,
+    pub kind: CheckKind,
+    pub outcome: Outcome,
+    /// the median of the iterations
+    pub latency: Duration,
+}
+
+/// Run each case of the set `iterations` times.
+pub fn run_set(set: &ReferenceSet, iterations: u32) -> Vec<CaseResult> {
+    set.cases
+        .iter()
+        .map(|case| run_case(&set.name, case, iterations))
+        .collect()
+}
+
+pub fn run_case(set_name: &str, case: &ReferenceCase, iterations: u32) -> CaseResult {
+    let mut latencies = Vec::new();
+    let mut outcome = Outcome::Mismatch;
+    for _ in 0..iterations.max(1) {
+        let started_at = Instant::now();
+        outcome = evaluate(&case.check);
+        latencies.push(started_at.elapsed());
+    }
+
+    CaseResult {
+        key: format!("{}/{}", set_name, case.name),
+        kind: case.check.kind(),
+        outcome,
+        latency: median(&mut latencies),
+    }
+}
+
+fn evaluate(check: &Check) -> Outcome {
+    let result = match check {
+        Check::Fiducial {
+            jpeg,
+            center,
+        } => fiducial::find_center_dot(jpeg).map(|dot| compare(dot.map(|dot| dot.center), *center)),
+        Check::Alignment {
+            before_jpeg,
+            after_jpeg,
+            shift_x,
+            shift_y,
+            max_shift,
+        } => {
+            placement::placement_difference(before_jpeg, after_jpeg, ALIGNMENT_CONTRAST, *max_shift).map(|difference| {
+                let error_x = (difference.shift_x - shift_x) as f64;
+                let error_y = (difference.shift_y - shift_y) as f64;
+                Outcome::Measured(error_x.hypot(error_y))
+            })
+        }
+        Check::Template {
+            jpeg,
+            template_png,
+            center,
+        } => template::match_template(jpeg, template_png, MIN_TEMPLATE_SCORE)
+            .map(|found| compare(found.map(|found| found.center), *center)),
+    };
+
+    result.unwrap_or_else(|e| Outcome::Failed(e.to_string()))
+}
+
+fn compare(found: Option<PixelPoint>, expected: Option<PixelPoint>) -> Outcome {
+    match (found, expected) {
+        (Some(found), Some(expected)) => Outcome::Measured((found.x - expected.x).hypot(found.y - expected.y)),
+        (None, None) => Outcome::Measured(0.0),
+        _ => Outcome::Mismatch,
+    }
+}
+
+pub(crate) fn median(durations: &mut [Duration]) -> Duration {
+    durations.sort();
+    durations
+        .get(durations.len() / 2)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// The results of a previous run, stored as RON, so that a change to the vision code can be compared with it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    /// by [`CaseResult::key`]
+    pub cases: BTreeMap<String, BaselineCase>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BaselineCase {
+    pub error_px: f64,
+    pub latency_us: u64,
+}
+
+impl Baseline {
+    /// Cases that didn't measure are left out, they fail regardless of the baseline.
+    pub fn from_results(results: &[CaseResult]) -> Self {
+        let cases = results
+            .iter()
+            .filter_map(|result| match result.outcome {
+                Outcome::Measured(error_px) => Some((result.key.clone(), BaselineCase {
+                    error_px,
+                    latency_us: result.latency.as_micros() as u64,
+                })),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            cases,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Regression {
+    /// further from the ground truth than the tolerance of the set, or the outcome wasn't measured
+    OutOfTolerance { key: String, outcome: Outcome },
+    LessAccurate {
+        key: String,
+        baseline_px: f64,
+        error_px: f64,
+    },
+    Slower {
+        key: String,
+        baseline: Duration,
+        latency: Duration,
+    },
+}
+
+/// `latency_factor` is how many times slower than the baseline a case may be, latency varies between runs, more so on
+/// a shared CI runner than on a quiet workstation.
+pub fn regressions(
+    results: &[CaseResult],
+    tolerance_px: f64,
+    baseline: Option<&Baseline>,
+    latency_factor: f64,
+) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+    for result in results {
+        let error_px = match result.outcome {
+            Outcome::Measured(error_px) if error_px <= tolerance_px => error_px,
+            _ => {
+                regressions.push(Regression::OutOfTolerance {
+                    key: result.key.clone(),
+                    outcome: result.outcome.clone(),
+                });
+                continue;
+            }
+        };
+
+        // a case that is new since the baseline was written has nothing to compare with
+        let Some(baseline_case) = baseline.and_then(|baseline| baseline.cases.get(&result.key)) else {
+            continue;
+        };
+
+        if error_px > baseline_case.error_px + ERROR_MARGIN_PX {
+            regressions.push(Regression::LessAccurate {
+                key: result.key.clone(),
+                baseline_px: baseline_case.error_px,
+                error_px,
+            });
+        }
+
+        let baseline_latency = Duration::from_micros(baseline_case.latency_us);
+        if result.latency > baseline_latency.mul_f64(latency_factor) {
+            regressions.push(Regression::Slower {
+                key: result.key.clone(),
+                baseline: baseline_latency,
+                latency: result.latency,
+            });
+        }
+    }
+    regressions
+}
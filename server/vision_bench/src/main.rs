@@ -0,0 +1,138 @@
+//! Benchmarks the machine vision of the server against reference images with known ground truth, reporting the accuracy
+//! and latency of each case, and the regressions since a baseline.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::Context;
+use clap::Parser;
+
+use crate::bench::{Baseline, CaseResult, Outcome, Regression};
+
+mod bench;
+mod reference;
+mod synthetic;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Parser, Debug)]
+#[command(name = "vision-bench", version, about = "MakerPnP - Vision benchmark")]
+struct Args {
+    /// A curated reference set, a directory with a `manifest.ron`, can be repeated, the built-in synthetic set always
+    /// runs
+    #[arg(long = "set", value_name = "DIRECTORY")]
+    sets: Vec<PathBuf>,
+
+    /// Compare the results with the baseline, a RON file written by `--write-baseline`
+    #[arg(long = "baseline", value_name = "PATH")]
+    baseline: Option<PathBuf>,
+
+    /// Write the results as the baseline, e.g. before changing the vision code
+    #[arg(long = "write-baseline", value_name = "PATH")]
+    write_baseline: Option<PathBuf>,
+
+    /// How many times each case is run, the median latency is reported
+    #[arg(long = "iterations", default_value_t = 5)]
+    iterations: u32,
+
+    /// A case is a latency regression when it takes this many times longer than in the baseline
+    #[arg(long = "latency-factor", default_value_t = 1.5)]
+    latency_factor: f64,
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let args = Args::parse();
+
+    let baseline = match &args.baseline {
+        Some(path) => Some(read_baseline(path)?),
+        None => None,
+    };
+
+    let mut sets = vec![synthetic::reference_set()?];
+    for directory in &args.sets {
+        sets.push(reference::load(directory)?);
+    }
+
+    let mut all_results = Vec::new();
+    let mut all_regressions = Vec::new();
+    for set in &sets {
+        let results = bench::run_set(set, args.iterations);
+        for result in &results {
+            println!("{}", format_result(result));
+        }
+        all_regressions.extend(bench::regressions(
+            &results,
+            set.tolerance_px,
+            baseline.as_ref(),
+            args.latency_factor,
+        ));
+        all_results.extend(results);
+    }
+
+    if let Some(path) = &args.write_baseline {
+        let content =
+            ron::ser::to_string_pretty(&Baseline::from_results(&all_results), ron::ser::PrettyConfig::default())?;
+        fs::write(path, content)?;
+        println!("Baseline written. path: {}", path.display());
+    }
+
+    println!();
+    if all_regressions.is_empty() {
+        println!("No regressions. cases: {}", all_results.len());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for regression in &all_regressions {
+        println!("{}", format_regression(regression));
+    }
+    println!("Regressions: {}, cases: {}", all_regressions.len(), all_results.len());
+    Ok(ExitCode::FAILURE)
+}
+
+fn read_baseline(path: &Path) -> anyhow::Result<Baseline> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Unable to read baseline. path: {}", path.display()))?;
+    ron::from_str::<Baseline>(&content).with_context(|| format!("Invalid baseline. path: {}", path.display()))
+}
+
+fn format_result(result: &CaseResult) -> String {
+    let outcome = match &result.outcome {
+        Outcome::Measured(error_px) => format!("{:.3} px", error_px),
+        Outcome::Mismatch => "mismatch".to_string(),
+        Outcome::Failed(reason) => format!("failed: {}", reason),
+    };
+    format!(
+        "{:<48} {:<10} {:>10.3} ms  {}",
+        result.key,
+        result.kind,
+        result.latency.as_secs_f64() * 1000.0,
+        outcome
+    )
+}
+
+fn format_regression(regression: &Regression) -> String {
+    match regression {
+        Regression::OutOfTolerance {
+            key,
+            outcome,
+        } => format!("Out of tolerance. case: {}, outcome: {:?}", key, outcome),
+        Regression::LessAccurate {
+            key,
+            baseline_px,
+            error_px,
+        } => format!(
+            "Less accurate. case: {}, baseline: {:.3} px, error: {:.3} px",
+            key, baseline_px, error_px
+        ),
+        Regression::Slower {
+            key,
+            baseline,
+            latency,
+        } => format!(
+            "Slower. case: {}, baseline: {:?}, latency: {:?}",
+            key, baseline, latency
+        ),
+    }
+}
@@ -0,0 +1,174 @@
+//! Reference image sets, images with known ground truth.
+//!
+//! A curated set is a directory with a `manifest.ron`, the paths of the images are relative to the directory.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use machine_geometry::PixelPoint;
+use serde::{Deserialize, Serialize};
+
+pub const MANIFEST_FILE: &str = "manifest.ron";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    /// the largest distance from the ground truth, in pixels, for a case to pass
+    pub tolerance_px: f64,
+    pub cases: Vec<ManifestCase>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestCase {
+    pub name: String,
+    pub check: ManifestCheck,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ManifestCheck {
+    /// `center` is relative to the center of the image, `None` if there is no dot
+    Fiducial { image: PathBuf, center: Option<PixelPoint> },
+    /// the offset of the after image from the before image of a placement
+    Alignment {
+        before: PathBuf,
+        after: PathBuf,
+        shift_x: i32,
+        shift_y: i32,
+        max_shift: u32,
+    },
+    /// `center` is the center of the template in the image, relative to the center of the image, `None` if the image
+    /// doesn't have the template
+    Template {
+        image: PathBuf,
+        template: PathBuf,
+        center: Option<PixelPoint>,
+    },
+}
+
+pub struct ReferenceSet {
+    pub name: String,
+    pub tolerance_px: f64,
+    pub cases: Vec<ReferenceCase>,
+}
+
+pub struct ReferenceCase {
+    pub name: String,
+    pub check: Check,
+}
+
+/// A [`ManifestCheck`] with the images loaded.
+pub enum Check {
+    Fiducial {
+        jpeg: Vec<u8>,
+        center: Option<PixelPoint>,
+    },
+    Alignment {
+        before_jpeg: Vec<u8>,
+        after_jpeg: Vec<u8>,
+        shift_x: i32,
+        shift_y: i32,
+        max_shift: u32,
+    },
+    Template {
+        jpeg: Vec<u8>,
+        template_png: Vec<u8>,
+        center: Option<PixelPoint>,
+    },
+}
+
+impl Check {
+    pub fn kind(&self) -> CheckKind {
+        match self {
+            Check::Fiducial {
+                ..
+            } => CheckKind::Fiducial,
+            Check::Alignment {
+                ..
+            } => CheckKind::Alignment,
+            Check::Template {
+                ..
+            } => CheckKind::Template,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckKind {
+    Fiducial,
+    Alignment,
+    Template,
+}
+
+impl fmt::Display for CheckKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckKind::Fiducial => f.pad("fiducial"),
+            CheckKind::Alignment => f.pad("alignment"),
+            CheckKind::Template => f.pad("template"),
+        }
+    }
+}
+
+/// Load the set in the directory, see [`MANIFEST_FILE`].
+pub fn load(directory: &Path) -> anyhow::Result<ReferenceSet> {
+    let manifest_path = directory.join(MANIFEST_FILE);
+    let content = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Unable to read manifest. path: {}", manifest_path.display()))?;
+    let manifest = ron::from_str::<Manifest>(&content)
+        .with_context(|| format!("Invalid manifest. path: {}", manifest_path.display()))?;
+
+    let read = |path: &Path| {
+        let path = directory.join(path);
+        fs::read(&path).with_context(|| format!("Unable to read image. path: {}", path.display()))
+    };
+
+    let cases = manifest
+        .cases
+        .into_iter()
+        .map(|case| {
+            let check = match case.check {
+                ManifestCheck::Fiducial {
+                    image,
+                    center,
+                } => Check::Fiducial {
+                    jpeg: read(&image)?,
+                    center,
+                },
+                ManifestCheck::Alignment {
+                    before,
+                    after,
+                    shift_x,
+                    shift_y,
+                    max_shift,
+                } => Check::Alignment {
+                    before_jpeg: read(&before)?,
+                    after_jpeg: read(&after)?,
+                    shift_x,
+                    shift_y,
+                    max_shift,
+                },
+                ManifestCheck::Template {
+                    image,
+                    template,
+                    center,
+                } => Check::Template {
+                    jpeg: read(&image)?,
+                    template_png: read(&template)?,
+                    center,
+                },
+            };
+            Ok(ReferenceCase {
+                name: case.name,
+                check,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(ReferenceSet {
+        name: manifest.name,
+        tolerance_px: manifest.tolerance_px,
+        cases,
+    })
+}
@@ -0,0 +1,271 @@
+//! The built-in reference set, rendered with known ground truth, so that the benchmark runs without curated images,
+//! e.g. in CI.
+//!
+//! Rendering is deterministic, the same images are benchmarked on every run.
+
+use anyhow::anyhow;
+use machine_geometry::PixelPoint;
+use opencv::core::{CV_8UC1, Mat, Point, Rect, Scalar, Size, Vector};
+use opencv::prelude::*;
+use opencv::{imgcodecs, imgproc};
+use server_vision::template::{self, CropRect};
+
+use crate::reference::{Check, ReferenceCase, ReferenceSet};
+
+pub const NAME: &str = "synthetic";
+
+/// Blur, noise and JPEG compression move the measured center of a dot slightly, the shifts and template positions are
+/// whole pixels and are expected to be exact.
+pub const TOLERANCE_PX: f64 = 0.5;
+
+const WIDTH: i32 = 640;
+const HEIGHT: i32 = 480;
+
+/// Circles are drawn with 1/16th pixel precision, so that the center of a dot can be between pixels.
+const SUBPIXEL_BITS: i32 = 4;
+
+const PLATE: f64 = 210.0;
+const DOT: f64 = 40.0;
+const DOT_DIAMETER: f64 = 40.0;
+const DOT_PITCH: f64 = 160.0;
+
+const BOARD: f64 = 80.0;
+const BOARD_SEED: u64 = 0x5eed_b0a2d;
+
+/// The part is 80x50 pixels, the template is cropped with a margin of board around it.
+const PART_POSITION: Point = Point {
+    x: -120,
+    y: 60,
+};
+const TEMPLATE_SIZE: Size = Size {
+    width: 100,
+    height: 70,
+};
+
+pub fn reference_set() -> anyhow::Result<ReferenceSet> {
+    let mut cases = Vec::new();
+
+    // (name, center, blur, noise)
+    for (index, (name, center, blur, noise)) in [
+        ("fiducial-centered", (0.0, 0.0), 0, 0),
+        ("fiducial-offset", (23.25, -17.5), 0, 0),
+        ("fiducial-blurred", (-41.5, 12.75), 9, 0),
+        ("fiducial-noisy", (8.0, 30.0625), 0, 24),
+        ("fiducial-blurred-noisy", (-15.875, -36.5), 7, 16),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let center = PixelPoint {
+            x: center.0,
+            y: center.1,
+        };
+        cases.push(ReferenceCase {
+            name: name.to_string(),
+            check: Check::Fiducial {
+                jpeg: plate(center, blur, noise, index as u64 + 1)?,
+                center: Some(center),
+            },
+        });
+    }
+
+    // (name, shift, noise)
+    for (index, (name, (shift_x, shift_y), noise)) in [
+        ("alignment-unshifted", (0, 0), 0),
+        ("alignment-shifted", (3, -2), 0),
+        ("alignment-shifted-far", (-7, 6), 0),
+        ("alignment-noisy", (4, 5), 12),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let before = board(Point::default(), false)?;
+        let mut after = board(Point::new(shift_x, shift_y), true)?;
+        add_noise(&mut after, noise, index as u64 + 1)?;
+        cases.push(ReferenceCase {
+            name: name.to_string(),
+            check: Check::Alignment {
+                before_jpeg: encode_jpeg(&before)?,
+                after_jpeg: encode_jpeg(&after)?,
+                shift_x,
+                shift_y,
+                max_shift: 10,
+            },
+        });
+    }
+
+    // the template is cropped from the unshifted board, the same as a template captured by the operator
+    let template_png = {
+        let board_jpeg = encode_jpeg(&board(Point::default(), true)?)?;
+        let rect = CropRect {
+            x: (WIDTH / 2 + PART_POSITION.x - TEMPLATE_SIZE.width / 2) as u32,
+            y: (HEIGHT / 2 + PART_POSITION.y - TEMPLATE_SIZE.height / 2) as u32,
+            width: TEMPLATE_SIZE.width as u32,
+            height: TEMPLATE_SIZE.height as u32,
+        };
+        template::crop_jpeg(&board_jpeg, rect)?
+            .ok_or_else(|| anyhow!("Template outside the image"))?
+            .png_bytes
+    };
+
+    // (name, shift, noise)
+    for (index, (name, (shift_x, shift_y), noise)) in [
+        ("template-exact", (0, 0), 0),
+        ("template-shifted", (17, -11), 0),
+        ("template-noisy", (-9, 14), 12),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let mut image = board(Point::new(shift_x, shift_y), true)?;
+        add_noise(&mut image, noise, index as u64 + 1)?;
+        cases.push(ReferenceCase {
+            name: name.to_string(),
+            check: Check::Template {
+                jpeg: encode_jpeg(&image)?,
+                template_png: template_png.clone(),
+                center: Some(PixelPoint {
+                    x: (PART_POSITION.x + shift_x) as f64,
+                    y: (PART_POSITION.y + shift_y) as f64,
+                }),
+            },
+        });
+    }
+
+    Ok(ReferenceSet {
+        name: NAME.to_string(),
+        tolerance_px: TOLERANCE_PX,
+        cases,
+    })
+}
+
+/// A calibration plate, dark dots on a light background, with the dot nearest the center at `center`.
+fn plate(center: PixelPoint, blur: i32, noise: i32, seed: u64) -> anyhow::Result<Vec<u8>> {
+    let mut image = Mat::new_rows_cols_with_default(HEIGHT, WIDTH, CV_8UC1, Scalar::all(PLATE))?;
+
+    let subpixel = |value: f64| (value * (1 << SUBPIXEL_BITS) as f64).round() as i32;
+    for row in -2..=2 {
+        for column in -3..=3 {
+            let x = WIDTH as f64 / 2.0 + center.x + column as f64 * DOT_PITCH;
+            let y = HEIGHT as f64 / 2.0 + center.y + row as f64 * DOT_PITCH;
+            imgproc::circle(
+                &mut image,
+                Point::new(subpixel(x), subpixel(y)),
+                subpixel(DOT_DIAMETER / 2.0),
+                Scalar::all(DOT),
+                imgproc::FILLED,
+                imgproc::LINE_AA,
+                SUBPIXEL_BITS,
+            )?;
+        }
+    }
+
+    if blur > 0 {
+        let mut blurred = Mat::default();
+        imgproc::gaussian_blur_def(&image, &mut blurred, Size::new(blur, blur), 0.0)?;
+        image = blurred;
+    }
+    add_noise(&mut image, noise, seed)?;
+
+    encode_jpeg(&image)
+}
+
+/// A board with pads of varying size and brightness at random positions, so that there is only one offset at which
+/// two images of it match, and optionally a placed part, drawn at [`PART_POSITION`].
+fn board(offset: Point, with_part: bool) -> anyhow::Result<Mat> {
+    let mut image = Mat::new_rows_cols_with_default(HEIGHT, WIDTH, CV_8UC1, Scalar::all(BOARD))?;
+
+    let mut random = Xorshift::new(BOARD_SEED);
+    for _ in 0..160 {
+        // pads beyond the edges are drawn too, they move into the image when it's shifted
+        let pad = Rect::new(
+            random.range(-40, WIDTH + 40) + offset.x,
+            random.range(-40, HEIGHT + 40) + offset.y,
+            random.range(6, 30),
+            random.range(6, 30),
+        );
+        let brightness = random.range(150, 240) as f64;
+        imgproc::rectangle(
+            &mut image,
+            pad,
+            Scalar::all(brightness),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            0,
+        )?;
+    }
+
+    if with_part {
+        let center = Point::new(
+            WIDTH / 2 + PART_POSITION.x + offset.x,
+            HEIGHT / 2 + PART_POSITION.y + offset.y,
+        );
+        // leads along the long sides, then the body over them, then the pin 1 marker
+        for lead in 0..6 {
+            let x = center.x - 35 + lead * 14;
+            let leads = Rect::new(x, center.y - 31, 6, 62);
+            imgproc::rectangle(
+                &mut image,
+                leads,
+                Scalar::all(235.0),
+                imgproc::FILLED,
+                imgproc::LINE_8,
+                0,
+            )?;
+        }
+        let body = Rect::new(center.x - 40, center.y - 25, 80, 50);
+        imgproc::rectangle(&mut image, body, Scalar::all(25.0), imgproc::FILLED, imgproc::LINE_8, 0)?;
+        imgproc::circle(
+            &mut image,
+            Point::new(center.x - 30, center.y - 15),
+            5,
+            Scalar::all(140.0),
+            imgproc::FILLED,
+            imgproc::LINE_AA,
+            0,
+        )?;
+    }
+
+    Ok(image)
+}
+
+/// Uniform noise, up to `amplitude` gray levels either way.
+fn add_noise(image: &mut Mat, amplitude: i32, seed: u64) -> anyhow::Result<()> {
+    if amplitude == 0 {
+        return Ok(());
+    }
+    let mut random = Xorshift::new(seed);
+    for value in image.data_bytes_mut()? {
+        let noisy = *value as i32 + random.range(-amplitude, amplitude + 1);
+        *value = noisy.clamp(0, 255) as u8;
+    }
+    Ok(())
+}
+
+fn encode_jpeg(image: &Mat) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vector::<u8>::new();
+    let params = Vector::from_slice(&[imgcodecs::IMWRITE_JPEG_QUALITY, 90]);
+    imgcodecs::imencode(".jpg", image, &mut buf, &params)?;
+    Ok(buf.to_vec())
+}
+
+/// Deterministic, so that a set renders the same on every run and every platform.
+struct Xorshift {
+    state: u64,
+}
+
+impl Xorshift {
+    fn new(seed: u64) -> Self {
+        Self {
+            state: seed.max(1),
+        }
+    }
+
+    /// In `min..max`.
+    fn range(&mut self, min: i32, max: i32) -> i32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        min + (self.state % (max - min) as u64) as i32
+    }
+}
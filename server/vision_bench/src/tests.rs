@@ -0,0 +1,216 @@
+use std::fs;
+use std::time::Duration;
+
+use machine_geometry::PixelPoint;
+
+use crate::bench::{self, Baseline, BaselineCase, CaseResult, Outcome, Regression};
+use crate::reference::{self, Check, CheckKind, MANIFEST_FILE};
+use crate::synthetic;
+
+fn result(key: &str, outcome: Outcome, latency_ms: u64) -> CaseResult {
+    CaseResult {
+        key: key.to_string(),
+        kind: CheckKind::Fiducial,
+        outcome,
+        latency: Duration::from_millis(latency_ms),
+    }
+}
+
+fn baseline(key: &str, error_px: f64, latency_ms: u64) -> Baseline {
+    Baseline {
+        cases: [(key.to_string(), BaselineCase {
+            error_px,
+            latency_us: latency_ms * 1000,
+        })]
+        .into(),
+    }
+}
+
+#[test]
+pub fn median_latency_is_reported() {
+    // given
+    let mut latencies = [3, 1, 100, 2, 4].map(Duration::from_millis);
+
+    // expect
+    assert_eq!(bench::median(&mut latencies), Duration::from_millis(3));
+    assert_eq!(bench::median(&mut []), Duration::ZERO);
+}
+
+#[test]
+pub fn case_outside_the_tolerance_is_a_regression() {
+    // given
+    let results = [
+        result("set/within", Outcome::Measured(0.4), 10),
+        result("set/outside", Outcome::Measured(0.6), 10),
+        result("set/mismatch", Outcome::Mismatch, 10),
+    ];
+
+    // when
+    let regressions = bench::regressions(&results, 0.5, None, 1.5);
+
+    // then
+    assert_eq!(regressions, vec![
+        Regression::OutOfTolerance {
+            key: "set/outside".to_string(),
+            outcome: Outcome::Measured(0.6),
+        },
+        Regression::OutOfTolerance {
+            key: "set/mismatch".to_string(),
+            outcome: Outcome::Mismatch,
+        },
+    ]);
+}
+
+#[test]
+pub fn less_accurate_than_the_baseline_is_a_regression() {
+    // given
+    let baseline = baseline("set/case", 0.1, 10);
+
+    // expect
+    // within the margin
+    let results = [result("set/case", Outcome::Measured(0.14), 10)];
+    assert_eq!(bench::regressions(&results, 0.5, Some(&baseline), 1.5), vec![]);
+
+    let results = [result("set/case", Outcome::Measured(0.3), 10)];
+    assert_eq!(bench::regressions(&results, 0.5, Some(&baseline), 1.5), vec![
+        Regression::LessAccurate {
+            key: "set/case".to_string(),
+            baseline_px: 0.1,
+            error_px: 0.3,
+        }
+    ]);
+}
+
+#[test]
+pub fn slower_than_the_baseline_is_a_regression() {
+    // given
+    let baseline = baseline("set/case", 0.1, 10);
+
+    // expect
+    let results = [result("set/case", Outcome::Measured(0.1), 15)];
+    assert_eq!(bench::regressions(&results, 0.5, Some(&baseline), 1.5), vec![]);
+
+    let results = [result("set/case", Outcome::Measured(0.1), 16)];
+    assert_eq!(bench::regressions(&results, 0.5, Some(&baseline), 1.5), vec![
+        Regression::Slower {
+            key: "set/case".to_string(),
+            baseline: Duration::from_millis(10),
+            latency: Duration::from_millis(16),
+        }
+    ]);
+}
+
+#[test]
+pub fn case_added_since_the_baseline_is_not_compared() {
+    // given
+    let baseline = baseline("set/case", 0.0, 10);
+    let results = [result("set/new-case", Outcome::Measured(0.3), 100)];
+
+    // expect
+    assert_eq!(bench::regressions(&results, 0.5, Some(&baseline), 1.5), vec![]);
+}
+
+#[test]
+pub fn baseline_has_the_measured_cases() {
+    // given
+    let results = [
+        result("set/measured", Outcome::Measured(0.25), 12),
+        result("set/failed", Outcome::Failed("Unable to decode image".to_string()), 1),
+    ];
+
+    // when
+    let baseline = Baseline::from_results(&results);
+
+    // then
+    assert_eq!(baseline, self::baseline("set/measured", 0.25, 12));
+    let content = ron::ser::to_string_pretty(&baseline, ron::ser::PrettyConfig::default()).unwrap();
+    assert_eq!(ron::from_str::<Baseline>(&content).unwrap(), baseline);
+}
+
+#[test]
+pub fn curated_set_is_loaded_from_the_manifest() {
+    // given
+    let directory = std::env::temp_dir().join(format!("vision-bench-test-{}", std::process::id()));
+    fs::create_dir_all(directory.join("images")).unwrap();
+    fs::write(directory.join("images/plate.jpg"), [1, 2, 3]).unwrap();
+    fs::write(
+        directory.join(MANIFEST_FILE),
+        r#"(
+            name: "down-camera",
+            tolerance_px: 1.5,
+            cases: [
+                (
+                    name: "plate",
+                    check: Fiducial(image: "images/plate.jpg", center: Some((x: 1.5, y: -2.0))),
+                ),
+            ],
+        )"#,
+    )
+    .unwrap();
+
+    // when
+    let set = reference::load(&directory).unwrap();
+
+    // then
+    assert_eq!(set.name, "down-camera");
+    assert_eq!(set.tolerance_px, 1.5);
+    assert_eq!(set.cases.len(), 1);
+    assert_eq!(set.cases[0].name, "plate");
+    let Check::Fiducial {
+        jpeg,
+        center,
+    } = &set.cases[0].check
+    else {
+        panic!("not a fiducial check");
+    };
+    assert_eq!(jpeg, &vec![1, 2, 3]);
+    assert_eq!(
+        *center,
+        Some(PixelPoint {
+            x: 1.5,
+            y: -2.0,
+        })
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+pub fn missing_image_is_an_error() {
+    // given
+    let directory = std::env::temp_dir().join(format!("vision-bench-missing-test-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(
+        directory.join(MANIFEST_FILE),
+        r#"(
+            name: "down-camera",
+            tolerance_px: 1.5,
+            cases: [(name: "plate", check: Fiducial(image: "plate.jpg", center: None))],
+        )"#,
+    )
+    .unwrap();
+
+    // expect
+    assert!(reference::load(&directory).is_err());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+pub fn synthetic_set_is_within_tolerance() {
+    // given
+    let set = synthetic::reference_set().unwrap();
+
+    // when
+    let results = bench::run_set(&set, 1);
+
+    // then
+    assert_eq!(bench::regressions(&results, set.tolerance_px, None, 1.0), vec![]);
+    for kind in [CheckKind::Fiducial, CheckKind::Alignment, CheckKind::Template] {
+        assert!(
+            results
+                .iter()
+                .any(|result| result.kind == kind)
+        );
+    }
+}